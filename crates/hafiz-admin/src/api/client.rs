@@ -23,6 +23,12 @@ fn api_base() -> String {
 /// Get credentials from localStorage
 fn get_auth_header() -> Option<String> {
     let storage = window()?.local_storage().ok()??;
+
+    // An ID token from SSO sign-in takes precedence
+    if let Ok(Some(token)) = storage.get_item("hafiz_id_token") {
+        return Some(format!("Bearer {}", token));
+    }
+
     let access_key = storage.get_item("hafiz_access_key").ok()??;
     let secret_key = storage.get_item("hafiz_secret_key").ok()??;
 
@@ -75,6 +81,48 @@ async fn get<T: serde::de::DeserializeOwned>(endpoint: &str) -> Result<T, ApiErr
     })
 }
 
+/// Make authenticated GET request, returning a response header as well
+async fn get_with_header<T: serde::de::DeserializeOwned>(
+    endpoint: &str,
+    header: &str,
+) -> Result<(T, Option<String>), ApiError> {
+    let url = format!("{}{}", api_base(), endpoint);
+
+    let mut request = Request::get(&url);
+
+    if let Some(auth) = get_auth_header() {
+        request = request.header("Authorization", &auth);
+    }
+
+    let response = request
+        .send()
+        .await
+        .map_err(|e| ApiError {
+            code: "NetworkError".to_string(),
+            message: e.to_string(),
+        })?;
+
+    if !response.ok() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        return Err(ApiError {
+            code: format!("HTTP{}", status),
+            message: if text.is_empty() {
+                format!("Request failed with status {}", status)
+            } else {
+                text
+            },
+        });
+    }
+
+    let value = response.headers().get(header);
+    let body = response.json().await.map_err(|e| ApiError {
+        code: "ParseError".to_string(),
+        message: e.to_string(),
+    })?;
+    Ok((body, value))
+}
+
 /// Make authenticated POST request
 async fn post<T: serde::de::DeserializeOwned, B: serde::Serialize>(
     endpoint: &str,
@@ -249,6 +297,7 @@ pub async fn get_dashboard_stats() -> Result<DashboardStats, ApiError> {
                 created_at: b.created_at,
                 versioning_enabled: b.versioning_enabled,
                 encryption_enabled: b.encryption_enabled,
+                tags: Vec::new(),
             })
             .collect(),
     })
@@ -256,8 +305,11 @@ pub async fn get_dashboard_stats() -> Result<DashboardStats, ApiError> {
 
 // ============= Bucket Operations =============
 
-/// List all buckets with details
-pub async fn list_buckets() -> Result<Vec<BucketInfo>, ApiError> {
+/// Buckets listed per page
+pub const BUCKETS_PAGE_SIZE: usize = 100;
+
+/// List a page of buckets with details, starting after `after`
+pub async fn list_buckets(prefix: &str, after: Option<&str>) -> Result<BucketPage, ApiError> {
     #[derive(serde::Deserialize)]
     struct ApiBucketDetailed {
         name: String,
@@ -271,28 +323,35 @@ pub async fn list_buckets() -> Result<Vec<BucketInfo>, ApiError> {
         #[serde(default)]
         lifecycle_rules: i64,
         #[serde(default)]
-        tags: Vec<ApiTag>,
+        tags: Vec<BucketTag>,
     }
 
-    #[derive(serde::Deserialize)]
-    struct ApiTag {
-        key: String,
-        value: String,
+    let mut endpoint = format!(
+        "/buckets?limit={}&prefix={}",
+        BUCKETS_PAGE_SIZE,
+        urlencoding::encode(prefix)
+    );
+    if let Some(after) = after {
+        endpoint.push_str(&format!("&after={}", urlencoding::encode(after)));
     }
+    let (buckets, next_after): (Vec<ApiBucketDetailed>, _) =
+        get_with_header(&endpoint, "x-hafiz-next-after").await?;
 
-    let buckets: Vec<ApiBucketDetailed> = get("/buckets").await?;
-
-    Ok(buckets
-        .into_iter()
-        .map(|b| BucketInfo {
-            name: b.name,
-            object_count: b.object_count,
-            size: b.size,
-            created_at: b.created_at,
-            versioning_enabled: b.versioning_enabled,
-            encryption_enabled: b.encryption_enabled,
-        })
-        .collect())
+    Ok(BucketPage {
+        buckets: buckets
+            .into_iter()
+            .map(|b| BucketInfo {
+                name: b.name,
+                object_count: b.object_count,
+                size: b.size,
+                created_at: b.created_at,
+                versioning_enabled: b.versioning_enabled,
+                encryption_enabled: b.encryption_enabled,
+                tags: b.tags,
+            })
+            .collect(),
+        next_after,
+    })
 }
 
 /// Get bucket statistics
@@ -311,6 +370,8 @@ pub async fn get_bucket(name: &str) -> Result<BucketInfo, ApiError> {
         created_at: String,
         #[serde(default)]
         last_modified: Option<String>,
+        #[serde(default)]
+        tags: Vec<BucketTag>,
     }
 
     let stats: ApiBucketStats = get(&format!("/buckets/{}/stats", name)).await?;
@@ -322,6 +383,7 @@ pub async fn get_bucket(name: &str) -> Result<BucketInfo, ApiError> {
         created_at: stats.created_at,
         versioning_enabled: stats.version_count > stats.object_count,
         encryption_enabled: false,
+        tags: stats.tags,
     })
 }
 
@@ -335,19 +397,8 @@ pub async fn create_bucket(name: &str) -> Result<BucketInfo, ApiError> {
         });
     }
 
-    if name.len() < 3 || name.len() > 63 {
-        return Err(ApiError {
-            code: "InvalidBucketName".to_string(),
-            message: "Bucket name must be between 3 and 63 characters".to_string(),
-        });
-    }
-
-    if !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '.') {
-        return Err(ApiError {
-            code: "InvalidBucketName".to_string(),
-            message: "Bucket name must contain only lowercase letters, numbers, hyphens, and dots".to_string(),
-        });
-    }
+    // The rest depends on the server's bucket name rules, which may be
+    // relaxed; its InvalidBucketName error says what is wrong
 
     // Create bucket via S3 PUT /{bucket}
     let s3_url = format!("/{}", name);
@@ -368,9 +419,11 @@ pub async fn create_bucket(name: &str) -> Result<BucketInfo, ApiError> {
 
     if !response.ok() {
         let status = response.status();
+        let xml = response.text().await.unwrap_or_default();
         return Err(ApiError {
-            code: format!("HTTP{}", status),
-            message: format!("Failed to create bucket: status {}", status),
+            code: extract_xml_value(&xml, "Code").unwrap_or_else(|| format!("HTTP{}", status)),
+            message: extract_xml_value(&xml, "Message")
+                .unwrap_or_else(|| format!("Failed to create bucket: status {}", status)),
         });
     }
 
@@ -381,6 +434,7 @@ pub async fn create_bucket(name: &str) -> Result<BucketInfo, ApiError> {
         created_at: chrono::Utc::now().to_rfc3339(),
         versioning_enabled: false,
         encryption_enabled: false,
+        tags: Vec::new(),
     })
 }
 
@@ -567,6 +621,7 @@ pub async fn list_users() -> Result<Vec<UserInfo>, ApiError> {
             email: u.email,
             enabled: u.enabled,
             created_at: u.created_at,
+            is_admin: u.policies.iter().any(|p| p == "admin"),
         })
         .collect())
 }
@@ -680,6 +735,33 @@ pub async fn disable_user(access_key: &str) -> Result<(), ApiError> {
     Ok(())
 }
 
+// ============= Admin Roles =============
+
+/// List the admin roles given to users
+pub async fn list_admin_roles() -> Result<Vec<AdminRoleGrant>, ApiError> {
+    get("/roles").await
+}
+
+/// Give a user an admin role, replacing the one it had
+pub async fn set_admin_role(access_key: &str, role: AdminRole) -> Result<AdminRoleGrant, ApiError> {
+    #[derive(serde::Serialize)]
+    struct PutAdminRoleRequest {
+        role: AdminRole,
+    }
+
+    put(&format!("/roles/{}", access_key), &PutAdminRoleRequest { role }).await
+}
+
+/// Take a user's admin role away
+pub async fn remove_admin_role(access_key: &str) -> Result<(), ApiError> {
+    delete(&format!("/roles/{}", access_key)).await
+}
+
+/// The signed-in caller and its role
+pub async fn whoami() -> Result<WhoAmI, ApiError> {
+    get("/roles/me").await
+}
+
 // ============= Server Operations =============
 
 /// Get server information
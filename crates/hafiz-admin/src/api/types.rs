@@ -21,6 +21,23 @@ pub struct BucketInfo {
     pub created_at: String,
     pub versioning_enabled: bool,
     pub encryption_enabled: bool,
+    #[serde(default)]
+    pub tags: Vec<BucketTag>,
+}
+
+/// A page of buckets
+#[derive(Debug, Clone)]
+pub struct BucketPage {
+    pub buckets: Vec<BucketInfo>,
+    /// Last bucket of this page, when more follow
+    pub next_after: Option<String>,
+}
+
+/// Bucket tag
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BucketTag {
+    pub key: String,
+    pub value: String,
 }
 
 /// Object information
@@ -52,6 +69,70 @@ pub struct UserInfo {
     pub email: Option<String>,
     pub enabled: bool,
     pub created_at: String,
+    /// Admins are always super admins
+    #[serde(default)]
+    pub is_admin: bool,
+}
+
+/// Admin role, deciding which admin API endpoints a user may use
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminRole {
+    Viewer,
+    BucketOperator,
+    SecurityAdmin,
+    SuperAdmin,
+}
+
+impl AdminRole {
+    pub const ALL: [AdminRole; 4] = [
+        Self::Viewer,
+        Self::BucketOperator,
+        Self::SecurityAdmin,
+        Self::SuperAdmin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::BucketOperator => "bucket_operator",
+            Self::SecurityAdmin => "security_admin",
+            Self::SuperAdmin => "super_admin",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Viewer => "Viewer",
+            Self::BucketOperator => "Bucket operator",
+            Self::SecurityAdmin => "Security admin",
+            Self::SuperAdmin => "Super admin",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|role| role.as_str() == s)
+    }
+
+    /// Whether the role may give users roles
+    pub fn manages_roles(&self) -> bool {
+        matches!(self, Self::SecurityAdmin | Self::SuperAdmin)
+    }
+}
+
+/// An admin role given to a user
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AdminRoleGrant {
+    pub access_key: String,
+    pub role: AdminRole,
+    pub granted_at: String,
+}
+
+/// The signed-in caller and its role
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WhoAmI {
+    pub name: String,
+    pub role: AdminRole,
 }
 
 /// Server information
@@ -59,6 +59,21 @@ fn LoginPage() -> impl IntoView {
     let (error, set_error) = create_signal(Option::<String>::None);
     let (loading, set_loading) = create_signal(false);
 
+    // SSO: the identity provider redirects back with #id_token=...
+    if let Some(window) = web_sys::window() {
+        let fragment = window.location().hash().unwrap_or_default();
+        let id_token = fragment
+            .trim_start_matches('#')
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("id_token="));
+        if let Some(token) = id_token {
+            if let Ok(Some(storage)) = window.local_storage() {
+                let _ = storage.set_item("hafiz_id_token", token);
+            }
+            let _ = window.location().set_href("/");
+        }
+    }
+
     let on_submit = move |ev: leptos::ev::SubmitEvent| {
         ev.prevent_default();
         set_loading.set(true);
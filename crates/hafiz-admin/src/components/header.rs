@@ -2,10 +2,19 @@
 
 use leptos::*;
 
+use crate::api;
+
 #[component]
 pub fn Header() -> impl IntoView {
     let (search_query, set_search_query) = create_signal(String::new());
     let (show_user_menu, set_show_user_menu) = create_signal(false);
+    let me = create_resource(|| (), |_| async move { api::whoami().await.ok() });
+    let name = move || {
+        me.get()
+            .flatten()
+            .map(|me| me.name)
+            .unwrap_or_else(|| "Admin".to_string())
+    };
 
     let on_logout = move |_| {
         // Clear credentials
@@ -15,6 +24,7 @@ pub fn Header() -> impl IntoView {
         {
             let _ = storage.remove_item("hafiz_access_key");
             let _ = storage.remove_item("hafiz_secret_key");
+            let _ = storage.remove_item("hafiz_id_token");
         }
 
         // Redirect to login
@@ -62,9 +72,16 @@ pub fn Header() -> impl IntoView {
                         on:click=move |_| set_show_user_menu.update(|v| *v = !*v)
                     >
                         <div class="w-8 h-8 bg-blue-600 rounded-full flex items-center justify-center">
-                            <span class="text-sm font-medium text-white">"A"</span>
+                            <span class="text-sm font-medium text-white">
+                                {move || name().chars().next().unwrap_or('A').to_uppercase().to_string()}
+                            </span>
+                        </div>
+                        <div class="text-left">
+                            <p class="text-sm text-gray-300">{name}</p>
+                            {move || me.get().flatten().map(|me| view! {
+                                <p class="text-xs text-gray-500">{me.role.label()}</p>
+                            })}
                         </div>
-                        <span class="text-sm text-gray-300">"Admin"</span>
                         <svg class="w-4 h-4 text-gray-400" fill="none" stroke="currentColor" viewBox="0 0 24 24">
                             <path stroke-linecap="round" stroke-linejoin="round" stroke-width="2" d="M19 9l-7 7-7-7" />
                         </svg>
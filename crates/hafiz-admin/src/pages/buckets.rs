@@ -2,7 +2,7 @@
 
 use leptos::*;
 use leptos_router::use_params_map;
-use crate::api::{self, BucketInfo};
+use crate::api::{self, BucketInfo, BucketTag};
 use crate::components::{Button, ButtonVariant, Modal};
 
 #[component]
@@ -14,8 +14,15 @@ pub fn BucketsPage() -> impl IntoView {
     let (creating, set_creating) = create_signal(false);
     let (error, set_error) = create_signal(Option::<String>::None);
 
-    // Fetch buckets
-    let buckets = create_resource(|| (), |_| async move { api::list_buckets().await });
+    let (prefix, set_prefix) = create_signal(String::new());
+    // Last bucket of the previous page, None on the first
+    let (after, set_after) = create_signal(Option::<String>::None);
+
+    // Fetch a page of buckets
+    let buckets = create_resource(
+        move || (prefix.get(), after.get()),
+        |(prefix, after)| async move { api::list_buckets(&prefix, after.as_deref()).await },
+    );
 
     let on_create = move |_| {
         set_creating.set(true);
@@ -65,11 +72,46 @@ pub fn BucketsPage() -> impl IntoView {
                 </Button>
             </div>
 
+            // Prefix filter
+            <input
+                type="text"
+                class="w-full md:w-80 px-4 py-2 bg-gray-800 border border-gray-700 rounded-lg
+                       text-white placeholder-gray-400 focus:outline-none focus:border-blue-500"
+                placeholder="Filter by prefix"
+                prop:value=move || prefix.get()
+                on:change=move |ev| {
+                    set_after.set(None);
+                    set_prefix.set(event_target_value(&ev));
+                }
+            />
+
             // Buckets grid
             <Suspense fallback=move || view! { <BucketsGridSkeleton /> }>
                 {move || buckets.get().map(|result| match result {
-                    Ok(list) => {
-                        if list.is_empty() {
+                    Ok(page) => {
+                        let next_after = page.next_after.clone();
+                        let list = page.buckets;
+                        let pager = view! {
+                            <div class="flex justify-end space-x-3">
+                                <Show when=move || after.get().is_some()>
+                                    <Button
+                                        variant=ButtonVariant::Secondary
+                                        on_click=Callback::new(move |_| set_after.set(None))
+                                    >
+                                        "First page"
+                                    </Button>
+                                </Show>
+                                {next_after.map(|next| view! {
+                                    <Button
+                                        variant=ButtonVariant::Secondary
+                                        on_click=Callback::new(move |_| set_after.set(Some(next.clone())))
+                                    >
+                                        "Next page"
+                                    </Button>
+                                })}
+                            </div>
+                        };
+                        if list.is_empty() && after.get_untracked().is_none() && prefix.get_untracked().is_empty() {
                             view! {
                                 <div class="bg-gray-800 rounded-xl border border-gray-700 p-12 text-center">
                                     <svg class="w-16 h-16 mx-auto text-gray-600 mb-4" fill="none" stroke="currentColor" viewBox="0 0 24 24">
@@ -100,6 +142,7 @@ pub fn BucketsPage() -> impl IntoView {
                                         }
                                     }).collect_view()}
                                 </div>
+                                {pager}
                             }.into_view()
                         }
                     }
@@ -238,6 +281,12 @@ fn BucketCard(bucket: BucketInfo, on_delete: Callback<()>) -> impl IntoView {
                 </div>
             </div>
 
+            {(!bucket.tags.is_empty()).then(|| view! {
+                <div class="mt-4 flex flex-wrap gap-2">
+                    <TagList tags=bucket.tags.clone() />
+                </div>
+            })}
+
             <div class="mt-4 pt-4 border-t border-gray-700 flex items-center justify-between">
                 <span class="text-sm text-gray-400">
                     "Created " {format_date(&bucket.created_at)}
@@ -358,6 +407,20 @@ pub fn BucketDetailPage() -> impl IntoView {
                                 </div>
                             </div>
 
+                            // Tags
+                            <div class="bg-gray-800 rounded-xl border border-gray-700 p-6">
+                                <h3 class="text-lg font-semibold text-white mb-3">"Tags"</h3>
+                                {if info.tags.is_empty() {
+                                    view! { <p class="text-gray-400">"No tags"</p> }.into_view()
+                                } else {
+                                    view! {
+                                        <div class="flex flex-wrap gap-2">
+                                            <TagList tags=info.tags.clone() />
+                                        </div>
+                                    }.into_view()
+                                }}
+                            </div>
+
                             // Objects browser link
                             <div class="bg-gray-800 rounded-xl border border-gray-700 p-6">
                                 <a
@@ -388,6 +451,17 @@ pub fn BucketDetailPage() -> impl IntoView {
     }
 }
 
+#[component]
+fn TagList(tags: Vec<BucketTag>) -> impl IntoView {
+    tags.into_iter()
+        .map(|tag| view! {
+            <span class="px-2 py-1 text-xs bg-gray-700 text-gray-300 rounded">
+                {tag.key} "=" {tag.value}
+            </span>
+        })
+        .collect_view()
+}
+
 #[component]
 fn BucketDetailSkeleton() -> impl IntoView {
     view! {
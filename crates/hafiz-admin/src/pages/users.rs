@@ -2,7 +2,7 @@
 
 use leptos::*;
 use wasm_bindgen_futures::spawn_local;
-use crate::api::{self, UserInfo};
+use crate::api::{self, AdminRole, UserInfo};
 use crate::components::{Button, ButtonVariant, Modal};
 
 #[component]
@@ -15,6 +15,13 @@ pub fn UsersPage() -> impl IntoView {
         |_| async move { api::list_users().await }
     );
 
+    // Roles are only listed to callers who may manage them
+    let roles = create_resource(
+        move || refresh_trigger.get(),
+        |_| async move { api::list_admin_roles().await.unwrap_or_default() }
+    );
+    let me = create_resource(|| (), |_| async move { api::whoami().await.ok() });
+
     let on_refresh = move || {
         set_refresh_trigger.update(|t| *t += 1);
     };
@@ -41,6 +48,11 @@ pub fn UsersPage() -> impl IntoView {
                 <Suspense fallback=move || view! { <UsersTableSkeleton /> }>
                     {move || users.get().map(|result| match result {
                         Ok(list) => {
+                            let roles = roles.get().unwrap_or_default();
+                            let can_manage_roles = me
+                                .get()
+                                .flatten()
+                                .is_some_and(|me| me.role.manages_roles());
                             if list.is_empty() {
                                 view! {
                                     <div class="p-12 text-center">
@@ -60,14 +72,24 @@ pub fn UsersPage() -> impl IntoView {
                                                 <th class="px-4 py-3 text-left text-xs font-medium text-gray-400 uppercase">"User"</th>
                                                 <th class="px-4 py-3 text-left text-xs font-medium text-gray-400 uppercase">"Access Key"</th>
                                                 <th class="px-4 py-3 text-left text-xs font-medium text-gray-400 uppercase">"Status"</th>
+                                                <th class="px-4 py-3 text-left text-xs font-medium text-gray-400 uppercase">"Admin Role"</th>
                                                 <th class="px-4 py-3 text-left text-xs font-medium text-gray-400 uppercase">"Created"</th>
                                                 <th class="px-4 py-3 text-left text-xs font-medium text-gray-400 uppercase">"Actions"</th>
                                             </tr>
                                         </thead>
                                         <tbody class="divide-y divide-gray-700">
                                             {list.into_iter().map(|user| {
+                                                let role = roles
+                                                    .iter()
+                                                    .find(|grant| grant.access_key == user.access_key)
+                                                    .map(|grant| grant.role);
                                                 view! {
-                                                    <UserRow user=user on_refresh=move |_| on_refresh() />
+                                                    <UserRow
+                                                        user=user
+                                                        role=role
+                                                        can_manage_roles=can_manage_roles
+                                                        on_refresh=move |_| on_refresh()
+                                                    />
                                                 }
                                             }).collect_view()}
                                         </tbody>
@@ -100,6 +122,8 @@ pub fn UsersPage() -> impl IntoView {
 #[component]
 fn UserRow(
     user: UserInfo,
+    role: Option<AdminRole>,
+    can_manage_roles: bool,
     #[prop(into)] on_refresh: Callback<()>,
 ) -> impl IntoView {
     use wasm_bindgen_futures::spawn_local;
@@ -107,11 +131,13 @@ fn UserRow(
     let access_key = user.access_key.clone();
     let access_key_for_delete = access_key.clone();
     let access_key_for_toggle = access_key.clone();
+    let access_key_for_role = access_key.clone();
     let is_enabled = user.enabled;
     let user_name = user.name.clone();
 
     let (is_deleting, set_is_deleting) = create_signal(false);
     let (is_toggling, set_is_toggling) = create_signal(false);
+    let (is_saving_role, set_is_saving_role) = create_signal(false);
 
     let handle_delete = move |_| {
         let key = access_key_for_delete.clone();
@@ -158,6 +184,28 @@ fn UserRow(
         });
     };
 
+    let handle_role = move |ev| {
+        let key = access_key_for_role.clone();
+        let new_role = AdminRole::parse(&event_target_value(&ev));
+        let on_refresh = on_refresh.clone();
+
+        set_is_saving_role.set(true);
+        spawn_local(async move {
+            let result = match new_role {
+                Some(role) => api::set_admin_role(&key, role).await.map(|_| ()),
+                None => api::remove_admin_role(&key).await,
+            };
+            if let Err(e) = result {
+                web_sys::window()
+                    .unwrap()
+                    .alert_with_message(&format!("Changing the role failed: {}", e.message))
+                    .ok();
+            }
+            on_refresh.call(());
+            set_is_saving_role.set(false);
+        });
+    };
+
     view! {
         <tr class="hover:bg-gray-750 transition-colors">
             <td class="px-4 py-3">
@@ -200,6 +248,37 @@ fn UserRow(
                     }}
                 </button>
             </td>
+            <td class="px-4 py-3">
+                {if user.is_admin {
+                    view! {
+                        <span class="px-2 py-1 text-xs bg-purple-600/20 text-purple-400 rounded" title="Admins are always super admins">
+                            {AdminRole::SuperAdmin.label()}
+                        </span>
+                    }.into_view()
+                } else if !can_manage_roles {
+                    view! {
+                        <span class="text-sm text-gray-400" title="Only security admins can see and change roles">
+                            "—"
+                        </span>
+                    }.into_view()
+                } else {
+                    view! {
+                        <select
+                            class="bg-gray-700 border border-gray-600 rounded px-2 py-1 text-sm text-white
+                                   focus:outline-none focus:border-blue-500 disabled:opacity-50"
+                            disabled=move || is_saving_role.get()
+                            on:change=handle_role
+                        >
+                            <option value="" selected=role.is_none()>"None"</option>
+                            {AdminRole::ALL.into_iter().map(|option| view! {
+                                <option value=option.as_str() selected=role == Some(option)>
+                                    {option.label()}
+                                </option>
+                            }).collect_view()}
+                        </select>
+                    }.into_view()
+                }}
+            </td>
             <td class="px-4 py-3 text-gray-400 text-sm">
                 {format_date(&user.created_at)}
             </td>
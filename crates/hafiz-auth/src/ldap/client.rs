@@ -94,6 +94,80 @@ impl LdapClient {
         self.ldap_get_groups(user_dn, username).await
     }
 
+    /// List every user matched by the user filter, with groups and policies
+    pub async fn list_users(&self) -> Result<Vec<LdapUser>, String> {
+        if !self.config.enabled {
+            return Err("LDAP is not enabled".to_string());
+        }
+
+        let (conn, mut ldap) = self.create_connection().await?;
+        ldap3::drive!(conn);
+
+        let result = ldap
+            .simple_bind(&self.config.bind_dn, &self.config.bind_password)
+            .await
+            .map_err(|e| format!("Bind failed: {}", e))?;
+
+        if result.rc != 0 {
+            return Err(format!("Bind failed with code: {}", result.rc));
+        }
+
+        let filter = self.config.build_list_filter();
+        let attrs = vec![
+            &self.config.attribute_mappings.username as &str,
+            &self.config.attribute_mappings.email,
+            &self.config.attribute_mappings.display_name,
+        ];
+
+        debug!("Listing users with filter: {}", filter);
+
+        let (rs, _res) = ldap
+            .search(&self.config.user_base_dn, Scope::Subtree, &filter, attrs)
+            .await
+            .map_err(|e| format!("User search failed: {}", e))?
+            .success()
+            .map_err(|e| format!("User search error: {}", e))?;
+
+        let mut users = Vec::with_capacity(rs.len());
+        for result in rs {
+            let entry = SearchEntry::construct(result);
+            let username = match get_first_attr(&entry, &self.config.attribute_mappings.username) {
+                Some(username) => username,
+                None => {
+                    warn!("Skipping directory entry without a username: {}", entry.dn);
+                    continue;
+                }
+            };
+
+            // A failed group lookup must not look like "member of nothing",
+            // or sync would strip the user's policies
+            let groups = self
+                .ldap_get_groups_with_connection(&mut ldap, &entry.dn, &username)
+                .await?;
+            let policies = self.config.map_groups_to_policies(&groups);
+
+            users.push(LdapUser {
+                dn: entry.dn.clone(),
+                email: get_first_attr(&entry, &self.config.attribute_mappings.email),
+                display_name: get_first_attr(&entry, &self.config.attribute_mappings.display_name),
+                username,
+                groups,
+                policies,
+                attributes: entry.attrs.into_iter().collect(),
+            });
+        }
+
+        let _ = ldap.unbind().await;
+
+        debug!("Listed {} directory users", users.len());
+        Ok(users)
+    }
+
+    /// Configuration in use
+    pub fn config(&self) -> &LdapConfig {
+        &self.config
+    }
+
     /// Test LDAP connection
     pub async fn test_connection(&self) -> TestLdapConnectionResponse {
         match self.ldap_test_connection().await {
@@ -495,6 +569,27 @@ impl LdapAuthProvider {
         self.client.authenticate(username, password).await
     }
 
+    /// Look up a user's groups in the directory
+    pub async fn get_user_groups(&self, username: &str) -> Result<Vec<String>, String> {
+        match self.client.search_user(username).await? {
+            Some(user) => Ok(user.groups),
+            None => Err(format!("User {} not found", username)),
+        }
+    }
+
+    /// Map a user's directory groups to Hafiz policies
+    pub async fn get_user_policies(&self, username: &str) -> Result<Vec<String>, String> {
+        match self.client.search_user(username).await? {
+            Some(user) => Ok(user.policies),
+            None => Err(format!("User {} not found", username)),
+        }
+    }
+
+    /// List all directory users, for sync
+    pub async fn list_users(&self) -> Result<Vec<LdapUser>, String> {
+        self.client.list_users().await
+    }
+
     /// Get underlying client for admin operations
     pub fn client(&self) -> Arc<LdapClient> {
         self.client.clone()
@@ -417,6 +417,46 @@ pub struct UpdateLdapConfigRequest {
     pub config: LdapConfig,
 }
 
+impl From<&hafiz_core::config::LdapConfigSection> for LdapConfig {
+    fn from(section: &hafiz_core::config::LdapConfigSection) -> Self {
+        let server_type = match section.server_type.as_str() {
+            "active_directory" => LdapServerType::ActiveDirectory,
+            "openldap" => LdapServerType::OpenLdap,
+            "389ds" => LdapServerType::Directory389,
+            _ => LdapServerType::Ldap,
+        };
+        let member = match server_type {
+            LdapServerType::OpenLdap | LdapServerType::Directory389 => "memberUid",
+            _ => "member",
+        };
+
+        Self {
+            enabled: section.enabled,
+            server_url: section.server_url.clone(),
+            start_tls: section.start_tls,
+            skip_tls_verify: section.skip_tls_verify,
+            bind_dn: section.bind_dn.clone(),
+            bind_password: section.bind_password.clone(),
+            user_base_dn: section.user_base_dn.clone(),
+            user_filter: section.user_filter.clone(),
+            group_base_dn: section.group_base_dn.clone(),
+            group_filter: section.group_filter.clone(),
+            attribute_mappings: AttributeMappings {
+                username: section.username_attribute.clone(),
+                email: section.email_attribute.clone(),
+                display_name: section.display_name_attribute.clone(),
+                group_name: section.group_name_attribute.clone(),
+                member: member.to_string(),
+            },
+            group_policies: section.group_policies.clone(),
+            default_policies: section.default_policies.clone(),
+            timeout_seconds: section.timeout_seconds,
+            cache_ttl_seconds: section.cache_ttl_seconds,
+            server_type,
+        }
+    }
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
@@ -435,6 +475,16 @@ impl LdapConfig {
         })
     }
 
+    /// Build the filter matching every user, for directory sync
+    pub fn build_list_filter(&self) -> String {
+        self.user_filter.replace("{username}", "*")
+    }
+
+    /// Whether any of the groups has a policy mapping
+    pub fn has_mapped_group(&self, groups: &[String]) -> bool {
+        groups.iter().any(|g| self.group_policies.contains_key(g))
+    }
+
     /// Map groups to policies
     pub fn map_groups_to_policies(&self, groups: &[String]) -> Vec<String> {
         let mut policies = Vec::new();
@@ -570,6 +620,24 @@ mod tests {
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn test_config_from_section() {
+        let mut section = hafiz_core::config::LdapConfigSection {
+            server_type: "active_directory".to_string(),
+            user_filter: "(sAMAccountName={username})".to_string(),
+            ..Default::default()
+        };
+        section
+            .group_policies
+            .insert("S3 Admins".to_string(), vec!["admin".to_string()]);
+
+        let config = LdapConfig::from(&section);
+        assert_eq!(config.server_type, LdapServerType::ActiveDirectory);
+        assert_eq!(config.build_list_filter(), "(sAMAccountName=*)");
+        assert!(config.has_mapped_group(&["S3 Admins".to_string()]));
+        assert!(!config.has_mapped_group(&["Everyone".to_string()]));
+    }
+
     #[test]
     fn test_active_directory_defaults() {
         let config = LdapConfig::from_server_type(
@@ -1,6 +1,7 @@
 //! Authentication for Hafiz
 
 pub mod ldap;
+pub mod oidc;
 pub mod presigned;
 pub mod signature;
 
@@ -8,11 +9,16 @@ pub use ldap::{
     LdapAuthProvider, LdapClient, LdapConfig, LdapUser, LdapAuthResult,
     LdapStatus, LdapServerType, AttributeMappings,
 };
+pub use oidc::{OidcIdentity, OidcVerifier};
 pub use presigned::{
     generate_presigned_url, verify_presigned_url,
-    extract_access_key_from_presigned, is_presigned_request,
+    extract_access_key_from_presigned, extract_region_from_presigned,
+    extract_security_token_from_presigned, is_presigned_request,
+    presigned_constraints, PresignedConstraints,
+};
+pub use signature::{
+    check_clock_skew, request_time, sign_request_v4, uri_encode, verify_signature_v4, SignatureV4,
 };
-pub use signature::{SignatureV4, verify_signature_v4};
 
 use rand::Rng;
 
@@ -46,3 +52,15 @@ pub fn generate_credentials() -> (String, String) {
 
     (access_key, secret_key)
 }
+
+/// Generate temporary access key, secret key and session token for STS
+pub fn generate_temporary_credentials() -> (String, String, String) {
+    let (access_key, secret_key) = generate_credentials();
+
+    // Temporary keys start with ASIA, like AWS
+    let access_key = format!("ASIA{}", &access_key[4..]);
+
+    let mut token = [0u8; 48];
+    rand::thread_rng().fill(&mut token[..]);
+    (access_key, secret_key, hex::encode(token))
+}
@@ -0,0 +1,160 @@
+//! HTTP client for the Hafiz admin API
+
+use crate::config::Config;
+use anyhow::{Context, Result};
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Base path of the admin API on the server
+const ADMIN_API_PREFIX: &str = "/api/v1";
+
+/// Thin wrapper around reqwest that authenticates with admin credentials
+pub struct AdminClient {
+    http: reqwest::Client,
+    base_url: String,
+    auth: AdminAuth,
+}
+
+/// How admin requests authenticate
+enum AdminAuth {
+    Token(String),
+    Keys { access_key: String, secret_key: String },
+}
+
+impl AdminClient {
+    /// Create an admin client from configuration
+    pub fn new(config: &Config) -> Result<Self> {
+        let auth = match &config.admin_token {
+            Some(token) => AdminAuth::Token(token.clone()),
+            None => {
+                config.validate()?;
+                AdminAuth::Keys {
+                    access_key: config.access_key.clone().unwrap(),
+                    secret_key: config.secret_key.clone().unwrap(),
+                }
+            }
+        };
+        let endpoint = config
+            .admin_endpoint
+            .as_ref()
+            .or(config.endpoint.as_ref())
+            .context("Endpoint not configured. Set HAFIZ_ENDPOINT or use 'hafiz configure'")?
+            .trim_end_matches('/');
+
+        let http = reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(config.timeout))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            http,
+            base_url: format!("{}{}", endpoint, ADMIN_API_PREFIX),
+            auth,
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self.http.request(method, format!("{}{}", self.base_url, path));
+        match &self.auth {
+            AdminAuth::Token(token) => request.bearer_auth(token),
+            AdminAuth::Keys { access_key, secret_key } => request.basic_auth(access_key, Some(secret_key)),
+        }
+    }
+
+    /// GET a JSON resource
+    pub async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let resp = self.send(self.request(Method::GET, path)).await?;
+        resp.json().await.context("Invalid response from admin API")
+    }
+
+    /// GET a JSON resource with query parameters
+    pub async fn get_query<Q: Serialize, T: DeserializeOwned>(&self, path: &str, query: &Q) -> Result<T> {
+        let resp = self.send(self.request(Method::GET, path).query(query)).await?;
+        resp.json().await.context("Invalid response from admin API")
+    }
+
+    /// POST a JSON body and decode the JSON response
+    pub async fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        let resp = self.send(self.request(Method::POST, path).json(body)).await?;
+        resp.json().await.context("Invalid response from admin API")
+    }
+
+    /// PUT a JSON body and decode the JSON response
+    pub async fn put<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> Result<T> {
+        let resp = self.send(self.request(Method::PUT, path).json(body)).await?;
+        resp.json().await.context("Invalid response from admin API")
+    }
+
+    /// POST with query parameters and no body
+    pub async fn post_query<Q: Serialize, T: DeserializeOwned>(&self, path: &str, query: &Q) -> Result<T> {
+        let resp = self.send(self.request(Method::POST, path).query(query)).await?;
+        resp.json().await.context("Invalid response from admin API")
+    }
+
+    /// DELETE a resource
+    pub async fn delete(&self, path: &str) -> Result<()> {
+        self.send(self.request(Method::DELETE, path)).await?;
+        Ok(())
+    }
+
+    /// DELETE with query parameters and decode the JSON response
+    pub async fn delete_query<Q: Serialize, T: DeserializeOwned>(&self, path: &str, query: &Q) -> Result<T> {
+        let resp = self.send(self.request(Method::DELETE, path).query(query)).await?;
+        resp.json().await.context("Invalid response from admin API")
+    }
+
+    /// GET a resource and copy its body to `out`, returning the bytes copied
+    pub async fn download<W: tokio::io::AsyncWrite + Unpin>(&self, path: &str, out: &mut W) -> Result<u64> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut resp = self.send(self.request(Method::GET, path)).await?;
+        let mut copied = 0;
+        while let Some(chunk) = resp.chunk().await.context("Admin API response was cut off")? {
+            out.write_all(&chunk).await?;
+            copied += chunk.len() as u64;
+        }
+        out.flush().await?;
+        Ok(copied)
+    }
+
+    /// POST a raw body and decode the JSON response
+    pub async fn post_body<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        content_type: &str,
+        body: impl Into<reqwest::Body>,
+    ) -> Result<T> {
+        let req = self
+            .request(Method::POST, path)
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body);
+        let resp = self.send(req).await?;
+        resp.json().await.context("Invalid response from admin API")
+    }
+
+    async fn send(&self, req: RequestBuilder) -> Result<Response> {
+        let resp = req
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach admin API at {}", self.base_url))?;
+
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+
+        let body = resp.text().await.unwrap_or_default();
+        match status {
+            StatusCode::UNAUTHORIZED => {
+                anyhow::bail!("Admin API rejected credentials ({})", status)
+            }
+            StatusCode::FORBIDDEN if body.is_empty() => anyhow::bail!(
+                "Admin API refused the request ({}): the user is disabled, or its admin role doesn't permit this",
+                status
+            ),
+            _ if body.is_empty() => anyhow::bail!("Admin API error: {}", status),
+            _ => anyhow::bail!("Admin API error: {}: {}", status, body),
+        }
+    }
+}
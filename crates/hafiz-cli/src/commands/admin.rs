@@ -0,0 +1,2492 @@
+//! admin command - server administration via the admin API
+
+use super::CommandContext;
+use crate::admin_client::AdminClient;
+use crate::s3_client::S3Uri;
+use crate::utils::{confirm, format_size};
+use crate::{
+    AccountQuotaArgs, AdminAccessPointAction, AdminAccountAction, AdminAction, AdminBatchAction, AdminCacheAction, AdminCaptureAction, AdminClusterAction, AdminGcAction, AdminKeyRewrapAction,
+    AdminMetadataAction, AdminMirrorAction, AdminPruneAction, AdminRoleAction, AdminScrubAction, AdminSearchAction,
+    AdminSftpAction, AdminSnapshotAction, AdminUserAction, BatchJobArgs,
+};
+use anyhow::{Context, Result};
+use std::io::Read;
+use colored::Colorize;
+use hafiz_core::types::AdminRole;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct UserInfo {
+    name: String,
+    access_key: String,
+    email: Option<String>,
+    enabled: bool,
+    created_at: String,
+    last_used: Option<String>,
+    #[serde(default)]
+    policies: Vec<String>,
+    #[serde(default)]
+    account_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct UserListResponse {
+    users: Vec<UserInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateUserRequest {
+    name: String,
+    email: Option<String>,
+    policies: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    account_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CreateUserResponse {
+    name: String,
+    access_key: String,
+    secret_key: String,
+    email: Option<String>,
+    created_at: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RotateKeysQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    grace_seconds: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RotateKeysResponse {
+    access_key: String,
+    secret_key: String,
+    created_at: String,
+    previous_access_key: String,
+    previous_key_expires_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccessKeyInfo {
+    access_key: String,
+    status: String,
+    created_at: Option<String>,
+    expires_at: Option<String>,
+    last_used: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccessKeyListResponse {
+    keys: Vec<AccessKeyInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BucketStorageInfo {
+    name: String,
+    size: i64,
+    percentage: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DashboardStats {
+    total_buckets: i64,
+    total_objects: i64,
+    total_size: i64,
+    total_users: i64,
+    #[serde(default)]
+    storage_by_bucket: Vec<BucketStorageInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct GcQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    older_than_hours: Option<i64>,
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct GcReport {
+    dry_run: bool,
+    older_than_hours: i64,
+    uploads_aborted: i64,
+    parts_deleted: i64,
+    bytes_reclaimed: i64,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ScrubQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bucket: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScrubStatus {
+    scheduled: bool,
+    interval_secs: u64,
+    running: bool,
+    last_run: Option<ScrubReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScrubReport {
+    started_at: String,
+    finished_at: Option<String>,
+    objects_checked: u64,
+    bytes_checked: u64,
+    objects_damaged: u64,
+    objects_repaired: u64,
+    objects_quarantined: u64,
+    #[serde(default)]
+    findings: Vec<ScrubFinding>,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ScrubFinding {
+    bucket: String,
+    key: String,
+    issue: String,
+    action: String,
+    detail: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchJobRequest {
+    manifest: serde_json::Value,
+    operation: serde_json::Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<BatchReportTarget>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    concurrency: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_retries: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchReportTarget {
+    bucket: String,
+    prefix: String,
+    failed_only: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BatchJobStatus {
+    id: String,
+    operation: serde_json::Value,
+    state: String,
+    created_at: String,
+    finished_at: Option<String>,
+    total_objects: u64,
+    succeeded: u64,
+    failed: u64,
+    retries: u64,
+    report: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PruneRequest {
+    bucket: String,
+    prefix: String,
+    older_than_days: Option<u32>,
+    keep_last: Option<u32>,
+    delete_markers: bool,
+    dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PruneJobStatus {
+    id: String,
+    request: PruneRequest,
+    state: String,
+    created_at: String,
+    finished_at: Option<String>,
+    bucket_versions: i64,
+    keys_scanned: u64,
+    versions_scanned: u64,
+    versions_pruned: u64,
+    delete_markers_removed: u64,
+    bytes_freed: i64,
+    locked_versions: u64,
+    failed: u64,
+    current_key: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PruneJobList {
+    jobs: Vec<PruneJobStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WarmupRequest {
+    bucket: String,
+    keys: Vec<String>,
+    prefix: String,
+    max_bytes: Option<u64>,
+    evict: bool,
+    concurrency: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WarmupJobStatus {
+    id: String,
+    request: WarmupRequest,
+    state: String,
+    created_at: String,
+    finished_at: Option<String>,
+    max_bytes: u64,
+    objects_scanned: u64,
+    objects_loaded: u64,
+    bytes_loaded: u64,
+    already_cached: u64,
+    too_large: u64,
+    missing: u64,
+    failed: u64,
+    current_key: Option<String>,
+    reason: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WarmupJobList {
+    jobs: Vec<WarmupJobStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyRewrapStatus {
+    running: bool,
+    last_run: Option<KeyRewrapRun>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct KeyRewrapRun {
+    state: String,
+    from_key_check: String,
+    to_key_check: String,
+    position: Option<String>,
+    objects_scanned: u64,
+    objects_rewrapped: u64,
+    objects_current: u64,
+    objects_failed: u64,
+    started_at: String,
+    updated_at: String,
+    finished_at: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MirrorJobStatus {
+    name: String,
+    schedule: String,
+    endpoint: String,
+    buckets: Vec<String>,
+    running: bool,
+    next_run: Option<String>,
+    last_run: Option<MirrorReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MirrorReport {
+    started_at: String,
+    finished_at: Option<String>,
+    buckets_scanned: u64,
+    objects_mirrored: u64,
+    bytes_mirrored: u64,
+    objects_failed: u64,
+    #[serde(default)]
+    errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BatchJobList {
+    jobs: Vec<BatchJobStatus>,
+}
+
+#[derive(Debug, Serialize)]
+struct RepairQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bucket: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RepairReport {
+    buckets_compared: u64,
+    divergent_leaves: u64,
+    divergent_objects: u64,
+    objects_repaired: u64,
+    repair_failures: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct SetConsistencyRequest {
+    level: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BucketConsistency {
+    bucket: String,
+    level: Option<String>,
+    effective: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SetBackendRequest {
+    backend: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BucketBackend {
+    bucket: String,
+    backend: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StorageBackendInfo {
+    name: String,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    buckets: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct StorageBackendsResponse {
+    default: String,
+    backends: Vec<StorageBackendInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BandwidthLimit {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    scope: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    name: String,
+    ingress_bytes_per_sec: u64,
+    egress_bytes_per_sec: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BucketCompression {
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    bucket: String,
+    codec: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    level: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct TopologyNode {
+    id: String,
+    name: String,
+    status: String,
+    share: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RackTopology {
+    rack: Option<String>,
+    share: f64,
+    nodes: Vec<TopologyNode>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ZoneTopology {
+    zone: Option<String>,
+    share: f64,
+    racks: Vec<RackTopology>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ClusterTopology {
+    placement_enabled: bool,
+    replication_factor: usize,
+    zones: Vec<ZoneTopology>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NodeInfo {
+    id: String,
+    name: String,
+    endpoint: String,
+    role: String,
+    status: String,
+    zone: Option<String>,
+    rack: Option<String>,
+    last_heartbeat: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RebalanceProgress {
+    in_progress: bool,
+    objects_scanned: u64,
+    objects_moved: u64,
+    objects_failed: u64,
+    bytes_moved: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NodeList {
+    nodes: Vec<NodeInfo>,
+    total: usize,
+    healthy: usize,
+    draining: usize,
+    rebalance: RebalanceProgress,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct NodeOperation {
+    node: NodeInfo,
+    rebalance: RebalanceProgress,
+}
+
+#[derive(Debug, Serialize)]
+struct AddNodeRequest {
+    endpoint: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DumpSummary {
+    users: u64,
+    buckets: u64,
+    bucket_policies: u64,
+    bucket_tags: u64,
+    objects: u64,
+    object_tags: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct BucketSnapshot {
+    id: String,
+    bucket: String,
+    versioning: String,
+    #[serde(default)]
+    description: Option<String>,
+    created_at: String,
+    #[serde(default)]
+    completed_at: Option<String>,
+    versions: i64,
+    total_bytes: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SnapshotReport {
+    snapshot: BucketSnapshot,
+    dropped: u64,
+    recaptured: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateSnapshotRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RestoreSnapshotRequest {
+    bucket: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RestoredBucket {
+    name: String,
+    versioning: String,
+}
+
+pub async fn execute(ctx: &CommandContext, action: AdminAction) -> Result<()> {
+    let client = AdminClient::new(&ctx.config)?;
+
+    match action {
+        AdminAction::User { action } => user(ctx, &client, action).await,
+        AdminAction::Stats => stats(ctx, &client).await,
+        AdminAction::Gc { action } => gc(ctx, &client, action).await,
+        AdminAction::Scrub { action } => scrub(ctx, &client, action).await,
+        AdminAction::Batch { action } => batch(ctx, &client, action).await,
+        AdminAction::Prune { action } => prune(ctx, &client, action).await,
+        AdminAction::Cache { action } => cache(ctx, &client, action).await,
+        AdminAction::KeyRewrap { action } => key_rewrap(ctx, &client, action).await,
+        AdminAction::AccessPoint { action } => access_point(ctx, &client, action).await,
+        AdminAction::Cluster { action } => cluster(ctx, &client, action).await,
+        AdminAction::Repair { bucket } => repair(ctx, &client, bucket).await,
+        AdminAction::Consistency { bucket, level, clear } => {
+            consistency(ctx, &client, bucket, level, clear).await
+        }
+        AdminAction::Backend { bucket, backend, clear } => {
+            storage_backend(ctx, &client, bucket, backend, clear).await
+        }
+        AdminAction::Compression { bucket, codec, level, clear } => {
+            compression(ctx, &client, bucket, codec, level, clear).await
+        }
+        AdminAction::Bandwidth { bucket, access_key, ingress, egress, clear } => {
+            bandwidth(ctx, &client, bucket, access_key, ingress, egress, clear).await
+        }
+        AdminAction::Metadata { action } => metadata(ctx, &client, action).await,
+        AdminAction::Snapshot { action } => snapshot(ctx, &client, action).await,
+        AdminAction::Mirror { action } => mirror(ctx, &client, action).await,
+        AdminAction::Sftp { action } => sftp(ctx, &client, action).await,
+        AdminAction::Role { action } => role(ctx, &client, action).await,
+        AdminAction::Account { action } => account(ctx, &client, action).await,
+        AdminAction::Search { action } => search(ctx, &client, action).await,
+        AdminAction::Capture { action } => capture(ctx, &client, action).await,
+    }
+}
+
+async fn user(ctx: &CommandContext, client: &AdminClient, action: AdminUserAction) -> Result<()> {
+    match action {
+        AdminUserAction::Add { name, email, policies, account } => {
+            ctx.debug(&format!("Creating user: {}", name));
+            let req = CreateUserRequest {
+                name,
+                email,
+                policies: if policies.is_empty() { None } else { Some(policies) },
+                account_id: account,
+            };
+            let created: CreateUserResponse = client.post("/users", &req).await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&created)?);
+            } else {
+                println!("{}: {}", "add_user".green(), created.name);
+                println!("  {}: {}", "Access Key".cyan(), created.access_key);
+                println!("  {}: {}", "Secret Key".cyan(), created.secret_key);
+                println!();
+                println!("{}", "Store the secret key now; it cannot be retrieved again.".yellow());
+            }
+        }
+        AdminUserAction::List => {
+            let resp: UserListResponse = client.get("/users").await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&resp.users)?);
+            } else {
+                for u in &resp.users {
+                    let status = if u.enabled {
+                        "enabled".green()
+                    } else {
+                        "disabled".red()
+                    };
+                    println!(
+                        "{:<24} {:<20} {:<9} {:<16} {}",
+                        u.access_key,
+                        u.name,
+                        status,
+                        u.account_id,
+                        u.email.as_deref().unwrap_or("-")
+                    );
+                }
+                if resp.users.is_empty() {
+                    ctx.info("No users");
+                }
+            }
+        }
+        AdminUserAction::Remove { access_key, force } => {
+            if !force && !confirm(&format!("Remove user '{}'?", access_key)) {
+                ctx.info("Cancelled");
+                return Ok(());
+            }
+
+            client.delete(&format!("/users/{}", access_key)).await?;
+            ctx.info(&format!("{}: {}", "remove_user".green(), access_key));
+        }
+        AdminUserAction::Enable { access_key } => {
+            let _: UserInfo = client
+                .post(&format!("/users/{}/enable", access_key), &serde_json::json!({}))
+                .await?;
+            ctx.info(&format!("{}: {}", "enable_user".green(), access_key));
+        }
+        AdminUserAction::Disable { access_key } => {
+            let _: UserInfo = client
+                .post(&format!("/users/{}/disable", access_key), &serde_json::json!({}))
+                .await?;
+            ctx.info(&format!("{}: {}", "disable_user".green(), access_key));
+        }
+        AdminUserAction::Rotate { access_key, grace } => {
+            let rotated: RotateKeysResponse = client
+                .post_query(
+                    &format!("/users/{}/keys", access_key),
+                    &RotateKeysQuery { grace_seconds: grace },
+                )
+                .await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&rotated)?);
+            } else {
+                println!("{}: {}", "rotate_key".green(), rotated.previous_access_key);
+                println!("  {}: {}", "Access Key".cyan(), rotated.access_key);
+                println!("  {}: {}", "Secret Key".cyan(), rotated.secret_key);
+                match &rotated.previous_key_expires_at {
+                    Some(expires) => println!(
+                        "  {}: {} until {}",
+                        "Old Key".cyan(),
+                        rotated.previous_access_key,
+                        expires
+                    ),
+                    None => println!("  {}: {} retired", "Old Key".cyan(), rotated.previous_access_key),
+                }
+                println!();
+                println!("{}", "Store the secret key now; it cannot be retrieved again.".yellow());
+            }
+        }
+        AdminUserAction::Keys { access_key } => {
+            let resp: AccessKeyListResponse = client.get(&format!("/users/{}/keys", access_key)).await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&resp.keys)?);
+            } else {
+                for k in &resp.keys {
+                    let status = if k.status == "active" {
+                        k.status.green()
+                    } else {
+                        k.status.yellow()
+                    };
+                    println!(
+                        "{:<24} {:<9} last used {:<26} expires {}",
+                        k.access_key,
+                        status,
+                        k.last_used.as_deref().unwrap_or("never"),
+                        k.expires_at.as_deref().unwrap_or("-")
+                    );
+                }
+            }
+        }
+        AdminUserAction::RetireKey { access_key, old_access_key } => {
+            client
+                .delete(&format!("/users/{}/keys/{}", access_key, old_access_key))
+                .await?;
+            ctx.info(&format!("{}: {}", "retire_key".green(), old_access_key));
+        }
+    }
+
+    Ok(())
+}
+
+async fn stats(ctx: &CommandContext, client: &AdminClient) -> Result<()> {
+    let stats: DashboardStats = client.get("/stats").await?;
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+        return Ok(());
+    }
+
+    println!("{}", "Server Statistics".blue().bold());
+    println!();
+    println!("  {}: {}", "Buckets".cyan(), stats.total_buckets);
+    println!("  {}: {}", "Objects".cyan(), stats.total_objects);
+    println!(
+        "  {}: {} ({})",
+        "Total Size".cyan(),
+        stats.total_size,
+        format_size(stats.total_size, true)
+    );
+    println!("  {}: {}", "Users".cyan(), stats.total_users);
+
+    if !stats.storage_by_bucket.is_empty() {
+        println!();
+        println!("{}", "Storage by Bucket".blue().bold());
+        for b in &stats.storage_by_bucket {
+            println!(
+                "  {:<32} {:>12} {:>6.1}%",
+                b.name,
+                format_size(b.size, true),
+                b.percentage
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn gc(ctx: &CommandContext, client: &AdminClient, action: AdminGcAction) -> Result<()> {
+    match action {
+        AdminGcAction::Run {
+            older_than_hours,
+            dryrun,
+        } => {
+            let query = GcQuery {
+                older_than_hours,
+                dry_run: dryrun,
+            };
+            let report: GcReport = client.post_query("/gc/run", &query).await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+
+            let prefix = if report.dry_run { "(dryrun) " } else { "" };
+            println!(
+                "{}{}: {} stale upload(s) older than {}h, {} part(s), {} reclaimed",
+                prefix,
+                "gc".green(),
+                report.uploads_aborted,
+                report.older_than_hours,
+                report.parts_deleted,
+                format_size(report.bytes_reclaimed, true)
+            );
+            for err in &report.errors {
+                ctx.error(&format!("{}: {}", "gc error".red(), err));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn scrub(ctx: &CommandContext, client: &AdminClient, action: AdminScrubAction) -> Result<()> {
+    let status: ScrubStatus = match action {
+        AdminScrubAction::Status => client.get("/scrub").await?,
+        AdminScrubAction::Run { bucket } => {
+            client
+                .post_query("/scrub/run", &ScrubQuery { bucket })
+                .await?
+        }
+    };
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    if status.running {
+        println!("{}: running", "scrub".green());
+    } else if !status.scheduled {
+        println!("{}: not scheduled", "scrub".green());
+    } else {
+        println!("{}: every {}s", "scrub".green(), status.interval_secs);
+    }
+
+    let Some(report) = status.last_run else {
+        println!("No scrub has completed yet");
+        return Ok(());
+    };
+    println!(
+        "Last run {}: {} object(s), {} checked, {} damaged, {} repaired, {} quarantined",
+        report.finished_at.as_deref().unwrap_or(&report.started_at),
+        report.objects_checked,
+        format_size(report.bytes_checked as i64, true),
+        report.objects_damaged,
+        report.objects_repaired,
+        report.objects_quarantined
+    );
+    for finding in &report.findings {
+        let detail = finding
+            .detail
+            .as_deref()
+            .map(|d| format!(" ({})", d))
+            .unwrap_or_default();
+        println!(
+            "  {}/{}: {}, {}{}",
+            finding.bucket,
+            finding.key,
+            finding.issue.red(),
+            finding.action,
+            detail
+        );
+    }
+    for err in &report.errors {
+        ctx.error(&format!("{}: {}", "scrub error".red(), err));
+    }
+
+    Ok(())
+}
+
+async fn mirror(ctx: &CommandContext, client: &AdminClient, action: AdminMirrorAction) -> Result<()> {
+    let jobs: Vec<MirrorJobStatus> = match action {
+        AdminMirrorAction::Status => client.get("/mirror").await?,
+        AdminMirrorAction::Run { job } => {
+            let status: MirrorJobStatus = client
+                .post(&format!("/mirror/{}/run", job), &serde_json::json!({}))
+                .await?;
+            vec![status]
+        }
+    };
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&jobs)?);
+        return Ok(());
+    }
+    if jobs.is_empty() {
+        println!("No mirror jobs configured");
+        return Ok(());
+    }
+
+    for job in &jobs {
+        let state = if job.running {
+            "running".to_string()
+        } else {
+            format!("next run {}", job.next_run.as_deref().unwrap_or("never"))
+        };
+        println!("{}: {} ({})", job.name.green(), job.endpoint, state);
+        println!("  schedule: {}", job.schedule);
+        println!("  buckets: {}", job.buckets.join(", "));
+
+        let Some(report) = &job.last_run else {
+            println!("  no run has completed yet");
+            continue;
+        };
+        println!(
+            "  last run {}: {} bucket(s), {} object(s) mirrored, {}, {} failed",
+            report.finished_at.as_deref().unwrap_or(&report.started_at),
+            report.buckets_scanned,
+            report.objects_mirrored,
+            format_size(report.bytes_mirrored as i64, true),
+            report.objects_failed
+        );
+        for err in &report.errors {
+            ctx.error(&format!("{}: {}", "mirror error".red(), err));
+        }
+    }
+
+    Ok(())
+}
+
+async fn batch(ctx: &CommandContext, client: &AdminClient, action: AdminBatchAction) -> Result<()> {
+    let (job, operation) = match action {
+        AdminBatchAction::List => {
+            let list: BatchJobList = client.get("/batch/jobs").await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&list)?);
+                return Ok(());
+            }
+
+            println!(
+                "{:<36} {:<8} {:<10} {:>10} {:>8}  {}",
+                "ID", "TYPE", "STATE", "DONE", "FAILED", "CREATED"
+            );
+            for job in &list.jobs {
+                println!(
+                    "{:<36} {:<8} {:<10} {:>10} {:>8}  {}",
+                    job.id,
+                    job.operation["type"].as_str().unwrap_or("-"),
+                    job.state,
+                    format!("{}/{}", job.succeeded + job.failed, job.total_objects),
+                    job.failed,
+                    job.created_at
+                );
+            }
+            return Ok(());
+        }
+        AdminBatchAction::Status { job_id } => {
+            let status: BatchJobStatus = client.get(&format!("/batch/jobs/{}", job_id)).await?;
+            return print_batch_job(ctx, &status);
+        }
+        AdminBatchAction::Cancel { job_id } => {
+            let status: BatchJobStatus = client
+                .post(&format!("/batch/jobs/{}/cancel", job_id), &serde_json::json!({}))
+                .await?;
+            return print_batch_job(ctx, &status);
+        }
+        AdminBatchAction::Copy { job, dest } => {
+            let dest = S3Uri::parse(&format!("s3://{}", dest.trim_start_matches("s3://")))?;
+            let operation = serde_json::json!({
+                "type": "copy",
+                "bucket": dest.bucket,
+                "prefix": dest.key.unwrap_or_default(),
+            });
+            (job, operation)
+        }
+        AdminBatchAction::Tag { job, tags } => {
+            let tags = tags
+                .iter()
+                .map(|tag| {
+                    let (key, value) = tag
+                        .split_once('=')
+                        .with_context(|| format!("Tag must be key=value: {}", tag))?;
+                    Ok(serde_json::json!({ "key": key, "value": value }))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            (job, serde_json::json!({ "type": "tag", "tags": tags }))
+        }
+        AdminBatchAction::Delete { job, force } => {
+            if !force && !confirm(&format!("Delete every object listed in '{}'?", job.manifest)) {
+                ctx.info("Cancelled");
+                return Ok(());
+            }
+            (job, serde_json::json!({ "type": "delete" }))
+        }
+    };
+
+    let request = batch_job_request(job, operation)?;
+    let status: BatchJobStatus = client.post("/batch/jobs", &request).await?;
+    print_batch_job(ctx, &status)
+}
+
+fn batch_job_request(job: BatchJobArgs, operation: serde_json::Value) -> Result<BatchJobRequest> {
+    let manifest = if job.manifest.starts_with("s3://") {
+        let uri = S3Uri::parse(&job.manifest)?;
+        let key = uri.key.context("Manifest must name an object: s3://bucket/key")?;
+        serde_json::json!({ "bucket": uri.bucket, "key": key })
+    } else {
+        let csv = if job.manifest == "-" {
+            let mut buf = String::new();
+            std::io::stdin()
+                .read_to_string(&mut buf)
+                .context("Failed to read manifest from stdin")?;
+            buf
+        } else {
+            std::fs::read_to_string(&job.manifest)
+                .with_context(|| format!("Failed to read file: {}", job.manifest))?
+        };
+        serde_json::json!({ "csv": csv })
+    };
+
+    let report = match job.report {
+        Some(report) => {
+            let uri = S3Uri::parse(&report)?;
+            Some(BatchReportTarget {
+                bucket: uri.bucket,
+                prefix: uri.key.unwrap_or_default(),
+                failed_only: job.failed_only,
+            })
+        }
+        None => None,
+    };
+
+    Ok(BatchJobRequest {
+        manifest,
+        operation,
+        report,
+        concurrency: job.concurrency,
+        max_retries: job.max_retries,
+    })
+}
+
+fn print_batch_job(ctx: &CommandContext, status: &BatchJobStatus) -> Result<()> {
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(status)?);
+        return Ok(());
+    }
+
+    let state = match status.state.as_str() {
+        "completed" => status.state.green(),
+        "failed" | "cancelled" => status.state.red(),
+        _ => status.state.yellow(),
+    };
+    println!(
+        "{}: {} {} ({})",
+        "batch".green(),
+        status.id,
+        state,
+        status.operation["type"].as_str().unwrap_or("-")
+    );
+    println!(
+        "  {} of {} object(s) done, {} failed, {} retried",
+        status.succeeded + status.failed,
+        status.total_objects,
+        status.failed,
+        status.retries
+    );
+    if let Some(report) = &status.report {
+        println!("  report: s3://{}", report);
+    }
+    if let Some(err) = &status.error {
+        ctx.error(&format!("{}: {}", "batch error".red(), err));
+    }
+    Ok(())
+}
+
+async fn prune(ctx: &CommandContext, client: &AdminClient, action: AdminPruneAction) -> Result<()> {
+    let status: PruneJobStatus = match action {
+        AdminPruneAction::List => {
+            let list: PruneJobList = client.get("/prune/jobs").await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&list)?);
+                return Ok(());
+            }
+
+            println!(
+                "{:<36} {:<20} {:<10} {:>12} {:>10}  CREATED",
+                "ID", "BUCKET", "STATE", "KEYS", "PRUNED"
+            );
+            for job in &list.jobs {
+                let state = if job.request.dry_run {
+                    format!("{}*", job.state)
+                } else {
+                    job.state.clone()
+                };
+                println!(
+                    "{:<36} {:<20} {:<10} {:>12} {:>10}  {}",
+                    job.id,
+                    job.request.bucket,
+                    state,
+                    job.keys_scanned,
+                    job.versions_pruned + job.delete_markers_removed,
+                    job.created_at
+                );
+            }
+            return Ok(());
+        }
+        AdminPruneAction::Status { job_id } => {
+            client.get(&format!("/prune/jobs/{}", job_id)).await?
+        }
+        AdminPruneAction::Cancel { job_id } => {
+            client
+                .post(&format!("/prune/jobs/{}/cancel", job_id), &serde_json::json!({}))
+                .await?
+        }
+        AdminPruneAction::Run {
+            path,
+            older_than_days,
+            keep_last,
+            delete_markers,
+            dry_run,
+            wait,
+            force,
+        } => {
+            let path = path.trim_start_matches("s3://");
+            let uri = S3Uri::parse(&format!("s3://{}", path))?;
+            if older_than_days.is_none() && keep_last.is_none() && !delete_markers {
+                anyhow::bail!("Give --older-than-days, --keep-last or --delete-markers");
+            }
+            if !dry_run
+                && !force
+                && !confirm(&format!("Permanently remove old versions in 's3://{}'?", path))
+            {
+                ctx.info("Cancelled");
+                return Ok(());
+            }
+
+            let request = PruneRequest {
+                bucket: uri.bucket,
+                prefix: uri.key.unwrap_or_default(),
+                older_than_days,
+                keep_last,
+                delete_markers,
+                dry_run,
+            };
+            let mut status: PruneJobStatus = client.post("/prune/jobs", &request).await?;
+            if wait {
+                while status.state == "running" {
+                    if !ctx.is_json() {
+                        print_prune_progress(&status);
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    status = client.get(&format!("/prune/jobs/{}", status.id)).await?;
+                }
+            }
+            status
+        }
+    };
+
+    print_prune_job(ctx, &status)
+}
+
+/// One line of progress while following a job
+fn print_prune_progress(status: &PruneJobStatus) {
+    let share = if status.bucket_versions > 0 {
+        format!(
+            " (~{}%)",
+            (status.versions_scanned * 100 / status.bucket_versions as u64).min(100)
+        )
+    } else {
+        String::new()
+    };
+    println!(
+        "  {} key(s), {} version(s) scanned{}, {} pruned, at {}",
+        status.keys_scanned,
+        status.versions_scanned,
+        share,
+        status.versions_pruned + status.delete_markers_removed,
+        status.current_key.as_deref().unwrap_or("-")
+    );
+}
+
+fn print_prune_job(ctx: &CommandContext, status: &PruneJobStatus) -> Result<()> {
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(status)?);
+        return Ok(());
+    }
+
+    let state = match status.state.as_str() {
+        "completed" => status.state.green(),
+        "failed" | "cancelled" => status.state.red(),
+        _ => status.state.yellow(),
+    };
+    let target = if status.request.prefix.is_empty() {
+        status.request.bucket.clone()
+    } else {
+        format!("{}/{}", status.request.bucket, status.request.prefix)
+    };
+    println!(
+        "{}: {} {} (s3://{}{})",
+        "prune".green(),
+        status.id,
+        state,
+        target,
+        if status.request.dry_run { ", dry run" } else { "" }
+    );
+    println!(
+        "  {} key(s), {} version(s) scanned of ~{} in the bucket",
+        status.keys_scanned, status.versions_scanned, status.bucket_versions
+    );
+    println!(
+        "  {} {} version(s) and {} delete marker(s), {}",
+        if status.request.dry_run { "would remove" } else { "removed" },
+        status.versions_pruned,
+        status.delete_markers_removed,
+        format_size(status.bytes_freed, true)
+    );
+    if status.locked_versions > 0 || status.failed > 0 {
+        println!(
+            "  kept {} locked version(s), {} failed",
+            status.locked_versions, status.failed
+        );
+    }
+    if let Some(err) = &status.error {
+        ctx.error(&format!("{}: {}", "prune error".red(), err));
+    }
+    Ok(())
+}
+
+async fn cache(ctx: &CommandContext, client: &AdminClient, action: AdminCacheAction) -> Result<()> {
+    let status: WarmupJobStatus = match action {
+        AdminCacheAction::List => {
+            let list: WarmupJobList = client.get("/cache/warmup").await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&list)?);
+                return Ok(());
+            }
+
+            println!(
+                "{:<36} {:<20} {:<10} {:>10} {:>12}  CREATED",
+                "ID", "BUCKET", "STATE", "OBJECTS", "BYTES"
+            );
+            for job in &list.jobs {
+                println!(
+                    "{:<36} {:<20} {:<10} {:>10} {:>12}  {}",
+                    job.id,
+                    job.request.bucket,
+                    job.state,
+                    job.objects_loaded,
+                    format_size(job.bytes_loaded as i64, true),
+                    job.created_at
+                );
+            }
+            return Ok(());
+        }
+        AdminCacheAction::Status { job_id } => {
+            client.get(&format!("/cache/warmup/{}", job_id)).await?
+        }
+        AdminCacheAction::Cancel { job_id } => {
+            client
+                .post(&format!("/cache/warmup/{}/cancel", job_id), &serde_json::json!({}))
+                .await?
+        }
+        AdminCacheAction::Warmup {
+            path,
+            keys_from,
+            max_bytes,
+            evict,
+            concurrency,
+            wait,
+        } => {
+            let path = path.trim_start_matches("s3://");
+            let uri = S3Uri::parse(&format!("s3://{}", path))?;
+            let keys = match keys_from {
+                Some(source) => {
+                    let list = if source == "-" {
+                        let mut buf = String::new();
+                        std::io::stdin()
+                            .read_to_string(&mut buf)
+                            .context("Failed to read keys from stdin")?;
+                        buf
+                    } else {
+                        std::fs::read_to_string(&source)
+                            .with_context(|| format!("Failed to read file: {}", source))?
+                    };
+                    let keys: Vec<String> = list
+                        .lines()
+                        .map(str::trim)
+                        .filter(|line| !line.is_empty())
+                        .map(String::from)
+                        .collect();
+                    if keys.is_empty() {
+                        anyhow::bail!("No keys listed in {}", source);
+                    }
+                    keys
+                }
+                None => Vec::new(),
+            };
+
+            let request = WarmupRequest {
+                bucket: uri.bucket,
+                keys,
+                prefix: uri.key.unwrap_or_default(),
+                max_bytes,
+                evict,
+                concurrency,
+            };
+            let mut status: WarmupJobStatus = client.post("/cache/warmup", &request).await?;
+            if wait {
+                while status.state == "running" {
+                    if !ctx.is_json() {
+                        print_warmup_progress(&status);
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    status = client.get(&format!("/cache/warmup/{}", status.id)).await?;
+                }
+            }
+            status
+        }
+    };
+
+    print_warmup_job(ctx, &status)
+}
+
+/// One line of progress while following a job
+fn print_warmup_progress(status: &WarmupJobStatus) {
+    let share = if status.request.keys.is_empty() {
+        String::new()
+    } else {
+        format!(
+            " of {} (~{}%)",
+            status.request.keys.len(),
+            status.objects_scanned * 100 / status.request.keys.len() as u64
+        )
+    };
+    println!(
+        "  {} object(s) scanned{}, {} of {} loaded, at {}",
+        status.objects_scanned,
+        share,
+        format_size(status.bytes_loaded as i64, true),
+        format_size(status.max_bytes as i64, true),
+        status.current_key.as_deref().unwrap_or("-")
+    );
+}
+
+fn print_warmup_job(ctx: &CommandContext, status: &WarmupJobStatus) -> Result<()> {
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(status)?);
+        return Ok(());
+    }
+
+    let state = match status.state.as_str() {
+        "completed" => status.state.green(),
+        "failed" | "cancelled" => status.state.red(),
+        _ => status.state.yellow(),
+    };
+    let target = if !status.request.keys.is_empty() {
+        format!("{} key(s) in s3://{}", status.request.keys.len(), status.request.bucket)
+    } else if status.request.prefix.is_empty() {
+        format!("s3://{}", status.request.bucket)
+    } else {
+        format!("s3://{}/{}", status.request.bucket, status.request.prefix)
+    };
+    println!("{}: {} {} ({})", "cache warm-up".green(), status.id, state, target);
+    println!(
+        "  loaded {} object(s), {} of at most {}",
+        status.objects_loaded,
+        format_size(status.bytes_loaded as i64, true),
+        format_size(status.max_bytes as i64, true)
+    );
+    println!(
+        "  {} cached already, {} too large, {} missing, {} failed",
+        status.already_cached, status.too_large, status.missing, status.failed
+    );
+    if let Some(reason) = &status.reason {
+        let message = format!("{}: {}", "stopped".yellow(), reason);
+        if status.state == "failed" {
+            ctx.error(&message);
+        } else {
+            println!("  {}", message);
+        }
+    }
+    Ok(())
+}
+
+async fn key_rewrap(ctx: &CommandContext, client: &AdminClient, action: AdminKeyRewrapAction) -> Result<()> {
+    let status: KeyRewrapStatus = match action {
+        AdminKeyRewrapAction::Status => client.get("/encryption/rewrap").await?,
+        AdminKeyRewrapAction::Cancel => {
+            client
+                .post("/encryption/rewrap/cancel", &serde_json::json!({}))
+                .await?
+        }
+        AdminKeyRewrapAction::Start { wait } => {
+            let mut status: KeyRewrapStatus = client
+                .post("/encryption/rewrap", &serde_json::json!({}))
+                .await?;
+            if wait {
+                while status.running {
+                    if let (Some(run), false) = (&status.last_run, ctx.is_json()) {
+                        println!(
+                            "  {} object version(s) scanned, {} re-wrapped, at {}",
+                            run.objects_scanned,
+                            run.objects_rewrapped,
+                            run.position.as_deref().unwrap_or("-")
+                        );
+                    }
+                    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                    status = client.get("/encryption/rewrap").await?;
+                }
+            }
+            status
+        }
+    };
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    let Some(run) = &status.last_run else {
+        println!("No key re-wrap has run yet");
+        return Ok(());
+    };
+    let state = match run.state.as_str() {
+        "completed" => run.state.green(),
+        "failed" => run.state.red(),
+        _ => run.state.yellow(),
+    };
+    println!(
+        "{}: {} (master key {} to {})",
+        "key re-wrap".green(),
+        state,
+        &run.from_key_check[..run.from_key_check.len().min(8)],
+        &run.to_key_check[..run.to_key_check.len().min(8)]
+    );
+    println!(
+        "  {} object version(s) scanned, {} re-wrapped, {} already current, {} failed",
+        run.objects_scanned, run.objects_rewrapped, run.objects_current, run.objects_failed
+    );
+    if let Some(position) = &run.position {
+        println!("  last done: {}", position);
+    }
+    println!(
+        "  started {}, {}",
+        run.started_at,
+        match &run.finished_at {
+            Some(finished) => format!("finished {}", finished),
+            None => format!("updated {}", run.updated_at),
+        }
+    );
+    if let Some(err) = &run.error {
+        ctx.error(&format!("{}: {}", "key re-wrap error".red(), err));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccessPointInfo {
+    name: String,
+    bucket: String,
+    alias: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    policy: Option<serde_json::Value>,
+    allowed_cidrs: Vec<String>,
+    created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccessPointList {
+    access_points: Vec<AccessPointInfo>,
+}
+
+#[derive(Debug, Serialize)]
+struct CreateAccessPointRequest {
+    name: String,
+    bucket: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    policy: Option<serde_json::Value>,
+    allowed_cidrs: Vec<String>,
+}
+
+/// Read a JSON policy document from a file, or stdin for `-`
+fn read_policy(source: &str) -> Result<serde_json::Value> {
+    let text = if source == "-" {
+        let mut buf = String::new();
+        std::io::stdin()
+            .read_to_string(&mut buf)
+            .context("Failed to read policy from stdin")?;
+        buf
+    } else {
+        std::fs::read_to_string(source).with_context(|| format!("Failed to read {}", source))?
+    };
+    serde_json::from_str(&text).context("Policy is not valid JSON")
+}
+
+async fn access_point(
+    ctx: &CommandContext,
+    client: &AdminClient,
+    action: AdminAccessPointAction,
+) -> Result<()> {
+    let info: AccessPointInfo = match action {
+        AdminAccessPointAction::List { bucket } => {
+            let path = match bucket {
+                Some(bucket) => format!("/access-points?bucket={}", bucket),
+                None => "/access-points".to_string(),
+            };
+            let list: AccessPointList = client.get(&path).await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&list)?);
+                return Ok(());
+            }
+
+            println!("{:<30} {:<30} {:<8} {}", "NAME", "BUCKET", "POLICY", "NETWORKS");
+            for ap in &list.access_points {
+                println!(
+                    "{:<30} {:<30} {:<8} {}",
+                    ap.name,
+                    ap.bucket,
+                    if ap.policy.is_some() { "yes" } else { "no" },
+                    if ap.allowed_cidrs.is_empty() {
+                        "any".to_string()
+                    } else {
+                        ap.allowed_cidrs.join(",")
+                    }
+                );
+            }
+            return Ok(());
+        }
+        AdminAccessPointAction::Create { name, bucket, allowed_cidrs, policy } => {
+            let request = CreateAccessPointRequest {
+                name,
+                bucket,
+                policy: policy.as_deref().map(read_policy).transpose()?,
+                allowed_cidrs,
+            };
+            client.post("/access-points", &request).await?
+        }
+        AdminAccessPointAction::Info { name } => {
+            client.get(&format!("/access-points/{}", name)).await?
+        }
+        AdminAccessPointAction::Delete { name, force } => {
+            if !force && !confirm(&format!("Delete access point '{}'?", name)) {
+                ctx.info("Cancelled");
+                return Ok(());
+            }
+            client.delete(&format!("/access-points/{}", name)).await?;
+            ctx.info(&format!("Deleted access point {}", name));
+            return Ok(());
+        }
+        AdminAccessPointAction::Policy { name, file, clear } => {
+            let path = format!("/access-points/{}/policy", name);
+            if clear {
+                client.delete(&path).await?;
+                client.get(&format!("/access-points/{}", name)).await?
+            } else {
+                let policy = read_policy(file.as_deref().unwrap_or("-"))?;
+                client.put(&path, &policy).await?
+            }
+        }
+    };
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+
+    println!("{}: {} -> {}", "access point".green(), info.name.cyan(), info.bucket);
+    println!("  alias: {}", info.alias);
+    if let Some(hostname) = &info.hostname {
+        println!("  hostname: {}", hostname);
+    }
+    println!(
+        "  networks: {}",
+        if info.allowed_cidrs.is_empty() {
+            "any".to_string()
+        } else {
+            info.allowed_cidrs.join(", ")
+        }
+    );
+    match &info.policy {
+        Some(policy) => println!("  policy:\n{}", serde_json::to_string_pretty(policy)?),
+        None => println!("  policy: none"),
+    }
+    Ok(())
+}
+
+async fn cluster(ctx: &CommandContext, client: &AdminClient, action: AdminClusterAction) -> Result<()> {
+    match action {
+        AdminClusterAction::Nodes => {
+            let list: NodeList = client.get("/cluster/nodes").await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&list)?);
+                return Ok(());
+            }
+
+            println!(
+                "{:<20} {:<20} {:<12} {:<10} {:<16} {}",
+                "ID", "NAME", "STATUS", "ROLE", "ZONE/RACK", "ENDPOINT"
+            );
+            for node in &list.nodes {
+                let status = match node.status.as_str() {
+                    "healthy" => node.status.green(),
+                    "draining" | "degraded" | "starting" => node.status.yellow(),
+                    _ => node.status.red(),
+                };
+                let domain = format!(
+                    "{}/{}",
+                    node.zone.as_deref().unwrap_or("-"),
+                    node.rack.as_deref().unwrap_or("-")
+                );
+                println!(
+                    "{:<20} {:<20} {:<12} {:<10} {:<16} {}",
+                    node.id, node.name, status, node.role, domain, node.endpoint
+                );
+            }
+            println!(
+                "\n{} node(s), {} healthy, {} draining",
+                list.total, list.healthy, list.draining
+            );
+            print_rebalance(&list.rebalance);
+        }
+        AdminClusterAction::Add { endpoint } => {
+            let result: NodeOperation = client
+                .post("/cluster/nodes", &AddNodeRequest { endpoint })
+                .await?;
+            node_operation(ctx, "add_node", &result)?;
+        }
+        AdminClusterAction::Drain { node_id } => {
+            let result: NodeOperation = client
+                .post(&format!("/cluster/nodes/{}/drain", node_id), &serde_json::json!({}))
+                .await?;
+            node_operation(ctx, "drain_node", &result)?;
+        }
+        AdminClusterAction::Activate { node_id } => {
+            let result: NodeOperation = client
+                .post(&format!("/cluster/nodes/{}/activate", node_id), &serde_json::json!({}))
+                .await?;
+            node_operation(ctx, "activate_node", &result)?;
+        }
+        AdminClusterAction::Decommission { node_id, force, yes } => {
+            if !yes && !confirm(&format!("Decommission node '{}'?", node_id)) {
+                ctx.info("Cancelled");
+                return Ok(());
+            }
+
+            client
+                .delete(&format!("/cluster/nodes/{}?force={}", node_id, force))
+                .await?;
+            ctx.info(&format!("{}: {}", "decommission_node".green(), node_id));
+        }
+        AdminClusterAction::Topology => {
+            let topology: ClusterTopology = client.get("/cluster/topology").await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&topology)?);
+                return Ok(());
+            }
+
+            let placement = if topology.placement_enabled {
+                "hash ring"
+            } else {
+                "full replication"
+            };
+            println!(
+                "{}: {} replica(s) per object ({})",
+                "topology".green(),
+                topology.replication_factor,
+                placement
+            );
+
+            let label = |value: &Option<String>| value.clone().unwrap_or_else(|| "-".to_string());
+            for zone in &topology.zones {
+                println!("{} {:<24} {:>6.1}%", "zone".cyan(), label(&zone.zone), zone.share * 100.0);
+                for rack in &zone.racks {
+                    println!("  {} {:<22} {:>6.1}%", "rack".cyan(), label(&rack.rack), rack.share * 100.0);
+                    for node in &rack.nodes {
+                        println!(
+                            "    {:<20} {:<10} {:>6.1}%  {}",
+                            node.name,
+                            node.status,
+                            node.share * 100.0,
+                            node.id.dimmed()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn node_operation(ctx: &CommandContext, label: &str, result: &NodeOperation) -> Result<()> {
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(result)?);
+        return Ok(());
+    }
+
+    ctx.info(&format!(
+        "{}: {} ({}) is {}",
+        label.green(),
+        result.node.id,
+        result.node.endpoint,
+        result.node.status
+    ));
+    print_rebalance(&result.rebalance);
+    Ok(())
+}
+
+fn print_rebalance(progress: &RebalanceProgress) {
+    let state = if progress.in_progress { "running" } else { "idle" };
+    println!(
+        "{}: {}, {} scanned, {} moved ({}), {} failed",
+        "rebalance".cyan(),
+        state,
+        progress.objects_scanned,
+        progress.objects_moved,
+        format_size(progress.bytes_moved as i64, true),
+        progress.objects_failed
+    );
+}
+
+async fn repair(ctx: &CommandContext, client: &AdminClient, bucket: Option<String>) -> Result<()> {
+    let report: RepairReport = client
+        .post_query("/cluster/repair", &RepairQuery { bucket })
+        .await?;
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "{}: {} comparison(s), {} divergent leaf range(s), {} divergent object(s), {} repaired",
+        "repair".green(),
+        report.buckets_compared,
+        report.divergent_leaves,
+        report.divergent_objects,
+        report.objects_repaired
+    );
+    if report.repair_failures > 0 {
+        ctx.error(&format!(
+            "{}: {} object(s) could not be repaired",
+            "repair error".red(),
+            report.repair_failures
+        ));
+    }
+
+    Ok(())
+}
+
+async fn consistency(
+    ctx: &CommandContext,
+    client: &AdminClient,
+    bucket: String,
+    level: Option<String>,
+    clear: bool,
+) -> Result<()> {
+    let path = format!("/cluster/buckets/{}/consistency", bucket);
+
+    if clear {
+        client.delete(&path).await?;
+    } else if let Some(level) = level {
+        let _: BucketConsistency = client.put(&path, &SetConsistencyRequest { level }).await?;
+    }
+    let current: BucketConsistency = client.get(&path).await?;
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&current)?);
+        return Ok(());
+    }
+
+    let source = if current.level.is_some() { "bucket" } else { "cluster default" };
+    println!(
+        "{}: {} ({})",
+        current.bucket.cyan(),
+        current.effective.green(),
+        source
+    );
+
+    Ok(())
+}
+
+async fn storage_backend(
+    ctx: &CommandContext,
+    client: &AdminClient,
+    bucket: Option<String>,
+    backend: Option<String>,
+    clear: bool,
+) -> Result<()> {
+    let Some(bucket) = bucket else {
+        let list: StorageBackendsResponse = client.get("/storage/backends").await?;
+        if ctx.is_json() {
+            println!("{}", serde_json::to_string_pretty(&list)?);
+            return Ok(());
+        }
+
+        println!("{} (default storage)", list.default.cyan());
+        for backend in &list.backends {
+            println!("{} ({})", backend.name.cyan(), backend.kind);
+            for bucket in &backend.buckets {
+                println!("  {}", bucket);
+            }
+        }
+        return Ok(());
+    };
+
+    let path = format!("/buckets/{}/backend", bucket);
+    if clear {
+        client.delete(&path).await?;
+    } else if let Some(backend) = backend {
+        let _: BucketBackend = client.put(&path, &SetBackendRequest { backend }).await?;
+    }
+    let current: BucketBackend = client.get(&path).await?;
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&current)?);
+        return Ok(());
+    }
+
+    println!("{}: {}", current.bucket.cyan(), current.backend.green());
+    Ok(())
+}
+
+async fn compression(
+    ctx: &CommandContext,
+    client: &AdminClient,
+    bucket: String,
+    codec: Option<String>,
+    level: Option<i32>,
+    clear: bool,
+) -> Result<()> {
+    let path = format!("/buckets/{}/compression", bucket);
+
+    if clear {
+        client.delete(&path).await?;
+    } else if let Some(codec) = codec {
+        let req = BucketCompression {
+            bucket: String::new(),
+            codec,
+            level,
+        };
+        let _: BucketCompression = client.put(&path, &req).await?;
+    }
+    let current: BucketCompression = client.get(&path).await?;
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&current)?);
+        return Ok(());
+    }
+
+    match current.level {
+        Some(level) => println!("{}: {} (level {})", current.bucket.cyan(), current.codec.green(), level),
+        None => println!("{}: {}", current.bucket.cyan(), current.codec.green()),
+    }
+    Ok(())
+}
+
+async fn bandwidth(
+    ctx: &CommandContext,
+    client: &AdminClient,
+    bucket: Option<String>,
+    access_key: Option<String>,
+    ingress: Option<u64>,
+    egress: Option<u64>,
+    clear: bool,
+) -> Result<()> {
+    let path = match (bucket, access_key) {
+        (Some(bucket), _) => format!("/buckets/{}/bandwidth", bucket),
+        (None, Some(access_key)) => format!("/users/{}/bandwidth", access_key),
+        (None, None) => {
+            let limits: Vec<BandwidthLimit> = client.get("/bandwidth").await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&limits)?);
+            } else if limits.is_empty() {
+                println!("No bandwidth limits set");
+            } else {
+                for limit in &limits {
+                    print_bandwidth(limit);
+                }
+            }
+            return Ok(());
+        }
+    };
+
+    if clear {
+        client.delete(&path).await?;
+    } else if ingress.is_some() || egress.is_some() {
+        // Keep the direction not given as it is
+        let current: BandwidthLimit = client.get(&path).await?;
+        let req = BandwidthLimit {
+            scope: String::new(),
+            name: String::new(),
+            ingress_bytes_per_sec: ingress.unwrap_or(current.ingress_bytes_per_sec),
+            egress_bytes_per_sec: egress.unwrap_or(current.egress_bytes_per_sec),
+        };
+        let _: BandwidthLimit = client.put(&path, &req).await?;
+    }
+    let current: BandwidthLimit = client.get(&path).await?;
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&current)?);
+        return Ok(());
+    }
+    print_bandwidth(&current);
+    Ok(())
+}
+
+fn print_bandwidth(limit: &BandwidthLimit) {
+    let rate = |bytes_per_sec: u64| match bytes_per_sec {
+        0 => "unlimited".to_string(),
+        n => format!("{}/s", format_size(n as i64, true)),
+    };
+    println!(
+        "{} {}: ingress {}, egress {}",
+        limit.scope.replace('_', " "),
+        limit.name.cyan(),
+        rate(limit.ingress_bytes_per_sec).green(),
+        rate(limit.egress_bytes_per_sec).green()
+    );
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SftpUser {
+    username: String,
+    access_key: String,
+    home: String,
+    keys: Vec<SftpKey>,
+    created_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SftpKey {
+    fingerprint: String,
+    public_key: String,
+    comment: Option<String>,
+    created_at: String,
+}
+
+async fn sftp(ctx: &CommandContext, client: &AdminClient, action: AdminSftpAction) -> Result<()> {
+    match action {
+        AdminSftpAction::List => {
+            let users: Vec<SftpUser> = client.get("/sftp/users").await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&users)?);
+            } else if users.is_empty() {
+                println!("No SFTP logins");
+            } else {
+                for user in &users {
+                    print_sftp_user(user);
+                }
+            }
+        }
+        AdminSftpAction::Set { username, access_key, home } => {
+            let req = serde_json::json!({ "access_key": access_key, "home": home });
+            let user: SftpUser = client.put(&format!("/sftp/users/{}", username), &req).await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&user)?);
+            } else {
+                print_sftp_user(&user);
+            }
+        }
+        AdminSftpAction::Remove { username, force } => {
+            if !force && !confirm(&format!("Remove SFTP login '{}'?", username)) {
+                ctx.info("Cancelled");
+                return Ok(());
+            }
+
+            client.delete(&format!("/sftp/users/{}", username)).await?;
+            ctx.info(&format!("{}: {}", "remove_sftp_user".green(), username));
+        }
+        AdminSftpAction::AddKey { username, file } => {
+            let contents = if file == "-" {
+                let mut buf = String::new();
+                std::io::stdin()
+                    .read_to_string(&mut buf)
+                    .context("Failed to read public keys from stdin")?;
+                buf
+            } else {
+                std::fs::read_to_string(&file).with_context(|| format!("Failed to read file: {}", file))?
+            };
+
+            let mut added = Vec::new();
+            for line in contents.lines().map(str::trim) {
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let req = serde_json::json!({ "public_key": line });
+                let key: SftpKey = client.post(&format!("/sftp/users/{}/keys", username), &req).await?;
+                added.push(key);
+            }
+            if added.is_empty() {
+                anyhow::bail!("No public keys found in {}", file);
+            }
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&added)?);
+            } else {
+                for key in &added {
+                    println!("{}: {} {}", "add_sftp_key".green(), username, key.fingerprint);
+                }
+            }
+        }
+        AdminSftpAction::RemoveKey { username, fingerprint } => {
+            let query = url::form_urlencoded::Serializer::new(String::new())
+                .append_pair("fingerprint", &fingerprint)
+                .finish();
+            client.delete(&format!("/sftp/users/{}/keys?{}", username, query)).await?;
+            ctx.info(&format!("{}: {} {}", "remove_sftp_key".green(), username, fingerprint));
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SearchStatus {
+    enabled: bool,
+    documents: u64,
+    pending: usize,
+    rebuilding: bool,
+}
+
+async fn search(ctx: &CommandContext, client: &AdminClient, action: AdminSearchAction) -> Result<()> {
+    let status: SearchStatus = match action {
+        AdminSearchAction::Status => client.get("/search/status").await?,
+        AdminSearchAction::Rebuild => client.post("/search/rebuild", &serde_json::json!({})).await?,
+    };
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else if !status.enabled {
+        println!("{}: not enabled", "search".green());
+    } else {
+        println!(
+            "{}: {} objects indexed, {} waiting{}",
+            "search".green(),
+            status.documents,
+            status.pending,
+            if status.rebuilding { ", rebuilding" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CaptureFilter {
+    operations: Vec<String>,
+    buckets: Vec<String>,
+    principal: Option<String>,
+    errors_only: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct CaptureRequest {
+    bucket: String,
+    prefix: String,
+    filter: CaptureFilter,
+    include_bodies: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_body_bytes: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_requests: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    duration_secs: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CaptureStatus {
+    id: String,
+    state: String,
+    bucket: String,
+    prefix: String,
+    filter: CaptureFilter,
+    include_bodies: bool,
+    max_body_bytes: usize,
+    max_requests: u64,
+    started_at: String,
+    expires_at: String,
+    stopped_at: Option<String>,
+    stop_reason: Option<String>,
+    captured: u64,
+    failed: u64,
+}
+
+async fn capture(ctx: &CommandContext, client: &AdminClient, action: AdminCaptureAction) -> Result<()> {
+    let status: CaptureStatus = match action {
+        AdminCaptureAction::Start {
+            destination,
+            operations,
+            buckets,
+            principal,
+            errors_only,
+            bodies,
+            max_body_size,
+            max_requests,
+            duration,
+        } => {
+            let uri = S3Uri::parse(&destination)?;
+            let mut prefix = uri.key.unwrap_or_default();
+            if !prefix.is_empty() && !prefix.ends_with('/') {
+                prefix.push('/');
+            }
+            let request = CaptureRequest {
+                bucket: uri.bucket,
+                prefix,
+                filter: CaptureFilter {
+                    operations,
+                    buckets,
+                    principal,
+                    errors_only,
+                },
+                include_bodies: bodies,
+                max_body_bytes: max_body_size,
+                max_requests,
+                duration_secs: duration,
+            };
+            client.post("/capture", &request).await?
+        }
+        AdminCaptureAction::Status => client.get("/capture").await?,
+        AdminCaptureAction::Stop => client.post("/capture/stop", &serde_json::json!({})).await?,
+    };
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    let state = match status.state.as_str() {
+        "running" => status.state.yellow(),
+        _ => status.state.green(),
+    };
+    println!("{}: {} {}", "capture".green(), status.id, state);
+    println!(
+        "  {}: s3://{}/{}",
+        "Records".cyan(),
+        status.bucket,
+        status.prefix
+    );
+    println!(
+        "  {}: {} of at most {}{}",
+        "Captured".cyan(),
+        status.captured,
+        status.max_requests,
+        if status.failed > 0 {
+            format!(", {} failed", status.failed).red().to_string()
+        } else {
+            String::new()
+        }
+    );
+    if status.include_bodies {
+        println!("  {}: up to {}", "Bodies".cyan(), format_size(status.max_body_bytes as i64, true));
+    }
+    let filter = &status.filter;
+    if !filter.operations.is_empty() {
+        println!("  {}: {}", "Operations".cyan(), filter.operations.join(", "));
+    }
+    if !filter.buckets.is_empty() {
+        println!("  {}: {}", "Buckets".cyan(), filter.buckets.join(", "));
+    }
+    if let Some(principal) = &filter.principal {
+        println!("  {}: {}", "Principal".cyan(), principal);
+    }
+    if filter.errors_only {
+        println!("  {}: errors only", "Outcome".cyan());
+    }
+    println!("  {}: {}", "Started".cyan(), status.started_at);
+    match (&status.stopped_at, &status.stop_reason) {
+        (Some(at), Some(reason)) => println!("  {}: {} ({})", "Stopped".cyan(), at, reason),
+        _ => println!("  {}: {}", "Ends".cyan(), status.expires_at),
+    }
+    Ok(())
+}
+
+fn print_sftp_user(user: &SftpUser) {
+    println!("{} as {} in {}", user.username.green(), user.access_key, user.home.cyan());
+    if user.keys.is_empty() {
+        println!("  no keys; the login cannot authenticate");
+    }
+    for key in &user.keys {
+        println!("  {} {}", key.fingerprint, key.comment.as_deref().unwrap_or(""));
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AdminRoleGrant {
+    access_key: String,
+    role: AdminRole,
+    granted_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WhoAmI {
+    name: String,
+    role: AdminRole,
+}
+
+async fn role(ctx: &CommandContext, client: &AdminClient, action: AdminRoleAction) -> Result<()> {
+    match action {
+        AdminRoleAction::List => {
+            let grants: Vec<AdminRoleGrant> = client.get("/roles").await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&grants)?);
+            } else if grants.is_empty() {
+                println!("No admin roles granted; only admins can use the admin API");
+            } else {
+                println!("{:<24} {:<16} {}", "ACCESS KEY".bold(), "ROLE".bold(), "GRANTED".bold());
+                for grant in &grants {
+                    println!("{:<24} {:<16} {}", grant.access_key, grant.role, grant.granted_at);
+                }
+            }
+        }
+        AdminRoleAction::Grant { access_key, role } => {
+            let req = serde_json::json!({ "role": role });
+            let grant: AdminRoleGrant = client.put(&format!("/roles/{}", access_key), &req).await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&grant)?);
+            } else {
+                ctx.info(&format!("{}: {} {}", "grant_role".green(), grant.access_key, grant.role));
+            }
+        }
+        AdminRoleAction::Revoke { access_key } => {
+            client.delete(&format!("/roles/{}", access_key)).await?;
+            ctx.info(&format!("{}: {}", "revoke_role".green(), access_key));
+        }
+        AdminRoleAction::Whoami => {
+            let me: WhoAmI = client.get("/roles/me").await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&me)?);
+            } else {
+                println!("{} ({})", me.name.green(), me.role);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AccountQuota {
+    max_buckets: u64,
+    max_objects: u64,
+    max_bytes: u64,
+}
+
+impl AccountQuota {
+    /// This quota with the limits given on the command line
+    fn with_args(self, args: AccountQuotaArgs) -> Self {
+        Self {
+            max_buckets: args.max_buckets.unwrap_or(self.max_buckets),
+            max_objects: args.max_objects.unwrap_or(self.max_objects),
+            max_bytes: args.max_bytes.unwrap_or(self.max_bytes),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountUsage {
+    buckets: u64,
+    objects: u64,
+    bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct AccountInfo {
+    id: String,
+    name: String,
+    status: String,
+    quota: AccountQuota,
+    #[serde(default)]
+    key_generation: u32,
+    created_at: String,
+    usage: AccountUsage,
+    users: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct AccountListResponse {
+    accounts: Vec<AccountInfo>,
+}
+
+async fn account(ctx: &CommandContext, client: &AdminClient, action: AdminAccountAction) -> Result<()> {
+    match action {
+        AdminAccountAction::List => {
+            let resp: AccountListResponse = client.get("/accounts").await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&resp.accounts)?);
+            } else {
+                println!(
+                    "{:<20} {:<10} {:>6} {:>8} {:>10} {:>12}",
+                    "ACCOUNT".bold(),
+                    "STATUS".bold(),
+                    "USERS".bold(),
+                    "BUCKETS".bold(),
+                    "OBJECTS".bold(),
+                    "SIZE".bold()
+                );
+                for a in &resp.accounts {
+                    println!(
+                        "{:<20} {:<10} {:>6} {:>8} {:>10} {:>12}",
+                        a.id,
+                        a.status,
+                        a.users,
+                        a.usage.buckets,
+                        a.usage.objects,
+                        format_size(a.usage.bytes as i64, true)
+                    );
+                }
+            }
+        }
+        AdminAccountAction::Show { id } => {
+            let info: AccountInfo = client.get(&format!("/accounts/{}", id)).await?;
+            print_account(ctx, &info)?;
+        }
+        AdminAccountAction::Create { id, name, quota } => {
+            let req = serde_json::json!({
+                "id": id,
+                "name": name,
+                "quota": AccountQuota::default().with_args(quota),
+            });
+            let info: AccountInfo = client.post("/accounts", &req).await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                ctx.info(&format!("{}: {}", "create_account".green(), info.id));
+            }
+        }
+        AdminAccountAction::Quota { id, quota } => {
+            let current: AccountInfo = client.get(&format!("/accounts/{}", id)).await?;
+            let req = serde_json::json!({ "quota": current.quota.with_args(quota) });
+            let info: AccountInfo = client.put(&format!("/accounts/{}", id), &req).await?;
+            print_account(ctx, &info)?;
+        }
+        AdminAccountAction::Suspend { id } => {
+            let _: AccountInfo = client
+                .post(&format!("/accounts/{}/suspend", id), &serde_json::json!({}))
+                .await?;
+            ctx.info(&format!("{}: {}", "suspend_account".green(), id));
+        }
+        AdminAccountAction::Resume { id } => {
+            let _: AccountInfo = client
+                .post(&format!("/accounts/{}/resume", id), &serde_json::json!({}))
+                .await?;
+            ctx.info(&format!("{}: {}", "resume_account".green(), id));
+        }
+        AdminAccountAction::RotateKey { id } => {
+            let info: AccountInfo = client
+                .post(&format!("/accounts/{}/rotate-key", id), &serde_json::json!({}))
+                .await?;
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+            } else {
+                ctx.info(&format!("{}: {}/v{}", "rotate_account_key".green(), info.id, info.key_generation));
+            }
+        }
+        AdminAccountAction::Remove { id, force } => {
+            if !force && !confirm(&format!("Remove account '{}'?", id)) {
+                ctx.info("Cancelled");
+                return Ok(());
+            }
+            client.delete(&format!("/accounts/{}", id)).await?;
+            ctx.info(&format!("{}: {}", "remove_account".green(), id));
+        }
+        AdminAccountAction::Assign { access_key, account } => {
+            let req = serde_json::json!({ "account_id": account });
+            let user: UserInfo = client
+                .put(&format!("/users/{}/account", access_key), &req)
+                .await?;
+            ctx.info(&format!("{}: {} to {}", "assign_account".green(), user.access_key, user.account_id));
+        }
+    }
+    Ok(())
+}
+
+fn print_account(ctx: &CommandContext, info: &AccountInfo) -> Result<()> {
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(info)?);
+        return Ok(());
+    }
+    let limit = |max: u64, show: &dyn Fn(u64) -> String| {
+        if max == 0 {
+            "unlimited".to_string()
+        } else {
+            show(max)
+        }
+    };
+    let size = |bytes: u64| format_size(bytes as i64, true);
+    let status = if info.status == "suspended" {
+        info.status.red()
+    } else {
+        info.status.green()
+    };
+    println!("{} ({}) {}", info.id.green(), info.name, status);
+    println!("  {}: {}", "Users".cyan(), info.users);
+    println!(
+        "  {}: {} of {}",
+        "Buckets".cyan(),
+        info.usage.buckets,
+        limit(info.quota.max_buckets, &|n| n.to_string())
+    );
+    println!(
+        "  {}: {} of {}",
+        "Objects".cyan(),
+        info.usage.objects,
+        limit(info.quota.max_objects, &|n| n.to_string())
+    );
+    println!(
+        "  {}: {} of {}",
+        "Size".cyan(),
+        size(info.usage.bytes),
+        limit(info.quota.max_bytes, &size)
+    );
+    println!("  {}: {}/v{}", "Key".cyan(), info.id, info.key_generation);
+    println!("  {}: {}", "Created".cyan(), info.created_at);
+    Ok(())
+}
+
+async fn metadata(ctx: &CommandContext, client: &AdminClient, action: AdminMetadataAction) -> Result<()> {
+    match action {
+        AdminMetadataAction::Export { output } => {
+            let bytes = match output.as_deref() {
+                None | Some("-") => client.download("/metadata/export", &mut tokio::io::stdout()).await?,
+                Some(path) => {
+                    let mut file = tokio::fs::File::create(path)
+                        .await
+                        .with_context(|| format!("Failed to create {}", path))?;
+                    let bytes = client.download("/metadata/export", &mut file).await?;
+                    ctx.info(&format!("{}: wrote {} to {}", "export".green(), format_size(bytes as i64, true), path));
+                    bytes
+                }
+            };
+            ctx.debug(&format!("Exported {} bytes of metadata", bytes));
+        }
+        AdminMetadataAction::Import { file, force } => {
+            if !force && !confirm("Import metadata, replacing buckets, objects and users with the same names?") {
+                println!("Aborted");
+                return Ok(());
+            }
+
+            let summary: DumpSummary = if file == "-" {
+                let mut dump = Vec::new();
+                std::io::stdin().read_to_end(&mut dump).context("Failed to read stdin")?;
+                client.post_body("/metadata/import", "application/x-ndjson", dump).await?
+            } else {
+                let dump = tokio::fs::File::open(&file)
+                    .await
+                    .with_context(|| format!("Failed to open {}", file))?;
+                client.post_body("/metadata/import", "application/x-ndjson", dump).await?
+            };
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+                return Ok(());
+            }
+            println!(
+                "{}: {} user(s), {} bucket(s), {} object version(s), {} policy(ies), {} tag set(s)",
+                "import".green(),
+                summary.users,
+                summary.buckets,
+                summary.objects,
+                summary.bucket_policies,
+                summary.bucket_tags + summary.object_tags
+            );
+        }
+    }
+
+    Ok(())
+}
+
+async fn snapshot(ctx: &CommandContext, client: &AdminClient, action: AdminSnapshotAction) -> Result<()> {
+    match action {
+        AdminSnapshotAction::Create { bucket, description } => {
+            let report: SnapshotReport = client
+                .post(
+                    &format!("/buckets/{}/snapshots", bucket),
+                    &CreateSnapshotRequest { description },
+                )
+                .await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+                return Ok(());
+            }
+            print_snapshot(&report.snapshot);
+            if report.dropped > 0 || report.recaptured > 0 {
+                println!(
+                    "  {} version(s) deleted during the snapshot were left out, {} overwritten were captured as rewritten",
+                    report.dropped, report.recaptured
+                );
+            }
+        }
+        AdminSnapshotAction::List { bucket } => {
+            let snapshots: Vec<BucketSnapshot> = match bucket {
+                Some(bucket) => client.get(&format!("/buckets/{}/snapshots", bucket)).await?,
+                None => client.get("/snapshots").await?,
+            };
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&snapshots)?);
+                return Ok(());
+            }
+            println!(
+                "{:<36} {:<24} {:>10} {:>10}  {}",
+                "ID", "BUCKET", "VERSIONS", "SIZE", "CREATED"
+            );
+            for snapshot in &snapshots {
+                let created = if snapshot.completed_at.is_some() {
+                    snapshot.created_at.clone()
+                } else {
+                    format!("{} (incomplete)", snapshot.created_at)
+                };
+                println!(
+                    "{:<36} {:<24} {:>10} {:>10}  {}",
+                    snapshot.id,
+                    snapshot.bucket,
+                    snapshot.versions,
+                    format_size(snapshot.total_bytes, true),
+                    created
+                );
+            }
+        }
+        AdminSnapshotAction::Info { id } => {
+            let snapshot: BucketSnapshot = client.get(&format!("/snapshots/{}", id)).await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&snapshot)?);
+                return Ok(());
+            }
+            print_snapshot(&snapshot);
+        }
+        AdminSnapshotAction::Delete { id, force } => {
+            if !force && !confirm(&format!("Delete snapshot '{}' and its data?", id)) {
+                println!("Aborted");
+                return Ok(());
+            }
+            client.delete(&format!("/snapshots/{}", id)).await?;
+            ctx.info(&format!("{}: {}", "delete".green(), id));
+        }
+        AdminSnapshotAction::Restore { id, bucket } => {
+            let restored: RestoredBucket = client
+                .post(
+                    &format!("/snapshots/{}/restore", id),
+                    &RestoreSnapshotRequest { bucket },
+                )
+                .await?;
+
+            if ctx.is_json() {
+                println!("{}", serde_json::to_string_pretty(&restored)?);
+                return Ok(());
+            }
+            println!("{}: {} -> {}", "restore".green(), id, restored.name.cyan());
+        }
+    }
+
+    Ok(())
+}
+
+fn print_snapshot(snapshot: &BucketSnapshot) {
+    println!("{}: {}", "snapshot".green(), snapshot.id);
+    println!("  Bucket:      {}", snapshot.bucket.cyan());
+    if let Some(description) = &snapshot.description {
+        println!("  Description: {}", description);
+    }
+    println!("  Created:     {}", snapshot.created_at);
+    match &snapshot.completed_at {
+        Some(at) => println!("  Completed:   {}", at),
+        None => println!("  Completed:   {}", "no (cannot be restored)".yellow()),
+    }
+    println!("  Versioning:  {}", snapshot.versioning);
+    println!("  Versions:    {}", snapshot.versions);
+    println!("  Size:        {}", format_size(snapshot.total_bytes, true));
+}
@@ -0,0 +1,788 @@
+//! bucket command - manage bucket configuration
+//!
+//! Supports policy, CORS, lifecycle, versioning and tagging. Documents are
+//! read from and written to JSON (AWS CLI shape) or S3 XML files.
+
+use super::CommandContext;
+use crate::s3_client::{create_client, S3Uri};
+use crate::{BucketAction, BucketConfigAction, DocumentFormat};
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use colored::Colorize;
+use hafiz_client::{BucketConfig, Client};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::Read;
+
+pub async fn execute(ctx: &CommandContext, action: BucketAction) -> Result<()> {
+    let client = create_client(&ctx.config)?;
+
+    match action {
+        BucketAction::Policy { action } => policy(ctx, &client, action).await,
+        BucketAction::Cors { action } => cors(ctx, &client, action).await,
+        BucketAction::Lifecycle { action } => lifecycle(ctx, &client, action).await,
+        BucketAction::Versioning { action } => versioning(ctx, &client, action).await,
+        BucketAction::Tagging { action } => tagging(ctx, &client, action).await,
+    }
+}
+
+// ============= Policy =============
+
+async fn policy(ctx: &CommandContext, client: &Client, action: BucketConfigAction) -> Result<()> {
+    match action {
+        BucketConfigAction::Get { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            if format == Some(DocumentFormat::Xml) {
+                anyhow::bail!("Bucket policies are JSON documents; XML output is not supported");
+            }
+
+            ctx.debug(&format!("Getting policy for bucket: {}", bucket));
+            let policy = client
+                .get_bucket_config(&bucket, BucketConfig::Policy)
+                .await
+                .context("Failed to get bucket policy")?;
+
+            // Re-indent so the document is diff-friendly; fall back to the raw text
+            let pretty = serde_json::from_str::<serde_json::Value>(&policy)
+                .ok()
+                .and_then(|v| serde_json::to_string_pretty(&v).ok())
+                .unwrap_or(policy);
+
+            write_document(ctx, &pretty, file.as_deref())
+        }
+        BucketConfigAction::Set { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            let (content, format) = read_document(&file, format)?;
+            if format == DocumentFormat::Xml {
+                anyhow::bail!("Bucket policies must be JSON documents");
+            }
+            serde_json::from_str::<serde_json::Value>(&content)
+                .context("Policy file is not valid JSON")?;
+
+            ctx.debug(&format!("Setting policy for bucket: {}", bucket));
+            client
+                .put_bucket_config(&bucket, BucketConfig::Policy, content)
+                .await
+                .context("Failed to set bucket policy")?;
+
+            report(ctx, "put_policy", &bucket);
+            Ok(())
+        }
+        BucketConfigAction::Delete { bucket } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            client
+                .delete_bucket_config(&bucket, BucketConfig::Policy)
+                .await
+                .context("Failed to delete bucket policy")?;
+
+            report(ctx, "delete_policy", &bucket);
+            Ok(())
+        }
+    }
+}
+
+// ============= CORS =============
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CorsDocument {
+    #[serde(rename = "CORSRules", alias = "CORSRule", default)]
+    cors_rules: Vec<CorsRuleDoc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CorsRuleDoc {
+    #[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(alias = "AllowedHeader", default, skip_serializing_if = "Vec::is_empty")]
+    allowed_headers: Vec<String>,
+    #[serde(alias = "AllowedMethod", default)]
+    allowed_methods: Vec<String>,
+    #[serde(alias = "AllowedOrigin", default)]
+    allowed_origins: Vec<String>,
+    #[serde(alias = "ExposeHeader", default, skip_serializing_if = "Vec::is_empty")]
+    expose_headers: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_age_seconds: Option<i32>,
+}
+
+impl CorsDocument {
+    fn to_xml(&self) -> String {
+        let mut xml = String::from("<CORSConfiguration>\n");
+        for rule in &self.cors_rules {
+            xml.push_str("  <CORSRule>\n");
+            push_opt(&mut xml, 4, "ID", rule.id.as_deref());
+            push_all(&mut xml, 4, "AllowedHeader", &rule.allowed_headers);
+            push_all(&mut xml, 4, "AllowedMethod", &rule.allowed_methods);
+            push_all(&mut xml, 4, "AllowedOrigin", &rule.allowed_origins);
+            push_all(&mut xml, 4, "ExposeHeader", &rule.expose_headers);
+            push_opt(&mut xml, 4, "MaxAgeSeconds", rule.max_age_seconds.map(|v| v.to_string()).as_deref());
+            xml.push_str("  </CORSRule>\n");
+        }
+        xml.push_str("</CORSConfiguration>");
+        xml
+    }
+}
+
+async fn cors(ctx: &CommandContext, client: &Client, action: BucketConfigAction) -> Result<()> {
+    match action {
+        BucketConfigAction::Get { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            ctx.debug(&format!("Getting CORS configuration for bucket: {}", bucket));
+
+            let xml = client
+                .get_bucket_config(&bucket, BucketConfig::Cors)
+                .await
+                .context("Failed to get bucket CORS configuration")?;
+
+            let doc: CorsDocument =
+                quick_xml::de::from_str(&xml).context("Failed to parse bucket CORS configuration")?;
+            let content = match output_format(format, file.as_deref()) {
+                DocumentFormat::Json => serde_json::to_string_pretty(&doc)?,
+                DocumentFormat::Xml => doc.to_xml(),
+            };
+            write_document(ctx, &content, file.as_deref())
+        }
+        BucketConfigAction::Set { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            let doc: CorsDocument = parse_document(&file, format)?;
+            if doc.cors_rules.is_empty() {
+                anyhow::bail!("CORS configuration must contain at least one rule");
+            }
+
+            ctx.debug(&format!("Setting CORS configuration for bucket: {}", bucket));
+            client
+                .put_bucket_config(&bucket, BucketConfig::Cors, doc.to_xml())
+                .await
+                .context("Failed to set bucket CORS configuration")?;
+
+            report(ctx, "put_cors", &bucket);
+            Ok(())
+        }
+        BucketConfigAction::Delete { bucket } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            client
+                .delete_bucket_config(&bucket, BucketConfig::Cors)
+                .await
+                .context("Failed to delete bucket CORS configuration")?;
+
+            report(ctx, "delete_cors", &bucket);
+            Ok(())
+        }
+    }
+}
+
+// ============= Lifecycle =============
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LifecycleDocument {
+    #[serde(alias = "Rule", default)]
+    rules: Vec<LifecycleRuleDoc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LifecycleRuleDoc {
+    #[serde(rename = "ID", skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    filter: Option<LifecycleFilterDoc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expiration: Option<ExpirationDoc>,
+    #[serde(alias = "Transition", default, skip_serializing_if = "Vec::is_empty")]
+    transitions: Vec<TransitionDoc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    noncurrent_version_expiration: Option<NoncurrentExpirationDoc>,
+    #[serde(alias = "NoncurrentVersionTransition", default, skip_serializing_if = "Vec::is_empty")]
+    noncurrent_version_transitions: Vec<TransitionDoc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    abort_incomplete_multipart_upload: Option<AbortMultipartDoc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LifecycleFilterDoc {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tag: Option<TagDoc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object_size_greater_than: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object_size_less_than: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    and: Option<LifecycleAndDoc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct LifecycleAndDoc {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(alias = "Tag", default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<TagDoc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object_size_greater_than: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    object_size_less_than: Option<i64>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ExpirationDoc {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    days: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expired_object_delete_marker: Option<bool>,
+}
+
+/// Used for both Transition and NoncurrentVersionTransition
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TransitionDoc {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    days: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    noncurrent_days: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    storage_class: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct NoncurrentExpirationDoc {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    noncurrent_days: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    newer_noncurrent_versions: Option<i32>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AbortMultipartDoc {
+    days_after_initiation: Option<i32>,
+}
+
+impl LifecycleDocument {
+    /// Check what the server would otherwise reject with a less helpful error
+    fn validate(&self) -> Result<()> {
+        for rule in &self.rules {
+            if rule.status != "Enabled" && rule.status != "Disabled" {
+                anyhow::bail!(
+                    "Invalid lifecycle rule status '{}': expected Enabled or Disabled",
+                    rule.status
+                );
+            }
+
+            let dates = rule
+                .expiration
+                .iter()
+                .filter_map(|e| e.date.as_deref())
+                .chain(rule.transitions.iter().filter_map(|t| t.date.as_deref()));
+            for date in dates {
+                DateTime::parse_from_rfc3339(date).with_context(|| {
+                    format!("Invalid date '{}': expected ISO 8601 (e.g. 2030-01-01T00:00:00Z)", date)
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn to_xml(&self) -> String {
+        let mut xml = String::from("<LifecycleConfiguration>\n");
+        for rule in &self.rules {
+            xml.push_str("  <Rule>\n");
+            push_opt(&mut xml, 4, "ID", rule.id.as_deref());
+            push_opt(&mut xml, 4, "Prefix", rule.prefix.as_deref());
+
+            if let Some(f) = &rule.filter {
+                xml.push_str("    <Filter>\n");
+                push_opt(&mut xml, 6, "Prefix", f.prefix.as_deref());
+                if let Some(tag) = &f.tag {
+                    tag.push_xml(&mut xml, 6);
+                }
+                push_opt(&mut xml, 6, "ObjectSizeGreaterThan", f.object_size_greater_than.map(|v| v.to_string()).as_deref());
+                push_opt(&mut xml, 6, "ObjectSizeLessThan", f.object_size_less_than.map(|v| v.to_string()).as_deref());
+                if let Some(a) = &f.and {
+                    xml.push_str("      <And>\n");
+                    push_opt(&mut xml, 8, "Prefix", a.prefix.as_deref());
+                    for tag in &a.tags {
+                        tag.push_xml(&mut xml, 8);
+                    }
+                    push_opt(&mut xml, 8, "ObjectSizeGreaterThan", a.object_size_greater_than.map(|v| v.to_string()).as_deref());
+                    push_opt(&mut xml, 8, "ObjectSizeLessThan", a.object_size_less_than.map(|v| v.to_string()).as_deref());
+                    xml.push_str("      </And>\n");
+                }
+                xml.push_str("    </Filter>\n");
+            }
+
+            push_opt(&mut xml, 4, "Status", Some(&rule.status));
+
+            if let Some(e) = &rule.expiration {
+                xml.push_str("    <Expiration>\n");
+                push_opt(&mut xml, 6, "Date", e.date.as_deref());
+                push_opt(&mut xml, 6, "Days", e.days.map(|v| v.to_string()).as_deref());
+                push_opt(&mut xml, 6, "ExpiredObjectDeleteMarker", e.expired_object_delete_marker.map(|v| v.to_string()).as_deref());
+                xml.push_str("    </Expiration>\n");
+            }
+
+            for t in &rule.transitions {
+                xml.push_str("    <Transition>\n");
+                push_opt(&mut xml, 6, "Date", t.date.as_deref());
+                push_opt(&mut xml, 6, "Days", t.days.map(|v| v.to_string()).as_deref());
+                push_opt(&mut xml, 6, "StorageClass", t.storage_class.as_deref());
+                xml.push_str("    </Transition>\n");
+            }
+
+            if let Some(n) = &rule.noncurrent_version_expiration {
+                xml.push_str("    <NoncurrentVersionExpiration>\n");
+                push_opt(&mut xml, 6, "NoncurrentDays", n.noncurrent_days.map(|v| v.to_string()).as_deref());
+                push_opt(&mut xml, 6, "NewerNoncurrentVersions", n.newer_noncurrent_versions.map(|v| v.to_string()).as_deref());
+                xml.push_str("    </NoncurrentVersionExpiration>\n");
+            }
+
+            for t in &rule.noncurrent_version_transitions {
+                xml.push_str("    <NoncurrentVersionTransition>\n");
+                push_opt(&mut xml, 6, "NoncurrentDays", t.noncurrent_days.map(|v| v.to_string()).as_deref());
+                push_opt(&mut xml, 6, "StorageClass", t.storage_class.as_deref());
+                xml.push_str("    </NoncurrentVersionTransition>\n");
+            }
+
+            if let Some(a) = &rule.abort_incomplete_multipart_upload {
+                xml.push_str("    <AbortIncompleteMultipartUpload>\n");
+                push_opt(&mut xml, 6, "DaysAfterInitiation", a.days_after_initiation.map(|v| v.to_string()).as_deref());
+                xml.push_str("    </AbortIncompleteMultipartUpload>\n");
+            }
+
+            xml.push_str("  </Rule>\n");
+        }
+        xml.push_str("</LifecycleConfiguration>");
+        xml
+    }
+}
+
+async fn lifecycle(ctx: &CommandContext, client: &Client, action: BucketConfigAction) -> Result<()> {
+    match action {
+        BucketConfigAction::Get { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            ctx.debug(&format!("Getting lifecycle configuration for bucket: {}", bucket));
+
+            let xml = client
+                .get_bucket_config(&bucket, BucketConfig::Lifecycle)
+                .await
+                .context("Failed to get bucket lifecycle configuration")?;
+
+            let doc: LifecycleDocument = quick_xml::de::from_str(&xml)
+                .context("Failed to parse bucket lifecycle configuration")?;
+            let content = match output_format(format, file.as_deref()) {
+                DocumentFormat::Json => serde_json::to_string_pretty(&doc)?,
+                DocumentFormat::Xml => doc.to_xml(),
+            };
+            write_document(ctx, &content, file.as_deref())
+        }
+        BucketConfigAction::Set { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            let doc: LifecycleDocument = parse_document(&file, format)?;
+            if doc.rules.is_empty() {
+                anyhow::bail!("Lifecycle configuration must contain at least one rule");
+            }
+
+            doc.validate()?;
+
+            ctx.debug(&format!("Setting lifecycle configuration for bucket: {}", bucket));
+            client
+                .put_bucket_config(&bucket, BucketConfig::Lifecycle, doc.to_xml())
+                .await
+                .context("Failed to set bucket lifecycle configuration")?;
+
+            report(ctx, "put_lifecycle", &bucket);
+            Ok(())
+        }
+        BucketConfigAction::Delete { bucket } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            client
+                .delete_bucket_config(&bucket, BucketConfig::Lifecycle)
+                .await
+                .context("Failed to delete bucket lifecycle configuration")?;
+
+            report(ctx, "delete_lifecycle", &bucket);
+            Ok(())
+        }
+    }
+}
+
+// ============= Versioning =============
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct VersioningDocument {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(rename = "MFADelete", alias = "MfaDelete", skip_serializing_if = "Option::is_none")]
+    mfa_delete: Option<String>,
+}
+
+impl VersioningDocument {
+    fn to_xml(&self) -> String {
+        let mut xml = String::from("<VersioningConfiguration>\n");
+        push_opt(&mut xml, 2, "Status", self.status.as_deref());
+        push_opt(&mut xml, 2, "MfaDelete", self.mfa_delete.as_deref());
+        xml.push_str("</VersioningConfiguration>");
+        xml
+    }
+}
+
+async fn versioning(ctx: &CommandContext, client: &Client, action: BucketConfigAction) -> Result<()> {
+    match action {
+        BucketConfigAction::Get { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            ctx.debug(&format!("Getting versioning status for bucket: {}", bucket));
+
+            let resp = client
+                .bucket_versioning(&bucket)
+                .await
+                .context("Failed to get bucket versioning")?;
+
+            let doc = VersioningDocument {
+                status: resp.status,
+                mfa_delete: resp.mfa_delete,
+            };
+            let content = match output_format(format, file.as_deref()) {
+                DocumentFormat::Json => serde_json::to_string_pretty(&doc)?,
+                DocumentFormat::Xml => doc.to_xml(),
+            };
+            write_document(ctx, &content, file.as_deref())
+        }
+        BucketConfigAction::Set { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            let doc: VersioningDocument = parse_document(&file, format)?;
+
+            let status = match doc.status.as_deref() {
+                Some("Enabled") | Some("Suspended") => doc.status.as_deref().unwrap(),
+                Some(other) => anyhow::bail!("Invalid versioning status '{}': expected Enabled or Suspended", other),
+                None => anyhow::bail!("Versioning document must specify Status"),
+            };
+
+            ctx.debug(&format!("Setting versioning to {} for bucket: {}", status, bucket));
+            client
+                .put_bucket_config(&bucket, BucketConfig::Versioning, doc.to_xml())
+                .await
+                .context("Failed to set bucket versioning")?;
+
+            report(ctx, "put_versioning", &bucket);
+            Ok(())
+        }
+        BucketConfigAction::Delete { .. } => {
+            anyhow::bail!(
+                "Versioning cannot be removed from a bucket; use 'hafiz bucket versioning set' with Status \"Suspended\""
+            )
+        }
+    }
+}
+
+// ============= Tagging =============
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TaggingDocument {
+    #[serde(default)]
+    tag_set: Vec<TagDoc>,
+}
+
+/// XML shape of a tagging document (`<Tagging><TagSet><Tag>...`)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TaggingXml {
+    #[serde(default)]
+    tag_set: TagSetXml,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct TagSetXml {
+    #[serde(rename = "Tag", default)]
+    tags: Vec<TagDoc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct TagDoc {
+    key: String,
+    value: String,
+}
+
+impl TagDoc {
+    fn push_xml(&self, xml: &mut String, indent: usize) {
+        let pad = " ".repeat(indent);
+        xml.push_str(&format!("{}<Tag>\n", pad));
+        push_opt(xml, indent + 2, "Key", Some(&self.key));
+        push_opt(xml, indent + 2, "Value", Some(&self.value));
+        xml.push_str(&format!("{}</Tag>\n", pad));
+    }
+}
+
+impl TaggingDocument {
+    fn to_xml(&self) -> String {
+        let mut xml = String::from("<Tagging>\n  <TagSet>\n");
+        for tag in &self.tag_set {
+            tag.push_xml(&mut xml, 4);
+        }
+        xml.push_str("  </TagSet>\n</Tagging>");
+        xml
+    }
+}
+
+async fn tagging(ctx: &CommandContext, client: &Client, action: BucketConfigAction) -> Result<()> {
+    match action {
+        BucketConfigAction::Get { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            ctx.debug(&format!("Getting tags for bucket: {}", bucket));
+
+            let xml = client
+                .get_bucket_config(&bucket, BucketConfig::Tagging)
+                .await
+                .context("Failed to get bucket tagging")?;
+
+            let doc = TaggingDocument {
+                tag_set: quick_xml::de::from_str::<TaggingXml>(&xml)
+                    .context("Failed to parse bucket tagging")?
+                    .tag_set
+                    .tags,
+            };
+            let content = match output_format(format, file.as_deref()) {
+                DocumentFormat::Json => serde_json::to_string_pretty(&doc)?,
+                DocumentFormat::Xml => doc.to_xml(),
+            };
+            write_document(ctx, &content, file.as_deref())
+        }
+        BucketConfigAction::Set { bucket, file, format } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            let (content, format) = read_document(&file, format)?;
+            let doc = match format {
+                DocumentFormat::Json => serde_json::from_str::<TaggingDocument>(&content)
+                    .context("Failed to parse JSON tagging document")?,
+                DocumentFormat::Xml => TaggingDocument {
+                    tag_set: quick_xml::de::from_str::<TaggingXml>(&content)
+                        .context("Failed to parse XML tagging document")?
+                        .tag_set
+                        .tags,
+                },
+            };
+
+            ctx.debug(&format!("Setting {} tag(s) for bucket: {}", doc.tag_set.len(), bucket));
+            client
+                .put_bucket_config(&bucket, BucketConfig::Tagging, doc.to_xml())
+                .await
+                .context("Failed to set bucket tagging")?;
+
+            report(ctx, "put_tagging", &bucket);
+            Ok(())
+        }
+        BucketConfigAction::Delete { bucket } => {
+            let bucket = parse_bucket_name(&bucket)?;
+            client
+                .delete_bucket_config(&bucket, BucketConfig::Tagging)
+                .await
+                .context("Failed to delete bucket tagging")?;
+
+            report(ctx, "delete_tagging", &bucket);
+            Ok(())
+        }
+    }
+}
+
+// ============= Helpers =============
+
+/// Accept either `s3://bucket` or a bare bucket name
+fn parse_bucket_name(bucket: &str) -> Result<String> {
+    let name = if bucket.starts_with("s3://") {
+        S3Uri::parse(bucket)?.bucket
+    } else {
+        bucket.trim_end_matches('/').to_string()
+    };
+
+    if name.is_empty() {
+        anyhow::bail!("Bucket name cannot be empty");
+    }
+
+    Ok(name)
+}
+
+/// Pick the document format: explicit flag, then file extension, then content sniffing
+fn detect_format(explicit: Option<DocumentFormat>, path: Option<&str>, content: Option<&str>) -> DocumentFormat {
+    if let Some(format) = explicit {
+        return format;
+    }
+
+    if let Some(path) = path {
+        if path.to_lowercase().ends_with(".xml") {
+            return DocumentFormat::Xml;
+        }
+        if path.to_lowercase().ends_with(".json") {
+            return DocumentFormat::Json;
+        }
+    }
+
+    match content {
+        Some(c) if c.trim_start().starts_with('<') => DocumentFormat::Xml,
+        _ => DocumentFormat::Json,
+    }
+}
+
+fn output_format(explicit: Option<DocumentFormat>, path: Option<&str>) -> DocumentFormat {
+    detect_format(explicit, path, None)
+}
+
+/// Read a document from a file, or stdin when the path is `-`
+fn read_document(path: &str, format: Option<DocumentFormat>) -> Result<(String, DocumentFormat)> {
+    let content = if path == "-" {
+        let mut buf = String::new();
+        std::io::stdin()
+            .read_to_string(&mut buf)
+            .context("Failed to read document from stdin")?;
+        buf
+    } else {
+        fs::read_to_string(path).with_context(|| format!("Failed to read file: {}", path))?
+    };
+
+    let format = detect_format(format, Some(path), Some(&content));
+    Ok((content, format))
+}
+
+fn parse_document<T: serde::de::DeserializeOwned>(path: &str, format: Option<DocumentFormat>) -> Result<T> {
+    let (content, format) = read_document(path, format)?;
+    match format {
+        DocumentFormat::Json => serde_json::from_str(&content).context("Failed to parse JSON document"),
+        DocumentFormat::Xml => quick_xml::de::from_str(&content).context("Failed to parse XML document"),
+    }
+}
+
+/// Write a document to a file, or stdout when no file is given
+fn write_document(ctx: &CommandContext, content: &str, file: Option<&str>) -> Result<()> {
+    match file {
+        Some(path) if path != "-" => {
+            fs::write(path, format!("{}\n", content))
+                .with_context(|| format!("Failed to write file: {}", path))?;
+            ctx.info(&format!("Wrote {}", path));
+        }
+        _ => println!("{}", content),
+    }
+    Ok(())
+}
+
+fn report(ctx: &CommandContext, operation: &str, bucket: &str) {
+    if !ctx.quiet {
+        println!("{}: s3://{}", operation.green(), bucket);
+    }
+}
+
+fn push_opt(xml: &mut String, indent: usize, tag: &str, value: Option<&str>) {
+    if let Some(v) = value {
+        xml.push_str(&format!("{}<{}>{}</{}>\n", " ".repeat(indent), tag, xml_escape(v), tag));
+    }
+}
+
+fn push_all(xml: &mut String, indent: usize, tag: &str, values: &[String]) {
+    for v in values {
+        push_opt(xml, indent, tag, Some(v));
+    }
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bucket_name() {
+        assert_eq!(parse_bucket_name("s3://mybucket").unwrap(), "mybucket");
+        assert_eq!(parse_bucket_name("s3://mybucket/").unwrap(), "mybucket");
+        assert_eq!(parse_bucket_name("mybucket").unwrap(), "mybucket");
+        assert!(parse_bucket_name("s3://").is_err());
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(detect_format(None, Some("cors.xml"), None), DocumentFormat::Xml);
+        assert_eq!(detect_format(None, Some("cors.json"), Some("<x/>")), DocumentFormat::Json);
+        assert_eq!(detect_format(None, Some("-"), Some("  <CORSConfiguration/>")), DocumentFormat::Xml);
+        assert_eq!(detect_format(Some(DocumentFormat::Json), Some("a.xml"), None), DocumentFormat::Json);
+        assert_eq!(detect_format(None, None, None), DocumentFormat::Json);
+    }
+
+    #[test]
+    fn test_cors_document_json_and_xml() {
+        let json = r#"{"CORSRules":[{"AllowedMethods":["GET","PUT"],"AllowedOrigins":["*"],"MaxAgeSeconds":3000}]}"#;
+        let doc: CorsDocument = serde_json::from_str(json).unwrap();
+        assert_eq!(doc.cors_rules.len(), 1);
+        assert_eq!(doc.cors_rules[0].allowed_methods, vec!["GET", "PUT"]);
+
+        let xml = doc.to_xml();
+        let parsed: CorsDocument = quick_xml::de::from_str(&xml).unwrap();
+        assert_eq!(parsed.cors_rules.len(), 1);
+        assert_eq!(parsed.cors_rules[0].allowed_methods, vec!["GET", "PUT"]);
+        assert_eq!(parsed.cors_rules[0].allowed_origins, vec!["*"]);
+        assert_eq!(parsed.cors_rules[0].max_age_seconds, Some(3000));
+    }
+
+    #[test]
+    fn test_lifecycle_document_xml_roundtrip() {
+        let xml = r#"<LifecycleConfiguration>
+            <Rule>
+                <ID>expire-logs</ID>
+                <Filter><Prefix>logs/</Prefix></Filter>
+                <Status>Enabled</Status>
+                <Expiration><Days>30</Days></Expiration>
+                <Transition><Days>7</Days><StorageClass>GLACIER</StorageClass></Transition>
+            </Rule>
+        </LifecycleConfiguration>"#;
+
+        let doc: LifecycleDocument = quick_xml::de::from_str(xml).unwrap();
+        assert_eq!(doc.rules.len(), 1);
+        let rule = &doc.rules[0];
+        assert_eq!(rule.id.as_deref(), Some("expire-logs"));
+        assert_eq!(rule.filter.as_ref().unwrap().prefix.as_deref(), Some("logs/"));
+        assert_eq!(rule.expiration.as_ref().unwrap().days, Some(30));
+        assert_eq!(rule.transitions.len(), 1);
+
+        let reparsed: LifecycleDocument = quick_xml::de::from_str(&doc.to_xml()).unwrap();
+        assert_eq!(reparsed.rules[0].status, "Enabled");
+        assert!(doc.validate().is_ok());
+
+        let mut bad = doc;
+        bad.rules[0].expiration.as_mut().unwrap().date = Some("next tuesday".into());
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_tagging_document_xml() {
+        let doc = TaggingDocument {
+            tag_set: vec![TagDoc {
+                key: "team".into(),
+                value: "storage & infra".into(),
+            }],
+        };
+
+        let xml = doc.to_xml();
+        assert!(xml.contains("storage &amp; infra"));
+
+        let parsed: TaggingXml = quick_xml::de::from_str(&xml).unwrap();
+        assert_eq!(parsed.tag_set.tags.len(), 1);
+        assert_eq!(parsed.tag_set.tags[0].value, "storage & infra");
+    }
+}
@@ -6,17 +6,14 @@ use anyhow::{Context, Result};
 use tokio::io::{stdout, AsyncReadExt, AsyncWriteExt};
 
 pub async fn execute(ctx: &CommandContext, path: &str) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let uri = S3Uri::parse(path)?;
     let key = uri.key.as_ref().context("Object key required")?;
 
     ctx.debug(&format!("Streaming s3://{}/{}", uri.bucket, key));
 
     let resp = client
-        .get_object()
-        .bucket(&uri.bucket)
-        .key(key)
-        .send()
+        .get_object(&uri.bucket, key)
         .await
         .context("Failed to get object")?;
 
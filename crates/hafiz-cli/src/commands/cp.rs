@@ -2,11 +2,11 @@
 
 use super::CommandContext;
 use crate::progress::{create_spinner, create_transfer_progress, format_bytes};
-use crate::s3_client::{create_client, is_s3_uri, S3Uri, TransferDirection};
+use crate::s3_client::{create_client, is_s3_uri, multipart_config, S3Uri, TransferDirection};
 use crate::utils::{determine_dest_key, guess_content_type, matches_patterns};
 use anyhow::{Context, Result};
-use aws_sdk_s3::primitives::ByteStream;
 use colored::Colorize;
+use hafiz_client::{Client, PutObjectOptions};
 use std::path::Path;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
@@ -47,7 +47,7 @@ async fn upload(
     destination: &str,
     opts: &CpOptions,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let dest_uri = S3Uri::parse(destination)?;
     let source_path = Path::new(source);
 
@@ -73,7 +73,7 @@ async fn upload(
 
 async fn upload_file(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     source: &Path,
     dest_uri: &S3Uri,
     opts: &CpOptions,
@@ -120,28 +120,22 @@ async fn upload_file(
         .clone()
         .unwrap_or_else(|| guess_content_type(source.to_str().unwrap_or("")));
 
-    let body = ByteStream::from_path(source)
-        .await
-        .context("Failed to read file")?;
-
     let progress = if opts.show_progress {
         Some(create_transfer_progress(file_size, filename))
     } else {
         None
     };
 
-    let mut req = client
-        .put_object()
-        .bucket(&dest_uri.bucket)
-        .key(&dest_key)
-        .content_type(content_type)
-        .body(body);
-
-    if let Some(storage_class) = &opts.storage_class {
-        req = req.storage_class(storage_class.as_str().into());
-    }
-
-    req.send().await.context("Upload failed")?;
+    client
+        .upload_file(
+            &dest_uri.bucket,
+            &dest_key,
+            source,
+            &put_options(opts, content_type),
+            &multipart_config(&ctx.config),
+        )
+        .await
+        .context("Upload failed")?;
 
     if let Some(pb) = progress {
         pb.finish_with_message("Done");
@@ -162,7 +156,7 @@ async fn upload_file(
 
 async fn upload_directory(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     source: &Path,
     dest_uri: &S3Uri,
     opts: &CpOptions,
@@ -230,20 +224,15 @@ async fn upload_directory(
             .clone()
             .unwrap_or_else(|| guess_content_type(path.to_str().unwrap_or("")));
 
-        let body = ByteStream::from_path(&path).await?;
-
-        let mut req = client
-            .put_object()
-            .bucket(&dest_uri.bucket)
-            .key(&dest_key)
-            .content_type(content_type)
-            .body(body);
-
-        if let Some(storage_class) = &opts.storage_class {
-            req = req.storage_class(storage_class.as_str().into());
-        }
-
-        req.send().await?;
+        client
+            .upload_file(
+                &dest_uri.bucket,
+                &dest_key,
+                &path,
+                &put_options(opts, content_type),
+                &multipart_config(&ctx.config),
+            )
+            .await?;
 
         uploaded += 1;
         total_bytes += file_size;
@@ -278,7 +267,7 @@ async fn download(
     destination: &str,
     opts: &CpOptions,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let source_uri = S3Uri::parse(source)?;
     let dest_path = Path::new(destination);
 
@@ -293,7 +282,7 @@ async fn download(
 
 async fn download_object(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     source_uri: &S3Uri,
     dest_path: &Path,
     opts: &CpOptions,
@@ -331,14 +320,11 @@ async fn download_object(
     }
 
     let resp = client
-        .get_object()
-        .bucket(&source_uri.bucket)
-        .key(key)
-        .send()
+        .get_object(&source_uri.bucket, key)
         .await
         .context("Download failed")?;
 
-    let content_length = resp.content_length().unwrap_or(0) as u64;
+    let content_length = resp.meta.content_length.unwrap_or(0) as u64;
 
     let progress = if opts.show_progress {
         Some(create_transfer_progress(
@@ -387,7 +373,7 @@ async fn download_object(
 
 async fn download_prefix(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     source_uri: &S3Uri,
     dest_path: &Path,
     opts: &CpOptions,
@@ -402,36 +388,18 @@ async fn download_prefix(
 
     // List all objects
     let mut objects: Vec<(String, i64)> = Vec::new();
-    let mut continuation_token: Option<String> = None;
-
-    loop {
-        let mut req = client
-            .list_objects_v2()
-            .bucket(&source_uri.bucket)
-            .prefix(&prefix);
-
-        if let Some(token) = &continuation_token {
-            req = req.continuation_token(token);
-        }
+    let mut pages = client
+        .list_objects(&source_uri.bucket)
+        .prefix(prefix.as_str())
+        .into_paginator();
 
-        let resp = req.send().await?;
-
-        if let Some(contents) = resp.contents {
-            for obj in contents {
-                if let (Some(key), Some(size)) = (obj.key(), obj.size()) {
-                    // Check patterns
-                    if matches_patterns(key, opts.include.as_deref(), opts.exclude.as_deref())? {
-                        objects.push((key.to_string(), size));
-                    }
-                }
+    while let Some(page) = pages.next_page().await? {
+        for obj in page.objects {
+            // Check patterns
+            if matches_patterns(&obj.key, opts.include.as_deref(), opts.exclude.as_deref())? {
+                objects.push((obj.key, obj.size));
             }
         }
-
-        if resp.is_truncated.unwrap_or(false) {
-            continuation_token = resp.next_continuation_token;
-        } else {
-            break;
-        }
     }
 
     if let Some(s) = spinner {
@@ -464,12 +432,7 @@ async fn download_prefix(
             fs::create_dir_all(parent).await?;
         }
 
-        let resp = client
-            .get_object()
-            .bucket(&source_uri.bucket)
-            .key(&key)
-            .send()
-            .await?;
+        let resp = client.get_object(&source_uri.bucket, &key).await?;
 
         let mut file = fs::File::create(&final_path).await?;
         let mut stream = resp.body.into_async_read();
@@ -517,7 +480,7 @@ async fn s3_copy(
     destination: &str,
     opts: &CpOptions,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let source_uri = S3Uri::parse(source)?;
     let dest_uri = S3Uri::parse(destination)?;
 
@@ -541,19 +504,15 @@ async fn s3_copy(
         source_uri.bucket, source_key, dest_uri.bucket, dest_key
     ));
 
-    let copy_source = format!("{}/{}", source_uri.bucket, source_key);
-
-    let mut req = client
-        .copy_object()
-        .bucket(&dest_uri.bucket)
-        .key(&dest_key)
-        .copy_source(&copy_source);
-
-    if let Some(storage_class) = &opts.storage_class {
-        req = req.storage_class(storage_class.as_str().into());
-    }
+    let options = PutObjectOptions {
+        storage_class: opts.storage_class.clone(),
+        ..Default::default()
+    };
 
-    req.send().await.context("Copy failed")?;
+    client
+        .copy_object(&source_uri.bucket, source_key, &dest_uri.bucket, &dest_key, &options)
+        .await
+        .context("Copy failed")?;
 
     if !ctx.quiet {
         println!(
@@ -569,6 +528,14 @@ async fn s3_copy(
     Ok(())
 }
 
+fn put_options(opts: &CpOptions, content_type: String) -> PutObjectOptions {
+    PutObjectOptions {
+        content_type: Some(content_type),
+        storage_class: opts.storage_class.clone(),
+        ..Default::default()
+    }
+}
+
 fn destination_is_directory(path: &Path) -> bool {
     // Check if path ends with / or is an existing directory
     path.to_str()
@@ -1,8 +1,9 @@
 //! du command - calculate disk usage
 
 use super::CommandContext;
+use crate::listing::list_objects_parallel;
 use crate::s3_client::{create_client, S3Uri};
-use crate::utils::format_size;
+use crate::utils::{format_size, join_key};
 use anyhow::Result;
 use colored::Colorize;
 use serde::Serialize;
@@ -29,71 +30,47 @@ pub async fn execute(
     path: &str,
     human_readable: bool,
     summarize: bool,
+    max_concurrency: Option<usize>,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let uri = S3Uri::parse(path)?;
     let prefix = uri.key.clone().unwrap_or_default();
+    let max_concurrency = max_concurrency.unwrap_or(ctx.config.max_concurrent_requests);
 
     ctx.debug(&format!(
-        "Calculating disk usage for s3://{}/{}",
-        uri.bucket, prefix
+        "Calculating disk usage for s3://{}/{} ({} concurrent listings)",
+        uri.bucket, prefix, max_concurrency
     ));
 
     // Track size by prefix (first level)
     let mut prefix_sizes: HashMap<String, (i64, usize)> = HashMap::new();
     let mut total_size: i64 = 0;
     let mut total_count: usize = 0;
-    let mut continuation_token: Option<String> = None;
+    let mut objects =
+        list_objects_parallel(&client, &uri.bucket, &prefix, max_concurrency);
 
-    loop {
-        let mut req = client
-            .list_objects_v2()
-            .bucket(&uri.bucket)
-            .prefix(&prefix);
+    while let Some(obj) = objects.recv().await {
+        let obj = obj?;
+        let (key, size) = (obj.key.as_str(), obj.size);
+        total_size += size;
+        total_count += 1;
 
-        if let Some(token) = &continuation_token {
-            req = req.continuation_token(token);
-        }
-
-        let resp = req.send().await?;
-
-        if let Some(contents) = resp.contents {
-            for obj in contents {
-                if let (Some(key), Some(size)) = (obj.key(), obj.size()) {
-                    total_size += size;
-                    total_count += 1;
-
-                    // Get first-level prefix after the base prefix
-                    let relative = key.strip_prefix(&prefix).unwrap_or(key);
-                    let relative = relative.trim_start_matches('/');
-
-                    if !summarize {
-                        let first_part = if let Some(idx) = relative.find('/') {
-                            format!("{}/", &relative[..idx])
-                        } else {
-                            relative.to_string()
-                        };
-
-                        let full_prefix = if prefix.is_empty() {
-                            first_part
-                        } else if prefix.ends_with('/') {
-                            format!("{}{}", prefix, first_part)
-                        } else {
-                            format!("{}/{}", prefix, first_part)
-                        };
-
-                        let entry = prefix_sizes.entry(full_prefix).or_insert((0, 0));
-                        entry.0 += size;
-                        entry.1 += 1;
-                    }
-                }
-            }
-        }
+        // Get first-level prefix after the base prefix
+        let relative = key.strip_prefix(&prefix).unwrap_or(key);
+        let relative = relative.trim_start_matches('/');
 
-        if resp.is_truncated.unwrap_or(false) {
-            continuation_token = resp.next_continuation_token;
-        } else {
-            break;
+        if !summarize {
+            let first_part = if let Some(idx) = relative.find('/') {
+                format!("{}/", &relative[..idx])
+            } else {
+                relative.to_string()
+            };
+
+            let entry = prefix_sizes
+                .entry(join_key(&prefix, &first_part))
+                .or_insert((0, 0));
+            entry.0 += size;
+            entry.1 += 1;
         }
     }
 
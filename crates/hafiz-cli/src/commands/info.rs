@@ -6,6 +6,7 @@ use crate::utils::format_size;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use colored::Colorize;
+use hafiz_client::Client;
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -18,7 +19,7 @@ struct BucketInfoResult {
 }
 
 pub async fn execute(ctx: &CommandContext, path: &str) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let uri = S3Uri::parse(path)?;
 
     if uri.key.is_none() || uri.key.as_deref() == Some("") {
@@ -32,56 +33,30 @@ pub async fn execute(ctx: &CommandContext, path: &str) -> Result<()> {
 
 async fn bucket_info(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     bucket: &str,
 ) -> Result<()> {
     ctx.debug(&format!("Getting info for bucket: {}", bucket));
 
     // Get bucket location
-    let location = client
-        .get_bucket_location()
-        .bucket(bucket)
-        .send()
-        .await
-        .ok()
-        .and_then(|r| r.location_constraint().map(|l| l.as_str().to_string()));
+    let location = client.bucket_location(bucket).await.ok().flatten();
 
     // Get versioning status
     let versioning = client
-        .get_bucket_versioning()
-        .bucket(bucket)
-        .send()
+        .bucket_versioning(bucket)
         .await
         .ok()
-        .and_then(|r| r.status().map(|s| s.as_str().to_string()));
+        .and_then(|v| v.status);
 
     // Count objects and size
     let mut object_count = 0;
     let mut total_size: i64 = 0;
-    let mut continuation_token: Option<String> = None;
-
-    loop {
-        let mut req = client.list_objects_v2().bucket(bucket);
-
-        if let Some(token) = &continuation_token {
-            req = req.continuation_token(token);
-        }
+    let mut pages = client.list_objects(bucket).into_paginator();
 
-        let resp = req.send().await?;
-
-        if let Some(contents) = resp.contents {
-            for obj in contents {
-                object_count += 1;
-                if let Some(size) = obj.size() {
-                    total_size += size;
-                }
-            }
-        }
-
-        if resp.is_truncated.unwrap_or(false) {
-            continuation_token = resp.next_continuation_token;
-        } else {
-            break;
+    while let Some(page) = pages.next_page().await? {
+        for obj in page.objects {
+            object_count += 1;
+            total_size += obj.size;
         }
     }
 
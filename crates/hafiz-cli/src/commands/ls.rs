@@ -1,12 +1,12 @@
 //! ls command - list buckets or objects
 
 use super::CommandContext;
+use crate::listing::list_objects_parallel;
 use crate::s3_client::{create_client, S3Uri};
 use crate::utils::{format_datetime, format_size, format_storage_class};
 use anyhow::Result;
-use aws_sdk_s3::types::Object;
-use chrono::{DateTime, Utc};
 use colored::Colorize;
+use hafiz_client::{Client, Object};
 use serde::Serialize;
 
 #[derive(Serialize)]
@@ -24,6 +24,18 @@ struct ObjectInfo {
     etag: Option<String>,
 }
 
+impl ObjectInfo {
+    fn from_object(o: &Object) -> Self {
+        Self {
+            key: o.key.clone(),
+            size: o.size,
+            last_modified: o.last_modified.as_ref().map(format_datetime),
+            storage_class: o.storage_class.clone(),
+            etag: o.etag.clone(),
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct ListResult {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -43,13 +55,19 @@ pub async fn execute(
     human_readable: bool,
     recursive: bool,
     summarize: bool,
+    max_concurrency: Option<usize>,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let uri = S3Uri::parse(path)?;
 
     if uri.bucket.is_empty() {
         // List buckets
         list_buckets(ctx, &client, long).await
+    } else if recursive {
+        // Fan out across prefixes and stream results
+        let max_concurrency = max_concurrency.unwrap_or(ctx.config.max_concurrent_requests);
+        list_objects_recursive(ctx, &client, &uri, long, human_readable, summarize, max_concurrency)
+            .await
     } else {
         // List objects
         list_objects(ctx, &client, &uri, long, human_readable, recursive, summarize).await
@@ -58,25 +76,19 @@ pub async fn execute(
 
 async fn list_buckets(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     long: bool,
 ) -> Result<()> {
     ctx.debug("Listing buckets...");
 
-    let resp = client.list_buckets().send().await?;
-    let buckets = resp.buckets();
+    let buckets = client.list_buckets().await?;
 
     if ctx.is_json() {
         let bucket_infos: Vec<BucketInfo> = buckets
             .iter()
             .map(|b| BucketInfo {
-                name: b.name().unwrap_or("").to_string(),
-                creation_date: b.creation_date().map(|d| {
-                    let secs = d.secs();
-                    DateTime::<Utc>::from_timestamp(secs, 0)
-                        .map(|dt| format_datetime(&dt))
-                        .unwrap_or_default()
-                }),
+                name: b.name.clone(),
+                creation_date: b.creation_date.as_ref().map(format_datetime),
             })
             .collect();
 
@@ -89,15 +101,10 @@ async fn list_buckets(
         };
         println!("{}", serde_json::to_string_pretty(&result)?);
     } else {
-        for bucket in buckets {
-            let name = bucket.name().unwrap_or("");
+        for bucket in &buckets {
+            let name = bucket.name.as_str();
             if long {
-                let date = bucket.creation_date().map(|d| {
-                    let secs = d.secs();
-                    DateTime::<Utc>::from_timestamp(secs, 0)
-                        .map(|dt| format_datetime(&dt))
-                        .unwrap_or_default()
-                });
+                let date = bucket.creation_date.as_ref().map(format_datetime);
                 println!(
                     "{} {}",
                     date.unwrap_or_else(|| "                   ".to_string()),
@@ -118,7 +125,7 @@ async fn list_buckets(
 
 async fn list_objects(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     uri: &S3Uri,
     long: bool,
     human_readable: bool,
@@ -132,73 +139,20 @@ async fn list_objects(
     ));
 
     let prefix = uri.key.clone().unwrap_or_default();
-    let delimiter = if recursive { None } else { Some("/".to_string()) };
-
-    let mut continuation_token: Option<String> = None;
-    let mut all_objects: Vec<Object> = Vec::new();
-    let mut all_prefixes: Vec<String> = Vec::new();
-    let mut total_size: i64 = 0;
-
-    loop {
-        let mut req = client
-            .list_objects_v2()
-            .bucket(&uri.bucket)
-            .prefix(&prefix);
 
-        if let Some(delim) = &delimiter {
-            req = req.delimiter(delim);
-        }
-
-        if let Some(token) = &continuation_token {
-            req = req.continuation_token(token);
-        }
-
-        let resp = req.send().await?;
-
-        // Collect objects
-        if let Some(contents) = resp.contents {
-            for obj in contents {
-                if let Some(size) = obj.size {
-                    total_size += size;
-                }
-                all_objects.push(obj);
-            }
-        }
-
-        // Collect common prefixes (directories)
-        if let Some(prefixes) = resp.common_prefixes {
-            for prefix in prefixes {
-                if let Some(p) = prefix.prefix {
-                    all_prefixes.push(p);
-                }
-            }
-        }
-
-        // Check for more results
-        if resp.is_truncated.unwrap_or(false) {
-            continuation_token = resp.next_continuation_token;
-        } else {
-            break;
-        }
+    let mut req = client.list_objects(&uri.bucket).prefix(prefix);
+    if !recursive {
+        req = req.delimiter("/");
     }
 
+    let listing = req.collect().await?;
+    let all_objects = listing.objects;
+    let all_prefixes = listing.common_prefixes;
+    let total_size: i64 = all_objects.iter().map(|o| o.size).sum();
+
     // Output results
     if ctx.is_json() {
-        let object_infos: Vec<ObjectInfo> = all_objects
-            .iter()
-            .map(|o| ObjectInfo {
-                key: o.key().unwrap_or("").to_string(),
-                size: o.size().unwrap_or(0),
-                last_modified: o.last_modified().map(|d| {
-                    let secs = d.secs();
-                    DateTime::<Utc>::from_timestamp(secs, 0)
-                        .map(|dt| format_datetime(&dt))
-                        .unwrap_or_default()
-                }),
-                storage_class: o.storage_class().map(|s| s.as_str().to_string()),
-                etag: o.e_tag().map(|s| s.to_string()),
-            })
-            .collect();
+        let object_infos: Vec<ObjectInfo> = all_objects.iter().map(ObjectInfo::from_object).collect();
 
         let result = ListResult {
             buckets: None,
@@ -231,28 +185,7 @@ async fn list_objects(
 
         // Print objects
         for obj in &all_objects {
-            let key = obj.key().unwrap_or("");
-            let size = obj.size().unwrap_or(0);
-
-            if long {
-                let date = obj.last_modified().map(|d| {
-                    let secs = d.secs();
-                    DateTime::<Utc>::from_timestamp(secs, 0)
-                        .map(|dt| format_datetime(&dt))
-                        .unwrap_or_default()
-                });
-                let storage = format_storage_class(obj.storage_class().map(|s| s.as_str()));
-
-                println!(
-                    "{} {:>12}  {:8}  {}",
-                    date.unwrap_or_else(|| "                   ".to_string()),
-                    format_size(size, human_readable),
-                    storage,
-                    key
-                );
-            } else {
-                println!("{}", key);
-            }
+            print_object(obj, long, human_readable);
         }
 
         if !ctx.quiet {
@@ -266,3 +199,86 @@ async fn list_objects(
 
     Ok(())
 }
+
+async fn list_objects_recursive(
+    ctx: &CommandContext,
+    client: &Client,
+    uri: &S3Uri,
+    long: bool,
+    human_readable: bool,
+    summarize: bool,
+    max_concurrency: usize,
+) -> Result<()> {
+    ctx.debug(&format!(
+        "Listing objects in bucket '{}' with prefix '{}' ({} concurrent listings)",
+        uri.bucket,
+        uri.key_or_empty(),
+        max_concurrency
+    ));
+
+    let mut objects =
+        list_objects_parallel(client, &uri.bucket, uri.key_or_empty(), max_concurrency);
+
+    // JSON output is collected and sorted; text output streams as pages arrive
+    let mut collected: Vec<Object> = Vec::new();
+    let mut total_objects: usize = 0;
+    let mut total_size: i64 = 0;
+
+    while let Some(obj) = objects.recv().await {
+        let obj = obj?;
+        total_objects += 1;
+        total_size += obj.size;
+
+        if ctx.is_json() {
+            collected.push(obj);
+        } else if !summarize {
+            print_object(&obj, long, human_readable);
+        }
+    }
+
+    if ctx.is_json() {
+        collected.sort_by(|a, b| a.key.cmp(&b.key));
+        let result = ListResult {
+            buckets: None,
+            objects: Some(collected.iter().map(ObjectInfo::from_object).collect()),
+            prefixes: None,
+            total_objects,
+            total_size,
+        };
+        println!("{}", serde_json::to_string_pretty(&result)?);
+    } else if summarize {
+        println!(
+            "Total Objects: {}\nTotal Size: {}",
+            total_objects,
+            format_size(total_size, human_readable)
+        );
+    } else if !ctx.quiet {
+        println!(
+            "\nTotal: {} object(s), {}",
+            total_objects,
+            format_size(total_size, human_readable)
+        );
+    }
+
+    Ok(())
+}
+
+fn print_object(obj: &Object, long: bool, human_readable: bool) {
+    let key = obj.key.as_str();
+    let size = obj.size;
+
+    if long {
+        let date = obj.last_modified.as_ref().map(format_datetime);
+        let storage = format_storage_class(obj.storage_class.as_deref());
+
+        println!(
+            "{} {:>12}  {:8}  {}",
+            date.unwrap_or_else(|| "                   ".to_string()),
+            format_size(size, human_readable),
+            storage,
+            key
+        );
+    } else {
+        println!("{}", key);
+    }
+}
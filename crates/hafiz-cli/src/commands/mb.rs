@@ -3,7 +3,6 @@
 use super::CommandContext;
 use crate::s3_client::{create_client, S3Uri};
 use anyhow::{Context, Result};
-use aws_sdk_s3::types::{BucketLocationConstraint, CreateBucketConfiguration};
 use colored::Colorize;
 
 pub async fn execute(
@@ -11,7 +10,7 @@ pub async fn execute(
     bucket: &str,
     region: Option<String>,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
 
     // Parse bucket name from s3:// URI if provided
     let bucket_name = if bucket.starts_with("s3://") {
@@ -27,19 +26,14 @@ pub async fn execute(
 
     ctx.debug(&format!("Creating bucket: {}", bucket_name));
 
-    let mut req = client.create_bucket().bucket(&bucket_name);
-
     // Add region constraint if not us-east-1
     let region_str = region.as_deref().unwrap_or(&ctx.config.region);
-    if region_str != "us-east-1" {
-        let constraint = BucketLocationConstraint::from(region_str);
-        let config = CreateBucketConfiguration::builder()
-            .location_constraint(constraint)
-            .build();
-        req = req.create_bucket_configuration(config);
-    }
+    let location = Some(region_str).filter(|r| *r != "us-east-1");
 
-    req.send().await.context("Failed to create bucket")?;
+    client
+        .create_bucket(&bucket_name, location)
+        .await
+        .context("Failed to create bucket")?;
 
     if !ctx.quiet {
         println!(
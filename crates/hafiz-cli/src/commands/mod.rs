@@ -1,5 +1,7 @@
 //! CLI command implementations
 
+pub mod admin;
+pub mod bucket;
 pub mod cat;
 pub mod configure;
 pub mod cp;
@@ -8,10 +10,15 @@ pub mod head;
 pub mod info;
 pub mod ls;
 pub mod mb;
+#[cfg(feature = "mount")]
+pub mod mount;
 pub mod mv;
 pub mod presign;
 pub mod rb;
+pub mod replay;
 pub mod rm;
+pub mod search;
+pub mod server;
 pub mod sync;
 
 use crate::config::Config;
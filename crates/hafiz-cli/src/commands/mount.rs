@@ -0,0 +1,95 @@
+//! mount command - FUSE filesystem over a bucket
+
+use super::CommandContext;
+use crate::mount::{HafizFs, MountOptions};
+use crate::s3_client::{create_client, multipart_config, S3Uri};
+use anyhow::{bail, Context, Result};
+use fuser::MountOption;
+use std::path::Path;
+use std::time::Duration;
+
+pub async fn execute(
+    ctx: &CommandContext,
+    path: &str,
+    mountpoint: &str,
+    options: MountOptions,
+) -> Result<()> {
+    let client = create_client(&ctx.config)?;
+    let uri = S3Uri::parse(path)?;
+    if uri.bucket.is_empty() {
+        bail!("Bucket required, e.g. s3://bucket or s3://bucket/prefix/");
+    }
+    let mountpoint = Path::new(mountpoint);
+    if !mountpoint.is_dir() {
+        bail!("Mount point is not a directory: {}", mountpoint.display());
+    }
+    if !client
+        .bucket_exists(&uri.bucket)
+        .await
+        .context("Failed to check bucket")?
+    {
+        bail!("Bucket does not exist: {}", uri.bucket);
+    }
+
+    let prefix = match uri.key_or_empty().trim_matches('/') {
+        "" => String::new(),
+        prefix => format!("{}/", prefix),
+    };
+
+    let mut mount_options = vec![
+        MountOption::FSName(format!("hafiz:{}", uri.bucket)),
+        MountOption::Subtype("hafiz".to_string()),
+        MountOption::DefaultPermissions,
+        MountOption::NoAtime,
+    ];
+    if options.read_only {
+        mount_options.push(MountOption::RO);
+    }
+
+    ctx.debug(&format!(
+        "Mounting s3://{}/{} at {} (cache TTL {:?})",
+        uri.bucket,
+        prefix,
+        mountpoint.display(),
+        options.cache_ttl
+    ));
+
+    let fs = HafizFs::new(
+        client,
+        uri.bucket.clone(),
+        prefix,
+        tokio::runtime::Handle::current(),
+        multipart_config(&ctx.config),
+        options,
+    );
+    let session = fuser::spawn_mount2(fs, mountpoint, &mount_options)
+        .with_context(|| format!("Failed to mount on {}", mountpoint.display()))?;
+
+    ctx.info(&format!(
+        "Mounted {} at {}; press Ctrl-C or run `umount {}` to unmount",
+        path,
+        mountpoint.display(),
+        mountpoint.display()
+    ));
+
+    // Wait for Ctrl-C, or for the session to end after an external umount
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    let mut ticks = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            _ = ticks.tick() => {
+                if session.guard.is_finished() {
+                    break;
+                }
+            }
+        }
+    }
+
+    // Dropping the session unmounts, which can block on in-flight requests
+    tokio::task::spawn_blocking(move || drop(session)).await?;
+    ctx.info(&format!("Unmounted {}", mountpoint.display()));
+
+    Ok(())
+}
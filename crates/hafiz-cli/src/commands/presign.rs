@@ -3,7 +3,6 @@
 use super::CommandContext;
 use crate::s3_client::{create_client, S3Uri};
 use anyhow::{Context, Result};
-use aws_sdk_s3::presigning::PresigningConfig;
 use std::time::Duration;
 
 pub async fn execute(
@@ -12,7 +11,7 @@ pub async fn execute(
     expires: u64,
     method: &str,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let uri = S3Uri::parse(path)?;
     let key = uri.key.as_ref().context("Object key required")?;
 
@@ -21,29 +20,11 @@ pub async fn execute(
         uri.bucket, key, method, expires
     ));
 
-    let presign_config = PresigningConfig::builder()
-        .expires_in(Duration::from_secs(expires))
-        .build()?;
+    let expires_in = Duration::from_secs(expires);
 
     let url = match method.to_uppercase().as_str() {
-        "GET" => {
-            let req = client
-                .get_object()
-                .bucket(&uri.bucket)
-                .key(key)
-                .presigned(presign_config)
-                .await?;
-            req.uri().to_string()
-        }
-        "PUT" => {
-            let req = client
-                .put_object()
-                .bucket(&uri.bucket)
-                .key(key)
-                .presigned(presign_config)
-                .await?;
-            req.uri().to_string()
-        }
+        "GET" => client.presign_get(&uri.bucket, key, expires_in)?,
+        "PUT" => client.presign_put(&uri.bucket, key, expires_in)?,
         _ => anyhow::bail!("Unsupported method: {}. Use GET or PUT.", method),
     };
 
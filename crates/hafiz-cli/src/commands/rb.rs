@@ -1,15 +1,41 @@
 //! rb command - remove bucket
 
-use super::rm::{execute as rm_execute, RmOptions};
 use super::CommandContext;
+use crate::admin_client::AdminClient;
 use crate::s3_client::{create_client, S3Uri};
-use crate::utils::confirm;
+use crate::utils::{confirm, format_size};
 use anyhow::{Context, Result};
 use colored::Colorize;
+use serde::{Deserialize, Serialize};
 
-pub async fn execute(ctx: &CommandContext, bucket: &str, force: bool) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+#[derive(Debug, Serialize)]
+struct DeleteBucketQuery {
+    force: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate: Option<u32>,
+    bypass_governance: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DeleteBucketReport {
+    bucket: String,
+    deleted: bool,
+    versions_deleted: u64,
+    delete_markers_deleted: u64,
+    uploads_aborted: u64,
+    bytes_freed: i64,
+    locked_versions: u64,
+    #[serde(default)]
+    errors: Vec<String>,
+}
 
+pub async fn execute(
+    ctx: &CommandContext,
+    bucket: &str,
+    force: bool,
+    rate: Option<u32>,
+    bypass_governance: bool,
+) -> Result<()> {
     // Parse bucket name from s3:// URI if provided
     let bucket_name = if bucket.starts_with("s3://") {
         let uri = S3Uri::parse(bucket)?;
@@ -22,7 +48,7 @@ pub async fn execute(ctx: &CommandContext, bucket: &str, force: bool) -> Result<
         anyhow::bail!("Bucket name cannot be empty");
     }
 
-    // If force, delete all objects first
+    // The server empties the bucket itself, versions and uploads included
     if force {
         if !ctx.quiet {
             let msg = format!(
@@ -35,26 +61,56 @@ pub async fn execute(ctx: &CommandContext, bucket: &str, force: bool) -> Result<
             }
         }
 
-        ctx.debug(&format!("Deleting all objects in bucket: {}", bucket_name));
+        ctx.debug(&format!("Emptying and removing bucket: {}", bucket_name));
 
-        let rm_opts = RmOptions {
-            recursive: true,
+        let client = AdminClient::new(&ctx.config)?;
+        let query = DeleteBucketQuery {
             force: true,
-            include: None,
-            exclude: None,
-            dryrun: false,
+            rate,
+            bypass_governance,
         };
+        let report: DeleteBucketReport = client
+            .delete_query(&format!("/buckets/{}", bucket_name), &query)
+            .await?;
+
+        if ctx.is_json() {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else if !ctx.quiet {
+            println!(
+                "{}: {} version(s), {} delete marker(s), {} upload(s), {} freed",
+                "empty_bucket".yellow(),
+                report.versions_deleted,
+                report.delete_markers_deleted,
+                report.uploads_aborted,
+                format_size(report.bytes_freed, true)
+            );
+        }
+        for err in &report.errors {
+            ctx.error(&format!("{}: {}", "rb error".red(), err));
+        }
+
+        if !report.deleted {
+            if report.locked_versions > 0 {
+                anyhow::bail!(
+                    "Bucket not removed: {} version(s) are protected by object lock",
+                    report.locked_versions
+                );
+            }
+            anyhow::bail!("Bucket not removed");
+        }
 
-        let s3_path = format!("s3://{}/", bucket_name);
-        rm_execute(ctx, &s3_path, rm_opts).await?;
+        if !ctx.quiet && !ctx.is_json() {
+            println!("{}: s3://{}", "remove_bucket".red(), bucket_name);
+        }
+        return Ok(());
     }
 
+    let client = create_client(&ctx.config)?;
+
     ctx.debug(&format!("Removing bucket: {}", bucket_name));
 
     client
-        .delete_bucket()
-        .bucket(&bucket_name)
-        .send()
+        .delete_bucket(&bucket_name)
         .await
         .context("Failed to delete bucket. Bucket may not be empty (use --force).")?;
 
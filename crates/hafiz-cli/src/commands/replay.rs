@@ -0,0 +1,424 @@
+//! replay command - send captured requests to a server again
+//!
+//! Reads the records a request capture wrote, in the order the requests
+//! arrived, and sends each one to the target server signed with the target's
+//! credentials. The status each request gets is compared with the one
+//! recorded, so a problem can be reproduced against a test server.
+
+use super::CommandContext;
+use crate::s3_client::{create_client, is_s3_uri, S3Uri};
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use colored::Colorize;
+use hafiz_client::{Client, Credentials, Method, RawRequest};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Headers the replaying client sets itself, or that only made sense on
+/// the original connection
+const SKIPPED_HEADERS: &[&str] = &[
+    "host",
+    "x-amz-date",
+    "x-amz-content-sha256",
+    "x-amz-decoded-content-length",
+    "content-length",
+    "transfer-encoding",
+    "connection",
+    "keep-alive",
+    "expect",
+    "date",
+];
+
+/// Where and how to replay
+pub struct ReplayOptions {
+    pub target: String,
+    pub access_key: Option<String>,
+    pub secret_key: Option<String>,
+    pub limit: Option<usize>,
+    pub dry_run: bool,
+}
+
+/// One captured request, as written by the server
+#[derive(Debug, Deserialize)]
+struct CapturedRequest {
+    request_id: String,
+    operation: Option<String>,
+    method: String,
+    path: String,
+    query: Option<String>,
+    headers: Vec<(String, String)>,
+    body: Option<String>,
+    status: u16,
+}
+
+/// Outcome of one replayed request
+#[derive(Debug, Serialize)]
+struct ReplayResult {
+    request_id: String,
+    operation: Option<String>,
+    method: String,
+    path: String,
+    recorded_status: u16,
+    status: Option<u16>,
+    /// Why the request was not sent, or the error it got
+    error: Option<String>,
+}
+
+impl ReplayResult {
+    fn matched(&self) -> bool {
+        self.status == Some(self.recorded_status)
+    }
+}
+
+pub async fn execute(ctx: &CommandContext, source: &str, opts: ReplayOptions) -> Result<()> {
+    let mut records = load_records(ctx, source).await?;
+    if let Some(limit) = opts.limit {
+        records.truncate(limit);
+    }
+    ctx.debug(&format!(
+        "Replaying {} requests to {}",
+        records.len(),
+        opts.target
+    ));
+
+    let client = target_client(ctx, &opts)?;
+    let mut results = Vec::new();
+    for record in records {
+        let mut result = ReplayResult {
+            request_id: record.request_id.clone(),
+            operation: record.operation.clone(),
+            method: record.method.clone(),
+            path: record.path.clone(),
+            recorded_status: record.status,
+            status: None,
+            error: None,
+        };
+        match build_request(&record) {
+            Ok(_) if opts.dry_run => {}
+            Ok(request) => match client.send_raw(&request).await {
+                Ok(response) => result.status = Some(response.status),
+                Err(e) => result.error = Some(e.to_string()),
+            },
+            Err(e) => result.error = Some(e.to_string()),
+        }
+        if !ctx.is_json() {
+            print_result(&result, opts.dry_run);
+        }
+        results.push(result);
+    }
+
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else if !ctx.quiet && !opts.dry_run {
+        let matched = results.iter().filter(|r| r.matched()).count();
+        let failed = results.iter().filter(|r| r.error.is_some()).count();
+        println!(
+            "\n{} replayed: {} matched, {} differed, {} not sent",
+            results.len(),
+            matched,
+            results.len() - matched - failed,
+            failed
+        );
+    }
+    Ok(())
+}
+
+fn target_client(ctx: &CommandContext, opts: &ReplayOptions) -> Result<Client> {
+    let access_key = opts
+        .access_key
+        .clone()
+        .or_else(|| ctx.config.access_key.clone())
+        .context("No access key for the target; use --target-access-key")?;
+    let secret_key = opts
+        .secret_key
+        .clone()
+        .or_else(|| ctx.config.secret_key.clone())
+        .context("No secret key for the target; use --target-secret-key")?;
+
+    Client::builder(opts.target.as_str())
+        .region(ctx.config.region.clone())
+        .credentials(Credentials::new(access_key, secret_key))
+        .timeout(Duration::from_secs(ctx.config.timeout))
+        .build()
+        .with_context(|| format!("Invalid target: {}", opts.target))
+}
+
+/// Records from a capture prefix, a directory or one file, oldest first
+async fn load_records(ctx: &CommandContext, source: &str) -> Result<Vec<CapturedRequest>> {
+    let mut sources = Vec::new();
+    if is_s3_uri(source) {
+        let client = create_client(&ctx.config)?;
+        let uri = S3Uri::parse(source)?;
+        let mut pages = client
+            .list_objects(&uri.bucket)
+            .prefix(uri.key_or_empty())
+            .into_paginator();
+        let mut keys = Vec::new();
+        while let Some(page) = pages.next_page().await? {
+            keys.extend(
+                page.objects
+                    .into_iter()
+                    .map(|o| o.key)
+                    .filter(|k| k.ends_with(".json")),
+            );
+        }
+        keys.sort();
+        for key in keys {
+            let object = client
+                .get_object(&uri.bucket, &key)
+                .await
+                .with_context(|| format!("Failed to get s3://{}/{}", uri.bucket, key))?;
+            sources.push((
+                format!("s3://{}/{}", uri.bucket, key),
+                object.body.bytes().await?.to_vec(),
+            ));
+        }
+    } else {
+        let path = Path::new(source);
+        let mut files = Vec::new();
+        if path.is_dir() {
+            for entry in
+                std::fs::read_dir(path).with_context(|| format!("Failed to read {}", source))?
+            {
+                let file = entry?.path();
+                if file.extension().is_some_and(|e| e == "json") {
+                    files.push(file);
+                }
+            }
+            files.sort();
+        } else {
+            files.push(path.to_path_buf());
+        }
+        for file in files {
+            let data = std::fs::read(&file)
+                .with_context(|| format!("Failed to read {}", file.display()))?;
+            sources.push((file.display().to_string(), data));
+        }
+    }
+
+    sources
+        .into_iter()
+        .map(|(name, data)| {
+            serde_json::from_slice(&data)
+                .with_context(|| format!("{} is not a captured request", name))
+        })
+        .collect()
+}
+
+/// The request to send for a record
+fn build_request(record: &CapturedRequest) -> Result<RawRequest> {
+    let method = Method::from_bytes(record.method.as_bytes())
+        .with_context(|| format!("Invalid method {}", record.method))?;
+    let mut request = RawRequest::new(method);
+
+    let path = record.path.trim_start_matches('/');
+    let (bucket, key) = match path.split_once('/') {
+        Some((bucket, key)) => (bucket, Some(key).filter(|k| !k.is_empty())),
+        None => (path, None),
+    };
+    if !bucket.is_empty() {
+        request.bucket = Some(bucket.to_string());
+    }
+    request.key = key.map(decode).transpose()?;
+
+    if let Some(query) = &record.query {
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (name, value) = param.split_once('=').unwrap_or((param, ""));
+            request.query.push((decode(name)?, decode(value)?));
+        }
+    }
+
+    let mut streaming = false;
+    for (name, value) in &record.headers {
+        let name = name.to_ascii_lowercase();
+        if name == "x-amz-content-sha256" && value.starts_with("STREAMING-") {
+            streaming = true;
+        }
+        if SKIPPED_HEADERS.contains(&name.as_str()) {
+            continue;
+        }
+        request.headers.push((name, value.clone()));
+    }
+
+    match &record.body {
+        Some(body) => {
+            let body = BASE64.decode(body).context("Invalid body")?;
+            request.body = if streaming {
+                decode_aws_chunked(&body)
+                    .context("Invalid aws-chunked body")?
+                    .into()
+            } else {
+                body.into()
+            };
+        }
+        None if has_body(record) => bail!("Body was not captured"),
+        None => {}
+    }
+    if streaming {
+        // The chunks are sent decoded, so the encoding no longer applies
+        for (name, value) in request.headers.iter_mut() {
+            if name == "content-encoding" {
+                *value = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|v| *v != "aws-chunked")
+                    .collect::<Vec<_>>()
+                    .join(",");
+            }
+        }
+        request
+            .headers
+            .retain(|(name, value)| name != "content-encoding" || !value.is_empty());
+    }
+    Ok(request)
+}
+
+/// Whether the original request sent a body
+fn has_body(record: &CapturedRequest) -> bool {
+    record.headers.iter().any(|(name, value)| {
+        (name.eq_ignore_ascii_case("content-length") && value.trim() != "0")
+            || name.eq_ignore_ascii_case("transfer-encoding")
+    })
+}
+
+/// Percent-decode a path or query component
+fn decode(s: &str) -> Result<String> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded).with_context(|| format!("{} is not UTF-8 once decoded", s))
+}
+
+/// Payload of an `aws-chunked` body: `<hex size>;chunk-signature=...\r\n`
+/// followed by the data and `\r\n`, until a chunk of size 0
+fn decode_aws_chunked(mut body: &[u8]) -> Option<Vec<u8>> {
+    let mut payload = Vec::new();
+    loop {
+        let line_end = body.windows(2).position(|w| w == b"\r\n")?;
+        let line = std::str::from_utf8(&body[..line_end]).ok()?;
+        let size = usize::from_str_radix(line.split(';').next()?.trim(), 16).ok()?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Some(payload);
+        }
+        payload.extend_from_slice(body.get(..size)?);
+        body = body.get(size + 2..)?;
+    }
+}
+
+fn print_result(result: &ReplayResult, dry_run: bool) {
+    let request = format!("{} {}", result.method, result.path);
+    let operation = result.operation.as_deref().unwrap_or("-");
+    match (&result.status, &result.error) {
+        (_, Some(error)) => println!("{} {} ({}): {}", "skip".yellow(), request, operation, error),
+        (None, None) if dry_run => {
+            println!("{} ({}) {}", request, operation, result.recorded_status)
+        }
+        (Some(status), None) if result.matched() => {
+            println!("{} {} ({}) {}", "ok".green(), request, operation, status)
+        }
+        (Some(status), None) => println!(
+            "{} {} ({}) {} -> {}",
+            "diff".red(),
+            request,
+            operation,
+            result.recorded_status,
+            status
+        ),
+        (None, None) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(
+        method: &str,
+        path: &str,
+        query: Option<&str>,
+        headers: &[(&str, &str)],
+    ) -> CapturedRequest {
+        CapturedRequest {
+            request_id: "r1".into(),
+            operation: None,
+            method: method.into(),
+            path: path.into(),
+            query: query.map(String::from),
+            headers: headers
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            body: None,
+            status: 200,
+        }
+    }
+
+    #[test]
+    fn test_build_request() {
+        let record = record(
+            "GET",
+            "/photos/2024/a%20b&c.jpg",
+            Some("versionId=v%2B1&tagging"),
+            &[
+                ("Host", "old:9000"),
+                ("x-amz-date", "20240101T000000Z"),
+                ("range", "bytes=0-9"),
+            ],
+        );
+        let request = build_request(&record).unwrap();
+        assert_eq!(request.method, Method::GET);
+        assert_eq!(request.bucket.as_deref(), Some("photos"));
+        assert_eq!(request.key.as_deref(), Some("2024/a b&c.jpg"));
+        assert_eq!(
+            request.query,
+            vec![
+                ("versionId".into(), "v+1".into()),
+                ("tagging".into(), String::new())
+            ]
+        );
+        assert_eq!(request.headers, vec![("range".into(), "bytes=0-9".into())]);
+    }
+
+    #[test]
+    fn test_missing_body_is_refused() {
+        let record = record("PUT", "/photos/a.jpg", None, &[("content-length", "10")]);
+        assert!(build_request(&record).is_err());
+    }
+
+    #[test]
+    fn test_streaming_body_is_decoded() {
+        let mut record = record(
+            "PUT",
+            "/photos/a.txt",
+            None,
+            &[
+                ("x-amz-content-sha256", "STREAMING-AWS4-HMAC-SHA256-PAYLOAD"),
+                ("content-encoding", "aws-chunked"),
+                ("content-length", "100"),
+            ],
+        );
+        let chunked = "5;chunk-signature=aa\r\nhello\r\n6;chunk-signature=bb\r\n world\r\n0;chunk-signature=cc\r\n\r\n";
+        record.body = Some(BASE64.encode(chunked));
+        let request = build_request(&record).unwrap();
+        assert_eq!(&request.body[..], b"hello world");
+        assert!(request.headers.is_empty());
+    }
+}
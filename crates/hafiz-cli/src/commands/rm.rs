@@ -4,8 +4,8 @@ use super::CommandContext;
 use crate::s3_client::{create_client, S3Uri};
 use crate::utils::{confirm, matches_patterns};
 use anyhow::{Context, Result};
-use aws_sdk_s3::types::{Delete, ObjectIdentifier};
 use colored::Colorize;
+use hafiz_client::Client;
 
 pub struct RmOptions {
     pub recursive: bool,
@@ -16,7 +16,7 @@ pub struct RmOptions {
 }
 
 pub async fn execute(ctx: &CommandContext, path: &str, opts: RmOptions) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let uri = S3Uri::parse(path)?;
 
     if uri.key.is_none() && !opts.recursive {
@@ -34,7 +34,7 @@ pub async fn execute(ctx: &CommandContext, path: &str, opts: RmOptions) -> Resul
 
 async fn delete_object(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     uri: &S3Uri,
     opts: &RmOptions,
 ) -> Result<()> {
@@ -56,10 +56,7 @@ async fn delete_object(
     ctx.debug(&format!("Deleting s3://{}/{}", uri.bucket, key));
 
     client
-        .delete_object()
-        .bucket(&uri.bucket)
-        .key(key)
-        .send()
+        .delete_object(&uri.bucket, key)
         .await
         .context("Delete failed")?;
 
@@ -72,7 +69,7 @@ async fn delete_object(
 
 async fn delete_prefix(
     ctx: &CommandContext,
-    client: &aws_sdk_s3::Client,
+    client: &Client,
     uri: &S3Uri,
     opts: &RmOptions,
 ) -> Result<()> {
@@ -80,36 +77,15 @@ async fn delete_prefix(
 
     // List all objects
     let mut objects: Vec<String> = Vec::new();
-    let mut continuation_token: Option<String> = None;
+    let mut pages = client.list_objects(&uri.bucket).prefix(prefix).into_paginator();
 
-    loop {
-        let mut req = client
-            .list_objects_v2()
-            .bucket(&uri.bucket)
-            .prefix(&prefix);
-
-        if let Some(token) = &continuation_token {
-            req = req.continuation_token(token);
-        }
-
-        let resp = req.send().await?;
-
-        if let Some(contents) = resp.contents {
-            for obj in contents {
-                if let Some(key) = obj.key() {
-                    // Check patterns
-                    if matches_patterns(key, opts.include.as_deref(), opts.exclude.as_deref())? {
-                        objects.push(key.to_string());
-                    }
-                }
+    while let Some(page) = pages.next_page().await? {
+        for obj in page.objects {
+            // Check patterns
+            if matches_patterns(&obj.key, opts.include.as_deref(), opts.exclude.as_deref())? {
+                objects.push(obj.key);
             }
         }
-
-        if resp.is_truncated.unwrap_or(false) {
-            continuation_token = resp.next_continuation_token;
-        } else {
-            break;
-        }
     }
 
     if objects.is_empty() {
@@ -138,44 +114,17 @@ async fn delete_prefix(
             continue;
         }
 
-        let delete_objects: Vec<ObjectIdentifier> = chunk
-            .iter()
-            .map(|key| ObjectIdentifier::builder().key(key).build().unwrap())
-            .collect();
-
-        let delete = Delete::builder()
-            .set_objects(Some(delete_objects))
-            .build()?;
-
-        let resp = client
-            .delete_objects()
-            .bucket(&uri.bucket)
-            .delete(delete)
-            .send()
-            .await?;
-
-        if let Some(deleted_objs) = resp.deleted {
-            for obj in deleted_objs {
-                if !ctx.quiet {
-                    println!(
-                        "{}: s3://{}/{}",
-                        "delete".red(),
-                        uri.bucket,
-                        obj.key().unwrap_or("")
-                    );
-                }
+        let resp = client.delete_objects(&uri.bucket, chunk).await?;
+
+        for key in &resp.deleted {
+            if !ctx.quiet {
+                println!("{}: s3://{}/{}", "delete".red(), uri.bucket, key);
             }
-            deleted += deleted_objs.len();
         }
+        deleted += resp.deleted.len();
 
-        if let Some(errors) = resp.errors {
-            for err in errors {
-                ctx.error(&format!(
-                    "Failed to delete {}: {}",
-                    err.key().unwrap_or(""),
-                    err.message().unwrap_or("")
-                ));
-            }
+        for err in resp.errors {
+            ctx.error(&format!("Failed to delete {}: {}", err.key, err.message));
         }
     }
 
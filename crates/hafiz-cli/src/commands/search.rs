@@ -0,0 +1,84 @@
+//! search command - find objects through the server's search index
+
+use super::CommandContext;
+use crate::admin_client::AdminClient;
+use crate::s3_client::S3Uri;
+use crate::utils::format_size;
+use anyhow::Result;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize)]
+struct SearchParams<'a> {
+    q: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bucket: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prefix: Option<&'a str>,
+    limit: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SearchResponse {
+    query: String,
+    hits: Vec<SearchHit>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SearchHit {
+    bucket: String,
+    key: String,
+    size: u64,
+    etag: String,
+    content_type: Option<String>,
+    last_modified: String,
+    score: f32,
+}
+
+pub async fn execute(
+    ctx: &CommandContext,
+    query: &str,
+    path: Option<&str>,
+    limit: usize,
+    human_readable: bool,
+) -> Result<()> {
+    let client = AdminClient::new(&ctx.config)?;
+    let uri = path.map(S3Uri::parse).transpose()?;
+    let params = SearchParams {
+        q: query,
+        bucket: uri
+            .as_ref()
+            .map(|u| u.bucket.as_str())
+            .filter(|b| !b.is_empty()),
+        prefix: uri.as_ref().and_then(|u| u.key.as_deref()),
+        limit,
+    };
+    ctx.debug(&format!("Searching for '{}'", query));
+
+    let response: SearchResponse = client.get_query("/search", &params).await?;
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&response)?);
+        return Ok(());
+    }
+
+    for hit in &response.hits {
+        let date = chrono::DateTime::parse_from_rfc3339(&hit.last_modified)
+            .map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|_| hit.last_modified.clone());
+        println!(
+            "{} {:>12}  {}",
+            date,
+            format_size(hit.size as i64, human_readable),
+            format!("s3://{}/{}", hit.bucket, hit.key).blue()
+        );
+    }
+    if !ctx.quiet {
+        let more = if response.hits.len() == limit {
+            " (limit reached; use --limit for more)"
+        } else {
+            ""
+        };
+        println!("\n{} object(s) found{}", response.hits.len(), more);
+    }
+    Ok(())
+}
@@ -0,0 +1,119 @@
+//! server command - run the S3 server, check its configuration, or
+//! migrate its storage layout
+
+use super::CommandContext;
+use anyhow::{anyhow, Result};
+use colored::Colorize;
+use hafiz_core::config::{ConfigLoader, HafizConfig};
+
+pub struct ServerOptions {
+    pub config: Option<String>,
+    pub overrides: Vec<String>,
+    pub check_config: bool,
+    pub migrate_layout: bool,
+}
+
+pub async fn execute(ctx: &CommandContext, options: ServerOptions) -> Result<()> {
+    let mut loader = ConfigLoader::new().env();
+    if let Some(path) = &options.config {
+        loader = loader.file(path);
+    }
+    for assignment in options.overrides {
+        loader = loader.set(assignment);
+    }
+    let config = loader
+        .load()
+        .and_then(|config| config.validate().map(|_| config))
+        .map_err(config_error)?;
+
+    if options.check_config {
+        let printed = config.to_redacted_toml().map_err(config_error)?;
+        if ctx.is_json() {
+            let table: toml::Table = toml::from_str(&printed)?;
+            println!("{}", serde_json::to_string_pretty(&table)?);
+        } else {
+            print!("{}", printed);
+            eprintln!("{} Configuration is valid", "✓".green());
+        }
+        return Ok(());
+    }
+    if options.migrate_layout {
+        return migrate_layout(ctx, &config).await;
+    }
+
+    run(config).await
+}
+
+#[cfg(feature = "server")]
+async fn run(config: HafizConfig) -> Result<()> {
+    let _telemetry = hafiz_s3_api::telemetry::init(&config).map_err(config_error)?;
+    hafiz_s3_api::S3Server::new(config)
+        .run()
+        .await
+        .map_err(|e| anyhow!("{}", e))
+}
+
+#[cfg(not(feature = "server"))]
+async fn run(_config: HafizConfig) -> Result<()> {
+    Err(anyhow!(
+        "This hafiz was built without the server; only --check-config is available. \
+         Install it with: cargo install hafiz-cli --features server"
+    ))
+}
+
+/// Move the data of the default storage and every local backend into the
+/// current directory layout
+#[cfg(feature = "server")]
+async fn migrate_layout(ctx: &CommandContext, config: &HafizConfig) -> Result<()> {
+    use hafiz_core::config::BackendConfig;
+
+    // In gateway mode the data directory only holds the read cache
+    let mut dirs = Vec::new();
+    if !config.gateway.enabled {
+        dirs.push(config.storage.data_dir.clone());
+    }
+    for backend in config.storage.backends.values() {
+        if let BackendConfig::Local(local) = backend {
+            dirs.push(local.data_dir.clone());
+        }
+    }
+
+    let mut results = Vec::new();
+    for dir in dirs {
+        let migration = hafiz_storage::LocalStorage::new(&dir)
+            .migrate_layout()
+            .await
+            .map_err(|e| anyhow!("{}: {}", dir.display(), e))?;
+        if !ctx.is_json() {
+            println!(
+                "{} {}: moved {} files, dropped {} stale ones in {} buckets",
+                "✓".green(),
+                dir.display(),
+                migration.moved,
+                migration.stale,
+                migration.buckets
+            );
+        }
+        results.push(serde_json::json!({ "data_dir": dir, "migration": migration }));
+    }
+    if ctx.is_json() {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "server"))]
+async fn migrate_layout(_ctx: &CommandContext, _config: &HafizConfig) -> Result<()> {
+    Err(anyhow!(
+        "This hafiz was built without the server, which --migrate-layout needs. \
+         Install it with: cargo install hafiz-cli --features server"
+    ))
+}
+
+/// Configuration problems are already worded for the reader
+fn config_error(e: hafiz_core::Error) -> anyhow::Error {
+    match e {
+        hafiz_core::Error::InvalidArgument(message) => anyhow!(message),
+        e => anyhow!(e),
+    }
+}
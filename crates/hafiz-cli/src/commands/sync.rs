@@ -2,17 +2,19 @@
 
 use super::CommandContext;
 use crate::progress::{create_spinner, format_bytes};
-use crate::s3_client::{create_client, is_s3_uri, S3Uri, TransferDirection};
-use crate::utils::{guess_content_type, matches_patterns};
+use crate::s3_client::{create_client, is_s3_uri, multipart_config, S3Uri, TransferDirection};
+use crate::utils::{guess_content_type, join_key, matches_patterns};
 use anyhow::{Context, Result};
-use aws_sdk_s3::primitives::ByteStream;
-use aws_sdk_s3::types::{Delete, ObjectIdentifier};
 use chrono::{DateTime, Utc};
 use colored::Colorize;
-use std::collections::HashMap;
-use std::path::Path;
+use hafiz_client::{Client, PutObjectOptions};
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tokio::fs;
 use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
 use walkdir::WalkDir;
 
 pub struct SyncOptions {
@@ -22,6 +24,9 @@ pub struct SyncOptions {
     pub size_only: bool,
     pub dryrun: bool,
     pub parallel: usize,
+    pub watch: bool,
+    pub debounce_ms: u64,
+    pub poll_interval: u64,
 }
 
 #[derive(Debug)]
@@ -39,8 +44,20 @@ pub async fn execute(
     let direction = TransferDirection::determine(source, destination);
 
     match direction {
-        TransferDirection::Upload => sync_upload(ctx, source, destination, &opts).await,
-        TransferDirection::Download => sync_download(ctx, source, destination, &opts).await,
+        TransferDirection::Upload => {
+            sync_upload(ctx, source, destination, &opts).await?;
+            if opts.watch {
+                watch_upload(ctx, source, destination, &opts).await?;
+            }
+            Ok(())
+        }
+        TransferDirection::Download => {
+            sync_download(ctx, source, destination, &opts).await?;
+            if opts.watch {
+                watch_download(ctx, source, destination, &opts).await?;
+            }
+            Ok(())
+        }
         TransferDirection::S3ToS3 => {
             anyhow::bail!("S3 to S3 sync is not yet supported. Use cp --recursive instead.")
         }
@@ -56,7 +73,7 @@ async fn sync_upload(
     destination: &str,
     opts: &SyncOptions,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let dest_uri = S3Uri::parse(destination)?;
     let source_path = Path::new(source);
 
@@ -104,47 +121,27 @@ async fn sync_upload(
     // Get remote objects
     let prefix = dest_uri.key.clone().unwrap_or_default();
     let mut remote_files: HashMap<String, FileInfo> = HashMap::new();
-    let mut continuation_token: Option<String> = None;
-
-    loop {
-        let mut req = client
-            .list_objects_v2()
-            .bucket(&dest_uri.bucket)
-            .prefix(&prefix);
-
-        if let Some(token) = &continuation_token {
-            req = req.continuation_token(token);
-        }
-
-        let resp = req.send().await?;
+    let mut pages = client
+        .list_objects(&dest_uri.bucket)
+        .prefix(prefix.as_str())
+        .into_paginator();
 
-        if let Some(contents) = resp.contents {
-            for obj in contents {
-                if let Some(key) = obj.key() {
-                    let relative = key.strip_prefix(&prefix).unwrap_or(key);
-                    let relative = relative.trim_start_matches('/');
+    while let Some(page) = pages.next_page().await? {
+        for obj in page.objects {
+            let relative = obj.key.strip_prefix(&prefix).unwrap_or(&obj.key);
+            let relative = relative.trim_start_matches('/');
 
-                    if !matches_patterns(relative, opts.include.as_deref(), opts.exclude.as_deref())? {
-                        continue;
-                    }
-
-                    remote_files.insert(
-                        relative.to_string(),
-                        FileInfo {
-                            size: obj.size().unwrap_or(0),
-                            last_modified: obj.last_modified().map(|d| {
-                                DateTime::<Utc>::from_timestamp(d.secs(), 0).unwrap_or_default()
-                            }),
-                        },
-                    );
-                }
+            if !matches_patterns(relative, opts.include.as_deref(), opts.exclude.as_deref())? {
+                continue;
             }
-        }
 
-        if resp.is_truncated.unwrap_or(false) {
-            continuation_token = resp.next_continuation_token;
-        } else {
-            break;
+            remote_files.insert(
+                relative.to_string(),
+                FileInfo {
+                    size: obj.size,
+                    last_modified: obj.last_modified,
+                },
+            );
         }
     }
 
@@ -222,15 +219,14 @@ async fn sync_upload(
 
         let metadata = fs::metadata(&local_path).await?;
         let content_type = guess_content_type(relative);
-        let body = ByteStream::from_path(&local_path).await?;
-
         client
-            .put_object()
-            .bucket(&dest_uri.bucket)
-            .key(&dest_key)
-            .content_type(content_type)
-            .body(body)
-            .send()
+            .upload_file(
+                &dest_uri.bucket,
+                &dest_key,
+                &local_path,
+                &PutObjectOptions::default().content_type(content_type),
+                &multipart_config(&ctx.config),
+            )
             .await?;
 
         uploaded += 1;
@@ -273,35 +269,14 @@ async fn sync_upload(
                 continue;
             }
 
-            let delete_objects: Vec<ObjectIdentifier> = keys_to_delete
-                .iter()
-                .map(|key| ObjectIdentifier::builder().key(key).build().unwrap())
-                .collect();
+            let resp = client.delete_objects(&dest_uri.bucket, &keys_to_delete).await?;
 
-            let delete = Delete::builder()
-                .set_objects(Some(delete_objects))
-                .build()?;
-
-            let resp = client
-                .delete_objects()
-                .bucket(&dest_uri.bucket)
-                .delete(delete)
-                .send()
-                .await?;
-
-            if let Some(deleted_objs) = resp.deleted {
-                for obj in &deleted_objs {
-                    if !ctx.quiet {
-                        println!(
-                            "{}: s3://{}/{}",
-                            "delete".red(),
-                            dest_uri.bucket,
-                            obj.key().unwrap_or("")
-                        );
-                    }
+            for key in &resp.deleted {
+                if !ctx.quiet {
+                    println!("{}: s3://{}/{}", "delete".red(), dest_uri.bucket, key);
                 }
-                deleted += deleted_objs.len();
             }
+            deleted += resp.deleted.len();
         }
     }
 
@@ -323,7 +298,7 @@ async fn sync_download(
     destination: &str,
     opts: &SyncOptions,
 ) -> Result<()> {
-    let client = create_client(&ctx.config).await?;
+    let client = create_client(&ctx.config)?;
     let source_uri = S3Uri::parse(source)?;
     let dest_path = Path::new(destination);
 
@@ -340,51 +315,31 @@ async fn sync_download(
     // Get remote objects
     let prefix = source_uri.key.clone().unwrap_or_default();
     let mut remote_files: HashMap<String, FileInfo> = HashMap::new();
-    let mut continuation_token: Option<String> = None;
-
-    loop {
-        let mut req = client
-            .list_objects_v2()
-            .bucket(&source_uri.bucket)
-            .prefix(&prefix);
-
-        if let Some(token) = &continuation_token {
-            req = req.continuation_token(token);
-        }
-
-        let resp = req.send().await?;
+    let mut pages = client
+        .list_objects(&source_uri.bucket)
+        .prefix(prefix.as_str())
+        .into_paginator();
 
-        if let Some(contents) = resp.contents {
-            for obj in contents {
-                if let Some(key) = obj.key() {
-                    let relative = key.strip_prefix(&prefix).unwrap_or(key);
-                    let relative = relative.trim_start_matches('/');
-
-                    if relative.is_empty() {
-                        continue;
-                    }
+    while let Some(page) = pages.next_page().await? {
+        for obj in page.objects {
+            let relative = obj.key.strip_prefix(&prefix).unwrap_or(&obj.key);
+            let relative = relative.trim_start_matches('/');
 
-                    if !matches_patterns(relative, opts.include.as_deref(), opts.exclude.as_deref())? {
-                        continue;
-                    }
+            if relative.is_empty() {
+                continue;
+            }
 
-                    remote_files.insert(
-                        relative.to_string(),
-                        FileInfo {
-                            size: obj.size().unwrap_or(0),
-                            last_modified: obj.last_modified().map(|d| {
-                                DateTime::<Utc>::from_timestamp(d.secs(), 0).unwrap_or_default()
-                            }),
-                        },
-                    );
-                }
+            if !matches_patterns(relative, opts.include.as_deref(), opts.exclude.as_deref())? {
+                continue;
             }
-        }
 
-        if resp.is_truncated.unwrap_or(false) {
-            continuation_token = resp.next_continuation_token;
-        } else {
-            break;
+            remote_files.insert(
+                relative.to_string(),
+                FileInfo {
+                    size: obj.size,
+                    last_modified: obj.last_modified,
+                },
+            );
         }
     }
 
@@ -494,14 +449,11 @@ async fn sync_download(
         }
 
         let resp = client
-            .get_object()
-            .bucket(&source_uri.bucket)
-            .key(&remote_key)
-            .send()
+            .get_object(&source_uri.bucket, &remote_key)
             .await
             .context(format!("Failed to download {}", remote_key))?;
 
-        let size = resp.content_length().unwrap_or(0) as u64;
+        let size = resp.meta.content_length.unwrap_or(0) as u64;
         let mut file = fs::File::create(&local_path).await?;
         let mut stream = resp.body.into_async_read();
 
@@ -563,3 +515,207 @@ async fn sync_download(
 
     Ok(())
 }
+
+/// Keep uploading local changes until interrupted.
+///
+/// Filesystem events are debounced so editors that write a file in several
+/// steps only trigger a single upload.
+async fn watch_upload(
+    ctx: &CommandContext,
+    source: &str,
+    destination: &str,
+    opts: &SyncOptions,
+) -> Result<()> {
+    let client = create_client(&ctx.config)?;
+    let dest_uri = S3Uri::parse(destination)?;
+    let prefix = dest_uri.key.clone().unwrap_or_default();
+    let source_path = std::fs::canonicalize(source)
+        .with_context(|| format!("Failed to resolve source directory: {}", source))?;
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<DebounceEventResult>();
+    let mut debouncer = new_debouncer(Duration::from_millis(opts.debounce_ms), move |res| {
+        let _ = tx.send(res);
+    })
+    .context("Failed to create filesystem watcher")?;
+
+    debouncer
+        .watcher()
+        .watch(&source_path, RecursiveMode::Recursive)
+        .with_context(|| format!("Failed to watch {}", source_path.display()))?;
+
+    ctx.info(&format!(
+        "Watching {} for changes (Ctrl+C to stop)",
+        source_path.display()
+    ));
+
+    loop {
+        let events = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            res = rx.recv() => match res {
+                Some(Ok(events)) => events,
+                Some(Err(e)) => {
+                    ctx.error(&format!("{}: {}", "watch error".red(), e));
+                    continue;
+                }
+                None => break,
+            },
+        };
+
+        // A single debounce window can report the same path several times
+        let paths: BTreeSet<PathBuf> = events.into_iter().map(|e| e.path).collect();
+
+        for path in paths {
+            if let Err(e) =
+                apply_local_change(ctx, &client, &source_path, &dest_uri.bucket, &prefix, &path, opts).await
+            {
+                ctx.error(&format!("{}: {}: {}", "error".red(), path.display(), e));
+            }
+        }
+    }
+
+    ctx.info("Stopped watching");
+    Ok(())
+}
+
+/// Mirror a single changed path to the bucket
+async fn apply_local_change(
+    ctx: &CommandContext,
+    client: &Client,
+    source_path: &Path,
+    bucket: &str,
+    prefix: &str,
+    path: &Path,
+    opts: &SyncOptions,
+) -> Result<()> {
+    if path.is_dir() {
+        // Directories moved into the tree only report the directory itself
+        for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+            if entry.file_type().is_file() {
+                Box::pin(apply_local_change(
+                    ctx,
+                    client,
+                    source_path,
+                    bucket,
+                    prefix,
+                    entry.path(),
+                    opts,
+                ))
+                .await?;
+            }
+        }
+        return Ok(());
+    }
+
+    let relative = match path.strip_prefix(source_path) {
+        Ok(r) => r.to_str().unwrap_or("").replace('\\', "/"),
+        Err(_) => return Ok(()),
+    };
+
+    if relative.is_empty()
+        || !matches_patterns(&relative, opts.include.as_deref(), opts.exclude.as_deref())?
+    {
+        return Ok(());
+    }
+
+    let dest_key = join_key(prefix, &relative);
+
+    if path.is_file() {
+        if opts.dryrun {
+            println!(
+                "(dryrun) upload: {} -> s3://{}/{}",
+                path.display(),
+                bucket,
+                dest_key
+            );
+            return Ok(());
+        }
+
+        let options = PutObjectOptions::default().content_type(guess_content_type(&relative));
+        client
+            .upload_file(bucket, &dest_key, path, &options, &multipart_config(&ctx.config))
+            .await
+            .with_context(|| format!("Failed to upload {}", dest_key))?;
+
+        if !ctx.quiet {
+            println!(
+                "{}: {} -> s3://{}/{}",
+                "upload".green(),
+                path.display(),
+                bucket,
+                dest_key
+            );
+        }
+    } else if opts.delete && !path.exists() {
+        // A removed path may have been a directory, so everything under it
+        // goes as well
+        let mut keys = vec![dest_key.clone()];
+        let dir_prefix = format!("{}/", dest_key);
+        let mut pages = client
+            .list_objects(bucket)
+            .prefix(dir_prefix.as_str())
+            .into_paginator();
+        while let Some(page) = pages.next_page().await? {
+            for obj in page.objects {
+                let nested = format!("{}/{}", relative, &obj.key[dir_prefix.len()..]);
+                if matches_patterns(&nested, opts.include.as_deref(), opts.exclude.as_deref())? {
+                    keys.push(obj.key);
+                }
+            }
+        }
+
+        if opts.dryrun {
+            for key in &keys {
+                println!("(dryrun) delete: s3://{}/{}", bucket, key);
+            }
+            return Ok(());
+        }
+
+        let resp = client
+            .delete_objects(bucket, &keys)
+            .await
+            .with_context(|| format!("Failed to delete {}", dest_key))?;
+        if let Some(err) = resp.errors.first() {
+            anyhow::bail!("Failed to delete {}: {}", err.key, err.message);
+        }
+
+        if !ctx.quiet {
+            for key in &resp.deleted {
+                println!("{}: s3://{}/{}", "delete".red(), bucket, key);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Keep pulling remote changes until interrupted by re-running the
+/// download sync on a fixed interval.
+async fn watch_download(
+    ctx: &CommandContext,
+    source: &str,
+    destination: &str,
+    opts: &SyncOptions,
+) -> Result<()> {
+    ctx.info(&format!(
+        "Polling {} every {}s (Ctrl+C to stop)",
+        source, opts.poll_interval
+    ));
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(opts.poll_interval.max(1)));
+    // The first tick completes immediately and the initial sync already ran
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            _ = ticker.tick() => {
+                if let Err(e) = sync_download(ctx, source, destination, opts).await {
+                    ctx.error(&format!("{}: {}", "sync error".red(), e));
+                }
+            }
+        }
+    }
+
+    ctx.info("Stopped watching");
+    Ok(())
+}
@@ -14,6 +14,8 @@
 //! endpoint = "https://s3.example.com"
 //! access_key = "prod-access-key"
 //! secret_key = "prod-secret-key"
+//! # Administration on a separate listener
+//! admin_endpoint = "https://admin.example.com:9001"
 //! ```
 
 use anyhow::{Context, Result};
@@ -64,6 +66,15 @@ pub struct Config {
     /// Request timeout in seconds
     #[serde(default = "default_timeout")]
     pub timeout: u64,
+
+    /// Admin API endpoint, when the server has a separate admin listener
+    /// (default: the S3 endpoint)
+    #[serde(default)]
+    pub admin_endpoint: Option<String>,
+
+    /// Admin API bearer token, used instead of the access and secret key
+    #[serde(default)]
+    pub admin_token: Option<String>,
 }
 
 fn default_region() -> String {
@@ -164,6 +175,12 @@ impl Config {
         if let Ok(region) = std::env::var("AWS_REGION") {
             config.region = region;
         }
+        if let Ok(endpoint) = std::env::var("HAFIZ_ADMIN_ENDPOINT") {
+            config.admin_endpoint = Some(endpoint);
+        }
+        if let Ok(token) = std::env::var("HAFIZ_ADMIN_TOKEN") {
+            config.admin_token = Some(token);
+        }
 
         Ok(config)
     }
@@ -268,6 +285,8 @@ impl Config {
             "multipart_chunksize" => Some(self.multipart_chunksize.to_string()),
             "max_concurrent_requests" => Some(self.max_concurrent_requests.to_string()),
             "timeout" => Some(self.timeout.to_string()),
+            "admin_endpoint" => self.admin_endpoint.clone(),
+            "admin_token" => self.admin_token.as_ref().map(|_| "***".to_string()),
             _ => None,
         }
     }
@@ -286,6 +305,8 @@ impl Config {
             "multipart_chunksize" => self.multipart_chunksize = value.parse()?,
             "max_concurrent_requests" => self.max_concurrent_requests = value.parse()?,
             "timeout" => self.timeout = value.parse()?,
+            "admin_endpoint" => self.admin_endpoint = Some(value.to_string()),
+            "admin_token" => self.admin_token = Some(value.to_string()),
             _ => anyhow::bail!("Unknown config key: {}", key),
         }
         Ok(())
@@ -305,6 +326,8 @@ impl Config {
             "multipart_chunksize",
             "max_concurrent_requests",
             "timeout",
+            "admin_endpoint",
+            "admin_token",
         ]
     }
 }
@@ -0,0 +1,96 @@
+//! Parallel object listing
+//!
+//! Walks a bucket by issuing delimiter-based ListObjectsV2 requests per
+//! prefix and fanning out into each common prefix concurrently. Objects are
+//! streamed through a channel as pages arrive, so callers can start
+//! printing or aggregating before the whole bucket has been listed.
+
+use anyhow::Result;
+use hafiz_client::{Client, Object};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+
+/// Capacity of the result channel; bounds memory when the consumer is slow
+const CHANNEL_CAPACITY: usize = 4096;
+
+/// List every object under `prefix`, using up to `max_concurrency`
+/// in-flight ListObjectsV2 requests.
+///
+/// Results arrive in no particular order. The first error is sent through
+/// the channel and stops the branch of the walk that hit it.
+pub fn list_objects_parallel(
+    client: &Client,
+    bucket: &str,
+    prefix: &str,
+    max_concurrency: usize,
+) -> mpsc::Receiver<Result<Object>> {
+    let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+    let walker = Arc::new(Walker {
+        client: client.clone(),
+        bucket: bucket.to_string(),
+        permits: Semaphore::new(max_concurrency.max(1)),
+    });
+
+    tokio::spawn(walker.list_prefix(prefix.to_string(), tx));
+    rx
+}
+
+struct Walker {
+    client: Client,
+    bucket: String,
+    permits: Semaphore,
+}
+
+impl Walker {
+    fn list_prefix(
+        self: Arc<Self>,
+        prefix: String,
+        tx: mpsc::Sender<Result<Object>>,
+    ) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+        Box::pin(async move {
+            let mut pages = self
+                .client
+                .list_objects(&self.bucket)
+                .prefix(prefix.as_str())
+                .delimiter("/")
+                .into_paginator();
+
+            loop {
+                // Only the request itself holds a permit, so child walks
+                // never wait on their parent
+                let page = {
+                    let _permit = match self.permits.acquire().await {
+                        Ok(p) => p,
+                        Err(_) => return,
+                    };
+                    pages.next_page().await
+                };
+
+                let page = match page {
+                    Ok(Some(page)) => page,
+                    Ok(None) => break,
+                    Err(e) => {
+                        let _ = tx
+                            .send(Err(anyhow::Error::new(e)
+                                .context(format!("Failed to list s3://{}/{}", self.bucket, prefix))))
+                            .await;
+                        return;
+                    }
+                };
+
+                for child in page.common_prefixes {
+                    tokio::spawn(self.clone().list_prefix(child, tx.clone()));
+                }
+
+                for obj in page.objects {
+                    if tx.send(Ok(obj)).await.is_err() {
+                        // Receiver dropped; stop walking
+                        return;
+                    }
+                }
+            }
+        })
+    }
+}
@@ -8,9 +8,15 @@
 //!   hafiz mb s3://bucket
 //!   hafiz rb s3://bucket
 //!   hafiz rm s3://bucket/key
+//!   hafiz mount s3://bucket /mnt/bucket
+//!   hafiz server --config /etc/hafiz/config.toml --check-config
 
+mod admin_client;
 mod commands;
 mod config;
+mod listing;
+#[cfg(feature = "mount")]
+mod mount;
 mod progress;
 mod s3_client;
 mod utils;
@@ -92,6 +98,11 @@ enum Commands {
         /// Show only summary
         #[arg(long)]
         summarize: bool,
+
+        /// Maximum concurrent listing requests for recursive listings
+        /// (defaults to max_concurrent_requests from config)
+        #[arg(long)]
+        max_concurrency: Option<usize>,
     },
 
     /// Copy files to/from S3
@@ -185,6 +196,18 @@ enum Commands {
         /// Number of parallel transfers
         #[arg(long, default_value = "4")]
         parallel: usize,
+
+        /// Keep running and sync changes as they happen
+        #[arg(long)]
+        watch: bool,
+
+        /// Debounce window for filesystem events in watch mode (milliseconds)
+        #[arg(long, default_value = "500")]
+        debounce_ms: u64,
+
+        /// Polling interval when watching a bucket for downloads (seconds)
+        #[arg(long, default_value = "30")]
+        poll_interval: u64,
     },
 
     /// Remove objects
@@ -234,6 +257,14 @@ enum Commands {
         /// Force delete (delete all objects first)
         #[arg(long, short)]
         force: bool,
+
+        /// With --force, maximum versions the server deletes per second (0 = unlimited)
+        #[arg(long)]
+        rate: Option<u32>,
+
+        /// With --force, also delete versions under GOVERNANCE retention
+        #[arg(long)]
+        bypass_governance: bool,
     },
 
     /// Get object info/metadata
@@ -280,6 +311,11 @@ enum Commands {
         /// Summarize (show only total)
         #[arg(long, short)]
         summarize: bool,
+
+        /// Maximum concurrent listing requests
+        /// (defaults to max_concurrent_requests from config)
+        #[arg(long)]
+        max_concurrency: Option<usize>,
     },
 
     /// Stream object content to stdout
@@ -287,6 +323,99 @@ enum Commands {
         /// S3 path
         path: String,
     },
+
+    /// Find objects by key, user metadata and tags (needs the server's search index)
+    Search {
+        /// Terms to match, e.g. `invoice 2024` or `tags.project:apollo`
+        query: String,
+
+        /// Only search this bucket or prefix (s3://bucket or s3://bucket/prefix/)
+        path: Option<String>,
+
+        /// Maximum number of objects to show
+        #[arg(long, default_value = "100")]
+        limit: usize,
+
+        /// Human-readable sizes
+        #[arg(long, short = 'H')]
+        human_readable: bool,
+    },
+
+    /// Send requests recorded by `hafiz admin capture` to a server again
+    Replay {
+        /// Records of a capture: s3://bucket/prefix/, a local directory or a file
+        source: String,
+
+        /// Server to send the requests to (e.g., http://localhost:9100)
+        #[arg(long)]
+        target: String,
+
+        /// Access key on the target (defaults to the configured one)
+        #[arg(long)]
+        target_access_key: Option<String>,
+
+        /// Secret key on the target (defaults to the configured one)
+        #[arg(long)]
+        target_secret_key: Option<String>,
+
+        /// Replay at most this many requests
+        #[arg(long)]
+        limit: Option<usize>,
+
+        /// List the requests without sending them
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Manage bucket configuration (policy, CORS, lifecycle, versioning, tagging)
+    Bucket {
+        #[command(subcommand)]
+        action: BucketAction,
+    },
+
+    /// Server administration (users, stats, maintenance) via the admin API
+    Admin {
+        #[command(subcommand)]
+        action: AdminAction,
+    },
+
+    /// Run the S3 server (needs the `server` feature), or check its configuration
+    Server {
+        /// Configuration file (TOML)
+        #[arg(long, short = 'c', env = "HAFIZ_CONFIG")]
+        config: Option<String>,
+
+        /// Override a setting, e.g. --set storage.max_put_size=10GiB (repeatable)
+        #[arg(long = "set", value_name = "KEY=VALUE")]
+        overrides: Vec<String>,
+
+        /// Validate the configuration, print the effective settings and exit
+        #[arg(long)]
+        check_config: bool,
+
+        /// Move object data stored by earlier versions into the current
+        /// directory layout of the local storage backends, then exit
+        #[arg(long, conflicts_with = "check_config")]
+        migrate_layout: bool,
+    },
+
+    /// Mount a bucket as a local filesystem (FUSE)
+    #[cfg(feature = "mount")]
+    Mount {
+        /// Bucket or prefix to mount (s3://bucket or s3://bucket/prefix/)
+        path: String,
+
+        /// Local directory to mount on
+        mountpoint: String,
+
+        /// Refuse writes
+        #[arg(long)]
+        read_only: bool,
+
+        /// Seconds to cache directory listings and file attributes
+        #[arg(long, default_value = "5")]
+        cache_ttl: u64,
+    },
 }
 
 #[derive(Subcommand)]
@@ -317,6 +446,827 @@ pub enum ConfigureAction {
     },
 }
 
+#[derive(Subcommand)]
+pub enum BucketAction {
+    /// Manage bucket policy
+    Policy {
+        #[command(subcommand)]
+        action: BucketConfigAction,
+    },
+    /// Manage CORS configuration
+    Cors {
+        #[command(subcommand)]
+        action: BucketConfigAction,
+    },
+    /// Manage lifecycle configuration
+    Lifecycle {
+        #[command(subcommand)]
+        action: BucketConfigAction,
+    },
+    /// Manage versioning state
+    Versioning {
+        #[command(subcommand)]
+        action: BucketConfigAction,
+    },
+    /// Manage bucket tags
+    Tagging {
+        #[command(subcommand)]
+        action: BucketConfigAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum BucketConfigAction {
+    /// Fetch the current configuration
+    Get {
+        /// Bucket name or s3:// path
+        bucket: String,
+
+        /// Write the document to a file instead of stdout
+        #[arg(long, short)]
+        file: Option<String>,
+
+        /// Document format (inferred from the file extension if omitted)
+        #[arg(long)]
+        format: Option<DocumentFormat>,
+    },
+    /// Apply a configuration document
+    Set {
+        /// Bucket name or s3:// path
+        bucket: String,
+
+        /// Document file ("-" for stdin)
+        file: String,
+
+        /// Document format (inferred from the file extension or content if omitted)
+        #[arg(long)]
+        format: Option<DocumentFormat>,
+    },
+    /// Remove the configuration
+    Delete {
+        /// Bucket name or s3:// path
+        bucket: String,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum)]
+pub enum DocumentFormat {
+    Json,
+    Xml,
+}
+
+#[derive(Subcommand)]
+pub enum AdminAction {
+    /// Manage users
+    User {
+        #[command(subcommand)]
+        action: AdminUserAction,
+    },
+    /// Admin roles of users
+    Role {
+        #[command(subcommand)]
+        action: AdminRoleAction,
+    },
+    /// Accounts (tenants) holding users and buckets
+    Account {
+        #[command(subcommand)]
+        action: AdminAccountAction,
+    },
+    /// Show server statistics
+    Stats,
+    /// Garbage collection
+    Gc {
+        #[command(subcommand)]
+        action: AdminGcAction,
+    },
+    /// Object integrity scrubbing
+    Scrub {
+        #[command(subcommand)]
+        action: AdminScrubAction,
+    },
+    /// Batch operations on the objects listed in a manifest
+    Batch {
+        #[command(subcommand)]
+        action: AdminBatchAction,
+    },
+    /// Remove old object versions and orphaned delete markers
+    Prune {
+        #[command(subcommand)]
+        action: AdminPruneAction,
+    },
+    /// Load objects into the server's read cache ahead of traffic
+    Cache {
+        #[command(subcommand)]
+        action: AdminCacheAction,
+    },
+    /// Re-wrap object data keys under a new master key
+    KeyRewrap {
+        #[command(subcommand)]
+        action: AdminKeyRewrapAction,
+    },
+    /// Access points: named entries to a bucket with their own policy and networks
+    AccessPoint {
+        #[command(subcommand)]
+        action: AdminAccessPointAction,
+    },
+    /// Cluster administration (cluster mode)
+    Cluster {
+        #[command(subcommand)]
+        action: AdminClusterAction,
+    },
+    /// Compare replicas and repair divergent objects (cluster mode)
+    Repair {
+        /// Only repair this bucket
+        #[arg(long)]
+        bucket: Option<String>,
+    },
+    /// Show or set a bucket's consistency level (cluster mode)
+    Consistency {
+        /// Bucket name
+        bucket: String,
+        /// New level
+        #[arg(value_parser = ["one", "quorum", "all"])]
+        level: Option<String>,
+        /// Revert to the cluster default
+        #[arg(long, conflicts_with = "level")]
+        clear: bool,
+    },
+    /// List storage backends, or show or set the backend of a bucket
+    Backend {
+        /// Bucket name; lists the backends when omitted
+        bucket: Option<String>,
+        /// Backend to store the bucket on
+        #[arg(requires = "bucket")]
+        backend: Option<String>,
+        /// Move the bucket back to the default storage
+        #[arg(long, requires = "bucket", conflicts_with = "backend")]
+        clear: bool,
+    },
+    /// Show or set the compression of new objects in a bucket
+    Compression {
+        /// Bucket name
+        bucket: String,
+        /// Codec for new objects
+        #[arg(value_parser = ["none", "zstd", "lz4"])]
+        codec: Option<String>,
+        /// Codec level (zstd: 1-22)
+        #[arg(long, requires = "codec")]
+        level: Option<i32>,
+        /// Stop compressing new objects
+        #[arg(long, conflicts_with = "codec")]
+        clear: bool,
+    },
+    /// List bandwidth limits, or show or set those of a bucket or access key
+    Bandwidth {
+        /// Bucket whose requests to limit
+        #[arg(long, group = "target")]
+        bucket: Option<String>,
+        /// Access key whose requests to limit
+        #[arg(long, group = "target")]
+        access_key: Option<String>,
+        /// Upload rate in bytes per second; 0 for unlimited
+        #[arg(long, requires = "target")]
+        ingress: Option<u64>,
+        /// Download rate in bytes per second; 0 for unlimited
+        #[arg(long, requires = "target")]
+        egress: Option<u64>,
+        /// Remove the limit
+        #[arg(long, requires = "target", conflicts_with_all = ["ingress", "egress"])]
+        clear: bool,
+    },
+    /// Export or import the server's metadata as a portable dump
+    Metadata {
+        #[command(subcommand)]
+        action: AdminMetadataAction,
+    },
+    /// Point-in-time bucket snapshots
+    Snapshot {
+        #[command(subcommand)]
+        action: AdminSnapshotAction,
+    },
+    /// Scheduled mirroring to external S3 endpoints
+    Mirror {
+        #[command(subcommand)]
+        action: AdminMirrorAction,
+    },
+    /// Logins of the SFTP gateway
+    Sftp {
+        #[command(subcommand)]
+        action: AdminSftpAction,
+    },
+    /// The object search index
+    Search {
+        #[command(subcommand)]
+        action: AdminSearchAction,
+    },
+    /// Record matching S3 requests into a bucket for `hafiz replay`
+    Capture {
+        #[command(subcommand)]
+        action: AdminCaptureAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminUserAction {
+    /// Create a user and print its credentials
+    Add {
+        /// Display name
+        name: String,
+
+        /// Email address
+        #[arg(long)]
+        email: Option<String>,
+
+        /// Policy to attach (repeatable)
+        #[arg(long = "policy")]
+        policies: Vec<String>,
+
+        /// Account to add the user to (default account when omitted)
+        #[arg(long)]
+        account: Option<String>,
+    },
+    /// List users
+    List,
+    /// Remove a user
+    Remove {
+        /// Access key of the user
+        access_key: String,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Enable a user
+    Enable {
+        /// Access key of the user
+        access_key: String,
+    },
+    /// Disable a user
+    Disable {
+        /// Access key of the user
+        access_key: String,
+    },
+    /// Issue a new key pair, keeping the old key valid for a grace period
+    Rotate {
+        /// Access key of the user
+        access_key: String,
+
+        /// Seconds the old key stays valid (server default when omitted, 0 retires it now)
+        #[arg(long)]
+        grace: Option<u64>,
+    },
+    /// List a user's keys with their last use
+    Keys {
+        /// Access key of the user
+        access_key: String,
+    },
+    /// Retire an old key before its grace period ends
+    RetireKey {
+        /// Current access key of the user
+        access_key: String,
+
+        /// The old access key to retire
+        old_access_key: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminRoleAction {
+    /// List users with an admin role
+    List,
+    /// Give a user an admin role, replacing the one it has
+    Grant {
+        /// Access key of the user
+        access_key: String,
+
+        /// viewer, bucket-operator, security-admin or super-admin
+        role: hafiz_core::types::AdminRole,
+    },
+    /// Take a user's admin role away
+    Revoke {
+        /// Access key of the user
+        access_key: String,
+    },
+    /// Show who the configured credentials are and their role
+    Whoami,
+}
+
+#[derive(Subcommand)]
+pub enum AdminAccountAction {
+    /// List accounts with their usage
+    List,
+    /// Show an account, its quota and usage
+    Show {
+        /// Account ID
+        id: String,
+    },
+    /// Create an account
+    Create {
+        /// Account ID: lowercase letters, numbers and hyphens
+        id: String,
+
+        /// Display name
+        #[arg(long)]
+        name: Option<String>,
+
+        #[command(flatten)]
+        quota: AccountQuotaArgs,
+    },
+    /// Change an account's quota; limits not given stay as they are
+    Quota {
+        /// Account ID
+        id: String,
+
+        #[command(flatten)]
+        quota: AccountQuotaArgs,
+    },
+    /// Refuse all requests of the account's users and to its buckets
+    Suspend {
+        /// Account ID
+        id: String,
+    },
+    /// Lift an account's suspension
+    Resume {
+        /// Account ID
+        id: String,
+    },
+    /// Encrypt the account's new SSE-S3 objects under a new key
+    RotateKey {
+        /// Account ID
+        id: String,
+    },
+    /// Remove an account without users or buckets
+    Remove {
+        /// Account ID
+        id: String,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Move a user to an account
+    Assign {
+        /// Access key of the user
+        access_key: String,
+
+        /// Account ID
+        account: String,
+    },
+}
+
+/// Account quota limits; 0 means unlimited
+#[derive(clap::Args)]
+pub struct AccountQuotaArgs {
+    /// Most buckets the account may have
+    #[arg(long)]
+    pub max_buckets: Option<u64>,
+
+    /// Most objects all of its buckets may hold
+    #[arg(long)]
+    pub max_objects: Option<u64>,
+
+    /// Most bytes all of its buckets may store
+    #[arg(long)]
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Subcommand)]
+pub enum AdminMetadataAction {
+    /// Write buckets, objects and their versions, tags, policies and users as JSON lines
+    Export {
+        /// File to write; stdout when omitted or -
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Load a dump, replacing buckets, objects and users with the same names
+    Import {
+        /// Dump file, or - for stdin
+        file: String,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminSnapshotAction {
+    /// Snapshot every version and delete marker of a bucket
+    Create {
+        /// Bucket name
+        bucket: String,
+
+        /// Note kept with the snapshot
+        #[arg(long, short)]
+        description: Option<String>,
+    },
+    /// List snapshots, oldest first
+    List {
+        /// Only snapshots of this bucket
+        bucket: Option<String>,
+    },
+    /// Show a snapshot
+    Info {
+        /// Snapshot ID
+        id: String,
+    },
+    /// Delete a snapshot and its data
+    Delete {
+        /// Snapshot ID
+        id: String,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Restore a snapshot into a new bucket
+    Restore {
+        /// Snapshot ID
+        id: String,
+
+        /// Bucket to create; must not exist
+        bucket: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminGcAction {
+    /// Abort abandoned multipart uploads and delete their parts
+    Run {
+        /// Only collect uploads older than this many hours (server default: 24)
+        #[arg(long)]
+        older_than_hours: Option<i64>,
+
+        /// Show what would be removed
+        #[arg(long)]
+        dryrun: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminScrubAction {
+    /// Show scrubber status and the findings of the last run
+    Status,
+    /// Re-hash stored objects against their ETags now
+    Run {
+        /// Only scrub this bucket
+        #[arg(long)]
+        bucket: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminMirrorAction {
+    /// Show mirror jobs, their next run and the outcome of the last one
+    Status,
+    /// Run a mirror job now
+    Run {
+        /// Job name
+        job: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminSearchAction {
+    /// Show the size of the index and the changes waiting for it
+    Status,
+    /// Empty the index and index every object again
+    Rebuild,
+}
+
+#[derive(Subcommand)]
+pub enum AdminCaptureAction {
+    /// Start recording requests; one capture runs at a time
+    Start {
+        /// Where to write the records (s3://bucket or s3://bucket/prefix/)
+        destination: String,
+
+        /// Only this operation, e.g. PutObject (repeatable)
+        #[arg(long = "operation")]
+        operations: Vec<String>,
+
+        /// Only requests to this bucket (repeatable)
+        #[arg(long = "bucket")]
+        buckets: Vec<String>,
+
+        /// Only requests signed with this access key
+        #[arg(long)]
+        principal: Option<String>,
+
+        /// Only requests answered with an error
+        #[arg(long)]
+        errors_only: bool,
+
+        /// Also record request and response bodies up to --max-body-size
+        #[arg(long)]
+        bodies: bool,
+
+        /// Largest body recorded, in bytes (server default: 65536)
+        #[arg(long)]
+        max_body_size: Option<usize>,
+
+        /// Stop after this many requests (server default: 1000)
+        #[arg(long)]
+        max_requests: Option<u64>,
+
+        /// Stop after this many seconds (server default: 3600)
+        #[arg(long)]
+        duration: Option<u64>,
+    },
+    /// Show the running or last capture
+    Status,
+    /// Stop the running capture
+    Stop,
+}
+
+#[derive(Subcommand)]
+pub enum AdminSftpAction {
+    /// List SFTP logins with their keys
+    List,
+    /// Create a login, or change the user and home of an existing one
+    Set {
+        /// SSH user name
+        username: String,
+        /// Access key of the user the login acts as
+        #[arg(long)]
+        access_key: String,
+        /// Directory the login is confined to: bucket or bucket/prefix
+        #[arg(long)]
+        home: String,
+    },
+    /// Remove a login and its keys
+    Remove {
+        /// SSH user name
+        username: String,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Let a login authenticate with the public keys in a file
+    AddKey {
+        /// SSH user name
+        username: String,
+        /// `.pub` or `authorized_keys` file ("-" for stdin)
+        file: String,
+    },
+    /// Remove a key of a login
+    RemoveKey {
+        /// SSH user name
+        username: String,
+        /// Key fingerprint, as shown by `list`
+        fingerprint: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminBatchAction {
+    /// List batch jobs, newest first
+    List,
+    /// Show the progress of a job
+    Status {
+        /// Job ID
+        job_id: String,
+    },
+    /// Stop a job once the objects in flight are done
+    Cancel {
+        /// Job ID
+        job_id: String,
+    },
+    /// Copy the listed objects into another bucket
+    Copy {
+        #[command(flatten)]
+        job: BatchJobArgs,
+
+        /// Destination bucket or s3:// path; a key is used as prefix
+        dest: String,
+    },
+    /// Replace the tags of the listed objects
+    Tag {
+        #[command(flatten)]
+        job: BatchJobArgs,
+
+        /// Tag as key=value (repeatable)
+        #[arg(long = "tag", required = true)]
+        tags: Vec<String>,
+    },
+    /// Delete the listed objects, or the versions named in the manifest
+    Delete {
+        #[command(flatten)]
+        job: BatchJobArgs,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminPruneAction {
+    /// List prune jobs, newest first
+    List,
+    /// Show the progress of a job
+    Status {
+        /// Job ID
+        job_id: String,
+    },
+    /// Stop a job once the key in progress is done
+    Cancel {
+        /// Job ID
+        job_id: String,
+    },
+    /// Start pruning a bucket, or the keys under s3://bucket/prefix
+    Run {
+        /// Bucket or s3:// path
+        path: String,
+
+        /// Remove versions that have been noncurrent for this many days
+        #[arg(long)]
+        older_than_days: Option<u32>,
+
+        /// Keep this many of the newest noncurrent versions of each key
+        #[arg(long)]
+        keep_last: Option<u32>,
+
+        /// Remove delete markers that no longer hide any version
+        #[arg(long)]
+        delete_markers: bool,
+
+        /// Only report what would be removed
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Follow the job's progress until it finishes
+        #[arg(long)]
+        wait: bool,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminCacheAction {
+    /// List warm-up jobs, newest first
+    List,
+    /// Show the progress of a warm-up job
+    Status {
+        /// Job ID
+        job_id: String,
+    },
+    /// Stop a warm-up job once the objects in flight are loaded
+    Cancel {
+        /// Job ID
+        job_id: String,
+    },
+    /// Load the objects under s3://bucket/prefix, or the listed keys, into the cache
+    Warmup {
+        /// Bucket or s3:// path
+        path: String,
+
+        /// File with one key per line to load instead of the prefix ("-" for stdin)
+        #[arg(long)]
+        keys_from: Option<String>,
+
+        /// Stop after loading this many bytes (server default: half the memory cache)
+        #[arg(long)]
+        max_bytes: Option<u64>,
+
+        /// Keep loading once the memory cache is full, evicting older entries
+        #[arg(long)]
+        evict: bool,
+
+        /// Objects loaded at once
+        #[arg(long, default_value = "4")]
+        concurrency: usize,
+
+        /// Follow the job's progress until it finishes
+        #[arg(long)]
+        wait: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum AdminKeyRewrapAction {
+    /// Show the progress of the last run
+    Status,
+    /// Start a run, or resume an interrupted one
+    Start {
+        /// Follow the run's progress until it stops
+        #[arg(long)]
+        wait: bool,
+    },
+    /// Pause the run; starting again resumes it
+    Cancel,
+}
+
+#[derive(Subcommand)]
+pub enum AdminAccessPointAction {
+    /// List access points
+    List {
+        /// Only those of this bucket
+        #[arg(long)]
+        bucket: Option<String>,
+    },
+    /// Create an access point for a bucket
+    Create {
+        /// Access point name
+        name: String,
+        /// Bucket it gives access to
+        bucket: String,
+        /// Only accept requests from this network, e.g. 10.0.0.0/8 (repeatable)
+        #[arg(long = "allow-cidr")]
+        allowed_cidrs: Vec<String>,
+        /// Policy document: a local file, or - for stdin
+        #[arg(long)]
+        policy: Option<String>,
+    },
+    /// Show an access point
+    Info {
+        /// Access point name
+        name: String,
+    },
+    /// Delete an access point
+    Delete {
+        /// Access point name
+        name: String,
+        /// Skip confirmation
+        #[arg(long, short)]
+        force: bool,
+    },
+    /// Set or remove the policy of an access point
+    Policy {
+        /// Access point name
+        name: String,
+        /// Policy document: a local file, or - for stdin
+        #[arg(required_unless_present = "clear")]
+        file: Option<String>,
+        /// Remove the policy
+        #[arg(long, conflicts_with = "file")]
+        clear: bool,
+    },
+}
+
+/// Options shared by batch job submissions
+#[derive(clap::Args)]
+pub struct BatchJobArgs {
+    /// CSV of bucket,key[,version_id] lines: an s3:// object, a local file, or - for stdin
+    pub manifest: String,
+
+    /// Write a completion report to this s3://bucket/prefix
+    #[arg(long)]
+    pub report: Option<String>,
+
+    /// Only list failed objects in the report
+    #[arg(long, requires = "report")]
+    pub failed_only: bool,
+
+    /// Objects worked on at once (server default: 16)
+    #[arg(long)]
+    pub concurrency: Option<usize>,
+
+    /// Retries of an object after a transient error (server default: 3)
+    #[arg(long)]
+    pub max_retries: Option<u32>,
+}
+
+#[derive(Subcommand)]
+pub enum AdminClusterAction {
+    /// List cluster nodes with their health
+    Nodes,
+    /// Show how replicas are spread across zones, racks and nodes
+    Topology,
+    /// Add a node by its cluster endpoint, or re-add a decommissioned one
+    Add {
+        /// Cluster endpoint of the node (e.g. http://node-2:9001)
+        endpoint: String,
+    },
+    /// Stop placing data on a node and move its data to the other nodes
+    Drain {
+        /// Node ID
+        node_id: String,
+    },
+    /// Return a drained node to service
+    Activate {
+        /// Node ID
+        node_id: String,
+    },
+    /// Remove a drained node from the cluster
+    Decommission {
+        /// Node ID
+        node_id: String,
+
+        /// Decommission even if the node is not drained or unreachable
+        #[arg(long)]
+        force: bool,
+
+        /// Skip confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+}
+
 #[tokio::main]
 async fn main() {
     if let Err(e) = run().await {
@@ -357,8 +1307,18 @@ async fn run() -> Result<()> {
             human_readable,
             recursive,
             summarize,
+            max_concurrency,
         } => {
-            commands::ls::execute(&ctx, &path, long, human_readable, recursive, summarize).await
+            commands::ls::execute(
+                &ctx,
+                &path,
+                long,
+                human_readable,
+                recursive,
+                summarize,
+                max_concurrency,
+            )
+            .await
         }
 
         Commands::Cp {
@@ -407,6 +1367,9 @@ async fn run() -> Result<()> {
             size_only,
             dryrun,
             parallel,
+            watch,
+            debounce_ms,
+            poll_interval,
         } => {
             commands::sync::execute(
                 &ctx,
@@ -419,6 +1382,9 @@ async fn run() -> Result<()> {
                     size_only,
                     dryrun,
                     parallel,
+                    watch,
+                    debounce_ms,
+                    poll_interval,
                 },
             )
             .await
@@ -448,7 +1414,12 @@ async fn run() -> Result<()> {
 
         Commands::Mb { bucket, region } => commands::mb::execute(&ctx, &bucket, region).await,
 
-        Commands::Rb { bucket, force } => commands::rb::execute(&ctx, &bucket, force).await,
+        Commands::Rb {
+            bucket,
+            force,
+            rate,
+            bypass_governance,
+        } => commands::rb::execute(&ctx, &bucket, force, rate, bypass_governance).await,
 
         Commands::Head { path } => commands::head::execute(&ctx, &path).await,
 
@@ -466,8 +1437,79 @@ async fn run() -> Result<()> {
             path,
             human_readable,
             summarize,
-        } => commands::du::execute(&ctx, &path, human_readable, summarize).await,
+            max_concurrency,
+        } => commands::du::execute(&ctx, &path, human_readable, summarize, max_concurrency).await,
 
         Commands::Cat { path } => commands::cat::execute(&ctx, &path).await,
+
+        Commands::Search {
+            query,
+            path,
+            limit,
+            human_readable,
+        } => commands::search::execute(&ctx, &query, path.as_deref(), limit, human_readable).await,
+
+        Commands::Replay {
+            source,
+            target,
+            target_access_key,
+            target_secret_key,
+            limit,
+            dry_run,
+        } => {
+            commands::replay::execute(
+                &ctx,
+                &source,
+                commands::replay::ReplayOptions {
+                    target,
+                    access_key: target_access_key,
+                    secret_key: target_secret_key,
+                    limit,
+                    dry_run,
+                },
+            )
+            .await
+        }
+
+        Commands::Bucket { action } => commands::bucket::execute(&ctx, action).await,
+
+        Commands::Admin { action } => commands::admin::execute(&ctx, action).await,
+
+        Commands::Server {
+            config,
+            overrides,
+            check_config,
+            migrate_layout,
+        } => {
+            commands::server::execute(
+                &ctx,
+                commands::server::ServerOptions {
+                    config,
+                    overrides,
+                    check_config,
+                    migrate_layout,
+                },
+            )
+            .await
+        }
+
+        #[cfg(feature = "mount")]
+        Commands::Mount {
+            path,
+            mountpoint,
+            read_only,
+            cache_ttl,
+        } => {
+            commands::mount::execute(
+                &ctx,
+                &path,
+                &mountpoint,
+                mount::MountOptions {
+                    read_only,
+                    cache_ttl: std::time::Duration::from_secs(cache_ttl),
+                },
+            )
+            .await
+        }
     }
 }
@@ -0,0 +1,157 @@
+//! Open files of a mount
+//!
+//! Reads are served from ranged GETs with read-ahead. Writes go to a local
+//! spool file that is uploaded, in parts when large, on flush or close.
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+use bytes::Bytes;
+
+/// Bytes fetched per GET when a read misses the buffer
+pub const READ_AHEAD: u64 = 4 * 1024 * 1024;
+
+pub enum Handle {
+    Read(ReadHandle),
+    Write(WriteHandle),
+}
+
+/// A file opened read-only
+pub struct ReadHandle {
+    pub ino: u64,
+    offset: u64,
+    buffer: Bytes,
+}
+
+impl ReadHandle {
+    pub fn new(ino: u64) -> Self {
+        Self {
+            ino,
+            offset: 0,
+            buffer: Bytes::new(),
+        }
+    }
+
+    /// Up to `size` bytes at `offset`, if the buffer holds all of them or
+    /// the object ends inside it
+    pub fn cached(&self, offset: u64, size: u64, object_size: u64) -> Option<Bytes> {
+        let end = (offset + size).min(object_size);
+        let buffer_end = self.offset + self.buffer.len() as u64;
+        if offset < self.offset || end > buffer_end || offset > end {
+            return None;
+        }
+        let start = (offset - self.offset) as usize;
+        Some(self.buffer.slice(start..(end - self.offset) as usize))
+    }
+
+    pub fn fill(&mut self, offset: u64, data: Bytes) {
+        self.offset = offset;
+        self.buffer = data;
+    }
+}
+
+/// A file opened for writing, spooled to a local file until uploaded
+pub struct WriteHandle {
+    pub ino: u64,
+    /// Whether the spool holds changes not yet uploaded
+    pub dirty: bool,
+    file: File,
+    path: PathBuf,
+}
+
+impl WriteHandle {
+    pub fn create(spool_dir: &Path, fh: u64, ino: u64) -> io::Result<Self> {
+        let path = spool_dir.join(format!("hafiz-mount-{}-{}", std::process::id(), fh));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Self {
+            ino,
+            dirty: false,
+            file,
+            path,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn size(&self) -> io::Result<u64> {
+        Ok(self.file.metadata()?.len())
+    }
+
+    pub fn set_len(&mut self, size: u64) -> io::Result<()> {
+        self.file.set_len(size)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.file.write_all_at(data, offset)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    pub fn read_at(&self, offset: u64, size: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0; size];
+        let mut read = 0;
+        while read < size {
+            match self.file.read_at(&mut buf[read..], offset + read as u64)? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        buf.truncate(read);
+        Ok(buf)
+    }
+}
+
+impl Drop for WriteHandle {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_buffer() {
+        let mut handle = ReadHandle::new(2);
+        assert!(handle.cached(0, 10, 100).is_none());
+
+        handle.fill(10, Bytes::from_static(b"0123456789"));
+        assert_eq!(handle.cached(12, 3, 100).unwrap(), "234");
+        assert_eq!(handle.cached(10, 10, 100).unwrap(), "0123456789");
+        assert!(handle.cached(15, 10, 100).is_none());
+        assert!(handle.cached(5, 3, 100).is_none());
+        // Short reads at the end of the object
+        assert_eq!(handle.cached(15, 10, 20).unwrap(), "56789");
+    }
+
+    #[test]
+    fn test_spool_file() {
+        let dir = std::env::temp_dir();
+        let mut handle = WriteHandle::create(&dir, u64::MAX, 2).unwrap();
+        assert!(!handle.dirty);
+
+        handle.write_at(0, b"hello world").unwrap();
+        handle.write_at(6, b"there").unwrap();
+        assert!(handle.dirty);
+        assert_eq!(handle.read_at(0, 100).unwrap(), b"hello there");
+
+        handle.set_len(5).unwrap();
+        assert_eq!(handle.size().unwrap(), 5);
+
+        let path = handle.path().to_path_buf();
+        drop(handle);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,167 @@
+//! Inode numbers and cached directory listings of a mount
+//!
+//! Paths and directories follow [`hafiz_core::directory`]: paths are
+//! relative to the mounted prefix and the root is `""`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use fuser::FileType;
+use hafiz_client::ListObjectsPage;
+use hafiz_core::directory::{is_within, DirEntry, EntryKind, Listing, ListingCache};
+
+pub const ROOT_INO: u64 = fuser::FUSE_ROOT_ID;
+
+pub fn file_type(kind: EntryKind) -> FileType {
+    match kind {
+        EntryKind::File => FileType::RegularFile,
+        EntryKind::Dir => FileType::Directory,
+    }
+}
+
+/// Inode table plus directory listings cached for the cache TTL
+pub struct Inodes {
+    paths: HashMap<u64, String>,
+    inos: HashMap<String, u64>,
+    next_ino: u64,
+    pub listings: ListingCache,
+}
+
+impl Inodes {
+    pub fn new(ttl: Duration) -> Self {
+        let mut inodes = Self {
+            paths: HashMap::new(),
+            inos: HashMap::new(),
+            next_ino: ROOT_INO + 1,
+            listings: ListingCache::new(ttl),
+        };
+        inodes.paths.insert(ROOT_INO, String::new());
+        inodes.inos.insert(String::new(), ROOT_INO);
+        inodes
+    }
+
+    /// Inode of `path`, allocating one on first use
+    pub fn ino(&mut self, path: &str) -> u64 {
+        if let Some(ino) = self.inos.get(path) {
+            return *ino;
+        }
+        let ino = self.next_ino;
+        self.next_ino += 1;
+        self.paths.insert(ino, path.to_string());
+        self.inos.insert(path.to_string(), ino);
+        ino
+    }
+
+    pub fn path(&self, ino: u64) -> Option<&str> {
+        self.paths.get(&ino).map(String::as_str)
+    }
+
+    /// Move the inodes of `from` and everything below it to `to`, keeping
+    /// their numbers
+    pub fn rename(&mut self, from: &str, to: &str) {
+        let moved: Vec<(String, u64)> = self
+            .inos
+            .iter()
+            .filter(|(path, _)| is_within(path, from))
+            .map(|(path, ino)| (path.clone(), *ino))
+            .collect();
+        for (path, ino) in moved {
+            let renamed = format!("{}{}", to, &path[from.len()..]);
+            self.inos.remove(&path);
+            if let Some(old) = self.inos.insert(renamed.clone(), ino) {
+                self.paths.remove(&old);
+            }
+            self.paths.insert(ino, renamed);
+        }
+    }
+
+    pub fn remove(&mut self, path: &str) {
+        if let Some(ino) = self.inos.remove(path) {
+            self.paths.remove(&ino);
+        }
+    }
+}
+
+/// Directory entries of a delimited listing of `prefix`
+pub fn listing(prefix: &str, page: &ListObjectsPage) -> Listing {
+    let mut listing = Listing::default();
+    for object in &page.objects {
+        let entry = DirEntry {
+            etag: object.etag.clone(),
+            ..DirEntry::file(object.size.max(0) as u64, object.last_modified)
+        };
+        listing.add_object(prefix, &object.key, entry);
+    }
+    for common in &page.common_prefixes {
+        listing.add_prefix(prefix, common);
+    }
+    listing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hafiz_client::Object;
+
+    fn object(key: &str, size: i64) -> Object {
+        Object {
+            key: key.to_string(),
+            size,
+            last_modified: None,
+            etag: None,
+            storage_class: None,
+        }
+    }
+
+    #[test]
+    fn test_listing_from_page() {
+        let page = ListObjectsPage {
+            objects: vec![
+                object("photos/", 0),
+                object("photos/a.jpg", 10),
+                object("photos/b", 3),
+            ],
+            common_prefixes: vec!["photos/2024/".to_string(), "photos/b/".to_string()],
+            ..Default::default()
+        };
+
+        let listing = listing("photos/", &page);
+        assert!(listing.has_marker);
+        let names: Vec<_> = listing.entries.keys().map(String::as_str).collect();
+        assert_eq!(names, ["2024", "a.jpg", "b"]);
+        assert_eq!(listing.entries["a.jpg"].kind, EntryKind::File);
+        assert_eq!(listing.entries["a.jpg"].size, 10);
+        assert!(listing.entries["2024"].is_dir());
+        assert!(listing.entries["b"].is_dir());
+    }
+
+    #[test]
+    fn test_inode_numbers_are_stable() {
+        let mut inodes = Inodes::new(Duration::from_secs(5));
+        assert_eq!(inodes.ino(""), ROOT_INO);
+
+        let a = inodes.ino("a");
+        assert_eq!(inodes.ino("a"), a);
+        assert_ne!(inodes.ino("b"), a);
+
+        inodes.rename("a", "c");
+        assert_eq!(inodes.ino("c"), a);
+        assert_eq!(inodes.path(a), Some("c"));
+
+        inodes.remove("c");
+        assert_eq!(inodes.path(a), None);
+    }
+
+    #[test]
+    fn test_rename_moves_children() {
+        let mut inodes = Inodes::new(Duration::from_secs(5));
+        let dir = inodes.ino("a");
+        let file = inodes.ino("a/b/f");
+        let other = inodes.ino("ab");
+
+        inodes.rename("a", "x/a");
+        assert_eq!(inodes.path(dir), Some("x/a"));
+        assert_eq!(inodes.path(file), Some("x/a/b/f"));
+        assert_eq!(inodes.path(other), Some("ab"));
+    }
+}
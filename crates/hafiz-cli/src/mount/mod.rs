@@ -0,0 +1,831 @@
+//! FUSE filesystem over a bucket, for `hafiz mount`
+//!
+//! Keys map to paths below the mounted prefix and `/`-delimited prefixes to
+//! directories, as in the server's WebDAV and SFTP gateways (see
+//! [`hafiz_core::directory`]). Directory listings double as the attribute
+//! cache: a lookup or getattr is answered from the parent's listing while
+//! it is younger than the cache TTL. FUSE calls arrive on the session
+//! thread and block on the CLI's Tokio runtime for S3 requests.
+
+mod handles;
+mod inodes;
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::Utc;
+use fuser::{
+    FileAttr, Filesystem, KernelConfig, ReplyAttr, ReplyCreate, ReplyData, ReplyDirectory,
+    ReplyEmpty, ReplyEntry, ReplyOpen, ReplyStatfs, ReplyWrite, Request, TimeOrNow,
+};
+use hafiz_client::{Client, GetObjectOptions, MultipartConfig, PutObjectOptions};
+use hafiz_core::directory::{
+    child_path, is_within, split_path, DirEntry, EntryKind, Listing, Tree, TreeCopy, TreeStore,
+};
+use libc::c_int;
+use tokio::runtime::Handle as Runtime;
+
+use handles::{Handle, ReadHandle, WriteHandle, READ_AHEAD};
+use inodes::{file_type, listing, Inodes};
+
+const BLOCK_SIZE: u32 = 4096;
+
+/// Objects between progress reports of a directory rename
+const RENAME_PROGRESS_EVERY: usize = 100;
+
+/// Settings of a mount
+#[derive(Debug, Clone)]
+pub struct MountOptions {
+    pub read_only: bool,
+    /// How long listings and attributes are trusted
+    pub cache_ttl: Duration,
+}
+
+pub struct HafizFs {
+    client: Client,
+    bucket: String,
+    /// Keys below the mounted prefix
+    tree: Tree,
+    runtime: Runtime,
+    multipart: MultipartConfig,
+    options: MountOptions,
+    inodes: Inodes,
+    handles: HashMap<u64, Handle>,
+    next_fh: u64,
+    spool_dir: PathBuf,
+    uid: u32,
+    gid: u32,
+}
+
+impl HafizFs {
+    pub fn new(
+        client: Client,
+        bucket: String,
+        prefix: String,
+        runtime: Runtime,
+        multipart: MultipartConfig,
+        options: MountOptions,
+    ) -> Self {
+        // SAFETY: getuid and getgid cannot fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            client,
+            bucket,
+            tree: Tree::new(&prefix),
+            runtime,
+            multipart,
+            inodes: Inodes::new(options.cache_ttl),
+            options,
+            handles: HashMap::new(),
+            next_fh: 1,
+            spool_dir: std::env::temp_dir(),
+            uid,
+            gid,
+        }
+    }
+
+    fn key(&self, path: &str) -> String {
+        self.tree.key(path)
+    }
+
+    /// Key prefix of the objects inside directory `dir`
+    fn dir_prefix(&self, dir: &str) -> String {
+        self.tree.dir_prefix(dir)
+    }
+
+    fn path(&self, ino: u64) -> Result<String, c_int> {
+        self.inodes
+            .path(ino)
+            .map(str::to_string)
+            .ok_or(libc::ENOENT)
+    }
+
+    fn writable(&self) -> Result<(), c_int> {
+        if self.options.read_only {
+            Err(libc::EROFS)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// List `dir` unless its cached listing is still fresh
+    fn load_dir(&mut self, dir: &str) -> Result<(), c_int> {
+        if self.inodes.listings.get(dir).is_some() {
+            return Ok(());
+        }
+        let prefix = self.dir_prefix(dir);
+        let request = self
+            .client
+            .list_objects(&self.bucket)
+            .prefix(&prefix)
+            .delimiter("/");
+        let page = self
+            .runtime
+            .block_on(request.collect())
+            .map_err(|e| errno(&e))?;
+        self.inodes.listings.insert(dir, listing(&prefix, &page));
+        Ok(())
+    }
+
+    /// Entry of `path`, including files created but not yet uploaded
+    fn entry(&mut self, path: &str) -> Result<DirEntry, c_int> {
+        if path.is_empty() {
+            return Ok(DirEntry::dir());
+        }
+        let (dir, name) = split_path(path);
+        self.load_dir(dir)?;
+        let ino = self.inodes.ino(path);
+        if let Some(entry) = self.spooled_entry(ino) {
+            return Ok(entry);
+        }
+        self.inodes
+            .listings
+            .get_stale(dir)
+            .and_then(|listing| listing.get(name))
+            .cloned()
+            .ok_or(libc::ENOENT)
+    }
+
+    /// The entry of a file open for writing, sized by its spool file
+    fn spooled_entry(&self, ino: u64) -> Option<DirEntry> {
+        self.handles.values().find_map(|handle| match handle {
+            Handle::Write(h) if h.ino == ino && h.dirty => h
+                .size()
+                .ok()
+                .map(|size| DirEntry::file(size, Some(Utc::now()))),
+            _ => None,
+        })
+    }
+
+    fn attr(&self, ino: u64, entry: &DirEntry) -> FileAttr {
+        let (perm, nlink) = match entry.kind {
+            EntryKind::File => (0o644, 1),
+            EntryKind::Dir => (0o755, 2),
+        };
+        let mtime = entry
+            .modified
+            .map(SystemTime::from)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        FileAttr {
+            ino,
+            size: entry.size,
+            blocks: entry.size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind: file_type(entry.kind),
+            perm,
+            nlink,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+
+    fn add_handle(&mut self, handle: Handle) -> u64 {
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        self.handles.insert(fh, handle);
+        fh
+    }
+
+    /// Open `path` for writing, starting from its current content unless
+    /// truncating
+    fn open_write(&mut self, ino: u64, path: &str, truncate: bool) -> Result<u64, c_int> {
+        let mut handle =
+            WriteHandle::create(&self.spool_dir, self.next_fh, ino).map_err(io_errno)?;
+        if truncate {
+            handle.dirty = true;
+        } else {
+            let key = self.key(path);
+            let spool = handle.path().to_path_buf();
+            let downloaded = self.runtime.block_on(async {
+                let object = self.client.get_object(&self.bucket, &key).await?;
+                let mut file = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .open(&spool)
+                    .await?;
+                object.body.write_to(&mut file).await
+            });
+            match downloaded {
+                Ok(_) => {}
+                // A file created by another writer and not yet listed
+                Err(e) if e.is_not_found() => handle.dirty = true,
+                Err(e) => return Err(errno(&e)),
+            }
+        }
+        Ok(self.add_handle(Handle::Write(handle)))
+    }
+
+    /// Upload the spool of write handle `fh` if it changed
+    fn upload(&mut self, fh: u64) -> Result<(), c_int> {
+        let (ino, spool) = match self.handles.get(&fh) {
+            Some(Handle::Write(h)) if h.dirty => (h.ino, h.path().to_path_buf()),
+            Some(_) => return Ok(()),
+            None => return Err(libc::EBADF),
+        };
+        let path = self.path(ino)?;
+        let key = self.key(&path);
+        self.runtime
+            .block_on(self.client.upload_file(
+                &self.bucket,
+                &key,
+                &spool,
+                &PutObjectOptions::default(),
+                &self.multipart,
+            ))
+            .map_err(|e| errno(&e))?;
+
+        let mut size = 0;
+        if let Some(Handle::Write(h)) = self.handles.get_mut(&fh) {
+            h.dirty = false;
+            size = h.size().map_err(io_errno)?;
+        }
+        self.inodes
+            .listings
+            .put_entry(&path, DirEntry::file(size, Some(Utc::now())));
+        Ok(())
+    }
+
+    fn read_object(&mut self, fh: u64, offset: u64, size: u64) -> Result<Bytes, c_int> {
+        let ino = match self.handles.get(&fh) {
+            Some(Handle::Write(h)) => {
+                return h
+                    .read_at(offset, size as usize)
+                    .map(Bytes::from)
+                    .map_err(io_errno);
+            }
+            Some(Handle::Read(h)) => h.ino,
+            None => return Err(libc::EBADF),
+        };
+        let path = self.path(ino)?;
+        let object_size = self.entry(&path)?.size;
+        if offset >= object_size {
+            return Ok(Bytes::new());
+        }
+        if let Some(Handle::Read(h)) = self.handles.get(&fh) {
+            if let Some(data) = h.cached(offset, size, object_size) {
+                return Ok(data);
+            }
+        }
+
+        let options = GetObjectOptions {
+            range: Some(offset..offset + size.max(READ_AHEAD)),
+            ..Default::default()
+        };
+        let key = self.key(&path);
+        let data = self
+            .runtime
+            .block_on(async {
+                let object = self
+                    .client
+                    .get_object_with(&self.bucket, &key, &options)
+                    .await?;
+                object.body.bytes().await
+            })
+            .map_err(|e| errno(&e))?;
+
+        let end = (size as usize).min(data.len());
+        let reply = data.slice(..end);
+        if let Some(Handle::Read(h)) = self.handles.get_mut(&fh) {
+            h.fill(offset, data);
+        }
+        Ok(reply)
+    }
+
+    fn create_dir(&mut self, parent: u64, name: &OsStr) -> Result<(u64, DirEntry), c_int> {
+        self.writable()?;
+        let path = child_path(&self.path(parent)?, name.to_str().ok_or(libc::EINVAL)?);
+        if self.entry(&path).is_ok() {
+            return Err(libc::EEXIST);
+        }
+        let marker = format!("{}/", self.key(&path));
+        self.runtime
+            .block_on(self.client.put_object(
+                &self.bucket,
+                &marker,
+                Bytes::new(),
+                &PutObjectOptions::default(),
+            ))
+            .map_err(|e| errno(&e))?;
+
+        self.inodes.listings.put_entry(&path, DirEntry::dir());
+        self.inodes.listings.insert(&path, Listing::created());
+        Ok((self.inodes.ino(&path), DirEntry::dir()))
+    }
+
+    /// Store the marker of the directory of `path` if removing `path` left
+    /// it empty, so the directory does not go away too
+    fn keep_parent(&mut self, path: &str) -> Result<(), c_int> {
+        let (dir, _) = split_path(path);
+        let prefix = self.dir_prefix(dir);
+        let survives = match self.inodes.listings.parent_survives(path) {
+            Some(survives) => survives,
+            None => {
+                let request = self
+                    .client
+                    .list_objects(&self.bucket)
+                    .prefix(&prefix)
+                    .max_keys(1);
+                let page = self
+                    .runtime
+                    .block_on(request.send())
+                    .map_err(|e| errno(&e))?;
+                !page.objects.is_empty() || !page.common_prefixes.is_empty()
+            }
+        };
+        if survives {
+            return Ok(());
+        }
+        self.runtime
+            .block_on(self.client.put_object(
+                &self.bucket,
+                &prefix,
+                Bytes::new(),
+                &PutObjectOptions::default(),
+            ))
+            .map_err(|e| errno(&e))?;
+        self.inodes.listings.insert(dir, Listing::created());
+        Ok(())
+    }
+
+    fn remove(&mut self, parent: u64, name: &OsStr, kind: EntryKind) -> Result<(), c_int> {
+        self.writable()?;
+        let path = child_path(&self.path(parent)?, name.to_str().ok_or(libc::EINVAL)?);
+        let entry = self.entry(&path)?;
+        let key = match (kind, entry.kind) {
+            (EntryKind::File, EntryKind::File) => self.key(&path),
+            (EntryKind::Dir, EntryKind::Dir) => {
+                self.load_dir(&path)?;
+                if self
+                    .inodes
+                    .listings
+                    .get_stale(&path)
+                    .is_some_and(|l| !l.is_empty())
+                {
+                    return Err(libc::ENOTEMPTY);
+                }
+                self.dir_prefix(&path)
+            }
+            (EntryKind::File, EntryKind::Dir) => return Err(libc::EISDIR),
+            (EntryKind::Dir, EntryKind::File) => return Err(libc::ENOTDIR),
+        };
+        self.runtime
+            .block_on(self.client.delete_object(&self.bucket, &key))
+            .map_err(|e| errno(&e))?;
+
+        self.keep_parent(&path)?;
+        self.inodes.listings.remove_entry(&path);
+        self.inodes.remove(&path);
+        Ok(())
+    }
+
+    fn rename_file(
+        &mut self,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+    ) -> Result<(), c_int> {
+        self.writable()?;
+        let from = child_path(&self.path(parent)?, name.to_str().ok_or(libc::EINVAL)?);
+        let to = child_path(
+            &self.path(new_parent)?,
+            new_name.to_str().ok_or(libc::EINVAL)?,
+        );
+        let entry = self.entry(&from)?;
+        match (entry.kind, self.entry(&to).ok().map(|e| e.kind)) {
+            (EntryKind::Dir, Some(EntryKind::File)) => return Err(libc::ENOTDIR),
+            (EntryKind::File, Some(EntryKind::Dir)) => return Err(libc::EISDIR),
+            (EntryKind::Dir, Some(EntryKind::Dir)) => {
+                self.load_dir(&to)?;
+                if self
+                    .inodes
+                    .listings
+                    .get_stale(&to)
+                    .is_some_and(|l| !l.is_empty())
+                {
+                    return Err(libc::ENOTEMPTY);
+                }
+            }
+            _ => {}
+        }
+        if entry.is_dir() && is_within(&to, &from) {
+            return Err(libc::EINVAL);
+        }
+
+        // A directory is renamed by copying and deleting every key below it
+        let plan = if entry.is_dir() {
+            let (from_prefix, to_prefix) = (self.dir_prefix(&from), self.dir_prefix(&to));
+            let request = self.client.list_objects(&self.bucket).prefix(&from_prefix);
+            let page = self
+                .runtime
+                .block_on(request.collect())
+                .map_err(|e| errno(&e))?;
+            let keys = page.objects.into_iter().map(|object| object.key);
+            TreeCopy::dir(&from_prefix, &to_prefix, keys, true)
+        } else {
+            TreeCopy::file(&self.key(&from), &self.key(&to), true)
+        };
+        let store = ClientTree {
+            client: &self.client,
+            bucket: &self.bucket,
+        };
+        let result = self.runtime.block_on(plan.run(&store, |progress| {
+            if progress.done % RENAME_PROGRESS_EVERY == 0 {
+                eprintln!(
+                    "Renaming {} to {}: {} of {} objects",
+                    from, to, progress.done, progress.total
+                );
+            }
+        }));
+
+        self.inodes.listings.invalidate_tree(&to);
+        if let Err(e) = result {
+            self.inodes.listings.invalidate_entry(&to);
+            self.inodes.listings.invalidate_entry(&from);
+            return Err(errno(&e));
+        }
+        self.inodes.listings.put_entry(&to, entry);
+        self.keep_parent(&from)?;
+        self.inodes.listings.remove_entry(&from);
+        self.inodes.rename(&from, &to);
+        Ok(())
+    }
+
+    /// Change the size of a file that is not open for writing
+    fn truncate(&mut self, ino: u64, size: u64) -> Result<(), c_int> {
+        let path = self.path(ino)?;
+        let fh = self.open_write(ino, &path, size == 0)?;
+        let resized = match self.handles.get_mut(&fh) {
+            Some(Handle::Write(h)) => h.set_len(size).map_err(io_errno),
+            _ => Err(libc::EBADF),
+        };
+        let uploaded = resized.and_then(|_| self.upload(fh));
+        self.handles.remove(&fh);
+        uploaded
+    }
+}
+
+impl Filesystem for HafizFs {
+    fn init(&mut self, _req: &Request<'_>, _config: &mut KernelConfig) -> Result<(), c_int> {
+        self.load_dir("")
+    }
+
+    /// Upload files still open at unmount
+    fn destroy(&mut self) {
+        let dirty: Vec<u64> = self
+            .handles
+            .iter()
+            .filter(|(_, h)| matches!(h, Handle::Write(w) if w.dirty))
+            .map(|(fh, _)| *fh)
+            .collect();
+        for fh in dirty {
+            if let Err(code) = self.upload(fh) {
+                eprintln!("Failed to upload an open file at unmount (errno {})", code);
+            }
+        }
+        self.handles.clear();
+    }
+
+    fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        let Some(name) = name.to_str() else {
+            return reply.error(libc::ENOENT);
+        };
+        let path = match self.path(parent) {
+            Ok(dir) => child_path(&dir, name),
+            Err(code) => return reply.error(code),
+        };
+        match self.entry(&path) {
+            Ok(entry) => {
+                let ino = self.inodes.ino(&path);
+                reply.entry(&self.options.cache_ttl, &self.attr(ino, &entry), 0);
+            }
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+        match self.path(ino).and_then(|path| self.entry(&path)) {
+            Ok(entry) => reply.attr(&self.options.cache_ttl, &self.attr(ino, &entry)),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    /// Only size changes are stored; S3 keeps no modes, owners or times
+    fn setattr(
+        &mut self,
+        req: &Request<'_>,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<TimeOrNow>,
+        _mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        if let Some(size) = size {
+            let resized =
+                self.writable()
+                    .and_then(|_| match fh.and_then(|fh| self.handles.get_mut(&fh)) {
+                        Some(Handle::Write(h)) => h.set_len(size).map_err(io_errno),
+                        Some(Handle::Read(_)) => Err(libc::EBADF),
+                        None => self.truncate(ino, size),
+                    });
+            if let Err(code) = resized {
+                return reply.error(code);
+            }
+        }
+        self.getattr(req, ino, reply);
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: ReplyEntry,
+    ) {
+        match self.create_dir(parent, name) {
+            Ok((ino, entry)) => reply.entry(&self.options.cache_ttl, &self.attr(ino, &entry), 0),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(parent, name, EntryKind::File) {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove(parent, name, EntryKind::Dir) {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        match self.rename_file(parent, name, new_parent, new_name) {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+        let opened = if flags & libc::O_ACCMODE == libc::O_RDONLY {
+            Ok(self.add_handle(Handle::Read(ReadHandle::new(ino))))
+        } else {
+            self.writable()
+                .and_then(|_| self.path(ino))
+                .and_then(|path| self.open_write(ino, &path, flags & libc::O_TRUNC != 0))
+        };
+        match opened {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyData,
+    ) {
+        match self.read_object(fh, offset.max(0) as u64, size as u64) {
+            Ok(data) => reply.data(&data),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        match self.handles.get_mut(&fh) {
+            Some(Handle::Write(h)) => match h.write_at(offset.max(0) as u64, data) {
+                Ok(()) => reply.written(data.len() as u32),
+                Err(e) => reply.error(io_errno(e)),
+            },
+            _ => reply.error(libc::EBADF),
+        }
+    }
+
+    /// Upload on every close of a changed file, so close() reports failures
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: ReplyEmpty,
+    ) {
+        match self.upload(fh) {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn fsync(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _datasync: bool,
+        reply: ReplyEmpty,
+    ) {
+        match self.upload(fh) {
+            Ok(()) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        let uploaded = self.upload(fh);
+        self.handles.remove(&fh);
+        match uploaded {
+            Ok(()) | Err(libc::EBADF) => reply.ok(),
+            Err(code) => reply.error(code),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let dir = match self.path(ino) {
+            Ok(dir) => dir,
+            Err(code) => return reply.error(code),
+        };
+        if let Err(code) = self.load_dir(&dir) {
+            return reply.error(code);
+        }
+
+        let parent = self.inodes.ino(split_path(&dir).0);
+        let children: Vec<(String, EntryKind)> = self
+            .inodes
+            .listings
+            .get_stale(&dir)
+            .map(|listing| &listing.entries)
+            .into_iter()
+            .flatten()
+            .skip((offset.max(2) - 2) as usize)
+            .map(|(name, entry)| (name.clone(), entry.kind))
+            .collect();
+
+        let mut entries = vec![
+            (ino, EntryKind::Dir, ".".to_string()),
+            (parent, EntryKind::Dir, "..".to_string()),
+        ];
+        entries.drain(..(offset.clamp(0, 2) as usize));
+        for (name, kind) in children {
+            let child = self.inodes.ino(&child_path(&dir, &name));
+            entries.push((child, kind, name));
+        }
+
+        let first = offset.max(0);
+        for (i, (ino, kind, name)) in entries.into_iter().enumerate() {
+            // The offset passed back is that of the next entry
+            if reply.add(ino, first + i as i64 + 1, file_type(kind), name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn statfs(&mut self, _req: &Request<'_>, _ino: u64, reply: ReplyStatfs) {
+        // Buckets have no fixed capacity; report plenty of free space
+        const BLOCKS: u64 = 1 << 40;
+        reply.statfs(
+            BLOCKS,
+            BLOCKS,
+            BLOCKS,
+            u64::MAX >> 1,
+            u64::MAX >> 1,
+            BLOCK_SIZE,
+            1024,
+            BLOCK_SIZE,
+        );
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        _flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let created = self.writable().and_then(|_| {
+            let path = child_path(&self.path(parent)?, name.to_str().ok_or(libc::EINVAL)?);
+            let ino = self.inodes.ino(&path);
+            let fh = self.open_write(ino, &path, true)?;
+            let entry = DirEntry::file(0, Some(Utc::now()));
+            self.inodes.listings.put_entry(&path, entry.clone());
+            Ok((ino, fh, entry))
+        });
+        match created {
+            Ok((ino, fh, entry)) => {
+                reply.created(&self.options.cache_ttl, &self.attr(ino, &entry), 0, fh, 0)
+            }
+            Err(code) => reply.error(code),
+        }
+    }
+}
+
+/// Objects of a directory rename, copied within the mounted bucket
+struct ClientTree<'a> {
+    client: &'a Client,
+    bucket: &'a str,
+}
+
+#[async_trait]
+impl TreeStore for ClientTree<'_> {
+    type Error = hafiz_client::Error;
+
+    async fn copy_object(&self, from: &str, to: &str) -> Result<(), Self::Error> {
+        self.client
+            .copy_object(
+                self.bucket,
+                from,
+                self.bucket,
+                to,
+                &PutObjectOptions::default(),
+            )
+            .await
+            .map(|_| ())
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), Self::Error> {
+        self.client.delete_object(self.bucket, key).await
+    }
+}
+
+/// errno for a failed S3 request
+fn errno(e: &hafiz_client::Error) -> c_int {
+    match e.status() {
+        Some(404) => libc::ENOENT,
+        Some(403) => libc::EACCES,
+        Some(409) => libc::EEXIST,
+        Some(507) => libc::ENOSPC,
+        _ => libc::EIO,
+    }
+}
+
+fn io_errno(e: std::io::Error) -> c_int {
+    e.raw_os_error().unwrap_or(libc::EIO)
+}
@@ -2,29 +2,35 @@
 
 use crate::config::Config;
 use anyhow::{Context, Result};
-use aws_config::Region;
-use aws_credential_types::Credentials;
-use aws_sdk_s3::config::Builder as S3ConfigBuilder;
-use aws_sdk_s3::Client;
+use hafiz_client::{Client, Credentials, MultipartConfig};
+use std::time::Duration;
 
 /// Create an S3 client from configuration
-pub async fn create_client(config: &Config) -> Result<Client> {
+pub fn create_client(config: &Config) -> Result<Client> {
     config.validate()?;
 
     let endpoint = config.endpoint.as_ref().unwrap();
     let access_key = config.access_key.as_ref().unwrap();
     let secret_key = config.secret_key.as_ref().unwrap();
 
-    let credentials = Credentials::new(access_key, secret_key, None, None, "hafiz-cli");
+    let client = Client::builder(endpoint.as_str())
+        .region(config.region.clone())
+        .credentials(Credentials::new(access_key.as_str(), secret_key.as_str()))
+        .path_style(config.path_style)
+        .timeout(Duration::from_secs(config.timeout))
+        .build()
+        .with_context(|| format!("Invalid endpoint: {}", endpoint))?;
 
-    let s3_config = S3ConfigBuilder::new()
-        .region(Region::new(config.region.clone()))
-        .credentials_provider(credentials)
-        .endpoint_url(endpoint)
-        .force_path_style(config.path_style)
-        .build();
+    Ok(client)
+}
 
-    Ok(Client::from_conf(s3_config))
+/// Multipart settings from configuration
+pub fn multipart_config(config: &Config) -> MultipartConfig {
+    MultipartConfig {
+        threshold: config.multipart_threshold,
+        part_size: config.multipart_chunksize,
+        concurrency: config.max_concurrent_requests,
+    }
 }
 
 /// Parse an S3 URI into bucket and key components
@@ -0,0 +1,158 @@
+//! Request and response bodies
+//!
+//! Uploads take a [`Body`]: bytes in memory, which are hashed and signed,
+//! or a stream of known length, such as a file, which is sent as it is
+//! read with an unsigned payload. Downloads return an [`ObjectBody`] that
+//! streams the response.
+
+use std::path::Path;
+
+use bytes::Bytes;
+use futures::{Stream, TryStream, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::error::Result;
+use crate::signing::{sha256_hex, UNSIGNED_PAYLOAD};
+
+/// Body of an upload
+pub struct Body {
+    inner: Inner,
+}
+
+enum Inner {
+    Bytes(Bytes),
+    Stream { body: reqwest::Body, len: u64 },
+}
+
+impl Body {
+    pub fn empty() -> Self {
+        Self::from(Bytes::new())
+    }
+
+    /// Stream the file at `path`
+    pub async fn from_path(path: impl AsRef<Path>) -> Result<Self> {
+        let file = tokio::fs::File::open(path).await?;
+        let len = file.metadata().await?.len();
+        Ok(Self::from_reader(file, len))
+    }
+
+    /// Stream `len` bytes from `reader`
+    pub fn from_reader<R>(reader: R, len: u64) -> Self
+    where
+        R: AsyncRead + Send + Sync + 'static,
+    {
+        Self::from_stream(ReaderStream::new(reader), len)
+    }
+
+    /// Stream `len` bytes of chunks from `stream`
+    pub fn from_stream<S>(stream: S, len: u64) -> Self
+    where
+        S: TryStream + Send + Sync + 'static,
+        S::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+        Bytes: From<S::Ok>,
+    {
+        Self {
+            inner: Inner::Stream {
+                body: reqwest::Body::wrap_stream(stream),
+                len,
+            },
+        }
+    }
+
+    /// Length in bytes
+    pub fn len(&self) -> u64 {
+        match &self.inner {
+            Inner::Bytes(bytes) => bytes.len() as u64,
+            Inner::Stream { len, .. } => *len,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// `x-amz-content-sha256` to sign the body with
+    pub(crate) fn payload_hash(&self) -> String {
+        match &self.inner {
+            Inner::Bytes(bytes) => sha256_hex(bytes),
+            Inner::Stream { .. } => UNSIGNED_PAYLOAD.to_string(),
+        }
+    }
+
+    pub(crate) fn into_reqwest(self) -> reqwest::Body {
+        match self.inner {
+            Inner::Bytes(bytes) => reqwest::Body::from(bytes),
+            Inner::Stream { body, .. } => body,
+        }
+    }
+}
+
+impl From<Bytes> for Body {
+    fn from(bytes: Bytes) -> Self {
+        Self {
+            inner: Inner::Bytes(bytes),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::from(Bytes::from(bytes))
+    }
+}
+
+impl From<String> for Body {
+    fn from(text: String) -> Self {
+        Self::from(Bytes::from(text))
+    }
+}
+
+impl From<&'static [u8]> for Body {
+    fn from(bytes: &'static [u8]) -> Self {
+        Self::from(Bytes::from_static(bytes))
+    }
+}
+
+impl From<&'static str> for Body {
+    fn from(text: &'static str) -> Self {
+        Self::from(Bytes::from_static(text.as_bytes()))
+    }
+}
+
+/// Body of a downloaded object, read as it arrives
+pub struct ObjectBody {
+    response: reqwest::Response,
+}
+
+impl ObjectBody {
+    pub(crate) fn new(response: reqwest::Response) -> Self {
+        Self { response }
+    }
+
+    /// Read the whole body into memory
+    pub async fn bytes(self) -> Result<Bytes> {
+        Ok(self.response.bytes().await?)
+    }
+
+    /// Chunks of the body
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes>> + Send {
+        self.response.bytes_stream().map_err(Into::into)
+    }
+
+    /// The body as an [`AsyncRead`]
+    pub fn into_async_read(self) -> impl AsyncRead + Send + Unpin {
+        StreamReader::new(Box::pin(self.response.bytes_stream().map_err(std::io::Error::other)))
+    }
+
+    /// Copy the body into `writer`, returning the number of bytes written
+    pub async fn write_to<W>(self, writer: &mut W) -> Result<u64>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut reader = self.into_async_read();
+        let written = tokio::io::copy(&mut reader, writer).await?;
+        writer.flush().await?;
+        Ok(written)
+    }
+}
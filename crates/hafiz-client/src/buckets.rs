@@ -0,0 +1,175 @@
+//! Bucket operations
+
+use reqwest::Method;
+use serde::Deserialize;
+
+use crate::client::{Client, S3Request};
+use crate::error::Result;
+use crate::types::{Bucket, BucketConfig, BucketVersioning};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListAllMyBucketsResult {
+    #[serde(default)]
+    buckets: BucketList,
+}
+
+#[derive(Default, Deserialize)]
+struct BucketList {
+    #[serde(rename = "Bucket", default)]
+    buckets: Vec<Bucket>,
+}
+
+#[derive(Deserialize)]
+struct LocationConstraint {
+    #[serde(rename = "$text")]
+    location: Option<String>,
+}
+
+impl Client {
+    pub async fn list_buckets(&self) -> Result<Vec<Bucket>> {
+        let result: ListAllMyBucketsResult = self.send_xml(S3Request::new(Method::GET)).await?;
+        Ok(result.buckets.buckets)
+    }
+
+    /// Create a bucket, in `location` if given
+    pub async fn create_bucket(&self, bucket: &str, location: Option<&str>) -> Result<()> {
+        let mut request = S3Request::new(Method::PUT).bucket(bucket);
+        if let Some(location) = location {
+            request = request.body(format!(
+                "<CreateBucketConfiguration><LocationConstraint>{}</LocationConstraint></CreateBucketConfiguration>",
+                xml_escape(location)
+            ));
+        }
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Delete an empty bucket
+    pub async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        self.send(S3Request::new(Method::DELETE).bucket(bucket)).await?;
+        Ok(())
+    }
+
+    /// Whether the bucket exists and is accessible
+    pub async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        match self.send(S3Request::new(Method::HEAD).bucket(bucket)).await {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_not_found() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Location constraint; `None` for `us-east-1`
+    pub async fn bucket_location(&self, bucket: &str) -> Result<Option<String>> {
+        let request = S3Request::new(Method::GET).bucket(bucket).query("location", "");
+        let result: LocationConstraint = self.send_xml(request).await?;
+        Ok(result.location.filter(|l| !l.is_empty()))
+    }
+
+    pub async fn bucket_versioning(&self, bucket: &str) -> Result<BucketVersioning> {
+        let request = S3Request::new(Method::GET).bucket(bucket).query("versioning", "");
+        self.send_xml(request).await
+    }
+
+    /// Enable or suspend versioning; `status` is `Enabled` or `Suspended`
+    pub async fn put_bucket_versioning(&self, bucket: &str, status: &str) -> Result<()> {
+        let document = format!(
+            "<VersioningConfiguration><Status>{}</Status></VersioningConfiguration>",
+            xml_escape(status)
+        );
+        self.put_bucket_config(bucket, BucketConfig::Versioning, document).await
+    }
+
+    /// A bucket configuration document as the server returns it: XML, or
+    /// JSON for the policy
+    pub async fn get_bucket_config(&self, bucket: &str, config: BucketConfig) -> Result<String> {
+        let request = S3Request::new(Method::GET)
+            .bucket(bucket)
+            .query(config.subresource(), "");
+        Ok(self.send(request).await?.text().await?)
+    }
+
+    /// Replace a bucket configuration document
+    pub async fn put_bucket_config(
+        &self,
+        bucket: &str,
+        config: BucketConfig,
+        document: impl Into<String>,
+    ) -> Result<()> {
+        let document = document.into();
+        let content_type = match config {
+            BucketConfig::Policy => "application/json",
+            _ => "application/xml",
+        };
+        let request = S3Request::new(Method::PUT)
+            .bucket(bucket)
+            .query(config.subresource(), "")
+            .header("content-type", content_type)
+            .header("content-md5", content_md5(document.as_bytes()))
+            .body(document);
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Remove a bucket configuration document
+    pub async fn delete_bucket_config(&self, bucket: &str, config: BucketConfig) -> Result<()> {
+        let request = S3Request::new(Method::DELETE)
+            .bucket(bucket)
+            .query(config.subresource(), "");
+        self.send(request).await?;
+        Ok(())
+    }
+}
+
+/// Base64 MD5 of a body, as `Content-MD5` wants it
+pub(crate) fn content_md5(body: &[u8]) -> String {
+    use base64::{engine::general_purpose::STANDARD, Engine as _};
+    use md5::{Digest, Md5};
+
+    STANDARD.encode(Md5::digest(body))
+}
+
+pub(crate) fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_buckets() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ListAllMyBucketsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                <Owner><ID>hafiz</ID><DisplayName>hafiz</DisplayName></Owner>
+                <Buckets>
+                    <Bucket><Name>logs</Name><CreationDate>2024-01-02T03:04:05.000Z</CreationDate></Bucket>
+                    <Bucket><Name>photos</Name><CreationDate>2024-02-03T04:05:06.000Z</CreationDate></Bucket>
+                </Buckets>
+            </ListAllMyBucketsResult>"#;
+        let result: ListAllMyBucketsResult = quick_xml::de::from_str(body).unwrap();
+        let names: Vec<_> = result.buckets.buckets.iter().map(|b| b.name.as_str()).collect();
+        assert_eq!(names, ["logs", "photos"]);
+        assert!(result.buckets.buckets[0].creation_date.is_some());
+
+        let empty: ListAllMyBucketsResult =
+            quick_xml::de::from_str("<ListAllMyBucketsResult><Buckets/></ListAllMyBucketsResult>").unwrap();
+        assert!(empty.buckets.buckets.is_empty());
+
+        let location: LocationConstraint =
+            quick_xml::de::from_str("<LocationConstraint>eu-west-1</LocationConstraint>").unwrap();
+        assert_eq!(location.location.as_deref(), Some("eu-west-1"));
+        let location: LocationConstraint = quick_xml::de::from_str("<LocationConstraint/>").unwrap();
+        assert_eq!(location.location, None);
+    }
+
+    #[test]
+    fn test_content_md5() {
+        assert_eq!(content_md5(b""), "1B2M2Y8AsgTpgAmY7PhCfg==");
+    }
+}
@@ -0,0 +1,387 @@
+//! Client configuration and request plumbing
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use reqwest::{Method, Response};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::body::Body;
+use crate::error::{service_error, Error, Result};
+use crate::signing::{authorization, canonical_query, uri_encode, SigningParams};
+
+const DEFAULT_REGION: &str = "us-east-1";
+
+/// Access key and secret to sign requests with
+#[derive(Clone)]
+pub struct Credentials {
+    pub access_key: String,
+    pub secret_key: String,
+    /// Token of temporary credentials
+    pub session_token: Option<String>,
+}
+
+impl Credentials {
+    pub fn new(access_key: impl Into<String>, secret_key: impl Into<String>) -> Self {
+        Self {
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            session_token: None,
+        }
+    }
+
+    pub fn with_session_token(mut self, token: impl Into<String>) -> Self {
+        self.session_token = Some(token.into());
+        self
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("access_key", &self.access_key)
+            .field("secret_key", &"***")
+            .field("session_token", &self.session_token.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Builder for a [`Client`]
+pub struct ClientBuilder {
+    endpoint: String,
+    region: String,
+    credentials: Option<Credentials>,
+    path_style: bool,
+    timeout: Option<Duration>,
+}
+
+impl ClientBuilder {
+    /// Region to sign for; defaults to `us-east-1`
+    pub fn region(mut self, region: impl Into<String>) -> Self {
+        self.region = region.into();
+        self
+    }
+
+    pub fn credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Address buckets as `endpoint/bucket` (the default) rather than
+    /// `bucket.endpoint`
+    pub fn path_style(mut self, path_style: bool) -> Self {
+        self.path_style = path_style;
+        self
+    }
+
+    /// Timeout of each request, body included
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn build(self) -> Result<Client> {
+        let endpoint = Url::parse(&self.endpoint)
+            .map_err(|e| Error::InvalidArgument(format!("Invalid endpoint {}: {}", self.endpoint, e)))?;
+        if !matches!(endpoint.scheme(), "http" | "https") || endpoint.host_str().is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "Endpoint must be an http(s) URL: {}",
+                self.endpoint
+            )));
+        }
+        let credentials = self
+            .credentials
+            .ok_or_else(|| Error::InvalidArgument("Credentials are required".into()))?;
+
+        let mut http = reqwest::Client::builder().user_agent(concat!("hafiz-client/", env!("CARGO_PKG_VERSION")));
+        if let Some(timeout) = self.timeout {
+            http = http.timeout(timeout);
+        }
+
+        Ok(Client {
+            inner: Arc::new(Inner {
+                http: http.build()?,
+                endpoint,
+                region: self.region,
+                credentials,
+                path_style: self.path_style,
+            }),
+        })
+    }
+}
+
+/// Async S3 client; cheap to clone
+#[derive(Clone)]
+pub struct Client {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    http: reqwest::Client,
+    endpoint: Url,
+    region: String,
+    credentials: Credentials,
+    path_style: bool,
+}
+
+impl fmt::Debug for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Client")
+            .field("endpoint", &self.inner.endpoint.as_str())
+            .field("region", &self.inner.region)
+            .field("path_style", &self.inner.path_style)
+            .finish()
+    }
+}
+
+impl Client {
+    /// Builder for a client of the server at `endpoint`, e.g.
+    /// `http://localhost:9000`
+    pub fn builder(endpoint: impl Into<String>) -> ClientBuilder {
+        ClientBuilder {
+            endpoint: endpoint.into(),
+            region: DEFAULT_REGION.to_string(),
+            credentials: None,
+            path_style: true,
+            timeout: None,
+        }
+    }
+
+    pub fn endpoint(&self) -> &Url {
+        &self.inner.endpoint
+    }
+
+    pub fn region(&self) -> &str {
+        &self.inner.region
+    }
+
+    pub(crate) fn signing_params(&self) -> SigningParams<'_> {
+        SigningParams {
+            credentials: &self.inner.credentials,
+            region: &self.inner.region,
+            time: Utc::now(),
+        }
+    }
+
+    /// Host (with any non-default port) and encoded path of a bucket or
+    /// object
+    pub(crate) fn target(&self, bucket: Option<&str>, key: Option<&str>) -> (String, String) {
+        let endpoint = &self.inner.endpoint;
+        let mut host = endpoint.host_str().unwrap_or_default().to_string();
+        if let Some(port) = endpoint.port() {
+            host = format!("{}:{}", host, port);
+        }
+        let key = key.map(|k| uri_encode(k, false)).unwrap_or_default();
+
+        match bucket {
+            Some(bucket) if self.inner.path_style => (host, format!("/{}/{}", bucket, key)),
+            Some(bucket) => (format!("{}.{}", bucket, host), format!("/{}", key)),
+            None => (host, "/".to_string()),
+        }
+    }
+
+    /// URL for a host, encoded path and canonical query
+    pub(crate) fn url(&self, host: &str, path: &str, query: &str) -> Result<Url> {
+        let mut url = format!("{}://{}{}", self.inner.endpoint.scheme(), host, path);
+        if !query.is_empty() {
+            url.push('?');
+            url.push_str(query);
+        }
+        Url::parse(&url).map_err(|e| Error::InvalidArgument(format!("Invalid request URL {}: {}", url, e)))
+    }
+
+    /// Sign and send a request; error responses become [`Error::Service`]
+    pub(crate) async fn send(&self, request: S3Request<'_>) -> Result<Response> {
+        let response = self.dispatch(request).await?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+
+        let status = response.status();
+        let request_id = header(&response, "x-amz-request-id");
+        let body = response.text().await.unwrap_or_default();
+        Err(service_error(status, request_id, &body))
+    }
+
+    /// Sign and send a request, whatever the response
+    pub(crate) async fn dispatch(&self, request: S3Request<'_>) -> Result<Response> {
+        let (host, path) = self.target(request.bucket, request.key);
+        let query = canonical_query(&request.query);
+        let url = self.url(&host, &path, &query)?;
+
+        let params = self.signing_params();
+        let payload_hash = request.body.payload_hash();
+        let mut headers = request.headers;
+        headers.insert("host".into(), host);
+        headers.insert("x-amz-date".into(), params.amz_date());
+        headers.insert("x-amz-content-sha256".into(), payload_hash.clone());
+        if request.method == Method::PUT || request.method == Method::POST {
+            headers.insert("content-length".into(), request.body.len().to_string());
+        }
+        if let Some(token) = &self.inner.credentials.session_token {
+            headers.insert("x-amz-security-token".into(), token.clone());
+        }
+        let authorization = authorization(request.method.as_str(), &path, &query, &headers, &payload_hash, &params);
+
+        let mut builder = self
+            .inner
+            .http
+            .request(request.method, url)
+            .header("authorization", authorization);
+        for (name, value) in &headers {
+            // reqwest derives Host from the URL
+            if name != "host" {
+                builder = builder.header(name.as_str(), value.as_str());
+            }
+        }
+
+        Ok(builder.body(request.body.into_reqwest()).send().await?)
+    }
+
+    /// Send a request and parse its XML response
+    ///
+    /// Some operations answer `200 OK` with an `<Error>` document, e.g.
+    /// when CompleteMultipartUpload fails after the response started.
+    pub(crate) async fn send_xml<T: DeserializeOwned>(&self, request: S3Request<'_>) -> Result<T> {
+        let response = self.send(request).await?;
+        let status = response.status();
+        let body = response.text().await?;
+        parse_xml(status, &body)
+    }
+}
+
+pub(crate) fn parse_xml<T: DeserializeOwned>(status: reqwest::StatusCode, body: &str) -> Result<T> {
+    if let Some(root) = body.split('<').find(|s| !s.starts_with('?') && !s.trim().is_empty()) {
+        if root.starts_with("Error>") || root.starts_with("Error ") {
+            return Err(service_error(status, None, body));
+        }
+    }
+    quick_xml::de::from_str(body).map_err(|e| Error::InvalidResponse(format!("Invalid XML response: {}", e)))
+}
+
+/// Value of a response header
+pub(crate) fn header(response: &Response, name: &str) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+/// One S3 request before signing
+pub(crate) struct S3Request<'a> {
+    method: Method,
+    bucket: Option<&'a str>,
+    key: Option<&'a str>,
+    query: Vec<(String, String)>,
+    headers: BTreeMap<String, String>,
+    body: Body,
+}
+
+impl<'a> S3Request<'a> {
+    pub(crate) fn new(method: Method) -> Self {
+        Self {
+            method,
+            bucket: None,
+            key: None,
+            query: Vec::new(),
+            headers: BTreeMap::new(),
+            body: Body::empty(),
+        }
+    }
+
+    pub(crate) fn bucket(mut self, bucket: &'a str) -> Self {
+        self.bucket = Some(bucket);
+        self
+    }
+
+    pub(crate) fn key(mut self, key: &'a str) -> Self {
+        self.key = Some(key);
+        self
+    }
+
+    /// Add a query parameter; subresources such as `?cors` take an empty
+    /// value
+    pub(crate) fn query(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.query.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub(crate) fn query_opt(self, name: &str, value: Option<impl Into<String>>) -> Self {
+        match value {
+            Some(value) => self.query(name, value),
+            None => self,
+        }
+    }
+
+    /// Add a header; `name` must be lowercase
+    pub(crate) fn header(mut self, name: &str, value: impl Into<String>) -> Self {
+        self.headers.insert(name.to_string(), value.into());
+        self
+    }
+
+    pub(crate) fn header_opt(self, name: &str, value: Option<impl Into<String>>) -> Self {
+        match value {
+            Some(value) => self.header(name, value),
+            None => self,
+        }
+    }
+
+    pub(crate) fn body(mut self, body: impl Into<Body>) -> Self {
+        self.body = body.into();
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(endpoint: &str, path_style: bool) -> Client {
+        Client::builder(endpoint)
+            .credentials(Credentials::new("AKID", "secret"))
+            .path_style(path_style)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_target_addressing() {
+        let path = client("http://localhost:9000", true);
+        assert_eq!(
+            path.target(Some("photos"), Some("2024/a b.jpg")),
+            ("localhost:9000".to_string(), "/photos/2024/a%20b.jpg".to_string())
+        );
+        assert_eq!(path.target(Some("photos"), None).1, "/photos/");
+        assert_eq!(path.target(None, None), ("localhost:9000".to_string(), "/".to_string()));
+
+        let virtual_host = client("https://s3.example.com", false);
+        assert_eq!(
+            virtual_host.target(Some("photos"), Some("a.jpg")),
+            ("photos.s3.example.com".to_string(), "/a.jpg".to_string())
+        );
+    }
+
+    #[test]
+    fn test_builder_rejects_bad_endpoints() {
+        assert!(Client::builder("localhost:9000")
+            .credentials(Credentials::new("a", "b"))
+            .build()
+            .is_err());
+        assert!(Client::builder("http://localhost:9000").build().is_err());
+    }
+
+    #[test]
+    fn test_parse_xml_error_in_ok_response() {
+        #[derive(Debug, serde::Deserialize)]
+        struct Anything {}
+
+        let body = r#"<?xml version="1.0"?><Error><Code>InternalError</Code><Message>boom</Message></Error>"#;
+        let err = parse_xml::<Anything>(reqwest::StatusCode::OK, body).unwrap_err();
+        assert_eq!(err.code(), Some("InternalError"));
+    }
+}
@@ -0,0 +1,124 @@
+//! Client errors
+
+use reqwest::StatusCode;
+use serde::Deserialize;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The request could not be sent or its response not read
+    #[error("HTTP request failed: {0}")]
+    Http(#[from] reqwest::Error),
+
+    /// The server answered with an S3 error
+    #[error("{code}: {message} (HTTP {status})")]
+    Service {
+        status: u16,
+        code: String,
+        message: String,
+        request_id: Option<String>,
+    },
+
+    #[error("Invalid response: {0}")]
+    InvalidResponse(String),
+
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl Error {
+    /// S3 error code, e.g. `NoSuchKey`
+    pub fn code(&self) -> Option<&str> {
+        match self {
+            Self::Service { code, .. } => Some(code),
+            _ => None,
+        }
+    }
+
+    /// HTTP status of a service error
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Service { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether the bucket, object or upload does not exist
+    pub fn is_not_found(&self) -> bool {
+        self.status() == Some(404)
+    }
+}
+
+/// `<Error>` document of a failed request
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub(crate) struct ErrorDocument {
+    #[serde(default)]
+    code: String,
+    #[serde(default)]
+    message: String,
+    request_id: Option<String>,
+}
+
+impl ErrorDocument {
+    pub(crate) fn into_error(self, status: StatusCode) -> Error {
+        Error::Service {
+            status: status.as_u16(),
+            code: self.code,
+            message: self.message,
+            request_id: self.request_id,
+        }
+    }
+}
+
+/// Error for a response with status `status` and body `body`
+///
+/// HEAD responses and some proxies send no error document; the code is
+/// then derived from the status.
+pub(crate) fn service_error(status: StatusCode, request_id: Option<String>, body: &str) -> Error {
+    match quick_xml::de::from_str::<ErrorDocument>(body) {
+        Ok(doc) if !doc.code.is_empty() => doc.into_error(status),
+        _ => {
+            let code = match status {
+                StatusCode::NOT_FOUND => "NotFound",
+                StatusCode::FORBIDDEN => "Forbidden",
+                StatusCode::BAD_REQUEST => "BadRequest",
+                _ => status.canonical_reason().unwrap_or("Unknown"),
+            };
+            Error::Service {
+                status: status.as_u16(),
+                code: code.to_string(),
+                message: body.trim().to_string(),
+                request_id,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_service_error_from_document() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <Error>
+                <Code>NoSuchKey</Code>
+                <Message>The specified key does not exist.</Message>
+                <RequestId>4442587FB7D0A2F9</RequestId>
+            </Error>"#;
+        let err = service_error(StatusCode::NOT_FOUND, None, body);
+        assert_eq!(err.code(), Some("NoSuchKey"));
+        assert!(err.is_not_found());
+        assert!(matches!(err, Error::Service { request_id: Some(ref id), .. } if id == "4442587FB7D0A2F9"));
+
+        let err = service_error(StatusCode::FORBIDDEN, Some("abc".into()), "");
+        assert_eq!(err.code(), Some("Forbidden"));
+        assert_eq!(err.status(), Some(403));
+    }
+}
@@ -0,0 +1,50 @@
+//! Async S3 client for Hafiz
+//!
+//! A small, typed client for the S3 API that Hafiz serves, for Rust
+//! applications that want to talk to Hafiz without the AWS SDK. Requests
+//! are signed with Signature Version 4 and sent with reqwest.
+//!
+//! ```no_run
+//! use hafiz_client::{Body, Client, Credentials, PutObjectOptions};
+//!
+//! # async fn example() -> hafiz_client::Result<()> {
+//! let client = Client::builder("http://localhost:9000")
+//!     .credentials(Credentials::new("access-key", "secret-key"))
+//!     .build()?;
+//!
+//! client.create_bucket("photos", None).await?;
+//! let body = Body::from_path("cat.jpg").await?;
+//! let options = PutObjectOptions::default().content_type("image/jpeg");
+//! client.put_object("photos", "cat.jpg", body, &options).await?;
+//!
+//! let mut pages = client.list_objects("photos").prefix("cat").into_paginator();
+//! while let Some(page) = pages.next_page().await? {
+//!     for object in page.objects {
+//!         println!("{} {}", object.key, object.size);
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod body;
+mod buckets;
+mod client;
+mod error;
+mod list;
+mod multipart;
+mod objects;
+mod presign;
+mod raw;
+mod signing;
+mod types;
+
+pub use body::{Body, ObjectBody};
+pub use client::{Client, ClientBuilder, Credentials};
+pub use error::{Error, Result};
+pub use list::{ListObjects, ListObjectsPaginator};
+pub use multipart::{MAX_PARTS, MIN_PART_SIZE};
+pub use presign::MAX_PRESIGN_EXPIRY;
+pub use raw::{RawRequest, RawResponse};
+pub use reqwest::Method;
+pub use types::*;
@@ -0,0 +1,199 @@
+//! ListObjectsV2 and its paginator
+//!
+//! [`Client::list_objects`] returns a request builder. `send` fetches one
+//! page; `into_paginator` follows continuation tokens page by page, and
+//! `into_stream` yields every object across pages.
+
+use futures::Stream;
+use reqwest::Method;
+use serde::Deserialize;
+
+use crate::client::{Client, S3Request};
+use crate::error::{Error, Result};
+use crate::types::{ListObjectsPage, Object};
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ListBucketResult {
+    #[serde(default)]
+    contents: Vec<Object>,
+    #[serde(default)]
+    common_prefixes: Vec<CommonPrefix>,
+    #[serde(default)]
+    is_truncated: bool,
+    next_continuation_token: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CommonPrefix {
+    prefix: String,
+}
+
+/// A ListObjectsV2 request
+#[derive(Debug, Clone)]
+pub struct ListObjects {
+    client: Client,
+    bucket: String,
+    prefix: Option<String>,
+    delimiter: Option<String>,
+    start_after: Option<String>,
+    max_keys: Option<u32>,
+    continuation_token: Option<String>,
+}
+
+impl Client {
+    /// List the objects of a bucket
+    pub fn list_objects(&self, bucket: &str) -> ListObjects {
+        ListObjects {
+            client: self.clone(),
+            bucket: bucket.to_string(),
+            prefix: None,
+            delimiter: None,
+            start_after: None,
+            max_keys: None,
+            continuation_token: None,
+        }
+    }
+}
+
+impl ListObjects {
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into()).filter(|p: &String| !p.is_empty());
+        self
+    }
+
+    /// Roll keys up to common prefixes at `delimiter`, usually `/`
+    pub fn delimiter(mut self, delimiter: impl Into<String>) -> Self {
+        self.delimiter = Some(delimiter.into());
+        self
+    }
+
+    pub fn start_after(mut self, key: impl Into<String>) -> Self {
+        self.start_after = Some(key.into());
+        self
+    }
+
+    /// Keys per page; the server caps it at 1,000
+    pub fn max_keys(mut self, max_keys: u32) -> Self {
+        self.max_keys = Some(max_keys);
+        self
+    }
+
+    pub fn continuation_token(mut self, token: impl Into<String>) -> Self {
+        self.continuation_token = Some(token.into());
+        self
+    }
+
+    /// Fetch one page
+    pub async fn send(&self) -> Result<ListObjectsPage> {
+        let request = S3Request::new(Method::GET)
+            .bucket(&self.bucket)
+            .query("list-type", "2")
+            .query_opt("prefix", self.prefix.clone())
+            .query_opt("delimiter", self.delimiter.clone())
+            .query_opt("start-after", self.start_after.clone())
+            .query_opt("max-keys", self.max_keys.map(|n| n.to_string()))
+            .query_opt("continuation-token", self.continuation_token.clone());
+
+        let result: ListBucketResult = self.client.send_xml(request).await?;
+        Ok(ListObjectsPage {
+            objects: result.contents,
+            common_prefixes: result.common_prefixes.into_iter().map(|p| p.prefix).collect(),
+            is_truncated: result.is_truncated,
+            next_continuation_token: result.next_continuation_token.filter(|_| result.is_truncated),
+        })
+    }
+
+    /// Page through the listing
+    pub fn into_paginator(self) -> ListObjectsPaginator {
+        ListObjectsPaginator {
+            request: self,
+            done: false,
+        }
+    }
+
+    /// Every object of the listing, fetching pages as needed
+    pub fn into_stream(self) -> impl Stream<Item = Result<Object>> + Send {
+        use futures::stream::{self, TryStreamExt};
+
+        stream::try_unfold(self.into_paginator(), |mut pages| async move {
+            Ok::<_, Error>(pages.next_page().await?.map(|page| (page.objects, pages)))
+        })
+        .map_ok(|objects| stream::iter(objects.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    /// Fetch every page and merge them
+    pub async fn collect(self) -> Result<ListObjectsPage> {
+        let mut pages = self.into_paginator();
+        let mut all = ListObjectsPage::default();
+        while let Some(page) = pages.next_page().await? {
+            all.objects.extend(page.objects);
+            all.common_prefixes.extend(page.common_prefixes);
+        }
+        Ok(all)
+    }
+}
+
+/// Follows continuation tokens of a [`ListObjects`] request
+pub struct ListObjectsPaginator {
+    request: ListObjects,
+    done: bool,
+}
+
+impl ListObjectsPaginator {
+    /// The next page, or `None` after the last one
+    pub async fn next_page(&mut self) -> Result<Option<ListObjectsPage>> {
+        if self.done {
+            return Ok(None);
+        }
+        let page = self.request.send().await?;
+        match &page.next_continuation_token {
+            Some(token) => self.request.continuation_token = Some(token.clone()),
+            None => self.done = true,
+        }
+        Ok(Some(page))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_list_bucket_result() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                <Name>photos</Name>
+                <Prefix>2024/</Prefix>
+                <KeyCount>2</KeyCount>
+                <MaxKeys>1000</MaxKeys>
+                <Delimiter>/</Delimiter>
+                <IsTruncated>true</IsTruncated>
+                <NextContinuationToken>abc</NextContinuationToken>
+                <Contents>
+                    <Key>2024/a.jpg</Key>
+                    <LastModified>2024-03-01T10:00:00.000Z</LastModified>
+                    <ETag>"9b2cf535f27731c974343645a3985328"</ETag>
+                    <Size>1024</Size>
+                    <StorageClass>STANDARD</StorageClass>
+                </Contents>
+                <CommonPrefixes><Prefix>2024/march/</Prefix></CommonPrefixes>
+            </ListBucketResult>"#;
+        let result: ListBucketResult = quick_xml::de::from_str(body).unwrap();
+        assert_eq!(result.contents.len(), 1);
+        let object = &result.contents[0];
+        assert_eq!(object.key, "2024/a.jpg");
+        assert_eq!(object.size, 1024);
+        assert_eq!(object.etag.as_deref(), Some("\"9b2cf535f27731c974343645a3985328\""));
+        assert!(object.last_modified.is_some());
+        assert_eq!(result.common_prefixes[0].prefix, "2024/march/");
+        assert!(result.is_truncated);
+        assert_eq!(result.next_continuation_token.as_deref(), Some("abc"));
+
+        let empty: ListBucketResult =
+            quick_xml::de::from_str("<ListBucketResult><IsTruncated>false</IsTruncated></ListBucketResult>").unwrap();
+        assert!(empty.contents.is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+//! Multipart uploads
+//!
+//! The individual calls are exposed for callers that produce parts
+//! themselves. [`Client::upload_file`] drives the whole upload for a file:
+//! it reads parts at their offsets, uploads several at once, and aborts
+//! the upload if any part or the completion fails.
+
+use std::io::SeekFrom;
+use std::path::Path;
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+use reqwest::Method;
+use serde::Deserialize;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::body::Body;
+use crate::buckets::xml_escape;
+use crate::client::{header, Client, S3Request};
+use crate::error::{Error, Result};
+use crate::types::{CompletedPart, MultipartConfig, PutObjectOptions, PutObjectOutput};
+
+/// Smallest part S3 accepts, except for the last one
+pub const MIN_PART_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Most parts an upload may have
+pub const MAX_PARTS: u64 = 10_000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InitiateMultipartUploadResult {
+    upload_id: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CompleteMultipartUploadResult {
+    #[serde(rename = "ETag")]
+    etag: Option<String>,
+}
+
+impl Client {
+    /// Start a multipart upload, returning its upload ID
+    pub async fn create_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        options: &PutObjectOptions,
+    ) -> Result<String> {
+        let request = S3Request::new(Method::POST)
+            .bucket(bucket)
+            .key(key)
+            .query("uploads", "")
+            .put_options(options);
+        let result: InitiateMultipartUploadResult = self.send_xml(request).await?;
+        Ok(result.upload_id)
+    }
+
+    /// Upload part `part_number`, counting from 1
+    pub async fn upload_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: u32,
+        body: impl Into<Body>,
+    ) -> Result<CompletedPart> {
+        let request = S3Request::new(Method::PUT)
+            .bucket(bucket)
+            .key(key)
+            .query("partNumber", part_number.to_string())
+            .query("uploadId", upload_id)
+            .body(body);
+        let response = self.send(request).await?;
+        let etag = header(&response, "etag")
+            .ok_or_else(|| Error::InvalidResponse(format!("No ETag for part {}", part_number)))?;
+        Ok(CompletedPart { part_number, etag })
+    }
+
+    /// Assemble the uploaded parts into the object
+    pub async fn complete_multipart_upload(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        parts: &[CompletedPart],
+    ) -> Result<PutObjectOutput> {
+        let mut document = String::from("<CompleteMultipartUpload>");
+        for part in parts {
+            document.push_str(&format!(
+                "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+                part.part_number,
+                xml_escape(&part.etag)
+            ));
+        }
+        document.push_str("</CompleteMultipartUpload>");
+
+        let request = S3Request::new(Method::POST)
+            .bucket(bucket)
+            .key(key)
+            .query("uploadId", upload_id)
+            .header("content-type", "application/xml")
+            .body(document);
+        let response = self.send(request).await?;
+        let version_id = header(&response, "x-amz-version-id");
+        let status = response.status();
+        let body = response.text().await?;
+        let result: CompleteMultipartUploadResult = crate::client::parse_xml(status, &body)?;
+        Ok(PutObjectOutput {
+            etag: result.etag,
+            version_id,
+        })
+    }
+
+    /// Abandon an upload and free its parts
+    pub async fn abort_multipart_upload(&self, bucket: &str, key: &str, upload_id: &str) -> Result<()> {
+        let request = S3Request::new(Method::DELETE)
+            .bucket(bucket)
+            .key(key)
+            .query("uploadId", upload_id);
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Upload a file, in parts if it reaches `multipart.threshold`
+    pub async fn upload_file(
+        &self,
+        bucket: &str,
+        key: &str,
+        path: impl AsRef<Path>,
+        options: &PutObjectOptions,
+        multipart: &MultipartConfig,
+    ) -> Result<PutObjectOutput> {
+        let path = path.as_ref();
+        let len = tokio::fs::metadata(path).await?.len();
+        if len < multipart.threshold {
+            return self.put_object(bucket, key, Body::from_path(path).await?, options).await;
+        }
+
+        let upload_id = self.create_multipart_upload(bucket, key, options).await?;
+        let result = match self.upload_file_parts(bucket, key, &upload_id, path, len, multipart).await {
+            Ok(parts) => self.complete_multipart_upload(bucket, key, &upload_id, &parts).await,
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            // The original error matters more than a failed abort
+            let _ = self.abort_multipart_upload(bucket, key, &upload_id).await;
+        }
+        result
+    }
+
+    async fn upload_file_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        path: &Path,
+        len: u64,
+        multipart: &MultipartConfig,
+    ) -> Result<Vec<CompletedPart>> {
+        let part_size = part_size(len, multipart.part_size);
+        let parts = len.div_ceil(part_size).max(1);
+
+        stream::iter(0..parts)
+            .map(|index| async move {
+                let offset = index * part_size;
+                let mut buf = vec![0u8; part_size.min(len - offset) as usize];
+                let mut file = tokio::fs::File::open(path).await?;
+                file.seek(SeekFrom::Start(offset)).await?;
+                file.read_exact(&mut buf).await?;
+                self.upload_part(bucket, key, upload_id, index as u32 + 1, buf).await
+            })
+            .buffered(multipart.concurrency.max(1))
+            .try_collect()
+            .await
+    }
+}
+
+/// Part size for a `len` byte upload: at least the S3 minimum, and large
+/// enough to stay within the part limit
+fn part_size(len: u64, wanted: u64) -> u64 {
+    wanted.max(MIN_PART_SIZE).max(len.div_ceil(MAX_PARTS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_size() {
+        assert_eq!(part_size(100, 1024), MIN_PART_SIZE);
+        assert_eq!(part_size(100 * 1024 * 1024, 8 * 1024 * 1024), 8 * 1024 * 1024);
+        // 1 TiB in 8 MiB parts would need 131,072 parts
+        let tib: u64 = 1024 * 1024 * 1024 * 1024;
+        assert_eq!(tib.div_ceil(part_size(tib, 8 * 1024 * 1024)), MAX_PARTS);
+    }
+}
@@ -0,0 +1,239 @@
+//! Object operations
+
+use chrono::{DateTime, Utc};
+use reqwest::{Method, Response};
+use serde::Deserialize;
+
+use crate::body::{Body, ObjectBody};
+use crate::buckets::{content_md5, xml_escape};
+use crate::client::{header, Client, S3Request};
+use crate::error::{Error, Result};
+use crate::signing::uri_encode;
+use crate::types::{
+    DeleteError, DeleteObjectsOutput, GetObjectOptions, GetObjectOutput, ObjectMeta, PutObjectOptions,
+    PutObjectOutput,
+};
+
+/// Keys DeleteObjects takes per request
+const DELETE_BATCH: usize = 1000;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DeleteResult {
+    #[serde(default)]
+    deleted: Vec<Deleted>,
+    #[serde(rename = "Error", default)]
+    errors: Vec<DeleteError>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Deleted {
+    key: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CopyObjectResult {
+    #[serde(rename = "ETag")]
+    etag: Option<String>,
+}
+
+impl S3Request<'_> {
+    /// Headers of [`PutObjectOptions`]
+    pub(crate) fn put_options(mut self, options: &PutObjectOptions) -> Self {
+        self = self
+            .header_opt("content-type", options.content_type.clone())
+            .header_opt("x-amz-storage-class", options.storage_class.clone());
+        for (name, value) in &options.metadata {
+            self = self.header(&format!("x-amz-meta-{}", name.to_lowercase()), value.clone());
+        }
+        self
+    }
+}
+
+impl Client {
+    /// Upload an object in a single request
+    ///
+    /// Files and other streams are sent as they are read; use
+    /// [`upload_file`](Self::upload_file) to split large files into parts.
+    pub async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: impl Into<Body>,
+        options: &PutObjectOptions,
+    ) -> Result<PutObjectOutput> {
+        let request = S3Request::new(Method::PUT)
+            .bucket(bucket)
+            .key(key)
+            .put_options(options)
+            .body(body);
+        let response = self.send(request).await?;
+        Ok(PutObjectOutput {
+            etag: header(&response, "etag"),
+            version_id: header(&response, "x-amz-version-id"),
+        })
+    }
+
+    /// Download an object; the body is streamed
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<GetObjectOutput> {
+        self.get_object_with(bucket, key, &GetObjectOptions::default()).await
+    }
+
+    /// Download an object, a version of it or a range of bytes
+    pub async fn get_object_with(
+        &self,
+        bucket: &str,
+        key: &str,
+        options: &GetObjectOptions,
+    ) -> Result<GetObjectOutput> {
+        let range = match &options.range {
+            Some(range) if range.start >= range.end => {
+                return Err(Error::InvalidArgument(format!(
+                    "Empty range {}..{}",
+                    range.start, range.end
+                )))
+            }
+            Some(range) if range.end == u64::MAX => Some(format!("bytes={}-", range.start)),
+            Some(range) => Some(format!("bytes={}-{}", range.start, range.end - 1)),
+            None => None,
+        };
+        let request = S3Request::new(Method::GET)
+            .bucket(bucket)
+            .key(key)
+            .query_opt("versionId", options.version_id.clone())
+            .header_opt("range", range);
+
+        let response = self.send(request).await?;
+        Ok(GetObjectOutput {
+            meta: object_meta(&response),
+            body: ObjectBody::new(response),
+        })
+    }
+
+    pub async fn head_object(&self, bucket: &str, key: &str) -> Result<ObjectMeta> {
+        let response = self
+            .send(S3Request::new(Method::HEAD).bucket(bucket).key(key))
+            .await?;
+        Ok(object_meta(&response))
+    }
+
+    /// Delete an object; deleting a missing key succeeds
+    pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        self.send(S3Request::new(Method::DELETE).bucket(bucket).key(key))
+            .await?;
+        Ok(())
+    }
+
+    /// Permanently delete one version of an object
+    pub async fn delete_object_version(&self, bucket: &str, key: &str, version_id: &str) -> Result<()> {
+        let request = S3Request::new(Method::DELETE)
+            .bucket(bucket)
+            .key(key)
+            .query("versionId", version_id);
+        self.send(request).await?;
+        Ok(())
+    }
+
+    /// Delete many objects, 1,000 keys per request
+    ///
+    /// Keys the server could not delete are reported in
+    /// [`DeleteObjectsOutput::errors`] rather than failing the call.
+    pub async fn delete_objects<S: AsRef<str>>(&self, bucket: &str, keys: &[S]) -> Result<DeleteObjectsOutput> {
+        let mut output = DeleteObjectsOutput::default();
+        for batch in keys.chunks(DELETE_BATCH) {
+            let mut document = String::from("<Delete><Quiet>false</Quiet>");
+            for key in batch {
+                document.push_str(&format!("<Object><Key>{}</Key></Object>", xml_escape(key.as_ref())));
+            }
+            document.push_str("</Delete>");
+
+            let request = S3Request::new(Method::POST)
+                .bucket(bucket)
+                .query("delete", "")
+                .header("content-type", "application/xml")
+                .header("content-md5", content_md5(document.as_bytes()))
+                .body(document);
+            let result: DeleteResult = self.send_xml(request).await?;
+            output.deleted.extend(result.deleted.into_iter().map(|d| d.key));
+            output.errors.extend(result.errors);
+        }
+        Ok(output)
+    }
+
+    /// Copy an object on the server
+    ///
+    /// The copy keeps the source's metadata unless `options` sets a
+    /// content type or metadata, which then replace it.
+    pub async fn copy_object(
+        &self,
+        source_bucket: &str,
+        source_key: &str,
+        bucket: &str,
+        key: &str,
+        options: &PutObjectOptions,
+    ) -> Result<PutObjectOutput> {
+        let copy_source = format!("/{}/{}", source_bucket, uri_encode(source_key, false));
+        let mut request = S3Request::new(Method::PUT)
+            .bucket(bucket)
+            .key(key)
+            .header("x-amz-copy-source", copy_source)
+            .put_options(options);
+        if options.content_type.is_some() || !options.metadata.is_empty() {
+            request = request.header("x-amz-metadata-directive", "REPLACE");
+        }
+
+        let response = self.send(request).await?;
+        let version_id = header(&response, "x-amz-version-id");
+        let status = response.status();
+        let body = response.text().await?;
+        let result: CopyObjectResult = crate::client::parse_xml(status, &body)?;
+        Ok(PutObjectOutput {
+            etag: result.etag,
+            version_id,
+        })
+    }
+}
+
+fn object_meta(response: &Response) -> ObjectMeta {
+    let metadata = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| {
+            let name = name.as_str().strip_prefix("x-amz-meta-")?;
+            Some((name.to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect();
+
+    ObjectMeta {
+        content_length: header(response, "content-length").and_then(|v| v.parse().ok()),
+        content_type: header(response, "content-type"),
+        etag: header(response, "etag"),
+        last_modified: header(response, "last-modified")
+            .and_then(|v| DateTime::parse_from_rfc2822(&v).ok())
+            .map(|d| d.with_timezone(&Utc)),
+        storage_class: header(response, "x-amz-storage-class"),
+        version_id: header(response, "x-amz-version-id"),
+        metadata,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_delete_result() {
+        let body = r#"<DeleteResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+                <Deleted><Key>a.txt</Key></Deleted>
+                <Deleted><Key>b.txt</Key></Deleted>
+                <Error><Key>locked.txt</Key><Code>AccessDenied</Code><Message>Object is locked</Message></Error>
+            </DeleteResult>"#;
+        let result: DeleteResult = quick_xml::de::from_str(body).unwrap();
+        let deleted: Vec<_> = result.deleted.iter().map(|d| d.key.as_str()).collect();
+        assert_eq!(deleted, ["a.txt", "b.txt"]);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].code, "AccessDenied");
+    }
+}
@@ -0,0 +1,71 @@
+//! Presigned URLs
+
+use std::time::Duration;
+
+use reqwest::Method;
+
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::signing::presigned_query;
+
+/// Longest validity of a presigned URL
+pub const MAX_PRESIGN_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+impl Client {
+    /// URL that downloads an object without credentials until it expires
+    pub fn presign_get(&self, bucket: &str, key: &str, expires_in: Duration) -> Result<String> {
+        self.presign(Method::GET, bucket, key, expires_in)
+    }
+
+    /// URL that uploads an object without credentials until it expires
+    pub fn presign_put(&self, bucket: &str, key: &str, expires_in: Duration) -> Result<String> {
+        self.presign(Method::PUT, bucket, key, expires_in)
+    }
+
+    /// URL for `method` on an object, valid for up to seven days
+    pub fn presign(&self, method: Method, bucket: &str, key: &str, expires_in: Duration) -> Result<String> {
+        if expires_in.is_zero() || expires_in > MAX_PRESIGN_EXPIRY {
+            return Err(Error::InvalidArgument(format!(
+                "Presigned URLs expire after 1 second to 7 days, not {}s",
+                expires_in.as_secs()
+            )));
+        }
+
+        let (host, path) = self.target(Some(bucket), Some(key));
+        let query = presigned_query(
+            method.as_str(),
+            &host,
+            &path,
+            &[],
+            expires_in.as_secs(),
+            &self.signing_params(),
+        );
+        Ok(self.url(&host, &path, &query)?.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Credentials;
+
+    #[test]
+    fn test_presign_url_shape() {
+        let client = Client::builder("http://localhost:9000")
+            .credentials(Credentials::new("AKID", "secret"))
+            .build()
+            .unwrap();
+
+        let url = client
+            .presign_get("photos", "2024/a b.jpg", Duration::from_secs(3600))
+            .unwrap();
+        assert!(url.starts_with("http://localhost:9000/photos/2024/a%20b.jpg?X-Amz-Algorithm=AWS4-HMAC-SHA256"));
+        assert!(url.contains("&X-Amz-Expires=3600&"));
+        assert!(url.contains("&X-Amz-Signature="));
+
+        assert!(client.presign_put("photos", "a", Duration::ZERO).is_err());
+        assert!(client
+            .presign_put("photos", "a", MAX_PRESIGN_EXPIRY + Duration::from_secs(1))
+            .is_err());
+    }
+}
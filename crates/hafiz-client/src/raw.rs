@@ -0,0 +1,86 @@
+//! Requests built by hand
+//!
+//! For tools that send requests the typed API has no method for, such as
+//! replaying recorded traffic. The request is signed like any other, and
+//! the response comes back as received, error statuses included.
+
+use bytes::Bytes;
+use reqwest::Method;
+
+use crate::client::{Client, S3Request};
+use crate::error::Result;
+
+/// A request for any operation
+#[derive(Debug, Clone)]
+pub struct RawRequest {
+    pub method: Method,
+    pub bucket: Option<String>,
+    pub key: Option<String>,
+    /// Decoded query parameters; subresources such as `?acl` take an empty
+    /// value
+    pub query: Vec<(String, String)>,
+    /// Headers to send and sign; `host`, `x-amz-date`,
+    /// `x-amz-content-sha256` and `content-length` are set by the client
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+}
+
+impl RawRequest {
+    pub fn new(method: Method) -> Self {
+        Self {
+            method,
+            bucket: None,
+            key: None,
+            query: Vec::new(),
+            headers: Vec::new(),
+            body: Bytes::new(),
+        }
+    }
+}
+
+/// A response as received
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Bytes,
+}
+
+impl RawResponse {
+    /// Value of a header
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl Client {
+    /// Sign and send a request, returning the response whatever its status
+    pub async fn send_raw(&self, request: &RawRequest) -> Result<RawResponse> {
+        let mut s3 = S3Request::new(request.method.clone());
+        if let Some(bucket) = &request.bucket {
+            s3 = s3.bucket(bucket);
+        }
+        if let Some(key) = &request.key {
+            s3 = s3.key(key);
+        }
+        for (name, value) in &request.query {
+            s3 = s3.query(name, value.clone());
+        }
+        for (name, value) in &request.headers {
+            s3 = s3.header(&name.to_ascii_lowercase(), value.clone());
+        }
+
+        let response = self.dispatch(s3.body(request.body.clone())).await?;
+        let status = response.status().as_u16();
+        let headers = response
+            .headers()
+            .iter()
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect();
+        let body = response.bytes().await?;
+        Ok(RawResponse { status, headers, body })
+    }
+}
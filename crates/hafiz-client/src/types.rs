@@ -0,0 +1,191 @@
+//! Request options and response types
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::body::ObjectBody;
+
+/// A bucket from ListBuckets
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Bucket {
+    pub name: String,
+    pub creation_date: Option<DateTime<Utc>>,
+}
+
+/// An object from ListObjectsV2
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Object {
+    pub key: String,
+    #[serde(default)]
+    pub size: i64,
+    pub last_modified: Option<DateTime<Utc>>,
+    #[serde(rename = "ETag")]
+    pub etag: Option<String>,
+    pub storage_class: Option<String>,
+}
+
+/// One page of ListObjectsV2
+#[derive(Debug, Clone, Default)]
+pub struct ListObjectsPage {
+    pub objects: Vec<Object>,
+    /// Prefixes rolled up by the delimiter
+    pub common_prefixes: Vec<String>,
+    pub is_truncated: bool,
+    pub next_continuation_token: Option<String>,
+}
+
+/// Versioning state of a bucket
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BucketVersioning {
+    /// `Enabled` or `Suspended`; `None` if versioning was never enabled
+    pub status: Option<String>,
+    #[serde(alias = "MFADelete")]
+    pub mfa_delete: Option<String>,
+}
+
+/// Bucket configuration documents, addressed by their subresource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketConfig {
+    /// JSON bucket policy
+    Policy,
+    Cors,
+    Lifecycle,
+    Versioning,
+    Tagging,
+    Encryption,
+    Website,
+    Notification,
+    Replication,
+    ObjectLock,
+}
+
+impl BucketConfig {
+    /// Query parameter naming the subresource
+    pub fn subresource(&self) -> &'static str {
+        match self {
+            Self::Policy => "policy",
+            Self::Cors => "cors",
+            Self::Lifecycle => "lifecycle",
+            Self::Versioning => "versioning",
+            Self::Tagging => "tagging",
+            Self::Encryption => "encryption",
+            Self::Website => "website",
+            Self::Notification => "notification",
+            Self::Replication => "replication",
+            Self::ObjectLock => "object-lock",
+        }
+    }
+}
+
+/// Headers of a HeadObject or GetObject response
+#[derive(Debug, Clone, Default)]
+pub struct ObjectMeta {
+    pub content_length: Option<i64>,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime<Utc>>,
+    pub storage_class: Option<String>,
+    pub version_id: Option<String>,
+    /// User metadata, without the `x-amz-meta-` prefix
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Options of PutObject, CopyObject and CreateMultipartUpload
+#[derive(Debug, Clone, Default)]
+pub struct PutObjectOptions {
+    pub content_type: Option<String>,
+    pub storage_class: Option<String>,
+    /// User metadata, sent as `x-amz-meta-*` headers
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl PutObjectOptions {
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.content_type = Some(content_type.into());
+        self
+    }
+
+    pub fn storage_class(mut self, storage_class: impl Into<String>) -> Self {
+        self.storage_class = Some(storage_class.into());
+        self
+    }
+
+    pub fn metadata(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.metadata.insert(name.into(), value.into());
+        self
+    }
+}
+
+/// Result of a write
+#[derive(Debug, Clone, Default)]
+pub struct PutObjectOutput {
+    pub etag: Option<String>,
+    pub version_id: Option<String>,
+}
+
+/// Options of GetObject
+#[derive(Debug, Clone, Default)]
+pub struct GetObjectOptions {
+    /// Bytes to read, as `start..end`; `end` is clamped to the object
+    pub range: Option<std::ops::Range<u64>>,
+    pub version_id: Option<String>,
+}
+
+/// A GetObject response; the body is streamed
+pub struct GetObjectOutput {
+    pub meta: ObjectMeta,
+    pub body: ObjectBody,
+}
+
+/// Result of DeleteObjects
+#[derive(Debug, Clone, Default)]
+pub struct DeleteObjectsOutput {
+    pub deleted: Vec<String>,
+    pub errors: Vec<DeleteError>,
+}
+
+/// A key DeleteObjects failed to delete
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct DeleteError {
+    pub key: String,
+    #[serde(default)]
+    pub code: String,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// An uploaded part of a multipart upload
+#[derive(Debug, Clone)]
+pub struct CompletedPart {
+    pub part_number: u32,
+    pub etag: String,
+}
+
+/// When and how [`Client::upload_file`](crate::Client::upload_file) splits
+/// an upload into parts
+#[derive(Debug, Clone)]
+pub struct MultipartConfig {
+    /// Files of at least this size are uploaded in parts
+    pub threshold: u64,
+    /// Size of each part but the last; raised to the 5 MiB minimum, and so
+    /// that no more than 10,000 parts are needed
+    pub part_size: u64,
+    /// Parts uploaded at once
+    pub concurrency: usize,
+}
+
+impl Default for MultipartConfig {
+    fn default() -> Self {
+        Self {
+            threshold: 8 * 1024 * 1024,
+            part_size: 8 * 1024 * 1024,
+            concurrency: 4,
+        }
+    }
+}
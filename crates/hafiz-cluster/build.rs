@@ -0,0 +1,12 @@
+fn main() {
+    #[cfg(feature = "grpc")]
+    {
+        println!("cargo:rerun-if-changed=proto/cluster.proto");
+        tonic_build::configure()
+            .build_client(true)
+            .build_server(true)
+            .bytes(["."])
+            .compile_protos(&["proto/cluster.proto"], &["proto"])
+            .expect("failed to compile cluster.proto");
+    }
+}
@@ -0,0 +1,697 @@
+//! Anti-entropy repair between replicas
+//!
+//! Each node periodically builds a Merkle tree per bucket over its object
+//! metadata (key → etag/version) and compares it with every healthy peer.
+//! Only the leaves whose hashes differ are exchanged in full, and objects
+//! that are missing locally or older than the peer's copy are pulled from
+//! the peer. Because every node pulls, divergence is repaired in both
+//! directions once each side has run.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use hafiz_core::types::{
+    AntiEntropyStats, ClusterConfig, ClusterNode, NodeId, ObjectInfo, ObjectInternal,
+};
+use hafiz_metadata::MetadataStore;
+use hafiz_storage::StorageEngine;
+
+use crate::discovery::DiscoveryService;
+use crate::error::{ClusterError, ClusterResult};
+use crate::placement::{checksum, LocalStore, PlacementService};
+use crate::transport::ClusterTransport;
+
+/// Number of leaves in every tree; a power of two so the tree is complete
+pub const LEAF_COUNT: usize = 256;
+
+/// Peer endpoint returning the leaf hashes of a bucket
+pub const TREE_PATH: &str = "/cluster/anti-entropy/tree";
+
+/// Peer endpoint returning the entries of selected leaves
+pub const LEAVES_PATH: &str = "/cluster/anti-entropy/leaves";
+
+/// Objects listed per page while building a tree
+const LIST_PAGE_SIZE: i32 = 1000;
+
+/// Timeout for tree and leaf exchanges
+const RPC_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// One object as seen by anti-entropy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObjectDigest {
+    pub key: String,
+    pub etag: String,
+    pub version_id: String,
+    pub last_modified: DateTime<Utc>,
+    /// Filled in for leaf entries sent to a peer for repair; not hashed
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+impl From<&ObjectInfo> for ObjectDigest {
+    fn from(object: &ObjectInfo) -> Self {
+        Self {
+            key: object.key.clone(),
+            etag: object.etag.clone(),
+            version_id: object.version_id.clone().unwrap_or_else(|| "null".to_string()),
+            last_modified: object.last_modified,
+            content_type: None,
+        }
+    }
+}
+
+/// Leaf a key hashes into
+pub fn leaf_index(key: &str) -> usize {
+    Sha256::digest(key.as_bytes())[0] as usize % LEAF_COUNT
+}
+
+/// Binary Merkle tree over `LEAF_COUNT` key-hash ranges
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// `levels[0]` are the leaves, the last level is the root
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Build a tree from object digests, in any order
+    pub fn build<'a>(entries: impl IntoIterator<Item = &'a ObjectDigest>) -> Self {
+        let mut sorted: Vec<&ObjectDigest> = entries.into_iter().collect();
+        sorted.sort_by(|a, b| a.key.cmp(&b.key));
+
+        let mut hashers: Vec<Sha256> = (0..LEAF_COUNT).map(|_| Sha256::new()).collect();
+        for entry in sorted {
+            let hasher = &mut hashers[leaf_index(&entry.key)];
+            for field in [&entry.key, &entry.etag, &entry.version_id] {
+                hasher.update(field.as_bytes());
+                hasher.update([0]);
+            }
+        }
+
+        Self::from_leaves(hashers.into_iter().map(|h| h.finalize().into()).collect())
+    }
+
+    /// Rebuild a tree from its leaf hashes
+    pub fn from_leaves(leaves: Vec<[u8; 32]>) -> Self {
+        let mut levels = vec![leaves];
+        while levels.last().map_or(false, |l| l.len() > 1) {
+            let parents = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| {
+                    let mut hasher = Sha256::new();
+                    for child in pair {
+                        hasher.update(child);
+                    }
+                    hasher.finalize().into()
+                })
+                .collect();
+            levels.push(parents);
+        }
+        Self { levels }
+    }
+
+    /// Rebuild a tree from hex-encoded leaf hashes received from a peer
+    pub fn from_hex_leaves(leaves: &[String]) -> ClusterResult<Self> {
+        if leaves.len() != LEAF_COUNT {
+            return Err(ClusterError::Transport(format!(
+                "Expected {} Merkle leaves, got {}",
+                LEAF_COUNT,
+                leaves.len()
+            )));
+        }
+
+        let leaves = leaves
+            .iter()
+            .map(|leaf| {
+                hex::decode(leaf)
+                    .ok()
+                    .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+                    .ok_or_else(|| ClusterError::Transport("Invalid Merkle leaf hash".to_string()))
+            })
+            .collect::<ClusterResult<Vec<_>>>()?;
+
+        Ok(Self::from_leaves(leaves))
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().and_then(|l| l.first()).copied().unwrap_or_default()
+    }
+
+    pub fn leaves(&self) -> &[[u8; 32]] {
+        &self.levels[0]
+    }
+
+    pub fn hex_leaves(&self) -> Vec<String> {
+        self.leaves().iter().map(hex::encode).collect()
+    }
+
+    /// Indexes of the leaves that differ, descending only into differing subtrees
+    pub fn diff(&self, other: &MerkleTree) -> Vec<usize> {
+        let mut divergent = Vec::new();
+        if self.levels.len() == other.levels.len() {
+            self.diff_node(other, self.levels.len() - 1, 0, &mut divergent);
+        }
+        divergent
+    }
+
+    fn diff_node(&self, other: &MerkleTree, level: usize, index: usize, divergent: &mut Vec<usize>) {
+        let (Some(ours), Some(theirs)) = (self.levels[level].get(index), other.levels[level].get(index))
+        else {
+            return;
+        };
+        if ours == theirs {
+            return;
+        }
+        if level == 0 {
+            divergent.push(index);
+            return;
+        }
+        self.diff_node(other, level - 1, index * 2, divergent);
+        self.diff_node(other, level - 1, index * 2 + 1, divergent);
+    }
+}
+
+/// Request for a bucket's leaf hashes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeRequest {
+    pub bucket: String,
+    /// Requesting node; with placement only objects both nodes own are hashed
+    pub node_id: NodeId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TreeResponse {
+    /// Hex-encoded leaf hashes, `None` if the bucket does not exist here
+    pub leaves: Option<Vec<String>>,
+}
+
+/// Request for the entries of divergent leaves
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeavesRequest {
+    pub bucket: String,
+    pub node_id: NodeId,
+    pub leaves: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeavesResponse {
+    pub entries: Vec<ObjectDigest>,
+}
+
+/// Outcome of one anti-entropy run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub buckets_compared: u64,
+    pub divergent_leaves: u64,
+    pub divergent_objects: u64,
+    pub objects_repaired: u64,
+    pub repair_failures: u64,
+}
+
+/// Background Merkle comparison and repair
+pub struct AntiEntropyService {
+    enabled: bool,
+    interval: Duration,
+    local_node_id: NodeId,
+    discovery: Arc<DiscoveryService>,
+    transport: Arc<ClusterTransport>,
+    placement: Arc<PlacementService>,
+    store: RwLock<Option<LocalStore>>,
+    stats: RwLock<AntiEntropyStats>,
+    running: AtomicBool,
+    shutdown: Arc<RwLock<bool>>,
+}
+
+impl AntiEntropyService {
+    pub fn new(
+        config: &ClusterConfig,
+        discovery: Arc<DiscoveryService>,
+        transport: Arc<ClusterTransport>,
+        placement: Arc<PlacementService>,
+    ) -> Self {
+        Self {
+            enabled: config.anti_entropy_enabled,
+            interval: Duration::from_secs(config.anti_entropy_interval_secs.max(1)),
+            local_node_id: config.node_id.clone(),
+            discovery,
+            transport,
+            placement,
+            store: RwLock::new(None),
+            stats: RwLock::new(AntiEntropyStats::default()),
+            running: AtomicBool::new(false),
+            shutdown: Arc::new(RwLock::new(false)),
+        }
+    }
+
+    /// Give the service access to local objects
+    pub fn attach_store(&self, metadata: Arc<MetadataStore>, storage: Arc<dyn StorageEngine>) {
+        *self.store.write() = Some(LocalStore { metadata, storage });
+    }
+
+    pub fn stats(&self) -> AntiEntropyStats {
+        self.stats.read().clone()
+    }
+
+    /// Start periodic runs if anti-entropy is enabled
+    pub fn start(self: &Arc<Self>) {
+        if !self.enabled {
+            return;
+        }
+
+        *self.shutdown.write() = false;
+        let service = Arc::clone(self);
+
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(service.interval);
+            // The first tick completes immediately; give discovery time to settle
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if *service.shutdown.read() {
+                    break;
+                }
+                if let Err(e) = service.run(None).await {
+                    warn!("Anti-entropy run failed: {}", e);
+                }
+            }
+        });
+
+        info!("Anti-entropy started (every {:?})", self.interval);
+    }
+
+    pub fn stop(&self) {
+        *self.shutdown.write() = true;
+    }
+
+    /// Compare with every healthy peer and repair divergent objects
+    ///
+    /// Limited to `bucket` if given. Fails if a run is already in progress.
+    pub async fn run(&self, bucket: Option<&str>) -> ClusterResult<RepairReport> {
+        let store = self
+            .store
+            .read()
+            .clone()
+            .ok_or_else(|| ClusterError::Internal("No local store attached".to_string()))?;
+
+        if self.running.swap(true, Ordering::SeqCst) {
+            return Err(ClusterError::Conflict(
+                "Anti-entropy run already in progress".to_string(),
+            ));
+        }
+        self.stats.write().in_progress = true;
+
+        let result = self.run_buckets(&store, bucket).await;
+
+        {
+            let mut stats = self.stats.write();
+            stats.in_progress = false;
+            stats.last_run_at = Some(Utc::now());
+            if let Ok(report) = &result {
+                stats.runs += 1;
+                stats.buckets_compared += report.buckets_compared;
+                stats.divergent_leaves += report.divergent_leaves;
+                stats.divergent_objects += report.divergent_objects;
+                stats.objects_repaired += report.objects_repaired;
+                stats.repair_failures += report.repair_failures;
+            }
+        }
+        self.running.store(false, Ordering::SeqCst);
+
+        if let Ok(report) = &result {
+            metrics::counter!("hafiz_cluster_anti_entropy_divergent_leaves_total")
+                .increment(report.divergent_leaves);
+            metrics::counter!("hafiz_cluster_anti_entropy_divergent_objects_total")
+                .increment(report.divergent_objects);
+            metrics::counter!("hafiz_cluster_anti_entropy_repaired_objects_total")
+                .increment(report.objects_repaired);
+            metrics::counter!("hafiz_cluster_anti_entropy_repair_failures_total")
+                .increment(report.repair_failures);
+            info!(
+                "Anti-entropy finished: {} comparisons, {} divergent objects, {} repaired, {} failed",
+                report.buckets_compared,
+                report.divergent_objects,
+                report.objects_repaired,
+                report.repair_failures
+            );
+        }
+
+        result
+    }
+
+    async fn run_buckets(
+        &self,
+        store: &LocalStore,
+        bucket: Option<&str>,
+    ) -> ClusterResult<RepairReport> {
+        let buckets = match bucket {
+            Some(bucket) => vec![bucket.to_string()],
+            None => store.metadata.list_bucket_names().await?,
+        };
+        let peers: Vec<ClusterNode> = self
+            .discovery
+            .healthy_nodes()
+            .into_iter()
+            .filter(|n| n.id != self.local_node_id)
+            .collect();
+
+        let mut report = RepairReport::default();
+        for bucket in &buckets {
+            for peer in &peers {
+                if let Err(e) = self.sync_bucket(store, peer, bucket, &mut report).await {
+                    warn!("Anti-entropy of {} with {} failed: {}", bucket, peer.id, e);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    async fn sync_bucket(
+        &self,
+        store: &LocalStore,
+        peer: &ClusterNode,
+        bucket: &str,
+        report: &mut RepairReport,
+    ) -> ClusterResult<()> {
+        let local_entries = self.digests(store, bucket, &peer.id).await?;
+        let local_tree = MerkleTree::build(&local_entries);
+
+        let request = TreeRequest {
+            bucket: bucket.to_string(),
+            node_id: self.local_node_id.clone(),
+        };
+        let response: TreeResponse = self
+            .transport
+            .post_json(peer, TREE_PATH, &request, RPC_TIMEOUT)
+            .await?;
+        let Some(leaves) = response.leaves else {
+            debug!("Peer {} has no bucket {}", peer.id, bucket);
+            return Ok(());
+        };
+        let remote_tree = MerkleTree::from_hex_leaves(&leaves)?;
+        report.buckets_compared += 1;
+
+        let divergent = local_tree.diff(&remote_tree);
+        if divergent.is_empty() {
+            return Ok(());
+        }
+        report.divergent_leaves += divergent.len() as u64;
+        debug!(
+            "{} leaves of {} differ from {}",
+            divergent.len(),
+            bucket,
+            peer.id
+        );
+
+        let request = LeavesRequest {
+            bucket: bucket.to_string(),
+            node_id: self.local_node_id.clone(),
+            leaves: divergent,
+        };
+        let response: LeavesResponse = self
+            .transport
+            .post_json(peer, LEAVES_PATH, &request, RPC_TIMEOUT)
+            .await?;
+
+        let local: HashMap<&str, &ObjectDigest> =
+            local_entries.iter().map(|e| (e.key.as_str(), e)).collect();
+
+        for entry in &response.entries {
+            let stale = match local.get(entry.key.as_str()) {
+                None => true,
+                Some(ours) => ours.etag != entry.etag && entry.last_modified > ours.last_modified,
+            };
+            if !stale {
+                continue;
+            }
+
+            report.divergent_objects += 1;
+            match self.repair(store, peer, bucket, entry).await {
+                Ok(()) => report.objects_repaired += 1,
+                Err(e) => {
+                    warn!("Failed to repair {}/{} from {}: {}", bucket, entry.key, peer.id, e);
+                    report.repair_failures += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy one object from a peer into local storage and metadata
+    async fn repair(
+        &self,
+        store: &LocalStore,
+        peer: &ClusterNode,
+        bucket: &str,
+        entry: &ObjectDigest,
+    ) -> ClusterResult<()> {
+        let version = Some(entry.version_id.as_str()).filter(|v| *v != "null");
+        let (data, expected) = self
+            .transport
+            .fetch_object_data(peer, bucket, &entry.key, version)
+            .await?;
+
+        if let Some(expected) = expected {
+            let got = checksum(&data);
+            if got != expected {
+                return Err(ClusterError::ChecksumMismatch { expected, got });
+            }
+        }
+
+        let storage_key = match version {
+            Some(vid) => format!("{}?versionId={}", entry.key, vid),
+            None => entry.key.clone(),
+        };
+        let size = data.len() as i64;
+        store.storage.put(bucket, &storage_key, data).await?;
+
+        // Keep the peer's etag and timestamp so both trees converge
+        let mut object = ObjectInternal::new(
+            bucket.to_string(),
+            entry.key.clone(),
+            size,
+            entry.etag.clone(),
+            entry
+                .content_type
+                .clone()
+                .unwrap_or_else(|| "application/octet-stream".to_string()),
+        );
+        object.version_id = entry.version_id.clone();
+        object.last_modified = entry.last_modified;
+        store.metadata.put_object(&object).await?;
+
+        debug!("Repaired {}/{} from {}", bucket, entry.key, peer.id);
+        Ok(())
+    }
+
+    /// Serve a peer's request for the leaf hashes of a bucket
+    pub async fn handle_tree(&self, req: TreeRequest) -> ClusterResult<TreeResponse> {
+        let store = self.local_store()?;
+        if store.metadata.get_bucket(&req.bucket).await?.is_none() {
+            return Ok(TreeResponse { leaves: None });
+        }
+
+        let entries = self.digests(&store, &req.bucket, &req.node_id).await?;
+        Ok(TreeResponse {
+            leaves: Some(MerkleTree::build(&entries).hex_leaves()),
+        })
+    }
+
+    /// Serve a peer's request for the entries of divergent leaves
+    pub async fn handle_leaves(&self, req: LeavesRequest) -> ClusterResult<LeavesResponse> {
+        let store = self.local_store()?;
+        let mut entries: Vec<ObjectDigest> = self
+            .digests(&store, &req.bucket, &req.node_id)
+            .await?
+            .into_iter()
+            .filter(|e| req.leaves.contains(&leaf_index(&e.key)))
+            .collect();
+
+        for entry in &mut entries {
+            if let Some(object) = store.metadata.get_object(&req.bucket, &entry.key).await? {
+                entry.content_type = Some(object.content_type);
+            }
+        }
+
+        Ok(LeavesResponse { entries })
+    }
+
+    /// Replace the local copy of an object with a healthy replica
+    ///
+    /// Used by the scrubber when the local data no longer matches its
+    /// etag. Peers are tried in turn; a copy is accepted only if it hashes
+    /// to `etag` (single-part objects) or has the recorded size. Returns
+    /// the node the object was restored from.
+    pub async fn restore_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+        etag: &str,
+        size: i64,
+    ) -> ClusterResult<NodeId> {
+        let store = self.local_store()?;
+        let version = Some(version_id).filter(|v| *v != "null");
+        let peers: Vec<ClusterNode> = self
+            .discovery
+            .healthy_nodes()
+            .into_iter()
+            .filter(|n| n.id != self.local_node_id && self.shared_with(bucket, key, &n.id))
+            .collect();
+
+        for peer in &peers {
+            let data = match self.transport.fetch_object_data(peer, bucket, key, version).await {
+                Ok((data, _)) => data,
+                Err(e) => {
+                    debug!("Replica of {}/{} on {} unavailable: {}", bucket, key, peer.id, e);
+                    continue;
+                }
+            };
+            let intact = if hafiz_crypto::is_multipart_etag(etag) {
+                data.len() as i64 == size
+            } else {
+                hafiz_crypto::md5_hash(&data) == etag
+            };
+            if !intact {
+                warn!("Replica of {}/{} on {} is damaged too", bucket, key, peer.id);
+                continue;
+            }
+
+            let storage_key = match version {
+                Some(vid) => format!("{}?versionId={}", key, vid),
+                None => key.to_string(),
+            };
+            store.storage.put(bucket, &storage_key, data).await?;
+            info!("Restored {}/{} from {}", bucket, key, peer.id);
+            return Ok(peer.id.clone());
+        }
+
+        Err(ClusterError::ReplicationFailed(format!(
+            "No intact replica of {}/{} among {} peer(s)",
+            bucket,
+            key,
+            peers.len()
+        )))
+    }
+
+    fn local_store(&self) -> ClusterResult<LocalStore> {
+        self.store
+            .read()
+            .clone()
+            .ok_or_else(|| ClusterError::Internal("No local store attached".to_string()))
+    }
+
+    /// Digests of the local objects in `bucket` that `peer` should also hold
+    async fn digests(
+        &self,
+        store: &LocalStore,
+        bucket: &str,
+        peer: &str,
+    ) -> ClusterResult<Vec<ObjectDigest>> {
+        let mut digests = Vec::new();
+        let mut token: Option<String> = None;
+
+        loop {
+            let (objects, _, truncated, next) = store
+                .metadata
+                .list_objects(bucket, None, None, LIST_PAGE_SIZE, token.as_deref())
+                .await?;
+
+            digests.extend(
+                objects
+                    .iter()
+                    .filter(|o| self.shared_with(bucket, &o.key, peer))
+                    .map(ObjectDigest::from),
+            );
+
+            if !truncated || next.is_none() {
+                break;
+            }
+            token = next;
+        }
+
+        Ok(digests)
+    }
+
+    /// Whether both this node and `peer` are expected to hold `bucket/key`
+    fn shared_with(&self, bucket: &str, key: &str, peer: &str) -> bool {
+        if !self.placement.is_enabled() {
+            return true;
+        }
+        let owners = self.placement.owners(bucket, key);
+        owners.contains(&self.local_node_id) && owners.iter().any(|o| o == peer)
+    }
+}
+
+impl std::fmt::Debug for AntiEntropyService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AntiEntropyService")
+            .field("enabled", &self.enabled)
+            .field("interval", &self.interval)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(key: &str, etag: &str) -> ObjectDigest {
+        ObjectDigest {
+            key: key.to_string(),
+            etag: etag.to_string(),
+            version_id: "null".to_string(),
+            last_modified: Utc::now(),
+            content_type: None,
+        }
+    }
+
+    #[test]
+    fn test_identical_trees() {
+        let a = vec![digest("a", "1"), digest("b", "2"), digest("c", "3")];
+        let mut b = a.clone();
+        b.reverse();
+
+        let left = MerkleTree::build(&a);
+        let right = MerkleTree::build(&b);
+        assert_eq!(left.root(), right.root());
+        assert!(left.diff(&right).is_empty());
+    }
+
+    #[test]
+    fn test_diff_finds_divergent_leaves() {
+        let entries: Vec<ObjectDigest> = (0..100).map(|i| digest(&format!("key-{}", i), "x")).collect();
+        let mut changed = entries.clone();
+        changed[42].etag = "y".to_string();
+        changed.push(digest("new-key", "z"));
+
+        let left = MerkleTree::build(&entries);
+        let right = MerkleTree::build(&changed);
+        let mut expected = vec![leaf_index("key-42"), leaf_index("new-key")];
+        expected.sort();
+        expected.dedup();
+
+        assert_ne!(left.root(), right.root());
+        assert_eq!(left.diff(&right), expected);
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let tree = MerkleTree::build(&[digest("a", "1")]);
+        let restored = MerkleTree::from_hex_leaves(&tree.hex_leaves()).unwrap();
+        assert_eq!(tree, restored);
+
+        assert!(MerkleTree::from_hex_leaves(&["00".to_string()]).is_err());
+    }
+}
@@ -5,20 +5,30 @@
 //! - Handle cluster API requests
 //! - Manage cluster state
 //! - Coordinate failover
+//! - Drain, decommission and re-add nodes
 
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use parking_lot::RwLock;
 use tokio::sync::mpsc;
 use tracing::{error, info, warn};
 
 use hafiz_core::types::{
-    ClusterConfig, ClusterMessage, ClusterNode, ClusterNodeStatus, ClusterStats, NodeId, NodeStats,
-    ReplicationEvent, ReplicationRule,
+    ClusterConfig, ClusterMessage, ClusterNode, ClusterNodeStatus, ClusterStats, MetadataConsensus,
+    NodeId, NodeStats, ReplicationEvent, ReplicationRule,
 };
 
+use hafiz_metadata::MetadataStore;
+use hafiz_storage::StorageEngine;
+
+use crate::anti_entropy::AntiEntropyService;
 use crate::discovery::{DiscoveryEvent, DiscoveryService};
 use crate::error::{ClusterError, ClusterResult};
+use crate::placement::PlacementService;
+use crate::raft::{HttpRaftNetwork, RaftConfig, RaftNode, RaftStorage, StateMachine};
 use crate::replicator::{Replicator, ReplicatorConfig, ReplicatorStats};
 use crate::transport::{ClusterTransport, TransportConfig};
 
@@ -34,6 +44,12 @@ pub struct ClusterManager {
     replication_tx: mpsc::Sender<ReplicationEvent>,
     /// Transport layer
     transport: Arc<ClusterTransport>,
+    /// Consistent hash placement
+    placement: Arc<PlacementService>,
+    /// Merkle tree comparison and repair between replicas
+    anti_entropy: Arc<AntiEntropyService>,
+    /// Raft node for metadata consensus, once started
+    raft: RwLock<Option<Arc<RaftNode>>>,
     /// Whether cluster mode is enabled
     enabled: bool,
 }
@@ -54,6 +70,10 @@ impl ClusterManager {
             ca_cert_path: config.cluster_ca_cert.clone(),
             client_cert_path: config.cluster_tls_cert.clone(),
             client_key_path: config.cluster_tls_key.clone(),
+            protocol: config.cluster_transport,
+            local_node_id: config.node_id.clone(),
+            cluster_name: config.name.clone(),
+            shared_secret: config.shared_secret.clone(),
             ..Default::default()
         };
         let transport = Arc::new(ClusterTransport::new(transport_config)?);
@@ -69,7 +89,12 @@ impl ClusterManager {
         ));
 
         // Create replicator
-        let replicator_config = ReplicatorConfig::default();
+        let replicator_config = ReplicatorConfig {
+            hint_dir: config.hint_dir.as_ref().map(PathBuf::from),
+            hint_ttl: Duration::from_secs(config.hint_ttl_secs),
+            max_hints_per_node: config.max_hints_per_node,
+            ..ReplicatorConfig::default()
+        };
         let (replicator, replication_tx) = Replicator::new(
             replicator_config,
             Arc::clone(&transport),
@@ -78,8 +103,25 @@ impl ClusterManager {
         );
         let replicator = Arc::new(replicator);
 
+        let placement = Arc::new(PlacementService::new(
+            &config,
+            Arc::clone(&discovery),
+            Arc::clone(&transport),
+        ));
+
+        let anti_entropy = Arc::new(AntiEntropyService::new(
+            &config,
+            Arc::clone(&discovery),
+            Arc::clone(&transport),
+            Arc::clone(&placement),
+        ));
+
         // Start listening for discovery events
-        Self::handle_discovery_events(discovery_rx, Arc::clone(&replicator));
+        Self::handle_discovery_events(
+            discovery_rx,
+            Arc::clone(&replicator),
+            Arc::clone(&placement),
+        );
 
         Ok(Self {
             config,
@@ -87,6 +129,9 @@ impl ClusterManager {
             replicator,
             replication_tx,
             transport,
+            placement,
+            anti_entropy,
+            raft: RwLock::new(None),
             enabled,
         })
     }
@@ -103,9 +148,14 @@ impl ClusterManager {
         // Start discovery
         self.discovery.start().await?;
 
+        // Pick up nodes learned from the seeds
+        self.placement.on_membership_change();
+
         // Start replicator
         self.replicator.start().await?;
 
+        self.anti_entropy.start();
+
         info!("Cluster manager started successfully");
         Ok(())
     }
@@ -132,6 +182,10 @@ impl ClusterManager {
         }
 
         // Stop components
+        if let Some(raft) = self.raft() {
+            raft.stop();
+        }
+        self.anti_entropy.stop();
         self.replicator.stop();
         self.discovery.stop();
 
@@ -139,11 +193,82 @@ impl ClusterManager {
         Ok(())
     }
 
+    /// Start Raft metadata consensus on top of the given state machine.
+    ///
+    /// Only valid when `metadata_consensus = "raft"` and `raft_data_dir` is
+    /// set. Peers are the nodes known to discovery.
+    pub async fn start_raft(
+        &self,
+        state_machine: Arc<dyn StateMachine>,
+    ) -> ClusterResult<Arc<RaftNode>> {
+        if self.config.metadata_consensus != MetadataConsensus::Raft {
+            return Err(ClusterError::InvalidConfig(
+                "metadata_consensus is not set to raft".to_string(),
+            ));
+        }
+        if let Some(raft) = self.raft() {
+            return Ok(raft);
+        }
+
+        let raft_config = RaftConfig::from_cluster_config(&self.config);
+        let network = Arc::new(HttpRaftNetwork::new(
+            Arc::clone(&self.discovery),
+            Arc::clone(&self.transport),
+            self.config.node_id.clone(),
+            raft_config.election_timeout,
+        ));
+        let raft_dir = self.config.raft_data_dir.as_ref().ok_or_else(|| {
+            ClusterError::InvalidConfig("Raft needs raft_data_dir to persist its log".to_string())
+        })?;
+        let storage = RaftStorage::new(Some(PathBuf::from(raft_dir)));
+
+        let raft = RaftNode::new(
+            self.config.node_id.clone(),
+            raft_config,
+            state_machine,
+            network,
+            storage,
+        )
+        .await?;
+        raft.start();
+
+        *self.raft.write() = Some(Arc::clone(&raft));
+        info!("Raft metadata consensus started");
+        Ok(raft)
+    }
+
+    /// Object placement service
+    pub fn placement(&self) -> Arc<PlacementService> {
+        Arc::clone(&self.placement)
+    }
+
+    /// Anti-entropy service
+    pub fn anti_entropy(&self) -> Arc<AntiEntropyService> {
+        Arc::clone(&self.anti_entropy)
+    }
+
+    /// Give services that move or repair data access to local objects
+    pub fn attach_store(&self, metadata: Arc<MetadataStore>, storage: Arc<dyn StorageEngine>) {
+        self.placement
+            .attach_store(Arc::clone(&metadata), Arc::clone(&storage));
+        self.anti_entropy.attach_store(metadata, storage);
+    }
+
+    /// The Raft node, if metadata consensus is running
+    pub fn raft(&self) -> Option<Arc<RaftNode>> {
+        self.raft.read().clone()
+    }
+
     /// Check if cluster mode is enabled
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
 
+    /// Get the cluster configuration
+    pub fn config(&self) -> &ClusterConfig {
+        &self.config
+    }
+
     /// Get the local node
     pub fn local_node(&self) -> ClusterNode {
         self.discovery.local_node()
@@ -182,6 +307,8 @@ impl ClusterManager {
             pending_replications: replicator_stats.pending,
             failed_replications: replicator_stats.failed,
             replication_lag_secs: 0, // TODO: Calculate
+            rebalance: self.placement.progress(),
+            anti_entropy: self.anti_entropy.stats(),
         }
     }
 
@@ -205,6 +332,11 @@ impl ClusterManager {
         self.replicator.rules()
     }
 
+    /// Record a bucket's tags for replication rules that filter on them
+    pub fn set_bucket_tags(&self, bucket: &str, tags: HashMap<String, String>) {
+        self.replicator.set_bucket_tags(bucket, tags);
+    }
+
     /// Queue a replication event
     pub async fn queue_replication(&self, event: ReplicationEvent) -> ClusterResult<()> {
         if !self.enabled {
@@ -213,11 +345,166 @@ impl ClusterManager {
         self.replicator.queue_event(event).await
     }
 
+    /// Wait for queued replication events to be sent, up to `timeout`
+    pub async fn drain_replication(&self, timeout: Duration) -> bool {
+        self.replicator.drain(timeout).await
+    }
+
     /// Get the replication event sender (for direct access)
     pub fn replication_sender(&self) -> mpsc::Sender<ReplicationEvent> {
         self.replication_tx.clone()
     }
 
+    /// Drain a node: it takes no new data, and the placement rebalance moves
+    /// the objects it owns to the remaining nodes
+    pub async fn drain_node(&self, node_id: &str) -> ClusterResult<ClusterNode> {
+        self.change_node_status(node_id, ClusterNodeStatus::Draining)
+            .await
+    }
+
+    /// Return a drained node to service
+    pub async fn activate_node(&self, node_id: &str) -> ClusterResult<ClusterNode> {
+        self.change_node_status(node_id, ClusterNodeStatus::Healthy)
+            .await
+    }
+
+    /// Remove a node from the cluster.
+    ///
+    /// The node must be drained first unless `force` is set. It is told to
+    /// stop heartbeating and the other members are told it left; it can
+    /// later be re-added with [`ClusterManager::add_node`].
+    pub async fn decommission_node(&self, node_id: &str, force: bool) -> ClusterResult<()> {
+        let is_local = node_id == self.config.node_id;
+        let node = if is_local {
+            self.local_node()
+        } else {
+            self.get_node(node_id)
+                .ok_or_else(|| ClusterError::NodeNotFound(node_id.to_string()))?
+        };
+
+        if !force {
+            if node.status != ClusterNodeStatus::Draining {
+                return Err(ClusterError::Conflict(format!(
+                    "Node {} must be drained before it is decommissioned",
+                    node_id
+                )));
+            }
+            if self.placement.progress().in_progress {
+                return Err(ClusterError::Conflict(
+                    "A rebalance is still moving data; retry once it finishes".to_string(),
+                ));
+            }
+        }
+
+        info!("Decommissioning node {} (force: {})", node_id, force);
+
+        let leave = ClusterMessage::LeaveNotification {
+            node_id: node_id.to_string(),
+            reason: "Decommissioned".to_string(),
+        };
+        for peer in self.nodes() {
+            if peer.id == node_id {
+                continue;
+            }
+            if let Err(e) = self.transport.send_message(&peer, &leave).await {
+                warn!("Failed to notify {} of decommission: {}", peer.id, e);
+            }
+        }
+
+        if is_local {
+            self.discovery.set_local_status(ClusterNodeStatus::Left);
+        } else {
+            let message = ClusterMessage::NodeStatusChange {
+                node_id: node_id.to_string(),
+                status: ClusterNodeStatus::Left,
+            };
+            if let Err(e) = self.transport.send_message(&node, &message).await {
+                if !force {
+                    return Err(e);
+                }
+                warn!("Decommissioned node {} was not reachable: {}", node_id, e);
+            }
+            self.discovery.handle_leave(node_id, "Decommissioned").await?;
+        }
+
+        Ok(())
+    }
+
+    /// Add the node serving `cluster_endpoint` to the cluster, or re-add a
+    /// decommissioned one
+    pub async fn add_node(&self, cluster_endpoint: &str) -> ClusterResult<ClusterNode> {
+        let endpoint = cluster_endpoint.trim_end_matches('/');
+        let request = ClusterMessage::JoinRequest {
+            node: self.local_node(),
+            cluster_name: self.config.name.clone(),
+        };
+
+        let nodes = match self.transport.send_join_request(endpoint, &request).await? {
+            ClusterMessage::JoinResponse {
+                accepted: true,
+                nodes,
+                ..
+            } => nodes,
+            ClusterMessage::JoinResponse { message, .. } => {
+                return Err(ClusterError::JoinRejected(message.unwrap_or_default()))
+            }
+            _ => {
+                return Err(ClusterError::Internal(
+                    "Unexpected response to join request".to_string(),
+                ))
+            }
+        };
+
+        // The responder lists itself last
+        let mut node = nodes
+            .iter()
+            .find(|n| n.cluster_endpoint.trim_end_matches('/') == endpoint)
+            .or_else(|| nodes.last())
+            .filter(|n| n.id != self.config.node_id)
+            .cloned()
+            .ok_or_else(|| ClusterError::NodeNotFound(endpoint.to_string()))?;
+
+        if node.status != ClusterNodeStatus::Healthy {
+            let message = ClusterMessage::NodeStatusChange {
+                node_id: node.id.clone(),
+                status: ClusterNodeStatus::Healthy,
+            };
+            self.transport.send_message(&node, &message).await?;
+            node.status = ClusterNodeStatus::Healthy;
+        }
+
+        info!("Added node {} ({}) to the cluster", node.id, endpoint);
+        self.discovery
+            .handle_join_request(node.clone(), &self.config.name)
+            .await?;
+        Ok(node)
+    }
+
+    /// Set a node's status locally and on the node itself; the other
+    /// members learn it from the node's heartbeats
+    async fn change_node_status(
+        &self,
+        node_id: &str,
+        status: ClusterNodeStatus,
+    ) -> ClusterResult<ClusterNode> {
+        if node_id == self.config.node_id {
+            self.discovery.set_local_status(status);
+            self.placement.on_membership_change();
+            return Ok(self.local_node());
+        }
+
+        let node = self
+            .get_node(node_id)
+            .ok_or_else(|| ClusterError::NodeNotFound(node_id.to_string()))?;
+        let message = ClusterMessage::NodeStatusChange {
+            node_id: node_id.to_string(),
+            status,
+        };
+        self.transport.send_message(&node, &message).await?;
+
+        self.discovery.set_node_status(node_id, status).await
+    }
+
     /// Handle an incoming cluster message
     pub async fn handle_message(&self, message: ClusterMessage) -> ClusterResult<ClusterMessage> {
         match message {
@@ -255,6 +542,18 @@ impl ClusterManager {
                     replication_rules: self.replication_rules(),
                 })
             }
+            ClusterMessage::NodeStatusChange { node_id, status } => {
+                if node_id == self.config.node_id {
+                    self.discovery.set_local_status(status);
+                    self.placement.on_membership_change();
+                } else {
+                    self.discovery.set_node_status(&node_id, status).await?;
+                }
+                Ok(ClusterMessage::Heartbeat {
+                    node: self.discovery.local_node(),
+                    stats: NodeStats::default(),
+                })
+            }
             _ => Err(ClusterError::Internal("Unhandled message type".to_string())),
         }
     }
@@ -263,24 +562,37 @@ impl ClusterManager {
     fn handle_discovery_events(
         mut rx: mpsc::Receiver<DiscoveryEvent>,
         replicator: Arc<Replicator>,
+        placement: Arc<PlacementService>,
     ) {
         tokio::spawn(async move {
             while let Some(event) = rx.recv().await {
                 match event {
                     DiscoveryEvent::NodeJoined(node) => {
                         info!("Node joined cluster: {} ({})", node.name, node.id);
+                        placement.on_membership_change();
+                        replicator.replay_hints(Some(node.id));
                     }
                     DiscoveryEvent::NodeLeft(node_id) => {
                         warn!("Node left cluster: {}", node_id);
+                        placement.on_membership_change();
                     }
                     DiscoveryEvent::NodeUnhealthy(node_id) => {
                         warn!("Node became unhealthy: {}", node_id);
                     }
                     DiscoveryEvent::NodeRecovered(node_id) => {
                         info!("Node recovered: {}", node_id);
+                        replicator.replay_hints(Some(node_id));
+                    }
+                    DiscoveryEvent::NodeStatusChanged(node_id, status) => {
+                        info!("Node {} is now {:?}", node_id, status);
+                        placement.on_membership_change();
+                        if status == ClusterNodeStatus::Healthy {
+                            replicator.replay_hints(Some(node_id));
+                        }
                     }
                     DiscoveryEvent::StateSynced => {
                         info!("Cluster state synchronized");
+                        placement.on_membership_change();
                     }
                 }
             }
@@ -5,6 +5,7 @@
 //! - Heartbeat-based health monitoring
 //! - Automatic node failure detection
 //! - Cluster state synchronization
+//! - Drain and decommission status changes
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -50,6 +51,8 @@ pub enum DiscoveryEvent {
     NodeUnhealthy(NodeId),
     /// A node recovered
     NodeRecovered(NodeId),
+    /// A node was drained or returned to service
+    NodeStatusChanged(NodeId, ClusterNodeStatus),
     /// Cluster state was synchronized
     StateSynced,
 }
@@ -61,12 +64,15 @@ impl DiscoveryService {
         transport: Arc<ClusterTransport>,
         event_tx: mpsc::Sender<DiscoveryEvent>,
     ) -> Self {
-        let local_node = ClusterNode::new(
+        let mut local_node = ClusterNode::new(
             config.node_id.clone(),
             config.node_name.clone(),
             config.advertise_endpoint.clone(),
             config.cluster_endpoint.clone(),
         );
+        local_node.grpc_endpoint = config.grpc_endpoint.clone();
+        local_node.zone = config.zone.clone();
+        local_node.rack = config.rack.clone();
 
         Self {
             local_node: Arc::new(RwLock::new(local_node)),
@@ -110,6 +116,37 @@ impl DiscoveryService {
         self.local_node.read().clone()
     }
 
+    /// Change the local node's status; peers learn it from the next heartbeat
+    pub fn set_local_status(&self, status: ClusterNodeStatus) {
+        let mut local = self.local_node.write();
+        if local.status != status {
+            info!("Local node status: {:?} -> {:?}", local.status, status);
+            local.status = status;
+        }
+    }
+
+    /// Record a status change of a known node, returning the updated node
+    pub async fn set_node_status(
+        &self,
+        node_id: &str,
+        status: ClusterNodeStatus,
+    ) -> ClusterResult<ClusterNode> {
+        let node = {
+            let mut nodes = self.nodes.write();
+            let node = nodes
+                .get_mut(node_id)
+                .ok_or_else(|| ClusterError::NodeNotFound(node_id.to_string()))?;
+            node.status = status;
+            node.clone()
+        };
+
+        let _ = self
+            .event_tx
+            .send(DiscoveryEvent::NodeStatusChanged(node_id.to_string(), status))
+            .await;
+        Ok(node)
+    }
+
     /// Get all known nodes
     pub fn nodes(&self) -> Vec<ClusterNode> {
         self.nodes.read().values().cloned().collect()
@@ -278,32 +315,45 @@ impl DiscoveryService {
         node: ClusterNode,
         stats: NodeStats,
     ) -> ClusterResult<()> {
-        let mut nodes = self.nodes.write();
-
-        if let Some(existing) = nodes.get_mut(&node.id) {
-            // Update existing node
-            existing.status = node.status;
-            existing.last_heartbeat = Utc::now();
-
-            // Check if node recovered
-            if existing.status == ClusterNodeStatus::Healthy
-                && matches!(
-                    existing.status,
-                    ClusterNodeStatus::Unreachable | ClusterNodeStatus::Degraded
-                )
-            {
-                let _ = self
-                    .event_tx
-                    .send(DiscoveryEvent::NodeRecovered(node.id.clone()))
-                    .await;
+        // A decommissioned node has been removed and must rejoin first
+        if node.status == ClusterNodeStatus::Left {
+            return Ok(());
+        }
+
+        let event = {
+            let mut nodes = self.nodes.write();
+
+            if let Some(existing) = nodes.get_mut(&node.id) {
+                // Update existing node
+                let previous = std::mem::replace(&mut existing.status, node.status);
+                existing.last_heartbeat = Utc::now();
+
+                if previous == ClusterNodeStatus::Draining || node.status == ClusterNodeStatus::Draining {
+                    (previous != node.status)
+                        .then(|| DiscoveryEvent::NodeStatusChanged(node.id.clone(), node.status))
+                } else if node.status == ClusterNodeStatus::Healthy
+                    && matches!(
+                        previous,
+                        ClusterNodeStatus::Unreachable | ClusterNodeStatus::Degraded
+                    )
+                {
+                    // Node recovered
+                    Some(DiscoveryEvent::NodeRecovered(node.id.clone()))
+                } else {
+                    None
+                }
+            } else {
+                // New node - add it
+                let mut new_node = node.clone();
+                new_node.last_heartbeat = Utc::now();
+                nodes.insert(node.id.clone(), new_node);
+
+                Some(DiscoveryEvent::NodeJoined(node))
             }
-        } else {
-            // New node - add it
-            let mut new_node = node.clone();
-            new_node.last_heartbeat = Utc::now();
-            nodes.insert(node.id.clone(), new_node);
+        };
 
-            let _ = self.event_tx.send(DiscoveryEvent::NodeJoined(node)).await;
+        if let Some(event) = event {
+            let _ = self.event_tx.send(event).await;
         }
 
         Ok(())
@@ -313,8 +363,8 @@ impl DiscoveryService {
     pub async fn handle_leave(&self, node_id: &str, reason: &str) -> ClusterResult<()> {
         info!("Node {} leaving cluster: {}", node_id, reason);
 
-        if let Some(mut node) = self.nodes.write().remove(node_id) {
-            node.status = ClusterNodeStatus::Left;
+        let removed = self.nodes.write().remove(node_id);
+        if removed.is_some() {
             let _ = self
                 .event_tx
                 .send(DiscoveryEvent::NodeLeft(node_id.to_string()))
@@ -343,6 +393,10 @@ impl DiscoveryService {
                 }
 
                 let local = local_node.read().clone();
+                if local.status == ClusterNodeStatus::Left {
+                    // Decommissioned; stay silent until re-added
+                    continue;
+                }
                 let heartbeat = ClusterMessage::Heartbeat {
                     node: local.clone(),
                     stats: NodeStats::default(), // TODO: Collect real stats
@@ -442,4 +496,36 @@ mod tests {
             _ => panic!("Wrong event type"),
         }
     }
+    #[tokio::test]
+    async fn test_heartbeat_status_changes() {
+        let transport = Arc::new(ClusterTransport::new(Default::default()).unwrap());
+        let (tx, mut rx) = mpsc::channel(16);
+        let discovery = DiscoveryService::new(ClusterConfig::default(), transport, tx);
+
+        let mut node = ClusterNode::new(
+            "peer".to_string(),
+            "Peer".to_string(),
+            "http://peer:9000".to_string(),
+            "http://peer:9001".to_string(),
+        );
+        node.status = ClusterNodeStatus::Healthy;
+        discovery.handle_heartbeat(node.clone(), NodeStats::default()).await.unwrap();
+        assert!(matches!(rx.recv().await, Some(DiscoveryEvent::NodeJoined(_))));
+
+        node.status = ClusterNodeStatus::Draining;
+        discovery.handle_heartbeat(node.clone(), NodeStats::default()).await.unwrap();
+        assert!(matches!(
+            rx.recv().await,
+            Some(DiscoveryEvent::NodeStatusChanged(_, ClusterNodeStatus::Draining))
+        ));
+
+        // A decommissioned node's heartbeats are ignored
+        node.status = ClusterNodeStatus::Left;
+        discovery.handle_heartbeat(node, NodeStats::default()).await.unwrap();
+        assert_eq!(
+            discovery.get_node("peer").unwrap().status,
+            ClusterNodeStatus::Draining
+        );
+        assert!(rx.try_recv().is_err());
+    }
 }
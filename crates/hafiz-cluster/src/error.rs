@@ -45,7 +45,13 @@ pub enum ClusterError {
     Conflict(String),
 
     #[error("Storage error: {0}")]
-    Storage(#[from] hafiz_core::error::HafizError),
+    Storage(#[from] hafiz_core::Error),
+
+    #[error("Not the Raft leader (leader: {0:?})")]
+    NotLeader(Option<String>),
+
+    #[error("Raft proposal timed out")]
+    ProposalTimeout,
 
     #[error("Serialization error: {0}")]
     Serialization(#[from] serde_json::Error),
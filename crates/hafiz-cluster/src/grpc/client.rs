@@ -0,0 +1,300 @@
+//! gRPC client side of the cluster transport
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use parking_lot::RwLock;
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+use tonic::metadata::MetadataValue;
+use tonic::{Code, Request, Status};
+
+use hafiz_core::types::{ClusterMessage, NodeId};
+
+use super::proto::cluster_client::ClusterClient;
+use super::proto::{
+    object_chunk::Chunk, CallRequest, FetchObjectRequest, HandshakeRequest, JsonPayload,
+    ObjectChunk, ObjectHeader, PingRequest,
+};
+use super::server::data_chunks;
+use super::{GrpcTlsConfig, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use crate::error::{ClusterError, ClusterResult};
+use crate::transport::PEER_SECRET_HEADER;
+
+/// gRPC client with one multiplexed channel per peer endpoint
+pub struct GrpcClient {
+    local_node_id: NodeId,
+    cluster_name: String,
+    /// Sent as metadata with every call
+    shared_secret: Option<MetadataValue<tonic::metadata::Ascii>>,
+    tls: Option<ClientTlsConfig>,
+    connect_timeout: Duration,
+    timeout: Duration,
+    channels: RwLock<HashMap<String, Channel>>,
+}
+
+impl GrpcClient {
+    /// Fails if `tls` names certificates that cannot be read
+    pub fn new(
+        local_node_id: NodeId,
+        cluster_name: String,
+        shared_secret: Option<String>,
+        tls: Option<GrpcTlsConfig>,
+        connect_timeout: Duration,
+        timeout: Duration,
+    ) -> ClusterResult<Self> {
+        let shared_secret = shared_secret
+            .map(|secret| {
+                secret
+                    .parse()
+                    .map_err(|_| ClusterError::InvalidConfig("Invalid cluster shared secret".to_string()))
+            })
+            .transpose()?;
+        let tls = tls
+            .map(|tls| -> ClusterResult<_> {
+                Ok(ClientTlsConfig::new()
+                    .ca_certificate(tls.ca_certificate()?)
+                    .identity(tls.identity()?))
+            })
+            .transpose()?;
+        Ok(Self {
+            local_node_id,
+            cluster_name,
+            shared_secret,
+            tls,
+            connect_timeout,
+            timeout,
+            channels: RwLock::new(HashMap::new()),
+        })
+    }
+
+    /// Drop the cached channel to an endpoint so the next call reconnects
+    pub fn forget(&self, endpoint: &str) {
+        self.channels.write().remove(endpoint);
+    }
+
+    /// Negotiate the protocol version with a peer
+    pub async fn handshake(&self, endpoint: &str) -> ClusterResult<u32> {
+        let request = HandshakeRequest {
+            node_id: self.local_node_id.clone(),
+            cluster_name: self.cluster_name.clone(),
+            protocol_version: PROTOCOL_VERSION,
+            min_protocol_version: MIN_PROTOCOL_VERSION,
+        };
+
+        let response = self
+            .client(endpoint)
+            .await?
+            .handshake(self.request(request, self.timeout))
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        if response.protocol_version == 0 {
+            return Err(ClusterError::Transport(format!(
+                "No common protocol version with {} (server {})",
+                endpoint, response.server_version
+            )));
+        }
+
+        Ok(response.protocol_version)
+    }
+
+    /// Check that a peer is reachable
+    pub async fn ping(&self, endpoint: &str) -> ClusterResult<()> {
+        self.client(endpoint)
+            .await?
+            .ping(self.request(PingRequest {}, Duration::from_secs(5)))
+            .await
+            .map_err(status_error)?;
+        Ok(())
+    }
+
+    /// Exchange a cluster message with a peer
+    pub async fn send_message(
+        &self,
+        endpoint: &str,
+        message: &ClusterMessage,
+    ) -> ClusterResult<ClusterMessage> {
+        let payload = JsonPayload {
+            json: serde_json::to_vec(message)?.into(),
+        };
+
+        let response = self
+            .client(endpoint)
+            .await?
+            .send_message(self.request(payload, self.timeout))
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        Ok(serde_json::from_slice(&response.json)?)
+    }
+
+    /// JSON call to a cluster endpoint path, the gRPC counterpart of
+    /// `ClusterTransport::post_json`
+    pub async fn call_json<Req, Resp>(
+        &self,
+        endpoint: &str,
+        path: &str,
+        body: &Req,
+        timeout: Duration,
+    ) -> ClusterResult<Resp>
+    where
+        Req: serde::Serialize + ?Sized,
+        Resp: serde::de::DeserializeOwned,
+    {
+        let request = CallRequest {
+            path: path.to_string(),
+            json: serde_json::to_vec(body)?.into(),
+        };
+
+        let response = self
+            .client(endpoint)
+            .await?
+            .call(self.request(request, timeout))
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        Ok(serde_json::from_slice(&response.json)?)
+    }
+
+    /// Stream object data from a peer, returning the data and its checksum
+    pub async fn fetch_object(
+        &self,
+        endpoint: &str,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> ClusterResult<(Bytes, Option<String>)> {
+        let request = FetchObjectRequest {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id: version_id.map(String::from),
+        };
+
+        let mut stream = self
+            .client(endpoint)
+            .await?
+            .fetch_object(self.request(request, self.timeout))
+            .await
+            .map_err(status_error)?
+            .into_inner();
+
+        let mut checksum = None;
+        let mut data = BytesMut::new();
+
+        while let Some(chunk) = stream.message().await.map_err(status_error)? {
+            match chunk.chunk {
+                Some(Chunk::Header(header)) => {
+                    checksum = header.checksum;
+                    data.reserve(header.size as usize);
+                }
+                Some(Chunk::Data(bytes)) => data.extend_from_slice(&bytes),
+                None => {}
+            }
+        }
+
+        Ok((data.freeze(), checksum))
+    }
+
+    /// Stream object data to a peer
+    pub async fn upload_object(
+        &self,
+        endpoint: &str,
+        bucket: &str,
+        key: &str,
+        data: Bytes,
+        checksum: Option<&str>,
+        metadata: &HashMap<String, String>,
+    ) -> ClusterResult<()> {
+        let header = ObjectChunk {
+            chunk: Some(Chunk::Header(ObjectHeader {
+                bucket: bucket.to_string(),
+                key: key.to_string(),
+                size: data.len() as u64,
+                checksum: checksum.map(String::from),
+                metadata: metadata.clone(),
+            })),
+        };
+        let chunks: Vec<ObjectChunk> = std::iter::once(header).chain(data_chunks(data)).collect();
+
+        self.client(endpoint)
+            .await?
+            .upload_object(self.request(tokio_stream::iter(chunks), self.timeout))
+            .await
+            .map_err(status_error)?;
+
+        Ok(())
+    }
+
+    fn request<T>(&self, message: T, timeout: Duration) -> Request<T> {
+        let mut request = Request::new(message);
+        request.set_timeout(timeout);
+        if let Some(secret) = &self.shared_secret {
+            request.metadata_mut().insert(PEER_SECRET_HEADER, secret.clone());
+        }
+        request
+    }
+
+    async fn client(&self, endpoint: &str) -> ClusterResult<ClusterClient<Channel>> {
+        let cached = self.channels.read().get(endpoint).cloned();
+        let channel = match cached {
+            Some(channel) => channel,
+            None => {
+                let channel = self.connect(endpoint).await?;
+                self.channels
+                    .write()
+                    .insert(endpoint.to_string(), channel.clone());
+                channel
+            }
+        };
+
+        // Object data is chunked, but snapshots and listings travel as a
+        // single JSON payload
+        Ok(ClusterClient::new(channel)
+            .max_decoding_message_size(usize::MAX)
+            .max_encoding_message_size(usize::MAX))
+    }
+
+    async fn connect(&self, endpoint: &str) -> ClusterResult<Channel> {
+        let mut builder = Endpoint::from_shared(endpoint.to_string())
+            .map_err(|e| ClusterError::InvalidConfig(format!("Invalid gRPC endpoint {}: {}", endpoint, e)))?
+            .connect_timeout(self.connect_timeout)
+            .tcp_nodelay(true);
+
+        if let Some(tls) = &self.tls {
+            builder = builder
+                .tls_config(tls.clone())
+                .map_err(|e| ClusterError::Transport(e.to_string()))?;
+        }
+
+        builder
+            .connect()
+            .await
+            .map_err(|e| ClusterError::NodeUnreachable(format!("{}: {}", endpoint, e)))
+    }
+}
+
+impl std::fmt::Debug for GrpcClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GrpcClient")
+            .field("local_node_id", &self.local_node_id)
+            .field("tls", &self.tls.is_some())
+            .finish()
+    }
+}
+
+fn status_error(status: Status) -> ClusterError {
+    match status.code() {
+        Code::Unavailable | Code::DeadlineExceeded => {
+            ClusterError::NodeUnreachable(status.message().to_string())
+        }
+        Code::FailedPrecondition => ClusterError::NotLeader(
+            Some(status.message().to_string()).filter(|leader| !leader.is_empty()),
+        ),
+        Code::DataLoss => ClusterError::ReplicationFailed(status.message().to_string()),
+        code => ClusterError::Transport(format!("gRPC {:?}: {}", code, status.message())),
+    }
+}
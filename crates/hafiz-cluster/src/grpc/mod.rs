@@ -0,0 +1,226 @@
+//! gRPC cluster transport
+//!
+//! An alternative to the HTTP transport with mutual TLS between nodes and
+//! streaming object transfer. `ClusterTransport` negotiates it per peer with
+//! a version handshake and falls back to HTTP for peers that do not serve
+//! gRPC or speak an incompatible protocol version.
+
+mod client;
+mod server;
+
+pub use client::GrpcClient;
+pub use server::{ClusterRpcHandler, GrpcServer};
+
+use tonic::transport::{Certificate, Identity};
+
+use hafiz_core::types::ClusterConfig;
+
+use crate::error::{ClusterError, ClusterResult};
+
+/// Generated protobuf types and service stubs
+pub(crate) mod proto {
+    tonic::include_proto!("hafiz.cluster.v1");
+}
+
+/// Highest protocol version spoken by this build
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest protocol version this build still accepts
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Size of data chunks in object transfer streams
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Pick the protocol version to use with a peer that speaks `min..=max`
+///
+/// Returns `None` if the ranges do not overlap.
+pub fn negotiate_version(min: u32, max: u32) -> Option<u32> {
+    let version = max.min(PROTOCOL_VERSION);
+    (version >= min.max(MIN_PROTOCOL_VERSION)).then_some(version)
+}
+
+/// Certificates for mutual TLS between nodes
+///
+/// Every node presents its own certificate and only accepts peers whose
+/// certificate is signed by the cluster CA.
+#[derive(Debug, Clone)]
+pub struct GrpcTlsConfig {
+    pub cert_path: String,
+    pub key_path: String,
+    pub ca_cert_path: String,
+}
+
+impl GrpcTlsConfig {
+    /// TLS settings from the cluster configuration, `None` if TLS is disabled
+    pub fn from_cluster_config(config: &ClusterConfig) -> ClusterResult<Option<Self>> {
+        if !config.cluster_tls_enabled {
+            return Ok(None);
+        }
+
+        match (
+            &config.cluster_tls_cert,
+            &config.cluster_tls_key,
+            &config.cluster_ca_cert,
+        ) {
+            (Some(cert), Some(key), Some(ca)) => Ok(Some(Self {
+                cert_path: cert.clone(),
+                key_path: key.clone(),
+                ca_cert_path: ca.clone(),
+            })),
+            _ => Err(ClusterError::InvalidConfig(
+                "gRPC mutual TLS requires cluster_tls_cert, cluster_tls_key and cluster_ca_cert"
+                    .to_string(),
+            )),
+        }
+    }
+
+    pub(crate) fn identity(&self) -> ClusterResult<Identity> {
+        Ok(Identity::from_pem(
+            read_pem(&self.cert_path)?,
+            read_pem(&self.key_path)?,
+        ))
+    }
+
+    pub(crate) fn ca_certificate(&self) -> ClusterResult<Certificate> {
+        Ok(Certificate::from_pem(read_pem(&self.ca_cert_path)?))
+    }
+}
+
+fn read_pem(path: &str) -> ClusterResult<Vec<u8>> {
+    std::fs::read(path)
+        .map_err(|e| ClusterError::InvalidConfig(format!("Failed to read {}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use hafiz_core::types::{ClusterMessage, NodeId};
+
+    use super::*;
+
+    /// Answers every call with its own input
+    struct EchoHandler;
+
+    #[async_trait]
+    impl ClusterRpcHandler for EchoHandler {
+        async fn handle_message(&self, message: ClusterMessage) -> ClusterResult<ClusterMessage> {
+            Ok(message)
+        }
+
+        async fn call(&self, _path: &str, body: Bytes) -> ClusterResult<Bytes> {
+            Ok(body)
+        }
+
+        async fn get_object(
+            &self,
+            _bucket: &str,
+            key: &str,
+            _version_id: Option<&str>,
+        ) -> ClusterResult<Option<Bytes>> {
+            Ok(Some(Bytes::copy_from_slice(key.as_bytes())))
+        }
+
+        async fn put_object(
+            &self,
+            _bucket: &str,
+            _key: &str,
+            _data: Bytes,
+            _metadata: HashMap<String, String>,
+        ) -> ClusterResult<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        assert_eq!(negotiate_version(1, 1), Some(1));
+        // Newer peer that still accepts our version
+        assert_eq!(negotiate_version(1, PROTOCOL_VERSION + 3), Some(PROTOCOL_VERSION));
+        // Peer that requires a newer protocol
+        assert_eq!(negotiate_version(PROTOCOL_VERSION + 1, PROTOCOL_VERSION + 2), None);
+    }
+
+    #[test]
+    fn test_tls_requires_all_files() {
+        let mut config = ClusterConfig::default();
+        assert!(GrpcTlsConfig::from_cluster_config(&config).unwrap().is_none());
+
+        config.cluster_tls_enabled = true;
+        config.cluster_tls_cert = Some("node.crt".to_string());
+        assert!(GrpcTlsConfig::from_cluster_config(&config).is_err());
+
+        config.cluster_tls_key = Some("node.key".to_string());
+        config.cluster_ca_cert = Some("ca.crt".to_string());
+        let tls = GrpcTlsConfig::from_cluster_config(&config).unwrap().unwrap();
+        assert_eq!(tls.ca_cert_path, "ca.crt");
+    }
+
+    #[test]
+    fn test_server_refuses_unreadable_certificates() {
+        let config = ClusterConfig {
+            cluster_tls_enabled: true,
+            cluster_tls_cert: Some("/nonexistent/node.crt".to_string()),
+            cluster_tls_key: Some("/nonexistent/node.key".to_string()),
+            cluster_ca_cert: Some("/nonexistent/ca.crt".to_string()),
+            ..ClusterConfig::default()
+        };
+        assert!(GrpcServer::new(&config, Arc::new(EchoHandler)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_loopback_requires_shared_secret() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = ClusterConfig {
+            shared_secret: Some("s3cret".to_string()),
+            ..ClusterConfig::default()
+        };
+        let server = GrpcServer::new(&config, Arc::new(EchoHandler)).unwrap();
+        tokio::spawn(server.serve(SocketAddr::from(([127, 0, 0, 1], port)), std::future::pending()));
+
+        let endpoint = format!("http://127.0.0.1:{}", port);
+        let client = |secret: Option<&str>| {
+            GrpcClient::new(
+                NodeId::new(),
+                config.name.clone(),
+                secret.map(String::from),
+                None,
+                Duration::from_secs(1),
+                Duration::from_secs(5),
+            )
+            .unwrap()
+        };
+
+        // Wait for the server to start listening
+        let peer = client(Some("s3cret"));
+        let mut version = None;
+        for _ in 0..50 {
+            match peer.handshake(&endpoint).await {
+                Ok(v) => {
+                    version = Some(v);
+                    break;
+                }
+                Err(_) => {
+                    peer.forget(&endpoint);
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                }
+            }
+        }
+        assert_eq!(version, Some(PROTOCOL_VERSION));
+
+        let (data, _) = peer.fetch_object(&endpoint, "bucket", "key", None).await.unwrap();
+        assert_eq!(&data[..], b"key");
+
+        assert!(client(Some("wrong")).handshake(&endpoint).await.is_err());
+        assert!(client(None).ping(&endpoint).await.is_err());
+    }
+}
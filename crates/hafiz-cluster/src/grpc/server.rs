@@ -0,0 +1,318 @@
+//! gRPC server side of the cluster transport
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use futures::Stream;
+use tonic::service::interceptor::InterceptedService;
+use tonic::transport::{Server, ServerTlsConfig};
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{debug, info};
+
+use hafiz_core::types::{ClusterConfig, ClusterMessage, NodeId};
+
+use super::proto::cluster_server::{Cluster, ClusterServer};
+use super::proto::{
+    object_chunk::Chunk, CallRequest, FetchObjectRequest, HandshakeRequest, HandshakeResponse,
+    JsonPayload, ObjectChunk, ObjectHeader, PingRequest, PingResponse, UploadObjectResponse,
+};
+use super::{negotiate_version, GrpcTlsConfig, CHUNK_SIZE};
+use crate::error::{ClusterError, ClusterResult};
+use crate::placement::checksum;
+use crate::transport::{verify_peer_secret, PEER_SECRET_HEADER};
+
+/// Serves the requests that arrive over gRPC
+///
+/// Implemented by the S3 server, which owns storage, metadata and the
+/// cluster manager; every method has an HTTP counterpart under `/cluster/*`.
+#[async_trait]
+pub trait ClusterRpcHandler: Send + Sync + 'static {
+    /// Handle a cluster membership message
+    async fn handle_message(&self, message: ClusterMessage) -> ClusterResult<ClusterMessage>;
+
+    /// Handle a JSON call to a cluster endpoint path
+    async fn call(&self, path: &str, body: Bytes) -> ClusterResult<Bytes>;
+
+    /// Read object data, `None` if the object is not stored here
+    async fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> ClusterResult<Option<Bytes>>;
+
+    /// Store object data pushed by a peer; the checksum is already verified
+    async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Bytes,
+        metadata: HashMap<String, String>,
+    ) -> ClusterResult<()>;
+}
+
+/// gRPC server for node-to-node traffic
+pub struct GrpcServer {
+    service: ClusterService,
+    tls: Option<ServerTlsConfig>,
+    shared_secret: Option<String>,
+}
+
+impl GrpcServer {
+    /// Fails if TLS is enabled but its certificates are missing or unreadable
+    pub fn new(config: &ClusterConfig, handler: Arc<dyn ClusterRpcHandler>) -> ClusterResult<Self> {
+        let tls = GrpcTlsConfig::from_cluster_config(config)?
+            .map(|tls| -> ClusterResult<_> {
+                Ok(ServerTlsConfig::new()
+                    .identity(tls.identity()?)
+                    .client_ca_root(tls.ca_certificate()?))
+            })
+            .transpose()?;
+        Ok(Self {
+            service: ClusterService {
+                local_node_id: config.node_id.clone(),
+                cluster_name: config.name.clone(),
+                handler,
+            },
+            tls,
+            shared_secret: config.shared_secret.clone(),
+        })
+    }
+
+    /// Serve until `shutdown` completes
+    ///
+    /// With TLS configured, peers must present a certificate signed by the
+    /// cluster CA, and with a shared secret every call must carry it.
+    pub async fn serve<F>(self, addr: SocketAddr, shutdown: F) -> ClusterResult<()>
+    where
+        F: Future<Output = ()> + Send,
+    {
+        let mut builder = Server::builder();
+
+        if let Some(tls) = &self.tls {
+            builder = builder
+                .tls_config(tls.clone())
+                .map_err(|e| ClusterError::InvalidConfig(e.to_string()))?;
+        }
+
+        info!(
+            "Cluster gRPC server listening on {} (mTLS: {})",
+            addr,
+            self.tls.is_some()
+        );
+
+        let service = ClusterServer::new(self.service)
+            .max_decoding_message_size(usize::MAX)
+            .max_encoding_message_size(usize::MAX);
+        let shared_secret = self.shared_secret;
+        let service = InterceptedService::new(service, move |request: Request<()>| {
+            let presented = request
+                .metadata()
+                .get(PEER_SECRET_HEADER)
+                .and_then(|v| v.to_str().ok());
+            if verify_peer_secret(shared_secret.as_deref(), presented) {
+                Ok(request)
+            } else {
+                Err(Status::unauthenticated("Invalid cluster secret"))
+            }
+        });
+
+        builder
+            .add_service(service)
+            .serve_with_shutdown(addr, shutdown)
+            .await
+            .map_err(|e| ClusterError::Transport(e.to_string()))
+    }
+}
+
+struct ClusterService {
+    local_node_id: NodeId,
+    cluster_name: String,
+    handler: Arc<dyn ClusterRpcHandler>,
+}
+
+type ObjectStream = Pin<Box<dyn Stream<Item = Result<ObjectChunk, Status>> + Send>>;
+
+#[async_trait]
+impl Cluster for ClusterService {
+    async fn handshake(
+        &self,
+        request: Request<HandshakeRequest>,
+    ) -> Result<Response<HandshakeResponse>, Status> {
+        let req = request.into_inner();
+
+        if req.cluster_name != self.cluster_name {
+            return Err(Status::permission_denied(
+                ClusterError::ClusterNameMismatch {
+                    expected: self.cluster_name.clone(),
+                    got: req.cluster_name,
+                }
+                .to_string(),
+            ));
+        }
+
+        let version = negotiate_version(req.min_protocol_version, req.protocol_version);
+        debug!(
+            "gRPC handshake from {}: protocol {:?}",
+            req.node_id, version
+        );
+
+        Ok(Response::new(HandshakeResponse {
+            node_id: self.local_node_id.clone(),
+            protocol_version: version.unwrap_or(0),
+            server_version: hafiz_core::VERSION.to_string(),
+        }))
+    }
+
+    async fn ping(&self, _request: Request<PingRequest>) -> Result<Response<PingResponse>, Status> {
+        Ok(Response::new(PingResponse {
+            node_id: self.local_node_id.clone(),
+        }))
+    }
+
+    async fn send_message(
+        &self,
+        request: Request<JsonPayload>,
+    ) -> Result<Response<JsonPayload>, Status> {
+        let message: ClusterMessage = serde_json::from_slice(&request.into_inner().json)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let response = self
+            .handler
+            .handle_message(message)
+            .await
+            .map_err(error_status)?;
+
+        let json = serde_json::to_vec(&response).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(JsonPayload { json: json.into() }))
+    }
+
+    async fn call(&self, request: Request<CallRequest>) -> Result<Response<JsonPayload>, Status> {
+        let req = request.into_inner();
+        let json = self
+            .handler
+            .call(&req.path, req.json)
+            .await
+            .map_err(error_status)?;
+        Ok(Response::new(JsonPayload { json }))
+    }
+
+    type FetchObjectStream = ObjectStream;
+
+    async fn fetch_object(
+        &self,
+        request: Request<FetchObjectRequest>,
+    ) -> Result<Response<Self::FetchObjectStream>, Status> {
+        let req = request.into_inner();
+        let data = self
+            .handler
+            .get_object(&req.bucket, &req.key, req.version_id.as_deref())
+            .await
+            .map_err(error_status)?
+            .ok_or_else(|| Status::not_found(format!("{}/{}", req.bucket, req.key)))?;
+
+        let header = ObjectChunk {
+            chunk: Some(Chunk::Header(ObjectHeader {
+                bucket: req.bucket,
+                key: req.key,
+                size: data.len() as u64,
+                checksum: Some(checksum(&data)),
+                metadata: HashMap::new(),
+            })),
+        };
+        let chunks: Vec<Result<ObjectChunk, Status>> = std::iter::once(header)
+            .chain(data_chunks(data))
+            .map(Ok)
+            .collect();
+
+        Ok(Response::new(Box::pin(tokio_stream::iter(chunks))))
+    }
+
+    async fn upload_object(
+        &self,
+        request: Request<Streaming<ObjectChunk>>,
+    ) -> Result<Response<UploadObjectResponse>, Status> {
+        let mut stream = request.into_inner();
+
+        let header = match stream.message().await? {
+            Some(ObjectChunk {
+                chunk: Some(Chunk::Header(header)),
+            }) => header,
+            _ => {
+                return Err(Status::invalid_argument(
+                    "Upload must start with an object header",
+                ))
+            }
+        };
+
+        let mut data = BytesMut::with_capacity(header.size as usize);
+        while let Some(chunk) = stream.message().await? {
+            match chunk.chunk {
+                Some(Chunk::Data(bytes)) => data.extend_from_slice(&bytes),
+                _ => return Err(Status::invalid_argument("Unexpected chunk in upload stream")),
+            }
+        }
+        let data = data.freeze();
+
+        if let Some(expected) = header.checksum {
+            let got = checksum(&data);
+            if got != expected {
+                return Err(error_status(ClusterError::ChecksumMismatch { expected, got }));
+            }
+        }
+
+        let bytes_received = data.len() as u64;
+        self.handler
+            .put_object(&header.bucket, &header.key, data, header.metadata)
+            .await
+            .map_err(error_status)?;
+
+        Ok(Response::new(UploadObjectResponse { bytes_received }))
+    }
+}
+
+/// Split object data into stream chunks without copying
+pub(crate) fn data_chunks(data: Bytes) -> impl Iterator<Item = ObjectChunk> {
+    (0..data.len()).step_by(CHUNK_SIZE).map(move |start| {
+        let end = (start + CHUNK_SIZE).min(data.len());
+        ObjectChunk {
+            chunk: Some(Chunk::Data(data.slice(start..end))),
+        }
+    })
+}
+
+fn error_status(error: ClusterError) -> Status {
+    match error {
+        ClusterError::NodeNotFound(msg) => Status::not_found(msg),
+        ClusterError::NotLeader(leader) => Status::failed_precondition(leader.unwrap_or_default()),
+        ClusterError::ChecksumMismatch { .. } => Status::data_loss(error.to_string()),
+        ClusterError::ClusterNameMismatch { .. } | ClusterError::JoinRejected(_) => {
+            Status::permission_denied(error.to_string())
+        }
+        _ => Status::internal(error.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_chunks() {
+        let data = Bytes::from(vec![7u8; CHUNK_SIZE * 2 + 10]);
+        let sizes: Vec<usize> = data_chunks(data)
+            .map(|c| match c.chunk {
+                Some(Chunk::Data(bytes)) => bytes.len(),
+                _ => panic!("expected data chunk"),
+            })
+            .collect();
+        assert_eq!(sizes, vec![CHUNK_SIZE, CHUNK_SIZE, 10]);
+
+        assert_eq!(data_chunks(Bytes::new()).count(), 0);
+    }
+}
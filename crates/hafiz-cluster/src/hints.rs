@@ -0,0 +1,308 @@
+//! Hinted handoff
+//!
+//! When a replication target is down, the coordinating node keeps a "hint"
+//! (the replication event plus its target) and replays it once the target
+//! is healthy again. Hints are written one file per hint under the hint
+//! directory so they survive restarts; each file is written to a temporary
+//! name and renamed into place. Hints older than the TTL are dropped and
+//! left to anti-entropy.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use hafiz_core::types::{NodeId, ReplicationEvent};
+
+use crate::error::ClusterResult;
+
+/// Replication event waiting for a target node to come back
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hint {
+    pub id: String,
+    pub target_node: NodeId,
+    pub event: ReplicationEvent,
+    pub created_at: DateTime<Utc>,
+    /// Failed replay attempts so far
+    pub attempts: u32,
+}
+
+impl Hint {
+    fn is_expired(&self, ttl: Duration) -> bool {
+        let age = Utc::now() - self.created_at;
+        age.to_std().map_or(false, |age| age > ttl)
+    }
+}
+
+/// Per-node queues of hints, optionally persisted to disk
+#[derive(Debug)]
+pub struct HintStore {
+    dir: Option<PathBuf>,
+    ttl: Duration,
+    max_per_node: usize,
+    queues: RwLock<HashMap<NodeId, VecDeque<Hint>>>,
+}
+
+impl HintStore {
+    /// Open the store, loading hints left by a previous run
+    pub fn open(dir: Option<PathBuf>, ttl: Duration, max_per_node: usize) -> ClusterResult<Self> {
+        let store = Self {
+            dir,
+            ttl,
+            max_per_node: max_per_node.max(1),
+            queues: RwLock::new(HashMap::new()),
+        };
+
+        if let Some(dir) = &store.dir {
+            std::fs::create_dir_all(dir)?;
+
+            let mut loaded: Vec<Hint> = Vec::new();
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                match std::fs::read(&path).map(|data| serde_json::from_slice::<Hint>(&data)) {
+                    Ok(Ok(hint)) => loaded.push(hint),
+                    Ok(Err(e)) => warn!("Skipping unreadable hint {}: {}", path.display(), e),
+                    Err(e) => warn!("Skipping unreadable hint {}: {}", path.display(), e),
+                }
+            }
+
+            loaded.sort_by_key(|h| h.created_at);
+            debug!("Loaded {} hints from {}", loaded.len(), dir.display());
+
+            let mut queues = store.queues.write();
+            for hint in loaded {
+                queues.entry(hint.target_node.clone()).or_default().push_back(hint);
+            }
+        }
+
+        Ok(store)
+    }
+
+    /// Store that keeps hints in memory only
+    pub fn in_memory(ttl: Duration, max_per_node: usize) -> Self {
+        Self {
+            dir: None,
+            ttl,
+            max_per_node: max_per_node.max(1),
+            queues: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Queue a hint for `target`.
+    ///
+    /// Returns the number of older hints dropped to stay within the per-node limit.
+    pub fn add(&self, target: &str, event: &ReplicationEvent) -> ClusterResult<u64> {
+        let hint = Hint {
+            id: Uuid::new_v4().to_string(),
+            target_node: target.to_string(),
+            event: event.clone(),
+            created_at: Utc::now(),
+            attempts: 0,
+        };
+        self.persist(&hint)?;
+
+        let dropped: Vec<Hint> = {
+            let mut queues = self.queues.write();
+            let queue = queues.entry(target.to_string()).or_default();
+            queue.push_back(hint);
+            let excess = queue.len().saturating_sub(self.max_per_node);
+            queue.drain(..excess).collect()
+        };
+
+        for hint in &dropped {
+            self.unlink(hint);
+        }
+        if !dropped.is_empty() {
+            warn!(
+                "Hint queue for {} is full; dropped {} oldest hints",
+                target,
+                dropped.len()
+            );
+        }
+
+        Ok(dropped.len() as u64)
+    }
+
+    /// Nodes that have queued hints
+    pub fn nodes(&self) -> Vec<NodeId> {
+        self.queues
+            .read()
+            .iter()
+            .filter(|(_, q)| !q.is_empty())
+            .map(|(node, _)| node.clone())
+            .collect()
+    }
+
+    /// Oldest hint for a node
+    pub fn peek(&self, node: &str) -> Option<Hint> {
+        self.queues.read().get(node).and_then(|q| q.front().cloned())
+    }
+
+    /// Remove a hint after it was delivered
+    pub fn remove(&self, hint: &Hint) {
+        let removed = {
+            let mut queues = self.queues.write();
+            match queues.get_mut(&hint.target_node) {
+                Some(queue) => {
+                    let before = queue.len();
+                    queue.retain(|h| h.id != hint.id);
+                    queue.len() < before
+                }
+                None => false,
+            }
+        };
+        if removed {
+            self.unlink(hint);
+        }
+    }
+
+    /// Record a failed replay of a hint
+    pub fn record_attempt(&self, hint: &Hint) {
+        let updated = {
+            let mut queues = self.queues.write();
+            queues
+                .get_mut(&hint.target_node)
+                .and_then(|q| q.iter_mut().find(|h| h.id == hint.id))
+                .map(|h| {
+                    h.attempts += 1;
+                    h.clone()
+                })
+        };
+        if let Some(hint) = updated {
+            if let Err(e) = self.persist(&hint) {
+                warn!("Failed to update hint {}: {}", hint.id, e);
+            }
+        }
+    }
+
+    /// Drop hints older than the TTL, returning how many were dropped
+    pub fn expire(&self) -> u64 {
+        let expired: Vec<Hint> = {
+            let mut queues = self.queues.write();
+            let mut expired = Vec::new();
+            for queue in queues.values_mut() {
+                while queue.front().map_or(false, |h| h.is_expired(self.ttl)) {
+                    expired.extend(queue.pop_front());
+                }
+            }
+            queues.retain(|_, q| !q.is_empty());
+            expired
+        };
+
+        for hint in &expired {
+            self.unlink(hint);
+        }
+        expired.len() as u64
+    }
+
+    /// Total number of queued hints
+    pub fn len(&self) -> usize {
+        self.queues.read().values().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of hints queued per node
+    pub fn depths(&self) -> HashMap<NodeId, usize> {
+        self.queues
+            .read()
+            .iter()
+            .map(|(node, q)| (node.clone(), q.len()))
+            .collect()
+    }
+
+    fn persist(&self, hint: &Hint) -> ClusterResult<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        let path = dir.join(format!("{}.json", hint.id));
+        let tmp = dir.join(format!("{}.json.tmp", hint.id));
+        std::fs::write(&tmp, serde_json::to_vec(hint)?)?;
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn unlink(&self, hint: &Hint) {
+        if let Some(dir) = &self.dir {
+            let path = dir.join(format!("{}.json", hint.id));
+            if let Err(e) = std::fs::remove_file(&path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Failed to remove hint {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(key: &str) -> ReplicationEvent {
+        ReplicationEvent::object_created(
+            "node-a".to_string(),
+            "bucket".to_string(),
+            key.to_string(),
+            None,
+            None,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_queue_order_and_limit() {
+        let store = HintStore::in_memory(Duration::from_secs(60), 2);
+        store.add("node-b", &event("one")).unwrap();
+        store.add("node-b", &event("two")).unwrap();
+        assert_eq!(store.add("node-b", &event("three")).unwrap(), 1);
+
+        assert_eq!(store.len(), 2);
+        let oldest = store.peek("node-b").unwrap();
+        assert_eq!(oldest.event.key.as_deref(), Some("two"));
+
+        store.remove(&oldest);
+        assert_eq!(store.peek("node-b").unwrap().event.key.as_deref(), Some("three"));
+    }
+
+    #[test]
+    fn test_expire() {
+        let store = HintStore::in_memory(Duration::ZERO, 10);
+        store.add("node-b", &event("one")).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(store.expire(), 1);
+        assert!(store.is_empty());
+        assert!(store.nodes().is_empty());
+    }
+
+    #[test]
+    fn test_hints_survive_reopen() {
+        let dir = std::env::temp_dir().join(format!("hafiz-hints-{}", Uuid::new_v4()));
+        {
+            let store = HintStore::open(Some(dir.clone()), Duration::from_secs(60), 10).unwrap();
+            store.add("node-b", &event("one")).unwrap();
+            store.add("node-c", &event("two")).unwrap();
+            let hint = store.peek("node-c").unwrap();
+            store.remove(&hint);
+        }
+
+        let store = HintStore::open(Some(dir.clone()), Duration::from_secs(60), 10).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.peek("node-b").unwrap().event.key.as_deref(), Some("one"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
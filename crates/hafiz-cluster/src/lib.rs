@@ -29,27 +29,43 @@
 //!
 //! - **Automatic Discovery**: Nodes find each other via seed nodes
 //! - **Async Replication**: Non-blocking object replication
+//! - **Data Placement**: Consistent hash ring with background rebalancing
+//!   and zone/rack-aware replica spreading
+//! - **Anti-Entropy**: Merkle tree comparison and repair between replicas
+//! - **Hinted Handoff**: Writes for unreachable replicas are queued and replayed
 //! - **Consistency Levels**: One, Quorum, or All
 //! - **Conflict Resolution**: Last-write-wins, first-write-wins, etc.
 //! - **Health Monitoring**: Automatic failure detection
-//! - **TLS Support**: Encrypted cluster communication
+//! - **TLS Support**: Encrypted cluster communication, mutual TLS over gRPC
+//! - **gRPC Transport**: Streaming object transfer, negotiated per peer with
+//!   HTTP fallback (`grpc` feature)
+//! - **Metadata Consensus**: Optional Raft log for linearizable metadata writes
 
+pub mod anti_entropy;
 mod cluster;
 mod discovery;
 mod error;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod hints;
+pub mod placement;
+pub mod raft;
 mod replicator;
 mod transport;
 
+pub use anti_entropy::{AntiEntropyService, MerkleTree, RepairReport};
 pub use cluster::ClusterManager;
 pub use discovery::DiscoveryService;
 pub use error::{ClusterError, ClusterResult};
+pub use placement::{ClusterTopology, FailureDomain, HashRing, PlacementService};
+pub use raft::{MetadataCommand, MetadataStateMachine, RaftNode, RaftStatus};
 pub use replicator::Replicator;
-pub use transport::ClusterTransport;
+pub use transport::{verify_peer_secret, ClusterTransport, TransportConfig, PEER_SECRET_HEADER};
 
 // Re-export types from core
 pub use hafiz_core::types::{
-    ClusterConfig, ClusterMessage, ClusterNode, ClusterNodeStatus, ClusterStats,
-    ConflictResolution, ConsistencyLevel, NodeId, NodeRole, NodeStats,
-    ReplicationEvent, ReplicationEventType, ReplicationMode, ReplicationProgress,
+    AntiEntropyStats, ClusterConfig, ClusterMessage, ClusterNode, ClusterNodeStatus, ClusterStats,
+    ClusterTransportProtocol, ConflictResolution, ConsistencyLevel, ConsistencyOutcome, MetadataConsensus, NodeId,
+    NodeRole, NodeStats, RebalanceProgress, ReplicationEvent, ReplicationEventType, ReplicationMode, ReplicationProgress,
     ReplicationRule, ReplicationStatus,
 };
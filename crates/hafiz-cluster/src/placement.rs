@@ -0,0 +1,926 @@
+//! Consistent hash data placement
+//!
+//! Objects are assigned to nodes by hashing `bucket/key` onto a ring of
+//! virtual nodes; the first `replication_factor` distinct nodes clockwise
+//! from that point own the object. When membership changes only objects
+//! whose owner set changed have to move, and the rebalancer copies them to
+//! their new owners in the background.
+//!
+//! Nodes may be labelled with a zone and rack. Owners are then picked so
+//! replicas land in distinct zones first and distinct racks second, falling
+//! back to ring order when there are fewer domains than replicas.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use bytes::Bytes;
+use chrono::Utc;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use hafiz_core::types::{
+    ClusterConfig, ClusterNode, ClusterNodeStatus, ConsistencyLevel, ConsistencyOutcome, NodeId,
+    RebalanceProgress,
+};
+use hafiz_metadata::MetadataStore;
+use hafiz_storage::StorageEngine;
+
+use crate::discovery::DiscoveryService;
+use crate::error::{ClusterError, ClusterResult};
+use crate::transport::ClusterTransport;
+
+/// Objects listed per page while scanning during a rebalance
+const REBALANCE_PAGE_SIZE: i32 = 1000;
+
+/// Metadata key used to carry the content type to other nodes
+pub const CONTENT_TYPE_META_KEY: &str = "content-type";
+
+/// Zone and rack a node runs in
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FailureDomain {
+    pub zone: Option<String>,
+    pub rack: Option<String>,
+}
+
+impl FailureDomain {
+    pub fn of(node: &ClusterNode) -> Self {
+        Self {
+            zone: node.zone.clone(),
+            rack: node.rack.clone(),
+        }
+    }
+
+    fn is_labelled(&self) -> bool {
+        self.zone.is_some() || self.rack.is_some()
+    }
+
+    /// Whether no node in `others` shares this zone (unlabelled never conflicts)
+    fn new_zone<'a>(&self, mut others: impl Iterator<Item = &'a FailureDomain>) -> bool {
+        self.zone.is_none() || !others.any(|o| o.zone == self.zone)
+    }
+
+    /// Whether no node in `others` shares this zone and rack
+    fn new_rack<'a>(&self, mut others: impl Iterator<Item = &'a FailureDomain>) -> bool {
+        self.rack.is_none() || !others.any(|o| o.zone == self.zone && o.rack == self.rack)
+    }
+}
+
+/// Consistent hash ring of node IDs
+#[derive(Debug, Clone, Default)]
+pub struct HashRing {
+    virtual_nodes: u32,
+    ring: BTreeMap<u64, NodeId>,
+    nodes: BTreeSet<NodeId>,
+    domains: BTreeMap<NodeId, FailureDomain>,
+}
+
+impl HashRing {
+    pub fn new(virtual_nodes: u32) -> Self {
+        Self {
+            virtual_nodes: virtual_nodes.max(1),
+            ..Default::default()
+        }
+    }
+
+    /// Build a ring containing the given nodes
+    pub fn with_nodes(virtual_nodes: u32, nodes: impl IntoIterator<Item = NodeId>) -> Self {
+        let mut ring = Self::new(virtual_nodes);
+        for node in nodes {
+            ring.add_node(&node);
+        }
+        ring
+    }
+
+    /// Build a ring of nodes with their failure domains
+    pub fn with_members(
+        virtual_nodes: u32,
+        members: impl IntoIterator<Item = (NodeId, FailureDomain)>,
+    ) -> Self {
+        let mut ring = Self::new(virtual_nodes);
+        for (node, domain) in members {
+            ring.add_node(&node);
+            ring.set_domain(&node, domain);
+        }
+        ring
+    }
+
+    /// Label a node with its failure domain
+    pub fn set_domain(&mut self, node_id: &str, domain: FailureDomain) {
+        if self.nodes.contains(node_id) {
+            self.domains.insert(node_id.to_string(), domain);
+        }
+    }
+
+    pub fn domain(&self, node_id: &str) -> Option<&FailureDomain> {
+        self.domains.get(node_id)
+    }
+
+    /// Whether both rings place every object on the same nodes
+    pub fn same_layout(&self, other: &HashRing) -> bool {
+        self.nodes == other.nodes
+            && self.virtual_nodes == other.virtual_nodes
+            && self.labelled().eq(other.labelled())
+    }
+
+    fn labelled(&self) -> impl Iterator<Item = (&NodeId, &FailureDomain)> {
+        self.domains.iter().filter(|(_, d)| d.is_labelled())
+    }
+
+    pub fn add_node(&mut self, node_id: &str) {
+        if !self.nodes.insert(node_id.to_string()) {
+            return;
+        }
+        for i in 0..self.virtual_nodes {
+            self.ring
+                .insert(hash64(format!("{}#{}", node_id, i).as_bytes()), node_id.to_string());
+        }
+    }
+
+    pub fn remove_node(&mut self, node_id: &str) {
+        if self.nodes.remove(node_id) {
+            self.ring.retain(|_, n| n != node_id);
+            self.domains.remove(node_id);
+        }
+    }
+
+    pub fn contains(&self, node_id: &str) -> bool {
+        self.nodes.contains(node_id)
+    }
+
+    pub fn nodes(&self) -> &BTreeSet<NodeId> {
+        &self.nodes
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Up to `count` distinct nodes owning `bucket/key`, primary first
+    pub fn owners(&self, bucket: &str, key: &str, count: usize) -> Vec<NodeId> {
+        self.owners_at(hash64(format!("{}/{}", bucket, key).as_bytes()), count)
+    }
+
+    /// Share of the key space each node holds a replica of, with `count`
+    /// replicas per key
+    pub fn ownership(&self, count: usize) -> BTreeMap<NodeId, f64> {
+        let mut shares: BTreeMap<NodeId, f64> = BTreeMap::new();
+        let Some(mut prev) = self.ring.keys().next_back().copied() else {
+            return shares;
+        };
+
+        for &point in self.ring.keys() {
+            // Keys hashing into (prev, point] start their walk at `point`
+            let arc = if self.ring.len() == 1 {
+                1.0
+            } else {
+                point.wrapping_sub(prev) as f64 / u64::MAX as f64
+            };
+            for owner in self.owners_at(point, count) {
+                *shares.entry(owner).or_default() += arc;
+            }
+            prev = point;
+        }
+
+        shares
+    }
+
+    fn owners_at(&self, point: u64, count: usize) -> Vec<NodeId> {
+        let wanted = count.min(self.nodes.len());
+        let default_domain = FailureDomain::default();
+        let domain = |node: &NodeId| self.domains.get(node).unwrap_or(&default_domain);
+
+        // Without labels the first distinct nodes clockwise are the owners;
+        // with labels every node is a candidate, in ring order
+        let spread = self.domains.values().any(FailureDomain::is_labelled);
+        let limit = if spread { self.nodes.len() } else { wanted };
+        let mut candidates: Vec<&NodeId> = Vec::with_capacity(limit);
+        for node in self.ring.range(point..).chain(self.ring.range(..point)).map(|(_, n)| n) {
+            if candidates.len() == limit {
+                break;
+            }
+            if !candidates.contains(&node) {
+                candidates.push(node);
+            }
+        }
+
+        // Prefer a new zone, then a new rack, then any node
+        let mut owners: Vec<NodeId> = Vec::with_capacity(wanted);
+        for pass in 0..3 {
+            for node in &candidates {
+                if owners.len() == wanted {
+                    return owners;
+                }
+                if owners.contains(node) {
+                    continue;
+                }
+                let d = domain(node);
+                let fits = match pass {
+                    0 => {
+                        d.new_zone(owners.iter().map(domain))
+                            && d.new_rack(owners.iter().map(domain))
+                    }
+                    1 => d.new_rack(owners.iter().map(domain)),
+                    _ => true,
+                };
+                if fits {
+                    owners.push((*node).clone());
+                }
+            }
+        }
+
+        owners
+    }
+}
+
+/// Placement of replicas across failure domains
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterTopology {
+    pub placement_enabled: bool,
+    pub replication_factor: usize,
+    pub zones: Vec<ZoneTopology>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ZoneTopology {
+    pub zone: Option<String>,
+    /// Fraction of all object replicas placed in this zone
+    pub share: f64,
+    pub racks: Vec<RackTopology>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RackTopology {
+    pub rack: Option<String>,
+    /// Fraction of all object replicas placed in this rack
+    pub share: f64,
+    pub nodes: Vec<TopologyNode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopologyNode {
+    pub id: NodeId,
+    pub name: String,
+    pub status: ClusterNodeStatus,
+    /// Fraction of all object replicas placed on this node
+    pub share: f64,
+}
+
+/// Position on the ring; must be identical on every node
+fn hash64(data: &[u8]) -> u64 {
+    let digest = Sha256::digest(data);
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_be_bytes(bytes)
+}
+
+/// Local data used by the rebalancer and anti-entropy repair
+#[derive(Clone)]
+pub(crate) struct LocalStore {
+    pub(crate) metadata: Arc<MetadataStore>,
+    pub(crate) storage: Arc<dyn StorageEngine>,
+}
+
+/// Assigns objects to nodes and moves data when membership changes
+pub struct PlacementService {
+    enabled: bool,
+    local_node_id: NodeId,
+    replication_factor: usize,
+    virtual_nodes: u32,
+    discovery: Arc<DiscoveryService>,
+    transport: Arc<ClusterTransport>,
+    ring: RwLock<HashRing>,
+    store: RwLock<Option<LocalStore>>,
+    /// Ring before the first membership change that has not been rebalanced yet
+    pending: RwLock<Option<HashRing>>,
+    rebalancing: AtomicBool,
+    progress: RwLock<RebalanceProgress>,
+}
+
+impl PlacementService {
+    pub fn new(
+        config: &ClusterConfig,
+        discovery: Arc<DiscoveryService>,
+        transport: Arc<ClusterTransport>,
+    ) -> Self {
+        let local = FailureDomain {
+            zone: config.zone.clone(),
+            rack: config.rack.clone(),
+        };
+        let ring = HashRing::with_members(
+            config.placement_virtual_nodes,
+            [(config.node_id.clone(), local)],
+        );
+
+        Self {
+            enabled: config.placement_enabled,
+            local_node_id: config.node_id.clone(),
+            replication_factor: config.default_replication_factor.max(1) as usize,
+            virtual_nodes: config.placement_virtual_nodes,
+            discovery,
+            transport,
+            ring: RwLock::new(ring),
+            store: RwLock::new(None),
+            pending: RwLock::new(None),
+            rebalancing: AtomicBool::new(false),
+            progress: RwLock::new(RebalanceProgress::default()),
+        }
+    }
+
+    /// Whether objects are placed with the hash ring
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Give the rebalancer access to local objects
+    pub fn attach_store(&self, metadata: Arc<MetadataStore>, storage: Arc<dyn StorageEngine>) {
+        *self.store.write() = Some(LocalStore { metadata, storage });
+    }
+
+    /// Current ring
+    pub fn ring(&self) -> HashRing {
+        self.ring.read().clone()
+    }
+
+    /// Progress of the current or last rebalance
+    pub fn progress(&self) -> RebalanceProgress {
+        self.progress.read().clone()
+    }
+
+    /// IDs of the nodes owning `bucket/key`, primary first
+    pub fn owners(&self, bucket: &str, key: &str) -> Vec<NodeId> {
+        self.ring
+            .read()
+            .owners(bucket, key, self.replication_factor)
+    }
+
+    /// Nodes holding a replica of `bucket/key`: its owners with placement
+    /// enabled, otherwise every member
+    pub fn replicas(&self, bucket: &str, key: &str) -> Vec<NodeId> {
+        if self.enabled {
+            return self.owners(bucket, key);
+        }
+
+        // Members in other zones first, so the first acknowledgements come
+        // from other failure domains; draining members take no new copies
+        let mut members = self.members();
+        let local = FailureDomain::of(&members[0]);
+        members[1..].sort_by_key(|n| local.zone.is_some() && n.zone == local.zone);
+        members
+            .into_iter()
+            .enumerate()
+            .filter(|(i, n)| *i == 0 || n.status != ClusterNodeStatus::Draining)
+            .map(|(_, n)| n.id)
+            .collect()
+    }
+
+    /// Replica distribution across zones, racks and nodes
+    pub fn topology(&self) -> ClusterTopology {
+        let members = self.members();
+        let ring = Self::ring_of(self.virtual_nodes, &members);
+        let count = if self.enabled {
+            self.replication_factor
+        } else {
+            members.len()
+        };
+        let ownership = ring.ownership(count);
+        let total: f64 = ownership.values().sum();
+
+        let mut zones: BTreeMap<Option<String>, BTreeMap<Option<String>, Vec<TopologyNode>>> =
+            BTreeMap::new();
+        for node in members {
+            let share = match ownership.get(&node.id) {
+                Some(owned) if total > 0.0 => owned / total,
+                _ => 0.0,
+            };
+            zones
+                .entry(node.zone.clone())
+                .or_default()
+                .entry(node.rack.clone())
+                .or_default()
+                .push(TopologyNode {
+                    id: node.id,
+                    name: node.name,
+                    status: node.status,
+                    share,
+                });
+        }
+
+        let zones = zones
+            .into_iter()
+            .map(|(zone, racks)| {
+                let racks: Vec<RackTopology> = racks
+                    .into_iter()
+                    .map(|(rack, nodes)| RackTopology {
+                        rack,
+                        share: nodes.iter().map(|n| n.share).sum(),
+                        nodes,
+                    })
+                    .collect();
+                ZoneTopology {
+                    zone,
+                    share: racks.iter().map(|r| r.share).sum(),
+                    racks,
+                }
+            })
+            .collect();
+
+        ClusterTopology {
+            placement_enabled: self.enabled,
+            replication_factor: count,
+            zones,
+        }
+    }
+
+    /// Local node first, then every other member that has not left
+    fn members(&self) -> Vec<ClusterNode> {
+        std::iter::once(self.discovery.local_node())
+            .chain(
+                self.discovery
+                    .nodes()
+                    .into_iter()
+                    .filter(|n| n.status != ClusterNodeStatus::Left && n.id != self.local_node_id),
+            )
+            .collect()
+    }
+
+    /// Ring of the members that take data; draining nodes own nothing
+    fn ring_of(virtual_nodes: u32, members: &[ClusterNode]) -> HashRing {
+        HashRing::with_members(
+            virtual_nodes,
+            members
+                .iter()
+                .filter(|n| n.status != ClusterNodeStatus::Draining)
+                .map(|n| (n.id.clone(), FailureDomain::of(n))),
+        )
+    }
+
+    /// Whether the local node is one of the owners of `bucket/key`
+    pub fn is_local_owner(&self, bucket: &str, key: &str) -> bool {
+        !self.enabled || self.owners(bucket, key).contains(&self.local_node_id)
+    }
+
+    /// Rebuild the ring from discovery.
+    ///
+    /// Returns the previous ring if the membership changed.
+    pub fn refresh(&self) -> Option<HashRing> {
+        let ring = Self::ring_of(self.virtual_nodes, &self.members());
+
+        let mut current = self.ring.write();
+        if current.same_layout(&ring) {
+            return None;
+        }
+
+        info!(
+            "Placement ring changed: {} -> {} nodes",
+            current.len(),
+            ring.len()
+        );
+        Some(std::mem::replace(&mut *current, ring))
+    }
+
+    /// Refresh the ring and start a rebalance if the owners of any object may have changed
+    pub fn on_membership_change(self: &Arc<Self>) {
+        if !self.enabled {
+            return;
+        }
+        let Some(previous) = self.refresh() else {
+            return;
+        };
+
+        {
+            // Keep the oldest unbalanced ring so a burst of changes is handled in one pass
+            let mut pending = self.pending.write();
+            if pending.is_none() {
+                *pending = Some(previous);
+            }
+        }
+
+        self.spawn_rebalance();
+    }
+
+    fn spawn_rebalance(self: &Arc<Self>) {
+        if self.store.read().is_none() {
+            debug!("Placement ring changed but no local store is attached; skipping rebalance");
+            return;
+        }
+        if self.rebalancing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let service = Arc::clone(self);
+        tokio::spawn(async move {
+            loop {
+                let previous = service.pending.write().take();
+                match previous {
+                    Some(previous) => {
+                        if let Err(e) = service.rebalance(&previous).await {
+                            warn!("Rebalance failed: {}", e);
+                        }
+                    }
+                    None => {
+                        service.rebalancing.store(false, Ordering::SeqCst);
+                        // A change may have arrived after the take() above
+                        if service.pending.read().is_none()
+                            || service.rebalancing.swap(true, Ordering::SeqCst)
+                        {
+                            break;
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    /// Copy local objects whose owner set changed between `previous` and the current ring
+    async fn rebalance(&self, previous: &HashRing) -> ClusterResult<()> {
+        let store = self
+            .store
+            .read()
+            .clone()
+            .ok_or_else(|| ClusterError::Internal("No local store attached".to_string()))?;
+        let current = self.ring();
+
+        *self.progress.write() = RebalanceProgress {
+            in_progress: true,
+            started_at: Some(Utc::now()),
+            ..Default::default()
+        };
+        info!("Starting rebalance across {} nodes", current.len());
+
+        let result = self.rebalance_buckets(&store, previous, &current).await;
+
+        let mut progress = self.progress.write();
+        progress.in_progress = false;
+        progress.finished_at = Some(Utc::now());
+        info!(
+            "Rebalance finished: {} scanned, {} moved, {} failed",
+            progress.objects_scanned, progress.objects_moved, progress.objects_failed
+        );
+
+        result
+    }
+
+    async fn rebalance_buckets(
+        &self,
+        store: &LocalStore,
+        previous: &HashRing,
+        current: &HashRing,
+    ) -> ClusterResult<()> {
+        for bucket in store.metadata.list_bucket_names().await? {
+            let mut token: Option<String> = None;
+
+            loop {
+                let (objects, _, truncated, next) = store
+                    .metadata
+                    .list_objects(&bucket, None, None, REBALANCE_PAGE_SIZE, token.as_deref())
+                    .await?;
+
+                for object in objects {
+                    self.progress.write().objects_scanned += 1;
+                    self.rebalance_object(store, previous, current, &bucket, &object.key)
+                        .await;
+                }
+
+                if !truncated || next.is_none() {
+                    break;
+                }
+                token = next;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn rebalance_object(
+        &self,
+        store: &LocalStore,
+        previous: &HashRing,
+        current: &HashRing,
+        bucket: &str,
+        key: &str,
+    ) {
+        let old_owners = previous.owners(bucket, key, self.replication_factor);
+        let new_owners = current.owners(bucket, key, self.replication_factor);
+
+        // Exactly one surviving previous owner pushes each object; if none
+        // survives (the owners are draining) the first previous owner does
+        let pusher = old_owners
+            .iter()
+            .find(|n| current.contains(n))
+            .or_else(|| old_owners.first());
+        if pusher != Some(&self.local_node_id) {
+            return;
+        }
+
+        let targets: Vec<&NodeId> = new_owners
+            .iter()
+            .filter(|n| !old_owners.contains(n) && **n != self.local_node_id)
+            .collect();
+        if targets.is_empty() {
+            return;
+        }
+
+        match self.copy_object(store, bucket, key, &targets).await {
+            Ok(bytes) => {
+                {
+                    let mut progress = self.progress.write();
+                    progress.objects_moved += 1;
+                    progress.bytes_moved += bytes;
+                }
+
+                if !new_owners.contains(&self.local_node_id) {
+                    // Metadata stays so reads on this node can be proxied
+                    if let Err(e) = store.storage.delete(bucket, key).await {
+                        warn!("Failed to drop moved object {}/{}: {}", bucket, key, e);
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Failed to move {}/{}: {}", bucket, key, e);
+                self.progress.write().objects_failed += 1;
+            }
+        }
+    }
+
+    /// Copy one local object to `targets`, returning the bytes sent per target
+    async fn copy_object(
+        &self,
+        store: &LocalStore,
+        bucket: &str,
+        key: &str,
+        targets: &[&NodeId],
+    ) -> ClusterResult<u64> {
+        let data = store.storage.get(bucket, key).await?;
+        let mut metadata = HashMap::new();
+        if let Some(object) = store.metadata.get_object(bucket, key).await? {
+            metadata = object.metadata;
+            metadata.insert(CONTENT_TYPE_META_KEY.to_string(), object.content_type);
+        }
+
+        for target in targets {
+            let node = self
+                .resolve(target)
+                .ok_or_else(|| ClusterError::NodeNotFound(target.to_string()))?;
+            self.transport
+                .upload_object_data(
+                    &node,
+                    bucket,
+                    key,
+                    data.clone(),
+                    Some(&checksum(&data)),
+                    &metadata,
+                )
+                .await?;
+        }
+
+        Ok(data.len() as u64)
+    }
+
+    /// Write object data that is already stored locally to the other replicas.
+    ///
+    /// With placement enabled every remote owner is written; otherwise only
+    /// as many members as `level` needs, and the replicator copies the rest.
+    /// The local copy counts as an acknowledgement when this node is a
+    /// replica. Fails with `QuorumNotReached` if `level` was not met.
+    pub async fn write(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Bytes,
+        metadata: &HashMap<String, String>,
+        level: ConsistencyLevel,
+    ) -> ClusterResult<ConsistencyOutcome> {
+        let replicas = self.replicas(bucket, key);
+        let required = level.required_acks(replicas.len());
+        let mut acks = usize::from(replicas.contains(&self.local_node_id));
+        let sum = checksum(&data);
+
+        for replica in replicas.iter().filter(|r| **r != self.local_node_id) {
+            if !self.enabled && acks >= required {
+                break;
+            }
+            let Some(node) = self.resolve(replica) else {
+                warn!("Replica {} of {}/{} is unknown", replica, bucket, key);
+                continue;
+            };
+
+            match self
+                .transport
+                .upload_object_data(&node, bucket, key, data.clone(), Some(&sum), metadata)
+                .await
+            {
+                Ok(()) => acks += 1,
+                Err(e) => warn!("Failed to store {}/{} on {}: {}", bucket, key, replica, e),
+            }
+        }
+
+        let outcome = ConsistencyOutcome::new(level, acks, replicas.len());
+        if !outcome.is_satisfied() {
+            return Err(ClusterError::QuorumNotReached {
+                needed: required as u32,
+                got: acks as u32,
+            });
+        }
+        Ok(outcome)
+    }
+
+    /// Read object data from enough replicas to meet `level`.
+    ///
+    /// `local` is this node's copy, if it has one. Copies are compared by
+    /// checksum and the one held by the most replicas is returned.
+    pub async fn read(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+        local: Option<Bytes>,
+        level: ConsistencyLevel,
+    ) -> ClusterResult<(Bytes, ConsistencyOutcome)> {
+        let replicas = self.replicas(bucket, key);
+        let required = level.required_acks(replicas.len());
+
+        // (checksum, data, replicas holding it), in the order first seen
+        let mut copies: Vec<(String, Bytes, usize)> = Vec::new();
+        let add = |copies: &mut Vec<(String, Bytes, usize)>, data: Bytes| {
+            let sum = checksum(&data);
+            match copies.iter_mut().find(|c| c.0 == sum) {
+                Some(copy) => copy.2 += 1,
+                None => copies.push((sum, data, 1)),
+            }
+        };
+
+        if let Some(data) = local {
+            add(&mut copies, data);
+        }
+
+        for replica in replicas.iter().filter(|r| **r != self.local_node_id) {
+            if copies.iter().any(|c| c.2 >= required) {
+                break;
+            }
+            let Some(node) = self.resolve(replica) else {
+                continue;
+            };
+
+            match self
+                .transport
+                .fetch_object_data(&node, bucket, key, version_id)
+                .await
+            {
+                Ok((data, Some(expected))) if checksum(&data) != expected => {
+                    debug!("Discarding corrupt copy of {}/{} from {}", bucket, key, replica);
+                }
+                Ok((data, _)) => add(&mut copies, data),
+                Err(e) => debug!("Read of {}/{} from {} failed: {}", bucket, key, replica, e),
+            }
+        }
+
+        let mut best: Option<(Bytes, usize)> = None;
+        for (_, data, count) in copies {
+            if best.as_ref().map_or(true, |(_, c)| count > *c) {
+                best = Some((data, count));
+            }
+        }
+        let Some((data, acks)) = best else {
+            return Err(ClusterError::NoHealthyNodes);
+        };
+
+        let outcome = ConsistencyOutcome::new(level, acks, replicas.len());
+        if !outcome.is_satisfied() {
+            return Err(ClusterError::QuorumNotReached {
+                needed: required as u32,
+                got: acks as u32,
+            });
+        }
+        Ok((data, outcome))
+    }
+
+    fn resolve(&self, node_id: &str) -> Option<ClusterNode> {
+        if node_id == self.local_node_id {
+            Some(self.discovery.local_node())
+        } else {
+            self.discovery.get_node(node_id)
+        }
+    }
+}
+
+/// SHA-256 checksum in the format used by the replication transport
+pub fn checksum(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+impl std::fmt::Debug for PlacementService {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PlacementService")
+            .field("enabled", &self.enabled)
+            .field("replication_factor", &self.replication_factor)
+            .field("nodes", &self.ring.read().len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ring(nodes: &[&str]) -> HashRing {
+        HashRing::with_nodes(64, nodes.iter().map(|n| n.to_string()))
+    }
+
+    #[test]
+    fn test_owners_are_distinct_and_stable() {
+        let ring = ring(&["a", "b", "c"]);
+        let owners = ring.owners("bucket", "key", 2);
+        assert_eq!(owners.len(), 2);
+        assert_ne!(owners[0], owners[1]);
+        assert_eq!(owners, ring.owners("bucket", "key", 2));
+
+        // Asking for more owners than nodes returns every node
+        assert_eq!(ring.owners("bucket", "key", 5).len(), 3);
+        assert!(HashRing::new(64).owners("bucket", "key", 2).is_empty());
+    }
+
+    #[test]
+    fn test_adding_node_moves_few_keys() {
+        let before = ring(&["a", "b", "c"]);
+        let mut after = before.clone();
+        after.add_node("d");
+
+        let keys: Vec<String> = (0..2000).map(|i| format!("key-{}", i)).collect();
+        let moved = keys
+            .iter()
+            .filter(|k| before.owners("b", k, 1) != after.owners("b", k, 1))
+            .count();
+
+        // Roughly a quarter of the keys should move to the new node
+        assert!(moved > 200 && moved < 900, "moved {}", moved);
+        for key in &keys {
+            let owner = &after.owners("b", key, 1)[0];
+            if *owner != before.owners("b", key, 1)[0] {
+                assert_eq!(owner, "d");
+            }
+        }
+    }
+
+    #[test]
+    fn test_owners_spread_across_zones() {
+        let domain = |zone: &str, rack: &str| FailureDomain {
+            zone: Some(zone.to_string()),
+            rack: Some(rack.to_string()),
+        };
+        let ring = HashRing::with_members(
+            64,
+            [
+                ("a1".to_string(), domain("a", "r1")),
+                ("a2".to_string(), domain("a", "r1")),
+                ("a3".to_string(), domain("a", "r2")),
+                ("b1".to_string(), domain("b", "r1")),
+            ],
+        );
+
+        for i in 0..200 {
+            let key = format!("key-{}", i);
+
+            // Two replicas always land in different zones
+            let owners = ring.owners("bucket", &key, 2);
+            let zones: BTreeSet<_> = owners.iter().map(|o| &ring.domain(o).unwrap().zone).collect();
+            assert_eq!(zones.len(), 2, "{:?}", owners);
+
+            // A third replica in zone a goes to the other rack
+            let owners = ring.owners("bucket", &key, 3);
+            let domains: BTreeSet<_> = owners
+                .iter()
+                .map(|o| {
+                    let d = ring.domain(o).unwrap();
+                    (d.zone.clone(), d.rack.clone())
+                })
+                .collect();
+            assert_eq!(domains.len(), 3, "{:?}", owners);
+        }
+    }
+
+    #[test]
+    fn test_ownership() {
+        let ring = ring(&["a", "b", "c", "d"]);
+        let shares = ring.ownership(2);
+        let total: f64 = shares.values().sum();
+        assert!((total - 2.0).abs() < 0.01, "total {}", total);
+        for share in shares.values() {
+            assert!(*share > 0.25 && *share < 0.75, "share {}", share);
+        }
+    }
+
+    #[test]
+    fn test_remove_node() {
+        let mut ring = ring(&["a", "b"]);
+        ring.remove_node("a");
+        assert!(!ring.contains("a"));
+        assert_eq!(ring.owners("bucket", "key", 3), vec!["b".to_string()]);
+    }
+}
@@ -0,0 +1,258 @@
+//! In-memory Raft log with snapshot-based compaction
+//!
+//! Entries before `snapshot_index` have been folded into a state machine
+//! snapshot and are no longer retained. Indexes are 1-based; index 0 is the
+//! empty log.
+
+use serde::{Deserialize, Serialize};
+
+use super::state_machine::MetadataCommand;
+use super::{LogIndex, Term};
+
+/// A single replicated log entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub index: LogIndex,
+    pub term: Term,
+    pub command: MetadataCommand,
+}
+
+/// The replicated log
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RaftLog {
+    /// Index of the last entry covered by the snapshot
+    snapshot_index: LogIndex,
+    /// Term of the last entry covered by the snapshot
+    snapshot_term: Term,
+    /// Entries after the snapshot, in index order
+    entries: Vec<LogEntry>,
+}
+
+impl RaftLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot_index(&self) -> LogIndex {
+        self.snapshot_index
+    }
+
+    pub fn snapshot_term(&self) -> Term {
+        self.snapshot_term
+    }
+
+    pub fn last_index(&self) -> LogIndex {
+        self.entries
+            .last()
+            .map(|e| e.index)
+            .unwrap_or(self.snapshot_index)
+    }
+
+    pub fn last_term(&self) -> Term {
+        self.entries
+            .last()
+            .map(|e| e.term)
+            .unwrap_or(self.snapshot_term)
+    }
+
+    /// Term of the entry at `index`, if it is known
+    pub fn term_at(&self, index: LogIndex) -> Option<Term> {
+        if index == self.snapshot_index {
+            return Some(self.snapshot_term);
+        }
+        self.get(index).map(|e| e.term)
+    }
+
+    pub fn get(&self, index: LogIndex) -> Option<&LogEntry> {
+        if index <= self.snapshot_index {
+            return None;
+        }
+        self.entries.get((index - self.snapshot_index - 1) as usize)
+    }
+
+    /// Entries starting at `from`, at most `max` of them
+    pub fn entries_from(&self, from: LogIndex, max: usize) -> Vec<LogEntry> {
+        if from <= self.snapshot_index {
+            return Vec::new();
+        }
+        let start = (from - self.snapshot_index - 1) as usize;
+        self.entries.iter().skip(start).take(max).cloned().collect()
+    }
+
+    /// Append a new entry at the end of the log
+    pub fn append(&mut self, term: Term, command: MetadataCommand) -> LogIndex {
+        let index = self.last_index() + 1;
+        self.entries.push(LogEntry {
+            index,
+            term,
+            command,
+        });
+        index
+    }
+
+    /// Append entries received from a leader, dropping any conflicting suffix.
+    ///
+    /// Returns the index of the first entry written, if the log was modified.
+    pub fn merge(&mut self, entries: Vec<LogEntry>) -> Option<LogIndex> {
+        let mut changed = None;
+
+        for entry in entries {
+            if entry.index <= self.snapshot_index {
+                continue;
+            }
+
+            let index = entry.index;
+            match self.term_at(index) {
+                Some(term) if term == entry.term => continue,
+                Some(_) => {
+                    self.truncate_from(index);
+                    self.entries.push(entry);
+                }
+                None => self.entries.push(entry),
+            }
+            changed = changed.or(Some(index));
+        }
+
+        changed
+    }
+
+    /// Re-add an entry read back from storage, replacing any entry at its
+    /// index and everything after it.
+    ///
+    /// Returns false if the entry would leave a gap.
+    pub fn replay(&mut self, entry: LogEntry) -> bool {
+        if entry.index <= self.snapshot_index {
+            return true;
+        }
+        if entry.index > self.last_index() + 1 {
+            return false;
+        }
+        self.truncate_from(entry.index);
+        self.entries.push(entry);
+        true
+    }
+
+    /// Remove the entry at `index` and everything after it
+    pub fn truncate_from(&mut self, index: LogIndex) {
+        if index <= self.snapshot_index {
+            self.entries.clear();
+            return;
+        }
+        self.entries
+            .truncate((index - self.snapshot_index - 1) as usize);
+    }
+
+    /// Drop entries up to and including `index`, which is now covered by a snapshot
+    pub fn compact(&mut self, index: LogIndex, term: Term) {
+        if index <= self.snapshot_index {
+            return;
+        }
+
+        if index >= self.last_index() {
+            self.entries.clear();
+        } else {
+            let drop = (index - self.snapshot_index) as usize;
+            self.entries.drain(..drop);
+        }
+
+        self.snapshot_index = index;
+        self.snapshot_term = term;
+    }
+
+    /// Replace the whole log with an installed snapshot
+    pub fn reset_to_snapshot(&mut self, index: LogIndex, term: Term) {
+        if self.term_at(index) == Some(term) {
+            // Keep any entries that follow the snapshot point
+            self.compact(index, term);
+        } else {
+            self.entries.clear();
+            self.snapshot_index = index;
+            self.snapshot_term = term;
+        }
+    }
+
+    /// Number of entries retained in memory
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noop_log(n: u64, term: Term) -> RaftLog {
+        let mut log = RaftLog::new();
+        for _ in 0..n {
+            log.append(term, MetadataCommand::Noop);
+        }
+        log
+    }
+
+    #[test]
+    fn test_append_and_lookup() {
+        let log = noop_log(3, 1);
+        assert_eq!(log.last_index(), 3);
+        assert_eq!(log.last_term(), 1);
+        assert_eq!(log.term_at(0), Some(0));
+        assert_eq!(log.term_at(2), Some(1));
+        assert_eq!(log.term_at(4), None);
+        assert_eq!(log.entries_from(2, 10).len(), 2);
+    }
+
+    #[test]
+    fn test_merge_truncates_conflicts() {
+        let mut log = noop_log(3, 1);
+        let incoming = vec![
+            LogEntry { index: 2, term: 1, command: MetadataCommand::Noop },
+            LogEntry { index: 3, term: 2, command: MetadataCommand::Noop },
+            LogEntry { index: 4, term: 2, command: MetadataCommand::Noop },
+        ];
+
+        assert_eq!(log.merge(incoming), Some(3));
+        assert_eq!(log.last_index(), 4);
+        assert_eq!(log.term_at(3), Some(2));
+
+        // Re-sending the same entries is a no-op
+        let again = log.entries_from(2, 10);
+        assert_eq!(log.merge(again), None);
+    }
+
+    #[test]
+    fn test_replay() {
+        let mut log = noop_log(3, 1);
+        assert!(log.replay(LogEntry { index: 2, term: 2, command: MetadataCommand::Noop }));
+        assert_eq!(log.last_index(), 2);
+        assert_eq!(log.term_at(2), Some(2));
+        assert!(!log.replay(LogEntry { index: 4, term: 2, command: MetadataCommand::Noop }));
+    }
+
+    #[test]
+    fn test_compact() {
+        let mut log = noop_log(5, 1);
+        log.compact(3, 1);
+        assert_eq!(log.snapshot_index(), 3);
+        assert_eq!(log.len(), 2);
+        assert_eq!(log.last_index(), 5);
+        assert!(log.get(3).is_none());
+        assert_eq!(log.get(4).map(|e| e.index), Some(4));
+        assert_eq!(log.term_at(3), Some(1));
+        assert!(log.entries_from(2, 10).is_empty());
+
+        log.append(2, MetadataCommand::Noop);
+        assert_eq!(log.last_index(), 6);
+    }
+
+    #[test]
+    fn test_reset_to_snapshot() {
+        let mut log = noop_log(3, 1);
+        log.reset_to_snapshot(10, 4);
+        assert_eq!(log.last_index(), 10);
+        assert_eq!(log.last_term(), 4);
+        assert!(log.is_empty());
+    }
+}
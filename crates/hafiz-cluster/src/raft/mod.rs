@@ -0,0 +1,84 @@
+//! Raft consensus for cluster metadata
+//!
+//! When `metadata_consensus = "raft"`, bucket and object metadata mutations
+//! are appended to a replicated log and only applied once a majority of
+//! nodes has stored them. Every node applies the same commands in the same
+//! order, so metadata stays consistent without relying on last-write-wins.
+//!
+//! The implementation follows the Raft paper: randomized election timeouts,
+//! leader-driven log replication, commit on majority for current-term
+//! entries, and snapshot-based log compaction. Followers forward proposals
+//! to the current leader. Votes and commits count against a configured set
+//! of voters rather than the nodes discovery happens to know about, so a
+//! node that has not found its peers yet cannot elect itself. Joint
+//! consensus is not implemented; the voter set only changes on restart.
+
+mod log;
+mod network;
+mod node;
+mod rpc;
+mod state_machine;
+mod storage;
+
+use std::time::Duration;
+
+use hafiz_core::types::{ClusterConfig, NodeId};
+
+pub use log::{LogEntry, RaftLog};
+pub use network::{HttpRaftNetwork, RaftNetwork};
+pub use node::{RaftNode, RaftRole, RaftStatus};
+pub use rpc::{
+    AppendEntriesRequest, AppendEntriesResponse, ForwardRequest, ForwardResponse,
+    InstallSnapshotRequest, InstallSnapshotResponse, VoteRequest, VoteResponse,
+};
+pub use state_machine::{MemoryStateMachine, MetadataCommand, MetadataStateMachine, StateMachine};
+pub use storage::{HardState, RaftStorage, Snapshot};
+
+/// Raft term number
+pub type Term = u64;
+
+/// Position in the Raft log (1-based)
+pub type LogIndex = u64;
+
+/// Raft timing and compaction settings
+#[derive(Debug, Clone)]
+pub struct RaftConfig {
+    /// Minimum election timeout; the actual timeout is randomized up to twice this
+    pub election_timeout: Duration,
+    /// Interval between leader heartbeats
+    pub heartbeat_interval: Duration,
+    /// Number of applied entries after which the log is compacted
+    pub snapshot_threshold: u64,
+    /// Maximum entries sent in a single AppendEntries request
+    pub max_entries_per_append: usize,
+    /// How long a proposal waits to be committed and applied
+    pub proposal_timeout: Duration,
+    /// Voting members, the local node included
+    pub voters: Vec<NodeId>,
+}
+
+impl Default for RaftConfig {
+    fn default() -> Self {
+        Self {
+            election_timeout: Duration::from_millis(1000),
+            heartbeat_interval: Duration::from_millis(200),
+            snapshot_threshold: 10_000,
+            max_entries_per_append: 256,
+            proposal_timeout: Duration::from_secs(10),
+            voters: Vec::new(),
+        }
+    }
+}
+
+impl RaftConfig {
+    /// Build Raft settings from the cluster configuration
+    pub fn from_cluster_config(config: &ClusterConfig) -> Self {
+        Self {
+            election_timeout: Duration::from_millis(config.raft_election_timeout_ms),
+            heartbeat_interval: Duration::from_millis(config.raft_heartbeat_interval_ms),
+            snapshot_threshold: config.raft_snapshot_threshold,
+            voters: config.raft_voters.clone(),
+            ..Default::default()
+        }
+    }
+}
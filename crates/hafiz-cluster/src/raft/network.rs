@@ -0,0 +1,113 @@
+//! Raft peer communication
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+
+use hafiz_core::types::{ClusterNodeStatus, NodeId};
+
+use super::rpc::{
+    AppendEntriesRequest, AppendEntriesResponse, ForwardRequest, ForwardResponse,
+    InstallSnapshotRequest, InstallSnapshotResponse, VoteRequest, VoteResponse,
+};
+use crate::discovery::DiscoveryService;
+use crate::error::{ClusterError, ClusterResult};
+use crate::transport::ClusterTransport;
+
+/// How a Raft node reaches its peers
+#[async_trait]
+pub trait RaftNetwork: Send + Sync + 'static {
+    /// Voting members other than the local node
+    fn peers(&self) -> Vec<NodeId>;
+
+    async fn request_vote(&self, target: &str, req: VoteRequest) -> ClusterResult<VoteResponse>;
+
+    async fn append_entries(
+        &self,
+        target: &str,
+        req: AppendEntriesRequest,
+    ) -> ClusterResult<AppendEntriesResponse>;
+
+    async fn install_snapshot(
+        &self,
+        target: &str,
+        req: InstallSnapshotRequest,
+    ) -> ClusterResult<InstallSnapshotResponse>;
+
+    async fn forward(&self, target: &str, req: ForwardRequest) -> ClusterResult<ForwardResponse>;
+}
+
+/// Raft network over the cluster HTTP transport
+///
+/// Peers are the nodes known to discovery that have not left the cluster.
+pub struct HttpRaftNetwork {
+    discovery: Arc<DiscoveryService>,
+    transport: Arc<ClusterTransport>,
+    local_node_id: NodeId,
+    timeout: Duration,
+}
+
+impl HttpRaftNetwork {
+    pub fn new(
+        discovery: Arc<DiscoveryService>,
+        transport: Arc<ClusterTransport>,
+        local_node_id: NodeId,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            discovery,
+            transport,
+            local_node_id,
+            timeout,
+        }
+    }
+
+    async fn call<Req, Resp>(&self, target: &str, path: &str, req: &Req) -> ClusterResult<Resp>
+    where
+        Req: serde::Serialize + Sync,
+        Resp: serde::de::DeserializeOwned,
+    {
+        let node = self
+            .discovery
+            .get_node(target)
+            .ok_or_else(|| ClusterError::NodeNotFound(target.to_string()))?;
+        self.transport.post_json(&node, path, req, self.timeout).await
+    }
+}
+
+#[async_trait]
+impl RaftNetwork for HttpRaftNetwork {
+    fn peers(&self) -> Vec<NodeId> {
+        self.discovery
+            .nodes()
+            .into_iter()
+            .filter(|n| n.id != self.local_node_id && n.status != ClusterNodeStatus::Left)
+            .map(|n| n.id)
+            .collect()
+    }
+
+    async fn request_vote(&self, target: &str, req: VoteRequest) -> ClusterResult<VoteResponse> {
+        self.call(target, "/cluster/raft/vote", &req).await
+    }
+
+    async fn append_entries(
+        &self,
+        target: &str,
+        req: AppendEntriesRequest,
+    ) -> ClusterResult<AppendEntriesResponse> {
+        self.call(target, "/cluster/raft/append", &req).await
+    }
+
+    async fn install_snapshot(
+        &self,
+        target: &str,
+        req: InstallSnapshotRequest,
+    ) -> ClusterResult<InstallSnapshotResponse> {
+        self.call(target, "/cluster/raft/snapshot", &req).await
+    }
+
+    async fn forward(&self, target: &str, req: ForwardRequest) -> ClusterResult<ForwardResponse> {
+        self.call(target, "/cluster/raft/forward", &req).await
+    }
+}
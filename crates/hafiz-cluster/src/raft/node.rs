@@ -0,0 +1,1352 @@
+//! Raft node: elections, log replication and the apply loop
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+use rand::Rng;
+use serde::Serialize;
+use tokio::sync::{oneshot, watch};
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, error, info, warn};
+
+use hafiz_core::types::NodeId;
+
+use super::log::RaftLog;
+use super::network::RaftNetwork;
+use super::rpc::{
+    AppendEntriesRequest, AppendEntriesResponse, ForwardRequest, ForwardResponse,
+    InstallSnapshotRequest, InstallSnapshotResponse, VoteRequest, VoteResponse,
+};
+use super::state_machine::{MetadataCommand, StateMachine};
+use super::storage::{HardState, RaftStorage, Snapshot};
+use super::{LogIndex, RaftConfig, Term};
+use crate::error::{ClusterError, ClusterResult};
+
+/// Role of a node in the current term
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RaftRole {
+    Follower,
+    Candidate,
+    Leader,
+}
+
+/// Point-in-time view of a node's Raft state
+#[derive(Debug, Clone, Serialize)]
+pub struct RaftStatus {
+    pub node_id: NodeId,
+    pub role: RaftRole,
+    pub term: Term,
+    pub leader_id: Option<NodeId>,
+    pub commit_index: LogIndex,
+    pub last_applied: LogIndex,
+    pub last_log_index: LogIndex,
+    pub snapshot_index: LogIndex,
+}
+
+/// Leader's view of a follower
+struct PeerProgress {
+    next_index: LogIndex,
+    match_index: LogIndex,
+    /// A replication request is outstanding
+    in_flight: bool,
+}
+
+struct RaftState {
+    role: RaftRole,
+    hard: HardState,
+    leader_id: Option<NodeId>,
+    log: RaftLog,
+    snapshot: Option<Snapshot>,
+    commit_index: LogIndex,
+    last_applied: LogIndex,
+    election_deadline: Instant,
+    next_heartbeat: Instant,
+    peers: HashMap<NodeId, PeerProgress>,
+    /// The term or vote changed but could not be written
+    hard_state_dirty: bool,
+    /// A log write failed, so the file may lag the log or end in a torn line
+    log_dirty: bool,
+}
+
+/// Request sent to a follower by the leader
+enum Outgoing {
+    Append(AppendEntriesRequest),
+    Snapshot(InstallSnapshotRequest),
+}
+
+/// Proposal waiting to be applied: the term it was proposed in and the caller
+type Waiter = (Term, oneshot::Sender<ClusterResult<()>>);
+
+/// A single member of a Raft group
+pub struct RaftNode {
+    id: NodeId,
+    config: RaftConfig,
+    state: Mutex<RaftState>,
+    state_machine: Arc<dyn StateMachine>,
+    network: Arc<dyn RaftNetwork>,
+    storage: RaftStorage,
+    /// Serializes access to the state machine (apply, snapshot, restore)
+    apply_lock: tokio::sync::Mutex<()>,
+    waiters: Mutex<HashMap<LogIndex, Waiter>>,
+    applied_tx: watch::Sender<LogIndex>,
+    running: AtomicBool,
+}
+
+impl RaftNode {
+    /// Create a node, recovering any persisted state.
+    ///
+    /// If a snapshot was persisted it is restored into the state machine, and
+    /// committed entries after it are re-applied once the node learns the
+    /// commit index from the leader.
+    pub async fn new(
+        id: NodeId,
+        config: RaftConfig,
+        state_machine: Arc<dyn StateMachine>,
+        network: Arc<dyn RaftNetwork>,
+        storage: RaftStorage,
+    ) -> ClusterResult<Arc<Self>> {
+        if config.voters.is_empty() {
+            return Err(ClusterError::InvalidConfig("Raft needs at least one voter".to_string()));
+        }
+
+        let mut recovered = storage.load()?;
+        let mut last_applied = 0;
+
+        if let Some(snapshot) = &recovered.snapshot {
+            state_machine.restore(&snapshot.data).await?;
+            // The log is saved after the snapshot, so it may lag behind it
+            if snapshot.index > recovered.log.snapshot_index() {
+                recovered.log.reset_to_snapshot(snapshot.index, snapshot.term);
+            }
+            last_applied = snapshot.index;
+        }
+
+        info!(
+            "Raft node {} starting at term {} (last log index {}, snapshot index {})",
+            id,
+            recovered.hard_state.current_term,
+            recovered.log.last_index(),
+            last_applied
+        );
+
+        let now = Instant::now();
+        let state = RaftState {
+            role: RaftRole::Follower,
+            hard: recovered.hard_state,
+            leader_id: None,
+            log: recovered.log,
+            snapshot: recovered.snapshot,
+            commit_index: last_applied,
+            last_applied,
+            election_deadline: now + random_election_timeout(&config),
+            next_heartbeat: now,
+            peers: HashMap::new(),
+            hard_state_dirty: false,
+            log_dirty: false,
+        };
+
+        let (applied_tx, _) = watch::channel(last_applied);
+
+        Ok(Arc::new(Self {
+            id,
+            config,
+            state: Mutex::new(state),
+            state_machine,
+            network,
+            storage,
+            apply_lock: tokio::sync::Mutex::new(()),
+            waiters: Mutex::new(HashMap::new()),
+            applied_tx,
+            running: AtomicBool::new(false),
+        }))
+    }
+
+    /// Start the election/heartbeat timer
+    pub fn start(self: &Arc<Self>) {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let node = Arc::clone(self);
+        tokio::spawn(async move { node.run().await });
+    }
+
+    /// Stop the timer; the node stops campaigning and sending heartbeats
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn is_leader(&self) -> bool {
+        self.state.lock().role == RaftRole::Leader
+    }
+
+    pub fn leader_id(&self) -> Option<NodeId> {
+        self.state.lock().leader_id.clone()
+    }
+
+    pub fn status(&self) -> RaftStatus {
+        let s = self.state.lock();
+        RaftStatus {
+            node_id: self.id.clone(),
+            role: s.role,
+            term: s.hard.current_term,
+            leader_id: s.leader_id.clone(),
+            commit_index: s.commit_index,
+            last_applied: s.last_applied,
+            last_log_index: s.log.last_index(),
+            snapshot_index: s.log.snapshot_index(),
+        }
+    }
+
+    /// Replicate a command and wait until it has been applied locally.
+    ///
+    /// Followers forward the command to the leader. Returns the log index
+    /// the command was committed at.
+    pub async fn propose(self: &Arc<Self>, command: MetadataCommand) -> ClusterResult<LogIndex> {
+        let leader = {
+            let s = self.state.lock();
+            match s.role {
+                RaftRole::Leader => None,
+                _ => Some(s.leader_id.clone()),
+            }
+        };
+
+        let leader = match leader {
+            None => return self.propose_local(command).await,
+            Some(None) => return Err(ClusterError::NotLeader(None)),
+            Some(Some(leader)) => leader,
+        };
+
+        debug!("Forwarding Raft proposal to leader {}", leader);
+        let resp = self
+            .network
+            .forward(&leader, ForwardRequest { command })
+            .await?;
+
+        let index = match (resp.index, resp.error) {
+            (Some(index), None) => index,
+            (_, error) if resp.leader_id.as_deref() != Some(leader.as_str()) => {
+                debug!("Forwarded proposal rejected: {:?}", error);
+                return Err(ClusterError::NotLeader(resp.leader_id));
+            }
+            (_, error) => {
+                return Err(ClusterError::ReplicationFailed(
+                    error.unwrap_or_else(|| "unknown error".to_string()),
+                ))
+            }
+        };
+
+        // Read-your-writes on this node
+        self.wait_applied(index).await?;
+        Ok(index)
+    }
+
+    /// Append a command on the leader and wait for it to be applied
+    async fn propose_local(self: &Arc<Self>, command: MetadataCommand) -> ClusterResult<LogIndex> {
+        let (index, rx) = {
+            let mut s = self.state.lock();
+            if s.role != RaftRole::Leader {
+                return Err(ClusterError::NotLeader(s.leader_id.clone()));
+            }
+
+            let term = s.hard.current_term;
+            let index = s.log.append(term, command);
+            if let Err(e) = self.persist_log(&mut s, Some(index)) {
+                // Not sent to anyone yet, so it can simply be dropped
+                s.log.truncate_from(index);
+                return Err(e);
+            }
+
+            let (tx, rx) = oneshot::channel();
+            self.waiters.lock().insert(index, (term, tx));
+            (index, rx)
+        };
+
+        self.broadcast_append();
+
+        match tokio::time::timeout(self.config.proposal_timeout, rx).await {
+            Ok(Ok(result)) => result.map(|_| index),
+            Ok(Err(_)) => Err(ClusterError::Internal("Raft proposal dropped".to_string())),
+            Err(_) => {
+                self.waiters.lock().remove(&index);
+                Err(ClusterError::ProposalTimeout)
+            }
+        }
+    }
+
+    /// Wait until the local state machine has applied `index`
+    async fn wait_applied(&self, index: LogIndex) -> ClusterResult<()> {
+        let mut rx = self.applied_tx.subscribe();
+        let result = tokio::time::timeout(self.config.proposal_timeout, async {
+            rx.wait_for(|&i| i >= index).await.map(|_| ())
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) => Err(ClusterError::Internal("Raft node shut down".to_string())),
+            Err(_) => Err(ClusterError::ProposalTimeout),
+        }
+    }
+
+    // ========================================================================
+    // RPC handlers
+    // ========================================================================
+
+    pub fn handle_request_vote(&self, req: VoteRequest) -> VoteResponse {
+        let mut s = self.state.lock();
+
+        if req.term > s.hard.current_term {
+            self.become_follower(&mut s, req.term, None);
+        }
+
+        let up_to_date = (req.last_log_term, req.last_log_index) >= (s.log.last_term(), s.log.last_index());
+        let can_vote = s
+            .hard
+            .voted_for
+            .as_ref()
+            .map_or(true, |v| *v == req.candidate_id);
+        let mut granted = req.term == s.hard.current_term
+            && can_vote
+            && up_to_date
+            && self.sync_hard_state(&mut s).is_ok();
+
+        if granted {
+            let previous = s.hard.voted_for.replace(req.candidate_id.clone());
+            granted = self.persist_hard_state(&mut s).is_ok();
+            if granted {
+                debug!(
+                    "Node {} voting for {} in term {}",
+                    self.id, req.candidate_id, req.term
+                );
+                s.election_deadline = Instant::now() + random_election_timeout(&self.config);
+            } else {
+                // A vote a restart could forget might be cast twice
+                s.hard.voted_for = previous;
+            }
+        }
+
+        VoteResponse {
+            term: s.hard.current_term,
+            vote_granted: granted,
+        }
+    }
+
+    /// Fails, rather than acknowledging entries, if the term or the entries
+    /// cannot be persisted
+    pub fn handle_append_entries(
+        self: &Arc<Self>,
+        req: AppendEntriesRequest,
+    ) -> ClusterResult<AppendEntriesResponse> {
+        let mut s = self.state.lock();
+
+        if req.term < s.hard.current_term {
+            return Ok(AppendEntriesResponse {
+                term: s.hard.current_term,
+                success: false,
+                last_log_index: s.log.last_index(),
+            });
+        }
+
+        self.become_follower(&mut s, req.term, Some(req.leader_id.clone()));
+        s.election_deadline = Instant::now() + random_election_timeout(&self.config);
+        self.sync_hard_state(&mut s)?;
+
+        // Entries at or before the snapshot are committed and therefore match
+        if req.prev_log_index > s.log.snapshot_index() {
+            let hint = match s.log.term_at(req.prev_log_index) {
+                Some(term) if term == req.prev_log_term => None,
+                Some(_) => Some(req.prev_log_index - 1),
+                None => Some(s.log.last_index()),
+            };
+            if let Some(hint) = hint {
+                return Ok(AppendEntriesResponse {
+                    term: s.hard.current_term,
+                    success: false,
+                    last_log_index: hint,
+                });
+            }
+        }
+
+        let last_new = req.prev_log_index + req.entries.len() as LogIndex;
+        let from = s.log.merge(req.entries);
+        self.persist_log(&mut s, from)?;
+
+        let commit = req.leader_commit.min(last_new);
+        let advanced = commit > s.commit_index;
+        if advanced {
+            s.commit_index = commit;
+        }
+
+        let resp = AppendEntriesResponse {
+            term: s.hard.current_term,
+            success: true,
+            last_log_index: last_new,
+        };
+        drop(s);
+
+        if advanced {
+            self.spawn_apply();
+        }
+        Ok(resp)
+    }
+
+    pub async fn handle_install_snapshot(
+        &self,
+        req: InstallSnapshotRequest,
+    ) -> ClusterResult<InstallSnapshotResponse> {
+        {
+            let mut s = self.state.lock();
+            if req.term < s.hard.current_term {
+                return Ok(InstallSnapshotResponse {
+                    term: s.hard.current_term,
+                });
+            }
+
+            self.become_follower(&mut s, req.term, Some(req.leader_id.clone()));
+            s.election_deadline = Instant::now() + random_election_timeout(&self.config);
+            self.sync_hard_state(&mut s)?;
+        }
+
+        let _guard = self.apply_lock.lock().await;
+
+        if self.state.lock().last_applied >= req.last_included_index {
+            // Already caught up past this snapshot
+            return Ok(InstallSnapshotResponse {
+                term: self.state.lock().hard.current_term,
+            });
+        }
+
+        info!(
+            "Installing Raft snapshot at index {} from {}",
+            req.last_included_index, req.leader_id
+        );
+        self.state_machine.restore(&req.data).await?;
+
+        let snapshot = Snapshot {
+            index: req.last_included_index,
+            term: req.last_included_term,
+            data: req.data,
+        };
+        self.storage.save_snapshot(&snapshot)?;
+
+        let mut s = self.state.lock();
+        s.log.reset_to_snapshot(snapshot.index, snapshot.term);
+        s.commit_index = s.commit_index.max(snapshot.index);
+        s.last_applied = snapshot.index;
+        self.rewrite_log(&mut s)?;
+        self.applied_tx.send_replace(snapshot.index);
+        s.snapshot = Some(snapshot);
+
+        Ok(InstallSnapshotResponse {
+            term: s.hard.current_term,
+        })
+    }
+
+    /// Handle a proposal forwarded by a follower
+    pub async fn handle_forward(self: &Arc<Self>, req: ForwardRequest) -> ForwardResponse {
+        match self.propose_local(req.command).await {
+            Ok(index) => ForwardResponse {
+                index: Some(index),
+                error: None,
+                leader_id: Some(self.id.clone()),
+            },
+            Err(ClusterError::NotLeader(leader)) => ForwardResponse {
+                index: None,
+                error: Some("not the leader".to_string()),
+                leader_id: leader,
+            },
+            Err(e) => ForwardResponse {
+                index: None,
+                error: Some(e.to_string()),
+                leader_id: Some(self.id.clone()),
+            },
+        }
+    }
+
+    // ========================================================================
+    // Elections
+    // ========================================================================
+
+    async fn run(self: Arc<Self>) {
+        let tick = (self.config.heartbeat_interval / 4).max(Duration::from_millis(10));
+        let mut interval = tokio::time::interval(tick);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        while self.running.load(Ordering::SeqCst) {
+            interval.tick().await;
+
+            let now = Instant::now();
+            let (heartbeat_due, election_due) = {
+                let s = self.state.lock();
+                match s.role {
+                    RaftRole::Leader => (now >= s.next_heartbeat, false),
+                    _ => (false, now >= s.election_deadline && self.is_voter(&self.id)),
+                }
+            };
+
+            if heartbeat_due {
+                self.broadcast_append();
+            } else if election_due {
+                self.start_election().await;
+            }
+        }
+
+        debug!("Raft node {} stopped", self.id);
+    }
+
+    async fn start_election(self: &Arc<Self>) {
+        let peers = self.network.peers();
+
+        let (term, req) = {
+            let mut s = self.state.lock();
+            s.role = RaftRole::Candidate;
+            s.hard.current_term += 1;
+            s.hard.voted_for = Some(self.id.clone());
+            s.leader_id = None;
+            s.election_deadline = Instant::now() + random_election_timeout(&self.config);
+            if self.persist_hard_state(&mut s).is_err() {
+                // Campaign again at the next timeout
+                return;
+            }
+
+            let req = VoteRequest {
+                term: s.hard.current_term,
+                candidate_id: self.id.clone(),
+                last_log_index: s.log.last_index(),
+                last_log_term: s.log.last_term(),
+            };
+            (s.hard.current_term, req)
+        };
+
+        info!("Node {} starting election for term {}", self.id, term);
+
+        let votes = futures::future::join_all(peers.iter().map(|peer| {
+            let req = req.clone();
+            async move {
+                let result = tokio::time::timeout(
+                    self.config.election_timeout,
+                    self.network.request_vote(peer, req),
+                )
+                .await;
+                (peer, result)
+            }
+        }))
+        .await;
+
+        let mut s = self.state.lock();
+        let mut granted = 1;
+
+        for (peer, result) in votes {
+            match result {
+                Ok(Ok(resp)) => {
+                    if resp.term > s.hard.current_term {
+                        self.become_follower(&mut s, resp.term, None);
+                        return;
+                    }
+                    if resp.vote_granted && resp.term == term && self.is_voter(peer) {
+                        granted += 1;
+                    }
+                }
+                Ok(Err(e)) => debug!("Vote request to {} failed: {}", peer, e),
+                Err(_) => debug!("Vote request to {} timed out", peer),
+            }
+        }
+
+        if s.role == RaftRole::Candidate
+            && s.hard.current_term == term
+            && granted >= quorum(self.config.voters.len())
+        {
+            self.become_leader(&mut s, &peers);
+            drop(s);
+            self.broadcast_append();
+        }
+    }
+
+    fn become_leader(&self, s: &mut RaftState, peers: &[NodeId]) {
+        info!(
+            "Node {} became Raft leader for term {}",
+            self.id, s.hard.current_term
+        );
+
+        s.role = RaftRole::Leader;
+        s.leader_id = Some(self.id.clone());
+
+        let next_index = s.log.last_index() + 1;
+        s.peers = peers
+            .iter()
+            .map(|p| {
+                (
+                    p.clone(),
+                    PeerProgress {
+                        next_index,
+                        match_index: 0,
+                        in_flight: false,
+                    },
+                )
+            })
+            .collect();
+
+        // Entries from earlier terms only commit once an entry from the
+        // current term does
+        let term = s.hard.current_term;
+        let index = s.log.append(term, MetadataCommand::Noop);
+        // Retried with the next heartbeat; until then this node's log does
+        // not count towards a majority
+        let _ = self.persist_log(s, Some(index));
+    }
+
+    fn become_follower(&self, s: &mut RaftState, term: Term, leader_id: Option<NodeId>) {
+        let newer_term = term > s.hard.current_term;
+        if newer_term {
+            s.hard.current_term = term;
+            s.hard.voted_for = None;
+            // Retried before this node next votes or acknowledges entries
+            let _ = self.persist_hard_state(s);
+        }
+
+        if s.role != RaftRole::Follower {
+            debug!("Node {} stepping down in term {}", self.id, term);
+            s.role = RaftRole::Follower;
+            s.election_deadline = Instant::now() + random_election_timeout(&self.config);
+            s.peers.clear();
+        }
+
+        if leader_id.is_some() {
+            s.leader_id = leader_id;
+        } else if newer_term {
+            s.leader_id = None;
+        }
+    }
+
+    // ========================================================================
+    // Replication
+    // ========================================================================
+
+    /// Send AppendEntries (or a snapshot) to every follower without a request in flight
+    fn broadcast_append(self: &Arc<Self>) {
+        let peers = self.network.peers();
+
+        let (targets, committed) = {
+            let mut s = self.state.lock();
+            if s.role != RaftRole::Leader {
+                return;
+            }
+            s.next_heartbeat = Instant::now() + self.config.heartbeat_interval;
+            if s.log_dirty {
+                let _ = self.rewrite_log(&mut s);
+            }
+
+            // Track membership changes reported by the network
+            let next_index = s.log.last_index() + 1;
+            s.peers.retain(|id, _| peers.contains(id));
+            for peer in &peers {
+                s.peers.entry(peer.clone()).or_insert(PeerProgress {
+                    next_index,
+                    match_index: 0,
+                    in_flight: false,
+                });
+            }
+
+            let targets: Vec<NodeId> = s
+                .peers
+                .iter_mut()
+                .filter(|(_, p)| !p.in_flight)
+                .map(|(id, p)| {
+                    p.in_flight = true;
+                    id.clone()
+                })
+                .collect();
+
+            // A single-node group commits as soon as it appends
+            (targets, self.advance_commit(&mut s))
+        };
+
+        for peer in targets {
+            let node = Arc::clone(self);
+            tokio::spawn(async move { node.replicate_to(peer).await });
+        }
+
+        if committed {
+            self.spawn_apply();
+        }
+    }
+
+    /// Replicate to one follower until it has caught up or a request fails
+    async fn replicate_to(self: Arc<Self>, peer: NodeId) {
+        loop {
+            match self.replicate_once(&peer).await {
+                Ok((committed, more)) => {
+                    if committed {
+                        self.spawn_apply();
+                    }
+                    if more {
+                        continue;
+                    }
+                }
+                Err(e) => debug!("Raft replication to {} failed: {}", peer, e),
+            }
+            break;
+        }
+
+        if let Some(p) = self.state.lock().peers.get_mut(&peer) {
+            p.in_flight = false;
+        }
+    }
+
+    /// Send a single request to `peer`.
+    ///
+    /// Returns whether the commit index advanced and whether the follower
+    /// still needs more entries.
+    async fn replicate_once(&self, peer: &str) -> ClusterResult<(bool, bool)> {
+        let outgoing = {
+            let s = self.state.lock();
+            if s.role != RaftRole::Leader {
+                return Ok((false, false));
+            }
+            let Some(progress) = s.peers.get(peer) else {
+                return Ok((false, false));
+            };
+
+            let term = s.hard.current_term;
+            let next_index = progress.next_index;
+
+            if next_index <= s.log.snapshot_index() {
+                let snapshot = s.snapshot.as_ref().ok_or_else(|| {
+                    ClusterError::Internal("Raft log compacted without a snapshot".to_string())
+                })?;
+                Outgoing::Snapshot(InstallSnapshotRequest {
+                    term,
+                    leader_id: self.id.clone(),
+                    last_included_index: snapshot.index,
+                    last_included_term: snapshot.term,
+                    data: snapshot.data.clone(),
+                })
+            } else {
+                let prev_log_index = next_index - 1;
+                Outgoing::Append(AppendEntriesRequest {
+                    term,
+                    leader_id: self.id.clone(),
+                    prev_log_index,
+                    prev_log_term: s.log.term_at(prev_log_index).unwrap_or(0),
+                    entries: s
+                        .log
+                        .entries_from(next_index, self.config.max_entries_per_append),
+                    leader_commit: s.commit_index,
+                })
+            }
+        };
+
+        let (term, result) = match outgoing {
+            Outgoing::Snapshot(req) => {
+                let term = req.term;
+                let index = req.last_included_index;
+                let resp = self.network.install_snapshot(peer, req).await?;
+                (term, (resp.term, Ok(index)))
+            }
+            Outgoing::Append(req) => {
+                let term = req.term;
+                let last_sent = req.prev_log_index + req.entries.len() as LogIndex;
+                let resp = self.network.append_entries(peer, req).await?;
+                let result = if resp.success {
+                    Ok(last_sent)
+                } else {
+                    Err(resp.last_log_index)
+                };
+                (term, (resp.term, result))
+            }
+        };
+        let (resp_term, result) = result;
+
+        let mut s = self.state.lock();
+        if resp_term > s.hard.current_term {
+            self.become_follower(&mut s, resp_term, None);
+            return Ok((false, false));
+        }
+        if s.role != RaftRole::Leader || s.hard.current_term != term {
+            return Ok((false, false));
+        }
+
+        let last_index = s.log.last_index();
+        let Some(progress) = s.peers.get_mut(peer) else {
+            return Ok((false, false));
+        };
+
+        match result {
+            Ok(matched) => {
+                progress.match_index = progress.match_index.max(matched);
+                progress.next_index = progress.match_index + 1;
+            }
+            Err(hint) => {
+                progress.next_index = (hint + 1)
+                    .min(progress.next_index.saturating_sub(1))
+                    .max(1);
+            }
+        }
+
+        let more = progress.next_index <= last_index;
+        Ok((self.advance_commit(&mut s), more))
+    }
+
+    /// Advance the commit index to the highest current-term entry stored on a
+    /// majority of the voters
+    fn advance_commit(&self, s: &mut RaftState) -> bool {
+        if s.role != RaftRole::Leader {
+            return false;
+        }
+
+        let mut matched: Vec<LogIndex> = self
+            .config
+            .voters
+            .iter()
+            .map(|voter| {
+                if *voter == self.id {
+                    if s.log_dirty {
+                        0
+                    } else {
+                        s.log.last_index()
+                    }
+                } else {
+                    s.peers.get(voter).map_or(0, |p| p.match_index)
+                }
+            })
+            .collect();
+        matched.sort_unstable_by(|a, b| b.cmp(a));
+
+        let candidate = matched[quorum(self.config.voters.len()) - 1];
+        if candidate > s.commit_index && s.log.term_at(candidate) == Some(s.hard.current_term) {
+            s.commit_index = candidate;
+            return true;
+        }
+        false
+    }
+
+    // ========================================================================
+    // Applying and compaction
+    // ========================================================================
+
+    fn spawn_apply(self: &Arc<Self>) {
+        let node = Arc::clone(self);
+        tokio::spawn(async move {
+            while !node.apply_committed().await && node.running.load(Ordering::SeqCst) {
+                tokio::time::sleep(node.config.heartbeat_interval).await;
+            }
+        });
+    }
+
+    /// Apply committed entries in order.
+    ///
+    /// Returns false if an entry failed to apply; it is left unapplied so
+    /// that a retry picks it up again.
+    async fn apply_committed(&self) -> bool {
+        let _guard = self.apply_lock.lock().await;
+        let mut applied_all = true;
+
+        loop {
+            let entry = {
+                let s = self.state.lock();
+                if s.last_applied >= s.commit_index {
+                    break;
+                }
+                match s.log.get(s.last_applied + 1) {
+                    Some(entry) => entry.clone(),
+                    None => break,
+                }
+            };
+
+            let result = self.state_machine.apply(&entry.command).await;
+            match &result {
+                Err(e) if !is_rejection(e) => {
+                    warn!("Failed to apply Raft entry {}, will retry: {}", entry.index, e);
+                    applied_all = false;
+                    break;
+                }
+                Err(e) => debug!("Raft entry {} rejected: {}", entry.index, e),
+                Ok(()) => {}
+            }
+
+            self.state.lock().last_applied = entry.index;
+            self.applied_tx.send_replace(entry.index);
+
+            if let Some((term, tx)) = self.waiters.lock().remove(&entry.index) {
+                // A different term means our entry was overwritten by a new leader
+                let result = if term == entry.term {
+                    result
+                } else {
+                    Err(ClusterError::NotLeader(None))
+                };
+                let _ = tx.send(result);
+            }
+        }
+
+        if let Err(e) = self.maybe_snapshot().await {
+            warn!("Failed to snapshot Raft state: {}", e);
+        }
+        applied_all
+    }
+
+    /// Compact the log once enough entries have been applied since the last snapshot.
+    ///
+    /// Must be called with `apply_lock` held.
+    async fn maybe_snapshot(&self) -> ClusterResult<()> {
+        let (index, term) = {
+            let s = self.state.lock();
+            if s.last_applied.saturating_sub(s.log.snapshot_index()) < self.config.snapshot_threshold {
+                return Ok(());
+            }
+            let Some(term) = s.log.term_at(s.last_applied) else {
+                return Ok(());
+            };
+            (s.last_applied, term)
+        };
+
+        let data = self.state_machine.snapshot().await?;
+        let snapshot = Snapshot { index, term, data };
+        self.storage.save_snapshot(&snapshot)?;
+
+        let mut s = self.state.lock();
+        s.log.compact(index, term);
+        s.snapshot = Some(snapshot);
+        self.rewrite_log(&mut s)?;
+
+        info!("Compacted Raft log up to index {}", index);
+        Ok(())
+    }
+
+    fn is_voter(&self, id: &str) -> bool {
+        self.config.voters.iter().any(|v| v == id)
+    }
+
+    /// Write the term and vote; a failure is remembered until a retry succeeds
+    fn persist_hard_state(&self, s: &mut RaftState) -> ClusterResult<()> {
+        let result = self.storage.save_hard_state(&s.hard);
+        if let Err(e) = &result {
+            error!("Failed to persist Raft hard state: {}", e);
+        }
+        s.hard_state_dirty = result.is_err();
+        result
+    }
+
+    /// Retry a failed hard state write
+    fn sync_hard_state(&self, s: &mut RaftState) -> ClusterResult<()> {
+        if s.hard_state_dirty {
+            self.persist_hard_state(s)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Append the entries from `from` on, or rewrite the whole log after an
+    /// earlier failure
+    fn persist_log(&self, s: &mut RaftState, from: Option<LogIndex>) -> ClusterResult<()> {
+        if s.log_dirty {
+            return self.rewrite_log(s);
+        }
+        let Some(from) = from else {
+            return Ok(());
+        };
+        let result = self.storage.append_log(&s.log.entries_from(from, usize::MAX));
+        self.log_written(s, result)
+    }
+
+    fn rewrite_log(&self, s: &mut RaftState) -> ClusterResult<()> {
+        let result = self.storage.save_log(&s.log);
+        self.log_written(s, result)
+    }
+
+    fn log_written(&self, s: &mut RaftState, result: ClusterResult<()>) -> ClusterResult<()> {
+        if let Err(e) = &result {
+            error!("Failed to persist Raft log: {}", e);
+        }
+        s.log_dirty = result.is_err();
+        result
+    }
+}
+
+/// Whether an apply error is the command's own outcome, which every node
+/// reaches alike (a missing bucket, say), rather than a failure to apply it
+fn is_rejection(e: &ClusterError) -> bool {
+    matches!(e, ClusterError::Storage(e) if e.http_status() < 500)
+}
+
+/// Votes (or replicas) needed for a majority of `voters`
+fn quorum(voters: usize) -> usize {
+    voters / 2 + 1
+}
+
+fn random_election_timeout(config: &RaftConfig) -> Duration {
+    let base = config.election_timeout.as_millis() as u64;
+    Duration::from_millis(base + rand::thread_rng().gen_range(0..=base))
+}
+
+impl std::fmt::Debug for RaftNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RaftNode")
+            .field("id", &self.id)
+            .field("config", &self.config)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use async_trait::async_trait;
+    use parking_lot::RwLock;
+
+    use super::*;
+    use crate::raft::state_machine::MemoryStateMachine;
+    use crate::raft::LogEntry;
+
+    /// Routes RPCs directly between in-process nodes
+    #[derive(Default)]
+    struct Router {
+        nodes: RwLock<HashMap<NodeId, Arc<RaftNode>>>,
+        down: RwLock<HashSet<NodeId>>,
+    }
+
+    impl Router {
+        fn target(&self, from: &str, to: &str) -> ClusterResult<Arc<RaftNode>> {
+            let down = self.down.read();
+            if down.contains(from) || down.contains(to) {
+                return Err(ClusterError::NodeUnreachable(to.to_string()));
+            }
+            self.nodes
+                .read()
+                .get(to)
+                .cloned()
+                .ok_or_else(|| ClusterError::NodeNotFound(to.to_string()))
+        }
+    }
+
+    struct LocalNetwork {
+        router: Arc<Router>,
+        id: NodeId,
+        members: Vec<NodeId>,
+    }
+
+    #[async_trait]
+    impl RaftNetwork for LocalNetwork {
+        fn peers(&self) -> Vec<NodeId> {
+            self.members.iter().filter(|m| **m != self.id).cloned().collect()
+        }
+
+        async fn request_vote(&self, target: &str, req: VoteRequest) -> ClusterResult<VoteResponse> {
+            Ok(self.router.target(&self.id, target)?.handle_request_vote(req))
+        }
+
+        async fn append_entries(
+            &self,
+            target: &str,
+            req: AppendEntriesRequest,
+        ) -> ClusterResult<AppendEntriesResponse> {
+            self.router.target(&self.id, target)?.handle_append_entries(req)
+        }
+
+        async fn install_snapshot(
+            &self,
+            target: &str,
+            req: InstallSnapshotRequest,
+        ) -> ClusterResult<InstallSnapshotResponse> {
+            self.router
+                .target(&self.id, target)?
+                .handle_install_snapshot(req)
+                .await
+        }
+
+        async fn forward(&self, target: &str, req: ForwardRequest) -> ClusterResult<ForwardResponse> {
+            Ok(self.router.target(&self.id, target)?.handle_forward(req).await)
+        }
+    }
+
+    fn test_config(snapshot_threshold: u64, voters: &[NodeId]) -> RaftConfig {
+        RaftConfig {
+            election_timeout: Duration::from_millis(50),
+            heartbeat_interval: Duration::from_millis(10),
+            snapshot_threshold,
+            max_entries_per_append: 4,
+            proposal_timeout: Duration::from_secs(5),
+            voters: voters.to_vec(),
+        }
+    }
+
+    async fn cluster(
+        size: usize,
+        snapshot_threshold: u64,
+    ) -> (Arc<Router>, Vec<(Arc<RaftNode>, Arc<MemoryStateMachine>)>) {
+        let router = Arc::new(Router::default());
+        let members: Vec<NodeId> = (1..=size).map(|i| format!("node-{}", i)).collect();
+        let mut nodes = Vec::new();
+
+        for id in &members {
+            let sm = Arc::new(MemoryStateMachine::new());
+            let network = Arc::new(LocalNetwork {
+                router: Arc::clone(&router),
+                id: id.clone(),
+                members: members.clone(),
+            });
+            let node = RaftNode::new(
+                id.clone(),
+                test_config(snapshot_threshold, &members),
+                sm.clone(),
+                network,
+                RaftStorage::in_memory(),
+            )
+            .await
+            .unwrap();
+            router.nodes.write().insert(id.clone(), Arc::clone(&node));
+            nodes.push((node, sm));
+        }
+
+        for (node, _) in &nodes {
+            node.start();
+        }
+        (router, nodes)
+    }
+
+    async fn wait_for(mut condition: impl FnMut() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("condition not reached in time");
+    }
+
+    /// Buckets deleted by applied commands, ignoring leader no-ops
+    fn user_commands(sm: &MemoryStateMachine) -> Vec<String> {
+        sm.applied()
+            .into_iter()
+            .filter_map(|c| match c {
+                MetadataCommand::DeleteBucket { name } => Some(name),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn delete_bucket(name: &str) -> MetadataCommand {
+        MetadataCommand::DeleteBucket {
+            name: name.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_single_node_commits() {
+        let (_router, nodes) = cluster(1, 1000).await;
+        let (node, sm) = &nodes[0];
+
+        wait_for(|| node.is_leader()).await;
+        let index = node.propose(delete_bucket("a")).await.unwrap();
+
+        assert_eq!(node.status().commit_index, index);
+        assert_eq!(user_commands(sm), vec!["a"]);
+    }
+
+    #[tokio::test]
+    async fn test_replicates_and_forwards() {
+        let (_router, nodes) = cluster(3, 1000).await;
+
+        wait_for(|| nodes.iter().filter(|(n, _)| n.is_leader()).count() == 1).await;
+        let leader = nodes.iter().find(|(n, _)| n.is_leader()).unwrap().0.clone();
+        let follower = nodes.iter().find(|(n, _)| !n.is_leader()).unwrap().0.clone();
+        wait_for(|| follower.leader_id().is_some()).await;
+
+        leader.propose(delete_bucket("a")).await.unwrap();
+        follower.propose(delete_bucket("b")).await.unwrap();
+
+        let expected = vec!["a", "b"];
+        // The forwarding follower has applied its own write before propose returns
+        assert!(user_commands(&nodes.iter().find(|(n, _)| Arc::ptr_eq(n, &follower)).unwrap().1)
+            .ends_with(&["b".to_string()]));
+        for (_, sm) in &nodes {
+            wait_for(|| user_commands(sm) == expected).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lagging_follower_catches_up_from_snapshot() {
+        let (router, nodes) = cluster(3, 5).await;
+
+        wait_for(|| nodes.iter().any(|(n, _)| n.is_leader())).await;
+        let leader = nodes.iter().find(|(n, _)| n.is_leader()).unwrap().0.clone();
+        let (lagging, lagging_sm) = nodes.iter().find(|(n, _)| !n.is_leader()).unwrap().clone();
+
+        router.down.write().insert(lagging.node_id().to_string());
+        for i in 0..12 {
+            leader.propose(delete_bucket(&format!("b{}", i))).await.unwrap();
+        }
+        wait_for(|| leader.status().snapshot_index > 0).await;
+
+        router.down.write().clear();
+        let expected = user_commands(&nodes.iter().find(|(n, _)| Arc::ptr_eq(n, &leader)).unwrap().1);
+        assert_eq!(expected.len(), 12);
+        wait_for(|| user_commands(&lagging_sm) == expected).await;
+        assert!(lagging.status().snapshot_index > 0);
+    }
+
+    /// Node `voters[0]`, whose network knows no other node
+    async fn lone_node(
+        voters: &[NodeId],
+        state_machine: Arc<dyn StateMachine>,
+        storage: RaftStorage,
+        snapshot_threshold: u64,
+    ) -> Arc<RaftNode> {
+        let network = Arc::new(LocalNetwork {
+            router: Arc::new(Router::default()),
+            id: voters[0].clone(),
+            members: vec![voters[0].clone()],
+        });
+        RaftNode::new(
+            voters[0].clone(),
+            test_config(snapshot_threshold, voters),
+            state_machine,
+            network,
+            storage,
+        )
+        .await
+        .unwrap()
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("hafiz-raft-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_undiscovered_voters_still_count() {
+        // Discovery has not found the other two voters yet
+        let voters: Vec<NodeId> = (1..=3).map(|i| format!("node-{}", i)).collect();
+        let sm = Arc::new(MemoryStateMachine::new());
+        let node = lone_node(&voters, sm, RaftStorage::in_memory(), 1000).await;
+        node.start();
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(node.status().term > 1);
+        assert!(!node.is_leader());
+    }
+
+    #[tokio::test]
+    async fn test_log_survives_restart() {
+        let dir = temp_dir();
+        let voters = vec!["node-1".to_string()];
+        let start = || {
+            let sm = Arc::new(MemoryStateMachine::new());
+            lone_node(&voters, sm, RaftStorage::new(Some(dir.clone())), 5)
+        };
+
+        let node = start().await;
+        node.start();
+        wait_for(|| node.is_leader()).await;
+        for i in 0..8 {
+            node.propose(delete_bucket(&format!("b{}", i))).await.unwrap();
+        }
+        wait_for(|| node.status().snapshot_index > 0).await;
+        node.stop();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let before = node.status();
+
+        let after = start().await.status();
+        assert_eq!(after.term, before.term);
+        assert_eq!(after.last_log_index, before.last_log_index);
+        assert_eq!(after.snapshot_index, before.snapshot_index);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_no_acknowledgement_without_persistence() {
+        let dir = temp_dir();
+        let voters: Vec<NodeId> = (1..=3).map(|i| format!("node-{}", i)).collect();
+        let sm = Arc::new(MemoryStateMachine::new());
+        let node = lone_node(&voters, sm, RaftStorage::new(Some(dir.clone())), 1000).await;
+
+        // Every write fails while the directory is gone
+        std::fs::remove_dir_all(&dir).unwrap();
+        let vote = VoteRequest {
+            term: 1,
+            candidate_id: voters[1].clone(),
+            last_log_index: 0,
+            last_log_term: 0,
+        };
+        assert!(!node.handle_request_vote(vote.clone()).vote_granted);
+        let append = AppendEntriesRequest {
+            term: 1,
+            leader_id: voters[1].clone(),
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![LogEntry {
+                index: 1,
+                term: 1,
+                command: delete_bucket("a"),
+            }],
+            leader_commit: 1,
+        };
+        assert!(node.handle_append_entries(append.clone()).is_err());
+        assert_eq!(node.status().commit_index, 0);
+
+        std::fs::create_dir_all(&dir).unwrap();
+        assert!(node.handle_request_vote(vote).vote_granted);
+        // The log file went with the directory, so appending to it still
+        // fails; the retry rewrites the whole log instead
+        assert!(node.handle_append_entries(append.clone()).is_err());
+        assert!(node.handle_append_entries(append).unwrap().success);
+        let recovered = RaftStorage::new(Some(dir.clone())).load().unwrap();
+        assert_eq!(recovered.hard_state.voted_for.as_deref(), Some("node-2"));
+        assert_eq!(recovered.log.last_index(), 1);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Fails to apply its first few commands
+    struct FlakyStateMachine {
+        failures: Mutex<usize>,
+        inner: MemoryStateMachine,
+    }
+
+    #[async_trait]
+    impl StateMachine for FlakyStateMachine {
+        async fn apply(&self, command: &MetadataCommand) -> ClusterResult<()> {
+            {
+                let mut failures = self.failures.lock();
+                if *failures > 0 {
+                    *failures -= 1;
+                    return Err(ClusterError::Io(std::io::Error::other("disk full")));
+                }
+            }
+            self.inner.apply(command).await
+        }
+
+        async fn snapshot(&self) -> ClusterResult<Vec<u8>> {
+            self.inner.snapshot().await
+        }
+
+        async fn restore(&self, snapshot: &[u8]) -> ClusterResult<()> {
+            self.inner.restore(snapshot).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_apply_is_retried() {
+        let voters = vec!["node-1".to_string()];
+        let sm = Arc::new(FlakyStateMachine {
+            failures: Mutex::new(3),
+            inner: MemoryStateMachine::new(),
+        });
+        let node = lone_node(&voters, sm.clone(), RaftStorage::in_memory(), 1000).await;
+        node.start();
+        wait_for(|| node.is_leader()).await;
+
+        node.propose(delete_bucket("a")).await.unwrap();
+        node.propose(delete_bucket("b")).await.unwrap();
+        assert_eq!(user_commands(&sm.inner), vec!["a", "b"]);
+    }
+}
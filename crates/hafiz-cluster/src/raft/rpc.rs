@@ -0,0 +1,95 @@
+//! Raft RPC messages
+//!
+//! These are exchanged as JSON over the cluster transport at
+//! `/cluster/raft/*`.
+
+use serde::{Deserialize, Serialize};
+
+use hafiz_core::types::NodeId;
+
+use super::log::LogEntry;
+use super::state_machine::MetadataCommand;
+use super::{LogIndex, Term};
+
+/// RequestVote RPC
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteRequest {
+    pub term: Term,
+    pub candidate_id: NodeId,
+    pub last_log_index: LogIndex,
+    pub last_log_term: Term,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VoteResponse {
+    pub term: Term,
+    pub vote_granted: bool,
+}
+
+/// AppendEntries RPC, also used as the leader heartbeat
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendEntriesRequest {
+    pub term: Term,
+    pub leader_id: NodeId,
+    pub prev_log_index: LogIndex,
+    pub prev_log_term: Term,
+    pub entries: Vec<LogEntry>,
+    pub leader_commit: LogIndex,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppendEntriesResponse {
+    pub term: Term,
+    pub success: bool,
+    /// On success, the follower's last log index; on failure, a hint for
+    /// where the leader should retry from
+    pub last_log_index: LogIndex,
+}
+
+/// InstallSnapshot RPC
+///
+/// Snapshots are sent in a single request rather than in chunks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallSnapshotRequest {
+    pub term: Term,
+    pub leader_id: NodeId,
+    pub last_included_index: LogIndex,
+    pub last_included_term: Term,
+    #[serde(with = "hex_bytes")]
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallSnapshotResponse {
+    pub term: Term,
+}
+
+/// A proposal forwarded from a follower to the leader
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardRequest {
+    pub command: MetadataCommand,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ForwardResponse {
+    /// Log index the command was committed at
+    pub index: Option<LogIndex>,
+    /// Set if the leader could not commit the command
+    pub error: Option<String>,
+    /// Leader known to the responding node, if it was not the leader
+    pub leader_id: Option<NodeId>,
+}
+
+/// Hex-encode snapshot bytes; a JSON array of numbers would be several times larger
+mod hex_bytes {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        hex::decode(s).map_err(serde::de::Error::custom)
+    }
+}
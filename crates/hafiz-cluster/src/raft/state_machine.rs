@@ -0,0 +1,277 @@
+//! Replicated metadata state machine
+//!
+//! Every committed [`MetadataCommand`] is applied, in log order, to a
+//! [`StateMachine`] on each node. [`MetadataStateMachine`] applies commands
+//! to the node's local `MetadataStore`.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+use hafiz_core::types::{Bucket, ObjectInternal, TagSet, VersioningStatus};
+use hafiz_metadata::MetadataStore;
+
+use crate::error::{ClusterError, ClusterResult};
+
+/// A metadata mutation that goes through the Raft log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum MetadataCommand {
+    /// Appended by a new leader to commit entries from earlier terms
+    Noop,
+    CreateBucket {
+        bucket: Bucket,
+    },
+    DeleteBucket {
+        name: String,
+    },
+    SetBucketVersioning {
+        name: String,
+        status: VersioningStatus,
+    },
+    PutObject {
+        object: ObjectInternal,
+    },
+    DeleteObject {
+        bucket: String,
+        key: String,
+    },
+    DeleteObjectVersion {
+        bucket: String,
+        key: String,
+        version_id: String,
+    },
+    /// Keys with an optional version ID each; unversioned entries get the
+    /// delete markers in `marker_ids`, if given
+    DeleteObjects {
+        bucket: String,
+        entries: Vec<(String, Option<String>)>,
+        marker_ids: Option<Vec<String>>,
+    },
+    PutObjectTags {
+        bucket: String,
+        key: String,
+        version_id: Option<String>,
+        tags: TagSet,
+    },
+    DeleteObjectTags {
+        bucket: String,
+        key: String,
+        version_id: Option<String>,
+    },
+    PutObjectAcl {
+        bucket: String,
+        key: String,
+        version_id: Option<String>,
+        acl_xml: String,
+    },
+    PutObjectRetention {
+        bucket: String,
+        key: String,
+        version_id: Option<String>,
+        retention_xml: String,
+    },
+    PutObjectLegalHold {
+        bucket: String,
+        key: String,
+        version_id: Option<String>,
+        hold_xml: String,
+    },
+    PutBucketPolicy {
+        bucket: String,
+        policy: String,
+    },
+    DeleteBucketPolicy {
+        bucket: String,
+    },
+}
+
+/// Target of committed log entries
+#[async_trait]
+pub trait StateMachine: Send + Sync + 'static {
+    /// Apply a committed command
+    async fn apply(&self, command: &MetadataCommand) -> ClusterResult<()>;
+
+    /// Serialize the full state for log compaction / follower catch-up
+    async fn snapshot(&self) -> ClusterResult<Vec<u8>>;
+
+    /// Replace the full state with a snapshot produced by [`StateMachine::snapshot`]
+    async fn restore(&self, snapshot: &[u8]) -> ClusterResult<()>;
+}
+
+/// Applies metadata commands to the local SQLite metadata store
+pub struct MetadataStateMachine {
+    store: Arc<MetadataStore>,
+    scratch_dir: PathBuf,
+}
+
+impl MetadataStateMachine {
+    /// `scratch_dir` holds temporary snapshot files while they are built or restored
+    pub fn new(store: Arc<MetadataStore>, scratch_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            store,
+            scratch_dir: scratch_dir.into(),
+        }
+    }
+
+    fn scratch_file(&self, name: &str) -> PathBuf {
+        self.scratch_dir
+            .join(format!("{}-{}.db", name, uuid::Uuid::new_v4()))
+    }
+}
+
+#[async_trait]
+impl StateMachine for MetadataStateMachine {
+    async fn apply(&self, command: &MetadataCommand) -> ClusterResult<()> {
+        match command {
+            MetadataCommand::Noop => Ok(()),
+            MetadataCommand::CreateBucket { bucket } => self.store.create_bucket(bucket).await,
+            MetadataCommand::DeleteBucket { name } => self.store.delete_bucket(name).await,
+            MetadataCommand::SetBucketVersioning { name, status } => {
+                self.store.set_bucket_versioning(name, *status).await
+            }
+            MetadataCommand::PutObject { object } => self.store.put_object(object).await,
+            MetadataCommand::DeleteObject { bucket, key } => {
+                self.store.delete_object(bucket, key).await
+            }
+            MetadataCommand::DeleteObjectVersion {
+                bucket,
+                key,
+                version_id,
+            } => self
+                .store
+                .delete_object_version(bucket, key, version_id)
+                .await
+                .map(|_| ()),
+            MetadataCommand::DeleteObjects {
+                bucket,
+                entries,
+                marker_ids,
+            } => self
+                .store
+                .delete_objects_with_markers(bucket, entries, marker_ids.as_deref())
+                .await
+                .map(|_| ()),
+            MetadataCommand::PutObjectTags {
+                bucket,
+                key,
+                version_id,
+                tags,
+            } => {
+                self.store
+                    .put_object_tags(bucket, key, version_id.as_deref(), tags)
+                    .await
+            }
+            MetadataCommand::DeleteObjectTags {
+                bucket,
+                key,
+                version_id,
+            } => {
+                self.store
+                    .delete_object_tags(bucket, key, version_id.as_deref())
+                    .await
+            }
+            MetadataCommand::PutObjectAcl {
+                bucket,
+                key,
+                version_id,
+                acl_xml,
+            } => {
+                self.store
+                    .put_object_acl(bucket, key, version_id.as_deref(), acl_xml)
+                    .await
+            }
+            MetadataCommand::PutObjectRetention {
+                bucket,
+                key,
+                version_id,
+                retention_xml,
+            } => {
+                self.store
+                    .put_object_retention(bucket, key, version_id.as_deref(), retention_xml)
+                    .await
+            }
+            MetadataCommand::PutObjectLegalHold {
+                bucket,
+                key,
+                version_id,
+                hold_xml,
+            } => {
+                self.store
+                    .put_object_legal_hold(bucket, key, version_id.as_deref(), hold_xml)
+                    .await
+            }
+            MetadataCommand::PutBucketPolicy { bucket, policy } => {
+                self.store.put_bucket_policy(bucket, policy).await
+            }
+            MetadataCommand::DeleteBucketPolicy { bucket } => {
+                self.store.delete_bucket_policy(bucket).await
+            }
+        }
+        .map_err(ClusterError::from)
+    }
+
+    async fn snapshot(&self) -> ClusterResult<Vec<u8>> {
+        tokio::fs::create_dir_all(&self.scratch_dir).await?;
+        let path = self.scratch_file("snapshot");
+
+        self.store.snapshot_to_file(&path).await?;
+        let data = tokio::fs::read(&path).await;
+        let _ = tokio::fs::remove_file(&path).await;
+
+        Ok(data?)
+    }
+
+    async fn restore(&self, snapshot: &[u8]) -> ClusterResult<()> {
+        tokio::fs::create_dir_all(&self.scratch_dir).await?;
+        let path = self.scratch_file("restore");
+
+        tokio::fs::write(&path, snapshot).await?;
+        let result = self
+            .store
+            .restore_tables_from_file(&path, MetadataStore::REPLICATED_TABLES)
+            .await;
+        let _ = tokio::fs::remove_file(&path).await;
+
+        result.map_err(ClusterError::from)
+    }
+}
+
+/// In-memory state machine that records applied commands
+///
+/// Useful for tests and for nodes that only participate in elections.
+#[derive(Default)]
+pub struct MemoryStateMachine {
+    applied: RwLock<Vec<MetadataCommand>>,
+}
+
+impl MemoryStateMachine {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Commands applied so far, in order
+    pub fn applied(&self) -> Vec<MetadataCommand> {
+        self.applied.read().clone()
+    }
+}
+
+#[async_trait]
+impl StateMachine for MemoryStateMachine {
+    async fn apply(&self, command: &MetadataCommand) -> ClusterResult<()> {
+        self.applied.write().push(command.clone());
+        Ok(())
+    }
+
+    async fn snapshot(&self) -> ClusterResult<Vec<u8>> {
+        Ok(serde_json::to_vec(&*self.applied.read())?)
+    }
+
+    async fn restore(&self, snapshot: &[u8]) -> ClusterResult<()> {
+        *self.applied.write() = serde_json::from_slice(snapshot)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,283 @@
+//! Durable Raft state
+//!
+//! Persists the current term, vote, log and latest snapshot under a data
+//! directory. Files are written to a temporary name and renamed into place
+//! so a crash never leaves a torn file behind. Without a data directory
+//! everything stays in memory, which is only safe for tests.
+//!
+//! The log is the exception: new entries are appended to it, one JSON line
+//! each, after a first line holding the snapshot point it continues from.
+//! An entry replaces any earlier line at the same index and those after
+//! it, as a leader overwriting conflicting entries does. Only lines ending
+//! in a newline count, so an append torn by a crash is ignored. The file
+//! is rewritten from scratch at startup and when the log is compacted.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use hafiz_core::types::NodeId;
+
+use super::log::{LogEntry, RaftLog};
+use super::{LogIndex, Term};
+use crate::error::{ClusterError, ClusterResult};
+
+const HARD_STATE_FILE: &str = "hard_state.json";
+const LOG_FILE: &str = "log.jsonl";
+const SNAPSHOT_META_FILE: &str = "snapshot.json";
+const SNAPSHOT_DATA_FILE: &str = "snapshot.bin";
+
+/// Term and vote, which must survive restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HardState {
+    pub current_term: Term,
+    pub voted_for: Option<NodeId>,
+}
+
+/// Latest state machine snapshot
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub index: LogIndex,
+    pub term: Term,
+    pub data: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotMeta {
+    index: LogIndex,
+    term: Term,
+}
+
+/// First line of the log file
+#[derive(Serialize, Deserialize)]
+struct LogStart {
+    snapshot_index: LogIndex,
+    snapshot_term: Term,
+}
+
+/// Everything recovered from disk at startup
+#[derive(Debug, Default)]
+pub struct RecoveredState {
+    pub hard_state: HardState,
+    pub log: RaftLog,
+    pub snapshot: Option<Snapshot>,
+}
+
+/// File-backed Raft storage
+#[derive(Debug, Clone)]
+pub struct RaftStorage {
+    dir: Option<PathBuf>,
+}
+
+impl RaftStorage {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self { dir }
+    }
+
+    /// Storage that never touches disk
+    pub fn in_memory() -> Self {
+        Self { dir: None }
+    }
+
+    /// Directory used for scratch files, if persistent
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// Load persisted state, or defaults if nothing has been written yet
+    pub fn load(&self) -> ClusterResult<RecoveredState> {
+        let Some(dir) = &self.dir else {
+            return Ok(RecoveredState::default());
+        };
+
+        std::fs::create_dir_all(dir)?;
+
+        let hard_state = read_json(&dir.join(HARD_STATE_FILE))?.unwrap_or_default();
+        // Rewritten so that appends never follow a torn line
+        let log = read_log(&dir.join(LOG_FILE))?.unwrap_or_default();
+        self.save_log(&log)?;
+
+        let snapshot = match read_json::<SnapshotMeta>(&dir.join(SNAPSHOT_META_FILE))? {
+            Some(meta) => Some(Snapshot {
+                index: meta.index,
+                term: meta.term,
+                data: std::fs::read(dir.join(SNAPSHOT_DATA_FILE))?,
+            }),
+            None => None,
+        };
+
+        Ok(RecoveredState {
+            hard_state,
+            log,
+            snapshot,
+        })
+    }
+
+    pub fn save_hard_state(&self, state: &HardState) -> ClusterResult<()> {
+        match &self.dir {
+            Some(dir) => write_atomic(&dir.join(HARD_STATE_FILE), &serde_json::to_vec(state)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Append entries to the log; they replace any entries already stored
+    /// at their indexes and after them
+    pub fn append_log(&self, entries: &[LogEntry]) -> ClusterResult<()> {
+        use std::io::Write;
+
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let mut data = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut data, entry)?;
+            data.push(b'\n');
+        }
+
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(dir.join(LOG_FILE))?;
+        file.write_all(&data)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Rewrite the log file with just the entries `log` still holds
+    pub fn save_log(&self, log: &RaftLog) -> ClusterResult<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        let start = LogStart {
+            snapshot_index: log.snapshot_index(),
+            snapshot_term: log.snapshot_term(),
+        };
+        let mut data = serde_json::to_vec(&start)?;
+        data.push(b'\n');
+        for entry in log.entries_from(log.snapshot_index() + 1, usize::MAX) {
+            serde_json::to_writer(&mut data, &entry)?;
+            data.push(b'\n');
+        }
+        write_atomic(&dir.join(LOG_FILE), &data)
+    }
+
+    pub fn save_snapshot(&self, snapshot: &Snapshot) -> ClusterResult<()> {
+        let Some(dir) = &self.dir else {
+            return Ok(());
+        };
+
+        // Data first, so the metadata never points at a missing snapshot
+        write_atomic(&dir.join(SNAPSHOT_DATA_FILE), &snapshot.data)?;
+        let meta = SnapshotMeta {
+            index: snapshot.index,
+            term: snapshot.term,
+        };
+        write_atomic(&dir.join(SNAPSHOT_META_FILE), &serde_json::to_vec(&meta)?)?;
+
+        debug!("Persisted Raft snapshot at index {}", snapshot.index);
+        Ok(())
+    }
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> ClusterResult<Option<T>> {
+    match std::fs::read(path) {
+        Ok(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Replay the log file written by [`RaftStorage::save_log`] and
+/// [`RaftStorage::append_log`]
+fn read_log(path: &Path) -> ClusterResult<Option<RaftLog>> {
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    // Anything after the last newline is an append cut short by a crash
+    let complete = match data.iter().rposition(|b| *b == b'\n') {
+        Some(end) => &data[..end],
+        None => return Ok(None),
+    };
+    let mut lines = complete.split(|b| *b == b'\n');
+
+    let start: LogStart = serde_json::from_slice(lines.next().unwrap_or_default())?;
+    let mut log = RaftLog::new();
+    log.reset_to_snapshot(start.snapshot_index, start.snapshot_term);
+
+    for line in lines {
+        let entry: LogEntry = serde_json::from_slice(line)?;
+        let index = entry.index;
+        if !log.replay(entry) {
+            return Err(ClusterError::Internal(format!(
+                "Raft log {} skips to index {}",
+                path.display(),
+                index
+            )));
+        }
+    }
+    Ok(Some(log))
+}
+
+fn write_atomic(path: &Path, data: &[u8]) -> ClusterResult<()> {
+    use std::io::Write;
+
+    let tmp = path.with_extension("tmp");
+    let mut file = std::fs::File::create(&tmp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raft::MetadataCommand;
+
+    fn entry(index: LogIndex, term: Term) -> LogEntry {
+        LogEntry {
+            index,
+            term,
+            command: MetadataCommand::Noop,
+        }
+    }
+
+    #[test]
+    fn test_log_appends_replay() {
+        let dir = std::env::temp_dir().join(format!("hafiz-raft-{}", uuid::Uuid::new_v4()));
+        let storage = RaftStorage::new(Some(dir.clone()));
+        assert_eq!(storage.load().unwrap().log.last_index(), 0);
+
+        storage.append_log(&[entry(1, 1), entry(2, 1), entry(3, 1)]).unwrap();
+        // A new leader overwrote entry 3 onwards
+        storage.append_log(&[entry(3, 2), entry(4, 2)]).unwrap();
+
+        // A torn append is dropped
+        let mut file = std::fs::OpenOptions::new().append(true).open(dir.join(LOG_FILE)).unwrap();
+        std::io::Write::write_all(&mut file, b"{\"index\":5,").unwrap();
+
+        let mut log = storage.load().unwrap().log;
+        assert_eq!(log.last_index(), 4);
+        assert_eq!(log.term_at(3), Some(2));
+        storage.append_log(&[entry(5, 2)]).unwrap();
+        assert_eq!(storage.load().unwrap().log.last_index(), 5);
+
+        log.compact(2, 1);
+        log.merge(vec![entry(5, 2)]);
+        storage.save_log(&log).unwrap();
+        let log = storage.load().unwrap().log;
+        assert_eq!(log.snapshot_index(), 2);
+        assert_eq!(log.len(), 3);
+        assert_eq!(log.last_index(), 5);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -6,8 +6,10 @@
 //! - Checksum verification
 //! - Retry with exponential backoff
 //! - Conflict resolution
+//! - Hinted handoff for targets that are down
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -20,14 +22,22 @@ use tokio::time::interval;
 use tracing::{debug, error, info, warn};
 
 use hafiz_core::types::{
-    ClusterNode, ConflictResolution, NodeId, ReplicationEvent, ReplicationEventType,
-    ReplicationMode, ReplicationProgress, ReplicationRule, ReplicationStatus,
+    ClusterNode, ClusterNodeStatus, ConflictResolution, NodeId, ReplicationEvent,
+    ReplicationEventType, ReplicationMode, ReplicationProgress, ReplicationRule, ReplicationStatus,
 };
 
 use crate::discovery::DiscoveryService;
 use crate::error::{ClusterError, ClusterResult};
+use crate::hints::{Hint, HintStore};
 use crate::transport::ClusterTransport;
 
+/// Hint metric names
+const HINTS_QUEUED: &str = "hafiz_cluster_hints_queued";
+const HINTS_STORED_TOTAL: &str = "hafiz_cluster_hints_stored_total";
+const HINTS_REPLAYED_TOTAL: &str = "hafiz_cluster_hints_replayed_total";
+const HINTS_EXPIRED_TOTAL: &str = "hafiz_cluster_hints_expired_total";
+const HINTS_DROPPED_TOTAL: &str = "hafiz_cluster_hints_dropped_total";
+
 /// Configuration for the replicator
 #[derive(Debug, Clone)]
 pub struct ReplicatorConfig {
@@ -45,6 +55,14 @@ pub struct ReplicatorConfig {
     pub conflict_resolution: ConflictResolution,
     /// Batch size for bulk operations
     pub batch_size: usize,
+    /// Directory for hinted handoff files (hints kept in memory if unset)
+    pub hint_dir: Option<PathBuf>,
+    /// How long a hint is kept before it is dropped
+    pub hint_ttl: Duration,
+    /// Maximum hints queued per target node; the oldest are dropped beyond this
+    pub max_hints_per_node: usize,
+    /// How often hints for recovered nodes are replayed
+    pub hint_replay_interval: Duration,
 }
 
 impl Default for ReplicatorConfig {
@@ -57,6 +75,10 @@ impl Default for ReplicatorConfig {
             verify_checksums: true,
             conflict_resolution: ConflictResolution::LastWriteWins,
             batch_size: 100,
+            hint_dir: None,
+            hint_ttl: Duration::from_secs(3 * 3600),
+            max_hints_per_node: 100_000,
+            hint_replay_interval: Duration::from_secs(10),
         }
     }
 }
@@ -78,6 +100,14 @@ pub struct ReplicatorStats {
     pub bytes_replicated: u64,
     /// Average replication latency in ms
     pub avg_latency_ms: f64,
+    /// Hints currently queued for nodes that are down
+    pub hints_queued: u64,
+    /// Hints written since startup
+    pub hints_stored: u64,
+    /// Hints delivered after their target came back
+    pub hints_replayed: u64,
+    /// Hints dropped after their TTL or because a queue was full
+    pub hints_expired: u64,
 }
 
 /// The replication engine
@@ -92,10 +122,14 @@ pub struct Replicator {
     event_tx: mpsc::Sender<ReplicationEvent>,
     /// Replication rules
     rules: Arc<RwLock<Vec<ReplicationRule>>>,
+    /// Tags of each source bucket, for rules with a bucket tag filter
+    bucket_tags: Arc<RwLock<HashMap<String, HashMap<String, String>>>>,
     /// Replication progress tracking
     progress: Arc<RwLock<HashMap<String, ReplicationProgress>>>,
     /// Statistics
     stats: Arc<RwLock<ReplicatorStats>>,
+    /// Hinted handoff queues
+    hints: Arc<HintStore>,
     /// Shutdown signal
     shutdown: Arc<RwLock<bool>>,
     /// This node's ID
@@ -112,20 +146,37 @@ impl Replicator {
     ) -> (Self, mpsc::Sender<ReplicationEvent>) {
         let (event_tx, event_rx) = mpsc::channel(config.queue_size);
 
+        let hints = HintStore::open(
+            config.hint_dir.clone(),
+            config.hint_ttl,
+            config.max_hints_per_node,
+        )
+        .unwrap_or_else(|e| {
+            error!("Failed to open hint directory, keeping hints in memory: {}", e);
+            HintStore::in_memory(config.hint_ttl, config.max_hints_per_node)
+        });
+        if !hints.is_empty() {
+            info!("Recovered {} hints from a previous run", hints.len());
+        }
+
         let replicator = Self {
             config: config.clone(),
             transport,
             discovery,
             event_tx: event_tx.clone(),
             rules: Arc::new(RwLock::new(Vec::new())),
+            bucket_tags: Arc::new(RwLock::new(HashMap::new())),
             progress: Arc::new(RwLock::new(HashMap::new())),
             stats: Arc::new(RwLock::new(ReplicatorStats::default())),
+            hints: Arc::new(hints),
             shutdown: Arc::new(RwLock::new(false)),
             node_id,
         };
+        Self::update_hint_metrics(&replicator.hints, &replicator.stats);
 
         // Start the processing loop in a separate task
         replicator.start_processing_loop(event_rx);
+        replicator.start_hint_replay_loop();
 
         (replicator, event_tx)
     }
@@ -136,6 +187,20 @@ impl Replicator {
         Ok(())
     }
 
+    /// Wait until the event queue is empty, up to `timeout`
+    ///
+    /// Returns false if events were still pending when it ran out.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.stats.read().pending > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
+    }
+
     /// Stop the replicator
     pub fn stop(&self) {
         info!("Stopping replicator");
@@ -161,6 +226,16 @@ impl Replicator {
         self.rules.read().clone()
     }
 
+    /// Record the current tags of a bucket; an empty set forgets it
+    pub fn set_bucket_tags(&self, bucket: &str, tags: HashMap<String, String>) {
+        let mut bucket_tags = self.bucket_tags.write();
+        if tags.is_empty() {
+            bucket_tags.remove(bucket);
+        } else {
+            bucket_tags.insert(bucket.to_string(), tags);
+        }
+    }
+
     /// Get replication progress for an object
     pub fn get_progress(&self, bucket: &str, key: &str) -> Option<ReplicationProgress> {
         let progress_key = format!("{}/{}", bucket, key);
@@ -172,6 +247,29 @@ impl Replicator {
         self.stats.read().clone()
     }
 
+    /// Replay queued hints now, for one node or all recovered nodes
+    pub fn replay_hints(&self, node_id: Option<NodeId>) {
+        let transport = Arc::clone(&self.transport);
+        let discovery = Arc::clone(&self.discovery);
+        let hints = Arc::clone(&self.hints);
+        let progress = Arc::clone(&self.progress);
+        let stats = Arc::clone(&self.stats);
+        let config = self.config.clone();
+
+        tokio::spawn(async move {
+            Self::replay(
+                &hints,
+                &transport,
+                &discovery,
+                &progress,
+                &stats,
+                &config,
+                node_id.as_deref(),
+            )
+            .await;
+        });
+    }
+
     /// Queue a replication event
     pub async fn queue_event(&self, event: ReplicationEvent) -> ClusterResult<()> {
         self.event_tx
@@ -188,8 +286,10 @@ impl Replicator {
         let transport = Arc::clone(&self.transport);
         let discovery = Arc::clone(&self.discovery);
         let rules = Arc::clone(&self.rules);
+        let bucket_tags = Arc::clone(&self.bucket_tags);
         let progress = Arc::clone(&self.progress);
         let stats = Arc::clone(&self.stats);
+        let hints = Arc::clone(&self.hints);
         let shutdown = Arc::clone(&self.shutdown);
         let config = self.config.clone();
         let node_id = self.node_id.clone();
@@ -208,8 +308,10 @@ impl Replicator {
                         let transport = Arc::clone(&transport);
                         let discovery = Arc::clone(&discovery);
                         let rules = Arc::clone(&rules);
+                        let bucket_tags = Arc::clone(&bucket_tags);
                         let progress = Arc::clone(&progress);
                         let stats = Arc::clone(&stats);
+                        let hints = Arc::clone(&hints);
                         let config = config.clone();
                         let node_id = node_id.clone();
 
@@ -219,7 +321,10 @@ impl Replicator {
                                 &transport,
                                 &discovery,
                                 &rules,
+                                &bucket_tags,
                                 &progress,
+                                &hints,
+                                &stats,
                                 &config,
                                 &node_id,
                             )
@@ -256,13 +361,181 @@ impl Replicator {
         });
     }
 
+    /// Periodically expire old hints and replay hints for recovered nodes
+    fn start_hint_replay_loop(&self) {
+        let transport = Arc::clone(&self.transport);
+        let discovery = Arc::clone(&self.discovery);
+        let hints = Arc::clone(&self.hints);
+        let progress = Arc::clone(&self.progress);
+        let stats = Arc::clone(&self.stats);
+        let shutdown = Arc::clone(&self.shutdown);
+        let config = self.config.clone();
+
+        tokio::spawn(async move {
+            let mut ticker = interval(config.hint_replay_interval);
+
+            loop {
+                ticker.tick().await;
+                if *shutdown.read() {
+                    break;
+                }
+                Self::replay(&hints, &transport, &discovery, &progress, &stats, &config, None)
+                    .await;
+            }
+        });
+    }
+
+    /// Deliver queued hints, oldest first, to healthy target nodes
+    async fn replay(
+        hints: &HintStore,
+        transport: &ClusterTransport,
+        discovery: &DiscoveryService,
+        progress: &RwLock<HashMap<String, ReplicationProgress>>,
+        stats: &RwLock<ReplicatorStats>,
+        config: &ReplicatorConfig,
+        only: Option<&str>,
+    ) {
+        let expired = hints.expire();
+        if expired > 0 {
+            warn!("Dropped {} hints older than {:?}", expired, hints.ttl());
+            stats.write().hints_expired += expired;
+            metrics::counter!(HINTS_EXPIRED_TOTAL).increment(expired);
+        }
+
+        for node_id in hints.nodes() {
+            if only.map_or(false, |n| n != node_id) {
+                continue;
+            }
+            let Some(node) = discovery.get_node(&node_id) else {
+                continue;
+            };
+            if !node.is_healthy() {
+                continue;
+            }
+
+            let mut replayed = 0u64;
+            while let Some(hint) = hints.peek(&node_id) {
+                match Self::deliver_hint(&hint, &node, transport, discovery, progress, config).await {
+                    Ok(bytes) => {
+                        hints.remove(&hint);
+                        replayed += 1;
+                        stats.write().bytes_replicated += bytes;
+                    }
+                    Err(HintFailure::Source(e)) if hint.attempts + 1 >= config.max_retries => {
+                        warn!(
+                            "Dropping hint {} for {}: source unavailable after {} attempts: {}",
+                            hint.id,
+                            node_id,
+                            hint.attempts + 1,
+                            e
+                        );
+                        hints.remove(&hint);
+                        stats.write().hints_expired += 1;
+                        metrics::counter!(HINTS_DROPPED_TOTAL).increment(1);
+                    }
+                    Err(HintFailure::Source(e)) | Err(HintFailure::Target(e)) => {
+                        debug!("Replaying hint {} to {} failed: {}", hint.id, node_id, e);
+                        hints.record_attempt(&hint);
+                        break;
+                    }
+                }
+            }
+
+            if replayed > 0 {
+                info!("Replayed {} hints to {}", replayed, node_id);
+                stats.write().hints_replayed += replayed;
+                metrics::counter!(HINTS_REPLAYED_TOTAL).increment(replayed);
+            }
+        }
+
+        Self::update_hint_metrics(hints, stats);
+    }
+
+    /// Deliver one hinted event to its target
+    async fn deliver_hint(
+        hint: &Hint,
+        target: &ClusterNode,
+        transport: &ClusterTransport,
+        discovery: &DiscoveryService,
+        progress: &RwLock<HashMap<String, ReplicationProgress>>,
+        config: &ReplicatorConfig,
+    ) -> Result<u64, HintFailure> {
+        let event = &hint.event;
+
+        match event.event_type {
+            ReplicationEventType::ObjectCreated | ReplicationEventType::MetadataUpdated => {
+                let key = event.key.as_ref().ok_or_else(|| {
+                    HintFailure::Source(ClusterError::Internal("Object event missing key".to_string()))
+                })?;
+                let (data, checksum) = Self::fetch_source(event, key, transport, discovery, config)
+                    .await
+                    .map_err(HintFailure::Source)?;
+                let len = data.len() as u64;
+
+                transport
+                    .upload_object_data(
+                        target,
+                        &event.bucket,
+                        key,
+                        data,
+                        checksum.as_deref(),
+                        &event.metadata,
+                    )
+                    .await
+                    .map_err(HintFailure::Target)?;
+                Ok(len)
+            }
+            ReplicationEventType::ObjectDeleted => {
+                Self::replicate_delete(event, &[target], transport, progress)
+                    .await
+                    .map_err(HintFailure::Target)?;
+                Ok(0)
+            }
+            ReplicationEventType::BucketCreated | ReplicationEventType::BucketDeleted => Ok(0),
+        }
+    }
+
+    /// Queue a hint for a target that could not be reached
+    fn store_hint(
+        hints: &HintStore,
+        stats: &RwLock<ReplicatorStats>,
+        target: &NodeId,
+        event: &ReplicationEvent,
+    ) {
+        match hints.add(target, event) {
+            Ok(dropped) => {
+                debug!("Stored hint for {} ({:?} {})", target, event.event_type, event.bucket);
+                let mut s = stats.write();
+                s.hints_stored += 1;
+                s.hints_expired += dropped;
+                metrics::counter!(HINTS_STORED_TOTAL).increment(1);
+                if dropped > 0 {
+                    metrics::counter!(HINTS_DROPPED_TOTAL).increment(dropped);
+                }
+            }
+            Err(e) => error!("Failed to store hint for {}: {}", target, e),
+        }
+        Self::update_hint_metrics(hints, stats);
+    }
+
+    fn update_hint_metrics(hints: &HintStore, stats: &RwLock<ReplicatorStats>) {
+        let depths = hints.depths();
+        stats.write().hints_queued = depths.values().sum::<usize>() as u64;
+        for (node, depth) in depths {
+            metrics::gauge!(HINTS_QUEUED, "node" => node).set(depth as f64);
+        }
+    }
+
     /// Process a single replication event
     async fn process_event(
         event: &ReplicationEvent,
         transport: &ClusterTransport,
         discovery: &DiscoveryService,
         rules: &RwLock<Vec<ReplicationRule>>,
+        bucket_tags: &RwLock<HashMap<String, HashMap<String, String>>>,
         progress: &RwLock<HashMap<String, ReplicationProgress>>,
+        hints: &HintStore,
+        stats: &RwLock<ReplicatorStats>,
         config: &ReplicatorConfig,
         local_node_id: &str,
     ) -> ClusterResult<u64> {
@@ -271,11 +544,15 @@ impl Replicator {
         // Find matching rules
         let matching_rules: Vec<ReplicationRule> = {
             let rules = rules.read();
+            let bucket_tags = bucket_tags.read();
+            let no_tags = HashMap::new();
+            let tags = bucket_tags.get(&event.bucket).unwrap_or(&no_tags);
             rules
                 .iter()
                 .filter(|r| {
                     r.enabled
                         && r.source_bucket == event.bucket
+                        && r.matches_bucket(tags)
                         && event.key.as_ref().map_or(true, |k| {
                             r.matches(k, &event.metadata)
                         })
@@ -289,27 +566,41 @@ impl Replicator {
             return Ok(0);
         }
 
-        // Get target nodes
-        let healthy_nodes = discovery.healthy_nodes();
+        // Get target nodes; members that are down get a hint instead, and
+        // draining members take no new data
+        let members: Vec<ClusterNode> = discovery
+            .nodes()
+            .into_iter()
+            .filter(|n| !matches!(n.status, ClusterNodeStatus::Left | ClusterNodeStatus::Draining))
+            .collect();
         let mut total_bytes: u64 = 0;
 
         for rule in matching_rules {
             // Determine target nodes for this rule
-            let targets: Vec<&ClusterNode> = if rule.target_nodes.is_empty() {
-                // Replicate to all healthy nodes except source
-                healthy_nodes
-                    .iter()
-                    .filter(|n| n.id != event.source_node && n.id != local_node_id)
-                    .collect()
-            } else {
-                healthy_nodes
-                    .iter()
-                    .filter(|n| rule.target_nodes.contains(&n.id))
-                    .collect()
-            };
+            let (targets, down): (Vec<&ClusterNode>, Vec<&ClusterNode>) = members
+                .iter()
+                .filter(|n| {
+                    if rule.target_nodes.is_empty() {
+                        // Replicate to all nodes except source
+                        n.id != event.source_node && n.id != local_node_id
+                    } else {
+                        rule.target_nodes.contains(&n.id)
+                    }
+                })
+                .partition(|n| n.is_healthy());
+
+            let hint_down = matches!(
+                event.event_type,
+                ReplicationEventType::ObjectCreated | ReplicationEventType::MetadataUpdated
+            ) || (event.event_type == ReplicationEventType::ObjectDeleted && rule.replicate_deletes);
+            if hint_down {
+                for node in &down {
+                    Self::store_hint(hints, stats, &node.id, event);
+                }
+            }
 
             if targets.is_empty() {
-                debug!("No target nodes for rule {}", rule.id);
+                debug!("No reachable target nodes for rule {}", rule.id);
                 continue;
             }
 
@@ -322,6 +613,8 @@ impl Replicator {
                         transport,
                         discovery,
                         progress,
+                        hints,
+                        stats,
                         config,
                     )
                     .await?;
@@ -349,6 +642,8 @@ impl Replicator {
         transport: &ClusterTransport,
         discovery: &DiscoveryService,
         progress: &RwLock<HashMap<String, ReplicationProgress>>,
+        hints: &HintStore,
+        stats: &RwLock<ReplicatorStats>,
         config: &ReplicatorConfig,
     ) -> ClusterResult<u64> {
         let key = event.key.as_ref().ok_or_else(|| {
@@ -376,32 +671,7 @@ impl Replicator {
             );
         }
 
-        // Fetch object data from source node
-        let source_node = discovery
-            .get_node(&event.source_node)
-            .ok_or_else(|| ClusterError::NodeNotFound(event.source_node.clone()))?;
-
-        let (data, checksum) = transport
-            .fetch_object_data(
-                &source_node,
-                &event.bucket,
-                key,
-                event.version_id.as_deref(),
-            )
-            .await?;
-
-        // Verify checksum if enabled
-        if config.verify_checksums {
-            if let Some(expected) = &event.checksum {
-                let actual = Self::compute_checksum(&data);
-                if &actual != expected {
-                    return Err(ClusterError::ChecksumMismatch {
-                        expected: expected.clone(),
-                        got: actual,
-                    });
-                }
-            }
-        }
+        let (data, checksum) = Self::fetch_source(event, key, transport, discovery, config).await?;
 
         let data_len = data.len() as u64;
 
@@ -437,13 +707,51 @@ impl Replicator {
             }
 
             if let Err(e) = result {
-                warn!("Failed to replicate to {}: {}", target.id, e);
+                warn!("Failed to replicate to {}, storing hint: {}", target.id, e);
+                Self::store_hint(hints, stats, &target.id, event);
             }
         }
 
         Ok(data_len)
     }
 
+    /// Fetch and verify object data from the event's source node
+    async fn fetch_source(
+        event: &ReplicationEvent,
+        key: &str,
+        transport: &ClusterTransport,
+        discovery: &DiscoveryService,
+        config: &ReplicatorConfig,
+    ) -> ClusterResult<(Bytes, Option<String>)> {
+        let source_node = discovery
+            .get_node(&event.source_node)
+            .ok_or_else(|| ClusterError::NodeNotFound(event.source_node.clone()))?;
+
+        let (data, checksum) = transport
+            .fetch_object_data(
+                &source_node,
+                &event.bucket,
+                key,
+                event.version_id.as_deref(),
+            )
+            .await?;
+
+        // Verify checksum if enabled
+        if config.verify_checksums {
+            if let Some(expected) = &event.checksum {
+                let actual = Self::compute_checksum(&data);
+                if &actual != expected {
+                    return Err(ClusterError::ChecksumMismatch {
+                        expected: expected.clone(),
+                        got: actual,
+                    });
+                }
+            }
+        }
+
+        Ok((data, checksum))
+    }
+
     /// Replicate a delete operation to target nodes
     async fn replicate_delete(
         event: &ReplicationEvent,
@@ -474,6 +782,14 @@ impl Replicator {
     }
 }
 
+/// Why a hint could not be delivered
+enum HintFailure {
+    /// The object could not be read from its source; retrying may not help
+    Source(ClusterError),
+    /// The target is still unreachable
+    Target(ClusterError),
+}
+
 impl std::fmt::Debug for Replicator {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Replicator")
@@ -1,10 +1,12 @@
 //! Cluster transport layer for node-to-node communication
 //!
-//! Handles HTTP-based communication between cluster nodes with:
-//! - TLS support for secure communication
+//! Handles communication between cluster nodes with:
+//! - TLS support for secure communication, with client certificates
 //! - Automatic retry with exponential backoff
 //! - Connection pooling
 //! - Request timeouts
+//! - gRPC with per-peer version negotiation and HTTP fallback (`grpc` feature)
+//! - A shared secret sent with every request, checked by the receiving node
 
 use std::sync::Arc;
 use std::time::Duration;
@@ -13,9 +15,18 @@ use bytes::Bytes;
 use reqwest::{Client, ClientBuilder};
 use tracing::{debug, error, warn};
 
-use hafiz_core::types::{ClusterMessage, ClusterNode, NodeId};
+use hafiz_core::types::{ClusterMessage, ClusterNode, ClusterTransportProtocol, NodeId};
 
 use crate::error::{ClusterError, ClusterResult};
+#[cfg(feature = "grpc")]
+use crate::grpc::{GrpcClient, GrpcTlsConfig};
+
+/// Header (and gRPC metadata key) carrying the cluster's shared secret
+pub const PEER_SECRET_HEADER: &str = "x-hafiz-cluster-secret";
+
+/// How long a failed gRPC negotiation keeps a peer on HTTP before retrying
+#[cfg(feature = "grpc")]
+const RENEGOTIATE_AFTER: Duration = Duration::from_secs(60);
 
 /// Transport configuration
 #[derive(Debug, Clone)]
@@ -36,6 +47,14 @@ pub struct TransportConfig {
     pub client_cert_path: Option<String>,
     /// Client key path
     pub client_key_path: Option<String>,
+    /// Preferred wire protocol
+    pub protocol: ClusterTransportProtocol,
+    /// Local node ID, sent in the gRPC handshake
+    pub local_node_id: NodeId,
+    /// Cluster name, checked by peers in the gRPC handshake
+    pub cluster_name: String,
+    /// Sent to peers with every request
+    pub shared_secret: Option<String>,
 }
 
 impl Default for TransportConfig {
@@ -49,14 +68,31 @@ impl Default for TransportConfig {
             ca_cert_path: None,
             client_cert_path: None,
             client_key_path: None,
+            protocol: ClusterTransportProtocol::Http,
+            local_node_id: NodeId::new(),
+            cluster_name: String::new(),
+            shared_secret: None,
         }
     }
 }
 
+/// Protocol negotiated with a peer
+#[cfg(feature = "grpc")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PeerProtocol {
+    Http,
+    Grpc,
+}
+
 /// Cluster transport for node communication
 pub struct ClusterTransport {
     client: Client,
     config: TransportConfig,
+    #[cfg(feature = "grpc")]
+    grpc: Option<GrpcClient>,
+    /// Negotiation results keyed by peer gRPC endpoint
+    #[cfg(feature = "grpc")]
+    negotiated: parking_lot::RwLock<std::collections::HashMap<String, (PeerProtocol, std::time::Instant)>>,
 }
 
 impl ClusterTransport {
@@ -72,13 +108,127 @@ impl ClusterTransport {
             builder = builder.danger_accept_invalid_certs(true);
         }
 
-        // TODO: Add custom CA and client certificates if provided
+        if let Some(secret) = &config.shared_secret {
+            let mut value = reqwest::header::HeaderValue::from_str(secret)
+                .map_err(|_| ClusterError::InvalidConfig("Invalid cluster shared secret".to_string()))?;
+            value.set_sensitive(true);
+            let headers = reqwest::header::HeaderMap::from_iter([(
+                reqwest::header::HeaderName::from_static(PEER_SECRET_HEADER),
+                value,
+            )]);
+            builder = builder.default_headers(headers);
+        }
+
+        if let Some(ca_path) = &config.ca_cert_path {
+            let pem = read_pem(ca_path)?;
+            let ca = reqwest::Certificate::from_pem(&pem)
+                .map_err(|e| ClusterError::InvalidConfig(format!("Invalid CA certificate: {}", e)))?;
+            builder = builder.add_root_certificate(ca);
+        }
+
+        if let (Some(cert_path), Some(key_path)) = (&config.client_cert_path, &config.client_key_path) {
+            let mut pem = read_pem(cert_path)?;
+            pem.extend_from_slice(&read_pem(key_path)?);
+            let identity = reqwest::Identity::from_pem(&pem)
+                .map_err(|e| ClusterError::InvalidConfig(format!("Invalid client certificate: {}", e)))?;
+            builder = builder.identity(identity);
+        }
 
         let client = builder
             .build()
             .map_err(|e| ClusterError::Transport(e.to_string()))?;
 
-        Ok(Self { client, config })
+        #[cfg(feature = "grpc")]
+        let grpc = match config.protocol {
+            ClusterTransportProtocol::Grpc => {
+                // Never fall back to plaintext when TLS was asked for
+                let tls = match (&config.client_cert_path, &config.client_key_path, &config.ca_cert_path) {
+                    _ if !config.verify_tls => None,
+                    (Some(cert), Some(key), Some(ca)) => Some(GrpcTlsConfig {
+                        cert_path: cert.clone(),
+                        key_path: key.clone(),
+                        ca_cert_path: ca.clone(),
+                    }),
+                    _ => {
+                        return Err(ClusterError::InvalidConfig(
+                            "gRPC mutual TLS requires cluster_tls_cert, cluster_tls_key and cluster_ca_cert"
+                                .to_string(),
+                        ))
+                    }
+                };
+                Some(GrpcClient::new(
+                    config.local_node_id.clone(),
+                    config.cluster_name.clone(),
+                    config.shared_secret.clone(),
+                    tls,
+                    config.connect_timeout,
+                    config.timeout,
+                )?)
+            }
+            ClusterTransportProtocol::Http => None,
+        };
+
+        #[cfg(not(feature = "grpc"))]
+        if config.protocol == ClusterTransportProtocol::Grpc {
+            warn!("gRPC cluster transport requested but not compiled in; using HTTP");
+        }
+
+        Ok(Self {
+            client,
+            config,
+            #[cfg(feature = "grpc")]
+            grpc,
+            #[cfg(feature = "grpc")]
+            negotiated: parking_lot::RwLock::new(std::collections::HashMap::new()),
+        })
+    }
+
+    /// gRPC client and endpoint for a node, if gRPC was negotiated with it
+    ///
+    /// The handshake runs on first contact. Peers without a gRPC endpoint or
+    /// with an incompatible protocol version are reached over HTTP, and
+    /// negotiation is retried after `RENEGOTIATE_AFTER`.
+    #[cfg(feature = "grpc")]
+    async fn grpc_for<'a>(&'a self, node: &'a ClusterNode) -> Option<(&'a GrpcClient, &'a str)> {
+        let grpc = self.grpc.as_ref()?;
+        let endpoint = node.grpc_endpoint.as_deref()?;
+
+        let cached = self.negotiated.read().get(endpoint).copied();
+        let protocol = match cached {
+            Some((PeerProtocol::Grpc, _)) => PeerProtocol::Grpc,
+            Some((PeerProtocol::Http, at)) if at.elapsed() < RENEGOTIATE_AFTER => PeerProtocol::Http,
+            _ => {
+                let protocol = match grpc.handshake(endpoint).await {
+                    Ok(version) => {
+                        debug!("Using gRPC protocol v{} with node {}", version, node.id);
+                        PeerProtocol::Grpc
+                    }
+                    Err(e) => {
+                        warn!("gRPC handshake with node {} failed, using HTTP: {}", node.id, e);
+                        grpc.forget(endpoint);
+                        PeerProtocol::Http
+                    }
+                };
+                self.negotiated
+                    .write()
+                    .insert(endpoint.to_string(), (protocol, std::time::Instant::now()));
+                protocol
+            }
+        };
+
+        (protocol == PeerProtocol::Grpc).then_some((grpc, endpoint))
+    }
+
+    /// Force renegotiation with a peer whose gRPC connection failed
+    #[cfg(feature = "grpc")]
+    fn track<T>(&self, endpoint: &str, result: ClusterResult<T>) -> ClusterResult<T> {
+        if let Err(ClusterError::NodeUnreachable(_)) = &result {
+            self.negotiated.write().remove(endpoint);
+            if let Some(grpc) = &self.grpc {
+                grpc.forget(endpoint);
+            }
+        }
+        result
     }
 
     /// Send a message to a node
@@ -87,11 +237,19 @@ impl ClusterTransport {
         node: &ClusterNode,
         message: &ClusterMessage,
     ) -> ClusterResult<ClusterMessage> {
+        #[cfg(feature = "grpc")]
+        if let Some((grpc, endpoint)) = self.grpc_for(node).await {
+            return self.track(endpoint, grpc.send_message(endpoint, message).await);
+        }
+
         let url = format!("{}/cluster/message", node.cluster_endpoint);
         self.send_with_retry(&url, message).await
     }
 
     /// Send a join request to a seed node
+    ///
+    /// Always HTTP: a seed is only known by its cluster endpoint until it
+    /// answers with its node record.
     pub async fn send_join_request(
         &self,
         seed_endpoint: &str,
@@ -107,6 +265,13 @@ impl ClusterTransport {
         node: &ClusterNode,
         message: &ClusterMessage,
     ) -> ClusterResult<()> {
+        #[cfg(feature = "grpc")]
+        if let Some((grpc, endpoint)) = self.grpc_for(node).await {
+            return self
+                .track(endpoint, grpc.send_message(endpoint, message).await)
+                .map(|_| ());
+        }
+
         let url = format!("{}/cluster/heartbeat", node.cluster_endpoint);
         let _: ClusterMessage = self.send_with_retry(&url, message).await?;
         Ok(())
@@ -120,6 +285,12 @@ impl ClusterTransport {
         key: &str,
         version_id: Option<&str>,
     ) -> ClusterResult<(Bytes, Option<String>)> {
+        #[cfg(feature = "grpc")]
+        if let Some((grpc, endpoint)) = self.grpc_for(node).await {
+            let result = grpc.fetch_object(endpoint, bucket, key, version_id).await;
+            return self.track(endpoint, result);
+        }
+
         let mut url = format!(
             "{}/cluster/objects/{}/{}",
             node.cluster_endpoint, bucket, key
@@ -167,6 +338,14 @@ impl ClusterTransport {
         checksum: Option<&str>,
         metadata: &std::collections::HashMap<String, String>,
     ) -> ClusterResult<()> {
+        #[cfg(feature = "grpc")]
+        if let Some((grpc, endpoint)) = self.grpc_for(node).await {
+            let result = grpc
+                .upload_object(endpoint, bucket, key, data, checksum, metadata)
+                .await;
+            return self.track(endpoint, result);
+        }
+
         let url = format!(
             "{}/cluster/objects/{}/{}",
             node.cluster_endpoint, bucket, key
@@ -199,9 +378,16 @@ impl ClusterTransport {
 
     /// Check if a node is reachable
     pub async fn ping(&self, node: &ClusterNode) -> ClusterResult<Duration> {
-        let url = format!("{}/cluster/ping", node.cluster_endpoint);
         let start = std::time::Instant::now();
 
+        #[cfg(feature = "grpc")]
+        if let Some((grpc, endpoint)) = self.grpc_for(node).await {
+            let result = grpc.ping(endpoint).await.map(|_| start.elapsed());
+            return self.track(endpoint, result);
+        }
+
+        let url = format!("{}/cluster/ping", node.cluster_endpoint);
+
         let response = self
             .client
             .get(&url)
@@ -219,6 +405,51 @@ impl ClusterTransport {
         }
     }
 
+    /// POST a JSON body to a cluster endpoint path without retrying
+    ///
+    /// Used by protocols such as Raft that handle retries themselves.
+    pub async fn post_json<Req, Resp>(
+        &self,
+        node: &ClusterNode,
+        path: &str,
+        body: &Req,
+        timeout: Duration,
+    ) -> ClusterResult<Resp>
+    where
+        Req: serde::Serialize + ?Sized,
+        Resp: serde::de::DeserializeOwned,
+    {
+        #[cfg(feature = "grpc")]
+        if let Some((grpc, endpoint)) = self.grpc_for(node).await {
+            let result = grpc.call_json(endpoint, path, body, timeout).await;
+            return self.track(endpoint, result);
+        }
+
+        let url = format!("{}{}", node.cluster_endpoint, path);
+        let response = self
+            .client
+            .post(&url)
+            .json(body)
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| ClusterError::Transport(e.to_string()))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(ClusterError::Transport(format!(
+                "Request failed with status {}: {}",
+                status, body
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| ClusterError::Transport(e.to_string()))
+    }
+
     /// Send a message with retry logic
     async fn send_with_retry<T: serde::de::DeserializeOwned>(
         &self,
@@ -277,6 +508,29 @@ impl ClusterTransport {
     }
 }
 
+/// Whether a request presented the cluster's shared secret
+///
+/// Always true when no secret is configured; peers are then authenticated
+/// by their TLS client certificates.
+pub fn verify_peer_secret(expected: Option<&str>, presented: Option<&str>) -> bool {
+    let Some(expected) = expected else {
+        return true;
+    };
+    let presented = presented.unwrap_or("");
+    // Compare in constant time so the secret cannot be guessed byte by byte
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn read_pem(path: &str) -> ClusterResult<Vec<u8>> {
+    std::fs::read(path)
+        .map_err(|e| ClusterError::InvalidConfig(format!("Failed to read {}: {}", path, e)))
+}
+
 impl std::fmt::Debug for ClusterTransport {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("ClusterTransport")
@@ -289,11 +543,48 @@ impl std::fmt::Debug for ClusterTransport {
 mod tests {
     use super::*;
 
+    #[cfg(feature = "grpc")]
+    #[test]
+    fn test_grpc_refuses_incomplete_tls() {
+        let config = TransportConfig {
+            protocol: ClusterTransportProtocol::Grpc,
+            verify_tls: true,
+            ca_cert_path: Some("ca.crt".to_string()),
+            ..TransportConfig::default()
+        };
+        assert!(matches!(
+            ClusterTransport::new(config),
+            Err(ClusterError::InvalidConfig(_))
+        ));
+    }
+
     #[test]
     fn test_transport_config_default() {
         let config = TransportConfig::default();
         assert_eq!(config.timeout, Duration::from_secs(30));
         assert_eq!(config.max_retries, 3);
         assert!(config.verify_tls);
+        assert_eq!(config.protocol, ClusterTransportProtocol::Http);
+    }
+
+    #[test]
+    fn test_verify_peer_secret() {
+        assert!(verify_peer_secret(None, None));
+        assert!(verify_peer_secret(Some("s3cret"), Some("s3cret")));
+        assert!(!verify_peer_secret(Some("s3cret"), Some("s3creT")));
+        assert!(!verify_peer_secret(Some("s3cret"), Some("s3cret2")));
+        assert!(!verify_peer_secret(Some("s3cret"), None));
+    }
+
+    #[test]
+    fn test_transport_rejects_missing_ca() {
+        let config = TransportConfig {
+            ca_cert_path: Some("/nonexistent/ca.crt".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            ClusterTransport::new(config),
+            Err(ClusterError::InvalidConfig(_))
+        ));
     }
 }
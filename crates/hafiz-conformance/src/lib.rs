@@ -0,0 +1,11 @@
+//! S3 conformance tests for Hafiz
+//!
+//! The tests live under `tests/`, one file per area of the API, in the
+//! spirit of ceph's s3-tests: each starts an ephemeral server with
+//! [`hafiz_testing::TestServer`] and checks responses against the
+//! behavior the S3 API specifies, including error codes and statuses.
+//!
+//! ```text
+//! cargo test -p hafiz-conformance
+//! cargo test -p hafiz-conformance --test multipart
+//! ```
@@ -0,0 +1,142 @@
+//! Error codes and HTTP statuses of common failures
+
+use hafiz_client::GetObjectOptions;
+use hafiz_testing::{assert_s3_error, unique_name, TestServer, ACCESS_KEY, SECRET_KEY};
+
+#[tokio::test]
+async fn missing_bucket() {
+    let server = TestServer::start().await;
+    let client = server.client();
+
+    let result = client
+        .get_object("no-such-bucket", "key")
+        .await
+        .map(|o| o.meta);
+    assert_s3_error(result, "NoSuchBucket", 404);
+    assert_s3_error(
+        client.delete_bucket("no-such-bucket").await,
+        "NoSuchBucket",
+        404,
+    );
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn missing_key() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+
+    let result = server
+        .client()
+        .get_object(&bucket, "missing")
+        .await
+        .map(|o| o.meta);
+    assert_s3_error(result, "NoSuchKey", 404);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn creating_an_existing_bucket_conflicts() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+
+    let result = server.client().create_bucket(&bucket, None).await;
+    assert_s3_error(result, "BucketAlreadyExists", 409);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn invalid_bucket_names_are_rejected() {
+    let server = TestServer::start().await;
+    let too_long = "a".repeat(64);
+
+    for name in [
+        "ab",
+        "UpperCase",
+        "under_score",
+        "-leading-dash",
+        "double..dot",
+        too_long.as_str(),
+    ] {
+        let result = server.client().create_bucket(name, None).await;
+        assert_s3_error(result, "InvalidBucketName", 400);
+    }
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn deleting_a_non_empty_bucket_conflicts() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let bucket = server.create_bucket().await;
+    server.put(&bucket, "key", "data").await;
+
+    assert_s3_error(client.delete_bucket(&bucket).await, "BucketNotEmpty", 409);
+
+    client.delete_object(&bucket, "key").await.unwrap();
+    client.delete_bucket(&bucket).await.unwrap();
+    assert!(!client.bucket_exists(&bucket).await.unwrap());
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn deleting_a_missing_key_succeeds() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+
+    server
+        .client()
+        .delete_object(&bucket, "missing")
+        .await
+        .unwrap();
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn unknown_access_key_is_rejected() {
+    let server = TestServer::start().await;
+    let client = server.client_as(&unique_name("nobody"), SECRET_KEY);
+
+    assert_s3_error(client.list_buckets().await, "InvalidAccessKeyId", 403);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn wrong_secret_key_is_rejected() {
+    let server = TestServer::start().await;
+    let client = server.client_as(ACCESS_KEY, "not-the-secret-key");
+
+    assert_s3_error(client.list_buckets().await, "SignatureDoesNotMatch", 403);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn unsatisfiable_range_is_rejected() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+    server.put(&bucket, "key", "0123456789").await;
+
+    let options = GetObjectOptions {
+        range: Some(4..8),
+        ..Default::default()
+    };
+    let object = server
+        .client()
+        .get_object_with(&bucket, "key", &options)
+        .await
+        .unwrap();
+    assert_eq!(object.meta.content_length, Some(4));
+    assert_eq!(object.body.bytes().await.unwrap(), "4567");
+
+    let options = GetObjectOptions {
+        range: Some(10..20),
+        ..Default::default()
+    };
+    let result = server
+        .client()
+        .get_object_with(&bucket, "key", &options)
+        .await
+        .map(|o| o.meta);
+    assert_s3_error(result, "InvalidRange", 416);
+    server.shutdown().await;
+}
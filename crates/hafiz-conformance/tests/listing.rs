@@ -0,0 +1,206 @@
+//! ListObjectsV2: ordering, prefixes, delimiters and pagination
+
+use hafiz_testing::{assert_s3_error, TestServer};
+
+fn keys(page: &hafiz_client::ListObjectsPage) -> Vec<&str> {
+    page.objects.iter().map(|o| o.key.as_str()).collect()
+}
+
+#[tokio::test]
+async fn empty_bucket_lists_nothing() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+
+    let page = server.client().list_objects(&bucket).send().await.unwrap();
+    assert!(page.objects.is_empty());
+    assert!(page.common_prefixes.is_empty());
+    assert!(!page.is_truncated);
+    assert!(page.next_continuation_token.is_none());
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn keys_are_listed_in_utf8_binary_order() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+    for key in ["b", "a/b", "B", "a", "ä", "a-b", "0"] {
+        server.put(&bucket, key, "x").await;
+    }
+
+    let page = server.client().list_objects(&bucket).send().await.unwrap();
+    assert_eq!(keys(&page), ["0", "B", "a", "a-b", "a/b", "b", "ä"]);
+    assert_eq!(page.objects[0].size, 1);
+    assert!(page.objects[0].etag.is_some());
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn prefix_limits_the_listing() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+    for key in [
+        "photos/2024/a.jpg",
+        "photos/2025/b.jpg",
+        "photosets.txt",
+        "videos/c.mp4",
+    ] {
+        server.put(&bucket, key, "x").await;
+    }
+
+    let page = server
+        .client()
+        .list_objects(&bucket)
+        .prefix("photos/")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(keys(&page), ["photos/2024/a.jpg", "photos/2025/b.jpg"]);
+
+    let page = server
+        .client()
+        .list_objects(&bucket)
+        .prefix("nothing/")
+        .send()
+        .await
+        .unwrap();
+    assert!(page.objects.is_empty());
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn delimiter_rolls_up_common_prefixes() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+    for key in ["a/1", "a/2", "b/c/1", "c", "d"] {
+        server.put(&bucket, key, "x").await;
+    }
+
+    let page = server
+        .client()
+        .list_objects(&bucket)
+        .delimiter("/")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(keys(&page), ["c", "d"]);
+    assert_eq!(page.common_prefixes, ["a/", "b/"]);
+
+    let page = server
+        .client()
+        .list_objects(&bucket)
+        .prefix("b/")
+        .delimiter("/")
+        .send()
+        .await
+        .unwrap();
+    assert!(page.objects.is_empty());
+    assert_eq!(page.common_prefixes, ["b/c/"]);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn max_keys_paginates_with_continuation_tokens() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+    let all: Vec<String> = (0..7).map(|i| format!("key-{:02}", i)).collect();
+    for key in &all {
+        server.put(&bucket, key, "x").await;
+    }
+
+    let mut listed = Vec::new();
+    let mut token = None;
+    let mut pages = 0;
+    loop {
+        let mut request = server.client().list_objects(&bucket).max_keys(3);
+        if let Some(token) = token.take() {
+            request = request.continuation_token(token);
+        }
+        let page = request.send().await.unwrap();
+        pages += 1;
+        assert!(page.objects.len() <= 3);
+        listed.extend(page.objects.iter().map(|o| o.key.clone()));
+        if !page.is_truncated {
+            assert!(page.next_continuation_token.is_none());
+            break;
+        }
+        token = Some(
+            page.next_continuation_token
+                .expect("truncated page without a token"),
+        );
+    }
+
+    assert_eq!(pages, 3);
+    assert_eq!(listed, all);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn max_keys_counts_common_prefixes() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+    for key in ["a/1", "a/2", "b/1", "c", "d/1"] {
+        server.put(&bucket, key, "x").await;
+    }
+
+    let page = server
+        .client()
+        .list_objects(&bucket)
+        .delimiter("/")
+        .max_keys(2)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(page.common_prefixes, ["a/", "b/"]);
+    assert!(page.objects.is_empty());
+    assert!(page.is_truncated);
+
+    let rest = server
+        .client()
+        .list_objects(&bucket)
+        .delimiter("/")
+        .continuation_token(page.next_continuation_token.unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(keys(&rest), ["c"]);
+    assert_eq!(rest.common_prefixes, ["d/"]);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn start_after_skips_earlier_keys() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+    for key in ["a", "b", "c", "d"] {
+        server.put(&bucket, key, "x").await;
+    }
+
+    let page = server
+        .client()
+        .list_objects(&bucket)
+        .start_after("b")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(keys(&page), ["c", "d"]);
+
+    // The marker need not be an existing key
+    let page = server
+        .client()
+        .list_objects(&bucket)
+        .start_after("bb")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(keys(&page), ["c", "d"]);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn listing_a_missing_bucket_fails() {
+    let server = TestServer::start().await;
+
+    let result = server.client().list_objects("no-such-bucket").send().await;
+    assert_s3_error(result, "NoSuchBucket", 404);
+    server.shutdown().await;
+}
@@ -0,0 +1,169 @@
+//! Multipart uploads: assembly, ETags, part size limits and aborts
+
+use hafiz_client::{CompletedPart, PutObjectOptions, MIN_PART_SIZE};
+use hafiz_testing::{assert_s3_error, test_data, TestServer};
+
+const PART_SIZE: usize = MIN_PART_SIZE as usize;
+
+#[tokio::test]
+async fn completed_upload_concatenates_parts() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let bucket = server.create_bucket().await;
+
+    let upload_id = client
+        .create_multipart_upload(&bucket, "big", &PutObjectOptions::default())
+        .await
+        .unwrap();
+    let bodies = [
+        test_data(PART_SIZE, 1),
+        test_data(PART_SIZE, 2),
+        test_data(1000, 3),
+    ];
+    let mut parts = Vec::new();
+    for (i, body) in bodies.iter().enumerate() {
+        let part = client
+            .upload_part(&bucket, "big", &upload_id, i as u32 + 1, body.clone())
+            .await
+            .unwrap();
+        parts.push(part);
+    }
+    let output = client
+        .complete_multipart_upload(&bucket, "big", &upload_id, &parts)
+        .await
+        .unwrap();
+
+    // Multipart ETags are the MD5 of the part MD5s, suffixed with the part count
+    let etag = output.etag.unwrap();
+    assert!(etag.trim_matches('"').ends_with("-3"), "etag {}", etag);
+
+    let object = client.get_object(&bucket, "big").await.unwrap();
+    assert_eq!(
+        object.meta.content_length,
+        Some(2 * PART_SIZE as i64 + 1000)
+    );
+    assert_eq!(object.meta.etag.as_deref(), Some(etag.as_str()));
+    assert_eq!(object.body.bytes().await.unwrap(), bodies.concat());
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn single_part_upload_may_be_small() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let bucket = server.create_bucket().await;
+
+    let upload_id = client
+        .create_multipart_upload(&bucket, "small", &PutObjectOptions::default())
+        .await
+        .unwrap();
+    let part = client
+        .upload_part(&bucket, "small", &upload_id, 1, "tiny")
+        .await
+        .unwrap();
+    client
+        .complete_multipart_upload(&bucket, "small", &upload_id, &[part])
+        .await
+        .unwrap();
+
+    let object = client.get_object(&bucket, "small").await.unwrap();
+    assert_eq!(object.body.bytes().await.unwrap(), "tiny");
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn parts_below_the_minimum_are_rejected() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let bucket = server.create_bucket().await;
+
+    let upload_id = client
+        .create_multipart_upload(&bucket, "key", &PutObjectOptions::default())
+        .await
+        .unwrap();
+    let first = client
+        .upload_part(&bucket, "key", &upload_id, 1, "too small")
+        .await
+        .unwrap();
+    let last = client
+        .upload_part(&bucket, "key", &upload_id, 2, "last")
+        .await
+        .unwrap();
+
+    let result = client
+        .complete_multipart_upload(&bucket, "key", &upload_id, &[first, last])
+        .await;
+    assert_s3_error(result, "EntityTooSmall", 400);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn completing_with_an_unknown_part_fails() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let bucket = server.create_bucket().await;
+
+    let upload_id = client
+        .create_multipart_upload(&bucket, "key", &PutObjectOptions::default())
+        .await
+        .unwrap();
+    let part = client
+        .upload_part(&bucket, "key", &upload_id, 1, "data")
+        .await
+        .unwrap();
+
+    let unknown = CompletedPart {
+        part_number: 2,
+        etag: part.etag.clone(),
+    };
+    let result = client
+        .complete_multipart_upload(&bucket, "key", &upload_id, &[unknown])
+        .await;
+    assert_s3_error(result, "InvalidPart", 400);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn aborted_upload_is_gone() {
+    let server = TestServer::start().await;
+    let client = server.client();
+    let bucket = server.create_bucket().await;
+
+    let upload_id = client
+        .create_multipart_upload(&bucket, "key", &PutObjectOptions::default())
+        .await
+        .unwrap();
+    let part = client
+        .upload_part(&bucket, "key", &upload_id, 1, "data")
+        .await
+        .unwrap();
+    client
+        .abort_multipart_upload(&bucket, "key", &upload_id)
+        .await
+        .unwrap();
+
+    let result = client
+        .upload_part(&bucket, "key", &upload_id, 2, "more")
+        .await;
+    assert_s3_error(result, "NoSuchUpload", 404);
+    let result = client
+        .complete_multipart_upload(&bucket, "key", &upload_id, &[part])
+        .await;
+    assert_s3_error(result, "NoSuchUpload", 404);
+    let result = client.get_object(&bucket, "key").await.map(|o| o.meta);
+    assert_s3_error(result, "NoSuchKey", 404);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn unknown_upload_id_fails() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+
+    let result = server
+        .client()
+        .upload_part(&bucket, "key", "no-such-upload", 1, "data")
+        .await;
+    assert_s3_error(result, "NoSuchUpload", 404);
+    server.shutdown().await;
+}
@@ -0,0 +1,147 @@
+//! Bucket versioning: version IDs, reads of old versions and delete markers
+
+use hafiz_client::GetObjectOptions;
+use hafiz_testing::{assert_s3_error, TestServer};
+
+async fn versioned_bucket(server: &TestServer) -> String {
+    let bucket = server.create_bucket().await;
+    server
+        .client()
+        .put_bucket_versioning(&bucket, "Enabled")
+        .await
+        .unwrap();
+    bucket
+}
+
+async fn read_version(
+    server: &TestServer,
+    bucket: &str,
+    key: &str,
+    version_id: &str,
+) -> hafiz_client::Result<bytes::Bytes> {
+    let options = GetObjectOptions {
+        version_id: Some(version_id.to_string()),
+        ..Default::default()
+    };
+    server
+        .client()
+        .get_object_with(bucket, key, &options)
+        .await?
+        .body
+        .bytes()
+        .await
+}
+
+#[tokio::test]
+async fn new_buckets_are_unversioned() {
+    let server = TestServer::start().await;
+    let bucket = server.create_bucket().await;
+
+    let versioning = server.client().bucket_versioning(&bucket).await.unwrap();
+    assert_eq!(versioning.status, None);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn versioning_status_round_trips() {
+    let server = TestServer::start().await;
+    let bucket = versioned_bucket(&server).await;
+
+    let versioning = server.client().bucket_versioning(&bucket).await.unwrap();
+    assert_eq!(versioning.status.as_deref(), Some("Enabled"));
+
+    server
+        .client()
+        .put_bucket_versioning(&bucket, "Suspended")
+        .await
+        .unwrap();
+    let versioning = server.client().bucket_versioning(&bucket).await.unwrap();
+    assert_eq!(versioning.status.as_deref(), Some("Suspended"));
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn every_write_gets_a_new_version() {
+    let server = TestServer::start().await;
+    let bucket = versioned_bucket(&server).await;
+
+    let v1 = server
+        .put(&bucket, "key", "first")
+        .await
+        .expect("no version ID");
+    let v2 = server
+        .put(&bucket, "key", "second")
+        .await
+        .expect("no version ID");
+    assert_ne!(v1, v2);
+
+    let current = server.client().get_object(&bucket, "key").await.unwrap();
+    assert_eq!(current.meta.version_id.as_deref(), Some(v2.as_str()));
+    assert_eq!(current.body.bytes().await.unwrap(), "second");
+
+    assert_eq!(
+        read_version(&server, &bucket, "key", &v1).await.unwrap(),
+        "first"
+    );
+    assert_eq!(
+        read_version(&server, &bucket, "key", &v2).await.unwrap(),
+        "second"
+    );
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn delete_adds_a_delete_marker() {
+    let server = TestServer::start().await;
+    let bucket = versioned_bucket(&server).await;
+    let v1 = server.put(&bucket, "key", "data").await.unwrap();
+
+    server.client().delete_object(&bucket, "key").await.unwrap();
+
+    let result = server
+        .client()
+        .get_object(&bucket, "key")
+        .await
+        .map(|o| o.meta);
+    assert_s3_error(result, "NoSuchKey", 404);
+    let page = server.client().list_objects(&bucket).send().await.unwrap();
+    assert!(page.objects.is_empty());
+
+    // The data is still there under its version ID
+    assert_eq!(
+        read_version(&server, &bucket, "key", &v1).await.unwrap(),
+        "data"
+    );
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn deleting_the_latest_version_restores_the_previous_one() {
+    let server = TestServer::start().await;
+    let bucket = versioned_bucket(&server).await;
+    server.put(&bucket, "key", "first").await.unwrap();
+    let v2 = server.put(&bucket, "key", "second").await.unwrap();
+
+    server
+        .client()
+        .delete_object_version(&bucket, "key", &v2)
+        .await
+        .unwrap();
+
+    let current = server.client().get_object(&bucket, "key").await.unwrap();
+    assert_eq!(current.body.bytes().await.unwrap(), "first");
+    let result = read_version(&server, &bucket, "key", &v2).await;
+    assert_s3_error(result, "NoSuchVersion", 404);
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn unknown_version_id_fails() {
+    let server = TestServer::start().await;
+    let bucket = versioned_bucket(&server).await;
+    server.put(&bucket, "key", "data").await.unwrap();
+
+    let result = read_version(&server, &bucket, "key", "no-such-version").await;
+    assert_s3_error(result, "NoSuchVersion", 404);
+    server.shutdown().await;
+}
@@ -0,0 +1,637 @@
+//! Layered loading of the server configuration
+//!
+//! [`ConfigLoader`] builds a [`HafizConfig`] from, in increasing order of
+//! precedence, the defaults, a TOML file, `HAFIZ_*` environment variables
+//! and `section.key=value` overrides from the command line. The layers are
+//! merged as TOML before anything is deserialized, so every source is held
+//! to the same rules: a key the configuration doesn't know is an error
+//! naming the closest known key, and a value of the wrong type is an error
+//! naming the key and where it was set.
+//!
+//! Sizes may be written with a unit, e.g. `max_put_size = "5GiB"`; see
+//! [`parse_byte_size`].
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::de::{self, Deserializer, Visitor};
+use toml::{Table, Value};
+
+use super::HafizConfig;
+use crate::{Error, Result};
+
+/// Environment variables predating `HAFIZ__SECTION__KEY`, the key each sets
+/// and the switch it turns on
+const ENV_KEYS: &[(&str, &str, Option<&str>)] = &[
+    ("HAFIZ_BIND_ADDRESS", "server.bind_address", None),
+    ("HAFIZ_PORT", "server.port", None),
+    ("HAFIZ_DATA_DIR", "storage.data_dir", None),
+    ("HAFIZ_DATABASE_URL", "database.url", None),
+    ("HAFIZ_ROOT_ACCESS_KEY", "auth.root_access_key", None),
+    ("HAFIZ_ROOT_SECRET_KEY", "auth.root_secret_key", None),
+    ("HAFIZ_LOG_LEVEL", "logging.level", None),
+    ("HAFIZ_TLS_CERT", "tls.cert_file", Some("tls.enabled")),
+    ("HAFIZ_TLS_KEY", "tls.key_file", None),
+    (
+        "HAFIZ_ENCRYPTION_KEY",
+        "encryption.master_key",
+        Some("encryption.enabled"),
+    ),
+    ("HAFIZ_SSE_S3_ENABLED", "encryption.sse_s3_enabled", None),
+    ("HAFIZ_SSE_C_ENABLED", "encryption.sse_c_enabled", None),
+    (
+        "HAFIZ_GATEWAY_ENDPOINT",
+        "gateway.endpoint",
+        Some("gateway.enabled"),
+    ),
+    ("HAFIZ_GATEWAY_ACCESS_KEY", "gateway.access_key", None),
+    ("HAFIZ_GATEWAY_SECRET_KEY", "gateway.secret_key", None),
+];
+
+/// Prefix of environment variables naming any key, as
+/// `HAFIZ__STORAGE__MAX_PUT_SIZE` for `storage.max_put_size`
+const ENV_PREFIX: &str = "HAFIZ__";
+
+/// Keys whose values are left out of printed configurations
+const SECRET_KEYS: &[&str] = &[
+    "root_secret_key",
+    "master_key",
+    "previous_master_key",
+    "secret_key",
+    "secret_access_key",
+    "bind_password",
+    "shared_secret",
+];
+
+/// Builds a [`HafizConfig`] from a file, the environment and overrides
+#[derive(Debug, Clone, Default)]
+pub struct ConfigLoader {
+    file: Option<PathBuf>,
+    env: bool,
+    overrides: Vec<String>,
+}
+
+impl ConfigLoader {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read this TOML file over the defaults
+    pub fn file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.file = Some(path.into());
+        self
+    }
+
+    /// Apply the `HAFIZ_*` environment variables over the file
+    pub fn env(mut self) -> Self {
+        self.env = true;
+        self
+    }
+
+    /// Apply a `section.key=value` override over the environment
+    pub fn set(mut self, assignment: impl Into<String>) -> Self {
+        self.overrides.push(assignment.into());
+        self
+    }
+
+    pub fn load(&self) -> Result<HafizConfig> {
+        if self.env {
+            self.load_with_env(std::env::vars())
+        } else {
+            self.load_with_env(std::iter::empty())
+        }
+    }
+
+    fn load_with_env(&self, vars: impl Iterator<Item = (String, String)>) -> Result<HafizConfig> {
+        let defaults = to_table(&HafizConfig::default())?;
+        let mut layers = Layers {
+            table: defaults.clone(),
+            origins: HashMap::new(),
+            defaults: &defaults,
+        };
+
+        if let Some(path) = &self.file {
+            let content = std::fs::read_to_string(path).map_err(|e| {
+                Error::InvalidArgument(format!("Failed to read config {}: {}", path.display(), e))
+            })?;
+            let file: Table = toml::from_str(&content).map_err(|e| {
+                Error::InvalidArgument(format!("Failed to parse config {}: {}", path.display(), e))
+            })?;
+            merge(&mut layers.table, file);
+        }
+
+        let mut vars: Vec<(String, String)> = vars.collect();
+        vars.sort();
+        for (name, value) in &vars {
+            if let Some(&(_, key, switch)) = ENV_KEYS.iter().find(|(var, _, _)| var == name) {
+                layers.set(key, value, name)?;
+                if let Some(switch) = switch {
+                    layers.set(switch, "true", name)?;
+                }
+            } else if let Some(rest) = name.strip_prefix(ENV_PREFIX) {
+                let key = rest.to_lowercase().replace("__", ".");
+                layers.set(&key, value, name)?;
+            }
+        }
+
+        for assignment in &self.overrides {
+            let (key, value) = assignment.split_once('=').ok_or_else(|| {
+                Error::InvalidArgument(format!(
+                    "Override {} is not of the form section.key=value",
+                    assignment
+                ))
+            })?;
+            layers.set(key.trim(), value.trim(), &format!("--set {}", assignment))?;
+        }
+
+        layers.deserialize(self.file.as_deref())
+    }
+}
+
+/// The layers merged over the defaults, and where each overridden key was set
+struct Layers<'a> {
+    table: Table,
+    origins: HashMap<String, String>,
+    defaults: &'a Table,
+}
+
+impl Layers<'_> {
+    fn set(&mut self, key: &str, raw: &str, origin: &str) -> Result<()> {
+        let path: Vec<&str> = key.split('.').collect();
+        if path.iter().any(|part| part.is_empty()) {
+            return Err(Error::InvalidArgument(format!(
+                "Invalid key {} (from {})",
+                key, origin
+            )));
+        }
+        let value = typed_value(lookup(self.defaults, &path), raw);
+
+        let (last, parents) = path.split_last().expect("split yields one part");
+        let mut table = &mut self.table;
+        for (depth, part) in parents.iter().enumerate() {
+            table = match table
+                .entry(part.to_string())
+                .or_insert_with(|| Value::Table(Table::new()))
+            {
+                Value::Table(table) => table,
+                _ => {
+                    return Err(Error::InvalidArgument(format!(
+                        "Cannot set {}: {} is not a section (from {})",
+                        key,
+                        path[..=depth].join("."),
+                        origin
+                    )))
+                }
+            };
+        }
+        table.insert(last.to_string(), value);
+        self.origins.insert(key.to_string(), origin.to_string());
+        Ok(())
+    }
+
+    fn deserialize(self, file: Option<&Path>) -> Result<HafizConfig> {
+        let origin = |key: &str| match self.origins.get(key) {
+            Some(origin) => format!("from {}", origin),
+            None => match file {
+                Some(file) => format!("in {}", file.display()),
+                None => "in the defaults".to_string(),
+            },
+        };
+
+        let mut unknown = Vec::new();
+        let mut callback = |path: serde_ignored::Path| unknown.push(key_path(&path));
+        let deserializer =
+            serde_ignored::Deserializer::new(Value::Table(self.table), &mut callback);
+        let config: std::result::Result<HafizConfig, _> =
+            serde_path_to_error::deserialize(deserializer);
+
+        if !unknown.is_empty() {
+            let problems: Vec<String> = unknown
+                .iter()
+                .map(|key| {
+                    let hint = suggestion(self.defaults, key)
+                        .map(|known| format!("; did you mean {}?", known))
+                        .unwrap_or_default();
+                    format!("Unknown key {} ({}){}", key, origin(key), hint)
+                })
+                .collect();
+            return Err(Error::InvalidArgument(format!(
+                "Invalid configuration:\n  {}",
+                problems.join("\n  ")
+            )));
+        }
+
+        config.map_err(|e| {
+            let key = e.path().to_string();
+            Error::InvalidArgument(format!(
+                "Invalid configuration: {} ({}): {}",
+                key,
+                origin(&key),
+                e.inner().message()
+            ))
+        })
+    }
+}
+
+impl HafizConfig {
+    /// The configuration as TOML, with secrets replaced by `<redacted>`
+    pub fn to_redacted_toml(&self) -> Result<String> {
+        let mut table = to_table(self)?;
+        redact(&mut table);
+        toml::to_string_pretty(&table)
+            .map_err(|e| Error::InternalError(format!("Failed to serialize config: {}", e)))
+    }
+}
+
+fn to_table(config: &HafizConfig) -> Result<Table> {
+    Table::try_from(config)
+        .map_err(|e| Error::InternalError(format!("Failed to serialize config: {}", e)))
+}
+
+fn redact(table: &mut Table) {
+    // An Azure backend's access key is its password
+    let azure = table.get("type").and_then(Value::as_str) == Some("azure");
+    for (key, value) in table.iter_mut() {
+        match value {
+            Value::Table(inner) => redact(inner),
+            Value::Array(items) => {
+                for item in items {
+                    if let Value::Table(inner) = item {
+                        redact(inner);
+                    }
+                }
+            }
+            Value::String(s)
+                if !s.is_empty()
+                    && (SECRET_KEYS.contains(&key.as_str()) || (azure && key == "access_key")) =>
+            {
+                *s = "<redacted>".to_string();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Merge a layer into the ones below it, section by section
+fn merge(base: &mut Table, layer: Table) {
+    for (key, value) in layer {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(layer)) => merge(base, layer),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn lookup<'a>(table: &'a Table, path: &[&str]) -> Option<&'a Value> {
+    let (first, rest) = path.split_first()?;
+    let value = table.get(*first)?;
+    if rest.is_empty() {
+        return Some(value);
+    }
+    lookup(value.as_table()?, rest)
+}
+
+/// Type a value from the environment or command line like the default it
+/// replaces; without a default, read it as a TOML value if it is one
+fn typed_value(default: Option<&Value>, raw: &str) -> Value {
+    match default {
+        Some(Value::String(_)) => Value::String(raw.to_string()),
+        _ => {
+            if raw.contains('\n') {
+                return Value::String(raw.to_string());
+            }
+            toml::from_str::<Table>(&format!("value = {}", raw))
+                .ok()
+                .and_then(|mut table| table.remove("value"))
+                .unwrap_or_else(|| Value::String(raw.to_string()))
+        }
+    }
+}
+
+/// `a.b[0].c`, the form serde_path_to_error gives for the same key
+fn key_path(path: &serde_ignored::Path) -> String {
+    use serde_ignored::Path as P;
+    match path {
+        P::Root => String::new(),
+        P::Seq { parent, index } => format!("{}[{}]", key_path(parent), index),
+        P::Map { parent, key } => match key_path(parent) {
+            parent if parent.is_empty() => key.clone(),
+            parent => format!("{}.{}", parent, key),
+        },
+        P::Some { parent } | P::NewtypeStruct { parent } | P::NewtypeVariant { parent } => {
+            key_path(parent)
+        }
+    }
+}
+
+/// The known key closest to an unknown one, if it is close enough to be a typo
+fn suggestion(defaults: &Table, key: &str) -> Option<String> {
+    let (parent, name) = key.rsplit_once('.').unwrap_or(("", key));
+    let siblings = if parent.is_empty() {
+        defaults
+    } else {
+        let path: Vec<&str> = parent.split('.').collect();
+        lookup(defaults, &path)?.as_table()?
+    };
+    siblings
+        .keys()
+        .map(|known| (edit_distance(name, known), known))
+        .filter(|(distance, known)| *distance <= 2.max(known.len() / 4))
+        .min()
+        .map(|(_, known)| match parent {
+            "" => known.clone(),
+            parent => format!("{}.{}", parent, known),
+        })
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = (diagonal + usize::from(ca != *cb))
+                .min(above + 1)
+                .min(row[j] + 1);
+            diagonal = above;
+        }
+    }
+    row[b.len()]
+}
+
+/// The message of a validation error, without the error kind
+pub(super) fn message(e: Error) -> String {
+    match e {
+        Error::InvalidArgument(message) => message,
+        e => e.to_string(),
+    }
+}
+
+/// Parse a size such as `1048576`, `64KiB`, `10 GiB` or `1.5GB`
+///
+/// Units are case-insensitive; `KiB`, `MiB`, `GiB` and `TiB` are powers of
+/// 1024, `KB`, `MB`, `GB` and `TB` powers of 1000.
+pub fn parse_byte_size(s: &str) -> std::result::Result<u64, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000 * 1000,
+        "g" | "gb" => 1000 * 1000 * 1000,
+        "t" | "tb" => 1000 * 1000 * 1000 * 1000,
+        "ki" | "kib" => 1 << 10,
+        "mi" | "mib" => 1 << 20,
+        "gi" | "gib" => 1 << 30,
+        "ti" | "tib" => 1 << 40,
+        _ => return Err(invalid_size(s)),
+    };
+    let bytes = if let Ok(n) = number.parse::<u64>() {
+        n.checked_mul(multiplier)
+    } else {
+        number
+            .parse::<f64>()
+            .ok()
+            .map(|n| n * multiplier as f64)
+            .filter(|bytes| bytes.is_finite() && *bytes < u64::MAX as f64)
+            .map(|bytes| bytes.round() as u64)
+    };
+    bytes.ok_or_else(|| invalid_size(s))
+}
+
+fn invalid_size(s: &str) -> String {
+    format!(
+        "invalid size {:?}, expected bytes or a number with a unit such as 64KiB, 10GiB or 5GB",
+        s
+    )
+}
+
+/// Deserialize a size given as bytes or a string with a unit
+pub(super) fn byte_size<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: TryFrom<u64>,
+{
+    struct SizeVisitor;
+
+    impl Visitor<'_> for SizeVisitor {
+        type Value = u64;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a size in bytes, or a string such as \"10GiB\"")
+        }
+
+        fn visit_u64<E: de::Error>(self, v: u64) -> std::result::Result<u64, E> {
+            Ok(v)
+        }
+
+        fn visit_i64<E: de::Error>(self, v: i64) -> std::result::Result<u64, E> {
+            u64::try_from(v).map_err(|_| E::custom(format!("size {} is negative", v)))
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<u64, E> {
+            parse_byte_size(v).map_err(E::custom)
+        }
+    }
+
+    let bytes = deserializer.deserialize_any(SizeVisitor)?;
+    T::try_from(bytes).map_err(|_| de::Error::custom(format!("size {} is too large", bytes)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AdminRole;
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Iterator<Item = (String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    struct ConfigFile(PathBuf);
+
+    impl ConfigFile {
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for ConfigFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn write_config(content: &str) -> ConfigFile {
+        let path = std::env::temp_dir().join(format!("hafiz-{}.toml", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        ConfigFile(path)
+    }
+
+    #[test]
+    fn test_parse_byte_size() {
+        assert_eq!(parse_byte_size("1048576"), Ok(1048576));
+        assert_eq!(parse_byte_size("64KiB"), Ok(64 * 1024));
+        assert_eq!(parse_byte_size("10 GiB"), Ok(10 << 30));
+        assert_eq!(parse_byte_size("5gb"), Ok(5_000_000_000));
+        assert_eq!(parse_byte_size("1.5MiB"), Ok(1536 * 1024));
+        assert!(parse_byte_size("10 GXB").is_err());
+        assert!(parse_byte_size("GiB").is_err());
+        assert!(parse_byte_size("99999999999TiB").is_err());
+    }
+
+    #[test]
+    fn test_layers_in_order() {
+        let file = write_config(
+            r#"
+            [server]
+            port = 9001
+            max_body_size = "1GiB"
+
+            [storage]
+            max_put_size = "64MiB"
+            "#,
+        );
+        let config = ConfigLoader::new()
+            .file(file.path())
+            .set("storage.max_put_size=128MiB")
+            .load_with_env(vars(&[
+                ("HAFIZ_PORT", "9002"),
+                ("HAFIZ__STORAGE__MAX_PUT_SIZE", "32MiB"),
+                ("HAFIZ__AUTH__ROOT_ACCESS_KEY", "12345"),
+                ("HAFIZ_ENDPOINT", "http://ignored"),
+            ]))
+            .unwrap();
+        assert_eq!(config.server.port, 9002);
+        assert_eq!(config.server.max_body_size, 1 << 30);
+        assert_eq!(config.storage.max_put_size, 128 << 20);
+        // Typed like the default, so digits stay a string
+        assert_eq!(config.auth.root_access_key, "12345");
+        assert_eq!(
+            config.storage.min_part_size,
+            HafizConfig::default().storage.min_part_size
+        );
+    }
+
+    #[test]
+    fn test_env_switches() {
+        let config = ConfigLoader::new()
+            .load_with_env(vars(&[("HAFIZ_GATEWAY_ENDPOINT", "http://upstream:9000")]))
+            .unwrap();
+        assert!(config.gateway.enabled);
+        assert_eq!(config.gateway.endpoint, "http://upstream:9000");
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let file = write_config("[storage]\nmax_put_sise = 100\n\n[stroage]\nx = 1\n");
+        let err = message(ConfigLoader::new().file(file.path()).load().unwrap_err());
+        assert!(err.contains("Unknown key storage.max_put_sise"), "{}", err);
+        assert!(
+            err.contains("did you mean storage.max_put_size?"),
+            "{}",
+            err
+        );
+        assert!(err.contains("Unknown key stroage ("), "{}", err);
+        assert!(err.contains("did you mean storage?"), "{}", err);
+
+        let err = message(
+            ConfigLoader::new()
+                .set("server.prot=9000")
+                .load()
+                .unwrap_err(),
+        );
+        assert!(err.contains("(from --set server.prot=9000)"), "{}", err);
+        assert!(err.contains("did you mean server.port?"), "{}", err);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        let err = message(
+            ConfigLoader::new()
+                .load_with_env(vars(&[("HAFIZ_PORT", "ninety")]))
+                .unwrap_err(),
+        );
+        assert!(err.contains("server.port (from HAFIZ_PORT)"), "{}", err);
+
+        let err = message(
+            ConfigLoader::new()
+                .set("storage.max_put_size=5 GXB")
+                .load()
+                .unwrap_err(),
+        );
+        assert!(err.contains("storage.max_put_size"), "{}", err);
+        assert!(err.contains("invalid size"), "{}", err);
+
+        assert!(ConfigLoader::new().set("storage").load().is_err());
+        assert!(ConfigLoader::new().set("server.port.x=1").load().is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = HafizConfig::default();
+        assert!(config.validate().is_ok());
+        config.logging.format = "yaml".to_string();
+        config.storage.read_chunk_size = 0;
+        let err = message(config.validate().unwrap_err());
+        assert!(err.contains("[storage] storage.read_chunk_size"), "{}", err);
+        assert!(err.contains("[logging] logging.format"), "{}", err);
+    }
+
+    #[test]
+    fn test_admin_listener() {
+        let token = "0123456789abcdef".repeat(4);
+        let config = ConfigLoader::new()
+            .set("admin.separate_listener=true")
+            .set("admin.allowed_cidrs=[\"10.0.0.0/8\", \"::1\"]")
+            .set(format!(
+                "admin.tokens=[{{ name = \"ci\", sha256 = \"{}\" }}, \
+                 {{ name = \"grafana\", sha256 = \"{}\", role = \"viewer\" }}]",
+                token,
+                "f".repeat(64)
+            ))
+            .load()
+            .unwrap();
+        assert!(config.validate().is_ok());
+        assert!(config.admin.allows_ip("10.1.2.3".parse().unwrap()));
+        assert!(config.admin.allows_ip("::1".parse().unwrap()));
+        assert!(!config.admin.allows_ip("192.168.0.1".parse().unwrap()));
+        let ci = config.admin.token(&token.to_uppercase()).unwrap();
+        assert_eq!((ci.name.as_str(), ci.role), ("ci", AdminRole::SuperAdmin));
+        assert_eq!(config.admin.token(&"f".repeat(64)).unwrap().role, AdminRole::Viewer);
+
+        let mut invalid = config.clone();
+        invalid.server.admin_port = invalid.server.port;
+        assert!(invalid.validate().is_err());
+        let mut invalid = config.clone();
+        invalid.admin.allowed_cidrs.push("10.0.0.0/33".to_string());
+        assert!(invalid.validate().is_err());
+        let mut invalid = config;
+        invalid.admin.tokens[0].sha256 = "secret".to_string();
+        assert!(invalid.validate().is_err());
+    }
+
+    #[test]
+    fn test_redacted_toml() {
+        let mut config = HafizConfig::default();
+        config.gateway.secret_key = "upstream-secret".to_string();
+        let printed = config.to_redacted_toml().unwrap();
+        assert!(!printed.contains("upstream-secret"));
+        assert!(printed.contains("root_secret_key = \"<redacted>\""));
+
+        // What is printed loads back
+        let file = write_config(&printed);
+        assert!(ConfigLoader::new().file(file.path()).load().is_ok());
+    }
+}
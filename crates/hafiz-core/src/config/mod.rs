@@ -0,0 +1,2739 @@
+//! Configuration for Hafiz
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+mod load;
+
+pub use load::{parse_byte_size, ConfigLoader};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HafizConfig {
+    #[serde(default)]
+    pub server: ServerConfig,
+
+    #[serde(default)]
+    pub tls: TlsConfig,
+
+    #[serde(default)]
+    pub storage: StorageConfig,
+
+    #[serde(default)]
+    pub database: DatabaseConfig,
+
+    #[serde(default)]
+    pub auth: AuthConfig,
+
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+
+    #[serde(default)]
+    pub logging: LoggingConfig,
+
+    #[serde(default)]
+    pub lifecycle: LifecycleWorkerConfig,
+
+    #[serde(default)]
+    pub scrub: ScrubConfig,
+
+    #[serde(default)]
+    pub mirror: MirrorConfig,
+
+    #[serde(default)]
+    pub cluster: ClusterConfigSection,
+
+    #[serde(default)]
+    pub ldap: LdapConfigSection,
+
+    #[serde(default)]
+    pub oidc: OidcConfigSection,
+
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    #[serde(default)]
+    pub gateway: GatewayConfig,
+
+    #[serde(default)]
+    pub transform: TransformConfig,
+
+    #[serde(default)]
+    pub webdav: WebDavConfig,
+
+    #[serde(default)]
+    pub sftp: SftpConfig,
+
+    #[serde(default)]
+    pub search: SearchConfig,
+
+    #[serde(default)]
+    pub admin: AdminConfig,
+}
+
+impl Default for HafizConfig {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::default(),
+            tls: TlsConfig::default(),
+            storage: StorageConfig::default(),
+            database: DatabaseConfig::default(),
+            auth: AuthConfig::default(),
+            encryption: EncryptionConfig::default(),
+            logging: LoggingConfig::default(),
+            lifecycle: LifecycleWorkerConfig::default(),
+            scrub: ScrubConfig::default(),
+            mirror: MirrorConfig::default(),
+            cluster: ClusterConfigSection::default(),
+            ldap: LdapConfigSection::default(),
+            oidc: OidcConfigSection::default(),
+            telemetry: TelemetryConfig::default(),
+            metrics: MetricsConfig::default(),
+            gateway: GatewayConfig::default(),
+            transform: TransformConfig::default(),
+            webdav: WebDavConfig::default(),
+            sftp: SftpConfig::default(),
+            search: SearchConfig::default(),
+            admin: AdminConfig::default(),
+        }
+    }
+}
+
+impl HafizConfig {
+    /// Read a TOML file on top of the defaults, rejecting unknown keys
+    pub fn from_file(path: &str) -> crate::Result<Self> {
+        ConfigLoader::new().file(path).load()
+    }
+
+    /// The defaults with the `HAFIZ_*` environment variables applied
+    pub fn from_env() -> crate::Result<Self> {
+        ConfigLoader::new().env().load()
+    }
+
+    /// Check every section, reporting all problems at once
+    pub fn validate(&self) -> crate::Result<()> {
+        let checks = [
+            ("server", self.server.admission.validate()),
+            ("tls", self.tls.validate()),
+            ("storage", self.storage.validate()),
+            ("encryption", self.encryption.validate()),
+            ("mirror", self.mirror.validate()),
+            ("gateway", self.gateway.validate()),
+            ("logging", self.logging.validate()),
+            ("admin", self.admin.validate(&self.server)),
+            ("cluster", self.cluster.validate()),
+        ];
+        let problems: Vec<String> = checks
+            .into_iter()
+            .filter_map(|(section, result)| {
+                result.err().map(|e| format!("[{}] {}", section, load::message(e)))
+            })
+            .collect();
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(crate::Error::InvalidArgument(format!(
+                "Invalid configuration:\n  {}",
+                problems.join("\n  ")
+            )))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerConfig {
+    pub bind_address: String,
+    pub port: u16,
+    pub admin_port: u16,
+    pub workers: usize,
+    pub max_connections: usize,
+    pub request_timeout_secs: u64,
+    /// How long in-flight requests may run after a shutdown signal
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+    /// Serve HTTP/2: negotiated through ALPN over TLS, by prior knowledge otherwise
+    #[serde(default = "default_true")]
+    pub http2_enabled: bool,
+    /// Concurrent streams a client may open on one HTTP/2 connection
+    #[serde(default = "default_http2_max_concurrent_streams")]
+    pub http2_max_concurrent_streams: u32,
+    /// Interval of HTTP/2 keep-alive pings (seconds); 0 disables them
+    #[serde(default = "default_http2_keep_alive_interval")]
+    pub http2_keep_alive_interval_secs: u64,
+    /// Keep HTTP/1.1 connections open between requests
+    #[serde(default = "default_true")]
+    pub keep_alive: bool,
+    /// How long an idle HTTP/1.1 connection waits for the next request, and
+    /// how long an HTTP/2 keep-alive ping may go unanswered (seconds)
+    #[serde(default = "default_keep_alive_timeout")]
+    pub keep_alive_timeout_secs: u64,
+    /// Largest request body accepted by the S3 API (bytes); 0 means no limit
+    #[serde(default = "default_max_body_size", deserialize_with = "load::byte_size")]
+    pub max_body_size: u64,
+    /// Domain under which access points are addressed by hostname, as
+    /// `<name>.<domain>`; unset, only their aliases reach them
+    #[serde(default)]
+    pub access_point_domain: Option<String>,
+    /// Rules names of new buckets must follow
+    #[serde(default)]
+    pub bucket_name_rules: crate::types::BucketNameRules,
+    /// Require names of new buckets of accounts other than the default to
+    /// start with `<account>-`, giving each tenant a namespace of its own
+    #[serde(default)]
+    pub bucket_name_account_prefix: bool,
+    /// Shedding of low-priority S3 requests under overload
+    #[serde(default)]
+    pub admission: AdmissionConfig,
+}
+
+fn default_shutdown_timeout_secs() -> u64 {
+    30
+}
+
+fn default_http2_max_concurrent_streams() -> u32 {
+    256
+}
+
+fn default_http2_keep_alive_interval() -> u64 {
+    30
+}
+
+fn default_keep_alive_timeout() -> u64 {
+    75
+}
+
+fn default_max_body_size() -> u64 {
+    5 * 1024 * 1024 * 1024 // 5 GiB, the S3 single-PUT limit
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: "0.0.0.0".to_string(),
+            port: 9000,
+            admin_port: 9001,
+            workers: num_cpus::get(),
+            max_connections: 10000,
+            request_timeout_secs: 300,
+            shutdown_timeout_secs: default_shutdown_timeout_secs(),
+            http2_enabled: true,
+            http2_max_concurrent_streams: default_http2_max_concurrent_streams(),
+            http2_keep_alive_interval_secs: default_http2_keep_alive_interval(),
+            keep_alive: true,
+            keep_alive_timeout_secs: default_keep_alive_timeout(),
+            max_body_size: default_max_body_size(),
+            access_point_domain: None,
+            bucket_name_rules: crate::types::BucketNameRules::default(),
+            bucket_name_account_prefix: false,
+            admission: AdmissionConfig::default(),
+        }
+    }
+}
+
+/// Admission control of S3 requests
+///
+/// The server counts as overloaded while `max_in_flight` requests are in
+/// progress, or while recent storage calls took longer than
+/// `storage_latency_ms` on average. Listings then wait up to
+/// `queue_timeout_ms` for the overload to pass and are refused with
+/// `SlowDown` after that; small object reads and writes are always
+/// admitted, and other requests are refused beyond `max_in_flight`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdmissionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Requests in progress at which the server counts as overloaded
+    #[serde(default = "default_admission_max_in_flight")]
+    pub max_in_flight: usize,
+    /// Average storage call latency at which the server counts as
+    /// overloaded (milliseconds); 0 only looks at `max_in_flight`
+    #[serde(default = "default_admission_storage_latency_ms")]
+    pub storage_latency_ms: u64,
+    /// Low-priority requests waiting for the overload to pass; more are
+    /// refused at once
+    #[serde(default = "default_admission_max_queued")]
+    pub max_queued: usize,
+    /// How long a low-priority request waits before it is refused
+    /// (milliseconds)
+    #[serde(default = "default_admission_queue_timeout_ms")]
+    pub queue_timeout_ms: u64,
+    /// Largest PUT body still treated as small, high-priority traffic
+    #[serde(default = "default_admission_small_object_size", deserialize_with = "load::byte_size")]
+    pub small_object_size: u64,
+}
+
+fn default_admission_max_in_flight() -> usize {
+    1024
+}
+
+fn default_admission_storage_latency_ms() -> u64 {
+    500
+}
+
+fn default_admission_max_queued() -> usize {
+    256
+}
+
+fn default_admission_queue_timeout_ms() -> u64 {
+    2000
+}
+
+fn default_admission_small_object_size() -> u64 {
+    1024 * 1024 // 1 MiB
+}
+
+impl Default for AdmissionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_in_flight: default_admission_max_in_flight(),
+            storage_latency_ms: default_admission_storage_latency_ms(),
+            max_queued: default_admission_max_queued(),
+            queue_timeout_ms: default_admission_queue_timeout_ms(),
+            small_object_size: default_admission_small_object_size(),
+        }
+    }
+}
+
+impl AdmissionConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.enabled && self.max_in_flight == 0 {
+            return Err(crate::Error::InvalidArgument(
+                "server.admission.max_in_flight must be greater than 0".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// TLS/HTTPS Configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    /// Enable TLS
+    pub enabled: bool,
+    /// Path to certificate file (PEM format)
+    pub cert_file: Option<PathBuf>,
+    /// Path to private key file (PEM format)
+    pub key_file: Option<PathBuf>,
+    /// Path to CA certificate for client verification (mTLS)
+    pub client_ca_file: Option<PathBuf>,
+    /// Require client certificate (mTLS)
+    pub require_client_cert: bool,
+    /// Minimum TLS version (1.2 or 1.3)
+    pub min_version: TlsVersion,
+    /// Enable HSTS header
+    pub hsts_enabled: bool,
+    /// HSTS max age in seconds
+    pub hsts_max_age: u64,
+    /// How often cert_file and key_file are checked for changes (seconds); 0 disables reload
+    #[serde(default = "default_tls_reload_interval")]
+    pub reload_interval_secs: u64,
+    /// Certificates provisioned and renewed through ACME
+    #[serde(default)]
+    pub acme: AcmeConfig,
+    /// Users that clients presenting a matching certificate authenticate
+    /// as, without signing their requests; needs `client_ca_file`
+    #[serde(default)]
+    pub client_cert_users: Vec<ClientCertMapping>,
+}
+
+/// Maps client certificates to a user: by subject common name or by a DNS,
+/// URI or email subject alternative name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClientCertMapping {
+    #[serde(default)]
+    pub common_name: Option<String>,
+    #[serde(default)]
+    pub san: Option<String>,
+    /// Access key of the user the certificate authenticates as
+    pub access_key: String,
+}
+
+impl ClientCertMapping {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.common_name.is_some() == self.san.is_some() {
+            return Err(crate::Error::InvalidArgument(format!(
+                "Client certificate mapping for {} needs one of common_name or san",
+                self.access_key
+            )));
+        }
+        if self.access_key.is_empty() {
+            return Err(crate::Error::InvalidArgument(
+                "Client certificate mapping without access_key".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn default_tls_reload_interval() -> u64 {
+    30
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert_file: None,
+            key_file: None,
+            client_ca_file: None,
+            require_client_cert: false,
+            min_version: TlsVersion::Tls12,
+            hsts_enabled: true,
+            hsts_max_age: 31536000, // 1 year
+            reload_interval_secs: default_tls_reload_interval(),
+            acme: AcmeConfig::default(),
+            client_cert_users: Vec::new(),
+        }
+    }
+}
+
+impl TlsConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.enabled {
+            if self.acme.enabled {
+                self.acme.validate()?;
+            } else {
+                if self.cert_file.is_none() {
+                    return Err(crate::Error::InvalidArgument(
+                        "TLS enabled but cert_file not specified".into(),
+                    ));
+                }
+                if self.key_file.is_none() {
+                    return Err(crate::Error::InvalidArgument(
+                        "TLS enabled but key_file not specified".into(),
+                    ));
+                }
+
+                // Check files exist
+                if let Some(ref cert) = self.cert_file {
+                    if !cert.exists() {
+                        return Err(crate::Error::InvalidArgument(format!(
+                            "Certificate file not found: {:?}",
+                            cert
+                        )));
+                    }
+                }
+                if let Some(ref key) = self.key_file {
+                    if !key.exists() {
+                        return Err(crate::Error::InvalidArgument(format!(
+                            "Key file not found: {:?}",
+                            key
+                        )));
+                    }
+                }
+            }
+            if self.require_client_cert || !self.client_cert_users.is_empty() {
+                if let Some(ref ca) = self.client_ca_file {
+                    if !ca.exists() {
+                        return Err(crate::Error::InvalidArgument(format!(
+                            "Client CA file not found: {:?}",
+                            ca
+                        )));
+                    }
+                } else {
+                    return Err(crate::Error::InvalidArgument(
+                        "Client certificates need client_ca_file".into(),
+                    ));
+                }
+            }
+            for mapping in &self.client_cert_users {
+                mapping.validate()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// ACME (e.g. Let's Encrypt) certificate provisioning
+///
+/// The certificate is ordered for `domains` and renewed before it expires.
+/// The CA checks control of each domain with a `tls-alpn-01` challenge on
+/// port 443, so the HTTPS listener must be reachable there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcmeConfig {
+    /// Provision certificates through ACME instead of cert_file/key_file
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Domains the certificate covers; the first is the subject
+    #[serde(default)]
+    pub domains: Vec<String>,
+
+    /// Contact address registered with the CA for expiry notices
+    #[serde(default)]
+    pub contact_email: Option<String>,
+
+    /// ACME directory of the CA
+    #[serde(default = "default_acme_directory")]
+    pub directory_url: String,
+
+    /// Where the account key, certificate and private key are kept
+    #[serde(default = "default_acme_cache_dir")]
+    pub cache_dir: PathBuf,
+
+    /// Renew once the certificate expires within this many days
+    #[serde(default = "default_acme_renew_before_days")]
+    pub renew_before_days: u32,
+
+    /// Agree to the CA's terms of service; required by every public CA
+    #[serde(default)]
+    pub accept_terms: bool,
+}
+
+fn default_acme_directory() -> String {
+    "https://acme-v02.api.letsencrypt.org/directory".to_string()
+}
+
+fn default_acme_cache_dir() -> PathBuf {
+    PathBuf::from("/data/hafiz/acme")
+}
+
+fn default_acme_renew_before_days() -> u32 {
+    30
+}
+
+impl Default for AcmeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            domains: Vec::new(),
+            contact_email: None,
+            directory_url: default_acme_directory(),
+            cache_dir: default_acme_cache_dir(),
+            renew_before_days: default_acme_renew_before_days(),
+            accept_terms: false,
+        }
+    }
+}
+
+impl AcmeConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.domains.is_empty() {
+            return Err(crate::Error::InvalidArgument(
+                "ACME enabled but no domains specified".into(),
+            ));
+        }
+        if !self.accept_terms {
+            return Err(crate::Error::InvalidArgument(
+                "ACME enabled but the CA's terms of service are not accepted (accept_terms)".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Certificate chain obtained from the CA
+    pub fn cert_file(&self) -> PathBuf {
+        self.cache_dir.join("cert.pem")
+    }
+
+    /// Private key of the certificate
+    pub fn key_file(&self) -> PathBuf {
+        self.cache_dir.join("key.pem")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TlsVersion {
+    #[serde(rename = "1.2")]
+    Tls12,
+    #[serde(rename = "1.3")]
+    Tls13,
+}
+
+impl Default for TlsVersion {
+    fn default() -> Self {
+        Self::Tls12
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StorageConfig {
+    pub data_dir: PathBuf,
+    pub temp_dir: PathBuf,
+    /// Largest object a multipart upload may assemble (bytes)
+    #[serde(deserialize_with = "load::byte_size")]
+    pub max_object_size: u64,
+    /// Largest single PutObject or UploadPart body (bytes)
+    #[serde(default = "default_max_put_size", deserialize_with = "load::byte_size")]
+    pub max_put_size: u64,
+    /// Smallest size of every multipart part but the last (bytes)
+    #[serde(default = "default_min_part_size", deserialize_with = "load::byte_size")]
+    pub min_part_size: u64,
+    /// Named backends that buckets can be mapped to
+    #[serde(default)]
+    pub backends: HashMap<String, BackendConfig>,
+    /// Bucket name to backend name; other buckets use the default storage
+    #[serde(default)]
+    pub buckets: HashMap<String, String>,
+    /// Backend holding the data of objects in archive storage classes
+    /// (`GLACIER`, `DEEP_ARCHIVE`); unset refuses those classes
+    #[serde(default)]
+    pub archive_backend: Option<String>,
+    /// Moving cold objects of opted-in buckets to the archive backend
+    #[serde(default)]
+    pub tiering: TieringConfig,
+    /// Deduplicate object data in the default storage
+    #[serde(default)]
+    pub dedup: DedupConfig,
+    /// Free space kept on the data volume
+    #[serde(default)]
+    pub reserve: SpaceReserveConfig,
+    /// Size of the chunks GETs stream local object data in
+    #[serde(default = "default_read_chunk_size", deserialize_with = "load::byte_size")]
+    pub read_chunk_size: usize,
+    /// When local object data is flushed to disk
+    #[serde(default)]
+    pub fsync: FsyncPolicy,
+    /// How often `fsync = "batched"` flushes written data, in milliseconds
+    #[serde(default = "default_fsync_interval_ms")]
+    pub fsync_interval_ms: u64,
+    /// Parts a multipart completion copies into the local object at once
+    #[serde(default = "default_assemble_concurrency")]
+    pub assemble_concurrency: usize,
+    /// Cache of recently read object data
+    #[serde(default)]
+    pub read_cache: ReadCacheConfig,
+}
+
+/// Durability of local writes: what a crash or power loss may lose
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FsyncPolicy {
+    /// Flush each object before the write is acknowledged; nothing
+    /// acknowledged is lost
+    #[default]
+    PerObject,
+    /// Flush the objects written in each `fsync_interval_ms` together;
+    /// up to that much acknowledged data may be lost
+    Batched,
+    /// Leave flushing to the operating system
+    Never,
+}
+
+fn default_max_put_size() -> u64 {
+    5 * 1024 * 1024 * 1024 // 5 GiB, the S3 single-PUT limit
+}
+
+fn default_min_part_size() -> u64 {
+    crate::MIN_PART_SIZE
+}
+
+fn default_read_chunk_size() -> usize {
+    256 * 1024 // 256 KiB
+}
+
+fn default_fsync_interval_ms() -> u64 {
+    100
+}
+
+fn default_assemble_concurrency() -> usize {
+    8
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("/data/hafiz"),
+            temp_dir: PathBuf::from("/tmp/hafiz"),
+            max_object_size: crate::MAX_OBJECT_SIZE,
+            max_put_size: default_max_put_size(),
+            min_part_size: default_min_part_size(),
+            backends: HashMap::new(),
+            buckets: HashMap::new(),
+            archive_backend: None,
+            tiering: TieringConfig::default(),
+            dedup: DedupConfig::default(),
+            reserve: SpaceReserveConfig::default(),
+            read_chunk_size: default_read_chunk_size(),
+            fsync: FsyncPolicy::default(),
+            fsync_interval_ms: default_fsync_interval_ms(),
+            assemble_concurrency: default_assemble_concurrency(),
+            read_cache: ReadCacheConfig::default(),
+        }
+    }
+}
+
+/// Name under which the default storage is listed; not usable for a named backend
+pub const DEFAULT_STORAGE_BACKEND: &str = "default";
+
+impl StorageConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.backends.contains_key(DEFAULT_STORAGE_BACKEND) {
+            return Err(crate::Error::InvalidArgument(format!(
+                "Storage backend name {} is reserved",
+                DEFAULT_STORAGE_BACKEND
+            )));
+        }
+        self.dedup.validate()?;
+        if self.read_chunk_size == 0 {
+            return Err(crate::Error::InvalidArgument(
+                "storage.read_chunk_size must be greater than 0".into(),
+            ));
+        }
+        if self.fsync == FsyncPolicy::Batched && self.fsync_interval_ms == 0 {
+            return Err(crate::Error::InvalidArgument(
+                "storage.fsync_interval_ms must be greater than 0".into(),
+            ));
+        }
+        if self.assemble_concurrency == 0 {
+            return Err(crate::Error::InvalidArgument(
+                "storage.assemble_concurrency must be greater than 0".into(),
+            ));
+        }
+        self.read_cache.validate()?;
+        for (name, backend) in &self.backends {
+            backend.validate().map_err(|e| {
+                crate::Error::InvalidArgument(format!("Storage backend {}: {}", name, e))
+            })?;
+        }
+        for (bucket, backend) in &self.buckets {
+            if !self.backends.contains_key(backend) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "Bucket {} is mapped to unknown storage backend {}",
+                    bucket, backend
+                )));
+            }
+        }
+        if let Some(backend) = &self.archive_backend {
+            if !self.backends.contains_key(backend) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "Archive backend {} is not a configured storage backend",
+                    backend
+                )));
+            }
+        }
+        self.tiering.validate()?;
+        Ok(())
+    }
+}
+
+/// Access-based tiering. Reads are sampled into per-object access
+/// statistics; objects of buckets that opted in are moved to the archive
+/// backend once they have not been read for the bucket's number of days,
+/// and moved back when read again. Tiered objects stay readable throughout.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TieringConfig {
+    /// Fraction of reads recorded, from 0 (exclusive) to 1
+    #[serde(default = "default_tiering_sample_rate")]
+    pub sample_rate: f64,
+    /// How often recorded reads are written to the metadata database, in seconds
+    #[serde(default = "default_tiering_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+    /// Interval between passes archiving cold objects in seconds; 0 leaves
+    /// passes to the admin API
+    #[serde(default = "default_tiering_scan_interval_secs")]
+    pub scan_interval_secs: u64,
+}
+
+fn default_tiering_sample_rate() -> f64 {
+    0.1
+}
+
+fn default_tiering_flush_interval_secs() -> u64 {
+    30
+}
+
+fn default_tiering_scan_interval_secs() -> u64 {
+    6 * 3600
+}
+
+impl Default for TieringConfig {
+    fn default() -> Self {
+        Self {
+            sample_rate: default_tiering_sample_rate(),
+            flush_interval_secs: default_tiering_flush_interval_secs(),
+            scan_interval_secs: default_tiering_scan_interval_secs(),
+        }
+    }
+}
+
+impl TieringConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if !(self.sample_rate > 0.0 && self.sample_rate <= 1.0) {
+            return Err(crate::Error::InvalidArgument(format!(
+                "storage.tiering.sample_rate must be in (0, 1], got {}",
+                self.sample_rate
+            )));
+        }
+        if self.flush_interval_secs == 0 {
+            return Err(crate::Error::InvalidArgument(
+                "storage.tiering.flush_interval_secs must be greater than 0".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Writes are refused once free space on the data volume would drop below
+/// the larger of the two minimums
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpaceReserveConfig {
+    #[serde(default = "default_min_free_bytes", deserialize_with = "load::byte_size")]
+    pub min_free_bytes: u64,
+    /// Percentage of the volume size; 0 disables
+    #[serde(default)]
+    pub min_free_percent: f64,
+    /// How often free space is measured
+    #[serde(default = "default_space_check_interval_secs")]
+    pub check_interval_secs: u64,
+}
+
+fn default_min_free_bytes() -> u64 {
+    1024 * 1024 * 1024 // 1 GiB
+}
+
+fn default_space_check_interval_secs() -> u64 {
+    10
+}
+
+impl Default for SpaceReserveConfig {
+    fn default() -> Self {
+        Self {
+            min_free_bytes: default_min_free_bytes(),
+            min_free_percent: 0.0,
+            check_interval_secs: default_space_check_interval_secs(),
+        }
+    }
+}
+
+/// Read-through cache of object data, kept in memory and optionally in a
+/// directory on a fast local disk for larger working sets
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Memory the cached data may take
+    #[serde(default = "default_read_cache_memory_bytes", deserialize_with = "load::byte_size")]
+    pub memory_bytes: u64,
+    /// Largest object or range that is cached; larger reads are streamed
+    #[serde(default = "default_read_cache_max_object_size", deserialize_with = "load::byte_size")]
+    pub max_object_size: u64,
+    /// Directory of the disk tier, e.g. on an NVMe drive; unset keeps the
+    /// cache in memory only
+    #[serde(default)]
+    pub disk_dir: Option<PathBuf>,
+    /// Space the disk tier may take
+    #[serde(default = "default_read_cache_disk_bytes", deserialize_with = "load::byte_size")]
+    pub disk_bytes: u64,
+}
+
+fn default_read_cache_memory_bytes() -> u64 {
+    256 * 1024 * 1024 // 256 MiB
+}
+
+fn default_read_cache_max_object_size() -> u64 {
+    8 * 1024 * 1024 // 8 MiB
+}
+
+fn default_read_cache_disk_bytes() -> u64 {
+    10 * 1024 * 1024 * 1024 // 10 GiB
+}
+
+impl Default for ReadCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            memory_bytes: default_read_cache_memory_bytes(),
+            max_object_size: default_read_cache_max_object_size(),
+            disk_dir: None,
+            disk_bytes: default_read_cache_disk_bytes(),
+        }
+    }
+}
+
+impl ReadCacheConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.max_object_size == 0 || self.max_object_size > self.memory_bytes {
+            return Err(crate::Error::InvalidArgument(
+                "storage.read_cache.max_object_size must be between 1 and memory_bytes".into(),
+            ));
+        }
+        if self.disk_dir.is_some() && self.disk_bytes < self.max_object_size {
+            return Err(crate::Error::InvalidArgument(
+                "storage.read_cache.disk_bytes must be at least max_object_size".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Content-addressable deduplication: object data is split into chunks that
+/// are stored once by SHA-256 and shared between objects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DedupConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How object data is split into chunks
+    #[serde(default)]
+    pub chunking: ChunkingMode,
+    /// Chunk size in bytes; the average size for content-defined chunking
+    #[serde(default = "default_dedup_chunk_size", deserialize_with = "load::byte_size")]
+    pub chunk_size: usize,
+}
+
+/// Chunk boundary selection for deduplication
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkingMode {
+    /// Equal-sized chunks; cheap, but an insertion shifts every later chunk
+    Fixed,
+    /// Boundaries follow the content, so shifted data still deduplicates
+    #[default]
+    ContentDefined,
+}
+
+fn default_dedup_chunk_size() -> usize {
+    1024 * 1024 // 1 MiB
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            chunking: ChunkingMode::default(),
+            chunk_size: default_dedup_chunk_size(),
+        }
+    }
+}
+
+impl DedupConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if !(4096..=64 * 1024 * 1024).contains(&self.chunk_size) {
+            return Err(crate::Error::InvalidArgument(format!(
+                "Dedup chunk_size must be between 4 KiB and 64 MiB, got {}",
+                self.chunk_size
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Storage backend a bucket can be mapped to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum BackendConfig {
+    /// Local directory, e.g. a second disk for hot or cold data
+    Local(LocalBackendConfig),
+    /// Process memory; contents are lost on restart
+    Memory,
+    /// S3-compatible service, with a local read cache
+    S3(GatewayConfig),
+    /// Azure Blob Storage; buckets map onto containers
+    Azure(AzureBackendConfig),
+    /// Google Cloud Storage; buckets map onto GCS buckets
+    Gcs(GcsBackendConfig),
+}
+
+impl BackendConfig {
+    /// Short name of the backend type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Local(_) => "local",
+            Self::Memory => "memory",
+            Self::S3(_) => "s3",
+            Self::Azure(_) => "azure",
+            Self::Gcs(_) => "gcs",
+        }
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        match self {
+            Self::Local(config) => {
+                if config.data_dir.as_os_str().is_empty() {
+                    return Err(crate::Error::InvalidArgument(
+                        "Local backend requires data_dir".into(),
+                    ));
+                }
+                Ok(())
+            }
+            Self::Memory => Ok(()),
+            Self::S3(config) => GatewayConfig {
+                enabled: true,
+                ..config.clone()
+            }
+            .validate(),
+            Self::Azure(config) => {
+                if config.account.is_empty() || config.access_key.is_empty() {
+                    return Err(crate::Error::InvalidArgument(
+                        "Azure backend requires account and access_key".into(),
+                    ));
+                }
+                Ok(())
+            }
+            Self::Gcs(_) => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalBackendConfig {
+    /// Directory holding the backend's buckets
+    pub data_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AzureBackendConfig {
+    /// Storage account name
+    pub account: String,
+    /// Storage account access key
+    pub access_key: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GcsBackendConfig {
+    /// Service account key file; application default credentials are used when unset
+    #[serde(default)]
+    pub credentials_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// In-process cache in front of the database
+    #[serde(default)]
+    pub cache: MetadataCacheConfig,
+    /// Seconds between recounts of the per-bucket usage counters; 0 disables
+    #[serde(default = "default_usage_reconcile_secs")]
+    pub usage_reconcile_secs: u64,
+    /// Use SQLite's write-ahead log, so reads do not block on writes
+    #[serde(default = "default_true")]
+    pub sqlite_wal: bool,
+    /// How long a SQLite query waits for a lock held by another connection
+    /// or process before failing (milliseconds)
+    #[serde(default = "default_sqlite_busy_timeout_ms")]
+    pub sqlite_busy_timeout_ms: u64,
+}
+
+fn default_usage_reconcile_secs() -> u64 {
+    24 * 3600
+}
+
+fn default_sqlite_busy_timeout_ms() -> u64 {
+    5000
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: "sqlite:///data/hafiz/hafiz.db?mode=rwc".to_string(),
+            max_connections: 100,
+            min_connections: 5,
+            cache: MetadataCacheConfig::default(),
+            usage_reconcile_secs: default_usage_reconcile_secs(),
+            sqlite_wal: true,
+            sqlite_busy_timeout_ms: default_sqlite_busy_timeout_ms(),
+        }
+    }
+}
+
+/// Cache of bucket, object, bucket policy and CORS lookups
+///
+/// Writes through this server invalidate their entries right away; the TTL
+/// bounds how long a change made elsewhere, such as by another process
+/// sharing the database, can go unseen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetadataCacheConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Entries kept in each of the caches
+    #[serde(default = "default_metadata_cache_entries")]
+    pub max_entries: u64,
+    #[serde(default = "default_metadata_cache_ttl")]
+    pub ttl_secs: u64,
+}
+
+fn default_metadata_cache_entries() -> u64 {
+    100_000
+}
+
+fn default_metadata_cache_ttl() -> u64 {
+    30
+}
+
+impl Default for MetadataCacheConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_entries: default_metadata_cache_entries(),
+            ttl_secs: default_metadata_cache_ttl(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthConfig {
+    /// Require credentials; unsigned S3 requests are limited to what bucket
+    /// policies and ACLs allow the anonymous principal
+    pub enabled: bool,
+    pub root_access_key: String,
+    pub root_secret_key: String,
+    /// How long the old key keeps working after a rotation (seconds)
+    #[serde(default = "default_key_rotation_grace")]
+    pub key_rotation_grace_seconds: u64,
+    /// Account ID the root user's buckets answer to in
+    /// `x-amz-expected-bucket-owner`, besides their owner ID
+    #[serde(default)]
+    pub account_id: Option<String>,
+    /// How far the time a request was signed at may be from the server's
+    /// clock (seconds)
+    #[serde(default = "default_max_clock_skew")]
+    pub max_clock_skew_seconds: u64,
+}
+
+fn default_key_rotation_grace() -> u64 {
+    7 * 24 * 3600
+}
+
+fn default_max_clock_skew() -> u64 {
+    15 * 60
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            root_access_key: "minioadmin".to_string(),
+            root_secret_key: "minioadmin".to_string(),
+            key_rotation_grace_seconds: default_key_rotation_grace(),
+            account_id: None,
+            max_clock_skew_seconds: default_max_clock_skew(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    pub level: String,
+    pub format: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            level: "info".to_string(),
+            format: "pretty".to_string(),
+        }
+    }
+}
+
+impl LoggingConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if !matches!(self.format.as_str(), "pretty" | "json") {
+            return Err(crate::Error::InvalidArgument(format!(
+                "logging.format must be pretty or json, not {}",
+                self.format
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Server-Side Encryption Configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionConfig {
+    /// Enable encryption subsystem
+    pub enabled: bool,
+    /// Enable SSE-S3 (server-managed keys)
+    pub sse_s3_enabled: bool,
+    /// Enable SSE-C (customer-provided keys)
+    pub sse_c_enabled: bool,
+    /// Master encryption key (hex encoded, 32 bytes = 64 hex chars)
+    /// For production: use key_file or key_env
+    pub master_key: Option<String>,
+    /// Path to file containing master key
+    pub master_key_file: Option<PathBuf>,
+    /// Environment variable containing master key
+    pub master_key_env: Option<String>,
+    /// Fetch or unwrap the master key through a provider, in place of
+    /// master_key, master_key_file or master_key_env
+    #[serde(default)]
+    pub master_key_provider: Option<MasterKeyProviderConfig>,
+    /// File containing a passphrase to derive the master key from, in
+    /// place of a hex key
+    #[serde(default)]
+    pub master_passphrase_file: Option<PathBuf>,
+    /// Environment variable containing the passphrase
+    #[serde(default)]
+    pub master_passphrase_env: Option<String>,
+    /// How the master key is derived from the passphrase
+    #[serde(default)]
+    pub kdf: KdfConfig,
+    /// Default encryption for new objects (none, AES256)
+    pub default_encryption: DefaultEncryption,
+    /// Store user secret keys encrypted under the master key
+    #[serde(default = "default_encrypt_secret_keys")]
+    pub encrypt_secret_keys: bool,
+    /// Master key being rotated away from (hex), kept until stored secrets
+    /// and data encryption keys are re-wrapped under the current one
+    #[serde(default)]
+    pub previous_master_key: Option<String>,
+    /// Path to file containing the previous master key
+    #[serde(default)]
+    pub previous_master_key_file: Option<PathBuf>,
+}
+
+fn default_encrypt_secret_keys() -> bool {
+    true
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sse_s3_enabled: true,
+            sse_c_enabled: true,
+            master_key: None,
+            master_key_file: None,
+            master_key_env: None,
+            master_key_provider: None,
+            master_passphrase_file: None,
+            master_passphrase_env: None,
+            kdf: KdfConfig::default(),
+            default_encryption: DefaultEncryption::None,
+            encrypt_secret_keys: true,
+            previous_master_key: None,
+            previous_master_key_file: None,
+        }
+    }
+}
+
+impl EncryptionConfig {
+    /// Get master key from configured source
+    ///
+    /// `None` when encryption is disabled, when the key comes from
+    /// `master_key_provider`, or when only a passphrase is configured: the
+    /// key is then derived with settings kept in the metadata database.
+    pub fn get_master_key(&self) -> crate::Result<Option<Vec<u8>>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        // Try direct key first
+        if let Some(ref key) = self.master_key {
+            let bytes = hex::decode(key)
+                .map_err(|e| crate::Error::InvalidArgument(format!("Invalid master key hex: {}", e)))?;
+            if bytes.len() != 32 {
+                return Err(crate::Error::InvalidArgument(
+                    "Master key must be 32 bytes (64 hex characters)".into(),
+                ));
+            }
+            return Ok(Some(bytes));
+        }
+
+        // Try key file
+        if let Some(ref path) = self.master_key_file {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| crate::Error::InternalError(format!("Failed to read key file: {}", e)))?;
+            let bytes = hex::decode(content.trim())
+                .map_err(|e| crate::Error::InvalidArgument(format!("Invalid master key in file: {}", e)))?;
+            if bytes.len() != 32 {
+                return Err(crate::Error::InvalidArgument(
+                    "Master key must be 32 bytes (64 hex characters)".into(),
+                ));
+            }
+            return Ok(Some(bytes));
+        }
+
+        // Try environment variable
+        if let Some(ref env_var) = self.master_key_env {
+            if let Ok(key) = std::env::var(env_var) {
+                let bytes = hex::decode(&key)
+                    .map_err(|e| crate::Error::InvalidArgument(format!("Invalid master key in env: {}", e)))?;
+                if bytes.len() != 32 {
+                    return Err(crate::Error::InvalidArgument(
+                        "Master key must be 32 bytes (64 hex characters)".into(),
+                    ));
+                }
+                return Ok(Some(bytes));
+            }
+        }
+
+        if self.master_key_provider.is_some() || self.has_passphrase() {
+            return Ok(None);
+        }
+
+        Err(crate::Error::InvalidArgument(
+            "Encryption enabled but no master key configured".into(),
+        ))
+    }
+
+    /// Get the master key being rotated away from, if one is configured
+    pub fn get_previous_master_key(&self) -> crate::Result<Option<Vec<u8>>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        let hex_key = match (&self.previous_master_key, &self.previous_master_key_file) {
+            (Some(key), _) => key.clone(),
+            (None, Some(path)) => std::fs::read_to_string(path)
+                .map_err(|e| crate::Error::InternalError(format!("Failed to read previous key file: {}", e)))?
+                .trim()
+                .to_string(),
+            (None, None) => return Ok(None),
+        };
+        let bytes = hex::decode(&hex_key)
+            .map_err(|e| crate::Error::InvalidArgument(format!("Invalid previous master key hex: {}", e)))?;
+        if bytes.len() != 32 {
+            return Err(crate::Error::InvalidArgument(
+                "Previous master key must be 32 bytes (64 hex characters)".into(),
+            ));
+        }
+        Ok(Some(bytes))
+    }
+
+    fn has_passphrase(&self) -> bool {
+        self.master_passphrase_file.is_some() || self.master_passphrase_env.is_some()
+    }
+
+    /// Get the passphrase to derive the master key from, if one is configured
+    pub fn get_master_passphrase(&self) -> crate::Result<Option<String>> {
+        if !self.enabled {
+            return Ok(None);
+        }
+
+        if let Some(ref path) = self.master_passphrase_file {
+            let content = std::fs::read_to_string(path)
+                .map_err(|e| crate::Error::InternalError(format!("Failed to read passphrase file: {}", e)))?;
+            // A trailing newline is not part of the passphrase
+            let passphrase = content.trim_end_matches(['\r', '\n']);
+            if passphrase.is_empty() {
+                return Err(crate::Error::InvalidArgument("Passphrase file is empty".into()));
+            }
+            return Ok(Some(passphrase.to_string()));
+        }
+
+        if let Some(ref env_var) = self.master_passphrase_env {
+            if let Ok(passphrase) = std::env::var(env_var) {
+                if !passphrase.is_empty() {
+                    return Ok(Some(passphrase));
+                }
+            }
+            return Err(crate::Error::InvalidArgument(format!(
+                "Master passphrase variable {} is not set",
+                env_var
+            )));
+        }
+
+        Ok(None)
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.enabled {
+            // Ensure at least one key source is configured
+            if self.master_key.is_none()
+                && self.master_key_file.is_none()
+                && self.master_key_env.is_none()
+                && self.master_key_provider.is_none()
+                && !self.has_passphrase()
+            {
+                return Err(crate::Error::InvalidArgument(
+                    "Encryption enabled but no master key source configured".into(),
+                ));
+            }
+            if self.master_passphrase_file.is_some() && self.master_passphrase_env.is_some() {
+                return Err(crate::Error::InvalidArgument(
+                    "Configure either master_passphrase_file or master_passphrase_env, not both".into(),
+                ));
+            }
+            if self.previous_master_key.is_some() && self.previous_master_key_file.is_some() {
+                return Err(crate::Error::InvalidArgument(
+                    "Configure either previous_master_key or previous_master_key_file, not both".into(),
+                ));
+            }
+            self.kdf.validate()?;
+            if let Some(provider) = &self.master_key_provider {
+                provider.validate()?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Where the master key comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum MasterKeyProviderConfig {
+    /// Hex key in a local file
+    File { path: PathBuf },
+    /// Hex key in an environment variable
+    Env { var: String },
+    /// Key wrapped by a HashiCorp Vault transit key
+    Vault(VaultKeyConfig),
+    /// Key wrapped by an AWS KMS key
+    AwsKms(AwsKmsKeyConfig),
+}
+
+impl MasterKeyProviderConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        let wrapped_key_file = match self {
+            Self::File { path } => {
+                if path.as_os_str().is_empty() {
+                    return Err(crate::Error::InvalidArgument("File key provider requires path".into()));
+                }
+                return Ok(());
+            }
+            Self::Env { var } => {
+                if var.is_empty() {
+                    return Err(crate::Error::InvalidArgument("Env key provider requires var".into()));
+                }
+                return Ok(());
+            }
+            Self::Vault(config) => {
+                if config.address.is_empty() || config.key_name.is_empty() {
+                    return Err(crate::Error::InvalidArgument(
+                        "Vault key provider requires address and key_name".into(),
+                    ));
+                }
+                &config.wrapped_key_file
+            }
+            Self::AwsKms(config) => {
+                if config.region.is_empty() {
+                    return Err(crate::Error::InvalidArgument("AWS KMS key provider requires region".into()));
+                }
+                if config.access_key_id.is_some() != config.secret_access_key.is_some() {
+                    return Err(crate::Error::InvalidArgument(
+                        "AWS KMS key provider needs both access_key_id and secret_access_key, or neither".into(),
+                    ));
+                }
+                &config.wrapped_key_file
+            }
+        };
+        if wrapped_key_file.as_os_str().is_empty() {
+            return Err(crate::Error::InvalidArgument("Key provider requires wrapped_key_file".into()));
+        }
+        Ok(())
+    }
+}
+
+/// Master key wrapped by a Vault transit key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VaultKeyConfig {
+    /// Vault address, e.g. https://vault.internal:8200
+    pub address: String,
+    /// File containing the Vault token; VAULT_TOKEN is used if unset
+    #[serde(default)]
+    pub token_file: Option<PathBuf>,
+    /// Mount path of the transit secrets engine
+    #[serde(default = "default_vault_transit_mount")]
+    pub mount: String,
+    /// Name of the transit key
+    pub key_name: String,
+    /// Vault Enterprise namespace
+    #[serde(default)]
+    pub namespace: Option<String>,
+    /// File holding the vault:v1:... ciphertext of the master key
+    pub wrapped_key_file: PathBuf,
+}
+
+fn default_vault_transit_mount() -> String {
+    "transit".to_string()
+}
+
+/// Master key wrapped by an AWS KMS key
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AwsKmsKeyConfig {
+    pub region: String,
+    /// KMS endpoint, for VPC endpoints or testing
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Key the master key was encrypted with
+    #[serde(default)]
+    pub key_id: Option<String>,
+    /// Credentials; the AWS_* environment variables are used if unset
+    #[serde(default)]
+    pub access_key_id: Option<String>,
+    #[serde(default)]
+    pub secret_access_key: Option<String>,
+    /// File holding the base64 CiphertextBlob of the master key
+    pub wrapped_key_file: PathBuf,
+}
+
+/// Key derivation function for a master passphrase
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum KdfAlgorithm {
+    Argon2id,
+    /// PBKDF2-HMAC-SHA256, for deployments that need a FIPS-approved KDF
+    Pbkdf2Sha256,
+    /// Single unsalted SHA-256; only to migrate keys derived that way
+    Sha256,
+}
+
+/// Derivation of the master key from a passphrase
+///
+/// The salt is generated on first start and kept in the metadata database
+/// with these settings. Changing them later re-encrypts stored secrets
+/// under the newly derived key on the next start.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KdfConfig {
+    pub algorithm: KdfAlgorithm,
+    /// Argon2id memory cost in KiB
+    pub memory_kib: u32,
+    /// Argon2id passes (default 2) or PBKDF2 iterations (default 600000)
+    pub iterations: Option<u32>,
+    /// Argon2id lanes
+    pub parallelism: u32,
+}
+
+impl Default for KdfConfig {
+    fn default() -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Argon2id,
+            memory_kib: 19 * 1024,
+            iterations: None,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfConfig {
+    /// Passes or iterations, with the algorithm's default
+    pub fn iterations(&self) -> u32 {
+        self.iterations.unwrap_or(match self.algorithm {
+            KdfAlgorithm::Argon2id => 2,
+            KdfAlgorithm::Pbkdf2Sha256 => 600_000,
+            KdfAlgorithm::Sha256 => 0,
+        })
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        match self.algorithm {
+            KdfAlgorithm::Argon2id => {
+                if self.iterations() == 0 || self.parallelism == 0 {
+                    return Err(crate::Error::InvalidArgument(
+                        "Argon2id needs at least one pass and one lane".into(),
+                    ));
+                }
+                if self.memory_kib < 8 * self.parallelism {
+                    return Err(crate::Error::InvalidArgument(
+                        "Argon2id needs at least 8 KiB of memory per lane".into(),
+                    ));
+                }
+            }
+            KdfAlgorithm::Pbkdf2Sha256 => {
+                if self.iterations() == 0 {
+                    return Err(crate::Error::InvalidArgument(
+                        "PBKDF2 needs at least one iteration".into(),
+                    ));
+                }
+            }
+            KdfAlgorithm::Sha256 => {}
+        }
+        Ok(())
+    }
+}
+
+/// Default encryption type for new objects
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DefaultEncryption {
+    /// No encryption by default
+    None,
+    /// AES-256 encryption by default
+    #[serde(rename = "AES256")]
+    Aes256,
+}
+
+impl Default for DefaultEncryption {
+    fn default() -> Self {
+        Self::None
+    }
+}
+
+/// Lifecycle worker configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LifecycleWorkerConfig {
+    /// Enable lifecycle worker
+    pub enabled: bool,
+    /// Interval between scans in seconds
+    pub scan_interval_secs: u64,
+    /// Batch size for processing objects
+    pub batch_size: usize,
+    /// Abort multipart uploads this many days after they were started when
+    /// no bucket lifecycle rule says otherwise; 0 keeps them
+    #[serde(default = "default_abort_incomplete_multipart_days")]
+    pub abort_incomplete_multipart_days: u32,
+}
+
+fn default_abort_incomplete_multipart_days() -> u32 {
+    7
+}
+
+impl Default for LifecycleWorkerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            scan_interval_secs: 3600, // 1 hour
+            batch_size: 1000,
+            abort_incomplete_multipart_days: default_abort_incomplete_multipart_days(),
+        }
+    }
+}
+
+/// Background integrity scrubber: re-hashes stored objects against their
+/// recorded ETags to catch bit rot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScrubConfig {
+    /// Run the scrubber on a schedule; runs can always be started through the admin API
+    #[serde(default)]
+    pub enabled: bool,
+    /// Interval between runs in seconds
+    #[serde(default = "default_scrub_interval_secs")]
+    pub interval_secs: u64,
+    /// Read throughput limit in bytes per second; 0 means unlimited
+    #[serde(default = "default_scrub_max_bytes_per_sec", deserialize_with = "load::byte_size")]
+    pub max_bytes_per_sec: u64,
+    /// Move damaged data that cannot be repaired out of the way so it is
+    /// never served
+    #[serde(default = "default_scrub_quarantine")]
+    pub quarantine: bool,
+}
+
+fn default_scrub_interval_secs() -> u64 {
+    7 * 24 * 3600 // weekly
+}
+
+fn default_scrub_max_bytes_per_sec() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_scrub_quarantine() -> bool {
+    true
+}
+
+impl Default for ScrubConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_scrub_interval_secs(),
+            max_bytes_per_sec: default_scrub_max_bytes_per_sec(),
+            quarantine: true,
+        }
+    }
+}
+
+/// Scheduled one-way mirroring of buckets to an external S3-compatible
+/// endpoint, a lighter alternative to cluster replication rules
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MirrorConfig {
+    #[serde(default)]
+    pub jobs: Vec<MirrorJobConfig>,
+}
+
+impl MirrorConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        let mut names = std::collections::HashSet::new();
+        for job in &self.jobs {
+            if !names.insert(job.name.as_str()) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "Duplicate mirror job name: {}",
+                    job.name
+                )));
+            }
+            job.validate().map_err(|e| {
+                crate::Error::InvalidArgument(format!("Mirror job {}: {}", job.name, e))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+/// One mirror job: copies objects of `buckets` that are new or changed
+/// since its last run to buckets of the same name at `endpoint`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MirrorJobConfig {
+    pub name: String,
+    /// Cron expression with a seconds field, e.g. `0 0 2 * * *` for 02:00
+    /// every day, evaluated in UTC
+    pub schedule: String,
+    pub buckets: Vec<String>,
+    /// Target S3 endpoint
+    pub endpoint: String,
+    #[serde(default = "default_gateway_region")]
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    #[serde(default)]
+    pub path_style: bool,
+    /// Upload throughput limit in bytes per second; 0 means unlimited
+    #[serde(default, deserialize_with = "load::byte_size")]
+    pub max_bytes_per_sec: u64,
+    /// Attempts per object before it is left for the next run
+    #[serde(default = "default_mirror_max_attempts")]
+    pub max_attempts: u32,
+}
+
+fn default_mirror_max_attempts() -> u32 {
+    3
+}
+
+impl MirrorJobConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.name.is_empty() {
+            return Err(crate::Error::InvalidArgument("Mirror jobs need a name".into()));
+        }
+        if self.buckets.is_empty() {
+            return Err(crate::Error::InvalidArgument("No buckets to mirror".into()));
+        }
+        if self.max_attempts == 0 {
+            return Err(crate::Error::InvalidArgument(
+                "max_attempts must be at least 1".into(),
+            ));
+        }
+        if !self.endpoint.starts_with("http://") && !self.endpoint.starts_with("https://") {
+            return Err(crate::Error::InvalidArgument(format!(
+                "Endpoint must be an http(s) URL: {}",
+                self.endpoint
+            )));
+        }
+        if self.access_key.is_empty() || self.secret_key.is_empty() {
+            return Err(crate::Error::InvalidArgument(
+                "access_key and secret_key are required".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Connection settings of the target, in the form the S3 client takes
+    pub fn target(&self) -> GatewayConfig {
+        GatewayConfig {
+            enabled: true,
+            endpoint: self.endpoint.clone(),
+            region: self.region.clone(),
+            access_key: self.access_key.clone(),
+            secret_key: self.secret_key.clone(),
+            path_style: self.path_style,
+            ..GatewayConfig::default()
+        }
+    }
+}
+
+/// Cluster configuration for multi-node setup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClusterConfigSection {
+    /// Enable cluster mode
+    pub enabled: bool,
+    /// Cluster name (must match across all nodes)
+    pub name: String,
+    /// This node's unique ID (auto-generated if not set)
+    pub node_id: Option<String>,
+    /// This node's human-readable name
+    pub node_name: Option<String>,
+    /// This node's advertised API endpoint
+    pub advertise_endpoint: Option<String>,
+    /// This node's cluster communication port
+    pub cluster_port: u16,
+    /// Seed nodes for cluster discovery
+    pub seed_nodes: Vec<String>,
+    /// Heartbeat interval in seconds
+    pub heartbeat_interval_secs: u64,
+    /// Node timeout in seconds
+    pub node_timeout_secs: u64,
+    /// Default replication mode (none, async, sync)
+    pub default_replication_mode: String,
+    /// Default replication factor
+    pub default_replication_factor: u32,
+    /// Default consistency level (one, quorum, all), overridable per bucket
+    #[serde(default = "default_consistency_level")]
+    pub default_consistency_level: String,
+    /// Enable TLS for cluster communication
+    pub cluster_tls_enabled: bool,
+    /// Cluster TLS certificate path
+    pub cluster_tls_cert: Option<String>,
+    /// Cluster TLS key path
+    pub cluster_tls_key: Option<String>,
+    /// Cluster CA certificate path
+    pub cluster_ca_cert: Option<String>,
+    /// Secret every node presents on the cluster listener; needed unless
+    /// cluster TLS verifies peers by their certificates
+    #[serde(default)]
+    pub shared_secret: Option<String>,
+    /// Metadata consensus mode (none, raft)
+    #[serde(default = "default_metadata_consensus")]
+    pub metadata_consensus: String,
+    /// Raft election timeout in milliseconds
+    #[serde(default = "default_raft_election_timeout_ms")]
+    pub raft_election_timeout_ms: u64,
+    /// Raft heartbeat interval in milliseconds
+    #[serde(default = "default_raft_heartbeat_interval_ms")]
+    pub raft_heartbeat_interval_ms: u64,
+    /// Applied entries between Raft snapshots
+    #[serde(default = "default_raft_snapshot_threshold")]
+    pub raft_snapshot_threshold: u64,
+    /// Raft log/snapshot directory (`raft` under the storage data directory
+    /// if unset)
+    #[serde(default)]
+    pub raft_data_dir: Option<String>,
+    /// Node IDs of the Raft voting members, this node included
+    #[serde(default)]
+    pub raft_voters: Vec<String>,
+    /// Place objects on nodes with a consistent hash ring
+    #[serde(default)]
+    pub placement_enabled: bool,
+    /// Virtual nodes per physical node on the hash ring
+    #[serde(default = "default_placement_virtual_nodes")]
+    pub placement_virtual_nodes: u32,
+    /// Run background anti-entropy comparisons between replicas
+    #[serde(default)]
+    pub anti_entropy_enabled: bool,
+    /// Seconds between anti-entropy runs
+    #[serde(default = "default_anti_entropy_interval_secs")]
+    pub anti_entropy_interval_secs: u64,
+    /// Hinted handoff directory (hints kept in memory if unset)
+    #[serde(default)]
+    pub hint_dir: Option<String>,
+    /// Seconds a hint is kept before it is dropped
+    #[serde(default = "default_hint_ttl_secs")]
+    pub hint_ttl_secs: u64,
+    /// Maximum hints queued per unavailable node
+    #[serde(default = "default_max_hints_per_node")]
+    pub max_hints_per_node: usize,
+    /// Cluster wire protocol (http, grpc)
+    #[serde(default = "default_cluster_transport")]
+    pub cluster_transport: String,
+    /// gRPC cluster port, used when cluster_transport is grpc
+    #[serde(default = "default_cluster_grpc_port")]
+    pub cluster_grpc_port: u16,
+    /// Failure-domain zone; replicas are spread across zones when possible
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// Failure-domain rack within the zone
+    #[serde(default)]
+    pub rack: Option<String>,
+    /// Redirect object requests to a node holding the object (off,
+    /// accelerated, all); needs placement
+    #[serde(default = "default_redirect_mode")]
+    pub redirect_mode: String,
+}
+
+fn default_anti_entropy_interval_secs() -> u64 {
+    3600
+}
+
+fn default_consistency_level() -> String {
+    "one".to_string()
+}
+
+fn default_hint_ttl_secs() -> u64 {
+    3 * 3600
+}
+
+fn default_max_hints_per_node() -> usize {
+    100_000
+}
+
+fn default_cluster_transport() -> String {
+    "http".to_string()
+}
+
+fn default_cluster_grpc_port() -> u16 {
+    9002
+}
+
+fn default_placement_virtual_nodes() -> u32 {
+    128
+}
+
+fn default_redirect_mode() -> String {
+    "off".to_string()
+}
+
+fn default_metadata_consensus() -> String {
+    "none".to_string()
+}
+
+fn default_raft_election_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_raft_heartbeat_interval_ms() -> u64 {
+    200
+}
+
+fn default_raft_snapshot_threshold() -> u64 {
+    10_000
+}
+
+impl Default for ClusterConfigSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            name: "hafiz-cluster".to_string(),
+            node_id: None,
+            node_name: None,
+            advertise_endpoint: None,
+            cluster_port: 9001,
+            seed_nodes: Vec::new(),
+            heartbeat_interval_secs: 5,
+            node_timeout_secs: 30,
+            default_replication_mode: "async".to_string(),
+            default_replication_factor: 2,
+            default_consistency_level: default_consistency_level(),
+            cluster_tls_enabled: false,
+            cluster_tls_cert: None,
+            cluster_tls_key: None,
+            cluster_ca_cert: None,
+            shared_secret: None,
+            metadata_consensus: default_metadata_consensus(),
+            raft_election_timeout_ms: default_raft_election_timeout_ms(),
+            raft_heartbeat_interval_ms: default_raft_heartbeat_interval_ms(),
+            raft_snapshot_threshold: default_raft_snapshot_threshold(),
+            raft_data_dir: None,
+            raft_voters: Vec::new(),
+            placement_enabled: false,
+            placement_virtual_nodes: default_placement_virtual_nodes(),
+            anti_entropy_enabled: false,
+            anti_entropy_interval_secs: default_anti_entropy_interval_secs(),
+            hint_dir: None,
+            hint_ttl_secs: default_hint_ttl_secs(),
+            max_hints_per_node: default_max_hints_per_node(),
+            cluster_transport: default_cluster_transport(),
+            cluster_grpc_port: default_cluster_grpc_port(),
+            zone: None,
+            rack: None,
+            redirect_mode: default_redirect_mode(),
+        }
+    }
+}
+
+impl ClusterConfigSection {
+    /// Nodes must be able to tell their peers from anyone else reaching the
+    /// cluster port
+    pub fn validate(&self) -> crate::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if self.cluster_tls_enabled
+            && (self.cluster_tls_cert.is_none() || self.cluster_tls_key.is_none() || self.cluster_ca_cert.is_none())
+        {
+            return Err(crate::Error::InvalidArgument(
+                "cluster_tls_enabled needs cluster_tls_cert, cluster_tls_key and cluster_ca_cert".into(),
+            ));
+        }
+        if !self.cluster_tls_enabled && self.shared_secret.as_deref().unwrap_or("").is_empty() {
+            return Err(crate::Error::InvalidArgument(
+                "Cluster mode needs a shared_secret or cluster TLS to authenticate peers".into(),
+            ));
+        }
+        if !matches!(self.metadata_consensus.as_str(), "none" | "raft") {
+            return Err(crate::Error::InvalidArgument(format!(
+                "metadata_consensus must be none or raft, not {}",
+                self.metadata_consensus
+            )));
+        }
+        if self.metadata_consensus == "raft" {
+            // A majority of a fixed voter set, not of whichever nodes
+            // discovery has found so far
+            let node_id = self.node_id.as_deref().ok_or_else(|| {
+                crate::Error::InvalidArgument("Raft needs a fixed node_id".into())
+            })?;
+            if !self.raft_voters.iter().any(|v| v == node_id) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "raft_voters must list every voting node, {} included",
+                    node_id
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Convert to ClusterConfig for the cluster module
+    pub fn to_cluster_config(&self, server_config: &ServerConfig) -> crate::types::ClusterConfig {
+        let node_id = self.node_id.clone().unwrap_or_else(|| {
+            uuid::Uuid::new_v4().to_string()
+        });
+
+        let node_name = self.node_name.clone().unwrap_or_else(|| {
+            hostname::get()
+                .map(|h| h.to_string_lossy().to_string())
+                .unwrap_or_else(|_| "hafiz-node".to_string())
+        });
+
+        let advertise_endpoint = self.advertise_endpoint.clone().unwrap_or_else(|| {
+            format!("http://{}:{}", server_config.bind_address, server_config.port)
+        });
+
+        let cluster_endpoint = format!(
+            "{}://{}:{}",
+            if self.cluster_tls_enabled { "https" } else { "http" },
+            server_config.bind_address,
+            self.cluster_port
+        );
+
+        let cluster_transport = match self.cluster_transport.as_str() {
+            "grpc" => crate::types::ClusterTransportProtocol::Grpc,
+            _ => crate::types::ClusterTransportProtocol::Http,
+        };
+
+        let grpc_endpoint = (cluster_transport == crate::types::ClusterTransportProtocol::Grpc)
+            .then(|| {
+                let scheme = if self.cluster_tls_enabled { "https" } else { "http" };
+                format!("{}://{}:{}", scheme, server_config.bind_address, self.cluster_grpc_port)
+            });
+
+        crate::types::ClusterConfig {
+            name: self.name.clone(),
+            node_id,
+            node_name,
+            advertise_endpoint,
+            cluster_endpoint,
+            seed_nodes: self.seed_nodes.clone(),
+            heartbeat_interval_secs: self.heartbeat_interval_secs,
+            node_timeout_secs: self.node_timeout_secs,
+            default_replication_mode: match self.default_replication_mode.as_str() {
+                "sync" => crate::types::ReplicationMode::Sync,
+                "async" => crate::types::ReplicationMode::Async,
+                _ => crate::types::ReplicationMode::None,
+            },
+            default_replication_factor: self.default_replication_factor,
+            default_consistency_level: self
+                .default_consistency_level
+                .parse()
+                .unwrap_or_default(),
+            cluster_tls_enabled: self.cluster_tls_enabled,
+            cluster_tls_cert: self.cluster_tls_cert.clone(),
+            cluster_tls_key: self.cluster_tls_key.clone(),
+            cluster_ca_cert: self.cluster_ca_cert.clone(),
+            shared_secret: self.shared_secret.clone(),
+            metadata_consensus: match self.metadata_consensus.as_str() {
+                "raft" => crate::types::MetadataConsensus::Raft,
+                _ => crate::types::MetadataConsensus::None,
+            },
+            raft_election_timeout_ms: self.raft_election_timeout_ms,
+            raft_heartbeat_interval_ms: self.raft_heartbeat_interval_ms,
+            raft_snapshot_threshold: self.raft_snapshot_threshold,
+            raft_data_dir: self.raft_data_dir.clone(),
+            raft_voters: self.raft_voters.clone(),
+            placement_enabled: self.placement_enabled,
+            placement_virtual_nodes: self.placement_virtual_nodes,
+            anti_entropy_enabled: self.anti_entropy_enabled,
+            anti_entropy_interval_secs: self.anti_entropy_interval_secs,
+            hint_dir: self.hint_dir.clone(),
+            hint_ttl_secs: self.hint_ttl_secs,
+            max_hints_per_node: self.max_hints_per_node,
+            cluster_transport,
+            grpc_endpoint,
+            zone: self.zone.clone(),
+            rack: self.rack.clone(),
+            redirect_mode: crate::types::RedirectMode::parse(&self.redirect_mode),
+        }
+    }
+}
+
+/// Gateway mode: objects live in an upstream S3 service and are cached locally
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GatewayConfig {
+    /// Serve objects from the upstream endpoint instead of local storage
+    #[serde(default)]
+    pub enabled: bool,
+    /// Upstream S3 endpoint
+    #[serde(default = "default_gateway_endpoint")]
+    pub endpoint: String,
+    /// Region used to sign upstream requests
+    #[serde(default = "default_gateway_region")]
+    pub region: String,
+    /// Upstream access key
+    #[serde(default)]
+    pub access_key: String,
+    /// Upstream secret key
+    #[serde(default)]
+    pub secret_key: String,
+    /// Address buckets as `endpoint/bucket` instead of `bucket.endpoint`
+    #[serde(default)]
+    pub path_style: bool,
+    /// Directory for cached objects (default: `<data_dir>/gateway-cache`)
+    #[serde(default)]
+    pub cache_dir: Option<PathBuf>,
+    /// Maximum size of the local cache in bytes; least recently used objects are evicted
+    #[serde(default = "default_gateway_cache_max_bytes", deserialize_with = "load::byte_size")]
+    pub cache_max_bytes: u64,
+    /// How long a cached object is served before it is fetched again
+    #[serde(default = "default_gateway_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+}
+
+fn default_gateway_endpoint() -> String {
+    "https://s3.amazonaws.com".to_string()
+}
+
+fn default_gateway_region() -> String {
+    "us-east-1".to_string()
+}
+
+fn default_gateway_cache_max_bytes() -> u64 {
+    10 * 1024 * 1024 * 1024 // 10 GiB
+}
+
+fn default_gateway_cache_ttl_secs() -> u64 {
+    3600
+}
+
+impl Default for GatewayConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: default_gateway_endpoint(),
+            region: default_gateway_region(),
+            access_key: String::new(),
+            secret_key: String::new(),
+            path_style: false,
+            cache_dir: None,
+            cache_max_bytes: default_gateway_cache_max_bytes(),
+            cache_ttl_secs: default_gateway_cache_ttl_secs(),
+        }
+    }
+}
+
+impl GatewayConfig {
+    pub fn validate(&self) -> crate::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !self.endpoint.starts_with("http://") && !self.endpoint.starts_with("https://") {
+            return Err(crate::Error::InvalidArgument(format!(
+                "Gateway endpoint must be an http(s) URL: {}",
+                self.endpoint
+            )));
+        }
+        if self.access_key.is_empty() || self.secret_key.is_empty() {
+            return Err(crate::Error::InvalidArgument(
+                "Gateway mode requires upstream access_key and secret_key".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// WebDAV access to buckets, for mounting them as network drives
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebDavConfig {
+    /// Serve WebDAV on a listener of its own
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to bind (default: the S3 API's `bind_address`)
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    /// Port of the WebDAV listener
+    #[serde(default = "default_webdav_port")]
+    pub port: u16,
+    /// Path the buckets are listed under, e.g. `/dav`
+    #[serde(default = "default_webdav_prefix")]
+    pub prefix: String,
+    /// How long directory listings are reused for each user; 0 disables
+    /// the cache
+    #[serde(default = "default_listing_cache_secs")]
+    pub listing_cache_secs: u64,
+}
+
+fn default_webdav_port() -> u16 {
+    9010
+}
+
+fn default_webdav_prefix() -> String {
+    "/".to_string()
+}
+
+fn default_listing_cache_secs() -> u64 {
+    5
+}
+
+impl Default for WebDavConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: None,
+            port: default_webdav_port(),
+            prefix: default_webdav_prefix(),
+            listing_cache_secs: default_listing_cache_secs(),
+        }
+    }
+}
+
+/// SFTP access to buckets, for appliances and scripts that drop files
+///
+/// Logins and their public keys are managed through the admin API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftpConfig {
+    /// Serve SFTP on a listener of its own (needs the `sftp` build feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Address to bind (default: the S3 API's `bind_address`)
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    /// Port of the SFTP listener
+    #[serde(default = "default_sftp_port")]
+    pub port: u16,
+    /// Ed25519 host key in PKCS#8 PEM, generated on first start when
+    /// missing (default: `sftp_host_key.pem` in the storage data directory)
+    #[serde(default)]
+    pub host_key_file: Option<PathBuf>,
+    /// Close sessions idle for this long
+    #[serde(default = "default_sftp_idle_timeout")]
+    pub idle_timeout_secs: u64,
+    /// How long a session reuses directory listings; 0 disables the cache
+    #[serde(default = "default_listing_cache_secs")]
+    pub listing_cache_secs: u64,
+}
+
+fn default_sftp_port() -> u16 {
+    2222
+}
+
+fn default_sftp_idle_timeout() -> u64 {
+    600
+}
+
+impl Default for SftpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_address: None,
+            port: default_sftp_port(),
+            host_key_file: None,
+            idle_timeout_secs: default_sftp_idle_timeout(),
+            listing_cache_secs: default_listing_cache_secs(),
+        }
+    }
+}
+
+/// Administration on a listener of its own
+///
+/// With `separate_listener`, the admin API, admin panel and metrics move
+/// from the S3 port to `server.admin_port`, so the S3 API can be exposed
+/// without them. The admin listener authenticates every API request and
+/// only accepts connections from `allowed_cidrs`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Serve administration on `server.admin_port` instead of the S3 port
+    #[serde(default)]
+    pub separate_listener: bool,
+    /// Address of the admin listener (default: `server.bind_address`)
+    #[serde(default)]
+    pub bind_address: Option<String>,
+    /// Networks admin requests must come from; empty allows any
+    #[serde(default)]
+    pub allowed_cidrs: Vec<String>,
+    /// Bearer tokens the admin API accepts besides credentials and OIDC
+    #[serde(default)]
+    pub tokens: Vec<AdminTokenConfig>,
+    /// TLS of the admin listener; unset, it uses `[tls]`
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+/// A bearer token for the admin API, stored as its SHA-256 so the
+/// configuration doesn't hold it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminTokenConfig {
+    /// Name the token is logged by
+    pub name: String,
+    /// Hex SHA-256 of the token
+    pub sha256: String,
+    /// Admin role of callers using the token
+    #[serde(default = "default_admin_token_role")]
+    pub role: crate::types::AdminRole,
+}
+
+fn default_admin_token_role() -> crate::types::AdminRole {
+    crate::types::AdminRole::SuperAdmin
+}
+
+impl AdminConfig {
+    pub fn validate(&self, server: &ServerConfig) -> crate::Result<()> {
+        crate::types::AccessPoint::validate_cidrs(&self.allowed_cidrs)?;
+        let mut names = std::collections::HashSet::new();
+        for token in &self.tokens {
+            if token.name.is_empty() || !names.insert(token.name.as_str()) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "Admin token names must be unique and not empty: {:?}",
+                    token.name
+                )));
+            }
+            if token.sha256.len() != 64 || !token.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(crate::Error::InvalidArgument(format!(
+                    "Admin token {}: sha256 must be 64 hex digits",
+                    token.name
+                )));
+            }
+        }
+        if self.separate_listener {
+            if server.admin_port == server.port {
+                return Err(crate::Error::InvalidArgument(
+                    "server.admin_port must differ from server.port for a separate admin listener".into(),
+                ));
+            }
+            if let Some(tls) = &self.tls {
+                tls.validate()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a client address may reach the admin listener
+    pub fn allows_ip(&self, ip: std::net::IpAddr) -> bool {
+        self.allowed_cidrs.is_empty()
+            || self
+                .allowed_cidrs
+                .iter()
+                .filter_map(|c| c.parse::<crate::types::Cidr>().ok())
+                .any(|c| c.contains(ip))
+    }
+
+    /// The configured token with this SHA-256, if any
+    pub fn token(&self, sha256: &str) -> Option<&AdminTokenConfig> {
+        self.tokens
+            .iter()
+            .find(|token| token.sha256.eq_ignore_ascii_case(sha256))
+    }
+}
+
+/// Full-text index of object keys, user metadata and tags
+///
+/// The index is kept current as objects are written and deleted and is
+/// queried through the admin API.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchConfig {
+    /// Index objects (needs the `search` build feature)
+    #[serde(default)]
+    pub enabled: bool,
+    /// Index directory (default: `search-index` in the storage data
+    /// directory); built from the metadata store when empty
+    #[serde(default)]
+    pub index_dir: Option<PathBuf>,
+}
+
+/// Content rewriting on GET
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TransformConfig {
+    /// Checked in order; the first rule matching an object applies
+    #[serde(default)]
+    pub rules: Vec<TransformRule>,
+}
+
+/// Transforms applied to the objects of one bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransformRule {
+    pub bucket: String,
+    /// Only keys starting with this
+    #[serde(default)]
+    pub prefix: String,
+    /// Only objects of these content types; all objects when empty
+    #[serde(default)]
+    pub content_types: Vec<String>,
+    /// Applied in order, each to the output of the one before
+    pub steps: Vec<TransformStep>,
+}
+
+impl TransformRule {
+    /// Whether the rule covers an object
+    pub fn matches(&self, bucket: &str, key: &str, content_type: &str) -> bool {
+        let mime = content_type.split(';').next().unwrap_or("").trim();
+        self.bucket == bucket
+            && key.starts_with(&self.prefix)
+            && (self.content_types.is_empty()
+                || self.content_types.iter().any(|t| t.eq_ignore_ascii_case(mime)))
+    }
+}
+
+/// One transform of a rule
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransformStep {
+    /// Name the transform is registered under
+    pub name: String,
+    #[serde(default)]
+    pub params: HashMap<String, String>,
+}
+
+/// LDAP/Active Directory Configuration Section
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LdapConfigSection {
+    /// Enable LDAP authentication
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// LDAP server URL (ldap:// or ldaps://)
+    #[serde(default = "default_ldap_url")]
+    pub server_url: String,
+
+    /// Use STARTTLS
+    #[serde(default)]
+    pub start_tls: bool,
+
+    /// Skip TLS certificate verification
+    #[serde(default)]
+    pub skip_tls_verify: bool,
+
+    /// Bind DN for LDAP queries
+    #[serde(default)]
+    pub bind_dn: String,
+
+    /// Bind password
+    #[serde(default)]
+    pub bind_password: String,
+
+    /// Base DN for user searches
+    #[serde(default)]
+    pub user_base_dn: String,
+
+    /// User search filter (use {username} placeholder)
+    #[serde(default = "default_user_filter")]
+    pub user_filter: String,
+
+    /// Base DN for group searches
+    #[serde(default)]
+    pub group_base_dn: Option<String>,
+
+    /// Group search filter (use {dn} or {username} placeholder)
+    #[serde(default)]
+    pub group_filter: Option<String>,
+
+    /// Username attribute
+    #[serde(default = "default_username_attr")]
+    pub username_attribute: String,
+
+    /// Email attribute
+    #[serde(default = "default_email_attr")]
+    pub email_attribute: String,
+
+    /// Display name attribute
+    #[serde(default = "default_displayname_attr")]
+    pub display_name_attribute: String,
+
+    /// Group name attribute
+    #[serde(default = "default_groupname_attr")]
+    pub group_name_attribute: String,
+
+    /// Connection timeout in seconds
+    #[serde(default = "default_ldap_timeout")]
+    pub timeout_seconds: u64,
+
+    /// Cache TTL for authenticated users (seconds)
+    #[serde(default = "default_cache_ttl")]
+    pub cache_ttl_seconds: u64,
+
+    /// LDAP server type: ldap, active_directory, openldap, 389ds
+    #[serde(default)]
+    pub server_type: String,
+
+    /// Group to policy mappings (JSON string or inline)
+    /// Example: {"admins": ["admin"], "developers": ["readwrite"]}
+    #[serde(default)]
+    pub group_policies: std::collections::HashMap<String, Vec<String>>,
+
+    /// Default policies for users without group mapping
+    #[serde(default = "default_policies")]
+    pub default_policies: Vec<String>,
+
+    /// Provision and deactivate users from the directory on a schedule
+    #[serde(default)]
+    pub sync_enabled: bool,
+
+    /// Interval between directory syncs (seconds)
+    #[serde(default = "default_ldap_sync_interval")]
+    pub sync_interval_seconds: u64,
+
+    /// Only provision users who belong to a group in `group_policies`
+    #[serde(default)]
+    pub sync_require_group: bool,
+}
+
+fn default_ldap_sync_interval() -> u64 {
+    900
+}
+
+fn default_ldap_url() -> String {
+    "ldap://localhost:389".to_string()
+}
+
+fn default_user_filter() -> String {
+    "(uid={username})".to_string()
+}
+
+fn default_username_attr() -> String {
+    "uid".to_string()
+}
+
+fn default_email_attr() -> String {
+    "mail".to_string()
+}
+
+fn default_displayname_attr() -> String {
+    "cn".to_string()
+}
+
+fn default_groupname_attr() -> String {
+    "cn".to_string()
+}
+
+fn default_ldap_timeout() -> u64 {
+    10
+}
+
+fn default_cache_ttl() -> u64 {
+    300
+}
+
+fn default_policies() -> Vec<String> {
+    vec!["readonly".to_string()]
+}
+
+impl Default for LdapConfigSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            server_url: default_ldap_url(),
+            start_tls: false,
+            skip_tls_verify: false,
+            bind_dn: String::new(),
+            bind_password: String::new(),
+            user_base_dn: String::new(),
+            user_filter: default_user_filter(),
+            group_base_dn: None,
+            group_filter: None,
+            username_attribute: default_username_attr(),
+            email_attribute: default_email_attr(),
+            display_name_attribute: default_displayname_attr(),
+            group_name_attribute: default_groupname_attr(),
+            timeout_seconds: default_ldap_timeout(),
+            cache_ttl_seconds: default_cache_ttl(),
+            server_type: "ldap".to_string(),
+            group_policies: std::collections::HashMap::new(),
+            default_policies: default_policies(),
+            sync_enabled: false,
+            sync_interval_seconds: default_ldap_sync_interval(),
+            sync_require_group: false,
+        }
+    }
+}
+
+/// OpenID Connect federation
+///
+/// ID tokens from the configured issuer are accepted as admin API bearer
+/// tokens and can be exchanged for temporary S3 credentials through
+/// `AssumeRoleWithWebIdentity`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OidcConfigSection {
+    /// Enable OIDC federation
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Issuer URL; must match the token's `iss` claim exactly
+    #[serde(default)]
+    pub issuer_url: String,
+
+    /// Accepted `aud` values (usually the client ID)
+    #[serde(default)]
+    pub audiences: Vec<String>,
+
+    /// JWKS URL; discovered from the issuer's openid-configuration if unset
+    #[serde(default)]
+    pub jwks_url: Option<String>,
+
+    /// How often signing keys are re-fetched (seconds)
+    #[serde(default = "default_jwks_refresh")]
+    pub jwks_refresh_seconds: u64,
+
+    /// Allowed clock skew when checking `exp` and `nbf` (seconds)
+    #[serde(default = "default_oidc_leeway")]
+    pub leeway_seconds: u64,
+
+    /// Claim holding the Hafiz username
+    #[serde(default = "default_username_claim")]
+    pub username_claim: String,
+
+    /// Claim holding the user's groups (string or array of strings)
+    #[serde(default = "default_groups_claim")]
+    pub groups_claim: String,
+
+    /// Group to policy mappings
+    /// Example: {"storage-admins": ["admin"], "developers": ["readwrite"]}
+    #[serde(default)]
+    pub group_policies: std::collections::HashMap<String, Vec<String>>,
+
+    /// Default policies for users without group mapping
+    #[serde(default = "default_policies")]
+    pub default_policies: Vec<String>,
+
+    /// Default lifetime of STS credentials (seconds)
+    #[serde(default = "default_sts_duration")]
+    pub sts_default_duration_seconds: u64,
+
+    /// Longest lifetime a caller may request (seconds)
+    #[serde(default = "default_sts_max_duration")]
+    pub sts_max_duration_seconds: u64,
+}
+
+fn default_jwks_refresh() -> u64 {
+    3600
+}
+
+fn default_oidc_leeway() -> u64 {
+    60
+}
+
+fn default_username_claim() -> String {
+    "preferred_username".to_string()
+}
+
+fn default_groups_claim() -> String {
+    "groups".to_string()
+}
+
+fn default_sts_duration() -> u64 {
+    3600
+}
+
+fn default_sts_max_duration() -> u64 {
+    43200
+}
+
+impl Default for OidcConfigSection {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            issuer_url: String::new(),
+            audiences: Vec::new(),
+            jwks_url: None,
+            jwks_refresh_seconds: default_jwks_refresh(),
+            leeway_seconds: default_oidc_leeway(),
+            username_claim: default_username_claim(),
+            groups_claim: default_groups_claim(),
+            group_policies: std::collections::HashMap::new(),
+            default_policies: default_policies(),
+            sts_default_duration_seconds: default_sts_duration(),
+            sts_max_duration_seconds: default_sts_max_duration(),
+        }
+    }
+}
+
+/// OpenTelemetry export
+///
+/// Sends request, storage and metadata spans plus the server metrics to an
+/// OTLP collector over gRPC. Needs a build with the `otel` feature.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// Enable OTLP export
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Collector endpoint
+    #[serde(default = "default_otlp_endpoint")]
+    pub endpoint: String,
+
+    /// `service.name` reported with every span and metric
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+
+    /// Fraction of traces to sample, 0.0 to 1.0; a sampled parent is always followed
+    #[serde(default = "default_sampling_ratio")]
+    pub sampling_ratio: f64,
+
+    /// Export metrics as well as traces
+    #[serde(default = "default_true")]
+    pub export_metrics: bool,
+
+    /// How often metrics are pushed (seconds)
+    #[serde(default = "default_metrics_interval")]
+    pub metrics_interval_seconds: u64,
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_service_name() -> String {
+    "hafiz".to_string()
+}
+
+fn default_sampling_ratio() -> f64 {
+    1.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_metrics_interval() -> u64 {
+    60
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: default_otlp_endpoint(),
+            service_name: default_service_name(),
+            sampling_ratio: default_sampling_ratio(),
+            export_metrics: true,
+            metrics_interval_seconds: default_metrics_interval(),
+        }
+    }
+}
+
+/// Prometheus metric labels and slow request reporting
+///
+/// S3 operation metrics are labelled by operation only unless buckets are
+/// listed here. Every distinct label value is a new time series, so only the
+/// listed buckets get their own; the rest are counted together.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    /// Buckets whose operations carry a `bucket` label; a trailing `*`
+    /// matches any suffix. Other buckets are labelled `_other`
+    #[serde(default)]
+    pub bucket_labels: Vec<String>,
+
+    /// Buckets whose operations carry a `prefix` label with the first path
+    /// segment of the key, e.g. `logs/` for `logs/2024/01.gz`, as well as a
+    /// `bucket` label
+    #[serde(default)]
+    pub prefix_labels: Vec<String>,
+
+    /// Most distinct values of each label; later ones are labelled `_other`
+    #[serde(default = "default_max_label_values")]
+    pub max_label_values: usize,
+
+    /// S3 operations taking longer than this (milliseconds) are logged at
+    /// WARN and counted in `hafiz_slow_requests_total`; 0 turns it off
+    #[serde(default)]
+    pub slow_request_ms: u64,
+
+    /// Likewise for operations sending or receiving more bytes than this;
+    /// 0 turns it off
+    #[serde(default, deserialize_with = "load::byte_size")]
+    pub large_object_bytes: u64,
+}
+
+fn default_max_label_values() -> usize {
+    100
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            bucket_labels: Vec::new(),
+            prefix_labels: Vec::new(),
+            max_label_values: default_max_label_values(),
+            slow_request_ms: 0,
+            large_object_bytes: 0,
+        }
+    }
+}
+
+// Helper for num_cpus in default
+mod num_cpus {
+    pub fn get() -> usize {
+        std::thread::available_parallelism()
+            .map(|p| p.get())
+            .unwrap_or(4)
+    }
+}
@@ -0,0 +1,627 @@
+//! Directory semantics over `/`-delimited keys
+//!
+//! Buckets hold keys, not directories. The file gateways (`hafiz mount`,
+//! WebDAV and SFTP) all present keys as a directory tree the same way:
+//!
+//! - a directory is a prefix ending in `/` with keys below it, whether or
+//!   not a zero-byte `dir/` marker object is stored for it;
+//! - creating a directory stores its marker, so empty directories survive;
+//! - removing or moving away the last entry of a directory without a
+//!   marker stores one, so the directory does not vanish under the client;
+//! - a name that is both an object and a prefix shows as the directory;
+//! - renaming a directory copies and deletes every key below it, the
+//!   marker last, so an interrupted rename leaves the source directory in
+//!   place with whatever was not yet moved.
+//!
+//! Paths are relative to the root of a tree, without leading or trailing
+//! slashes; the root is `""`. Listings are cached for a short time, like
+//! the attribute cache of an NFS client: a stat following a directory
+//! listing is answered without another request.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Listings a [`ListingCache`] holds before evicting the oldest
+pub const MAX_CACHED_LISTINGS: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntryKind {
+    File,
+    Dir,
+}
+
+/// A file or directory as listed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub kind: EntryKind,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub etag: Option<String>,
+}
+
+impl DirEntry {
+    pub fn dir() -> Self {
+        Self {
+            kind: EntryKind::Dir,
+            size: 0,
+            modified: None,
+            etag: None,
+        }
+    }
+
+    pub fn file(size: u64, modified: Option<DateTime<Utc>>) -> Self {
+        Self {
+            kind: EntryKind::File,
+            size,
+            modified,
+            etag: None,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Dir
+    }
+}
+
+/// Keys of a directory tree rooted at a key prefix
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tree {
+    /// Empty, or ending with `/`
+    root: String,
+}
+
+impl Tree {
+    /// Tree rooted at `root`, with or without its trailing slash
+    pub fn new(root: &str) -> Self {
+        let root = root.trim_matches('/');
+        Self {
+            root: if root.is_empty() {
+                String::new()
+            } else {
+                format!("{}/", root)
+            },
+        }
+    }
+
+    /// Key prefix of the root, empty or ending with `/`
+    pub fn root(&self) -> &str {
+        &self.root
+    }
+
+    /// Key of the file at `path`
+    pub fn key(&self, path: &str) -> String {
+        format!("{}{}", self.root, path)
+    }
+
+    /// Key prefix of the entries of directory `path`, which is also the key
+    /// of its marker object
+    pub fn dir_prefix(&self, path: &str) -> String {
+        if path.is_empty() {
+            self.root.clone()
+        } else {
+            format!("{}{}/", self.root, path)
+        }
+    }
+}
+
+/// Path of `name` inside `dir`
+pub fn child_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+/// Directory containing `path`, and the name within it
+pub fn split_path(path: &str) -> (&str, &str) {
+    match path.rfind('/') {
+        Some(idx) => (&path[..idx], &path[idx + 1..]),
+        None => ("", path),
+    }
+}
+
+/// Whether `path` is `dir` or below it
+pub fn is_within(path: &str, dir: &str) -> bool {
+    dir.is_empty()
+        || path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Path without empty, `.` and `..` segments
+///
+/// `..` never leads above the root.
+pub fn normalize(path: &str) -> String {
+    let mut segments = Vec::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    segments.join("/")
+}
+
+/// Entries of one directory, from a delimited listing of its prefix
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Listing {
+    pub entries: BTreeMap<String, DirEntry>,
+    /// Whether the directory's own marker object is stored
+    pub has_marker: bool,
+}
+
+impl Listing {
+    /// A directory that was just created
+    pub fn created() -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            has_marker: true,
+        }
+    }
+
+    /// Add an object of the listing of `prefix`
+    pub fn add_object(&mut self, prefix: &str, key: &str, entry: DirEntry) {
+        let Some(name) = key.strip_prefix(prefix) else {
+            return;
+        };
+        if name.is_empty() {
+            self.has_marker = true;
+        } else if !name.contains('/') && !self.entries.get(name).is_some_and(DirEntry::is_dir) {
+            self.entries.insert(name.to_string(), entry);
+        }
+    }
+
+    /// Add a common prefix of the listing of `prefix`
+    pub fn add_prefix(&mut self, prefix: &str, common_prefix: &str) {
+        let name = common_prefix
+            .strip_prefix(prefix)
+            .unwrap_or(common_prefix)
+            .trim_end_matches('/');
+        if !name.is_empty() && !name.contains('/') {
+            self.entries.insert(name.to_string(), DirEntry::dir());
+        }
+    }
+
+    /// Whether the directory exists at all; without a marker it vanishes
+    /// with its last entry
+    pub fn exists(&self) -> bool {
+        self.has_marker || !self.entries.is_empty()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DirEntry> {
+        self.entries.get(name)
+    }
+}
+
+struct Cached {
+    listing: Listing,
+    fetched: Instant,
+}
+
+/// Directory listings trusted for a TTL
+///
+/// Changes made through the gateway are applied to the cached listings
+/// with [`put_entry`](Self::put_entry) and
+/// [`remove_entry`](Self::remove_entry); changes made by anyone else show
+/// once the TTL runs out. With a zero TTL no listing is ever fresh, but
+/// [`get_stale`](Self::get_stale) still returns the last one.
+pub struct ListingCache {
+    ttl: Duration,
+    listings: HashMap<String, Cached>,
+}
+
+impl ListingCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            listings: HashMap::new(),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Listing of `dir`, if cached and fresh
+    pub fn get(&self, dir: &str) -> Option<&Listing> {
+        self.listings
+            .get(dir)
+            .filter(|cached| cached.fetched.elapsed() < self.ttl)
+            .map(|cached| &cached.listing)
+    }
+
+    /// Listing of `dir`, fresh or not
+    pub fn get_stale(&self, dir: &str) -> Option<&Listing> {
+        self.listings.get(dir).map(|cached| &cached.listing)
+    }
+
+    /// Entry of `path` from the fresh listing of its directory: `None` when
+    /// unknown, `Some(None)` when known to be missing
+    pub fn lookup(&self, path: &str) -> Option<Option<&DirEntry>> {
+        let (dir, name) = split_path(path);
+        self.get(dir).map(|listing| listing.get(name))
+    }
+
+    pub fn insert(&mut self, dir: &str, listing: Listing) {
+        if self.listings.len() >= MAX_CACHED_LISTINGS && !self.listings.contains_key(dir) {
+            self.evict();
+        }
+        self.listings.insert(
+            dir.to_string(),
+            Cached {
+                listing,
+                fetched: Instant::now(),
+            },
+        );
+    }
+
+    /// Record a file or directory created or changed at `path`
+    ///
+    /// Its ancestors become directories, as they do once a key is stored
+    /// below them.
+    pub fn put_entry(&mut self, path: &str, entry: DirEntry) {
+        let (dir, name) = split_path(path);
+        if let Some(cached) = self.listings.get_mut(dir) {
+            cached.listing.entries.insert(name.to_string(), entry);
+        }
+        if !dir.is_empty() {
+            self.put_entry(dir, DirEntry::dir());
+        }
+    }
+
+    /// Record a change at `path` whose resulting entry is unknown, so it is
+    /// listed again
+    pub fn invalidate_entry(&mut self, path: &str) {
+        let (dir, _) = split_path(path);
+        self.listings.remove(dir);
+        self.invalidate_tree(path);
+        if !dir.is_empty() {
+            self.put_entry(dir, DirEntry::dir());
+        }
+    }
+
+    /// Record the removal of `path`, and of everything below it
+    ///
+    /// A directory without a marker goes away with its last entry.
+    pub fn remove_entry(&mut self, path: &str) {
+        self.invalidate_tree(path);
+        let (dir, name) = split_path(path);
+        let Some(cached) = self.listings.get_mut(dir) else {
+            // Whether `dir` is left empty is unknown, and so is its entry
+            if !dir.is_empty() {
+                self.listings.remove(split_path(dir).0);
+            }
+            return;
+        };
+        cached.listing.entries.remove(name);
+        if !dir.is_empty() && !cached.listing.exists() {
+            self.remove_entry(dir);
+        }
+    }
+
+    /// Whether the directory of `path` still exists once `path` is
+    /// removed, from its listing fresh or not; `None` when not cached
+    ///
+    /// The root always exists.
+    pub fn parent_survives(&self, path: &str) -> Option<bool> {
+        let (dir, name) = split_path(path);
+        if dir.is_empty() {
+            return Some(true);
+        }
+        let listing = self.get_stale(dir)?;
+        Some(listing.has_marker || listing.entries.keys().any(|other| other != name))
+    }
+
+    /// Forget the listings of `dir` and of every directory below it
+    pub fn invalidate_tree(&mut self, dir: &str) {
+        self.listings.retain(|cached, _| !is_within(cached, dir));
+    }
+
+    pub fn clear(&mut self) {
+        self.listings.clear();
+    }
+
+    /// Drop expired listings, or the oldest one if none has expired
+    fn evict(&mut self) {
+        let ttl = self.ttl;
+        self.listings
+            .retain(|_, cached| cached.fetched.elapsed() < ttl);
+        if self.listings.len() < MAX_CACHED_LISTINGS {
+            return;
+        }
+        let oldest = self
+            .listings
+            .iter()
+            .min_by_key(|(_, cached)| cached.fetched)
+            .map(|(dir, _)| dir.clone());
+        if let Some(oldest) = oldest {
+            self.listings.remove(&oldest);
+        }
+    }
+}
+
+/// Object operations a [`TreeCopy`] is carried out with
+#[async_trait]
+pub trait TreeStore: Send + Sync {
+    type Error: Send;
+
+    /// Copy the object at key `from` to key `to`
+    async fn copy_object(&self, from: &str, to: &str) -> Result<(), Self::Error>;
+
+    async fn delete_object(&self, key: &str) -> Result<(), Self::Error>;
+}
+
+/// One object of a [`TreeCopy`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopyStep {
+    pub from: String,
+    pub to: String,
+}
+
+/// Progress of a [`TreeCopy`], reported after each object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CopyProgress {
+    pub done: usize,
+    pub total: usize,
+}
+
+/// Copy, or move, a file or a directory tree object by object
+#[derive(Debug, Clone)]
+pub struct TreeCopy {
+    steps: Vec<CopyStep>,
+    remove_source: bool,
+}
+
+impl TreeCopy {
+    pub fn file(from: &str, to: &str, remove_source: bool) -> Self {
+        Self {
+            steps: vec![CopyStep {
+                from: from.to_string(),
+                to: to.to_string(),
+            }],
+            remove_source,
+        }
+    }
+
+    /// Copy every key under `from_prefix` to `to_prefix`
+    ///
+    /// `keys` are those of an undelimited listing of `from_prefix`. The
+    /// marker of the source directory goes last.
+    pub fn dir(
+        from_prefix: &str,
+        to_prefix: &str,
+        keys: impl IntoIterator<Item = String>,
+        remove_source: bool,
+    ) -> Self {
+        let mut steps: Vec<CopyStep> = keys
+            .into_iter()
+            .filter_map(|key| {
+                let rest = key.strip_prefix(from_prefix)?;
+                let to = format!("{}{}", to_prefix, rest);
+                Some(CopyStep { from: key, to })
+            })
+            .collect();
+        steps.sort_by_key(|step| step.from == from_prefix);
+        Self {
+            steps,
+            remove_source,
+        }
+    }
+
+    pub fn steps(&self) -> &[CopyStep] {
+        &self.steps
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+
+    /// Copy each object, deleting the source right after its copy when
+    /// moving, and report progress after each
+    ///
+    /// Stops at the first failure; objects already moved stay moved.
+    pub async fn run<S: TreeStore>(
+        &self,
+        store: &S,
+        mut progress: impl FnMut(CopyProgress) + Send,
+    ) -> Result<(), S::Error> {
+        let total = self.steps.len();
+        for (i, step) in self.steps.iter().enumerate() {
+            store.copy_object(&step.from, &step.to).await?;
+            if self.remove_source {
+                store.delete_object(&step.from).await?;
+            }
+            progress(CopyProgress { done: i + 1, total });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(child_path("", "a"), "a");
+        assert_eq!(child_path("a/b", "c"), "a/b/c");
+        assert_eq!(split_path("a"), ("", "a"));
+        assert_eq!(split_path("a/b/c"), ("a/b", "c"));
+        assert!(is_within("a/b", "a"));
+        assert!(is_within("a", "a"));
+        assert!(is_within("a", ""));
+        assert!(!is_within("ab", "a"));
+
+        assert_eq!(normalize("/"), "");
+        assert_eq!(normalize("in//./out/"), "in/out");
+        assert_eq!(normalize("/../../etc/passwd"), "etc/passwd");
+
+        let tree = Tree::new("/scans/in/");
+        assert_eq!(tree.root(), "scans/in/");
+        assert_eq!(tree.key("a.pdf"), "scans/in/a.pdf");
+        assert_eq!(tree.dir_prefix(""), "scans/in/");
+        assert_eq!(tree.dir_prefix("2024"), "scans/in/2024/");
+        assert_eq!(Tree::new("").dir_prefix("a"), "a/");
+    }
+
+    #[test]
+    fn test_listing() {
+        let mut listing = Listing::default();
+        listing.add_object("photos/", "photos/", DirEntry::file(0, None));
+        listing.add_object("photos/", "photos/a.jpg", DirEntry::file(10, None));
+        listing.add_object("photos/", "photos/b", DirEntry::file(3, None));
+        listing.add_prefix("photos/", "photos/2024/");
+        listing.add_prefix("photos/", "photos/b/");
+
+        assert!(listing.has_marker);
+        let names: Vec<_> = listing.entries.keys().map(String::as_str).collect();
+        assert_eq!(names, ["2024", "a.jpg", "b"]);
+        assert_eq!(listing.get("a.jpg").unwrap().size, 10);
+        assert!(listing.get("2024").unwrap().is_dir());
+        // A name that is both a file and a directory is the directory
+        assert!(listing.get("b").unwrap().is_dir());
+        listing.add_object("photos/", "photos/b", DirEntry::file(3, None));
+        assert!(listing.get("b").unwrap().is_dir());
+
+        // An implicit directory exists while it has entries
+        let mut implicit = Listing::default();
+        assert!(!implicit.exists());
+        implicit.add_prefix("a/", "a/b/");
+        assert!(implicit.exists());
+        assert!(Listing::created().exists());
+    }
+
+    #[test]
+    fn test_cache() {
+        let mut cache = ListingCache::new(Duration::ZERO);
+        cache.insert("", Listing::created());
+        assert!(cache.get("").is_none());
+        assert!(cache.lookup("a").is_none());
+
+        let mut cache = ListingCache::new(Duration::from_secs(60));
+        cache.insert("", Listing::default());
+        cache.insert("a", Listing::created());
+        cache.insert("a/b", Listing::created());
+        assert_eq!(cache.lookup("a"), Some(None));
+
+        cache.put_entry("a", DirEntry::dir());
+        cache.put_entry("a/f", DirEntry::file(1, None));
+        assert!(cache.lookup("a").unwrap().unwrap().is_dir());
+        assert_eq!(cache.lookup("a/f").unwrap().unwrap().size, 1);
+
+        cache.remove_entry("a");
+        assert_eq!(cache.lookup("a"), Some(None));
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("a/b").is_none());
+        assert!(cache.get("").is_some());
+    }
+
+    #[test]
+    fn test_cache_implicit_dirs() {
+        let mut cache = ListingCache::new(Duration::from_secs(60));
+        cache.insert("", Listing::default());
+        cache.insert("a", Listing::default());
+
+        // Writing a/b/c makes a/b a directory
+        cache.put_entry("a/b/c", DirEntry::file(1, None));
+        assert!(cache.lookup("a").unwrap().unwrap().is_dir());
+        assert!(cache.lookup("a/b").unwrap().unwrap().is_dir());
+
+        // Removing the only file of a directory without a marker removes it
+        cache.insert("a/b", Listing::default());
+        cache.put_entry("a/b/c", DirEntry::file(1, None));
+        cache.remove_entry("a/b/c");
+        assert!(cache.get("a").is_none());
+        assert_eq!(cache.lookup("a"), Some(None));
+
+        // A directory with a marker stays
+        cache.insert("a", Listing::default());
+        cache.put_entry("a/d", DirEntry::dir());
+        cache.insert("a/d", Listing::created());
+        cache.put_entry("a/d/e", DirEntry::file(1, None));
+        cache.remove_entry("a/d/e");
+        assert!(cache.lookup("a/d").unwrap().unwrap().is_dir());
+
+        // Removing a/d/g would leave a/d empty, but it has a marker
+        cache.put_entry("a/d/g", DirEntry::file(1, None));
+        assert_eq!(cache.parent_survives("a/d/g"), Some(true));
+        cache.insert("a/h", Listing::default());
+        cache.put_entry("a/h/i", DirEntry::file(1, None));
+        assert_eq!(cache.parent_survives("a/h/i"), Some(false));
+        cache.put_entry("a/h/j", DirEntry::file(1, None));
+        assert_eq!(cache.parent_survives("a/h/i"), Some(true));
+        assert_eq!(cache.parent_survives("x/y"), None);
+        assert_eq!(cache.parent_survives("y"), Some(true));
+
+        cache.invalidate_entry("a/d/f");
+        assert!(cache.get("a/d").is_none());
+        assert!(cache.lookup("a/d").unwrap().unwrap().is_dir());
+    }
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    #[async_trait]
+    impl TreeStore for Recorder {
+        type Error = String;
+
+        async fn copy_object(&self, from: &str, to: &str) -> Result<(), String> {
+            if from.ends_with("fail") {
+                return Err(from.to_string());
+            }
+            self.0.lock().unwrap().push(format!("copy {} {}", from, to));
+            Ok(())
+        }
+
+        async fn delete_object(&self, key: &str) -> Result<(), String> {
+            self.0.lock().unwrap().push(format!("delete {}", key));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_tree_move() {
+        let keys = ["a/", "a/x", "a/b/y"].map(String::from);
+        let plan = TreeCopy::dir("a/", "c/", keys, true);
+        assert_eq!(plan.steps().last().unwrap().from, "a/");
+
+        let store = Recorder::default();
+        let mut reported = Vec::new();
+        plan.run(&store, |p| reported.push(p)).await.unwrap();
+        assert_eq!(
+            *store.0.lock().unwrap(),
+            [
+                "copy a/x c/x",
+                "delete a/x",
+                "copy a/b/y c/b/y",
+                "delete a/b/y",
+                "copy a/ c/",
+                "delete a/",
+            ]
+        );
+        assert_eq!(reported.last(), Some(&CopyProgress { done: 3, total: 3 }));
+
+        let keys = ["a/", "a/fail", "a/x"].map(String::from);
+        let store = Recorder::default();
+        let plan = TreeCopy::dir("a/", "c/", keys, false);
+        assert_eq!(plan.run(&store, |_| {}).await, Err("a/fail".to_string()));
+        assert!(store.0.lock().unwrap().is_empty());
+    }
+}
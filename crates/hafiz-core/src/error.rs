@@ -16,12 +16,43 @@ pub enum Error {
     #[error("The requested bucket name is not available")]
     BucketAlreadyExists,
 
+    #[error("The bucket you tried to create already exists, and you own it")]
+    BucketAlreadyOwnedByYou,
+
     #[error("The bucket you tried to delete is not empty")]
     BucketNotEmpty,
 
     #[error("The bucket does not have a policy")]
     NoSuchBucketPolicy,
 
+    #[error("The TagSet does not exist")]
+    NoSuchTagSet,
+
+    #[error("The specified access point does not exist: {0}")]
+    NoSuchAccessPoint(String),
+
+    #[error("The CORS configuration does not exist")]
+    NoSuchCORSConfiguration,
+
+    #[error("Object Lock configuration does not exist for this bucket")]
+    ObjectLockConfigurationNotFound,
+
+    #[error("You have attempted to create more buckets than allowed")]
+    TooManyBuckets,
+
+    #[error("The request is not valid with the current state of the bucket: {0}")]
+    InvalidBucketState(String),
+
+    #[error("A conflicting conditional operation is currently in progress against this resource: {0}")]
+    OperationAborted(String),
+
+    #[error("Please re-send this request to the specified temporary endpoint. Continue to use the original request endpoint for future requests.")]
+    TemporaryRedirect {
+        /// Host to re-send the request to
+        endpoint: String,
+        bucket: String,
+    },
+
     // Object Errors
     #[error("The specified key does not exist")]
     NoSuchKey,
@@ -29,9 +60,21 @@ pub enum Error {
     #[error("The specified key does not exist: {0}")]
     NoSuchKeyNamed(String),
 
+    #[error("The specified version does not exist")]
+    NoSuchVersion,
+
     #[error("The specified multipart upload does not exist")]
     NoSuchUpload,
 
+    #[error("The specified object does not have an Object Lock configuration")]
+    NoSuchObjectLockConfiguration,
+
+    #[error("The operation is not valid for the object's storage class: {0}")]
+    InvalidObjectState(String),
+
+    #[error("Object restore is already in progress")]
+    RestoreAlreadyInProgress,
+
     #[error("The lifecycle configuration does not exist")]
     NoSuchLifecycleConfiguration,
 
@@ -41,10 +84,25 @@ pub enum Error {
     #[error("Object is too large")]
     EntityTooLarge,
 
+    #[error("Your proposed upload is smaller than the minimum allowed size: {0}")]
+    EntityTooSmall(String),
+
+    #[error("Your key is too long")]
+    KeyTooLong,
+
+    #[error("Your metadata headers exceed the maximum allowed metadata size")]
+    MetadataTooLarge,
+
+    #[error("The storage class you specified is not valid: {0}")]
+    InvalidStorageClass(String),
+
     // Access Errors
     #[error("Access Denied")]
     AccessDenied,
 
+    #[error("Access Denied because object protected by object lock: {0}")]
+    ObjectLocked(String),
+
     #[error("The AWS access key ID you provided does not exist")]
     InvalidAccessKeyId,
 
@@ -54,6 +112,26 @@ pub enum Error {
     #[error("Request has expired")]
     ExpiredPresignedRequest,
 
+    #[error("The difference between the request time and the server's time is too large")]
+    RequestTimeTooSkewed {
+        /// Request time as the client sent it
+        request_time: String,
+        server_time: chrono::DateTime<chrono::Utc>,
+        max_skew_ms: i64,
+    },
+
+    #[error("Invalid web identity token: {0}")]
+    InvalidIdentityToken(String),
+
+    #[error("Could not reach the identity provider: {0}")]
+    IdpCommunicationError(String),
+
+    #[error("There is a problem with your account that prevents the operation from completing: {0}")]
+    AccountProblem(String),
+
+    #[error("Quota exceeded: {0}")]
+    QuotaExceeded(String),
+
     // Policy and ACL Errors
     #[error("Malformed policy document: {0}")]
     MalformedPolicy(String),
@@ -77,9 +155,30 @@ pub enum Error {
     #[error("Missing required header: {0}")]
     MissingHeader(String),
 
+    #[error("You must provide the Content-Length HTTP header")]
+    MissingContentLength,
+
     #[error("Invalid range: {0}")]
     InvalidRange(String),
 
+    #[error("The requested part number is not satisfiable: {0}")]
+    InvalidPartNumber(String),
+
+    #[error("At least one of the pre-conditions you specified did not hold")]
+    PreconditionFailed,
+
+    #[error("The specified method is not allowed against this resource: {0}")]
+    MethodNotAllowed(String),
+
+    #[error("Your socket connection to the server was not read from or written to within the timeout period")]
+    RequestTimeout,
+
+    #[error("The Content-MD5 you specified did not match what we received")]
+    BadDigest,
+
+    #[error("The Content-MD5 you specified is not valid")]
+    InvalidDigest,
+
     // Storage Errors
     #[error("Storage backend error: {0}")]
     StorageError(String),
@@ -95,6 +194,15 @@ pub enum Error {
     #[error("Not implemented: {0}")]
     NotImplemented(String),
 
+    #[error("Service unavailable: {0}")]
+    ServiceUnavailable(String),
+
+    #[error("Please reduce your request rate")]
+    SlowDown,
+
+    #[error("Insufficient storage: {0}")]
+    InsufficientStorage(String),
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 
@@ -107,17 +215,40 @@ impl Error {
         match self {
             Error::NoSuchBucket | Error::NoSuchBucketNamed(_) => "NoSuchBucket",
             Error::BucketAlreadyExists => "BucketAlreadyExists",
+            Error::BucketAlreadyOwnedByYou => "BucketAlreadyOwnedByYou",
             Error::BucketNotEmpty => "BucketNotEmpty",
             Error::NoSuchBucketPolicy => "NoSuchBucketPolicy",
+            Error::NoSuchTagSet => "NoSuchTagSet",
+            Error::NoSuchAccessPoint(_) => "NoSuchAccessPoint",
+            Error::NoSuchCORSConfiguration => "NoSuchCORSConfiguration",
+            Error::ObjectLockConfigurationNotFound => "ObjectLockConfigurationNotFoundError",
+            Error::TooManyBuckets => "TooManyBuckets",
+            Error::InvalidBucketState(_) => "InvalidBucketState",
+            Error::OperationAborted(_) => "OperationAborted",
+            Error::TemporaryRedirect { .. } => "TemporaryRedirect",
             Error::NoSuchKey | Error::NoSuchKeyNamed(_) => "NoSuchKey",
+            Error::NoSuchVersion => "NoSuchVersion",
             Error::NoSuchUpload => "NoSuchUpload",
+            Error::NoSuchObjectLockConfiguration => "NoSuchObjectLockConfiguration",
+            Error::InvalidObjectState(_) => "InvalidObjectState",
+            Error::RestoreAlreadyInProgress => "RestoreAlreadyInProgress",
             Error::NoSuchLifecycleConfiguration => "NoSuchLifecycleConfiguration",
             Error::InvalidPart(_) => "InvalidPart",
             Error::EntityTooLarge => "EntityTooLarge",
+            Error::EntityTooSmall(_) => "EntityTooSmall",
+            Error::KeyTooLong => "KeyTooLongError",
+            Error::MetadataTooLarge => "MetadataTooLarge",
+            Error::InvalidStorageClass(_) => "InvalidStorageClass",
             Error::AccessDenied => "AccessDenied",
+            Error::ObjectLocked(_) => "AccessDenied",
             Error::InvalidAccessKeyId => "InvalidAccessKeyId",
             Error::SignatureDoesNotMatch => "SignatureDoesNotMatch",
             Error::ExpiredPresignedRequest => "AccessDenied",
+            Error::RequestTimeTooSkewed { .. } => "RequestTimeTooSkewed",
+            Error::InvalidIdentityToken(_) => "InvalidIdentityToken",
+            Error::IdpCommunicationError(_) => "IDPCommunicationError",
+            Error::AccountProblem(_) => "AccountProblem",
+            Error::QuotaExceeded(_) => "QuotaExceeded",
             Error::MalformedPolicy(_) => "MalformedPolicy",
             Error::MalformedACL(_) => "MalformedACLError",
             Error::InvalidBucketName(_) => "InvalidBucketName",
@@ -125,11 +256,21 @@ impl Error {
             Error::InvalidRequest(_) => "InvalidRequest",
             Error::MalformedXML(_) => "MalformedXMLDocument",
             Error::MissingHeader(_) => "MissingSecurityHeader",
+            Error::MissingContentLength => "MissingContentLength",
             Error::InvalidRange(_) => "InvalidRange",
+            Error::InvalidPartNumber(_) => "InvalidPartNumber",
+            Error::PreconditionFailed => "PreconditionFailed",
+            Error::MethodNotAllowed(_) => "MethodNotAllowed",
+            Error::RequestTimeout => "RequestTimeout",
+            Error::BadDigest => "BadDigest",
+            Error::InvalidDigest => "InvalidDigest",
             Error::StorageError(_) => "InternalError",
             Error::DatabaseError(_) => "InternalError",
             Error::InternalError(_) => "InternalError",
             Error::NotImplemented(_) => "NotImplemented",
+            Error::ServiceUnavailable(_) => "ServiceUnavailable",
+            Error::SlowDown => "SlowDown",
+            Error::InsufficientStorage(_) => "InsufficientStorage",
             Error::Io(_) => "InternalError",
             Error::Other(_) => "InternalError",
         }
@@ -145,27 +286,65 @@ impl Error {
             | Error::MalformedACL(_)
             | Error::MissingHeader(_)
             | Error::InvalidPart(_)
-            | Error::EntityTooLarge => 400,
+            | Error::EntityTooLarge
+            | Error::EntityTooSmall(_)
+            | Error::KeyTooLong
+            | Error::MetadataTooLarge
+            | Error::InvalidStorageClass(_)
+            | Error::RequestTimeout
+            | Error::BadDigest
+            | Error::InvalidDigest
+            | Error::InvalidIdentityToken(_)
+            | Error::IdpCommunicationError(_)
+            | Error::TooManyBuckets => 400,
 
             Error::AccessDenied
+            | Error::ObjectLocked(_)
             | Error::InvalidAccessKeyId
             | Error::SignatureDoesNotMatch
-            | Error::ExpiredPresignedRequest => 403,
+            | Error::ExpiredPresignedRequest
+            | Error::RequestTimeTooSkewed { .. }
+            | Error::InvalidObjectState(_)
+            | Error::AccountProblem(_)
+            | Error::QuotaExceeded(_) => 403,
 
             Error::NoSuchBucket
             | Error::NoSuchBucketNamed(_)
             | Error::NoSuchKey
             | Error::NoSuchKeyNamed(_)
+            | Error::NoSuchVersion
             | Error::NoSuchUpload
             | Error::NoSuchLifecycleConfiguration
-            | Error::NoSuchBucketPolicy => 404,
+            | Error::NoSuchBucketPolicy
+            | Error::NoSuchTagSet
+            | Error::NoSuchAccessPoint(_)
+            | Error::NoSuchCORSConfiguration
+            | Error::ObjectLockConfigurationNotFound
+            | Error::NoSuchObjectLockConfiguration => 404,
+
+            Error::TemporaryRedirect { .. } => 307,
+
+            Error::MethodNotAllowed(_) => 405,
 
-            Error::BucketAlreadyExists | Error::BucketNotEmpty => 409,
+            Error::BucketAlreadyExists
+            | Error::BucketAlreadyOwnedByYou
+            | Error::BucketNotEmpty
+            | Error::InvalidBucketState(_)
+            | Error::OperationAborted(_)
+            | Error::RestoreAlreadyInProgress => 409,
 
-            Error::InvalidRange(_) => 416,
+            Error::MissingContentLength => 411,
+
+            Error::PreconditionFailed => 412,
+
+            Error::InvalidRange(_) | Error::InvalidPartNumber(_) => 416,
 
             Error::NotImplemented(_) => 501,
 
+            Error::ServiceUnavailable(_) | Error::SlowDown => 503,
+
+            Error::InsufficientStorage(_) => 507,
+
             _ => 500,
         }
     }
@@ -176,17 +355,43 @@ impl Error {
 pub struct S3Error {
     pub code: String,
     pub message: String,
+    /// Bucket or object the request addressed, e.g. `/bucket/key`
     pub resource: Option<String>,
     pub request_id: String,
+    /// Identifies the host that served the request, as `x-amz-id-2`
+    pub host_id: String,
+    /// Elements some errors add after `Message`, as (name, value)
+    pub details: Vec<(&'static str, String)>,
 }
 
 impl From<Error> for S3Error {
     fn from(err: Error) -> Self {
+        let details = match &err {
+            Error::RequestTimeTooSkewed {
+                request_time,
+                server_time,
+                max_skew_ms,
+            } => vec![
+                ("RequestTime", request_time.clone()),
+                (
+                    "ServerTime",
+                    server_time.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+                ),
+                ("MaxAllowedSkewMilliseconds", max_skew_ms.to_string()),
+            ],
+            Error::TemporaryRedirect { endpoint, bucket } => vec![
+                ("Endpoint", endpoint.clone()),
+                ("Bucket", bucket.clone()),
+            ],
+            _ => Vec::new(),
+        };
         S3Error {
             code: err.code().to_string(),
             message: err.to_string(),
             resource: None,
             request_id: String::new(),
+            host_id: String::new(),
+            details,
         }
     }
 }
@@ -202,20 +407,33 @@ impl S3Error {
         self
     }
 
+    pub fn with_host_id(mut self, host_id: impl Into<String>) -> Self {
+        self.host_id = host_id.into();
+        self
+    }
+
     pub fn to_xml(&self) -> String {
         let resource = self.resource.as_deref().unwrap_or("");
+        let details: String = self
+            .details
+            .iter()
+            .map(|(name, value)| format!("\n<{}>{}</{}>", name, xml_escape(value), name))
+            .collect();
         format!(
             r#"<?xml version="1.0" encoding="UTF-8"?>
 <Error>
 <Code>{}</Code>
-<Message>{}</Message>
+<Message>{}</Message>{}
 <Resource>{}</Resource>
 <RequestId>{}</RequestId>
+<HostId>{}</HostId>
 </Error>"#,
             xml_escape(&self.code),
             xml_escape(&self.message),
+            details,
             xml_escape(resource),
-            xml_escape(&self.request_id)
+            xml_escape(&self.request_id),
+            xml_escape(&self.host_id)
         )
     }
 }
@@ -227,3 +445,79 @@ fn xml_escape(s: &str) -> String {
         .replace('"', "&quot;")
         .replace('\'', "&apos;")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_codes_and_statuses() {
+        let cases = [
+            (Error::NoSuchVersion, "NoSuchVersion", 404),
+            (Error::PreconditionFailed, "PreconditionFailed", 412),
+            (Error::InvalidBucketState("x".into()), "InvalidBucketState", 409),
+            (Error::InvalidObjectState("x".into()), "InvalidObjectState", 403),
+            (Error::RestoreAlreadyInProgress, "RestoreAlreadyInProgress", 409),
+            (Error::ObjectLockConfigurationNotFound, "ObjectLockConfigurationNotFoundError", 404),
+            (Error::KeyTooLong, "KeyTooLongError", 400),
+            (Error::SlowDown, "SlowDown", 503),
+            (
+                Error::RequestTimeTooSkewed {
+                    request_time: "20240101T000000Z".into(),
+                    server_time: chrono::Utc::now(),
+                    max_skew_ms: 900_000,
+                },
+                "RequestTimeTooSkewed",
+                403,
+            ),
+            (Error::MethodNotAllowed("x".into()), "MethodNotAllowed", 405),
+            (Error::MissingContentLength, "MissingContentLength", 411),
+            (Error::TooManyBuckets, "TooManyBuckets", 400),
+            (Error::BucketAlreadyOwnedByYou, "BucketAlreadyOwnedByYou", 409),
+            (Error::AccountProblem("x".into()), "AccountProblem", 403),
+            (Error::QuotaExceeded("x".into()), "QuotaExceeded", 403),
+            (
+                Error::TemporaryRedirect {
+                    endpoint: "node2:9000".into(),
+                    bucket: "x".into(),
+                },
+                "TemporaryRedirect",
+                307,
+            ),
+        ];
+        for (err, code, status) in cases {
+            assert_eq!(err.code(), code);
+            assert_eq!(err.http_status(), status, "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_error_xml() {
+        let xml = S3Error::from(Error::NoSuchKey)
+            .with_resource("/photos/a&b.jpg")
+            .with_request_id("REQ1")
+            .with_host_id("aG9zdA==")
+            .to_xml();
+        assert!(xml.contains("<Code>NoSuchKey</Code>"));
+        assert!(xml.contains("<Resource>/photos/a&amp;b.jpg</Resource>"));
+        assert!(xml.contains("<RequestId>REQ1</RequestId>"));
+        assert!(xml.contains("<HostId>aG9zdA==</HostId>"));
+    }
+
+    #[test]
+    fn test_skew_error_xml() {
+        let server_time = chrono::DateTime::parse_from_rfc3339("2024-01-01T00:20:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let xml = S3Error::from(Error::RequestTimeTooSkewed {
+            request_time: "20240101T000000Z".into(),
+            server_time,
+            max_skew_ms: 900_000,
+        })
+        .to_xml();
+        assert!(xml.contains("<Code>RequestTimeTooSkewed</Code>"));
+        assert!(xml.contains("<RequestTime>20240101T000000Z</RequestTime>"));
+        assert!(xml.contains("<ServerTime>2024-01-01T00:20:00Z</ServerTime>"));
+        assert!(xml.contains("<MaxAllowedSkewMilliseconds>900000</MaxAllowedSkewMilliseconds>"));
+    }
+}
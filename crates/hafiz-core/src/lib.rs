@@ -3,6 +3,7 @@
 //! Core types, traits, and utilities for the Hafiz object storage system.
 
 pub mod config;
+pub mod directory;
 pub mod error;
 pub mod types;
 pub mod utils;
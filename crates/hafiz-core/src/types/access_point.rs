@@ -0,0 +1,194 @@
+//! Access point types
+//!
+//! An access point is a named entry to one bucket with a policy and network
+//! restrictions of its own. Clients address it by its alias in place of the
+//! bucket name, or by hostname when an access point domain is configured.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// Suffix that turns an access point name into its alias
+pub const ACCESS_POINT_ALIAS_SUFFIX: &str = "-s3alias";
+
+/// Maximum length of an access point name
+pub const MAX_ACCESS_POINT_NAME_LENGTH: usize = 50;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccessPoint {
+    pub name: String,
+    pub bucket: String,
+    /// Policy document (JSON) evaluated before the bucket policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub policy: Option<String>,
+    /// Networks requests must come from; empty allows any
+    #[serde(default)]
+    pub allowed_cidrs: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AccessPoint {
+    pub fn new(name: String, bucket: String) -> Self {
+        Self {
+            name,
+            bucket,
+            policy: None,
+            allowed_cidrs: Vec::new(),
+            created_at: Utc::now(),
+        }
+    }
+
+    /// Name that addresses the access point in place of a bucket name
+    pub fn alias(&self) -> String {
+        format!("{}{}", self.name, ACCESS_POINT_ALIAS_SUFFIX)
+    }
+
+    /// Access point name of an alias
+    pub fn name_from_alias(alias: &str) -> Option<&str> {
+        alias
+            .strip_suffix(ACCESS_POINT_ALIAS_SUFFIX)
+            .filter(|name| !name.is_empty())
+    }
+
+    /// ARN of the access point, or of an object reached through it
+    pub fn resource(&self, key: Option<&str>) -> String {
+        match key {
+            Some(key) => format!("arn:aws:s3:::accesspoint/{}/object/{}", self.name, key),
+            None => format!("arn:aws:s3:::accesspoint/{}", self.name),
+        }
+    }
+
+    /// Whether a client address satisfies the network restrictions
+    pub fn allows_ip(&self, ip: IpAddr) -> bool {
+        self.allowed_cidrs.is_empty()
+            || self
+                .allowed_cidrs
+                .iter()
+                .filter_map(|c| c.parse::<Cidr>().ok())
+                .any(|c| c.contains(ip))
+    }
+
+    /// Access point names follow bucket naming, without periods
+    pub fn validate_name(name: &str) -> Result<(), crate::Error> {
+        let invalid = |msg: &str| Err(crate::Error::InvalidArgument(format!("Invalid access point name: {}", msg)));
+        if name.len() < crate::MIN_BUCKET_NAME_LENGTH || name.len() > MAX_ACCESS_POINT_NAME_LENGTH {
+            return invalid("must be between 3 and 50 characters");
+        }
+        if !name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return invalid("only lowercase letters, numbers and hyphens are allowed");
+        }
+        if name.starts_with('-') || name.ends_with('-') {
+            return invalid("must start and end with a letter or number");
+        }
+        Ok(())
+    }
+
+    /// Validate the allowed networks
+    pub fn validate_cidrs(cidrs: &[String]) -> Result<(), crate::Error> {
+        for cidr in cidrs {
+            cidr.parse::<Cidr>()?;
+        }
+        Ok(())
+    }
+}
+
+/// An IPv4 or IPv6 network, e.g. `10.0.0.0/8`; a bare address is a single host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_matches(u32::from(net).into(), u32::from(ip).into(), 32, self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_matches(u128::from(net), u128::from(ip), 128, self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn prefix_matches(net: u128, ip: u128, bits: u8, prefix_len: u8) -> bool {
+    let shift = u32::from(bits - prefix_len);
+    shift >= u32::from(bits) || (net >> shift) == (ip >> shift)
+}
+
+impl FromStr for Cidr {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || crate::Error::InvalidArgument(format!("Invalid CIDR block: {}", s));
+        let (addr, prefix_len) = match s.trim().split_once('/') {
+            Some((addr, len)) => (addr, Some(len)),
+            None => (s.trim(), None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(len) => len.parse::<u8>().ok().filter(|l| *l <= max).ok_or_else(invalid)?,
+            None => max,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cidr_contains() {
+        let net: Cidr = "10.1.0.0/16".parse().unwrap();
+        assert!(net.contains("10.1.200.3".parse().unwrap()));
+        assert!(!net.contains("10.2.0.1".parse().unwrap()));
+        // IPv4-mapped IPv6 clients match IPv4 networks
+        assert!(net.contains("::ffff:10.1.0.9".parse().unwrap()));
+
+        let any: Cidr = "0.0.0.0/0".parse().unwrap();
+        assert!(any.contains("192.0.2.1".parse().unwrap()));
+        assert!(!any.contains("2001:db8::1".parse().unwrap()));
+
+        let host: Cidr = "2001:db8::1".parse().unwrap();
+        assert!(host.contains("2001:db8::1".parse().unwrap()));
+        assert!(!host.contains("2001:db8::2".parse().unwrap()));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("vpc-123".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_access_point_addressing() {
+        let mut ap = AccessPoint::new("analytics".into(), "data".into());
+        assert_eq!(ap.alias(), "analytics-s3alias");
+        assert_eq!(AccessPoint::name_from_alias("analytics-s3alias"), Some("analytics"));
+        assert_eq!(AccessPoint::name_from_alias("-s3alias"), None);
+        assert_eq!(AccessPoint::name_from_alias("data"), None);
+        assert_eq!(ap.resource(Some("a/b")), "arn:aws:s3:::accesspoint/analytics/object/a/b");
+
+        assert!(ap.allows_ip("203.0.113.5".parse().unwrap()));
+        ap.allowed_cidrs = vec!["192.168.0.0/24".into()];
+        assert!(ap.allows_ip("192.168.0.7".parse().unwrap()));
+        assert!(!ap.allows_ip("203.0.113.5".parse().unwrap()));
+
+        assert!(AccessPoint::validate_name("analytics-ro").is_ok());
+        assert!(AccessPoint::validate_name("Analytics").is_err());
+        assert!(AccessPoint::validate_name("a.b.c").is_err());
+        assert!(AccessPoint::validate_name("-ab").is_err());
+    }
+}
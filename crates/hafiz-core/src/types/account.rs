@@ -0,0 +1,220 @@
+//! Account types
+//!
+//! An account is a tenant: it holds users and buckets, and its quota caps
+//! what all of its buckets may store together. A bucket records the account
+//! it belongs to as its owner ID. Users and buckets that predate accounts
+//! belong to the default account, [`DEFAULT_ACCOUNT`].
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// ID of the account of the root user, and of everything not assigned to
+/// another account
+pub const DEFAULT_ACCOUNT: &str = "root";
+
+/// Maximum length of an account ID
+pub const MAX_ACCOUNT_ID_LENGTH: usize = 63;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Account {
+    /// Short name, used as the owner ID of the account's buckets
+    pub id: String,
+    /// Display name
+    pub name: String,
+    pub status: AccountStatus,
+    #[serde(default)]
+    pub quota: AccountQuota,
+    /// Generation of the key new SSE-S3 objects are encrypted under; each
+    /// key rotation moves to the next
+    #[serde(default = "first_key_generation")]
+    pub key_generation: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Account {
+    pub fn new(id: String, name: String) -> Self {
+        Self {
+            id,
+            name,
+            status: AccountStatus::Active,
+            quota: AccountQuota::default(),
+            key_generation: first_key_generation(),
+            created_at: Utc::now(),
+        }
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.status == AccountStatus::Suspended
+    }
+
+    /// Account IDs are lowercase letters, numbers and hyphens
+    pub fn validate_id(id: &str) -> Result<(), crate::Error> {
+        let invalid = |msg: &str| {
+            Err(crate::Error::InvalidArgument(format!(
+                "Invalid account ID: {}",
+                msg
+            )))
+        };
+        if id.is_empty() || id.len() > MAX_ACCOUNT_ID_LENGTH {
+            return invalid("must be between 1 and 63 characters");
+        }
+        if !id
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            return invalid("only lowercase letters, numbers and hyphens are allowed");
+        }
+        if id.starts_with('-') || id.ends_with('-') {
+            return invalid("must start and end with a letter or number");
+        }
+        Ok(())
+    }
+}
+
+fn first_key_generation() -> u32 {
+    1
+}
+
+/// Whether an account's users and buckets may be used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    Active,
+    /// Every request by its users or to its buckets is refused; nothing is
+    /// deleted
+    Suspended,
+}
+
+impl AccountStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Active => "active",
+            Self::Suspended => "suspended",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "suspended" => Self::Suspended,
+            _ => Self::Active,
+        }
+    }
+}
+
+impl fmt::Display for AccountStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Limits on what an account's buckets may hold together; 0 means unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountQuota {
+    #[serde(default)]
+    pub max_buckets: u64,
+    #[serde(default)]
+    pub max_objects: u64,
+    #[serde(default)]
+    pub max_bytes: u64,
+}
+
+impl AccountQuota {
+    pub fn is_unlimited(&self) -> bool {
+        self.max_buckets == 0 && self.max_objects == 0 && self.max_bytes == 0
+    }
+
+    /// Refuse adding `buckets`, `objects` and `bytes` to `usage` when that
+    /// would go over a limit
+    pub fn check(
+        &self,
+        usage: &AccountUsage,
+        buckets: u64,
+        objects: u64,
+        bytes: u64,
+    ) -> Result<(), crate::Error> {
+        let over = |max: u64, used: u64, added: u64| {
+            max > 0 && added > 0 && used.saturating_add(added) > max
+        };
+        if over(self.max_buckets, usage.buckets, buckets) {
+            return Err(crate::Error::TooManyBuckets);
+        }
+        if over(self.max_objects, usage.objects, objects) {
+            return Err(crate::Error::QuotaExceeded(format!(
+                "the account may hold at most {} objects",
+                self.max_objects
+            )));
+        }
+        if over(self.max_bytes, usage.bytes, bytes) {
+            return Err(crate::Error::QuotaExceeded(format!(
+                "the account may store at most {} bytes",
+                self.max_bytes
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// What an account's buckets hold
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccountUsage {
+    pub buckets: u64,
+    pub objects: u64,
+    pub bytes: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_id() {
+        assert!(Account::validate_id("acme").is_ok());
+        assert!(Account::validate_id("tenant-42").is_ok());
+        assert!(Account::validate_id("").is_err());
+        assert!(Account::validate_id("Acme").is_err());
+        assert!(Account::validate_id("acme.corp").is_err());
+        assert!(Account::validate_id("-acme").is_err());
+        assert!(Account::validate_id(&"a".repeat(64)).is_err());
+    }
+
+    #[test]
+    fn test_quota_check() {
+        let quota = AccountQuota {
+            max_buckets: 2,
+            max_objects: 0,
+            max_bytes: 1000,
+        };
+        let usage = AccountUsage {
+            buckets: 2,
+            objects: 50,
+            bytes: 900,
+        };
+
+        assert!(matches!(
+            quota.check(&usage, 1, 0, 0),
+            Err(crate::Error::TooManyBuckets)
+        ));
+        // Unlimited objects
+        assert!(quota.check(&usage, 0, 1_000_000, 100).is_ok());
+        assert!(matches!(
+            quota.check(&usage, 0, 1, 101),
+            Err(crate::Error::QuotaExceeded(_))
+        ));
+        // Requests that add nothing pass even when over quota
+        assert!(quota.check(&usage, 0, 0, 0).is_ok());
+        assert!(AccountQuota::default()
+            .check(&usage, 10, 10, u64::MAX)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_status_serde() {
+        assert_eq!(
+            serde_json::to_string(&AccountStatus::Suspended).unwrap(),
+            "\"suspended\""
+        );
+        assert_eq!(AccountStatus::parse("suspended"), AccountStatus::Suspended);
+        assert_eq!(AccountStatus::parse("active"), AccountStatus::Active);
+    }
+}
@@ -2,6 +2,12 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::TagSet;
+
+/// Maximum number of tags per bucket
+pub const MAX_TAGS_PER_BUCKET: usize = 50;
 
 /// Bucket versioning status
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
@@ -43,6 +49,42 @@ impl VersioningStatus {
     }
 }
 
+/// Bucket transfer acceleration status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub enum AccelerateStatus {
+    Enabled,
+    Suspended,
+}
+
+impl AccelerateStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Enabled => "Enabled",
+            Self::Suspended => "Suspended",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "Enabled" => Some(Self::Enabled),
+            "Suspended" => Some(Self::Suspended),
+            _ => None,
+        }
+    }
+
+    /// Refuse enabling acceleration on a bucket whose name has periods,
+    /// which S3 does not allow as accelerated endpoints are virtual hosts
+    pub fn check_bucket_name(&self, name: &str) -> Result<(), crate::Error> {
+        if *self == Self::Enabled && name.contains('.') {
+            return Err(crate::Error::InvalidRequest(
+                "Transfer acceleration is not supported for bucket names with periods".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Bucket {
     pub name: String,
@@ -76,34 +118,52 @@ impl Bucket {
         self
     }
 
+    /// Check a name for a new bucket against S3's rules
     pub fn validate_name(name: &str) -> Result<(), crate::Error> {
+        Self::validate_name_with(name, BucketNameRules::Strict)
+    }
+
+    pub fn validate_name_with(name: &str, rules: BucketNameRules) -> Result<(), crate::Error> {
+        let strict = rules == BucketNameRules::Strict;
+        let max_len = if strict {
+            crate::MAX_BUCKET_NAME_LENGTH
+        } else {
+            MAX_RELAXED_BUCKET_NAME_LENGTH
+        };
         if name.len() < crate::MIN_BUCKET_NAME_LENGTH {
             return Err(crate::Error::InvalidBucketName(
                 "Bucket name too short (min 3 characters)".into(),
             ));
         }
-        if name.len() > crate::MAX_BUCKET_NAME_LENGTH {
-            return Err(crate::Error::InvalidBucketName(
-                "Bucket name too long (max 63 characters)".into(),
-            ));
+        if name.len() > max_len {
+            return Err(crate::Error::InvalidBucketName(format!(
+                "Bucket name too long (max {} characters)",
+                max_len
+            )));
         }
 
         let chars: Vec<char> = name.chars().collect();
+        let letter_or_digit = |c: char| {
+            c.is_ascii_digit() || if strict { c.is_ascii_lowercase() } else { c.is_ascii_alphabetic() }
+        };
+        let letter = if strict { "lowercase letter" } else { "letter" };
 
-        if !chars[0].is_ascii_lowercase() && !chars[0].is_ascii_digit() {
-            return Err(crate::Error::InvalidBucketName(
-                "Must start with lowercase letter or number".into(),
-            ));
+        if !letter_or_digit(chars[0]) {
+            return Err(crate::Error::InvalidBucketName(format!(
+                "Must start with {} or number",
+                letter
+            )));
         }
 
-        if !chars.last().unwrap().is_ascii_lowercase() && !chars.last().unwrap().is_ascii_digit() {
-            return Err(crate::Error::InvalidBucketName(
-                "Must end with lowercase letter or number".into(),
-            ));
+        if !letter_or_digit(*chars.last().unwrap()) {
+            return Err(crate::Error::InvalidBucketName(format!(
+                "Must end with {} or number",
+                letter
+            )));
         }
 
         for c in &chars {
-            if !c.is_ascii_lowercase() && !c.is_ascii_digit() && *c != '-' && *c != '.' {
+            if !letter_or_digit(*c) && *c != '-' && *c != '.' && (strict || *c != '_') {
                 return Err(crate::Error::InvalidBucketName(format!(
                     "Invalid character: {}",
                     c
@@ -111,20 +171,84 @@ impl Bucket {
             }
         }
 
+        // Reserved for access point aliases
+        if name.ends_with(super::ACCESS_POINT_ALIAS_SUFFIX) {
+            return Err(crate::Error::InvalidBucketName(format!(
+                "Cannot end with {}",
+                super::ACCESS_POINT_ALIAS_SUFFIX
+            )));
+        }
+
+        if !strict {
+            return Ok(());
+        }
+
+        // Each label between periods must be a valid DNS label
         if name.contains("..") {
             return Err(crate::Error::InvalidBucketName(
                 "Cannot have consecutive periods".into(),
             ));
         }
+        if name.contains(".-") || name.contains("-.") {
+            return Err(crate::Error::InvalidBucketName(
+                "Periods cannot be next to hyphens".into(),
+            ));
+        }
+
+        let labels: Vec<&str> = name.split('.').collect();
+        if labels.len() == 4 && labels.iter().all(|l| l.chars().all(|c| c.is_ascii_digit())) {
+            return Err(crate::Error::InvalidBucketName(
+                "Cannot be formatted as an IP address".into(),
+            ));
+        }
+
+        if let Some(prefix) = RESERVED_BUCKET_PREFIXES.iter().find(|p| name.starts_with(*p)) {
+            return Err(crate::Error::InvalidBucketName(format!(
+                "Cannot start with {}",
+                prefix
+            )));
+        }
+        if let Some(suffix) = RESERVED_BUCKET_SUFFIXES.iter().find(|s| name.ends_with(*s)) {
+            return Err(crate::Error::InvalidBucketName(format!(
+                "Cannot end with {}",
+                suffix
+            )));
+        }
 
         Ok(())
     }
 }
 
+/// Prefixes S3 reserves: punycode, and names of its own buckets
+const RESERVED_BUCKET_PREFIXES: [&str; 3] = ["xn--", "sthree-", "amzn-s3-demo-"];
+
+/// Suffixes S3 reserves for Object Lambda, Multi-Region and directory
+/// bucket aliases
+const RESERVED_BUCKET_SUFFIXES: [&str; 4] = ["--ol-s3", ".mrap", "--x-s3", "--table-s3"];
+
+/// Maximum bucket name length under [`BucketNameRules::Relaxed`]
+pub const MAX_RELAXED_BUCKET_NAME_LENGTH: usize = 255;
+
+/// Rules names of new buckets must follow
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BucketNameRules {
+    /// S3's rules: DNS-compatible names that also work in virtual-hosted
+    /// style requests
+    #[default]
+    Strict,
+    /// For internal deployments that address buckets by path only; also
+    /// allows uppercase letters, underscores, up to 255 characters and the
+    /// names S3 reserves
+    Relaxed,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BucketInfo {
     pub name: String,
     pub creation_date: DateTime<Utc>,
+    #[serde(default)]
+    pub region: String,
 }
 
 impl From<Bucket> for BucketInfo {
@@ -132,6 +256,278 @@ impl From<Bucket> for BucketInfo {
         Self {
             name: b.name,
             creation_date: b.created_at,
+            region: b.region,
         }
     }
 }
+
+/// Codec used to compress object data at rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionCodec {
+    /// Stored as is
+    None,
+    /// Better ratio, moderate CPU cost
+    Zstd,
+    /// Fast, lower ratio
+    Lz4,
+}
+
+impl CompressionCodec {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompressionCodec::None => "none",
+            CompressionCodec::Zstd => "zstd",
+            CompressionCodec::Lz4 => "lz4",
+        }
+    }
+}
+
+impl std::fmt::Display for CompressionCodec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for CompressionCodec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(CompressionCodec::None),
+            "zstd" => Ok(CompressionCodec::Zstd),
+            "lz4" => Ok(CompressionCodec::Lz4),
+            other => Err(format!("Unknown compression codec: {}", other)),
+        }
+    }
+}
+
+/// Transparent compression setting of a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketCompression {
+    pub codec: CompressionCodec,
+    /// Codec level; zstd accepts 1-22, lz4 has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<i32>,
+}
+
+impl BucketCompression {
+    pub fn validate(&self) -> crate::Result<()> {
+        match (self.codec, self.level) {
+            (CompressionCodec::Zstd, Some(level)) if !(1..=22).contains(&level) => Err(
+                crate::Error::InvalidArgument(format!("zstd level must be 1-22, got {}", level)),
+            ),
+            (CompressionCodec::Lz4 | CompressionCodec::None, Some(_)) => Err(
+                crate::Error::InvalidArgument(format!("{} takes no level", self.codec)),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Access-based tiering setting of a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketTiering {
+    /// Objects not read for this many days move to the archive backend
+    pub archive_after_days: u32,
+}
+
+impl BucketTiering {
+    pub fn validate(&self) -> crate::Result<()> {
+        if self.archive_after_days == 0 {
+            return Err(crate::Error::InvalidArgument(
+                "archive_after_days must be at least 1".into(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// How a bucket's key space is listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NamespaceMode {
+    /// Listings roll keys up into CommonPrefixes at the delimiter
+    #[default]
+    Hierarchical,
+    /// Listings ignore the delimiter and return keys only; for buckets of
+    /// machine-generated keys that are never browsed as folders
+    Flat,
+}
+
+impl NamespaceMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Hierarchical => "hierarchical",
+            Self::Flat => "flat",
+        }
+    }
+
+    pub fn is_flat(&self) -> bool {
+        *self == Self::Flat
+    }
+}
+
+impl std::fmt::Display for NamespaceMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for NamespaceMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "hierarchical" => Ok(Self::Hierarchical),
+            "flat" => Ok(Self::Flat),
+            other => Err(format!("Unknown namespace mode: {}", other)),
+        }
+    }
+}
+
+/// Point-in-time copy of every version and delete marker of a bucket,
+/// kept until deleted and restorable into a new bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BucketSnapshot {
+    pub id: String,
+    /// Bucket the snapshot was taken of
+    pub bucket: String,
+    pub owner_id: String,
+    pub region: String,
+    pub versioning: VersioningStatus,
+    pub object_lock_enabled: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// Set once all data is captured; incomplete snapshots cannot be restored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Object versions and delete markers captured
+    pub versions: i64,
+    /// Bytes of object data captured
+    pub total_bytes: i64,
+}
+
+impl BucketSnapshot {
+    pub fn is_complete(&self) -> bool {
+        self.completed_at.is_some()
+    }
+}
+
+/// Validate a bucket tag set before it replaces the stored one
+pub fn validate_bucket_tags(tags: &TagSet) -> crate::Result<()> {
+    if tags.len() > MAX_TAGS_PER_BUCKET {
+        return Err(crate::Error::InvalidArgument(format!(
+            "Maximum {} tags per bucket",
+            MAX_TAGS_PER_BUCKET
+        )));
+    }
+    let mut seen = std::collections::HashSet::new();
+    for tag in &tags.tags {
+        tag.validate()?;
+        if tag.key.starts_with("aws:") {
+            return Err(crate::Error::InvalidArgument(
+                "Tag keys starting with aws: are reserved".into(),
+            ));
+        }
+        if !seen.insert(tag.key.as_str()) {
+            return Err(crate::Error::InvalidArgument(format!(
+                "Duplicate tag key: {}",
+                tag.key
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Whether a bucket carries every tag in `filter`; an empty filter matches
+/// all buckets
+pub fn bucket_tags_match(filter: &HashMap<String, String>, tags: &HashMap<String, String>) -> bool {
+    filter.iter().all(|(k, v)| tags.get(k) == Some(v))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Tag;
+
+    #[test]
+    fn test_validate_name() {
+        for name in ["my-bucket", "data.2024", "a1b", "logs.example.com"] {
+            assert!(Bucket::validate_name(name).is_ok(), "{}", name);
+        }
+        let reason = |name: &str| match Bucket::validate_name(name) {
+            Err(crate::Error::InvalidBucketName(reason)) => reason,
+            other => panic!("{}: {:?}", name, other),
+        };
+        assert_eq!(reason("ab"), "Bucket name too short (min 3 characters)");
+        assert_eq!(reason("My-Bucket"), "Must start with lowercase letter or number");
+        assert_eq!(reason("my_bucket"), "Invalid character: _");
+        assert_eq!(reason("my..bucket"), "Cannot have consecutive periods");
+        assert_eq!(reason("my-.bucket"), "Periods cannot be next to hyphens");
+        assert_eq!(reason("192.168.5.4"), "Cannot be formatted as an IP address");
+        assert_eq!(reason("xn--bcher-kva"), "Cannot start with xn--");
+        assert_eq!(reason("sthree-data"), "Cannot start with sthree-");
+        assert_eq!(reason("reports--ol-s3"), "Cannot end with --ol-s3");
+        assert_eq!(reason("analytics-s3alias"), "Cannot end with -s3alias");
+        // Numeric labels are fine unless the name looks like an address
+        assert!(Bucket::validate_name("2024.01.15").is_ok());
+    }
+
+    #[test]
+    fn test_validate_name_relaxed() {
+        let relaxed = |name: &str| Bucket::validate_name_with(name, BucketNameRules::Relaxed);
+        assert!(relaxed("My_Bucket").is_ok());
+        assert!(relaxed("10.0.0.1").is_ok());
+        assert!(relaxed("xn--legacy").is_ok());
+        assert!(relaxed(&"a".repeat(255)).is_ok());
+        assert!(relaxed(&"a".repeat(256)).is_err());
+        assert!(relaxed("_bucket").is_err());
+        assert!(relaxed("my/bucket").is_err());
+        assert!(relaxed("analytics-s3alias").is_err());
+    }
+
+    #[test]
+    fn test_accelerate_status() {
+        assert_eq!(AccelerateStatus::parse("Enabled"), Some(AccelerateStatus::Enabled));
+        assert_eq!(AccelerateStatus::parse("Suspended"), Some(AccelerateStatus::Suspended));
+        assert_eq!(AccelerateStatus::parse("enabled"), None);
+
+        assert!(AccelerateStatus::Enabled.check_bucket_name("media-uploads").is_ok());
+        assert!(AccelerateStatus::Enabled.check_bucket_name("logs.example.com").is_err());
+        assert!(AccelerateStatus::Suspended.check_bucket_name("logs.example.com").is_ok());
+    }
+
+    #[test]
+    fn test_validate_bucket_tags() {
+        let mut tags = TagSet::new();
+        tags.tags.push(Tag::new("cost-center", "1234"));
+        assert!(validate_bucket_tags(&tags).is_ok());
+
+        tags.tags.push(Tag::new("cost-center", "5678"));
+        assert!(validate_bucket_tags(&tags).is_err());
+
+        let mut reserved = TagSet::new();
+        reserved.tags.push(Tag::new("aws:createdBy", "x"));
+        assert!(validate_bucket_tags(&reserved).is_err());
+
+        let mut many = TagSet::new();
+        for i in 0..=MAX_TAGS_PER_BUCKET {
+            many.tags.push(Tag::new(format!("k{}", i), "v"));
+        }
+        assert!(validate_bucket_tags(&many).is_err());
+    }
+
+    #[test]
+    fn test_bucket_tags_match() {
+        let tags: HashMap<String, String> =
+            [("team".to_string(), "data".to_string())].into_iter().collect();
+        assert!(bucket_tags_match(&HashMap::new(), &tags));
+        assert!(bucket_tags_match(&tags, &tags));
+
+        let other: HashMap<String, String> =
+            [("team".to_string(), "web".to_string())].into_iter().collect();
+        assert!(!bucket_tags_match(&other, &tags));
+    }
+}
@@ -302,6 +302,61 @@ impl fmt::Display for ByteSize {
     }
 }
 
+/// What a bandwidth limit applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BandwidthScope {
+    /// All requests addressed to a bucket
+    Bucket,
+    /// All requests signed with an access key
+    AccessKey,
+}
+
+impl BandwidthScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Bucket => "bucket",
+            Self::AccessKey => "access_key",
+        }
+    }
+}
+
+impl fmt::Display for BandwidthScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for BandwidthScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bucket" => Ok(Self::Bucket),
+            "access_key" => Ok(Self::AccessKey),
+            other => Err(format!("Unknown bandwidth scope: {}", other)),
+        }
+    }
+}
+
+/// Byte rate limits shared by all requests of a bucket or access key;
+/// 0 means unlimited
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct BandwidthLimit {
+    /// Request bodies, in bytes per second
+    #[serde(default)]
+    pub ingress_bytes_per_sec: u64,
+    /// Response bodies, in bytes per second
+    #[serde(default)]
+    pub egress_bytes_per_sec: u64,
+}
+
+impl BandwidthLimit {
+    pub fn is_unlimited(&self) -> bool {
+        self.ingress_bytes_per_sec == 0 && self.egress_bytes_per_sec == 0
+    }
+}
+
 /// Timestamp wrapper with S3-compatible formatting
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Timestamp(pub DateTime<Utc>);
@@ -406,10 +406,15 @@ impl CorsResponseHeaders {
     }
 
     /// Build CORS headers for an actual request
+    ///
+    /// A rule open to every origin answers `*` without credentials, as S3
+    /// does; otherwise the origin is echoed back.
     pub fn for_actual_request(rule: &CorsRule, origin: &str) -> Self {
+        let any_origin = rule.allowed_origins.iter().any(|o| o == "*");
         let mut headers = Self {
-            allow_origin: Some(origin.to_string()),
-            allow_credentials: true,
+            allow_origin: Some(if any_origin { "*" } else { origin }.to_string()),
+            allow_methods: Some(rule.allowed_methods_header()),
+            allow_credentials: !any_origin,
             vary: Some("Origin".to_string()),
             ..Default::default()
         };
@@ -674,4 +679,28 @@ mod tests {
         let rule = config.find_matching_rule("https://app.example.com", "DELETE");
         assert!(rule.is_none());
     }
+
+    #[test]
+    fn test_actual_request_headers() {
+        let rule = CorsRule {
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            allowed_methods: vec![CorsMethod::GET, CorsMethod::HEAD],
+            expose_headers: vec!["ETag".to_string()],
+            ..Default::default()
+        };
+        let headers = CorsResponseHeaders::for_actual_request(&rule, "https://app.example.com");
+        assert_eq!(headers.allow_origin.as_deref(), Some("https://app.example.com"));
+        assert_eq!(headers.allow_methods.as_deref(), Some("GET, HEAD"));
+        assert_eq!(headers.expose_headers.as_deref(), Some("ETag"));
+        assert!(headers.allow_credentials);
+
+        let public = CorsRule {
+            allowed_origins: vec!["*".to_string()],
+            allowed_methods: vec![CorsMethod::GET],
+            ..Default::default()
+        };
+        let headers = CorsResponseHeaders::for_actual_request(&public, "https://any.example");
+        assert_eq!(headers.allow_origin.as_deref(), Some("*"));
+        assert!(!headers.allow_credentials);
+    }
 }
@@ -304,10 +304,14 @@ pub struct NoncurrentVersionTransition {
     pub newer_noncurrent_versions: Option<u32>,
 }
 
-/// Storage classes (for future tiered storage support)
+/// Storage classes
+///
+/// Archive classes keep their data on the archive backend, and an object
+/// in one must be restored before it can be read.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum StorageClass {
     Standard,
+    ReducedRedundancy,
     InfrequentAccess,
     Archive,
     DeepArchive,
@@ -323,11 +327,38 @@ impl StorageClass {
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Standard => "STANDARD",
+            Self::ReducedRedundancy => "REDUCED_REDUNDANCY",
             Self::InfrequentAccess => "STANDARD_IA",
             Self::Archive => "GLACIER",
             Self::DeepArchive => "DEEP_ARCHIVE",
         }
     }
+
+    /// Whether objects of this class must be restored before they are read
+    pub fn is_archive(&self) -> bool {
+        matches!(self, Self::Archive | Self::DeepArchive)
+    }
+}
+
+impl std::fmt::Display for StorageClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for StorageClass {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "STANDARD" => Ok(Self::Standard),
+            "REDUCED_REDUNDANCY" => Ok(Self::ReducedRedundancy),
+            "STANDARD_IA" => Ok(Self::InfrequentAccess),
+            "GLACIER" => Ok(Self::Archive),
+            "DEEP_ARCHIVE" => Ok(Self::DeepArchive),
+            other => Err(crate::Error::InvalidStorageClass(other.to_string())),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -358,4 +389,20 @@ mod tests {
         assert!(filter.matches("any-key", &tags));
         assert!(!filter.matches("any-key", &[]));
     }
+
+    #[test]
+    fn test_storage_class_names() {
+        for class in [
+            StorageClass::Standard,
+            StorageClass::ReducedRedundancy,
+            StorageClass::InfrequentAccess,
+            StorageClass::Archive,
+            StorageClass::DeepArchive,
+        ] {
+            assert_eq!(class.as_str().parse::<StorageClass>().unwrap(), class);
+        }
+        assert!("GLACIER".parse::<StorageClass>().unwrap().is_archive());
+        assert!(!"STANDARD_IA".parse::<StorageClass>().unwrap().is_archive());
+        assert!("standard".parse::<StorageClass>().is_err());
+    }
 }
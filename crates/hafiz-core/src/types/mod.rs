@@ -1,5 +1,7 @@
 //! Core types for Hafiz
 
+mod access_point;
+mod account;
 mod acl;
 mod bucket;
 mod common;
@@ -15,6 +17,8 @@ mod storage;
 mod user;
 
 // Re-export everything except modules with duplicates
+pub use access_point::*;
+pub use account::*;
 pub use acl::*;
 pub use bucket::*;
 pub use common::*;
@@ -29,11 +33,14 @@ pub use storage::*;
 
 // Re-export from replication
 pub use replication::{
-    ClusterConfig, ClusterMessage, ClusterNode, ClusterNodeStatus, ClusterStats,
-    ConflictResolution, ConsistencyLevel, NodeId, NodeRole, NodeStats,
-    ReplicationEvent, ReplicationEventType, ReplicationMode,
+    ACHIEVED_CONSISTENCY_HEADER, CONSISTENCY_HEADER, CONSISTENCY_REPLICAS_HEADER,
+    AntiEntropyStats, ClusterConfig, ClusterMessage, ClusterNode, ClusterNodeStatus, ClusterStats,
+    ClusterTransportProtocol, ConflictResolution, ConsistencyLevel, ConsistencyOutcome,
+    MetadataConsensus, NodeId, NodeRole, NodeStats, RebalanceProgress, RedirectMode, ReplicationEvent, ReplicationEventType, ReplicationMode,
     ReplicationProgress, ReplicationRule, ReplicationStatus,
 };
 
 // Re-export from user (except Owner which conflicts with acl)
-pub use user::{Credentials, User};
+pub use user::{
+    AdminRole, AdminRoleGrant, Credentials, DirectoryUser, RetiringKey, SftpKey, SftpUser, TemporaryCredentials, User,
+};
@@ -4,6 +4,7 @@ use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+use super::lifecycle::StorageClass;
 use super::user::Owner;
 
 /// Version ID for versioned objects
@@ -15,6 +16,10 @@ pub const MAX_TAGS_PER_OBJECT: usize = 10;
 pub const MAX_TAG_KEY_LENGTH: usize = 128;
 /// Maximum tag value length
 pub const MAX_TAG_VALUE_LENGTH: usize = 256;
+/// Maximum total size of user metadata: the bytes of every key and value
+pub const MAX_USER_METADATA_SIZE: usize = 2 * 1024;
+/// Maximum total size of the headers of a request that writes an object
+pub const MAX_REQUEST_HEADER_SIZE: usize = 8 * 1024;
 
 /// Object tag
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -151,6 +156,14 @@ pub struct EncryptionInfo {
     pub data_nonce: Option<String>,
     /// MD5 of customer key (for SSE-C)
     pub sse_customer_key_md5: Option<String>,
+    /// Plaintext bytes per encrypted segment; `None` for data sealed as
+    /// one message
+    #[serde(default)]
+    pub segment_size: Option<usize>,
+    /// Account key the DEK is encrypted under (for SSE-S3), like
+    /// `acme/v2`; `None` for objects encrypted under the master key itself
+    #[serde(default)]
+    pub key_id: Option<String>,
 }
 
 impl EncryptionInfo {
@@ -161,6 +174,12 @@ impl EncryptionInfo {
     pub fn is_encrypted(&self) -> bool {
         self.encryption_type != EncryptionType::None
     }
+
+    /// Whether the stored data is ciphertext; objects marked encrypted by
+    /// releases that did not encrypt yet hold their plaintext
+    pub fn is_sealed(&self) -> bool {
+        self.is_encrypted() && self.data_nonce.is_some()
+    }
 }
 
 /// Simple Object representation for API layer
@@ -209,6 +228,17 @@ pub struct ObjectInternal {
     /// Encryption information (None if not encrypted)
     #[serde(default)]
     pub encryption: EncryptionInfo,
+    /// Sizes of the parts of the multipart upload that created the object,
+    /// in order; empty for objects uploaded in one piece
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub part_sizes: Vec<i64>,
+    /// Storage class the object was written with, e.g. `STANDARD`
+    #[serde(default = "default_storage_class")]
+    pub storage_class: String,
+}
+
+fn default_storage_class() -> String {
+    StorageClass::Standard.as_str().to_string()
 }
 
 impl ObjectInternal {
@@ -225,6 +255,8 @@ impl ObjectInternal {
             is_latest: true,
             is_delete_marker: false,
             encryption: EncryptionInfo::none(),
+            part_sizes: Vec::new(),
+            storage_class: default_storage_class(),
         }
     }
 
@@ -238,6 +270,18 @@ impl ObjectInternal {
         self
     }
 
+    pub fn with_storage_class(mut self, storage_class: StorageClass) -> Self {
+        self.storage_class = storage_class.as_str().to_string();
+        self
+    }
+
+    /// Whether the object's data is on the archive backend
+    pub fn is_archived(&self) -> bool {
+        self.storage_class
+            .parse::<StorageClass>()
+            .is_ok_and(|class| class.is_archive())
+    }
+
     pub fn as_delete_marker(bucket: String, key: String, version_id: String) -> Self {
         Self {
             bucket,
@@ -251,7 +295,36 @@ impl ObjectInternal {
             is_latest: true,
             is_delete_marker: true,
             encryption: EncryptionInfo::none(),
+            part_sizes: Vec::new(),
+            storage_class: default_storage_class(),
+        }
+    }
+
+    /// Number of parts the object was uploaded in
+    pub fn parts_count(&self) -> usize {
+        self.part_sizes.len().max(1)
+    }
+
+    /// First and last byte of a part of the upload that created the object.
+    /// An object uploaded in one piece is its own part 1.
+    pub fn part_range(&self, part_number: i32) -> Result<(i64, i64), crate::Error> {
+        let invalid = || {
+            crate::Error::InvalidPartNumber(format!(
+                "part {} of {}",
+                part_number,
+                self.parts_count()
+            ))
+        };
+        let index = usize::try_from(part_number)
+            .ok()
+            .and_then(|n| n.checked_sub(1))
+            .ok_or_else(invalid)?;
+        if self.part_sizes.is_empty() {
+            return if index == 0 { Ok((0, self.size - 1)) } else { Err(invalid()) };
         }
+        let size = *self.part_sizes.get(index).ok_or_else(invalid)?;
+        let start: i64 = self.part_sizes[..index].iter().sum();
+        Ok((start, start + size - 1))
     }
 
     pub fn validate_key(key: &str) -> Result<(), crate::Error> {
@@ -267,6 +340,22 @@ impl ObjectInternal {
         Ok(())
     }
 
+    /// Check user metadata against the limits S3 applies: US-ASCII keys
+    /// and at most [`MAX_USER_METADATA_SIZE`] bytes of keys and values
+    pub fn validate_metadata(metadata: &HashMap<String, String>) -> Result<(), crate::Error> {
+        if let Some(key) = metadata.keys().find(|k| k.is_empty() || !k.is_ascii()) {
+            return Err(crate::Error::InvalidArgument(format!(
+                "Metadata key must be non-empty US-ASCII: {:?}",
+                key
+            )));
+        }
+        let size: usize = metadata.iter().map(|(k, v)| k.len() + v.len()).sum();
+        if size > MAX_USER_METADATA_SIZE {
+            return Err(crate::Error::MetadataTooLarge);
+        }
+        Ok(())
+    }
+
     /// Generate a new version ID
     pub fn generate_version_id() -> String {
         use std::time::{SystemTime, UNIX_EPOCH};
@@ -339,6 +428,12 @@ pub struct ListObjectsResult {
     pub common_prefixes: Vec<String>,
     pub continuation_token: Option<String>,
     pub next_continuation_token: Option<String>,
+    /// V2 `start-after`, echoed back
+    pub start_after: Option<String>,
+    /// `url` when keys and prefixes in the response are URL-encoded
+    pub encoding_type: Option<String>,
+    /// Owner listed with each object: always in V1, with `fetch-owner` in V2
+    pub owner: Option<super::acl::Owner>,
 }
 
 /// Result for ListObjectVersions
@@ -418,3 +513,45 @@ impl ByteRange {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_part_range() {
+        let mut object = ObjectInternal::new("b".into(), "k".into(), 25, "e".into(), "t".into());
+        assert_eq!(object.parts_count(), 1);
+        assert_eq!(object.part_range(1).unwrap(), (0, 24));
+        assert!(object.part_range(2).is_err());
+
+        object.part_sizes = vec![10, 10, 5];
+        assert_eq!(object.parts_count(), 3);
+        assert_eq!(object.part_range(1).unwrap(), (0, 9));
+        assert_eq!(object.part_range(3).unwrap(), (20, 24));
+        assert!(object.part_range(0).is_err());
+        assert!(object.part_range(4).is_err());
+        assert!(object.part_range(-1).is_err());
+    }
+
+    #[test]
+    fn test_validate_metadata() {
+        let mut metadata = HashMap::new();
+        assert!(ObjectInternal::validate_metadata(&metadata).is_ok());
+
+        metadata.insert("owner".to_string(), "a".repeat(MAX_USER_METADATA_SIZE - 5));
+        assert!(ObjectInternal::validate_metadata(&metadata).is_ok());
+        metadata.insert("x".to_string(), String::new());
+        assert!(matches!(
+            ObjectInternal::validate_metadata(&metadata),
+            Err(crate::Error::MetadataTooLarge)
+        ));
+
+        let mut metadata = HashMap::new();
+        metadata.insert("größe".to_string(), "1".to_string());
+        assert!(matches!(
+            ObjectInternal::validate_metadata(&metadata),
+            Err(crate::Error::InvalidArgument(_))
+        ));
+    }
+}
@@ -155,6 +155,23 @@ impl ObjectRetention {
     pub fn can_modify(&self, has_governance_bypass: bool) -> bool {
         self.can_delete(has_governance_bypass)
     }
+
+    /// Check if this retention may be replaced by `new`
+    ///
+    /// Active retention can always be extended, and GOVERNANCE can be
+    /// raised to COMPLIANCE. Anything else needs the lock to be removable.
+    pub fn allows_update(&self, new: &ObjectRetention, has_governance_bypass: bool) -> bool {
+        if self.can_modify(has_governance_bypass) {
+            return true;
+        }
+
+        let extends = match (self.retain_until(), new.retain_until()) {
+            (Some(current), Some(requested)) => requested >= current,
+            _ => false,
+        };
+        let keeps_mode = self.mode == new.mode || new.mode == RetentionMode::Compliance;
+        extends && keeps_mode
+    }
 }
 
 // ============================================================================
@@ -554,6 +571,25 @@ mod tests {
         assert!(!retention.can_delete(true)); // Compliance mode - no bypass
     }
 
+    #[test]
+    fn test_locked_retention_can_only_be_extended() {
+        let until = Utc::now() + Duration::days(30);
+        let compliance = ObjectRetention::new(RetentionMode::Compliance, until);
+
+        let longer = ObjectRetention::new(RetentionMode::Compliance, until + Duration::days(1));
+        let shorter = ObjectRetention::new(RetentionMode::Compliance, until - Duration::days(1));
+        let weaker = ObjectRetention::new(RetentionMode::Governance, until + Duration::days(1));
+        assert!(compliance.allows_update(&longer, true));
+        assert!(!compliance.allows_update(&shorter, true));
+        assert!(!compliance.allows_update(&weaker, true));
+
+        let governance = ObjectRetention::new(RetentionMode::Governance, until);
+        let stricter = ObjectRetention::new(RetentionMode::Compliance, until);
+        assert!(governance.allows_update(&stricter, false));
+        assert!(!governance.allows_update(&shorter, false));
+        assert!(governance.allows_update(&shorter, true));
+    }
+
     #[test]
     fn test_object_lock_state() {
         let mut state = ObjectLockState::default();
@@ -57,6 +57,14 @@ impl PolicyDocument {
             PolicyEffect::Deny // Default deny
         }
     }
+
+    /// Whether a statement explicitly denies the request. Unlike the
+    /// default deny of `evaluate`, this overrides grants made by ACLs.
+    pub fn explicitly_denies(&self, request: &PolicyRequest) -> bool {
+        self.statement
+            .iter()
+            .any(|s| matches!(s.evaluate(request), StatementResult::ExplicitDeny))
+    }
 }
 
 impl Default for PolicyDocument {
@@ -348,8 +356,15 @@ pub mod actions {
     pub const DELETE_OBJECT: &str = "s3:DeleteObject";
     pub const GET_OBJECT_ACL: &str = "s3:GetObjectAcl";
     pub const PUT_OBJECT_ACL: &str = "s3:PutObjectAcl";
+    pub const GET_OBJECT_TAGGING: &str = "s3:GetObjectTagging";
+    pub const PUT_OBJECT_TAGGING: &str = "s3:PutObjectTagging";
+    pub const DELETE_OBJECT_TAGGING: &str = "s3:DeleteObjectTagging";
     pub const LIST_MULTIPART_UPLOAD_PARTS: &str = "s3:ListMultipartUploadParts";
     pub const ABORT_MULTIPART_UPLOAD: &str = "s3:AbortMultipartUpload";
+
+    // Listing
+    pub const LIST_BUCKET_VERSIONS: &str = "s3:ListBucketVersions";
+    pub const LIST_BUCKET_MULTIPART_UPLOADS: &str = "s3:ListBucketMultipartUploads";
 }
 
 /// Simple wildcard matching (supports * and ?)
@@ -25,6 +25,15 @@ pub struct PresignedRequest {
     pub signed_headers: Option<Vec<(String, String)>>,
     /// Version ID for versioned objects
     pub version_id: Option<String>,
+    /// Largest body a PUT may send, in bytes
+    #[serde(default)]
+    pub max_content_length: Option<u64>,
+    /// Whether `content_type` is enforced on PUT rather than only suggested
+    #[serde(default)]
+    pub pin_content_type: bool,
+    /// Whether the URL can be used only once
+    #[serde(default)]
+    pub single_use: bool,
 }
 
 impl Default for PresignedRequest {
@@ -38,6 +47,9 @@ impl Default for PresignedRequest {
             content_md5: None,
             signed_headers: None,
             version_id: None,
+            max_content_length: None,
+            pin_content_type: false,
+            single_use: false,
         }
     }
 }
@@ -187,6 +199,24 @@ impl PresignedRequestBuilder {
         self
     }
 
+    /// Limit the body of a PUT to `bytes`
+    pub fn max_content_length(mut self, bytes: u64) -> Self {
+        self.request.max_content_length = Some(bytes);
+        self
+    }
+
+    /// Require the content type set with [`Self::content_type`] on PUT
+    pub fn pin_content_type(mut self) -> Self {
+        self.request.pin_content_type = true;
+        self
+    }
+
+    /// Make the URL usable only once
+    pub fn single_use(mut self) -> Self {
+        self.request.single_use = true;
+        self
+    }
+
     /// Build the request
     pub fn build(self) -> Result<PresignedRequest, String> {
         if self.request.bucket.is_empty() {
@@ -195,6 +225,14 @@ impl PresignedRequestBuilder {
         if self.request.key.is_empty() {
             return Err("Object key is required".to_string());
         }
+        if self.request.pin_content_type && self.request.content_type.is_none() {
+            return Err("A pinned content type must be set".to_string());
+        }
+        if self.request.method != PresignedMethod::Put
+            && (self.request.max_content_length.is_some() || self.request.pin_content_type)
+        {
+            return Err("Content constraints only apply to PUT".to_string());
+        }
 
         PresignedLimits::validate_expires(self.request.expires_in)?;
 
@@ -227,6 +265,37 @@ mod tests {
         assert_eq!(request.expires_in, 3600);
     }
 
+    #[test]
+    fn test_presigned_constraints() {
+        let request = PresignedRequestBuilder::new()
+            .method(PresignedMethod::Put)
+            .bucket("my-bucket")
+            .key("upload.bin")
+            .max_content_length(1024)
+            .content_type("image/png")
+            .pin_content_type()
+            .single_use()
+            .build()
+            .unwrap();
+        assert_eq!(request.max_content_length, Some(1024));
+        assert!(request.pin_content_type && request.single_use);
+
+        let unpinned = PresignedRequestBuilder::new()
+            .method(PresignedMethod::Put)
+            .bucket("my-bucket")
+            .key("upload.bin")
+            .pin_content_type()
+            .build();
+        assert!(unpinned.is_err());
+
+        let get = PresignedRequestBuilder::new()
+            .bucket("my-bucket")
+            .key("upload.bin")
+            .max_content_length(1024)
+            .build();
+        assert!(get.is_err());
+    }
+
     #[test]
     fn test_presigned_limits() {
         assert!(PresignedLimits::validate_expires(0).is_err());
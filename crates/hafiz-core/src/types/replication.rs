@@ -37,6 +37,97 @@ pub enum ConsistencyLevel {
     All,
 }
 
+impl ConsistencyLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConsistencyLevel::One => "one",
+            ConsistencyLevel::Quorum => "quorum",
+            ConsistencyLevel::All => "all",
+        }
+    }
+
+    /// Number of replicas that must acknowledge out of `replicas`
+    pub fn required_acks(&self, replicas: usize) -> usize {
+        match self {
+            ConsistencyLevel::One => replicas.min(1),
+            ConsistencyLevel::Quorum => replicas / 2 + 1,
+            ConsistencyLevel::All => replicas,
+        }
+        .min(replicas)
+    }
+
+    /// Strongest level met by `acks` acknowledgements out of `replicas`
+    pub fn achieved(acks: usize, replicas: usize) -> Option<Self> {
+        [ConsistencyLevel::All, ConsistencyLevel::Quorum, ConsistencyLevel::One]
+            .into_iter()
+            .find(|level| acks > 0 && acks >= level.required_acks(replicas))
+    }
+}
+
+impl std::fmt::Display for ConsistencyLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ConsistencyLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "one" => Ok(ConsistencyLevel::One),
+            "quorum" => Ok(ConsistencyLevel::Quorum),
+            "all" => Ok(ConsistencyLevel::All),
+            other => Err(format!("Unknown consistency level: {}", other)),
+        }
+    }
+}
+
+/// Request header selecting the consistency level for one request
+pub const CONSISTENCY_HEADER: &str = "x-hafiz-consistency";
+
+/// Response header with the consistency level that was reached
+pub const ACHIEVED_CONSISTENCY_HEADER: &str = "x-hafiz-consistency-achieved";
+
+/// Response header with the acknowledging replicas, as `acks/replicas`
+pub const CONSISTENCY_REPLICAS_HEADER: &str = "x-hafiz-consistency-replicas";
+
+/// Result of a read or write coordinated at some consistency level
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConsistencyOutcome {
+    /// Level the request asked for
+    pub requested: ConsistencyLevel,
+    /// Replicas that acknowledged the write or agreed on the read
+    pub acks: usize,
+    /// Replicas of the object
+    pub replicas: usize,
+}
+
+impl ConsistencyOutcome {
+    pub fn new(requested: ConsistencyLevel, acks: usize, replicas: usize) -> Self {
+        Self {
+            requested,
+            acks,
+            replicas,
+        }
+    }
+
+    /// Acknowledgements the requested level needs
+    pub fn required(&self) -> usize {
+        self.requested.required_acks(self.replicas)
+    }
+
+    /// Whether the requested level was met
+    pub fn is_satisfied(&self) -> bool {
+        self.acks >= self.required()
+    }
+
+    /// Strongest level the acknowledgements satisfy
+    pub fn achieved(&self) -> Option<ConsistencyLevel> {
+        ConsistencyLevel::achieved(self.acks, self.replicas)
+    }
+}
+
 /// Conflict resolution strategy
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
@@ -52,6 +143,53 @@ pub enum ConflictResolution {
     Custom,
 }
 
+/// How bucket/object metadata is kept consistent across nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MetadataConsensus {
+    /// Each node owns its metadata; changes propagate via replication events
+    #[default]
+    None,
+    /// Metadata writes go through a Raft log and are linearizable
+    Raft,
+}
+
+/// Wire protocol used between cluster nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ClusterTransportProtocol {
+    /// JSON over HTTP on the cluster endpoint
+    #[default]
+    Http,
+    /// gRPC with streaming object transfer, falling back to HTTP for
+    /// peers that do not speak it
+    Grpc,
+}
+
+/// Which object requests are answered with a redirect to a node holding
+/// the object, rather than proxied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RedirectMode {
+    /// Always proxy
+    #[default]
+    Off,
+    /// Redirect requests to buckets with transfer acceleration enabled
+    Accelerated,
+    /// Redirect requests to every bucket
+    All,
+}
+
+impl RedirectMode {
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "accelerated" => Self::Accelerated,
+            "all" => Self::All,
+            _ => Self::Off,
+        }
+    }
+}
+
 /// Node status in the cluster
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "lowercase")]
@@ -95,6 +233,9 @@ pub struct ClusterNode {
     pub endpoint: String,
     /// Internal cluster communication endpoint
     pub cluster_endpoint: String,
+    /// gRPC endpoint, if the node serves the gRPC cluster transport
+    #[serde(default)]
+    pub grpc_endpoint: Option<String>,
     /// Node role
     pub role: NodeRole,
     /// Current status
@@ -103,6 +244,9 @@ pub struct ClusterNode {
     pub region: Option<String>,
     /// Node zone within region
     pub zone: Option<String>,
+    /// Rack within zone
+    #[serde(default)]
+    pub rack: Option<String>,
     /// Weight for load balancing (higher = more traffic)
     pub weight: u32,
     /// Node metadata/labels
@@ -123,10 +267,12 @@ impl ClusterNode {
             name,
             endpoint,
             cluster_endpoint,
+            grpc_endpoint: None,
             role: NodeRole::Primary,
             status: ClusterNodeStatus::Starting,
             region: None,
             zone: None,
+            rack: None,
             weight: 100,
             labels: HashMap::new(),
             joined_at: now,
@@ -181,6 +327,91 @@ pub struct ClusterConfig {
     pub cluster_tls_key: Option<String>,
     /// Path to cluster CA certificate
     pub cluster_ca_cert: Option<String>,
+    /// Secret peers present on the cluster listener
+    #[serde(default, skip_serializing)]
+    pub shared_secret: Option<String>,
+    /// Metadata consensus mode
+    #[serde(default)]
+    pub metadata_consensus: MetadataConsensus,
+    /// Raft election timeout in milliseconds (randomized up to 2x)
+    #[serde(default = "default_raft_election_timeout_ms")]
+    pub raft_election_timeout_ms: u64,
+    /// Raft leader heartbeat interval in milliseconds
+    #[serde(default = "default_raft_heartbeat_interval_ms")]
+    pub raft_heartbeat_interval_ms: u64,
+    /// Number of applied log entries after which a snapshot is taken
+    #[serde(default = "default_raft_snapshot_threshold")]
+    pub raft_snapshot_threshold: u64,
+    /// Directory for the Raft log and snapshots; required for Raft
+    #[serde(default)]
+    pub raft_data_dir: Option<String>,
+    /// Raft voting members; quorum is a majority of them
+    #[serde(default)]
+    pub raft_voters: Vec<NodeId>,
+    /// Assign objects to nodes with a consistent hash ring
+    #[serde(default)]
+    pub placement_enabled: bool,
+    /// Virtual nodes per physical node on the hash ring
+    #[serde(default = "default_placement_virtual_nodes")]
+    pub placement_virtual_nodes: u32,
+    /// Run background anti-entropy comparisons between replicas
+    #[serde(default)]
+    pub anti_entropy_enabled: bool,
+    /// Seconds between anti-entropy runs
+    #[serde(default = "default_anti_entropy_interval_secs")]
+    pub anti_entropy_interval_secs: u64,
+    /// Directory for hinted handoff files (hints kept in memory if unset)
+    #[serde(default)]
+    pub hint_dir: Option<String>,
+    /// Seconds a hint is kept before it is dropped
+    #[serde(default = "default_hint_ttl_secs")]
+    pub hint_ttl_secs: u64,
+    /// Maximum hints queued per unavailable node
+    #[serde(default = "default_max_hints_per_node")]
+    pub max_hints_per_node: usize,
+    /// Preferred wire protocol between nodes
+    #[serde(default)]
+    pub cluster_transport: ClusterTransportProtocol,
+    /// This node's gRPC endpoint (gRPC server disabled if unset)
+    #[serde(default)]
+    pub grpc_endpoint: Option<String>,
+    /// Failure-domain zone of this node
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// Failure-domain rack of this node
+    #[serde(default)]
+    pub rack: Option<String>,
+    /// Object requests redirected to a node holding the object
+    #[serde(default)]
+    pub redirect_mode: RedirectMode,
+}
+
+fn default_placement_virtual_nodes() -> u32 {
+    128
+}
+
+fn default_anti_entropy_interval_secs() -> u64 {
+    3600
+}
+
+fn default_hint_ttl_secs() -> u64 {
+    3 * 3600
+}
+
+fn default_max_hints_per_node() -> usize {
+    100_000
+}
+
+fn default_raft_election_timeout_ms() -> u64 {
+    1000
+}
+
+fn default_raft_heartbeat_interval_ms() -> u64 {
+    200
+}
+
+fn default_raft_snapshot_threshold() -> u64 {
+    10_000
 }
 
 impl Default for ClusterConfig {
@@ -203,6 +434,25 @@ impl Default for ClusterConfig {
             cluster_tls_cert: None,
             cluster_tls_key: None,
             cluster_ca_cert: None,
+            shared_secret: None,
+            metadata_consensus: MetadataConsensus::None,
+            raft_election_timeout_ms: default_raft_election_timeout_ms(),
+            raft_heartbeat_interval_ms: default_raft_heartbeat_interval_ms(),
+            raft_snapshot_threshold: default_raft_snapshot_threshold(),
+            raft_data_dir: None,
+            raft_voters: Vec::new(),
+            placement_enabled: false,
+            placement_virtual_nodes: default_placement_virtual_nodes(),
+            anti_entropy_enabled: false,
+            anti_entropy_interval_secs: default_anti_entropy_interval_secs(),
+            hint_dir: None,
+            hint_ttl_secs: default_hint_ttl_secs(),
+            max_hints_per_node: default_max_hints_per_node(),
+            cluster_transport: ClusterTransportProtocol::Http,
+            grpc_endpoint: None,
+            zone: None,
+            rack: None,
+            redirect_mode: RedirectMode::Off,
         }
     }
 }
@@ -224,6 +474,9 @@ pub struct ReplicationRule {
     pub prefix_filter: Option<String>,
     /// Object tag filters
     pub tag_filters: HashMap<String, String>,
+    /// Tags the source bucket must carry for the rule to apply
+    #[serde(default)]
+    pub bucket_tag_filters: HashMap<String, String>,
     /// Replication mode for this rule
     pub mode: ReplicationMode,
     /// Priority (lower = higher priority)
@@ -249,6 +502,7 @@ impl ReplicationRule {
             target_nodes: Vec::new(),
             prefix_filter: None,
             tag_filters: HashMap::new(),
+            bucket_tag_filters: HashMap::new(),
             mode: ReplicationMode::Async,
             priority: 0,
             replicate_deletes: true,
@@ -276,6 +530,11 @@ impl ReplicationRule {
 
         true
     }
+
+    /// Check if the source bucket's tags satisfy this rule
+    pub fn matches_bucket(&self, bucket_tags: &HashMap<String, String>) -> bool {
+        super::bucket_tags_match(&self.bucket_tag_filters, bucket_tags)
+    }
 }
 
 /// Replication event types
@@ -421,6 +680,52 @@ pub struct ClusterStats {
     pub failed_replications: u64,
     /// Replication lag in seconds (max across all nodes)
     pub replication_lag_secs: u64,
+    /// Progress of the current or last data rebalance
+    #[serde(default)]
+    pub rebalance: RebalanceProgress,
+    /// Anti-entropy comparisons and repairs
+    #[serde(default)]
+    pub anti_entropy: AntiEntropyStats,
+}
+
+/// Progress of moving objects after the placement ring changed
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RebalanceProgress {
+    /// Whether a rebalance is currently running
+    pub in_progress: bool,
+    /// Objects examined so far
+    pub objects_scanned: u64,
+    /// Objects copied to their new owners
+    pub objects_moved: u64,
+    /// Objects that could not be copied
+    pub objects_failed: u64,
+    /// Bytes copied to new owners
+    pub bytes_moved: u64,
+    /// When the rebalance started
+    pub started_at: Option<DateTime<Utc>>,
+    /// When the rebalance finished
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Counters of the anti-entropy service
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AntiEntropyStats {
+    /// Whether a comparison is currently running
+    pub in_progress: bool,
+    /// Completed comparison runs
+    pub runs: u64,
+    /// Bucket/peer pairs compared
+    pub buckets_compared: u64,
+    /// Merkle tree leaves that differed from a peer
+    pub divergent_leaves: u64,
+    /// Objects found missing or stale locally
+    pub divergent_objects: u64,
+    /// Objects repaired from a peer
+    pub objects_repaired: u64,
+    /// Repairs that failed
+    pub repair_failures: u64,
+    /// When the last run finished
+    pub last_run_at: Option<DateTime<Utc>>,
 }
 
 /// Message types for cluster communication
@@ -471,6 +776,11 @@ pub enum ClusterMessage {
         nodes: Vec<ClusterNode>,
         replication_rules: Vec<ReplicationRule>,
     },
+    /// Operator changed a node's status (drain, return to service, decommission)
+    NodeStatusChange {
+        node_id: NodeId,
+        status: ClusterNodeStatus,
+    },
 }
 
 /// Statistics for a single node
@@ -514,6 +824,7 @@ mod tests {
                 m.insert("env".to_string(), "prod".to_string());
                 m
             },
+            bucket_tag_filters: HashMap::new(),
             mode: ReplicationMode::Async,
             priority: 0,
             replicate_deletes: true,
@@ -535,6 +846,42 @@ mod tests {
         assert!(!rule.matches("logs/app.log", &tags));
     }
 
+    #[test]
+    fn test_replication_rule_bucket_tags() {
+        let mut rule = ReplicationRule::new("source".into(), "dest".into());
+        let mut bucket_tags = HashMap::new();
+        assert!(rule.matches_bucket(&bucket_tags));
+
+        rule.bucket_tag_filters
+            .insert("replicate".to_string(), "true".to_string());
+        assert!(!rule.matches_bucket(&bucket_tags));
+
+        bucket_tags.insert("replicate".to_string(), "true".to_string());
+        assert!(rule.matches_bucket(&bucket_tags));
+    }
+
+    #[test]
+    fn test_consistency_level_acks() {
+        assert_eq!(ConsistencyLevel::One.required_acks(3), 1);
+        assert_eq!(ConsistencyLevel::Quorum.required_acks(3), 2);
+        assert_eq!(ConsistencyLevel::Quorum.required_acks(4), 3);
+        assert_eq!(ConsistencyLevel::All.required_acks(3), 3);
+        assert_eq!(ConsistencyLevel::Quorum.required_acks(1), 1);
+
+        assert_eq!(ConsistencyLevel::achieved(3, 3), Some(ConsistencyLevel::All));
+        assert_eq!(ConsistencyLevel::achieved(2, 3), Some(ConsistencyLevel::Quorum));
+        assert_eq!(ConsistencyLevel::achieved(1, 3), Some(ConsistencyLevel::One));
+        assert_eq!(ConsistencyLevel::achieved(0, 3), None);
+
+        assert_eq!("QUORUM".parse::<ConsistencyLevel>(), Ok(ConsistencyLevel::Quorum));
+        assert!("most".parse::<ConsistencyLevel>().is_err());
+
+        let outcome = ConsistencyOutcome::new(ConsistencyLevel::All, 2, 3);
+        assert!(!outcome.is_satisfied());
+        assert_eq!(outcome.required(), 3);
+        assert_eq!(outcome.achieved(), Some(ConsistencyLevel::Quorum));
+    }
+
     #[test]
     fn test_cluster_node_status() {
         let mut node = ClusterNode::new(
@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -51,6 +52,13 @@ pub struct Credentials {
     pub created_at: DateTime<Utc>,
     pub last_used: Option<DateTime<Utc>>,
     pub policies: Vec<String>,
+    /// Account the user belongs to
+    #[serde(default = "default_account")]
+    pub account_id: String,
+}
+
+fn default_account() -> String {
+    super::DEFAULT_ACCOUNT.to_string()
 }
 
 impl Credentials {
@@ -64,6 +72,7 @@ impl Credentials {
             created_at: Utc::now(),
             last_used: None,
             policies: Vec::new(),
+            account_id: default_account(),
         }
     }
 
@@ -81,10 +90,185 @@ impl Credentials {
             } else {
                 Vec::new()
             },
+            account_id: default_account(),
+        }
+    }
+}
+
+/// A user's previous key pair, still accepted until the rotation grace
+/// period ends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetiringKey {
+    pub access_key: String,
+    pub secret_key: String,
+    /// Current access key of the user the key belongs to
+    pub user_access_key: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// Role of an admin API caller, deciding which endpoints it may use
+///
+/// Every role may read; the others add write access to one area, and
+/// [`AdminRole::SuperAdmin`] may do everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdminRole {
+    /// Dashboards, statistics and settings, read-only
+    Viewer,
+    /// Bucket settings, snapshots, access points, batch jobs and
+    /// pre-signed URLs
+    BucketOperator,
+    /// Users, keys, SFTP logins, directory sync and admin roles
+    SecurityAdmin,
+    /// Everything, including accounts, server maintenance and metadata
+    /// dumps
+    SuperAdmin,
+}
+
+impl AdminRole {
+    pub const ALL: [AdminRole; 4] = [
+        Self::Viewer,
+        Self::BucketOperator,
+        Self::SecurityAdmin,
+        Self::SuperAdmin,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Viewer => "viewer",
+            Self::BucketOperator => "bucket_operator",
+            Self::SecurityAdmin => "security_admin",
+            Self::SuperAdmin => "super_admin",
+        }
+    }
+
+    /// Whether this role may use endpoints that require `required`
+    pub fn permits(&self, required: AdminRole) -> bool {
+        *self == required || *self == Self::SuperAdmin || required == Self::Viewer
+    }
+
+    /// Role carried by a set of policies: `admin` is a super admin, and
+    /// `admin:<role>` grants that role, so directory and identity provider
+    /// groups can map to roles
+    pub fn from_policies(policies: &[String]) -> Option<Self> {
+        if policies.iter().any(|p| p == "admin") {
+            return Some(Self::SuperAdmin);
+        }
+        policies
+            .iter()
+            .filter_map(|p| p.strip_prefix("admin:")?.parse().ok())
+            .reduce(|a: AdminRole, b| if b.permits(a) { b } else { a })
+    }
+}
+
+impl fmt::Display for AdminRole {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for AdminRole {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let normalized = s.to_ascii_lowercase().replace('-', "_");
+        Self::ALL
+            .into_iter()
+            .find(|role| role.as_str() == normalized)
+            .ok_or_else(|| {
+                format!(
+                    "Unknown admin role: {} (expected viewer, bucket_operator, security_admin or super_admin)",
+                    s
+                )
+            })
+    }
+}
+
+/// An admin role given to a user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminRoleGrant {
+    /// Access key of the user; the grant follows key rotations
+    pub access_key: String,
+    pub role: AdminRole,
+    pub granted_at: DateTime<Utc>,
+}
+
+/// Short-lived credentials issued by STS for a federated identity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemporaryCredentials {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: String,
+    /// Identity provider subject the credentials were issued to
+    pub subject: String,
+    /// Username from the identity provider
+    pub username: String,
+    pub policies: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TemporaryCredentials {
+    pub fn is_expired(&self) -> bool {
+        Utc::now() >= self.expires_at
+    }
+
+    /// View as regular credentials for code that authorizes by access key
+    pub fn to_credentials(&self) -> Credentials {
+        Credentials {
+            access_key: self.access_key.clone(),
+            secret_key: self.secret_key.clone(),
+            name: Some(self.username.clone()),
+            email: None,
+            enabled: !self.is_expired(),
+            created_at: self.created_at,
+            last_used: None,
+            policies: self.policies.clone(),
+            account_id: default_account(),
         }
     }
 }
 
+/// Link between a directory (LDAP) account and the Hafiz user provisioned for it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DirectoryUser {
+    /// Username in the directory
+    pub username: String,
+    /// Access key of the provisioned Hafiz user
+    pub access_key: String,
+    pub dn: String,
+    pub groups: Vec<String>,
+    /// Policies mapped from the groups at the last sync
+    pub policies: Vec<String>,
+    pub synced_at: DateTime<Utc>,
+}
+
+/// A login of the SFTP gateway, acting as a Hafiz user inside one bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftpUser {
+    /// SSH user name
+    pub username: String,
+    /// Access key of the Hafiz user the session acts as
+    pub access_key: String,
+    /// Directory the session is confined to: `bucket` or `bucket/prefix`
+    pub home: String,
+    pub keys: Vec<SftpKey>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A public key an SFTP login authenticates with
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SftpKey {
+    /// `SHA256:` fingerprint, as printed by `ssh-keygen -l`
+    pub fingerprint: String,
+    /// Key in `authorized_keys` form, without the comment
+    pub public_key: String,
+    pub comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Owner {
     pub id: String,
@@ -99,3 +283,50 @@ impl From<User> for Owner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admin_role_permits() {
+        assert!(AdminRole::Viewer.permits(AdminRole::Viewer));
+        assert!(!AdminRole::Viewer.permits(AdminRole::BucketOperator));
+        assert!(AdminRole::BucketOperator.permits(AdminRole::Viewer));
+        assert!(!AdminRole::BucketOperator.permits(AdminRole::SecurityAdmin));
+        assert!(!AdminRole::SecurityAdmin.permits(AdminRole::BucketOperator));
+        assert!(!AdminRole::SecurityAdmin.permits(AdminRole::SuperAdmin));
+        for role in AdminRole::ALL {
+            assert!(AdminRole::SuperAdmin.permits(role));
+        }
+    }
+
+    #[test]
+    fn test_admin_role_parse() {
+        assert_eq!("bucket-operator".parse(), Ok(AdminRole::BucketOperator));
+        assert_eq!("Security_Admin".parse(), Ok(AdminRole::SecurityAdmin));
+        for role in AdminRole::ALL {
+            assert_eq!(role.as_str().parse(), Ok(role));
+        }
+        assert!("owner".parse::<AdminRole>().is_err());
+    }
+
+    #[test]
+    fn test_admin_role_from_policies() {
+        let policies = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(AdminRole::from_policies(&policies(&["readwrite"])), None);
+        assert_eq!(
+            AdminRole::from_policies(&policies(&["readwrite", "admin"])),
+            Some(AdminRole::SuperAdmin)
+        );
+        assert_eq!(
+            AdminRole::from_policies(&policies(&["admin:viewer", "admin:security-admin"])),
+            Some(AdminRole::SecurityAdmin)
+        );
+        assert_eq!(
+            AdminRole::from_policies(&policies(&["admin:viewer", "admin:nobody"])),
+            Some(AdminRole::Viewer)
+        );
+    }
+}
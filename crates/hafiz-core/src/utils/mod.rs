@@ -7,6 +7,23 @@ pub fn generate_request_id() -> String {
     Uuid::new_v4().to_string().replace("-", "").to_uppercase()
 }
 
+/// ID of this host for the `x-amz-id-2` header and `HostId` of error bodies
+///
+/// It is the base64 of the host name, so an error reported by a client can
+/// be traced to the node that served it.
+pub fn host_id() -> &'static str {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use once_cell::sync::Lazy;
+
+    static HOST_ID: Lazy<String> = Lazy::new(|| {
+        let host = hostname::get()
+            .map(|h| h.to_string_lossy().to_string())
+            .unwrap_or_else(|_| "hafiz".to_string());
+        STANDARD.encode(host)
+    });
+    &HOST_ID
+}
+
 /// Generate an ETag from content hash
 pub fn generate_etag(md5_hash: &str) -> String {
     format!("\"{}\"", md5_hash)
@@ -17,6 +34,22 @@ pub fn parse_etag(etag: &str) -> String {
     etag.trim_matches('"').to_string()
 }
 
+/// Check a body against the base64 `Content-MD5` header sent with it
+pub fn verify_content_md5(content_md5: &str, body: &[u8]) -> crate::Result<()> {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use digest::Digest;
+
+    let expected = STANDARD
+        .decode(content_md5.trim())
+        .ok()
+        .filter(|digest| digest.len() == 16)
+        .ok_or(crate::Error::InvalidDigest)?;
+    if md5::Md5::digest(body).as_slice() != expected.as_slice() {
+        return Err(crate::Error::BadDigest);
+    }
+    Ok(())
+}
+
 /// XML escape string
 pub fn xml_escape(s: &str) -> String {
     s.replace('&', "&amp;")
@@ -35,3 +68,26 @@ pub fn format_s3_datetime(dt: &chrono::DateTime<chrono::Utc>) -> String {
 pub fn format_http_datetime(dt: &chrono::DateTime<chrono::Utc>) -> String {
     dt.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_content_md5() {
+        assert!(verify_content_md5("XrY7u+Ae7tCTyyK7j1rNww==", b"hello world").is_ok());
+        assert!(matches!(
+            verify_content_md5("XrY7u+Ae7tCTyyK7j1rNww==", b"hello world!"),
+            Err(crate::Error::BadDigest)
+        ));
+        assert!(matches!(
+            verify_content_md5("not base64", b"hello world"),
+            Err(crate::Error::InvalidDigest)
+        ));
+        // Valid base64, but not 128 bits
+        assert!(matches!(
+            verify_content_md5("aGVsbG8=", b"hello"),
+            Err(crate::Error::InvalidDigest)
+        ));
+    }
+}
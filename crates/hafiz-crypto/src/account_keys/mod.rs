@@ -0,0 +1,215 @@
+//! Per-account master keys
+//!
+//! Each account's objects are encrypted under a master key of its own, so
+//! a leaked account key exposes only that account's objects, and rotating
+//! it touches no other account. Account keys are not stored: they are
+//! derived from the server master key with HKDF-SHA256 (RFC 5869), over the
+//! account ID and a key generation. Rotating an account's key moves it to
+//! the next generation; objects keep the key ID they were written under
+//! and still decrypt, since older generations can be derived again.
+//!
+//! Key IDs are text, `<account>/v<generation>`. Objects without a key ID
+//! predate per-account keys and are encrypted under the master key itself.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::backend::{backend, key_array, KEY_LEN};
+use crate::encryption::{EncryptionError, KeyManager, StreamingEncryptor};
+
+/// HKDF salt, fixed so that the same master key always gives the same
+/// account keys
+const HKDF_SALT: &[u8] = b"hafiz account key";
+
+/// Generation of an account's first key
+pub const FIRST_KEY_GENERATION: u32 = 1;
+
+/// ID of generation `generation` of `account`'s key
+pub fn account_key_id(account: &str, generation: u32) -> String {
+    format!("{}/v{}", account, generation)
+}
+
+/// Account and generation of a key ID from [`account_key_id`]
+pub fn parse_account_key_id(key_id: &str) -> Option<(&str, u32)> {
+    let (account, generation) = key_id.rsplit_once("/v")?;
+    let generation = generation.parse().ok().filter(|g| *g >= FIRST_KEY_GENERATION)?;
+    (!account.is_empty()).then_some((account, generation))
+}
+
+/// HKDF-SHA256 with a single 32-byte output block
+pub fn hkdf_sha256(salt: &[u8], ikm: &[u8], info: &[u8]) -> [u8; KEY_LEN] {
+    let prk = backend().hmac_sha256(salt, ikm);
+    let mut block = Vec::with_capacity(info.len() + 1);
+    block.extend_from_slice(info);
+    block.push(1);
+    backend().hmac_sha256(&prk, &block)
+}
+
+/// Account keys derived from the server master key
+pub struct AccountKeys {
+    master: Arc<KeyManager>,
+    master_key: [u8; KEY_LEN],
+    /// Derived keys by key ID
+    derived: Mutex<HashMap<String, Arc<KeyManager>>>,
+}
+
+impl AccountKeys {
+    pub fn new(master_key: &[u8]) -> Result<Self, EncryptionError> {
+        Ok(Self {
+            master: Arc::new(KeyManager::new(master_key)?),
+            master_key: key_array(master_key)?,
+            derived: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Key manager for objects written under `key_id`, or under the master
+    /// key itself if `None`
+    pub fn key_manager(&self, key_id: Option<&str>) -> Result<Arc<KeyManager>, EncryptionError> {
+        let Some(key_id) = key_id else {
+            return Ok(self.master.clone());
+        };
+        let (account, generation) = parse_account_key_id(key_id)
+            .ok_or_else(|| EncryptionError::InvalidKey(format!("Invalid key ID: {}", key_id)))?;
+
+        let mut derived = self.derived.lock().unwrap();
+        if let Some(manager) = derived.get(key_id) {
+            return Ok(manager.clone());
+        }
+        let info = format!("account:{}:{}", account, generation);
+        let key = hkdf_sha256(HKDF_SALT, &self.master_key, info.as_bytes());
+        let manager = Arc::new(KeyManager::new(&key)?);
+        derived.insert(key_id.to_string(), manager.clone());
+        Ok(manager)
+    }
+
+    /// Encryptor for new objects of `account`, recording the key ID in the
+    /// [`EncryptedObjectInfo`](crate::EncryptedObjectInfo) it produces
+    pub fn encryptor(
+        &self,
+        account: &str,
+        generation: u32,
+        chunk_size: usize,
+    ) -> Result<StreamingEncryptor, EncryptionError> {
+        let key_id = account_key_id(account, generation);
+        let manager = self.key_manager(Some(&key_id))?;
+        Ok(StreamingEncryptor::new(manager, chunk_size).with_key_id(key_id))
+    }
+
+    /// Move the DEK of an object written under `key_id` to the same key
+    /// derived from `to`'s master key
+    ///
+    /// Returns the encrypted DEK and its nonce; the object data, encrypted
+    /// under the DEK itself, stays as it is.
+    pub fn rewrap_dek(
+        &self,
+        to: &AccountKeys,
+        key_id: Option<&str>,
+        encrypted_dek: &[u8],
+        dek_nonce: &[u8],
+    ) -> Result<(Vec<u8>, Vec<u8>), EncryptionError> {
+        let dek = self.key_manager(key_id)?.decrypt_dek(encrypted_dek, dek_nonce)?;
+        to.key_manager(key_id)?.encrypt_dek(&dek)
+    }
+
+    /// Encryptor able to decrypt objects written under `key_id`
+    pub fn decryptor(&self, key_id: Option<&str>, chunk_size: usize) -> Result<StreamingEncryptor, EncryptionError> {
+        let encryptor = StreamingEncryptor::new(self.key_manager(key_id)?, chunk_size);
+        Ok(match key_id {
+            Some(key_id) => encryptor.with_key_id(key_id.to_string()),
+            None => encryptor,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hkdf_known_answer() {
+        // RFC 5869 test case 1, first 32 bytes of the output
+        let ikm = [0x0b; 22];
+        let salt = hex::decode("000102030405060708090a0b0c").unwrap();
+        let info = hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap();
+        assert_eq!(
+            hex::encode(hkdf_sha256(&salt, &ikm, &info)),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf"
+        );
+    }
+
+    #[test]
+    fn test_key_ids() {
+        assert_eq!(account_key_id("acme", 3), "acme/v3");
+        assert_eq!(parse_account_key_id("acme/v3"), Some(("acme", 3)));
+        assert_eq!(parse_account_key_id("acme"), None);
+        assert_eq!(parse_account_key_id("acme/v0"), None);
+        assert_eq!(parse_account_key_id("/v1"), None);
+    }
+
+    #[test]
+    fn test_accounts_are_isolated() {
+        let keys = AccountKeys::new(&[7; 32]).unwrap();
+        let encryptor = keys.encryptor("acme", 1, 16).unwrap();
+        let (ciphertext, info) = encryptor.encrypt_stream(b"acme data").unwrap();
+        assert_eq!(info.key_id.as_deref(), Some("acme/v1"));
+
+        let decryptor = keys.decryptor(info.key_id.as_deref(), 16).unwrap();
+        assert_eq!(decryptor.decrypt_stream(&ciphertext, &info).unwrap(), b"acme data");
+
+        // Neither another account's key, a rotated key nor the master key
+        // opens the DEK
+        let encrypted_dek = info.encrypted_dek.as_ref().unwrap();
+        let dek_nonce = info.dek_nonce.as_ref().unwrap();
+        for other in [Some("globex/v1"), Some("acme/v2"), None] {
+            let manager = keys.key_manager(other).unwrap();
+            assert!(manager.decrypt_dek(encrypted_dek, dek_nonce).is_err());
+            let decryptor = keys.decryptor(other, 16).unwrap();
+            assert!(decryptor.decrypt_stream(&ciphertext, &info).is_err());
+        }
+
+        // The same master key derives the same account keys
+        let again = AccountKeys::new(&[7; 32]).unwrap();
+        let decryptor = again.decryptor(Some("acme/v1"), 16).unwrap();
+        assert_eq!(decryptor.decrypt_stream(&ciphertext, &info).unwrap(), b"acme data");
+    }
+
+    #[test]
+    fn test_rewrap_dek() {
+        let old = AccountKeys::new(&[7; 32]).unwrap();
+        let new = AccountKeys::new(&[9; 32]).unwrap();
+        for key_id in [Some("acme/v2"), None] {
+            let encryptor = match key_id {
+                Some(_) => old.encryptor("acme", 2, 16).unwrap(),
+                None => old.decryptor(None, 16).unwrap(),
+            };
+            let (ciphertext, mut info) = encryptor.encrypt_stream(b"some data").unwrap();
+
+            let (dek, nonce) = old
+                .rewrap_dek(
+                    &new,
+                    key_id,
+                    info.encrypted_dek.as_ref().unwrap(),
+                    info.dek_nonce.as_ref().unwrap(),
+                )
+                .unwrap();
+            info.encrypted_dek = Some(dek);
+            info.dek_nonce = Some(nonce);
+
+            // The data opens under the new master key only
+            let decryptor = new.decryptor(key_id, 16).unwrap();
+            assert_eq!(decryptor.decrypt_stream(&ciphertext, &info).unwrap(), b"some data");
+            let decryptor = old.decryptor(key_id, 16).unwrap();
+            assert!(decryptor.decrypt_stream(&ciphertext, &info).is_err());
+
+            // A DEK already moved does not open under the old key
+            assert!(old
+                .rewrap_dek(
+                    &new,
+                    key_id,
+                    info.encrypted_dek.as_ref().unwrap(),
+                    info.dek_nonce.as_ref().unwrap(),
+                )
+                .is_err());
+        }
+    }
+}
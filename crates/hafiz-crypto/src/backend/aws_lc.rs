@@ -0,0 +1,74 @@
+//! AWS-LC backend, through aws-lc-rs
+
+use aws_lc_rs::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
+use aws_lc_rs::rand::{SecureRandom, SystemRandom};
+use aws_lc_rs::{digest, hmac};
+
+use super::{CryptoBackend, KEY_LEN, NONCE_LEN};
+use crate::encryption::EncryptionError;
+
+pub struct AwsLcBackend;
+
+fn aead_key(key: &[u8; KEY_LEN]) -> Result<LessSafeKey, EncryptionError> {
+    UnboundKey::new(&AES_256_GCM, key)
+        .map(LessSafeKey::new)
+        .map_err(|_| EncryptionError::InvalidKey("Invalid AES-256 key".into()))
+}
+
+impl CryptoBackend for AwsLcBackend {
+    fn name(&self) -> &'static str {
+        "aws-lc"
+    }
+
+    fn is_fips(&self) -> bool {
+        aws_lc_rs::try_fips_mode().is_ok()
+    }
+
+    fn aes256_gcm_seal(
+        &self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let mut in_out = plaintext.to_vec();
+        aead_key(key)?
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(*nonce), Aad::from(aad), &mut in_out)
+            .map_err(|_| EncryptionError::EncryptionFailed("AES-GCM seal failed".into()))?;
+        Ok(in_out)
+    }
+
+    fn aes256_gcm_open(
+        &self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let mut in_out = ciphertext.to_vec();
+        let len = aead_key(key)?
+            .open_in_place(Nonce::assume_unique_for_key(*nonce), Aad::from(aad), &mut in_out)
+            .map_err(|_| EncryptionError::DecryptionFailed("AES-GCM authentication failed".into()))?
+            .len();
+        in_out.truncate(len);
+        Ok(in_out)
+    }
+
+    fn sha256(&self, data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out.copy_from_slice(digest::digest(&digest::SHA256, data).as_ref());
+        out
+    }
+
+    fn hmac_sha256(&self, key: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        out.copy_from_slice(hmac::sign(&hmac::Key::new(hmac::HMAC_SHA256, key), data).as_ref());
+        out
+    }
+
+    fn fill_random(&self, buf: &mut [u8]) {
+        SystemRandom::new()
+            .fill(buf)
+            .expect("system random number generator failed");
+    }
+}
@@ -0,0 +1,148 @@
+//! Pluggable provider of the primitives Hafiz encrypts and signs with
+//!
+//! AES-256-GCM, SHA-256, HMAC-SHA256 and the random number generator all go
+//! through [`backend()`], so the provider can be swapped at build time
+//! without touching the code that encrypts objects, seals secrets or checks
+//! signatures. One backend is compiled in, chosen by cargo feature:
+//!
+//! - `rustcrypto` (default): the pure-Rust RustCrypto crates
+//! - `ring`: *ring*
+//! - `aws-lc`: AWS-LC through aws-lc-rs; with `fips`, its FIPS 140-3
+//!   validated module
+//!
+//! If several are enabled, `aws-lc` wins over `ring`, and `ring` over
+//! `rustcrypto`. MD5 and SHA-1 are only used for ETags and checksums, not
+//! for security, and always come from RustCrypto.
+
+#[cfg(feature = "aws-lc")]
+mod aws_lc;
+#[cfg(all(feature = "ring", not(feature = "aws-lc")))]
+mod ring;
+#[cfg(all(feature = "rustcrypto", not(any(feature = "ring", feature = "aws-lc"))))]
+mod rustcrypto;
+
+#[cfg(not(any(feature = "rustcrypto", feature = "ring", feature = "aws-lc")))]
+compile_error!("hafiz-crypto needs one of the `rustcrypto`, `ring` or `aws-lc` features");
+
+use crate::encryption::EncryptionError;
+
+/// AES-256 key length
+pub const KEY_LEN: usize = 32;
+/// AES-GCM nonce length
+pub const NONCE_LEN: usize = 12;
+/// AES-GCM authentication tag length, appended to every ciphertext
+pub const TAG_LEN: usize = 16;
+
+/// Provider of the cryptographic primitives
+pub trait CryptoBackend: Send + Sync {
+    /// Name of the provider, for logs
+    fn name(&self) -> &'static str;
+
+    /// Whether the provider runs in a FIPS-validated mode
+    fn is_fips(&self) -> bool {
+        false
+    }
+
+    /// Encrypt with AES-256-GCM; the tag is appended to the ciphertext
+    fn aes256_gcm_seal(
+        &self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError>;
+
+    /// Decrypt and authenticate a ciphertext from [`aes256_gcm_seal`](Self::aes256_gcm_seal)
+    fn aes256_gcm_open(
+        &self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError>;
+
+    fn sha256(&self, data: &[u8]) -> [u8; 32];
+
+    fn hmac_sha256(&self, key: &[u8], data: &[u8]) -> [u8; 32];
+
+    /// Fill `buf` from a cryptographically secure random source
+    fn fill_random(&self, buf: &mut [u8]);
+}
+
+#[cfg(feature = "aws-lc")]
+static BACKEND: aws_lc::AwsLcBackend = aws_lc::AwsLcBackend;
+#[cfg(all(feature = "ring", not(feature = "aws-lc")))]
+static BACKEND: ring::RingBackend = ring::RingBackend;
+#[cfg(all(feature = "rustcrypto", not(any(feature = "ring", feature = "aws-lc"))))]
+static BACKEND: rustcrypto::RustCryptoBackend = rustcrypto::RustCryptoBackend;
+
+/// The backend this build uses
+pub fn backend() -> &'static dyn CryptoBackend {
+    &BACKEND
+}
+
+/// Key as a fixed-size array
+pub(crate) fn key_array(key: &[u8]) -> Result<[u8; KEY_LEN], EncryptionError> {
+    key.try_into()
+        .map_err(|_| EncryptionError::InvalidKey(format!("Key must be {} bytes", KEY_LEN)))
+}
+
+/// Nonce as a fixed-size array
+pub(crate) fn nonce_array(nonce: &[u8]) -> Result<[u8; NONCE_LEN], EncryptionError> {
+    nonce
+        .try_into()
+        .map_err(|_| EncryptionError::InvalidKey(format!("Nonce must be {} bytes", NONCE_LEN)))
+}
+
+/// A fresh random nonce
+pub(crate) fn random_nonce() -> [u8; NONCE_LEN] {
+    let mut nonce = [0u8; NONCE_LEN];
+    backend().fill_random(&mut nonce);
+    nonce
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test case 14 of the GCM specification (McGrew & Viega): all-zero
+    // 256-bit key, nonce and 16-byte block
+    #[test]
+    fn test_aes256_gcm_known_answer() {
+        let backend = backend();
+        let key = [0u8; KEY_LEN];
+        let nonce = [0u8; NONCE_LEN];
+
+        let sealed = backend.aes256_gcm_seal(&key, &nonce, b"", &[0u8; 16]).unwrap();
+        assert_eq!(
+            hex::encode(&sealed),
+            "cea7403d4d606b6e074ec5d3baf39d18d0d1c8a799996bf0265b98b5d48ab919"
+        );
+        assert_eq!(backend.aes256_gcm_open(&key, &nonce, b"", &sealed).unwrap(), [0u8; 16]);
+
+        let mut tampered = sealed.clone();
+        tampered[0] ^= 1;
+        assert!(backend.aes256_gcm_open(&key, &nonce, b"", &tampered).is_err());
+        assert!(backend.aes256_gcm_open(&key, &nonce, b"aad", &sealed).is_err());
+    }
+
+    #[test]
+    fn test_sha256_and_hmac_known_answers() {
+        let backend = backend();
+        assert_eq!(
+            hex::encode(backend.sha256(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // RFC 4231 test case 2
+        assert_eq!(
+            hex::encode(backend.hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let mut a = [0u8; 32];
+        let mut b = [0u8; 32];
+        backend.fill_random(&mut a);
+        backend.fill_random(&mut b);
+        assert_ne!(a, b);
+    }
+}
@@ -0,0 +1,60 @@
+//! RustCrypto backend
+
+use aes_gcm::{
+    aead::{Aead, KeyInit, OsRng, Payload},
+    Aes256Gcm, Nonce,
+};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+
+use super::{CryptoBackend, KEY_LEN, NONCE_LEN};
+use crate::encryption::EncryptionError;
+
+pub struct RustCryptoBackend;
+
+impl CryptoBackend for RustCryptoBackend {
+    fn name(&self) -> &'static str {
+        "rustcrypto"
+    }
+
+    fn aes256_gcm_seal(
+        &self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| EncryptionError::InvalidKey(e.to_string()))?;
+        cipher
+            .encrypt(Nonce::from_slice(nonce), Payload { msg: plaintext, aad })
+            .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))
+    }
+
+    fn aes256_gcm_open(
+        &self,
+        key: &[u8; KEY_LEN],
+        nonce: &[u8; NONCE_LEN],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|e| EncryptionError::InvalidKey(e.to_string()))?;
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))
+    }
+
+    fn sha256(&self, data: &[u8]) -> [u8; 32] {
+        Sha256::digest(data).into()
+    }
+
+    fn hmac_sha256(&self, key: &[u8], data: &[u8]) -> [u8; 32] {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC can take key of any size");
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn fill_random(&self, buf: &mut [u8]) {
+        OsRng.fill_bytes(buf);
+    }
+}
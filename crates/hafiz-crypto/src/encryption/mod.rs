@@ -8,18 +8,25 @@
 //! - Master Encryption Key (MEK): Stored securely, used to encrypt DEKs
 //! - Data Encryption Key (DEK): Per-object random key, encrypted with MEK
 //! - Envelope encryption: DEK encrypts data, MEK encrypts DEK
+//!
+//! Object data is sealed in [`segments`] so that ranged reads decrypt only
+//! the segments they cover. The AES-GCM operations run on the build's
+//! [`crate::backend`].
 
-use aes_gcm::{
-    aead::{Aead, KeyInit, OsRng},
-    Aes256Gcm, Nonce,
-};
 use digest::Digest;
 use md5::Md5;
-use rand::RngCore;
-use sha2::Sha256;
+use std::ops::Range;
 use std::sync::Arc;
 use thiserror::Error;
 
+use crate::backend::{backend, key_array, nonce_array, random_nonce, NONCE_LEN, TAG_LEN};
+use crate::kdf::KdfParams;
+
+pub mod segments;
+
+pub use segments::{SegmentLayout, SegmentRange, DEFAULT_SEGMENT_SIZE};
+use segments::{segment_aad, segment_nonce};
+
 /// Encryption errors
 #[derive(Debug, Error)]
 pub enum EncryptionError {
@@ -34,6 +41,9 @@ pub enum EncryptionError {
 
     #[error("Key derivation failed: {0}")]
     KeyDerivationFailed(String),
+
+    #[error("Master key provider failed: {0}")]
+    ProviderFailed(String),
 }
 
 /// Server-Side Encryption type
@@ -78,14 +88,26 @@ pub struct EncryptedObjectInfo {
     pub data_nonce: Vec<u8>,
     /// MD5 of customer key (for SSE-C)
     pub sse_customer_key_md5: Option<String>,
+    /// Plaintext bytes per segment; `None` for data sealed as one message
+    pub segment_size: Option<usize>,
+    /// Account key the DEK is encrypted under (for SSE-S3); `None` for the
+    /// master key itself
+    pub key_id: Option<String>,
+}
+
+impl EncryptedObjectInfo {
+    /// Segment layout of `ciphertext_len` bytes of object data, if segmented
+    pub fn layout(&self, ciphertext_len: u64) -> Result<Option<SegmentLayout>, EncryptionError> {
+        self.segment_size
+            .map(|size| SegmentLayout::from_ciphertext_len(size, ciphertext_len))
+            .transpose()
+    }
 }
 
 /// Key Manager for SSE-S3
 pub struct KeyManager {
     /// Master Encryption Key (256-bit)
     master_key: [u8; 32],
-    /// Cipher for MEK operations
-    mek_cipher: Aes256Gcm,
 }
 
 impl KeyManager {
@@ -97,15 +119,8 @@ impl KeyManager {
             ));
         }
 
-        let mut key = [0u8; 32];
-        key.copy_from_slice(master_key);
-
-        let mek_cipher = Aes256Gcm::new_from_slice(&key)
-            .map_err(|e| EncryptionError::InvalidKey(e.to_string()))?;
-
         Ok(Self {
-            master_key: key,
-            mek_cipher,
+            master_key: key_array(master_key)?,
         })
     }
 
@@ -117,46 +132,38 @@ impl KeyManager {
     }
 
     /// Create KeyManager from passphrase (derives key using SHA-256)
+    ///
+    /// A single unsalted hash is weak for low-entropy passphrases; use
+    /// [`from_passphrase_with`](Self::from_passphrase_with) instead.
     pub fn from_passphrase(passphrase: &str) -> Result<Self, EncryptionError> {
-        let mut hasher = Sha256::new();
-        hasher.update(passphrase.as_bytes());
-        let key = hasher.finalize();
+        let key = backend().sha256(passphrase.as_bytes());
         Self::new(&key)
     }
 
+    /// Create KeyManager from passphrase with a salted key derivation
+    pub fn from_passphrase_with(passphrase: &str, params: &KdfParams) -> Result<Self, EncryptionError> {
+        Self::new(&params.derive(passphrase)?)
+    }
+
     /// Generate a new random Data Encryption Key
     pub fn generate_dek(&self) -> [u8; 32] {
         let mut dek = [0u8; 32];
-        OsRng.fill_bytes(&mut dek);
+        backend().fill_random(&mut dek);
         dek
     }
 
     /// Encrypt DEK with Master Key (envelope encryption)
     pub fn encrypt_dek(&self, dek: &[u8; 32]) -> Result<(Vec<u8>, Vec<u8>), EncryptionError> {
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        let encrypted_dek = self
-            .mek_cipher
-            .encrypt(nonce, dek.as_ref())
-            .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))?;
+        let nonce = random_nonce();
+        let encrypted_dek = backend().aes256_gcm_seal(&self.master_key, &nonce, &[], dek)?;
 
-        Ok((encrypted_dek, nonce_bytes.to_vec()))
+        Ok((encrypted_dek, nonce.to_vec()))
     }
 
     /// Decrypt DEK with Master Key
     pub fn decrypt_dek(&self, encrypted_dek: &[u8], nonce: &[u8]) -> Result<[u8; 32], EncryptionError> {
-        if nonce.len() != 12 {
-            return Err(EncryptionError::InvalidKey("Nonce must be 12 bytes".into()));
-        }
-
-        let nonce = Nonce::from_slice(nonce);
-
-        let dek = self
-            .mek_cipher
-            .decrypt(nonce, encrypted_dek)
-            .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))?;
+        let nonce = nonce_array(nonce)?;
+        let dek = backend().aes256_gcm_open(&self.master_key, &nonce, &[], encrypted_dek)?;
 
         if dek.len() != 32 {
             return Err(EncryptionError::DecryptionFailed("Invalid DEK length".into()));
@@ -172,20 +179,12 @@ impl KeyManager {
 pub struct ObjectEncryptor {
     /// Data Encryption Key
     dek: [u8; 32],
-    /// Cipher instance
-    cipher: Aes256Gcm,
 }
 
 impl ObjectEncryptor {
     /// Create new encryptor with DEK
     pub fn new(dek: &[u8; 32]) -> Result<Self, EncryptionError> {
-        let cipher = Aes256Gcm::new_from_slice(dek)
-            .map_err(|e| EncryptionError::InvalidKey(e.to_string()))?;
-
-        Ok(Self {
-            dek: *dek,
-            cipher,
-        })
+        Ok(Self { dek: *dek })
     }
 
     /// Create encryptor from customer-provided key (SSE-C)
@@ -216,39 +215,122 @@ impl ObjectEncryptor {
 
     /// Encrypt data chunk
     pub fn encrypt(&self, data: &[u8]) -> Result<(Vec<u8>, Vec<u8>), EncryptionError> {
-        let mut nonce_bytes = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce_bytes);
-        let nonce = Nonce::from_slice(&nonce_bytes);
-
-        let ciphertext = self
-            .cipher
-            .encrypt(nonce, data)
-            .map_err(|e| EncryptionError::EncryptionFailed(e.to_string()))?;
+        let nonce = random_nonce();
+        let ciphertext = backend().aes256_gcm_seal(&self.dek, &nonce, &[], data)?;
 
-        Ok((ciphertext, nonce_bytes.to_vec()))
+        Ok((ciphertext, nonce.to_vec()))
     }
 
     /// Decrypt data chunk
     pub fn decrypt(&self, ciphertext: &[u8], nonce: &[u8]) -> Result<Vec<u8>, EncryptionError> {
-        if nonce.len() != 12 {
-            return Err(EncryptionError::InvalidKey("Nonce must be 12 bytes".into()));
+        let nonce = nonce_array(nonce)?;
+        backend().aes256_gcm_open(&self.dek, &nonce, &[], ciphertext)
+    }
+
+    /// Encrypt data as segments of `segment_size` bytes, returning the
+    /// ciphertext and the base nonce
+    pub fn encrypt_segmented(
+        &self,
+        data: &[u8],
+        segment_size: usize,
+    ) -> Result<(Vec<u8>, Vec<u8>), EncryptionError> {
+        if segment_size == 0 {
+            return Err(EncryptionError::EncryptionFailed("Segment size is 0".into()));
+        }
+        let nonce = random_nonce();
+        let layout = SegmentLayout::new(segment_size, data.len() as u64);
+        let mut ciphertext = Vec::with_capacity(layout.ciphertext_len() as usize);
+        for index in 0..layout.segment_count() {
+            let start = index as usize * segment_size;
+            let segment = &data[start..start + layout.segment_len(index)];
+            ciphertext.extend(self.encrypt_segment(&nonce, index, layout.is_last(index), segment)?);
         }
 
-        let nonce = Nonce::from_slice(nonce);
+        Ok((ciphertext, nonce.to_vec()))
+    }
+
+    /// Encrypt segment `index` of an object
+    pub fn encrypt_segment(
+        &self,
+        base_nonce: &[u8; NONCE_LEN],
+        index: u64,
+        last: bool,
+        data: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let nonce = segment_nonce(base_nonce, index);
+        backend().aes256_gcm_seal(&self.dek, &nonce, &segment_aad(index, last), data)
+    }
+
+    /// Decrypt segment `index` of an object
+    pub fn decrypt_segment(
+        &self,
+        base_nonce: &[u8; NONCE_LEN],
+        index: u64,
+        last: bool,
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let nonce = segment_nonce(base_nonce, index);
+        backend().aes256_gcm_open(&self.dek, &nonce, &segment_aad(index, last), ciphertext)
+    }
 
-        let plaintext = self
-            .cipher
-            .decrypt(nonce, ciphertext)
-            .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))?;
+    /// Decrypt plaintext `range` of a segmented object
+    ///
+    /// `ciphertext` holds only the bytes `layout.locate(range).ciphertext`
+    /// of the stored data, and only those segments are decrypted.
+    pub fn decrypt_range(
+        &self,
+        ciphertext: &[u8],
+        nonce: &[u8],
+        layout: &SegmentLayout,
+        range: Range<u64>,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        let nonce = nonce_array(nonce)?;
+        let located = layout.locate(range);
+        if ciphertext.len() as u64 != located.ciphertext.end - located.ciphertext.start {
+            return Err(EncryptionError::DecryptionFailed(format!(
+                "Expected ciphertext bytes {}..{}",
+                located.ciphertext.start, located.ciphertext.end
+            )));
+        }
 
+        let mut plaintext = Vec::with_capacity(located.len);
+        let first = located.segments.start;
+        for (index, segment) in located.segments.zip(ciphertext.chunks(layout.segment_size + TAG_LEN)) {
+            let segment = self.decrypt_segment(&nonce, index, layout.is_last(index), segment)?;
+            let skip = if index == first { located.skip } else { 0 };
+            let take = (located.len - plaintext.len()).min(segment.len().saturating_sub(skip));
+            plaintext.extend_from_slice(&segment[skip..skip + take]);
+        }
         Ok(plaintext)
     }
 
+    /// Decrypt plaintext `range` of object data sealed as described by
+    /// `info`
+    ///
+    /// For segmented data `ciphertext` holds the located segments of a
+    /// `ciphertext_len` byte object; data sealed as one message must be
+    /// passed whole.
+    fn decrypt_object_range(
+        &self,
+        ciphertext: &[u8],
+        info: &EncryptedObjectInfo,
+        ciphertext_len: u64,
+        range: Range<u64>,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        match info.layout(ciphertext_len)? {
+            Some(layout) => self.decrypt_range(ciphertext, &info.data_nonce, &layout, range),
+            None => {
+                let plaintext = self.decrypt(ciphertext, &info.data_nonce)?;
+                let end = (range.end as usize).min(plaintext.len());
+                let start = (range.start as usize).min(end);
+                Ok(plaintext[start..end].to_vec())
+            }
+        }
+    }
+
     /// Generate random nonce
     pub fn generate_nonce() -> [u8; 12] {
-        let mut nonce = [0u8; 12];
-        OsRng.fill_bytes(&mut nonce);
-        nonce
+        random_nonce()
     }
 }
 
@@ -256,6 +338,7 @@ impl ObjectEncryptor {
 pub struct StreamingEncryptor {
     key_manager: Arc<KeyManager>,
     chunk_size: usize,
+    key_id: Option<String>,
 }
 
 impl StreamingEncryptor {
@@ -263,9 +346,17 @@ impl StreamingEncryptor {
         Self {
             key_manager,
             chunk_size,
+            key_id: None,
         }
     }
 
+    /// Record `key_id` as the key of the objects this encrypts; see
+    /// [`crate::account_keys`]
+    pub fn with_key_id(mut self, key_id: String) -> Self {
+        self.key_id = Some(key_id);
+        self
+    }
+
     /// Encrypt a stream of data, returns encrypted chunks with metadata
     pub fn encrypt_stream(
         &self,
@@ -280,8 +371,8 @@ impl StreamingEncryptor {
         // Create object encryptor
         let encryptor = ObjectEncryptor::new(&dek)?;
 
-        // Encrypt data
-        let (ciphertext, data_nonce) = encryptor.encrypt(data)?;
+        // Encrypt data in segments of chunk_size bytes
+        let (ciphertext, data_nonce) = encryptor.encrypt_segmented(data, self.chunk_size)?;
 
         let info = EncryptedObjectInfo {
             sse_type: SseType::SseS3,
@@ -289,6 +380,8 @@ impl StreamingEncryptor {
             dek_nonce: Some(dek_nonce),
             data_nonce,
             sse_customer_key_md5: None,
+            segment_size: Some(self.chunk_size),
+            key_id: self.key_id.clone(),
         };
 
         Ok((ciphertext, info))
@@ -299,6 +392,20 @@ impl StreamingEncryptor {
         &self,
         ciphertext: &[u8],
         info: &EncryptedObjectInfo,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        self.decrypt_range(ciphertext, info, ciphertext.len() as u64, 0..u64::MAX)
+    }
+
+    /// Decrypt plaintext `range` of an object of `ciphertext_len` bytes
+    ///
+    /// `ciphertext` holds the bytes [`SegmentLayout::locate`] gives for
+    /// the range, or the whole object if it predates segmenting.
+    pub fn decrypt_range(
+        &self,
+        ciphertext: &[u8],
+        info: &EncryptedObjectInfo,
+        ciphertext_len: u64,
+        range: Range<u64>,
     ) -> Result<Vec<u8>, EncryptionError> {
         // Decrypt DEK with MEK
         let encrypted_dek = info
@@ -311,13 +418,19 @@ impl StreamingEncryptor {
             .as_ref()
             .ok_or_else(|| EncryptionError::DecryptionFailed("Missing DEK nonce".into()))?;
 
+        if info.key_id != self.key_id {
+            return Err(EncryptionError::InvalidKey(format!(
+                "Object is encrypted under key {}",
+                info.key_id.as_deref().unwrap_or("master")
+            )));
+        }
         let dek = self.key_manager.decrypt_dek(encrypted_dek, dek_nonce)?;
 
         // Create object encryptor
         let encryptor = ObjectEncryptor::new(&dek)?;
 
         // Decrypt data
-        encryptor.decrypt(ciphertext, &info.data_nonce)
+        encryptor.decrypt_object_range(ciphertext, info, ciphertext_len, range)
     }
 }
 
@@ -332,7 +445,7 @@ impl SseCEncryptor {
     ) -> Result<(Vec<u8>, EncryptedObjectInfo), EncryptionError> {
         let (encryptor, key_md5) = ObjectEncryptor::from_customer_key(customer_key_base64)?;
 
-        let (ciphertext, data_nonce) = encryptor.encrypt(data)?;
+        let (ciphertext, data_nonce) = encryptor.encrypt_segmented(data, DEFAULT_SEGMENT_SIZE)?;
 
         let info = EncryptedObjectInfo {
             sse_type: SseType::SseC,
@@ -340,6 +453,8 @@ impl SseCEncryptor {
             dek_nonce: None,
             data_nonce,
             sse_customer_key_md5: Some(key_md5),
+            segment_size: Some(DEFAULT_SEGMENT_SIZE),
+            key_id: None,
         };
 
         Ok((ciphertext, info))
@@ -350,6 +465,18 @@ impl SseCEncryptor {
         ciphertext: &[u8],
         info: &EncryptedObjectInfo,
         customer_key_base64: &str,
+    ) -> Result<Vec<u8>, EncryptionError> {
+        Self::decrypt_range(ciphertext, info, ciphertext.len() as u64, 0..u64::MAX, customer_key_base64)
+    }
+
+    /// Decrypt plaintext `range` with customer-provided key; see
+    /// [`StreamingEncryptor::decrypt_range`]
+    pub fn decrypt_range(
+        ciphertext: &[u8],
+        info: &EncryptedObjectInfo,
+        ciphertext_len: u64,
+        range: Range<u64>,
+        customer_key_base64: &str,
     ) -> Result<Vec<u8>, EncryptionError> {
         let (encryptor, key_md5) = ObjectEncryptor::from_customer_key(customer_key_base64)?;
 
@@ -362,7 +489,7 @@ impl SseCEncryptor {
             }
         }
 
-        encryptor.decrypt(ciphertext, &info.data_nonce)
+        encryptor.decrypt_object_range(ciphertext, info, ciphertext_len, range)
     }
 }
 
@@ -387,7 +514,7 @@ mod tests {
     #[test]
     fn test_object_encryption() {
         let mut dek = [0u8; 32];
-        OsRng.fill_bytes(&mut dek);
+        backend().fill_random(&mut dek);
 
         let encryptor = ObjectEncryptor::new(&dek).unwrap();
 
@@ -423,7 +550,7 @@ mod tests {
 
         // Generate random customer key
         let mut key = [0u8; 32];
-        OsRng.fill_bytes(&mut key);
+        backend().fill_random(&mut key);
         let key_base64 = STANDARD.encode(&key);
 
         let data = b"Customer encrypted data";
@@ -435,4 +562,61 @@ mod tests {
         let decrypted = SseCEncryptor::decrypt(&ciphertext, &info, &key_base64).unwrap();
         assert_eq!(decrypted.as_slice(), data);
     }
+
+    #[test]
+    fn test_segmented_range_decryption() {
+        let km = Arc::new(KeyManager::from_passphrase("test-key").unwrap());
+        let encryptor = StreamingEncryptor::new(km, 16);
+
+        let data: Vec<u8> = (0..100u8).collect();
+        let (ciphertext, info) = encryptor.encrypt_stream(&data).unwrap();
+        assert_eq!(ciphertext.len(), 100 + 7 * 16);
+        assert_eq!(encryptor.decrypt_stream(&ciphertext, &info).unwrap(), data);
+
+        // Decrypt only the segments holding bytes 30..70
+        let layout = info.layout(ciphertext.len() as u64).unwrap().unwrap();
+        let located = layout.locate(30..70);
+        assert_eq!(located.segments, 1..5);
+        let slice = &ciphertext[located.ciphertext.start as usize..located.ciphertext.end as usize];
+        let range = encryptor
+            .decrypt_range(slice, &info, ciphertext.len() as u64, 30..70)
+            .unwrap();
+        assert_eq!(range, &data[30..70]);
+
+        // A flipped bit, swapped segments or a dropped last segment fail
+        let mut tampered = ciphertext.clone();
+        tampered[40] ^= 1;
+        assert!(encryptor.decrypt_stream(&tampered, &info).is_err());
+
+        let mut swapped = ciphertext.clone();
+        swapped[..64].rotate_left(32);
+        assert!(encryptor.decrypt_stream(&swapped, &info).is_err());
+
+        let truncated = &ciphertext[..6 * 32];
+        assert!(encryptor.decrypt_stream(truncated, &info).is_err());
+    }
+
+    #[test]
+    fn test_single_message_objects_still_decrypt() {
+        let km = Arc::new(KeyManager::from_passphrase("test-key").unwrap());
+        let dek = km.generate_dek();
+        let (encrypted_dek, dek_nonce) = km.encrypt_dek(&dek).unwrap();
+        let (ciphertext, data_nonce) = ObjectEncryptor::new(&dek).unwrap().encrypt(b"legacy data").unwrap();
+        let info = EncryptedObjectInfo {
+            sse_type: SseType::SseS3,
+            encrypted_dek: Some(encrypted_dek),
+            dek_nonce: Some(dek_nonce),
+            data_nonce,
+            sse_customer_key_md5: None,
+            segment_size: None,
+            key_id: None,
+        };
+
+        let encryptor = StreamingEncryptor::new(km, 16);
+        assert_eq!(encryptor.decrypt_stream(&ciphertext, &info).unwrap(), b"legacy data");
+        let range = encryptor
+            .decrypt_range(&ciphertext, &info, ciphertext.len() as u64, 7..100)
+            .unwrap();
+        assert_eq!(range, b"data");
+    }
 }
@@ -0,0 +1,153 @@
+//! Segmented encryption of object data
+//!
+//! The plaintext is cut into segments of a fixed size (the last one may be
+//! shorter), each sealed as its own AES-GCM message. Segment `i` uses the
+//! object's base nonce with `i` XORed into its last eight bytes, and binds
+//! `i` and whether it is the last segment as associated data, so segments
+//! cannot be reordered, dropped or cut off at the end unnoticed.
+//!
+//! Every sealed segment is 16 bytes longer than its plaintext, so where a
+//! plaintext byte lives in the ciphertext follows from the segment size
+//! alone: a range read fetches and decrypts only the segments it covers.
+//! An empty object still has one, empty, segment.
+
+use std::ops::Range;
+
+use crate::backend::{NONCE_LEN, TAG_LEN};
+use crate::encryption::EncryptionError;
+
+/// Default plaintext bytes per segment
+pub const DEFAULT_SEGMENT_SIZE: usize = 4 * 1024 * 1024;
+
+/// Where the segments of an encrypted object lie
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentLayout {
+    /// Plaintext bytes per segment
+    pub segment_size: usize,
+    /// Size of the whole plaintext
+    pub plaintext_len: u64,
+}
+
+/// Segments covering a plaintext range, as found by
+/// [`SegmentLayout::locate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentRange {
+    /// Indexes of the segments
+    pub segments: Range<u64>,
+    /// Ciphertext bytes of those segments
+    pub ciphertext: Range<u64>,
+    /// Plaintext bytes to drop from the front of the first segment
+    pub skip: usize,
+    /// Plaintext bytes the range covers
+    pub len: usize,
+}
+
+impl SegmentLayout {
+    pub fn new(segment_size: usize, plaintext_len: u64) -> Self {
+        Self {
+            segment_size,
+            plaintext_len,
+        }
+    }
+
+    /// Layout of a ciphertext of `ciphertext_len` bytes
+    pub fn from_ciphertext_len(segment_size: usize, ciphertext_len: u64) -> Result<Self, EncryptionError> {
+        if segment_size == 0 {
+            return Err(EncryptionError::DecryptionFailed("Segment size is 0".into()));
+        }
+        let sealed = (segment_size + TAG_LEN) as u64;
+        let segments = ciphertext_len.div_ceil(sealed).max(1);
+        // Every segment, the last one included, holds at least its tag
+        let last = ciphertext_len.saturating_sub((segments - 1) * sealed);
+        if last < TAG_LEN as u64 {
+            return Err(EncryptionError::DecryptionFailed("Truncated segmented ciphertext".into()));
+        }
+        Ok(Self::new(segment_size, ciphertext_len - segments * TAG_LEN as u64))
+    }
+
+    /// Number of segments; at least one
+    pub fn segment_count(&self) -> u64 {
+        self.plaintext_len.div_ceil(self.segment_size as u64).max(1)
+    }
+
+    /// Size of the whole ciphertext
+    pub fn ciphertext_len(&self) -> u64 {
+        self.plaintext_len + self.segment_count() * TAG_LEN as u64
+    }
+
+    /// Plaintext length of segment `index`
+    pub fn segment_len(&self, index: u64) -> usize {
+        let start = index * self.segment_size as u64;
+        (self.plaintext_len.saturating_sub(start)).min(self.segment_size as u64) as usize
+    }
+
+    pub fn is_last(&self, index: u64) -> bool {
+        index + 1 == self.segment_count()
+    }
+
+    /// Segments and ciphertext bytes holding plaintext `range`, which is
+    /// clamped to the object
+    pub fn locate(&self, range: Range<u64>) -> SegmentRange {
+        let end = range.end.min(self.plaintext_len);
+        let start = range.start.min(end);
+        let size = self.segment_size as u64;
+        let sealed = size + TAG_LEN as u64;
+
+        let first = (start / size).min(self.segment_count() - 1);
+        let last = if end > start { (end - 1) / size } else { first };
+        let ciphertext_end = (last * sealed + self.segment_len(last) as u64 + TAG_LEN as u64).min(self.ciphertext_len());
+        SegmentRange {
+            segments: first..last + 1,
+            ciphertext: first * sealed..ciphertext_end,
+            skip: (start - first * size) as usize,
+            len: (end - start) as usize,
+        }
+    }
+}
+
+/// Nonce of segment `index`
+pub(crate) fn segment_nonce(base: &[u8; NONCE_LEN], index: u64) -> [u8; NONCE_LEN] {
+    let mut nonce = *base;
+    for (byte, i) in nonce[NONCE_LEN - 8..].iter_mut().zip(index.to_be_bytes()) {
+        *byte ^= i;
+    }
+    nonce
+}
+
+/// Associated data of segment `index`
+pub(crate) fn segment_aad(index: u64, last: bool) -> [u8; 9] {
+    let mut aad = [0u8; 9];
+    aad[..8].copy_from_slice(&index.to_be_bytes());
+    aad[8] = last as u8;
+    aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout_locates_ranges() {
+        let layout = SegmentLayout::new(10, 25);
+        assert_eq!(layout.segment_count(), 3);
+        assert_eq!(layout.ciphertext_len(), 25 + 3 * 16);
+        assert_eq!(SegmentLayout::from_ciphertext_len(10, 73).unwrap(), layout);
+
+        // Bytes 12..15 lie in the second segment only
+        let range = layout.locate(12..15);
+        assert_eq!(range.segments, 1..2);
+        assert_eq!(range.ciphertext, 26..52);
+        assert_eq!((range.skip, range.len), (2, 3));
+
+        // Across a boundary, up to the short last segment
+        let range = layout.locate(8..100);
+        assert_eq!(range.segments, 0..3);
+        assert_eq!(range.ciphertext, 0..73);
+        assert_eq!((range.skip, range.len), (8, 17));
+
+        let empty = SegmentLayout::new(10, 0);
+        assert_eq!(empty.segment_count(), 1);
+        assert_eq!(SegmentLayout::from_ciphertext_len(10, 16).unwrap(), empty);
+        assert!(SegmentLayout::from_ciphertext_len(10, 30).is_err());
+    }
+}
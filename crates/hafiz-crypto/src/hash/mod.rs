@@ -2,10 +2,10 @@
 
 use base64::{engine::general_purpose::STANDARD, Engine};
 use digest::Digest;
-use hmac::{Hmac, Mac};
 use md5::Md5;
 use sha1::Sha1;
-use sha2::Sha256;
+
+use crate::backend::backend;
 
 pub fn md5_hash(data: &[u8]) -> String {
     let mut hasher = Md5::new();
@@ -14,9 +14,7 @@ pub fn md5_hash(data: &[u8]) -> String {
 }
 
 pub fn sha256_hash(data: &[u8]) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(data);
-    hex::encode(hasher.finalize())
+    hex::encode(backend().sha256(data))
 }
 
 pub fn sha1_hash(data: &[u8]) -> String {
@@ -26,10 +24,7 @@ pub fn sha1_hash(data: &[u8]) -> String {
 }
 
 pub fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
-    type HmacSha256 = Hmac<Sha256>;
-    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC can take key of any size");
-    mac.update(data);
-    mac.finalize().into_bytes().to_vec()
+    backend().hmac_sha256(key, data).to_vec()
 }
 
 pub fn hmac_sha256_hex(key: &[u8], data: &[u8]) -> String {
@@ -43,7 +38,8 @@ pub fn md5_base64(data: &[u8]) -> String {
 }
 
 /// Calculate multipart upload ETag
-/// Format: MD5(concat(part_md5s))-part_count
+/// Format: MD5(concat(part_md5s))-part_count, as S3 reports it for objects
+/// assembled from parts
 pub fn multipart_etag(part_etags: &[String], part_count: usize) -> String {
     let mut hasher = Md5::new();
 
@@ -58,3 +54,49 @@ pub fn multipart_etag(part_etags: &[String], part_count: usize) -> String {
     let hash = hasher.finalize();
     format!("{}-{}", hex::encode(hash), part_count)
 }
+
+/// Whether an ETag has the `<md5-of-md5s>-<parts>` form of a multipart
+/// object, and so is not the MD5 of the object data
+pub fn is_multipart_etag(etag: &str) -> bool {
+    etag.trim_matches('"')
+        .rsplit_once('-')
+        .is_some_and(|(hash, count)| {
+            hash.len() == 32 && !count.is_empty() && count.bytes().all(|b| b.is_ascii_digit())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected values are the ETags S3 returns for the same payloads
+
+    #[test]
+    fn test_single_put_etag() {
+        assert_eq!(md5_hash(b""), "d41d8cd98f00b204e9800998ecf8427e");
+        assert_eq!(md5_hash(b"hello world"), "5eb63bbbe01eeed093cb22bb8f5acdc3");
+        assert!(!is_multipart_etag("5eb63bbbe01eeed093cb22bb8f5acdc3"));
+    }
+
+    #[test]
+    fn test_multipart_etag() {
+        let parts = vec![
+            md5_hash(&vec![b'a'; 5 * 1024 * 1024]),
+            md5_hash(b"b"),
+        ];
+        assert_eq!(parts[0], "79b281060d337b9b2b84ccf390adcf74");
+        let etag = multipart_etag(&parts, parts.len());
+        assert_eq!(etag, "e5a8c5272b26fc10581a21089559b006-2");
+        assert!(is_multipart_etag(&etag));
+        assert!(is_multipart_etag(&format!("\"{}\"", etag)));
+
+        // Quoted part ETags, as clients send them back, give the same result
+        let quoted: Vec<String> = parts.iter().map(|p| format!("\"{}\"", p)).collect();
+        assert_eq!(multipart_etag(&quoted, quoted.len()), etag);
+
+        // A one-part upload still gets the multipart form
+        let one = multipart_etag(&[md5_hash(b"hello world")], 1);
+        assert_eq!(one, "241d8a27c836427bd7f04461b60e7359-1");
+        assert!(!is_multipart_etag("not-an-etag"));
+    }
+}
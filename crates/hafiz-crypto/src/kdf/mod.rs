@@ -0,0 +1,233 @@
+//! Derivation of the master key from a passphrase
+//!
+//! Argon2id is the default. PBKDF2-HMAC-SHA256 is there for deployments
+//! that need a FIPS-approved derivation; it runs on the build's
+//! [`crate::backend`]. The unsalted single SHA-256 of
+//! [`KeyManager::from_passphrase`](crate::KeyManager::from_passphrase) is
+//! kept only so keys derived that way can be migrated.
+//!
+//! The salt and cost settings are not secret and must be stored: the same
+//! passphrase gives a different key under any other salt.
+
+use std::fmt;
+use std::str::FromStr;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+
+use crate::backend::{backend, KEY_LEN};
+use crate::encryption::EncryptionError;
+
+/// Length of a fresh salt
+pub const SALT_LEN: usize = 16;
+
+/// Argon2id memory cost in KiB, as recommended by OWASP
+pub const DEFAULT_ARGON2_MEMORY_KIB: u32 = 19 * 1024;
+pub const DEFAULT_ARGON2_ITERATIONS: u32 = 2;
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = 1;
+/// PBKDF2-HMAC-SHA256 iterations, as recommended by OWASP
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 600_000;
+
+/// Message MACed under a derived key to recognise it later
+const KEY_CHECK_MESSAGE: &[u8] = b"hafiz master key check";
+
+/// Key derivation function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfAlgorithm {
+    Argon2id,
+    Pbkdf2Sha256,
+    /// Single unsalted SHA-256; legacy, only for migrating from it
+    Sha256,
+}
+
+impl KdfAlgorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KdfAlgorithm::Argon2id => "argon2id",
+            KdfAlgorithm::Pbkdf2Sha256 => "pbkdf2-sha256",
+            KdfAlgorithm::Sha256 => "sha256",
+        }
+    }
+}
+
+impl fmt::Display for KdfAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for KdfAlgorithm {
+    type Err = EncryptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "argon2id" => Ok(KdfAlgorithm::Argon2id),
+            "pbkdf2-sha256" => Ok(KdfAlgorithm::Pbkdf2Sha256),
+            "sha256" => Ok(KdfAlgorithm::Sha256),
+            other => Err(EncryptionError::KeyDerivationFailed(format!(
+                "Unknown key derivation function: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Everything besides the passphrase that a derived key depends on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KdfParams {
+    pub algorithm: KdfAlgorithm,
+    pub salt: Vec<u8>,
+    /// Argon2id memory cost in KiB; unused otherwise
+    pub memory_kib: u32,
+    /// Argon2id passes or PBKDF2 iterations
+    pub iterations: u32,
+    /// Argon2id lanes; unused otherwise
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Argon2id with a fresh salt
+    pub fn argon2id(memory_kib: u32, iterations: u32, parallelism: u32) -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Argon2id,
+            salt: fresh_salt(),
+            memory_kib,
+            iterations,
+            parallelism,
+        }
+    }
+
+    /// PBKDF2-HMAC-SHA256 with a fresh salt
+    pub fn pbkdf2_sha256(iterations: u32) -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Pbkdf2Sha256,
+            salt: fresh_salt(),
+            memory_kib: 0,
+            iterations,
+            parallelism: 0,
+        }
+    }
+
+    /// The unsalted SHA-256 of [`KeyManager::from_passphrase`](crate::KeyManager::from_passphrase)
+    pub fn legacy_sha256() -> Self {
+        Self {
+            algorithm: KdfAlgorithm::Sha256,
+            salt: Vec::new(),
+            memory_kib: 0,
+            iterations: 0,
+            parallelism: 0,
+        }
+    }
+
+    /// Whether `other` uses the same function at the same cost; salts are
+    /// not compared
+    pub fn same_settings(&self, other: &KdfParams) -> bool {
+        self.algorithm == other.algorithm
+            && self.memory_kib == other.memory_kib
+            && self.iterations == other.iterations
+            && self.parallelism == other.parallelism
+    }
+
+    /// Derive a 256-bit key from `passphrase`
+    pub fn derive(&self, passphrase: &str) -> Result<[u8; KEY_LEN], EncryptionError> {
+        if passphrase.is_empty() {
+            return Err(EncryptionError::KeyDerivationFailed("Passphrase is empty".into()));
+        }
+
+        let mut key = [0u8; KEY_LEN];
+        match self.algorithm {
+            KdfAlgorithm::Argon2id => {
+                let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LEN))
+                    .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))?;
+                Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                    .hash_password_into(passphrase.as_bytes(), &self.salt, &mut key)
+                    .map_err(|e| EncryptionError::KeyDerivationFailed(e.to_string()))?;
+            }
+            KdfAlgorithm::Pbkdf2Sha256 => {
+                if self.iterations == 0 {
+                    return Err(EncryptionError::KeyDerivationFailed(
+                        "PBKDF2 needs at least one iteration".into(),
+                    ));
+                }
+                key = pbkdf2_hmac_sha256(passphrase.as_bytes(), &self.salt, self.iterations);
+            }
+            KdfAlgorithm::Sha256 => key = backend().sha256(passphrase.as_bytes()),
+        }
+        Ok(key)
+    }
+}
+
+/// Value that identifies a key without revealing it, to tell whether a
+/// passphrase gives the key secrets were sealed under
+pub fn key_check(key: &[u8]) -> String {
+    hex::encode(backend().hmac_sha256(key, KEY_CHECK_MESSAGE))
+}
+
+fn fresh_salt() -> Vec<u8> {
+    let mut salt = vec![0u8; SALT_LEN];
+    backend().fill_random(&mut salt);
+    salt
+}
+
+/// PBKDF2 (RFC 8018) for a single 32-byte block
+fn pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; KEY_LEN] {
+    let backend = backend();
+    let mut block = salt.to_vec();
+    block.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut u = backend.hmac_sha256(password, &block);
+    let mut out = u;
+    for _ in 1..iterations {
+        u = backend.hmac_sha256(password, &u);
+        out.iter_mut().zip(u.iter()).for_each(|(o, u)| *o ^= u);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pbkdf2_known_answer() {
+        // RFC 7914 section 11
+        let params = KdfParams {
+            salt: b"salt".to_vec(),
+            ..KdfParams::pbkdf2_sha256(1)
+        };
+        assert_eq!(
+            hex::encode(params.derive("passwd").unwrap()),
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+    }
+
+    #[test]
+    fn test_derivation_depends_on_salt_and_settings() {
+        let a = KdfParams::argon2id(64, 1, 1);
+        let b = KdfParams::argon2id(64, 1, 1);
+        assert!(a.same_settings(&b));
+        assert_ne!(a.salt, b.salt);
+
+        let key = a.derive("correct horse").unwrap();
+        assert_eq!(key, a.derive("correct horse").unwrap());
+        assert_ne!(key, a.derive("battery staple").unwrap());
+        assert_ne!(key, b.derive("correct horse").unwrap());
+        assert!(!a.same_settings(&KdfParams::argon2id(128, 1, 1)));
+        assert!(a.derive("").is_err());
+
+        assert_eq!(key_check(&key), key_check(&a.derive("correct horse").unwrap()));
+        assert_ne!(key_check(&key), key_check(&b.derive("correct horse").unwrap()));
+    }
+
+    #[test]
+    fn test_legacy_sha256_matches_from_passphrase() {
+        use crate::KeyManager;
+
+        let key = KdfParams::legacy_sha256().derive("test-master-key").unwrap();
+        let legacy = KeyManager::from_passphrase("test-master-key").unwrap();
+        let (encrypted, nonce) = legacy.encrypt_dek(&[9u8; 32]).unwrap();
+        assert_eq!(KeyManager::new(&key).unwrap().decrypt_dek(&encrypted, &nonce).unwrap(), [9u8; 32]);
+
+        assert_eq!("pbkdf2-sha256".parse::<KdfAlgorithm>().unwrap(), KdfAlgorithm::Pbkdf2Sha256);
+        assert!("scrypt".parse::<KdfAlgorithm>().is_err());
+    }
+}
@@ -1,7 +1,17 @@
 //! Cryptography utilities for Hafiz
 
+pub mod account_keys;
+pub mod backend;
 pub mod encryption;
 pub mod hash;
+pub mod kdf;
+pub mod providers;
+pub mod secrets;
 
+pub use account_keys::{account_key_id, parse_account_key_id, AccountKeys, FIRST_KEY_GENERATION};
+pub use backend::{backend, CryptoBackend};
 pub use encryption::*;
 pub use hash::*;
+pub use kdf::{key_check, KdfAlgorithm, KdfParams};
+pub use providers::{EnvKeyProvider, FileKeyProvider, MasterKeyProvider};
+pub use secrets::{is_sealed, SecretCipher};
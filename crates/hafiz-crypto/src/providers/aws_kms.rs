@@ -0,0 +1,217 @@
+//! AWS KMS provider
+//!
+//! The master key is wrapped with `aws kms encrypt`; the base64
+//! `CiphertextBlob` is kept in a file and passed to KMS `Decrypt` on
+//! startup. Requests are signed with Signature Version 4 using the
+//! configured credentials, or `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+//! and `AWS_SESSION_TOKEN` from the environment.
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use chrono::Utc;
+use serde_json::{json, Value};
+
+use super::{parse_key_material, read_wrapped_key, MasterKeyProvider};
+use crate::backend::KEY_LEN;
+use crate::encryption::EncryptionError;
+use crate::hash::{hmac_sha256, hmac_sha256_hex, sha256_hash};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Master key wrapped by an AWS KMS key
+pub struct AwsKmsProvider {
+    pub region: String,
+    /// KMS endpoint; defaults to `https://kms.<region>.amazonaws.com`
+    pub endpoint: Option<String>,
+    /// Key the blob was encrypted with; required for asymmetric keys
+    pub key_id: Option<String>,
+    pub access_key_id: Option<String>,
+    pub secret_access_key: Option<String>,
+    /// File holding the base64 `CiphertextBlob` of the master key
+    pub wrapped_key_file: PathBuf,
+}
+
+/// Credentials to sign with
+struct Credentials {
+    access_key_id: String,
+    secret_access_key: String,
+    session_token: Option<String>,
+}
+
+impl AwsKmsProvider {
+    fn credentials(&self) -> Result<Credentials, EncryptionError> {
+        if let (Some(access_key_id), Some(secret_access_key)) = (&self.access_key_id, &self.secret_access_key) {
+            return Ok(Credentials {
+                access_key_id: access_key_id.clone(),
+                secret_access_key: secret_access_key.clone(),
+                session_token: None,
+            });
+        }
+        match (std::env::var("AWS_ACCESS_KEY_ID"), std::env::var("AWS_SECRET_ACCESS_KEY")) {
+            (Ok(access_key_id), Ok(secret_access_key)) => Ok(Credentials {
+                access_key_id,
+                secret_access_key,
+                session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+            }),
+            _ => Err(EncryptionError::ProviderFailed("No AWS credentials for KMS".into())),
+        }
+    }
+
+    fn endpoint(&self) -> String {
+        self.endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://kms.{}.amazonaws.com", self.region))
+    }
+}
+
+#[async_trait]
+impl MasterKeyProvider for AwsKmsProvider {
+    fn describe(&self) -> String {
+        match &self.key_id {
+            Some(key_id) => format!("AWS KMS key {} in {}", key_id, self.region),
+            None => format!("AWS KMS in {}", self.region),
+        }
+    }
+
+    async fn master_key(&self) -> Result<[u8; KEY_LEN], EncryptionError> {
+        let blob = read_wrapped_key(&self.wrapped_key_file)?;
+        let mut request = json!({ "CiphertextBlob": blob });
+        if let Some(key_id) = &self.key_id {
+            request["KeyId"] = json!(key_id);
+        }
+        let body = request.to_string();
+
+        let endpoint = self.endpoint();
+        let host = reqwest::Url::parse(&endpoint)
+            .ok()
+            .and_then(|url| {
+                let host = url.host_str()?.to_string();
+                Some(match url.port() {
+                    Some(port) => format!("{}:{}", host, port),
+                    None => host,
+                })
+            })
+            .ok_or_else(|| EncryptionError::ProviderFailed(format!("Invalid KMS endpoint {}", endpoint)))?;
+
+        let credentials = self.credentials()?;
+        let mut headers = BTreeMap::new();
+        headers.insert("content-type".to_string(), "application/x-amz-json-1.1".to_string());
+        headers.insert("host".to_string(), host);
+        headers.insert("x-amz-date".to_string(), Utc::now().format("%Y%m%dT%H%M%SZ").to_string());
+        headers.insert("x-amz-target".to_string(), "TrentService.Decrypt".to_string());
+        if let Some(token) = &credentials.session_token {
+            headers.insert("x-amz-security-token".to_string(), token.clone());
+        }
+        let authorization = sign_v4(&headers, body.as_bytes(), &credentials, &self.region, "kms");
+
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| EncryptionError::ProviderFailed(e.to_string()))?;
+        let mut request = client.post(&endpoint).header("authorization", authorization).body(body);
+        for (name, value) in &headers {
+            if name != "host" {
+                request = request.header(name.as_str(), value.as_str());
+            }
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| EncryptionError::ProviderFailed(format!("KMS request failed: {}", e)))?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| EncryptionError::ProviderFailed(format!("Invalid KMS response: {}", e)))?;
+        if !status.is_success() {
+            return Err(EncryptionError::ProviderFailed(format!(
+                "KMS returned {}: {} {}",
+                status,
+                body["__type"].as_str().unwrap_or_default(),
+                body["message"].as_str().or(body["Message"].as_str()).unwrap_or_default()
+            )));
+        }
+
+        let plaintext = body["Plaintext"]
+            .as_str()
+            .ok_or_else(|| EncryptionError::ProviderFailed("KMS response has no Plaintext".into()))?;
+        let material = STANDARD
+            .decode(plaintext)
+            .map_err(|e| EncryptionError::ProviderFailed(format!("Invalid plaintext from KMS: {}", e)))?;
+        parse_key_material(&material)
+    }
+}
+
+/// `Authorization` header for a `POST /` with every header in `headers`
+/// signed; `headers` must hold `x-amz-date`
+fn sign_v4(
+    headers: &BTreeMap<String, String>,
+    body: &[u8],
+    credentials: &Credentials,
+    region: &str,
+    service: &str,
+) -> String {
+    let amz_date = &headers["x-amz-date"];
+    let date = &amz_date[..8];
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let canonical_request = format!(
+        "POST\n/\n\n{}\n{}\n{}",
+        canonical_headers,
+        signed_headers,
+        sha256_hash(body)
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        amz_date,
+        scope,
+        sha256_hash(canonical_request.as_bytes())
+    );
+    let key = hmac_sha256(format!("AWS4{}", credentials.secret_access_key).as_bytes(), date.as_bytes());
+    let key = hmac_sha256(&key, region.as_bytes());
+    let key = hmac_sha256(&key, service.as_bytes());
+    let key = hmac_sha256(&key, b"aws4_request");
+    let signature = hmac_sha256_hex(&key, string_to_sign.as_bytes());
+
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+        credentials.access_key_id, scope, signed_headers, signature
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_v4_decrypt_request() {
+        let mut headers = BTreeMap::new();
+        headers.insert("content-type".to_string(), "application/x-amz-json-1.1".to_string());
+        headers.insert("host".to_string(), "kms.us-east-1.amazonaws.com".to_string());
+        headers.insert("x-amz-date".to_string(), "20150830T123600Z".to_string());
+        headers.insert("x-amz-target".to_string(), "TrentService.Decrypt".to_string());
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+
+        let authorization = sign_v4(&headers, br#"{"CiphertextBlob":"AQID"}"#, &credentials, "us-east-1", "kms");
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/kms/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date;x-amz-target, \
+             Signature=b346aa351002213bf18c7a8eff78545bb55c3615133a6564b4e3b0a0f8ed407b"
+        );
+    }
+}
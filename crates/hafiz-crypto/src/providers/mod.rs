@@ -0,0 +1,146 @@
+//! Sources of the master key
+//!
+//! A [`MasterKeyProvider`] hands out the 256-bit master encryption key at
+//! startup, so it never has to be written into the config file:
+//!
+//! - [`FileKeyProvider`]: hex key in a local file
+//! - [`EnvKeyProvider`]: hex key in an environment variable
+//! - [`VaultTransitProvider`]: key wrapped by a HashiCorp Vault transit key
+//! - [`AwsKmsProvider`]: key wrapped by an AWS KMS key
+//!
+//! The remote providers only ever see the wrapped key, which is stored
+//! next to the server; they unwrap it on every start. The remote providers
+//! are behind the `vault` and `aws-kms` features.
+
+#[cfg(feature = "aws-kms")]
+mod aws_kms;
+#[cfg(feature = "vault")]
+mod vault;
+
+#[cfg(feature = "aws-kms")]
+pub use aws_kms::AwsKmsProvider;
+#[cfg(feature = "vault")]
+pub use vault::VaultTransitProvider;
+
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+
+use crate::backend::KEY_LEN;
+use crate::encryption::EncryptionError;
+
+/// Source of the master encryption key
+#[async_trait]
+pub trait MasterKeyProvider: Send + Sync {
+    /// Short description of where the key comes from, for logs
+    fn describe(&self) -> String;
+
+    /// Fetch or unwrap the master key
+    async fn master_key(&self) -> Result<[u8; KEY_LEN], EncryptionError>;
+}
+
+/// Master key as 32 raw bytes or 64 hex characters; surrounding whitespace
+/// is ignored in the hex form
+pub fn parse_key_material(material: &[u8]) -> Result<[u8; KEY_LEN], EncryptionError> {
+    if material.len() == KEY_LEN {
+        let mut key = [0u8; KEY_LEN];
+        key.copy_from_slice(material);
+        return Ok(key);
+    }
+
+    let text = std::str::from_utf8(material)
+        .map_err(|_| EncryptionError::InvalidKey("Master key must be 32 bytes or 64 hex characters".into()))?;
+    let bytes = hex::decode(text.trim())
+        .map_err(|e| EncryptionError::InvalidKey(format!("Invalid master key hex: {}", e)))?;
+    crate::backend::key_array(&bytes)
+        .map_err(|_| EncryptionError::InvalidKey("Master key must be 32 bytes (64 hex characters)".into()))
+}
+
+/// Read a wrapped key from a file, as the base64 or text a KMS returned
+#[cfg(any(feature = "vault", feature = "aws-kms"))]
+pub(crate) fn read_wrapped_key(path: &std::path::Path) -> Result<String, EncryptionError> {
+    let wrapped = std::fs::read_to_string(path).map_err(|e| {
+        EncryptionError::ProviderFailed(format!("Failed to read wrapped key {}: {}", path.display(), e))
+    })?;
+    Ok(wrapped.trim().to_string())
+}
+
+/// Hex master key in a local file
+pub struct FileKeyProvider {
+    path: PathBuf,
+}
+
+impl FileKeyProvider {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+#[async_trait]
+impl MasterKeyProvider for FileKeyProvider {
+    fn describe(&self) -> String {
+        format!("file {}", self.path.display())
+    }
+
+    async fn master_key(&self) -> Result<[u8; KEY_LEN], EncryptionError> {
+        let content = std::fs::read(&self.path).map_err(|e| {
+            EncryptionError::ProviderFailed(format!("Failed to read key file {}: {}", self.path.display(), e))
+        })?;
+        parse_key_material(&content)
+    }
+}
+
+/// Hex master key in an environment variable
+pub struct EnvKeyProvider {
+    var: String,
+}
+
+impl EnvKeyProvider {
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+}
+
+#[async_trait]
+impl MasterKeyProvider for EnvKeyProvider {
+    fn describe(&self) -> String {
+        format!("environment variable {}", self.var)
+    }
+
+    async fn master_key(&self) -> Result<[u8; KEY_LEN], EncryptionError> {
+        let value = std::env::var(&self.var)
+            .map_err(|_| EncryptionError::ProviderFailed(format!("{} is not set", self.var)))?;
+        parse_key_material(value.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_material_is_raw_or_hex() {
+        let hex_key = "00".repeat(31) + "ff";
+        assert_eq!(parse_key_material(hex_key.as_bytes()).unwrap()[31], 0xff);
+        assert_eq!(parse_key_material(format!("{}\n", hex_key).as_bytes()).unwrap()[31], 0xff);
+        assert_eq!(parse_key_material(&[7u8; 32]).unwrap(), [7u8; 32]);
+        assert!(parse_key_material(b"abcd").is_err());
+        assert!(parse_key_material(&[7u8; 33]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_file_and_env_providers() {
+        let path = std::env::temp_dir().join(format!("hafiz-master-key-{}", std::process::id()));
+        std::fs::write(&path, format!("{}\n", "ab".repeat(32))).unwrap();
+        let key = FileKeyProvider::new(&path).master_key().await.unwrap();
+        assert_eq!(key, [0xab; 32]);
+        std::fs::remove_file(&path).unwrap();
+        assert!(FileKeyProvider::new(&path).master_key().await.is_err());
+
+        let var = format!("HAFIZ_TEST_MASTER_KEY_{}", std::process::id());
+        assert!(EnvKeyProvider::new(&var).master_key().await.is_err());
+        std::env::set_var(&var, "cd".repeat(32));
+        assert_eq!(EnvKeyProvider::new(&var).master_key().await.unwrap(), [0xcd; 32]);
+        std::env::remove_var(&var);
+    }
+}
@@ -0,0 +1,87 @@
+//! HashiCorp Vault transit provider
+//!
+//! The master key is wrapped with `vault write transit/encrypt/<key>`; the
+//! resulting `vault:v1:...` ciphertext is kept in a file and decrypted
+//! through the transit secrets engine on startup.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde_json::{json, Value};
+
+use super::{parse_key_material, read_wrapped_key, MasterKeyProvider};
+use crate::backend::KEY_LEN;
+use crate::encryption::EncryptionError;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Master key wrapped by a Vault transit key
+pub struct VaultTransitProvider {
+    /// Vault address, e.g. `https://vault.internal:8200`
+    pub address: String,
+    pub token: String,
+    /// Mount path of the transit engine
+    pub mount: String,
+    /// Name of the transit key
+    pub key_name: String,
+    /// Vault Enterprise namespace
+    pub namespace: Option<String>,
+    /// File holding the `vault:v1:...` ciphertext of the master key
+    pub wrapped_key_file: PathBuf,
+}
+
+#[async_trait]
+impl MasterKeyProvider for VaultTransitProvider {
+    fn describe(&self) -> String {
+        format!("Vault transit key {} at {}", self.key_name, self.address)
+    }
+
+    async fn master_key(&self) -> Result<[u8; KEY_LEN], EncryptionError> {
+        let ciphertext = read_wrapped_key(&self.wrapped_key_file)?;
+        let url = format!(
+            "{}/v1/{}/decrypt/{}",
+            self.address.trim_end_matches('/'),
+            self.mount.trim_matches('/'),
+            self.key_name
+        );
+
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| EncryptionError::ProviderFailed(e.to_string()))?;
+        let mut request = client
+            .post(&url)
+            .header("X-Vault-Token", &self.token)
+            .json(&json!({ "ciphertext": ciphertext }));
+        if let Some(namespace) = &self.namespace {
+            request = request.header("X-Vault-Namespace", namespace);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| EncryptionError::ProviderFailed(format!("Vault request failed: {}", e)))?;
+        let status = response.status();
+        let body: Value = response
+            .json()
+            .await
+            .map_err(|e| EncryptionError::ProviderFailed(format!("Invalid Vault response: {}", e)))?;
+        if !status.is_success() {
+            return Err(EncryptionError::ProviderFailed(format!(
+                "Vault returned {}: {}",
+                status,
+                body["errors"]
+            )));
+        }
+
+        let plaintext = body["data"]["plaintext"]
+            .as_str()
+            .ok_or_else(|| EncryptionError::ProviderFailed("Vault response has no plaintext".into()))?;
+        let material = STANDARD
+            .decode(plaintext)
+            .map_err(|e| EncryptionError::ProviderFailed(format!("Invalid plaintext from Vault: {}", e)))?;
+        parse_key_material(&material)
+    }
+}
@@ -0,0 +1,110 @@
+//! Encryption of stored credentials
+//!
+//! Secret keys cannot be stored as hashes: SigV4 signs with the secret
+//! itself, so the server must be able to recover it. Instead each secret is
+//! envelope-encrypted: a fresh DEK encrypts the secret, the master key
+//! encrypts the DEK. The owning access key is bound in as associated data,
+//! so a sealed secret copied to another row does not decrypt.
+//!
+//! Sealed values are text, `hafiz:v1:` followed by base64 of
+//! `dek_nonce || encrypted_dek || data_nonce || ciphertext`.
+
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+
+use crate::backend::{backend, nonce_array, random_nonce, KEY_LEN, NONCE_LEN, TAG_LEN};
+use crate::encryption::{EncryptionError, KeyManager};
+
+/// Prefix of a sealed secret
+const SEALED_PREFIX: &str = "hafiz:v1:";
+
+/// 32-byte DEK plus the 16-byte GCM tag
+const ENCRYPTED_DEK_LEN: usize = KEY_LEN + TAG_LEN;
+
+/// Whether a stored value is a sealed secret rather than plaintext
+pub fn is_sealed(stored: &str) -> bool {
+    stored.starts_with(SEALED_PREFIX)
+}
+
+/// Seals and opens secrets under the master key
+pub struct SecretCipher {
+    key_manager: KeyManager,
+}
+
+impl SecretCipher {
+    pub fn new(master_key: &[u8]) -> Result<Self, EncryptionError> {
+        Ok(Self {
+            key_manager: KeyManager::new(master_key)?,
+        })
+    }
+
+    /// Encrypt `secret` for the row identified by `context`
+    pub fn seal(&self, context: &str, secret: &str) -> Result<String, EncryptionError> {
+        let dek = self.key_manager.generate_dek();
+        let (encrypted_dek, dek_nonce) = self.key_manager.encrypt_dek(&dek)?;
+
+        let data_nonce = random_nonce();
+        let ciphertext = backend().aes256_gcm_seal(&dek, &data_nonce, context.as_bytes(), secret.as_bytes())?;
+
+        let mut sealed = Vec::with_capacity(2 * NONCE_LEN + ENCRYPTED_DEK_LEN + ciphertext.len());
+        sealed.extend_from_slice(&dek_nonce);
+        sealed.extend_from_slice(&encrypted_dek);
+        sealed.extend_from_slice(&data_nonce);
+        sealed.extend_from_slice(&ciphertext);
+
+        Ok(format!("{}{}", SEALED_PREFIX, STANDARD.encode(sealed)))
+    }
+
+    /// Decrypt a value produced by [`seal`](Self::seal) with the same context
+    pub fn open(&self, context: &str, stored: &str) -> Result<String, EncryptionError> {
+        let encoded = stored
+            .strip_prefix(SEALED_PREFIX)
+            .ok_or_else(|| EncryptionError::DecryptionFailed("Not a sealed secret".into()))?;
+        let sealed = STANDARD
+            .decode(encoded)
+            .map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))?;
+        if sealed.len() < 2 * NONCE_LEN + ENCRYPTED_DEK_LEN {
+            return Err(EncryptionError::DecryptionFailed("Sealed secret too short".into()));
+        }
+
+        let (dek_nonce, rest) = sealed.split_at(NONCE_LEN);
+        let (encrypted_dek, rest) = rest.split_at(ENCRYPTED_DEK_LEN);
+        let (data_nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let dek = self.key_manager.decrypt_dek(encrypted_dek, dek_nonce)?;
+        let secret = backend().aes256_gcm_open(&dek, &nonce_array(data_nonce)?, context.as_bytes(), ciphertext)?;
+
+        String::from_utf8(secret).map_err(|e| EncryptionError::DecryptionFailed(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cipher(byte: u8) -> SecretCipher {
+        SecretCipher::new(&[byte; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_seal_roundtrip() {
+        let c = cipher(7);
+        let sealed = c.seal("AKIAEXAMPLE", "wJalrXUtnFEMI/K7MDENG").unwrap();
+
+        assert!(is_sealed(&sealed));
+        assert!(!sealed.contains("wJalrXUtnFEMI"));
+        assert_eq!(c.open("AKIAEXAMPLE", &sealed).unwrap(), "wJalrXUtnFEMI/K7MDENG");
+
+        // Fresh DEK and nonces every time
+        assert_ne!(sealed, c.seal("AKIAEXAMPLE", "wJalrXUtnFEMI/K7MDENG").unwrap());
+    }
+
+    #[test]
+    fn test_open_rejects_wrong_context_or_key() {
+        let sealed = cipher(7).seal("AKIAEXAMPLE", "secret").unwrap();
+
+        assert!(cipher(7).open("AKIAOTHER", &sealed).is_err());
+        assert!(cipher(8).open("AKIAEXAMPLE", &sealed).is_err());
+        assert!(cipher(7).open("AKIAEXAMPLE", "secret").is_err());
+        assert!(!is_sealed("secret"));
+    }
+}
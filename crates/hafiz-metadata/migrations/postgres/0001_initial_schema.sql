@@ -0,0 +1,102 @@
+-- Schema of the PostgreSQL metadata store as of the first versioned
+-- migration. IF NOT EXISTS lets databases created before migrations existed
+-- be adopted. Later schema changes get a migration of their own; never edit
+-- one that has been released.
+
+-- Users table
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    access_key TEXT UNIQUE NOT NULL,
+    secret_key TEXT NOT NULL,
+    display_name TEXT,
+    email TEXT,
+    is_admin BOOLEAN DEFAULT FALSE,
+    enabled BOOLEAN DEFAULT TRUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Buckets table
+CREATE TABLE IF NOT EXISTS buckets (
+    name TEXT PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    region TEXT NOT NULL DEFAULT 'us-east-1',
+    versioning TEXT DEFAULT '',
+    object_lock_enabled BOOLEAN DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Objects table
+CREATE TABLE IF NOT EXISTS objects (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT 'null',
+    size BIGINT NOT NULL,
+    etag TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    metadata JSONB,
+    last_modified TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    is_latest BOOLEAN DEFAULT TRUE,
+    is_delete_marker BOOLEAN DEFAULT FALSE,
+    encryption JSONB,
+    PRIMARY KEY (bucket, key, version_id)
+);
+
+-- Indexes
+CREATE INDEX IF NOT EXISTS idx_objects_bucket ON objects(bucket);
+
+CREATE INDEX IF NOT EXISTS idx_objects_latest ON objects(bucket, key, is_latest);
+
+CREATE INDEX IF NOT EXISTS idx_objects_prefix ON objects(bucket, key text_pattern_ops);
+
+-- Prefix listings scan byte-ordered key ranges
+CREATE INDEX IF NOT EXISTS idx_objects_key_range ON objects(bucket, (key COLLATE "C"));
+
+-- Object tags table
+CREATE TABLE IF NOT EXISTS object_tags (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT 'null',
+    tag_key TEXT NOT NULL,
+    tag_value TEXT NOT NULL,
+    PRIMARY KEY (bucket, key, version_id, tag_key)
+);
+
+-- Bucket lifecycle table
+CREATE TABLE IF NOT EXISTS bucket_lifecycle (
+    bucket TEXT PRIMARY KEY,
+    configuration JSONB NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Multipart uploads table
+CREATE TABLE IF NOT EXISTS multipart_uploads (
+    upload_id TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    content_type TEXT NOT NULL DEFAULT 'application/octet-stream',
+    metadata JSONB,
+    storage_class TEXT NOT NULL DEFAULT 'STANDARD',
+    initiator_id TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (bucket, key, upload_id)
+);
+
+-- Upload parts table
+CREATE TABLE IF NOT EXISTS upload_parts (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    upload_id TEXT NOT NULL,
+    part_number INTEGER NOT NULL,
+    size BIGINT NOT NULL,
+    etag TEXT NOT NULL,
+    last_modified TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (bucket, key, upload_id, part_number)
+);
+
+-- Bucket tags table
+CREATE TABLE IF NOT EXISTS bucket_tags (
+    bucket TEXT NOT NULL,
+    tag_key TEXT NOT NULL,
+    tag_value TEXT NOT NULL,
+    PRIMARY KEY (bucket, tag_key)
+);
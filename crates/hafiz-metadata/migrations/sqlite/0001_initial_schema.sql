@@ -0,0 +1,345 @@
+-- Schema of the SQLite metadata store as of the first versioned migration.
+--
+-- Statements use IF NOT EXISTS so that databases created before migrations
+-- existed can be adopted: MetadataStore adds the columns those databases
+-- may lack before this runs, and this migration creates whatever else is
+-- missing. Later schema changes get a migration of their own; never edit
+-- one that has been released.
+
+CREATE TABLE IF NOT EXISTS users (
+    id TEXT PRIMARY KEY,
+    access_key TEXT UNIQUE NOT NULL,
+    secret_key TEXT NOT NULL,
+    display_name TEXT,
+    email TEXT,
+    is_admin INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL,
+    enabled INTEGER NOT NULL DEFAULT 1,
+    last_used TEXT,
+    key_created_at TEXT
+);
+
+-- Previous keys of rotated users, valid until the grace period ends
+CREATE TABLE IF NOT EXISTS retiring_keys (
+    access_key TEXT PRIMARY KEY,
+    secret_key TEXT NOT NULL,
+    user_access_key TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL,
+    last_used TEXT
+);
+
+-- Users provisioned from an LDAP directory
+CREATE TABLE IF NOT EXISTS ldap_users (
+    username TEXT PRIMARY KEY,
+    access_key TEXT NOT NULL,
+    dn TEXT NOT NULL,
+    groups TEXT NOT NULL,
+    policies TEXT NOT NULL,
+    synced_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS buckets (
+    name TEXT PRIMARY KEY,
+    owner_id TEXT NOT NULL,
+    region TEXT NOT NULL,
+    versioning TEXT DEFAULT '',
+    object_lock_enabled INTEGER DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+-- Objects table with versioning support
+-- version_id: "null" for non-versioned, UUID for versioned
+-- is_latest: 1 for current version, 0 for old versions
+-- is_delete_marker: 1 if this is a delete marker
+-- encryption: JSON containing encryption info
+-- part_sizes: JSON array of the part sizes of multipart objects
+CREATE TABLE IF NOT EXISTS objects (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT 'null',
+    size INTEGER NOT NULL,
+    etag TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    metadata TEXT,
+    last_modified TEXT NOT NULL,
+    is_latest INTEGER DEFAULT 1,
+    is_delete_marker INTEGER DEFAULT 0,
+    encryption TEXT,
+    part_sizes TEXT,
+    storage_class TEXT NOT NULL DEFAULT 'STANDARD',
+    PRIMARY KEY (bucket, key, version_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_objects_bucket ON objects(bucket);
+
+CREATE INDEX IF NOT EXISTS idx_objects_latest ON objects(bucket, key, is_latest);
+
+-- Prefix listings scan key ranges of the current, live objects
+CREATE INDEX IF NOT EXISTS idx_objects_listing ON objects(bucket, key)
+WHERE is_latest = 1 AND is_delete_marker = 0;
+
+CREATE INDEX IF NOT EXISTS idx_objects_versions ON objects(bucket, key, last_modified);
+
+-- Per-bucket count and size of stored versions (delete markers excluded),
+-- kept current by triggers on objects so readers never have to scan the
+-- bucket. The triggers avoid OR IGNORE, which the upsert in insert_object
+-- would override.
+CREATE TABLE IF NOT EXISTS bucket_usage (
+    bucket TEXT PRIMARY KEY,
+    object_count INTEGER NOT NULL DEFAULT 0,
+    total_bytes INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE TRIGGER IF NOT EXISTS objects_usage_insert
+AFTER INSERT ON objects WHEN NEW.is_delete_marker = 0
+BEGIN
+    INSERT INTO bucket_usage (bucket) SELECT NEW.bucket
+    WHERE NOT EXISTS (SELECT 1 FROM bucket_usage WHERE bucket = NEW.bucket);
+    UPDATE bucket_usage
+    SET object_count = object_count + 1, total_bytes = total_bytes + NEW.size
+    WHERE bucket = NEW.bucket;
+END;
+
+CREATE TRIGGER IF NOT EXISTS objects_usage_delete
+AFTER DELETE ON objects WHEN OLD.is_delete_marker = 0
+BEGIN
+    UPDATE bucket_usage
+    SET object_count = object_count - 1, total_bytes = total_bytes - OLD.size
+    WHERE bucket = OLD.bucket;
+END;
+
+CREATE TRIGGER IF NOT EXISTS objects_usage_update
+AFTER UPDATE OF size, is_delete_marker ON objects
+BEGIN
+    INSERT INTO bucket_usage (bucket) SELECT NEW.bucket
+    WHERE NOT EXISTS (SELECT 1 FROM bucket_usage WHERE bucket = NEW.bucket);
+    UPDATE bucket_usage
+    SET object_count = object_count
+            - (OLD.is_delete_marker = 0) + (NEW.is_delete_marker = 0),
+        total_bytes = total_bytes
+            - CASE WHEN OLD.is_delete_marker = 0 THEN OLD.size ELSE 0 END
+            + CASE WHEN NEW.is_delete_marker = 0 THEN NEW.size ELSE 0 END
+    WHERE bucket = NEW.bucket;
+END;
+
+-- Adopted databases start the counters from one full count
+INSERT OR REPLACE INTO bucket_usage (bucket, object_count, total_bytes)
+SELECT bucket, COUNT(*), COALESCE(SUM(size), 0)
+FROM objects WHERE is_delete_marker = 0
+GROUP BY bucket;
+
+-- Object tagging table
+CREATE TABLE IF NOT EXISTS object_tags (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT 'null',
+    tag_key TEXT NOT NULL,
+    tag_value TEXT NOT NULL,
+    PRIMARY KEY (bucket, key, version_id, tag_key)
+);
+
+-- Bucket tagging table
+CREATE TABLE IF NOT EXISTS bucket_tags (
+    bucket TEXT NOT NULL,
+    tag_key TEXT NOT NULL,
+    tag_value TEXT NOT NULL,
+    PRIMARY KEY (bucket, tag_key)
+);
+
+-- Bucket lifecycle configuration table
+CREATE TABLE IF NOT EXISTS bucket_lifecycle (
+    bucket TEXT PRIMARY KEY,
+    configuration TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket policy table
+CREATE TABLE IF NOT EXISTS bucket_policies (
+    bucket TEXT PRIMARY KEY,
+    policy_json TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket ACL table
+CREATE TABLE IF NOT EXISTS bucket_acls (
+    bucket TEXT PRIMARY KEY,
+    acl_xml TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Object ACL table
+CREATE TABLE IF NOT EXISTS object_acls (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT 'null',
+    acl_xml TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (bucket, key, version_id)
+);
+
+-- Bucket notification configuration table
+CREATE TABLE IF NOT EXISTS bucket_notifications (
+    bucket TEXT PRIMARY KEY,
+    config_json TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket CORS configuration table
+CREATE TABLE IF NOT EXISTS bucket_cors (
+    bucket TEXT PRIMARY KEY,
+    cors_xml TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket consistency level table
+CREATE TABLE IF NOT EXISTS bucket_consistency (
+    bucket TEXT PRIMARY KEY,
+    level TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket storage backend table
+CREATE TABLE IF NOT EXISTS bucket_storage_backend (
+    bucket TEXT PRIMARY KEY,
+    backend TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket compression table
+CREATE TABLE IF NOT EXISTS bucket_compression (
+    bucket TEXT PRIMARY KEY,
+    codec TEXT NOT NULL,
+    level INTEGER,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket tiering table
+CREATE TABLE IF NOT EXISTS bucket_tiering (
+    bucket TEXT PRIMARY KEY,
+    archive_after_days INTEGER NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Bucket namespace mode table; absent means hierarchical
+CREATE TABLE IF NOT EXISTS bucket_namespace (
+    bucket TEXT PRIMARY KEY,
+    mode TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Sampled reads per object version, and whether tiering archived it
+CREATE TABLE IF NOT EXISTS object_access (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL,
+    last_accessed TEXT,
+    access_count INTEGER NOT NULL DEFAULT 0,
+    tiered INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (bucket, key, version_id)
+);
+
+-- Bucket Object Lock configuration table
+CREATE TABLE IF NOT EXISTS bucket_object_lock (
+    bucket TEXT PRIMARY KEY,
+    config_xml TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+-- Object retention table
+CREATE TABLE IF NOT EXISTS object_retention (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT '',
+    retention_xml TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (bucket, key, version_id)
+);
+
+-- Object legal hold table
+CREATE TABLE IF NOT EXISTS object_legal_hold (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT '',
+    hold_xml TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (bucket, key, version_id)
+);
+
+-- Deletes and overwrites that bypassed GOVERNANCE retention
+CREATE TABLE IF NOT EXISTS governance_bypass_log (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL DEFAULT '',
+    principal TEXT NOT NULL,
+    request_id TEXT NOT NULL,
+    retain_until TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_governance_bypass_bucket ON governance_bypass_log(bucket, id);
+
+-- Restores of archived objects; completed_at is NULL while one runs
+CREATE TABLE IF NOT EXISTS object_restores (
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL,
+    days INTEGER NOT NULL,
+    requested_at TEXT NOT NULL,
+    completed_at TEXT,
+    expires_at TEXT,
+    PRIMARY KEY (bucket, key, version_id)
+);
+
+-- Temporary credentials issued through STS
+CREATE TABLE IF NOT EXISTS sts_credentials (
+    access_key TEXT PRIMARY KEY,
+    secret_key TEXT NOT NULL,
+    session_token TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    username TEXT NOT NULL,
+    policies TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    expires_at TEXT NOT NULL
+);
+
+-- Nonces of single-use pre-signed URLs, kept until the URL expires
+CREATE TABLE IF NOT EXISTS presigned_nonces (
+    nonce TEXT PRIMARY KEY,
+    expires_at TEXT NOT NULL
+);
+
+-- Access points table
+CREATE TABLE IF NOT EXISTS access_points (
+    name TEXT PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    policy TEXT,
+    allowed_cidrs TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_access_points_bucket ON access_points(bucket);
+
+-- Multipart uploads in progress and their parts
+CREATE TABLE IF NOT EXISTS multipart_uploads (
+    upload_id TEXT PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    key TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    metadata TEXT,
+    storage_class TEXT DEFAULT 'STANDARD',
+    initiator_id TEXT DEFAULT 'root',
+    created_at TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS upload_parts (
+    upload_id TEXT NOT NULL,
+    part_number INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    etag TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (upload_id, part_number)
+);
+
+CREATE INDEX IF NOT EXISTS idx_multipart_listing ON multipart_uploads(bucket, key, upload_id);
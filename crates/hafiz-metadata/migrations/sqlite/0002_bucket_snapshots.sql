@@ -0,0 +1,44 @@
+-- Point-in-time bucket snapshots
+
+CREATE TABLE IF NOT EXISTS bucket_snapshots (
+    id TEXT PRIMARY KEY,
+    bucket TEXT NOT NULL,
+    owner_id TEXT NOT NULL,
+    region TEXT NOT NULL,
+    versioning TEXT DEFAULT '',
+    object_lock_enabled INTEGER DEFAULT 0,
+    description TEXT,
+    created_at TEXT NOT NULL,
+    completed_at TEXT,
+    versions INTEGER NOT NULL DEFAULT 0,
+    total_bytes INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_bucket_snapshots_bucket ON bucket_snapshots(bucket, created_at);
+
+-- Object versions and delete markers as they were when the snapshot was taken
+CREATE TABLE IF NOT EXISTS snapshot_objects (
+    snapshot_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    etag TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    metadata TEXT,
+    last_modified TEXT NOT NULL,
+    is_latest INTEGER DEFAULT 1,
+    is_delete_marker INTEGER DEFAULT 0,
+    encryption TEXT,
+    part_sizes TEXT,
+    storage_class TEXT NOT NULL DEFAULT 'STANDARD',
+    PRIMARY KEY (snapshot_id, key, version_id)
+);
+
+CREATE TABLE IF NOT EXISTS snapshot_object_tags (
+    snapshot_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    version_id TEXT NOT NULL,
+    tag_key TEXT NOT NULL,
+    tag_value TEXT NOT NULL,
+    PRIMARY KEY (snapshot_id, key, version_id, tag_key)
+);
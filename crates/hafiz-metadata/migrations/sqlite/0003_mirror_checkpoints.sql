@@ -0,0 +1,9 @@
+-- Progress of scheduled mirror jobs: objects of the bucket last modified
+-- up to mirrored_until have been copied to the job's target
+CREATE TABLE IF NOT EXISTS mirror_checkpoints (
+    job TEXT NOT NULL,
+    bucket TEXT NOT NULL,
+    mirrored_until TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (job, bucket)
+);
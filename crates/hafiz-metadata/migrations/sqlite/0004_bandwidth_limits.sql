@@ -0,0 +1,9 @@
+-- Byte rate limits of buckets and access keys; 0 means unlimited
+CREATE TABLE IF NOT EXISTS bandwidth_limits (
+    scope TEXT NOT NULL,
+    name TEXT NOT NULL,
+    ingress_bytes_per_sec INTEGER NOT NULL DEFAULT 0,
+    egress_bytes_per_sec INTEGER NOT NULL DEFAULT 0,
+    updated_at TEXT NOT NULL,
+    PRIMARY KEY (scope, name)
+);
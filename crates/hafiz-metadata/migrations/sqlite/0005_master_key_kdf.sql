@@ -0,0 +1,12 @@
+-- How the master key is derived from a passphrase; at most one row
+CREATE TABLE IF NOT EXISTS master_key_kdf (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    algorithm TEXT NOT NULL,
+    salt BLOB NOT NULL,
+    memory_kib INTEGER NOT NULL DEFAULT 0,
+    iterations INTEGER NOT NULL DEFAULT 0,
+    parallelism INTEGER NOT NULL DEFAULT 0,
+    -- Identifies the derived key, to reject a wrong passphrase
+    key_check TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
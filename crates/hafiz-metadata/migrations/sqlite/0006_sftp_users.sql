@@ -0,0 +1,20 @@
+-- Logins of the SFTP gateway, each confined to a bucket or prefix
+CREATE TABLE IF NOT EXISTS sftp_users (
+    username TEXT PRIMARY KEY,
+    access_key TEXT NOT NULL,
+    -- bucket or bucket/prefix
+    home TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_sftp_users_access_key ON sftp_users(access_key);
+
+-- Public keys SFTP logins authenticate with
+CREATE TABLE IF NOT EXISTS sftp_keys (
+    username TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    comment TEXT,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (username, fingerprint)
+);
@@ -0,0 +1,7 @@
+-- Admin API roles of users, by access key
+CREATE TABLE IF NOT EXISTS admin_roles (
+    access_key TEXT PRIMARY KEY,
+    -- viewer, bucket_operator, security_admin or super_admin
+    role TEXT NOT NULL,
+    granted_at TEXT NOT NULL
+);
@@ -0,0 +1,21 @@
+-- Tenants holding users and buckets; a bucket's owner_id is its account
+CREATE TABLE IF NOT EXISTS accounts (
+    id TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    -- active or suspended
+    status TEXT NOT NULL DEFAULT 'active',
+    -- Limits on all of the account's buckets together; 0 means unlimited
+    max_buckets INTEGER NOT NULL DEFAULT 0,
+    max_objects INTEGER NOT NULL DEFAULT 0,
+    max_bytes INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL
+);
+
+-- The default account, which existing users and buckets belong to
+INSERT OR IGNORE INTO accounts (id, name, created_at)
+VALUES ('root', 'Default', strftime('%Y-%m-%dT%H:%M:%SZ', 'now'));
+
+ALTER TABLE users ADD COLUMN account_id TEXT NOT NULL DEFAULT 'root';
+
+CREATE INDEX IF NOT EXISTS idx_users_account ON users(account_id);
+CREATE INDEX IF NOT EXISTS idx_buckets_owner ON buckets(owner_id);
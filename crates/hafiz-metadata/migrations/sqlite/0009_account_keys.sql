@@ -0,0 +1,3 @@
+-- Generation of the key each account's new SSE-S3 objects are encrypted
+-- under; rotating the key moves the account to the next
+ALTER TABLE accounts ADD COLUMN key_generation INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,6 @@
+-- Transfer acceleration status of buckets; absent if never configured
+CREATE TABLE IF NOT EXISTS bucket_accelerate (
+    bucket TEXT PRIMARY KEY,
+    status TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
@@ -0,0 +1,19 @@
+-- Progress of re-wrapping stored data encryption keys under a new master
+-- key, so that a run interrupted by a restart resumes where it stopped
+CREATE TABLE IF NOT EXISTS key_rewrap (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    from_key_check TEXT NOT NULL,
+    to_key_check TEXT NOT NULL,
+    state TEXT NOT NULL,
+    after_bucket TEXT NOT NULL DEFAULT '',
+    after_key TEXT NOT NULL DEFAULT '',
+    after_version_id TEXT NOT NULL DEFAULT '',
+    objects_scanned INTEGER NOT NULL DEFAULT 0,
+    objects_rewrapped INTEGER NOT NULL DEFAULT 0,
+    objects_current INTEGER NOT NULL DEFAULT 0,
+    objects_failed INTEGER NOT NULL DEFAULT 0,
+    started_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL,
+    finished_at TEXT,
+    error TEXT
+);
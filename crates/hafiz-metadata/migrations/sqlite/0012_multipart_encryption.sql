@@ -0,0 +1,3 @@
+-- Server-side encryption a multipart upload asked for when it was
+-- created, applied to the object once the upload completes
+ALTER TABLE multipart_uploads ADD COLUMN encryption TEXT NOT NULL DEFAULT '';
@@ -0,0 +1,122 @@
+//! In-process cache for hot metadata lookups
+//!
+//! A GET reads the bucket, the object and often the bucket policy and CORS
+//! rules. [`MetadataCache`] keeps recent answers, "not found" included, so
+//! repeated requests skip those database round trips. The store drops the
+//! affected entries whenever it writes them, and entries expire after a TTL
+//! to pick up changes made by other processes.
+//!
+//! Hits and misses are counted in `hafiz_cache_hits_total` and
+//! `hafiz_cache_misses_total`, labelled with the cache they hit.
+
+use hafiz_core::types::{AccessPoint, Bucket, ObjectInternal as Object};
+use hafiz_core::Result;
+use moka::sync::Cache;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Caches in front of [`MetadataStore`](crate::MetadataStore) lookups
+pub struct MetadataCache {
+    pub(crate) buckets: Lookup<String, Bucket>,
+    /// Latest version of each object
+    pub(crate) objects: Lookup<(String, String), Object>,
+    pub(crate) policies: Lookup<String, String>,
+    pub(crate) cors: Lookup<String, String>,
+    pub(crate) access_points: Lookup<String, AccessPoint>,
+}
+
+impl MetadataCache {
+    /// Caches of up to `max_entries` each, trusting entries for `ttl`
+    pub fn new(max_entries: u64, ttl: Duration) -> Self {
+        Self {
+            buckets: Lookup::new("bucket", max_entries, ttl),
+            objects: Lookup::new("object", max_entries, ttl),
+            policies: Lookup::new("bucket_policy", max_entries, ttl),
+            cors: Lookup::new("bucket_cors", max_entries, ttl),
+            access_points: Lookup::new("access_point", max_entries, ttl),
+        }
+    }
+
+    /// Forget everything about `bucket` itself
+    pub(crate) fn invalidate_bucket(&self, bucket: &str) {
+        self.buckets.invalidate(&bucket.to_string());
+        self.policies.invalidate(&bucket.to_string());
+        self.cors.invalidate(&bucket.to_string());
+    }
+
+    /// Forget all access points, e.g. after those of a bucket were dropped
+    pub(crate) fn invalidate_access_points(&self) {
+        self.access_points.clear();
+    }
+
+    pub(crate) fn invalidate_object(&self, bucket: &str, key: &str) {
+        self.objects.invalidate(&(bucket.to_string(), key.to_string()));
+    }
+
+    /// Forget everything, e.g. after tables were replaced wholesale
+    pub(crate) fn clear(&self) {
+        self.buckets.clear();
+        self.objects.clear();
+        self.policies.clear();
+        self.cors.clear();
+        self.access_points.clear();
+    }
+}
+
+/// One cache of lookups that may find nothing
+pub(crate) struct Lookup<K, V> {
+    name: &'static str,
+    entries: Cache<K, Option<V>>,
+    /// Bumped by every invalidation. A miss only stores what it read when
+    /// no invalidation happened meanwhile, so a read racing a write cannot
+    /// put the old value back.
+    generation: AtomicU64,
+}
+
+impl<K, V> Lookup<K, V>
+where
+    K: Hash + Eq + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    fn new(name: &'static str, max_entries: u64, ttl: Duration) -> Self {
+        Self {
+            name,
+            entries: Cache::builder()
+                .max_capacity(max_entries)
+                .time_to_live(ttl)
+                .build(),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Cached value of `key`, or the result of `load` on a miss
+    pub(crate) async fn get_or_load<F>(&self, key: K, load: F) -> Result<Option<V>>
+    where
+        F: Future<Output = Result<Option<V>>>,
+    {
+        if let Some(value) = self.entries.get(&key) {
+            metrics::counter!("hafiz_cache_hits_total", "cache" => self.name).increment(1);
+            return Ok(value);
+        }
+        metrics::counter!("hafiz_cache_misses_total", "cache" => self.name).increment(1);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let value = load.await?;
+        if self.generation.load(Ordering::Acquire) == generation {
+            self.entries.insert(key, value.clone());
+        }
+        Ok(value)
+    }
+
+    pub(crate) fn invalidate(&self, key: &K) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.entries.invalidate(key);
+    }
+
+    fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.entries.invalidate_all();
+    }
+}
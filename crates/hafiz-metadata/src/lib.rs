@@ -3,11 +3,13 @@
 //! Currently supports SQLite backend.
 //! PostgreSQL support planned for future releases.
 
+pub mod cache;
 pub mod repository;
 pub mod traits;
 
 // PostgreSQL disabled for now - needs implementation fixes
 // pub mod postgres;
 
+pub use cache::MetadataCache;
 pub use repository::MetadataStore;
 pub use traits::*;
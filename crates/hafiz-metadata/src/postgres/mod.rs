@@ -5,18 +5,23 @@ use chrono::{DateTime, Utc};
 use hafiz_core::types::{
     Bucket, Object, User, VersioningStatus, ObjectVersion, DeleteMarker,
     Tag, TagSet, LifecycleConfiguration, LifecycleRule, Credentials,
-    Owner,
+    Owner, DEFAULT_ACCOUNT,
 };
 use hafiz_core::{Error, Result};
+use sqlx::migrate::Migrator;
 use sqlx::postgres::{PgPool, PgPoolOptions};
 use std::collections::HashMap;
 use tracing::{debug, info};
 
+use crate::repository::prefix_end;
 use crate::traits::{
     MetadataRepository, MultipartUpload, MultipartUploadInfo,
     ObjectWithTags, UploadPart,
 };
 
+/// Versioned schema migrations, applied in order on connect
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/postgres");
+
 /// PostgreSQL metadata store
 pub struct PostgresStore {
     pool: PgPool,
@@ -36,174 +41,35 @@ impl PostgresStore {
         Ok(store)
     }
 
+    /// Bring the schema up to date with the embedded migrations, refusing
+    /// a database migrated by a newer release
     async fn init(&self) -> Result<()> {
-        // Users table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                access_key TEXT UNIQUE NOT NULL,
-                secret_key TEXT NOT NULL,
-                display_name TEXT,
-                email TEXT,
-                is_admin BOOLEAN DEFAULT FALSE,
-                enabled BOOLEAN DEFAULT TRUE,
-                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        // Buckets table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS buckets (
-                name TEXT PRIMARY KEY,
-                owner_id TEXT NOT NULL,
-                region TEXT NOT NULL DEFAULT 'us-east-1',
-                versioning TEXT DEFAULT '',
-                object_lock_enabled BOOLEAN DEFAULT FALSE,
-                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        // Objects table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS objects (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                version_id TEXT NOT NULL DEFAULT 'null',
-                size BIGINT NOT NULL,
-                etag TEXT NOT NULL,
-                content_type TEXT NOT NULL,
-                metadata JSONB,
-                last_modified TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-                is_latest BOOLEAN DEFAULT TRUE,
-                is_delete_marker BOOLEAN DEFAULT FALSE,
-                encryption JSONB,
-                PRIMARY KEY (bucket, key, version_id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        // Indexes
-        sqlx::query(
-            r#"CREATE INDEX IF NOT EXISTS idx_objects_bucket ON objects(bucket)"#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        sqlx::query(
-            r#"CREATE INDEX IF NOT EXISTS idx_objects_latest ON objects(bucket, key, is_latest)"#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        sqlx::query(
-            r#"CREATE INDEX IF NOT EXISTS idx_objects_prefix ON objects(bucket, key text_pattern_ops)"#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        // Object tags table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS object_tags (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                version_id TEXT NOT NULL DEFAULT 'null',
-                tag_key TEXT NOT NULL,
-                tag_value TEXT NOT NULL,
-                PRIMARY KEY (bucket, key, version_id, tag_key)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        // Bucket lifecycle table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_lifecycle (
-                bucket TEXT PRIMARY KEY,
-                configuration JSONB NOT NULL,
-                updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        // Multipart uploads table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS multipart_uploads (
-                upload_id TEXT NOT NULL,
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                content_type TEXT NOT NULL DEFAULT 'application/octet-stream',
-                metadata JSONB,
-                storage_class TEXT NOT NULL DEFAULT 'STANDARD',
-                initiator_id TEXT NOT NULL,
-                created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-                PRIMARY KEY (bucket, key, upload_id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        // Upload parts table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS upload_parts (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                upload_id TEXT NOT NULL,
-                part_number INTEGER NOT NULL,
-                size BIGINT NOT NULL,
-                etag TEXT NOT NULL,
-                last_modified TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-                PRIMARY KEY (bucket, key, upload_id, part_number)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        let newest = MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0);
+        let (migrated,): (bool,) =
+            sqlx::query_as(r#"SELECT to_regclass('_sqlx_migrations') IS NOT NULL"#)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        if migrated {
+            let (version,): (Option<i64>,) =
+                sqlx::query_as(r#"SELECT MAX(version) FROM _sqlx_migrations WHERE success"#)
+                    .fetch_one(&self.pool)
+                    .await
+                    .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            if let Some(version) = version.filter(|v| *v > newest) {
+                return Err(Error::DatabaseError(format!(
+                    "Database schema is at version {}, newer than version {} supported by this release; upgrade Hafiz before opening it",
+                    version, newest
+                )));
+            }
+        }
 
-        // Bucket tags table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_tags (
-                bucket TEXT NOT NULL,
-                tag_key TEXT NOT NULL,
-                tag_value TEXT NOT NULL,
-                PRIMARY KEY (bucket, tag_key)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        MIGRATOR
+            .run(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(format!("Schema migration failed: {}", e)))?;
 
-        info!("PostgreSQL metadata store initialized");
+        info!("PostgreSQL metadata schema at version {}", newest);
         Ok(())
     }
 }
@@ -282,6 +148,7 @@ impl MetadataRepository for PostgresStore {
                 created_at: r.6,
                 last_used: None,
                 policies: if r.4 { vec!["admin".to_string()] } else { Vec::new() },
+                account_id: DEFAULT_ACCOUNT.to_string(),
             })
             .collect())
     }
@@ -308,6 +175,7 @@ impl MetadataRepository for PostgresStore {
             created_at: r.6,
             last_used: None,
             policies: if r.4 { vec!["admin".to_string()] } else { Vec::new() },
+            account_id: DEFAULT_ACCOUNT.to_string(),
         }))
     }
 
@@ -465,6 +333,12 @@ impl MetadataRepository for PostgresStore {
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        sqlx::query(r#"DELETE FROM bucket_tags WHERE bucket = $1"#)
+            .bind(name)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
         debug!("Deleted bucket: {}", name);
         Ok(())
     }
@@ -499,11 +373,51 @@ impl MetadataRepository for PostgresStore {
         .bind(bucket)
         .fetch_all(&self.pool)
         .await
-        .unwrap_or_default();
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         Ok(rows.into_iter().collect())
     }
 
+    async fn put_bucket_tags(&self, bucket: &str, tags: &TagSet) -> Result<()> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(r#"DELETE FROM bucket_tags WHERE bucket = $1"#)
+            .bind(bucket)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        for tag in &tags.tags {
+            sqlx::query(
+                r#"INSERT INTO bucket_tags (bucket, tag_key, tag_value) VALUES ($1, $2, $3)"#,
+            )
+            .bind(bucket)
+            .bind(&tag.key)
+            .bind(&tag.value)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
+    async fn delete_bucket_tags(&self, bucket: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bucket_tags WHERE bucket = $1"#)
+            .bind(bucket)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        Ok(())
+    }
+
     // ============= Object Operations =============
 
     async fn create_object(&self, object: &Object) -> Result<()> {
@@ -616,20 +530,27 @@ impl MetadataRepository for PostgresStore {
     }
 
     async fn list_objects(&self, bucket: &str, prefix: &str, marker: &str, max_keys: i32) -> Result<Vec<Object>> {
-        let rows: Vec<(String, String, i64, String, String, Option<serde_json::Value>, DateTime<Utc>)> =
-            sqlx::query_as(
-                r#"
-                SELECT bucket, key, size, etag, content_type, metadata, last_modified
-                FROM objects
-                WHERE bucket = $1 AND key LIKE $2 AND key > $3 AND is_latest = true AND is_delete_marker = false
-                ORDER BY key
-                LIMIT $4
-                "#,
-            )
+        let end = prefix_end(prefix);
+        let sql = format!(
+            r#"
+            SELECT bucket, key, size, etag, content_type, metadata, last_modified
+            FROM objects
+            WHERE bucket = $1 AND key COLLATE "C" >= $2 AND key COLLATE "C" > $3{}
+              AND is_latest = true AND is_delete_marker = false
+            ORDER BY key COLLATE "C"
+            LIMIT $4
+            "#,
+            below(&end, 5)
+        );
+        let mut query = sqlx::query_as(&sql)
             .bind(bucket)
-            .bind(format!("{}%", prefix))
+            .bind(prefix)
             .bind(marker)
-            .bind(max_keys)
+            .bind(max_keys);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, i64, String, String, Option<serde_json::Value>, DateTime<Utc>)> = query
             .fetch_all(&self.pool)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
@@ -734,20 +655,27 @@ impl MetadataRepository for PostgresStore {
     }
 
     async fn list_object_versions(&self, bucket: &str, prefix: &str, marker: &str, max_keys: i32) -> Result<Vec<ObjectVersion>> {
-        let rows: Vec<(String, String, i64, String, DateTime<Utc>, bool)> =
-            sqlx::query_as(
-                r#"
-                SELECT key, version_id, size, etag, last_modified, is_latest
-                FROM objects
-                WHERE bucket = $1 AND key LIKE $2 AND key > $3 AND is_delete_marker = false
-                ORDER BY key, last_modified DESC
-                LIMIT $4
-                "#,
-            )
+        let end = prefix_end(prefix);
+        let sql = format!(
+            r#"
+            SELECT key, version_id, size, etag, last_modified, is_latest
+            FROM objects
+            WHERE bucket = $1 AND key COLLATE "C" >= $2 AND key COLLATE "C" > $3{}
+              AND is_delete_marker = false
+            ORDER BY key COLLATE "C", last_modified DESC
+            LIMIT $4
+            "#,
+            below(&end, 5)
+        );
+        let mut query = sqlx::query_as(&sql)
             .bind(bucket)
-            .bind(format!("{}%", prefix))
+            .bind(prefix)
             .bind(marker)
-            .bind(max_keys)
+            .bind(max_keys);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, i64, String, DateTime<Utc>, bool)> = query
             .fetch_all(&self.pool)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
@@ -794,19 +722,25 @@ impl MetadataRepository for PostgresStore {
     }
 
     async fn list_delete_markers(&self, bucket: &str, prefix: &str, max_keys: i32) -> Result<Vec<DeleteMarker>> {
-        let rows: Vec<(String, String, DateTime<Utc>)> =
-            sqlx::query_as(
-                r#"
-                SELECT key, version_id, last_modified
-                FROM objects
-                WHERE bucket = $1 AND key LIKE $2 AND is_delete_marker = true
-                ORDER BY key
-                LIMIT $3
-                "#,
-            )
+        let end = prefix_end(prefix);
+        let sql = format!(
+            r#"
+            SELECT key, version_id, last_modified
+            FROM objects
+            WHERE bucket = $1 AND key COLLATE "C" >= $2{} AND is_delete_marker = true
+            ORDER BY key COLLATE "C"
+            LIMIT $3
+            "#,
+            below(&end, 4)
+        );
+        let mut query = sqlx::query_as(&sql)
             .bind(bucket)
-            .bind(format!("{}%", prefix))
-            .bind(max_keys)
+            .bind(prefix)
+            .bind(max_keys);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, DateTime<Utc>)> = query
             .fetch_all(&self.pool)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
@@ -972,21 +906,28 @@ impl MetadataRepository for PostgresStore {
     async fn get_objects_for_lifecycle(&self, bucket: &str, prefix: Option<&str>, limit: i32) -> Result<Vec<ObjectWithTags>> {
         let prefix = prefix.unwrap_or("");
 
-        let rows: Vec<(String, String, i64, DateTime<Utc>, bool, bool)> = sqlx::query_as(
+        let end = prefix_end(prefix);
+        let sql = format!(
             r#"
             SELECT key, version_id, size, last_modified, is_latest, is_delete_marker
             FROM objects
-            WHERE bucket = $1 AND key LIKE $2 AND is_delete_marker = false
-            ORDER BY key
+            WHERE bucket = $1 AND key COLLATE "C" >= $2{} AND is_delete_marker = false
+            ORDER BY key COLLATE "C"
             LIMIT $3
             "#,
-        )
-        .bind(bucket)
-        .bind(format!("{}%", prefix))
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            below(&end, 4)
+        );
+        let mut query = sqlx::query_as(&sql)
+            .bind(bucket)
+            .bind(prefix)
+            .bind(limit);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, i64, DateTime<Utc>, bool, bool)> = query
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         let mut objects = Vec::new();
         for row in rows {
@@ -1067,20 +1008,26 @@ impl MetadataRepository for PostgresStore {
     }
 
     async fn list_multipart_uploads(&self, bucket: &str, prefix: &str, marker: &str, max_uploads: i32) -> Result<Vec<MultipartUploadInfo>> {
-        let rows: Vec<(String, String, String, String, DateTime<Utc>)> =
-            sqlx::query_as(
-                r#"
-                SELECT upload_id, key, initiator_id, storage_class, created_at
-                FROM multipart_uploads
-                WHERE bucket = $1 AND key LIKE $2 AND key > $3
-                ORDER BY key
-                LIMIT $4
-                "#,
-            )
+        let end = prefix_end(prefix);
+        let sql = format!(
+            r#"
+            SELECT upload_id, key, initiator_id, storage_class, created_at
+            FROM multipart_uploads
+            WHERE bucket = $1 AND key COLLATE "C" >= $2 AND key COLLATE "C" > $3{}
+            ORDER BY key COLLATE "C"
+            LIMIT $4
+            "#,
+            below(&end, 5)
+        );
+        let mut query = sqlx::query_as(&sql)
             .bind(bucket)
-            .bind(format!("{}%", prefix))
+            .bind(prefix)
             .bind(marker)
-            .bind(max_uploads)
+            .bind(max_uploads);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, String, String, DateTime<Utc>)> = query
             .fetch_all(&self.pool)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
@@ -1174,3 +1121,14 @@ impl MetadataRepository for PostgresStore {
             .collect())
     }
 }
+
+/// `key < $param` condition for a [`prefix_end`] bound
+///
+/// Keys compare with the "C" collation so the range matches byte order,
+/// which is also the order S3 lists keys in.
+fn below(end: &Option<String>, param: usize) -> String {
+    match end {
+        Some(_) => format!(r#" AND key COLLATE "C" < ${}"#, param),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,401 @@
+//! Bucket snapshots
+//!
+//! A snapshot records the rows of every object version, delete marker and
+//! object tag set of a bucket as they were at one instant, captured in a
+//! single transaction. The object data is not kept here: the caller copies
+//! it aside, fixes up the rows of versions that changed meanwhile, and then
+//! marks the snapshot complete. Restoring adds the recorded rows to a new
+//! bucket, again in one transaction.
+
+use chrono::{DateTime, Utc};
+use hafiz_core::types::{Bucket, BucketSnapshot, ObjectInternal as Object, VersioningStatus};
+use hafiz_core::{Error, Result};
+use sqlx::SqliteConnection;
+use tracing::{debug, info};
+
+use super::{object_from_row, parse_optional_timestamp, MetadataStore, ObjectRow};
+
+/// id, bucket, owner_id, region, versioning, object_lock_enabled,
+/// description, created_at, completed_at, versions, total_bytes
+type SnapshotRow = (
+    String,
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<i32>,
+    Option<String>,
+    String,
+    Option<String>,
+    i64,
+    i64,
+);
+
+const SNAPSHOT_COLUMNS: &str = "id, bucket, owner_id, region, versioning, object_lock_enabled, \
+     description, created_at, completed_at, versions, total_bytes";
+
+fn snapshot_from_row(r: SnapshotRow) -> BucketSnapshot {
+    BucketSnapshot {
+        id: r.0,
+        bucket: r.1,
+        owner_id: r.2,
+        region: r.3,
+        versioning: VersioningStatus::from_str(r.4.as_deref().unwrap_or("")),
+        object_lock_enabled: r.5.unwrap_or(0) != 0,
+        description: r.6,
+        created_at: DateTime::parse_from_rfc3339(&r.7)
+            .unwrap()
+            .with_timezone(&Utc),
+        completed_at: parse_optional_timestamp(r.8.as_deref()),
+        versions: r.9,
+        total_bytes: r.10,
+    }
+}
+
+fn db_error(e: sqlx::Error) -> Error {
+    Error::DatabaseError(e.to_string())
+}
+
+/// Copy the rows of one `(key, version_id)`, or of every version when
+/// `version` is `None`, from the live tables into a snapshot
+async fn capture_rows(
+    conn: &mut SqliteConnection,
+    id: &str,
+    bucket: &str,
+    version: Option<(&str, &str)>,
+) -> Result<()> {
+    let filter = if version.is_some() { "AND key = ? AND version_id = ?" } else { "" };
+
+    let objects = format!(
+        r#"
+        INSERT INTO snapshot_objects
+        (snapshot_id, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class)
+        SELECT ?, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class
+        FROM objects WHERE bucket = ? {}
+        "#,
+        filter
+    );
+    let tags = format!(
+        r#"
+        INSERT INTO snapshot_object_tags (snapshot_id, key, version_id, tag_key, tag_value)
+        SELECT ?, key, version_id, tag_key, tag_value
+        FROM object_tags WHERE bucket = ? {}
+        "#,
+        filter
+    );
+
+    for sql in [objects, tags] {
+        let mut query = sqlx::query(&sql).bind(id).bind(bucket);
+        if let Some((key, version_id)) = version {
+            query = query.bind(key).bind(version_id);
+        }
+        query.execute(&mut *conn).await.map_err(db_error)?;
+    }
+    Ok(())
+}
+
+/// Drop the rows of one version from a snapshot
+async fn forget_rows(conn: &mut SqliteConnection, id: &str, key: &str, version_id: &str) -> Result<()> {
+    for table in ["snapshot_objects", "snapshot_object_tags"] {
+        sqlx::query(&format!(
+            "DELETE FROM {} WHERE snapshot_id = ? AND key = ? AND version_id = ?",
+            table
+        ))
+        .bind(id)
+        .bind(key)
+        .bind(version_id)
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+    }
+    Ok(())
+}
+
+impl MetadataStore {
+    /// Record the current versions, delete markers and object tags of
+    /// `bucket` as a new, incomplete snapshot
+    pub async fn create_bucket_snapshot(
+        &self,
+        bucket: &str,
+        description: Option<&str>,
+    ) -> Result<BucketSnapshot> {
+        let id = uuid::Uuid::new_v4().to_string();
+
+        let mut tx = self.writer.begin().await.map_err(db_error)?;
+        let result = sqlx::query(
+            r#"
+            INSERT INTO bucket_snapshots
+            (id, bucket, owner_id, region, versioning, object_lock_enabled, description, created_at)
+            SELECT ?, name, owner_id, region, versioning, object_lock_enabled, ?, ?
+            FROM buckets WHERE name = ?
+            "#,
+        )
+        .bind(&id)
+        .bind(description)
+        .bind(Utc::now().to_rfc3339())
+        .bind(bucket)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        if result.rows_affected() == 0 {
+            return Err(Error::NoSuchBucket);
+        }
+
+        capture_rows(&mut tx, &id, bucket, None).await?;
+        Self::count_snapshot(&mut tx, &id).await?;
+        tx.commit().await.map_err(db_error)?;
+
+        let snapshot = self
+            .get_bucket_snapshot(&id)
+            .await?
+            .ok_or_else(|| Error::InternalError(format!("Snapshot {} vanished", id)))?;
+        debug!(
+            "Captured {} versions of bucket {} in snapshot {}",
+            snapshot.versions, bucket, id
+        );
+        Ok(snapshot)
+    }
+
+    /// Recount the versions and bytes held by a snapshot
+    async fn count_snapshot(conn: &mut SqliteConnection, id: &str) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE bucket_snapshots SET
+                versions = (SELECT COUNT(*) FROM snapshot_objects WHERE snapshot_id = ?1),
+                total_bytes = (SELECT COALESCE(SUM(size), 0) FROM snapshot_objects
+                               WHERE snapshot_id = ?1 AND is_delete_marker = 0)
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .execute(&mut *conn)
+        .await
+        .map_err(db_error)?;
+        Ok(())
+    }
+
+    /// Page through the versions recorded in a snapshot in key order,
+    /// starting after the `(key, version_id)` pair `after`
+    pub async fn list_snapshot_objects(
+        &self,
+        id: &str,
+        after: Option<(&str, &str)>,
+        limit: i64,
+    ) -> Result<Vec<Object>> {
+        let (after_key, after_version) = after.unwrap_or(("", ""));
+        let rows: Vec<ObjectRow> = sqlx::query_as(
+            r#"
+            SELECT b.bucket, o.key, o.version_id, o.size, o.etag, o.content_type, o.metadata, o.last_modified,
+                   o.is_latest, o.is_delete_marker, o.encryption, o.part_sizes, o.storage_class
+            FROM snapshot_objects o JOIN bucket_snapshots b ON b.id = o.snapshot_id
+            WHERE o.snapshot_id = ? AND (o.key, o.version_id) > (?, ?)
+            ORDER BY o.key, o.version_id
+            LIMIT ?
+            "#,
+        )
+        .bind(id)
+        .bind(after_key)
+        .bind(after_version)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(rows.into_iter().map(object_from_row).collect())
+    }
+
+    /// Record a version of a snapshot again from the live tables, for one
+    /// that changed before its data was copied. Returns the new record, or
+    /// `None` if the version no longer exists and was dropped.
+    pub async fn recapture_snapshot_object(
+        &self,
+        id: &str,
+        key: &str,
+        version_id: &str,
+    ) -> Result<Option<Object>> {
+        let snapshot = self
+            .get_bucket_snapshot(id)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("No such snapshot: {}", id)))?;
+
+        let mut tx = self.writer.begin().await.map_err(db_error)?;
+        forget_rows(&mut tx, id, key, version_id).await?;
+        capture_rows(&mut tx, id, &snapshot.bucket, Some((key, version_id))).await?;
+        tx.commit().await.map_err(db_error)?;
+
+        self.fetch_snapshot_object(id, key, version_id).await
+    }
+
+    /// Drop a version from a snapshot, e.g. one deleted before its data was
+    /// copied
+    pub async fn remove_snapshot_object(&self, id: &str, key: &str, version_id: &str) -> Result<()> {
+        let mut tx = self.writer.begin().await.map_err(db_error)?;
+        forget_rows(&mut tx, id, key, version_id).await?;
+        tx.commit().await.map_err(db_error)?;
+        Ok(())
+    }
+
+    async fn fetch_snapshot_object(&self, id: &str, key: &str, version_id: &str) -> Result<Option<Object>> {
+        let row: Option<ObjectRow> = sqlx::query_as(
+            r#"
+            SELECT b.bucket, o.key, o.version_id, o.size, o.etag, o.content_type, o.metadata, o.last_modified,
+                   o.is_latest, o.is_delete_marker, o.encryption, o.part_sizes, o.storage_class
+            FROM snapshot_objects o JOIN bucket_snapshots b ON b.id = o.snapshot_id
+            WHERE o.snapshot_id = ? AND o.key = ? AND o.version_id = ?
+            "#,
+        )
+        .bind(id)
+        .bind(key)
+        .bind(version_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(row.map(object_from_row))
+    }
+
+    /// Mark a snapshot complete once all of its data is captured
+    pub async fn complete_bucket_snapshot(&self, id: &str) -> Result<BucketSnapshot> {
+        let mut tx = self.writer.begin().await.map_err(db_error)?;
+        Self::count_snapshot(&mut tx, id).await?;
+        sqlx::query(r#"UPDATE bucket_snapshots SET completed_at = ? WHERE id = ?"#)
+            .bind(Utc::now().to_rfc3339())
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        let snapshot = self
+            .get_bucket_snapshot(id)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("No such snapshot: {}", id)))?;
+        info!(
+            "Snapshot {} of bucket {} complete: {} versions, {} bytes",
+            id, snapshot.bucket, snapshot.versions, snapshot.total_bytes
+        );
+        Ok(snapshot)
+    }
+
+    pub async fn get_bucket_snapshot(&self, id: &str) -> Result<Option<BucketSnapshot>> {
+        let row: Option<SnapshotRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM bucket_snapshots WHERE id = ?",
+            SNAPSHOT_COLUMNS
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(row.map(snapshot_from_row))
+    }
+
+    /// Snapshots of `bucket`, or of all buckets, oldest first
+    pub async fn list_bucket_snapshots(&self, bucket: Option<&str>) -> Result<Vec<BucketSnapshot>> {
+        let rows: Vec<SnapshotRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM bucket_snapshots WHERE ?1 IS NULL OR bucket = ?1 ORDER BY created_at, id",
+            SNAPSHOT_COLUMNS
+        ))
+        .bind(bucket)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(db_error)?;
+
+        Ok(rows.into_iter().map(snapshot_from_row).collect())
+    }
+
+    /// Forget a snapshot; its data must be deleted by the caller
+    pub async fn delete_bucket_snapshot(&self, id: &str) -> Result<()> {
+        let mut tx = self.writer.begin().await.map_err(db_error)?;
+        for table in ["snapshot_object_tags", "snapshot_objects"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE snapshot_id = ?", table))
+                .bind(id)
+                .execute(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        }
+        sqlx::query(r#"DELETE FROM bucket_snapshots WHERE id = ?"#)
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        debug!("Deleted snapshot {}", id);
+        Ok(())
+    }
+
+    /// Create `target` with the settings, versions and object tags recorded
+    /// in a snapshot; the data must already be in place
+    pub async fn restore_bucket_snapshot(&self, id: &str, target: &str) -> Result<Bucket> {
+        let snapshot = self
+            .get_bucket_snapshot(id)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("No such snapshot: {}", id)))?;
+        let bucket = Bucket {
+            name: target.to_string(),
+            owner_id: snapshot.owner_id,
+            region: snapshot.region,
+            versioning: snapshot.versioning,
+            object_lock_enabled: snapshot.object_lock_enabled,
+            created_at: Utc::now(),
+        };
+
+        let mut tx = self.writer.begin().await.map_err(db_error)?;
+        sqlx::query(
+            r#"
+            INSERT INTO buckets (name, owner_id, region, versioning, object_lock_enabled, created_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&bucket.name)
+        .bind(&bucket.owner_id)
+        .bind(&bucket.region)
+        .bind(bucket.versioning.as_str())
+        .bind(bucket.object_lock_enabled as i32)
+        .bind(bucket.created_at.to_rfc3339())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("UNIQUE constraint") {
+                Error::BucketAlreadyExists
+            } else {
+                db_error(e)
+            }
+        })?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO objects
+            (bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class)
+            SELECT ?, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class
+            FROM snapshot_objects WHERE snapshot_id = ?
+            "#,
+        )
+        .bind(target)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO object_tags (bucket, key, version_id, tag_key, tag_value)
+            SELECT ?, key, version_id, tag_key, tag_value
+            FROM snapshot_object_tags WHERE snapshot_id = ?
+            "#,
+        )
+        .bind(target)
+        .bind(id)
+        .execute(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        tx.commit().await.map_err(db_error)?;
+
+        if let Some(cache) = &self.cache {
+            cache.invalidate_bucket(target);
+        }
+        info!("Restored snapshot {} of bucket {} into {}", id, snapshot.bucket, target);
+        Ok(bucket)
+    }
+}
@@ -0,0 +1,641 @@
+//! Portable metadata dumps
+//!
+//! A dump is JSON lines: a [`DumpRecord::Header`], one record per user,
+//! bucket, bucket policy, bucket tag set, object version and object tag
+//! set, and a [`DumpRecord::End`] with the record counts, so a truncated
+//! dump is refused rather than half-imported. Records carry values rather
+//! than rows, so a dump taken from one backend can be loaded into another,
+//! and into later schema versions.
+//!
+//! Secret keys are written in plaintext, whatever the source store's
+//! master key, and sealed again under the master key of the store that
+//! imports them. Treat dumps like the credentials they contain.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use hafiz_core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::SqliteConnection;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::info;
+
+use super::{parse_optional_timestamp, MetadataStore};
+
+/// Identifies a metadata dump in its header
+pub const DUMP_FORMAT: &str = "hafiz-metadata-dump";
+
+/// Newest dump version this release writes and reads
+pub const DUMP_VERSION: u32 = 1;
+
+/// Object versions read per query while exporting
+const EXPORT_PAGE_SIZE: i64 = 1000;
+
+/// One line of a metadata dump
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DumpRecord {
+    Header {
+        format: String,
+        version: u32,
+        created_at: DateTime<Utc>,
+        /// Schema version of the store the dump was taken from
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        schema_version: Option<i64>,
+    },
+    User {
+        id: String,
+        access_key: String,
+        secret_key: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        display_name: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        email: Option<String>,
+        is_admin: bool,
+        enabled: bool,
+        created_at: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key_created_at: Option<DateTime<Utc>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_used: Option<DateTime<Utc>>,
+    },
+    Bucket {
+        name: String,
+        owner_id: String,
+        region: String,
+        /// "", "Enabled" or "Suspended"
+        versioning: String,
+        object_lock_enabled: bool,
+        created_at: DateTime<Utc>,
+    },
+    BucketPolicy {
+        bucket: String,
+        policy: String,
+    },
+    BucketTags {
+        bucket: String,
+        tags: BTreeMap<String, String>,
+    },
+    /// A version of an object, or a delete marker
+    Object {
+        bucket: String,
+        key: String,
+        version_id: String,
+        size: i64,
+        etag: String,
+        content_type: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<Value>,
+        last_modified: DateTime<Utc>,
+        is_latest: bool,
+        is_delete_marker: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        encryption: Option<Value>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        part_sizes: Option<Value>,
+        storage_class: String,
+    },
+    ObjectTags {
+        bucket: String,
+        key: String,
+        version_id: String,
+        tags: BTreeMap<String, String>,
+    },
+    /// Last line, counting the records before it
+    End { records: DumpSummary },
+}
+
+/// Records written or read, by kind
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DumpSummary {
+    pub users: u64,
+    pub buckets: u64,
+    pub bucket_policies: u64,
+    pub bucket_tags: u64,
+    pub objects: u64,
+    pub object_tags: u64,
+}
+
+impl DumpSummary {
+    fn count(&mut self, record: &DumpRecord) {
+        match record {
+            DumpRecord::Header { .. } | DumpRecord::End { .. } => {}
+            DumpRecord::User { .. } => self.users += 1,
+            DumpRecord::Bucket { .. } => self.buckets += 1,
+            DumpRecord::BucketPolicy { .. } => self.bucket_policies += 1,
+            DumpRecord::BucketTags { .. } => self.bucket_tags += 1,
+            DumpRecord::Object { .. } => self.objects += 1,
+            DumpRecord::ObjectTags { .. } => self.object_tags += 1,
+        }
+    }
+}
+
+/// id, access_key, secret_key, display_name, email, is_admin, enabled,
+/// created_at, key_created_at, last_used
+type UserRow = (
+    String,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    bool,
+    bool,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+/// name, owner_id, region, versioning, object_lock_enabled, created_at
+type BucketRow = (String, String, String, Option<String>, Option<bool>, String);
+
+fn parse_timestamp(value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|d| d.with_timezone(&Utc))
+        .map_err(|e| Error::InternalError(format!("Invalid timestamp {:?} in metadata: {}", value, e)))
+}
+
+fn parse_json(value: Option<String>) -> Option<Value> {
+    value
+        .and_then(|v| serde_json::from_str(&v).ok())
+        .filter(|v: &Value| !v.is_null())
+}
+
+fn db_error(e: sqlx::Error) -> Error {
+    Error::DatabaseError(e.to_string())
+}
+
+async fn write_record<W: AsyncWrite + Unpin>(
+    out: &mut W,
+    summary: &mut DumpSummary,
+    record: &DumpRecord,
+) -> Result<()> {
+    let mut line = serde_json::to_vec(record).map_err(|e| Error::InternalError(e.to_string()))?;
+    line.push(b'\n');
+    out.write_all(&line)
+        .await
+        .map_err(|e| Error::InternalError(format!("Failed to write metadata dump: {}", e)))?;
+    summary.count(record);
+    Ok(())
+}
+
+/// Group `(owner..., tag_key, tag_value)` rows into tag sets per owner
+fn group_tags<K: PartialEq>(rows: Vec<(K, String, String)>) -> Vec<(K, BTreeMap<String, String>)> {
+    let mut grouped: Vec<(K, BTreeMap<String, String>)> = Vec::new();
+    for (owner, tag_key, tag_value) in rows {
+        match grouped.last_mut() {
+            Some((last, tags)) if *last == owner => {
+                tags.insert(tag_key, tag_value);
+            }
+            _ => grouped.push((owner, BTreeMap::from([(tag_key, tag_value)]))),
+        }
+    }
+    grouped
+}
+
+impl MetadataStore {
+    /// Write a dump of the store to `out`
+    ///
+    /// Everything is read in one transaction, so the dump is consistent
+    /// even while requests keep writing.
+    pub async fn export_dump<W: AsyncWrite + Unpin>(&self, out: &mut W) -> Result<DumpSummary> {
+        let schema_version = self.schema_version().await?;
+        let mut tx = self.pool.begin().await.map_err(db_error)?;
+        let mut summary = DumpSummary::default();
+
+        write_record(
+            out,
+            &mut summary,
+            &DumpRecord::Header {
+                format: DUMP_FORMAT.to_string(),
+                version: DUMP_VERSION,
+                created_at: Utc::now(),
+                schema_version,
+            },
+        )
+        .await?;
+
+        let users: Vec<UserRow> = sqlx::query_as(
+            r#"
+            SELECT id, access_key, secret_key, display_name, email, is_admin, COALESCE(enabled, 1),
+                   created_at, key_created_at, last_used
+            FROM users ORDER BY access_key
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        for r in users {
+            let record = DumpRecord::User {
+                secret_key: self.open_secret(&r.1, r.2)?,
+                id: r.0,
+                access_key: r.1,
+                display_name: r.3,
+                email: r.4,
+                is_admin: r.5,
+                enabled: r.6,
+                created_at: parse_timestamp(&r.7)?,
+                key_created_at: parse_optional_timestamp(r.8.as_deref()),
+                last_used: parse_optional_timestamp(r.9.as_deref()),
+            };
+            write_record(out, &mut summary, &record).await?;
+        }
+
+        let buckets: Vec<BucketRow> = sqlx::query_as(
+            r#"
+            SELECT name, owner_id, region, versioning, object_lock_enabled, created_at
+            FROM buckets ORDER BY name
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        for r in buckets {
+            let record = DumpRecord::Bucket {
+                name: r.0,
+                owner_id: r.1,
+                region: r.2,
+                versioning: r.3.unwrap_or_default(),
+                object_lock_enabled: r.4.unwrap_or(false),
+                created_at: parse_timestamp(&r.5)?,
+            };
+            write_record(out, &mut summary, &record).await?;
+        }
+
+        let policies: Vec<(String, String)> =
+            sqlx::query_as(r#"SELECT bucket, policy_json FROM bucket_policies ORDER BY bucket"#)
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(db_error)?;
+        for (bucket, policy) in policies {
+            write_record(out, &mut summary, &DumpRecord::BucketPolicy { bucket, policy }).await?;
+        }
+
+        let bucket_tags: Vec<(String, String, String)> = sqlx::query_as(
+            r#"SELECT bucket, tag_key, tag_value FROM bucket_tags ORDER BY bucket, tag_key"#,
+        )
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(db_error)?;
+        for (bucket, tags) in group_tags(bucket_tags) {
+            write_record(out, &mut summary, &DumpRecord::BucketTags { bucket, tags }).await?;
+        }
+
+        // Object versions and their tags, a page at a time
+        let mut after = (String::new(), String::new(), String::new());
+        loop {
+            let rows: Vec<super::ObjectRow> = sqlx::query_as(
+                r#"
+                SELECT bucket, key, version_id, size, etag, content_type, metadata, last_modified,
+                       is_latest, is_delete_marker, encryption, part_sizes, storage_class
+                FROM objects
+                WHERE (bucket, key, version_id) > (?, ?, ?)
+                ORDER BY bucket, key, version_id
+                LIMIT ?
+                "#,
+            )
+            .bind(&after.0)
+            .bind(&after.1)
+            .bind(&after.2)
+            .bind(EXPORT_PAGE_SIZE)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            let Some(last) = rows.last() else {
+                break;
+            };
+            let first = (rows[0].0.clone(), rows[0].1.clone(), rows[0].2.clone());
+            let next = (last.0.clone(), last.1.clone(), last.2.clone());
+            let full_page = rows.len() as i64 == EXPORT_PAGE_SIZE;
+
+            for r in rows {
+                let record = DumpRecord::Object {
+                    bucket: r.0,
+                    key: r.1,
+                    version_id: r.2,
+                    size: r.3,
+                    etag: r.4,
+                    content_type: r.5,
+                    metadata: parse_json(r.6),
+                    last_modified: parse_timestamp(&r.7)?,
+                    is_latest: r.8 != 0,
+                    is_delete_marker: r.9 != 0,
+                    encryption: parse_json(r.10),
+                    part_sizes: parse_json(r.11),
+                    storage_class: r.12,
+                };
+                write_record(out, &mut summary, &record).await?;
+            }
+
+            // Tags of the versions on this page
+            let tags: Vec<(String, String, String, String, String)> = sqlx::query_as(
+                r#"
+                SELECT bucket, key, version_id, tag_key, tag_value FROM object_tags
+                WHERE (bucket, key, version_id) >= (?, ?, ?) AND (bucket, key, version_id) <= (?, ?, ?)
+                ORDER BY bucket, key, version_id, tag_key
+                "#,
+            )
+            .bind(&first.0)
+            .bind(&first.1)
+            .bind(&first.2)
+            .bind(&next.0)
+            .bind(&next.1)
+            .bind(&next.2)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(db_error)?;
+            let tags = tags.into_iter().map(|r| ((r.0, r.1, r.2), r.3, r.4)).collect();
+            for ((bucket, key, version_id), tags) in group_tags(tags) {
+                let record = DumpRecord::ObjectTags { bucket, key, version_id, tags };
+                write_record(out, &mut summary, &record).await?;
+            }
+
+            if !full_page {
+                break;
+            }
+            after = next;
+        }
+
+        tx.rollback().await.map_err(db_error)?;
+        let records = summary.clone();
+        write_record(out, &mut summary, &DumpRecord::End { records }).await?;
+        out.flush()
+            .await
+            .map_err(|e| Error::InternalError(format!("Failed to write metadata dump: {}", e)))?;
+
+        info!(
+            "Exported metadata: {} users, {} buckets, {} object versions",
+            summary.users, summary.buckets, summary.objects
+        );
+        Ok(summary)
+    }
+
+    /// Load a dump written by [`export_dump`](Self::export_dump)
+    ///
+    /// Records replace what the store holds under the same names and
+    /// leave everything else alone, so importing into an empty store
+    /// restores the dump exactly. The import is all or nothing.
+    pub async fn import_dump<R: AsyncBufRead + Unpin>(&self, input: R) -> Result<DumpSummary> {
+        let mut lines = input.lines();
+        let mut tx = self.writer.begin().await.map_err(db_error)?;
+        let mut summary = DumpSummary::default();
+        let mut line_number = 0;
+        let mut header_seen = false;
+        let mut complete = false;
+
+        while let Some(line) = lines
+            .next_line()
+            .await
+            .map_err(|e| Error::InvalidRequest(format!("Failed to read metadata dump: {}", e)))?
+        {
+            line_number += 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if complete {
+                return Err(Error::InvalidRequest(format!(
+                    "Metadata dump continues after its end on line {}",
+                    line_number
+                )));
+            }
+            let record: DumpRecord = serde_json::from_str(&line).map_err(|e| {
+                Error::InvalidRequest(format!("Invalid metadata dump record on line {}: {}", line_number, e))
+            })?;
+
+            match &record {
+                DumpRecord::Header { format, version, .. } if !header_seen => {
+                    if format != DUMP_FORMAT {
+                        return Err(Error::InvalidRequest(format!("Not a metadata dump: {}", format)));
+                    }
+                    if *version > DUMP_VERSION {
+                        return Err(Error::InvalidRequest(format!(
+                            "Metadata dump version {} is newer than version {} supported by this release",
+                            version, DUMP_VERSION
+                        )));
+                    }
+                    header_seen = true;
+                }
+                _ if !header_seen => {
+                    return Err(Error::InvalidRequest(
+                        "Metadata dump does not start with a header".to_string(),
+                    ));
+                }
+                DumpRecord::Header { .. } => {
+                    return Err(Error::InvalidRequest(format!(
+                        "Unexpected header on line {} of metadata dump",
+                        line_number
+                    )));
+                }
+                DumpRecord::End { records } => {
+                    if *records != summary {
+                        return Err(Error::InvalidRequest(
+                            "Metadata dump record counts do not match its end".to_string(),
+                        ));
+                    }
+                    complete = true;
+                }
+                _ => self.import_record(&mut tx, &record).await?,
+            }
+            summary.count(&record);
+        }
+        if !complete {
+            return Err(Error::InvalidRequest(
+                "Metadata dump is truncated: it has no end record".to_string(),
+            ));
+        }
+
+        tx.commit().await.map_err(db_error)?;
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+
+        info!(
+            "Imported metadata: {} users, {} buckets, {} object versions",
+            summary.users, summary.buckets, summary.objects
+        );
+        Ok(summary)
+    }
+
+    async fn import_record(&self, conn: &mut SqliteConnection, record: &DumpRecord) -> Result<()> {
+        match record {
+            DumpRecord::Header { .. } | DumpRecord::End { .. } => {}
+            DumpRecord::User {
+                id,
+                access_key,
+                secret_key,
+                display_name,
+                email,
+                is_admin,
+                enabled,
+                created_at,
+                key_created_at,
+                last_used,
+            } => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO users (id, access_key, secret_key, display_name, email, is_admin, enabled, created_at, key_created_at, last_used)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(access_key) DO UPDATE SET
+                        secret_key = excluded.secret_key,
+                        display_name = excluded.display_name,
+                        email = excluded.email,
+                        is_admin = excluded.is_admin,
+                        enabled = excluded.enabled,
+                        created_at = excluded.created_at,
+                        key_created_at = excluded.key_created_at,
+                        last_used = excluded.last_used
+                    "#,
+                )
+                .bind(id)
+                .bind(access_key)
+                .bind(self.seal_secret(access_key, secret_key)?)
+                .bind(display_name)
+                .bind(email)
+                .bind(is_admin)
+                .bind(enabled)
+                .bind(created_at.to_rfc3339())
+                .bind(key_created_at.map(|t| t.to_rfc3339()))
+                .bind(last_used.map(|t| t.to_rfc3339()))
+                .execute(&mut *conn)
+                .await
+                .map_err(db_error)?;
+            }
+            DumpRecord::Bucket {
+                name,
+                owner_id,
+                region,
+                versioning,
+                object_lock_enabled,
+                created_at,
+            } => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO buckets (name, owner_id, region, versioning, object_lock_enabled, created_at)
+                    VALUES (?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(name) DO UPDATE SET
+                        owner_id = excluded.owner_id,
+                        region = excluded.region,
+                        versioning = excluded.versioning,
+                        object_lock_enabled = excluded.object_lock_enabled,
+                        created_at = excluded.created_at
+                    "#,
+                )
+                .bind(name)
+                .bind(owner_id)
+                .bind(region)
+                .bind(versioning)
+                .bind(*object_lock_enabled as i32)
+                .bind(created_at.to_rfc3339())
+                .execute(&mut *conn)
+                .await
+                .map_err(db_error)?;
+            }
+            DumpRecord::BucketPolicy { bucket, policy } => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO bucket_policies (bucket, policy_json, updated_at)
+                    VALUES (?, ?, ?)
+                    ON CONFLICT(bucket) DO UPDATE SET policy_json = excluded.policy_json, updated_at = excluded.updated_at
+                    "#,
+                )
+                .bind(bucket)
+                .bind(policy)
+                .bind(Utc::now().to_rfc3339())
+                .execute(&mut *conn)
+                .await
+                .map_err(db_error)?;
+            }
+            DumpRecord::BucketTags { bucket, tags } => {
+                sqlx::query(r#"DELETE FROM bucket_tags WHERE bucket = ?"#)
+                    .bind(bucket)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(db_error)?;
+                for (tag_key, tag_value) in tags {
+                    sqlx::query(r#"INSERT INTO bucket_tags (bucket, tag_key, tag_value) VALUES (?, ?, ?)"#)
+                        .bind(bucket)
+                        .bind(tag_key)
+                        .bind(tag_value)
+                        .execute(&mut *conn)
+                        .await
+                        .map_err(db_error)?;
+                }
+            }
+            DumpRecord::Object {
+                bucket,
+                key,
+                version_id,
+                size,
+                etag,
+                content_type,
+                metadata,
+                last_modified,
+                is_latest,
+                is_delete_marker,
+                encryption,
+                part_sizes,
+                storage_class,
+            } => {
+                // An upsert rather than OR REPLACE, so the usage triggers see
+                // the change
+                sqlx::query(
+                    r#"
+                    INSERT INTO objects
+                    (bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(bucket, key, version_id) DO UPDATE SET
+                        size = excluded.size,
+                        etag = excluded.etag,
+                        content_type = excluded.content_type,
+                        metadata = excluded.metadata,
+                        last_modified = excluded.last_modified,
+                        is_latest = excluded.is_latest,
+                        is_delete_marker = excluded.is_delete_marker,
+                        encryption = excluded.encryption,
+                        part_sizes = excluded.part_sizes,
+                        storage_class = excluded.storage_class
+                    "#,
+                )
+                .bind(bucket)
+                .bind(key)
+                .bind(version_id)
+                .bind(size)
+                .bind(etag)
+                .bind(content_type)
+                .bind(metadata.as_ref().map(Value::to_string))
+                .bind(last_modified.to_rfc3339())
+                .bind(*is_latest as i32)
+                .bind(*is_delete_marker as i32)
+                .bind(encryption.as_ref().map(Value::to_string))
+                .bind(part_sizes.as_ref().map(Value::to_string))
+                .bind(storage_class)
+                .execute(&mut *conn)
+                .await
+                .map_err(db_error)?;
+            }
+            DumpRecord::ObjectTags { bucket, key, version_id, tags } => {
+                sqlx::query(r#"DELETE FROM object_tags WHERE bucket = ? AND key = ? AND version_id = ?"#)
+                    .bind(bucket)
+                    .bind(key)
+                    .bind(version_id)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(db_error)?;
+                for (tag_key, tag_value) in tags {
+                    sqlx::query(
+                        r#"INSERT INTO object_tags (bucket, key, version_id, tag_key, tag_value) VALUES (?, ?, ?, ?, ?)"#,
+                    )
+                    .bind(bucket)
+                    .bind(key)
+                    .bind(version_id)
+                    .bind(tag_key)
+                    .bind(tag_value)
+                    .execute(&mut *conn)
+                    .await
+                    .map_err(db_error)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
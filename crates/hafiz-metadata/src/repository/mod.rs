@@ -4,266 +4,319 @@ use chrono::{DateTime, Utc};
 use hafiz_core::types::{
     Bucket, BucketInfo, ObjectInternal as Object, ObjectInfo, User, VersioningStatus,
     ObjectVersion, DeleteMarker, Tag, TagSet, LifecycleConfiguration, LifecycleRule,
-    EncryptionInfo, EncryptionType, Owner,
+    EncryptionInfo, EncryptionType, Owner, ConsistencyLevel, BucketCompression, AccessPoint,
+    BucketTiering, NamespaceMode, StorageClass, BandwidthLimit, BandwidthScope, AccelerateStatus,
 };
+use hafiz_core::config::DatabaseConfig;
 use hafiz_core::{Error, Result};
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use hafiz_crypto::{is_sealed, EncryptionError, KdfParams, SecretCipher};
+use crate::cache::MetadataCache;
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteConnection, SqliteJournalMode, SqlitePool, SqlitePoolOptions,
+};
 use std::collections::HashMap;
-use tracing::{debug, info};
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{debug, info, instrument};
+
+mod bucket_snapshots;
+mod dump;
+
+pub use dump::{DumpRecord, DumpSummary, DUMP_FORMAT, DUMP_VERSION};
+
+/// Versioned schema migrations, applied in order on open
+static MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
 
 pub struct MetadataStore {
+    /// Connections for reads
     pool: SqlitePool,
+    /// The single connection every write goes through. SQLite allows one
+    /// writer at a time; queueing writers here instead of letting them race
+    /// for the database lock keeps concurrent requests from failing with
+    /// "database is locked".
+    writer: SqlitePool,
+    /// Encrypts secret keys at rest when a master key is configured
+    secrets: Option<SecretCipher>,
+    /// Hot bucket, object, policy and CORS lookups
+    cache: Option<MetadataCache>,
 }
 
 impl MetadataStore {
     pub async fn new(database_url: &str) -> Result<Self> {
-        let pool = SqlitePoolOptions::new()
-            .max_connections(100)
-            .connect(database_url)
+        Self::connect(&DatabaseConfig {
+            url: database_url.to_string(),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Open the database with the pool size, journal mode and busy timeout
+    /// of `config`
+    pub async fn connect(config: &DatabaseConfig) -> Result<Self> {
+        let in_memory = config.url.contains(":memory:") || config.url.contains("mode=memory");
+        let mut options = SqliteConnectOptions::from_str(&config.url)
+            .map_err(|e| Error::DatabaseError(e.to_string()))?
+            .busy_timeout(Duration::from_millis(config.sqlite_busy_timeout_ms));
+        // In-memory databases have no file to keep a log next to
+        if config.sqlite_wal && !in_memory {
+            options = options.journal_mode(SqliteJournalMode::Wal);
+        }
+
+        let mut pool_options = SqlitePoolOptions::new().max_connections(config.max_connections.max(1));
+        // An in-memory database is dropped with its last connection
+        if in_memory {
+            pool_options = pool_options
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None);
+        }
+        let pool = pool_options
+            .connect_with(options.clone())
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        // Each connection to an in-memory database opens a database of its
+        // own, so reads and writes must share the pool there
+        let writer = if in_memory {
+            pool.clone()
+        } else {
+            SqlitePoolOptions::new()
+                .max_connections(1)
+                .connect_with(options)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?
+        };
 
-        let store = Self { pool };
+        let store = Self { pool, writer, secrets: None, cache: None };
         store.init().await?;
 
         Ok(store)
     }
 
-    async fn init(&self) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                access_key TEXT UNIQUE NOT NULL,
-                secret_key TEXT NOT NULL,
-                display_name TEXT,
-                email TEXT,
-                is_admin INTEGER DEFAULT 0,
-                created_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    /// Wait for open queries and close every pooled connection
+    ///
+    /// Later queries fail, so call this last on shutdown.
+    pub async fn close(&self) {
+        self.writer.close().await;
+        self.pool.close().await;
+    }
 
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS buckets (
-                name TEXT PRIMARY KEY,
-                owner_id TEXT NOT NULL,
-                region TEXT NOT NULL,
-                versioning TEXT DEFAULT '',
-                object_lock_enabled INTEGER DEFAULT 0,
-                created_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    /// Store secret keys encrypted under the master key
+    ///
+    /// Rows written before are still read as plaintext; run
+    /// [`seal_plaintext_secrets`](Self::seal_plaintext_secrets) to
+    /// encrypt them.
+    pub fn with_secret_cipher(mut self, cipher: SecretCipher) -> Self {
+        self.secrets = Some(cipher);
+        self
+    }
 
-        // Objects table with versioning support
-        // version_id: "null" for non-versioned, UUID for versioned
-        // is_latest: 1 for current version, 0 for old versions
-        // is_delete_marker: 1 if this is a delete marker
-        // encryption: JSON containing encryption info
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS objects (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                version_id TEXT NOT NULL DEFAULT 'null',
-                size INTEGER NOT NULL,
-                etag TEXT NOT NULL,
-                content_type TEXT NOT NULL,
-                metadata TEXT,
-                last_modified TEXT NOT NULL,
-                is_latest INTEGER DEFAULT 1,
-                is_delete_marker INTEGER DEFAULT 0,
-                encryption TEXT,
-                PRIMARY KEY (bucket, key, version_id)
+    /// Serve bucket, object, policy and CORS lookups through `cache`
+    ///
+    /// Writes made through this store invalidate the entries they touch.
+    pub fn with_cache(mut self, cache: MetadataCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Value to store for the secret key of `access_key`
+    fn seal_secret(&self, access_key: &str, secret_key: &str) -> Result<String> {
+        match &self.secrets {
+            Some(cipher) => cipher
+                .seal(access_key, secret_key)
+                .map_err(|e| Error::InternalError(format!("Failed to encrypt secret key: {}", e))),
+            None => Ok(secret_key.to_string()),
+        }
+    }
+
+    /// Secret key of `access_key` from its stored value
+    fn open_secret(&self, access_key: &str, stored: String) -> Result<String> {
+        if !is_sealed(&stored) {
+            return Ok(stored);
+        }
+        let cipher = self.secrets.as_ref().ok_or_else(|| {
+            Error::InternalError(
+                "Secret keys are encrypted but no master key is configured".to_string(),
             )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        })?;
+        cipher
+            .open(access_key, &stored)
+            .map_err(|e| Error::InternalError(format!("Failed to decrypt secret key of {}: {}", access_key, e)))
+    }
 
-        sqlx::query(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_objects_bucket ON objects(bucket)
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    /// Encrypt secret keys still stored as plaintext
+    ///
+    /// Covers users, retiring keys and STS sessions. Returns how many rows
+    /// were encrypted; does nothing without a master key.
+    pub async fn seal_plaintext_secrets(&self) -> Result<u64> {
+        if self.secrets.is_none() {
+            return Ok(0);
+        }
 
-        sqlx::query(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_objects_latest ON objects(bucket, key, is_latest)
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        let mut sealed = 0;
+        for table in ["users", "retiring_keys", "sts_credentials"] {
+            let rows: Vec<(String, String)> = sqlx::query_as(&format!(
+                "SELECT access_key, secret_key FROM {} WHERE secret_key NOT LIKE 'hafiz:v1:%'",
+                table
+            ))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        // Object tagging table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS object_tags (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                version_id TEXT NOT NULL DEFAULT 'null',
-                tag_key TEXT NOT NULL,
-                tag_value TEXT NOT NULL,
-                PRIMARY KEY (bucket, key, version_id, tag_key)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            for (access_key, secret_key) in rows {
+                let value = self.seal_secret(&access_key, &secret_key)?;
+                sqlx::query(&format!(
+                    "UPDATE {} SET secret_key = ? WHERE access_key = ? AND secret_key = ?",
+                    table
+                ))
+                .bind(value)
+                .bind(&access_key)
+                .bind(&secret_key)
+                .execute(&self.writer)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+                sealed += 1;
+            }
+        }
 
-        // Bucket lifecycle configuration table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_lifecycle (
-                bucket TEXT PRIMARY KEY,
-                configuration TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        if sealed > 0 {
+            info!("Encrypted {} plaintext secret keys", sealed);
+        }
+        Ok(sealed)
+    }
 
-        // Bucket policy table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_policies (
-                bucket TEXT PRIMARY KEY,
-                policy_json TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    /// Re-encrypt secret keys sealed under `previous` with the current
+    /// master key
+    ///
+    /// Rows that already open with the current key are left alone, so an
+    /// interrupted run can be repeated. Returns how many rows were
+    /// re-encrypted.
+    pub async fn reseal_secrets(&self, previous: &SecretCipher) -> Result<u64> {
+        let Some(current) = &self.secrets else {
+            return Ok(0);
+        };
 
-        // Bucket ACL table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_acls (
-                bucket TEXT PRIMARY KEY,
-                acl_xml TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        let mut resealed = 0;
+        for table in ["users", "retiring_keys", "sts_credentials"] {
+            let rows: Vec<(String, String)> = sqlx::query_as(&format!(
+                "SELECT access_key, secret_key FROM {} WHERE secret_key LIKE 'hafiz:v1:%'",
+                table
+            ))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        // Object ACL table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS object_acls (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                version_id TEXT NOT NULL DEFAULT 'null',
-                acl_xml TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                PRIMARY KEY (bucket, key, version_id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            for (access_key, stored) in rows {
+                if current.open(&access_key, &stored).is_ok() {
+                    continue;
+                }
+                let secret_key = previous.open(&access_key, &stored).map_err(|e| {
+                    Error::InternalError(format!("Failed to decrypt secret key of {}: {}", access_key, e))
+                })?;
+                let value = self.seal_secret(&access_key, &secret_key)?;
+                sqlx::query(&format!(
+                    "UPDATE {} SET secret_key = ? WHERE access_key = ? AND secret_key = ?",
+                    table
+                ))
+                .bind(value)
+                .bind(&access_key)
+                .bind(&stored)
+                .execute(&self.writer)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+                resealed += 1;
+            }
+        }
 
-        // Bucket notification configuration table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_notifications (
-                bucket TEXT PRIMARY KEY,
-                config_json TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        if resealed > 0 {
+            info!("Re-encrypted {} secret keys under the new master key", resealed);
+        }
+        Ok(resealed)
+    }
 
-        // Bucket CORS configuration table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_cors (
-                bucket TEXT PRIMARY KEY,
-                cors_xml TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    /// Bring the schema up to date with the embedded migrations
+    ///
+    /// Refuses to open a database migrated by a newer release, which may
+    /// have changed the schema in ways this one does not understand.
+    async fn init(&self) -> Result<()> {
+        self.adopt_legacy_schema().await?;
+
+        let newest = MIGRATOR.iter().map(|m| m.version).max().unwrap_or(0);
+        if let Some(version) = self.schema_version().await? {
+            if version > newest {
+                return Err(Error::DatabaseError(format!(
+                    "Database schema is at version {}, newer than version {} supported by this release; upgrade Hafiz before opening it",
+                    version, newest
+                )));
+            }
+        }
 
-        // Bucket Object Lock configuration table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS bucket_object_lock (
-                bucket TEXT PRIMARY KEY,
-                config_xml TEXT NOT NULL,
-                updated_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        MIGRATOR
+            .run(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(format!("Schema migration failed: {}", e)))?;
 
-        // Object retention table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS object_retention (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                version_id TEXT NOT NULL DEFAULT '',
-                retention_xml TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                PRIMARY KEY (bucket, key, version_id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        info!("Metadata schema at version {}", newest);
+        Ok(())
+    }
 
-        // Object legal hold table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS object_legal_hold (
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                version_id TEXT NOT NULL DEFAULT '',
-                hold_xml TEXT NOT NULL,
-                updated_at TEXT NOT NULL,
-                PRIMARY KEY (bucket, key, version_id)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    /// Newest migration applied to the database, if it was ever migrated
+    pub async fn schema_version(&self) -> Result<Option<i64>> {
+        if !self.table_exists("_sqlx_migrations").await? {
+            return Ok(None);
+        }
+        let (version,): (Option<i64>,) =
+            sqlx::query_as(r#"SELECT MAX(version) FROM _sqlx_migrations WHERE success = 1"#)
+                .fetch_one(&self.writer)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        Ok(version)
+    }
 
-        info!("Metadata store initialized with versioning, tagging, lifecycle, policy, ACL, notification, CORS, and Object Lock support");
+    /// Add the columns the first migration expects to databases created
+    /// before migrations existed, whose tables it would otherwise skip
+    async fn adopt_legacy_schema(&self) -> Result<()> {
+        if self.table_exists("_sqlx_migrations").await? {
+            return Ok(());
+        }
+
+        for (table, columns) in [
+            (
+                "users",
+                &["enabled INTEGER NOT NULL DEFAULT 1", "last_used TEXT", "key_created_at TEXT"][..],
+            ),
+            (
+                "objects",
+                &["part_sizes TEXT", "storage_class TEXT NOT NULL DEFAULT 'STANDARD'"][..],
+            ),
+        ] {
+            if !self.table_exists(table).await? {
+                continue;
+            }
+            for column in columns {
+                if let Err(e) = sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {}", table, column))
+                    .execute(&self.writer)
+                    .await
+                {
+                    if !e.to_string().contains("duplicate column") {
+                        return Err(Error::DatabaseError(e.to_string()));
+                    }
+                }
+            }
+        }
         Ok(())
     }
 
+    async fn table_exists(&self, name: &str) -> Result<bool> {
+        let row: Option<(String,)> =
+            sqlx::query_as(r#"SELECT name FROM sqlite_master WHERE type = 'table' AND name = ?"#)
+                .bind(name)
+                .fetch_optional(&self.writer)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        Ok(row.is_some())
+    }
+
     // User operations
     pub async fn create_user(&self, user: &User) -> Result<()> {
         sqlx::query(
@@ -274,12 +327,12 @@ impl MetadataStore {
         )
         .bind(&user.id)
         .bind(&user.access_key)
-        .bind(&user.secret_key)
+        .bind(self.seal_secret(&user.access_key, &user.secret_key)?)
         .bind(&user.display_name)
         .bind(&user.email)
         .bind(user.is_admin)
         .bind(user.created_at.to_rfc3339())
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -300,17 +353,20 @@ impl MetadataStore {
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| User {
-            id: r.0,
-            access_key: r.1,
-            secret_key: r.2,
-            display_name: r.3,
-            email: r.4,
-            is_admin: r.5,
-            created_at: DateTime::parse_from_rfc3339(&r.6)
-                .unwrap()
-                .with_timezone(&Utc),
-        }))
+        row.map(|r| {
+            Ok(User {
+                secret_key: self.open_secret(&r.1, r.2)?,
+                id: r.0,
+                access_key: r.1,
+                display_name: r.3,
+                email: r.4,
+                is_admin: r.5,
+                created_at: DateTime::parse_from_rfc3339(&r.6)
+                    .unwrap()
+                    .with_timezone(&Utc),
+            })
+        })
+        .transpose()
     }
 
     // Bucket operations
@@ -327,7 +383,7 @@ impl MetadataStore {
         .bind(bucket.versioning.as_str())
         .bind(bucket.object_lock_enabled as i32)
         .bind(bucket.created_at.to_rfc3339())
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| {
             if e.to_string().contains("UNIQUE constraint") {
@@ -337,11 +393,22 @@ impl MetadataStore {
             }
         })?;
 
+        if let Some(cache) = &self.cache {
+            cache.invalidate_bucket(&bucket.name);
+        }
         debug!("Created bucket: {}", bucket.name);
         Ok(())
     }
 
+    #[instrument(name = "metadata.get_bucket", skip(self))]
     pub async fn get_bucket(&self, name: &str) -> Result<Option<Bucket>> {
+        match &self.cache {
+            Some(cache) => cache.buckets.get_or_load(name.to_string(), self.fetch_bucket(name)).await,
+            None => self.fetch_bucket(name).await,
+        }
+    }
+
+    async fn fetch_bucket(&self, name: &str) -> Result<Option<Bucket>> {
         let row: Option<(String, String, String, Option<String>, Option<i32>, String)> = sqlx::query_as(
             r#"
             SELECT name, owner_id, region, versioning, object_lock_enabled, created_at
@@ -372,10 +439,13 @@ impl MetadataStore {
         )
         .bind(status.as_str())
         .bind(name)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        if let Some(cache) = &self.cache {
+            cache.buckets.invalidate(&name.to_string());
+        }
         debug!("Set bucket {} versioning to {:?}", name, status);
         Ok(())
     }
@@ -396,96 +466,405 @@ impl MetadataStore {
 
         sqlx::query(r#"DELETE FROM buckets WHERE name = ?"#)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(r#"DELETE FROM bucket_tags WHERE bucket = ?"#)
+            .bind(name)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(r#"DELETE FROM bucket_usage WHERE bucket = ?"#)
+            .bind(name)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(r#"DELETE FROM access_points WHERE bucket = ?"#)
+            .bind(name)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(r#"DELETE FROM bucket_accelerate WHERE bucket = ?"#)
+            .bind(name)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        if let Some(cache) = &self.cache {
+            cache.invalidate_bucket(name);
+            cache.invalidate_access_points();
+        }
         debug!("Deleted bucket: {}", name);
         Ok(())
     }
 
-    pub async fn list_buckets(&self, owner_id: &str) -> Result<Vec<BucketInfo>> {
-        let rows: Vec<(String, String)> = sqlx::query_as(
-            r#"
-            SELECT name, created_at FROM buckets WHERE owner_id = ?
-            ORDER BY name
-            "#,
+    /// Stored versions and bytes of `bucket`, delete markers excluded
+    pub async fn get_bucket_usage(&self, bucket: &str) -> Result<BucketUsage> {
+        let row: Option<(i64, i64)> = sqlx::query_as(
+            r#"SELECT object_count, total_bytes FROM bucket_usage WHERE bucket = ?"#,
+        )
+        .bind(bucket)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row
+            .map(|(object_count, total_bytes)| BucketUsage {
+                object_count,
+                total_bytes,
+            })
+            .unwrap_or_default())
+    }
+
+    /// Usage counters of every bucket that has stored data
+    pub async fn list_bucket_usage(&self) -> Result<Vec<(String, BucketUsage)>> {
+        let rows: Vec<(String, i64, i64)> = sqlx::query_as(
+            r#"SELECT bucket, object_count, total_bytes FROM bucket_usage ORDER BY bucket"#,
         )
-        .bind(owner_id)
         .fetch_all(&self.pool)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         Ok(rows
             .into_iter()
-            .map(|r| BucketInfo {
-                name: r.0,
-                creation_date: DateTime::parse_from_rfc3339(&r.1)
-                    .unwrap()
-                    .with_timezone(&Utc),
+            .map(|(bucket, object_count, total_bytes)| {
+                (
+                    bucket,
+                    BucketUsage {
+                        object_count,
+                        total_bytes,
+                    },
+                )
             })
             .collect())
     }
 
-    // ============= Object operations (with versioning) =============
-
-    /// Put object - handles both versioned and non-versioned buckets
-    pub async fn put_object(&self, object: &Object) -> Result<()> {
-        let metadata_json = serde_json::to_string(&object.metadata)
-            .map_err(|e| Error::InternalError(e.to_string()))?;
-
-        let encryption_json = serde_json::to_string(&object.encryption)
-            .map_err(|e| Error::InternalError(e.to_string()))?;
+    /// Recount the usage of `bucket` from its objects and store the result
+    ///
+    /// Returns the counters as they were before, so callers can tell
+    /// whether they had drifted.
+    pub async fn reconcile_bucket_usage(&self, bucket: &str) -> Result<(BucketUsage, BucketUsage)> {
+        let mut tx = self
+            .writer
+            .begin()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        // Mark all existing versions of this key as non-latest
-        sqlx::query(
-            r#"UPDATE objects SET is_latest = 0 WHERE bucket = ? AND key = ?"#,
+        let stored: Option<(i64, i64)> = sqlx::query_as(
+            r#"SELECT object_count, total_bytes FROM bucket_usage WHERE bucket = ?"#,
         )
-        .bind(&object.bucket)
-        .bind(&object.key)
-        .execute(&self.pool)
+        .bind(bucket)
+        .fetch_optional(&mut *tx)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        sqlx::query(
+        let counted: (i64, i64) = sqlx::query_as(
             r#"
-            INSERT OR REPLACE INTO objects
-            (bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            SELECT COUNT(*), COALESCE(SUM(size), 0)
+            FROM objects WHERE bucket = ? AND is_delete_marker = 0
             "#,
         )
-        .bind(&object.bucket)
-        .bind(&object.key)
-        .bind(&object.version_id)
-        .bind(object.size)
-        .bind(&object.etag)
-        .bind(&object.content_type)
-        .bind(&metadata_json)
-        .bind(object.last_modified.to_rfc3339())
-        .bind(object.is_latest as i32)
-        .bind(object.is_delete_marker as i32)
-        .bind(&encryption_json)
-        .execute(&self.pool)
+        .bind(bucket)
+        .fetch_one(&mut *tx)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        debug!("Put object: {}/{} version={} encrypted={}",
-            object.bucket, object.key, object.version_id, object.encryption.is_encrypted());
-        Ok(())
-    }
+        sqlx::query(
+            r#"INSERT OR REPLACE INTO bucket_usage (bucket, object_count, total_bytes) VALUES (?, ?, ?)"#,
+        )
+        .bind(bucket)
+        .bind(counted.0)
+        .bind(counted.1)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-    /// Get the latest version of an object
-    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<Object>> {
-        self.get_object_version(bucket, key, None).await
-    }
+        tx.commit()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let before = stored
+            .map(|(object_count, total_bytes)| BucketUsage {
+                object_count,
+                total_bytes,
+            })
+            .unwrap_or_default();
+        let after = BucketUsage {
+            object_count: counted.0,
+            total_bytes: counted.1,
+        };
+        Ok((before, after))
+    }
+
+    pub async fn list_buckets(&self, owner_id: &str) -> Result<Vec<BucketInfo>> {
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT name, created_at, region FROM buckets WHERE owner_id = ?
+            ORDER BY name
+            "#,
+        )
+        .bind(owner_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(bucket_info_from_row).collect())
+    }
+
+    /// A page of buckets by name, after the bucket `after`
+    ///
+    /// Only buckets of `owner_id`, starting with `prefix` and in `region`
+    /// are listed, when given. Without a `limit` the page holds them all.
+    /// Also returns whether more buckets follow.
+    pub async fn list_buckets_page(
+        &self,
+        owner_id: Option<&str>,
+        prefix: Option<&str>,
+        region: Option<&str>,
+        after: Option<&str>,
+        limit: Option<usize>,
+    ) -> Result<(Vec<BucketInfo>, bool)> {
+        let prefix = prefix.unwrap_or("");
+        let after = after.unwrap_or("");
+
+        // Seek on the later of the prefix and the last bucket listed
+        let end = prefix_end(prefix);
+        let (lower, bound) = if after < prefix {
+            ("name >= ?", prefix)
+        } else {
+            ("name > ?", after)
+        };
+        let sql = format!(
+            r#"
+            SELECT name, created_at, region FROM buckets
+            WHERE {}{}{}{}
+            ORDER BY name
+            LIMIT ?
+            "#,
+            lower,
+            if end.is_some() { " AND name < ?" } else { "" },
+            if owner_id.is_some() { " AND owner_id = ?" } else { "" },
+            if region.is_some() { " AND region = ?" } else { "" },
+        );
+        let mut query = sqlx::query_as(&sql).bind(bound);
+        for value in [end.as_deref(), owner_id, region].into_iter().flatten() {
+            query = query.bind(value);
+        }
+        // A negative limit is none
+        let mut rows: Vec<(String, String, String)> = query
+            .bind(limit.map_or(-1, |limit| limit as i64 + 1))
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let more = limit.is_some_and(|limit| rows.len() > limit);
+        if let Some(limit) = limit {
+            rows.truncate(limit);
+        }
+        Ok((rows.into_iter().map(bucket_info_from_row).collect(), more))
+    }
+
+    /// Names of all buckets, regardless of owner
+    pub async fn list_bucket_names(&self) -> Result<Vec<String>> {
+        let rows: Vec<(String,)> = sqlx::query_as("SELECT name FROM buckets ORDER BY name")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(|r| r.0).collect())
+    }
+
+    // ============= Object operations (with versioning) =============
+
+    /// Put object - handles both versioned and non-versioned buckets
+    #[instrument(name = "metadata.put_object", skip_all, fields(bucket = %object.bucket, key = %object.key))]
+    pub async fn put_object(&self, object: &Object) -> Result<()> {
+        let mut tx = self
+            .writer
+            .begin()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        Self::insert_object(&mut tx, object).await?;
+        tx.commit()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if let Some(cache) = &self.cache {
+            cache.invalidate_object(&object.bucket, &object.key);
+        }
+        debug!("Put object: {}/{} version={} encrypted={}",
+            object.bucket, object.key, object.version_id, object.encryption.is_encrypted());
+        Ok(())
+    }
+
+    /// Replace the content type and user metadata of an object version in
+    /// place, leaving its data, ETag and version untouched. Returns the new
+    /// modification time.
+    pub async fn update_object_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+        content_type: &str,
+        metadata: &HashMap<String, String>,
+    ) -> Result<DateTime<Utc>> {
+        let metadata_json = serde_json::to_string(metadata)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+        let now = Utc::now();
+
+        let result = sqlx::query(
+            r#"
+            UPDATE objects SET content_type = ?, metadata = ?, last_modified = ?
+            WHERE bucket = ? AND key = ? AND version_id = ? AND is_delete_marker = 0
+            "#,
+        )
+        .bind(content_type)
+        .bind(&metadata_json)
+        .bind(now.to_rfc3339())
+        .bind(bucket)
+        .bind(key)
+        .bind(version_id)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NoSuchKey);
+        }
+        if let Some(cache) = &self.cache {
+            cache.invalidate_object(bucket, key);
+        }
+        debug!("Updated metadata of {}/{} version={}", bucket, key, version_id);
+        Ok(now)
+    }
+
+    /// Record the storage class of an object version whose data moved
+    pub async fn set_object_storage_class(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+        storage_class: &str,
+    ) -> Result<()> {
+        let result = sqlx::query(
+            r#"
+            UPDATE objects SET storage_class = ?
+            WHERE bucket = ? AND key = ? AND version_id = ? AND is_delete_marker = 0
+            "#,
+        )
+        .bind(storage_class)
+        .bind(bucket)
+        .bind(key)
+        .bind(version_id)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NoSuchKey);
+        }
+        if let Some(cache) = &self.cache {
+            cache.invalidate_object(bucket, key);
+        }
+        debug!(
+            "Storage class of {}/{} version={} set to {}",
+            bucket, key, version_id, storage_class
+        );
+        Ok(())
+    }
+
+    /// Add `object` as the latest version of its key
+    async fn insert_object(conn: &mut SqliteConnection, object: &Object) -> Result<()> {
+        let metadata_json = serde_json::to_string(&object.metadata)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+
+        let encryption_json = serde_json::to_string(&object.encryption)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+
+        let part_sizes_json = if object.part_sizes.is_empty() {
+            None
+        } else {
+            Some(
+                serde_json::to_string(&object.part_sizes)
+                    .map_err(|e| Error::InternalError(e.to_string()))?,
+            )
+        };
+
+        // Mark all existing versions of this key as non-latest
+        sqlx::query(
+            r#"UPDATE objects SET is_latest = 0 WHERE bucket = ? AND key = ?"#,
+        )
+        .bind(&object.bucket)
+        .bind(&object.key)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO objects
+            (bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(bucket, key, version_id) DO UPDATE SET
+                size = excluded.size,
+                etag = excluded.etag,
+                content_type = excluded.content_type,
+                metadata = excluded.metadata,
+                last_modified = excluded.last_modified,
+                is_latest = excluded.is_latest,
+                is_delete_marker = excluded.is_delete_marker,
+                encryption = excluded.encryption,
+                part_sizes = excluded.part_sizes,
+                storage_class = excluded.storage_class
+            "#,
+        )
+        .bind(&object.bucket)
+        .bind(&object.key)
+        .bind(&object.version_id)
+        .bind(object.size)
+        .bind(&object.etag)
+        .bind(&object.content_type)
+        .bind(&metadata_json)
+        .bind(object.last_modified.to_rfc3339())
+        .bind(object.is_latest as i32)
+        .bind(object.is_delete_marker as i32)
+        .bind(&encryption_json)
+        .bind(&part_sizes_json)
+        .bind(&object.storage_class)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Get the latest version of an object
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<Object>> {
+        self.get_object_version(bucket, key, None).await
+    }
 
     /// Get a specific version of an object
+    #[instrument(name = "metadata.get_object", skip(self))]
     pub async fn get_object_version(&self, bucket: &str, key: &str, version_id: Option<&str>) -> Result<Option<Object>> {
-        let row: Option<(String, String, String, i64, String, String, Option<String>, String, i32, i32, Option<String>)> =
+        match (&self.cache, version_id) {
+            (Some(cache), None) => {
+                let load = self.fetch_object_version(bucket, key, None);
+                cache.objects.get_or_load((bucket.to_string(), key.to_string()), load).await
+            }
+            _ => self.fetch_object_version(bucket, key, version_id).await,
+        }
+    }
+
+    async fn fetch_object_version(&self, bucket: &str, key: &str, version_id: Option<&str>) -> Result<Option<Object>> {
+        let row: Option<ObjectRow> =
             if let Some(vid) = version_id {
                 sqlx::query_as(
                     r#"
-                    SELECT bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption
+                    SELECT bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class
                     FROM objects WHERE bucket = ? AND key = ? AND version_id = ?
                     "#,
                 )
@@ -498,7 +877,7 @@ impl MetadataStore {
             } else {
                 sqlx::query_as(
                     r#"
-                    SELECT bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption
+                    SELECT bucket, key, version_id, size, etag, content_type, metadata, last_modified, is_latest, is_delete_marker, encryption, part_sizes, storage_class
                     FROM objects WHERE bucket = ? AND key = ? AND is_latest = 1
                     "#,
                 )
@@ -509,50 +888,29 @@ impl MetadataStore {
                 .map_err(|e| Error::DatabaseError(e.to_string()))?
             };
 
-        Ok(row.map(|r| {
-            let metadata: HashMap<String, String> = r
-                .6
-                .and_then(|m| serde_json::from_str(&m).ok())
-                .unwrap_or_default();
-
-            let encryption: EncryptionInfo = r
-                .10
-                .and_then(|e| serde_json::from_str(&e).ok())
-                .unwrap_or_default();
-
-            Object {
-                bucket: r.0,
-                key: r.1,
-                version_id: r.2,
-                size: r.3,
-                etag: r.4,
-                content_type: r.5,
-                metadata,
-                last_modified: DateTime::parse_from_rfc3339(&r.7)
-                    .unwrap()
-                    .with_timezone(&Utc),
-                is_latest: r.8 != 0,
-                is_delete_marker: r.9 != 0,
-                encryption,
-            }
-        }))
+        Ok(row.map(object_from_row))
     }
 
     /// Delete object - for non-versioned buckets, removes the object
     /// For versioned buckets, creates a delete marker
+    #[instrument(name = "metadata.delete_object", skip(self))]
     pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
         sqlx::query(r#"DELETE FROM objects WHERE bucket = ? AND key = ? AND version_id = 'null'"#)
             .bind(bucket)
             .bind(key)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        if let Some(cache) = &self.cache {
+            cache.invalidate_object(bucket, key);
+        }
         debug!("Deleted object: {}/{}", bucket, key);
         Ok(())
     }
 
     /// List objects - only returns latest non-deleted versions
+    #[instrument(name = "metadata.list_objects", skip(self))]
     pub async fn list_objects(
         &self,
         bucket: &str,
@@ -562,66 +920,182 @@ impl MetadataStore {
         continuation_token: Option<&str>,
     ) -> Result<(Vec<ObjectInfo>, Vec<String>, bool, Option<String>)> {
         let prefix = prefix.unwrap_or("");
-        let start_after = continuation_token.unwrap_or("");
+        let delimiter = delimiter.filter(|d| !d.is_empty());
+        let max_keys = max_keys.max(0) as usize;
+        let batch_size = (max_keys as i64 + 1).clamp(100, 1000);
+
+        // A marker inside a rolled-up prefix resumes after the whole prefix,
+        // so the prefix is not returned twice
+        let mut marker = match continuation_token {
+            Some(token) => match delimiter.and_then(|d| common_prefix(prefix, token, d)) {
+                Some(common) => past_prefix(&common),
+                None => token.to_string(),
+            },
+            None => String::new(),
+        };
+
+        let mut objects = Vec::new();
+        let mut common_prefixes: Vec<String> = Vec::new();
+        let mut last_entry: Option<String> = None;
+        let mut is_truncated = false;
+
+        // Only get latest versions that are not delete markers. The scan
+        // starts at the prefix or after the marker, whichever is later; SQLite
+        // only seeks on one lower bound, so it must not be given both.
+        let end = prefix_end(prefix);
+        let select = |lower: &str| {
+            format!(
+                r#"
+                SELECT key, version_id, size, etag, last_modified, storage_class
+                FROM objects
+                WHERE bucket = ? AND {}{} AND is_latest = 1 AND is_delete_marker = 0
+                ORDER BY key
+                LIMIT ?
+                "#,
+                lower,
+                below(&end)
+            )
+        };
+        let from_prefix = select("key >= ?");
+        let after_marker = select("key > ?");
+
+        // Roll up keys while fetching, so MaxKeys counts keys and common
+        // prefixes together as S3 does. After skipping a prefix the next
+        // fetch starts with a single row and grows back to the full batch,
+        // so a listing made of many large prefixes does not read a batch per
+        // prefix only to discard it.
+        let mut limit = batch_size;
+        'fetch: loop {
+            let (sql, lower) = if marker.as_str() < prefix {
+                (&from_prefix, prefix)
+            } else {
+                (&after_marker, marker.as_str())
+            };
+            let mut query = sqlx::query_as(sql).bind(bucket).bind(lower);
+            if let Some(end) = &end {
+                query = query.bind(end);
+            }
+            let rows: Vec<(String, String, i64, String, String, String)> = query
+                .bind(limit)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            let exhausted = (rows.len() as i64) < limit;
+
+            for row in rows {
+                if let Some(common) = delimiter.and_then(|d| common_prefix(prefix, &row.0, d)) {
+                    if objects.len() + common_prefixes.len() == max_keys {
+                        is_truncated = true;
+                        break 'fetch;
+                    }
+                    // Skip the rest of the keys under this prefix
+                    marker = past_prefix(&common);
+                    last_entry = Some(common.clone());
+                    common_prefixes.push(common);
+                    limit = 1;
+                    continue 'fetch;
+                }
+
+                if objects.len() + common_prefixes.len() == max_keys {
+                    is_truncated = true;
+                    break 'fetch;
+                }
+                marker = row.0.clone();
+                last_entry = Some(row.0.clone());
+                objects.push(ObjectInfo {
+                    key: row.0,
+                    size: row.2,
+                    etag: row.3,
+                    last_modified: DateTime::parse_from_rfc3339(&row.4)
+                        .unwrap()
+                        .with_timezone(&Utc),
+                    storage_class: row.5,
+                    version_id: Some(row.1),
+                    is_latest: Some(true),
+                });
+            }
+
+            if exhausted {
+                break;
+            }
+            limit = (limit * 2).min(batch_size);
+        }
+
+        let next_token = if is_truncated { last_entry } else { None };
+
+        Ok((objects, common_prefixes, is_truncated, next_token))
+    }
 
-        // Only get latest versions that are not delete markers
-        let rows: Vec<(String, String, i64, String, String)> = sqlx::query_as(
+    /// List objects of a flat-namespace bucket - latest non-deleted
+    /// versions, with no delimiter processing
+    ///
+    /// One query per page: keys are read in order and the page ends at
+    /// `max_keys`, without the roll-up loop of [`Self::list_objects`].
+    #[instrument(name = "metadata.list_objects_flat", skip(self))]
+    pub async fn list_objects_flat(
+        &self,
+        bucket: &str,
+        prefix: Option<&str>,
+        max_keys: i32,
+        continuation_token: Option<&str>,
+    ) -> Result<(Vec<ObjectInfo>, bool, Option<String>)> {
+        let prefix = prefix.unwrap_or("");
+        let max_keys = max_keys.max(0) as usize;
+        let marker = continuation_token.unwrap_or("");
+
+        // SQLite only seeks on one lower bound, so pass the later of the
+        // prefix and the marker
+        let end = prefix_end(prefix);
+        let (lower, bound) = if marker < prefix {
+            ("key >= ?", prefix)
+        } else {
+            ("key > ?", marker)
+        };
+        let sql = format!(
             r#"
-            SELECT key, version_id, size, etag, last_modified
+            SELECT key, version_id, size, etag, last_modified, storage_class
             FROM objects
-            WHERE bucket = ? AND key LIKE ? AND key > ? AND is_latest = 1 AND is_delete_marker = 0
+            WHERE bucket = ? AND {}{} AND is_latest = 1 AND is_delete_marker = 0
             ORDER BY key
             LIMIT ?
             "#,
-        )
-        .bind(bucket)
-        .bind(format!("{}%", prefix))
-        .bind(start_after)
-        .bind(max_keys + 1)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
-
-        let is_truncated = rows.len() > max_keys as usize;
-        let rows: Vec<_> = rows.into_iter().take(max_keys as usize).collect();
+            lower,
+            below(&end)
+        );
+        let mut query = sqlx::query_as(&sql).bind(bucket).bind(bound);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let mut rows: Vec<(String, String, i64, String, String, String)> = query
+            .bind(max_keys as i64 + 1)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        let is_truncated = rows.len() > max_keys;
+        rows.truncate(max_keys);
         let next_token = if is_truncated {
-            rows.last().map(|r| r.0.clone())
+            rows.last().map(|row| row.0.clone())
         } else {
             None
         };
 
-        let mut objects = Vec::new();
-        let mut common_prefixes = std::collections::HashSet::new();
-
-        for row in rows {
-            let key = row.0;
-
-            if let Some(delim) = delimiter {
-                let suffix = key.strip_prefix(prefix).unwrap_or(&key);
-                if let Some(idx) = suffix.find(delim) {
-                    let prefix_key = format!("{}{}{}", prefix, &suffix[..idx], delim);
-                    common_prefixes.insert(prefix_key);
-                    continue;
-                }
-            }
-
-            objects.push(ObjectInfo {
-                key,
+        let objects = rows
+            .into_iter()
+            .map(|row| ObjectInfo {
+                key: row.0,
                 size: row.2,
                 etag: row.3,
                 last_modified: DateTime::parse_from_rfc3339(&row.4)
                     .unwrap()
                     .with_timezone(&Utc),
-                storage_class: "STANDARD".to_string(),
+                storage_class: row.5,
                 version_id: Some(row.1),
                 is_latest: Some(true),
-            });
-        }
-
-        let common_prefixes: Vec<String> = common_prefixes.into_iter().collect();
+            })
+            .collect();
 
-        Ok((objects, common_prefixes, is_truncated, next_token))
+        Ok((objects, is_truncated, next_token))
     }
 
     /// List all versions of objects (for versioned buckets)
@@ -638,22 +1112,26 @@ impl MetadataStore {
         let key_marker = key_marker.unwrap_or("");
 
         // Get all versions including delete markers
-        let rows: Vec<(String, String, i64, String, String, i32, i32)> = sqlx::query_as(
+        let end = prefix_end(prefix);
+        let sql = format!(
             r#"
-            SELECT key, version_id, size, etag, last_modified, is_latest, is_delete_marker
+            SELECT key, version_id, size, etag, last_modified, is_latest, is_delete_marker, storage_class
             FROM objects
-            WHERE bucket = ? AND key LIKE ? AND key >= ?
+            WHERE bucket = ? AND key >= ?{}
             ORDER BY key, last_modified DESC
             LIMIT ?
             "#,
-        )
-        .bind(bucket)
-        .bind(format!("{}%", prefix))
-        .bind(key_marker)
-        .bind(max_keys + 1)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            below(&end)
+        );
+        let mut query = sqlx::query_as(&sql).bind(bucket).bind(prefix.max(key_marker));
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, i64, String, String, i32, i32, String)> = query
+            .bind(max_keys + 1)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         let is_truncated = rows.len() > max_keys as usize;
         let rows: Vec<_> = rows.into_iter().take(max_keys as usize).collect();
@@ -702,7 +1180,7 @@ impl MetadataStore {
                     last_modified,
                     etag: row.3,
                     size: row.2,
-                    storage_class: Some("STANDARD".to_string()),
+                    storage_class: Some(row.7),
                     owner: None,
                 });
             }
@@ -713,15 +1191,90 @@ impl MetadataStore {
         Ok((versions, delete_markers, common_prefixes, is_truncated, next_key_marker, next_version_id_marker))
     }
 
-    /// Delete a specific version of an object
-    pub async fn delete_object_version(&self, bucket: &str, key: &str, version_id: &str) -> Result<bool> {
-        let result = sqlx::query(
-            r#"DELETE FROM objects WHERE bucket = ? AND key = ? AND version_id = ?"#
-        )
-        .bind(bucket)
+    /// Page through every version and delete marker of `bucket`
+    ///
+    /// Entries are ordered by key and version ID and start after `after`,
+    /// so a caller deleting what it reads can keep paging from the last
+    /// entry it saw.
+    pub async fn list_version_refs(
+        &self,
+        bucket: &str,
+        after: Option<(&str, &str)>,
+        limit: i32,
+    ) -> Result<Vec<VersionRef>> {
+        let (key_marker, version_marker) = after.unwrap_or(("", ""));
+        let rows: Vec<(String, String, i64, i32, String, i32)> = sqlx::query_as(
+            r#"
+            SELECT key, version_id, size, is_delete_marker, last_modified, is_latest
+            FROM objects
+            WHERE bucket = ? AND (key > ? OR (key = ? AND version_id > ?))
+            ORDER BY key, version_id
+            LIMIT ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(key_marker)
+        .bind(key_marker)
+        .bind(version_marker)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(VersionRef::from_row).collect())
+    }
+
+    /// Every version and delete marker of the next `max_keys` keys of
+    /// `bucket` after `after` that start with `prefix`
+    ///
+    /// A key's versions are listed together, newest first, so its whole
+    /// history can be judged at once while paging through a large bucket.
+    pub async fn list_key_versions(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        after: Option<&str>,
+        max_keys: i32,
+    ) -> Result<Vec<VersionRef>> {
+        let end = prefix_end(prefix);
+        let sql = format!(
+            r#"
+            SELECT key, version_id, size, is_delete_marker, last_modified, is_latest
+            FROM objects
+            WHERE bucket = ? AND key IN (
+                SELECT DISTINCT key FROM objects
+                WHERE bucket = ? AND key >= ?{} AND key > ?
+                ORDER BY key
+                LIMIT ?
+            )
+            ORDER BY key, last_modified DESC
+            "#,
+            below(&end)
+        );
+        let mut query = sqlx::query_as(&sql).bind(bucket).bind(bucket).bind(prefix);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, i64, i32, String, i32)> = query
+            .bind(after.unwrap_or(""))
+            .bind(max_keys)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(VersionRef::from_row).collect())
+    }
+
+    /// Delete a specific version of an object
+    #[instrument(name = "metadata.delete_object_version", skip(self))]
+    pub async fn delete_object_version(&self, bucket: &str, key: &str, version_id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            r#"DELETE FROM objects WHERE bucket = ? AND key = ? AND version_id = ?"#
+        )
+        .bind(bucket)
         .bind(key)
         .bind(version_id)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -742,11 +1295,14 @@ impl MetadataStore {
             .bind(key)
             .bind(bucket)
             .bind(key)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
         }
 
+        if let Some(cache) = &self.cache {
+            cache.invalidate_object(bucket, key);
+        }
         debug!("Deleted object version: {}/{} version={}", bucket, key, version_id);
         Ok(result.rows_affected() > 0)
     }
@@ -763,57 +1319,131 @@ impl MetadataStore {
         Ok(version_id)
     }
 
-    // ============= Phase 2: Multipart Upload Operations =============
+    /// Delete many objects of `bucket` in one transaction
+    ///
+    /// An entry with a version ID removes that version and makes the next
+    /// most recent one the latest. Without a version ID the unversioned
+    /// object is removed or, with `add_delete_markers` (a bucket with
+    /// versioning enabled), hidden under a new delete marker. Outcomes are
+    /// returned in entry order; if any entry fails, none is applied.
+    pub async fn delete_objects(
+        &self,
+        bucket: &str,
+        entries: &[(String, Option<String>)],
+        add_delete_markers: bool,
+    ) -> Result<Vec<DeletedEntry>> {
+        let marker_ids: Option<Vec<String>> = add_delete_markers
+            .then(|| entries.iter().map(|_| Object::generate_version_id()).collect());
+        self.delete_objects_with_markers(bucket, entries, marker_ids.as_deref())
+            .await
+    }
 
-    /// Initialize multipart upload tables
-    pub async fn init_multipart_tables(&self) -> Result<()> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS multipart_uploads (
-                upload_id TEXT PRIMARY KEY,
-                bucket TEXT NOT NULL,
-                key TEXT NOT NULL,
-                content_type TEXT NOT NULL,
-                metadata TEXT,
-                storage_class TEXT DEFAULT 'STANDARD',
-                initiator_id TEXT DEFAULT 'root',
-                created_at TEXT NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+    /// [`Self::delete_objects`] with the delete markers' version IDs chosen
+    /// by the caller, one per entry, so that every node of a cluster
+    /// records the same ones
+    pub async fn delete_objects_with_markers(
+        &self,
+        bucket: &str,
+        entries: &[(String, Option<String>)],
+        marker_ids: Option<&[String]>,
+    ) -> Result<Vec<DeletedEntry>> {
+        if marker_ids.is_some_and(|ids| ids.len() != entries.len()) {
+            return Err(Error::InternalError(
+                "One delete marker version ID is needed per entry".into(),
+            ));
+        }
 
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS upload_parts (
-                upload_id TEXT NOT NULL,
-                part_number INTEGER NOT NULL,
-                size INTEGER NOT NULL,
-                etag TEXT NOT NULL,
-                created_at TEXT NOT NULL,
-                PRIMARY KEY (upload_id, part_number)
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        let mut tx = self
+            .writer
+            .begin()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        sqlx::query(
-            r#"
-            CREATE INDEX IF NOT EXISTS idx_multipart_bucket ON multipart_uploads(bucket, key)
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        let mut outcomes = Vec::with_capacity(entries.len());
+        for (i, (key, version_id)) in entries.iter().enumerate() {
+            let outcome = match (version_id, marker_ids) {
+                (Some(vid), _) => {
+                    let removed: Option<(i32,)> = sqlx::query_as(
+                        r#"
+                        DELETE FROM objects WHERE bucket = ? AND key = ? AND version_id = ?
+                        RETURNING is_delete_marker
+                        "#,
+                    )
+                    .bind(bucket)
+                    .bind(key)
+                    .bind(vid)
+                    .fetch_optional(&mut *tx)
+                    .await
+                    .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+                    if removed.is_some() {
+                        sqlx::query(
+                            r#"
+                            UPDATE objects SET is_latest = 1
+                            WHERE bucket = ? AND key = ? AND version_id = (
+                                SELECT version_id FROM objects
+                                WHERE bucket = ? AND key = ?
+                                ORDER BY last_modified DESC
+                                LIMIT 1
+                            )
+                            "#,
+                        )
+                        .bind(bucket)
+                        .bind(key)
+                        .bind(bucket)
+                        .bind(key)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+                    }
+
+                    let was_marker = removed.is_some_and(|r| r.0 != 0);
+                    DeletedEntry {
+                        delete_marker: was_marker,
+                        delete_marker_version_id: was_marker.then(|| vid.clone()),
+                    }
+                }
+                (None, Some(marker_ids)) => {
+                    let marker_id = marker_ids[i].clone();
+                    let marker = Object::as_delete_marker(
+                        bucket.to_string(),
+                        key.clone(),
+                        marker_id.clone(),
+                    );
+                    Self::insert_object(&mut tx, &marker).await?;
+                    DeletedEntry {
+                        delete_marker: true,
+                        delete_marker_version_id: Some(marker_id),
+                    }
+                }
+                (None, None) => {
+                    sqlx::query(r#"DELETE FROM objects WHERE bucket = ? AND key = ? AND version_id = 'null'"#)
+                        .bind(bucket)
+                        .bind(key)
+                        .execute(&mut *tx)
+                        .await
+                        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+                    DeletedEntry::default()
+                }
+            };
+            outcomes.push(outcome);
+        }
 
-        info!("Multipart upload tables initialized");
-        Ok(())
+        tx.commit()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if let Some(cache) = &self.cache {
+            for (key, _) in entries {
+                cache.invalidate_object(bucket, key);
+            }
+        }
+        debug!("Deleted {} objects from {}", entries.len(), bucket);
+        Ok(outcomes)
     }
 
+    // ============= Phase 2: Multipart Upload Operations =============
+
     /// Create a new multipart upload
     pub async fn create_multipart_upload(
         &self,
@@ -821,18 +1451,17 @@ impl MetadataStore {
         key: &str,
         content_type: &str,
         metadata: &HashMap<String, String>,
+        storage_class: &str,
+        encryption: EncryptionType,
     ) -> Result<String> {
-        // Ensure tables exist
-        self.init_multipart_tables().await?;
-
         let upload_id = uuid::Uuid::new_v4().to_string().replace("-", "");
         let metadata_json = serde_json::to_string(metadata)
             .map_err(|e| Error::InternalError(e.to_string()))?;
 
         sqlx::query(
             r#"
-            INSERT INTO multipart_uploads (upload_id, bucket, key, content_type, metadata, created_at)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT INTO multipart_uploads (upload_id, bucket, key, content_type, metadata, storage_class, encryption, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&upload_id)
@@ -840,8 +1469,10 @@ impl MetadataStore {
         .bind(key)
         .bind(content_type)
         .bind(&metadata_json)
+        .bind(storage_class)
+        .bind(encryption.as_str())
         .bind(Utc::now().to_rfc3339())
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -856,10 +1487,10 @@ impl MetadataStore {
         key: &str,
         upload_id: &str,
     ) -> Result<Option<MultipartUpload>> {
-        let row: Option<(String, String, String, String, Option<String>, String, String, String)> =
+        let row: Option<(String, String, String, String, Option<String>, String, String, String, String)> =
             sqlx::query_as(
                 r#"
-                SELECT upload_id, bucket, key, content_type, metadata, storage_class, initiator_id, created_at
+                SELECT upload_id, bucket, key, content_type, metadata, storage_class, initiator_id, created_at, encryption
                 FROM multipart_uploads
                 WHERE upload_id = ? AND bucket = ? AND key = ?
                 "#,
@@ -888,6 +1519,7 @@ impl MetadataStore {
                 created_at: DateTime::parse_from_rfc3339(&r.7)
                     .unwrap()
                     .with_timezone(&Utc),
+                encryption: EncryptionType::from_header(Some(&r.8)),
             }
         }))
     }
@@ -897,14 +1529,14 @@ impl MetadataStore {
         // Delete parts first
         sqlx::query(r#"DELETE FROM upload_parts WHERE upload_id = ?"#)
             .bind(upload_id)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         // Delete upload record
         sqlx::query(r#"DELETE FROM multipart_uploads WHERE upload_id = ?"#)
             .bind(upload_id)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -931,7 +1563,7 @@ impl MetadataStore {
         .bind(size)
         .bind(etag)
         .bind(Utc::now().to_rfc3339())
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -968,6 +1600,9 @@ impl MetadataStore {
     }
 
     /// List multipart uploads for a bucket
+    ///
+    /// Resumes after `key_marker`, or with `upload_id_marker` after that
+    /// upload within the same key.
     pub async fn list_multipart_uploads(
         &self,
         bucket: &str,
@@ -979,22 +1614,30 @@ impl MetadataStore {
         let prefix = prefix.unwrap_or("");
         let key_marker = key_marker.unwrap_or("");
 
-        let rows: Vec<(String, String, String, String, String)> = sqlx::query_as(
+        let end = prefix_end(prefix);
+        let sql = format!(
             r#"
             SELECT upload_id, key, initiator_id, storage_class, created_at
             FROM multipart_uploads
-            WHERE bucket = ? AND key LIKE ? AND key > ?
+            WHERE bucket = ? AND key >= ?{}
+              AND (key > ? OR (key = ? AND upload_id > ?))
             ORDER BY key, upload_id
             LIMIT ?
             "#,
-        )
-        .bind(bucket)
-        .bind(format!("{}%", prefix))
-        .bind(key_marker)
-        .bind(max_uploads + 1)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            below(&end)
+        );
+        let mut query = sqlx::query_as(&sql).bind(bucket).bind(prefix.max(key_marker));
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, String, String, String)> = query
+            .bind(key_marker)
+            .bind(key_marker)
+            .bind(upload_id_marker)
+            .bind(max_uploads + 1)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         let is_truncated = rows.len() > max_uploads as usize;
         let rows: Vec<_> = rows.into_iter().take(max_uploads as usize).collect();
@@ -1014,6 +1657,227 @@ impl MetadataStore {
 
         Ok((uploads, is_truncated))
     }
+
+    /// List multipart uploads across all buckets initiated before `cutoff`
+    pub async fn list_stale_multipart_uploads(
+        &self,
+        cutoff: DateTime<Utc>,
+    ) -> Result<Vec<MultipartUpload>> {
+        let rows: Vec<(String, String, String, String, Option<String>, String, String, String, String)> =
+            sqlx::query_as(
+                r#"
+                SELECT upload_id, bucket, key, content_type, metadata, storage_class, initiator_id, created_at, encryption
+                FROM multipart_uploads
+                WHERE created_at < ?
+                ORDER BY created_at
+                "#,
+            )
+            .bind(cutoff.to_rfc3339())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| MultipartUpload {
+                upload_id: r.0,
+                bucket: r.1,
+                key: r.2,
+                content_type: r.3,
+                metadata: r
+                    .4
+                    .and_then(|m| serde_json::from_str(&m).ok())
+                    .unwrap_or_default(),
+                storage_class: r.5,
+                initiator_id: r.6,
+                created_at: DateTime::parse_from_rfc3339(&r.7)
+                    .unwrap()
+                    .with_timezone(&Utc),
+                encryption: EncryptionType::from_header(Some(&r.8)),
+            })
+            .collect())
+    }
+}
+
+/// Usage counters of a bucket, see [`MetadataStore::get_bucket_usage`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BucketUsage {
+    pub object_count: i64,
+    pub total_bytes: i64,
+}
+
+/// A version or delete marker as listed by [`MetadataStore::list_version_refs`]
+#[derive(Debug, Clone)]
+pub struct VersionRef {
+    pub key: String,
+    pub version_id: String,
+    pub size: i64,
+    pub is_delete_marker: bool,
+    pub last_modified: DateTime<Utc>,
+    pub is_latest: bool,
+}
+
+impl VersionRef {
+    fn from_row(r: (String, String, i64, i32, String, i32)) -> Self {
+        Self {
+            key: r.0,
+            version_id: r.1,
+            size: r.2,
+            is_delete_marker: r.3 != 0,
+            last_modified: DateTime::parse_from_rfc3339(&r.4)
+                .map(|t| t.with_timezone(&Utc))
+                .unwrap_or_else(|_| Utc::now()),
+            is_latest: r.5 != 0,
+        }
+    }
+}
+
+/// A retention setting as listed by [`MetadataStore::list_object_retentions`]
+#[derive(Debug, Clone)]
+pub struct RetentionRecord {
+    pub key: String,
+    /// Empty for retention set without a version ID
+    pub version_id: String,
+    pub retention_xml: String,
+    pub updated_at: String,
+}
+
+/// Sampled reads of an object version
+#[derive(Debug, Clone)]
+pub struct ObjectAccess {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: String,
+    pub last_accessed: DateTime<Utc>,
+    /// Estimated reads since the last flush
+    pub count: i64,
+}
+
+/// Access statistics of a bucket
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct TieringStats {
+    /// Object versions with access statistics
+    pub tracked_objects: i64,
+    /// Object versions tiering moved to the archive backend
+    pub tiered_objects: i64,
+    pub tiered_bytes: i64,
+}
+
+/// An object version's encryption as listed by [`MetadataStore::list_wrapped_keys`]
+#[derive(Debug, Clone)]
+pub struct WrappedKey {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: String,
+    pub encryption: EncryptionInfo,
+}
+
+/// Progress of re-wrapping DEKs under a new master key
+#[derive(Debug, Clone)]
+pub struct KeyRewrapRecord {
+    /// Check values of the master keys the DEKs move from and to
+    pub from_key_check: String,
+    pub to_key_check: String,
+    pub state: String,
+    /// Bucket, key and version ID of the last object version done
+    pub after: (String, String, String),
+    pub objects_scanned: u64,
+    pub objects_rewrapped: u64,
+    /// Versions already under the new key
+    pub objects_current: u64,
+    pub objects_failed: u64,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+impl KeyRewrapRecord {
+    fn from_row(r: KeyRewrapRow) -> Self {
+        Self {
+            from_key_check: r.0,
+            to_key_check: r.1,
+            state: r.2,
+            after: (r.3, r.4, r.5),
+            objects_scanned: r.6.max(0) as u64,
+            objects_rewrapped: r.7.max(0) as u64,
+            objects_current: r.8.max(0) as u64,
+            objects_failed: r.9.max(0) as u64,
+            started_at: parse_optional_timestamp(Some(&r.10)).unwrap_or_else(Utc::now),
+            updated_at: parse_optional_timestamp(Some(&r.11)).unwrap_or_else(Utc::now),
+            finished_at: parse_optional_timestamp(r.12.as_deref()),
+            error: r.13,
+        }
+    }
+}
+
+/// Restore of an archived object version
+#[derive(Debug, Clone)]
+pub struct ObjectRestore {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: String,
+    /// Days the restored copy is kept
+    pub days: i32,
+    pub requested_at: DateTime<Utc>,
+    /// When the copy was made; `None` while the restore runs
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When the restored copy is removed again
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl ObjectRestore {
+    fn from_row(r: ObjectRestoreRow) -> Self {
+        let parse = |s: &str| {
+            DateTime::parse_from_rfc3339(s)
+                .map(|dt| dt.with_timezone(&Utc))
+                .ok()
+        };
+        Self {
+            requested_at: parse(&r.4).unwrap_or_else(Utc::now),
+            completed_at: r.5.as_deref().and_then(parse),
+            expires_at: r.6.as_deref().and_then(parse),
+            bucket: r.0,
+            key: r.1,
+            version_id: r.2,
+            days: r.3,
+        }
+    }
+
+    /// Whether the data is still being copied back
+    pub fn is_ongoing(&self) -> bool {
+        self.completed_at.is_none()
+    }
+
+    /// Whether a restored copy is readable at `now`
+    pub fn is_available(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires| expires > now)
+    }
+}
+
+/// A delete or overwrite that bypassed GOVERNANCE retention
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GovernanceBypassEntry {
+    /// Assigned when recorded; ignored by [`MetadataStore::record_governance_bypass`]
+    pub id: i64,
+    pub bucket: String,
+    pub key: String,
+    pub version_id: String,
+    /// Access key that bypassed the retention
+    pub principal: String,
+    pub request_id: String,
+    /// Retain-until date that was bypassed
+    pub retain_until: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Outcome of one entry of [`MetadataStore::delete_objects`]
+#[derive(Debug, Clone, Default)]
+pub struct DeletedEntry {
+    /// A delete marker was added, or the version removed was one
+    pub delete_marker: bool,
+    /// Version ID of that delete marker
+    pub delete_marker_version_id: Option<String>,
 }
 
 // ============= Phase 2: Multipart Upload Types =============
@@ -1029,6 +1893,8 @@ pub struct MultipartUpload {
     pub storage_class: String,
     pub initiator_id: String,
     pub created_at: DateTime<Utc>,
+    /// Server-side encryption of the completed object
+    pub encryption: EncryptionType,
 }
 
 /// Upload part record
@@ -1070,7 +1936,7 @@ impl MetadataStore {
         .bind(bucket)
         .bind(key)
         .bind(vid)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -1087,7 +1953,7 @@ impl MetadataStore {
             .bind(vid)
             .bind(&tag.key)
             .bind(&tag.value)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
         }
@@ -1141,7 +2007,7 @@ impl MetadataStore {
         .bind(bucket)
         .bind(key)
         .bind(vid)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -1171,7 +2037,7 @@ impl MetadataStore {
         .bind(bucket)
         .bind(&config_json)
         .bind(Utc::now().to_rfc3339())
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -1203,7 +2069,7 @@ impl MetadataStore {
     pub async fn delete_bucket_lifecycle(&self, bucket: &str) -> Result<()> {
         sqlx::query(r#"DELETE FROM bucket_lifecycle WHERE bucket = ?"#)
             .bind(bucket)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -1232,21 +2098,26 @@ impl MetadataStore {
     ) -> Result<Vec<ObjectWithTags>> {
         let prefix = prefix.unwrap_or("");
 
-        let rows: Vec<(String, String, i64, String, i32, i32)> = sqlx::query_as(
+        let end = prefix_end(prefix);
+        let sql = format!(
             r#"
             SELECT key, version_id, size, last_modified, is_latest, is_delete_marker
             FROM objects
-            WHERE bucket = ? AND key LIKE ? AND is_delete_marker = 0
+            WHERE bucket = ? AND key >= ?{} AND is_delete_marker = 0
             ORDER BY key
             LIMIT ?
             "#,
-        )
-        .bind(bucket)
-        .bind(format!("{}%", prefix))
-        .bind(limit)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            below(&end)
+        );
+        let mut query = sqlx::query_as(&sql).bind(bucket).bind(prefix);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, i64, String, i32, i32)> = query
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         let mut objects = Vec::new();
         for row in rows {
@@ -1301,16 +2172,26 @@ impl MetadataStore {
         .bind(&now)
         .bind(policy_json)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        if let Some(cache) = &self.cache {
+            cache.policies.invalidate(&bucket.to_string());
+        }
         debug!("Stored bucket policy for: {}", bucket);
         Ok(())
     }
 
     /// Get bucket policy JSON
     pub async fn get_bucket_policy(&self, bucket: &str) -> Result<Option<String>> {
+        match &self.cache {
+            Some(cache) => cache.policies.get_or_load(bucket.to_string(), self.fetch_bucket_policy(bucket)).await,
+            None => self.fetch_bucket_policy(bucket).await,
+        }
+    }
+
+    async fn fetch_bucket_policy(&self, bucket: &str) -> Result<Option<String>> {
         let row: Option<(String,)> = sqlx::query_as(
             r#"SELECT policy_json FROM bucket_policies WHERE bucket = ?"#,
         )
@@ -1326,10 +2207,13 @@ impl MetadataStore {
     pub async fn delete_bucket_policy(&self, bucket: &str) -> Result<()> {
         sqlx::query(r#"DELETE FROM bucket_policies WHERE bucket = ?"#)
             .bind(bucket)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        if let Some(cache) = &self.cache {
+            cache.policies.invalidate(&bucket.to_string());
+        }
         debug!("Deleted bucket policy for: {}", bucket);
         Ok(())
     }
@@ -1350,7 +2234,7 @@ impl MetadataStore {
         .bind(&now)
         .bind(acl_xml)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -1396,7 +2280,7 @@ impl MetadataStore {
         .bind(&now)
         .bind(acl_xml)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -1442,7 +2326,7 @@ impl MetadataStore {
         .bind(&now)
         .bind(config_json)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
@@ -1481,16 +2365,26 @@ impl MetadataStore {
         .bind(&now)
         .bind(cors_xml)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        if let Some(cache) = &self.cache {
+            cache.cors.invalidate(&bucket.to_string());
+        }
         debug!("Stored bucket CORS config for: {}", bucket);
         Ok(())
     }
 
     /// Get bucket CORS configuration XML
     pub async fn get_bucket_cors(&self, bucket: &str) -> Result<Option<String>> {
+        match &self.cache {
+            Some(cache) => cache.cors.get_or_load(bucket.to_string(), self.fetch_bucket_cors(bucket)).await,
+            None => self.fetch_bucket_cors(bucket).await,
+        }
+    }
+
+    async fn fetch_bucket_cors(&self, bucket: &str) -> Result<Option<String>> {
         let row: Option<(String,)> = sqlx::query_as(
             r#"SELECT cors_xml FROM bucket_cors WHERE bucket = ?"#,
         )
@@ -1506,301 +2400,2327 @@ impl MetadataStore {
     pub async fn delete_bucket_cors(&self, bucket: &str) -> Result<()> {
         sqlx::query(r#"DELETE FROM bucket_cors WHERE bucket = ?"#)
             .bind(bucket)
-            .execute(&self.pool)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
+        if let Some(cache) = &self.cache {
+            cache.cors.invalidate(&bucket.to_string());
+        }
         debug!("Deleted bucket CORS config for: {}", bucket);
         Ok(())
     }
 
-    // ============= Object Lock Operations =============
+    // ============= Consistency Operations =============
 
-    /// Store bucket Object Lock configuration
-    pub async fn put_bucket_object_lock_config(&self, bucket: &str, config_xml: &str) -> Result<()> {
+    /// Store the bucket's cluster consistency level
+    pub async fn put_bucket_consistency(&self, bucket: &str, level: ConsistencyLevel) -> Result<()> {
         let now = Utc::now().to_rfc3339();
 
         sqlx::query(
             r#"
-            INSERT INTO bucket_object_lock (bucket, config_xml, updated_at)
+            INSERT INTO bucket_consistency (bucket, level, updated_at)
             VALUES (?, ?, ?)
-            ON CONFLICT(bucket) DO UPDATE SET config_xml = ?, updated_at = ?
+            ON CONFLICT(bucket) DO UPDATE SET level = ?, updated_at = ?
             "#,
         )
         .bind(bucket)
-        .bind(config_xml)
+        .bind(level.as_str())
         .bind(&now)
-        .bind(config_xml)
+        .bind(level.as_str())
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        debug!("Stored bucket Object Lock config for: {}", bucket);
+        debug!("Stored consistency level {} for: {}", level, bucket);
         Ok(())
     }
 
-    /// Get bucket Object Lock configuration
-    pub async fn get_bucket_object_lock_config(&self, bucket: &str) -> Result<Option<String>> {
+    /// Get the bucket's cluster consistency level, if one is set
+    pub async fn get_bucket_consistency(&self, bucket: &str) -> Result<Option<ConsistencyLevel>> {
         let row: Option<(String,)> = sqlx::query_as(
-            r#"SELECT config_xml FROM bucket_object_lock WHERE bucket = ?"#,
+            r#"SELECT level FROM bucket_consistency WHERE bucket = ?"#,
         )
         .bind(bucket)
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| r.0))
+        row.map(|r| r.0.parse().map_err(Error::InternalError))
+            .transpose()
     }
 
-    /// Store object retention
-    pub async fn put_object_retention(
-        &self,
-        bucket: &str,
-        key: &str,
-        version_id: Option<&str>,
-        retention_xml: &str,
-    ) -> Result<()> {
+    /// Delete the bucket's consistency level, reverting to the cluster default
+    pub async fn delete_bucket_consistency(&self, bucket: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bucket_consistency WHERE bucket = ?"#)
+            .bind(bucket)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted consistency level for: {}", bucket);
+        Ok(())
+    }
+
+    // ============= Transfer Acceleration Operations =============
+
+    /// Store the bucket's transfer acceleration status
+    pub async fn put_bucket_accelerate(&self, bucket: &str, status: AccelerateStatus) -> Result<()> {
         let now = Utc::now().to_rfc3339();
-        let vid = version_id.unwrap_or("");
 
         sqlx::query(
             r#"
-            INSERT INTO object_retention (bucket, key, version_id, retention_xml, updated_at)
-            VALUES (?, ?, ?, ?, ?)
-            ON CONFLICT(bucket, key, version_id) DO UPDATE SET retention_xml = ?, updated_at = ?
+            INSERT INTO bucket_accelerate (bucket, status, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(bucket) DO UPDATE SET status = ?, updated_at = ?
             "#,
         )
         .bind(bucket)
-        .bind(key)
-        .bind(vid)
-        .bind(retention_xml)
+        .bind(status.as_str())
         .bind(&now)
-        .bind(retention_xml)
+        .bind(status.as_str())
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        debug!("Stored object retention for: {}/{}", bucket, key);
+        debug!("Stored accelerate status {} for: {}", status.as_str(), bucket);
         Ok(())
     }
 
-    /// Get object retention
-    pub async fn get_object_retention(
-        &self,
-        bucket: &str,
-        key: &str,
-        version_id: Option<&str>,
-    ) -> Result<Option<String>> {
-        let vid = version_id.unwrap_or("");
-
+    /// Get the bucket's transfer acceleration status, if it was ever set
+    pub async fn get_bucket_accelerate(&self, bucket: &str) -> Result<Option<AccelerateStatus>> {
         let row: Option<(String,)> = sqlx::query_as(
-            r#"SELECT retention_xml FROM object_retention WHERE bucket = ? AND key = ? AND version_id = ?"#,
+            r#"SELECT status FROM bucket_accelerate WHERE bucket = ?"#,
         )
         .bind(bucket)
-        .bind(key)
-        .bind(vid)
         .fetch_optional(&self.pool)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| r.0))
+        Ok(row.and_then(|r| AccelerateStatus::parse(&r.0)))
     }
 
-    /// Store object legal hold
-    pub async fn put_object_legal_hold(
+    // ============= Storage Backend Operations =============
+
+    /// Store the name of the storage backend holding a bucket's data
+    pub async fn put_bucket_backend(&self, bucket: &str, backend: &str) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO bucket_storage_backend (bucket, backend, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(bucket) DO UPDATE SET backend = ?, updated_at = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(backend)
+        .bind(&now)
+        .bind(backend)
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored storage backend for {}: {}", bucket, backend);
+        Ok(())
+    }
+
+    /// All buckets with a storage backend set, as (bucket, backend)
+    pub async fn list_bucket_backends(&self) -> Result<Vec<(String, String)>> {
+        sqlx::query_as(r#"SELECT bucket, backend FROM bucket_storage_backend ORDER BY bucket"#)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))
+    }
+
+    /// Delete the bucket's storage backend, reverting to the default storage
+    pub async fn delete_bucket_backend(&self, bucket: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bucket_storage_backend WHERE bucket = ?"#)
+            .bind(bucket)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted storage backend for: {}", bucket);
+        Ok(())
+    }
+
+    // ============= Compression Operations =============
+
+    /// Store the bucket's compression setting
+    pub async fn put_bucket_compression(
         &self,
         bucket: &str,
-        key: &str,
-        version_id: Option<&str>,
-        hold_xml: &str,
+        compression: &BucketCompression,
     ) -> Result<()> {
         let now = Utc::now().to_rfc3339();
-        let vid = version_id.unwrap_or("");
 
         sqlx::query(
             r#"
-            INSERT INTO object_legal_hold (bucket, key, version_id, hold_xml, updated_at)
+            INSERT INTO bucket_compression (bucket, codec, level, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(bucket) DO UPDATE SET codec = ?, level = ?, updated_at = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(compression.codec.as_str())
+        .bind(compression.level)
+        .bind(&now)
+        .bind(compression.codec.as_str())
+        .bind(compression.level)
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored compression for {}: {}", bucket, compression.codec);
+        Ok(())
+    }
+
+    /// All buckets with a compression setting
+    pub async fn list_bucket_compression(&self) -> Result<Vec<(String, BucketCompression)>> {
+        let rows: Vec<(String, String, Option<i32>)> = sqlx::query_as(
+            r#"SELECT bucket, codec, level FROM bucket_compression ORDER BY bucket"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        rows.into_iter()
+            .map(|(bucket, codec, level)| {
+                let codec = codec.parse().map_err(Error::InternalError)?;
+                Ok((bucket, BucketCompression { codec, level }))
+            })
+            .collect()
+    }
+
+    /// Delete the bucket's compression setting; new writes are stored uncompressed
+    pub async fn delete_bucket_compression(&self, bucket: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bucket_compression WHERE bucket = ?"#)
+            .bind(bucket)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted compression for: {}", bucket);
+        Ok(())
+    }
+
+    // ============= Namespace Operations =============
+
+    /// Store the bucket's namespace mode
+    pub async fn put_bucket_namespace(&self, bucket: &str, mode: NamespaceMode) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO bucket_namespace (bucket, mode, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(bucket) DO UPDATE SET mode = ?, updated_at = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(mode.as_str())
+        .bind(&now)
+        .bind(mode.as_str())
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored namespace mode for {}: {}", bucket, mode);
+        Ok(())
+    }
+
+    /// Namespace mode of a bucket, hierarchical unless set
+    pub async fn get_bucket_namespace(&self, bucket: &str) -> Result<NamespaceMode> {
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"SELECT mode FROM bucket_namespace WHERE bucket = ?"#,
+        )
+        .bind(bucket)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        match row {
+            Some((mode,)) => mode.parse().map_err(Error::InternalError),
+            None => Ok(NamespaceMode::default()),
+        }
+    }
+
+    /// Delete the bucket's namespace mode, reverting to hierarchical
+    pub async fn delete_bucket_namespace(&self, bucket: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bucket_namespace WHERE bucket = ?"#)
+            .bind(bucket)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted namespace mode for: {}", bucket);
+        Ok(())
+    }
+
+    // ============= Mirror Checkpoints =============
+
+    /// Time up to which changes of `bucket` were mirrored by `job`
+    pub async fn get_mirror_checkpoint(&self, job: &str, bucket: &str) -> Result<Option<DateTime<Utc>>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"SELECT mirrored_until FROM mirror_checkpoints WHERE job = ? AND bucket = ?"#,
+        )
+        .bind(job)
+        .bind(bucket)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(parse_optional_timestamp(row.as_ref().map(|r| r.0.as_str())))
+    }
+
+    /// Record that changes of `bucket` up to `until` were mirrored by `job`
+    pub async fn put_mirror_checkpoint(&self, job: &str, bucket: &str, until: DateTime<Utc>) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO mirror_checkpoints (job, bucket, mirrored_until, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(job, bucket) DO UPDATE SET
+                mirrored_until = excluded.mirrored_until,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(job)
+        .bind(bucket)
+        .bind(until.to_rfc3339())
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Mirror job {} checkpoint for {} at {}", job, bucket, until);
+        Ok(())
+    }
+
+    // ============= Bandwidth Limits =============
+
+    /// Store the bandwidth limit of a bucket or access key
+    pub async fn put_bandwidth_limit(
+        &self,
+        scope: BandwidthScope,
+        name: &str,
+        limit: &BandwidthLimit,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO bandwidth_limits (scope, name, ingress_bytes_per_sec, egress_bytes_per_sec, updated_at)
             VALUES (?, ?, ?, ?, ?)
-            ON CONFLICT(bucket, key, version_id) DO UPDATE SET hold_xml = ?, updated_at = ?
+            ON CONFLICT(scope, name) DO UPDATE SET
+                ingress_bytes_per_sec = excluded.ingress_bytes_per_sec,
+                egress_bytes_per_sec = excluded.egress_bytes_per_sec,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(scope.as_str())
+        .bind(name)
+        .bind(limit.ingress_bytes_per_sec as i64)
+        .bind(limit.egress_bytes_per_sec as i64)
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored bandwidth limit for {} {}", scope, name);
+        Ok(())
+    }
+
+    /// All stored bandwidth limits
+    pub async fn list_bandwidth_limits(&self) -> Result<Vec<(BandwidthScope, String, BandwidthLimit)>> {
+        let rows: Vec<(String, String, i64, i64)> = sqlx::query_as(
+            r#"
+            SELECT scope, name, ingress_bytes_per_sec, egress_bytes_per_sec
+            FROM bandwidth_limits ORDER BY scope, name
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        rows.into_iter()
+            .map(|(scope, name, ingress, egress)| {
+                let scope = scope.parse().map_err(Error::InternalError)?;
+                let limit = BandwidthLimit {
+                    ingress_bytes_per_sec: ingress.max(0) as u64,
+                    egress_bytes_per_sec: egress.max(0) as u64,
+                };
+                Ok((scope, name, limit))
+            })
+            .collect()
+    }
+
+    /// Delete the bandwidth limit of a bucket or access key
+    pub async fn delete_bandwidth_limit(&self, scope: BandwidthScope, name: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bandwidth_limits WHERE scope = ? AND name = ?"#)
+            .bind(scope.as_str())
+            .bind(name)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted bandwidth limit for {} {}", scope, name);
+        Ok(())
+    }
+
+    // ============= Master Key Derivation =============
+
+    /// Settings the master key was derived from its passphrase with, and
+    /// the check value of the derived key
+    pub async fn get_master_key_kdf(&self) -> Result<Option<(KdfParams, String)>> {
+        let row: Option<(String, Vec<u8>, i64, i64, i64, String)> = sqlx::query_as(
+            r#"SELECT algorithm, salt, memory_kib, iterations, parallelism, key_check FROM master_key_kdf WHERE id = 1"#,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        row.map(|(algorithm, salt, memory_kib, iterations, parallelism, key_check)| {
+            let params = KdfParams {
+                algorithm: algorithm.parse().map_err(|e: EncryptionError| Error::InternalError(e.to_string()))?,
+                salt,
+                memory_kib: memory_kib as u32,
+                iterations: iterations as u32,
+                parallelism: parallelism as u32,
+            };
+            Ok((params, key_check))
+        })
+        .transpose()
+    }
+
+    /// Record the settings the master key is now derived with
+    pub async fn put_master_key_kdf(&self, params: &KdfParams, key_check: &str) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO master_key_kdf (id, algorithm, salt, memory_kib, iterations, parallelism, key_check, updated_at)
+            VALUES (1, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                algorithm = excluded.algorithm,
+                salt = excluded.salt,
+                memory_kib = excluded.memory_kib,
+                iterations = excluded.iterations,
+                parallelism = excluded.parallelism,
+                key_check = excluded.key_check,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(params.algorithm.as_str())
+        .bind(&params.salt)
+        .bind(params.memory_kib as i64)
+        .bind(params.iterations as i64)
+        .bind(params.parallelism as i64)
+        .bind(key_check)
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored master key derivation settings ({})", params.algorithm);
+        Ok(())
+    }
+
+    // ============= Data Key Re-wrapping =============
+
+    /// Object versions with a stored DEK, ordered by bucket, key and
+    /// version ID, after `after`
+    pub async fn list_wrapped_keys(
+        &self,
+        after: (&str, &str, &str),
+        limit: i32,
+    ) -> Result<Vec<WrappedKey>> {
+        let (bucket, key, version_id) = after;
+        let rows: Vec<(String, String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT bucket, key, version_id, encryption
+            FROM objects
+            WHERE (bucket > ? OR (bucket = ? AND (key > ? OR (key = ? AND version_id > ?))))
+              AND encryption LIKE '%"encrypted_dek":"%'
+            ORDER BY bucket, key, version_id
+            LIMIT ?
             "#,
         )
         .bind(bucket)
+        .bind(bucket)
         .bind(key)
-        .bind(vid)
-        .bind(hold_xml)
+        .bind(key)
+        .bind(version_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| WrappedKey {
+                bucket: r.0,
+                key: r.1,
+                version_id: r.2,
+                encryption: serde_json::from_str(&r.3).unwrap_or_default(),
+            })
+            .collect())
+    }
+
+    /// Replace the encryption info of an object version, unless its DEK is
+    /// no longer `previous_dek`; returns whether it was replaced
+    pub async fn replace_wrapped_key(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+        previous_dek: &str,
+        encryption: &EncryptionInfo,
+    ) -> Result<bool> {
+        let encryption_json = serde_json::to_string(encryption)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+        let result = sqlx::query(
+            r#"
+            UPDATE objects SET encryption = ?
+            WHERE bucket = ? AND key = ? AND version_id = ? AND instr(encryption, ?) > 0
+            "#,
+        )
+        .bind(&encryption_json)
+        .bind(bucket)
+        .bind(key)
+        .bind(version_id)
+        .bind(previous_dek)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if let Some(cache) = &self.cache {
+            cache.invalidate_object(bucket, key);
+        }
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Progress of the last DEK re-wrapping run, if any
+    pub async fn get_key_rewrap(&self) -> Result<Option<KeyRewrapRecord>> {
+        let row: Option<KeyRewrapRow> = sqlx::query_as(
+            r#"
+            SELECT from_key_check, to_key_check, state, after_bucket, after_key, after_version_id,
+                   objects_scanned, objects_rewrapped, objects_current, objects_failed,
+                   started_at, updated_at, finished_at, error
+            FROM key_rewrap WHERE id = 1
+            "#,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(KeyRewrapRecord::from_row))
+    }
+
+    /// Record the progress of a DEK re-wrapping run
+    pub async fn put_key_rewrap(&self, record: &KeyRewrapRecord) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO key_rewrap (id, from_key_check, to_key_check, state, after_bucket, after_key,
+                after_version_id, objects_scanned, objects_rewrapped, objects_current, objects_failed,
+                started_at, updated_at, finished_at, error)
+            VALUES (1, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET
+                from_key_check = excluded.from_key_check,
+                to_key_check = excluded.to_key_check,
+                state = excluded.state,
+                after_bucket = excluded.after_bucket,
+                after_key = excluded.after_key,
+                after_version_id = excluded.after_version_id,
+                objects_scanned = excluded.objects_scanned,
+                objects_rewrapped = excluded.objects_rewrapped,
+                objects_current = excluded.objects_current,
+                objects_failed = excluded.objects_failed,
+                started_at = excluded.started_at,
+                updated_at = excluded.updated_at,
+                finished_at = excluded.finished_at,
+                error = excluded.error
+            "#,
+        )
+        .bind(&record.from_key_check)
+        .bind(&record.to_key_check)
+        .bind(&record.state)
+        .bind(&record.after.0)
+        .bind(&record.after.1)
+        .bind(&record.after.2)
+        .bind(record.objects_scanned as i64)
+        .bind(record.objects_rewrapped as i64)
+        .bind(record.objects_current as i64)
+        .bind(record.objects_failed as i64)
+        .bind(record.started_at.to_rfc3339())
+        .bind(record.updated_at.to_rfc3339())
+        .bind(record.finished_at.map(|t| t.to_rfc3339()))
+        .bind(&record.error)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    // ============= Tiering Operations =============
+
+    /// Opt a bucket into access-based tiering
+    pub async fn put_bucket_tiering(&self, bucket: &str, tiering: &BucketTiering) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO bucket_tiering (bucket, archive_after_days, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(bucket) DO UPDATE SET archive_after_days = ?, updated_at = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(tiering.archive_after_days as i64)
         .bind(&now)
-        .bind(hold_xml)
+        .bind(tiering.archive_after_days as i64)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored tiering for {}: {} days", bucket, tiering.archive_after_days);
+        Ok(())
+    }
+
+    /// Tiering setting of a bucket, if it opted in
+    pub async fn get_bucket_tiering(&self, bucket: &str) -> Result<Option<BucketTiering>> {
+        let row: Option<(i64,)> = sqlx::query_as(
+            r#"SELECT archive_after_days FROM bucket_tiering WHERE bucket = ?"#,
+        )
+        .bind(bucket)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(|(days,)| BucketTiering {
+            archive_after_days: days as u32,
+        }))
+    }
+
+    /// All buckets that opted into tiering
+    pub async fn list_bucket_tiering(&self) -> Result<Vec<(String, BucketTiering)>> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            r#"SELECT bucket, archive_after_days FROM bucket_tiering ORDER BY bucket"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(bucket, days)| {
+                (
+                    bucket,
+                    BucketTiering {
+                        archive_after_days: days as u32,
+                    },
+                )
+            })
+            .collect())
+    }
+
+    /// Opt a bucket out of tiering; objects already archived stay there until read
+    pub async fn delete_bucket_tiering(&self, bucket: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bucket_tiering WHERE bucket = ?"#)
+            .bind(bucket)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted tiering for: {}", bucket);
+        Ok(())
+    }
+
+    /// Add sampled reads to the access statistics in one transaction.
+    /// Returns the accessed versions that tiering had archived.
+    pub async fn record_object_accesses(&self, accesses: &[ObjectAccess]) -> Result<Vec<ObjectAccess>> {
+        let mut tx = self
+            .writer
+            .begin()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let mut tiered = Vec::new();
+        for access in accesses {
+            let (is_tiered,): (i64,) = sqlx::query_as(
+                r#"
+                INSERT INTO object_access (bucket, key, version_id, last_accessed, access_count)
+                VALUES (?, ?, ?, ?, ?)
+                ON CONFLICT(bucket, key, version_id) DO UPDATE SET
+                    last_accessed = MAX(COALESCE(last_accessed, ''), excluded.last_accessed),
+                    access_count = access_count + excluded.access_count
+                RETURNING tiered
+                "#,
+            )
+            .bind(&access.bucket)
+            .bind(&access.key)
+            .bind(&access.version_id)
+            .bind(access.last_accessed.to_rfc3339())
+            .bind(access.count)
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+            if is_tiered != 0 {
+                tiered.push(access.clone());
+            }
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(tiered)
+    }
+
+    /// Mark an object version as moved to or back from the archive backend
+    pub async fn set_object_tiered(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+        tiered: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO object_access (bucket, key, version_id, tiered)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(bucket, key, version_id) DO UPDATE SET tiered = excluded.tiered
+            "#,
+        )
+        .bind(bucket)
+        .bind(key)
+        .bind(version_id)
+        .bind(tiered as i64)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Forget the access statistics of an object version, e.g. after its
+    /// data was rewritten
+    pub async fn delete_object_access(&self, bucket: &str, key: &str, version_id: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM object_access WHERE bucket = ? AND key = ? AND version_id = ?"#)
+            .bind(bucket)
+            .bind(key)
+            .bind(version_id)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Drop the access statistics of object versions that no longer exist
+    pub async fn prune_object_access(&self) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM object_access WHERE NOT EXISTS (
+                SELECT 1 FROM objects o
+                WHERE o.bucket = object_access.bucket
+                  AND o.key = object_access.key
+                  AND o.version_id = object_access.version_id
+            )
+            "#,
+        )
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// (key, version_id) of object versions in `bucket` neither read nor
+    /// written since `cutoff` and not archived, ordered by key and version
+    pub async fn list_cold_objects(
+        &self,
+        bucket: &str,
+        cutoff: DateTime<Utc>,
+        after: Option<(&str, &str)>,
+        limit: i32,
+    ) -> Result<Vec<(String, String)>> {
+        let (after_key, after_version) = after.unwrap_or(("", ""));
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT o.key, o.version_id FROM objects o
+            LEFT JOIN object_access a
+                ON a.bucket = o.bucket AND a.key = o.key AND a.version_id = o.version_id
+            WHERE o.bucket = ? AND o.is_delete_marker = 0
+              AND o.storage_class NOT IN (?, ?)
+              AND COALESCE(a.tiered, 0) = 0
+              AND COALESCE(a.last_accessed, o.last_modified) < ?
+              AND (o.key > ? OR (o.key = ? AND o.version_id > ?))
+            ORDER BY o.key, o.version_id
+            LIMIT ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(StorageClass::Archive.as_str())
+        .bind(StorageClass::DeepArchive.as_str())
+        .bind(cutoff.to_rfc3339())
+        .bind(after_key)
+        .bind(after_key)
+        .bind(after_version)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows)
+    }
+
+    /// Access statistics of a bucket
+    pub async fn tiering_stats(&self, bucket: &str) -> Result<TieringStats> {
+        let (tracked_objects, tiered_objects, tiered_bytes): (i64, i64, i64) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*), COALESCE(SUM(a.tiered), 0),
+                   COALESCE(SUM(CASE WHEN a.tiered = 1 THEN o.size ELSE 0 END), 0)
+            FROM object_access a
+            JOIN objects o
+                ON o.bucket = a.bucket AND o.key = a.key AND o.version_id = a.version_id
+            WHERE a.bucket = ?
+            "#,
+        )
+        .bind(bucket)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(TieringStats {
+            tracked_objects,
+            tiered_objects,
+            tiered_bytes,
+        })
+    }
+
+    // ============= Access Point Operations =============
+
+    /// Create an access point; its name must not be taken
+    pub async fn create_access_point(&self, access_point: &AccessPoint) -> Result<()> {
+        let cidrs = serde_json::to_string(&access_point.allowed_cidrs)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO access_points (name, bucket, policy, allowed_cidrs, created_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(name) DO NOTHING
+            "#,
+        )
+        .bind(&access_point.name)
+        .bind(&access_point.bucket)
+        .bind(&access_point.policy)
+        .bind(&cidrs)
+        .bind(access_point.created_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::InvalidRequest(format!(
+                "Access point {} already exists",
+                access_point.name
+            )));
+        }
+        if let Some(cache) = &self.cache {
+            cache.access_points.invalidate(&access_point.name);
+        }
+        debug!("Created access point {} for bucket {}", access_point.name, access_point.bucket);
+        Ok(())
+    }
+
+    pub async fn get_access_point(&self, name: &str) -> Result<Option<AccessPoint>> {
+        match &self.cache {
+            Some(cache) => cache.access_points.get_or_load(name.to_string(), self.fetch_access_point(name)).await,
+            None => self.fetch_access_point(name).await,
+        }
+    }
+
+    async fn fetch_access_point(&self, name: &str) -> Result<Option<AccessPoint>> {
+        let row: Option<AccessPointRow> = sqlx::query_as(
+            r#"SELECT name, bucket, policy, allowed_cidrs, created_at FROM access_points WHERE name = ?"#,
+        )
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(access_point_from_row))
+    }
+
+    /// Access points, of one bucket or of all
+    pub async fn list_access_points(&self, bucket: Option<&str>) -> Result<Vec<AccessPoint>> {
+        let rows: Vec<AccessPointRow> = sqlx::query_as(
+            r#"
+            SELECT name, bucket, policy, allowed_cidrs, created_at FROM access_points
+            WHERE ? IS NULL OR bucket = ?
+            ORDER BY name
+            "#,
+        )
+        .bind(bucket)
+        .bind(bucket)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(access_point_from_row).collect())
+    }
+
+    /// Replace the access point's policy; `None` removes it
+    pub async fn put_access_point_policy(&self, name: &str, policy: Option<&str>) -> Result<()> {
+        let result = sqlx::query(r#"UPDATE access_points SET policy = ? WHERE name = ?"#)
+            .bind(policy)
+            .bind(name)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NoSuchAccessPoint(name.to_string()));
+        }
+        if let Some(cache) = &self.cache {
+            cache.access_points.invalidate(&name.to_string());
+        }
+        debug!("Stored policy for access point: {}", name);
+        Ok(())
+    }
+
+    pub async fn delete_access_point(&self, name: &str) -> Result<()> {
+        let result = sqlx::query(r#"DELETE FROM access_points WHERE name = ?"#)
+            .bind(name)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NoSuchAccessPoint(name.to_string()));
+        }
+        if let Some(cache) = &self.cache {
+            cache.access_points.invalidate(&name.to_string());
+        }
+        debug!("Deleted access point: {}", name);
+        Ok(())
+    }
+
+    // ============= Object Lock Operations =============
+
+    /// Store bucket Object Lock configuration
+    pub async fn put_bucket_object_lock_config(&self, bucket: &str, config_xml: &str) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+
+        sqlx::query(
+            r#"
+            INSERT INTO bucket_object_lock (bucket, config_xml, updated_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(bucket) DO UPDATE SET config_xml = ?, updated_at = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(config_xml)
+        .bind(&now)
+        .bind(config_xml)
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored bucket Object Lock config for: {}", bucket);
+        Ok(())
+    }
+
+    /// Get bucket Object Lock configuration
+    pub async fn get_bucket_object_lock_config(&self, bucket: &str) -> Result<Option<String>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"SELECT config_xml FROM bucket_object_lock WHERE bucket = ?"#,
+        )
+        .bind(bucket)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(|r| r.0))
+    }
+
+    /// Store object retention
+    pub async fn put_object_retention(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+        retention_xml: &str,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let vid = version_id.unwrap_or("");
+
+        sqlx::query(
+            r#"
+            INSERT INTO object_retention (bucket, key, version_id, retention_xml, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(bucket, key, version_id) DO UPDATE SET retention_xml = ?, updated_at = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(key)
+        .bind(vid)
+        .bind(retention_xml)
+        .bind(&now)
+        .bind(retention_xml)
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored object retention for: {}/{}", bucket, key);
+        Ok(())
+    }
+
+    /// Get object retention
+    pub async fn get_object_retention(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> Result<Option<String>> {
+        let vid = version_id.unwrap_or("");
+
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"SELECT retention_xml FROM object_retention WHERE bucket = ? AND key = ? AND version_id = ?"#,
+        )
+        .bind(bucket)
+        .bind(key)
+        .bind(vid)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(|r| r.0))
+    }
+
+    /// Store object legal hold
+    pub async fn put_object_legal_hold(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+        hold_xml: &str,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let vid = version_id.unwrap_or("");
+
+        sqlx::query(
+            r#"
+            INSERT INTO object_legal_hold (bucket, key, version_id, hold_xml, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(bucket, key, version_id) DO UPDATE SET hold_xml = ?, updated_at = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(key)
+        .bind(vid)
+        .bind(hold_xml)
+        .bind(&now)
+        .bind(hold_xml)
+        .bind(&now)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored object legal hold for: {}/{}", bucket, key);
+        Ok(())
+    }
+
+    /// Get object legal hold
+    pub async fn get_object_legal_hold(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> Result<Option<String>> {
+        let vid = version_id.unwrap_or("");
+
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"SELECT hold_xml FROM object_legal_hold WHERE bucket = ? AND key = ? AND version_id = ?"#,
+        )
+        .bind(bucket)
+        .bind(key)
+        .bind(vid)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(|r| r.0))
+    }
+
+    /// List the retention records of a bucket, ordered by key and version
+    ///
+    /// Pages by keyset: `after` is the (key, version ID) of the last record
+    /// of the previous page.
+    pub async fn list_object_retentions(
+        &self,
+        bucket: &str,
+        prefix: Option<&str>,
+        after: Option<(&str, &str)>,
+        limit: i32,
+    ) -> Result<Vec<RetentionRecord>> {
+        let (key_marker, version_marker) = after.unwrap_or(("", ""));
+        let prefix = prefix.unwrap_or("");
+        let end = prefix_end(prefix);
+        let rows: Vec<(String, String, String, String)> = sqlx::query_as(
+            r#"
+            SELECT key, version_id, retention_xml, updated_at
+            FROM object_retention
+            WHERE bucket = ? AND key >= ? AND (? IS NULL OR key < ?)
+              AND (key > ? OR (key = ? AND version_id > ?))
+            ORDER BY key, version_id
+            LIMIT ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(prefix)
+        .bind(&end)
+        .bind(&end)
+        .bind(key_marker)
+        .bind(key_marker)
+        .bind(version_marker)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| RetentionRecord {
+                key: r.0,
+                version_id: r.1,
+                retention_xml: r.2,
+                updated_at: r.3,
+            })
+            .collect())
+    }
+
+    /// Record a delete or overwrite that bypassed GOVERNANCE retention
+    pub async fn record_governance_bypass(&self, entry: &GovernanceBypassEntry) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO governance_bypass_log
+                (bucket, key, version_id, principal, request_id, retain_until, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&entry.bucket)
+        .bind(&entry.key)
+        .bind(&entry.version_id)
+        .bind(&entry.principal)
+        .bind(&entry.request_id)
+        .bind(&entry.retain_until)
+        .bind(entry.created_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// List recorded governance bypasses, newest first
+    ///
+    /// `before` is the ID of the last entry of the previous page.
+    pub async fn list_governance_bypasses(
+        &self,
+        bucket: Option<&str>,
+        before: Option<i64>,
+        limit: i32,
+    ) -> Result<Vec<GovernanceBypassEntry>> {
+        let rows: Vec<GovernanceBypassRow> = sqlx::query_as(
+            r#"
+            SELECT id, bucket, key, version_id, principal, request_id, retain_until, created_at
+            FROM governance_bypass_log
+            WHERE (? IS NULL OR bucket = ?) AND (? IS NULL OR id < ?)
+            ORDER BY id DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(bucket)
+        .bind(before)
+        .bind(before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| GovernanceBypassEntry {
+                id: r.0,
+                bucket: r.1,
+                key: r.2,
+                version_id: r.3,
+                principal: r.4,
+                request_id: r.5,
+                retain_until: r.6,
+                created_at: DateTime::parse_from_rfc3339(&r.7)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .unwrap_or_else(|_| Utc::now()),
+            })
+            .collect())
+    }
+
+    /// Record that a restore of an archived object version was requested
+    pub async fn start_object_restore(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+        days: i32,
+    ) -> Result<ObjectRestore> {
+        let restore = ObjectRestore {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id: version_id.to_string(),
+            days,
+            requested_at: Utc::now(),
+            completed_at: None,
+            expires_at: None,
+        };
+        sqlx::query(
+            r#"
+            INSERT INTO object_restores (bucket, key, version_id, days, requested_at, completed_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, NULL, NULL)
+            ON CONFLICT(bucket, key, version_id) DO UPDATE SET
+                days = excluded.days,
+                requested_at = excluded.requested_at,
+                completed_at = NULL,
+                expires_at = NULL
+            "#,
+        )
+        .bind(bucket)
+        .bind(key)
+        .bind(version_id)
+        .bind(days)
+        .bind(restore.requested_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(restore)
+    }
+
+    /// Mark a restore finished; the restored copy is kept until `expires_at`
+    pub async fn complete_object_restore(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+        days: i32,
+        expires_at: DateTime<Utc>,
+    ) -> Result<()> {
+        sqlx::query(
+            r#"
+            UPDATE object_restores SET days = ?, completed_at = COALESCE(completed_at, ?), expires_at = ?
+            WHERE bucket = ? AND key = ? AND version_id = ?
+            "#,
+        )
+        .bind(days)
+        .bind(Utc::now().to_rfc3339())
+        .bind(expires_at.to_rfc3339())
+        .bind(bucket)
+        .bind(key)
+        .bind(version_id)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Restore of an object version, if one was requested
+    pub async fn get_object_restore(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: &str,
+    ) -> Result<Option<ObjectRestore>> {
+        let row: Option<ObjectRestoreRow> = sqlx::query_as(
+            r#"
+            SELECT bucket, key, version_id, days, requested_at, completed_at, expires_at
+            FROM object_restores WHERE bucket = ? AND key = ? AND version_id = ?
+            "#,
+        )
+        .bind(bucket)
+        .bind(key)
+        .bind(version_id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(ObjectRestore::from_row))
+    }
+
+    /// Restores still copying data, oldest first
+    pub async fn list_pending_restores(&self) -> Result<Vec<ObjectRestore>> {
+        let rows: Vec<ObjectRestoreRow> = sqlx::query_as(
+            r#"
+            SELECT bucket, key, version_id, days, requested_at, completed_at, expires_at
+            FROM object_restores WHERE completed_at IS NULL
+            ORDER BY requested_at
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(ObjectRestore::from_row).collect())
+    }
+
+    /// Finished restores whose copies expired by `now`
+    pub async fn list_expired_restores(&self, now: DateTime<Utc>) -> Result<Vec<ObjectRestore>> {
+        let rows: Vec<ObjectRestoreRow> = sqlx::query_as(
+            r#"
+            SELECT bucket, key, version_id, days, requested_at, completed_at, expires_at
+            FROM object_restores WHERE expires_at IS NOT NULL AND expires_at <= ?
+            "#,
+        )
+        .bind(now.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(ObjectRestore::from_row).collect())
+    }
+
+    /// Forget the restore of an object version
+    pub async fn delete_object_restore(&self, bucket: &str, key: &str, version_id: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM object_restores WHERE bucket = ? AND key = ? AND version_id = ?"#)
+            .bind(bucket)
+            .bind(key)
+            .bind(version_id)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+// ============= Object Rows =============
+
+/// bucket, key, version_id, size, etag, content_type, metadata, last_modified,
+/// is_latest, is_delete_marker, encryption, part_sizes, storage_class
+type ObjectRow = (
+    String,
+    String,
+    String,
+    i64,
+    String,
+    String,
+    Option<String>,
+    String,
+    i32,
+    i32,
+    Option<String>,
+    Option<String>,
+    String,
+);
+
+fn object_from_row(r: ObjectRow) -> Object {
+    let metadata: HashMap<String, String> = r
+        .6
+        .and_then(|m| serde_json::from_str(&m).ok())
+        .unwrap_or_default();
+
+    let encryption: EncryptionInfo = r
+        .10
+        .and_then(|e| serde_json::from_str(&e).ok())
+        .unwrap_or_default();
+
+    Object {
+        bucket: r.0,
+        key: r.1,
+        version_id: r.2,
+        size: r.3,
+        etag: r.4,
+        content_type: r.5,
+        metadata,
+        last_modified: DateTime::parse_from_rfc3339(&r.7)
+            .unwrap()
+            .with_timezone(&Utc),
+        is_latest: r.8 != 0,
+        is_delete_marker: r.9 != 0,
+        encryption,
+        part_sizes: r
+            .11
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or_default(),
+        storage_class: r.12,
+    }
+}
+
+/// bucket, key, version_id, days, requested_at, completed_at, expires_at
+type ObjectRestoreRow = (String, String, String, i32, String, Option<String>, Option<String>);
+
+/// from_key_check, to_key_check, state, after_bucket, after_key,
+/// after_version_id, objects_scanned, objects_rewrapped, objects_current,
+/// objects_failed, started_at, updated_at, finished_at, error
+type KeyRewrapRow = (
+    String,
+    String,
+    String,
+    String,
+    String,
+    String,
+    i64,
+    i64,
+    i64,
+    i64,
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+);
+
+// ============= Object Lock Audit Rows =============
+
+/// id, bucket, key, version_id, principal, request_id, retain_until, created_at
+type GovernanceBypassRow = (i64, String, String, String, String, String, Option<String>, String);
+
+// ============= Access Point Rows =============
+
+/// name, bucket, policy, allowed_cidrs (JSON), created_at
+type AccessPointRow = (String, String, Option<String>, String, String);
+
+fn access_point_from_row(r: AccessPointRow) -> AccessPoint {
+    AccessPoint {
+        name: r.0,
+        bucket: r.1,
+        policy: r.2,
+        allowed_cidrs: serde_json::from_str(&r.3).unwrap_or_default(),
+        created_at: DateTime::parse_from_rfc3339(&r.4)
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|_| Utc::now()),
+    }
+}
+
+// ============= Credentials Operations for Admin API =============
+
+/// access_key, secret_key, display_name, email, is_admin, created_at, enabled, last_used
+type CredentialsRow = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    bool,
+    String,
+    bool,
+    Option<String>,
+    String,
+);
+
+fn bucket_info_from_row((name, created_at, region): (String, String, String)) -> BucketInfo {
+    BucketInfo {
+        name,
+        creation_date: DateTime::parse_from_rfc3339(&created_at)
+            .unwrap()
+            .with_timezone(&Utc),
+        region,
+    }
+}
+
+type AccountRow = (String, String, String, i64, i64, i64, i64, String);
+
+fn account_from_row(r: AccountRow) -> Account {
+    Account {
+        id: r.0,
+        name: r.1,
+        status: AccountStatus::parse(&r.2),
+        quota: AccountQuota {
+            max_buckets: r.3.max(0) as u64,
+            max_objects: r.4.max(0) as u64,
+            max_bytes: r.5.max(0) as u64,
+        },
+        key_generation: r.6.max(1) as u32,
+        created_at: parse_optional_timestamp(Some(&r.7)).unwrap_or_else(Utc::now),
+    }
+}
+
+fn account_usage_from_row((buckets, objects, bytes): (i64, i64, i64)) -> AccountUsage {
+    AccountUsage {
+        buckets: buckets.max(0) as u64,
+        objects: objects.max(0) as u64,
+        bytes: bytes.max(0) as u64,
+    }
+}
+
+fn parse_optional_timestamp(value: Option<&str>) -> Option<DateTime<Utc>> {
+    value
+        .and_then(|v| DateTime::parse_from_rfc3339(v).ok())
+        .map(|d| d.with_timezone(&Utc))
+}
+
+use hafiz_core::types::{
+    Account, AccountQuota, AccountStatus, AccountUsage, AdminRole, AdminRoleGrant, Credentials,
+    DirectoryUser, RetiringKey, SftpKey, SftpUser, TemporaryCredentials,
+};
+
+impl MetadataStore {
+    /// List all credentials (users)
+    pub async fn list_credentials(&self) -> Result<Vec<Credentials>> {
+        let rows: Vec<CredentialsRow> =
+            sqlx::query_as(
+                r#"
+                SELECT access_key, secret_key, display_name, email, is_admin, created_at, enabled, last_used, account_id
+                FROM users
+                ORDER BY created_at DESC
+                "#,
+            )
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        rows.into_iter()
+            .map(|r| self.credentials_from_row(r))
+            .collect()
+    }
+
+    /// Get credentials by access key
+    #[instrument(name = "metadata.get_credentials", skip(self))]
+    pub async fn get_credentials(&self, access_key: &str) -> Result<Option<Credentials>> {
+        let row: Option<CredentialsRow> =
+            sqlx::query_as(
+                r#"
+                SELECT access_key, secret_key, display_name, email, is_admin, created_at, enabled, last_used, account_id
+                FROM users WHERE access_key = ?
+                "#,
+            )
+            .bind(access_key)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        row.map(|r| self.credentials_from_row(r)).transpose()
+    }
+
+    fn credentials_from_row(&self, r: CredentialsRow) -> Result<Credentials> {
+        Ok(Credentials {
+            secret_key: self.open_secret(&r.0, r.1)?,
+            access_key: r.0,
+            name: r.2,
+            email: r.3,
+            enabled: r.6,
+            created_at: DateTime::parse_from_rfc3339(&r.5)
+                .unwrap()
+                .with_timezone(&Utc),
+            last_used: parse_optional_timestamp(r.7.as_deref()),
+            policies: if r.4 {
+                vec!["admin".to_string()]
+            } else {
+                Vec::new()
+            },
+            account_id: r.8,
+        })
+    }
+
+    /// Create new credentials
+    pub async fn create_credentials(&self, cred: &Credentials) -> Result<()> {
+        let id = uuid::Uuid::new_v4().to_string();
+        let is_admin = cred.policies.contains(&"admin".to_string());
+
+        sqlx::query(
+            r#"
+            INSERT INTO users (id, access_key, secret_key, display_name, email, is_admin, created_at, enabled, account_id)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(&cred.access_key)
+        .bind(self.seal_secret(&cred.access_key, &cred.secret_key)?)
+        .bind(&cred.name)
+        .bind(&cred.email)
+        .bind(is_admin)
+        .bind(cred.created_at.to_rfc3339())
+        .bind(cred.enabled)
+        .bind(&cred.account_id)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("UNIQUE constraint") {
+                Error::InternalError("User with this access key already exists".to_string())
+            } else {
+                Error::DatabaseError(e.to_string())
+            }
+        })?;
+
+        debug!("Created credentials for: {}", cred.name.as_deref().unwrap_or(&cred.access_key));
+        Ok(())
+    }
+
+    /// Update existing credentials
+    pub async fn update_credentials(&self, cred: &Credentials) -> Result<()> {
+        let is_admin = cred.policies.contains(&"admin".to_string());
+
+        sqlx::query(
+            r#"
+            UPDATE users
+            SET display_name = ?, email = ?, is_admin = ?, enabled = ?
+            WHERE access_key = ?
+            "#,
+        )
+        .bind(&cred.name)
+        .bind(&cred.email)
+        .bind(is_admin)
+        .bind(cred.enabled)
+        .bind(&cred.access_key)
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Updated credentials for: {}", cred.access_key);
+        Ok(())
+    }
+
+    /// Delete credentials
+    pub async fn delete_credentials(&self, access_key: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM users WHERE access_key = ?"#)
+            .bind(access_key)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(r#"DELETE FROM retiring_keys WHERE user_access_key = ?"#)
+            .bind(access_key)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        for stmt in [
+            "DELETE FROM sftp_keys WHERE username IN (SELECT username FROM sftp_users WHERE access_key = ?)",
+            "DELETE FROM sftp_users WHERE access_key = ?",
+            "DELETE FROM admin_roles WHERE access_key = ?",
+        ] {
+            sqlx::query(stmt)
+                .bind(access_key)
+                .execute(&self.writer)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        }
+
+        debug!("Deleted credentials for: {}", access_key);
+        Ok(())
+    }
+
+    /// Give a user a new key pair, keeping the old one valid until
+    /// `grace_until` (or dropping it at once when `None`)
+    pub async fn rotate_user_keys(
+        &self,
+        access_key: &str,
+        new_access_key: &str,
+        new_secret_key: &str,
+        grace_until: Option<DateTime<Utc>>,
+    ) -> Result<()> {
+        let now = Utc::now().to_rfc3339();
+        let mut tx = self
+            .writer
+            .begin()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        if let Some(expires_at) = grace_until {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO retiring_keys (access_key, secret_key, user_access_key, created_at, expires_at, last_used)
+                SELECT access_key, secret_key, ?, COALESCE(key_created_at, created_at), ?, last_used
+                FROM users WHERE access_key = ?
+                "#,
+            )
+            .bind(new_access_key)
+            .bind(sortable_timestamp(expires_at))
+            .bind(access_key)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            if result.rows_affected() == 0 {
+                return Err(Error::InvalidAccessKeyId);
+            }
+        }
+
+        let result = sqlx::query(
+            r#"
+            UPDATE users
+            SET access_key = ?, secret_key = ?, key_created_at = ?, last_used = NULL
+            WHERE access_key = ?
+            "#,
+        )
+        .bind(new_access_key)
+        .bind(self.seal_secret(new_access_key, new_secret_key)?)
+        .bind(&now)
+        .bind(access_key)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        if result.rows_affected() == 0 {
+            return Err(Error::InvalidAccessKeyId);
+        }
+
+        // Earlier retiring keys, directory links, SFTP logins and admin roles
+        // follow the user
+        for stmt in [
+            "UPDATE retiring_keys SET user_access_key = ? WHERE user_access_key = ?",
+            "UPDATE ldap_users SET access_key = ? WHERE access_key = ?",
+            "UPDATE sftp_users SET access_key = ? WHERE access_key = ?",
+            "UPDATE admin_roles SET access_key = ? WHERE access_key = ?",
+        ] {
+            sqlx::query(stmt)
+                .bind(new_access_key)
+                .bind(access_key)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Rotated keys of {} to {}", access_key, new_access_key);
+        Ok(())
+    }
+
+    /// When the user's current key pair was issued
+    pub async fn get_key_created_at(&self, access_key: &str) -> Result<Option<DateTime<Utc>>> {
+        let row: Option<(String,)> = sqlx::query_as(
+            r#"SELECT COALESCE(key_created_at, created_at) FROM users WHERE access_key = ?"#,
+        )
+        .bind(access_key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.and_then(|r| parse_optional_timestamp(Some(&r.0))))
+    }
+
+    /// Unexpired retiring keys of a user
+    pub async fn list_retiring_keys(&self, user_access_key: &str) -> Result<Vec<RetiringKey>> {
+        let rows: Vec<RetiringKeyRow> = sqlx::query_as(
+            r#"
+            SELECT access_key, secret_key, user_access_key, created_at, expires_at, last_used
+            FROM retiring_keys WHERE user_access_key = ? AND expires_at > ?
+            ORDER BY created_at
+            "#,
+        )
+        .bind(user_access_key)
+        .bind(sortable_timestamp(Utc::now()))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        rows.into_iter().map(|r| self.retiring_key_from_row(r)).collect()
+    }
+
+    /// Get an unexpired retiring key
+    pub async fn get_retiring_key(&self, access_key: &str) -> Result<Option<RetiringKey>> {
+        let row: Option<RetiringKeyRow> = sqlx::query_as(
+            r#"
+            SELECT access_key, secret_key, user_access_key, created_at, expires_at, last_used
+            FROM retiring_keys WHERE access_key = ? AND expires_at > ?
+            "#,
+        )
+        .bind(access_key)
+        .bind(sortable_timestamp(Utc::now()))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        row.map(|r| self.retiring_key_from_row(r)).transpose()
+    }
+
+    /// Stop accepting a retiring key before its grace period ends
+    pub async fn delete_retiring_key(&self, access_key: &str) -> Result<bool> {
+        let result = sqlx::query(r#"DELETE FROM retiring_keys WHERE access_key = ?"#)
+            .bind(access_key)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove retiring keys past their grace period
+    pub async fn purge_expired_retiring_keys(&self) -> Result<u64> {
+        let result = sqlx::query(r#"DELETE FROM retiring_keys WHERE expires_at <= ?"#)
+            .bind(sortable_timestamp(Utc::now()))
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Record that a key was used
+    pub async fn touch_access_key(&self, access_key: &str, at: DateTime<Utc>) -> Result<()> {
+        for stmt in [
+            "UPDATE users SET last_used = ? WHERE access_key = ?",
+            "UPDATE retiring_keys SET last_used = ? WHERE access_key = ?",
+        ] {
+            sqlx::query(stmt)
+                .bind(at.to_rfc3339())
+                .bind(access_key)
+                .execute(&self.writer)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Users provisioned from the LDAP directory
+    pub async fn list_directory_users(&self) -> Result<Vec<DirectoryUser>> {
+        let rows: Vec<(String, String, String, String, String, String)> = sqlx::query_as(
+            r#"SELECT username, access_key, dn, groups, policies, synced_at FROM ldap_users"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| DirectoryUser {
+                username: r.0,
+                access_key: r.1,
+                dn: r.2,
+                groups: serde_json::from_str(&r.3).unwrap_or_default(),
+                policies: serde_json::from_str(&r.4).unwrap_or_default(),
+                synced_at: DateTime::parse_from_rfc3339(&r.5)
+                    .unwrap()
+                    .with_timezone(&Utc),
+            })
+            .collect())
+    }
+
+    /// Record the Hafiz user provisioned for a directory account
+    pub async fn put_directory_user(&self, user: &DirectoryUser) -> Result<()> {
+        let groups = serde_json::to_string(&user.groups)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+        let policies = serde_json::to_string(&user.policies)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO ldap_users (username, access_key, dn, groups, policies, synced_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&user.username)
+        .bind(&user.access_key)
+        .bind(&user.dn)
+        .bind(groups)
+        .bind(policies)
+        .bind(user.synced_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(())
+    }
+
+    /// Create an SFTP login, or change the user and home of an existing one
+    ///
+    /// Keys are managed separately; `user.keys` is ignored.
+    pub async fn put_sftp_user(&self, user: &SftpUser) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO sftp_users (username, access_key, home, created_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(username) DO UPDATE SET
+                access_key = excluded.access_key,
+                home = excluded.home
+            "#,
+        )
+        .bind(&user.username)
+        .bind(&user.access_key)
+        .bind(&user.home)
+        .bind(user.created_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Stored SFTP login {} of {}", user.username, user.access_key);
+        Ok(())
+    }
+
+    /// Get an SFTP login with its keys
+    pub async fn get_sftp_user(&self, username: &str) -> Result<Option<SftpUser>> {
+        let row: Option<(String, String, String, String)> = sqlx::query_as(
+            r#"SELECT username, access_key, home, created_at FROM sftp_users WHERE username = ?"#,
+        )
+        .bind(username)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let mut keys = self.list_sftp_keys(Some(username)).await?;
+        Ok(Some(sftp_user_from_row(row, keys.remove(username).unwrap_or_default())))
+    }
+
+    /// All SFTP logins with their keys
+    pub async fn list_sftp_users(&self) -> Result<Vec<SftpUser>> {
+        let rows: Vec<(String, String, String, String)> = sqlx::query_as(
+            r#"SELECT username, access_key, home, created_at FROM sftp_users ORDER BY username"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let mut keys = self.list_sftp_keys(None).await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let user_keys = keys.remove(&row.0).unwrap_or_default();
+                sftp_user_from_row(row, user_keys)
+            })
+            .collect())
+    }
+
+    /// Keys of one SFTP login, or of all, by user name
+    async fn list_sftp_keys(&self, username: Option<&str>) -> Result<HashMap<String, Vec<SftpKey>>> {
+        let rows: Vec<(String, String, String, Option<String>, String)> = sqlx::query_as(
+            r#"
+            SELECT username, fingerprint, public_key, comment, created_at
+            FROM sftp_keys WHERE ? IS NULL OR username = ?
+            ORDER BY created_at
+            "#,
+        )
+        .bind(username)
+        .bind(username)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let mut keys: HashMap<String, Vec<SftpKey>> = HashMap::new();
+        for (username, fingerprint, public_key, comment, created_at) in rows {
+            keys.entry(username).or_default().push(SftpKey {
+                fingerprint,
+                public_key,
+                comment,
+                created_at: parse_optional_timestamp(Some(&created_at)).unwrap_or_else(Utc::now),
+            });
+        }
+        Ok(keys)
+    }
+
+    /// Delete an SFTP login and its keys; returns whether it existed
+    pub async fn delete_sftp_user(&self, username: &str) -> Result<bool> {
+        sqlx::query(r#"DELETE FROM sftp_keys WHERE username = ?"#)
+            .bind(username)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let result = sqlx::query(r#"DELETE FROM sftp_users WHERE username = ?"#)
+            .bind(username)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted SFTP login {}", username);
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Let an SFTP login authenticate with `key`; adding a key twice
+    /// replaces its comment
+    pub async fn add_sftp_key(&self, username: &str, key: &SftpKey) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO sftp_keys (username, fingerprint, public_key, comment, created_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(username, fingerprint) DO UPDATE SET comment = excluded.comment
+            "#,
+        )
+        .bind(username)
+        .bind(&key.fingerprint)
+        .bind(&key.public_key)
+        .bind(&key.comment)
+        .bind(key.created_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Added key {} to SFTP login {}", key.fingerprint, username);
+        Ok(())
+    }
+
+    /// Remove a key of an SFTP login; returns whether it existed
+    pub async fn delete_sftp_key(&self, username: &str, fingerprint: &str) -> Result<bool> {
+        let result = sqlx::query(r#"DELETE FROM sftp_keys WHERE username = ? AND fingerprint = ?"#)
+            .bind(username)
+            .bind(fingerprint)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Give a user an admin role, replacing the one it had
+    pub async fn put_admin_role(&self, grant: &AdminRoleGrant) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO admin_roles (access_key, role, granted_at)
+            VALUES (?, ?, ?)
+            ON CONFLICT(access_key) DO UPDATE SET
+                role = excluded.role,
+                granted_at = excluded.granted_at
+            "#,
+        )
+        .bind(&grant.access_key)
+        .bind(grant.role.as_str())
+        .bind(grant.granted_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Gave {} the admin role {}", grant.access_key, grant.role);
+        Ok(())
+    }
+
+    /// Admin role of a user, if it has one
+    pub async fn get_admin_role(&self, access_key: &str) -> Result<Option<AdminRole>> {
+        let row: Option<(String,)> =
+            sqlx::query_as(r#"SELECT role FROM admin_roles WHERE access_key = ?"#)
+                .bind(access_key)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        row.map(|(role,)| role.parse().map_err(Error::InternalError))
+            .transpose()
+    }
+
+    /// All admin role grants
+    pub async fn list_admin_roles(&self) -> Result<Vec<AdminRoleGrant>> {
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            r#"SELECT access_key, role, granted_at FROM admin_roles ORDER BY access_key"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        rows.into_iter()
+            .map(|(access_key, role, granted_at)| {
+                Ok(AdminRoleGrant {
+                    access_key,
+                    role: role.parse().map_err(Error::InternalError)?,
+                    granted_at: parse_optional_timestamp(Some(&granted_at)).unwrap_or_else(Utc::now),
+                })
+            })
+            .collect()
+    }
+
+    /// Take a user's admin role away; returns whether it had one
+    pub async fn delete_admin_role(&self, access_key: &str) -> Result<bool> {
+        let result = sqlx::query(r#"DELETE FROM admin_roles WHERE access_key = ?"#)
+            .bind(access_key)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Removed the admin role of {}", access_key);
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Create an account
+    pub async fn create_account(&self, account: &Account) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO accounts (id, name, status, max_buckets, max_objects, max_bytes, key_generation, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&account.id)
+        .bind(&account.name)
+        .bind(account.status.as_str())
+        .bind(account.quota.max_buckets as i64)
+        .bind(account.quota.max_objects as i64)
+        .bind(account.quota.max_bytes as i64)
+        .bind(account.key_generation as i64)
+        .bind(account.created_at.to_rfc3339())
+        .execute(&self.writer)
+        .await
+        .map_err(|e| {
+            if e.to_string().contains("UNIQUE constraint") {
+                Error::InvalidRequest(format!("Account {} already exists", account.id))
+            } else {
+                Error::DatabaseError(e.to_string())
+            }
+        })?;
+
+        debug!("Created account {}", account.id);
+        Ok(())
+    }
+
+    /// Get an account by ID
+    pub async fn get_account(&self, id: &str) -> Result<Option<Account>> {
+        let row: Option<AccountRow> = sqlx::query_as(
+            r#"
+            SELECT id, name, status, max_buckets, max_objects, max_bytes, key_generation, created_at
+            FROM accounts WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(row.map(account_from_row))
+    }
+
+    /// All accounts, by ID
+    pub async fn list_accounts(&self) -> Result<Vec<Account>> {
+        let rows: Vec<AccountRow> = sqlx::query_as(
+            r#"
+            SELECT id, name, status, max_buckets, max_objects, max_bytes, key_generation, created_at
+            FROM accounts ORDER BY id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(rows.into_iter().map(account_from_row).collect())
+    }
+
+    /// Update the name, status and quota of an account; returns whether it
+    /// exists
+    pub async fn update_account(&self, account: &Account) -> Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE accounts
+            SET name = ?, status = ?, max_buckets = ?, max_objects = ?, max_bytes = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(&account.name)
+        .bind(account.status.as_str())
+        .bind(account.quota.max_buckets as i64)
+        .bind(account.quota.max_objects as i64)
+        .bind(account.quota.max_bytes as i64)
+        .bind(&account.id)
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        debug!("Stored object legal hold for: {}/{}", bucket, key);
-        Ok(())
+        debug!("Updated account {}", account.id);
+        Ok(result.rows_affected() > 0)
     }
 
-    /// Get object legal hold
-    pub async fn get_object_legal_hold(
-        &self,
-        bucket: &str,
-        key: &str,
-        version_id: Option<&str>,
-    ) -> Result<Option<String>> {
-        let vid = version_id.unwrap_or("");
-
-        let row: Option<(String,)> = sqlx::query_as(
-            r#"SELECT hold_xml FROM object_legal_hold WHERE bucket = ? AND key = ? AND version_id = ?"#,
+    /// Move an account to its next key generation; returns the new
+    /// generation, or `None` if the account does not exist
+    pub async fn rotate_account_key(&self, id: &str) -> Result<Option<u32>> {
+        let generation: Option<(i64,)> = sqlx::query_as(
+            r#"
+            UPDATE accounts SET key_generation = key_generation + 1
+            WHERE id = ?
+            RETURNING key_generation
+            "#,
         )
-        .bind(bucket)
-        .bind(key)
-        .bind(vid)
-        .fetch_optional(&self.pool)
+        .bind(id)
+        .fetch_optional(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| r.0))
+        debug!("Rotated the key of account {}", id);
+        Ok(generation.map(|(generation,)| generation as u32))
     }
-}
 
-// ============= Credentials Operations for Admin API =============
+    /// Delete an account; returns whether it existed
+    ///
+    /// Callers make sure it has no users or buckets left.
+    pub async fn delete_account(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query(r#"DELETE FROM accounts WHERE id = ?"#)
+            .bind(id)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-use hafiz_core::types::Credentials;
+        debug!("Deleted account {}", id);
+        Ok(result.rows_affected() > 0)
+    }
 
-impl MetadataStore {
-    /// List all credentials (users)
-    pub async fn list_credentials(&self) -> Result<Vec<Credentials>> {
-        let rows: Vec<(String, String, Option<String>, Option<String>, bool, String)> =
-            sqlx::query_as(
-                r#"
-                SELECT access_key, secret_key, display_name, email, is_admin, created_at
-                FROM users
-                ORDER BY created_at DESC
-                "#,
-            )
-            .fetch_all(&self.pool)
+    /// Move a user to another account; returns whether the user exists
+    pub async fn set_user_account(&self, access_key: &str, account_id: &str) -> Result<bool> {
+        let result = sqlx::query(r#"UPDATE users SET account_id = ? WHERE access_key = ?"#)
+            .bind(account_id)
+            .bind(access_key)
+            .execute(&self.writer)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        Ok(rows
-            .into_iter()
-            .map(|r| Credentials {
-                access_key: r.0,
-                secret_key: r.1,
-                name: r.2,
-                email: r.3,
-                enabled: true, // Default to enabled for existing users
-                created_at: DateTime::parse_from_rfc3339(&r.5)
-                    .unwrap()
-                    .with_timezone(&Utc),
-                last_used: None,
-                policies: if r.4 {
-                    vec!["admin".to_string()]
-                } else {
-                    Vec::new()
-                },
-            })
-            .collect())
+        debug!("Moved user {} to account {}", access_key, account_id);
+        Ok(result.rows_affected() > 0)
     }
 
-    /// Get credentials by access key
-    pub async fn get_credentials(&self, access_key: &str) -> Result<Option<Credentials>> {
-        let row: Option<(String, String, Option<String>, Option<String>, bool, String)> =
-            sqlx::query_as(
-                r#"
-                SELECT access_key, secret_key, display_name, email, is_admin, created_at
-                FROM users WHERE access_key = ?
-                "#,
-            )
-            .bind(access_key)
-            .fetch_optional(&self.pool)
+    /// Number of users in an account
+    pub async fn count_account_users(&self, id: &str) -> Result<u64> {
+        let (count,): (i64,) = sqlx::query_as(r#"SELECT COUNT(*) FROM users WHERE account_id = ?"#)
+            .bind(id)
+            .fetch_one(&self.pool)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        Ok(row.map(|r| Credentials {
-            access_key: r.0,
-            secret_key: r.1,
-            name: r.2,
-            email: r.3,
-            enabled: true,
-            created_at: DateTime::parse_from_rfc3339(&r.5)
-                .unwrap()
-                .with_timezone(&Utc),
-            last_used: None,
-            policies: if r.4 {
-                vec!["admin".to_string()]
-            } else {
-                Vec::new()
-            },
-        }))
+        Ok(count.max(0) as u64)
     }
 
-    /// Create new credentials
-    pub async fn create_credentials(&self, cred: &Credentials) -> Result<()> {
-        let id = uuid::Uuid::new_v4().to_string();
-        let is_admin = cred.policies.contains(&"admin".to_string());
+    /// What the buckets of an account hold, from the usage counters
+    pub async fn get_account_usage(&self, id: &str) -> Result<AccountUsage> {
+        let row: (i64, i64, i64) = sqlx::query_as(
+            r#"
+            SELECT COUNT(*), COALESCE(SUM(u.object_count), 0), COALESCE(SUM(u.total_bytes), 0)
+            FROM buckets b LEFT JOIN bucket_usage u ON u.bucket = b.name
+            WHERE b.owner_id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        sqlx::query(
+        Ok(account_usage_from_row(row))
+    }
+
+    /// Usage of every account, including those without buckets
+    pub async fn list_account_usage(&self) -> Result<Vec<(String, AccountUsage)>> {
+        let rows: Vec<(String, i64, i64, i64)> = sqlx::query_as(
             r#"
-            INSERT INTO users (id, access_key, secret_key, display_name, email, is_admin, created_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?)
+            SELECT a.id, COUNT(b.name), COALESCE(SUM(u.object_count), 0), COALESCE(SUM(u.total_bytes), 0)
+            FROM accounts a
+            LEFT JOIN buckets b ON b.owner_id = a.id
+            LEFT JOIN bucket_usage u ON u.bucket = b.name
+            GROUP BY a.id ORDER BY a.id
             "#,
         )
-        .bind(&id)
-        .bind(&cred.access_key)
-        .bind(&cred.secret_key)
-        .bind(&cred.name)
-        .bind(&cred.email)
-        .bind(is_admin)
-        .bind(cred.created_at.to_rfc3339())
-        .execute(&self.pool)
+        .fetch_all(&self.pool)
         .await
-        .map_err(|e| {
-            if e.to_string().contains("UNIQUE constraint") {
-                Error::InternalError("User with this access key already exists".to_string())
-            } else {
-                Error::DatabaseError(e.to_string())
-            }
-        })?;
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        debug!("Created credentials for: {}", cred.name.as_deref().unwrap_or(&cred.access_key));
-        Ok(())
+        Ok(rows
+            .into_iter()
+            .map(|(id, buckets, objects, bytes)| (id, account_usage_from_row((buckets, objects, bytes))))
+            .collect())
     }
 
-    /// Update existing credentials
-    pub async fn update_credentials(&self, cred: &Credentials) -> Result<()> {
-        let is_admin = cred.policies.contains(&"admin".to_string());
+    /// Store temporary credentials issued through STS
+    pub async fn create_temporary_credentials(&self, cred: &TemporaryCredentials) -> Result<()> {
+        let policies = serde_json::to_string(&cred.policies)
+            .map_err(|e| Error::InternalError(e.to_string()))?;
 
         sqlx::query(
             r#"
-            UPDATE users
-            SET display_name = ?, email = ?, is_admin = ?
-            WHERE access_key = ?
+            INSERT INTO sts_credentials
+                (access_key, secret_key, session_token, subject, username, policies, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
-        .bind(&cred.name)
-        .bind(&cred.email)
-        .bind(is_admin)
         .bind(&cred.access_key)
-        .execute(&self.pool)
+        .bind(self.seal_secret(&cred.access_key, &cred.secret_key)?)
+        .bind(&cred.session_token)
+        .bind(&cred.subject)
+        .bind(&cred.username)
+        .bind(policies)
+        .bind(cred.created_at.to_rfc3339())
+        .bind(sortable_timestamp(cred.expires_at))
+        .execute(&self.writer)
         .await
         .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        debug!("Updated credentials for: {}", cred.access_key);
+        debug!("Issued temporary credentials {} to {}", cred.access_key, cred.subject);
         Ok(())
     }
 
-    /// Delete credentials
-    pub async fn delete_credentials(&self, access_key: &str) -> Result<()> {
-        sqlx::query(r#"DELETE FROM users WHERE access_key = ?"#)
+    /// Get unexpired temporary credentials by access key
+    pub async fn get_temporary_credentials(
+        &self,
+        access_key: &str,
+    ) -> Result<Option<TemporaryCredentials>> {
+        let row: Option<(String, String, String, String, String, String, String, String)> =
+            sqlx::query_as(
+                r#"
+                SELECT access_key, secret_key, session_token, subject, username, policies, created_at, expires_at
+                FROM sts_credentials WHERE access_key = ? AND expires_at > ?
+                "#,
+            )
             .bind(access_key)
-            .execute(&self.pool)
+            .bind(sortable_timestamp(Utc::now()))
+            .fetch_optional(&self.pool)
             .await
             .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
-        debug!("Deleted credentials for: {}", access_key);
-        Ok(())
+        row.map(|r| {
+            Ok(TemporaryCredentials {
+                secret_key: self.open_secret(&r.0, r.1)?,
+                access_key: r.0,
+                session_token: r.2,
+                subject: r.3,
+                username: r.4,
+                policies: serde_json::from_str(&r.5).unwrap_or_default(),
+                created_at: DateTime::parse_from_rfc3339(&r.6)
+                    .unwrap()
+                    .with_timezone(&Utc),
+                expires_at: DateTime::parse_from_rfc3339(&r.7)
+                    .unwrap()
+                    .with_timezone(&Utc),
+            })
+        })
+        .transpose()
+    }
+
+    /// Remove expired temporary credentials, returning how many were dropped
+    pub async fn purge_expired_temporary_credentials(&self) -> Result<u64> {
+        let result = sqlx::query(r#"DELETE FROM sts_credentials WHERE expires_at <= ?"#)
+            .bind(sortable_timestamp(Utc::now()))
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Record the use of a single-use pre-signed URL's nonce
+    ///
+    /// Returns false if the nonce was used before. Nonces of expired URLs
+    /// are dropped first, since those URLs are refused anyway.
+    pub async fn consume_presigned_nonce(&self, nonce: &str, expires_at: DateTime<Utc>) -> Result<bool> {
+        sqlx::query(r#"DELETE FROM presigned_nonces WHERE expires_at <= ?"#)
+            .bind(sortable_timestamp(Utc::now()))
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let result = sqlx::query(
+            r#"INSERT OR IGNORE INTO presigned_nonces (nonce, expires_at) VALUES (?, ?)"#,
+        )
+        .bind(nonce)
+        .bind(sortable_timestamp(expires_at))
+        .execute(&self.writer)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        Ok(result.rows_affected() == 1)
     }
 
     /// Get bucket versioning status
@@ -1818,7 +4738,6 @@ impl MetadataStore {
 
     /// Get bucket tags
     pub async fn get_bucket_tags(&self, bucket: &str) -> Result<HashMap<String, String>> {
-        // Check if bucket_tags table exists, if not return empty
         let rows: Vec<(String, String)> = sqlx::query_as(
             r#"
             SELECT tag_key, tag_value FROM bucket_tags
@@ -1828,11 +4747,76 @@ impl MetadataStore {
         .bind(bucket)
         .fetch_all(&self.pool)
         .await
-        .unwrap_or_default();
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         Ok(rows.into_iter().collect())
     }
 
+    /// Put bucket tags (replaces existing tags)
+    pub async fn put_bucket_tags(&self, bucket: &str, tags: &TagSet) -> Result<()> {
+        let mut tx = self
+            .writer
+            .begin()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query(r#"DELETE FROM bucket_tags WHERE bucket = ?"#)
+            .bind(bucket)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        for tag in &tags.tags {
+            sqlx::query(
+                r#"
+                INSERT INTO bucket_tags (bucket, tag_key, tag_value)
+                VALUES (?, ?, ?)
+                "#,
+            )
+            .bind(bucket)
+            .bind(&tag.key)
+            .bind(&tag.value)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+        }
+
+        tx.commit()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Put {} tags for bucket {}", tags.len(), bucket);
+        Ok(())
+    }
+
+    /// Delete bucket tags
+    pub async fn delete_bucket_tags(&self, bucket: &str) -> Result<()> {
+        sqlx::query(r#"DELETE FROM bucket_tags WHERE bucket = ?"#)
+            .bind(bucket)
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Deleted tags for bucket {}", bucket);
+        Ok(())
+    }
+
+    /// Tags of every tagged bucket
+    pub async fn list_bucket_tags(&self) -> Result<HashMap<String, HashMap<String, String>>> {
+        let rows: Vec<(String, String, String)> = sqlx::query_as(
+            r#"SELECT bucket, tag_key, tag_value FROM bucket_tags"#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let mut buckets: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for (bucket, key, value) in rows {
+            buckets.entry(bucket).or_default().insert(key, value);
+        }
+        Ok(buckets)
+    }
+
     /// Get lifecycle rules for a bucket
     pub async fn get_lifecycle_rules(&self, bucket: &str) -> Result<Vec<LifecycleRule>> {
         let config = self.get_bucket_lifecycle(bucket).await?;
@@ -1841,21 +4825,26 @@ impl MetadataStore {
 
     /// List delete markers in a bucket
     pub async fn list_delete_markers(&self, bucket: &str, prefix: &str, max_keys: i32) -> Result<Vec<DeleteMarker>> {
-        let rows: Vec<(String, String, String)> = sqlx::query_as(
+        let end = prefix_end(prefix);
+        let sql = format!(
             r#"
             SELECT key, version_id, last_modified
             FROM objects
-            WHERE bucket = ? AND key LIKE ? AND is_delete_marker = 1
+            WHERE bucket = ? AND key >= ?{} AND is_delete_marker = 1
             ORDER BY key
             LIMIT ?
             "#,
-        )
-        .bind(bucket)
-        .bind(format!("{}%", prefix))
-        .bind(max_keys)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| Error::DatabaseError(e.to_string()))?;
+            below(&end)
+        );
+        let mut query = sqlx::query_as(&sql).bind(bucket).bind(prefix);
+        if let Some(end) = &end {
+            query = query.bind(end);
+        }
+        let rows: Vec<(String, String, String)> = query
+            .bind(max_keys)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
 
         Ok(rows
             .into_iter()
@@ -1871,3 +4860,156 @@ impl MetadataStore {
             .collect())
     }
 }
+
+/// access_key, secret_key, user_access_key, created_at, expires_at, last_used
+type RetiringKeyRow = (String, String, String, String, String, Option<String>);
+
+impl MetadataStore {
+    fn retiring_key_from_row(&self, r: RetiringKeyRow) -> Result<RetiringKey> {
+        Ok(RetiringKey {
+            secret_key: self.open_secret(&r.0, r.1)?,
+            access_key: r.0,
+            user_access_key: r.2,
+            created_at: DateTime::parse_from_rfc3339(&r.3)
+                .unwrap()
+                .with_timezone(&Utc),
+            expires_at: DateTime::parse_from_rfc3339(&r.4)
+                .unwrap()
+                .with_timezone(&Utc),
+            last_used: parse_optional_timestamp(r.5.as_deref()),
+        })
+    }
+}
+
+/// Fixed-width RFC 3339 so timestamps compare correctly as text
+fn sortable_timestamp(at: DateTime<Utc>) -> String {
+    at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// username, access_key, home, created_at
+fn sftp_user_from_row(row: (String, String, String, String), keys: Vec<SftpKey>) -> SftpUser {
+    SftpUser {
+        username: row.0,
+        access_key: row.1,
+        home: row.2,
+        keys,
+        created_at: parse_optional_timestamp(Some(&row.3)).unwrap_or_else(Utc::now),
+    }
+}
+
+// ============= Snapshots for Cluster Metadata Replication =============
+
+impl MetadataStore {
+    /// Tables whose contents are replicated through the cluster metadata log
+    pub const REPLICATED_TABLES: &'static [&'static str] = &[
+        "buckets",
+        "objects",
+        "object_tags",
+        "bucket_tags",
+        "bucket_policies",
+        "object_acls",
+        "object_retention",
+        "object_legal_hold",
+    ];
+
+    /// Write a consistent copy of the whole database to `path`
+    pub async fn snapshot_to_file(&self, path: &std::path::Path) -> Result<()> {
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().to_string())
+            .execute(&self.writer)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        debug!("Wrote metadata snapshot to {}", path.display());
+        Ok(())
+    }
+
+    /// Replace the contents of `tables` with those from a snapshot file
+    /// produced by [`MetadataStore::snapshot_to_file`]
+    pub async fn restore_tables_from_file(
+        &self,
+        path: &std::path::Path,
+        tables: &[&str],
+    ) -> Result<()> {
+        // ATTACH is per-connection, so everything runs on one connection
+        let mut conn = self
+            .writer
+            .acquire()
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        sqlx::query("ATTACH DATABASE ? AS snapshot")
+            .bind(path.to_string_lossy().to_string())
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| Error::DatabaseError(e.to_string()))?;
+
+        let mut result = Ok(());
+        for stmt in std::iter::once("BEGIN".to_string())
+            .chain(tables.iter().flat_map(|t| {
+                [
+                    format!("DELETE FROM main.{}", t),
+                    format!("INSERT INTO main.{} SELECT * FROM snapshot.{}", t, t),
+                ]
+            }))
+            .chain(std::iter::once("COMMIT".to_string()))
+        {
+            if let Err(e) = sqlx::query(&stmt).execute(&mut *conn).await {
+                let _ = sqlx::query("ROLLBACK").execute(&mut *conn).await;
+                result = Err(Error::DatabaseError(e.to_string()));
+                break;
+            }
+        }
+
+        let _ = sqlx::query("DETACH DATABASE snapshot").execute(&mut *conn).await;
+        if let Some(cache) = &self.cache {
+            cache.clear();
+        }
+
+        if result.is_ok() {
+            info!("Restored {} metadata tables from snapshot", tables.len());
+        }
+        result
+    }
+}
+
+/// Common prefix `key` rolls up into under `delimiter`, if any
+fn common_prefix(prefix: &str, key: &str, delimiter: &str) -> Option<String> {
+    let suffix = key.strip_prefix(prefix)?;
+    let idx = suffix.find(delimiter)?;
+    Some(format!("{}{}{}", prefix, &suffix[..idx], delimiter))
+}
+
+/// Marker that sorts after every key starting with `prefix`
+fn past_prefix(prefix: &str) -> String {
+    format!("{}{}", prefix, char::MAX)
+}
+
+/// Smallest string above every key that starts with `prefix`
+///
+/// Listings select a prefix as the key range `[prefix, prefix_end)`, which
+/// the `(bucket, key)` indexes serve directly, unlike `LIKE 'prefix%'`.
+/// `None` means no upper bound (an empty prefix).
+pub(crate) fn prefix_end(prefix: &str) -> Option<String> {
+    let mut chars: Vec<char> = prefix.chars().collect();
+    while let Some(last) = chars.pop() {
+        let next = match last {
+            '\u{D7FF}' => Some('\u{E000}'),
+            c => char::from_u32(c as u32 + 1),
+        };
+        if let Some(next) = next {
+            chars.push(next);
+            return Some(chars.into_iter().collect());
+        }
+    }
+    None
+}
+
+/// `key < ?` condition for a [`prefix_end`] bound, bound right after the prefix
+fn below(end: &Option<String>) -> &'static str {
+    if end.is_some() {
+        " AND key < ?"
+    } else {
+        ""
+    }
+}
@@ -97,6 +97,8 @@ pub trait MetadataRepository: Send + Sync {
     async fn set_bucket_versioning(&self, name: &str, status: VersioningStatus) -> Result<()>;
     async fn get_bucket_versioning(&self, bucket: &str) -> Result<Option<String>>;
     async fn get_bucket_tags(&self, bucket: &str) -> Result<HashMap<String, String>>;
+    async fn put_bucket_tags(&self, bucket: &str, tags: &TagSet) -> Result<()>;
+    async fn delete_bucket_tags(&self, bucket: &str) -> Result<()>;
 
     // ============= Object Operations =============
 
@@ -0,0 +1,196 @@
+//! Access point endpoints
+//!
+//! Create and remove access points, and set the policies that are
+//! evaluated before the bucket policy for requests made through them.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hafiz_core::types::{AccessPoint, PolicyDocument};
+use serde::{Deserialize, Serialize};
+
+use crate::server::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct AccessPointInfo {
+    pub name: String,
+    pub bucket: String,
+    /// Use in place of the bucket name
+    pub alias: String,
+    /// Host serving the access point, when a domain is configured
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<serde_json::Value>,
+    pub allowed_cidrs: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl AccessPointInfo {
+    fn new(state: &AppState, access_point: AccessPoint) -> Self {
+        Self {
+            alias: access_point.alias(),
+            hostname: state
+                .config
+                .server
+                .access_point_domain
+                .as_ref()
+                .map(|domain| format!("{}.{}", access_point.name, domain)),
+            policy: access_point
+                .policy
+                .as_deref()
+                .and_then(|p| serde_json::from_str(p).ok()),
+            name: access_point.name,
+            bucket: access_point.bucket,
+            allowed_cidrs: access_point.allowed_cidrs,
+            created_at: access_point.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccessPointList {
+    pub access_points: Vec<AccessPointInfo>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccessPointQuery {
+    pub bucket: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAccessPointRequest {
+    pub name: String,
+    pub bucket: String,
+    #[serde(default)]
+    pub policy: Option<serde_json::Value>,
+    /// Networks requests must come from, e.g. `10.0.0.0/8`; empty allows any
+    #[serde(default)]
+    pub allowed_cidrs: Vec<String>,
+}
+
+fn to_error(e: hafiz_core::Error) -> (StatusCode, String) {
+    (
+        StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        e.to_string(),
+    )
+}
+
+/// Check a policy document and return it as stored
+fn policy_json(policy: &serde_json::Value) -> Result<String, (StatusCode, String)> {
+    serde_json::from_value::<PolicyDocument>(policy.clone())
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Malformed policy document: {}", e)))?;
+    Ok(policy.to_string())
+}
+
+/// GET /api/v1/access-points
+/// List access points, optionally those of one bucket
+pub async fn list_access_points(
+    State(state): State<AppState>,
+    Query(query): Query<AccessPointQuery>,
+) -> Result<Json<AccessPointList>, (StatusCode, String)> {
+    let access_points = state
+        .metadata
+        .list_access_points(query.bucket.as_deref())
+        .await
+        .map_err(to_error)?;
+    Ok(Json(AccessPointList {
+        access_points: access_points
+            .into_iter()
+            .map(|ap| AccessPointInfo::new(&state, ap))
+            .collect(),
+    }))
+}
+
+/// POST /api/v1/access-points
+pub async fn create_access_point(
+    State(state): State<AppState>,
+    Json(request): Json<CreateAccessPointRequest>,
+) -> Result<(StatusCode, Json<AccessPointInfo>), (StatusCode, String)> {
+    AccessPoint::validate_name(&request.name).map_err(to_error)?;
+    AccessPoint::validate_cidrs(&request.allowed_cidrs).map_err(to_error)?;
+    if state
+        .metadata
+        .get_bucket(&request.bucket)
+        .await
+        .map_err(to_error)?
+        .is_none()
+    {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Bucket {} not found", request.bucket),
+        ));
+    }
+
+    let mut access_point = AccessPoint::new(request.name, request.bucket);
+    access_point.policy = request.policy.as_ref().map(policy_json).transpose()?;
+    access_point.allowed_cidrs = request.allowed_cidrs;
+    state
+        .metadata
+        .create_access_point(&access_point)
+        .await
+        .map_err(|e| match e {
+            hafiz_core::Error::InvalidRequest(msg) => (StatusCode::CONFLICT, msg),
+            e => to_error(e),
+        })?;
+    Ok((StatusCode::CREATED, Json(AccessPointInfo::new(&state, access_point))))
+}
+
+/// GET /api/v1/access-points/:name
+pub async fn get_access_point(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<AccessPointInfo>, (StatusCode, String)> {
+    state
+        .metadata
+        .get_access_point(&name)
+        .await
+        .map_err(to_error)?
+        .map(|ap| Json(AccessPointInfo::new(&state, ap)))
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Access point {} not found", name)))
+}
+
+/// DELETE /api/v1/access-points/:name
+pub async fn delete_access_point(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .metadata
+        .delete_access_point(&name)
+        .await
+        .map_err(to_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PUT /api/v1/access-points/:name/policy
+/// Replace the access point policy with the posted document
+pub async fn put_access_point_policy(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(policy): Json<serde_json::Value>,
+) -> Result<Json<AccessPointInfo>, (StatusCode, String)> {
+    let policy = policy_json(&policy)?;
+    state
+        .metadata
+        .put_access_point_policy(&name, Some(&policy))
+        .await
+        .map_err(to_error)?;
+    get_access_point(State(state), Path(name)).await
+}
+
+/// DELETE /api/v1/access-points/:name/policy
+pub async fn delete_access_point_policy(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .metadata
+        .put_access_point_policy(&name, None)
+        .await
+        .map_err(to_error)?;
+    Ok(StatusCode::NO_CONTENT)
+}
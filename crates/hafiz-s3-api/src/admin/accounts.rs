@@ -0,0 +1,319 @@
+//! Account endpoints
+//!
+//! Accounts hold users and buckets, for deployments that serve several
+//! tenants. Each has a quota on what its buckets may hold together, and can
+//! be suspended, refusing every request of its users and to its buckets
+//! until it is resumed. The default account holds the root user and
+//! everything created before accounts existed; it cannot be deleted or
+//! suspended.
+//!
+//! SSE-S3 objects are encrypted under a key of the account owning their
+//! bucket, derived from the master key. Rotating it changes the key of new
+//! objects of that account only.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use hafiz_core::types::{Account, AccountQuota, AccountStatus, AccountUsage, DEFAULT_ACCOUNT};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::UserInfo;
+use crate::middleware::AdminPrincipal;
+use crate::server::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateAccountRequest {
+    pub id: String,
+    /// Display name; the ID if not given
+    pub name: Option<String>,
+    #[serde(default)]
+    pub quota: AccountQuota,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAccountRequest {
+    pub name: Option<String>,
+    pub quota: Option<AccountQuota>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetUserAccountRequest {
+    pub account_id: String,
+}
+
+/// An account with what it holds
+#[derive(Debug, Serialize)]
+pub struct AccountInfo {
+    #[serde(flatten)]
+    pub account: Account,
+    pub usage: AccountUsage,
+    pub users: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountListResponse {
+    pub accounts: Vec<AccountInfo>,
+}
+
+/// GET /api/v1/accounts
+/// List accounts with their usage
+pub async fn list_accounts(
+    State(state): State<AppState>,
+) -> Result<Json<AccountListResponse>, (StatusCode, String)> {
+    let accounts = state.metadata.list_accounts().await.map_err(internal)?;
+    let mut infos = Vec::with_capacity(accounts.len());
+    for account in accounts {
+        infos.push(account_info(&state, account).await?);
+    }
+    Ok(Json(AccountListResponse { accounts: infos }))
+}
+
+/// GET /api/v1/accounts/:id
+pub async fn get_account(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    let account = find_account(&state, &id).await?;
+    Ok(Json(account_info(&state, account).await?))
+}
+
+/// POST /api/v1/accounts
+/// Create an account
+pub async fn create_account(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Json(req): Json<CreateAccountRequest>,
+) -> Result<(StatusCode, Json<AccountInfo>), (StatusCode, String)> {
+    Account::validate_id(&req.id).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let existing = state
+        .metadata
+        .get_account(&req.id)
+        .await
+        .map_err(internal)?;
+    if existing.is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Account '{}' already exists", req.id),
+        ));
+    }
+
+    let mut account = Account::new(req.id.clone(), req.name.unwrap_or(req.id));
+    account.quota = req.quota;
+    state
+        .metadata
+        .create_account(&account)
+        .await
+        .map_err(internal)?;
+
+    info!("{} created account {}", caller.name, account.id);
+    Ok((
+        StatusCode::CREATED,
+        Json(account_info(&state, account).await?),
+    ))
+}
+
+/// PUT /api/v1/accounts/:id
+/// Rename an account or change its quota
+pub async fn update_account(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateAccountRequest>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    let mut account = find_account(&state, &id).await?;
+    if let Some(name) = req.name {
+        account.name = name;
+    }
+    if let Some(quota) = req.quota {
+        account.quota = quota;
+    }
+    state
+        .metadata
+        .update_account(&account)
+        .await
+        .map_err(internal)?;
+
+    info!("{} updated account {}", caller.name, account.id);
+    Ok(Json(account_info(&state, account).await?))
+}
+
+/// POST /api/v1/accounts/:id/suspend
+/// Refuse all requests of the account's users and to its buckets
+pub async fn suspend_account(
+    state: State<AppState>,
+    caller: Extension<AdminPrincipal>,
+    id: Path<String>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    set_account_status(state, caller, id, AccountStatus::Suspended).await
+}
+
+/// POST /api/v1/accounts/:id/resume
+/// Lift an account's suspension
+pub async fn resume_account(
+    state: State<AppState>,
+    caller: Extension<AdminPrincipal>,
+    id: Path<String>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    set_account_status(state, caller, id, AccountStatus::Active).await
+}
+
+async fn set_account_status(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Path(id): Path<String>,
+    status: AccountStatus,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    if id == DEFAULT_ACCOUNT && status == AccountStatus::Suspended {
+        return Err((
+            StatusCode::CONFLICT,
+            "The default account cannot be suspended".to_string(),
+        ));
+    }
+    let mut account = find_account(&state, &id).await?;
+    account.status = status;
+    state
+        .metadata
+        .update_account(&account)
+        .await
+        .map_err(internal)?;
+
+    info!("{} set account {} to {}", caller.name, account.id, status);
+    Ok(Json(account_info(&state, account).await?))
+}
+
+/// POST /api/v1/accounts/:id/rotate-key
+/// Encrypt the account's new SSE-S3 objects under a new key; existing
+/// objects keep theirs
+pub async fn rotate_account_key(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Path(id): Path<String>,
+) -> Result<Json<AccountInfo>, (StatusCode, String)> {
+    let generation = state
+        .metadata
+        .rotate_account_key(&id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Account '{}' not found", id)))?;
+
+    info!(
+        "{} rotated the key of account {} to {}",
+        caller.name,
+        id,
+        hafiz_crypto::account_key_id(&id, generation)
+    );
+    let account = find_account(&state, &id).await?;
+    Ok(Json(account_info(&state, account).await?))
+}
+
+/// DELETE /api/v1/accounts/:id
+/// Delete an account without users or buckets
+pub async fn delete_account(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if id == DEFAULT_ACCOUNT {
+        return Err((
+            StatusCode::CONFLICT,
+            "The default account cannot be deleted".to_string(),
+        ));
+    }
+    let account = find_account(&state, &id).await?;
+    let info = account_info(&state, account).await?;
+    if info.users > 0 || info.usage.buckets > 0 {
+        return Err((
+            StatusCode::CONFLICT,
+            format!(
+                "Account '{}' still has {} users and {} buckets",
+                id, info.users, info.usage.buckets
+            ),
+        ));
+    }
+
+    state.metadata.delete_account(&id).await.map_err(internal)?;
+    info!("{} deleted account {}", caller.name, id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// PUT /api/v1/users/:access_key/account
+/// Move a user to another account; its buckets stay where they are
+pub async fn set_user_account(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Path(access_key): Path<String>,
+    Json(req): Json<SetUserAccountRequest>,
+) -> Result<Json<UserInfo>, (StatusCode, String)> {
+    find_account(&state, &req.account_id).await?;
+    let moved = state
+        .metadata
+        .set_user_account(&access_key, &req.account_id)
+        .await
+        .map_err(internal)?;
+    let cred = match state
+        .metadata
+        .get_credentials(&access_key)
+        .await
+        .map_err(internal)?
+    {
+        Some(cred) if moved => cred,
+        _ => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("User '{}' not found", access_key),
+            ))
+        }
+    };
+
+    info!(
+        "{} moved user {} to account {}",
+        caller.name, access_key, req.account_id
+    );
+    Ok(Json(UserInfo {
+        name: cred.name.unwrap_or_else(|| cred.access_key.clone()),
+        access_key: cred.access_key,
+        email: cred.email,
+        enabled: cred.enabled,
+        created_at: cred.created_at.to_rfc3339(),
+        last_used: cred.last_used.map(|d| d.to_rfc3339()),
+        policies: cred.policies,
+        account_id: cred.account_id,
+    }))
+}
+
+async fn find_account(state: &AppState, id: &str) -> Result<Account, (StatusCode, String)> {
+    state
+        .metadata
+        .get_account(id)
+        .await
+        .map_err(internal)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Account '{}' not found", id)))
+}
+
+async fn account_info(
+    state: &AppState,
+    account: Account,
+) -> Result<AccountInfo, (StatusCode, String)> {
+    let usage = state
+        .metadata
+        .get_account_usage(&account.id)
+        .await
+        .map_err(internal)?;
+    let users = state
+        .metadata
+        .count_account_users(&account.id)
+        .await
+        .map_err(internal)?;
+    Ok(AccountInfo {
+        account,
+        usage,
+        users,
+    })
+}
+
+fn internal(e: hafiz_core::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
@@ -0,0 +1,144 @@
+//! Bandwidth limit endpoints
+//!
+//! Sets the byte rates that requests of a bucket or an access key may use,
+//! for request and response bodies separately. Limits are stored in the
+//! metadata database and apply at once, also to transfers in progress.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use hafiz_core::types::{BandwidthLimit, BandwidthScope};
+use serde::Serialize;
+use tracing::info;
+
+use crate::server::AppState;
+
+/// Bandwidth limit of a bucket or access key
+#[derive(Debug, Serialize)]
+pub struct BandwidthLimitResponse {
+    pub scope: BandwidthScope,
+    pub name: String,
+    #[serde(flatten)]
+    pub limit: BandwidthLimit,
+}
+
+/// GET /api/v1/bandwidth
+/// List all bandwidth limits in force
+pub async fn list_bandwidth_limits(State(state): State<AppState>) -> Json<Vec<BandwidthLimitResponse>> {
+    let limits = state
+        .bandwidth
+        .list()
+        .into_iter()
+        .map(|(scope, name, limit)| BandwidthLimitResponse { scope, name, limit })
+        .collect();
+    Json(limits)
+}
+
+/// GET /api/v1/buckets/:name/bandwidth
+/// Get a bucket's bandwidth limit; 0 means unlimited
+pub async fn get_bucket_bandwidth(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Json<BandwidthLimitResponse> {
+    Json(limit_response(&state, BandwidthScope::Bucket, bucket))
+}
+
+/// PUT /api/v1/buckets/:name/bandwidth
+/// Set a bucket's bandwidth limit, shared by all requests to the bucket
+pub async fn set_bucket_bandwidth(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(limit): Json<BandwidthLimit>,
+) -> Result<Json<BandwidthLimitResponse>, (StatusCode, String)> {
+    let exists = state
+        .metadata
+        .get_bucket(&bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .is_some();
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, format!("Bucket {} not found", bucket)));
+    }
+
+    set_limit(&state, BandwidthScope::Bucket, &bucket, limit).await?;
+    Ok(Json(limit_response(&state, BandwidthScope::Bucket, bucket)))
+}
+
+/// DELETE /api/v1/buckets/:name/bandwidth
+/// Remove a bucket's bandwidth limit
+pub async fn delete_bucket_bandwidth(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    set_limit(&state, BandwidthScope::Bucket, &bucket, BandwidthLimit::default()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/v1/users/:access_key/bandwidth
+/// Get an access key's bandwidth limit; 0 means unlimited
+pub async fn get_user_bandwidth(
+    State(state): State<AppState>,
+    Path(access_key): Path<String>,
+) -> Json<BandwidthLimitResponse> {
+    Json(limit_response(&state, BandwidthScope::AccessKey, access_key))
+}
+
+/// PUT /api/v1/users/:access_key/bandwidth
+/// Set an access key's bandwidth limit, shared by all requests it signs
+pub async fn set_user_bandwidth(
+    State(state): State<AppState>,
+    Path(access_key): Path<String>,
+    Json(limit): Json<BandwidthLimit>,
+) -> Result<Json<BandwidthLimitResponse>, (StatusCode, String)> {
+    let exists = state
+        .metadata
+        .get_user_by_access_key(&access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .is_some();
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, format!("User {} not found", access_key)));
+    }
+
+    set_limit(&state, BandwidthScope::AccessKey, &access_key, limit).await?;
+    Ok(Json(limit_response(&state, BandwidthScope::AccessKey, access_key)))
+}
+
+/// DELETE /api/v1/users/:access_key/bandwidth
+/// Remove an access key's bandwidth limit
+pub async fn delete_user_bandwidth(
+    State(state): State<AppState>,
+    Path(access_key): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    set_limit(&state, BandwidthScope::AccessKey, &access_key, BandwidthLimit::default()).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn limit_response(state: &AppState, scope: BandwidthScope, name: String) -> BandwidthLimitResponse {
+    let limit = state.bandwidth.get(scope, &name);
+    BandwidthLimitResponse { scope, name, limit }
+}
+
+/// Store and apply a limit; an unlimited one is removed
+async fn set_limit(
+    state: &AppState,
+    scope: BandwidthScope,
+    name: &str,
+    limit: BandwidthLimit,
+) -> Result<(), (StatusCode, String)> {
+    let stored = if limit.is_unlimited() {
+        state.metadata.delete_bandwidth_limit(scope, name).await
+    } else {
+        state.metadata.put_bandwidth_limit(scope, name, &limit).await
+    };
+    stored.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.bandwidth.set(scope, name, limit);
+
+    info!(
+        "Bandwidth limit of {} {}: ingress {} B/s, egress {} B/s",
+        scope, name, limit.ingress_bytes_per_sec, limit.egress_bytes_per_sec
+    );
+    Ok(())
+}
@@ -0,0 +1,77 @@
+//! Batch operation endpoints
+//!
+//! Submit jobs that copy, tag or delete the objects listed in a manifest,
+//! follow their progress and cancel them.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Serialize;
+
+use crate::batch::{BatchJobRequest, BatchJobStatus};
+use crate::server::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct BatchJobList {
+    pub jobs: Vec<BatchJobStatus>,
+}
+
+/// GET /api/v1/batch/jobs
+/// List jobs, newest first
+pub async fn list_batch_jobs(State(state): State<AppState>) -> Json<BatchJobList> {
+    Json(BatchJobList {
+        jobs: state.batch.list(),
+    })
+}
+
+/// POST /api/v1/batch/jobs
+/// Start a job in the background; poll GET /batch/jobs/:id for progress
+pub async fn create_batch_job(
+    State(state): State<AppState>,
+    Json(request): Json<BatchJobRequest>,
+) -> Result<(StatusCode, Json<BatchJobStatus>), (StatusCode, String)> {
+    let status = state.batch.submit(&state, request).await.map_err(|e| {
+        (
+            StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            e.to_string(),
+        )
+    })?;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+/// GET /api/v1/batch/jobs/:id
+pub async fn get_batch_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<BatchJobStatus>, (StatusCode, String)> {
+    state
+        .batch
+        .get(&id)
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Batch job {} not found", id)))
+}
+
+/// POST /api/v1/batch/jobs/:id/cancel
+/// Stop a job once the objects in flight are done
+pub async fn cancel_batch_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<BatchJobStatus>, (StatusCode, String)> {
+    let status = state
+        .batch
+        .get(&id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Batch job {} not found", id)))?;
+    if status.state.is_finished() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Batch job {} has already finished", id),
+        ));
+    }
+    state
+        .batch
+        .cancel(&id)
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Batch job {} not found", id)))
+}
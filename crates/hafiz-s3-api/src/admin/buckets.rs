@@ -0,0 +1,257 @@
+//! Bucket removal endpoint
+//!
+//! Deletes a bucket server-side. With `force`, every version, delete marker
+//! and incomplete multipart upload is removed first, in batches and at a
+//! bounded rate so emptying a large bucket does not starve client traffic.
+//! Versions under object lock are kept, and so is the bucket holding them.
+
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use hafiz_core::Error;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::consensus;
+use crate::multipart_expiry::abort_upload;
+use crate::routes::{enforce_object_lock, remove_bucket, storage_key};
+use crate::server::AppState;
+
+/// Versions deleted per metadata page
+const DEFAULT_BATCH_SIZE: u32 = 1000;
+
+/// Versions deleted per second unless the request sets `rate`
+const DEFAULT_RATE: u32 = 1000;
+
+/// Bucket removal parameters
+#[derive(Debug, Deserialize, Default)]
+pub struct DeleteBucketQuery {
+    /// Empty the bucket before removing it
+    #[serde(default)]
+    pub force: bool,
+    /// Versions read and deleted per batch
+    pub batch_size: Option<u32>,
+    /// Maximum versions deleted per second; 0 removes the limit
+    pub rate: Option<u32>,
+    /// Also delete versions under GOVERNANCE retention
+    #[serde(default)]
+    pub bypass_governance: bool,
+}
+
+/// Bucket removal report
+#[derive(Debug, Default, Serialize)]
+pub struct DeleteBucketReport {
+    pub bucket: String,
+    /// Whether the bucket itself was removed
+    pub deleted: bool,
+    pub versions_deleted: u64,
+    pub delete_markers_deleted: u64,
+    pub uploads_aborted: u64,
+    pub bytes_freed: i64,
+    /// Versions kept because object lock protects them
+    pub locked_versions: u64,
+    pub errors: Vec<String>,
+}
+
+/// DELETE /api/v1/buckets/:name
+/// Remove a bucket, emptying it first with `?force=true`
+pub async fn delete_bucket(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Query(params): Query<DeleteBucketQuery>,
+) -> Result<Json<DeleteBucketReport>, (StatusCode, String)> {
+    if state
+        .metadata
+        .get_bucket(&bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .is_none()
+    {
+        return Err((StatusCode::NOT_FOUND, format!("Bucket not found: {}", bucket)));
+    }
+
+    let mut report = DeleteBucketReport {
+        bucket: bucket.clone(),
+        ..Default::default()
+    };
+
+    if params.force {
+        let batch_size = params.batch_size.unwrap_or(DEFAULT_BATCH_SIZE).max(1);
+        let rate = params.rate.unwrap_or(DEFAULT_RATE);
+        empty_versions(&state, &bucket, batch_size, rate, params.bypass_governance, &mut report)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+        abort_uploads(&state, &bucket, batch_size, &mut report)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    }
+
+    match remove_bucket(&state, &bucket).await {
+        Ok(()) => report.deleted = true,
+        Err(Error::BucketNotEmpty) if !params.force => {
+            return Err((
+                StatusCode::CONFLICT,
+                "Bucket is not empty; use force=true to empty it first".to_string(),
+            ));
+        }
+        Err(e) => report.errors.push(e.to_string()),
+    }
+
+    info!(
+        "Admin delete of bucket {}: {} versions, {} delete markers, {} uploads, {} bytes, {} locked, removed={}",
+        bucket,
+        report.versions_deleted,
+        report.delete_markers_deleted,
+        report.uploads_aborted,
+        report.bytes_freed,
+        report.locked_versions,
+        report.deleted
+    );
+
+    Ok(Json(report))
+}
+
+/// Delete every version and delete marker of `bucket` that object lock allows
+async fn empty_versions(
+    state: &AppState,
+    bucket: &str,
+    batch_size: u32,
+    rate: u32,
+    bypass_governance: bool,
+    report: &mut DeleteBucketReport,
+) -> hafiz_core::Result<()> {
+    let lock_enabled = state
+        .metadata
+        .get_bucket_object_lock_config(bucket)
+        .await?
+        .is_some();
+    let started = Instant::now();
+    let mut processed: u64 = 0;
+    let mut after: Option<(String, String)> = None;
+
+    loop {
+        let page = state
+            .metadata
+            .list_version_refs(
+                bucket,
+                after.as_ref().map(|(k, v)| (k.as_str(), v.as_str())),
+                batch_size as i32,
+            )
+            .await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = Some((last.key.clone(), last.version_id.clone()));
+
+        for version in &page {
+            processed += 1;
+            if lock_enabled && !version.is_delete_marker {
+                match enforce_object_lock(
+                    state,
+                    bucket,
+                    &version.key,
+                    Some(&version.version_id),
+                    bypass_governance,
+                )
+                .await
+                {
+                    Ok(()) => {}
+                    Err(Error::ObjectLocked(_)) => {
+                        report.locked_versions += 1;
+                        continue;
+                    }
+                    Err(e) => {
+                        report.errors.push(format!("{}: {}", version.key, e));
+                        continue;
+                    }
+                }
+            }
+
+            if !version.is_delete_marker {
+                let storage_key = storage_key(&version.key, &version.version_id);
+                match state.storage.delete(bucket, &storage_key).await {
+                    Ok(()) | Err(Error::NoSuchKey) => {}
+                    Err(e) => {
+                        // Keep the record so the data is not orphaned
+                        report.errors.push(format!("{}: {}", version.key, e));
+                        continue;
+                    }
+                }
+            }
+
+            consensus::delete_object_version(state, bucket, &version.key, &version.version_id).await?;
+            if version.is_delete_marker {
+                report.delete_markers_deleted += 1;
+            } else {
+                report.versions_deleted += 1;
+                report.bytes_freed += version.size;
+            }
+        }
+
+        // Pace each batch so the average stays at or below `rate`
+        if rate > 0 {
+            let due = Duration::from_secs_f64(processed as f64 / rate as f64);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                tokio::time::sleep(wait).await;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Abort every incomplete multipart upload of `bucket`
+async fn abort_uploads(
+    state: &AppState,
+    bucket: &str,
+    batch_size: u32,
+    report: &mut DeleteBucketReport,
+) -> hafiz_core::Result<()> {
+    let mut key_marker: Option<String> = None;
+    let mut upload_id_marker: Option<String> = None;
+
+    loop {
+        let (uploads, truncated) = state
+            .metadata
+            .list_multipart_uploads(
+                bucket,
+                None,
+                key_marker.as_deref(),
+                upload_id_marker.as_deref(),
+                batch_size as i32,
+            )
+            .await?;
+
+        for info in &uploads {
+            let Some(upload) = state
+                .metadata
+                .get_multipart_upload(bucket, &info.key, &info.upload_id)
+                .await?
+            else {
+                continue;
+            };
+            match abort_upload(state, &upload, false).await {
+                Ok(reclaimed) => {
+                    report.uploads_aborted += 1;
+                    report.bytes_freed += reclaimed.bytes;
+                }
+                Err(e) => {
+                    warn!("Failed to abort upload {}: {}", upload.upload_id, e);
+                    report.errors.push(format!("{}: {}", upload.upload_id, e));
+                }
+            }
+        }
+
+        match uploads.last() {
+            Some(last) if truncated => {
+                key_marker = Some(last.key.clone());
+                upload_id_marker = Some(last.upload_id.clone());
+            }
+            _ => break,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Request capture endpoints
+//!
+//! Start recording matching S3 requests into a capture bucket for replay,
+//! follow a capture and stop it.
+
+use axum::{extract::State, http::StatusCode, Json};
+
+use crate::middleware::capture::{CaptureRequest, CaptureStatus};
+use crate::server::AppState;
+
+/// GET /api/v1/capture
+/// The running capture, or the last one
+pub async fn get_capture(
+    State(state): State<AppState>,
+) -> Result<Json<CaptureStatus>, (StatusCode, String)> {
+    state.capture.status().map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            "No request capture has been started".to_string(),
+        )
+    })
+}
+
+/// POST /api/v1/capture
+/// Start recording requests; fails while another capture runs
+pub async fn start_capture(
+    State(state): State<AppState>,
+    Json(request): Json<CaptureRequest>,
+) -> Result<(StatusCode, Json<CaptureStatus>), (StatusCode, String)> {
+    let status = state.capture.start(&state, request).await.map_err(|e| {
+        (
+            StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            e.to_string(),
+        )
+    })?;
+    Ok((StatusCode::CREATED, Json(status)))
+}
+
+/// POST /api/v1/capture/stop
+pub async fn stop_capture(
+    State(state): State<AppState>,
+) -> Result<Json<CaptureStatus>, (StatusCode, String)> {
+    state.capture.stop().map(Json).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            "No request capture is running".to_string(),
+        )
+    })
+}
@@ -2,22 +2,27 @@
 //!
 //! Provides REST API for cluster administration:
 //! - View cluster status and nodes
+//! - Drain, decommission and add nodes
 //! - Manage replication rules
 //! - Monitor replication progress
+//! - Trigger anti-entropy repair
+//! - Set per-bucket consistency levels
+//! - View replica placement across zones and racks
 
 #![cfg(feature = "cluster")]
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use hafiz_core::types::{
-    ClusterNode, ClusterNodeStatus, ClusterStats, ConflictResolution, NodeId, NodeRole,
-    ReplicationMode, ReplicationRule, ReplicationStatus,
+    ClusterNode, ClusterNodeStatus, ClusterStats, ConflictResolution, ConsistencyLevel, NodeId,
+    NodeRole, RebalanceProgress, ReplicationMode, ReplicationRule, ReplicationStatus,
 };
 
 use crate::server::AppState;
@@ -45,6 +50,7 @@ pub struct NodeInfoResponse {
     pub status: String,
     pub region: Option<String>,
     pub zone: Option<String>,
+    pub rack: Option<String>,
     pub joined_at: String,
     pub last_heartbeat: String,
     pub version: String,
@@ -60,6 +66,7 @@ impl From<ClusterNode> for NodeInfoResponse {
             status: format!("{:?}", node.status).to_lowercase(),
             region: node.region,
             zone: node.zone,
+            rack: node.rack,
             joined_at: node.joined_at.to_rfc3339(),
             last_heartbeat: node.last_heartbeat.to_rfc3339(),
             version: node.version,
@@ -73,6 +80,16 @@ pub struct NodesListResponse {
     pub nodes: Vec<NodeInfoResponse>,
     pub total: usize,
     pub healthy: usize,
+    pub draining: usize,
+    /// Data movement after the last membership change
+    pub rebalance: RebalanceProgress,
+}
+
+/// Result of a node drain, activation or add
+#[derive(Debug, Serialize)]
+pub struct NodeOperationResponse {
+    pub node: NodeInfoResponse,
+    pub rebalance: RebalanceProgress,
 }
 
 /// Replication rule response
@@ -84,6 +101,7 @@ pub struct ReplicationRuleResponse {
     pub destination_bucket: String,
     pub target_nodes: Vec<String>,
     pub prefix_filter: Option<String>,
+    pub bucket_tag_filters: HashMap<String, String>,
     pub mode: String,
     pub priority: i32,
     pub replicate_deletes: bool,
@@ -101,6 +119,7 @@ impl From<ReplicationRule> for ReplicationRuleResponse {
             destination_bucket: rule.destination_bucket,
             target_nodes: rule.target_nodes,
             prefix_filter: rule.prefix_filter,
+            bucket_tag_filters: rule.bucket_tag_filters,
             mode: format!("{:?}", rule.mode).to_lowercase(),
             priority: rule.priority,
             replicate_deletes: rule.replicate_deletes,
@@ -128,6 +147,20 @@ pub struct ReplicatorStatsResponse {
     pub in_progress: u64,
     pub bytes_replicated: u64,
     pub avg_latency_ms: f64,
+    pub hints_queued: u64,
+    pub hints_stored: u64,
+    pub hints_replayed: u64,
+    pub hints_expired: u64,
+}
+
+/// Bucket consistency response
+#[derive(Debug, Serialize)]
+pub struct BucketConsistencyResponse {
+    pub bucket: String,
+    /// Level set on the bucket, if any
+    pub level: Option<ConsistencyLevel>,
+    /// Level used for requests without an `x-hafiz-consistency` header
+    pub effective: ConsistencyLevel,
 }
 
 // ============================================================================
@@ -141,6 +174,8 @@ pub struct CreateReplicationRuleRequest {
     pub destination_bucket: Option<String>,
     pub target_nodes: Option<Vec<String>>,
     pub prefix_filter: Option<String>,
+    /// Only replicate while the source bucket carries all of these tags
+    pub bucket_tag_filters: Option<HashMap<String, String>>,
     pub mode: Option<String>,
     pub priority: Option<i32>,
     pub replicate_deletes: Option<bool>,
@@ -158,11 +193,25 @@ pub struct UpdateReplicationRuleRequest {
     pub replicate_deletes: Option<bool>,
 }
 
-/// Drain node request
+/// Add node request
+#[derive(Debug, Deserialize)]
+pub struct AddNodeRequest {
+    /// Cluster endpoint of the node, e.g. `http://node-2:9001`
+    pub endpoint: String,
+}
+
+/// Decommission node query
+#[derive(Debug, Deserialize)]
+pub struct DecommissionQuery {
+    /// Skip the drain and reachability checks
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// Set bucket consistency request
 #[derive(Debug, Deserialize)]
-pub struct DrainNodeRequest {
-    pub graceful: Option<bool>,
-    pub timeout_secs: Option<u64>,
+pub struct SetBucketConsistencyRequest {
+    pub level: ConsistencyLevel,
 }
 
 // ============================================================================
@@ -208,12 +257,15 @@ pub async fn list_cluster_nodes(
     nodes.insert(0, cluster.local_node().into());
 
     let healthy = nodes.iter().filter(|n| n.status == "healthy").count();
+    let draining = nodes.iter().filter(|n| n.status == "draining").count();
     let total = nodes.len();
 
     Ok(Json(NodesListResponse {
         nodes,
         total,
         healthy,
+        draining,
+        rebalance: cluster.placement().progress(),
     }))
 }
 
@@ -241,58 +293,80 @@ pub async fn get_cluster_node(
     Ok(Json(node.into()))
 }
 
+/// POST /api/v1/cluster/nodes
+/// Add a node to the cluster, or re-add a decommissioned one
+pub async fn add_cluster_node(
+    State(state): State<AppState>,
+    Json(request): Json<AddNodeRequest>,
+) -> Result<Json<NodeOperationResponse>, (StatusCode, String)> {
+    let cluster = state.cluster.as_ref().ok_or_else(|| {
+        (StatusCode::SERVICE_UNAVAILABLE, "Cluster mode not enabled".to_string())
+    })?;
+
+    let node = cluster
+        .add_node(&request.endpoint)
+        .await
+        .map_err(node_error)?;
+
+    Ok(Json(NodeOperationResponse {
+        node: node.into(),
+        rebalance: cluster.placement().progress(),
+    }))
+}
+
 /// POST /api/v1/cluster/nodes/:node_id/drain
-/// Drain a node (prepare for maintenance)
+/// Drain a node: stop placing new data on it and move its data to the other nodes
 pub async fn drain_cluster_node(
     State(state): State<AppState>,
     Path(node_id): Path<String>,
-    Json(request): Json<DrainNodeRequest>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+) -> Result<Json<NodeOperationResponse>, (StatusCode, String)> {
     let cluster = state.cluster.as_ref().ok_or_else(|| {
         (StatusCode::SERVICE_UNAVAILABLE, "Cluster mode not enabled".to_string())
     })?;
 
-    // TODO: Implement drain logic
-    // 1. Stop accepting new requests on the node
-    // 2. Wait for in-flight requests to complete
-    // 3. Trigger replication of any pending data
-    // 4. Mark node as draining
+    let node = cluster.drain_node(&node_id).await.map_err(node_error)?;
 
-    Ok(Json(serde_json::json!({
-        "status": "draining",
-        "node_id": node_id,
-        "message": "Node drain initiated"
-    })))
+    Ok(Json(NodeOperationResponse {
+        node: node.into(),
+        rebalance: cluster.placement().progress(),
+    }))
+}
+
+/// POST /api/v1/cluster/nodes/:node_id/activate
+/// Return a drained node to service
+pub async fn activate_cluster_node(
+    State(state): State<AppState>,
+    Path(node_id): Path<String>,
+) -> Result<Json<NodeOperationResponse>, (StatusCode, String)> {
+    let cluster = state.cluster.as_ref().ok_or_else(|| {
+        (StatusCode::SERVICE_UNAVAILABLE, "Cluster mode not enabled".to_string())
+    })?;
+
+    let node = cluster.activate_node(&node_id).await.map_err(node_error)?;
+
+    Ok(Json(NodeOperationResponse {
+        node: node.into(),
+        rebalance: cluster.placement().progress(),
+    }))
 }
 
 /// DELETE /api/v1/cluster/nodes/:node_id
-/// Remove a node from the cluster
+/// Decommission a drained node (`?force=true` skips the drain check)
 pub async fn remove_cluster_node(
     State(state): State<AppState>,
     Path(node_id): Path<String>,
-) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    Query(query): Query<DecommissionQuery>,
+) -> Result<StatusCode, (StatusCode, String)> {
     let cluster = state.cluster.as_ref().ok_or_else(|| {
         (StatusCode::SERVICE_UNAVAILABLE, "Cluster mode not enabled".to_string())
     })?;
 
-    // Don't allow removing the local node via API
-    if cluster.local_node().id == node_id {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            "Cannot remove local node via API".to_string(),
-        ));
-    }
-
-    // TODO: Implement node removal
-    // 1. Notify the node to leave
-    // 2. Remove from cluster membership
-    // 3. Trigger data rebalancing if needed
+    cluster
+        .decommission_node(&node_id, query.force)
+        .await
+        .map_err(node_error)?;
 
-    Ok(Json(serde_json::json!({
-        "status": "removed",
-        "node_id": node_id,
-        "message": "Node removed from cluster"
-    })))
+    Ok(StatusCode::NO_CONTENT)
 }
 
 /// GET /api/v1/cluster/replication/rules
@@ -346,6 +420,9 @@ pub async fn create_replication_rule(
     if let Some(prefix) = request.prefix_filter {
         rule.prefix_filter = Some(prefix);
     }
+    if let Some(filters) = request.bucket_tag_filters {
+        rule.bucket_tag_filters = filters;
+    }
     if let Some(mode) = request.mode {
         rule.mode = match mode.as_str() {
             "sync" => ReplicationMode::Sync,
@@ -423,6 +500,10 @@ pub async fn get_replication_stats(
         in_progress: stats.in_progress,
         bytes_replicated: stats.bytes_replicated,
         avg_latency_ms: stats.avg_latency_ms,
+        hints_queued: stats.hints_queued,
+        hints_stored: stats.hints_stored,
+        hints_replayed: stats.hints_replayed,
+        hints_expired: stats.hints_expired,
     }))
 }
 
@@ -450,3 +531,147 @@ pub async fn cluster_health_check(
         "timestamp": chrono::Utc::now().to_rfc3339()
     })))
 }
+
+/// GET /api/v1/cluster/raft
+/// Raft metadata consensus status for this node
+pub async fn get_raft_status(
+    State(state): State<AppState>,
+) -> Result<Json<hafiz_cluster::RaftStatus>, (StatusCode, String)> {
+    let cluster = state.cluster.as_ref().ok_or_else(|| {
+        (StatusCode::SERVICE_UNAVAILABLE, "Cluster mode not enabled".to_string())
+    })?;
+
+    let raft = cluster.raft().ok_or_else(|| {
+        (StatusCode::NOT_FOUND, "Raft metadata consensus not enabled".to_string())
+    })?;
+
+    Ok(Json(raft.status()))
+}
+
+/// GET /api/v1/cluster/topology
+/// Get the distribution of replicas across zones, racks and nodes
+pub async fn get_cluster_topology(
+    State(state): State<AppState>,
+) -> Result<Json<hafiz_cluster::ClusterTopology>, (StatusCode, String)> {
+    let cluster = state.cluster.as_ref().ok_or_else(|| {
+        (StatusCode::SERVICE_UNAVAILABLE, "Cluster mode not enabled".to_string())
+    })?;
+
+    Ok(Json(cluster.placement().topology()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RepairQuery {
+    /// Limit the comparison to one bucket
+    pub bucket: Option<String>,
+}
+
+/// POST /api/v1/cluster/repair
+/// Run an anti-entropy comparison with all peers now and repair divergent objects
+pub async fn run_cluster_repair(
+    State(state): State<AppState>,
+    Query(query): Query<RepairQuery>,
+) -> Result<Json<hafiz_cluster::RepairReport>, (StatusCode, String)> {
+    let cluster = state.cluster.as_ref().ok_or_else(|| {
+        (StatusCode::SERVICE_UNAVAILABLE, "Cluster mode not enabled".to_string())
+    })?;
+
+    cluster
+        .anti_entropy()
+        .run(query.bucket.as_deref())
+        .await
+        .map(Json)
+        .map_err(|e| match e {
+            hafiz_cluster::ClusterError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            e => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+        })
+}
+
+/// GET /api/v1/cluster/buckets/:name/consistency
+/// Get the consistency level used for a bucket
+pub async fn get_bucket_consistency(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Json<BucketConsistencyResponse>, (StatusCode, String)> {
+    ensure_bucket(&state, &bucket).await?;
+
+    let level = state
+        .metadata
+        .get_bucket_consistency(&bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(consistency_response(&state, bucket, level)))
+}
+
+/// PUT /api/v1/cluster/buckets/:name/consistency
+/// Set the consistency level for a bucket
+pub async fn set_bucket_consistency(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(req): Json<SetBucketConsistencyRequest>,
+) -> Result<Json<BucketConsistencyResponse>, (StatusCode, String)> {
+    ensure_bucket(&state, &bucket).await?;
+
+    state
+        .metadata
+        .put_bucket_consistency(&bucket, req.level)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(Json(consistency_response(&state, bucket, Some(req.level))))
+}
+
+/// DELETE /api/v1/cluster/buckets/:name/consistency
+/// Revert a bucket to the cluster default consistency level
+pub async fn delete_bucket_consistency(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    ensure_bucket(&state, &bucket).await?;
+
+    state
+        .metadata
+        .delete_bucket_consistency(&bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn node_error(error: hafiz_cluster::ClusterError) -> (StatusCode, String) {
+    use hafiz_cluster::ClusterError;
+
+    match error {
+        ClusterError::NodeNotFound(id) => (StatusCode::NOT_FOUND, format!("Node not found: {}", id)),
+        ClusterError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+        ClusterError::JoinRejected(msg) => (StatusCode::FORBIDDEN, msg),
+        e => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    }
+}
+
+async fn ensure_bucket(state: &AppState, bucket: &str) -> Result<(), (StatusCode, String)> {
+    match state.metadata.get_bucket(bucket).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("Bucket not found: {}", bucket))),
+        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+    }
+}
+
+fn consistency_response(
+    state: &AppState,
+    bucket: String,
+    level: Option<ConsistencyLevel>,
+) -> BucketConsistencyResponse {
+    let default = state
+        .cluster
+        .as_ref()
+        .map(|c| c.config().default_consistency_level)
+        .unwrap_or_default();
+
+    BucketConsistencyResponse {
+        bucket,
+        level,
+        effective: level.unwrap_or(default),
+    }
+}
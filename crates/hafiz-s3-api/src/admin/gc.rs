@@ -0,0 +1,94 @@
+//! Garbage collection endpoints
+//!
+//! Cleans up abandoned multipart uploads and the part data they left
+//! behind in storage.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::multipart_expiry::abort_upload;
+use crate::server::AppState;
+
+/// Default age after which an incomplete multipart upload is considered abandoned
+const DEFAULT_MULTIPART_MAX_AGE_HOURS: i64 = 24;
+
+/// GC run parameters
+#[derive(Debug, Deserialize, Default)]
+pub struct GcQuery {
+    /// Abort multipart uploads older than this many hours
+    pub older_than_hours: Option<i64>,
+    /// Report what would be removed without deleting anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// GC run report
+#[derive(Debug, Serialize)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub older_than_hours: i64,
+    pub uploads_aborted: i64,
+    pub parts_deleted: i64,
+    pub bytes_reclaimed: i64,
+    pub errors: Vec<String>,
+}
+
+/// Run garbage collection
+pub async fn run_gc(
+    State(state): State<AppState>,
+    Query(params): Query<GcQuery>,
+) -> Result<Json<GcReport>, (StatusCode, String)> {
+    let older_than_hours = params
+        .older_than_hours
+        .unwrap_or(DEFAULT_MULTIPART_MAX_AGE_HOURS);
+
+    if older_than_hours < 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "older_than_hours must not be negative".to_string(),
+        ));
+    }
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::hours(older_than_hours);
+
+    let uploads = state
+        .metadata
+        .list_stale_multipart_uploads(cutoff)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut report = GcReport {
+        dry_run: params.dry_run,
+        older_than_hours,
+        uploads_aborted: 0,
+        parts_deleted: 0,
+        bytes_reclaimed: 0,
+        errors: Vec::new(),
+    };
+
+    for upload in uploads {
+        match abort_upload(&state, &upload, params.dry_run).await {
+            Ok(reclaimed) => {
+                report.uploads_aborted += 1;
+                report.parts_deleted += reclaimed.parts;
+                report.bytes_reclaimed += reclaimed.bytes;
+            }
+            Err(e) => report.errors.push(format!("{}: {}", upload.upload_id, e)),
+        }
+    }
+
+    info!(
+        "GC {}: {} uploads, {} parts, {} bytes",
+        if params.dry_run { "dry run" } else { "completed" },
+        report.uploads_aborted,
+        report.parts_deleted,
+        report.bytes_reclaimed
+    );
+
+    Ok(Json(report))
+}
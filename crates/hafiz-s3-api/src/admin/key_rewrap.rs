@@ -0,0 +1,45 @@
+//! Master key rotation endpoints
+//!
+//! Start, follow and stop the background run that re-wraps the data
+//! encryption keys of stored objects under the current master key.
+
+use axum::{extract::State, http::StatusCode, Json};
+
+use crate::key_rewrap::KeyRewrapStatus;
+use crate::server::AppState;
+
+fn error(e: hafiz_core::Error) -> (StatusCode, String) {
+    (
+        StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        e.to_string(),
+    )
+}
+
+/// GET /api/v1/encryption/rewrap
+/// Whether a run is in progress, and the progress of the last one
+pub async fn get_key_rewrap_status(
+    State(state): State<AppState>,
+) -> Result<Json<KeyRewrapStatus>, (StatusCode, String)> {
+    state.key_rewrap.status(&state).await.map(Json).map_err(error)
+}
+
+/// POST /api/v1/encryption/rewrap
+/// Start or resume a run in the background; poll GET /encryption/rewrap
+/// for progress
+pub async fn start_key_rewrap(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<KeyRewrapStatus>), (StatusCode, String)> {
+    let status = state.key_rewrap.start(&state).await.map_err(error)?;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+/// POST /api/v1/encryption/rewrap/cancel
+/// Pause the run once the object version in progress is done
+pub async fn cancel_key_rewrap(
+    State(state): State<AppState>,
+) -> Result<Json<KeyRewrapStatus>, (StatusCode, String)> {
+    if !state.key_rewrap.cancel() {
+        return Err((StatusCode::CONFLICT, "No key re-wrap is running".to_string()));
+    }
+    state.key_rewrap.status(&state).await.map(Json).map_err(error)
+}
@@ -25,6 +25,9 @@ use std::sync::Arc;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info};
 
+use crate::ldap_sync::LdapSyncStatus;
+use crate::server::AppState;
+
 /// LDAP state for admin API
 pub struct LdapAdminState {
     pub config: Arc<RwLock<LdapConfig>>,
@@ -322,6 +325,33 @@ pub async fn test_ldap_authenticate(
     Json(ApiResponse::success(response))
 }
 
+/// GET /api/v1/ldap/sync - Get directory sync status and the last report
+pub async fn get_ldap_sync_status(State(state): State<AppState>) -> Json<LdapSyncStatus> {
+    Json(state.ldap_sync.status())
+}
+
+/// POST /api/v1/ldap/sync/run - Start a directory sync in the background
+pub async fn run_ldap_sync(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<LdapSyncStatus>), (StatusCode, String)> {
+    if !state.ldap_sync.is_enabled() {
+        return Err((StatusCode::BAD_REQUEST, "LDAP is not enabled".to_string()));
+    }
+    if state.ldap_sync.is_running() {
+        return Err((StatusCode::CONFLICT, "A directory sync is already running".to_string()));
+    }
+
+    let sync = state.ldap_sync.clone();
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        sync.run(&task_state).await;
+    });
+
+    let mut status = state.ldap_sync.status();
+    status.running = true;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
 // ============================================================================
 // Request/Response Types
 // ============================================================================
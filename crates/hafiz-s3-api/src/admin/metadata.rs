@@ -0,0 +1,77 @@
+//! Metadata export and import endpoints
+//!
+//! Streams the metadata store as a portable JSON-lines dump and loads one
+//! back, for moving between metadata backends and for disaster-recovery
+//! snapshots taken alongside storage snapshots.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use futures::TryStreamExt;
+use hafiz_metadata::repository::DumpSummary;
+use tokio::io::BufReader;
+use tokio_util::io::{ReaderStream, StreamReader};
+use tracing::{error, warn};
+
+use crate::server::AppState;
+
+/// Bytes buffered between the exporting task and the response
+const EXPORT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// GET /api/v1/metadata/export
+/// Stream a dump of the metadata store
+///
+/// The dump is read in one transaction. If the export fails part way the
+/// response ends without the closing record, which import refuses.
+pub async fn export_metadata(State(state): State<AppState>) -> Response {
+    let (mut writer, reader) = tokio::io::duplex(EXPORT_BUFFER_SIZE);
+    let metadata = state.metadata.clone();
+    tokio::spawn(async move {
+        if let Err(e) = metadata.export_dump(&mut writer).await {
+            error!("Metadata export failed: {}", e);
+        }
+    });
+
+    (
+        [
+            (header::CONTENT_TYPE, "application/x-ndjson"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"hafiz-metadata.jsonl\"",
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(reader)),
+    )
+        .into_response()
+}
+
+/// POST /api/v1/metadata/import
+/// Load a dump into the metadata store, replacing records with the same
+/// names; nothing is imported unless the whole dump is valid
+pub async fn import_metadata(
+    State(state): State<AppState>,
+    body: Body,
+) -> Result<Json<DumpSummary>, (StatusCode, String)> {
+    let stream = body
+        .into_data_stream()
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e));
+    let reader = BufReader::new(StreamReader::new(stream));
+
+    let summary = state.metadata.import_dump(reader).await.map_err(|e| {
+        let status = StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        (status, e.to_string())
+    })?;
+
+    // The dump may have replaced any object; index them all again
+    if state.search.is_enabled() {
+        if let Err(e) = state.search.rebuild(state.clone()) {
+            warn!("Could not rebuild the search index after an import: {}", e);
+        }
+    }
+
+    Ok(Json(summary))
+}
@@ -0,0 +1,44 @@
+//! Mirror job endpoints
+//!
+//! Shows the configured mirror jobs with their last reports and starts runs
+//! on demand.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+
+use crate::mirror::MirrorJobStatus;
+use crate::server::AppState;
+
+/// GET /api/v1/mirror
+/// List mirror jobs with their next run and the report of the last one
+pub async fn get_mirror_status(State(state): State<AppState>) -> Json<Vec<MirrorJobStatus>> {
+    Json(state.mirror.status())
+}
+
+/// POST /api/v1/mirror/:name/run
+/// Start a run of a mirror job in the background; poll GET /mirror for the
+/// report
+pub async fn run_mirror_job(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<(StatusCode, Json<MirrorJobStatus>), (StatusCode, String)> {
+    let mut status = state
+        .mirror
+        .job_status(&name)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Mirror job {} not found", name)))?;
+    if status.running {
+        return Err((StatusCode::CONFLICT, format!("Mirror job {} is already running", name)));
+    }
+
+    let mirror = state.mirror.clone();
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        mirror.run(&task_state, &name).await;
+    });
+
+    status.running = true;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
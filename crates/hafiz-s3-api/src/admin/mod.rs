@@ -1,14 +1,38 @@
 //! Admin API routes for Hafiz management
 //!
-//! These endpoints provide administrative access to manage buckets,
-//! users, cluster, LDAP, and view system statistics.
+//! These endpoints provide administrative access to manage accounts,
+//! buckets, users, cluster, LDAP, and view system statistics.
+//!
+//! Every request is authenticated, and each endpoint needs an admin role:
+//! reading needs any role, and changes need the role of their area.
 
+mod access_points;
+mod accounts;
+mod bandwidth;
+mod batch;
+mod buckets;
+mod capture;
 #[cfg(feature = "cluster")]
 mod cluster;
+mod gc;
+mod key_rewrap;
 mod ldap;
+mod metadata;
+mod mirror;
+mod namespace;
+mod object_lock;
 mod presigned;
+mod prune;
+mod roles;
+mod scrub;
+mod search;
+mod sftp;
+mod snapshots;
 mod stats;
+mod storage;
+mod tiering;
 mod users;
+mod warmup;
 mod server;
 
 use axum::{
@@ -17,99 +41,252 @@ use axum::{
     middleware,
 };
 
-use crate::middleware::auth::admin_auth;
+use hafiz_core::types::AdminRole;
+
+use crate::middleware::auth::{admin_auth, admin_role};
 use crate::server::AppState;
 
+pub use access_points::*;
+pub use accounts::*;
+pub use bandwidth::*;
+pub use batch::*;
+pub use buckets::*;
+pub use capture::*;
 #[cfg(feature = "cluster")]
 pub use cluster::*;
+pub use gc::*;
+pub use key_rewrap::*;
 pub use ldap::*;
+pub use metadata::*;
+pub use mirror::*;
+pub use namespace::*;
+pub use object_lock::*;
 pub use presigned::*;
+pub use prune::*;
+pub use roles::*;
+pub use scrub::*;
+pub use search::*;
+pub use sftp::*;
+pub use snapshots::*;
 pub use stats::*;
+pub use storage::*;
+pub use tiering::*;
 pub use users::*;
+pub use warmup::*;
 pub use server::*;
 
-/// Create the admin API router
-pub fn admin_routes() -> Router<AppState> {
+/// Create the admin API router, authenticating every request and checking
+/// the caller's role per endpoint
+pub fn admin_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .merge(viewer_routes())
+        .merge(requiring(AdminRole::BucketOperator, bucket_operator_routes()))
+        .merge(requiring(AdminRole::SecurityAdmin, security_admin_routes()))
+        .merge(requiring(AdminRole::SuperAdmin, super_admin_routes()))
+        .layer(middleware::from_fn_with_state(state, admin_auth))
+}
+
+/// Refuse the routes of `router` to callers whose role doesn't permit `role`
+fn requiring(role: AdminRole, router: Router<AppState>) -> Router<AppState> {
+    router.route_layer(middleware::from_fn_with_state(role, admin_role))
+}
+
+/// Reading, open to every role
+fn viewer_routes() -> Router<AppState> {
     let router = Router::new()
         // Dashboard & Stats
         .route("/stats", get(get_dashboard_stats))
         .route("/stats/storage", get(get_storage_stats))
+        .route("/stats/cost-allocation", get(get_cost_allocation))
 
         // Server info
         .route("/server/info", get(get_server_info))
         .route("/server/health", get(health_check))
+        .route("/roles/me", get(whoami))
 
-        // Bucket management (enhanced versions)
+        // Buckets
         .route("/buckets", get(list_buckets_detailed))
         .route("/buckets/:name/stats", get(get_bucket_stats))
+        .route("/buckets/:name/backend", get(get_bucket_backend))
+        .route("/buckets/:name/compression", get(get_bucket_compression))
+        .route("/buckets/:name/bandwidth", get(get_bucket_bandwidth))
+        .route("/buckets/:name/tiering", get(get_bucket_tiering))
+        .route("/buckets/:name/namespace", get(get_bucket_namespace))
+        .route("/buckets/:name/snapshots", get(list_bucket_snapshots))
+        .route("/buckets/:name/retention", get(list_retained_objects))
+        .route("/storage/backends", get(list_storage_backends))
 
-        // User management
+        // Accounts and users
+        .route("/accounts", get(list_accounts))
+        .route("/accounts/:id", get(get_account))
         .route("/users", get(list_users))
-        .route("/users", post(create_user))
         .route("/users/:access_key", get(get_user))
-        .route("/users/:access_key", delete(delete_user))
-        .route("/users/:access_key/enable", post(enable_user))
-        .route("/users/:access_key/disable", post(disable_user))
-        .route("/users/:access_key/keys", post(rotate_keys))
+        .route("/users/:access_key/keys", get(list_user_keys))
+        .route("/users/:access_key/bandwidth", get(get_user_bandwidth))
+        .route("/bandwidth", get(list_bandwidth_limits))
+        .route("/sftp/users", get(list_sftp_users))
+        .route("/sftp/users/:username", get(get_sftp_user))
+        .route("/search", get(search_objects))
+        .route("/search/status", get(get_search_status))
 
-        // Pre-signed URLs
-        .route("/presigned", post(generate_presigned))
-        .route("/presigned/download/:bucket/*key", post(generate_presigned_download))
-        .route("/presigned/upload/:bucket/*key", post(generate_presigned_upload));
+        // Maintenance status
+        .route("/scrub", get(get_scrub_status))
+        .route("/mirror", get(get_mirror_status))
+        .route("/tiering", get(get_tiering_status))
+        .route("/ldap/sync", get(get_ldap_sync_status))
+        .route("/encryption/rewrap", get(get_key_rewrap_status))
+        .route("/batch/jobs", get(list_batch_jobs))
+        .route("/batch/jobs/:id", get(get_batch_job))
+        .route("/prune/jobs", get(list_prune_jobs))
+        .route("/prune/jobs/:id", get(get_prune_job))
+        .route("/cache/warmup", get(list_warmup_jobs))
+        .route("/cache/warmup/:id", get(get_warmup_job))
+        .route("/capture", get(get_capture))
+        .route("/access-points", get(list_access_points))
+        .route("/access-points/:name", get(get_access_point))
+        .route("/object-lock/governance-bypasses", get(list_governance_bypasses))
+        .route("/snapshots", get(list_snapshots))
+        .route("/snapshots/:id", get(get_snapshot));
 
-    // Add cluster routes if feature is enabled
     #[cfg(feature = "cluster")]
     let router = router
         .route("/cluster/status", get(get_cluster_status))
         .route("/cluster/health", get(cluster_health_check))
+        .route("/cluster/raft", get(get_raft_status))
+        .route("/cluster/topology", get(get_cluster_topology))
         .route("/cluster/nodes", get(list_cluster_nodes))
         .route("/cluster/nodes/:node_id", get(get_cluster_node))
-        .route("/cluster/nodes/:node_id/drain", post(drain_cluster_node))
-        .route("/cluster/nodes/:node_id", delete(remove_cluster_node))
         .route("/cluster/replication/rules", get(list_replication_rules))
-        .route("/cluster/replication/rules", post(create_replication_rule))
         .route("/cluster/replication/rules/:rule_id", get(get_replication_rule))
-        .route("/cluster/replication/rules/:rule_id", delete(delete_replication_rule))
-        .route("/cluster/replication/stats", get(get_replication_stats));
+        .route("/cluster/replication/stats", get(get_replication_stats))
+        .route("/cluster/buckets/:name/consistency", get(get_bucket_consistency));
 
-    router.layer(middleware::from_fn(admin_auth))
+    router
 }
 
-/// Admin API without authentication (for development/testing)
-pub fn admin_routes_no_auth() -> Router<AppState> {
+/// Changes to buckets and their data
+fn bucket_operator_routes() -> Router<AppState> {
     let router = Router::new()
-        .route("/stats", get(get_dashboard_stats))
-        .route("/stats/storage", get(get_storage_stats))
-        .route("/server/info", get(get_server_info))
-        .route("/server/health", get(health_check))
-        .route("/buckets", get(list_buckets_detailed))
-        .route("/buckets/:name/stats", get(get_bucket_stats))
-        .route("/users", get(list_users))
+        .route("/buckets/:name", delete(delete_bucket))
+        .route(
+            "/buckets/:name/backend",
+            put(set_bucket_backend).delete(delete_bucket_backend),
+        )
+        .route(
+            "/buckets/:name/compression",
+            put(set_bucket_compression).delete(delete_bucket_compression),
+        )
+        .route(
+            "/buckets/:name/bandwidth",
+            put(set_bucket_bandwidth).delete(delete_bucket_bandwidth),
+        )
+        .route(
+            "/buckets/:name/tiering",
+            put(set_bucket_tiering).delete(delete_bucket_tiering),
+        )
+        .route(
+            "/buckets/:name/namespace",
+            put(set_bucket_namespace).delete(delete_bucket_namespace),
+        )
+        .route("/buckets/:name/snapshots", post(create_bucket_snapshot))
+        .route("/buckets/:name/retention/extend", post(extend_retention))
+        .route("/search/rebuild", post(rebuild_search_index))
+        .route("/batch/jobs", post(create_batch_job))
+        .route("/batch/jobs/:id/cancel", post(cancel_batch_job))
+        .route("/prune/jobs", post(create_prune_job))
+        .route("/prune/jobs/:id/cancel", post(cancel_prune_job))
+        .route("/cache/warmup", post(create_warmup_job))
+        .route("/cache/warmup/:id/cancel", post(cancel_warmup_job))
+        .route("/capture", post(start_capture))
+        .route("/capture/stop", post(stop_capture))
+        .route("/access-points", post(create_access_point))
+        .route("/access-points/:name", delete(delete_access_point))
+        .route(
+            "/access-points/:name/policy",
+            put(put_access_point_policy).delete(delete_access_point_policy),
+        )
+        .route("/snapshots/:id", delete(delete_snapshot))
+        .route("/snapshots/:id/restore", post(restore_snapshot))
+
+        // Pre-signed URLs
+        .route("/presigned", post(generate_presigned))
+        .route("/presigned/download/:bucket/*key", post(generate_presigned_download))
+        .route("/presigned/upload/:bucket/*key", post(generate_presigned_upload));
+
+    #[cfg(feature = "cluster")]
+    let router = router.route(
+        "/cluster/buckets/:name/consistency",
+        put(set_bucket_consistency).delete(delete_bucket_consistency),
+    );
+
+    router
+}
+
+/// Changes to users, their keys and logins, and admin roles
+fn security_admin_routes() -> Router<AppState> {
+    Router::new()
         .route("/users", post(create_user))
-        .route("/users/:access_key", get(get_user))
         .route("/users/:access_key", delete(delete_user))
         .route("/users/:access_key/enable", post(enable_user))
         .route("/users/:access_key/disable", post(disable_user))
+        .route("/users/:access_key/account", put(set_user_account))
         .route("/users/:access_key/keys", post(rotate_keys))
-        // Pre-signed URLs
-        .route("/presigned", post(generate_presigned))
-        .route("/presigned/download/:bucket/*key", post(generate_presigned_download))
-        .route("/presigned/upload/:bucket/*key", post(generate_presigned_upload));
+        .route("/users/:access_key/keys/:key_id", delete(retire_user_key))
+        .route(
+            "/users/:access_key/bandwidth",
+            put(set_user_bandwidth).delete(delete_user_bandwidth),
+        )
+        .route(
+            "/sftp/users/:username",
+            put(put_sftp_user).delete(delete_sftp_user),
+        )
+        .route(
+            "/sftp/users/:username/keys",
+            post(add_sftp_key).delete(delete_sftp_key),
+        )
+        .route("/ldap/sync/run", post(run_ldap_sync))
+        .route("/roles", get(list_admin_roles))
+        .route(
+            "/roles/:access_key",
+            put(put_admin_role).delete(delete_admin_role),
+        )
+}
+
+/// Accounts, server maintenance, cluster membership and metadata dumps
+fn super_admin_routes() -> Router<AppState> {
+    let router = Router::new()
+        .route("/accounts", post(create_account))
+        .route(
+            "/accounts/:id",
+            put(update_account).delete(delete_account),
+        )
+        .route("/accounts/:id/suspend", post(suspend_account))
+        .route("/accounts/:id/resume", post(resume_account))
+        .route("/accounts/:id/rotate-key", post(rotate_account_key))
+        .route("/encryption/rewrap", post(start_key_rewrap))
+        .route("/encryption/rewrap/cancel", post(cancel_key_rewrap))
+        .route("/gc/run", post(run_gc))
+        .route("/scrub/run", post(run_scrub))
+        .route("/mirror/:name/run", post(run_mirror_job))
+        .route("/tiering/run", post(run_tiering))
+        .route("/metadata/export", get(export_metadata))
+        .route("/metadata/import", post(import_metadata));
 
-    // Add cluster routes if feature is enabled
     #[cfg(feature = "cluster")]
     let router = router
-        .route("/cluster/status", get(get_cluster_status))
-        .route("/cluster/health", get(cluster_health_check))
-        .route("/cluster/nodes", get(list_cluster_nodes))
-        .route("/cluster/nodes/:node_id", get(get_cluster_node))
+        .route("/cluster/nodes", post(add_cluster_node))
+        .route(
+            "/cluster/nodes/:node_id",
+            delete(remove_cluster_node),
+        )
         .route("/cluster/nodes/:node_id/drain", post(drain_cluster_node))
-        .route("/cluster/nodes/:node_id", delete(remove_cluster_node))
-        .route("/cluster/replication/rules", get(list_replication_rules))
+        .route("/cluster/nodes/:node_id/activate", post(activate_cluster_node))
         .route("/cluster/replication/rules", post(create_replication_rule))
-        .route("/cluster/replication/rules/:rule_id", get(get_replication_rule))
-        .route("/cluster/replication/rules/:rule_id", delete(delete_replication_rule))
-        .route("/cluster/replication/stats", get(get_replication_stats));
+        .route(
+            "/cluster/replication/rules/:rule_id",
+            delete(delete_replication_rule),
+        )
+        .route("/cluster/repair", post(run_cluster_repair));
 
     router
 }
@@ -0,0 +1,89 @@
+//! Namespace mode endpoints
+//!
+//! Switches buckets between hierarchical and flat listings. A flat bucket
+//! ignores the delimiter of ListObjects, ListObjectsV2 and
+//! ListObjectVersions and never returns CommonPrefixes, so a page is read
+//! in a single query however the keys are laid out.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use hafiz_core::types::NamespaceMode;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::server::AppState;
+
+/// Bucket namespace request and response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BucketNamespace {
+    #[serde(default, skip_deserializing)]
+    pub bucket: String,
+    pub mode: NamespaceMode,
+}
+
+fn internal_error(e: hafiz_core::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// GET /api/v1/buckets/:name/namespace
+/// Get the namespace mode of a bucket
+pub async fn get_bucket_namespace(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Json<BucketNamespace>, (StatusCode, String)> {
+    let mode = state
+        .metadata
+        .get_bucket_namespace(&bucket)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(BucketNamespace { bucket, mode }))
+}
+
+/// PUT /api/v1/buckets/:name/namespace
+/// Set the namespace mode of a bucket; takes effect on the next listing
+pub async fn set_bucket_namespace(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(request): Json<BucketNamespace>,
+) -> Result<Json<BucketNamespace>, (StatusCode, String)> {
+    let exists = state
+        .metadata
+        .get_bucket(&bucket)
+        .await
+        .map_err(internal_error)?
+        .is_some();
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, format!("Bucket {} not found", bucket)));
+    }
+
+    state
+        .metadata
+        .put_bucket_namespace(&bucket, request.mode)
+        .await
+        .map_err(internal_error)?;
+    info!("Bucket {} uses a {} namespace", bucket, request.mode);
+
+    Ok(Json(BucketNamespace {
+        bucket,
+        mode: request.mode,
+    }))
+}
+
+/// DELETE /api/v1/buckets/:name/namespace
+/// Revert a bucket to hierarchical listings
+pub async fn delete_bucket_namespace(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .metadata
+        .delete_bucket_namespace(&bucket)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
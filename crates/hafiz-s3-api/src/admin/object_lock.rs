@@ -0,0 +1,298 @@
+//! Object Lock review endpoints
+//!
+//! List the objects of a bucket under retention, extend their retention in
+//! bulk, and read the log of deletes and overwrites that bypassed GOVERNANCE
+//! retention. Retention is only ever extended here: a retain-until date
+//! already later than the requested one is left alone, and so is retention
+//! that has expired.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use hafiz_core::types::{ObjectRetention, RetentionMode};
+use hafiz_metadata::repository::{GovernanceBypassEntry, RetentionRecord};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::consensus;
+use crate::server::AppState;
+
+/// Entries returned per page unless the request sets `limit`
+const DEFAULT_LIMIT: u32 = 1000;
+
+/// Retention records read per metadata page during a bulk extension
+const EXTEND_BATCH_SIZE: i32 = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct RetentionListQuery {
+    pub prefix: Option<String>,
+    /// Also list retention that has expired
+    #[serde(default)]
+    pub include_expired: bool,
+    /// Resume after this key...
+    pub key_marker: Option<String>,
+    /// ...and version ID, as returned in the previous page
+    pub version_id_marker: Option<String>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetentionInfo {
+    pub key: String,
+    /// Absent for retention set without a version ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_id: Option<String>,
+    pub mode: RetentionMode,
+    pub retain_until_date: String,
+    pub expired: bool,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RetentionList {
+    pub bucket: String,
+    pub retentions: Vec<RetentionInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_key_marker: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_version_id_marker: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExtendRetentionRequest {
+    /// New retain-until date
+    pub retain_until_date: DateTime<Utc>,
+    /// Only extend the retention of keys under this prefix
+    pub prefix: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct ExtendRetentionReport {
+    pub bucket: String,
+    pub extended: u64,
+    /// Retention already lasting until the requested date or later
+    pub unchanged: u64,
+    /// Expired retention, which is not renewed
+    pub expired: u64,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GovernanceBypassQuery {
+    pub bucket: Option<String>,
+    /// Resume with entries older than this ID
+    pub before: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GovernanceBypassList {
+    pub entries: Vec<GovernanceBypassEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_before: Option<i64>,
+}
+
+fn to_error(e: hafiz_core::Error) -> (StatusCode, String) {
+    (
+        StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        e.to_string(),
+    )
+}
+
+async fn require_bucket(state: &AppState, bucket: &str) -> Result<(), (StatusCode, String)> {
+    match state.metadata.get_bucket(bucket).await.map_err(to_error)? {
+        Some(_) => Ok(()),
+        None => Err((StatusCode::NOT_FOUND, format!("Bucket not found: {}", bucket))),
+    }
+}
+
+fn parse_retention(record: &RetentionRecord) -> Result<ObjectRetention, String> {
+    ObjectRetention::from_xml(&record.retention_xml)
+        .map_err(|e| format!("{} ({}): {}", record.key, record.version_id, e))
+}
+
+/// GET /api/v1/buckets/:name/retention
+/// List the objects of a bucket under retention
+pub async fn list_retained_objects(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Query(params): Query<RetentionListQuery>,
+) -> Result<Json<RetentionList>, (StatusCode, String)> {
+    require_bucket(&state, &bucket).await?;
+
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, DEFAULT_LIMIT) as usize;
+    let mut after = params
+        .key_marker
+        .map(|key| (key, params.version_id_marker.unwrap_or_default()));
+    let mut retentions = Vec::new();
+    let mut truncated = false;
+
+    'pages: loop {
+        let page = state
+            .metadata
+            .list_object_retentions(
+                &bucket,
+                params.prefix.as_deref(),
+                after.as_ref().map(|(k, v)| (k.as_str(), v.as_str())),
+                limit as i32,
+            )
+            .await
+            .map_err(to_error)?;
+        let full_page = page.len() == limit;
+
+        for record in page {
+            after = Some((record.key.clone(), record.version_id.clone()));
+            // Unreadable records are skipped; the S3 API ignores them too
+            let Ok(retention) = parse_retention(&record) else {
+                continue;
+            };
+            let expired = retention.is_expired();
+            if expired && !params.include_expired {
+                continue;
+            }
+            retentions.push(RetentionInfo {
+                key: record.key,
+                version_id: (!record.version_id.is_empty()).then_some(record.version_id),
+                mode: retention.mode,
+                retain_until_date: retention.retain_until_date,
+                expired,
+                updated_at: record.updated_at,
+            });
+            if retentions.len() == limit {
+                truncated = true;
+                break 'pages;
+            }
+        }
+
+        if !full_page {
+            break;
+        }
+    }
+
+    let (next_key_marker, next_version_id_marker) = match after {
+        Some((key, version_id)) if truncated => (Some(key), Some(version_id)),
+        _ => (None, None),
+    };
+    Ok(Json(RetentionList {
+        bucket,
+        retentions,
+        next_key_marker,
+        next_version_id_marker,
+    }))
+}
+
+/// POST /api/v1/buckets/:name/retention/extend
+/// Extend the retention of every object under a prefix to a later date
+pub async fn extend_retention(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(req): Json<ExtendRetentionRequest>,
+) -> Result<Json<ExtendRetentionReport>, (StatusCode, String)> {
+    require_bucket(&state, &bucket).await?;
+    if req.retain_until_date <= Utc::now() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "retain_until_date must be in the future".to_string(),
+        ));
+    }
+
+    let mut report = ExtendRetentionReport {
+        bucket: bucket.clone(),
+        ..Default::default()
+    };
+    let mut after: Option<(String, String)> = None;
+
+    loop {
+        let page = state
+            .metadata
+            .list_object_retentions(
+                &bucket,
+                req.prefix.as_deref(),
+                after.as_ref().map(|(k, v)| (k.as_str(), v.as_str())),
+                EXTEND_BATCH_SIZE,
+            )
+            .await
+            .map_err(to_error)?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = Some((last.key.clone(), last.version_id.clone()));
+
+        for record in &page {
+            let current = match parse_retention(record) {
+                Ok(retention) => retention,
+                Err(e) => {
+                    report.errors.push(e);
+                    continue;
+                }
+            };
+            if current.is_expired() {
+                report.expired += 1;
+                continue;
+            }
+            if current.retain_until().is_some_and(|until| until >= req.retain_until_date) {
+                report.unchanged += 1;
+                continue;
+            }
+
+            let extended = ObjectRetention::new(current.mode, req.retain_until_date);
+            let stored = match extended.to_xml() {
+                Ok(xml) => consensus::put_object_retention(
+                    &state,
+                    &bucket,
+                    &record.key,
+                    (!record.version_id.is_empty()).then_some(record.version_id.as_str()),
+                    &xml,
+                )
+                .await
+                .map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+            match stored {
+                Ok(()) => report.extended += 1,
+                Err(e) => report.errors.push(format!("{}: {}", record.key, e)),
+            }
+        }
+
+        if page.len() < EXTEND_BATCH_SIZE as usize {
+            break;
+        }
+    }
+
+    info!(
+        "Retention in bucket {} extended to {}: {} extended, {} unchanged, {} expired, {} errors",
+        bucket,
+        req.retain_until_date.to_rfc3339(),
+        report.extended,
+        report.unchanged,
+        report.expired,
+        report.errors.len()
+    );
+    Ok(Json(report))
+}
+
+/// GET /api/v1/object-lock/governance-bypasses
+/// List deletes and overwrites that bypassed GOVERNANCE retention, newest first
+pub async fn list_governance_bypasses(
+    State(state): State<AppState>,
+    Query(params): Query<GovernanceBypassQuery>,
+) -> Result<Json<GovernanceBypassList>, (StatusCode, String)> {
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, DEFAULT_LIMIT);
+    let entries = state
+        .metadata
+        .list_governance_bypasses(params.bucket.as_deref(), params.before, limit as i32)
+        .await
+        .map_err(to_error)?;
+    let next_before = entries
+        .last()
+        .filter(|_| entries.len() == limit as usize)
+        .map(|entry| entry.id);
+
+    Ok(Json(GovernanceBypassList {
+        entries,
+        next_before,
+    }))
+}
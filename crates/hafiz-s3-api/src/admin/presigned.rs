@@ -30,6 +30,14 @@ pub struct GeneratePresignedUrlRequest {
     pub content_type: Option<String>,
     /// Version ID for versioned objects
     pub version_id: Option<String>,
+    /// Largest body a PUT may send, in bytes
+    pub max_content_length: Option<u64>,
+    /// Require `content_type` on PUT
+    #[serde(default)]
+    pub pin_content_type: bool,
+    /// Make the URL usable only once
+    #[serde(default)]
+    pub single_use: bool,
 }
 
 fn default_expires() -> u64 {
@@ -72,6 +80,22 @@ pub async fn generate_presigned(
         (StatusCode::BAD_REQUEST, e)
     })?;
 
+    // Length and content type can only be enforced on uploads
+    if method != PresignedMethod::Put
+        && (request.max_content_length.is_some() || request.pin_content_type)
+    {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Content constraints only apply to PUT".to_string(),
+        ));
+    }
+    if request.pin_content_type && request.content_type.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "A pinned content type must be set".to_string(),
+        ));
+    }
+
     // Check if bucket exists
     state.metadata.get_bucket(&request.bucket).await.map_err(|_| {
         (StatusCode::NOT_FOUND, format!("Bucket not found: {}", request.bucket))
@@ -87,6 +111,9 @@ pub async fn generate_presigned(
         content_md5: None,
         signed_headers: None,
         version_id: request.version_id,
+        max_content_length: request.max_content_length,
+        pin_content_type: request.pin_content_type,
+        single_use: request.single_use,
     };
 
     // Determine the endpoint
@@ -137,6 +164,9 @@ pub async fn generate_presigned_download(
         expires_in: 3600,
         content_type: None,
         version_id: None,
+        max_content_length: None,
+        pin_content_type: false,
+        single_use: false,
     };
     generate_presigned(State(state), Json(request)).await
 }
@@ -154,6 +184,9 @@ pub async fn generate_presigned_upload(
         expires_in: 3600,
         content_type: None,
         version_id: None,
+        max_content_length: None,
+        pin_content_type: false,
+        single_use: false,
     };
     generate_presigned(State(state), Json(request)).await
 }
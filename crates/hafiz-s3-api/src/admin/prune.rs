@@ -0,0 +1,77 @@
+//! Version pruning endpoints
+//!
+//! Start jobs that remove old noncurrent versions and orphaned delete
+//! markers of a bucket, follow their progress and cancel them.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Serialize;
+
+use crate::prune::{PruneJobStatus, PruneRequest};
+use crate::server::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct PruneJobList {
+    pub jobs: Vec<PruneJobStatus>,
+}
+
+/// GET /api/v1/prune/jobs
+/// List jobs, newest first
+pub async fn list_prune_jobs(State(state): State<AppState>) -> Json<PruneJobList> {
+    Json(PruneJobList {
+        jobs: state.prune.list(),
+    })
+}
+
+/// POST /api/v1/prune/jobs
+/// Start a job in the background; poll GET /prune/jobs/:id for progress
+pub async fn create_prune_job(
+    State(state): State<AppState>,
+    Json(request): Json<PruneRequest>,
+) -> Result<(StatusCode, Json<PruneJobStatus>), (StatusCode, String)> {
+    let status = state.prune.submit(&state, request).await.map_err(|e| {
+        (
+            StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            e.to_string(),
+        )
+    })?;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+/// GET /api/v1/prune/jobs/:id
+pub async fn get_prune_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<PruneJobStatus>, (StatusCode, String)> {
+    state
+        .prune
+        .get(&id)
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Prune job {} not found", id)))
+}
+
+/// POST /api/v1/prune/jobs/:id/cancel
+/// Stop a job once the key in progress is done
+pub async fn cancel_prune_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<PruneJobStatus>, (StatusCode, String)> {
+    let status = state
+        .prune
+        .get(&id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Prune job {} not found", id)))?;
+    if status.state.is_finished() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Prune job {} has already finished", id),
+        ));
+    }
+    state
+        .prune
+        .cancel(&id)
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Prune job {} not found", id)))
+}
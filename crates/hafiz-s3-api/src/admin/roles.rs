@@ -0,0 +1,146 @@
+//! Admin role endpoints
+//!
+//! Gives users one of the admin roles, deciding which admin endpoints they
+//! may use. Admins (users with the admin flag, like the root user) are
+//! always super admins. Only a super admin may grant or take away the
+//! super admin role.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use chrono::Utc;
+use hafiz_core::types::{AdminRole, AdminRoleGrant};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::middleware::AdminPrincipal;
+use crate::server::AppState;
+
+#[derive(Debug, Deserialize)]
+pub struct PutAdminRoleRequest {
+    pub role: AdminRole,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WhoAmIResponse {
+    pub name: String,
+    pub role: AdminRole,
+}
+
+/// GET /api/v1/roles
+/// List the admin roles given to users
+pub async fn list_admin_roles(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<AdminRoleGrant>>, (StatusCode, String)> {
+    let grants = state
+        .metadata
+        .list_admin_roles()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(grants))
+}
+
+/// GET /api/v1/roles/me
+/// The caller and its role, so clients can offer what it may do
+pub async fn whoami(Extension(caller): Extension<AdminPrincipal>) -> Json<WhoAmIResponse> {
+    Json(WhoAmIResponse {
+        name: caller.name,
+        role: caller.role,
+    })
+}
+
+/// PUT /api/v1/roles/:access_key
+/// Give a user an admin role, replacing the one it had
+pub async fn put_admin_role(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Path(access_key): Path<String>,
+    Json(req): Json<PutAdminRoleRequest>,
+) -> Result<Json<AdminRoleGrant>, (StatusCode, String)> {
+    let cred = state
+        .metadata
+        .get_credentials(&access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("User {} not found", access_key)))?;
+    if AdminRole::from_policies(&cred.policies).is_some() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("User {} is an admin and always a super admin", access_key),
+        ));
+    }
+
+    let current = current_role(&state, &access_key).await?;
+    check_may_change(&caller, current)?;
+    check_may_change(&caller, Some(req.role))?;
+
+    let grant = AdminRoleGrant {
+        access_key,
+        role: req.role,
+        granted_at: Utc::now(),
+    };
+    state
+        .metadata
+        .put_admin_role(&grant)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!(
+        "{} gave {} the admin role {}",
+        caller.name, grant.access_key, grant.role
+    );
+    Ok(Json(grant))
+}
+
+/// DELETE /api/v1/roles/:access_key
+/// Take a user's admin role away
+pub async fn delete_admin_role(
+    State(state): State<AppState>,
+    Extension(caller): Extension<AdminPrincipal>,
+    Path(access_key): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let current = current_role(&state, &access_key).await?;
+    if current.is_none() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("User {} has no admin role", access_key),
+        ));
+    }
+    check_may_change(&caller, current)?;
+
+    state
+        .metadata
+        .delete_admin_role(&access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!("{} removed the admin role of {}", caller.name, access_key);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn current_role(
+    state: &AppState,
+    access_key: &str,
+) -> Result<Option<AdminRole>, (StatusCode, String)> {
+    state
+        .metadata
+        .get_admin_role(access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Only super admins may grant the super admin role or take it away
+fn check_may_change(
+    caller: &AdminPrincipal,
+    role: Option<AdminRole>,
+) -> Result<(), (StatusCode, String)> {
+    if role == Some(AdminRole::SuperAdmin) && caller.role != AdminRole::SuperAdmin {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Only a super admin may grant or remove the super_admin role".to_string(),
+        ));
+    }
+    Ok(())
+}
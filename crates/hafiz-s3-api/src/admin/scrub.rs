@@ -0,0 +1,57 @@
+//! Integrity scrub endpoints
+//!
+//! Shows the last scrub report and starts runs on demand.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Deserialize;
+
+use crate::scrub::ScrubStatus;
+use crate::server::AppState;
+
+#[derive(Debug, Deserialize, Default)]
+pub struct ScrubQuery {
+    /// Limit the run to one bucket
+    pub bucket: Option<String>,
+}
+
+/// GET /api/v1/scrub
+/// Get scrubber status and the report of the last run
+pub async fn get_scrub_status(State(state): State<AppState>) -> Json<ScrubStatus> {
+    Json(state.scrubber.status())
+}
+
+/// POST /api/v1/scrub/run
+/// Start a scrub in the background; poll GET /scrub for the report
+pub async fn run_scrub(
+    State(state): State<AppState>,
+    Query(query): Query<ScrubQuery>,
+) -> Result<(StatusCode, Json<ScrubStatus>), (StatusCode, String)> {
+    if let Some(bucket) = &query.bucket {
+        let exists = state
+            .metadata
+            .get_bucket(bucket)
+            .await
+            .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+            .is_some();
+        if !exists {
+            return Err((StatusCode::NOT_FOUND, format!("Bucket {} not found", bucket)));
+        }
+    }
+    if state.scrubber.is_running() {
+        return Err((StatusCode::CONFLICT, "A scrub is already running".to_string()));
+    }
+
+    let scrubber = state.scrubber.clone();
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        scrubber.run(&task_state, query.bucket.as_deref()).await;
+    });
+
+    let mut status = state.scrubber.status();
+    status.running = true;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
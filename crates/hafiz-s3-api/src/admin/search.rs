@@ -0,0 +1,54 @@
+//! Object search endpoints
+//!
+//! Queries the full-text index of object keys, user metadata and tags, and
+//! rebuilds it from the metadata store.
+
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Serialize;
+use tracing::info;
+
+use crate::search::{SearchHit, SearchQuery, SearchStatus};
+use crate::server::AppState;
+
+#[derive(Debug, Serialize)]
+pub struct SearchResponse {
+    pub query: String,
+    pub hits: Vec<SearchHit>,
+}
+
+fn search_error(e: hafiz_core::Error) -> (StatusCode, String) {
+    (
+        StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        e.to_string(),
+    )
+}
+
+/// GET /api/v1/search?q=...&bucket=...&prefix=...&limit=...
+/// Find objects by key, user metadata and tags, best matches first
+pub async fn search_objects(
+    State(state): State<AppState>,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<SearchResponse>, (StatusCode, String)> {
+    let q = query.q.clone();
+    let hits = state.search.search(query).await.map_err(search_error)?;
+    Ok(Json(SearchResponse { query: q, hits }))
+}
+
+/// GET /api/v1/search/status
+pub async fn get_search_status(State(state): State<AppState>) -> Json<SearchStatus> {
+    Json(state.search.status())
+}
+
+/// POST /api/v1/search/rebuild
+/// Empty the index and index every object again, in the background
+pub async fn rebuild_search_index(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<SearchStatus>), (StatusCode, String)> {
+    let status = state.search.rebuild(state.clone()).map_err(search_error)?;
+    info!("Rebuilding the object search index");
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
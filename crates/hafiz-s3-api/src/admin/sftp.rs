@@ -0,0 +1,230 @@
+//! SFTP login endpoints
+//!
+//! Manages the logins of the SFTP gateway: the Hafiz user each acts as,
+//! the bucket or prefix it is confined to and the public keys it may
+//! authenticate with. Changes apply to the next session.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use chrono::Utc;
+use hafiz_core::types::{SftpKey, SftpUser};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::server::AppState;
+use crate::sftp::{split_home, PublicKeyLine};
+
+#[derive(Debug, Deserialize)]
+pub struct PutSftpUserRequest {
+    /// Access key of the Hafiz user sessions act as
+    pub access_key: String,
+    /// `bucket` or `bucket/prefix`
+    pub home: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddSftpKeyRequest {
+    /// Public key in `authorized_keys` form
+    pub public_key: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SftpKeyQuery {
+    pub fingerprint: String,
+}
+
+/// GET /api/v1/sftp/users
+/// List SFTP logins with their keys
+pub async fn list_sftp_users(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<SftpUser>>, (StatusCode, String)> {
+    let users = state
+        .metadata
+        .list_sftp_users()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(users))
+}
+
+/// GET /api/v1/sftp/users/:username
+/// Get an SFTP login with its keys
+pub async fn get_sftp_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+) -> Result<Json<SftpUser>, (StatusCode, String)> {
+    let user = find_sftp_user(&state, &username).await?;
+    Ok(Json(user))
+}
+
+/// PUT /api/v1/sftp/users/:username
+/// Create an SFTP login, or change the user and home of an existing one
+pub async fn put_sftp_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    Json(req): Json<PutSftpUserRequest>,
+) -> Result<Json<SftpUser>, (StatusCode, String)> {
+    if !valid_username(&username) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "SFTP user names are 1-64 letters, digits, '.', '_' or '-'".to_string(),
+        ));
+    }
+    let Some((bucket, prefix)) = split_home(&req.home) else {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Invalid home '{}': expected bucket or bucket/prefix",
+                req.home
+            ),
+        ));
+    };
+
+    let user_exists = state
+        .metadata
+        .get_credentials(&req.access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .is_some();
+    if !user_exists {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("User {} not found", req.access_key),
+        ));
+    }
+    let bucket_exists = state
+        .metadata
+        .get_bucket(&bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .is_some();
+    if !bucket_exists {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Bucket {} not found", bucket),
+        ));
+    }
+
+    let user = SftpUser {
+        username: username.clone(),
+        access_key: req.access_key,
+        home: format!("{}/{}", bucket, prefix)
+            .trim_end_matches('/')
+            .to_string(),
+        keys: Vec::new(),
+        created_at: Utc::now(),
+    };
+    state
+        .metadata
+        .put_sftp_user(&user)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!(
+        "SFTP login {} acts as {} in {}",
+        username, user.access_key, user.home
+    );
+    let user = find_sftp_user(&state, &username).await?;
+    Ok(Json(user))
+}
+
+/// DELETE /api/v1/sftp/users/:username
+/// Delete an SFTP login and its keys
+pub async fn delete_sftp_user(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let deleted = state
+        .metadata
+        .delete_sftp_user(&username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !deleted {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("SFTP user {} not found", username),
+        ));
+    }
+
+    info!("Deleted SFTP login {}", username);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/v1/sftp/users/:username/keys
+/// Let an SFTP login authenticate with a public key
+pub async fn add_sftp_key(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    Json(req): Json<AddSftpKeyRequest>,
+) -> Result<(StatusCode, Json<SftpKey>), (StatusCode, String)> {
+    find_sftp_user(&state, &username).await?;
+    let parsed = PublicKeyLine::parse(&req.public_key).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let key = SftpKey {
+        fingerprint: parsed.fingerprint(),
+        public_key: parsed.to_openssh(),
+        comment: parsed.comment,
+        created_at: Utc::now(),
+    };
+    state
+        .metadata
+        .add_sftp_key(&username, &key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!("Added key {} to SFTP login {}", key.fingerprint, username);
+    Ok((StatusCode::CREATED, Json(key)))
+}
+
+/// DELETE /api/v1/sftp/users/:username/keys?fingerprint=SHA256:...
+/// Remove a public key of an SFTP login
+pub async fn delete_sftp_key(
+    State(state): State<AppState>,
+    Path(username): Path<String>,
+    Query(query): Query<SftpKeyQuery>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let deleted = state
+        .metadata
+        .delete_sftp_key(&username, &query.fingerprint)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !deleted {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("SFTP user {} has no key {}", username, query.fingerprint),
+        ));
+    }
+
+    info!(
+        "Removed key {} from SFTP login {}",
+        query.fingerprint, username
+    );
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn find_sftp_user(
+    state: &AppState,
+    username: &str,
+) -> Result<SftpUser, (StatusCode, String)> {
+    state
+        .metadata
+        .get_sftp_user(username)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("SFTP user {} not found", username),
+            )
+        })
+}
+
+fn valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= 64
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
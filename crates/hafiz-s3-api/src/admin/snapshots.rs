@@ -0,0 +1,132 @@
+//! Bucket snapshot endpoints
+//!
+//! Takes point-in-time snapshots of buckets, lists and deletes them, and
+//! restores one into a new bucket. Taking and restoring a snapshot run
+//! within the request.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use hafiz_core::types::{Bucket, BucketSnapshot};
+use serde::Deserialize;
+
+use crate::server::AppState;
+use crate::snapshots::{self, SnapshotReport};
+
+/// Body of POST /api/v1/buckets/:name/snapshots
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateSnapshotRequest {
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Body of POST /api/v1/snapshots/:id/restore
+#[derive(Debug, Deserialize)]
+pub struct RestoreSnapshotRequest {
+    /// New bucket to restore into
+    pub bucket: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListSnapshotsQuery {
+    pub bucket: Option<String>,
+}
+
+fn error_status(e: hafiz_core::Error) -> (StatusCode, String) {
+    let status = StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    (status, e.to_string())
+}
+
+async fn find_snapshot(state: &AppState, id: &str) -> Result<BucketSnapshot, (StatusCode, String)> {
+    state
+        .metadata
+        .get_bucket_snapshot(id)
+        .await
+        .map_err(error_status)?
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Snapshot {} not found", id)))
+}
+
+/// POST /api/v1/buckets/:name/snapshots
+/// Take a snapshot of a bucket
+pub async fn create_bucket_snapshot(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    body: Option<Json<CreateSnapshotRequest>>,
+) -> Result<(StatusCode, Json<SnapshotReport>), (StatusCode, String)> {
+    let Json(request) = body.unwrap_or_default();
+    let report = snapshots::create_snapshot(&state, &bucket, request.description.as_deref())
+        .await
+        .map_err(error_status)?;
+
+    Ok((StatusCode::CREATED, Json(report)))
+}
+
+/// GET /api/v1/buckets/:name/snapshots
+/// List the snapshots of a bucket, including those of a bucket since deleted
+pub async fn list_bucket_snapshots(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Json<Vec<BucketSnapshot>>, (StatusCode, String)> {
+    let snapshots = state
+        .metadata
+        .list_bucket_snapshots(Some(&bucket))
+        .await
+        .map_err(error_status)?;
+
+    Ok(Json(snapshots))
+}
+
+/// GET /api/v1/snapshots?bucket=
+/// List all snapshots, optionally of one bucket
+pub async fn list_snapshots(
+    State(state): State<AppState>,
+    Query(query): Query<ListSnapshotsQuery>,
+) -> Result<Json<Vec<BucketSnapshot>>, (StatusCode, String)> {
+    let snapshots = state
+        .metadata
+        .list_bucket_snapshots(query.bucket.as_deref())
+        .await
+        .map_err(error_status)?;
+
+    Ok(Json(snapshots))
+}
+
+/// GET /api/v1/snapshots/:id
+/// Get a snapshot
+pub async fn get_snapshot(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<BucketSnapshot>, (StatusCode, String)> {
+    Ok(Json(find_snapshot(&state, &id).await?))
+}
+
+/// DELETE /api/v1/snapshots/:id
+/// Delete a snapshot and its data
+pub async fn delete_snapshot(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    find_snapshot(&state, &id).await?;
+    snapshots::delete_snapshot(&state, &id)
+        .await
+        .map_err(error_status)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/v1/snapshots/:id/restore
+/// Restore a snapshot into a new bucket
+pub async fn restore_snapshot(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<RestoreSnapshotRequest>,
+) -> Result<(StatusCode, Json<Bucket>), (StatusCode, String)> {
+    find_snapshot(&state, &id).await?;
+    let bucket = snapshots::restore_snapshot(&state, &id, &request.bucket)
+        .await
+        .map_err(error_status)?;
+
+    Ok((StatusCode::CREATED, Json(bucket)))
+}
@@ -1,10 +1,13 @@
 //! Dashboard and statistics endpoints
 
+use std::collections::{BTreeMap, HashMap};
+
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode},
     Json,
 };
+use hafiz_core::types::bucket_tags_match;
 use serde::{Deserialize, Serialize};
 
 use crate::server::AppState;
@@ -43,6 +46,7 @@ pub struct BucketStorageInfo {
 #[derive(Debug, Serialize)]
 pub struct BucketDetailed {
     pub name: String,
+    pub region: String,
     pub object_count: i64,
     pub size: i64,
     pub created_at: String,
@@ -88,11 +92,70 @@ pub struct BucketStats {
     pub multipart_uploads: i64,
     pub created_at: String,
     pub last_modified: Option<String>,
+    pub tags: Vec<BucketTag>,
+}
+
+/// Bucket tag filter for the statistics endpoints, e.g. `?tag=team:data,env:prod`
+#[derive(Debug, Default, Deserialize)]
+pub struct BucketTagQuery {
+    pub tag: Option<String>,
+}
+
+impl BucketTagQuery {
+    fn filter(&self) -> Result<HashMap<String, String>, (StatusCode, String)> {
+        let mut filter = HashMap::new();
+        for pair in self.tag.iter().flat_map(|t| t.split(',')).filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once(':').ok_or_else(|| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    format!("Invalid tag filter {}, expected key:value", pair),
+                )
+            })?;
+            filter.insert(key.to_string(), value.to_string());
+        }
+        Ok(filter)
+    }
+}
+
+/// Page of the detailed bucket list, e.g. `?prefix=logs-&limit=100&after=logs-0042`
+#[derive(Debug, Deserialize)]
+pub struct BucketListQuery {
+    pub tag: Option<String>,
+    pub prefix: Option<String>,
+    /// Last bucket of the previous page
+    pub after: Option<String>,
+    /// Most buckets to read; all if not given
+    pub limit: Option<usize>,
+}
+
+/// Cost allocation query
+#[derive(Debug, Deserialize)]
+pub struct CostAllocationQuery {
+    /// Tag key to group buckets by
+    pub key: String,
+}
+
+/// Usage of the buckets sharing one value of a cost allocation tag
+#[derive(Debug, Serialize)]
+pub struct CostAllocationEntry {
+    /// Tag value; `None` groups the buckets without the tag
+    pub value: Option<String>,
+    pub buckets: Vec<String>,
+    pub object_count: i64,
+    pub size: i64,
+}
+
+/// Cost allocation report
+#[derive(Debug, Serialize)]
+pub struct CostAllocationResponse {
+    pub key: String,
+    pub entries: Vec<CostAllocationEntry>,
 }
 
 /// Get dashboard statistics
 pub async fn get_dashboard_stats(
     State(state): State<AppState>,
+    Query(query): Query<BucketTagQuery>,
 ) -> Result<Json<DashboardStats>, (StatusCode, String)> {
     let metadata = &state.metadata;
 
@@ -101,6 +164,7 @@ pub async fn get_dashboard_stats(
         .list_buckets()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let buckets = filter_by_tags(&state, buckets, &query.filter()?, |b| &b.name).await;
 
     let total_buckets = buckets.len() as i64;
     let mut total_objects: i64 = 0;
@@ -110,13 +174,7 @@ pub async fn get_dashboard_stats(
 
     // Calculate stats for each bucket
     for bucket in &buckets {
-        let objects = metadata
-            .list_objects(&bucket.name, "", "", 10000)
-            .await
-            .unwrap_or_default();
-
-        let bucket_objects = objects.len() as i64;
-        let bucket_size: i64 = objects.iter().map(|o| o.size).sum();
+        let (bucket_objects, bucket_size) = bucket_usage(&state, &bucket.name).await?;
 
         total_objects += bucket_objects;
         total_size += bucket_size;
@@ -175,6 +233,7 @@ pub async fn get_dashboard_stats(
 /// Get storage statistics
 pub async fn get_storage_stats(
     State(state): State<AppState>,
+    Query(query): Query<BucketTagQuery>,
 ) -> Result<Json<StorageStats>, (StatusCode, String)> {
     let metadata = &state.metadata;
 
@@ -182,6 +241,7 @@ pub async fn get_storage_stats(
         .list_buckets()
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let buckets = filter_by_tags(&state, buckets, &query.filter()?, |b| &b.name).await;
 
     let mut total_size: i64 = 0;
     let mut total_objects: i64 = 0;
@@ -245,26 +305,39 @@ pub async fn get_storage_stats(
 }
 
 /// List buckets with detailed information
+///
+/// With `limit`, one page is listed, and the `x-hafiz-next-after` header
+/// gives the `after` of the next page if there is one. The limit counts
+/// buckets before the tag filter, so a filtered page may be short.
 pub async fn list_buckets_detailed(
     State(state): State<AppState>,
-) -> Result<Json<Vec<BucketDetailed>>, (StatusCode, String)> {
+    Query(query): Query<BucketListQuery>,
+) -> Result<(HeaderMap, Json<Vec<BucketDetailed>>), (StatusCode, String)> {
     let metadata = &state.metadata;
 
-    let buckets = metadata
-        .list_buckets()
+    let (buckets, more) = metadata
+        .list_buckets_page(
+            None,
+            query.prefix.as_deref(),
+            None,
+            query.after.as_deref(),
+            query.limit,
+        )
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let mut headers = HeaderMap::new();
+    if let Some(last) = buckets.last().filter(|_| more) {
+        if let Ok(value) = HeaderValue::from_str(&last.name) {
+            headers.insert("x-hafiz-next-after", value);
+        }
+    }
+    let tags = BucketTagQuery { tag: query.tag }.filter()?;
+    let buckets = filter_by_tags(&state, buckets, &tags, |b| &b.name).await;
 
     let mut result = Vec::new();
 
     for bucket in buckets {
-        let objects = metadata
-            .list_objects(&bucket.name, "", "", 10000)
-            .await
-            .unwrap_or_default();
-
-        let object_count = objects.len() as i64;
-        let size: i64 = objects.iter().map(|o| o.size).sum();
+        let (object_count, size) = bucket_usage(&state, &bucket.name).await?;
 
         // Get versioning status
         let versioning = metadata
@@ -279,10 +352,11 @@ pub async fn list_buckets_detailed(
             .get_bucket_tags(&bucket.name)
             .await
             .unwrap_or_default();
-        let tags: Vec<BucketTag> = tags_map
+        let mut tags: Vec<BucketTag> = tags_map
             .into_iter()
             .map(|(key, value)| BucketTag { key, value })
             .collect();
+        tags.sort_by(|a, b| a.key.cmp(&b.key));
 
         // Get lifecycle rules count
         let lifecycle_rules = metadata
@@ -293,9 +367,10 @@ pub async fn list_buckets_detailed(
 
         result.push(BucketDetailed {
             name: bucket.name,
+            region: bucket.region,
             object_count,
             size,
-            created_at: bucket.created_at.to_rfc3339(),
+            created_at: bucket.creation_date.to_rfc3339(),
             versioning_enabled,
             versioning_status,
             encryption_enabled: false, // TODO
@@ -304,10 +379,7 @@ pub async fn list_buckets_detailed(
         });
     }
 
-    // Sort by name
-    result.sort_by(|a, b| a.name.cmp(&b.name));
-
-    Ok(Json(result))
+    Ok((headers, Json(result)))
 }
 
 /// Get statistics for a specific bucket
@@ -324,15 +396,14 @@ pub async fn get_bucket_stats(
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .ok_or((StatusCode::NOT_FOUND, format!("Bucket '{}' not found", name)))?;
 
+    let (object_count, total_size) = bucket_usage(&state, &name).await?;
+
     // Get objects
     let objects = metadata
         .list_objects(&name, "", "", 10000)
         .await
         .unwrap_or_default();
 
-    let object_count = objects.len() as i64;
-    let total_size: i64 = objects.iter().map(|o| o.size).sum();
-
     // Get versions count
     let version_count = metadata
         .list_object_versions(&name, "", "", 10000)
@@ -361,6 +432,15 @@ pub async fn get_bucket_stats(
         .max()
         .map(|d| d.to_rfc3339());
 
+    let mut tags: Vec<BucketTag> = metadata
+        .get_bucket_tags(&name)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(key, value)| BucketTag { key, value })
+        .collect();
+    tags.sort_by(|a, b| a.key.cmp(&b.key));
+
     Ok(Json(BucketStats {
         name,
         object_count,
@@ -370,5 +450,81 @@ pub async fn get_bucket_stats(
         multipart_uploads,
         created_at: bucket.created_at.to_rfc3339(),
         last_modified,
+        tags,
+    }))
+}
+
+/// GET /api/v1/stats/cost-allocation?key=team
+/// Group bucket usage by the value of a cost allocation tag
+pub async fn get_cost_allocation(
+    State(state): State<AppState>,
+    Query(query): Query<CostAllocationQuery>,
+) -> Result<Json<CostAllocationResponse>, (StatusCode, String)> {
+    let metadata = &state.metadata;
+
+    let buckets = metadata
+        .list_buckets("root")
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut groups: BTreeMap<Option<String>, CostAllocationEntry> = BTreeMap::new();
+    for bucket in buckets {
+        let value = metadata
+            .get_bucket_tags(&bucket.name)
+            .await
+            .unwrap_or_default()
+            .remove(&query.key);
+        let (object_count, size) = bucket_usage(&state, &bucket.name).await?;
+
+        let entry = groups
+            .entry(value.clone())
+            .or_insert_with(|| CostAllocationEntry {
+                value,
+                buckets: Vec::new(),
+                object_count: 0,
+                size: 0,
+            });
+        entry.buckets.push(bucket.name);
+        entry.object_count += object_count;
+        entry.size += size;
+    }
+
+    Ok(Json(CostAllocationResponse {
+        key: query.key,
+        entries: groups.into_values().collect(),
     }))
 }
+
+/// Object count and total size of the stored versions in a bucket
+async fn bucket_usage(state: &AppState, bucket: &str) -> Result<(i64, i64), (StatusCode, String)> {
+    let usage = state
+        .metadata
+        .get_bucket_usage(bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok((usage.object_count, usage.total_bytes))
+}
+
+/// Keep the buckets that carry every tag in `filter`
+async fn filter_by_tags<B>(
+    state: &AppState,
+    buckets: Vec<B>,
+    filter: &HashMap<String, String>,
+    name: impl Fn(&B) -> &str,
+) -> Vec<B> {
+    if filter.is_empty() {
+        return buckets;
+    }
+    let mut kept = Vec::with_capacity(buckets.len());
+    for bucket in buckets {
+        let tags = state
+            .metadata
+            .get_bucket_tags(name(&bucket))
+            .await
+            .unwrap_or_default();
+        if bucket_tags_match(filter, &tags) {
+            kept.push(bucket);
+        }
+    }
+    kept
+}
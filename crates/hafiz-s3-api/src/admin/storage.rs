@@ -0,0 +1,250 @@
+//! Storage backend endpoints
+//!
+//! Lists the configured storage backends and maps buckets onto them.
+//! Mappings made here are stored in the metadata database and take
+//! precedence over `[storage.buckets]` in the config file. Also sets the
+//! per-bucket compression codec.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use hafiz_core::config::DEFAULT_STORAGE_BACKEND;
+use hafiz_core::types::{BucketCompression, CompressionCodec};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::server::AppState;
+
+/// Storage backend summary
+#[derive(Debug, Serialize)]
+pub struct StorageBackendInfo {
+    pub name: String,
+    /// Backend type: local, memory, s3, azure or gcs
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Buckets mapped to this backend
+    pub buckets: Vec<String>,
+}
+
+/// Storage backends response
+#[derive(Debug, Serialize)]
+pub struct StorageBackendsResponse {
+    /// Backend used by buckets without a mapping
+    pub default: String,
+    pub backends: Vec<StorageBackendInfo>,
+}
+
+/// Bucket storage backend response
+#[derive(Debug, Serialize)]
+pub struct BucketBackendResponse {
+    pub bucket: String,
+    pub backend: String,
+}
+
+/// Set bucket storage backend request
+#[derive(Debug, Deserialize)]
+pub struct SetBucketBackendRequest {
+    /// Backend name, or `default` for the default storage
+    pub backend: String,
+}
+
+/// GET /api/v1/storage/backends
+/// List storage backends and the buckets mapped to them
+pub async fn list_storage_backends(
+    State(state): State<AppState>,
+) -> Json<StorageBackendsResponse> {
+    let mut buckets: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (bucket, backend) in state.storage_router.bucket_backends() {
+        buckets.entry(backend).or_default().push(bucket);
+    }
+
+    let backends = state
+        .storage_router
+        .backends()
+        .into_iter()
+        .map(|(name, kind)| {
+            let mut mapped = buckets.remove(&name).unwrap_or_default();
+            mapped.sort();
+            StorageBackendInfo {
+                name,
+                kind: kind.to_string(),
+                buckets: mapped,
+            }
+        })
+        .collect();
+
+    Json(StorageBackendsResponse {
+        default: DEFAULT_STORAGE_BACKEND.to_string(),
+        backends,
+    })
+}
+
+/// GET /api/v1/buckets/:name/backend
+/// Get the storage backend of a bucket
+pub async fn get_bucket_backend(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Json<BucketBackendResponse> {
+    let backend = state.storage_router.backend_name(&bucket);
+    Json(BucketBackendResponse { bucket, backend })
+}
+
+/// PUT /api/v1/buckets/:name/backend
+/// Map a bucket to a storage backend.
+///
+/// Data is not moved between backends, so existing buckets must be empty.
+/// Buckets that do not exist yet can be mapped ahead of their creation.
+pub async fn set_bucket_backend(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(req): Json<SetBucketBackendRequest>,
+) -> Result<Json<BucketBackendResponse>, (StatusCode, String)> {
+    let previous = state.storage_router.backend_name(&bucket);
+    if previous == req.backend {
+        return Ok(Json(BucketBackendResponse {
+            bucket,
+            backend: req.backend,
+        }));
+    }
+    let exists = ensure_empty(&state, &bucket).await?;
+
+    state
+        .storage_router
+        .set_bucket_backend(&bucket, &req.backend)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    // An existing bucket needs its container on the new backend
+    if exists {
+        if let Err(e) = state.storage.create_bucket(&bucket).await {
+            let _ = state.storage_router.set_bucket_backend(&bucket, &previous);
+            return Err((StatusCode::BAD_GATEWAY, e.to_string()));
+        }
+    }
+
+    let stored = if req.backend == DEFAULT_STORAGE_BACKEND {
+        state.metadata.delete_bucket_backend(&bucket).await
+    } else {
+        state.metadata.put_bucket_backend(&bucket, &req.backend).await
+    };
+    stored.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!("Mapped bucket {} to storage backend {}", bucket, req.backend);
+    Ok(Json(BucketBackendResponse {
+        bucket,
+        backend: req.backend,
+    }))
+}
+
+/// DELETE /api/v1/buckets/:name/backend
+/// Move a bucket back to the default storage
+pub async fn delete_bucket_backend(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    set_bucket_backend(
+        State(state),
+        Path(bucket),
+        Json(SetBucketBackendRequest {
+            backend: DEFAULT_STORAGE_BACKEND.to_string(),
+        }),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Bucket compression response
+#[derive(Debug, Serialize)]
+pub struct BucketCompressionResponse {
+    pub bucket: String,
+    #[serde(flatten)]
+    pub compression: BucketCompression,
+}
+
+/// GET /api/v1/buckets/:name/compression
+/// Get the codec new objects in a bucket are compressed with
+pub async fn get_bucket_compression(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Json<BucketCompressionResponse> {
+    let compression = state.compression.bucket_compression(&bucket);
+    Json(BucketCompressionResponse {
+        bucket,
+        compression,
+    })
+}
+
+/// PUT /api/v1/buckets/:name/compression
+/// Set the codec for new objects; existing objects keep theirs
+pub async fn set_bucket_compression(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(compression): Json<BucketCompression>,
+) -> Result<Json<BucketCompressionResponse>, (StatusCode, String)> {
+    compression
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    // The setting is kept even for `none` so objects compressed earlier
+    // are still recognised
+    state
+        .metadata
+        .put_bucket_compression(&bucket, &compression)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.compression.set_bucket_compression(&bucket, compression);
+
+    Ok(Json(BucketCompressionResponse {
+        bucket,
+        compression,
+    }))
+}
+
+/// DELETE /api/v1/buckets/:name/compression
+/// Stop compressing new objects in a bucket
+pub async fn delete_bucket_compression(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    set_bucket_compression(
+        State(state),
+        Path(bucket),
+        Json(BucketCompression {
+            codec: CompressionCodec::None,
+            level: None,
+        }),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Fail if the bucket holds objects; returns whether the bucket exists
+async fn ensure_empty(state: &AppState, bucket: &str) -> Result<bool, (StatusCode, String)> {
+    let exists = state
+        .metadata
+        .get_bucket(bucket)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .is_some();
+    if !exists {
+        return Ok(false);
+    }
+
+    let (objects, _, _, _) = state
+        .metadata
+        .list_objects(bucket, None, None, 1, None)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if !objects.is_empty() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Bucket {} is not empty; its data cannot change backends", bucket),
+        ));
+    }
+    Ok(true)
+}
@@ -0,0 +1,141 @@
+//! Tiering endpoints
+//!
+//! Opts buckets into access-based tiering, reports their access statistics
+//! and the last tiering pass, and starts passes on demand.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use hafiz_core::types::BucketTiering;
+use hafiz_metadata::repository::TieringStats;
+use serde::Serialize;
+use tracing::info;
+
+use crate::server::AppState;
+use crate::tiering::TieringStatus;
+
+/// Bucket tiering response
+#[derive(Debug, Serialize)]
+pub struct BucketTieringResponse {
+    pub bucket: String,
+    /// Absent when the bucket has not opted in
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tiering: Option<BucketTiering>,
+    pub stats: TieringStats,
+}
+
+fn internal_error(e: hafiz_core::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+}
+
+/// GET /api/v1/buckets/:name/tiering
+/// Get the tiering setting and access statistics of a bucket
+pub async fn get_bucket_tiering(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<Json<BucketTieringResponse>, (StatusCode, String)> {
+    let tiering = state
+        .metadata
+        .get_bucket_tiering(&bucket)
+        .await
+        .map_err(internal_error)?;
+    let stats = state
+        .metadata
+        .tiering_stats(&bucket)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(Json(BucketTieringResponse {
+        bucket,
+        tiering,
+        stats,
+    }))
+}
+
+/// PUT /api/v1/buckets/:name/tiering
+/// Archive objects of a bucket that go unread for a number of days
+pub async fn set_bucket_tiering(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    Json(tiering): Json<BucketTiering>,
+) -> Result<Json<BucketTieringResponse>, (StatusCode, String)> {
+    if !state.tiering.is_enabled() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Tiering needs storage.archive_backend".to_string(),
+        ));
+    }
+    tiering
+        .validate()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let exists = state
+        .metadata
+        .get_bucket(&bucket)
+        .await
+        .map_err(internal_error)?
+        .is_some();
+    if !exists {
+        return Err((StatusCode::NOT_FOUND, format!("Bucket {} not found", bucket)));
+    }
+
+    state
+        .metadata
+        .put_bucket_tiering(&bucket, &tiering)
+        .await
+        .map_err(internal_error)?;
+    info!(
+        "Bucket {} archives objects unread for {} days",
+        bucket, tiering.archive_after_days
+    );
+
+    get_bucket_tiering(State(state), Path(bucket)).await
+}
+
+/// DELETE /api/v1/buckets/:name/tiering
+/// Stop archiving the objects of a bucket; archived ones move back when read
+pub async fn delete_bucket_tiering(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .metadata
+        .delete_bucket_tiering(&bucket)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// GET /api/v1/tiering
+/// Get the tiering status and the report of the last pass
+pub async fn get_tiering_status(State(state): State<AppState>) -> Json<TieringStatus> {
+    Json(state.tiering.status())
+}
+
+/// POST /api/v1/tiering/run
+/// Start a tiering pass in the background; poll GET /tiering for the report
+pub async fn run_tiering(
+    State(state): State<AppState>,
+) -> Result<(StatusCode, Json<TieringStatus>), (StatusCode, String)> {
+    if !state.tiering.is_enabled() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Tiering needs storage.archive_backend".to_string(),
+        ));
+    }
+    let mut status = state.tiering.status();
+    if status.running {
+        return Err((StatusCode::CONFLICT, "A tiering pass is already running".to_string()));
+    }
+
+    let tiering = state.tiering.clone();
+    let task_state = state.clone();
+    tokio::spawn(async move {
+        tiering.run(&task_state).await;
+    });
+
+    status.running = true;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
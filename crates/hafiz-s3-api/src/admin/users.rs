@@ -1,7 +1,7 @@
 //! User management endpoints
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
     Json,
 };
@@ -9,6 +9,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::server::AppState;
 use hafiz_auth::generate_credentials;
+use hafiz_core::types::DEFAULT_ACCOUNT;
 
 /// User information response
 #[derive(Debug, Serialize)]
@@ -20,6 +21,7 @@ pub struct UserInfo {
     pub created_at: String,
     pub last_used: Option<String>,
     pub policies: Vec<String>,
+    pub account_id: String,
 }
 
 /// User list response
@@ -35,6 +37,8 @@ pub struct CreateUserRequest {
     pub name: String,
     pub email: Option<String>,
     pub policies: Option<Vec<String>>,
+    /// Account the user belongs to; the default account if not given
+    pub account_id: Option<String>,
 }
 
 /// Create user response
@@ -44,6 +48,7 @@ pub struct CreateUserResponse {
     pub access_key: String,
     pub secret_key: String,
     pub email: Option<String>,
+    pub account_id: String,
     pub created_at: String,
 }
 
@@ -56,12 +61,40 @@ pub struct UpdateUserRequest {
     pub policies: Option<Vec<String>>,
 }
 
+/// Key rotation options
+#[derive(Debug, Deserialize)]
+pub struct RotateKeysQuery {
+    /// How long the old key stays valid; defaults to
+    /// `auth.key_rotation_grace_seconds`, 0 retires it at once
+    pub grace_seconds: Option<u64>,
+}
+
 /// Key rotation response
 #[derive(Debug, Serialize)]
 pub struct RotateKeysResponse {
     pub access_key: String,
     pub secret_key: String,
     pub created_at: String,
+    pub previous_access_key: String,
+    /// When the previous key stops working, if it was kept
+    pub previous_key_expires_at: Option<String>,
+}
+
+/// One access key of a user
+#[derive(Debug, Serialize)]
+pub struct AccessKeyInfo {
+    pub access_key: String,
+    /// `active` for the current key, `retiring` during a grace period
+    pub status: String,
+    pub created_at: Option<String>,
+    pub expires_at: Option<String>,
+    pub last_used: Option<String>,
+}
+
+/// Access key list response
+#[derive(Debug, Serialize)]
+pub struct AccessKeyListResponse {
+    pub keys: Vec<AccessKeyInfo>,
 }
 
 /// List all users
@@ -85,6 +118,7 @@ pub async fn list_users(
             created_at: cred.created_at.to_rfc3339(),
             last_used: cred.last_used.map(|d| d.to_rfc3339()),
             policies: cred.policies,
+            account_id: cred.account_id,
         })
         .collect();
 
@@ -114,6 +148,7 @@ pub async fn get_user(
         created_at: cred.created_at.to_rfc3339(),
         last_used: cred.last_used.map(|d| d.to_rfc3339()),
         policies: cred.policies,
+        account_id: cred.account_id,
     }))
 }
 
@@ -131,11 +166,24 @@ pub async fn create_user(
         return Err((StatusCode::BAD_REQUEST, "Name too long (max 64 characters)".to_string()));
     }
 
+    let metadata = &state.metadata;
+
+    let account_id = req
+        .account_id
+        .clone()
+        .unwrap_or_else(|| DEFAULT_ACCOUNT.to_string());
+    let account_exists = metadata
+        .get_account(&account_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .is_some();
+    if !account_exists {
+        return Err((StatusCode::NOT_FOUND, format!("Account '{}' not found", account_id)));
+    }
+
     // Generate credentials
     let (access_key, secret_key) = generate_credentials();
 
-    let metadata = &state.metadata;
-
     // Check if name already exists
     let existing = metadata.list_credentials().await.unwrap_or_default();
     if existing.iter().any(|c| c.name.as_deref() == Some(&req.name)) {
@@ -153,6 +201,7 @@ pub async fn create_user(
         created_at: now,
         last_used: None,
         policies: req.policies.unwrap_or_default(),
+        account_id: account_id.clone(),
     };
 
     metadata
@@ -165,6 +214,7 @@ pub async fn create_user(
         access_key,
         secret_key,
         email: req.email,
+        account_id,
         created_at: now.to_rfc3339(),
     })))
 }
@@ -227,6 +277,7 @@ pub async fn enable_user(
         created_at: cred.created_at.to_rfc3339(),
         last_used: cred.last_used.map(|d| d.to_rfc3339()),
         policies: cred.policies,
+        account_id: cred.account_id,
     }))
 }
 
@@ -266,53 +317,126 @@ pub async fn disable_user(
         created_at: cred.created_at.to_rfc3339(),
         last_used: cred.last_used.map(|d| d.to_rfc3339()),
         policies: cred.policies,
+        account_id: cred.account_id,
     }))
 }
 
 /// Rotate user's access keys
+///
+/// The user gets a new key pair. The old key keeps working until the grace
+/// period ends, so clients can be moved over without downtime.
 pub async fn rotate_keys(
     State(state): State<AppState>,
     Path(access_key): Path<String>,
+    Query(query): Query<RotateKeysQuery>,
 ) -> Result<Json<RotateKeysResponse>, (StatusCode, String)> {
     let metadata = &state.metadata;
 
-    // Get current user
-    let old_cred = metadata
+    metadata
         .get_credentials(&access_key)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
         .ok_or((StatusCode::NOT_FOUND, format!("User '{}' not found", access_key)))?;
 
-    // Generate new credentials
-    let (new_access_key, new_secret_key) = generate_credentials();
+    let grace_seconds = query
+        .grace_seconds
+        .unwrap_or(state.config.auth.key_rotation_grace_seconds);
     let now = chrono::Utc::now();
+    let grace_until = (grace_seconds > 0).then(|| now + chrono::Duration::seconds(grace_seconds as i64));
 
-    // Create new credentials with same settings
-    let new_cred = hafiz_core::types::Credentials {
-        access_key: new_access_key.clone(),
-        secret_key: new_secret_key.clone(),
-        name: old_cred.name,
-        email: old_cred.email,
-        enabled: old_cred.enabled,
-        created_at: now,
-        last_used: None,
-        policies: old_cred.policies,
-    };
+    // Generate new credentials
+    let (new_access_key, new_secret_key) = generate_credentials();
 
-    // Delete old and create new
     metadata
-        .delete_credentials(&access_key)
+        .rotate_user_keys(&access_key, &new_access_key, &new_secret_key, grace_until)
         .await
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    metadata
-        .create_credentials(&new_cred)
-        .await
-        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    if let Err(e) = metadata.purge_expired_retiring_keys().await {
+        tracing::warn!("Failed to purge expired retiring keys: {}", e);
+    }
+
+    tracing::info!(
+        "Rotated access key {} to {} (old key valid for {}s)",
+        access_key, new_access_key, grace_seconds
+    );
 
     Ok(Json(RotateKeysResponse {
         access_key: new_access_key,
         secret_key: new_secret_key,
         created_at: now.to_rfc3339(),
+        previous_access_key: access_key,
+        previous_key_expires_at: grace_until.map(|d| d.to_rfc3339()),
     }))
 }
+
+/// List a user's current and retiring access keys
+pub async fn list_user_keys(
+    State(state): State<AppState>,
+    Path(access_key): Path<String>,
+) -> Result<Json<AccessKeyListResponse>, (StatusCode, String)> {
+    let metadata = &state.metadata;
+
+    let cred = metadata
+        .get_credentials(&access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .ok_or((StatusCode::NOT_FOUND, format!("User '{}' not found", access_key)))?;
+
+    let created_at = metadata
+        .get_key_created_at(&access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    let mut keys = vec![AccessKeyInfo {
+        access_key: cred.access_key,
+        status: "active".to_string(),
+        created_at: created_at.map(|d| d.to_rfc3339()),
+        expires_at: None,
+        last_used: cred.last_used.map(|d| d.to_rfc3339()),
+    }];
+
+    let retiring = metadata
+        .list_retiring_keys(&access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    keys.extend(retiring.into_iter().map(|key| AccessKeyInfo {
+        access_key: key.access_key,
+        status: "retiring".to_string(),
+        created_at: Some(key.created_at.to_rfc3339()),
+        expires_at: Some(key.expires_at.to_rfc3339()),
+        last_used: key.last_used.map(|d| d.to_rfc3339()),
+    }));
+
+    Ok(Json(AccessKeyListResponse { keys }))
+}
+
+/// Retire an old access key before its grace period ends
+pub async fn retire_user_key(
+    State(state): State<AppState>,
+    Path((access_key, key_id)): Path<(String, String)>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let metadata = &state.metadata;
+
+    if key_id == access_key {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "Cannot retire the current key; rotate it first".to_string(),
+        ));
+    }
+
+    let key = metadata
+        .get_retiring_key(&key_id)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .filter(|key| key.user_access_key == access_key)
+        .ok_or((StatusCode::NOT_FOUND, format!("Key '{}' not found for user '{}'", key_id, access_key)))?;
+
+    metadata
+        .delete_retiring_key(&key.access_key)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    tracing::info!("Retired access key {} of {}", key_id, access_key);
+    Ok(StatusCode::NO_CONTENT)
+}
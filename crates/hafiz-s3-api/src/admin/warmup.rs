@@ -0,0 +1,77 @@
+//! Read cache warm-up endpoints
+//!
+//! Start jobs that load keys or a prefix into the read cache ahead of
+//! expected traffic, follow their progress and cancel them.
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde::Serialize;
+
+use crate::server::AppState;
+use crate::warmup::{WarmupJobStatus, WarmupRequest};
+
+#[derive(Debug, Serialize)]
+pub struct WarmupJobList {
+    pub jobs: Vec<WarmupJobStatus>,
+}
+
+/// GET /api/v1/cache/warmup
+/// List jobs, newest first
+pub async fn list_warmup_jobs(State(state): State<AppState>) -> Json<WarmupJobList> {
+    Json(WarmupJobList {
+        jobs: state.warmup.list(),
+    })
+}
+
+/// POST /api/v1/cache/warmup
+/// Start a job in the background; poll GET /cache/warmup/:id for progress
+pub async fn create_warmup_job(
+    State(state): State<AppState>,
+    Json(request): Json<WarmupRequest>,
+) -> Result<(StatusCode, Json<WarmupJobStatus>), (StatusCode, String)> {
+    let status = state.warmup.submit(&state, request).await.map_err(|e| {
+        (
+            StatusCode::from_u16(e.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            e.to_string(),
+        )
+    })?;
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+/// GET /api/v1/cache/warmup/:id
+pub async fn get_warmup_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<WarmupJobStatus>, (StatusCode, String)> {
+    state
+        .warmup
+        .get(&id)
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Warm-up job {} not found", id)))
+}
+
+/// POST /api/v1/cache/warmup/:id/cancel
+/// Stop a job once the objects in flight are loaded
+pub async fn cancel_warmup_job(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<WarmupJobStatus>, (StatusCode, String)> {
+    let status = state
+        .warmup
+        .get(&id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Warm-up job {} not found", id)))?;
+    if status.state.is_finished() {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Warm-up job {} has already finished", id),
+        ));
+    }
+    state
+        .warmup
+        .cancel(&id)
+        .map(Json)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Warm-up job {} not found", id)))
+}
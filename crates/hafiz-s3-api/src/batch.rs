@@ -0,0 +1,634 @@
+//! Batch operations on many objects
+//!
+//! A job applies one operation (copy, tag or delete) to every object named
+//! in a manifest: a CSV of `bucket,key[,version_id]` lines with URL-encoded
+//! keys, the format S3 inventory reports and Batch Operations use. Jobs run
+//! in the background with a bounded number of objects in flight, and
+//! failures that may be transient are retried with backoff. When a job ends,
+//! the outcome of each object is written as a CSV report object.
+//!
+//! Jobs are tracked in memory. Finished jobs stay visible until
+//! [`MAX_FINISHED_JOBS`] newer ones have finished; jobs still running when
+//! the server stops are lost, and their reports are never written.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use hafiz_core::types::{ObjectInternal as Object, Tag, TagSet};
+use hafiz_core::{Error, Result};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::consensus;
+use crate::routes::{enforce_object_lock, storage_key};
+use crate::server::AppState;
+
+/// Finished jobs kept for the admin API
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// Most objects a job may work on at once
+pub const MAX_CONCURRENCY: usize = 64;
+
+/// Most retries of one object
+pub const MAX_RETRIES: u32 = 10;
+
+/// Wait before the first retry; doubles with each further attempt
+const RETRY_BACKOFF: Duration = Duration::from_millis(200);
+
+fn default_concurrency() -> usize {
+    16
+}
+
+fn default_max_retries() -> u32 {
+    3
+}
+
+/// What a job does to each object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOperation {
+    /// Copy each object to `bucket`, keyed `prefix` + its key
+    Copy {
+        bucket: String,
+        #[serde(default)]
+        prefix: String,
+    },
+    /// Replace the tags of each object
+    Tag { tags: Vec<Tag> },
+    /// Delete each object, or the version named in the manifest
+    Delete,
+    /// Bring archived objects back for `days`
+    Restore { days: u32 },
+}
+
+impl BatchOperation {
+    fn name(&self) -> &'static str {
+        match self {
+            BatchOperation::Copy { .. } => "copy",
+            BatchOperation::Tag { .. } => "tag",
+            BatchOperation::Delete => "delete",
+            BatchOperation::Restore { .. } => "restore",
+        }
+    }
+}
+
+/// Where the manifest comes from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ManifestSource {
+    /// A CSV object stored on this server
+    Object { bucket: String, key: String },
+    /// CSV sent with the request
+    Inline { csv: String },
+}
+
+/// Where the completion report goes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportTarget {
+    pub bucket: String,
+    /// Prepended to `batch-<job id>.csv`
+    #[serde(default)]
+    pub prefix: String,
+    /// Only list objects that failed
+    #[serde(default)]
+    pub failed_only: bool,
+}
+
+/// A job as submitted
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchJobRequest {
+    pub manifest: ManifestSource,
+    pub operation: BatchOperation,
+    pub report: Option<ReportTarget>,
+    /// Objects worked on at once
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    /// Retries of an object after a storage or database error
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchJobState {
+    /// Reading the manifest
+    Preparing,
+    Running,
+    Completed,
+    /// The job could not run, e.g. the manifest was unreadable
+    Failed,
+    Cancelled,
+}
+
+impl BatchJobState {
+    pub fn is_finished(self) -> bool {
+        matches!(
+            self,
+            BatchJobState::Completed | BatchJobState::Failed | BatchJobState::Cancelled
+        )
+    }
+}
+
+/// Job progress as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchJobStatus {
+    pub id: String,
+    pub operation: BatchOperation,
+    pub state: BatchJobState,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Objects in the manifest
+    pub total_objects: u64,
+    pub succeeded: u64,
+    pub failed: u64,
+    pub retries: u64,
+    /// `bucket/key` of the completion report, once written
+    pub report: Option<String>,
+    /// Why the job failed, or why its report could not be written
+    pub error: Option<String>,
+}
+
+/// One line of a manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ManifestEntry {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: Option<String>,
+}
+
+/// Parse a CSV manifest of `bucket,key[,version_id]` lines
+///
+/// Fields may be double-quoted and keys are URL-decoded, as in S3
+/// inventory reports. Blank lines are skipped.
+pub fn parse_manifest(csv: &str) -> Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    for (n, line) in csv.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(|f| f.trim().trim_matches('"')).collect();
+        if fields.len() < 2 || fields.len() > 3 || fields[0].is_empty() || fields[1].is_empty() {
+            return Err(Error::InvalidArgument(format!(
+                "Manifest line {}: expected bucket,key[,version_id]",
+                n + 1
+            )));
+        }
+        let key = urlencoding::decode(fields[1]).map_err(|_| {
+            Error::InvalidArgument(format!("Manifest line {}: key is not valid UTF-8", n + 1))
+        })?;
+        entries.push(ManifestEntry {
+            bucket: fields[0].to_string(),
+            key: key.into_owned(),
+            version_id: fields.get(2).filter(|v| !v.is_empty()).map(|v| v.to_string()),
+        });
+    }
+    Ok(entries)
+}
+
+/// Outcome of one object, as reported
+struct TaskResult {
+    entry: ManifestEntry,
+    retries: u64,
+    error: Option<Error>,
+}
+
+/// One submitted job
+struct BatchJob {
+    request: BatchJobRequest,
+    status: RwLock<BatchJobStatus>,
+    cancelled: AtomicBool,
+}
+
+/// Runs batch jobs and keeps track of them
+#[derive(Default)]
+pub struct BatchJobs {
+    /// In submission order
+    jobs: RwLock<Vec<Arc<BatchJob>>>,
+}
+
+impl BatchJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All known jobs, newest first
+    pub fn list(&self) -> Vec<BatchJobStatus> {
+        self.jobs
+            .read()
+            .iter()
+            .rev()
+            .map(|job| job.status.read().clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<BatchJobStatus> {
+        self.find(id).map(|job| job.status.read().clone())
+    }
+
+    /// Stop a job after the objects in flight; `None` if there is no such job
+    pub fn cancel(&self, id: &str) -> Option<BatchJobStatus> {
+        let job = self.find(id)?;
+        job.cancelled.store(true, Ordering::SeqCst);
+        let status = job.status.read().clone();
+        Some(status)
+    }
+
+    fn find(&self, id: &str) -> Option<Arc<BatchJob>> {
+        self.jobs.read().iter().find(|job| job.status.read().id == id).cloned()
+    }
+
+    /// Check a job and start it in the background
+    pub async fn submit(&self, state: &AppState, request: BatchJobRequest) -> Result<BatchJobStatus> {
+        validate(state, &request).await?;
+
+        let job = Arc::new(BatchJob {
+            status: RwLock::new(BatchJobStatus {
+                id: uuid::Uuid::new_v4().to_string(),
+                operation: request.operation.clone(),
+                state: BatchJobState::Preparing,
+                created_at: Utc::now(),
+                finished_at: None,
+                total_objects: 0,
+                succeeded: 0,
+                failed: 0,
+                retries: 0,
+                report: None,
+                error: None,
+            }),
+            request,
+            cancelled: AtomicBool::new(false),
+        });
+        let status = job.status.read().clone();
+        info!("Batch job {} submitted ({})", status.id, job.request.operation.name());
+
+        {
+            let mut jobs = self.jobs.write();
+            jobs.push(job.clone());
+            prune(&mut jobs);
+        }
+
+        let state = state.clone();
+        tokio::spawn(async move { job.run(&state).await });
+        Ok(status)
+    }
+}
+
+/// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`]
+fn prune(jobs: &mut Vec<Arc<BatchJob>>) {
+    let finished = jobs.iter().filter(|job| job.status.read().state.is_finished()).count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+    jobs.retain(|job| {
+        if excess > 0 && job.status.read().state.is_finished() {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+async fn validate(state: &AppState, request: &BatchJobRequest) -> Result<()> {
+    if request.concurrency == 0 || request.concurrency > MAX_CONCURRENCY {
+        return Err(Error::InvalidArgument(format!(
+            "concurrency must be 1-{}",
+            MAX_CONCURRENCY
+        )));
+    }
+    if request.max_retries > MAX_RETRIES {
+        return Err(Error::InvalidArgument(format!(
+            "max_retries must be at most {}",
+            MAX_RETRIES
+        )));
+    }
+
+    let mut buckets = Vec::new();
+    match &request.operation {
+        BatchOperation::Copy { bucket, .. } => buckets.push(bucket),
+        BatchOperation::Tag { tags } => {
+            let mut set = TagSet::new();
+            for tag in tags {
+                set.add(tag.clone())?;
+            }
+        }
+        BatchOperation::Delete => {}
+        BatchOperation::Restore { .. } => {
+            return Err(Error::NotImplemented(
+                "No storage class here archives objects, so there is nothing to restore".into(),
+            ));
+        }
+    }
+    if let ManifestSource::Object { bucket, .. } = &request.manifest {
+        buckets.push(bucket);
+    }
+    if let Some(report) = &request.report {
+        buckets.push(&report.bucket);
+    }
+    for bucket in buckets {
+        if state.metadata.get_bucket(bucket).await?.is_none() {
+            return Err(Error::NoSuchBucketNamed(bucket.clone()));
+        }
+    }
+    Ok(())
+}
+
+impl BatchJob {
+    async fn run(&self, state: &AppState) {
+        let entries = match self.load_manifest(state).await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Batch job {} failed to read its manifest: {}", self.id(), e);
+                self.finish(BatchJobState::Failed, Some(format!("Manifest: {}", e)));
+                return;
+            }
+        };
+        {
+            let mut status = self.status.write();
+            status.total_objects = entries.len() as u64;
+            status.state = BatchJobState::Running;
+        }
+
+        let operation = self.request.operation.name();
+        let mut results = stream::iter(entries)
+            .map(|entry| self.run_task(state, entry))
+            .buffer_unordered(self.request.concurrency);
+        let mut report = Vec::new();
+        while let Some(result) = results.next().await {
+            let Some(result) = result else { continue };
+            metrics::counter!(
+                "hafiz_batch_objects_total",
+                "operation" => operation,
+                "result" => if result.error.is_some() { "failed" } else { "succeeded" }
+            )
+            .increment(1);
+            {
+                let mut status = self.status.write();
+                status.retries += result.retries;
+                match result.error {
+                    Some(_) => status.failed += 1,
+                    None => status.succeeded += 1,
+                }
+            }
+            report.push(result);
+        }
+
+        let cancelled = self.cancelled.load(Ordering::SeqCst);
+        let mut error = None;
+        if let Some(target) = &self.request.report {
+            match self.write_report(state, target, &report).await {
+                Ok(location) => self.status.write().report = Some(location),
+                Err(e) => {
+                    warn!("Batch job {} failed to write its report: {}", self.id(), e);
+                    error = Some(format!("Report: {}", e));
+                }
+            }
+        }
+        let final_state = if cancelled {
+            BatchJobState::Cancelled
+        } else {
+            BatchJobState::Completed
+        };
+        self.finish(final_state, error);
+    }
+
+    fn id(&self) -> String {
+        self.status.read().id.clone()
+    }
+
+    fn finish(&self, state: BatchJobState, error: Option<String>) {
+        let mut status = self.status.write();
+        status.state = state;
+        status.error = error;
+        status.finished_at = Some(Utc::now());
+        info!(
+            "Batch job {} {:?}: {} of {} objects succeeded, {} failed",
+            status.id, state, status.succeeded, status.total_objects, status.failed
+        );
+    }
+
+    async fn load_manifest(&self, state: &AppState) -> Result<Vec<ManifestEntry>> {
+        match &self.request.manifest {
+            ManifestSource::Inline { csv } => parse_manifest(csv),
+            ManifestSource::Object { bucket, key } => {
+                let object = state
+                    .metadata
+                    .get_object(bucket, key)
+                    .await?
+                    .filter(|o| !o.is_delete_marker)
+                    .ok_or(Error::NoSuchKey)?;
+                let data = state
+                    .storage
+                    .get(bucket, &storage_key(key, &object.version_id))
+                    .await?;
+                let csv = std::str::from_utf8(&data)
+                    .map_err(|_| Error::InvalidArgument("Manifest is not UTF-8".into()))?;
+                parse_manifest(csv)
+            }
+        }
+    }
+
+    /// Apply the operation to one object, retrying transient failures;
+    /// `None` once the job is cancelled
+    async fn run_task(&self, state: &AppState, entry: ManifestEntry) -> Option<TaskResult> {
+        let mut retries = 0;
+        loop {
+            if self.cancelled.load(Ordering::SeqCst) {
+                return None;
+            }
+            match apply(state, &self.request.operation, &entry).await {
+                Ok(()) => {
+                    return Some(TaskResult { entry, retries, error: None });
+                }
+                Err(e) if is_transient(&e) && retries < self.request.max_retries as u64 => {
+                    tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(retries as u32)).await;
+                    retries += 1;
+                }
+                Err(e) => {
+                    return Some(TaskResult { entry, retries, error: Some(e) });
+                }
+            }
+        }
+    }
+
+    /// Store the report and return its `bucket/key`
+    async fn write_report(
+        &self,
+        state: &AppState,
+        target: &ReportTarget,
+        results: &[TaskResult],
+    ) -> Result<String> {
+        let mut csv = String::from("bucket,key,version_id,status,error_code,error_message\n");
+        for result in results {
+            if target.failed_only && result.error.is_none() {
+                continue;
+            }
+            let (status, code, message) = match &result.error {
+                Some(e) => ("failed", e.code().to_string(), e.to_string()),
+                None => ("succeeded", String::new(), String::new()),
+            };
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                csv_field(&result.entry.bucket),
+                urlencoding::encode(&result.entry.key),
+                csv_field(result.entry.version_id.as_deref().unwrap_or("")),
+                status,
+                code,
+                csv_field(&message)
+            ));
+        }
+
+        let key = format!("{}batch-{}.csv", target.prefix, self.id());
+        let data = Bytes::from(csv);
+        let size = data.len() as i64;
+        let etag = state.storage.put(&target.bucket, &key, data).await?;
+        let object = Object::new(target.bucket.clone(), key.clone(), size, etag, "text/csv".into());
+        consensus::put_object(state, &object).await?;
+        state.search.object_changed(&target.bucket, &key);
+        Ok(format!("{}/{}", target.bucket, key))
+    }
+}
+
+async fn apply(state: &AppState, operation: &BatchOperation, entry: &ManifestEntry) -> Result<()> {
+    let ManifestEntry { bucket, key, version_id } = entry;
+    match operation {
+        BatchOperation::Copy { bucket: dest_bucket, prefix } => {
+            let source = live_object(state, bucket, key, version_id.as_deref()).await?;
+            let data = state
+                .storage
+                .get(bucket, &storage_key(key, &source.version_id))
+                .await?;
+            let tags = state
+                .metadata
+                .get_object_tags(bucket, key, Some(&source.version_id))
+                .await?;
+
+            let dest_key = format!("{}{}", prefix, key);
+            enforce_object_lock(state, dest_bucket, &dest_key, None, false).await?;
+            let etag = state.storage.put(dest_bucket, &dest_key, data).await?;
+            let mut object = Object::new(
+                dest_bucket.clone(),
+                dest_key.clone(),
+                source.size,
+                etag,
+                source.content_type,
+            );
+            object.metadata = source.metadata;
+            consensus::put_object(state, &object).await?;
+            if !tags.is_empty() {
+                consensus::put_object_tags(state, dest_bucket, &dest_key, None, &tags).await?;
+            }
+            state.search.object_changed(dest_bucket, &dest_key);
+            Ok(())
+        }
+        BatchOperation::Tag { tags } => {
+            let object = live_object(state, bucket, key, version_id.as_deref()).await?;
+            let set = TagSet { tags: tags.clone() };
+            consensus::put_object_tags(state, bucket, key, Some(&object.version_id), &set).await?;
+            state.search.object_changed(bucket, key);
+            Ok(())
+        }
+        BatchOperation::Delete => {
+            let versioned = state
+                .metadata
+                .get_bucket(bucket)
+                .await?
+                .ok_or(Error::NoSuchBucket)?
+                .versioning
+                .is_versioning_enabled();
+            // Delete markers leave the data, locked or not, in place
+            if version_id.is_some() || !versioned {
+                enforce_object_lock(state, bucket, key, version_id.as_deref(), false).await?;
+                let vid = version_id.as_deref().unwrap_or(hafiz_core::types::NULL_VERSION_ID);
+                match state.storage.delete(bucket, &storage_key(key, vid)).await {
+                    Ok(()) | Err(Error::NoSuchKey) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            consensus::delete_objects(state, bucket, &[(key.clone(), version_id.clone())], versioned).await?;
+            state.search.object_changed(bucket, key);
+            Ok(())
+        }
+        BatchOperation::Restore { .. } => Err(Error::NotImplemented("RestoreObject".into())),
+    }
+}
+
+/// The object, or the named version, unless it is missing or a delete marker
+async fn live_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Result<Object> {
+    state
+        .metadata
+        .get_object_version(bucket, key, version_id)
+        .await?
+        .filter(|o| !o.is_delete_marker)
+        .ok_or(Error::NoSuchKey)
+}
+
+/// Errors worth another attempt
+fn is_transient(error: &Error) -> bool {
+    matches!(
+        error,
+        Error::StorageError(_)
+            | Error::DatabaseError(_)
+            | Error::InternalError(_)
+            | Error::ServiceUnavailable(_)
+            | Error::Io(_)
+    )
+}
+
+/// Quote a CSV field if it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_manifest() {
+        let csv = "photos,2024/a%20b.jpg\r\n\n\"photos\",\"c%2Cd.jpg\",v1\nlogs,x,\n";
+        let entries = parse_manifest(csv).unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ManifestEntry {
+                    bucket: "photos".into(),
+                    key: "2024/a b.jpg".into(),
+                    version_id: None,
+                },
+                ManifestEntry {
+                    bucket: "photos".into(),
+                    key: "c,d.jpg".into(),
+                    version_id: Some("v1".into()),
+                },
+                ManifestEntry {
+                    bucket: "logs".into(),
+                    key: "x".into(),
+                    version_id: None,
+                },
+            ]
+        );
+
+        assert!(parse_manifest("photos\n").is_err());
+        assert!(parse_manifest("photos,a,b,c\n").is_err());
+        assert!(parse_manifest(",key\n").is_err());
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a, \"b\""), "\"a, \"\"b\"\"\"");
+    }
+}
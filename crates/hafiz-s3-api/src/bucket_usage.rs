@@ -0,0 +1,127 @@
+//! Bucket usage counters
+//!
+//! The metadata store keeps each bucket's object count and stored bytes
+//! current as objects are written and deleted. This worker exports the
+//! totals, overall and per account, as storage gauges and periodically
+//! recounts every bucket from its objects, correcting counters that
+//! drifted, e.g. after the database was edited by hand.
+
+use std::time::Duration;
+
+use hafiz_core::config::DatabaseConfig;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::server::AppState;
+
+/// How often the storage gauges are refreshed from the counters
+const GAUGE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Outcome of one recount
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ReconcileReport {
+    pub buckets_checked: u64,
+    /// Buckets whose counters did not match their objects
+    pub buckets_corrected: u64,
+    pub errors: Vec<String>,
+}
+
+/// Recount the usage of every bucket and correct drifted counters
+pub async fn reconcile(state: &AppState) -> ReconcileReport {
+    let mut report = ReconcileReport::default();
+    let buckets = match state.metadata.list_bucket_names().await {
+        Ok(buckets) => buckets,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return report;
+        }
+    };
+
+    for bucket in buckets {
+        match state.metadata.reconcile_bucket_usage(&bucket).await {
+            Ok((before, after)) => {
+                report.buckets_checked += 1;
+                if before != after {
+                    warn!(
+                        "Usage of bucket {} drifted: {} objects / {} bytes counted, {} / {} recorded",
+                        bucket,
+                        after.object_count,
+                        after.total_bytes,
+                        before.object_count,
+                        before.total_bytes
+                    );
+                    report.buckets_corrected += 1;
+                    metrics::counter!("hafiz_bucket_usage_corrections_total").increment(1);
+                }
+            }
+            Err(e) => report.errors.push(format!("{}: {}", bucket, e)),
+        }
+    }
+
+    info!(
+        "Bucket usage recount: {} buckets checked, {} corrected, {} errors",
+        report.buckets_checked,
+        report.buckets_corrected,
+        report.errors.len()
+    );
+    report
+}
+
+/// Set the storage and account gauges from the usage counters
+pub async fn update_gauges(state: &AppState) {
+    let buckets = match state.metadata.list_bucket_names().await {
+        Ok(buckets) => buckets.len() as u64,
+        Err(e) => {
+            warn!("Failed to count buckets: {}", e);
+            return;
+        }
+    };
+    match state.metadata.list_bucket_usage().await {
+        Ok(usage) => {
+            let objects = usage.iter().map(|(_, u)| u.object_count.max(0) as u64).sum();
+            let bytes = usage.iter().map(|(_, u)| u.total_bytes.max(0) as u64).sum();
+            state.metrics.update_storage_stats(buckets, objects, bytes);
+        }
+        Err(e) => warn!("Failed to read bucket usage: {}", e),
+    }
+    match state.metadata.list_account_usage().await {
+        Ok(accounts) => {
+            for (account, usage) in accounts {
+                metrics::gauge!("hafiz_account_buckets", "account" => account.clone())
+                    .set(usage.buckets as f64);
+                metrics::gauge!("hafiz_account_objects", "account" => account.clone())
+                    .set(usage.objects as f64);
+                metrics::gauge!("hafiz_account_bytes", "account" => account).set(usage.bytes as f64);
+            }
+        }
+        Err(e) => warn!("Failed to read account usage: {}", e),
+    }
+}
+
+/// Refresh the storage gauges and run [`reconcile`] on its interval
+pub fn start(config: &DatabaseConfig, state: AppState) {
+    let gauge_state = state.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(GAUGE_INTERVAL);
+        loop {
+            ticker.tick().await;
+            update_gauges(&gauge_state).await;
+        }
+    });
+
+    if config.usage_reconcile_secs == 0 {
+        return;
+    }
+    let interval = Duration::from_secs(config.usage_reconcile_secs);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // The first tick fires at once; startup already counted new databases
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            reconcile(&state).await;
+        }
+    });
+
+    info!("Bucket usage recount scheduled every {:?}", interval);
+}
@@ -0,0 +1,401 @@
+//! Internal node-to-node endpoints
+//!
+//! Served under `/cluster/*` and called by other cluster members through
+//! `ClusterTransport`, never by S3 clients. With the `cluster-grpc` feature
+//! the same calls are also served over gRPC.
+//!
+//! [`cluster_router`] is served on the cluster port only, and every request
+//! must carry the cluster's shared secret; with cluster TLS, peers also
+//! present a client certificate signed by the cluster CA.
+
+#![cfg(feature = "cluster")]
+
+use axum::{
+    body::Body,
+    extract::{DefaultBodyLimit, Path, Query, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use bytes::Bytes;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+use hafiz_cluster::anti_entropy::{
+    AntiEntropyService, LeavesRequest, LeavesResponse, TreeRequest, TreeResponse, LEAVES_PATH,
+    TREE_PATH,
+};
+use hafiz_cluster::placement::{checksum, CONTENT_TYPE_META_KEY};
+use hafiz_cluster::{verify_peer_secret, ClusterManager, ClusterMessage, PEER_SECRET_HEADER};
+#[cfg(feature = "cluster-grpc")]
+use hafiz_cluster::{ClusterError, ClusterResult};
+use hafiz_cluster::raft::{
+    AppendEntriesRequest, AppendEntriesResponse, ForwardRequest, ForwardResponse,
+    InstallSnapshotRequest, InstallSnapshotResponse, RaftNode, VoteRequest, VoteResponse,
+};
+
+use hafiz_core::types::{ObjectInternal, NULL_VERSION_ID};
+use hafiz_core::Error;
+use hafiz_storage::StorageEngine;
+
+use crate::routes::storage_key;
+use crate::server::AppState;
+
+/// Header carrying the SHA-256 of object data between nodes
+const CHECKSUM_HEADER: &str = "x-hafiz-checksum";
+
+/// Prefix of headers carrying object metadata between nodes
+const META_HEADER_PREFIX: &str = "x-hafiz-meta-";
+
+/// Router of the cluster listener
+pub fn cluster_router(state: AppState) -> Router {
+    Router::new()
+        .route("/cluster/ping", get(ping))
+        .route("/cluster/join", post(cluster_message))
+        .route("/cluster/heartbeat", post(cluster_message))
+        .route("/cluster/message", post(cluster_message))
+        .route("/cluster/raft/vote", post(raft_vote))
+        .route("/cluster/raft/append", post(raft_append_entries))
+        // Snapshots carry the whole metadata database
+        .route(
+            "/cluster/raft/snapshot",
+            post(raft_install_snapshot).layer(DefaultBodyLimit::disable()),
+        )
+        .route("/cluster/raft/forward", post(raft_forward))
+        .route(TREE_PATH, post(anti_entropy_tree))
+        .route(LEAVES_PATH, post(anti_entropy_leaves))
+        .route(
+            "/cluster/objects/:bucket/*key",
+            get(get_object_data)
+                .put(put_object_data)
+                .layer(DefaultBodyLimit::disable()),
+        )
+        .layer(middleware::from_fn_with_state(state.clone(), peer_auth))
+        .with_state(state)
+}
+
+/// Refuse requests without the cluster's shared secret
+async fn peer_auth(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let Some(cluster) = state.cluster.as_ref() else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let presented = request
+        .headers()
+        .get(PEER_SECRET_HEADER)
+        .and_then(|v| v.to_str().ok());
+    if !verify_peer_secret(cluster.config().shared_secret.as_deref(), presented) {
+        warn!("Refused cluster request to {} without a valid secret", request.uri().path());
+        return (StatusCode::FORBIDDEN, "Invalid cluster secret").into_response();
+    }
+    next.run(request).await
+}
+
+fn cluster(state: &AppState) -> Result<&Arc<ClusterManager>, (StatusCode, String)> {
+    state
+        .cluster
+        .as_ref()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, "Cluster mode not enabled".to_string()))
+}
+
+/// GET /cluster/ping
+async fn ping() -> StatusCode {
+    StatusCode::OK
+}
+
+/// POST /cluster/{join,heartbeat,message}
+/// Membership and replication messages
+async fn cluster_message(
+    State(state): State<AppState>,
+    Json(message): Json<ClusterMessage>,
+) -> Result<Json<ClusterMessage>, (StatusCode, String)> {
+    cluster(&state)?
+        .handle_message(message)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn raft_node(state: &AppState) -> Result<Arc<RaftNode>, (StatusCode, String)> {
+    cluster(state)?.raft().ok_or_else(|| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Raft metadata consensus not enabled".to_string(),
+        )
+    })
+}
+
+/// POST /cluster/raft/vote
+async fn raft_vote(
+    State(state): State<AppState>,
+    Json(req): Json<VoteRequest>,
+) -> Result<Json<VoteResponse>, (StatusCode, String)> {
+    Ok(Json(raft_node(&state)?.handle_request_vote(req)))
+}
+
+/// POST /cluster/raft/append
+async fn raft_append_entries(
+    State(state): State<AppState>,
+    Json(req): Json<AppendEntriesRequest>,
+) -> Result<Json<AppendEntriesResponse>, (StatusCode, String)> {
+    raft_node(&state)?
+        .handle_append_entries(req)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// POST /cluster/raft/snapshot
+async fn raft_install_snapshot(
+    State(state): State<AppState>,
+    Json(req): Json<InstallSnapshotRequest>,
+) -> Result<Json<InstallSnapshotResponse>, (StatusCode, String)> {
+    raft_node(&state)?
+        .handle_install_snapshot(req)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// POST /cluster/raft/forward
+async fn raft_forward(
+    State(state): State<AppState>,
+    Json(req): Json<ForwardRequest>,
+) -> Result<Json<ForwardResponse>, (StatusCode, String)> {
+    Ok(Json(raft_node(&state)?.handle_forward(req).await))
+}
+
+fn anti_entropy(state: &AppState) -> Result<Arc<AntiEntropyService>, (StatusCode, String)> {
+    Ok(cluster(state)?.anti_entropy())
+}
+
+/// POST /cluster/anti-entropy/tree
+async fn anti_entropy_tree(
+    State(state): State<AppState>,
+    Json(req): Json<TreeRequest>,
+) -> Result<Json<TreeResponse>, (StatusCode, String)> {
+    anti_entropy(&state)?
+        .handle_tree(req)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// POST /cluster/anti-entropy/leaves
+async fn anti_entropy_leaves(
+    State(state): State<AppState>,
+    Json(req): Json<LeavesRequest>,
+) -> Result<Json<LeavesResponse>, (StatusCode, String)> {
+    anti_entropy(&state)?
+        .handle_leaves(req)
+        .await
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ObjectDataQuery {
+    #[serde(rename = "versionId")]
+    pub version_id: Option<String>,
+}
+
+/// GET /cluster/objects/:bucket/*key
+/// Raw object data for replication and proxied reads
+async fn get_object_data(
+    State(state): State<AppState>,
+    Path((bucket, key)): Path<(String, String)>,
+    Query(query): Query<ObjectDataQuery>,
+) -> Response {
+    match read_object_data(&state, &bucket, &key, query.version_id.as_deref()).await {
+        Ok(Some(data)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(CHECKSUM_HEADER, checksum(&data))
+            .body(Body::from(data))
+            .unwrap(),
+        Ok(None) => StatusCode::NOT_FOUND.into_response(),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    }
+}
+
+/// PUT /cluster/objects/:bucket/*key
+/// Store object data pushed by another node
+async fn put_object_data(
+    State(state): State<AppState>,
+    Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if let Some(expected) = headers.get(CHECKSUM_HEADER).and_then(|v| v.to_str().ok()) {
+        if checksum(&body) != expected {
+            return Err((StatusCode::BAD_REQUEST, "Checksum mismatch".to_string()));
+        }
+    }
+
+    let metadata: HashMap<String, String> = headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let name = name.as_str().strip_prefix(META_HEADER_PREFIX)?;
+            Some((name.to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect();
+
+    store_object_data(&state, &bucket, &key, body, metadata)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    Ok(StatusCode::OK)
+}
+
+async fn read_object_data(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Result<Option<Bytes>, Error> {
+    let storage_key = storage_key(key, version_id.unwrap_or(NULL_VERSION_ID));
+
+    match state.storage.get(bucket, &storage_key).await {
+        Ok(data) => Ok(Some(data)),
+        Err(Error::NoSuchKey) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+async fn store_object_data(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    data: Bytes,
+    mut metadata: HashMap<String, String>,
+) -> Result<(), Error> {
+    let content_type = metadata
+        .remove(CONTENT_TYPE_META_KEY)
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+    let size = data.len();
+
+    let etag = state.storage.put(bucket, key, data).await?;
+
+    // Record metadata if this node knows the bucket; with Raft consensus
+    // metadata arrives through the log instead
+    let consensus = state.cluster.as_ref().is_some_and(|c| c.raft().is_some());
+    if !consensus && state.metadata.get_bucket(bucket).await?.is_some() {
+        let mut object = ObjectInternal::new(bucket.to_string(), key.to_string(), size as i64, etag, content_type);
+        object.metadata = metadata;
+        state.metadata.put_object(&object).await?;
+        state.search.object_changed(bucket, key);
+    }
+
+    debug!("Stored {} bytes for {}/{} from peer", size, bucket, key);
+    Ok(())
+}
+
+/// Start the gRPC cluster server if this node advertises a gRPC endpoint
+#[cfg(feature = "cluster-grpc")]
+pub fn spawn_grpc_server(state: &AppState) -> Result<(), Error> {
+    use hafiz_cluster::grpc::GrpcServer;
+
+    let Some(cluster) = state.cluster.clone() else {
+        return Ok(());
+    };
+    let Some(endpoint) = cluster.config().grpc_endpoint.clone() else {
+        return Ok(());
+    };
+
+    let port = url::Url::parse(&endpoint)
+        .ok()
+        .and_then(|u| u.port())
+        .ok_or_else(|| Error::InvalidArgument(format!("Invalid cluster gRPC endpoint: {}", endpoint)))?;
+    let addr = std::net::SocketAddr::from(([0, 0, 0, 0], port));
+
+    let handler = Arc::new(GrpcHandler {
+        state: state.clone(),
+    });
+    let server = GrpcServer::new(cluster.config(), handler)
+        .map_err(|e| Error::InternalError(format!("Failed to configure cluster gRPC server: {}", e)))?;
+
+    tokio::spawn(async move {
+        if let Err(e) = server.serve(addr, std::future::pending()).await {
+            tracing::error!("Cluster gRPC server stopped: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// Serves gRPC cluster calls with the same logic as the HTTP endpoints
+#[cfg(feature = "cluster-grpc")]
+struct GrpcHandler {
+    state: AppState,
+}
+
+#[cfg(feature = "cluster-grpc")]
+#[async_trait::async_trait]
+impl hafiz_cluster::grpc::ClusterRpcHandler for GrpcHandler {
+    async fn handle_message(&self, message: ClusterMessage) -> ClusterResult<ClusterMessage> {
+        let cluster = self
+            .state
+            .cluster
+            .as_ref()
+            .ok_or_else(|| ClusterError::Internal("Cluster mode not enabled".to_string()))?;
+        cluster.handle_message(message).await
+    }
+
+    async fn call(&self, path: &str, body: Bytes) -> ClusterResult<Bytes> {
+        if path == TREE_PATH || path == LEAVES_PATH {
+            let service = anti_entropy(&self.state).map_err(|(_, msg)| ClusterError::Internal(msg))?;
+            let response = if path == TREE_PATH {
+                serde_json::to_vec(&service.handle_tree(serde_json::from_slice(&body)?).await?)?
+            } else {
+                serde_json::to_vec(&service.handle_leaves(serde_json::from_slice(&body)?).await?)?
+            };
+            return Ok(response.into());
+        }
+
+        let raft = raft_node(&self.state).map_err(|(_, msg)| ClusterError::Internal(msg))?;
+
+        let response = match path {
+            "/cluster/raft/vote" => {
+                serde_json::to_vec(&raft.handle_request_vote(serde_json::from_slice(&body)?))?
+            }
+            "/cluster/raft/append" => {
+                serde_json::to_vec(&raft.handle_append_entries(serde_json::from_slice(&body)?)?)?
+            }
+            "/cluster/raft/snapshot" => serde_json::to_vec(
+                &raft
+                    .handle_install_snapshot(serde_json::from_slice(&body)?)
+                    .await?,
+            )?,
+            "/cluster/raft/forward" => {
+                serde_json::to_vec(&raft.handle_forward(serde_json::from_slice(&body)?).await)?
+            }
+            _ => {
+                return Err(ClusterError::Internal(format!(
+                    "Unknown cluster path: {}",
+                    path
+                )))
+            }
+        };
+
+        Ok(response.into())
+    }
+
+    async fn get_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        version_id: Option<&str>,
+    ) -> ClusterResult<Option<Bytes>> {
+        Ok(read_object_data(&self.state, bucket, key, version_id).await?)
+    }
+
+    async fn put_object(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Bytes,
+        metadata: HashMap<String, String>,
+    ) -> ClusterResult<()> {
+        Ok(store_object_data(&self.state, bucket, key, data, metadata).await?)
+    }
+}
@@ -0,0 +1,302 @@
+//! Metadata writes under Raft consensus
+//!
+//! With `metadata_consensus = "raft"`, bucket and object metadata changes
+//! are proposed to the Raft log and applied on every node in log order
+//! instead of being written to the local store directly. Routes make these
+//! writes through the functions here, which fall back to the local store
+//! when consensus is not running.
+//!
+//! Only the changes a [`MetadataCommand`](hafiz_cluster::MetadataCommand)
+//! describes are replicated: buckets, versioning, bucket policies, objects
+//! and their versions and delete markers, and object tags, ACLs, retention
+//! and legal holds. Other bucket settings, multipart uploads in progress
+//! and bucket snapshot restores stay local to the node that received them.
+
+use hafiz_core::types::{Bucket, ObjectInternal, TagSet, VersioningStatus};
+use hafiz_core::Result;
+use hafiz_metadata::repository::DeletedEntry;
+#[cfg(feature = "cluster")]
+use hafiz_core::Error;
+#[cfg(feature = "cluster")]
+use hafiz_cluster::{ClusterError, MetadataCommand};
+
+use crate::server::AppState;
+
+/// Commit `command` through the Raft log, `None` if consensus is not running
+#[cfg(feature = "cluster")]
+async fn replicate(state: &AppState, command: impl FnOnce() -> MetadataCommand) -> Option<Result<()>> {
+    let raft = state.cluster.as_ref()?.raft()?;
+    Some(raft.propose(command()).await.map(|_| ()).map_err(consensus_error))
+}
+
+#[cfg(feature = "cluster")]
+fn consensus_error(error: ClusterError) -> Error {
+    match error {
+        // The command reached the state machine, which refused it
+        ClusterError::Storage(e) => e,
+        e @ (ClusterError::NotLeader(_)
+        | ClusterError::ProposalTimeout
+        | ClusterError::Transport(_)
+        | ClusterError::NodeUnreachable(_)) => {
+            Error::ServiceUnavailable(format!("Metadata consensus unavailable: {}", e))
+        }
+        e => Error::InternalError(e.to_string()),
+    }
+}
+
+pub async fn create_bucket(state: &AppState, bucket: &Bucket) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::CreateBucket { bucket: bucket.clone() }).await {
+        return result;
+    }
+    state.metadata.create_bucket(bucket).await
+}
+
+pub async fn delete_bucket(state: &AppState, name: &str) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::DeleteBucket { name: name.to_string() }).await {
+        return result;
+    }
+    state.metadata.delete_bucket(name).await
+}
+
+pub async fn set_bucket_versioning(state: &AppState, name: &str, status: VersioningStatus) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::SetBucketVersioning {
+        name: name.to_string(),
+        status,
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.set_bucket_versioning(name, status).await
+}
+
+pub async fn put_bucket_policy(state: &AppState, bucket: &str, policy: &str) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::PutBucketPolicy {
+        bucket: bucket.to_string(),
+        policy: policy.to_string(),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.put_bucket_policy(bucket, policy).await
+}
+
+pub async fn delete_bucket_policy(state: &AppState, bucket: &str) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::DeleteBucketPolicy {
+        bucket: bucket.to_string(),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.delete_bucket_policy(bucket).await
+}
+
+pub async fn put_object(state: &AppState, object: &ObjectInternal) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::PutObject { object: object.clone() }).await {
+        return result;
+    }
+    state.metadata.put_object(object).await
+}
+
+/// Delete the unversioned object `key`
+pub async fn delete_object(state: &AppState, bucket: &str, key: &str) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::DeleteObject {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.delete_object(bucket, key).await
+}
+
+/// Delete one version of `key`; whether it existed
+pub async fn delete_object_version(state: &AppState, bucket: &str, key: &str, version_id: &str) -> Result<bool> {
+    #[cfg(feature = "cluster")]
+    if state.cluster.as_ref().is_some_and(|c| c.raft().is_some()) {
+        let existed = state
+            .metadata
+            .get_object_version(bucket, key, Some(version_id))
+            .await?
+            .is_some();
+        let command = || MetadataCommand::DeleteObjectVersion {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            version_id: version_id.to_string(),
+        };
+        if let Some(result) = replicate(state, command).await {
+            return result.map(|_| existed);
+        }
+    }
+    state.metadata.delete_object_version(bucket, key, version_id).await
+}
+
+/// Hide `key` under a new delete marker; the marker's version ID
+pub async fn create_delete_marker(state: &AppState, bucket: &str, key: &str) -> Result<String> {
+    #[cfg(feature = "cluster")]
+    {
+        // The version ID is chosen here so every node records the same one
+        let version_id = ObjectInternal::generate_version_id();
+        let marker = ObjectInternal::as_delete_marker(bucket.to_string(), key.to_string(), version_id.clone());
+        if let Some(result) = replicate(state, || MetadataCommand::PutObject { object: marker }).await {
+            return result.map(|_| version_id);
+        }
+    }
+    state.metadata.create_delete_marker(bucket, key).await
+}
+
+/// Delete many objects of `bucket` in one step; see
+/// [`MetadataStore::delete_objects`](hafiz_metadata::MetadataStore::delete_objects)
+pub async fn delete_objects(
+    state: &AppState,
+    bucket: &str,
+    entries: &[(String, Option<String>)],
+    add_delete_markers: bool,
+) -> Result<Vec<DeletedEntry>> {
+    #[cfg(feature = "cluster")]
+    if state.cluster.as_ref().is_some_and(|c| c.raft().is_some()) {
+        // Marker version IDs are chosen here so every node records the same
+        // ones; outcomes are read before the command commits
+        let marker_ids: Option<Vec<String>> = add_delete_markers
+            .then(|| entries.iter().map(|_| ObjectInternal::generate_version_id()).collect());
+        let mut outcomes = Vec::with_capacity(entries.len());
+        for (i, (key, version_id)) in entries.iter().enumerate() {
+            outcomes.push(match (version_id, &marker_ids) {
+                (Some(vid), _) => {
+                    let was_marker = state
+                        .metadata
+                        .get_object_version(bucket, key, Some(vid))
+                        .await?
+                        .is_some_and(|o| o.is_delete_marker);
+                    DeletedEntry {
+                        delete_marker: was_marker,
+                        delete_marker_version_id: was_marker.then(|| vid.clone()),
+                    }
+                }
+                (None, Some(marker_ids)) => DeletedEntry {
+                    delete_marker: true,
+                    delete_marker_version_id: Some(marker_ids[i].clone()),
+                },
+                (None, None) => DeletedEntry::default(),
+            });
+        }
+
+        let command = || MetadataCommand::DeleteObjects {
+            bucket: bucket.to_string(),
+            entries: entries.to_vec(),
+            marker_ids,
+        };
+        if let Some(result) = replicate(state, command).await {
+            return result.map(|_| outcomes);
+        }
+    }
+    state.metadata.delete_objects(bucket, entries, add_delete_markers).await
+}
+
+pub async fn put_object_tags(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    tags: &TagSet,
+) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::PutObjectTags {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        version_id: version_id.map(str::to_string),
+        tags: tags.clone(),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.put_object_tags(bucket, key, version_id, tags).await
+}
+
+pub async fn delete_object_tags(state: &AppState, bucket: &str, key: &str, version_id: Option<&str>) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::DeleteObjectTags {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        version_id: version_id.map(str::to_string),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.delete_object_tags(bucket, key, version_id).await
+}
+
+pub async fn put_object_acl(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    acl_xml: &str,
+) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::PutObjectAcl {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        version_id: version_id.map(str::to_string),
+        acl_xml: acl_xml.to_string(),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.put_object_acl(bucket, key, version_id, acl_xml).await
+}
+
+pub async fn put_object_retention(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    retention_xml: &str,
+) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::PutObjectRetention {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        version_id: version_id.map(str::to_string),
+        retention_xml: retention_xml.to_string(),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.put_object_retention(bucket, key, version_id, retention_xml).await
+}
+
+pub async fn put_object_legal_hold(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    hold_xml: &str,
+) -> Result<()> {
+    #[cfg(feature = "cluster")]
+    if let Some(result) = replicate(state, || MetadataCommand::PutObjectLegalHold {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        version_id: version_id.map(str::to_string),
+        hold_xml: hold_xml.to_string(),
+    })
+    .await
+    {
+        return result;
+    }
+    state.metadata.put_object_legal_hold(bucket, key, version_id, hold_xml).await
+}
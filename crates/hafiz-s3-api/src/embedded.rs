@@ -0,0 +1,287 @@
+//! Hafiz inside another Rust application
+//!
+//! [`HafizServer::builder`] starts a server in the calling process, for
+//! integration tests and desktop apps that want an S3 endpoint without
+//! running the `hafiz` binary. The defaults suit tests: the server listens
+//! on a free port of 127.0.0.1, metadata lives in an in-memory SQLite
+//! database, and objects are stored in a fresh temporary directory that is
+//! removed when the server stops.
+//!
+//! ```no_run
+//! use hafiz_s3_api::HafizServer;
+//!
+//! # async fn example() -> hafiz_core::Result<()> {
+//! let server = HafizServer::builder()
+//!     .credentials("test-key", "test-secret")
+//!     .start()
+//!     .await?;
+//!
+//! println!("S3 endpoint at {}", server.endpoint());
+//!
+//! server.shutdown().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use hafiz_core::config::HafizConfig;
+use hafiz_core::{Error, Result};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::metrics::MetricsRecorder;
+use crate::server::S3Server;
+use crate::transform::ObjectTransform;
+
+/// Metadata database of embedded servers unless one is configured
+const IN_MEMORY_DATABASE: &str = "sqlite::memory:";
+
+/// Configures and starts a [`HafizServer`]
+pub struct HafizServerBuilder {
+    config: HafizConfig,
+    data_dir: Option<PathBuf>,
+    database_url: Option<String>,
+    transforms: Vec<Arc<dyn ObjectTransform>>,
+}
+
+impl HafizServerBuilder {
+    fn new() -> Self {
+        let mut config = HafizConfig::default();
+        config.server.bind_address = "127.0.0.1".to_string();
+        config.server.port = 0;
+
+        Self {
+            config,
+            data_dir: None,
+            database_url: None,
+            transforms: Vec::new(),
+        }
+    }
+
+    /// Start from a full configuration
+    ///
+    /// The data directory and database are still the embedded defaults
+    /// unless set with [`data_dir`](Self::data_dir) and
+    /// [`database_url`](Self::database_url).
+    pub fn config(mut self, config: HafizConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Store objects under `path` instead of a temporary directory
+    ///
+    /// The directory is kept when the server stops.
+    pub fn data_dir(mut self, path: impl Into<PathBuf>) -> Self {
+        self.data_dir = Some(path.into());
+        self
+    }
+
+    /// Keep metadata in this database instead of in memory
+    pub fn database_url(mut self, url: impl Into<String>) -> Self {
+        self.database_url = Some(url.into());
+        self
+    }
+
+    pub fn bind_address(mut self, address: impl Into<String>) -> Self {
+        self.config.server.bind_address = address.into();
+        self
+    }
+
+    /// Port to listen on; 0, the default, picks a free one
+    pub fn port(mut self, port: u16) -> Self {
+        self.config.server.port = port;
+        self
+    }
+
+    /// Access key and secret key of the root user
+    pub fn credentials(mut self, access_key: impl Into<String>, secret_key: impl Into<String>) -> Self {
+        self.config.auth.root_access_key = access_key.into();
+        self.config.auth.root_secret_key = secret_key.into();
+        self
+    }
+
+    /// Make a GET transform available to `[transform]` rules
+    pub fn with_transform(mut self, transform: Arc<dyn ObjectTransform>) -> Self {
+        self.transforms.push(transform);
+        self
+    }
+
+    /// Open storage and metadata and start serving
+    ///
+    /// Returns once the listener is bound, so requests can be sent to
+    /// [`HafizServer::endpoint`] right away.
+    pub async fn start(self) -> Result<HafizServer> {
+        let mut config = self.config;
+        let temp_dir = match self.data_dir {
+            Some(path) => {
+                config.storage.data_dir = path;
+                None
+            }
+            None => {
+                let path = std::env::temp_dir().join(format!("hafiz-embedded-{}", uuid::Uuid::new_v4()));
+                std::fs::create_dir_all(&path)?;
+                config.storage.data_dir = path.clone();
+                Some(path)
+            }
+        };
+        match self.database_url {
+            Some(url) => config.database.url = url,
+            None => config.database.url = IN_MEMORY_DATABASE.to_string(),
+        }
+
+        let started = start(config, self.transforms).await;
+        if started.is_err() {
+            if let Some(path) = &temp_dir {
+                remove_temp_dir(path);
+            }
+        }
+        let (server, addr, shutdown, task) = started?;
+
+        let scheme = if server.config().tls.enabled { "https" } else { "http" };
+        info!("Embedded Hafiz server listening on {}://{}", scheme, addr);
+
+        Ok(HafizServer {
+            addr,
+            scheme,
+            access_key: server.config().auth.root_access_key.clone(),
+            secret_key: server.config().auth.root_secret_key.clone(),
+            shutdown,
+            task: Some(task),
+            temp_dir,
+        })
+    }
+}
+
+type Started = (Arc<S3Server>, SocketAddr, watch::Sender<bool>, JoinHandle<Result<()>>);
+
+async fn start(config: HafizConfig, transforms: Vec<Arc<dyn ObjectTransform>>) -> Result<Started> {
+    let addr = format!("{}:{}", config.server.bind_address, config.server.port);
+    let server = transforms
+        .into_iter()
+        .fold(S3Server::new(config), |server, transform| server.with_transform(transform));
+    let server = Arc::new(server);
+
+    let (state, app) = server
+        .prepare(Arc::new(MetricsRecorder::shared().with_config(&server.config().metrics)))
+        .await?;
+    let listener = TcpListener::bind(&addr).await?;
+    let local_addr = listener.local_addr()?;
+
+    let (shutdown, shutdown_rx) = watch::channel(false);
+    let serving = server.clone();
+    let task = tokio::spawn(async move { serving.serve_until(listener, state, app, shutdown_rx).await });
+
+    Ok((server, local_addr, shutdown, task))
+}
+
+fn remove_temp_dir(path: &Path) {
+    if let Err(e) = std::fs::remove_dir_all(path) {
+        warn!("Failed to remove {}: {}", path.display(), e);
+    }
+}
+
+/// A Hafiz server running inside this process
+///
+/// Dropping the handle stops the server without waiting for it; call
+/// [`shutdown`](Self::shutdown) to wait until in-flight requests finished
+/// and the metadata database is closed.
+pub struct HafizServer {
+    addr: SocketAddr,
+    scheme: &'static str,
+    access_key: String,
+    secret_key: String,
+    shutdown: watch::Sender<bool>,
+    task: Option<JoinHandle<Result<()>>>,
+    /// Data directory created for this server, removed when it stops
+    temp_dir: Option<PathBuf>,
+}
+
+impl HafizServer {
+    pub fn builder() -> HafizServerBuilder {
+        HafizServerBuilder::new()
+    }
+
+    /// Address the server is bound to
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Endpoint URL for S3 clients, e.g. `http://127.0.0.1:53412`
+    pub fn endpoint(&self) -> String {
+        format!("{}://{}", self.scheme, self.addr)
+    }
+
+    /// Access key of the root user
+    pub fn access_key(&self) -> &str {
+        &self.access_key
+    }
+
+    /// Secret key of the root user
+    pub fn secret_key(&self) -> &str {
+        &self.secret_key
+    }
+
+    /// Stop accepting connections, let open requests finish and close the
+    /// metadata database
+    pub async fn shutdown(mut self) -> Result<()> {
+        let _ = self.shutdown.send(true);
+        let served = match self.task.take() {
+            Some(task) => task
+                .await
+                .map_err(|e| Error::InternalError(format!("Embedded server task failed: {}", e)))?,
+            None => Ok(()),
+        };
+        if let Some(path) = self.temp_dir.take() {
+            remove_temp_dir(&path);
+        }
+        served
+    }
+}
+
+impl Drop for HafizServer {
+    fn drop(&mut self) {
+        let _ = self.shutdown.send(true);
+        if let Some(path) = self.temp_dir.take() {
+            // The server may still be writing; remove the directory once it stopped
+            match self.task.take() {
+                Some(task) if tokio::runtime::Handle::try_current().is_ok() => {
+                    tokio::spawn(async move {
+                        let _ = task.await;
+                        remove_temp_dir(&path);
+                    });
+                }
+                _ => remove_temp_dir(&path),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_embedded_server_binds_free_port() {
+        let server = HafizServer::builder()
+            .credentials("embedded-key", "embedded-secret")
+            .start()
+            .await
+            .unwrap();
+
+        let addr = server.local_addr();
+        assert_ne!(addr.port(), 0);
+        assert_eq!(server.endpoint(), format!("http://{}", addr));
+        assert_eq!(server.access_key(), "embedded-key");
+        tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        let data_dir = server.temp_dir.clone().unwrap();
+        assert!(data_dir.exists());
+        server.shutdown().await.unwrap();
+        assert!(!data_dir.exists());
+    }
+}
@@ -8,6 +8,7 @@ use hafiz_core::types::{
 };
 use reqwest::Client;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
@@ -61,6 +62,8 @@ pub struct EventDispatcher {
     sender: mpsc::Sender<DispatchTask>,
     http_client: Client,
     config: EventDispatcherConfig,
+    /// Events queued or being delivered
+    pending: Arc<AtomicUsize>,
 }
 
 struct DispatchTask {
@@ -78,15 +81,17 @@ impl EventDispatcher {
             .build()
             .expect("Failed to create HTTP client");
 
+        let pending = Arc::new(AtomicUsize::new(0));
         let dispatcher = Self {
             sender,
             http_client: http_client.clone(),
             config: config.clone(),
+            pending: pending.clone(),
         };
 
         // Start worker tasks
         let worker_config = config.clone();
-        tokio::spawn(Self::dispatch_worker(receiver, http_client, worker_config));
+        tokio::spawn(Self::dispatch_worker(receiver, http_client, worker_config, pending));
 
         dispatcher
     }
@@ -121,10 +126,30 @@ impl EventDispatcher {
             config_id: "default".to_string(),
         };
 
-        self.sender
-            .send(task)
-            .await
-            .map_err(|e| format!("Failed to queue event: {}", e))
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.sender.send(task).await.map_err(|e| {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            format!("Failed to queue event: {}", e)
+        })
+    }
+
+    /// Number of events queued or being delivered
+    pub fn pending(&self) -> usize {
+        self.pending.load(Ordering::SeqCst)
+    }
+
+    /// Wait until every queued event has been delivered or given up on
+    ///
+    /// Returns false if events were still pending when `timeout` ran out.
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.pending() > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
     }
 
     /// Dispatch event synchronously (blocking)
@@ -184,6 +209,7 @@ impl EventDispatcher {
         mut receiver: mpsc::Receiver<DispatchTask>,
         http_client: Client,
         config: EventDispatcherConfig,
+        pending: Arc<AtomicUsize>,
     ) {
         info!("Event dispatch worker started");
 
@@ -244,6 +270,7 @@ impl EventDispatcher {
                     }
                 }
             }
+            pending.fetch_sub(1, Ordering::SeqCst);
         }
 
         info!("Event dispatch worker stopped");
@@ -10,3 +10,33 @@ mod dispatcher;
 pub use dispatcher::{
     EventDispatcher, EventDispatcherConfig, S3Event, DispatchResult, NotificationConfigStore,
 };
+
+use hafiz_core::types::NotificationConfiguration;
+use tracing::warn;
+
+use crate::server::AppState;
+
+/// Send `event` to the notification targets of its bucket
+///
+/// Notifications never fail the operation that caused them; errors are
+/// logged.
+pub async fn notify(state: &AppState, event: S3Event) {
+    let config = match state.metadata.get_bucket_notification(&event.bucket).await {
+        Ok(Some(json)) => json,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to read notification config of {}: {}", event.bucket, e);
+            return;
+        }
+    };
+    let config: NotificationConfiguration = match serde_json::from_str(&config) {
+        Ok(config) => config,
+        Err(e) => {
+            warn!("Invalid notification config of {}: {}", event.bucket, e);
+            return;
+        }
+    };
+    if let Err(e) = state.events.dispatch(event, &config).await {
+        warn!("{}", e);
+    }
+}
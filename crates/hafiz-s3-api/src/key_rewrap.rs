@@ -0,0 +1,452 @@
+//! Re-wrapping of data encryption keys under a new master key
+//!
+//! The data of each SSE-S3 object is encrypted under a DEK of its own,
+//! which is stored encrypted under the master key or an account key
+//! derived from it. Replacing the master key therefore only needs the DEKs
+//! re-encrypted, not the data. To rotate, configure the new key as the
+//! master key and the old one as `encryption.previous_master_key`, restart,
+//! and start a run; once it has completed, the previous key can go.
+//!
+//! A run walks every SSE-S3 object version in bucket, key and version
+//! order; the DEK is re-wrapped under the same account key ID, derived from
+//! the new master key. Its position and counts are written to the metadata
+//! database after each page, so a run that was cancelled, or interrupted
+//! by a restart, resumes where it stopped. DEKs that already open under the
+//! new key are left alone, so repeating a run is harmless. The two keys are
+//! recognized by their check values: a run recorded for other keys starts
+//! over from the beginning.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, Utc};
+use hafiz_core::types::{EncryptionInfo, EncryptionType};
+use hafiz_core::{Error, Result};
+use hafiz_crypto::{key_check, AccountKeys};
+use hafiz_metadata::repository::{KeyRewrapRecord, WrappedKey};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::master_key::current_master_key;
+use crate::server::AppState;
+
+/// Object versions read per metadata page
+const PAGE_SIZE: i32 = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RewrapState {
+    Running,
+    /// Cancelled; starting again resumes it
+    Paused,
+    Completed,
+    /// Stopped by an error; starting again resumes it
+    Failed,
+}
+
+impl RewrapState {
+    fn as_str(self) -> &'static str {
+        match self {
+            RewrapState::Running => "running",
+            RewrapState::Paused => "paused",
+            RewrapState::Completed => "completed",
+            RewrapState::Failed => "failed",
+        }
+    }
+
+    fn parse(s: &str) -> Self {
+        match s {
+            "running" => RewrapState::Running,
+            "paused" => RewrapState::Paused,
+            "completed" => RewrapState::Completed,
+            _ => RewrapState::Failed,
+        }
+    }
+}
+
+/// A run as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct RewrapRun {
+    pub state: RewrapState,
+    /// Check values of the previous and the current master key
+    pub from_key_check: String,
+    pub to_key_check: String,
+    /// `bucket/key` of the last object version done
+    pub position: Option<String>,
+    pub objects_scanned: u64,
+    pub objects_rewrapped: u64,
+    /// Versions whose DEK was already under the current key
+    pub objects_current: u64,
+    /// Versions whose DEK opened under neither key
+    pub objects_failed: u64,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub error: Option<String>,
+}
+
+impl From<KeyRewrapRecord> for RewrapRun {
+    fn from(record: KeyRewrapRecord) -> Self {
+        let (bucket, key, _) = &record.after;
+        Self {
+            state: RewrapState::parse(&record.state),
+            position: (!bucket.is_empty()).then(|| format!("{}/{}", bucket, key)),
+            from_key_check: record.from_key_check,
+            to_key_check: record.to_key_check,
+            objects_scanned: record.objects_scanned,
+            objects_rewrapped: record.objects_rewrapped,
+            objects_current: record.objects_current,
+            objects_failed: record.objects_failed,
+            started_at: record.started_at,
+            updated_at: record.updated_at,
+            finished_at: record.finished_at,
+            error: record.error,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyRewrapStatus {
+    /// Whether a run is in progress on this server
+    pub running: bool,
+    pub last_run: Option<RewrapRun>,
+}
+
+/// The master keys DEKs move between
+struct RewrapKeys {
+    from: AccountKeys,
+    to: AccountKeys,
+    from_check: String,
+    to_check: String,
+}
+
+/// Runs DEK re-wrapping in the background
+#[derive(Default)]
+pub struct KeyRewrap {
+    running: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+impl KeyRewrap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub async fn status(&self, state: &AppState) -> Result<KeyRewrapStatus> {
+        Ok(KeyRewrapStatus {
+            running: self.is_running(),
+            last_run: state.metadata.get_key_rewrap().await?.map(RewrapRun::from),
+        })
+    }
+
+    /// Stop the run after the object version in progress; `false` if none
+    /// is running
+    pub fn cancel(&self) -> bool {
+        if !self.is_running() {
+            return false;
+        }
+        self.cancelled.store(true, Ordering::SeqCst);
+        true
+    }
+
+    /// Start a run in the background, resuming the recorded one if it was
+    /// for the same keys and did not complete
+    pub async fn start(self: &Arc<Self>, state: &AppState) -> Result<KeyRewrapStatus> {
+        let keys = rewrap_keys(state).await?;
+        if self
+            .running
+            .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+            .is_err()
+        {
+            return Err(Error::OperationAborted("A key re-wrap is already running".into()));
+        }
+        self.cancelled.store(false, Ordering::SeqCst);
+
+        let now = Utc::now();
+        let record = match state.metadata.get_key_rewrap().await {
+            Ok(Some(mut record))
+                if record.from_key_check == keys.from_check
+                    && record.to_key_check == keys.to_check
+                    && RewrapState::parse(&record.state) != RewrapState::Completed =>
+            {
+                info!("Resuming key re-wrap after {} object versions", record.objects_scanned);
+                record.state = RewrapState::Running.as_str().to_string();
+                record.updated_at = now;
+                record.error = None;
+                record
+            }
+            Ok(_) => KeyRewrapRecord {
+                from_key_check: keys.from_check.clone(),
+                to_key_check: keys.to_check.clone(),
+                state: RewrapState::Running.as_str().to_string(),
+                after: Default::default(),
+                objects_scanned: 0,
+                objects_rewrapped: 0,
+                objects_current: 0,
+                objects_failed: 0,
+                started_at: now,
+                updated_at: now,
+                finished_at: None,
+                error: None,
+            },
+            Err(e) => {
+                self.running.store(false, Ordering::SeqCst);
+                return Err(e);
+            }
+        };
+        if let Err(e) = state.metadata.put_key_rewrap(&record).await {
+            self.running.store(false, Ordering::SeqCst);
+            return Err(e);
+        }
+
+        let rewrap = self.clone();
+        let task_state = state.clone();
+        tokio::spawn(async move {
+            rewrap.run(&task_state, &keys, record).await;
+            rewrap.running.store(false, Ordering::SeqCst);
+        });
+        self.status(state).await
+    }
+
+    /// Resume a run the last shutdown interrupted
+    pub fn resume_interrupted(self: &Arc<Self>, state: AppState) {
+        let rewrap = self.clone();
+        tokio::spawn(async move {
+            match state.metadata.get_key_rewrap().await {
+                Ok(Some(record)) if RewrapState::parse(&record.state) == RewrapState::Running => {}
+                Ok(_) => return,
+                Err(e) => {
+                    warn!("Failed to read key re-wrap progress: {}", e);
+                    return;
+                }
+            }
+            if let Err(e) = rewrap.start(&state).await {
+                warn!("Interrupted key re-wrap not resumed: {}", e);
+            }
+        });
+    }
+
+    async fn run(&self, state: &AppState, keys: &RewrapKeys, mut record: KeyRewrapRecord) {
+        let outcome = loop {
+            if self.cancelled.load(Ordering::SeqCst) {
+                break Ok(RewrapState::Paused);
+            }
+            let (bucket, key, version_id) = &record.after;
+            let page = match state
+                .metadata
+                .list_wrapped_keys((bucket, key, version_id), PAGE_SIZE)
+                .await
+            {
+                Ok(page) => page,
+                Err(e) => break Err(e),
+            };
+            let Some(last) = page.last() else {
+                break Ok(RewrapState::Completed);
+            };
+            let after = (last.bucket.clone(), last.key.clone(), last.version_id.clone());
+
+            for object in &page {
+                record.objects_scanned += 1;
+                let result = match rewrap_object(state, keys, object).await {
+                    Ok(true) => {
+                        record.objects_rewrapped += 1;
+                        "rewrapped"
+                    }
+                    Ok(false) => {
+                        record.objects_current += 1;
+                        "current"
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to re-wrap the key of {}/{} version {}: {}",
+                            object.bucket, object.key, object.version_id, e
+                        );
+                        record.objects_failed += 1;
+                        "failed"
+                    }
+                };
+                metrics::counter!("hafiz_key_rewrap_objects_total", "result" => result).increment(1);
+            }
+
+            record.after = after;
+            record.updated_at = Utc::now();
+            if let Err(e) = state.metadata.put_key_rewrap(&record).await {
+                break Err(e);
+            }
+        };
+
+        let final_state = match outcome {
+            Ok(final_state) => final_state,
+            Err(e) => {
+                warn!("Key re-wrap failed: {}", e);
+                record.error = Some(e.to_string());
+                RewrapState::Failed
+            }
+        };
+        record.state = final_state.as_str().to_string();
+        record.updated_at = Utc::now();
+        if final_state == RewrapState::Completed {
+            record.finished_at = Some(record.updated_at);
+        }
+        if let Err(e) = state.metadata.put_key_rewrap(&record).await {
+            warn!("Failed to record key re-wrap progress: {}", e);
+        }
+        info!(
+            "Key re-wrap {}: {} object versions scanned, {} re-wrapped, {} already current, {} failed",
+            final_state.as_str(),
+            record.objects_scanned,
+            record.objects_rewrapped,
+            record.objects_current,
+            record.objects_failed
+        );
+    }
+}
+
+/// The previous and the current master key, as configured
+async fn rewrap_keys(state: &AppState) -> Result<RewrapKeys> {
+    let config = &state.config.encryption;
+    let to = current_master_key(config, &state.metadata)
+        .await?
+        .ok_or_else(|| Error::InvalidArgument("Encryption is not enabled".into()))?;
+    let from = config.get_previous_master_key()?.ok_or_else(|| {
+        Error::InvalidArgument("No previous_master_key is configured to re-wrap from".into())
+    })?;
+    let (from_check, to_check) = (key_check(&from), key_check(&to));
+    if from_check == to_check {
+        return Err(Error::InvalidArgument(
+            "previous_master_key is the current master key".into(),
+        ));
+    }
+    let keys = |key: &[u8]| AccountKeys::new(key).map_err(|e| Error::InternalError(e.to_string()));
+    Ok(RewrapKeys {
+        from: keys(&from)?,
+        to: keys(&to)?,
+        from_check,
+        to_check,
+    })
+}
+
+/// Re-wrap the DEK of one object version; `false` if it was already
+/// under the current key or changed meanwhile
+async fn rewrap_object(state: &AppState, keys: &RewrapKeys, object: &WrappedKey) -> Result<bool> {
+    let Some(encryption) = rewrap_info(&keys.from, &keys.to, &object.encryption)? else {
+        return Ok(false);
+    };
+    let previous_dek = object.encryption.encrypted_dek.as_deref().unwrap_or_default();
+    state
+        .metadata
+        .replace_wrapped_key(
+            &object.bucket,
+            &object.key,
+            &object.version_id,
+            previous_dek,
+            &encryption,
+        )
+        .await
+}
+
+/// `info` with its DEK moved from `from` to `to`; `None` if there is no DEK
+/// or it is already under `to`
+fn rewrap_info(
+    from: &AccountKeys,
+    to: &AccountKeys,
+    info: &EncryptionInfo,
+) -> Result<Option<EncryptionInfo>> {
+    let (Some(encrypted_dek), Some(dek_nonce)) = (&info.encrypted_dek, &info.dek_nonce) else {
+        return Ok(None);
+    };
+    if info.encryption_type != EncryptionType::SseS3 {
+        return Ok(None);
+    }
+    let decode = |value: &str| {
+        BASE64
+            .decode(value)
+            .map_err(|e| Error::InternalError(format!("Invalid stored DEK: {}", e)))
+    };
+    let (encrypted_dek, dek_nonce) = (decode(encrypted_dek)?, decode(dek_nonce)?);
+    let key_id = info.key_id.as_deref();
+
+    let current = to
+        .key_manager(key_id)
+        .map_err(|e| Error::InternalError(e.to_string()))?;
+    if current.decrypt_dek(&encrypted_dek, &dek_nonce).is_ok() {
+        return Ok(None);
+    }
+    let (encrypted_dek, dek_nonce) = from
+        .rewrap_dek(to, key_id, &encrypted_dek, &dek_nonce)
+        .map_err(|e| Error::InternalError(e.to_string()))?;
+
+    Ok(Some(EncryptionInfo {
+        encrypted_dek: Some(BASE64.encode(encrypted_dek)),
+        dek_nonce: Some(BASE64.encode(dek_nonce)),
+        ..info.clone()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wrapped(keys: &AccountKeys, key_id: Option<&str>) -> EncryptionInfo {
+        let manager = keys.key_manager(key_id).unwrap();
+        let (encrypted_dek, dek_nonce) = manager.encrypt_dek(&manager.generate_dek()).unwrap();
+        EncryptionInfo {
+            encryption_type: EncryptionType::SseS3,
+            encrypted_dek: Some(BASE64.encode(encrypted_dek)),
+            dek_nonce: Some(BASE64.encode(dek_nonce)),
+            key_id: key_id.map(String::from),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rewrap_info() {
+        let from = AccountKeys::new(&[1; 32]).unwrap();
+        let to = AccountKeys::new(&[2; 32]).unwrap();
+        let other = AccountKeys::new(&[3; 32]).unwrap();
+
+        for key_id in [Some("acme/v1"), None] {
+            let info = wrapped(&from, key_id);
+            let moved = rewrap_info(&from, &to, &info).unwrap().unwrap();
+            assert_eq!(moved.key_id, info.key_id);
+            assert_ne!(moved.encrypted_dek, info.encrypted_dek);
+
+            // Done once, a repeated run leaves it alone
+            assert!(rewrap_info(&from, &to, &moved).unwrap().is_none());
+        }
+
+        // A DEK under neither key fails
+        assert!(rewrap_info(&from, &to, &wrapped(&other, None)).is_err());
+
+        // Objects without a stored DEK are skipped
+        assert!(rewrap_info(&from, &to, &EncryptionInfo::none()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_rewrap_after_account_rotation() {
+        let from = AccountKeys::new(&[1; 32]).unwrap();
+        let to = AccountKeys::new(&[2; 32]).unwrap();
+        let data: Vec<u8> = (0..10_000).map(|i| (i % 251) as u8).collect();
+        let size = data.len() as i64;
+
+        // Written before acme's key was rotated, and after it
+        let (before, before_info) = crate::sse::seal_sse_s3(&from, "acme", 1, &data).unwrap();
+        let (after, after_info) = crate::sse::seal_sse_s3(&from, "acme", 2, &data).unwrap();
+
+        for (stored, info) in [(before, before_info), (after, after_info)] {
+            let moved = rewrap_info(&from, &to, &info).unwrap().unwrap();
+            assert_eq!(moved.key_id, info.key_id);
+            assert_eq!(moved.data_nonce, info.data_nonce);
+
+            // The data stays as stored and opens under the new master key only
+            let read = crate::sse::open(Some(&to), &moved, None, size, &stored, 0..size as u64);
+            assert_eq!(read.unwrap(), data);
+            assert!(crate::sse::open(Some(&from), &moved, None, size, &stored, 0..size as u64).is_err());
+        }
+    }
+}
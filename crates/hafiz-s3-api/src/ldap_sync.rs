@@ -0,0 +1,348 @@
+//! LDAP directory sync
+//!
+//! Provisions a Hafiz user for every directory account (or only members of
+//! mapped groups), keeps their policies in step with group membership and
+//! disables users who left the directory, so access follows the directory
+//! between logins too.
+//!
+//! Provisioned users get a generated key pair; hand it out through the
+//! admin API. Users are disabled, never deleted, so re-adding an account
+//! brings back the same keys.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hafiz_auth::{generate_credentials, LdapAuthProvider, LdapConfig, LdapUser};
+use hafiz_core::config::LdapConfigSection;
+use hafiz_core::types::{Credentials, DirectoryUser};
+use hafiz_core::Result;
+use parking_lot::RwLock;
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use crate::server::AppState;
+
+/// Outcome of one sync run
+#[derive(Debug, Clone, Serialize)]
+pub struct LdapSyncReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub directory_users: u64,
+    pub provisioned: u64,
+    pub updated: u64,
+    pub deactivated: u64,
+    pub reactivated: u64,
+    pub errors: Vec<String>,
+}
+
+impl LdapSyncReport {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            finished_at: None,
+            directory_users: 0,
+            provisioned: 0,
+            updated: 0,
+            deactivated: 0,
+            reactivated: 0,
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Sync state as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct LdapSyncStatus {
+    pub enabled: bool,
+    pub scheduled: bool,
+    pub interval_secs: u64,
+    pub running: bool,
+    pub last_run: Option<LdapSyncReport>,
+}
+
+/// What a sync does for one account
+#[derive(Debug)]
+enum SyncAction {
+    /// New eligible account
+    Provision(LdapUser),
+    /// Known account still in the directory
+    Refresh { link: DirectoryUser, user: LdapUser, eligible: bool },
+    /// Known account gone from the directory
+    Deactivate(DirectoryUser),
+}
+
+/// Work out what to do for each directory account and provisioned user
+fn plan(config: &LdapConfig, require_group: bool, users: Vec<LdapUser>, links: Vec<DirectoryUser>) -> Vec<SyncAction> {
+    let mut links: HashMap<String, DirectoryUser> =
+        links.into_iter().map(|l| (l.username.clone(), l)).collect();
+
+    let mut actions = Vec::new();
+    for user in users {
+        let eligible = !require_group || config.has_mapped_group(&user.groups);
+        match links.remove(&user.username) {
+            Some(link) => actions.push(SyncAction::Refresh { link, user, eligible }),
+            None if eligible => actions.push(SyncAction::Provision(user)),
+            None => {}
+        }
+    }
+    actions.extend(links.into_values().map(SyncAction::Deactivate));
+    actions
+}
+
+/// Periodic directory sync
+pub struct LdapSync {
+    config: LdapConfigSection,
+    provider: Option<LdapAuthProvider>,
+    running: AtomicBool,
+    last_run: RwLock<Option<LdapSyncReport>>,
+}
+
+impl LdapSync {
+    pub fn new(config: LdapConfigSection) -> Self {
+        let provider = config
+            .enabled
+            .then(|| LdapAuthProvider::new(LdapConfig::from(&config)));
+        Self {
+            config,
+            provider,
+            running: AtomicBool::new(false),
+            last_run: RwLock::new(None),
+        }
+    }
+
+    pub fn status(&self) -> LdapSyncStatus {
+        LdapSyncStatus {
+            enabled: self.provider.is_some(),
+            scheduled: self.provider.is_some() && self.config.sync_enabled,
+            interval_secs: self.config.sync_interval_seconds,
+            running: self.is_running(),
+            last_run: self.last_run.read().clone(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.provider.is_some()
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Start scheduled syncs if LDAP and sync are enabled
+    pub fn start(self: &Arc<Self>, state: AppState) {
+        if self.provider.is_none() || !self.config.sync_enabled {
+            return;
+        }
+
+        let sync = Arc::clone(self);
+        let interval = Duration::from_secs(self.config.sync_interval_seconds.max(60));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if sync.run(&state).await.is_none() {
+                    debug!("Skipping scheduled LDAP sync; a run is in progress");
+                }
+            }
+        });
+
+        info!("LDAP directory sync started (every {:?})", interval);
+    }
+
+    /// Sync users with the directory
+    ///
+    /// Returns `None` without doing anything if LDAP is disabled or a run
+    /// is already in progress.
+    pub async fn run(&self, state: &AppState) -> Option<LdapSyncReport> {
+        let provider = self.provider.as_ref()?;
+        if self.running.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+
+        let mut report = LdapSyncReport::new();
+        if let Err(e) = self.sync(provider, state, &mut report).await {
+            report.errors.push(e);
+        }
+        report.finished_at = Some(Utc::now());
+
+        metrics::gauge!("hafiz_ldap_sync_last_run_timestamp_seconds")
+            .set(report.started_at.timestamp() as f64);
+        info!(
+            "LDAP sync finished: {} directory users, {} provisioned, {} updated, {} deactivated, {} reactivated",
+            report.directory_users,
+            report.provisioned,
+            report.updated,
+            report.deactivated,
+            report.reactivated
+        );
+
+        *self.last_run.write() = Some(report.clone());
+        self.running.store(false, Ordering::SeqCst);
+        Some(report)
+    }
+
+    async fn sync(
+        &self,
+        provider: &LdapAuthProvider,
+        state: &AppState,
+        report: &mut LdapSyncReport,
+    ) -> std::result::Result<(), String> {
+        // Any directory error aborts the run: a partial listing would
+        // deactivate everyone who was missed
+        let users = provider.list_users().await?;
+        let links = state
+            .metadata
+            .list_directory_users()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if users.is_empty() && !links.is_empty() {
+            return Err("Directory returned no users; refusing to deactivate everyone".to_string());
+        }
+        report.directory_users = users.len() as u64;
+
+        let client = provider.client();
+        for action in plan(client.config(), self.config.sync_require_group, users, links) {
+            if let Err(e) = self.apply(state, action, report).await {
+                warn!("LDAP sync: {}", e);
+                report.errors.push(e.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    async fn apply(&self, state: &AppState, action: SyncAction, report: &mut LdapSyncReport) -> Result<()> {
+        match action {
+            SyncAction::Provision(user) => {
+                self.provision(state, &user).await?;
+                report.provisioned += 1;
+            }
+            SyncAction::Refresh { link, user, eligible } => {
+                let Some(mut cred) = state.metadata.get_credentials(&link.access_key).await? else {
+                    // Deleted through the admin API; start over if still eligible
+                    if eligible {
+                        self.provision(state, &user).await?;
+                        report.provisioned += 1;
+                    }
+                    return Ok(());
+                };
+
+                let name = user.display_name.clone().or_else(|| Some(user.username.clone()));
+                let changed = cred.enabled != eligible
+                    || cred.policies.contains(&"admin".to_string()) != user.is_admin()
+                    || cred.name != name
+                    || cred.email != user.email
+                    || link.policies != user.policies;
+                if !changed {
+                    return Ok(());
+                }
+
+                if cred.enabled && !eligible {
+                    report.deactivated += 1;
+                } else if !cred.enabled && eligible {
+                    report.reactivated += 1;
+                } else {
+                    report.updated += 1;
+                }
+                cred.enabled = eligible;
+                cred.policies = user.policies.clone();
+                cred.name = name;
+                cred.email = user.email.clone();
+                state.metadata.update_credentials(&cred).await?;
+                state.metadata.put_directory_user(&directory_user(&user, &cred.access_key)).await?;
+            }
+            SyncAction::Deactivate(link) => {
+                if let Some(mut cred) = state.metadata.get_credentials(&link.access_key).await? {
+                    if cred.enabled {
+                        cred.enabled = false;
+                        state.metadata.update_credentials(&cred).await?;
+                        report.deactivated += 1;
+                        info!("Disabled {} ({}): no longer in the directory", link.username, link.access_key);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn provision(&self, state: &AppState, user: &LdapUser) -> Result<()> {
+        let (access_key, secret_key) = generate_credentials();
+        let mut cred = Credentials::new(access_key, secret_key);
+        cred.name = user.display_name.clone().or_else(|| Some(user.username.clone()));
+        cred.email = user.email.clone();
+        cred.policies = user.policies.clone();
+
+        state.metadata.create_credentials(&cred).await?;
+        state.metadata.put_directory_user(&directory_user(user, &cred.access_key)).await?;
+        info!("Provisioned {} from the directory as {}", user.username, cred.access_key);
+        Ok(())
+    }
+}
+
+fn directory_user(user: &LdapUser, access_key: &str) -> DirectoryUser {
+    DirectoryUser {
+        username: user.username.clone(),
+        access_key: access_key.to_string(),
+        dn: user.dn.clone(),
+        groups: user.groups.clone(),
+        policies: user.policies.clone(),
+        synced_at: Utc::now(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(name: &str, groups: &[&str]) -> LdapUser {
+        LdapUser {
+            dn: format!("uid={},ou=users,dc=example,dc=com", name),
+            username: name.to_string(),
+            email: None,
+            display_name: None,
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+            policies: Vec::new(),
+            attributes: HashMap::new(),
+        }
+    }
+
+    fn link(name: &str) -> DirectoryUser {
+        DirectoryUser {
+            username: name.to_string(),
+            access_key: format!("AK{}", name),
+            dn: String::new(),
+            groups: Vec::new(),
+            policies: Vec::new(),
+            synced_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_plan_provisions_refreshes_and_deactivates() {
+        let mut config = LdapConfig::default();
+        config
+            .group_policies
+            .insert("storage".to_string(), vec!["readwrite".to_string()]);
+
+        let users = vec![user("alice", &["storage"]), user("bob", &[]), user("carol", &[])];
+        let links = vec![link("bob"), link("dave")];
+
+        let actions = plan(&config, true, users, links);
+        assert_eq!(actions.len(), 3);
+        assert!(matches!(&actions[0], SyncAction::Provision(u) if u.username == "alice"));
+        // Known but no longer in a mapped group
+        assert!(matches!(
+            &actions[1],
+            SyncAction::Refresh { user, eligible: false, .. } if user.username == "bob"
+        ));
+        // carol is skipped: unknown and not eligible
+        assert!(matches!(&actions[2], SyncAction::Deactivate(l) if l.username == "dave"));
+
+        // Without the group requirement everyone is provisioned
+        let actions = plan(&config, false, vec![user("carol", &[])], Vec::new());
+        assert!(matches!(&actions[0], SyncAction::Provision(_)));
+    }
+}
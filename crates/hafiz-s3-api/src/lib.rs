@@ -1,15 +1,41 @@
 //! S3 API Server for Hafiz
 
 pub mod server;
+pub mod embedded;
 pub mod routes;
 pub mod middleware;
 pub mod xml;
+pub mod list_token;
 pub mod admin;
 pub mod metrics;
 pub mod tls;
 pub mod events;
+pub mod scrub;
+pub mod multipart_expiry;
+pub mod bucket_usage;
+pub mod restore;
+pub mod tiering;
+pub mod batch;
+pub mod prune;
+pub mod warmup;
+pub mod snapshots;
+pub mod mirror;
+pub mod master_key;
+pub mod key_rewrap;
+pub mod sse;
+pub mod ldap_sync;
+pub mod telemetry;
+pub mod transform;
+pub mod webdav;
+pub mod sftp;
+pub mod search;
+pub mod loopback;
+pub mod consensus;
+#[cfg(feature = "cluster")]
+pub mod cluster_rpc;
 
 pub use server::S3Server;
+pub use embedded::{HafizServer, HafizServerBuilder};
 pub use metrics::MetricsRecorder;
 pub use tls::TlsAcceptor;
 pub use events::{EventDispatcher, EventDispatcherConfig, S3Event};
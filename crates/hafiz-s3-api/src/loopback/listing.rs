@@ -0,0 +1,128 @@
+//! S3 listings read back by the gateways
+
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+/// One page of a ListObjectsV2 response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListPage {
+    #[serde(default)]
+    pub contents: Vec<ListedObject>,
+    #[serde(default)]
+    pub common_prefixes: Vec<ListedPrefix>,
+    #[serde(default)]
+    pub is_truncated: bool,
+    pub next_continuation_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListedObject {
+    pub key: String,
+    #[serde(default)]
+    pub size: u64,
+    pub last_modified: Option<String>,
+    #[serde(rename = "ETag")]
+    pub etag: Option<String>,
+}
+
+impl ListedObject {
+    pub fn modified(&self) -> Option<DateTime<Utc>> {
+        parse_s3_datetime(self.last_modified.as_deref()?)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListedPrefix {
+    pub prefix: String,
+}
+
+/// A ListBuckets response
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct BucketList {
+    #[serde(default)]
+    pub buckets: ListedBuckets,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListedBuckets {
+    #[serde(default)]
+    pub bucket: Vec<ListedBucket>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ListedBucket {
+    pub name: String,
+    pub creation_date: Option<String>,
+}
+
+impl ListedBucket {
+    pub fn created(&self) -> Option<DateTime<Utc>> {
+        parse_s3_datetime(self.creation_date.as_deref()?)
+    }
+}
+
+fn parse_s3_datetime(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|d| d.with_timezone(&Utc))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse_list_page() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>photos</Name>
+  <Prefix>2024/</Prefix>
+  <IsTruncated>true</IsTruncated>
+  <NextContinuationToken>next</NextContinuationToken>
+  <Contents>
+    <Key>2024/a &amp; b.jpg</Key>
+    <LastModified>2024-03-01T12:00:00.000Z</LastModified>
+    <ETag>"abc"</ETag>
+    <Size>42</Size>
+  </Contents>
+  <CommonPrefixes><Prefix>2024/march/</Prefix></CommonPrefixes>
+</ListBucketResult>"#;
+        let page: ListPage = quick_xml::de::from_str(body).unwrap();
+        assert!(page.is_truncated);
+        assert_eq!(page.next_continuation_token.as_deref(), Some("next"));
+        assert_eq!(page.contents[0].key, "2024/a & b.jpg");
+        assert_eq!(page.contents[0].size, 42);
+        assert_eq!(
+            page.contents[0].modified(),
+            Some(Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap())
+        );
+        assert_eq!(page.common_prefixes[0].prefix, "2024/march/");
+    }
+
+    #[test]
+    fn test_parse_bucket_list() {
+        let body = r#"<ListAllMyBucketsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Owner><ID>root</ID><DisplayName>root</DisplayName></Owner>
+  <Buckets>
+    <Bucket><Name>a</Name><CreationDate>2024-03-01T12:00:00.000Z</CreationDate></Bucket>
+    <Bucket><Name>b</Name><CreationDate>2024-03-02T12:00:00.000Z</CreationDate></Bucket>
+  </Buckets>
+</ListAllMyBucketsResult>"#;
+        let list: BucketList = quick_xml::de::from_str(body).unwrap();
+        let names: Vec<_> = list
+            .buckets
+            .bucket
+            .iter()
+            .map(|b| b.name.as_str())
+            .collect();
+        assert_eq!(names, ["a", "b"]);
+        assert!(list.buckets.bucket[0].created().is_some());
+    }
+}
@@ -0,0 +1,346 @@
+//! S3 requests made in-process by the file gateways
+//!
+//! The WebDAV and SFTP gateways carry out each file operation as S3
+//! requests through the S3 router, made by the logged-in user the same way
+//! requests of a mapped client certificate are (see [`CertificateUser`]),
+//! so bucket policies, ACLs, quotas and events apply as they do for S3
+//! clients.
+
+mod listing;
+
+use std::net::SocketAddr;
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    extract::ConnectInfo,
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use hafiz_core::directory::{CopyProgress, DirEntry, Listing, TreeStore};
+use tower::ServiceExt;
+use tracing::{info, warn};
+
+use crate::middleware::CertificateUser;
+pub use listing::{BucketList, ListPage, ListedBucket, ListedObject, ListedPrefix};
+
+/// Objects between progress reports of a tree copy
+const PROGRESS_EVERY: usize = 100;
+
+/// The user gateway requests are made by
+#[derive(Debug, Clone)]
+pub struct Caller {
+    pub access_key: String,
+    pub connect_info: Option<ConnectInfo<SocketAddr>>,
+}
+
+/// The S3 router, called in-process
+#[derive(Clone)]
+pub struct Loopback {
+    router: Router,
+}
+
+impl Loopback {
+    pub fn new(router: Router) -> Self {
+        Self { router }
+    }
+
+    /// Make an S3 request as `caller`
+    pub async fn call(
+        &self,
+        caller: &Caller,
+        method: Method,
+        uri: &str,
+        headers: HeaderMap,
+        body: Body,
+    ) -> Response {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(body)
+            .expect("S3 request URI is valid");
+        *request.headers_mut() = headers;
+        request
+            .extensions_mut()
+            .insert(CertificateUser(caller.access_key.clone()));
+        if let Some(connect_info) = caller.connect_info {
+            request.extensions_mut().insert(connect_info);
+        }
+        match self.router.clone().oneshot(request).await {
+            Ok(response) => response,
+            Err(infallible) => match infallible {},
+        }
+    }
+
+    pub async fn head(&self, caller: &Caller, bucket: &str, key: &str) -> Response {
+        self.call(
+            caller,
+            Method::HEAD,
+            &s3_path(bucket, key),
+            HeaderMap::new(),
+            Body::empty(),
+        )
+        .await
+    }
+
+    pub async fn delete(&self, caller: &Caller, bucket: &str, key: &str) -> Response {
+        self.call(
+            caller,
+            Method::DELETE,
+            &s3_path(bucket, key),
+            HeaderMap::new(),
+            Body::empty(),
+        )
+        .await
+    }
+
+    /// Store an empty object, such as a directory marker
+    pub async fn put_empty(&self, caller: &Caller, bucket: &str, key: &str) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("0"));
+        self.call(
+            caller,
+            Method::PUT,
+            &s3_path(bucket, key),
+            headers,
+            Body::empty(),
+        )
+        .await
+    }
+
+    /// All pages of a listing of `prefix`
+    pub async fn list(
+        &self,
+        caller: &Caller,
+        bucket: &str,
+        prefix: &str,
+        delimited: bool,
+    ) -> Result<Vec<ListPage>, Response> {
+        let mut pages = Vec::new();
+        let mut token = None;
+        loop {
+            let uri = format!(
+                "/{}?{}",
+                urlencoding::encode(bucket),
+                list_query(prefix, delimited, token.as_deref())
+            );
+            let response = self
+                .call(caller, Method::GET, &uri, HeaderMap::new(), Body::empty())
+                .await;
+            let page: ListPage = xml_body(response).await?;
+            token = page
+                .next_continuation_token
+                .clone()
+                .filter(|_| page.is_truncated);
+            pages.push(page);
+            if token.is_none() {
+                return Ok(pages);
+            }
+        }
+    }
+
+    /// Entries of the directory at `prefix`
+    pub async fn listing(
+        &self,
+        caller: &Caller,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Listing, Response> {
+        let mut listing = Listing::default();
+        for page in self.list(caller, bucket, prefix, true).await? {
+            for object in page.contents {
+                let entry = DirEntry {
+                    etag: object.etag.clone(),
+                    ..DirEntry::file(object.size, object.modified())
+                };
+                listing.add_object(prefix, &object.key, entry);
+            }
+            for common in page.common_prefixes {
+                listing.add_prefix(prefix, &common.prefix);
+            }
+        }
+        Ok(listing)
+    }
+
+    /// Server-side copy of an object
+    pub async fn copy(
+        &self,
+        caller: &Caller,
+        from_bucket: &str,
+        from_key: &str,
+        to_bucket: &str,
+        to_key: &str,
+    ) -> Response {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-amz-copy-source",
+            HeaderValue::from_str(&s3_path(from_bucket, from_key))
+                .expect("encoded path is a valid header"),
+        );
+        self.call(
+            caller,
+            Method::PUT,
+            &s3_path(to_bucket, to_key),
+            headers,
+            Body::empty(),
+        )
+        .await
+    }
+
+    /// Whether anything, a marker object included, is stored under `prefix`
+    pub async fn any_under(
+        &self,
+        caller: &Caller,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<bool, Response> {
+        let uri = format!(
+            "/{}?{}&max-keys=1",
+            urlencoding::encode(bucket),
+            list_query(prefix, true, None)
+        );
+        let response = self
+            .call(caller, Method::GET, &uri, HeaderMap::new(), Body::empty())
+            .await;
+        let page: ListPage = xml_body(response).await?;
+        Ok(!page.contents.is_empty() || !page.common_prefixes.is_empty())
+    }
+
+    /// Every key under `prefix`, including its marker object
+    pub async fn keys_under(
+        &self,
+        caller: &Caller,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Vec<String>, Response> {
+        let pages = self.list(caller, bucket, prefix, false).await?;
+        Ok(pages
+            .into_iter()
+            .flat_map(|page| page.contents.into_iter().map(|object| object.key))
+            .collect())
+    }
+}
+
+/// Objects of a [`TreeCopy`](hafiz_core::directory::TreeCopy) copied by
+/// `caller` from one bucket to another, or within one
+pub struct LoopbackTree<'a> {
+    pub s3: &'a Loopback,
+    pub caller: &'a Caller,
+    pub from_bucket: &'a str,
+    pub to_bucket: &'a str,
+}
+
+#[async_trait]
+impl TreeStore for LoopbackTree<'_> {
+    /// The failed S3 response
+    type Error = Response;
+
+    async fn copy_object(&self, from: &str, to: &str) -> Result<(), Response> {
+        let response = self
+            .s3
+            .copy(self.caller, self.from_bucket, from, self.to_bucket, to)
+            .await;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(response)
+        }
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<(), Response> {
+        let response = self.s3.delete(self.caller, self.from_bucket, key).await;
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(response)
+        }
+    }
+}
+
+/// Progress callback of a [`TreeCopy`](hafiz_core::directory::TreeCopy)
+/// logging every [`PROGRESS_EVERY`] objects, so long directory renames show
+/// in the log
+pub fn log_progress<'a>(
+    gateway: &'a str,
+    from: &'a str,
+    to: &'a str,
+) -> impl FnMut(CopyProgress) + Send + 'a {
+    move |progress| {
+        if progress.done % PROGRESS_EVERY == 0 {
+            info!(
+                "{} copy of {} to {}: {} of {} objects",
+                gateway, from, to, progress.done, progress.total
+            );
+        }
+    }
+}
+
+/// Body of a successful S3 response, or the response itself
+pub async fn xml_body<T: serde::de::DeserializeOwned>(response: Response) -> Result<T, Response> {
+    if !response.status().is_success() {
+        return Err(response);
+    }
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .map_err(|_| StatusCode::BAD_GATEWAY.into_response())?;
+    let body = String::from_utf8_lossy(&bytes);
+    quick_xml::de::from_str(&body).map_err(|e| {
+        warn!("Unreadable S3 response to gateway request: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_response()
+    })
+}
+
+/// Percent-encode each segment of a key, keeping the slashes
+pub fn encode_key(key: &str) -> String {
+    key.split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Path of an object in the S3 API
+pub fn s3_path(bucket: &str, key: &str) -> String {
+    format!("/{}/{}", urlencoding::encode(bucket), encode_key(key))
+}
+
+/// Percent-encoded ListObjectsV2 query
+fn list_query(prefix: &str, delimited: bool, token: Option<&str>) -> String {
+    let mut query = url::form_urlencoded::Serializer::new(String::new());
+    query
+        .append_pair("list-type", "2")
+        .append_pair("prefix", prefix);
+    if delimited {
+        query.append_pair("delimiter", "/");
+    }
+    if let Some(token) = token {
+        query.append_pair("continuation-token", token);
+    }
+    query.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_s3_path() {
+        assert_eq!(
+            s3_path("photos", "my trip/a&b.jpg"),
+            "/photos/my%20trip/a%26b.jpg"
+        );
+        assert_eq!(s3_path("photos", "2024/"), "/photos/2024/");
+    }
+
+    #[test]
+    fn test_list_query() {
+        assert_eq!(
+            list_query("a b/", true, None),
+            "list-type=2&prefix=a+b%2F&delimiter=%2F"
+        );
+        assert_eq!(
+            list_query("", false, Some("t")),
+            "list-type=2&prefix=&continuation-token=t"
+        );
+    }
+}
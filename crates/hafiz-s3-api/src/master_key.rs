@@ -0,0 +1,175 @@
+//! Master key for encrypting stored secrets
+//!
+//! The key is configured directly as hex, fetched or unwrapped through
+//! `encryption.master_key_provider`, or derived from a passphrase with
+//! `encryption.kdf`. The salt and settings of a derived key are kept in
+//! the metadata database together with a check value, so a wrong
+//! passphrase is refused at startup instead of failing on the first secret
+//! it cannot open.
+//!
+//! Secrets move to a new key on startup in two cases:
+//!
+//! - a passphrase is configured for the first time while `master_key` (or
+//!   its file, variable or provider) still gives the key they are sealed
+//!   under
+//! - the KDF settings changed, e.g. from the legacy `sha256` to `argon2id`
+//! - `previous_master_key` gives the key they were sealed under before the
+//!   master key was replaced
+//!
+//! Data encryption keys of objects move to a new key in the background
+//! instead, see [`crate::key_rewrap`].
+
+use hafiz_core::config::{EncryptionConfig, KdfAlgorithm, MasterKeyProviderConfig};
+use hafiz_core::{Error, Result};
+use hafiz_crypto::providers::{AwsKmsProvider, VaultTransitProvider};
+use hafiz_crypto::{key_check, EnvKeyProvider, FileKeyProvider, KdfParams, MasterKeyProvider, SecretCipher};
+use hafiz_metadata::MetadataStore;
+use tracing::{info, warn};
+
+fn cipher(key: &[u8]) -> Result<SecretCipher> {
+    SecretCipher::new(key).map_err(|e| Error::InternalError(e.to_string()))
+}
+
+/// Provider for `encryption.master_key_provider`
+fn key_provider(config: &MasterKeyProviderConfig) -> Result<Box<dyn MasterKeyProvider>> {
+    Ok(match config {
+        MasterKeyProviderConfig::File { path } => Box::new(FileKeyProvider::new(path)),
+        MasterKeyProviderConfig::Env { var } => Box::new(EnvKeyProvider::new(var)),
+        MasterKeyProviderConfig::Vault(vault) => {
+            let token = match &vault.token_file {
+                Some(path) => std::fs::read_to_string(path)
+                    .map_err(|e| Error::InternalError(format!("Failed to read Vault token file: {}", e)))?
+                    .trim()
+                    .to_string(),
+                None => std::env::var("VAULT_TOKEN").map_err(|_| {
+                    Error::InvalidArgument("Vault key provider needs token_file or VAULT_TOKEN".into())
+                })?,
+            };
+            Box::new(VaultTransitProvider {
+                address: vault.address.clone(),
+                token,
+                mount: vault.mount.clone(),
+                key_name: vault.key_name.clone(),
+                namespace: vault.namespace.clone(),
+                wrapped_key_file: vault.wrapped_key_file.clone(),
+            })
+        }
+        MasterKeyProviderConfig::AwsKms(kms) => Box::new(AwsKmsProvider {
+            region: kms.region.clone(),
+            endpoint: kms.endpoint.clone(),
+            key_id: kms.key_id.clone(),
+            access_key_id: kms.access_key_id.clone(),
+            secret_access_key: kms.secret_access_key.clone(),
+            wrapped_key_file: kms.wrapped_key_file.clone(),
+        }),
+    })
+}
+
+/// Master key configured directly or through a provider
+async fn configured_key(config: &EncryptionConfig) -> Result<Option<Vec<u8>>> {
+    let Some(provider) = config.master_key_provider.as_ref().filter(|_| config.enabled) else {
+        return config.get_master_key();
+    };
+    let provider = key_provider(provider)?;
+    let key = provider
+        .master_key()
+        .await
+        .map_err(|e| Error::InternalError(e.to_string()))?;
+    info!("Master key loaded from {}", provider.describe());
+    Ok(Some(key.to_vec()))
+}
+
+/// Fresh KDF settings with a new salt, as configured
+fn configured_params(config: &EncryptionConfig) -> KdfParams {
+    let kdf = &config.kdf;
+    match kdf.algorithm {
+        KdfAlgorithm::Argon2id => KdfParams::argon2id(kdf.memory_kib, kdf.iterations(), kdf.parallelism),
+        KdfAlgorithm::Pbkdf2Sha256 => KdfParams::pbkdf2_sha256(kdf.iterations()),
+        KdfAlgorithm::Sha256 => KdfParams::legacy_sha256(),
+    }
+}
+
+fn derive(params: &KdfParams, passphrase: &str) -> Result<Vec<u8>> {
+    params
+        .derive(passphrase)
+        .map(|key| key.to_vec())
+        .map_err(|e| Error::InvalidArgument(e.to_string()))
+}
+
+/// Encrypt the store's secrets under the configured master key, moving
+/// them over from a previous key where needed
+pub async fn apply_secret_cipher(config: &EncryptionConfig, metadata: MetadataStore) -> Result<MetadataStore> {
+    config.validate()?;
+    let configured_key = configured_key(config).await?;
+    let previous = config.get_previous_master_key()?;
+    let Some(passphrase) = config.get_master_passphrase()? else {
+        return match configured_key {
+            Some(key) => seal(metadata, &key, previous.as_deref(), None).await,
+            None => Ok(metadata),
+        };
+    };
+
+    let wanted = configured_params(config);
+    match metadata.get_master_key_kdf().await? {
+        None => {
+            let key = derive(&wanted, &passphrase)?;
+            info!("Deriving the master key from a passphrase ({})", wanted.algorithm);
+            seal(metadata, &key, configured_key.as_deref(), Some(wanted)).await
+        }
+        Some((stored, check)) => {
+            if configured_key.is_some() {
+                warn!("master_key is ignored: the master key is derived from the passphrase");
+            }
+            let key = derive(&stored, &passphrase)?;
+            if key_check(&key) != check {
+                return Err(Error::InvalidArgument(
+                    "Master passphrase does not give the key stored secrets are encrypted under".into(),
+                ));
+            }
+            if stored.same_settings(&wanted) {
+                return seal(metadata, &key, previous.as_deref(), None).await;
+            }
+
+            info!(
+                "Key derivation settings changed ({} to {}); re-encrypting stored secrets",
+                stored.algorithm, wanted.algorithm
+            );
+            let new_key = derive(&wanted, &passphrase)?;
+            seal(metadata, &new_key, Some(&key), Some(wanted)).await
+        }
+    }
+}
+
+/// Master key as configured, derived from the passphrase with the stored
+/// settings where one is configured
+pub async fn current_master_key(config: &EncryptionConfig, metadata: &MetadataStore) -> Result<Option<Vec<u8>>> {
+    let Some(passphrase) = config.get_master_passphrase()? else {
+        return configured_key(config).await;
+    };
+    let (params, _) = metadata.get_master_key_kdf().await?.ok_or_else(|| {
+        Error::InvalidArgument(
+            "The master key derivation settings are not stored; enable encrypt_secret_keys".into(),
+        )
+    })?;
+    derive(&params, &passphrase).map(Some)
+}
+
+/// Install `key`, re-encrypt what is sealed under `previous`, then record
+/// the settings `key` was derived with
+async fn seal(
+    metadata: MetadataStore,
+    key: &[u8],
+    previous: Option<&[u8]>,
+    derived_with: Option<KdfParams>,
+) -> Result<MetadataStore> {
+    let metadata = metadata.with_secret_cipher(cipher(key)?);
+    if let Some(previous) = previous {
+        metadata.reseal_secrets(&cipher(previous)?).await?;
+    }
+    // Only once everything opens with the new key
+    if let Some(params) = derived_with {
+        metadata.put_master_key_kdf(&params, &key_check(key)).await?;
+    }
+    metadata.seal_plaintext_secrets().await?;
+    Ok(metadata)
+}
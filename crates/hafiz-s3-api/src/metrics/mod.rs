@@ -2,6 +2,9 @@
 //!
 //! Exposes metrics at `/metrics` endpoint in Prometheus format.
 
+#[cfg(feature = "otel")]
+mod otel;
+
 use axum::{
     body::Body,
     extract::State,
@@ -9,11 +12,16 @@ use axum::{
     middleware::Next,
     response::{IntoResponse, Response},
 };
-use metrics::{counter, gauge, histogram};
+use hafiz_core::config::{MetricsConfig, TelemetryConfig};
+use metrics::{counter, gauge, histogram, Label};
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
-use std::sync::Arc;
-use std::time::Instant;
-use tracing::debug;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+use crate::middleware::request_principal;
 
 /// Metric names
 pub mod names {
@@ -28,6 +36,7 @@ pub mod names {
     pub const S3_OPERATIONS_TOTAL: &str = "hafiz_s3_operations_total";
     pub const S3_OPERATION_DURATION_SECONDS: &str = "hafiz_s3_operation_duration_seconds";
     pub const S3_OPERATION_ERRORS_TOTAL: &str = "hafiz_s3_operation_errors_total";
+    pub const SLOW_REQUESTS_TOTAL: &str = "hafiz_slow_requests_total";
 
     // Storage metrics
     pub const STORAGE_BYTES_READ_TOTAL: &str = "hafiz_storage_bytes_read_total";
@@ -64,6 +73,9 @@ pub enum S3Operation {
     GetBucketLifecycle,
     PutBucketLifecycle,
     DeleteBucketLifecycle,
+    GetBucketTagging,
+    PutBucketTagging,
+    DeleteBucketTagging,
     ListObjects,
     ListObjectVersions,
     ListMultipartUploads,
@@ -87,6 +99,43 @@ pub enum S3Operation {
 }
 
 impl S3Operation {
+    /// Every operation, in declaration order
+    pub const ALL: [S3Operation; 28] = [
+        Self::ListBuckets,
+        Self::CreateBucket,
+        Self::DeleteBucket,
+        Self::HeadBucket,
+        Self::GetBucketVersioning,
+        Self::PutBucketVersioning,
+        Self::GetBucketLifecycle,
+        Self::PutBucketLifecycle,
+        Self::DeleteBucketLifecycle,
+        Self::GetBucketTagging,
+        Self::PutBucketTagging,
+        Self::DeleteBucketTagging,
+        Self::ListObjects,
+        Self::ListObjectVersions,
+        Self::ListMultipartUploads,
+        Self::GetObject,
+        Self::PutObject,
+        Self::DeleteObject,
+        Self::HeadObject,
+        Self::CopyObject,
+        Self::GetObjectTagging,
+        Self::PutObjectTagging,
+        Self::DeleteObjectTagging,
+        Self::CreateMultipartUpload,
+        Self::UploadPart,
+        Self::CompleteMultipartUpload,
+        Self::AbortMultipartUpload,
+        Self::ListParts,
+    ];
+
+    /// The operation named `name`, e.g. `PutObject`
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|op| op.as_str() == name)
+    }
+
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::ListBuckets => "ListBuckets",
@@ -98,6 +147,9 @@ impl S3Operation {
             Self::GetBucketLifecycle => "GetBucketLifecycle",
             Self::PutBucketLifecycle => "PutBucketLifecycle",
             Self::DeleteBucketLifecycle => "DeleteBucketLifecycle",
+            Self::GetBucketTagging => "GetBucketTagging",
+            Self::PutBucketTagging => "PutBucketTagging",
+            Self::DeleteBucketTagging => "DeleteBucketTagging",
             Self::ListObjects => "ListObjects",
             Self::ListObjectVersions => "ListObjectVersions",
             Self::ListMultipartUploads => "ListMultipartUploads",
@@ -135,6 +187,9 @@ impl S3Operation {
             ("GET", false) if query.contains("lifecycle") => Some(Self::GetBucketLifecycle),
             ("PUT", false) if query.contains("lifecycle") => Some(Self::PutBucketLifecycle),
             ("DELETE", false) if query.contains("lifecycle") => Some(Self::DeleteBucketLifecycle),
+            ("GET", false) if query.contains("tagging") => Some(Self::GetBucketTagging),
+            ("PUT", false) if query.contains("tagging") => Some(Self::PutBucketTagging),
+            ("DELETE", false) if query.contains("tagging") => Some(Self::DeleteBucketTagging),
             ("GET", false) if query.contains("versions") => Some(Self::ListObjectVersions),
             ("GET", false) if query.contains("uploads") => Some(Self::ListMultipartUploads),
             ("GET", false) => Some(Self::ListObjects),
@@ -158,11 +213,163 @@ impl S3Operation {
     }
 }
 
+/// Label value for buckets and prefixes without series of their own
+pub const OTHER_LABEL: &str = "_other";
+
+/// Which buckets and prefixes S3 operation metrics are labelled with
+///
+/// Label values are handed out first come, first served up to the
+/// configured limit, so a wildcard pattern cannot grow the series count
+/// without bound.
+struct LabelPolicy {
+    buckets: Vec<String>,
+    prefixes: Vec<String>,
+    max_values: usize,
+    seen_buckets: Mutex<HashSet<String>>,
+    seen_prefixes: Mutex<HashSet<(String, String)>>,
+}
+
+impl LabelPolicy {
+    fn new(config: &MetricsConfig) -> Self {
+        Self {
+            buckets: config.bucket_labels.clone(),
+            prefixes: config.prefix_labels.clone(),
+            max_values: config.max_label_values,
+            seen_buckets: Mutex::new(HashSet::new()),
+            seen_prefixes: Mutex::new(HashSet::new()),
+        }
+    }
+
+    fn labels_buckets(&self) -> bool {
+        !self.buckets.is_empty() || !self.prefixes.is_empty()
+    }
+
+    fn labels_prefixes(&self) -> bool {
+        !self.prefixes.is_empty()
+    }
+
+    fn matches(patterns: &[String], bucket: &str) -> bool {
+        patterns.iter().any(|p| match p.strip_suffix('*') {
+            Some(start) => bucket.starts_with(start),
+            None => p == bucket,
+        })
+    }
+
+    /// `bucket` and, if prefixes are labelled, `prefix` labels for a request
+    fn labels(&self, bucket: Option<&str>, key: Option<&str>) -> Vec<Label> {
+        let mut labels = Vec::new();
+        if !self.labels_buckets() {
+            return labels;
+        }
+
+        let bucket = bucket.unwrap_or("");
+        let tracked = !bucket.is_empty()
+            && (Self::matches(&self.buckets, bucket) || Self::matches(&self.prefixes, bucket))
+            && self.admit_bucket(bucket);
+        labels.push(Label::new(
+            "bucket",
+            if tracked { bucket.to_string() } else { OTHER_LABEL.to_string() },
+        ));
+
+        if self.labels_prefixes() {
+            let prefix = match key {
+                Some(key) if tracked && Self::matches(&self.prefixes, bucket) => {
+                    let prefix = top_level_prefix(key);
+                    if self.admit_prefix(bucket, &prefix) {
+                        prefix
+                    } else {
+                        OTHER_LABEL.to_string()
+                    }
+                }
+                _ => String::new(),
+            };
+            labels.push(Label::new("prefix", prefix));
+        }
+        labels
+    }
+
+    fn admit_bucket(&self, bucket: &str) -> bool {
+        let mut seen = self.seen_buckets.lock();
+        if seen.contains(bucket) {
+            return true;
+        }
+        if seen.len() >= self.max_values {
+            return false;
+        }
+        seen.insert(bucket.to_string());
+        true
+    }
+
+    fn admit_prefix(&self, bucket: &str, prefix: &str) -> bool {
+        let mut seen = self.seen_prefixes.lock();
+        let entry = (bucket.to_string(), prefix.to_string());
+        if seen.contains(&entry) {
+            return true;
+        }
+        if seen.len() >= self.max_values {
+            return false;
+        }
+        seen.insert(entry);
+        true
+    }
+}
+
+/// First path segment of a key including its `/`, or empty for keys at the
+/// top of the bucket
+fn top_level_prefix(key: &str) -> String {
+    match key.split_once('/') {
+        Some((first, _)) => format!("{}/", first),
+        None => String::new(),
+    }
+}
+
+/// Bucket and key of a path-style request path
+fn split_path(path: &str) -> (Option<&str>, Option<&str>) {
+    let path = path.trim_start_matches('/');
+    match path.split_once('/') {
+        Some((bucket, key)) if !key.is_empty() => (Some(bucket), Some(key)),
+        Some((bucket, _)) => (Some(bucket), None),
+        None if path.is_empty() => (None, None),
+        None => (Some(path), None),
+    }
+}
+
+/// When an S3 operation counts as slow
+#[derive(Debug, Clone, Copy, Default)]
+struct SlowThresholds {
+    duration: Option<Duration>,
+    bytes: Option<u64>,
+}
+
+impl SlowThresholds {
+    fn new(config: &MetricsConfig) -> Self {
+        Self {
+            duration: (config.slow_request_ms > 0)
+                .then(|| Duration::from_millis(config.slow_request_ms)),
+            bytes: (config.large_object_bytes > 0).then_some(config.large_object_bytes),
+        }
+    }
+
+    /// Which thresholds an operation crossed, as the `reason` label
+    fn exceeded(&self, duration: Duration, bytes: u64) -> Option<&'static str> {
+        let slow = self.duration.is_some_and(|limit| duration > limit);
+        let large = self.bytes.is_some_and(|limit| bytes > limit);
+        match (slow, large) {
+            (true, true) => Some("duration_and_size"),
+            (true, false) => Some("duration"),
+            (false, true) => Some("size"),
+            (false, false) => None,
+        }
+    }
+}
+
 /// Metrics recorder
 #[derive(Clone)]
 pub struct MetricsRecorder {
     handle: PrometheusHandle,
     start_time: Instant,
+    labels: Arc<LabelPolicy>,
+    slow: SlowThresholds,
 }
 
 impl MetricsRecorder {
@@ -173,15 +380,68 @@ impl MetricsRecorder {
             .install_recorder()
             .expect("Failed to install Prometheus recorder");
 
+        Self::started(handle)
+    }
+
+    /// The process-wide recorder, installed on first use
+    ///
+    /// Only one recorder can be installed per process, so servers embedded
+    /// in another application share it. When the application installed a
+    /// recorder of its own, metrics go there and this one stays empty.
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<MetricsRecorder> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                let prometheus = PrometheusBuilder::new().build_recorder();
+                let handle = prometheus.handle();
+                let _ = metrics::set_global_recorder(prometheus);
+                Self::started(handle)
+            })
+            .clone()
+    }
+
+    /// Initialize the metrics system, also exporting over OTLP if configured
+    ///
+    /// Export goes through the meter provider that
+    /// [`telemetry::init`](crate::telemetry::init) sets up.
+    pub fn with_telemetry(config: &TelemetryConfig) -> Self {
+        #[cfg(feature = "otel")]
+        if config.enabled && config.export_metrics {
+            let prometheus = PrometheusBuilder::new().build_recorder();
+            let handle = prometheus.handle();
+            let fanout = metrics_util::layers::FanoutBuilder::default()
+                .add_recorder(prometheus)
+                .add_recorder(otel::OtelRecorder::new())
+                .build();
+            if metrics::set_global_recorder(fanout).is_err() {
+                panic!("Failed to install metrics recorder");
+            }
+            return Self::started(handle);
+        }
+
+        let _ = config;
+        Self::new()
+    }
+
+    fn started(handle: PrometheusHandle) -> Self {
         // Set initial info metric
         gauge!(names::INFO, "version" => env!("CARGO_PKG_VERSION")).set(1.0);
 
         Self {
             handle,
             start_time: Instant::now(),
+            labels: Arc::new(LabelPolicy::new(&MetricsConfig::default())),
+            slow: SlowThresholds::default(),
         }
     }
 
+    /// Apply the `[metrics]` bucket labels and slow request thresholds
+    pub fn with_config(mut self, config: &MetricsConfig) -> Self {
+        self.labels = Arc::new(LabelPolicy::new(config));
+        self.slow = SlowThresholds::new(config);
+        self
+    }
+
     /// Get metrics output in Prometheus format
     pub fn render(&self) -> String {
         // Update uptime
@@ -222,31 +482,41 @@ impl MetricsRecorder {
     }
 
     /// Record an S3 operation
-    pub fn record_s3_operation(&self, op: S3Operation, success: bool, duration_secs: f64) {
-        let op_name = op.as_str();
+    ///
+    /// `bucket` and `key` only add labels for buckets configured in
+    /// `[metrics]`.
+    pub fn record_s3_operation(
+        &self,
+        op: S3Operation,
+        bucket: Option<&str>,
+        key: Option<&str>,
+        success: bool,
+        duration_secs: f64,
+    ) {
+        let mut labels = vec![Label::new("operation", op.as_str())];
+        labels.extend(self.labels.labels(bucket, key));
 
-        counter!(
-            names::S3_OPERATIONS_TOTAL,
-            "operation" => op_name,
-            "status" => if success { "success" } else { "error" }
-        )
-        .increment(1);
+        let mut with_status = labels.clone();
+        with_status.push(Label::new("status", if success { "success" } else { "error" }));
+        counter!(names::S3_OPERATIONS_TOTAL, with_status).increment(1);
 
-        histogram!(
-            names::S3_OPERATION_DURATION_SECONDS,
-            "operation" => op_name
-        )
-        .record(duration_secs);
+        histogram!(names::S3_OPERATION_DURATION_SECONDS, labels.clone()).record(duration_secs);
 
         if !success {
-            counter!(
-                names::S3_OPERATION_ERRORS_TOTAL,
-                "operation" => op_name
-            )
-            .increment(1);
+            counter!(names::S3_OPERATION_ERRORS_TOTAL, labels).increment(1);
         }
     }
 
+    /// Count an S3 operation that crossed a slow request threshold
+    pub fn record_slow_request(&self, op: S3Operation, reason: &'static str) {
+        counter!(
+            names::SLOW_REQUESTS_TOTAL,
+            "operation" => op.as_str(),
+            "reason" => reason
+        )
+        .increment(1);
+    }
+
     /// Record bytes read from storage
     pub fn record_bytes_read(&self, bytes: u64) {
         counter!(names::STORAGE_BYTES_READ_TOTAL).increment(bytes);
@@ -317,10 +587,17 @@ pub async fn metrics_middleware(
 
     // Detect S3 operation
     let s3_op = S3Operation::from_request(&method, &path, query.as_deref());
+    let principal = match s3_op {
+        Some(_) if metrics.slow.duration.is_some() || metrics.slow.bytes.is_some() => {
+            request_principal(&request)
+        }
+        _ => None,
+    };
 
     let response = next.run(request).await;
 
-    let duration = start.elapsed().as_secs_f64();
+    let elapsed = start.elapsed();
+    let duration = elapsed.as_secs_f64();
     let status = response.status().as_u16();
 
     // Get response size from Content-Length header
@@ -337,7 +614,37 @@ pub async fn metrics_middleware(
     // Record S3 operation metrics
     if let Some(op) = s3_op {
         let success = status < 400;
-        metrics.record_s3_operation(op, success, duration);
+        let (bucket, key) = split_path(&path);
+        let key = key.map(|k| {
+            urlencoding::decode(k)
+                .map(|k| k.into_owned())
+                .unwrap_or_else(|_| k.to_string())
+        });
+        metrics.record_s3_operation(op, bucket, key.as_deref(), success, duration);
+
+        let bytes = request_size.max(response_size);
+        if let Some(reason) = metrics.slow.exceeded(elapsed, bytes) {
+            metrics.record_slow_request(op, reason);
+            let request_id = response
+                .headers()
+                .get("x-amz-request-id")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("-");
+            warn!(
+                request_id = %request_id,
+                operation = op.as_str(),
+                method = %method,
+                path = %path,
+                query = query.as_deref().unwrap_or(""),
+                principal = principal.as_deref().unwrap_or("-"),
+                status = status,
+                duration_ms = elapsed.as_millis() as u64,
+                request_bytes = request_size,
+                response_bytes = response_size,
+                reason = reason,
+                "Slow S3 request"
+            );
+        }
     }
 
     debug!(
@@ -392,4 +699,94 @@ mod tests {
             Some(S3Operation::GetBucketVersioning)
         );
     }
+
+    #[test]
+    fn test_operation_names() {
+        for op in S3Operation::ALL {
+            assert_eq!(S3Operation::from_name(op.as_str()), Some(op));
+        }
+        assert_eq!(S3Operation::from_name("putobject"), None);
+    }
+
+    fn policy(buckets: &[&str], prefixes: &[&str], max: usize) -> LabelPolicy {
+        LabelPolicy::new(&MetricsConfig {
+            bucket_labels: buckets.iter().map(|b| b.to_string()).collect(),
+            prefix_labels: prefixes.iter().map(|b| b.to_string()).collect(),
+            max_label_values: max,
+            ..Default::default()
+        })
+    }
+
+    fn values(labels: &[Label]) -> Vec<(&str, &str)> {
+        labels.iter().map(|l| (l.key(), l.value())).collect()
+    }
+
+    #[test]
+    fn test_slow_thresholds() {
+        let off = SlowThresholds::new(&MetricsConfig::default());
+        assert_eq!(off.exceeded(Duration::from_secs(3600), u64::MAX), None);
+
+        let slow = SlowThresholds::new(&MetricsConfig {
+            slow_request_ms: 500,
+            large_object_bytes: 1024,
+            ..Default::default()
+        });
+        assert_eq!(slow.exceeded(Duration::from_millis(500), 1024), None);
+        assert_eq!(slow.exceeded(Duration::from_millis(501), 10), Some("duration"));
+        assert_eq!(slow.exceeded(Duration::from_millis(10), 1025), Some("size"));
+        assert_eq!(
+            slow.exceeded(Duration::from_secs(1), 4096),
+            Some("duration_and_size")
+        );
+    }
+
+    #[test]
+    fn test_split_path() {
+        assert_eq!(split_path("/"), (None, None));
+        assert_eq!(split_path("/photos"), (Some("photos"), None));
+        assert_eq!(split_path("/photos/"), (Some("photos"), None));
+        assert_eq!(split_path("/photos/2024/cat.jpg"), (Some("photos"), Some("2024/cat.jpg")));
+    }
+
+    #[test]
+    fn test_no_bucket_labels_by_default() {
+        let labels = policy(&[], &[], 100).labels(Some("photos"), Some("a/b"));
+        assert!(labels.is_empty());
+    }
+
+    #[test]
+    fn test_bucket_allow_list() {
+        let policy = policy(&["photos", "logs-*"], &[], 100);
+        assert_eq!(values(&policy.labels(Some("photos"), Some("a/b"))), vec![("bucket", "photos")]);
+        assert_eq!(values(&policy.labels(Some("logs-eu"), None)), vec![("bucket", "logs-eu")]);
+        assert_eq!(values(&policy.labels(Some("backups"), None)), vec![("bucket", OTHER_LABEL)]);
+    }
+
+    #[test]
+    fn test_prefix_labels() {
+        let policy = policy(&["photos"], &["logs"], 100);
+        assert_eq!(
+            values(&policy.labels(Some("logs"), Some("app/2024/01.gz"))),
+            vec![("bucket", "logs"), ("prefix", "app/")]
+        );
+        assert_eq!(
+            values(&policy.labels(Some("logs"), Some("readme.txt"))),
+            vec![("bucket", "logs"), ("prefix", "")]
+        );
+        assert_eq!(
+            values(&policy.labels(Some("photos"), Some("2024/cat.jpg"))),
+            vec![("bucket", "photos"), ("prefix", "")]
+        );
+    }
+
+    #[test]
+    fn test_label_values_are_capped() {
+        let policy = policy(&["b*"], &["b*"], 2);
+        assert_eq!(values(&policy.labels(Some("b1"), Some("x/1")))[0], ("bucket", "b1"));
+        assert_eq!(values(&policy.labels(Some("b2"), Some("y/1")))[0], ("bucket", "b2"));
+        assert_eq!(values(&policy.labels(Some("b3"), None))[0], ("bucket", OTHER_LABEL));
+        // Values already handed out keep their series
+        assert_eq!(values(&policy.labels(Some("b1"), Some("x/2")))[1], ("prefix", "x/"));
+        assert_eq!(values(&policy.labels(Some("b1"), Some("z/1")))[1], ("prefix", OTHER_LABEL));
+    }
 }
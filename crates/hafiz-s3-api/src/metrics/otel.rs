@@ -0,0 +1,157 @@
+//! OpenTelemetry bridge for the `metrics` facade
+//!
+//! Forwards every counter, gauge and histogram recorded through the
+//! `metrics` macros to the global OpenTelemetry meter, so the Prometheus
+//! endpoint and OTLP export report the same series. OpenTelemetry has no
+//! settable gauge yet, so gauges are up/down counters fed the difference
+//! from their last value.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder,
+    SharedString, Unit,
+};
+use opentelemetry::metrics::Meter;
+use opentelemetry::KeyValue;
+use parking_lot::Mutex;
+
+fn attributes(key: &Key) -> Vec<KeyValue> {
+    key.labels()
+        .map(|l| KeyValue::new(l.key().to_string(), l.value().to_string()))
+        .collect()
+}
+
+struct OtelCounter {
+    counter: opentelemetry::metrics::Counter<u64>,
+    attributes: Vec<KeyValue>,
+    total: AtomicU64,
+}
+
+impl CounterFn for OtelCounter {
+    fn increment(&self, value: u64) {
+        self.total.fetch_add(value, Ordering::Relaxed);
+        self.counter.add(value, &self.attributes);
+    }
+
+    fn absolute(&self, value: u64) {
+        let previous = self.total.fetch_max(value, Ordering::Relaxed);
+        if value > previous {
+            self.counter.add(value - previous, &self.attributes);
+        }
+    }
+}
+
+struct OtelGauge {
+    counter: opentelemetry::metrics::UpDownCounter<f64>,
+    attributes: Vec<KeyValue>,
+    value: Mutex<f64>,
+}
+
+impl GaugeFn for OtelGauge {
+    fn increment(&self, value: f64) {
+        *self.value.lock() += value;
+        self.counter.add(value, &self.attributes);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.increment(-value);
+    }
+
+    fn set(&self, value: f64) {
+        let mut current = self.value.lock();
+        let delta = value - *current;
+        *current = value;
+        if delta != 0.0 {
+            self.counter.add(delta, &self.attributes);
+        }
+    }
+}
+
+struct OtelHistogram {
+    histogram: opentelemetry::metrics::Histogram<f64>,
+    attributes: Vec<KeyValue>,
+}
+
+impl HistogramFn for OtelHistogram {
+    fn record(&self, value: f64) {
+        self.histogram.record(value, &self.attributes);
+    }
+}
+
+/// `metrics` recorder writing to the global OpenTelemetry meter
+pub struct OtelRecorder {
+    meter: Meter,
+    counters: Mutex<HashMap<Key, Arc<OtelCounter>>>,
+    gauges: Mutex<HashMap<Key, Arc<OtelGauge>>>,
+    histograms: Mutex<HashMap<Key, Arc<OtelHistogram>>>,
+}
+
+impl OtelRecorder {
+    /// Bridge to the global meter provider, which must already be set up
+    pub fn new() -> Self {
+        Self {
+            meter: opentelemetry::global::meter("hafiz"),
+            counters: Mutex::new(HashMap::new()),
+            gauges: Mutex::new(HashMap::new()),
+            histograms: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Recorder for OtelRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        let counter = self
+            .counters
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(OtelCounter {
+                    counter: self.meter.u64_counter(key.name().to_string()).init(),
+                    attributes: attributes(key),
+                    total: AtomicU64::new(0),
+                })
+            })
+            .clone();
+        Counter::from_arc(counter)
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        let gauge = self
+            .gauges
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(OtelGauge {
+                    counter: self.meter.f64_up_down_counter(key.name().to_string()).init(),
+                    attributes: attributes(key),
+                    value: Mutex::new(0.0),
+                })
+            })
+            .clone();
+        Gauge::from_arc(gauge)
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        let histogram = self
+            .histograms
+            .lock()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                Arc::new(OtelHistogram {
+                    histogram: self.meter.f64_histogram(key.name().to_string()).init(),
+                    attributes: attributes(key),
+                })
+            })
+            .clone();
+        Histogram::from_arc(histogram)
+    }
+}
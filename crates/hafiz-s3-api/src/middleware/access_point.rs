@@ -0,0 +1,134 @@
+//! Access point addressing
+//!
+//! A request goes through access point `<name>` when it names the alias
+//! `<name>-s3alias` in place of a bucket, or, with
+//! `server.access_point_domain` set, when it is sent to host
+//! `<name>.<domain>`. [`access_point_routing`] runs before routing: it
+//! rewrites the path to the bucket behind the access point and attaches an
+//! [`AccessPointRequest`], which [`anonymous_access`](super::anonymous_access)
+//! then checks against the access point's networks and policy.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{header::HOST, Request, Uri},
+    middleware::Next,
+    response::Response,
+};
+use hafiz_core::{types::AccessPoint, Error};
+use tracing::debug;
+
+use super::request_id::error_response;
+use crate::server::AppState;
+
+/// The access point a request was addressed to
+#[derive(Debug, Clone)]
+pub struct AccessPointRequest(pub AccessPoint);
+
+/// Resolve access point addressing into a bucket path
+pub async fn access_point_routing(
+    State(state): State<AppState>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let host = request
+        .headers()
+        .get(HOST)
+        .and_then(|h| h.to_str().ok())
+        .map(str::to_string);
+    let Some((name, rest)) = addressed_access_point(
+        state.config.server.access_point_domain.as_deref(),
+        host.as_deref(),
+        request.uri().path(),
+    ) else {
+        return next.run(request).await;
+    };
+
+    let access_point = match state.metadata.get_access_point(&name).await {
+        Ok(Some(access_point)) => access_point,
+        Ok(None) => return error_response(Error::NoSuchAccessPoint(name)),
+        Err(e) => return error_response(e),
+    };
+
+    let path = format!("/{}{}", access_point.bucket, rest);
+    let uri = match request.uri().query() {
+        Some(query) => format!("{}?{}", path, query),
+        None => path,
+    };
+    match uri.parse::<Uri>() {
+        Ok(uri) => *request.uri_mut() = uri,
+        Err(e) => return error_response(Error::InvalidRequest(e.to_string())),
+    }
+    debug!(
+        "Request through access point {} to bucket {}",
+        access_point.name, access_point.bucket
+    );
+    request
+        .extensions_mut()
+        .insert(AccessPointRequest(access_point));
+    next.run(request).await
+}
+
+/// Access point name and the path below it, if the request addresses one
+fn addressed_access_point(
+    domain: Option<&str>,
+    host: Option<&str>,
+    path: &str,
+) -> Option<(String, String)> {
+    let host_name = domain.zip(host).and_then(|(domain, host)| {
+        let host = host.split(':').next().unwrap_or(host);
+        host.strip_suffix(domain)?
+            .strip_suffix('.')
+            .filter(|name| !name.is_empty() && !name.contains('.'))
+    });
+    let (name, rest) = match host_name {
+        Some(name) => (name, path),
+        None => {
+            let path = path.strip_prefix('/')?;
+            let (segment, rest) = match path.find('/') {
+                Some(idx) => path.split_at(idx),
+                None => (path, ""),
+            };
+            (AccessPoint::name_from_alias(segment)?, rest)
+        }
+    };
+    // `/bucket/` is not a route of its own
+    let rest = if rest == "/" { "" } else { rest };
+    Some((name.to_string(), rest.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addressed(domain: Option<&str>, host: &str, path: &str) -> Option<(String, String)> {
+        addressed_access_point(domain, Some(host), path)
+    }
+
+    #[test]
+    fn test_alias_addressing() {
+        let ap = |name: &str, rest: &str| Some((name.to_string(), rest.to_string()));
+        assert_eq!(addressed(None, "s3.local", "/logs-s3alias/a/b.txt"), ap("logs", "/a/b.txt"));
+        assert_eq!(addressed(None, "s3.local", "/logs-s3alias"), ap("logs", ""));
+        assert_eq!(addressed(None, "s3.local", "/logs-s3alias/"), ap("logs", ""));
+        assert_eq!(addressed(None, "s3.local", "/logs/a-s3alias"), None);
+        assert_eq!(addressed(None, "s3.local", "/"), None);
+    }
+
+    #[test]
+    fn test_host_addressing() {
+        let domain = Some("ap.example.com");
+        assert_eq!(
+            addressed(domain, "logs.ap.example.com:9000", "/a"),
+            Some(("logs".to_string(), "/a".to_string()))
+        );
+        assert_eq!(
+            addressed(domain, "logs.ap.example.com", "/"),
+            Some(("logs".to_string(), "".to_string()))
+        );
+        // Only one label below the domain, and only when one is configured
+        assert_eq!(addressed(domain, "a.logs.ap.example.com", "/x"), None);
+        assert_eq!(addressed(domain, "ap.example.com", "/bucket"), None);
+        assert_eq!(addressed(None, "logs.ap.example.com", "/bucket"), None);
+    }
+}
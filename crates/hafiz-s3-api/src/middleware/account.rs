@@ -0,0 +1,385 @@
+//! Account scoping and quotas
+//!
+//! Every user belongs to an account, and every bucket to the account of the
+//! user that created it. An authenticated request may only address buckets
+//! of the caller's own account; admins may address any bucket. Requests by
+//! users of a suspended account, and requests to buckets of one, are
+//! refused with `AccountProblem`. The source of a copy must be in the
+//! caller's account as well.
+//!
+//! Creating a bucket, uploading an object or part and copying an object are
+//! checked against the quota of the account that owns the bucket. Usage is
+//! read from the bucket usage counters, so uploads running at the same time
+//! may together overshoot a limit by a little. An overwrite counts as a new
+//! object.
+//!
+//! Bucket names are global: creating a bucket another account holds fails
+//! with `BucketAlreadyExists`, as on S3, rather than `AccessDenied`.
+//!
+//! Keys that belong to no user, like STS credentials, act in the default
+//! account.
+//!
+//! The caller is the verified identity of the request: the access key whose
+//! signature [`verify_signature`](super::verify_signature) checked, or the
+//! user of a mapped client certificate. An access key the request merely
+//! names counts for nothing, so with `auth.enabled` off, when signatures
+//! are not checked, accounts do not isolate anyone.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use hafiz_core::{
+    types::{Credentials, DEFAULT_ACCOUNT},
+    Error, Result,
+};
+use tracing::debug;
+
+use super::client_cert::{is_authenticated, request_principal};
+use super::request_id::{bucket_and_key, error_response};
+use crate::server::AppState;
+
+/// Object subresources whose PUT changes settings rather than data
+const OBJECT_SUBRESOURCES: [&str; 4] = ["acl", "legal-hold", "retention", "tagging"];
+
+/// What a request adds to the account owning its bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Addition {
+    Bucket,
+    /// New object data; a part adds bytes but no object
+    Object {
+        part: bool,
+        copy_source: Option<(String, String)>,
+    },
+}
+
+/// Keep requests to the buckets of the caller's account and within quota
+pub async fn account_scope(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let (bucket, key) = bucket_and_key(request.uri().path());
+
+    let caller = match is_authenticated(&request)
+        .then(|| request_principal(&request))
+        .flatten()
+    {
+        Some(access_key) => match credentials_of(&state, &access_key).await {
+            Ok(cred) => Some(Caller::from_credentials(cred)),
+            Err(e) => return error_response(e),
+        },
+        None => None,
+    };
+    if let Some(caller) = &caller {
+        if let Err(e) = check_active(&state, &caller.account_id).await {
+            return error_response(e);
+        }
+    }
+
+    let owner = match &bucket {
+        Some(bucket) => match state.metadata.get_bucket(bucket).await {
+            Ok(info) => info.map(|info| info.owner_id),
+            Err(e) => return error_response(e),
+        },
+        None => None,
+    };
+    if let Some(owner) = &owner {
+        match &caller {
+            Some(caller) if !caller.admin && caller.account_id != *owner => {
+                if creates_bucket(request.method(), key.is_some(), request.uri().query()) {
+                    return error_response(Error::BucketAlreadyExists);
+                }
+                debug!(
+                    "Bucket {} belongs to account {}, not {}",
+                    bucket.as_deref().unwrap_or_default(),
+                    owner,
+                    caller.account_id
+                );
+                return error_response(Error::AccessDenied);
+            }
+            Some(caller) if caller.account_id == *owner => {}
+            _ => {
+                if let Err(e) = check_active(&state, owner).await {
+                    return error_response(e);
+                }
+            }
+        }
+    }
+
+    // A copy reads its source with the caller's access too
+    if let (Some(caller), Some((source, _))) = (&caller, copy_source(request.headers())) {
+        if !caller.admin {
+            match state.metadata.get_bucket(&source).await {
+                Ok(Some(info)) if info.owner_id != caller.account_id => {
+                    return error_response(Error::AccessDenied);
+                }
+                Ok(_) => {}
+                Err(e) => return error_response(e),
+            }
+        }
+    }
+
+    // Requests count toward the account of their bucket, or for ListBuckets
+    // and CreateBucket, the caller's
+    let account = owner.clone().unwrap_or_else(|| {
+        caller
+            .as_ref()
+            .map(|caller| caller.account_id.clone())
+            .unwrap_or_else(|| DEFAULT_ACCOUNT.to_string())
+    });
+
+    let addition = addition(
+        request.method(),
+        owner.is_some(),
+        key.is_some(),
+        request.uri().query(),
+        request.headers(),
+    );
+    if let Some(addition) = addition {
+        if let Err(e) = check_quota(&state, &account, addition, request.headers()).await {
+            debug!("Request refused for account {}: {}", account, e);
+            return error_response(e);
+        }
+    }
+
+    metrics::counter!("hafiz_account_requests_total", "account" => account).increment(1);
+    next.run(request).await
+}
+
+/// Account of a request's principal; the default account for anonymous
+/// requests and keys that belong to no user
+pub async fn account_of(state: &AppState, access_key: Option<&str>) -> Result<String> {
+    let Some(access_key) = access_key else {
+        return Ok(DEFAULT_ACCOUNT.to_string());
+    };
+    Ok(credentials_of(state, access_key)
+        .await?
+        .map(|cred| cred.account_id)
+        .unwrap_or_else(|| DEFAULT_ACCOUNT.to_string()))
+}
+
+/// The user a key belongs to: its current key, a rotated key still in its
+/// grace period, or STS credentials
+async fn credentials_of(state: &AppState, access_key: &str) -> Result<Option<Credentials>> {
+    let metadata = &state.metadata;
+    if let Some(cred) = metadata.get_credentials(access_key).await? {
+        return Ok(Some(cred));
+    }
+    if let Some(retiring) = metadata.get_retiring_key(access_key).await? {
+        return metadata.get_credentials(&retiring.user_access_key).await;
+    }
+    Ok(metadata
+        .get_temporary_credentials(access_key)
+        .await?
+        .map(|temp| temp.to_credentials()))
+}
+
+/// Account and admin status of an authenticated request's caller
+struct Caller {
+    account_id: String,
+    admin: bool,
+}
+
+impl Caller {
+    fn from_credentials(cred: Option<Credentials>) -> Self {
+        match cred {
+            Some(cred) => Self {
+                admin: cred.policies.iter().any(|p| p == "admin"),
+                account_id: cred.account_id,
+            },
+            None => Self {
+                account_id: DEFAULT_ACCOUNT.to_string(),
+                admin: false,
+            },
+        }
+    }
+}
+
+/// Refuse requests of and to a suspended account
+async fn check_active(state: &AppState, account_id: &str) -> Result<()> {
+    match state.metadata.get_account(account_id).await? {
+        Some(account) if account.is_suspended() => Err(Error::AccountProblem(format!(
+            "account {} is suspended",
+            account_id
+        ))),
+        _ => Ok(()),
+    }
+}
+
+async fn check_quota(
+    state: &AppState,
+    account_id: &str,
+    addition: Addition,
+    headers: &HeaderMap,
+) -> Result<()> {
+    let Some(account) = state.metadata.get_account(account_id).await? else {
+        return Ok(());
+    };
+    if account.quota.is_unlimited() {
+        return Ok(());
+    }
+
+    let (buckets, objects, bytes) = match addition {
+        Addition::Bucket => (1, 0, 0),
+        Addition::Object { part, copy_source } => {
+            let bytes = match copy_source {
+                Some((bucket, key)) => state
+                    .metadata
+                    .get_object(&bucket, &key)
+                    .await?
+                    .map(|obj| obj.size.max(0) as u64)
+                    .unwrap_or(0),
+                None => content_length(headers).unwrap_or(0),
+            };
+            (0, u64::from(!part), bytes)
+        }
+    };
+    let usage = state.metadata.get_account_usage(account_id).await?;
+    account.quota.check(&usage, buckets, objects, bytes)
+}
+
+/// What a request would add to an account, if it is a bucket creation or
+/// an upload
+fn addition(
+    method: &Method,
+    bucket_exists: bool,
+    has_key: bool,
+    query: Option<&str>,
+    headers: &HeaderMap,
+) -> Option<Addition> {
+    if method != Method::PUT {
+        return None;
+    }
+    if !has_key {
+        let creates = !bucket_exists && creates_bucket(method, has_key, query);
+        return creates.then_some(Addition::Bucket);
+    }
+    let params = query_params(query);
+    if !bucket_exists || params.iter().any(|p| OBJECT_SUBRESOURCES.contains(p)) {
+        return None;
+    }
+    Some(Addition::Object {
+        part: params.contains(&"partNumber"),
+        copy_source: copy_source(headers),
+    })
+}
+
+/// Whether a request is a CreateBucket; any parameter besides the SDKs'
+/// operation hint configures an existing bucket
+fn creates_bucket(method: &Method, has_key: bool, query: Option<&str>) -> bool {
+    method == Method::PUT && !has_key && query_params(query).iter().all(|p| *p == "x-id")
+}
+
+/// Names of the query parameters
+fn query_params(query: Option<&str>) -> Vec<&str> {
+    query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.split_once('=').map_or(p, |(name, _)| name))
+        .collect()
+}
+
+/// Bucket and key of a copy's source
+fn copy_source(headers: &HeaderMap) -> Option<(String, String)> {
+    let source = headers.get("x-amz-copy-source")?.to_str().ok()?;
+    let source = source.split_once('?').map_or(source, |(path, _)| path);
+    let source = urlencoding::decode(source).ok()?;
+    let (bucket, key) = source.trim_start_matches('/').split_once('/')?;
+    Some((bucket.to_string(), key.to_string()))
+}
+
+/// Length of the object data, which aws-chunked bodies declare separately
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get("x-amz-decoded-content-length")
+        .or_else(|| headers.get("content-length"))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_creation() {
+        let headers = HeaderMap::new();
+        assert_eq!(
+            addition(&Method::PUT, false, false, None, &headers),
+            Some(Addition::Bucket)
+        );
+        assert_eq!(
+            addition(
+                &Method::PUT,
+                false,
+                false,
+                Some("x-id=CreateBucket"),
+                &headers
+            ),
+            Some(Addition::Bucket)
+        );
+        // Configuring a bucket, or one that exists already
+        assert_eq!(
+            addition(&Method::PUT, false, false, Some("versioning"), &headers),
+            None
+        );
+        assert_eq!(addition(&Method::PUT, true, false, None, &headers), None);
+        assert_eq!(addition(&Method::GET, false, false, None, &headers), None);
+
+        assert!(creates_bucket(&Method::PUT, false, Some("x-id=CreateBucket")));
+        assert!(!creates_bucket(&Method::PUT, false, Some("policy")));
+        assert!(!creates_bucket(&Method::PUT, true, None));
+    }
+
+    #[test]
+    fn test_object_uploads() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            addition(&Method::PUT, true, true, Some("x-id=PutObject"), &headers),
+            Some(Addition::Object {
+                part: false,
+                copy_source: None
+            })
+        );
+        assert_eq!(
+            addition(
+                &Method::PUT,
+                true,
+                true,
+                Some("partNumber=2&uploadId=abc"),
+                &headers
+            ),
+            Some(Addition::Object {
+                part: true,
+                copy_source: None
+            })
+        );
+        assert_eq!(
+            addition(&Method::PUT, true, true, Some("tagging"), &headers),
+            None
+        );
+        assert_eq!(
+            addition(&Method::PUT, true, true, Some("retention="), &headers),
+            None
+        );
+        // NoSuchBucket follows
+        assert_eq!(addition(&Method::PUT, false, true, None, &headers), None);
+
+        headers.insert(
+            "x-amz-copy-source",
+            "/src/photos/a%20b.jpg?versionId=3".parse().unwrap(),
+        );
+        assert_eq!(
+            addition(&Method::PUT, true, true, None, &headers),
+            Some(Addition::Object {
+                part: false,
+                copy_source: Some(("src".into(), "photos/a b.jpg".into()))
+            })
+        );
+    }
+}
@@ -0,0 +1,323 @@
+//! Admission control of S3 requests under overload
+//!
+//! Each request gets a priority from its operation. Small object reads and
+//! writes are the data plane clients wait on and are always admitted.
+//! Listings are the first to give way: while the server is overloaded,
+//! that is while `max_in_flight` requests are in progress or the average
+//! storage latency is above `storage_latency_ms`, they wait in a bounded
+//! queue for the overload to pass and get `SlowDown` once `queue_timeout_ms`
+//! is up. Everything else, such as large uploads and bucket configuration,
+//! is refused only beyond `max_in_flight`.
+//!
+//! A request counts as in progress until its response starts; bodies still
+//! streaming after that are not counted.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use hafiz_core::config::AdmissionConfig;
+use hafiz_core::Error;
+use hafiz_storage::StorageLatency;
+use tokio::sync::Notify;
+use tracing::debug;
+
+use super::request_id::error_response_with_retry;
+use crate::server::AppState;
+
+/// How often a queued request checks a latency-based overload
+const QUEUE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How a request is treated under overload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// Listings; queued, then refused
+    Low,
+    /// Refused beyond `max_in_flight`
+    Normal,
+    /// Small object reads and writes; always admitted
+    High,
+}
+
+impl Priority {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+            Priority::High => "high",
+        }
+    }
+}
+
+/// Priority of an S3 request from its method, path-style URL and headers
+pub fn classify(method: &Method, path: &str, query: &str, headers: &HeaderMap, small_object_size: u64) -> Priority {
+    let has = |name: &str| {
+        query
+            .split('&')
+            .any(|pair| pair.split_once('=').map_or(pair, |(k, _)| k) == name)
+    };
+    let path = path.trim_start_matches('/');
+    let has_key = path.split_once('/').is_some_and(|(_, key)| !key.is_empty());
+
+    if path.is_empty() {
+        // ListBuckets
+        return if method == Method::GET { Priority::Low } else { Priority::Normal };
+    }
+    if !has_key {
+        let listing = method == Method::GET
+            && (query.is_empty() || has("list-type") || has("versions") || has("uploads"));
+        return if listing { Priority::Low } else { Priority::Normal };
+    }
+
+    match *method {
+        // ListParts
+        Method::GET if has("uploadId") => Priority::Low,
+        Method::GET | Method::HEAD | Method::DELETE => Priority::High,
+        Method::PUT if headers.contains_key("x-amz-copy-source") => Priority::Normal,
+        Method::PUT => {
+            let length = headers
+                .get("content-length")
+                .and_then(|v| v.to_str().ok())
+                .and_then(|v| v.parse::<u64>().ok());
+            match length {
+                Some(length) if length <= small_object_size => Priority::High,
+                _ => Priority::Normal,
+            }
+        }
+        _ => Priority::Normal,
+    }
+}
+
+/// Counts requests in progress and decides which to admit
+pub struct AdmissionController {
+    config: AdmissionConfig,
+    storage_latency: Option<Arc<StorageLatency>>,
+    in_flight: AtomicUsize,
+    queued: AtomicUsize,
+    /// Woken whenever a request finishes
+    released: Notify,
+}
+
+/// A request in progress; finishing it may admit a queued one
+pub struct Permit<'a> {
+    controller: &'a AdmissionController,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let in_flight = self.controller.in_flight.fetch_sub(1, Ordering::SeqCst) - 1;
+        metrics::gauge!("hafiz_admission_in_flight").set(in_flight as f64);
+        self.controller.released.notify_waiters();
+    }
+}
+
+impl AdmissionController {
+    pub fn new(config: AdmissionConfig) -> Self {
+        Self {
+            config,
+            storage_latency: None,
+            in_flight: AtomicUsize::new(0),
+            queued: AtomicUsize::new(0),
+            released: Notify::new(),
+        }
+    }
+
+    /// Also count as overloaded while `latency` is above the threshold
+    pub fn with_storage_latency(mut self, latency: Arc<StorageLatency>) -> Self {
+        self.storage_latency = Some(latency);
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+
+    fn overloaded(&self) -> bool {
+        if self.in_flight() >= self.config.max_in_flight {
+            return true;
+        }
+        let threshold = Duration::from_millis(self.config.storage_latency_ms);
+        match &self.storage_latency {
+            Some(latency) if !threshold.is_zero() => latency.average() > threshold,
+            _ => false,
+        }
+    }
+
+    fn enter(&self) -> Permit<'_> {
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        metrics::gauge!("hafiz_admission_in_flight").set(in_flight as f64);
+        Permit { controller: self }
+    }
+
+    /// Admit a request of `priority`, waiting if it is queued; `SlowDown`
+    /// if it is refused
+    pub async fn admit(&self, priority: Priority) -> Result<Permit<'_>, Error> {
+        let admitted = match priority {
+            Priority::High => Some(self.enter()),
+            Priority::Normal => {
+                let permit = self.enter();
+                (self.in_flight() <= self.config.max_in_flight).then_some(permit)
+            }
+            Priority::Low if !self.overloaded() => Some(self.enter()),
+            Priority::Low => self.wait().await,
+        };
+        admitted.ok_or_else(|| {
+            metrics::counter!("hafiz_admission_rejected_total", "priority" => priority.as_str()).increment(1);
+            Error::SlowDown
+        })
+    }
+
+    /// Queue until the overload passes or the queue timeout is up
+    async fn wait(&self) -> Option<Permit<'_>> {
+        if self.queued.fetch_add(1, Ordering::SeqCst) >= self.config.max_queued {
+            self.queued.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+        metrics::gauge!("hafiz_admission_queued").set(self.queued.load(Ordering::SeqCst) as f64);
+
+        let start = Instant::now();
+        let deadline = start + Duration::from_millis(self.config.queue_timeout_ms);
+        let admitted = loop {
+            let released = self.released.notified();
+            if !self.overloaded() {
+                break Some(self.enter());
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break None;
+            }
+            let _ = tokio::time::timeout((deadline - now).min(QUEUE_POLL_INTERVAL), released).await;
+        };
+
+        let queued = self.queued.fetch_sub(1, Ordering::SeqCst) - 1;
+        metrics::gauge!("hafiz_admission_queued").set(queued as f64);
+        metrics::histogram!("hafiz_admission_queue_seconds").record(start.elapsed().as_secs_f64());
+        admitted
+    }
+}
+
+/// Admit, queue or refuse S3 requests by priority while overloaded
+pub async fn admission_control(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let controller = &state.admission;
+    if !controller.is_enabled() {
+        return next.run(request).await;
+    }
+
+    let priority = classify(
+        request.method(),
+        request.uri().path(),
+        request.uri().query().unwrap_or(""),
+        request.headers(),
+        state.config.server.admission.small_object_size,
+    );
+    let _permit = match controller.admit(priority).await {
+        Ok(permit) => permit,
+        Err(e) => {
+            debug!(
+                "Shedding {} {} ({:?} priority, {} in flight)",
+                request.method(),
+                request.uri().path(),
+                priority,
+                controller.in_flight()
+            );
+            return error_response_with_retry(e, 1);
+        }
+    };
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn classify_put(length: Option<&str>, copy: bool) -> Priority {
+        let mut headers = HeaderMap::new();
+        if let Some(length) = length {
+            headers.insert("content-length", length.parse().unwrap());
+        }
+        if copy {
+            headers.insert("x-amz-copy-source", "/b/src".parse().unwrap());
+        }
+        classify(&Method::PUT, "/b/k", "", &headers, 1024)
+    }
+
+    #[test]
+    fn test_classify() {
+        let none = HeaderMap::new();
+        let get = |path, query| classify(&Method::GET, path, query, &none, 1024);
+
+        assert_eq!(get("/", ""), Priority::Low);
+        assert_eq!(get("/b", ""), Priority::Low);
+        assert_eq!(get("/b", "list-type=2&prefix=a"), Priority::Low);
+        assert_eq!(get("/b", "versions"), Priority::Low);
+        assert_eq!(get("/b", "uploads"), Priority::Low);
+        assert_eq!(get("/b", "versioning"), Priority::Normal);
+        assert_eq!(get("/b/k", ""), Priority::High);
+        assert_eq!(get("/b/k", "uploadId=1"), Priority::Low);
+        assert_eq!(classify(&Method::PUT, "/b", "", &none, 1024), Priority::Normal);
+
+        assert_eq!(classify_put(Some("1024"), false), Priority::High);
+        assert_eq!(classify_put(Some("1025"), false), Priority::Normal);
+        assert_eq!(classify_put(None, false), Priority::Normal);
+        assert_eq!(classify_put(Some("0"), true), Priority::Normal);
+    }
+
+    #[tokio::test]
+    async fn test_admit_by_priority() {
+        let controller = AdmissionController::new(AdmissionConfig {
+            enabled: true,
+            max_in_flight: 1,
+            max_queued: 1,
+            queue_timeout_ms: 200,
+            ..AdmissionConfig::default()
+        });
+
+        let first = controller.admit(Priority::Normal).await.unwrap();
+        // At the limit: small data-plane requests still get in
+        let high = controller.admit(Priority::High).await.unwrap();
+        assert!(matches!(controller.admit(Priority::Normal).await, Err(Error::SlowDown)));
+        assert!(matches!(controller.admit(Priority::Low).await, Err(Error::SlowDown)));
+        drop(high);
+        assert_eq!(controller.in_flight(), 1);
+
+        // A queued listing gets in once a request finishes
+        let release = async {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            drop(first);
+        };
+        let (low, ()) = tokio::join!(controller.admit(Priority::Low), release);
+        assert!(low.is_ok());
+    }
+
+    #[test]
+    fn test_storage_latency_overload() {
+        let latency = Arc::new(StorageLatency::default());
+        let controller = AdmissionController::new(AdmissionConfig {
+            enabled: true,
+            storage_latency_ms: 10,
+            ..AdmissionConfig::default()
+        })
+        .with_storage_latency(latency.clone());
+
+        assert!(!controller.overloaded());
+        for _ in 0..50 {
+            latency.record(Duration::from_millis(100));
+        }
+        assert!(controller.overloaded());
+    }
+}
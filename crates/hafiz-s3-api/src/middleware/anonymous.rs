@@ -0,0 +1,492 @@
+//! Anonymous access for unsigned S3 requests
+//!
+//! With `auth.enabled`, a request that carries neither an `Authorization`
+//! header nor a presigned query string is made by the anonymous principal
+//! `*`. It is let through only when the bucket policy allows the action for
+//! `*`, or when an ACL grants the `AllUsers` group the matching permission
+//! and no policy statement explicitly denies it. Everything else gets
+//! `AccessDenied`, so a public download bucket needs nothing more than a
+//! `public-read` ACL or an `s3:GetObject` policy for `*`. Signed requests
+//! only get this far once [`verify_signature`](super::verify_signature) has
+//! checked their signature.
+//!
+//! Objects without an ACL of their own fall back to the bucket ACL.
+//!
+//! Requests made through an access point are checked against it first,
+//! signed or not: the client address must be in one of its allowed
+//! networks, and its policy must not deny the action. An anonymous request
+//! also needs an allow from the access point policy before the bucket
+//! policy and ACLs are consulted; a signed one needs it when the access
+//! point has a policy, unless made by the root user.
+
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use hafiz_core::{
+    types::{
+        actions, AccessControlPolicy, AccessPoint, Permission, PolicyDocument, PolicyEffect,
+        PolicyRequest,
+    },
+    Error,
+};
+use tracing::debug;
+
+use super::access_point::AccessPointRequest;
+use super::client_cert::{is_authenticated, request_principal};
+use super::request_id::error_response;
+use crate::server::AppState;
+use crate::xml;
+
+/// Principal that unsigned requests are evaluated as
+pub const ANONYMOUS_PRINCIPAL: &str = "*";
+
+/// Whose ACL governs a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AclTarget {
+    Bucket,
+    Object,
+}
+
+/// What an unsigned request asks to do
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AnonymousRequest {
+    bucket: String,
+    key: Option<String>,
+    version_id: Option<String>,
+    /// Policy action, e.g. `s3:GetObject`
+    action: String,
+    /// ACL permission that also grants the action, if any does
+    acl: Option<(AclTarget, Permission)>,
+}
+
+impl AnonymousRequest {
+    fn resource(&self) -> String {
+        match &self.key {
+            Some(key) => format!("arn:aws:s3:::{}/{}", self.bucket, key),
+            None => format!("arn:aws:s3:::{}", self.bucket),
+        }
+    }
+}
+
+/// Deny unsigned S3 requests that no policy or ACL opens to everyone
+pub async fn anonymous_access(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let access_key = request_principal(&request);
+    if let Some(access_key) = &access_key {
+        state.key_usage.record(&state.metadata, access_key);
+    }
+    if let Some(AccessPointRequest(access_point)) = request.extensions().get::<AccessPointRequest>() {
+        let client_ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let allowed = access_point_allows(&state, access_point, &request, client_ip, access_key.as_deref());
+        metrics::counter!(
+            "hafiz_access_point_requests_total",
+            "access_point" => access_point.name.clone(),
+            "outcome" => if allowed { "allowed" } else { "denied" }
+        )
+        .increment(1);
+        if !allowed {
+            return deny();
+        }
+    }
+    if !state.config.auth.enabled || is_authenticated(&request) {
+        return next.run(request).await;
+    }
+    // CORS preflights are unauthenticated by design
+    if request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+
+    let Some(anonymous) = classify(
+        request.method(),
+        request.uri().path(),
+        request.uri().query().unwrap_or(""),
+    ) else {
+        return deny();
+    };
+
+    let mut allowed = is_allowed(&state, &anonymous).await;
+
+    // A copy also reads its source
+    if allowed {
+        if let Some(source) = copy_source(request.headers()) {
+            allowed = is_allowed(&state, &source).await;
+        }
+    }
+
+    metrics::counter!(
+        "hafiz_anonymous_requests_total",
+        "outcome" => if allowed { "allowed" } else { "denied" }
+    )
+    .increment(1);
+
+    if !allowed {
+        debug!(
+            "Anonymous {} on {} denied",
+            anonymous.action,
+            anonymous.resource()
+        );
+        return deny();
+    }
+    next.run(request).await
+}
+
+/// Check a request against the access point it came through
+fn access_point_allows(
+    state: &AppState,
+    access_point: &AccessPoint,
+    request: &Request<Body>,
+    client_ip: Option<IpAddr>,
+    access_key: Option<&str>,
+) -> bool {
+    if !access_point.allowed_cidrs.is_empty()
+        && !client_ip.is_some_and(|ip| access_point.allows_ip(ip))
+    {
+        debug!(
+            "Request from {:?} outside the networks of access point {}",
+            client_ip, access_point.name
+        );
+        return false;
+    }
+    if !state.config.auth.enabled || request.method() == Method::OPTIONS {
+        return true;
+    }
+
+    let signed = is_authenticated(request);
+    if signed && access_key == Some(state.config.auth.root_access_key.as_str()) {
+        return true;
+    }
+    let policy = match &access_point.policy {
+        Some(json) => match serde_json::from_str::<PolicyDocument>(json) {
+            Ok(policy) => Some(policy),
+            // A policy that no longer parses grants nothing
+            Err(_) => return false,
+        },
+        None => None,
+    };
+    if policy.is_none() && signed {
+        return true;
+    }
+    let Some(classified) = classify(
+        request.method(),
+        request.uri().path(),
+        request.uri().query().unwrap_or(""),
+    ) else {
+        return false;
+    };
+    let principal = match access_key {
+        Some(key) if signed => key,
+        _ => ANONYMOUS_PRINCIPAL,
+    };
+    let resource = access_point.resource(classified.key.as_deref());
+    let mut policy_request = PolicyRequest::new(classified.action.clone(), resource.clone(), principal);
+    if let Some(ip) = client_ip {
+        policy_request = policy_request.with_context("aws:SourceIp", ip.to_string());
+    }
+    let allowed = policy.is_some_and(|p| p.evaluate(&policy_request) == PolicyEffect::Allow);
+    if !allowed {
+        debug!(
+            "{} on {} not allowed by access point {}",
+            classified.action, resource, access_point.name
+        );
+    }
+    allowed
+}
+
+/// Whether the request carries a signature of some kind
+pub(super) fn is_signed(headers: &HeaderMap, query: Option<&str>) -> bool {
+    headers.contains_key("authorization")
+        || query.is_some_and(|q| hafiz_auth::presigned::is_presigned_request(q) || q.contains("Signature="))
+}
+
+/// Evaluate the bucket policy, then the ACLs
+async fn is_allowed(state: &AppState, request: &AnonymousRequest) -> bool {
+    let policy = match state.metadata.get_bucket_policy(&request.bucket).await {
+        Ok(Some(json)) => serde_json::from_str::<PolicyDocument>(&json).ok(),
+        _ => None,
+    };
+    let policy_request = PolicyRequest::new(
+        request.action.clone(),
+        request.resource(),
+        ANONYMOUS_PRINCIPAL,
+    );
+    if let Some(policy) = &policy {
+        if policy.explicitly_denies(&policy_request) {
+            return false;
+        }
+        if policy.evaluate(&policy_request) == PolicyEffect::Allow {
+            return true;
+        }
+    }
+
+    let Some((target, permission)) = request.acl else {
+        return false;
+    };
+    let mut acl = None;
+    if target == AclTarget::Object {
+        if let Some(key) = &request.key {
+            acl = state
+                .metadata
+                .get_object_acl(&request.bucket, key, request.version_id.as_deref())
+                .await
+                .ok()
+                .flatten();
+        }
+    }
+    if acl.is_none() {
+        acl = state
+            .metadata
+            .get_bucket_acl(&request.bucket)
+            .await
+            .ok()
+            .flatten();
+    }
+
+    acl.and_then(|xml| xml::parse_access_control_policy(xml.as_bytes()).ok())
+        .is_some_and(|acl: AccessControlPolicy| acl.allows_anonymous(permission))
+}
+
+/// Read of the `x-amz-copy-source` object
+fn copy_source(headers: &HeaderMap) -> Option<AnonymousRequest> {
+    let source = headers.get("x-amz-copy-source")?.to_str().ok()?;
+    let source = urlencoding::decode(source).ok()?;
+    let (path, version_id) = match source.split_once("?versionId=") {
+        Some((path, vid)) => (path.to_string(), Some(vid.to_string())),
+        None => (source.to_string(), None),
+    };
+    let (bucket, key) = path.trim_start_matches('/').split_once('/')?;
+    Some(AnonymousRequest {
+        bucket: bucket.to_string(),
+        key: Some(key.to_string()),
+        version_id,
+        action: actions::GET_OBJECT.to_string(),
+        acl: Some((AclTarget::Object, Permission::Read)),
+    })
+}
+
+/// Work out the action an S3 request performs; `None` for requests that
+/// are never open to anonymous users
+fn classify(method: &Method, path: &str, query: &str) -> Option<AnonymousRequest> {
+    let path = path.trim_start_matches('/');
+    let (bucket, key) = match path.split_once('/') {
+        Some((bucket, key)) if !key.is_empty() => (bucket, Some(key)),
+        Some((bucket, _)) => (bucket, None),
+        None => (path, None),
+    };
+    // ListBuckets is always private
+    if bucket.is_empty() {
+        return None;
+    }
+    let key = key.map(|k| urlencoding::decode(k).map(|k| k.into_owned()).unwrap_or_else(|_| k.to_string()));
+
+    let params: Vec<(String, String)> = url::form_urlencoded::parse(query.as_bytes())
+        .into_owned()
+        .collect();
+    let has: HashSet<&str> = params.iter().map(|(k, _)| k.as_str()).collect();
+    let version_id = params
+        .iter()
+        .find(|(k, _)| k == "versionId")
+        .map(|(_, v)| v.clone());
+
+    use AclTarget::{Bucket, Object};
+    use Permission::{Read, ReadAcp, Write, WriteAcp};
+
+    let (action, acl): (&str, Option<(AclTarget, Permission)>) = match (method, key.is_some()) {
+        (&Method::HEAD, false) => (actions::LIST_BUCKET, Some((Bucket, Read))),
+        (&Method::GET, false) => {
+            if has.contains("acl") {
+                (actions::GET_BUCKET_ACL, Some((Bucket, ReadAcp)))
+            } else if has.contains("policy") {
+                (actions::GET_BUCKET_POLICY, None)
+            } else if has.contains("versioning") {
+                ("s3:GetBucketVersioning", None)
+            } else if has.contains("lifecycle") {
+                ("s3:GetLifecycleConfiguration", None)
+            } else if has.contains("tagging") {
+                ("s3:GetBucketTagging", None)
+            } else if has.contains("cors") {
+                ("s3:GetBucketCORS", None)
+            } else if has.contains("notification") {
+                ("s3:GetBucketNotification", None)
+            } else if has.contains("object-lock") {
+                ("s3:GetBucketObjectLockConfiguration", None)
+            } else if has.contains("accelerate") {
+                ("s3:GetAccelerateConfiguration", None)
+            } else if has.contains("location") {
+                (actions::GET_BUCKET_LOCATION, None)
+            } else if has.contains("versions") {
+                (actions::LIST_BUCKET_VERSIONS, Some((Bucket, Read)))
+            } else if has.contains("uploads") {
+                (actions::LIST_BUCKET_MULTIPART_UPLOADS, Some((Bucket, Read)))
+            } else {
+                (actions::LIST_BUCKET, Some((Bucket, Read)))
+            }
+        }
+        (&Method::PUT, false) => {
+            if has.contains("acl") {
+                (actions::PUT_BUCKET_ACL, Some((Bucket, WriteAcp)))
+            } else if has.contains("policy") {
+                (actions::PUT_BUCKET_POLICY, None)
+            } else if has.contains("versioning") {
+                ("s3:PutBucketVersioning", None)
+            } else if has.contains("lifecycle") {
+                ("s3:PutLifecycleConfiguration", None)
+            } else if has.contains("tagging") {
+                ("s3:PutBucketTagging", None)
+            } else if has.contains("cors") {
+                ("s3:PutBucketCORS", None)
+            } else if has.contains("notification") {
+                ("s3:PutBucketNotification", None)
+            } else if has.contains("object-lock") {
+                ("s3:PutBucketObjectLockConfiguration", None)
+            } else if has.contains("accelerate") {
+                ("s3:PutAccelerateConfiguration", None)
+            } else {
+                (actions::CREATE_BUCKET, None)
+            }
+        }
+        (&Method::DELETE, false) => {
+            if has.contains("policy") {
+                (actions::DELETE_BUCKET_POLICY, None)
+            } else if has.contains("lifecycle") {
+                ("s3:PutLifecycleConfiguration", None)
+            } else if has.contains("tagging") {
+                ("s3:PutBucketTagging", None)
+            } else if has.contains("cors") {
+                ("s3:PutBucketCORS", None)
+            } else {
+                (actions::DELETE_BUCKET, None)
+            }
+        }
+        (&Method::POST, false) if has.contains("delete") => {
+            // Evaluated for the whole bucket rather than key by key
+            return Some(AnonymousRequest {
+                bucket: bucket.to_string(),
+                key: Some("*".to_string()),
+                version_id: None,
+                action: actions::DELETE_OBJECT.to_string(),
+                acl: Some((Bucket, Write)),
+            });
+        }
+        (&Method::GET, true) => {
+            if has.contains("acl") {
+                (actions::GET_OBJECT_ACL, Some((Object, ReadAcp)))
+            } else if has.contains("tagging") {
+                (actions::GET_OBJECT_TAGGING, None)
+            } else if has.contains("retention") {
+                ("s3:GetObjectRetention", None)
+            } else if has.contains("legal-hold") {
+                ("s3:GetObjectLegalHold", None)
+            } else if has.contains("uploadId") {
+                (actions::LIST_MULTIPART_UPLOAD_PARTS, Some((Bucket, Read)))
+            } else if version_id.is_some() {
+                ("s3:GetObjectVersion", Some((Object, Read)))
+            } else {
+                (actions::GET_OBJECT, Some((Object, Read)))
+            }
+        }
+        (&Method::HEAD, true) if version_id.is_some() => {
+            ("s3:GetObjectVersion", Some((Object, Read)))
+        }
+        (&Method::HEAD, true) => (actions::GET_OBJECT, Some((Object, Read))),
+        (&Method::PUT, true) => {
+            if has.contains("acl") {
+                (actions::PUT_OBJECT_ACL, Some((Object, WriteAcp)))
+            } else if has.contains("tagging") {
+                (actions::PUT_OBJECT_TAGGING, None)
+            } else if has.contains("retention") {
+                ("s3:PutObjectRetention", None)
+            } else if has.contains("legal-hold") {
+                ("s3:PutObjectLegalHold", None)
+            } else {
+                (actions::PUT_OBJECT, Some((Bucket, Write)))
+            }
+        }
+        (&Method::DELETE, true) => {
+            if has.contains("uploadId") {
+                (actions::ABORT_MULTIPART_UPLOAD, Some((Bucket, Write)))
+            } else if has.contains("tagging") {
+                (actions::DELETE_OBJECT_TAGGING, None)
+            } else if version_id.is_some() {
+                ("s3:DeleteObjectVersion", Some((Bucket, Write)))
+            } else {
+                (actions::DELETE_OBJECT, Some((Bucket, Write)))
+            }
+        }
+        (&Method::POST, true) if has.contains("uploads") || has.contains("uploadId") => {
+            (actions::PUT_OBJECT, Some((Bucket, Write)))
+        }
+        _ => return None,
+    };
+
+    Some(AnonymousRequest {
+        bucket: bucket.to_string(),
+        key,
+        version_id,
+        action: action.to_string(),
+        acl,
+    })
+}
+
+fn deny() -> Response {
+    error_response(Error::AccessDenied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_reads() {
+        let get = classify(&Method::GET, "/public/docs/a%20b.pdf", "").unwrap();
+        assert_eq!(get.bucket, "public");
+        assert_eq!(get.key.as_deref(), Some("docs/a b.pdf"));
+        assert_eq!(get.action, actions::GET_OBJECT);
+        assert_eq!(get.acl, Some((AclTarget::Object, Permission::Read)));
+        assert_eq!(get.resource(), "arn:aws:s3:::public/docs/a b.pdf");
+
+        let list = classify(&Method::GET, "/public", "list-type=2&prefix=x").unwrap();
+        assert_eq!(list.action, actions::LIST_BUCKET);
+        assert_eq!(list.resource(), "arn:aws:s3:::public");
+
+        let acl = classify(&Method::GET, "/public/a", "acl").unwrap();
+        assert_eq!(acl.acl, Some((AclTarget::Object, Permission::ReadAcp)));
+    }
+
+    #[test]
+    fn test_classify_writes_and_config() {
+        let put = classify(&Method::PUT, "/public/a", "").unwrap();
+        assert_eq!(put.acl, Some((AclTarget::Bucket, Permission::Write)));
+
+        // Configuration changes are never granted by ACLs
+        let policy = classify(&Method::PUT, "/public", "policy").unwrap();
+        assert_eq!(policy.acl, None);
+        assert!(classify(&Method::PUT, "/public", "").unwrap().acl.is_none());
+        let accelerate = classify(&Method::PUT, "/public", "accelerate").unwrap();
+        assert_eq!(accelerate.action, "s3:PutAccelerateConfiguration");
+
+        // ListBuckets is not open to anyone
+        assert!(classify(&Method::GET, "/", "").is_none());
+    }
+
+    #[test]
+    fn test_signed_requests_skip_the_check() {
+        let mut headers = HeaderMap::new();
+        assert!(!is_signed(&headers, None));
+        assert!(is_signed(&headers, Some("X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Signature=abc")));
+        headers.insert("authorization", "AWS4-HMAC-SHA256 Credential=x".parse().unwrap());
+        assert!(is_signed(&headers, None));
+    }
+}
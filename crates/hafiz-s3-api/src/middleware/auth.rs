@@ -1,24 +1,62 @@
 //! Authentication middleware for Admin API
 
+use std::net::SocketAddr;
+
 use axum::{
     body::Body,
-    extract::State,
+    extract::{ConnectInfo, State},
     http::{Request, StatusCode},
     middleware::Next,
     response::Response,
 };
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use hafiz_core::types::{AdminRole, Credentials};
 
 use crate::server::AppState;
 
+/// Caller of an admin API request, added to the request's extensions by
+/// [`admin_auth`]
+#[derive(Debug, Clone)]
+pub struct AdminPrincipal {
+    /// Access key, token name or identity provider username
+    pub name: String,
+    pub role: AdminRole,
+}
+
+/// Refuse requests to the admin listener from outside `admin.allowed_cidrs`
+pub async fn admin_network(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let admin = &state.config.admin;
+    if !admin.allowed_cidrs.is_empty() && !client_ip.is_some_and(|ip| admin.allows_ip(ip)) {
+        tracing::warn!("Refused admin request from {:?} outside the allowed networks", client_ip);
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(next.run(request).await)
+}
+
 /// Admin authentication middleware
 ///
-/// Supports two authentication methods:
+/// Supports four authentication methods:
 /// 1. Bearer token: Authorization: Bearer <access_key>:<secret_key_base64>
 /// 2. Basic auth: Authorization: Basic <base64(access_key:secret_key)>
+/// 3. Admin token: Authorization: Bearer <token> (from `[admin] tokens`)
+/// 4. OIDC ID token: Authorization: Bearer <id_token> (when OIDC is enabled)
+///
+/// Every caller needs an admin role. Users have one when they are admins
+/// or were given one; tokens have the role they are configured with; and
+/// federated identities, by ID token or STS credentials, take theirs from
+/// an `admin` or `admin:<role>` policy. Callers without a role are refused
+/// with 403.
 pub async fn admin_auth(
     State(state): State<AppState>,
-    request: Request<Body>,
+    mut request: Request<Body>,
     next: Next,
 ) -> Result<Response, StatusCode> {
     let auth_header = request
@@ -26,12 +64,12 @@ pub async fn admin_auth(
         .get("Authorization")
         .and_then(|h| h.to_str().ok());
 
-    match auth_header {
+    let principal = match auth_header {
         Some(header) if header.starts_with("Bearer ") => {
-            validate_bearer_auth(header, &state).await?;
+            validate_bearer_auth(header, &state).await?
         }
         Some(header) if header.starts_with("Basic ") => {
-            validate_basic_auth(header, &state).await?;
+            validate_basic_auth(header, &state).await?
         }
         _ => {
             // For development, also check query params
@@ -45,7 +83,7 @@ pub async fn admin_auth(
                         .collect();
 
                     if let (Some(ak), Some(sk)) = (params.get("access_key"), params.get("secret_key")) {
-                        validate_credentials(ak, sk, &state).await?;
+                        validate_credentials(ak, sk, &state).await?
                     } else {
                         return Err(StatusCode::UNAUTHORIZED);
                     }
@@ -56,15 +94,55 @@ pub async fn admin_auth(
                 return Err(StatusCode::UNAUTHORIZED);
             }
         }
-    }
+    };
 
+    request.extensions_mut().insert(principal);
+    Ok(next.run(request).await)
+}
+
+/// Refuse admin requests whose caller's role doesn't permit `required`
+///
+/// Runs inside [`admin_auth`], which names the caller.
+pub async fn admin_role(
+    State(required): State<AdminRole>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let principal = request
+        .extensions()
+        .get::<AdminPrincipal>()
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    if !principal.role.permits(required) {
+        tracing::debug!(
+            "Refused {} {} to {} ({}): needs {}",
+            request.method(),
+            request.uri().path(),
+            principal.name,
+            principal.role,
+            required
+        );
+        return Err(StatusCode::FORBIDDEN);
+    }
     Ok(next.run(request).await)
 }
 
 /// Validate Bearer token authentication
-async fn validate_bearer_auth(header: &str, state: &AppState) -> Result<(), StatusCode> {
+async fn validate_bearer_auth(header: &str, state: &AppState) -> Result<AdminPrincipal, StatusCode> {
     let token = header.trim_start_matches("Bearer ");
 
+    // Anything without a key separator is an admin token or an OIDC ID token
+    if !token.contains(':') {
+        let digest = hafiz_crypto::sha256_hash(token.as_bytes());
+        if let Some(admin_token) = state.config.admin.token(&digest) {
+            tracing::debug!("Admin request with token {}", admin_token.name);
+            return Ok(AdminPrincipal {
+                name: admin_token.name.clone(),
+                role: admin_token.role,
+            });
+        }
+        return validate_id_token(token, state).await;
+    }
+
     // Token format: access_key:secret_key_base64
     let parts: Vec<&str> = token.splitn(2, ':').collect();
     if parts.len() != 2 {
@@ -81,7 +159,7 @@ async fn validate_bearer_auth(header: &str, state: &AppState) -> Result<(), Stat
 }
 
 /// Validate Basic authentication
-async fn validate_basic_auth(header: &str, state: &AppState) -> Result<(), StatusCode> {
+async fn validate_basic_auth(header: &str, state: &AppState) -> Result<AdminPrincipal, StatusCode> {
     let encoded = header.trim_start_matches("Basic ");
 
     let decoded = BASE64
@@ -99,15 +177,81 @@ async fn validate_basic_auth(header: &str, state: &AppState) -> Result<(), Statu
     validate_credentials(parts[0], parts[1], state).await
 }
 
+/// Validate an OIDC ID token
+async fn validate_id_token(token: &str, state: &AppState) -> Result<AdminPrincipal, StatusCode> {
+    let verifier = state.oidc.as_ref().ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let identity = verifier.verify(token).await.map_err(|e| {
+        tracing::debug!("Rejected admin ID token: {}", e);
+        StatusCode::UNAUTHORIZED
+    })?;
+
+    let role = AdminRole::from_policies(&identity.policies).ok_or(StatusCode::FORBIDDEN)?;
+    Ok(AdminPrincipal {
+        name: identity.username,
+        role,
+    })
+}
+
 /// Validate credentials against the metadata store
-async fn validate_credentials(access_key: &str, secret_key: &str, state: &AppState) -> Result<(), StatusCode> {
+///
+/// Besides a user's current key, this accepts a key that was rotated out
+/// and is still within its grace period, and STS credentials.
+async fn validate_credentials(
+    access_key: &str,
+    secret_key: &str,
+    state: &AppState,
+) -> Result<AdminPrincipal, StatusCode> {
     let metadata = &state.metadata;
 
-    let cred = metadata
+    let cred = match metadata
         .get_credentials(access_key)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+    {
+        Some(cred) => cred,
+        None => {
+            if let Some(retiring) = metadata
+                .get_retiring_key(access_key)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            {
+                if retiring.secret_key != secret_key {
+                    return Err(StatusCode::UNAUTHORIZED);
+                }
+                // The old key carries the permissions of its user
+                let owner = metadata
+                    .get_credentials(&retiring.user_access_key)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                    .ok_or(StatusCode::UNAUTHORIZED)?;
+                if !owner.enabled {
+                    return Err(StatusCode::FORBIDDEN);
+                }
+                let role = user_role(&owner, state).await?;
+                state.key_usage.record(metadata, access_key);
+                return Ok(AdminPrincipal {
+                    name: owner.access_key,
+                    role,
+                });
+            }
+
+            // Temporary credentials from STS
+            let temp = metadata
+                .get_temporary_credentials(access_key)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::UNAUTHORIZED)?;
+            if temp.secret_key != secret_key {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+            let role = AdminRole::from_policies(&temp.policies).ok_or(StatusCode::FORBIDDEN)?;
+            return Ok(AdminPrincipal {
+                name: temp.username,
+                role,
+            });
+        }
+    };
 
     if !cred.enabled {
         return Err(StatusCode::FORBIDDEN);
@@ -117,5 +261,24 @@ async fn validate_credentials(access_key: &str, secret_key: &str, state: &AppSta
         return Err(StatusCode::UNAUTHORIZED);
     }
 
-    Ok(())
+    let role = user_role(&cred, state).await?;
+    state.key_usage.record(metadata, access_key);
+    Ok(AdminPrincipal {
+        name: cred.access_key,
+        role,
+    })
+}
+
+/// Admin role of a user: admins are super admins, other users have the
+/// role they were given, if any
+async fn user_role(cred: &Credentials, state: &AppState) -> Result<AdminRole, StatusCode> {
+    if let Some(role) = AdminRole::from_policies(&cred.policies) {
+        return Ok(role);
+    }
+    state
+        .metadata
+        .get_admin_role(&cred.access_key)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::FORBIDDEN)
 }
@@ -0,0 +1,320 @@
+//! Bandwidth throttling per bucket and per access key
+//!
+//! Limits are set through the admin API and stored in the metadata
+//! database. Each bucket or access key with a limit has a token bucket per
+//! direction, shared by all of its requests. Request and response bodies are
+//! streamed through the token buckets of the addressed bucket and of the
+//! signing access key, in chunks of at most [`MAX_CHUNK`] bytes; a chunk
+//! waits until it fits under every rate that applies. Up to one second of
+//! traffic may pass as a burst.
+//!
+//! A changed limit takes effect for transfers already in progress.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use futures::{stream, StreamExt};
+use hafiz_core::types::{BandwidthLimit, BandwidthScope};
+use parking_lot::{Mutex, RwLock};
+
+use super::client_cert::request_principal;
+use super::request_id::bucket_and_key;
+use crate::server::AppState;
+
+/// Largest piece of a body passed on at once, so a large buffered body
+/// still flows at the limited rate rather than in one late burst
+pub const MAX_CHUNK: usize = 64 * 1024;
+
+/// Direction of a body relative to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    Ingress,
+    Egress,
+}
+
+impl Direction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Ingress => "ingress",
+            Direction::Egress => "egress",
+        }
+    }
+}
+
+/// Token bucket refilled at `rate` bytes per second, holding at most one
+/// second's worth
+struct TokenBucket {
+    state: Mutex<TokenState>,
+}
+
+struct TokenState {
+    /// Bytes per second; 0 means unlimited
+    rate: u64,
+    /// Negative while transfers that already went ahead are paid off
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u64, now: Instant) -> Self {
+        Self {
+            state: Mutex::new(TokenState {
+                rate,
+                tokens: rate as f64,
+                refilled: now,
+            }),
+        }
+    }
+
+    fn rate(&self) -> u64 {
+        self.state.lock().rate
+    }
+
+    fn set_rate(&self, rate: u64) {
+        let mut state = self.state.lock();
+        state.rate = rate;
+        state.tokens = state.tokens.min(rate as f64);
+    }
+
+    /// Take `bytes` tokens, running into debt if there are not enough;
+    /// returns how long the caller has to wait for the debt to be repaid
+    fn take(&self, bytes: usize, now: Instant) -> Duration {
+        let mut state = self.state.lock();
+        if state.rate == 0 {
+            return Duration::ZERO;
+        }
+        let rate = state.rate as f64;
+        let elapsed = now.saturating_duration_since(state.refilled).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * rate).min(rate) - bytes as f64;
+        state.refilled = state.refilled.max(now);
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / rate)
+        }
+    }
+}
+
+/// Token buckets of one bucket or access key
+struct Limits {
+    ingress: Arc<TokenBucket>,
+    egress: Arc<TokenBucket>,
+}
+
+impl Limits {
+    fn limit(&self) -> BandwidthLimit {
+        BandwidthLimit {
+            ingress_bytes_per_sec: self.ingress.rate(),
+            egress_bytes_per_sec: self.egress.rate(),
+        }
+    }
+
+    fn direction(&self, direction: Direction) -> &Arc<TokenBucket> {
+        match direction {
+            Direction::Ingress => &self.ingress,
+            Direction::Egress => &self.egress,
+        }
+    }
+}
+
+/// Bandwidth limits in force
+#[derive(Default)]
+pub struct BandwidthLimiter {
+    limits: RwLock<HashMap<(BandwidthScope, String), Limits>>,
+}
+
+impl BandwidthLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the limit of a bucket or access key; an unlimited one is removed
+    pub fn set(&self, scope: BandwidthScope, name: &str, limit: BandwidthLimit) {
+        let mut limits = self.limits.write();
+        let key = (scope, name.to_string());
+        if let Some(existing) = limits.get(&key) {
+            existing.ingress.set_rate(limit.ingress_bytes_per_sec);
+            existing.egress.set_rate(limit.egress_bytes_per_sec);
+            if limit.is_unlimited() {
+                limits.remove(&key);
+            }
+        } else if !limit.is_unlimited() {
+            let now = Instant::now();
+            limits.insert(
+                key,
+                Limits {
+                    ingress: Arc::new(TokenBucket::new(limit.ingress_bytes_per_sec, now)),
+                    egress: Arc::new(TokenBucket::new(limit.egress_bytes_per_sec, now)),
+                },
+            );
+        }
+    }
+
+    /// Limit of a bucket or access key; unlimited if none is set
+    pub fn get(&self, scope: BandwidthScope, name: &str) -> BandwidthLimit {
+        self.limits
+            .read()
+            .get(&(scope, name.to_string()))
+            .map(Limits::limit)
+            .unwrap_or_default()
+    }
+
+    /// All limits in force, by scope and name
+    pub fn list(&self) -> Vec<(BandwidthScope, String, BandwidthLimit)> {
+        let mut list: Vec<_> = self
+            .limits
+            .read()
+            .iter()
+            .map(|((scope, name), limits)| (*scope, name.clone(), limits.limit()))
+            .collect();
+        list.sort_by(|a, b| (a.0.as_str(), &a.1).cmp(&(b.0.as_str(), &b.1)));
+        list
+    }
+
+    /// Token buckets a body in `direction` passes through
+    fn buckets(
+        &self,
+        direction: Direction,
+        bucket: Option<&str>,
+        access_key: Option<&str>,
+    ) -> Vec<Arc<TokenBucket>> {
+        let limits = self.limits.read();
+        let targets = [
+            bucket.map(|b| (BandwidthScope::Bucket, b)),
+            access_key.map(|k| (BandwidthScope::AccessKey, k)),
+        ];
+        targets
+            .into_iter()
+            .flatten()
+            .filter_map(|(scope, name)| limits.get(&(scope, name.to_string())))
+            .map(|limits| limits.direction(direction))
+            .filter(|bucket| bucket.rate() > 0)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Stream request and response bodies at the rates of their bucket and
+/// access key
+pub async fn bandwidth_throttle(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let (bucket, _) = bucket_and_key(request.uri().path());
+    let access_key = request_principal(&request);
+    let limiter = &state.bandwidth;
+    let ingress = limiter.buckets(Direction::Ingress, bucket.as_deref(), access_key.as_deref());
+    let egress = limiter.buckets(Direction::Egress, bucket.as_deref(), access_key.as_deref());
+
+    let (parts, body) = request.into_parts();
+    let request = Request::from_parts(parts, throttle(body, ingress, Direction::Ingress));
+    let (parts, body) = next.run(request).await.into_parts();
+    Response::from_parts(parts, throttle(body, egress, Direction::Egress))
+}
+
+fn throttle(body: Body, buckets: Vec<Arc<TokenBucket>>, direction: Direction) -> Body {
+    if buckets.is_empty() {
+        return body;
+    }
+
+    let chunks = stream::unfold(
+        (body.into_data_stream(), Bytes::new(), buckets),
+        move |(mut inner, mut rest, buckets)| async move {
+            if rest.is_empty() {
+                match inner.next().await? {
+                    Ok(chunk) => rest = chunk,
+                    Err(e) => return Some((Err(e), (inner, rest, buckets))),
+                }
+            }
+            let chunk = rest.split_to(rest.len().min(MAX_CHUNK));
+            let now = Instant::now();
+            let wait = buckets
+                .iter()
+                .map(|bucket| bucket.take(chunk.len(), now))
+                .max()
+                .unwrap_or_default();
+            if !wait.is_zero() {
+                metrics::counter!("hafiz_bandwidth_throttled_bytes_total", "direction" => direction.as_str())
+                    .increment(chunk.len() as u64);
+                tokio::time::sleep(wait).await;
+            }
+            Some((Ok(chunk), (inner, rest, buckets)))
+        },
+    );
+    Body::from_stream(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket_allows_one_second_burst() {
+        let start = Instant::now();
+        let bucket = TokenBucket::new(1000, start);
+
+        assert_eq!(bucket.take(1000, start), Duration::ZERO);
+        // In debt: the next 500 bytes wait half a second
+        assert_eq!(bucket.take(500, start), Duration::from_millis(500));
+        // A second later the debt is repaid and 500 bytes go at once
+        assert_eq!(bucket.take(500, start + Duration::from_secs(1)), Duration::ZERO);
+        // Idle time never banks more than one second
+        assert_eq!(bucket.take(1000, start + Duration::from_secs(60)), Duration::ZERO);
+        assert_eq!(bucket.take(1, start + Duration::from_secs(60)), Duration::from_millis(1));
+    }
+
+    #[test]
+    fn test_limits_apply_per_scope_and_update_in_place() {
+        let limiter = BandwidthLimiter::new();
+        limiter.set(
+            BandwidthScope::Bucket,
+            "photos",
+            BandwidthLimit {
+                ingress_bytes_per_sec: 100,
+                egress_bytes_per_sec: 0,
+            },
+        );
+        limiter.set(
+            BandwidthScope::AccessKey,
+            "AKIA1",
+            BandwidthLimit {
+                ingress_bytes_per_sec: 0,
+                egress_bytes_per_sec: 200,
+            },
+        );
+
+        assert_eq!(limiter.buckets(Direction::Ingress, Some("photos"), Some("AKIA1")).len(), 1);
+        assert_eq!(limiter.buckets(Direction::Egress, Some("photos"), Some("AKIA1")).len(), 1);
+        assert_eq!(limiter.buckets(Direction::Ingress, Some("other"), Some("AKIA2")).len(), 0);
+        // A key's limit is not a bucket's of the same name
+        assert_eq!(limiter.buckets(Direction::Egress, Some("AKIA1"), None).len(), 0);
+
+        let held = limiter.buckets(Direction::Ingress, Some("photos"), None);
+        limiter.set(BandwidthScope::Bucket, "photos", BandwidthLimit::default());
+        assert_eq!(held[0].rate(), 0);
+        assert_eq!(limiter.get(BandwidthScope::Bucket, "photos"), BandwidthLimit::default());
+        assert_eq!(limiter.list().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_throttled_body_is_split_into_chunks() {
+        let bucket = Arc::new(TokenBucket::new(MAX_CHUNK as u64 * 3, Instant::now()));
+        let body = Body::from(vec![7u8; MAX_CHUNK * 3]);
+        let mut chunks = throttle(body, vec![bucket], Direction::Egress).into_data_stream();
+
+        let mut sizes = Vec::new();
+        while let Some(chunk) = chunks.next().await {
+            sizes.push(chunk.unwrap().len());
+        }
+        assert_eq!(sizes, vec![MAX_CHUNK; 3]);
+    }
+}
@@ -0,0 +1,667 @@
+//! Capture of S3 requests for later replay
+//!
+//! An administrator starts a capture through the admin API with a filter on
+//! operation, bucket, principal and outcome. While it runs, every matching
+//! S3 request is written as a [`CapturedRequest`] JSON object to the capture
+//! bucket, under `<prefix><capture id>/`, keyed by arrival time. A record
+//! holds the method, path, query, headers, status, response headers and
+//! duration of the request, and optionally the request and response bodies
+//! up to a size cap. `hafiz replay` sends the recorded requests to another
+//! server to reproduce a problem there.
+//!
+//! Credentials never reach the records: the `Authorization` header, session
+//! tokens, SSE-C keys and the signature parameters of pre-signed URLs are
+//! left out, and the replaying client signs with its own. Requests to the
+//! capture bucket itself are not captured.
+//!
+//! One capture runs at a time. It ends after `max_requests` records or
+//! `duration_secs`, whichever comes first, or when stopped through the API.
+//! Captures are kept in memory only, so a restart ends the running one.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    body::{Body, Bytes},
+    extract::State,
+    http::{HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use chrono::{DateTime, Duration, Utc};
+use hafiz_core::types::ObjectInternal as Object;
+use hafiz_core::{Error, Result};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::anonymous::ANONYMOUS_PRINCIPAL;
+use super::client_cert::request_principal;
+use super::request_id::{bucket_and_key, current_request_id, error_response};
+use crate::consensus;
+use crate::metrics::S3Operation;
+use crate::server::AppState;
+
+/// Most requests one capture may record
+pub const MAX_REQUESTS: u64 = 100_000;
+
+/// Largest request or response body a capture may record
+pub const MAX_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// Longest a capture may run
+pub const MAX_DURATION_SECS: u64 = 7 * 24 * 3600;
+
+/// Headers that carry credentials or keys
+const REDACTED_HEADERS: &[&str] = &[
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "x-amz-security-token",
+    "x-amz-server-side-encryption-customer-key",
+    "x-amz-copy-source-server-side-encryption-customer-key",
+];
+
+/// Query parameters of pre-signed URLs that carry the signature
+const SIGNATURE_PARAMS: &[&str] = &[
+    "x-amz-algorithm",
+    "x-amz-credential",
+    "x-amz-date",
+    "x-amz-expires",
+    "x-amz-signedheaders",
+    "x-amz-signature",
+    "x-amz-security-token",
+    "awsaccesskeyid",
+    "signature",
+    "expires",
+];
+
+fn default_prefix() -> String {
+    "captures/".to_string()
+}
+
+fn default_max_requests() -> u64 {
+    1000
+}
+
+fn default_max_body_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_duration_secs() -> u64 {
+    3600
+}
+
+/// Which requests a capture records
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CaptureFilter {
+    /// Operations to record, e.g. `PutObject`; all when empty
+    #[serde(default)]
+    pub operations: Vec<String>,
+    /// Buckets addressed; all when empty
+    #[serde(default)]
+    pub buckets: Vec<String>,
+    /// Access key that signed the request; anyone when unset
+    #[serde(default)]
+    pub principal: Option<String>,
+    /// Only requests answered with an error status
+    #[serde(default)]
+    pub errors_only: bool,
+}
+
+/// A capture as started through the admin API
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaptureRequest {
+    /// Bucket the records are written to
+    pub bucket: String,
+    /// Prepended to `<capture id>/` in the record keys
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    #[serde(default)]
+    pub filter: CaptureFilter,
+    /// Record request and response bodies of at most `max_body_bytes`
+    #[serde(default)]
+    pub include_bodies: bool,
+    #[serde(default = "default_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Stop after this many records
+    #[serde(default = "default_max_requests")]
+    pub max_requests: u64,
+    /// Stop after this many seconds
+    #[serde(default = "default_duration_secs")]
+    pub duration_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CaptureState {
+    Running,
+    Stopped,
+}
+
+/// Capture progress as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStatus {
+    pub id: String,
+    pub state: CaptureState,
+    pub bucket: String,
+    /// Where the records are, e.g. `captures/<id>/`
+    pub prefix: String,
+    pub filter: CaptureFilter,
+    pub include_bodies: bool,
+    pub max_body_bytes: usize,
+    pub max_requests: u64,
+    pub started_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub stopped_at: Option<DateTime<Utc>>,
+    /// Why the capture stopped: `stopped`, `limit` or `expired`
+    pub stop_reason: Option<String>,
+    /// Records written
+    pub captured: u64,
+    /// Records that could not be written
+    pub failed: u64,
+}
+
+/// One captured request, as stored in the capture bucket
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapturedRequest {
+    pub capture_id: String,
+    pub request_id: String,
+    pub time: DateTime<Utc>,
+    /// S3 operation, when it could be told from the request
+    pub operation: Option<String>,
+    /// Access key that signed the request, or the anonymous principal
+    pub principal: String,
+    pub method: String,
+    /// Path as sent, still percent-encoded
+    pub path: String,
+    /// Query string without signature parameters
+    pub query: Option<String>,
+    /// Headers without credentials, in the order sent
+    pub headers: Vec<(String, String)>,
+    /// Base64 request body, when bodies are captured and it fit the cap
+    pub body: Option<String>,
+    pub status: u16,
+    pub response_headers: Vec<(String, String)>,
+    /// Base64 response body, when bodies are captured and it fit the cap
+    pub response_body: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// The running or last capture
+struct Capture {
+    request: CaptureRequest,
+    operations: Vec<S3Operation>,
+    status: RwLock<CaptureStatus>,
+    /// Records claimed so far, including those still being written
+    claimed: AtomicU64,
+}
+
+impl Capture {
+    fn id(&self) -> String {
+        self.status.read().id.clone()
+    }
+
+    fn is_running(&self) -> bool {
+        self.status.read().state == CaptureState::Running
+    }
+
+    /// Whether a request for `bucket` by `principal` is wanted, before its
+    /// outcome is known
+    fn wants(&self, op: Option<S3Operation>, bucket: Option<&str>, principal: &str) -> bool {
+        let filter = &self.request.filter;
+        if bucket == Some(self.request.bucket.as_str()) {
+            return false;
+        }
+        if !self.operations.is_empty() && !op.is_some_and(|op| self.operations.contains(&op)) {
+            return false;
+        }
+        if !filter.buckets.is_empty()
+            && !bucket.is_some_and(|b| filter.buckets.iter().any(|f| f == b))
+        {
+            return false;
+        }
+        filter.principal.as_deref().map_or(true, |p| p == principal)
+    }
+
+    /// Take one of the remaining records; the last one ends the capture
+    fn claim(&self) -> bool {
+        let n = self.claimed.fetch_add(1, Ordering::SeqCst);
+        if n >= self.request.max_requests {
+            return false;
+        }
+        if n + 1 == self.request.max_requests {
+            self.stop("limit");
+        }
+        true
+    }
+
+    fn stop(&self, reason: &str) {
+        let mut status = self.status.write();
+        if status.state == CaptureState::Stopped {
+            return;
+        }
+        status.state = CaptureState::Stopped;
+        status.stopped_at = Some(Utc::now());
+        status.stop_reason = Some(reason.to_string());
+        info!(
+            "Request capture {} stopped ({}): {} requests recorded",
+            status.id, reason, status.captured
+        );
+    }
+
+    /// Store a record in the capture bucket
+    async fn write(&self, state: &AppState, record: &CapturedRequest) -> Result<()> {
+        let key = format!(
+            "{}{}/{}-{}.json",
+            self.request.prefix,
+            record.capture_id,
+            record.time.format("%Y%m%dT%H%M%S%.6fZ"),
+            record.request_id
+        );
+        let data = Bytes::from(
+            serde_json::to_vec_pretty(record).map_err(|e| Error::InternalError(e.to_string()))?,
+        );
+        let size = data.len() as i64;
+        let bucket = &self.request.bucket;
+        let etag = state.storage.put(bucket, &key, data).await?;
+        let object = Object::new(
+            bucket.clone(),
+            key.clone(),
+            size,
+            etag,
+            "application/json".into(),
+        );
+        consensus::put_object(state, &object).await?;
+        state.search.object_changed(bucket, &key);
+        Ok(())
+    }
+}
+
+/// Starts and stops request captures and decides which requests they record
+#[derive(Default)]
+pub struct RequestCapture {
+    current: RwLock<Option<Arc<Capture>>>,
+}
+
+impl RequestCapture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The running capture, or the last one
+    pub fn status(&self) -> Option<CaptureStatus> {
+        let capture = self.current.read().clone()?;
+        self.expire(&capture);
+        let status = capture.status.read().clone();
+        Some(status)
+    }
+
+    /// Check a capture and start recording
+    pub async fn start(&self, state: &AppState, request: CaptureRequest) -> Result<CaptureStatus> {
+        let operations = validate(&request)?;
+        if state.metadata.get_bucket(&request.bucket).await?.is_none() {
+            return Err(Error::NoSuchBucketNamed(request.bucket.clone()));
+        }
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let started_at = Utc::now();
+        let capture = Arc::new(Capture {
+            status: RwLock::new(CaptureStatus {
+                prefix: format!("{}{}/", request.prefix, id),
+                id,
+                state: CaptureState::Running,
+                bucket: request.bucket.clone(),
+                filter: request.filter.clone(),
+                include_bodies: request.include_bodies,
+                max_body_bytes: request.max_body_bytes,
+                max_requests: request.max_requests,
+                started_at,
+                expires_at: started_at + Duration::seconds(request.duration_secs as i64),
+                stopped_at: None,
+                stop_reason: None,
+                captured: 0,
+                failed: 0,
+            }),
+            request,
+            operations,
+            claimed: AtomicU64::new(0),
+        });
+
+        let mut current = self.current.write();
+        if let Some(running) = current.as_ref() {
+            self.expire(running);
+            if running.is_running() {
+                return Err(Error::OperationAborted(format!(
+                    "Request capture {} is already running",
+                    running.id()
+                )));
+            }
+        }
+        let status = capture.status.read().clone();
+        info!(
+            "Request capture {} started into {}/{}",
+            status.id, status.bucket, status.prefix
+        );
+        *current = Some(capture);
+        Ok(status)
+    }
+
+    /// Stop the running capture; `None` if none is running
+    pub fn stop(&self) -> Option<CaptureStatus> {
+        let capture = self.current.read().clone()?;
+        if !capture.is_running() {
+            return None;
+        }
+        capture.stop("stopped");
+        let status = capture.status.read().clone();
+        Some(status)
+    }
+
+    fn is_running(&self) -> bool {
+        self.current
+            .read()
+            .as_ref()
+            .is_some_and(|capture| capture.is_running())
+    }
+
+    /// The running capture if it wants this request
+    fn matching(
+        &self,
+        op: Option<S3Operation>,
+        bucket: Option<&str>,
+        principal: &str,
+    ) -> Option<Arc<Capture>> {
+        let capture = self.current.read().clone()?;
+        self.expire(&capture);
+        (capture.is_running() && capture.wants(op, bucket, principal)).then_some(capture)
+    }
+
+    fn expire(&self, capture: &Capture) {
+        let expired = {
+            let status = capture.status.read();
+            status.state == CaptureState::Running && Utc::now() >= status.expires_at
+        };
+        if expired {
+            capture.stop("expired");
+        }
+    }
+}
+
+/// Check the limits of a capture and resolve its operation names
+fn validate(request: &CaptureRequest) -> Result<Vec<S3Operation>> {
+    if request.max_requests == 0 || request.max_requests > MAX_REQUESTS {
+        return Err(Error::InvalidArgument(format!(
+            "max_requests must be 1-{}",
+            MAX_REQUESTS
+        )));
+    }
+    if request.max_body_bytes > MAX_BODY_BYTES {
+        return Err(Error::InvalidArgument(format!(
+            "max_body_bytes must be at most {}",
+            MAX_BODY_BYTES
+        )));
+    }
+    if request.duration_secs == 0 || request.duration_secs > MAX_DURATION_SECS {
+        return Err(Error::InvalidArgument(format!(
+            "duration_secs must be 1-{}",
+            MAX_DURATION_SECS
+        )));
+    }
+    request
+        .filter
+        .operations
+        .iter()
+        .map(|name| {
+            S3Operation::from_name(name)
+                .ok_or_else(|| Error::InvalidArgument(format!("Unknown operation: {}", name)))
+        })
+        .collect()
+}
+
+/// Record requests the running capture wants
+pub async fn capture_requests(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if !state.capture.is_running() {
+        return next.run(request).await;
+    }
+
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let query = request.uri().query().map(|q| q.to_string());
+    let op = S3Operation::from_request(&method, &path, query.as_deref());
+    let (bucket, _) = bucket_and_key(&path);
+    let principal = request_principal(&request).unwrap_or_else(|| ANONYMOUS_PRINCIPAL.to_string());
+
+    let Some(capture) = state.capture.matching(op, bucket.as_deref(), &principal) else {
+        return next.run(request).await;
+    };
+    let max_body = capture.request.max_body_bytes;
+    let include_bodies = capture.request.include_bodies;
+
+    let start = Instant::now();
+    let time = Utc::now();
+    let headers = recorded_headers(request.headers());
+    let (parts, body) = request.into_parts();
+    let mut body_bytes = None;
+    let body = if include_bodies && fits(&parts.headers, max_body) {
+        match axum::body::to_bytes(body, max_body).await {
+            Ok(bytes) => {
+                body_bytes = Some(bytes.clone());
+                Body::from(bytes)
+            }
+            Err(e) => {
+                return error_response(Error::InvalidRequest(format!(
+                    "Failed to read request body: {}",
+                    e
+                )));
+            }
+        }
+    } else {
+        body
+    };
+
+    let response = next.run(Request::from_parts(parts, body)).await;
+    let status = response.status();
+    if capture.request.filter.errors_only && status.as_u16() < 400 {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let mut response_bytes = None;
+    let body = if include_bodies && fits(&parts.headers, max_body) {
+        match axum::body::to_bytes(body, max_body).await {
+            Ok(bytes) => {
+                response_bytes = Some(bytes.clone());
+                Body::from(bytes)
+            }
+            Err(e) => {
+                return error_response(Error::InternalError(format!(
+                    "Failed to read response body: {}",
+                    e
+                )));
+            }
+        }
+    } else {
+        body
+    };
+    let response = Response::from_parts(parts, body);
+
+    if !capture.claim() {
+        return response;
+    }
+    let record = CapturedRequest {
+        capture_id: capture.id(),
+        request_id: current_request_id(),
+        time,
+        operation: op.map(|op| op.as_str().to_string()),
+        principal,
+        method,
+        path,
+        query: query.and_then(|q| recorded_query(&q)),
+        headers,
+        body: body_bytes.map(|b| BASE64.encode(b)),
+        status: status.as_u16(),
+        response_headers: recorded_headers(response.headers()),
+        response_body: response_bytes.map(|b| BASE64.encode(b)),
+        duration_ms: start.elapsed().as_millis() as u64,
+    };
+    tokio::spawn(async move {
+        match capture.write(&state, &record).await {
+            Ok(()) => capture.status.write().captured += 1,
+            Err(e) => {
+                warn!(
+                    "Request capture {} failed to store {}: {}",
+                    record.capture_id, record.request_id, e
+                );
+                capture.status.write().failed += 1;
+            }
+        }
+    });
+    response
+}
+
+/// Whether a body declares a length within `max`
+fn fits(headers: &HeaderMap, max: usize) -> bool {
+    headers
+        .get("content-length")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok())
+        .is_some_and(|len| len <= max)
+}
+
+/// Headers worth recording, without credentials
+fn recorded_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter(|(name, _)| !REDACTED_HEADERS.contains(&name.as_str()))
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+/// Query string without the signature of a pre-signed URL
+fn recorded_query(query: &str) -> Option<String> {
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|param| {
+            let name = param.split('=').next().unwrap_or_default();
+            !param.is_empty() && !SIGNATURE_PARAMS.contains(&name.to_ascii_lowercase().as_str())
+        })
+        .collect();
+    (!kept.is_empty()).then(|| kept.join("&"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn capture(filter: CaptureFilter, max_requests: u64) -> Capture {
+        let request = CaptureRequest {
+            bucket: "captures".into(),
+            prefix: default_prefix(),
+            filter,
+            include_bodies: false,
+            max_body_bytes: default_max_body_bytes(),
+            max_requests,
+            duration_secs: default_duration_secs(),
+        };
+        let operations = validate(&request).unwrap();
+        let now = Utc::now();
+        Capture {
+            status: RwLock::new(CaptureStatus {
+                id: "c1".into(),
+                state: CaptureState::Running,
+                bucket: request.bucket.clone(),
+                prefix: "captures/c1/".into(),
+                filter: request.filter.clone(),
+                include_bodies: false,
+                max_body_bytes: request.max_body_bytes,
+                max_requests,
+                started_at: now,
+                expires_at: now + Duration::hours(1),
+                stopped_at: None,
+                stop_reason: None,
+                captured: 0,
+                failed: 0,
+            }),
+            request,
+            operations,
+            claimed: AtomicU64::new(0),
+        }
+    }
+
+    #[test]
+    fn test_filter() {
+        let capture = capture(
+            CaptureFilter {
+                operations: vec!["PutObject".into()],
+                buckets: vec!["photos".into()],
+                principal: Some("AKIA1".into()),
+                errors_only: false,
+            },
+            10,
+        );
+        assert!(capture.wants(Some(S3Operation::PutObject), Some("photos"), "AKIA1"));
+        assert!(!capture.wants(Some(S3Operation::GetObject), Some("photos"), "AKIA1"));
+        assert!(!capture.wants(Some(S3Operation::PutObject), Some("logs"), "AKIA1"));
+        assert!(!capture.wants(Some(S3Operation::PutObject), Some("photos"), "AKIA2"));
+        assert!(!capture.wants(None, Some("photos"), "AKIA1"));
+    }
+
+    #[test]
+    fn test_capture_bucket_is_never_captured() {
+        let capture = capture(CaptureFilter::default(), 10);
+        assert!(capture.wants(Some(S3Operation::GetObject), Some("photos"), "AKIA1"));
+        assert!(!capture.wants(Some(S3Operation::PutObject), Some("captures"), "AKIA1"));
+    }
+
+    #[test]
+    fn test_claim_stops_at_limit() {
+        let capture = capture(CaptureFilter::default(), 2);
+        assert!(capture.claim());
+        assert!(capture.is_running());
+        assert!(capture.claim());
+        assert!(!capture.is_running());
+        assert_eq!(capture.status.read().stop_reason.as_deref(), Some("limit"));
+        assert!(!capture.claim());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut request = capture(CaptureFilter::default(), 1).request;
+        request.filter.operations = vec!["PutObjekt".into()];
+        assert!(validate(&request).is_err());
+        request.filter.operations.clear();
+        request.max_requests = 0;
+        assert!(validate(&request).is_err());
+        request.max_requests = 1;
+        request.max_body_bytes = MAX_BODY_BYTES + 1;
+        assert!(validate(&request).is_err());
+    }
+
+    #[test]
+    fn test_credentials_are_not_recorded() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "authorization",
+            "AWS4-HMAC-SHA256 Credential=...".parse().unwrap(),
+        );
+        headers.insert("x-amz-security-token", "token".parse().unwrap());
+        headers.insert("content-type", "text/plain".parse().unwrap());
+        assert_eq!(
+            recorded_headers(&headers),
+            vec![("content-type".to_string(), "text/plain".to_string())]
+        );
+
+        assert_eq!(
+            recorded_query("X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Signature=abc&versionId=v1"),
+            Some("versionId=v1".to_string())
+        );
+        assert_eq!(recorded_query("X-Amz-Signature=abc"), None);
+        assert_eq!(recorded_query("uploads"), Some("uploads".to_string()));
+    }
+}
@@ -0,0 +1,100 @@
+//! Authentication by TLS client certificate
+//!
+//! A connection whose client certificate maps to a user (see
+//! [`crate::tls::ClientCertificate`]) may send unsigned S3 requests: they
+//! are made by that user, as if signed with its access key. The user must
+//! exist and be enabled, otherwise the request is refused with
+//! `AccessDenied`. A request that is signed anyway is taken by its
+//! signature, so a trusted service can still act as another user.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::Request,
+    middleware::Next,
+    response::Response,
+};
+use hafiz_core::Error;
+use tracing::{debug, warn};
+
+use super::anonymous::is_signed;
+use super::request_id::early_error_response;
+use super::signature::SignedBy;
+use crate::server::AppState;
+use crate::tls::ClientCertificate;
+
+/// Access key of the user an unsigned request authenticated as by its
+/// client certificate, or by Basic auth at the WebDAV gateway
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertificateUser(pub String);
+
+/// Access key a request is made by: the one its verified signature was
+/// made with, or the user of its client certificate
+pub fn request_principal(request: &Request<Body>) -> Option<String> {
+    let extensions = request.extensions();
+    extensions
+        .get::<SignedBy>()
+        .map(|signed| signed.0.clone())
+        .or_else(|| extensions.get::<CertificateUser>().map(|user| user.0.clone()))
+}
+
+/// Whether a request carries credentials: a verified signature or a mapped
+/// client certificate
+pub fn is_authenticated(request: &Request<Body>) -> bool {
+    request.extensions().get::<SignedBy>().is_some()
+        || request.extensions().get::<CertificateUser>().is_some()
+}
+
+/// Resolve the user of the client certificate of unsigned requests
+pub async fn client_cert_auth(
+    State(state): State<AppState>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let access_key = match request.extensions().get::<ClientCertificate>() {
+        Some(ClientCertificate {
+            access_key: Some(access_key),
+            ..
+        }) if !is_signed(request.headers(), request.uri().query()) => access_key.clone(),
+        _ => return next.run(request).await,
+    };
+
+    match state.metadata.get_credentials(&access_key).await {
+        Ok(Some(credentials)) if credentials.enabled => {
+            debug!("Request authenticated by client certificate as {}", access_key);
+            request.extensions_mut().insert(CertificateUser(access_key));
+            next.run(request).await
+        }
+        Ok(_) => {
+            warn!(
+                "Client certificate maps to {}, which does not exist or is disabled",
+                access_key
+            );
+            early_error_response(&mut request, Error::AccessDenied)
+        }
+        Err(e) => early_error_response(&mut request, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_takes_precedence_over_certificate() {
+        let mut request = Request::builder().uri("/photos/a.jpg").body(Body::empty()).unwrap();
+        assert_eq!(request_principal(&request), None);
+        assert!(!is_authenticated(&request));
+
+        request
+            .extensions_mut()
+            .insert(CertificateUser("AKIACERT".to_string()));
+        assert_eq!(request_principal(&request).as_deref(), Some("AKIACERT"));
+        assert!(is_authenticated(&request));
+
+        request
+            .extensions_mut()
+            .insert(SignedBy("AKIASIGNED".to_string()));
+        assert_eq!(request_principal(&request).as_deref(), Some("AKIASIGNED"));
+    }
+}
@@ -0,0 +1,124 @@
+//! `x-amz-expected-bucket-owner` enforcement
+//!
+//! A client may name the account it expects to own the bucket it addresses,
+//! so that a request meant for its own bucket is never served by a bucket of
+//! the same name owned by someone else. `x-amz-source-expected-bucket-owner`
+//! does the same for the source bucket of a copy. A request whose bucket is
+//! owned by another account is refused with `AccessDenied`; one naming a
+//! bucket that does not exist goes on to fail with `NoSuchBucket`.
+//!
+//! A bucket matches its owner ID. Buckets of the root user also match
+//! `auth.account_id`, when set.
+//!
+//! This guards the client, not the bucket: it says nothing about who may
+//! act on a bucket, which [`account_scope`](super::account_scope) decides
+//! from the verified caller. The headers are only safe from tampering when
+//! the client signs them, in which case
+//! [`verify_signature`](super::verify_signature) refuses altered ones.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, Request},
+    middleware::Next,
+    response::Response,
+};
+use hafiz_core::Error;
+use tracing::debug;
+
+use super::request_id::error_response;
+use crate::server::AppState;
+
+/// Owner ID of buckets created by the root user
+const ROOT_OWNER: &str = "root";
+
+/// Refuse requests whose bucket is not owned by the expected account
+pub async fn expected_bucket_owner(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let checks = [
+        (
+            header(request.headers(), "x-amz-expected-bucket-owner"),
+            path_bucket(request.uri().path()),
+        ),
+        (
+            header(request.headers(), "x-amz-source-expected-bucket-owner"),
+            copy_source_bucket(request.headers()),
+        ),
+    ];
+
+    for (expected, bucket) in checks {
+        let (Some(expected), Some(bucket)) = (expected, bucket) else {
+            continue;
+        };
+        let owner = match state.metadata.get_bucket(&bucket).await {
+            Ok(Some(info)) => info.owner_id,
+            Ok(None) => continue,
+            Err(e) => return error_response(e),
+        };
+        if !owner_matches(&owner, state.config.auth.account_id.as_deref(), &expected) {
+            debug!(
+                "Bucket {} is owned by {}, not the expected {}",
+                bucket, owner, expected
+            );
+            return error_response(Error::AccessDenied);
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Whether a bucket owned by `owner` belongs to the `expected` account
+fn owner_matches(owner: &str, account_id: Option<&str>, expected: &str) -> bool {
+    owner == expected || (owner == ROOT_OWNER && account_id == Some(expected))
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+}
+
+/// Bucket addressed by a path-style URL
+fn path_bucket(path: &str) -> Option<String> {
+    let bucket = path.trim_start_matches('/').split('/').next()?;
+    (!bucket.is_empty()).then(|| bucket.to_string())
+}
+
+/// Bucket named by `x-amz-copy-source`
+fn copy_source_bucket(headers: &HeaderMap) -> Option<String> {
+    let source = headers.get("x-amz-copy-source")?.to_str().ok()?;
+    let source = urlencoding::decode(source).ok()?;
+    let (bucket, _) = source.trim_start_matches('/').split_once('/')?;
+    Some(bucket.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_owner_matches() {
+        assert!(owner_matches("root", None, "root"));
+        assert!(!owner_matches("root", None, "111122223333"));
+        assert!(owner_matches("root", Some("111122223333"), "111122223333"));
+        assert!(!owner_matches("alice", Some("111122223333"), "111122223333"));
+        assert!(owner_matches("alice", Some("111122223333"), "alice"));
+    }
+
+    #[test]
+    fn test_buckets() {
+        assert_eq!(path_bucket("/photos/a.jpg").as_deref(), Some("photos"));
+        assert_eq!(path_bucket("/photos").as_deref(), Some("photos"));
+        assert_eq!(path_bucket("/"), None);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-amz-copy-source", "/src%2Dbucket/a%20b?versionId=1".parse().unwrap());
+        assert_eq!(copy_source_bucket(&headers).as_deref(), Some("src-bucket"));
+        headers.insert("x-amz-copy-source", "src/key".parse().unwrap());
+        assert_eq!(copy_source_bucket(&headers).as_deref(), Some("src"));
+    }
+}
@@ -0,0 +1,107 @@
+//! Last-used tracking of access keys
+//!
+//! Records when each access key last signed a request, so an operator
+//! rotating a key can see whether clients still use the old one. Writes are
+//! throttled per key; the timestamps are accurate to [`FLUSH_INTERVAL`].
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use axum::http::HeaderMap;
+use chrono::Utc;
+use hafiz_auth::{extract_access_key_from_presigned, is_presigned_request, SignatureV4};
+use hafiz_metadata::MetadataStore;
+use parking_lot::Mutex;
+use tracing::debug;
+
+/// Minimum time between two last-used writes for one key
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Throttled last-used recorder
+#[derive(Default)]
+pub struct KeyUsageTracker {
+    last_flush: Mutex<HashMap<String, Instant>>,
+}
+
+impl KeyUsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Note a use of `access_key`, persisting it if the last write is stale
+    pub fn record(&self, metadata: &Arc<MetadataStore>, access_key: &str) {
+        if !self.should_flush(access_key, Instant::now()) {
+            return;
+        }
+
+        let metadata = metadata.clone();
+        let access_key = access_key.to_string();
+        tokio::spawn(async move {
+            if let Err(e) = metadata.touch_access_key(&access_key, Utc::now()).await {
+                debug!("Failed to record use of {}: {}", access_key, e);
+            }
+        });
+    }
+
+    fn should_flush(&self, access_key: &str, now: Instant) -> bool {
+        let mut last_flush = self.last_flush.lock();
+        match last_flush.get(access_key) {
+            Some(at) if now.duration_since(*at) < FLUSH_INTERVAL => false,
+            _ => {
+                last_flush.insert(access_key.to_string(), now);
+                true
+            }
+        }
+    }
+}
+
+/// Access key a request names, from the header or a presigned URL
+///
+/// The signature is not checked; the verified caller is
+/// [`request_principal`](super::request_principal).
+pub fn request_access_key(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    if let Some(header) = headers.get("authorization").and_then(|h| h.to_str().ok()) {
+        return SignatureV4::parse(header).ok().map(|sig| sig.access_key);
+    }
+    query
+        .filter(|q| is_presigned_request(q))
+        .and_then(|q| extract_access_key_from_presigned(q).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writes_are_throttled_per_key() {
+        let tracker = KeyUsageTracker::new();
+        let start = Instant::now();
+
+        assert!(tracker.should_flush("AK1", start));
+        assert!(!tracker.should_flush("AK1", start + Duration::from_secs(5)));
+        assert!(tracker.should_flush("AK2", start + Duration::from_secs(5)));
+        assert!(tracker.should_flush("AK1", start + FLUSH_INTERVAL));
+    }
+
+    #[test]
+    fn test_access_key_from_signed_requests() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "authorization",
+            "AWS4-HMAC-SHA256 Credential=AKIAEXAMPLE/20240101/us-east-1/s3/aws4_request, \
+             SignedHeaders=host, Signature=abc"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(request_access_key(&headers, None).as_deref(), Some("AKIAEXAMPLE"));
+
+        let query = "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential=AKIAOTHER%2F20240101%2Fus-east-1%2Fs3%2Faws4_request\
+                     &X-Amz-Date=20240101T000000Z&X-Amz-Expires=60&X-Amz-SignedHeaders=host&X-Amz-Signature=abc";
+        assert_eq!(
+            request_access_key(&HeaderMap::new(), Some(query)).as_deref(),
+            Some("AKIAOTHER")
+        );
+        assert_eq!(request_access_key(&HeaderMap::new(), Some("list-type=2")), None);
+    }
+}
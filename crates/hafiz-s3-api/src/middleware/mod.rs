@@ -1,5 +1,33 @@
 //! Middleware for S3 API
 
+pub mod access_point;
+pub mod admission;
+pub mod account;
+pub mod anonymous;
+pub mod bandwidth;
 pub mod auth;
+pub mod capture;
+pub mod client_cert;
+pub mod clock_skew;
+pub mod expected_owner;
+pub mod key_usage;
+pub mod presigned;
+pub mod redirect;
+pub mod request_id;
+pub mod signature;
 
-pub use auth::admin_auth;
+pub use access_point::{access_point_routing, AccessPointRequest};
+pub use admission::{admission_control, AdmissionController};
+pub use account::{account_of, account_scope};
+pub use anonymous::{anonymous_access, ANONYMOUS_PRINCIPAL};
+pub use bandwidth::{bandwidth_throttle, BandwidthLimiter};
+pub use auth::{admin_auth, admin_network, admin_role, AdminPrincipal};
+pub use capture::{capture_requests, RequestCapture};
+pub use client_cert::{client_cert_auth, request_principal, CertificateUser};
+pub use clock_skew::clock_skew;
+pub use expected_owner::expected_bucket_owner;
+pub use key_usage::KeyUsageTracker;
+pub use presigned::presigned_url_constraints;
+pub use redirect::cluster_redirect;
+pub use request_id::{current_principal, current_request_id, request_context, request_error, RequestId};
+pub use signature::{verify_signature, SignedBy};
@@ -0,0 +1,109 @@
+//! Pre-signed URL constraints
+//!
+//! A pre-signed URL may limit the body a PUT sends, pin the content type
+//! it declares, and be single-use. Length and content type are checked
+//! against the request headers; `Content-Length` is required when a limit
+//! is set, and the body cannot outgrow it. A single-use URL's nonce is
+//! recorded before the request runs, so a second request with the URL is
+//! refused even when the first one failed. Nonces are kept until their URL
+//! expires.
+//!
+//! The constraints are query parameters, covered by the URL's signature:
+//! with `auth.enabled`, a URL only gets here once
+//! [`verify_signature`](super::verify_signature) has checked it against its
+//! user's secret key, so a URL whose constraints were loosened or dropped
+//! is refused with `SignatureDoesNotMatch`. Without `auth.enabled` nothing
+//! is signed and the constraints only hold against honest clients.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{HeaderMap, Method, Request},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{Duration, Utc};
+use hafiz_auth::{is_presigned_request, presigned_constraints};
+use hafiz_core::{types::PresignedLimits, Error};
+use tracing::debug;
+
+use super::request_id::error_response;
+use super::signature::SignedBy;
+use crate::server::AppState;
+
+/// Enforce the constraints a pre-signed URL carries
+pub async fn presigned_url_constraints(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let query = request.uri().query().unwrap_or("");
+    if !is_presigned_request(query) {
+        return next.run(request).await;
+    }
+    if state.config.auth.enabled && request.extensions().get::<SignedBy>().is_none() {
+        // Constraints of a URL nobody vouched for mean nothing
+        return error_response(Error::AccessDenied);
+    }
+    let constraints = match presigned_constraints(query) {
+        Ok(constraints) => constraints,
+        Err(e) => return error_response(e),
+    };
+    if constraints.is_empty() {
+        return next.run(request).await;
+    }
+
+    if request.method() == Method::PUT {
+        let headers = request.headers();
+        if let Err(e) = constraints.check_upload(
+            content_length(headers),
+            headers.get("content-type").and_then(|v| v.to_str().ok()),
+        ) {
+            debug!("Pre-signed upload to {} refused: {}", request.uri().path(), e);
+            metrics::counter!("hafiz_presigned_rejections_total", "reason" => e.code()).increment(1);
+            return error_response(e);
+        }
+    }
+
+    if let Some(nonce) = &constraints.nonce {
+        let expires_at = constraints
+            .expires_at
+            .unwrap_or_else(|| Utc::now() + Duration::seconds(PresignedLimits::MAX_EXPIRES as i64));
+        match state.metadata.consume_presigned_nonce(nonce, expires_at).await {
+            Ok(true) => {}
+            Ok(false) => {
+                debug!("Single-use URL for {} was already used", request.uri().path());
+                metrics::counter!("hafiz_presigned_rejections_total", "reason" => "reused")
+                    .increment(1);
+                return error_response(Error::AccessDenied);
+            }
+            Err(e) => return error_response(e),
+        }
+    }
+
+    next.run(request).await
+}
+
+/// Length of the object data, which aws-chunked bodies declare separately
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers
+        .get("x-amz-decoded-content-length")
+        .or_else(|| headers.get("content-length"))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_length() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(content_length(&headers), None);
+        headers.insert("content-length", "2048".parse().unwrap());
+        assert_eq!(content_length(&headers), Some(2048));
+        headers.insert("x-amz-decoded-content-length", "1024".parse().unwrap());
+        assert_eq!(content_length(&headers), Some(1024));
+    }
+}
@@ -0,0 +1,168 @@
+//! Redirects to the nodes holding object data
+//!
+//! With data placement, an object is stored on the nodes the hash ring
+//! assigns it to, and a read on any other node is proxied to one of them.
+//! `cluster.redirect_mode` lets clients fetch the data from such a node
+//! directly instead: the read is answered with `307 TemporaryRedirect`, a
+//! `Location` on the first healthy owner's advertised endpoint, and the
+//! S3 error body naming that `Endpoint`, which the AWS SDKs follow.
+//!
+//! With `accelerated`, only buckets with transfer acceleration enabled are
+//! redirected; with `all`, every bucket. Only GET and HEAD of object data
+//! are redirected: writes must be recorded by the node that receives them,
+//! and a presigned URL is signed for the host it names, so both are still
+//! served here. When no owner is healthy, the request is served as well.
+
+use axum::{
+    body::Body,
+    extract::State,
+    http::{Method, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+#[cfg(feature = "cluster")]
+use hafiz_core::types::{AccelerateStatus, RedirectMode};
+use hafiz_core::Error;
+
+use super::request_id::{bucket_and_key, current_request_id, request_error};
+use crate::server::AppState;
+
+/// Query parameters of a plain object read; any other asks for a
+/// subresource, which is answered from local metadata
+const READ_PARAMS: [&str; 3] = ["versionId", "partNumber", "x-id"];
+
+/// Redirect object reads to a node holding the object, if enabled
+pub async fn cluster_redirect(
+    State(state): State<AppState>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if !is_redirectable(request.method(), request.uri().query()) {
+        return next.run(request).await;
+    }
+    let (Some(bucket), Some(key)) = bucket_and_key(request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    match redirect_endpoint(&state, &bucket, &key).await {
+        Some(endpoint) => {
+            let path = request
+                .uri()
+                .path_and_query()
+                .map_or("/", |p| p.as_str());
+            redirect_response(&endpoint, path, bucket)
+        }
+        None => next.run(request).await,
+    }
+}
+
+/// Whether a request reads object data and is not presigned
+fn is_redirectable(method: &Method, query: Option<&str>) -> bool {
+    if method != Method::GET && method != Method::HEAD {
+        return false;
+    }
+    let query = query.unwrap_or_default();
+    if hafiz_auth::presigned::is_presigned_request(query) || query.contains("Signature=") {
+        return false;
+    }
+    query
+        .split('&')
+        .filter(|p| !p.is_empty())
+        .map(|p| p.split_once('=').map_or(p, |(name, _)| name))
+        .all(|name| READ_PARAMS.contains(&name) || name.starts_with("response-"))
+}
+
+/// Endpoint of a healthy node owning `bucket/key`, unless this node owns
+/// it or the bucket is not redirected
+#[cfg(feature = "cluster")]
+async fn redirect_endpoint(state: &AppState, bucket: &str, key: &str) -> Option<String> {
+    let cluster = state.cluster.as_ref()?;
+    let placement = cluster.placement();
+    if !placement.is_enabled() || placement.is_local_owner(bucket, key) {
+        return None;
+    }
+    match cluster.config().redirect_mode {
+        RedirectMode::Off => return None,
+        RedirectMode::Accelerated => {
+            let status = state.metadata.get_bucket_accelerate(bucket).await.ok()??;
+            if status != AccelerateStatus::Enabled {
+                return None;
+            }
+        }
+        RedirectMode::All => {}
+    }
+
+    placement
+        .owners(bucket, key)
+        .iter()
+        .filter_map(|id| cluster.get_node(id))
+        .find(|node| node.is_healthy())
+        .map(|node| node.endpoint)
+}
+
+#[cfg(not(feature = "cluster"))]
+async fn redirect_endpoint(_state: &AppState, _bucket: &str, _key: &str) -> Option<String> {
+    None
+}
+
+fn redirect_response(endpoint: &str, path: &str, bucket: String) -> Response {
+    let endpoint = endpoint.trim_end_matches('/');
+    let host = endpoint
+        .split_once("://")
+        .map_or(endpoint, |(_, host)| host)
+        .to_string();
+    let location = format!("{}{}", endpoint, path);
+    let error = request_error(Error::TemporaryRedirect {
+        endpoint: host,
+        bucket,
+    });
+    Response::builder()
+        .status(StatusCode::TEMPORARY_REDIRECT)
+        .header("Location", location)
+        .header("Content-Type", "application/xml")
+        .header("x-amz-request-id", current_request_id())
+        .body(Body::from(error.to_xml()))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redirectable_requests() {
+        assert!(is_redirectable(&Method::GET, None));
+        assert!(is_redirectable(&Method::HEAD, Some("versionId=3")));
+        assert!(is_redirectable(
+            &Method::GET,
+            Some("partNumber=2&response-content-type=text%2Fplain")
+        ));
+
+        // Writes, subresources and presigned URLs are served here
+        assert!(!is_redirectable(&Method::PUT, None));
+        assert!(!is_redirectable(&Method::GET, Some("tagging")));
+        assert!(!is_redirectable(&Method::GET, Some("uploadId=abc")));
+        assert!(!is_redirectable(
+            &Method::GET,
+            Some("X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Signature=abc")
+        ));
+        assert!(!is_redirectable(
+            &Method::GET,
+            Some("AWSAccessKeyId=AKIA&Expires=1&Signature=abc")
+        ));
+    }
+
+    #[test]
+    fn test_redirect_response() {
+        let response = redirect_response(
+            "https://node2.hafiz.local:9000/",
+            "/photos/a%20b.jpg?versionId=3",
+            "photos".into(),
+        );
+        assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(
+            response.headers()["location"],
+            "https://node2.hafiz.local:9000/photos/a%20b.jpg?versionId=3"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Request IDs and per-request tracing spans
+//!
+//! Every S3 request gets one ID, made here. It is stored in the request
+//! extensions as [`RequestId`] and is also current for the task that serves
+//! the request, so handlers take it from [`current_request_id`] rather than
+//! minting their own. The same ID goes in the `x-amz-request-id` header, the
+//! error body and every log line of the request. Error bodies built with
+//! [`request_error`] also name the resource the request addressed and this
+//! host, whose ID is sent with every response as `x-amz-id-2`.
+//!
+//! Middleware layered outside [`request_context`] refuses requests with
+//! [`early_error_response`], which assigns the ID up front, so those errors
+//! carry one too.
+//!
+//! The request runs inside an `s3_request` span that carries the request ID,
+//! method, bucket, key and principal, so logs can be matched with access
+//! logs and client-side reports.
+
+use axum::{
+    body::Body,
+    http::{HeaderValue, Request, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use hafiz_core::error::S3Error;
+use hafiz_core::utils::{generate_request_id, host_id};
+use hafiz_core::Error;
+use tracing::{field, info_span, Instrument};
+
+use super::anonymous::ANONYMOUS_PRINCIPAL;
+use super::client_cert::request_principal;
+
+tokio::task_local! {
+    static CURRENT: RequestScope;
+}
+
+/// What the task serving a request knows about it
+#[derive(Debug, Clone)]
+struct RequestScope {
+    id: RequestId,
+    /// Decoded path of the request, e.g. `/bucket/key`
+    resource: String,
+    /// Access key that signed the request, or the anonymous principal
+    principal: String,
+}
+
+/// ID of the request being served
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+impl RequestId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// ID of the request the current task serves
+///
+/// Outside a request, e.g. in background jobs, a fresh ID is returned.
+pub fn current_request_id() -> String {
+    CURRENT
+        .try_with(|scope| scope.id.0.clone())
+        .unwrap_or_else(|_| generate_request_id())
+}
+
+/// Principal of the request the current task serves, if any
+pub fn current_principal() -> Option<String> {
+    CURRENT.try_with(|scope| scope.principal.clone()).ok()
+}
+
+/// S3 error body for `err`, with the ID and resource of the current request
+pub fn request_error(err: Error) -> S3Error {
+    let error = S3Error::from(err)
+        .with_request_id(current_request_id())
+        .with_host_id(host_id());
+    match CURRENT.try_with(|scope| scope.resource.clone()) {
+        Ok(resource) => error.with_resource(resource),
+        Err(_) => error,
+    }
+}
+
+/// S3 error response for `err`, for middleware that refuses a request
+pub(super) fn error_response(err: Error) -> Response {
+    build_error_response(err, None)
+}
+
+/// [`error_response`] asking the client to retry after `seconds`
+pub(super) fn error_response_with_retry(err: Error, seconds: u64) -> Response {
+    build_error_response(err, Some(seconds))
+}
+
+fn build_error_response(err: Error, retry_after: Option<u64>) -> Response {
+    let request_id = current_request_id();
+    let status =
+        StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut builder = Response::builder()
+        .status(status)
+        .header("Content-Type", "application/xml")
+        .header("x-amz-request-id", &request_id);
+    if let Some(seconds) = retry_after {
+        builder = builder.header("Retry-After", seconds.to_string());
+    }
+    builder.body(Body::from(request_error(err).to_xml())).unwrap()
+}
+
+/// Error response for a request refused before [`request_context`] runs,
+/// with the ID the request would have been served under
+pub(super) fn early_error_response(request: &mut Request<Body>, err: Error) -> Response {
+    let scope = request_scope(request);
+    let mut response = CURRENT.sync_scope(scope, || error_response(err));
+    if let Ok(value) = HeaderValue::from_str(host_id()) {
+        response.headers_mut().insert("x-amz-id-2", value);
+    }
+    response
+}
+
+/// ID, resource and principal of a request; the ID is assigned here unless
+/// the request already has one
+fn request_scope(request: &mut Request<Body>) -> RequestScope {
+    let id = match request.extensions().get::<RequestId>() {
+        Some(id) => id.clone(),
+        None => {
+            let id = RequestId(generate_request_id());
+            request.extensions_mut().insert(id.clone());
+            id
+        }
+    };
+    let path = request.uri().path();
+    let resource = urlencoding::decode(path)
+        .map(|p| p.into_owned())
+        .unwrap_or_else(|_| path.to_string());
+    let principal = request_principal(request).unwrap_or_else(|| ANONYMOUS_PRINCIPAL.to_string());
+    RequestScope {
+        id,
+        resource,
+        principal,
+    }
+}
+
+/// Assign the request ID and run the request in its span
+pub async fn request_context(mut request: Request<Body>, next: Next) -> Response {
+    let scope = request_scope(&mut request);
+    let request_id = scope.id.clone();
+    let (bucket, key) = bucket_and_key(request.uri().path());
+
+    let span = info_span!(
+        "s3_request",
+        request_id = %request_id.as_str(),
+        method = %request.method(),
+        bucket = field::Empty,
+        key = field::Empty,
+        principal = %scope.principal,
+        status = field::Empty,
+    );
+    if let Some(bucket) = &bucket {
+        span.record("bucket", bucket.as_str());
+    }
+    if let Some(key) = &key {
+        span.record("key", key.as_str());
+    }
+
+    let mut response = CURRENT
+        .scope(scope, next.run(request).instrument(span.clone()))
+        .await;
+    span.record("status", response.status().as_u16());
+
+    // Handlers that build their own responses may not set it
+    if let Ok(value) = HeaderValue::from_str(request_id.as_str()) {
+        response.headers_mut().entry("x-amz-request-id").or_insert(value);
+    }
+    if let Ok(value) = HeaderValue::from_str(host_id()) {
+        response.headers_mut().insert("x-amz-id-2", value);
+    }
+    response
+}
+
+/// Bucket and decoded object key addressed by a path-style URL
+pub(super) fn bucket_and_key(path: &str) -> (Option<String>, Option<String>) {
+    let path = path.trim_start_matches('/');
+    let (bucket, key) = match path.split_once('/') {
+        Some((bucket, key)) if !key.is_empty() => (bucket, Some(key)),
+        Some((bucket, _)) => (bucket, None),
+        None => (path, None),
+    };
+    let bucket = (!bucket.is_empty()).then(|| bucket.to_string());
+    let key = key.map(|k| urlencoding::decode(k).map(|k| k.into_owned()).unwrap_or_else(|_| k.to_string()));
+    (bucket, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_and_key() {
+        assert_eq!(bucket_and_key("/"), (None, None));
+        assert_eq!(bucket_and_key("/photos"), (Some("photos".into()), None));
+        assert_eq!(bucket_and_key("/photos/"), (Some("photos".into()), None));
+        assert_eq!(
+            bucket_and_key("/photos/2024/a%20b.jpg"),
+            (Some("photos".into()), Some("2024/a b.jpg".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_current_request_id_is_scoped() {
+        let scope = RequestScope {
+            id: RequestId("ABC123".to_string()),
+            resource: "/photos/a.jpg".to_string(),
+            principal: "AKIAEXAMPLE".to_string(),
+        };
+        let (inside, principal, error) = CURRENT
+            .scope(scope, async {
+                (current_request_id(), current_principal(), request_error(Error::NoSuchKey))
+            })
+            .await;
+        assert_eq!(inside, "ABC123");
+        assert_eq!(principal.as_deref(), Some("AKIAEXAMPLE"));
+        assert_eq!(error.request_id, "ABC123");
+        assert_eq!(error.resource.as_deref(), Some("/photos/a.jpg"));
+        assert_eq!(error.host_id, host_id());
+
+        // Outside a request every call makes a new one
+        assert_ne!(current_request_id(), current_request_id());
+        assert!(current_principal().is_none());
+        assert!(request_error(Error::NoSuchKey).resource.is_none());
+    }
+
+    #[test]
+    fn test_early_error_response_keeps_the_request_id() {
+        let mut request = Request::builder()
+            .uri("/photos/a.jpg")
+            .body(Body::empty())
+            .unwrap();
+        let response = early_error_response(&mut request, Error::SignatureDoesNotMatch);
+        let id = request.extensions().get::<RequestId>().unwrap().clone();
+        assert_eq!(response.headers()["x-amz-request-id"], id.as_str());
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // The span of the request later takes the same ID
+        assert_eq!(request_scope(&mut request).id, id);
+    }
+}
@@ -0,0 +1,206 @@
+//! Signature verification of S3 requests
+//!
+//! With `auth.enabled`, a request signed with AWS Signature V4, in its
+//! `Authorization` header or as a pre-signed URL, is checked against the
+//! secret key of the access key it names before anything acts on it. An
+//! unknown access key, or STS credentials sent without their session token,
+//! get `InvalidAccessKeyId`; a wrong signature gets `SignatureDoesNotMatch`;
+//! a disabled user gets `AccessDenied`. Other `Authorization` schemes are
+//! refused.
+//!
+//! A verified request carries a [`SignedBy`] extension naming its access
+//! key, and only then is it made by that key: this is where
+//! [`request_principal`](super::request_principal) takes the caller from.
+//! Without `auth.enabled`, signatures are not checked and a request is made
+//! by the access key it names.
+//!
+//! The signature covers the payload hash the client declares in
+//! `x-amz-content-sha256`; the body itself is not hashed against it.
+
+use std::collections::BTreeMap;
+
+use axum::{
+    body::Body,
+    extract::{OriginalUri, State},
+    http::{header::HOST, HeaderMap, Request, Uri},
+    middleware::Next,
+    response::Response,
+};
+use hafiz_auth::{
+    extract_access_key_from_presigned, extract_region_from_presigned,
+    extract_security_token_from_presigned, is_presigned_request, verify_presigned_url,
+    verify_signature_v4, SignatureV4,
+};
+use hafiz_core::Error;
+use tracing::debug;
+
+use super::key_usage::request_access_key;
+use super::request_id::early_error_response;
+use crate::server::AppState;
+
+/// Access key a request is made by, once its signature is verified
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedBy(pub String);
+
+/// Verify the signature of signed S3 requests and name their caller
+pub async fn verify_signature(
+    State(state): State<AppState>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Response {
+    let header_signed = request.headers().contains_key("authorization");
+    let presigned = request.uri().query().is_some_and(is_presigned_request);
+    if !header_signed && !presigned {
+        return next.run(request).await;
+    }
+
+    let access_key = if state.config.auth.enabled {
+        // Access point addressing rewrites the path after the client signed it
+        let uri = request
+            .extensions()
+            .get::<OriginalUri>()
+            .map_or(request.uri(), |OriginalUri(uri)| uri)
+            .clone();
+        let headers = signed_headers(request.headers(), &uri, request.uri());
+        match verify(&state, request.method().as_str(), &uri, &headers).await {
+            Ok(access_key) => Some(access_key),
+            Err(e) => {
+                debug!(
+                    "Refusing {} {}: {}",
+                    request.method(),
+                    request.uri().path(),
+                    e
+                );
+                metrics::counter!("hafiz_signature_rejections_total", "error" => e.code())
+                    .increment(1);
+                return early_error_response(&mut request, e);
+            }
+        }
+    } else {
+        request_access_key(request.headers(), request.uri().query())
+    };
+    if let Some(access_key) = access_key {
+        request.extensions_mut().insert(SignedBy(access_key));
+    }
+    next.run(request).await
+}
+
+/// Check the request's signature; the access key it was made with
+async fn verify(
+    state: &AppState,
+    method: &str,
+    uri: &Uri,
+    headers: &BTreeMap<String, String>,
+) -> Result<String, Error> {
+    let query = uri.query().unwrap_or("");
+
+    if let Some(header) = headers.get("authorization") {
+        let sig = SignatureV4::parse(header)?;
+        let session_token = headers.get("x-amz-security-token").map(String::as_str);
+        let secret_key = secret_key(state, &sig.access_key, session_token).await?;
+
+        let payload_hash = headers
+            .get("x-amz-content-sha256")
+            .ok_or_else(|| Error::MissingHeader("x-amz-content-sha256".into()))?;
+        let path = urlencoding::decode(uri.path())
+            .map_err(|_| Error::InvalidRequest("Invalid request path".into()))?;
+        if !verify_signature_v4(
+            method,
+            &path,
+            query,
+            headers,
+            payload_hash,
+            &secret_key,
+            &sig,
+        )? {
+            return Err(Error::SignatureDoesNotMatch);
+        }
+        Ok(sig.access_key)
+    } else {
+        let access_key = extract_access_key_from_presigned(query)?;
+        let session_token = extract_security_token_from_presigned(query);
+        let secret_key = secret_key(state, &access_key, session_token.as_deref()).await?;
+
+        let region = extract_region_from_presigned(query)?;
+        if !verify_presigned_url(method, uri.path(), query, headers, &secret_key, &region)? {
+            return Err(Error::SignatureDoesNotMatch);
+        }
+        Ok(access_key)
+    }
+}
+
+/// Secret key of `access_key`: a user's key, a key rotated out and still in
+/// its grace period, or STS credentials presented with their session token
+async fn secret_key(
+    state: &AppState,
+    access_key: &str,
+    session_token: Option<&str>,
+) -> Result<String, Error> {
+    let metadata = &state.metadata;
+    if let Some(cred) = metadata.get_credentials(access_key).await? {
+        if !cred.enabled {
+            return Err(Error::AccessDenied);
+        }
+        return Ok(cred.secret_key);
+    }
+    if let Some(retiring) = metadata.get_retiring_key(access_key).await? {
+        // The old key carries the permissions of its user
+        return match metadata.get_credentials(&retiring.user_access_key).await? {
+            Some(owner) if owner.enabled => Ok(retiring.secret_key),
+            Some(_) => Err(Error::AccessDenied),
+            None => Err(Error::InvalidAccessKeyId),
+        };
+    }
+    match metadata.get_temporary_credentials(access_key).await? {
+        Some(temp) if session_token == Some(temp.session_token.as_str()) => Ok(temp.secret_key),
+        _ => Err(Error::InvalidAccessKeyId),
+    }
+}
+
+/// Headers by lowercase name, as signed; HTTP/2 requests carry the host in
+/// the URI instead of a header
+fn signed_headers(
+    header_map: &HeaderMap,
+    uri: &Uri,
+    request_uri: &Uri,
+) -> BTreeMap<String, String> {
+    let mut headers = BTreeMap::new();
+    for (name, value) in header_map {
+        let Ok(value) = value.to_str() else {
+            continue;
+        };
+        headers
+            .entry(name.as_str().to_string())
+            .and_modify(|v: &mut String| {
+                v.push(',');
+                v.push_str(value);
+            })
+            .or_insert_with(|| value.to_string());
+    }
+    if !headers.contains_key(HOST.as_str()) {
+        if let Some(authority) = uri.authority().or(request_uri.authority()) {
+            headers.insert(HOST.as_str().to_string(), authority.to_string());
+        }
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_headers() {
+        let request = Request::builder()
+            .uri("https://s3.example.com/photos/a.jpg")
+            .header("X-Amz-Date", "20240101T000000Z")
+            .header("x-amz-meta-tag", "a")
+            .header("x-amz-meta-tag", "b")
+            .body(Body::empty())
+            .unwrap();
+        let headers = signed_headers(request.headers(), request.uri(), request.uri());
+        assert_eq!(headers["x-amz-date"], "20240101T000000Z");
+        assert_eq!(headers["x-amz-meta-tag"], "a,b");
+        assert_eq!(headers["host"], "s3.example.com");
+    }
+}
@@ -0,0 +1,324 @@
+//! Scheduled mirroring to an external S3 endpoint
+//!
+//! Each `[[mirror.jobs]]` entry copies the current version of every object
+//! of its buckets that was written since the job's last run to a bucket of
+//! the same name at the target endpoint, on a cron schedule. Progress is
+//! kept per bucket as a checkpoint in the metadata database: the time up to
+//! which all changes are known to be mirrored. An object that still fails
+//! after its retries holds the checkpoint back, so the next run tries it
+//! again.
+//!
+//! Mirroring is one-way and additive: deletes are not propagated, and only
+//! current versions are copied. `hafiz_mirror_lag_seconds` reports how far
+//! each bucket's checkpoint trails behind.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use cron::Schedule;
+use hafiz_core::config::{MirrorConfig, MirrorJobConfig};
+use hafiz_core::types::{ObjectInfo, NULL_VERSION_ID};
+use hafiz_core::{Error, Result};
+use hafiz_storage::S3Client;
+use parking_lot::RwLock;
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use crate::routes::storage_key;
+use crate::server::AppState;
+use crate::sse;
+
+/// Objects listed per metadata page
+const LIST_PAGE_SIZE: i32 = 1000;
+
+/// Errors kept in a report; failures keep being counted past this
+const MAX_ERRORS: usize = 100;
+
+/// Writes still in flight when a run starts may commit with an earlier
+/// modification time than the run's start; the checkpoint stays this far
+/// behind so the next run picks them up
+const IN_FLIGHT_MARGIN: chrono::Duration = chrono::Duration::seconds(60);
+
+/// Wait before the first retry of an object; doubled for every further one
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Outcome of one run of a mirror job
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub buckets_scanned: u64,
+    pub objects_mirrored: u64,
+    pub bytes_mirrored: u64,
+    /// Objects that failed every attempt; retried on the next run
+    pub objects_failed: u64,
+    pub errors: Vec<String>,
+}
+
+impl MirrorReport {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            finished_at: None,
+            buckets_scanned: 0,
+            objects_mirrored: 0,
+            bytes_mirrored: 0,
+            objects_failed: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, message: String) {
+        if self.errors.len() < MAX_ERRORS {
+            self.errors.push(message);
+        }
+    }
+}
+
+/// Mirror job state as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct MirrorJobStatus {
+    pub name: String,
+    pub schedule: String,
+    pub endpoint: String,
+    pub buckets: Vec<String>,
+    pub running: bool,
+    pub next_run: Option<DateTime<Utc>>,
+    pub last_run: Option<MirrorReport>,
+}
+
+/// One configured job with its target client
+struct MirrorJob {
+    config: MirrorJobConfig,
+    schedule: Schedule,
+    target: S3Client,
+    running: AtomicBool,
+    last_run: RwLock<Option<MirrorReport>>,
+}
+
+/// The configured mirror jobs
+pub struct Mirror {
+    jobs: Vec<Arc<MirrorJob>>,
+}
+
+impl Mirror {
+    pub fn new(config: MirrorConfig) -> Result<Self> {
+        config.validate()?;
+        let jobs = config
+            .jobs
+            .into_iter()
+            .map(|config| {
+                let schedule: Schedule = config.schedule.parse().map_err(|e| {
+                    Error::InvalidArgument(format!(
+                        "Mirror job {}: invalid schedule {:?}: {}",
+                        config.name, config.schedule, e
+                    ))
+                })?;
+                Ok(Arc::new(MirrorJob {
+                    target: S3Client::new(&config.target())?,
+                    schedule,
+                    config,
+                    running: AtomicBool::new(false),
+                    last_run: RwLock::new(None),
+                }))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { jobs })
+    }
+
+    pub fn status(&self) -> Vec<MirrorJobStatus> {
+        self.jobs.iter().map(|job| job.status()).collect()
+    }
+
+    /// Status of the job called `name`
+    pub fn job_status(&self, name: &str) -> Option<MirrorJobStatus> {
+        self.job(name).map(|job| job.status())
+    }
+
+    fn job(&self, name: &str) -> Option<&Arc<MirrorJob>> {
+        self.jobs.iter().find(|job| job.config.name == name)
+    }
+
+    /// Run every job on its schedule
+    pub fn start(&self, state: AppState) {
+        for job in &self.jobs {
+            info!(
+                "Mirror job {} scheduled ({} to {})",
+                job.config.name, job.config.schedule, job.config.endpoint
+            );
+            let job = Arc::clone(job);
+            let state = state.clone();
+            tokio::spawn(async move {
+                while let Some(next) = job.schedule.upcoming(Utc).next() {
+                    let wait = (next - Utc::now()).to_std().unwrap_or_default();
+                    tokio::time::sleep(wait).await;
+                    if job.run(&state).await.is_none() {
+                        debug!("Skipping scheduled run of mirror job {}; a run is in progress", job.config.name);
+                    }
+                }
+            });
+        }
+    }
+
+    /// Run the job called `name` now
+    ///
+    /// Returns `None` if there is no such job or it is already running.
+    pub async fn run(&self, state: &AppState, name: &str) -> Option<MirrorReport> {
+        self.job(name)?.run(state).await
+    }
+}
+
+impl MirrorJob {
+    fn status(&self) -> MirrorJobStatus {
+        MirrorJobStatus {
+            name: self.config.name.clone(),
+            schedule: self.config.schedule.clone(),
+            endpoint: self.config.endpoint.clone(),
+            buckets: self.config.buckets.clone(),
+            running: self.running.load(Ordering::SeqCst),
+            next_run: self.schedule.upcoming(Utc).next(),
+            last_run: self.last_run.read().clone(),
+        }
+    }
+
+    async fn run(&self, state: &AppState) -> Option<MirrorReport> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+
+        let mut report = MirrorReport::new();
+        for bucket in &self.config.buckets {
+            if let Err(e) = self.mirror_bucket(state, bucket, &mut report).await {
+                warn!("Mirror job {} failed on bucket {}: {}", self.config.name, bucket, e);
+                report.error(format!("{}: {}", bucket, e));
+            }
+        }
+        report.finished_at = Some(Utc::now());
+
+        info!(
+            "Mirror job {} finished: {} objects ({} bytes) mirrored, {} failed",
+            self.config.name, report.objects_mirrored, report.bytes_mirrored, report.objects_failed
+        );
+
+        *self.last_run.write() = Some(report.clone());
+        self.running.store(false, Ordering::SeqCst);
+        Some(report)
+    }
+
+    async fn mirror_bucket(&self, state: &AppState, bucket: &str, report: &mut MirrorReport) -> Result<()> {
+        let job = self.config.name.as_str();
+        if state.metadata.get_bucket(bucket).await?.is_none() {
+            return Err(Error::NoSuchBucketNamed(bucket.to_string()));
+        }
+        if !self.target.bucket_exists(bucket).await? {
+            return Err(Error::InvalidRequest(format!(
+                "Target bucket {} does not exist at {}",
+                bucket, self.config.endpoint
+            )));
+        }
+
+        let since = state.metadata.get_mirror_checkpoint(job, bucket).await?;
+        let mut until = report.started_at - IN_FLIGHT_MARGIN;
+        let mut token: Option<String> = None;
+        loop {
+            let (objects, _, truncated, next) = state
+                .metadata
+                .list_objects(bucket, None, None, LIST_PAGE_SIZE, token.as_deref())
+                .await?;
+
+            for object in objects.iter().filter(|o| since.map_or(true, |since| o.last_modified > since)) {
+                if let Err(e) = self.mirror_object(state, bucket, object, report).await {
+                    warn!("Mirror job {} could not copy {}/{}: {}", job, bucket, object.key, e);
+                    report.objects_failed += 1;
+                    report.error(format!("{}/{}: {}", bucket, object.key, e));
+                    metrics::counter!("hafiz_mirror_objects_total", "job" => job.to_string(), "status" => "failed")
+                        .increment(1);
+                    // Keep the object after the checkpoint
+                    until = until.min(object.last_modified - chrono::Duration::microseconds(1));
+                }
+            }
+
+            if !truncated || next.is_none() {
+                break;
+            }
+            token = next;
+        }
+
+        if let Some(since) = since {
+            until = until.max(since);
+        }
+        state.metadata.put_mirror_checkpoint(job, bucket, until).await?;
+        report.buckets_scanned += 1;
+
+        let labels = [("job", job.to_string()), ("bucket", bucket.to_string())];
+        metrics::gauge!("hafiz_mirror_checkpoint_timestamp_seconds", &labels).set(until.timestamp() as f64);
+        metrics::gauge!("hafiz_mirror_lag_seconds", &labels)
+            .set((Utc::now() - until).num_milliseconds() as f64 / 1000.0);
+        Ok(())
+    }
+
+    /// Copy the current version of one object, retrying failures
+    async fn mirror_object(
+        &self,
+        state: &AppState,
+        bucket: &str,
+        listed: &ObjectInfo,
+        report: &mut MirrorReport,
+    ) -> Result<()> {
+        let version_id = listed.version_id.as_deref().unwrap_or(NULL_VERSION_ID);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let result = async {
+                let Some(object) = state
+                    .metadata
+                    .get_object_version(bucket, &listed.key, Some(version_id))
+                    .await?
+                else {
+                    // Deleted since it was listed
+                    return Ok(None);
+                };
+                // The target gets the plaintext; SSE-C objects cannot be read without their key
+                let storage_key = storage_key(&object.key, &object.version_id);
+                let data = sse::read_object(state, &object, &storage_key, None, None).await?;
+                let size = data.len();
+                self.target
+                    .put_object_with_metadata(bucket, &object.key, data, &object.content_type, &object.metadata)
+                    .await?;
+                Ok::<_, Error>(Some(size))
+            }
+            .await;
+
+            match result {
+                Ok(Some(size)) => {
+                    let job = self.config.name.clone();
+                    report.objects_mirrored += 1;
+                    report.bytes_mirrored += size as u64;
+                    metrics::counter!("hafiz_mirror_objects_total", "job" => job.clone(), "status" => "mirrored")
+                        .increment(1);
+                    metrics::counter!("hafiz_mirror_bytes_total", "job" => job).increment(size as u64);
+                    debug!("Mirrored {}/{} ({} bytes)", bucket, listed.key, size);
+                    self.throttle(size).await;
+                    return Ok(());
+                }
+                Ok(None) => return Ok(()),
+                Err(e) if attempt >= self.config.max_attempts => return Err(e),
+                Err(e) => {
+                    debug!("Retrying {}/{} after attempt {}: {}", bucket, listed.key, attempt, e);
+                    tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt - 1)).await;
+                }
+            }
+        }
+    }
+
+    /// Sleep long enough to stay under the configured upload rate
+    async fn throttle(&self, bytes: usize) {
+        if self.config.max_bytes_per_sec == 0 || bytes == 0 {
+            return;
+        }
+        let secs = bytes as f64 / self.config.max_bytes_per_sec as f64;
+        tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+    }
+}
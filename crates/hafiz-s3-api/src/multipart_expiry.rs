@@ -0,0 +1,202 @@
+//! Expiry of incomplete multipart uploads
+//!
+//! An upload that is never completed or aborted keeps its parts in storage
+//! indefinitely. The worker aborts uploads once they are older than the
+//! `AbortIncompleteMultipartUpload` days of a bucket lifecycle rule matching
+//! their key, or, for keys no rule covers, than
+//! `lifecycle.abort_incomplete_multipart_days`. Reclaimed space is exported
+//! as `hafiz_multipart_expired_bytes_total`.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::Utc;
+use hafiz_core::config::LifecycleWorkerConfig;
+use hafiz_core::types::LifecycleConfiguration;
+use hafiz_core::Result;
+use hafiz_metadata::repository::MultipartUpload;
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use crate::server::AppState;
+
+/// Part data an aborted upload released
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Reclaimed {
+    pub parts: i64,
+    pub bytes: i64,
+}
+
+/// Outcome of one expiry pass
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ExpiryReport {
+    pub uploads_aborted: u64,
+    pub parts_deleted: i64,
+    pub bytes_reclaimed: i64,
+    pub errors: Vec<String>,
+}
+
+/// Days after which an upload of `key` is aborted, if ever
+pub fn expiry_days(
+    lifecycle: Option<&LifecycleConfiguration>,
+    key: &str,
+    default_days: u32,
+) -> Option<u32> {
+    lifecycle
+        .into_iter()
+        .flat_map(|config| &config.rules)
+        .filter(|rule| rule.applies_to(key, &[]))
+        .filter_map(|rule| rule.abort_incomplete_multipart_upload.as_ref())
+        .map(|abort| abort.days_after_initiation)
+        .min()
+        .or((default_days > 0).then_some(default_days))
+}
+
+/// Delete the parts of an upload from storage, then its record. With
+/// `dry_run` only the parts that would be released are counted.
+pub async fn abort_upload(
+    state: &AppState,
+    upload: &MultipartUpload,
+    dry_run: bool,
+) -> Result<Reclaimed> {
+    let parts = state.metadata.list_upload_parts(&upload.upload_id).await?;
+    let reclaimed = Reclaimed {
+        parts: parts.len() as i64,
+        bytes: parts.iter().map(|p| p.size).sum(),
+    };
+    if dry_run {
+        return Ok(reclaimed);
+    }
+
+    let part_numbers: Vec<i32> = parts.iter().map(|p| p.part_number).collect();
+    if let Err(e) = state
+        .storage
+        .abort_parts(&upload.bucket, &upload.key, &upload.upload_id, &part_numbers)
+        .await
+    {
+        // The record still goes, or the upload would be retried forever
+        warn!(
+            "Failed to delete parts of {}/{} ({}): {}",
+            upload.bucket, upload.key, upload.upload_id, e
+        );
+    }
+    state.metadata.delete_multipart_upload(&upload.upload_id).await?;
+    Ok(reclaimed)
+}
+
+/// Abort every upload past its expiry
+pub async fn expire_uploads(state: &AppState) -> ExpiryReport {
+    let default_days = state.config.lifecycle.abort_incomplete_multipart_days;
+    let mut report = ExpiryReport::default();
+
+    // Lifecycle days are whole days, so nothing younger than one can expire
+    let cutoff = Utc::now() - chrono::Duration::days(1);
+    let uploads = match state.metadata.list_stale_multipart_uploads(cutoff).await {
+        Ok(uploads) => uploads,
+        Err(e) => {
+            report.errors.push(e.to_string());
+            return report;
+        }
+    };
+
+    let mut lifecycles: HashMap<String, Option<LifecycleConfiguration>> = HashMap::new();
+    for upload in uploads {
+        if !lifecycles.contains_key(&upload.bucket) {
+            let lifecycle = match state.metadata.get_bucket_lifecycle(&upload.bucket).await {
+                Ok(lifecycle) => lifecycle,
+                Err(e) => {
+                    report.errors.push(format!("{}: {}", upload.bucket, e));
+                    continue;
+                }
+            };
+            lifecycles.insert(upload.bucket.clone(), lifecycle);
+        }
+        let lifecycle = lifecycles[&upload.bucket].as_ref();
+
+        let Some(days) = expiry_days(lifecycle, &upload.key, default_days) else {
+            continue;
+        };
+        if upload.created_at + chrono::Duration::days(days as i64) > Utc::now() {
+            continue;
+        }
+
+        match abort_upload(state, &upload, false).await {
+            Ok(reclaimed) => {
+                debug!(
+                    "Expired multipart upload {} of {}/{} after {} days",
+                    upload.upload_id, upload.bucket, upload.key, days
+                );
+                report.uploads_aborted += 1;
+                report.parts_deleted += reclaimed.parts;
+                report.bytes_reclaimed += reclaimed.bytes;
+                metrics::counter!("hafiz_multipart_uploads_expired_total").increment(1);
+                metrics::counter!("hafiz_multipart_expired_bytes_total")
+                    .increment(reclaimed.bytes.max(0) as u64);
+            }
+            Err(e) => report.errors.push(format!("{}: {}", upload.upload_id, e)),
+        }
+    }
+
+    if report.uploads_aborted > 0 || !report.errors.is_empty() {
+        info!(
+            "Multipart expiry: {} uploads aborted, {} bytes reclaimed, {} errors",
+            report.uploads_aborted,
+            report.bytes_reclaimed,
+            report.errors.len()
+        );
+    }
+    report
+}
+
+/// Run [`expire_uploads`] on the lifecycle scan interval
+pub fn start(config: &LifecycleWorkerConfig, state: AppState) {
+    if !config.enabled {
+        return;
+    }
+
+    let interval = Duration::from_secs(config.scan_interval_secs.max(60));
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            expire_uploads(&state).await;
+        }
+    });
+
+    info!("Multipart upload expiry started (every {:?})", interval);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hafiz_core::types::{LifecycleRule, RuleStatus};
+
+    #[test]
+    fn test_expiry_days() {
+        assert_eq!(expiry_days(None, "a", 7), Some(7));
+        assert_eq!(expiry_days(None, "a", 0), None);
+
+        let mut lifecycle = LifecycleConfiguration::new();
+        lifecycle
+            .add_rule(
+                LifecycleRule::new("logs")
+                    .with_prefix_filter("logs/")
+                    .with_abort_incomplete_multipart(1),
+            )
+            .unwrap();
+        lifecycle
+            .add_rule(
+                LifecycleRule::new("off")
+                    .with_status(RuleStatus::Disabled)
+                    .with_abort_incomplete_multipart(0),
+            )
+            .unwrap();
+
+        // A matching rule wins over the default, even a longer one
+        assert_eq!(expiry_days(Some(&lifecycle), "logs/x", 7), Some(1));
+        assert_eq!(expiry_days(Some(&lifecycle), "logs/x", 0), Some(1));
+        // Other keys fall back to the default; disabled rules never apply
+        assert_eq!(expiry_days(Some(&lifecycle), "data/x", 7), Some(7));
+        assert_eq!(expiry_days(Some(&lifecycle), "data/x", 0), None);
+    }
+}
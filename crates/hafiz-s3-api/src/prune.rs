@@ -0,0 +1,443 @@
+//! Pruning of old object versions and orphaned delete markers
+//!
+//! A prune job walks a bucket, or a prefix of it, key by key and removes
+//! noncurrent versions by age and count, independently of any lifecycle
+//! configuration. A version is noncurrent from the moment a newer version
+//! or delete marker replaced it; `older_than_days` counts from then, and
+//! `keep_last` keeps that many of the newest noncurrent versions of each
+//! key. With both, a version is only removed when both allow it, as with
+//! the `NoncurrentDays` and `NewerNoncurrentVersions` of lifecycle rules.
+//!
+//! With `delete_markers`, a delete marker that is the current version of a
+//! key with no other versions left is removed too; it hides nothing and
+//! only slows down listings. Versions under object lock are kept, and so
+//! is the delete marker above them.
+//!
+//! A dry run goes through the same steps, object lock checks included,
+//! without removing anything. Jobs are tracked in memory like batch jobs
+//! and report their progress while they run, so a scan of a very large
+//! bucket can be followed and cancelled.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Duration, Utc};
+use hafiz_core::{Error, Result};
+use hafiz_metadata::repository::VersionRef;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::consensus;
+use crate::routes::storage_key;
+use crate::routes::enforce_object_lock;
+use crate::server::AppState;
+
+/// Finished jobs kept for the admin API
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// Keys whose versions are read per metadata page
+const KEYS_PER_PAGE: i32 = 500;
+
+/// What a job removes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PruneRequest {
+    pub bucket: String,
+    /// Only keys starting with this
+    #[serde(default)]
+    pub prefix: String,
+    /// Remove noncurrent versions that have been noncurrent this long
+    pub older_than_days: Option<u32>,
+    /// Keep this many of the newest noncurrent versions of each key
+    pub keep_last: Option<u32>,
+    /// Remove delete markers that no longer hide any version
+    #[serde(default)]
+    pub delete_markers: bool,
+    /// Only count what would be removed
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl PruneRequest {
+    /// Whether noncurrent versions are pruned at all
+    fn prunes_versions(&self) -> bool {
+        self.older_than_days.is_some() || self.keep_last.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneJobState {
+    Running,
+    Completed,
+    /// The scan stopped, e.g. the bucket was deleted
+    Failed,
+    Cancelled,
+}
+
+impl PruneJobState {
+    pub fn is_finished(self) -> bool {
+        !matches!(self, PruneJobState::Running)
+    }
+}
+
+/// Job progress as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct PruneJobStatus {
+    pub id: String,
+    pub request: PruneRequest,
+    pub state: PruneJobState,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Versions stored in the bucket when the job started, to judge how
+    /// far the scan has got
+    pub bucket_versions: i64,
+    pub keys_scanned: u64,
+    pub versions_scanned: u64,
+    /// Noncurrent versions and delete markers removed, or that a dry run
+    /// would remove
+    pub versions_pruned: u64,
+    /// Orphaned delete markers removed
+    pub delete_markers_removed: u64,
+    pub bytes_freed: i64,
+    /// Versions kept because object lock protects them
+    pub locked_versions: u64,
+    /// Versions that could not be removed
+    pub failed: u64,
+    /// Last key scanned
+    pub current_key: Option<String>,
+    /// Why the job failed
+    pub error: Option<String>,
+}
+
+/// Noncurrent versions of one key that the policy removes
+///
+/// `versions` is the key's history, newest first; the first entry is the
+/// current version. Returns indices into `versions`.
+pub fn select_noncurrent(
+    versions: &[VersionRef],
+    older_than_days: Option<u32>,
+    keep_last: Option<u32>,
+    now: DateTime<Utc>,
+) -> Vec<usize> {
+    if older_than_days.is_none() && keep_last.is_none() {
+        return Vec::new();
+    }
+    (1..versions.len())
+        .filter(|&i| {
+            // Noncurrent since the next newer version was written
+            let since = versions[i - 1].last_modified;
+            let old_enough = older_than_days
+                .is_none_or(|days| now - since >= Duration::days(days as i64));
+            let beyond_kept = keep_last.is_none_or(|n| i - 1 >= n as usize);
+            old_enough && beyond_kept
+        })
+        .collect()
+}
+
+/// One submitted job
+struct PruneJob {
+    status: RwLock<PruneJobStatus>,
+    cancelled: AtomicBool,
+}
+
+/// Runs prune jobs and keeps track of them
+#[derive(Default)]
+pub struct PruneJobs {
+    /// In submission order
+    jobs: RwLock<Vec<Arc<PruneJob>>>,
+}
+
+impl PruneJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All known jobs, newest first
+    pub fn list(&self) -> Vec<PruneJobStatus> {
+        self.jobs
+            .read()
+            .iter()
+            .rev()
+            .map(|job| job.status.read().clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<PruneJobStatus> {
+        self.find(id).map(|job| job.status.read().clone())
+    }
+
+    /// Stop a job after the key in progress; `None` if there is no such job
+    pub fn cancel(&self, id: &str) -> Option<PruneJobStatus> {
+        let job = self.find(id)?;
+        job.cancelled.store(true, Ordering::SeqCst);
+        let status = job.status.read().clone();
+        Some(status)
+    }
+
+    fn find(&self, id: &str) -> Option<Arc<PruneJob>> {
+        self.jobs.read().iter().find(|job| job.status.read().id == id).cloned()
+    }
+
+    /// Check a job and start it in the background
+    pub async fn submit(&self, state: &AppState, request: PruneRequest) -> Result<PruneJobStatus> {
+        if !request.prunes_versions() && !request.delete_markers {
+            return Err(Error::InvalidArgument(
+                "Set older_than_days, keep_last or delete_markers".into(),
+            ));
+        }
+        if state.metadata.get_bucket(&request.bucket).await?.is_none() {
+            return Err(Error::NoSuchBucketNamed(request.bucket.clone()));
+        }
+        let usage = state.metadata.get_bucket_usage(&request.bucket).await?;
+
+        let job = Arc::new(PruneJob {
+            status: RwLock::new(PruneJobStatus {
+                id: uuid::Uuid::new_v4().to_string(),
+                request,
+                state: PruneJobState::Running,
+                created_at: Utc::now(),
+                finished_at: None,
+                bucket_versions: usage.object_count,
+                keys_scanned: 0,
+                versions_scanned: 0,
+                versions_pruned: 0,
+                delete_markers_removed: 0,
+                bytes_freed: 0,
+                locked_versions: 0,
+                failed: 0,
+                current_key: None,
+                error: None,
+            }),
+            cancelled: AtomicBool::new(false),
+        });
+        let status = job.status.read().clone();
+        info!(
+            "Prune job {} submitted for bucket {}{}",
+            status.id,
+            status.request.bucket,
+            if status.request.dry_run { " (dry run)" } else { "" }
+        );
+
+        {
+            let mut jobs = self.jobs.write();
+            jobs.push(job.clone());
+            prune_finished(&mut jobs);
+        }
+
+        let state = state.clone();
+        tokio::spawn(async move { job.run(&state).await });
+        Ok(status)
+    }
+}
+
+/// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`]
+fn prune_finished(jobs: &mut Vec<Arc<PruneJob>>) {
+    let finished = jobs.iter().filter(|job| job.status.read().state.is_finished()).count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+    jobs.retain(|job| {
+        if excess > 0 && job.status.read().state.is_finished() {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+/// What happened to one version
+enum Outcome {
+    Removed,
+    Locked,
+    Failed,
+}
+
+impl PruneJob {
+    async fn run(&self, state: &AppState) {
+        let request = self.status.read().request.clone();
+        let result = self.scan(state, &request).await;
+
+        let mut status = self.status.write();
+        status.state = match result {
+            Ok(()) if self.cancelled.load(Ordering::SeqCst) => PruneJobState::Cancelled,
+            Ok(()) => PruneJobState::Completed,
+            Err(e) => {
+                warn!("Prune job {} failed: {}", status.id, e);
+                status.error = Some(e.to_string());
+                PruneJobState::Failed
+            }
+        };
+        status.finished_at = Some(Utc::now());
+        info!(
+            "Prune job {} {:?}: {} keys scanned, {} versions and {} delete markers {}, {} bytes, {} locked, {} failed",
+            status.id,
+            status.state,
+            status.keys_scanned,
+            status.versions_pruned,
+            status.delete_markers_removed,
+            if request.dry_run { "to remove" } else { "removed" },
+            status.bytes_freed,
+            status.locked_versions,
+            status.failed
+        );
+    }
+
+    /// Page through the keys of the bucket and prune each
+    async fn scan(&self, state: &AppState, request: &PruneRequest) -> Result<()> {
+        let bucket = &request.bucket;
+        let lock_enabled = state
+            .metadata
+            .get_bucket_object_lock_config(bucket)
+            .await?
+            .is_some();
+        let mut after: Option<String> = None;
+
+        loop {
+            let page = state
+                .metadata
+                .list_key_versions(bucket, &request.prefix, after.as_deref(), KEYS_PER_PAGE)
+                .await?;
+            let Some(last) = page.last() else {
+                return Ok(());
+            };
+            after = Some(last.key.clone());
+
+            for versions in page.chunk_by(|a, b| a.key == b.key) {
+                if self.cancelled.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                self.prune_key(state, request, lock_enabled, versions).await;
+                let mut status = self.status.write();
+                status.keys_scanned += 1;
+                status.versions_scanned += versions.len() as u64;
+                status.current_key = Some(versions[0].key.clone());
+            }
+        }
+    }
+
+    /// Prune the history of one key, newest first
+    async fn prune_key(
+        &self,
+        state: &AppState,
+        request: &PruneRequest,
+        lock_enabled: bool,
+        versions: &[VersionRef],
+    ) {
+        let selected = select_noncurrent(versions, request.older_than_days, request.keep_last, Utc::now());
+        let mut removed = 0;
+        for &i in &selected {
+            let version = &versions[i];
+            match self.remove(state, request, lock_enabled, version).await {
+                Outcome::Removed => {
+                    removed += 1;
+                    let mut status = self.status.write();
+                    status.versions_pruned += 1;
+                    if !version.is_delete_marker {
+                        status.bytes_freed += version.size;
+                    }
+                }
+                Outcome::Locked => self.status.write().locked_versions += 1,
+                Outcome::Failed => self.status.write().failed += 1,
+            }
+        }
+
+        // The current version is a delete marker with nothing left below it
+        let current = &versions[0];
+        if request.delete_markers && current.is_delete_marker && removed == versions.len() - 1 {
+            match self.remove(state, request, lock_enabled, current).await {
+                Outcome::Removed => self.status.write().delete_markers_removed += 1,
+                Outcome::Locked => self.status.write().locked_versions += 1,
+                Outcome::Failed => self.status.write().failed += 1,
+            }
+        }
+    }
+
+    async fn remove(
+        &self,
+        state: &AppState,
+        request: &PruneRequest,
+        lock_enabled: bool,
+        version: &VersionRef,
+    ) -> Outcome {
+        let bucket = &request.bucket;
+        if lock_enabled && !version.is_delete_marker {
+            match enforce_object_lock(state, bucket, &version.key, Some(&version.version_id), false)
+                .await
+            {
+                Ok(()) => {}
+                Err(Error::ObjectLocked(_)) => return Outcome::Locked,
+                Err(e) => {
+                    warn!("Prune of {}/{} failed: {}", bucket, version.key, e);
+                    return Outcome::Failed;
+                }
+            }
+        }
+        if request.dry_run {
+            return Outcome::Removed;
+        }
+
+        if !version.is_delete_marker {
+            match state
+                .storage
+                .delete(bucket, &storage_key(&version.key, &version.version_id))
+                .await
+            {
+                Ok(()) | Err(Error::NoSuchKey) => {}
+                Err(e) => {
+                    // Keep the record so the data is not orphaned
+                    warn!("Prune of {}/{} failed: {}", bucket, version.key, e);
+                    return Outcome::Failed;
+                }
+            }
+        }
+        if let Err(e) = consensus::delete_object_version(state, bucket, &version.key, &version.version_id).await {
+            warn!("Prune of {}/{} failed: {}", bucket, version.key, e);
+            return Outcome::Failed;
+        }
+        state.search.object_changed(bucket, &version.key);
+        metrics::counter!(
+            "hafiz_prune_removed_total",
+            "kind" => if version.is_delete_marker { "delete_marker" } else { "version" }
+        )
+        .increment(1);
+        Outcome::Removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(id: &str, days_ago: i64, is_delete_marker: bool, now: DateTime<Utc>) -> VersionRef {
+        VersionRef {
+            key: "a".into(),
+            version_id: id.into(),
+            size: 10,
+            is_delete_marker,
+            last_modified: now - Duration::days(days_ago),
+            is_latest: false,
+        }
+    }
+
+    #[test]
+    fn test_select_noncurrent() {
+        let now = Utc::now();
+        // v4 is current; v3 became noncurrent 1 day ago, v2 5 days, v1 20 days
+        let versions = vec![
+            version("v4", 1, false, now),
+            version("v3", 5, false, now),
+            version("v2", 20, true, now),
+            version("v1", 30, false, now),
+        ];
+
+        assert!(select_noncurrent(&versions, None, None, now).is_empty());
+        assert_eq!(select_noncurrent(&versions, Some(5), None, now), vec![2, 3]);
+        assert_eq!(select_noncurrent(&versions, None, Some(1), now), vec![2, 3]);
+        assert_eq!(select_noncurrent(&versions, None, Some(0), now), vec![1, 2, 3]);
+        // Both must allow it
+        assert_eq!(select_noncurrent(&versions, Some(10), Some(1), now), vec![3]);
+        assert_eq!(select_noncurrent(&versions, Some(1), Some(2), now), vec![3]);
+
+        // The current version is never selected
+        assert!(select_noncurrent(&versions[..1], Some(0), Some(0), now).is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+//! Restores of archived objects
+//!
+//! RestoreObject copies the data of an object in an archive storage class
+//! back to the backend of its bucket, where it stays readable for the
+//! requested number of days. The copy runs in the background: HEAD reports
+//! `ongoing-request="true"` in `x-amz-restore` until it is done and
+//! `s3:ObjectRestore:Completed` is sent. The worker resumes restores a
+//! restart interrupted and drops restored copies once they expire; the
+//! archived data is never touched.
+
+use std::time::Duration;
+
+use chrono::Utc;
+use hafiz_core::types::{ObjectInternal, S3EventType};
+use hafiz_core::{Result, DEFAULT_REGION};
+use hafiz_metadata::repository::ObjectRestore;
+use tracing::{info, warn};
+
+use crate::events::{self, S3Event};
+use crate::routes::storage_key;
+use crate::server::AppState;
+
+/// How often expired restored copies are dropped
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Principal reported for restores resumed after a restart
+const SYSTEM_PRINCIPAL: &str = "hafiz";
+
+/// Event about the restore of `object`
+pub fn restore_event(
+    event_type: S3EventType,
+    object: &ObjectInternal,
+    request_id: &str,
+    principal: &str,
+) -> S3Event {
+    S3Event {
+        event_type,
+        bucket: object.bucket.clone(),
+        key: object.key.clone(),
+        size: object.size,
+        etag: object.etag.clone(),
+        version_id: Some(object.version_id.clone()),
+        request_id: request_id.to_string(),
+        principal_id: principal.to_string(),
+        source_ip: String::new(),
+        region: DEFAULT_REGION.to_string(),
+    }
+}
+
+/// Copy the data of a requested restore back and mark the restore done
+///
+/// A restore of an object version that is gone or no longer archived is
+/// dropped.
+pub async fn run_restore(
+    state: &AppState,
+    restore: &ObjectRestore,
+    request_id: &str,
+    principal: &str,
+) -> Result<()> {
+    let object = state
+        .metadata
+        .get_object_version(&restore.bucket, &restore.key, Some(&restore.version_id))
+        .await?;
+    let Some(object) = object.filter(|o| o.is_archived()) else {
+        return state
+            .metadata
+            .delete_object_restore(&restore.bucket, &restore.key, &restore.version_id)
+            .await;
+    };
+
+    state
+        .storage_router
+        .restore(&object.bucket, &storage_key(&object.key, &object.version_id))
+        .await?;
+    let expires_at = Utc::now() + chrono::Duration::days(restore.days as i64);
+    state
+        .metadata
+        .complete_object_restore(
+            &restore.bucket,
+            &restore.key,
+            &restore.version_id,
+            restore.days,
+            expires_at,
+        )
+        .await?;
+
+    info!(
+        "Restored {}/{} ({}) until {}",
+        object.bucket,
+        object.key,
+        object.version_id,
+        expires_at.to_rfc3339()
+    );
+    metrics::counter!("hafiz_object_restores_total").increment(1);
+    events::notify(
+        state,
+        restore_event(S3EventType::ObjectRestoreCompleted, &object, request_id, principal),
+    )
+    .await;
+    Ok(())
+}
+
+/// Run [`run_restore`] in the background
+///
+/// A restore that fails is forgotten, so that it can be requested again.
+pub fn schedule(state: AppState, restore: ObjectRestore, request_id: String, principal: String) {
+    tokio::spawn(async move {
+        if let Err(e) = run_restore(&state, &restore, &request_id, &principal).await {
+            warn!(
+                "Restore of {}/{} ({}) failed: {}",
+                restore.bucket, restore.key, restore.version_id, e
+            );
+            metrics::counter!("hafiz_object_restore_failures_total").increment(1);
+            if let Err(e) = state
+                .metadata
+                .delete_object_restore(&restore.bucket, &restore.key, &restore.version_id)
+                .await
+            {
+                warn!("Failed to clear failed restore: {}", e);
+            }
+        }
+    });
+}
+
+/// Drop restored copies that expired; returns how many were dropped
+pub async fn expire_restores(state: &AppState) -> u64 {
+    let expired = match state.metadata.list_expired_restores(Utc::now()).await {
+        Ok(expired) => expired,
+        Err(e) => {
+            warn!("Failed to list expired restores: {}", e);
+            return 0;
+        }
+    };
+
+    let mut dropped = 0;
+    for restore in expired {
+        let object = match state
+            .metadata
+            .get_object_version(&restore.bucket, &restore.key, Some(&restore.version_id))
+            .await
+        {
+            Ok(object) => object,
+            Err(e) => {
+                warn!("Failed to read {}/{}: {}", restore.bucket, restore.key, e);
+                continue;
+            }
+        };
+        // Objects moved out of the archive since own the data in the hot tier
+        if let Some(object) = object.filter(|o| o.is_archived()) {
+            if let Err(e) = state
+                .storage_router
+                .evict(&object.bucket, &storage_key(&object.key, &object.version_id))
+                .await
+            {
+                warn!(
+                    "Failed to drop restored copy of {}/{}: {}",
+                    object.bucket, object.key, e
+                );
+                continue;
+            }
+        }
+        match state
+            .metadata
+            .delete_object_restore(&restore.bucket, &restore.key, &restore.version_id)
+            .await
+        {
+            Ok(()) => dropped += 1,
+            Err(e) => warn!("Failed to clear expired restore: {}", e),
+        }
+    }
+
+    if dropped > 0 {
+        info!("Dropped {} expired restored copies", dropped);
+    }
+    dropped
+}
+
+/// Resume interrupted restores and run [`expire_restores`] on its interval
+pub fn start(state: AppState) {
+    if !state.storage_router.has_archive() {
+        return;
+    }
+    tokio::spawn(async move {
+        match state.metadata.list_pending_restores().await {
+            Ok(pending) => {
+                if !pending.is_empty() {
+                    info!("Resuming {} interrupted restores", pending.len());
+                }
+                for restore in pending {
+                    schedule(
+                        state.clone(),
+                        restore,
+                        String::new(),
+                        SYSTEM_PRINCIPAL.to_string(),
+                    );
+                }
+            }
+            Err(e) => warn!("Failed to list pending restores: {}", e),
+        }
+
+        let mut ticker = tokio::time::interval(EXPIRY_INTERVAL);
+        loop {
+            ticker.tick().await;
+            expire_restores(&state).await;
+        }
+    });
+}
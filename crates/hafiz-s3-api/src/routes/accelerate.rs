@@ -0,0 +1,89 @@
+//! Bucket transfer acceleration
+//!
+//! Endpoints:
+//! - GET /{bucket}?accelerate - Get bucket accelerate configuration
+//! - PUT /{bucket}?accelerate - Enable or suspend acceleration
+//!
+//! On a cluster with placement and `redirect_mode = "accelerated"`, reads
+//! of objects in an accelerated bucket are redirected to a node holding
+//! them instead of being proxied (see [`crate::middleware::cluster_redirect`]).
+//! Otherwise the setting is only stored and reported, so that tools that
+//! configure it succeed.
+
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use hafiz_core::Error;
+use tracing::{debug, info};
+
+use super::{error_response, success_response};
+use crate::middleware::current_request_id;
+use crate::server::AppState;
+use crate::xml;
+
+/// GET /{bucket}?accelerate - Get bucket accelerate configuration
+pub async fn get_bucket_accelerate(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> impl IntoResponse {
+    let request_id = current_request_id();
+    debug!("GetBucketAccelerateConfiguration bucket={} request_id={}", bucket, request_id);
+
+    match state.metadata.get_bucket(&bucket).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return error_response(Error::NoSuchBucketNamed(bucket), &request_id),
+        Err(e) => return error_response(e, &request_id),
+    }
+
+    match state.metadata.get_bucket_accelerate(&bucket).await {
+        Ok(status) => {
+            let xml = xml::get_bucket_accelerate_response(status);
+            success_response(StatusCode::OK, xml, &request_id)
+        }
+        Err(e) => error_response(e, &request_id),
+    }
+}
+
+/// PUT /{bucket}?accelerate - Enable or suspend acceleration
+pub async fn put_bucket_accelerate(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let request_id = current_request_id();
+    info!("PutBucketAccelerateConfiguration bucket={} request_id={}", bucket, request_id);
+
+    match state.metadata.get_bucket(&bucket).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return error_response(Error::NoSuchBucketNamed(bucket), &request_id),
+        Err(e) => return error_response(e, &request_id),
+    }
+
+    let status = match xml::parse_accelerate_configuration(&body) {
+        Ok(Some(status)) => status,
+        Ok(None) => {
+            return error_response(
+                Error::MalformedXML("Status must be Enabled or Suspended".into()),
+                &request_id,
+            )
+        }
+        Err(e) => return error_response(Error::MalformedXML(e.to_string()), &request_id),
+    };
+    if let Err(e) = status.check_bucket_name(&bucket) {
+        return error_response(e, &request_id);
+    }
+
+    if let Err(e) = state.metadata.put_bucket_accelerate(&bucket, status).await {
+        return error_response(e, &request_id);
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("x-amz-request-id", &request_id)
+        .body(Body::empty())
+        .unwrap()
+}
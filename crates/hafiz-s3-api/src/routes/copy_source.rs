@@ -0,0 +1,261 @@
+//! Copy sources
+//!
+//! CopyObject and UploadPartCopy name their source in `x-amz-copy-source`
+//! and may make the copy conditional on it:
+//!
+//! - `x-amz-copy-source-if-match`: the source ETag is one of the listed
+//! - `x-amz-copy-source-if-none-match`: the source ETag is none of them
+//! - `x-amz-copy-source-if-unmodified-since`: the source is not newer
+//! - `x-amz-copy-source-if-modified-since`: the source is newer
+//!
+//! A failed condition refuses the copy with `412 PreconditionFailed`. As in
+//! S3, a matching `if-match` overrides a failed `if-unmodified-since`, and a
+//! passing `if-none-match` overrides a failed `if-modified-since`. Dates
+//! that do not parse are ignored.
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use hafiz_core::{
+    types::{ByteRange, ObjectInternal},
+    Error,
+};
+use tracing::{debug, info};
+
+use super::{check_put_size, check_readable, error_response, UploadPartQuery};
+use crate::middleware::current_request_id;
+use crate::sse;
+use crate::server::AppState;
+use crate::xml;
+
+/// Bucket and key named by `x-amz-copy-source`
+pub fn parse_copy_source(headers: &HeaderMap) -> Result<(String, String), Error> {
+    let copy_source = headers
+        .get("x-amz-copy-source")
+        .ok_or_else(|| Error::InvalidRequest("Missing x-amz-copy-source header".into()))?
+        .to_str()
+        .unwrap_or("");
+
+    // Parse source: /bucket/key or bucket/key
+    let source = copy_source.trim_start_matches('/');
+    let Some((bucket, key)) = source.split_once('/') else {
+        return Err(Error::InvalidRequest("Invalid copy source format".into()));
+    };
+    let key = urlencoding::decode(key).unwrap_or_else(|_| key.into()).to_string();
+    Ok((bucket.to_string(), key))
+}
+
+/// Fail with `PreconditionFailed` unless `source` meets the copy-source
+/// conditions of the request
+pub fn check_copy_source_conditions(headers: &HeaderMap, source: &ObjectInternal) -> Result<(), Error> {
+    let if_match = header(headers, "x-amz-copy-source-if-match");
+    let if_none_match = header(headers, "x-amz-copy-source-if-none-match");
+    let modified = truncate_to_secs(source.last_modified);
+
+    if let Some(etags) = if_match {
+        if !etag_matches(etags, &source.etag) {
+            return Err(precondition_failed("x-amz-copy-source-if-match"));
+        }
+    } else if let Some(since) = date_header(headers, "x-amz-copy-source-if-unmodified-since") {
+        if modified > since {
+            return Err(precondition_failed("x-amz-copy-source-if-unmodified-since"));
+        }
+    }
+
+    if let Some(etags) = if_none_match {
+        if etag_matches(etags, &source.etag) {
+            return Err(precondition_failed("x-amz-copy-source-if-none-match"));
+        }
+    } else if let Some(since) = date_header(headers, "x-amz-copy-source-if-modified-since") {
+        if modified <= since {
+            return Err(precondition_failed("x-amz-copy-source-if-modified-since"));
+        }
+    }
+
+    Ok(())
+}
+
+fn precondition_failed(header: &str) -> Error {
+    debug!("Copy source condition {} failed", header);
+    Error::PreconditionFailed
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+fn date_header(headers: &HeaderMap, name: &str) -> Option<DateTime<Utc>> {
+    let value = header(headers, name)?;
+    DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Whether `etag` is in a comma-separated list of quoted ETags or `*`
+fn etag_matches(list: &str, etag: &str) -> bool {
+    list.split(',')
+        .map(|candidate| candidate.trim().trim_start_matches("W/").trim_matches('"'))
+        .any(|candidate| candidate == "*" || candidate == etag.trim_matches('"'))
+}
+
+/// HTTP dates have no fractional seconds
+fn truncate_to_secs(time: DateTime<Utc>) -> DateTime<Utc> {
+    DateTime::from_timestamp(time.timestamp(), 0).unwrap_or(time)
+}
+
+/// Upload part copy (PUT /bucket/key?uploadId=xxx&partNumber=n with
+/// x-amz-copy-source)
+///
+/// `x-amz-copy-source-range` copies part of the source.
+pub async fn upload_part_copy(
+    State(state): State<AppState>,
+    Path((bucket, key)): Path<(String, String)>,
+    Query(params): Query<UploadPartQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let request_id = current_request_id();
+    let (src_bucket, src_key) = match parse_copy_source(&headers) {
+        Ok(source) => source,
+        Err(e) => return error_response(e, &request_id),
+    };
+    info!(
+        "UploadPartCopy source={}/{} bucket={} key={} uploadId={} partNumber={} request_id={}",
+        src_bucket, src_key, bucket, key, params.upload_id, params.part_number, request_id
+    );
+
+    if params.part_number < 1 || params.part_number as u32 > hafiz_core::MAX_PARTS {
+        return error_response(
+            Error::InvalidArgument(format!(
+                "Part number must be between 1 and {}",
+                hafiz_core::MAX_PARTS
+            )),
+            &request_id,
+        );
+    }
+    match state.metadata.get_multipart_upload(&bucket, &key, &params.upload_id).await {
+        Ok(None) => return error_response(Error::NoSuchUpload, &request_id),
+        Err(e) => return error_response(e, &request_id),
+        _ => {}
+    }
+
+    let source = match state.metadata.get_object(&src_bucket, &src_key).await {
+        Ok(Some(obj)) => obj,
+        Ok(None) => return error_response(Error::NoSuchKey, &request_id),
+        Err(e) => return error_response(e, &request_id),
+    };
+    if let Err(e) = check_copy_source_conditions(&headers, &source) {
+        return error_response(e, &request_id);
+    }
+    if let Err(e) = check_readable(&state, &source).await {
+        return error_response(e, &request_id);
+    }
+
+    let range = match header(&headers, "x-amz-copy-source-range") {
+        Some(range) => match ByteRange::parse(range).and_then(|r| r.resolve(source.size)) {
+            Ok(range) => Some(range),
+            Err(e) => return error_response(e, &request_id),
+        },
+        None => None,
+    };
+    let data = match sse::copy_source_customer_key(&headers) {
+        Ok(source_key) => sse::read_object(&state, &source, &src_key, range, source_key.as_ref()).await,
+        Err(e) => Err(e),
+    };
+    let data = match data {
+        Ok(data) => data,
+        Err(e) => return error_response(e, &request_id),
+    };
+    if let Err(e) = check_put_size(&state, &data) {
+        return error_response(e, &request_id);
+    }
+
+    let etag = match state
+        .storage
+        .put_part(&bucket, &key, &params.upload_id, params.part_number, data.clone())
+        .await
+    {
+        Ok(etag) => etag,
+        Err(e) => return error_response(e, &request_id),
+    };
+    if let Err(e) = state
+        .metadata
+        .put_upload_part(&params.upload_id, params.part_number, data.len() as i64, &etag)
+        .await
+    {
+        let _ = state
+            .storage
+            .abort_parts(&bucket, &key, &params.upload_id, &[params.part_number])
+            .await;
+        return error_response(e, &request_id);
+    }
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/xml")
+        .header("x-amz-request-id", &request_id)
+        .body(Body::from(xml::copy_part_response(&etag, &Utc::now())))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> ObjectInternal {
+        let mut object = ObjectInternal::new("b".into(), "k".into(), 3, "abc123".into(), "t".into());
+        object.last_modified = DateTime::parse_from_rfc3339("2024-01-01T12:00:00.5Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        object
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_etag_conditions() {
+        let object = source();
+        let ok = |pairs: &[(&'static str, &str)]| check_copy_source_conditions(&headers(pairs), &object).is_ok();
+
+        assert!(ok(&[]));
+        assert!(ok(&[("x-amz-copy-source-if-match", "\"abc123\"")]));
+        assert!(ok(&[("x-amz-copy-source-if-match", "\"other\", \"abc123\"")]));
+        assert!(ok(&[("x-amz-copy-source-if-match", "*")]));
+        assert!(!ok(&[("x-amz-copy-source-if-match", "\"other\"")]));
+        assert!(ok(&[("x-amz-copy-source-if-none-match", "\"other\"")]));
+        assert!(!ok(&[("x-amz-copy-source-if-none-match", "abc123")]));
+    }
+
+    #[test]
+    fn test_date_conditions() {
+        let object = source();
+        let ok = |pairs: &[(&'static str, &str)]| check_copy_source_conditions(&headers(pairs), &object).is_ok();
+        let before = "Mon, 01 Jan 2024 11:00:00 GMT";
+        let at = "Mon, 01 Jan 2024 12:00:00 GMT";
+
+        assert!(ok(&[("x-amz-copy-source-if-unmodified-since", at)]));
+        assert!(!ok(&[("x-amz-copy-source-if-unmodified-since", before)]));
+        assert!(ok(&[("x-amz-copy-source-if-modified-since", before)]));
+        assert!(!ok(&[("x-amz-copy-source-if-modified-since", at)]));
+        assert!(ok(&[("x-amz-copy-source-if-modified-since", "not a date")]));
+
+        // A matching ETag overrides the date
+        assert!(ok(&[
+            ("x-amz-copy-source-if-match", "\"abc123\""),
+            ("x-amz-copy-source-if-unmodified-since", before),
+        ]));
+        assert!(ok(&[
+            ("x-amz-copy-source-if-none-match", "\"other\""),
+            ("x-amz-copy-source-if-modified-since", at),
+        ]));
+    }
+}
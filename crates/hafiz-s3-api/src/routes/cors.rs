@@ -8,21 +8,24 @@
 //! - PUT /{bucket}?cors - Set bucket CORS configuration
 //! - DELETE /{bucket}?cors - Delete bucket CORS configuration
 //! - OPTIONS /{bucket}/{key} - CORS preflight request
+//!
+//! Other requests get their CORS headers from the [`cors_headers`] middleware.
 
 use axum::{
     body::Body,
     extract::{Path, State},
-    http::{header, HeaderMap, Method, StatusCode},
+    http::{header, HeaderMap, Method, Request, StatusCode},
+    middleware::Next,
     response::{IntoResponse, Response},
 };
 use bytes::Bytes;
 use hafiz_core::{
     types::{CorsConfiguration, CorsResponseHeaders},
-    utils::generate_request_id,
     Error,
 };
 use tracing::{debug, error, info, warn};
 
+use crate::middleware::{current_request_id, request_error};
 use crate::server::AppState;
 
 // ============================================================================
@@ -31,7 +34,7 @@ use crate::server::AppState;
 
 fn error_response(err: Error, request_id: &str) -> Response {
     let status = StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-    let s3_error = hafiz_core::error::S3Error::from(err).with_request_id(request_id);
+    let s3_error = request_error(err).with_request_id(request_id);
 
     Response::builder()
         .status(status)
@@ -58,25 +61,6 @@ fn no_content_response(request_id: &str) -> Response {
         .unwrap()
 }
 
-fn cors_error_response(code: &str, message: &str, request_id: &str) -> Response {
-    let xml = format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<Error>
-<Code>{}</Code>
-<Message>{}</Message>
-<RequestId>{}</RequestId>
-</Error>"#,
-        code, message, request_id
-    );
-
-    Response::builder()
-        .status(StatusCode::FORBIDDEN)
-        .header("Content-Type", "application/xml")
-        .header("x-amz-request-id", request_id)
-        .body(Body::from(xml))
-        .unwrap()
-}
-
 // ============================================================================
 // CORS Configuration Handlers
 // ============================================================================
@@ -86,7 +70,7 @@ pub async fn get_bucket_cors(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetBucketCors bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -109,11 +93,7 @@ pub async fn get_bucket_cors(
         }
         Ok(None) => {
             // No CORS configuration - return error per S3 spec
-            cors_error_response(
-                "NoSuchCORSConfiguration",
-                "The CORS configuration does not exist",
-                &request_id,
-            )
+            error_response(Error::NoSuchCORSConfiguration, &request_id)
         }
         Err(e) => {
             error!("Error getting CORS config: {}", e);
@@ -128,7 +108,7 @@ pub async fn put_bucket_cors(
     Path(bucket): Path<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutBucketCors bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -199,7 +179,7 @@ pub async fn delete_bucket_cors(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("DeleteBucketCors bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -241,7 +221,7 @@ pub async fn handle_cors_preflight(
     Path(path): Path<String>,
     headers: HeaderMap,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
 
     // Extract bucket from path (first segment)
     let bucket = path.split('/').next().unwrap_or(&path);
@@ -368,6 +348,40 @@ fn cors_forbidden_response(origin: &str, request_id: &str) -> Response {
 // CORS Middleware Helper
 // ============================================================================
 
+/// Answer actual (non-preflight) cross-origin requests
+///
+/// Responses from a bucket with a CORS configuration carry `Vary: Origin`,
+/// whether or not the request came from a browser, so that caches keep
+/// the responses for different origins apart. When a rule matches the
+/// request's origin and method, the `Access-Control-*` headers are added
+/// as well; error responses included, so browsers can read them.
+pub async fn cors_headers(State(state): State<AppState>, request: Request<Body>, next: Next) -> Response {
+    // Preflights are answered by handle_cors_preflight
+    if request.method() == Method::OPTIONS {
+        return next.run(request).await;
+    }
+    let bucket = request
+        .uri()
+        .path()
+        .trim_start_matches('/')
+        .split('/')
+        .next()
+        .filter(|b| !b.is_empty())
+        .map(str::to_string);
+    let origin = request
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|o| o.to_str().ok())
+        .map(str::to_string);
+    let method = request.method().as_str().to_string();
+
+    let response = next.run(request).await;
+    match bucket {
+        Some(bucket) => add_cors_headers(&state, &bucket, origin.as_deref(), &method, response).await,
+        None => response,
+    }
+}
+
 /// Add CORS headers to a response based on request origin
 ///
 /// This should be called for actual (non-preflight) requests to add
@@ -377,34 +391,80 @@ pub async fn add_cors_headers_to_response(
     bucket: &str,
     origin: &str,
     method: &str,
+    response: Response,
+) -> Response {
+    add_cors_headers(state, bucket, Some(origin), method, response).await
+}
+
+async fn add_cors_headers(
+    state: &AppState,
+    bucket: &str,
+    origin: Option<&str>,
+    method: &str,
     mut response: Response,
 ) -> Response {
     // Get bucket's CORS configuration
     let cors_config = match state.metadata.get_bucket_cors(bucket).await {
         Ok(Some(xml)) => match CorsConfiguration::from_xml(&xml) {
-            Ok(config) => config,
-            Err(_) => return response,
+            Ok(config) if !config.is_empty() => config,
+            _ => return response,
         },
         _ => return response,
     };
 
+    let headers = response.headers_mut();
+    add_vary(headers, "Origin");
+
     // Find matching rule
+    let Some(origin) = origin else {
+        return response;
+    };
     if let Some(rule) = cors_config.find_matching_rule(origin, method) {
         let cors_headers = CorsResponseHeaders::for_actual_request(rule, origin);
-
-        let headers = response.headers_mut();
         for (name, value) in cors_headers.to_header_vec() {
-            if let Ok(header_name) = name.parse::<header::HeaderName>() {
-                if let Ok(header_value) = value.parse::<header::HeaderValue>() {
-                    headers.insert(header_name, header_value);
-                }
+            if name.eq_ignore_ascii_case("vary") {
+                continue;
+            }
+            if let (Ok(name), Ok(value)) = (
+                name.parse::<header::HeaderName>(),
+                value.parse::<header::HeaderValue>(),
+            ) {
+                headers.insert(name, value);
             }
         }
+    } else {
+        debug!(
+            "No CORS rule for origin={} method={} bucket={}",
+            origin, method, bucket
+        );
     }
 
     response
 }
 
+/// Add `field` to the Vary header, keeping what is already there
+fn add_vary(headers: &mut HeaderMap, field: &str) {
+    let existing: Vec<String> = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect();
+    if existing.iter().any(|v| v == "*" || v.eq_ignore_ascii_case(field)) {
+        return;
+    }
+    let value = existing
+        .into_iter()
+        .chain(std::iter::once(field.to_string()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if let Ok(value) = value.parse() {
+        headers.insert(header::VARY, value);
+    }
+}
+
 /// Check if origin is allowed for a bucket
 pub async fn is_origin_allowed(
     state: &AppState,
@@ -471,4 +531,18 @@ mod tests {
         assert!(config.find_matching_rule("https://example.com", "DELETE").is_none());
         assert!(config.find_matching_rule("https://other.com", "GET").is_none());
     }
+
+    #[test]
+    fn test_add_vary() {
+        let mut headers = HeaderMap::new();
+        add_vary(&mut headers, "Origin");
+        assert_eq!(headers[header::VARY], "Origin");
+        add_vary(&mut headers, "origin");
+        assert_eq!(headers[header::VARY], "Origin");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::VARY, "Accept-Encoding".parse().unwrap());
+        add_vary(&mut headers, "Origin");
+        assert_eq!(headers[header::VARY], "Accept-Encoding, Origin");
+    }
 }
@@ -1,30 +1,55 @@
 //! S3 API Routes
 
+mod accelerate;
+mod copy_source;
 mod cors;
 mod notification;
 mod object_lock;
+mod placement;
 mod policy;
-
-pub use cors::{handle_cors_preflight, add_cors_headers_to_response, is_origin_allowed};
-pub use object_lock::{can_delete_object, get_lock_error_message};
+mod storage_class;
+mod sts;
+
+pub use sts::sts_handler;
+pub use cors::{handle_cors_preflight, add_cors_headers_to_response, cors_headers, is_origin_allowed};
+pub use object_lock::{
+    can_delete_object, enforce_object_lock, get_lock_error_message, governance_bypass,
+    object_lock_headers, object_lock_state, WriteLock,
+};
+pub use storage_class::{
+    change_storage_class, check_readable, place_object, requested_storage_class,
+    restore_header, storage_class_header,
+};
+pub(crate) use storage_class::storage_key;
 
 use axum::{
     body::Body,
     extract::{Path, Query, RawQuery, State},
-    http::{HeaderMap, StatusCode},
+    http::{HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 use bytes::Bytes;
+use futures::stream::{self, StreamExt};
 use hafiz_core::{
-    types::{Bucket, ByteRange, ListObjectsResult, Object},
-    utils::{format_http_datetime, format_s3_datetime, generate_etag, generate_request_id},
+    types::{
+        validate_bucket_tags, Bucket, ByteRange, ConsistencyLevel, EncryptionInfo, EncryptionType,
+        ListObjectsResult, Object, ObjectInternal, Owner, Tag, TagSet, DEFAULT_ACCOUNT,
+        MAX_REQUEST_HEADER_SIZE, NULL_VERSION_ID,
+    },
+    utils::{format_http_datetime, format_s3_datetime, generate_etag, verify_content_md5},
     Error,
 };
+use hafiz_storage::ByteStream;
 use serde::Deserialize;
 use std::collections::BTreeMap;
 use tracing::{debug, error, info};
 
+use crate::consensus;
+use crate::list_token::ListFilter;
+use crate::middleware::{account_of, current_principal, current_request_id, request_error};
 use crate::server::AppState;
+use crate::sse;
+use crate::transform::{self, Pipeline, TransformInput};
 use crate::xml;
 
 /// Error response wrapper
@@ -38,7 +63,7 @@ impl IntoResponse for S3Response {
 
 fn error_response(err: Error, request_id: &str) -> Response {
     let status = StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-    let s3_error = hafiz_core::error::S3Error::from(err).with_request_id(request_id);
+    let s3_error = request_error(err).with_request_id(request_id);
 
     Response::builder()
         .status(status)
@@ -57,6 +82,45 @@ fn success_response(status: StatusCode, body: String, request_id: &str) -> Respo
         .unwrap()
 }
 
+/// Mark a response for a server-side encrypted object
+fn encryption_headers(
+    mut builder: axum::http::response::Builder,
+    encryption: &EncryptionInfo,
+) -> axum::http::response::Builder {
+    if encryption.is_encrypted() {
+        builder = builder.header("x-amz-server-side-encryption", encryption.encryption_type.as_str());
+        if let Some(ref md5) = encryption.sse_customer_key_md5 {
+            builder = builder.header("x-amz-server-side-encryption-customer-key-MD5", md5);
+        }
+    }
+    builder
+}
+
+/// Reject a PutObject or UploadPart body over the single-PUT limit
+fn check_put_size(state: &AppState, body: &[u8]) -> hafiz_core::Result<()> {
+    if body.len() as u64 > state.config.storage.max_put_size {
+        return Err(Error::EntityTooLarge);
+    }
+    Ok(())
+}
+
+/// Body-limit rejections happen before any handler runs; give them the S3
+/// error clients expect
+pub async fn entity_too_large(response: Response) -> Response {
+    if response.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        return error_response(Error::EntityTooLarge, &current_request_id());
+    }
+    response
+}
+
+/// Verify the body against Content-MD5 when the client sent one
+fn check_content_md5(headers: &HeaderMap, body: &[u8]) -> hafiz_core::Result<()> {
+    match headers.get("content-md5") {
+        Some(value) => verify_content_md5(value.to_str().map_err(|_| Error::InvalidDigest)?, body),
+        None => Ok(()),
+    }
+}
+
 // ============= Handler Dispatchers =============
 
 /// Generic query params for dispatching
@@ -70,7 +134,7 @@ pub struct DispatchQuery {
     delete: Option<String>,
 }
 
-/// Bucket GET dispatcher - ListObjects, ListMultipartUploads, GetBucketVersioning, GetBucketLifecycle, ListObjectVersions, GetBucketPolicy, GetBucketAcl, or GetBucketNotification
+/// Bucket GET dispatcher - ListObjects, ListMultipartUploads, GetBucketVersioning, GetBucketLifecycle, GetBucketTagging, ListObjectVersions, GetBucketPolicy, GetBucketAcl, or GetBucketNotification
 pub async fn bucket_get_handler(
     state: State<AppState>,
     path: Path<String>,
@@ -89,6 +153,11 @@ pub async fn bucket_get_handler(
         return get_bucket_lifecycle(state, path).await.into_response();
     }
 
+    // Check if this is a get bucket tagging request
+    if query_str == "tagging" || query_str.starts_with("tagging&") {
+        return get_bucket_tagging(state, path).await.into_response();
+    }
+
     // Check if this is a get bucket policy request
     if query_str == "policy" || query_str.starts_with("policy&") {
         return policy::get_bucket_policy(state, path).await.into_response();
@@ -114,6 +183,11 @@ pub async fn bucket_get_handler(
         return object_lock::get_bucket_object_lock_config(state, path).await.into_response();
     }
 
+    // Check if this is a get bucket accelerate request
+    if query_str == "accelerate" || query_str.starts_with("accelerate&") {
+        return accelerate::get_bucket_accelerate(state, path).await.into_response();
+    }
+
     // Check if this is a list object versions request
     if query_str.contains("versions") {
         let params: ListObjectVersionsQuery = serde_urlencoded::from_str(&query_str).unwrap_or_default();
@@ -131,7 +205,7 @@ pub async fn bucket_get_handler(
     get_bucket(state, path, query).await.into_response()
 }
 
-/// Bucket PUT dispatcher - CreateBucket, PutBucketVersioning, PutBucketLifecycle, PutBucketPolicy, PutBucketAcl, or PutBucketNotification
+/// Bucket PUT dispatcher - CreateBucket, PutBucketVersioning, PutBucketLifecycle, PutBucketTagging, PutBucketPolicy, PutBucketAcl, or PutBucketNotification
 pub async fn bucket_put_handler(
     state: State<AppState>,
     path: Path<String>,
@@ -151,6 +225,11 @@ pub async fn bucket_put_handler(
         return put_bucket_lifecycle(state, path, body).await.into_response();
     }
 
+    // Check if this is a put bucket tagging request
+    if query_str == "tagging" || query_str.starts_with("tagging&") {
+        return put_bucket_tagging(state, path, body).await.into_response();
+    }
+
     // Check if this is a put bucket policy request
     if query_str == "policy" || query_str.starts_with("policy&") {
         return policy::put_bucket_policy(state, path, body).await.into_response();
@@ -176,11 +255,16 @@ pub async fn bucket_put_handler(
         return object_lock::put_bucket_object_lock_config(state, path, body).await.into_response();
     }
 
+    // Check if this is a put bucket accelerate request
+    if query_str == "accelerate" || query_str.starts_with("accelerate&") {
+        return accelerate::put_bucket_accelerate(state, path, body).await.into_response();
+    }
+
     // Default: CreateBucket
     create_bucket(state, path).await.into_response()
 }
 
-/// Bucket DELETE dispatcher - DeleteBucket, DeleteBucketLifecycle, or DeleteBucketPolicy
+/// Bucket DELETE dispatcher - DeleteBucket, DeleteBucketLifecycle, DeleteBucketTagging, or DeleteBucketPolicy
 pub async fn bucket_delete_handler(
     state: State<AppState>,
     path: Path<String>,
@@ -193,6 +277,11 @@ pub async fn bucket_delete_handler(
         return delete_bucket_lifecycle(state, path).await.into_response();
     }
 
+    // Check if this is a delete bucket tagging request
+    if query_str == "tagging" || query_str.starts_with("tagging&") {
+        return delete_bucket_tagging(state, path).await.into_response();
+    }
+
     // Check if this is a delete bucket policy request
     if query_str == "policy" || query_str.starts_with("policy&") {
         return policy::delete_bucket_policy(state, path).await.into_response();
@@ -211,6 +300,7 @@ pub async fn bucket_delete_handler(
 pub async fn bucket_post_handler(
     state: State<AppState>,
     path: Path<String>,
+    headers: HeaderMap,
     raw_query: RawQuery,
     body: Bytes,
 ) -> impl IntoResponse {
@@ -218,11 +308,11 @@ pub async fn bucket_post_handler(
 
     if query_str.contains("delete") {
         let params: DeleteObjectsQuery = serde_urlencoded::from_str(&query_str).unwrap_or_default();
-        return delete_objects(state, path, Query(params), body).await.into_response();
+        return delete_objects(state, path, headers, Query(params), body).await.into_response();
     }
 
     // Unknown POST operation
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     error_response(Error::InvalidRequest("Unknown bucket POST operation".into()), &request_id)
 }
 
@@ -269,13 +359,39 @@ pub async fn object_get_handler(
         return list_parts(state, path, Query(params)).await.into_response();
     }
 
-    // Check for versionId query param
-    let version_id: Option<String> = serde_urlencoded::from_str::<std::collections::HashMap<String, String>>(&query_str)
-        .ok()
-        .and_then(|m| m.get("versionId").cloned());
+    // Check for versionId and partNumber query params
+    let params: std::collections::HashMap<String, String> =
+        serde_urlencoded::from_str(&query_str).unwrap_or_default();
+    let version_id = params.get("versionId").cloned();
+    let part_number = match part_number_param(&params) {
+        Ok(part_number) => part_number,
+        Err(e) => return error_response(e, &current_request_id()),
+    };
+
+    // Default: GetObject (with optional version or part)
+    get_object_versioned(state, path, headers, version_id, part_number).await.into_response()
+}
+
+/// The `partNumber` of a GET or HEAD, which reads one part of a multipart object
+fn part_number_param(params: &std::collections::HashMap<String, String>) -> Result<Option<i32>, Error> {
+    params
+        .get("partNumber")
+        .map(|n| {
+            n.parse::<i32>()
+                .map_err(|_| Error::InvalidArgument("partNumber must be a positive integer".into()))
+        })
+        .transpose()
+}
 
-    // Default: GetObject (with optional version)
-    get_object_versioned(state, path, headers, version_id).await.into_response()
+/// Byte range of the requested part, or `None` for an empty one
+fn requested_part(object: &ObjectInternal, part_number: i32, headers: &HeaderMap) -> Result<Option<(i64, i64)>, Error> {
+    if headers.contains_key("range") {
+        return Err(Error::InvalidRequest(
+            "Cannot specify both Range header and partNumber query parameter".into(),
+        ));
+    }
+    let (start, end) = object.part_range(part_number)?;
+    Ok((end >= start).then_some((start, end)))
 }
 
 /// Object PUT dispatcher - PutObject, CopyObject, UploadPart, PutObjectTagging, or PutObjectAcl
@@ -319,7 +435,10 @@ pub async fn object_put_handler(
     // Check if this is an upload part request
     if query_str.contains("uploadId") && query_str.contains("partNumber") {
         let params: UploadPartQuery = serde_urlencoded::from_str(&query_str).unwrap_or_default();
-        return upload_part(state, path, Query(params), body).await.into_response();
+        if headers.contains_key("x-amz-copy-source") {
+            return copy_source::upload_part_copy(state, path, Query(params), headers).await.into_response();
+        }
+        return upload_part(state, path, Query(params), headers, body).await.into_response();
     }
 
     // Check if this is a copy request
@@ -335,6 +454,7 @@ pub async fn object_put_handler(
 pub async fn object_delete_handler(
     state: State<AppState>,
     path: Path<(String, String)>,
+    headers: HeaderMap,
     raw_query: RawQuery,
 ) -> impl IntoResponse {
     let query_str = raw_query.0.unwrap_or_default();
@@ -359,7 +479,7 @@ pub async fn object_delete_handler(
         .and_then(|m| m.get("versionId").cloned());
 
     // Default: DeleteObject (with optional version)
-    delete_object_versioned(state, path, version_id).await.into_response()
+    delete_object_versioned(state, path, headers, version_id).await.into_response()
 }
 
 /// Object POST dispatcher - CreateMultipartUpload or CompleteMultipartUpload
@@ -372,10 +492,16 @@ pub async fn object_post_handler(
 ) -> impl IntoResponse {
     let query_str = raw_query.0.unwrap_or_default();
 
+    // Check if this is a restore object request
+    if query_str == "restore" || query_str.starts_with("restore&") || query_str.contains("&restore") {
+        let query: object_lock::RetentionQuery = serde_urlencoded::from_str(&query_str).unwrap_or_default();
+        return storage_class::restore_object(state, path, Query(query), body).await.into_response();
+    }
+
     // Check if this is a complete multipart upload request
     if query_str.contains("uploadId") {
         let params: CompleteMultipartQuery = serde_urlencoded::from_str(&query_str).unwrap_or_default();
-        return complete_multipart_upload(state, path, Query(params), body).await.into_response();
+        return complete_multipart_upload(state, path, headers, Query(params), body).await.into_response();
     }
 
     // Check if this is a create multipart upload request
@@ -385,26 +511,100 @@ pub async fn object_post_handler(
     }
 
     // Unknown POST operation
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     error_response(Error::InvalidRequest("Unknown object POST operation".into()), &request_id)
 }
 
 // ============= Service Operations =============
 
-/// List all buckets
+/// Most buckets a ListBuckets page may hold
+const MAX_BUCKETS_PER_PAGE: i64 = 10_000;
+
+#[derive(Debug, Deserialize)]
+pub struct ListBucketsQuery {
+    #[serde(rename = "max-buckets")]
+    max_buckets: Option<i64>,
+    #[serde(rename = "continuation-token")]
+    continuation_token: Option<String>,
+    prefix: Option<String>,
+    #[serde(rename = "bucket-region")]
+    bucket_region: Option<String>,
+}
+
+/// List the buckets of the caller's account
+///
+/// Without `max-buckets` or a continuation token all buckets are listed in
+/// one response, as before pagination existed.
 pub async fn list_buckets(
     State(state): State<AppState>,
-    headers: HeaderMap,
+    Query(params): Query<ListBucketsQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("ListBuckets request_id={}", request_id);
 
-    // Get user from auth (simplified - using root for now)
-    let owner_id = "root";
+    // Buckets of the caller's account
+    let owner_id = match account_of(&state, current_principal().as_deref()).await {
+        Ok(account) => account,
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    let limit = match params.max_buckets {
+        Some(max) if !(1..=MAX_BUCKETS_PER_PAGE).contains(&max) => {
+            return error_response(
+                Error::InvalidArgument(format!(
+                    "max-buckets must be between 1 and {}",
+                    MAX_BUCKETS_PER_PAGE
+                )),
+                &request_id,
+            )
+        }
+        Some(max) => Some(max as usize),
+        None if params.continuation_token.is_some() => Some(MAX_BUCKETS_PER_PAGE as usize),
+        None => None,
+    };
+
+    // The region filter stands in for the delimiter ListBuckets lacks
+    let filter = ListFilter {
+        operation: "ListBuckets",
+        bucket: &owner_id,
+        prefix: params.prefix.as_deref(),
+        delimiter: params.bucket_region.as_deref(),
+    };
+    let after = match params.continuation_token.as_deref() {
+        Some(token) => match state.list_tokens.decode(&filter, token) {
+            Some(position) => Some(position.key),
+            None => {
+                return error_response(
+                    Error::InvalidArgument("The continuation token provided is incorrect".into()),
+                    &request_id,
+                )
+            }
+        },
+        None => None,
+    };
 
-    match state.metadata.list_buckets(owner_id).await {
-        Ok(buckets) => {
-            let xml = xml::list_buckets_response(&buckets, owner_id);
+    let page = state
+        .metadata
+        .list_buckets_page(
+            Some(&owner_id),
+            params.prefix.as_deref(),
+            params.bucket_region.as_deref(),
+            after.as_deref(),
+            limit,
+        )
+        .await;
+    match page {
+        Ok((buckets, more)) => {
+            let next_token = buckets
+                .last()
+                .filter(|_| more)
+                .map(|last| state.list_tokens.encode(&filter, &last.name, None));
+            let xml = xml::list_buckets_response(
+                &buckets,
+                &owner_id,
+                params.prefix.as_deref(),
+                next_token.as_deref(),
+            );
             success_response(StatusCode::OK, xml, &request_id)
         }
         Err(e) => {
@@ -427,6 +627,12 @@ pub struct ListObjectsQuery {
     #[serde(rename = "continuation-token")]
     continuation_token: Option<String>,
     marker: Option<String>,
+    #[serde(rename = "start-after")]
+    start_after: Option<String>,
+    #[serde(rename = "encoding-type")]
+    encoding_type: Option<String>,
+    #[serde(rename = "fetch-owner")]
+    fetch_owner: Option<bool>,
 }
 
 /// HEAD bucket - check if bucket exists
@@ -434,18 +640,28 @@ pub async fn head_bucket(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("HeadBucket bucket={} request_id={}", bucket, request_id);
 
     match state.metadata.get_bucket(&bucket).await {
-        Ok(Some(_)) => Response::builder()
-            .status(StatusCode::OK)
-            .header("x-amz-request-id", &request_id)
-            .body(Body::empty())
-            .unwrap(),
-        Ok(None) => error_response(Error::NoSuchBucket, &request_id),
-        Err(e) => error_response(e, &request_id),
+        Ok(Some(_)) => {}
+        Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
+        Err(e) => return error_response(e, &request_id),
     }
+
+    // Stored versions and bytes, read from counters rather than counted
+    let usage = match state.metadata.get_bucket_usage(&bucket).await {
+        Ok(usage) => usage,
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("x-amz-request-id", &request_id)
+        .header("x-hafiz-object-count", usage.object_count)
+        .header("x-hafiz-bytes-used", usage.total_bytes)
+        .body(Body::empty())
+        .unwrap()
 }
 
 /// GET bucket - list objects or get bucket info
@@ -454,38 +670,97 @@ pub async fn get_bucket(
     Path(bucket): Path<String>,
     Query(params): Query<ListObjectsQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetBucket/ListObjects bucket={} request_id={}", bucket, request_id);
 
     // Check bucket exists
-    match state.metadata.get_bucket(&bucket).await {
+    let bucket_info = match state.metadata.get_bucket(&bucket).await {
+        Ok(Some(b)) => b,
         Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
         Err(e) => return error_response(e, &request_id),
-        _ => {}
+    };
+
+    if let Err(e) = check_encoding_type(params.encoding_type.as_deref()) {
+        return error_response(e, &request_id);
     }
 
-    let max_keys = params.max_keys.unwrap_or(1000).min(1000);
-    let continuation = params.continuation_token.as_deref().or(params.marker.as_deref());
+    let max_keys = params.max_keys.unwrap_or(1000).clamp(0, 1000);
     let is_v2 = params.list_type.as_deref() == Some("2");
 
-    match state.metadata.list_objects(
-        &bucket,
-        params.prefix.as_deref(),
-        params.delimiter.as_deref(),
-        max_keys,
-        continuation,
-    ).await {
-        Ok((objects, common_prefixes, is_truncated, next_token)) => {
+    // Flat-namespace buckets list keys only; the delimiter is ignored
+    let flat = match state.metadata.get_bucket_namespace(&bucket).await {
+        Ok(mode) => mode.is_flat(),
+        Err(e) => return error_response(e, &request_id),
+    };
+    let delimiter = if flat { None } else { params.delimiter.clone() };
+
+    let filter = ListFilter {
+        operation: if is_v2 { "ListObjectsV2" } else { "ListObjects" },
+        bucket: &bucket,
+        prefix: params.prefix.as_deref(),
+        delimiter: delimiter.as_deref(),
+    };
+
+    // V2 resumes from the continuation token, or else after StartAfter;
+    // V1 after the marker
+    let marker = if is_v2 {
+        match params.continuation_token.as_deref() {
+            Some(token) => match state.list_tokens.decode(&filter, token) {
+                Some(position) => Some(position.key),
+                None => {
+                    return error_response(
+                        Error::InvalidArgument("The continuation token provided is incorrect".into()),
+                        &request_id,
+                    )
+                }
+            },
+            None => params.start_after.clone(),
+        }
+    } else {
+        params
+            .marker
+            .as_deref()
+            .map(|m| state.list_tokens.decode_marker(&filter, m, None).key)
+    };
+
+    // Objects belong to the bucket owner
+    let owner = (!is_v2 || params.fetch_owner == Some(true)).then(|| Owner {
+        id: bucket_info.owner_id.clone(),
+        display_name: Some(bucket_info.owner_id.clone()),
+    });
+
+    let listing = if flat {
+        state
+            .metadata
+            .list_objects_flat(&bucket, params.prefix.as_deref(), max_keys, marker.as_deref())
+            .await
+            .map(|(objects, is_truncated, next_marker)| (objects, Vec::new(), is_truncated, next_marker))
+    } else {
+        state.metadata.list_objects(
+            &bucket,
+            params.prefix.as_deref(),
+            delimiter.as_deref(),
+            max_keys,
+            marker.as_deref(),
+        ).await
+    };
+
+    match listing {
+        Ok((objects, common_prefixes, is_truncated, next_marker)) => {
+            let next_token = next_marker.map(|m| state.list_tokens.encode(&filter, &m, None));
             let result = ListObjectsResult {
                 name: bucket,
                 prefix: params.prefix,
-                delimiter: params.delimiter,
+                delimiter,
                 max_keys,
                 is_truncated,
                 contents: objects,
                 common_prefixes,
-                continuation_token: params.continuation_token,
+                continuation_token: if is_v2 { params.continuation_token } else { params.marker },
                 next_continuation_token: next_token,
+                start_after: if is_v2 { params.start_after } else { None },
+                encoding_type: params.encoding_type,
+                owner,
             };
 
             let xml = if is_v2 {
@@ -503,23 +778,46 @@ pub async fn get_bucket(
     }
 }
 
+/// Refuse an `encoding-type` other than `url`, the only one S3 defines
+fn check_encoding_type(encoding_type: Option<&str>) -> Result<(), Error> {
+    match encoding_type {
+        None | Some("url") => Ok(()),
+        Some(encoding) => Err(Error::InvalidArgument(format!(
+            "Invalid Encoding Method specified in Request: {}",
+            encoding
+        ))),
+    }
+}
+
 /// PUT bucket - create bucket
 pub async fn create_bucket(
     State(state): State<AppState>,
     Path(bucket_name): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("CreateBucket bucket={} request_id={}", bucket_name, request_id);
 
+    // The bucket belongs to the creator's account
+    let owner_id = match account_of(&state, current_principal().as_deref()).await {
+        Ok(account) => account,
+        Err(e) => return error_response(e, &request_id),
+    };
+
     // Validate bucket name
-    if let Err(e) = Bucket::validate_name(&bucket_name) {
+    if let Err(e) = check_new_bucket_name(&state, &bucket_name, &owner_id).await {
         return error_response(e, &request_id);
     }
-
-    let bucket = Bucket::new(bucket_name.clone(), "root".to_string());
-
-    // Create in metadata
-    if let Err(e) = state.metadata.create_bucket(&bucket).await {
+    let bucket = Bucket::new(bucket_name.clone(), owner_id);
+
+    // Create in metadata; names are unique across all accounts
+    if let Err(e) = consensus::create_bucket(&state, &bucket).await {
+        let e = match e {
+            Error::BucketAlreadyExists => match state.metadata.get_bucket(&bucket_name).await {
+                Ok(Some(existing)) if existing.owner_id == bucket.owner_id => Error::BucketAlreadyOwnedByYou,
+                _ => Error::BucketAlreadyExists,
+            },
+            e => e,
+        };
         return error_response(e, &request_id);
     }
 
@@ -527,7 +825,7 @@ pub async fn create_bucket(
     if let Err(e) = state.storage.create_bucket(&bucket_name).await {
         error!("Failed to create bucket storage: {}", e);
         // Rollback metadata
-        let _ = state.metadata.delete_bucket(&bucket_name).await;
+        let _ = consensus::delete_bucket(&state, &bucket_name).await;
         return error_response(e, &request_id);
     }
 
@@ -539,24 +837,50 @@ pub async fn create_bucket(
         .unwrap()
 }
 
+/// Check the name of a new bucket of `account` against the configured rules
+///
+/// With `bucket_name_account_prefix`, other accounts' bucket names start
+/// with `<account>.`, and the default account cannot take names in another
+/// account's namespace. Account IDs have no periods, so namespaces never
+/// overlap.
+pub async fn check_new_bucket_name(state: &AppState, name: &str, account: &str) -> Result<(), Error> {
+    let server = &state.config.server;
+    Bucket::validate_name_with(name, server.bucket_name_rules)?;
+    if !server.bucket_name_account_prefix {
+        return Ok(());
+    }
+
+    let namespace = name.split_once('.').map(|(namespace, _)| namespace);
+    if account != DEFAULT_ACCOUNT {
+        if namespace != Some(account) {
+            return Err(Error::InvalidBucketName(format!(
+                "Bucket names of account {} must start with {}.",
+                account, account
+            )));
+        }
+    } else if let Some(namespace) = namespace.filter(|n| *n != DEFAULT_ACCOUNT) {
+        if state.metadata.get_account(namespace).await?.is_some() {
+            return Err(Error::InvalidBucketName(format!(
+                "Bucket names starting with {}. belong to account {}",
+                namespace, namespace
+            )));
+        }
+    }
+    Ok(())
+}
+
 /// DELETE bucket
 pub async fn delete_bucket(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("DeleteBucket bucket={} request_id={}", bucket, request_id);
 
-    // Delete from metadata (will check if empty)
-    if let Err(e) = state.metadata.delete_bucket(&bucket).await {
+    if let Err(e) = remove_bucket(&state, &bucket).await {
         return error_response(e, &request_id);
     }
 
-    // Delete storage
-    if let Err(e) = state.storage.delete_bucket(&bucket).await {
-        error!("Failed to delete bucket storage: {}", e);
-    }
-
     Response::builder()
         .status(StatusCode::NO_CONTENT)
         .header("x-amz-request-id", &request_id)
@@ -564,28 +888,118 @@ pub async fn delete_bucket(
         .unwrap()
 }
 
+/// Remove an empty bucket with its storage and per-bucket settings
+pub async fn remove_bucket(state: &AppState, bucket: &str) -> Result<(), Error> {
+    // Delete from metadata (will check if empty)
+    consensus::delete_bucket(state, bucket).await?;
+
+    // Delete storage
+    if let Err(e) = state.storage.delete_bucket(bucket).await {
+        error!("Failed to delete bucket storage: {}", e);
+    }
+
+    // Metadata dropped the bucket's tags with it
+    sync_bucket_tags(state, bucket, &TagSet::new());
+
+    // The bucket was empty, so no compressed objects are left behind
+    state.compression.clear_bucket_compression(bucket);
+    if let Err(e) = state.metadata.delete_bucket_compression(bucket).await {
+        error!("Failed to delete bucket compression setting: {}", e);
+    }
+    Ok(())
+}
+
 // ============= Object Operations =============
 
-/// HEAD object
+/// HEAD object; with `partNumber`, describes that part of a multipart object
 pub async fn head_object(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
+    Query(params): Query<std::collections::HashMap<String, String>>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("HeadObject bucket={} key={} request_id={}", bucket, key, request_id);
 
-    match state.metadata.get_object(&bucket, &key).await {
-        Ok(Some(obj)) => Response::builder()
+    let obj = match find_object(&state, &bucket, &key).await {
+        Ok(Some(obj)) => obj,
+        Ok(None) => return error_response(Error::NoSuchKey, &request_id),
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    let mut response = Response::builder()
+        .header("Content-Type", &obj.content_type)
+        .header("ETag", generate_etag(&obj.etag))
+        .header("Last-Modified", format_http_datetime(&obj.last_modified))
+        .header("x-amz-request-id", &request_id);
+    response = encryption_headers(response, &obj.encryption);
+    response = object_lock_headers(&state, &bucket, &key, &obj.version_id, response).await;
+    response = storage_class_header(response, &obj);
+    response = restore_header(&state, response, &obj).await;
+
+    let part_number = match part_number_param(&params) {
+        Ok(part_number) => part_number,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let part = match part_number.map(|n| requested_part(&obj, n, &headers)).transpose() {
+        Ok(part) => part,
+        Err(e) => return error_response(e, &request_id),
+    };
+    if part_number.is_some() && !obj.part_sizes.is_empty() {
+        response = response.header("x-amz-mp-parts-count", obj.parts_count());
+    }
+    response = match part.flatten() {
+        Some((start, end)) => response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header("Content-Length", end - start + 1)
+            .header("Content-Range", format!("bytes {}-{}/{}", start, end, obj.size)),
+        None => response
             .status(StatusCode::OK)
-            .header("Content-Type", &obj.content_type)
-            .header("Content-Length", obj.size.to_string())
-            .header("ETag", generate_etag(&obj.etag))
-            .header("Last-Modified", format_http_datetime(&obj.last_modified))
-            .header("x-amz-request-id", &request_id)
-            .body(Body::empty())
-            .unwrap(),
-        Ok(None) => error_response(Error::NoSuchKey, &request_id),
-        Err(e) => error_response(e, &request_id),
+            .header("Content-Length", obj.size.to_string()),
+    };
+
+    response.body(Body::empty()).unwrap()
+}
+
+/// Object metadata, falling back to the storage backend for objects it
+/// holds that were never written through this server (gateway mode).
+/// Objects found that way are recorded in the metadata store.
+async fn find_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+) -> Result<Option<ObjectInternal>, Error> {
+    if let Some(obj) = state.metadata.get_object(bucket, key).await? {
+        return Ok(Some(obj));
+    }
+    if state.metadata.get_bucket(bucket).await?.is_none() {
+        return Ok(None);
+    }
+    let Some(stat) = state.storage.stat(bucket, key).await? else {
+        return Ok(None);
+    };
+
+    let mut obj = ObjectInternal::new(
+        bucket.to_string(),
+        key.to_string(),
+        stat.size,
+        stat.etag,
+        stat.content_type
+            .unwrap_or_else(|| "application/octet-stream".to_string()),
+    );
+    obj.last_modified = stat.last_modified;
+    consensus::put_object(state, &obj).await?;
+    state.search.object_changed(bucket, key);
+    debug!("Imported {}/{} from the storage backend", bucket, key);
+
+    Ok(Some(obj))
+}
+
+/// Error for an object that was not found, naming the version if one was asked for
+fn missing_object(version_id: Option<&str>) -> Error {
+    match version_id {
+        Some(_) => Error::NoSuchVersion,
+        None => Error::NoSuchKey,
     }
 }
 
@@ -595,31 +1009,65 @@ pub async fn get_object(
     Path((bucket, key)): Path<(String, String)>,
     headers: HeaderMap,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetObject bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Get metadata
-    let obj = match state.metadata.get_object(&bucket, &key).await {
+    let obj = match find_object(&state, &bucket, &key).await {
         Ok(Some(obj)) => obj,
         Ok(None) => return error_response(Error::NoSuchKey, &request_id),
         Err(e) => return error_response(e, &request_id),
     };
+    if let Err(e) = check_readable(&state, &obj).await {
+        return error_response(e, &request_id);
+    }
+    state.tiering.record(&obj);
+
+    let level = match placement::requested_consistency(&state, &bucket, &headers).await {
+        Ok(level) => level,
+        Err(e) => return error_response(e, &request_id),
+    };
 
     // Check for range request
     let range_header = headers.get("range").and_then(|v| v.to_str().ok());
 
-    let (data, status, content_range) = if let Some(range_str) = range_header {
+    let (data, status, content_range, consistency) = if let Some(range_str) = range_header {
         match ByteRange::parse(range_str) {
             Ok(range) => {
                 match range.resolve(obj.size) {
                     Ok((start, end)) => {
-                        match state.storage.get_range(&bucket, &key, start, end).await {
-                            Ok(data) => {
-                                let content_range = format!("bytes {}-{}/{}", start, end, obj.size);
-                                (data, StatusCode::PARTIAL_CONTENT, Some(content_range))
+                        // Stronger levels compare whole copies across replicas
+                        let local = if level == ConsistencyLevel::One {
+                            match state.storage.get_range(&bucket, &key, start, end).await {
+                                Ok(data) => Some(data),
+                                Err(Error::NoSuchKey) => None,
+                                Err(e) => return error_response(e, &request_id),
                             }
-                            Err(e) => return error_response(e, &request_id),
-                        }
+                        } else {
+                            None
+                        };
+                        let (data, consistency) = match local {
+                            Some(data) => (data, placement::local_outcome(&state, &bucket, &key)),
+                            None => match placement::read_object(&state, &bucket, &key, level).await {
+                                // A replica may hold fewer bytes than the metadata records
+                                Ok((data, _)) if end as usize >= data.len() => {
+                                    let e = Error::InternalError(format!(
+                                        "Replica of {}/{} has {} bytes, expected {}",
+                                        bucket,
+                                        key,
+                                        data.len(),
+                                        obj.size
+                                    ));
+                                    return error_response(e, &request_id);
+                                }
+                                Ok((data, consistency)) => {
+                                    (data.slice(start as usize..=end as usize), consistency)
+                                }
+                                Err(e) => return error_response(e, &request_id),
+                            },
+                        };
+                        let content_range = format!("bytes {}-{}/{}", start, end, obj.size);
+                        (data, StatusCode::PARTIAL_CONTENT, Some(content_range), consistency)
                     }
                     Err(e) => return error_response(e, &request_id),
                 }
@@ -627,8 +1075,8 @@ pub async fn get_object(
             Err(e) => return error_response(e, &request_id),
         }
     } else {
-        match state.storage.get(&bucket, &key).await {
-            Ok(data) => (data, StatusCode::OK, None),
+        match placement::read_object(&state, &bucket, &key, level).await {
+            Ok((data, consistency)) => (data, StatusCode::OK, None, consistency),
             Err(e) => return error_response(e, &request_id),
         }
     };
@@ -641,10 +1089,14 @@ pub async fn get_object(
         .header("Last-Modified", format_http_datetime(&obj.last_modified))
         .header("Accept-Ranges", "bytes")
         .header("x-amz-request-id", &request_id);
+    builder = encryption_headers(builder, &obj.encryption);
+    builder = object_lock_headers(&state, &bucket, &key, &obj.version_id, builder).await;
+    builder = storage_class_header(builder, &obj);
 
     if let Some(range) = content_range {
         builder = builder.header("Content-Range", range);
     }
+    builder = placement::consistency_headers(builder, consistency.as_ref());
 
     builder.body(Body::from(data)).unwrap()
 }
@@ -656,20 +1108,49 @@ pub async fn put_object(
     headers: HeaderMap,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("PutObject bucket={} key={} size={} request_id={}", bucket, key, body.len(), request_id);
 
     // Check bucket exists
-    match state.metadata.get_bucket(&bucket).await {
+    let bucket_info = match state.metadata.get_bucket(&bucket).await {
+        Ok(Some(info)) => info,
         Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
         Err(e) => return error_response(e, &request_id),
-        _ => {}
-    }
+    };
 
     // Validate key
     if let Err(e) = Object::validate_key(&key) {
         return error_response(e, &request_id);
     }
+    if let Err(e) = check_put_size(&state, &body) {
+        return error_response(e, &request_id);
+    }
+    if let Err(e) = check_content_md5(&headers, &body) {
+        return error_response(e, &request_id);
+    }
+    let metadata = match extract_user_metadata(&headers) {
+        Ok(metadata) => metadata,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let storage_class = match requested_storage_class(&state, &headers) {
+        Ok(storage_class) => storage_class,
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    let level = match placement::requested_consistency(&state, &bucket, &headers).await {
+        Ok(level) => level,
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    // A locked object cannot be overwritten in place
+    let bypass = governance_bypass(&state, &bucket, &key, &headers).await;
+    if let Err(e) = enforce_object_lock(&state, &bucket, &key, None, bypass).await {
+        return error_response(e, &request_id);
+    }
+    let lock = match WriteLock::from_request(&state, &bucket, &headers).await {
+        Ok(lock) => lock,
+        Err(e) => return error_response(e, &request_id),
+    };
 
     // Get content type
     let content_type = headers
@@ -682,57 +1163,63 @@ pub async fn put_object(
                 .to_string()
         });
 
-    // Check for SSE headers
-    let sse_header = headers
-        .get("x-amz-server-side-encryption")
-        .and_then(|v| v.to_str().ok());
-
-    let sse_c_key = headers
-        .get("x-amz-server-side-encryption-customer-key")
-        .and_then(|v| v.to_str().ok());
-
-    let sse_c_key_md5 = headers
-        .get("x-amz-server-side-encryption-customer-key-md5")
-        .and_then(|v| v.to_str().ok());
-
-    // Determine encryption type
-    let encryption_type = if sse_c_key.is_some() {
-        hafiz_core::types::EncryptionType::SseC
-    } else if sse_header == Some("AES256") || sse_header == Some("aws:kms") {
-        hafiz_core::types::EncryptionType::SseS3
-    } else {
-        hafiz_core::types::EncryptionType::None
+    // Encrypt the data as the SSE headers ask; SSE-S3 under the key of the
+    // account owning the bucket
+    let encryption_type = match sse::requested_encryption(&state, &headers) {
+        Ok(encryption_type) => encryption_type,
+        Err(e) => return error_response(e, &request_id),
     };
-
-    // Build encryption info (actual encryption handled by storage layer)
-    let encryption = hafiz_core::types::EncryptionInfo {
-        encryption_type,
-        encrypted_dek: None,
-        dek_nonce: None,
-        data_nonce: None,
-        sse_customer_key_md5: sse_c_key_md5.map(String::from),
+    let customer_key = match sse::customer_key(&headers) {
+        Ok(customer_key) => customer_key,
+        Err(e) => return error_response(e, &request_id),
     };
+    let size = body.len() as i64;
+    let owner = &bucket_info.owner_id;
+    let (data, encryption) =
+        match sse::seal(&state, owner, encryption_type, customer_key.as_ref(), body).await {
+            Ok(sealed) => sealed,
+            Err(e) => return error_response(e, &request_id),
+        };
 
     // Store data
-    let etag = match state.storage.put(&bucket, &key, body.clone()).await {
+    let etag = match state.storage.put(&bucket, &key, data.clone()).await {
         Ok(etag) => etag,
         Err(e) => return error_response(e, &request_id),
     };
 
     // Store metadata
-    let object = Object::new(
+    let mut object = Object::new(
         bucket.clone(),
         key.clone(),
-        body.len() as i64,
+        size,
         etag.clone(),
-        content_type,
-    ).with_encryption(encryption.clone());
+        content_type.clone(),
+    )
+    .with_encryption(encryption.clone())
+    .with_storage_class(storage_class);
+    object.metadata = metadata;
 
-    if let Err(e) = state.metadata.put_object(&object).await {
+    if let Err(e) = place_object(&state, &object).await {
+        let _ = state.storage.delete(&bucket, &key).await;
+        return error_response(e, &request_id);
+    }
+    if let Err(e) = consensus::put_object(&state, &object).await {
         // Rollback storage
         let _ = state.storage.delete(&bucket, &key).await;
         return error_response(e, &request_id);
     }
+    if let Err(e) = lock.record(&state, &bucket, &key).await {
+        error!("Failed to record Object Lock for {}/{}: {}", bucket, key, e);
+        return error_response(e, &request_id);
+    }
+    state.search.object_changed(&bucket, &key);
+
+    // Copy to the other replicas the consistency level needs
+    let consistency =
+        match placement::distribute(&state, &bucket, &key, data, &content_type, level).await {
+            Ok(consistency) => consistency,
+            Err(e) => return error_response(e, &request_id),
+        };
 
     // Build response with SSE headers
     let mut builder = Response::builder()
@@ -740,13 +1227,8 @@ pub async fn put_object(
         .header("ETag", generate_etag(&etag))
         .header("x-amz-request-id", &request_id);
 
-    // Add SSE response headers
-    if encryption.encryption_type != hafiz_core::types::EncryptionType::None {
-        builder = builder.header("x-amz-server-side-encryption", encryption.encryption_type.as_str());
-    }
-    if let Some(ref md5) = encryption.sse_customer_key_md5 {
-        builder = builder.header("x-amz-server-side-encryption-customer-key-MD5", md5);
-    }
+    builder = encryption_headers(builder, &encryption);
+    builder = placement::consistency_headers(builder, consistency.as_ref());
 
     builder.body(Body::empty()).unwrap()
 }
@@ -756,18 +1238,23 @@ pub async fn delete_object(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("DeleteObject bucket={} key={} request_id={}", bucket, key, request_id);
 
+    if let Err(e) = enforce_object_lock(&state, &bucket, &key, None, false).await {
+        return error_response(e, &request_id);
+    }
+
     // Delete from storage
     if let Err(e) = state.storage.delete(&bucket, &key).await {
         error!("Failed to delete object storage: {}", e);
     }
 
     // Delete from metadata
-    if let Err(e) = state.metadata.delete_object(&bucket, &key).await {
+    if let Err(e) = consensus::delete_object(&state, &bucket, &key).await {
         return error_response(e, &request_id);
     }
+    state.search.object_changed(&bucket, &key);
 
     Response::builder()
         .status(StatusCode::NO_CONTENT)
@@ -784,44 +1271,50 @@ pub async fn copy_object(
     Path((dest_bucket, dest_key)): Path<(String, String)>,
     headers: HeaderMap,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
 
-    // Get copy source header
-    let copy_source = match headers.get("x-amz-copy-source") {
-        Some(v) => v.to_str().unwrap_or(""),
-        None => return error_response(Error::InvalidRequest("Missing x-amz-copy-source header".into()), &request_id),
+    let (src_bucket, src_key) = match copy_source::parse_copy_source(&headers) {
+        Ok(source) => source,
+        Err(e) => return error_response(e, &request_id),
     };
 
-    info!("CopyObject source={} dest={}/{} request_id={}", copy_source, dest_bucket, dest_key, request_id);
-
-    // Parse source: /bucket/key or bucket/key
-    let source = copy_source.trim_start_matches('/');
-    let parts: Vec<&str> = source.splitn(2, '/').collect();
-    if parts.len() != 2 {
-        return error_response(Error::InvalidRequest("Invalid copy source format".into()), &request_id);
-    }
-    let (src_bucket, src_key) = (parts[0], parts[1]);
-
-    // URL decode the key
-    let src_key = urlencoding::decode(src_key).unwrap_or_else(|_| src_key.into()).to_string();
+    info!("CopyObject source={}/{} dest={}/{} request_id={}", src_bucket, src_key, dest_bucket, dest_key, request_id);
 
     // Check destination bucket exists
-    match state.metadata.get_bucket(&dest_bucket).await {
+    let dest_bucket_info = match state.metadata.get_bucket(&dest_bucket).await {
+        Ok(Some(info)) => info,
         Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
         Err(e) => return error_response(e, &request_id),
-        _ => {}
+    };
+
+    let user_metadata = match extract_user_metadata(&headers) {
+        Ok(metadata) => metadata,
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    let bypass = governance_bypass(&state, &dest_bucket, &dest_key, &headers).await;
+    if let Err(e) = enforce_object_lock(&state, &dest_bucket, &dest_key, None, bypass).await {
+        return error_response(e, &request_id);
     }
+    let lock = match WriteLock::from_request(&state, &dest_bucket, &headers).await {
+        Ok(lock) => lock,
+        Err(e) => return error_response(e, &request_id),
+    };
 
     // Get source object metadata
-    let src_object = match state.metadata.get_object(src_bucket, &src_key).await {
+    let src_object = match state.metadata.get_object(&src_bucket, &src_key).await {
         Ok(Some(obj)) => obj,
         Ok(None) => return error_response(Error::NoSuchKey, &request_id),
         Err(e) => return error_response(e, &request_id),
     };
-
-    // Read source data
-    let data = match state.storage.get(src_bucket, &src_key).await {
-        Ok(data) => data,
+    if let Err(e) = copy_source::check_copy_source_conditions(&headers, &src_object) {
+        return error_response(e, &request_id);
+    }
+    if let Err(e) = check_readable(&state, &src_object).await {
+        return error_response(e, &request_id);
+    }
+    let storage_class = match requested_storage_class(&state, &headers) {
+        Ok(storage_class) => storage_class,
         Err(e) => return error_response(e, &request_id),
     };
 
@@ -831,6 +1324,57 @@ pub async fn copy_object(
         .and_then(|v| v.to_str().ok())
         .unwrap_or("COPY");
 
+    // A copy onto itself only changes metadata or the storage class, which
+    // needs no data copy
+    if src_bucket == dest_bucket && src_key == dest_key {
+        let class_change = headers.contains_key("x-amz-storage-class");
+        if metadata_directive != "REPLACE" && !class_change {
+            return error_response(
+                Error::InvalidRequest(
+                    "This copy request is illegal because it is trying to copy an object to itself without changing the object's metadata".into(),
+                ),
+                &request_id,
+            );
+        }
+        if class_change {
+            if let Err(e) = change_storage_class(&state, &src_object, storage_class).await {
+                return error_response(e, &request_id);
+            }
+        }
+        if metadata_directive != "REPLACE" {
+            let xml = xml::copy_object_response(&src_object.etag, &src_object.last_modified);
+            return copy_response(xml, &src_object.encryption, &request_id);
+        }
+        return replace_object_metadata(&state, src_object, &headers, user_metadata, &request_id)
+            .await;
+    }
+
+    // Read source data, decrypted; the copy is encrypted as its own
+    // headers ask
+    let source_key = match sse::copy_source_customer_key(&headers) {
+        Ok(source_key) => source_key,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let data = match sse::read_object(&state, &src_object, &src_key, None, source_key.as_ref()).await {
+        Ok(data) => data,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let encryption_type = match sse::requested_encryption(&state, &headers) {
+        Ok(encryption_type) => encryption_type,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let customer_key = match sse::customer_key(&headers) {
+        Ok(customer_key) => customer_key,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let size = data.len() as i64;
+    let owner = &dest_bucket_info.owner_id;
+    let (data, encryption) =
+        match sse::seal(&state, owner, encryption_type, customer_key.as_ref(), data).await {
+            Ok(sealed) => sealed,
+            Err(e) => return error_response(e, &request_id),
+        };
+
     let (content_type, metadata) = if metadata_directive == "REPLACE" {
         // Use new metadata from headers
         let ct = headers
@@ -838,7 +1382,7 @@ pub async fn copy_object(
             .and_then(|v| v.to_str().ok())
             .map(String::from)
             .unwrap_or_else(|| src_object.content_type.clone());
-        (ct, extract_user_metadata(&headers))
+        (ct, user_metadata)
     } else {
         // Copy metadata from source
         (src_object.content_type.clone(), src_object.metadata.clone())
@@ -851,41 +1395,107 @@ pub async fn copy_object(
     };
 
     // Create destination object metadata
-    let mut dest_object = Object::new(
+    let mut dest_object = ObjectInternal::new(
         dest_bucket.clone(),
         dest_key.clone(),
-        data.len() as i64,
+        size,
         etag.clone(),
         content_type,
     );
     dest_object.metadata = metadata;
+    dest_object.encryption = encryption;
+    let dest_object = dest_object.with_storage_class(storage_class);
 
-    if let Err(e) = state.metadata.put_object(&dest_object).await {
+    if let Err(e) = place_object(&state, &dest_object).await {
         let _ = state.storage.delete(&dest_bucket, &dest_key).await;
         return error_response(e, &request_id);
     }
+    if let Err(e) = consensus::put_object(&state, &dest_object).await {
+        let _ = state.storage.delete(&dest_bucket, &dest_key).await;
+        return error_response(e, &request_id);
+    }
+    if let Err(e) = lock.record(&state, &dest_bucket, &dest_key).await {
+        error!("Failed to record Object Lock for {}/{}: {}", dest_bucket, dest_key, e);
+        return error_response(e, &request_id);
+    }
+    state.search.object_changed(&dest_bucket, &dest_key);
 
     let xml = xml::copy_object_response(&etag, &dest_object.last_modified);
-    success_response(StatusCode::OK, xml, &request_id)
+    copy_response(xml, &dest_object.encryption, &request_id)
+}
+
+fn copy_response(xml: String, encryption: &EncryptionInfo, request_id: &str) -> Response {
+    let builder = Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", "application/xml")
+        .header("x-amz-request-id", request_id);
+    encryption_headers(builder, encryption)
+        .body(Body::from(xml))
+        .unwrap()
+}
+
+/// CopyObject onto itself with `REPLACE`: rewrite the metadata row only
+async fn replace_object_metadata(
+    state: &AppState,
+    object: ObjectInternal,
+    headers: &HeaderMap,
+    metadata: std::collections::HashMap<String, String>,
+    request_id: &str,
+) -> Response {
+    let content_type = headers
+        .get("content-type")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or(&object.content_type);
+
+    match state
+        .metadata
+        .update_object_metadata(&object.bucket, &object.key, &object.version_id, content_type, &metadata)
+        .await
+    {
+        Ok(last_modified) => {
+            debug!("Replaced metadata of {}/{} in place", object.bucket, object.key);
+            state.search.object_changed(&object.bucket, &object.key);
+            let xml = xml::copy_object_response(&object.etag, &last_modified);
+            copy_response(xml, &object.encryption, request_id)
+        }
+        Err(e) => error_response(e, request_id),
+    }
 }
 
-/// Extract user metadata from headers (x-amz-meta-*)
-fn extract_user_metadata(headers: &HeaderMap) -> std::collections::HashMap<String, String> {
+/// Extract user metadata from headers (x-amz-meta-*), rejecting values
+/// that are not US-ASCII and requests over the header or metadata limits
+fn extract_user_metadata(
+    headers: &HeaderMap,
+) -> Result<std::collections::HashMap<String, String>, Error> {
+    let header_size: usize = headers
+        .iter()
+        .map(|(name, value)| name.as_str().len() + value.len())
+        .sum();
+    if header_size > MAX_REQUEST_HEADER_SIZE {
+        return Err(Error::MetadataTooLarge);
+    }
+
     let mut metadata = std::collections::HashMap::new();
     for (name, value) in headers.iter() {
         let name_str = name.as_str().to_lowercase();
-        if name_str.starts_with("x-amz-meta-") {
-            if let Ok(v) = value.to_str() {
-                let key = name_str.strip_prefix("x-amz-meta-").unwrap().to_string();
-                metadata.insert(key, v.to_string());
-            }
+        if let Some(key) = name_str.strip_prefix("x-amz-meta-") {
+            let v = value.to_str().map_err(|_| {
+                Error::InvalidArgument(format!("Value of header {} must be US-ASCII", name_str))
+            })?;
+            metadata.insert(key.to_string(), v.to_string());
         }
     }
-    metadata
+    ObjectInternal::validate_metadata(&metadata)?;
+    Ok(metadata)
 }
 
+/// Most keys one DeleteObjects request may name
+const MAX_DELETE_OBJECTS: usize = 1000;
+
+/// Lock checks and storage deletions a DeleteObjects request runs at once
+const DELETE_OBJECTS_CONCURRENCY: usize = 32;
+
 /// DELETE multiple objects (POST /?delete)
-#[derive(Debug, Deserialize)]
 #[derive(Debug, Deserialize, Default)]
 pub struct DeleteObjectsQuery {
     delete: Option<String>,
@@ -894,52 +1504,128 @@ pub struct DeleteObjectsQuery {
 pub async fn delete_objects(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
+    headers: HeaderMap,
     Query(_params): Query<DeleteObjectsQuery>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("DeleteObjects bucket={} request_id={}", bucket, request_id);
 
+    if let Err(e) = check_content_md5(&headers, &body) {
+        return error_response(e, &request_id);
+    }
+
     // Parse XML body
     let delete_request = match xml::parse_delete_objects(&body) {
         Ok(req) => req,
         Err(e) => return error_response(Error::MalformedXML(e.to_string()), &request_id),
     };
+    let count = delete_request.objects.len();
+    if count == 0 || count > MAX_DELETE_OBJECTS {
+        return error_response(
+            Error::MalformedXML(format!(
+                "DeleteObjects takes 1 to {} keys, got {}",
+                MAX_DELETE_OBJECTS, count
+            )),
+            &request_id,
+        );
+    }
 
+    let bucket_info = match state.metadata.get_bucket(&bucket).await {
+        Ok(Some(b)) => b,
+        Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
+        Err(e) => return error_response(e, &request_id),
+    };
+    let versioned = bucket_info.versioning.is_versioning_enabled();
     let quiet = delete_request.quiet.unwrap_or(false);
+
+    // Check locks, a bounded number of keys at a time. Delete markers
+    // leave the data, locked or not, in place.
+    let checked: Vec<_> = stream::iter(delete_request.objects)
+        .map(|obj| {
+            let (state, bucket, headers) = (&state, &bucket, &headers);
+            async move {
+                let result = async {
+                    if obj.version_id.is_none() && versioned {
+                        return Ok(());
+                    }
+                    let bypass = governance_bypass(state, bucket, &obj.key, headers).await;
+                    enforce_object_lock(state, bucket, &obj.key, obj.version_id.as_deref(), bypass).await
+                }
+                .await;
+                (obj, result)
+            }
+        })
+        .buffered(DELETE_OBJECTS_CONCURRENCY)
+        .collect()
+        .await;
+
     let mut deleted = Vec::new();
     let mut errors = Vec::new();
+    let mut pending = Vec::new();
+    for (obj, result) in checked {
+        match result {
+            Ok(()) => pending.push((obj.key, obj.version_id)),
+            Err(e) => errors.push(xml::DeleteError {
+                key: obj.key,
+                version_id: obj.version_id,
+                code: e.code().to_string(),
+                message: e.to_string(),
+            }),
+        }
+    }
 
-    for obj in delete_request.objects {
-        let key = obj.key;
-        let version_id = obj.version_id;
+    // One transaction for all the metadata, and only then the data, so a
+    // failed update leaves every object readable
+    if !pending.is_empty() {
+        match consensus::delete_objects(&state, &bucket, &pending, versioned).await {
+            Ok(outcomes) => {
+                let removed = pending
+                    .iter()
+                    .filter(|(_, version_id)| version_id.is_some() || !versioned)
+                    .map(|(key, version_id)| {
+                        storage_key(key, version_id.as_deref().unwrap_or(NULL_VERSION_ID))
+                    });
+                stream::iter(removed)
+                    .for_each_concurrent(DELETE_OBJECTS_CONCURRENCY, |storage_key| {
+                        let (state, bucket) = (&state, &bucket);
+                        async move {
+                            match state.storage.delete(bucket, &storage_key).await {
+                                Ok(()) | Err(Error::NoSuchKey) => {}
+                                Err(e) => error!(
+                                    "Failed to delete data of {}/{}: {}",
+                                    bucket, storage_key, e
+                                ),
+                            }
+                        }
+                    })
+                    .await;
 
-        match state.storage.delete(&bucket, &key).await {
-            Ok(_) => {
-                if let Err(e) = state.metadata.delete_object(&bucket, &key).await {
+                for (key, _) in &pending {
+                    state.search.object_changed(&bucket, key);
+                }
+                if !quiet {
+                    for ((key, version_id), outcome) in pending.into_iter().zip(outcomes) {
+                        deleted.push(xml::DeletedObject {
+                            key,
+                            version_id,
+                            delete_marker: outcome.delete_marker,
+                            delete_marker_version_id: outcome.delete_marker_version_id,
+                        });
+                    }
+                }
+            }
+            Err(e) => {
+                error!("DeleteObjects metadata update failed: {}", e);
+                for (key, version_id) in pending {
                     errors.push(xml::DeleteError {
-                        key: key.clone(),
-                        version_id: version_id.clone(),
-                        code: e.code().to_string(),
-                        message: e.to_string(),
-                    });
-                } else if !quiet {
-                    deleted.push(xml::DeletedObject {
                         key,
                         version_id,
-                        delete_marker: false,
-                        delete_marker_version_id: None,
+                        code: e.code().to_string(),
+                        message: e.to_string(),
                     });
                 }
             }
-            Err(e) => {
-                errors.push(xml::DeleteError {
-                    key,
-                    version_id,
-                    code: e.code().to_string(),
-                    message: e.to_string(),
-                });
-            }
         }
     }
 
@@ -961,7 +1647,7 @@ pub async fn create_multipart_upload(
     headers: HeaderMap,
     Query(_params): Query<CreateMultipartQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("CreateMultipartUpload bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Check bucket exists
@@ -988,13 +1674,42 @@ pub async fn create_multipart_upload(
         });
 
     // Extract user metadata
-    let metadata = extract_user_metadata(&headers);
+    let metadata = match extract_user_metadata(&headers) {
+        Ok(metadata) => metadata,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let storage_class = match requested_storage_class(&state, &headers) {
+        Ok(storage_class) => storage_class,
+        Err(e) => return error_response(e, &request_id),
+    };
+    // The object is encrypted when the upload completes
+    let encryption = match sse::requested_encryption(&state, &headers) {
+        Ok(EncryptionType::SseC) => {
+            return error_response(
+                Error::NotImplemented("SSE-C is not supported for multipart uploads".into()),
+                &request_id,
+            );
+        }
+        Ok(encryption) => encryption,
+        Err(e) => return error_response(e, &request_id),
+    };
 
     // Create multipart upload
-    match state.metadata.create_multipart_upload(&bucket, &key, &content_type, &metadata).await {
+    match state
+        .metadata
+        .create_multipart_upload(&bucket, &key, &content_type, &metadata, storage_class.as_str(), encryption)
+        .await
+    {
         Ok(upload_id) => {
             let xml = xml::initiate_multipart_upload_response(&bucket, &key, &upload_id);
-            success_response(StatusCode::OK, xml, &request_id)
+            let mut response = success_response(StatusCode::OK, xml, &request_id);
+            if encryption != EncryptionType::None {
+                response.headers_mut().insert(
+                    "x-amz-server-side-encryption",
+                    HeaderValue::from_static(encryption.as_str()),
+                );
+            }
+            response
         }
         Err(e) => error_response(e, &request_id),
     }
@@ -1014,21 +1729,31 @@ pub async fn upload_part(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
     Query(params): Query<UploadPartQuery>,
+    headers: HeaderMap,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!(
         "UploadPart bucket={} key={} uploadId={} partNumber={} size={} request_id={}",
         bucket, key, params.upload_id, params.part_number, body.len(), request_id
     );
 
     // Validate part number (1-10000)
-    if params.part_number < 1 || params.part_number > 10000 {
+    if params.part_number < 1 || params.part_number as u32 > hafiz_core::MAX_PARTS {
         return error_response(
-            Error::InvalidArgument("Part number must be between 1 and 10000".into()),
+            Error::InvalidArgument(format!(
+                "Part number must be between 1 and {}",
+                hafiz_core::MAX_PARTS
+            )),
             &request_id,
         );
     }
+    if let Err(e) = check_put_size(&state, &body) {
+        return error_response(e, &request_id);
+    }
+    if let Err(e) = check_content_md5(&headers, &body) {
+        return error_response(e, &request_id);
+    }
 
     // Verify upload exists
     match state.metadata.get_multipart_upload(&bucket, &key, &params.upload_id).await {
@@ -1038,8 +1763,11 @@ pub async fn upload_part(
     }
 
     // Store part data
-    let part_key = format!("{}/.parts/{}/{}", key, params.upload_id, params.part_number);
-    let etag = match state.storage.put(&bucket, &part_key, body.clone()).await {
+    let etag = match state
+        .storage
+        .put_part(&bucket, &key, &params.upload_id, params.part_number, body.clone())
+        .await
+    {
         Ok(etag) => etag,
         Err(e) => return error_response(e, &request_id),
     };
@@ -1051,7 +1779,10 @@ pub async fn upload_part(
         body.len() as i64,
         &etag,
     ).await {
-        let _ = state.storage.delete(&bucket, &part_key).await;
+        let _ = state
+            .storage
+            .abort_parts(&bucket, &key, &params.upload_id, &[params.part_number])
+            .await;
         return error_response(e, &request_id);
     }
 
@@ -1073,10 +1804,11 @@ pub struct CompleteMultipartQuery {
 pub async fn complete_multipart_upload(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
     Query(params): Query<CompleteMultipartQuery>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!(
         "CompleteMultipartUpload bucket={} key={} uploadId={} request_id={}",
         bucket, key, params.upload_id, request_id
@@ -1101,6 +1833,16 @@ pub async fn complete_multipart_upload(
         Err(e) => return error_response(e, &request_id),
     };
 
+    if completion.parts.len() > hafiz_core::MAX_PARTS as usize {
+        return error_response(
+            Error::InvalidArgument(format!(
+                "An upload can have at most {} parts",
+                hafiz_core::MAX_PARTS
+            )),
+            &request_id,
+        );
+    }
+
     // Validate parts match
     if completion.parts.len() != parts.len() {
         return error_response(
@@ -1109,8 +1851,8 @@ pub async fn complete_multipart_upload(
         );
     }
 
-    // Concatenate all parts
-    let mut final_data = Vec::new();
+    // Check the listed parts against the stored ones
+    let mut part_numbers = Vec::new();
     let mut part_etags = Vec::new();
 
     for (i, completed_part) in completion.parts.iter().enumerate() {
@@ -1118,15 +1860,8 @@ pub async fn complete_multipart_upload(
 
         match stored_part {
             Some(sp) if sp.part_number == completed_part.part_number => {
-                // Read part data
-                let part_key = format!("{}/.parts/{}/{}", key, params.upload_id, completed_part.part_number);
-                match state.storage.get(&bucket, &part_key).await {
-                    Ok(data) => {
-                        final_data.extend_from_slice(&data);
-                        part_etags.push(sp.etag.clone());
-                    }
-                    Err(e) => return error_response(e, &request_id),
-                }
+                part_numbers.push(sp.part_number);
+                part_etags.push(sp.etag.clone());
             }
             _ => {
                 return error_response(
@@ -1137,40 +1872,111 @@ pub async fn complete_multipart_upload(
         }
     }
 
-    // Calculate final ETag (MD5 of concatenated part MD5s + "-" + part count)
-    let final_etag = hafiz_crypto::multipart_etag(&part_etags, parts.len());
+    // Every part but the last must reach the minimum part size
+    let min_part_size = state.config.storage.min_part_size;
+    if let Some(small) = parts[..parts.len().saturating_sub(1)]
+        .iter()
+        .find(|p| (p.size as u64) < min_part_size)
+    {
+        return error_response(
+            Error::EntityTooSmall(format!(
+                "part {} is {} bytes, parts but the last need at least {}",
+                small.part_number, small.size, min_part_size
+            )),
+            &request_id,
+        );
+    }
+    let total_size: u64 = parts.iter().map(|p| p.size as u64).sum();
+    if total_size > state.config.storage.max_object_size {
+        return error_response(Error::EntityTooLarge, &request_id);
+    }
 
-    // Store final object
-    if let Err(e) = state.storage.put(&bucket, &key, Bytes::from(final_data.clone())).await {
+    let bypass = governance_bypass(&state, &bucket, &key, &headers).await;
+    if let Err(e) = enforce_object_lock(&state, &bucket, &key, None, bypass).await {
         return error_response(e, &request_id);
     }
+    let lock = match WriteLock::from_request(&state, &bucket, &headers).await {
+        Ok(lock) => lock,
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    // Calculate final ETag (MD5 of concatenated part MD5s + "-" + part count)
+    let final_etag = hafiz_crypto::multipart_etag(&part_etags, parts.len());
+
+    // Assemble the final object; the backend discards the parts
+    let size = match state
+        .storage
+        .complete_parts(&bucket, &key, &params.upload_id, &part_numbers)
+        .await
+    {
+        Ok(size) => size,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let encryption = match seal_assembled(&state, &bucket, &key, upload.encryption).await {
+        Ok(encryption) => encryption,
+        Err(e) => {
+            let _ = state.storage.delete(&bucket, &key).await;
+            return error_response(e, &request_id);
+        }
+    };
 
     // Create object metadata
-    let mut object = Object::new(
+    let mut object = ObjectInternal::new(
         bucket.clone(),
         key.clone(),
-        final_data.len() as i64,
+        size,
         final_etag.clone(),
         upload.content_type.clone(),
     );
     object.metadata = upload.metadata.clone();
+    object.part_sizes = parts.iter().map(|p| p.size).collect();
+    object.storage_class = upload.storage_class.clone();
+    object.encryption = encryption;
 
-    if let Err(e) = state.metadata.put_object(&object).await {
+    if let Err(e) = place_object(&state, &object).await {
         let _ = state.storage.delete(&bucket, &key).await;
         return error_response(e, &request_id);
     }
-
-    // Clean up parts
-    for part in &parts {
-        let part_key = format!("{}/.parts/{}/{}", key, params.upload_id, part.part_number);
-        let _ = state.storage.delete(&bucket, &part_key).await;
+    if let Err(e) = consensus::put_object(&state, &object).await {
+        let _ = state.storage.delete(&bucket, &key).await;
+        return error_response(e, &request_id);
+    }
+    if let Err(e) = lock.record(&state, &bucket, &key).await {
+        error!("Failed to record Object Lock for {}/{}: {}", bucket, key, e);
+        return error_response(e, &request_id);
     }
+    state.search.object_changed(&bucket, &key);
 
     // Delete upload record
     let _ = state.metadata.delete_multipart_upload(&params.upload_id).await;
 
     let xml = xml::complete_multipart_upload_response(&bucket, &key, &final_etag);
-    success_response(StatusCode::OK, xml, &request_id)
+    let mut response = success_response(StatusCode::OK, xml, &request_id);
+    if object.encryption.is_encrypted() {
+        response.headers_mut().insert(
+            "x-amz-server-side-encryption",
+            HeaderValue::from_static(object.encryption.encryption_type.as_str()),
+        );
+    }
+    response
+}
+
+/// Encrypt the just-assembled data of a multipart upload in place, as the
+/// upload asked for when it was created
+async fn seal_assembled(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    encryption_type: EncryptionType,
+) -> Result<EncryptionInfo, Error> {
+    if encryption_type == EncryptionType::None {
+        return Ok(EncryptionInfo::none());
+    }
+    let owner = state.metadata.get_bucket(bucket).await?.ok_or(Error::NoSuchBucket)?.owner_id;
+    let data = state.storage.get(bucket, key).await?;
+    let (data, encryption) = sse::seal(state, &owner, encryption_type, None, data).await?;
+    state.storage.put(bucket, key, data).await?;
+    Ok(encryption)
 }
 
 #[derive(Debug, Deserialize, Default)]
@@ -1185,7 +1991,7 @@ pub async fn abort_multipart_upload(
     Path((bucket, key)): Path<(String, String)>,
     Query(params): Query<AbortMultipartQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!(
         "AbortMultipartUpload bucket={} key={} uploadId={} request_id={}",
         bucket, key, params.upload_id, request_id
@@ -1193,10 +1999,11 @@ pub async fn abort_multipart_upload(
 
     // Get all parts to clean up
     if let Ok(parts) = state.metadata.list_upload_parts(&params.upload_id).await {
-        for part in parts {
-            let part_key = format!("{}/.parts/{}/{}", key, params.upload_id, part.part_number);
-            let _ = state.storage.delete(&bucket, &part_key).await;
-        }
+        let part_numbers: Vec<i32> = parts.iter().map(|p| p.part_number).collect();
+        let _ = state
+            .storage
+            .abort_parts(&bucket, &key, &params.upload_id, &part_numbers)
+            .await;
     }
 
     // Delete upload record
@@ -1227,7 +2034,7 @@ pub async fn list_parts(
     Path((bucket, key)): Path<(String, String)>,
     Query(params): Query<ListPartsQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!(
         "ListParts bucket={} key={} uploadId={} request_id={}",
         bucket, key, params.upload_id, request_id
@@ -1295,6 +2102,8 @@ pub struct ListMultipartUploadsQuery {
     key_marker: Option<String>,
     #[serde(rename = "upload-id-marker")]
     upload_id_marker: Option<String>,
+    #[serde(rename = "encoding-type")]
+    encoding_type: Option<String>,
 }
 
 /// List multipart uploads (GET /bucket?uploads)
@@ -1303,7 +2112,7 @@ pub async fn list_multipart_uploads(
     Path(bucket): Path<String>,
     Query(params): Query<ListMultipartUploadsQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("ListMultipartUploads bucket={} request_id={}", bucket, request_id);
 
     // Check bucket exists
@@ -1313,16 +2122,37 @@ pub async fn list_multipart_uploads(
         _ => {}
     }
 
+    if let Err(e) = check_encoding_type(params.encoding_type.as_deref()) {
+        return error_response(e, &request_id);
+    }
+
     let max_uploads = params.max_uploads.unwrap_or(1000).min(1000);
 
+    let filter = ListFilter {
+        operation: "ListMultipartUploads",
+        bucket: &bucket,
+        prefix: params.prefix.as_deref(),
+        delimiter: params.delimiter.as_deref(),
+    };
+    let position = params.key_marker.as_deref().map(|m| {
+        state
+            .list_tokens
+            .decode_marker(&filter, m, params.upload_id_marker.as_deref())
+    });
+
     match state.metadata.list_multipart_uploads(
         &bucket,
         params.prefix.as_deref(),
-        params.key_marker.as_deref(),
-        params.upload_id_marker.as_deref(),
+        position.as_ref().map(|p| p.key.as_str()),
+        position.as_ref().and_then(|p| p.version.as_deref()),
         max_uploads,
     ).await {
         Ok((uploads, is_truncated)) => {
+            let next = uploads.last().filter(|_| is_truncated);
+            let next_key_marker = next
+                .map(|u| state.list_tokens.encode(&filter, &u.key, Some(&u.upload_id)));
+            let next_upload_id_marker = next.map(|u| u.upload_id.clone());
+
             // Convert to UploadInfo for XML response
             let upload_infos: Vec<xml::UploadInfo> = uploads
                 .into_iter()
@@ -1344,6 +2174,9 @@ pub async fn list_multipart_uploads(
                 max_uploads,
                 is_truncated,
                 &upload_infos,
+                next_key_marker.as_deref(),
+                next_upload_id_marker.as_deref(),
+                params.encoding_type.as_deref(),
             );
             success_response(StatusCode::OK, xml, &request_id)
         }
@@ -1358,7 +2191,7 @@ pub async fn get_bucket_versioning(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetBucketVersioning bucket={} request_id={}", bucket, request_id);
 
     match state.metadata.get_bucket(&bucket).await {
@@ -1377,7 +2210,7 @@ pub async fn put_bucket_versioning(
     Path(bucket): Path<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("PutBucketVersioning bucket={} request_id={}", bucket, request_id);
 
     // Check bucket exists
@@ -1401,7 +2234,7 @@ pub async fn put_bucket_versioning(
         );
     }
 
-    if let Err(e) = state.metadata.set_bucket_versioning(&bucket, status).await {
+    if let Err(e) = consensus::set_bucket_versioning(&state, &bucket, status).await {
         return error_response(e, &request_id);
     }
 
@@ -1425,6 +2258,8 @@ pub struct ListObjectVersionsQuery {
     key_marker: Option<String>,
     #[serde(rename = "version-id-marker")]
     version_id_marker: Option<String>,
+    #[serde(rename = "encoding-type")]
+    encoding_type: Option<String>,
 }
 
 /// GET list object versions
@@ -1433,7 +2268,7 @@ pub async fn list_object_versions(
     Path(bucket): Path<String>,
     Query(params): Query<ListObjectVersionsQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("ListObjectVersions bucket={} request_id={}", bucket, request_id);
 
     // Check bucket exists
@@ -1443,21 +2278,49 @@ pub async fn list_object_versions(
         _ => {}
     }
 
+    if let Err(e) = check_encoding_type(params.encoding_type.as_deref()) {
+        return error_response(e, &request_id);
+    }
+
     let max_keys = params.max_keys.unwrap_or(1000).min(1000);
 
+    // Flat-namespace buckets list keys only; the delimiter is ignored
+    let delimiter = match state.metadata.get_bucket_namespace(&bucket).await {
+        Ok(mode) if mode.is_flat() => None,
+        Ok(_) => params.delimiter.as_deref(),
+        Err(e) => return error_response(e, &request_id),
+    };
+
+    let filter = ListFilter {
+        operation: "ListObjectVersions",
+        bucket: &bucket,
+        prefix: params.prefix.as_deref(),
+        delimiter,
+    };
+    let position = params.key_marker.as_deref().map(|m| {
+        state
+            .list_tokens
+            .decode_marker(&filter, m, params.version_id_marker.as_deref())
+    });
+
     match state.metadata.list_object_versions(
         &bucket,
         params.prefix.as_deref(),
-        params.delimiter.as_deref(),
+        delimiter,
         max_keys,
-        params.key_marker.as_deref(),
-        params.version_id_marker.as_deref(),
+        position.as_ref().map(|p| p.key.as_str()),
+        position.as_ref().and_then(|p| p.version.as_deref()),
     ).await {
         Ok((versions, delete_markers, common_prefixes, is_truncated, next_key_marker, next_version_id_marker)) => {
+            let next_key_marker = next_key_marker.map(|k| {
+                state
+                    .list_tokens
+                    .encode(&filter, &k, next_version_id_marker.as_deref())
+            });
             let xml = xml::list_object_versions_response(
                 &bucket,
                 params.prefix.as_deref(),
-                params.delimiter.as_deref(),
+                delimiter,
                 params.key_marker.as_deref(),
                 params.version_id_marker.as_deref(),
                 max_keys,
@@ -1467,6 +2330,7 @@ pub async fn list_object_versions(
                 &common_prefixes,
                 next_key_marker.as_deref(),
                 next_version_id_marker.as_deref(),
+                params.encoding_type.as_deref(),
             );
             success_response(StatusCode::OK, xml, &request_id)
         }
@@ -1482,8 +2346,9 @@ pub async fn get_object_versioned(
     Path((bucket, key)): Path<(String, String)>,
     headers: HeaderMap,
     version_id: Option<String>,
+    part_number: Option<i32>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!(
         "GetObject bucket={} key={} version={:?} request_id={}",
         bucket, key, version_id, request_id
@@ -1492,51 +2357,76 @@ pub async fn get_object_versioned(
     // Get object metadata (with optional version)
     let object = match state.metadata.get_object_version(&bucket, &key, version_id.as_deref()).await {
         Ok(Some(obj)) => obj,
-        Ok(None) => return error_response(Error::NoSuchKey, &request_id),
+        Ok(None) => return error_response(missing_object(version_id.as_deref()), &request_id),
         Err(e) => return error_response(e, &request_id),
     };
 
-    // Check if it's a delete marker
+    // A current delete marker reads as a missing key; asking for the
+    // marker itself by version ID is not allowed
     if object.is_delete_marker {
-        return Response::builder()
-            .status(StatusCode::NOT_FOUND)
-            .header("x-amz-request-id", &request_id)
-            .header("x-amz-version-id", &object.version_id)
-            .header("x-amz-delete-marker", "true")
-            .body(Body::empty())
-            .unwrap();
+        let err = match version_id {
+            Some(_) => Error::MethodNotAllowed("The version is a delete marker".into()),
+            None => Error::NoSuchKey,
+        };
+        let mut response = error_response(err, &request_id);
+        if let Ok(value) = HeaderValue::from_str(&object.version_id) {
+            response.headers_mut().insert("x-amz-version-id", value);
+        }
+        response
+            .headers_mut()
+            .insert("x-amz-delete-marker", HeaderValue::from_static("true"));
+        return response;
+    }
+    if let Err(e) = check_readable(&state, &object).await {
+        return error_response(e, &request_id);
     }
+    state.tiering.record(&object);
 
-    // Check for Range header
-    let range = headers
-        .get("range")
-        .and_then(|v| v.to_str().ok())
-        .map(|r| hafiz_core::types::ByteRange::parse(r));
+    // Check for Range header, or the part asked for with partNumber
+    let range = match part_number {
+        Some(n) => match requested_part(&object, n, &headers) {
+            Ok(part) => part.map(Ok),
+            Err(e) => return error_response(e, &request_id),
+        },
+        None => headers
+            .get("range")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|r| hafiz_core::types::ByteRange::parse(r).ok())
+            .map(|r| r.resolve(object.size)),
+    };
+    let parts_count = (part_number.is_some() && !object.part_sizes.is_empty())
+        .then(|| object.parts_count());
 
     // Determine storage key based on version
-    let storage_key = if object.version_id == "null" {
-        key.clone()
-    } else {
-        format!("{}?versionId={}", key, object.version_id)
-    };
+    let storage_key = storage_key(&key, &object.version_id);
+
+    if let Some(pipeline) = state.transforms.find(&bucket, &key, &object.content_type) {
+        return transformed_object_response(&state, pipeline, &object, &storage_key, &headers, &request_id).await;
+    }
 
     // Get object data
-    let data = if let Some(Ok(byte_range)) = range {
-        match byte_range.resolve(object.size) {
+    let data = if let Some(range) = range {
+        match range {
             Ok((start, end)) => {
-                match state.storage.get_range(&bucket, &storage_key, start as u64, end as u64).await {
+                // Large ranges (video seeking) are streamed, not buffered
+                match object_data(&state, &object, &storage_key, Some((start, end)), &headers).await {
                     Ok(data) => {
-                        return Response::builder()
+                        let mut response = Response::builder()
                             .status(StatusCode::PARTIAL_CONTENT)
                             .header("Content-Type", &object.content_type)
-                            .header("Content-Length", data.len())
+                            .header("Content-Length", end - start + 1)
                             .header("Content-Range", format!("bytes {}-{}/{}", start, end, object.size))
                             .header("ETag", format!("\"{}\"", object.etag))
                             .header("Last-Modified", format_http_datetime(&object.last_modified))
                             .header("x-amz-request-id", &request_id)
-                            .header("x-amz-version-id", &object.version_id)
-                            .body(Body::from(data))
-                            .unwrap();
+                            .header("x-amz-version-id", &object.version_id);
+                        if let Some(count) = parts_count {
+                            response = response.header("x-amz-mp-parts-count", count);
+                        }
+                        response = encryption_headers(response, &object.encryption);
+                        response = object_lock_headers(&state, &bucket, &key, &object.version_id, response).await;
+                        response = storage_class_header(response, &object);
+                        return response.body(Body::from_stream(data)).unwrap();
                     }
                     Err(e) => return error_response(e, &request_id),
                 }
@@ -1544,7 +2434,7 @@ pub async fn get_object_versioned(
             Err(e) => return error_response(e, &request_id),
         }
     } else {
-        match state.storage.get(&bucket, &storage_key).await {
+        match object_data(&state, &object, &storage_key, None, &headers).await {
             Ok(data) => data,
             Err(e) => return error_response(e, &request_id),
         }
@@ -1553,35 +2443,122 @@ pub async fn get_object_versioned(
     let mut response = Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", &object.content_type)
-        .header("Content-Length", data.len())
+        .header("Content-Length", object.size)
         .header("ETag", format!("\"{}\"", object.etag))
         .header("Last-Modified", format_http_datetime(&object.last_modified))
         .header("x-amz-request-id", &request_id)
         .header("x-amz-version-id", &object.version_id);
 
-    // Add SSE headers if encrypted
-    if object.encryption.is_encrypted() {
-        response = response.header("x-amz-server-side-encryption", object.encryption.encryption_type.as_str());
-        if let Some(ref md5) = object.encryption.sse_customer_key_md5 {
-            response = response.header("x-amz-server-side-encryption-customer-key-MD5", md5);
+    response = encryption_headers(response, &object.encryption);
+    response = object_lock_headers(&state, &bucket, &key, &object.version_id, response).await;
+    response = storage_class_header(response, &object);
+
+    // Add user metadata
+    for (k, v) in &object.metadata {
+        response = response.header(format!("x-amz-meta-{}", k), v);
+    }
+
+    response.body(Body::from_stream(data)).unwrap()
+}
+
+/// Object data for a GET, from the read cache when it is small enough
+///
+/// Encrypted data is decrypted, reading only the segments of the range.
+async fn object_data(
+    state: &AppState,
+    object: &ObjectInternal,
+    storage_key: &str,
+    range: Option<(i64, i64)>,
+    headers: &HeaderMap,
+) -> Result<ByteStream, Error> {
+    if object.encryption.is_sealed() {
+        let customer_key = sse::customer_key(headers)?;
+        let data = sse::read_object(state, object, storage_key, range, customer_key.as_ref()).await?;
+        return Ok(stream::once(async move { Ok(data) }).boxed());
+    }
+    let len = range.map_or(object.size, |(start, end)| end - start + 1);
+    if let Some(cache) = &state.read_cache {
+        if (len as u64) <= cache.max_object_size() {
+            let data = cache.get(&object.bucket, storage_key, &object.etag, range).await?;
+            return Ok(stream::once(async move { Ok(data) }).boxed());
         }
     }
+    match range {
+        Some((start, end)) => state.storage.get_range_stream(&object.bucket, storage_key, start, end).await,
+        None => state.storage.get_stream(&object.bucket, storage_key).await,
+    }
+}
 
-    // Add user metadata
+/// GET object through a transform pipeline
+///
+/// The transformed body has no known length or ETag, and the whole
+/// object is served whatever Range asked for.
+async fn transformed_object_response(
+    state: &AppState,
+    pipeline: &Pipeline,
+    object: &ObjectInternal,
+    storage_key: &str,
+    headers: &HeaderMap,
+    request_id: &str,
+) -> Response {
+    debug!(
+        "Transforming {}/{} with {:?}",
+        object.bucket,
+        object.key,
+        pipeline.names()
+    );
+    let data = match sse::customer_key(headers) {
+        Ok(customer_key) => sse::read_object(state, object, storage_key, None, customer_key.as_ref()).await,
+        Err(e) => Err(e),
+    };
+    let data = match data {
+        Ok(data) => data,
+        Err(e) => return error_response(e, request_id),
+    };
+    let input = TransformInput {
+        bucket: object.bucket.clone(),
+        key: object.key.clone(),
+        content_type: object.content_type.clone(),
+    };
+    let output = match pipeline.apply(input, transform::once(data)) {
+        Ok(output) => output,
+        Err(e) => return error_response(e, request_id),
+    };
+
+    // An error before any output becomes an error response rather than a
+    // truncated body
+    let mut body = output.body;
+    let first = match body.next().await {
+        Some(Ok(chunk)) => Some(Ok(chunk)),
+        Some(Err(e)) => return error_response(e, request_id),
+        None => None,
+    };
+    let body = stream::iter(first).chain(body);
+
+    let mut response = Response::builder()
+        .status(StatusCode::OK)
+        .header(
+            "Content-Type",
+            output.content_type.as_deref().unwrap_or(&object.content_type),
+        )
+        .header("Last-Modified", format_http_datetime(&object.last_modified))
+        .header("x-amz-request-id", request_id)
+        .header("x-amz-version-id", &object.version_id);
     for (k, v) in &object.metadata {
         response = response.header(format!("x-amz-meta-{}", k), v);
     }
 
-    response.body(Body::from(data)).unwrap()
+    response.body(Body::from_stream(body)).unwrap()
 }
 
 /// DELETE object with versioning support
 pub async fn delete_object_versioned(
     State(state): State<AppState>,
     Path((bucket, key)): Path<(String, String)>,
+    headers: HeaderMap,
     version_id: Option<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!(
         "DeleteObject bucket={} key={} version={:?} request_id={}",
         bucket, key, version_id, request_id
@@ -1594,14 +2571,25 @@ pub async fn delete_object_versioned(
         Err(e) => return error_response(e, &request_id),
     };
 
+    // Delete markers leave locked data in place; everything else must
+    // respect retention and legal holds
+    let permanent = version_id.is_some() || !bucket_info.versioning.is_versioning_enabled();
+    if permanent {
+        let bypass = governance_bypass(&state, &bucket, &key, &headers).await;
+        if let Err(e) = enforce_object_lock(&state, &bucket, &key, version_id.as_deref(), bypass).await {
+            return error_response(e, &request_id);
+        }
+    }
+
     if let Some(vid) = version_id {
         // Delete specific version
-        if let Err(e) = state.storage.delete(&bucket, &format!("{}?versionId={}", key, vid)).await {
+        if let Err(e) = state.storage.delete(&bucket, &storage_key(&key, &vid)).await {
             error!("Failed to delete object storage: {}", e);
         }
 
-        match state.metadata.delete_object_version(&bucket, &key, &vid).await {
+        match consensus::delete_object_version(&state, &bucket, &key, &vid).await {
             Ok(deleted) => {
+                state.search.object_changed(&bucket, &key);
                 let mut builder = Response::builder()
                     .status(StatusCode::NO_CONTENT)
                     .header("x-amz-request-id", &request_id)
@@ -1617,8 +2605,9 @@ pub async fn delete_object_versioned(
         }
     } else if bucket_info.versioning.is_versioning_enabled() {
         // Versioned bucket without version ID: create delete marker
-        match state.metadata.create_delete_marker(&bucket, &key).await {
+        match consensus::create_delete_marker(&state, &bucket, &key).await {
             Ok(marker_version_id) => {
+                state.search.object_changed(&bucket, &key);
                 Response::builder()
                     .status(StatusCode::NO_CONTENT)
                     .header("x-amz-request-id", &request_id)
@@ -1635,9 +2624,10 @@ pub async fn delete_object_versioned(
             error!("Failed to delete object storage: {}", e);
         }
 
-        if let Err(e) = state.metadata.delete_object(&bucket, &key).await {
+        if let Err(e) = consensus::delete_object(&state, &bucket, &key).await {
             return error_response(e, &request_id);
         }
+        state.search.object_changed(&bucket, &key);
 
         Response::builder()
             .status(StatusCode::NO_CONTENT)
@@ -1655,7 +2645,7 @@ pub async fn get_object_tagging(
     Path((bucket, key)): Path<(String, String)>,
     version_id: Option<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!(
         "GetObjectTagging bucket={} key={} version={:?} request_id={}",
         bucket, key, version_id, request_id
@@ -1664,7 +2654,7 @@ pub async fn get_object_tagging(
     // Check object exists
     match state.metadata.get_object_version(&bucket, &key, version_id.as_deref()).await {
         Ok(Some(_)) => {}
-        Ok(None) => return error_response(Error::NoSuchKey, &request_id),
+        Ok(None) => return error_response(missing_object(version_id.as_deref()), &request_id),
         Err(e) => return error_response(e, &request_id),
     }
 
@@ -1693,7 +2683,7 @@ pub async fn put_object_tagging(
     version_id: Option<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!(
         "PutObjectTagging bucket={} key={} version={:?} request_id={}",
         bucket, key, version_id, request_id
@@ -1702,7 +2692,7 @@ pub async fn put_object_tagging(
     // Check object exists
     match state.metadata.get_object_version(&bucket, &key, version_id.as_deref()).await {
         Ok(Some(_)) => {}
-        Ok(None) => return error_response(Error::NoSuchKey, &request_id),
+        Ok(None) => return error_response(missing_object(version_id.as_deref()), &request_id),
         Err(e) => return error_response(e, &request_id),
     }
 
@@ -1719,9 +2709,10 @@ pub async fn put_object_tagging(
         }
     }
 
-    if let Err(e) = state.metadata.put_object_tags(&bucket, &key, version_id.as_deref(), &tags).await {
+    if let Err(e) = consensus::put_object_tags(&state, &bucket, &key, version_id.as_deref(), &tags).await {
         return error_response(e, &request_id);
     }
+    state.search.object_changed(&bucket, &key);
 
     let mut builder = Response::builder()
         .status(StatusCode::OK)
@@ -1740,7 +2731,7 @@ pub async fn delete_object_tagging(
     Path((bucket, key)): Path<(String, String)>,
     version_id: Option<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!(
         "DeleteObjectTagging bucket={} key={} version={:?} request_id={}",
         bucket, key, version_id, request_id
@@ -1749,13 +2740,14 @@ pub async fn delete_object_tagging(
     // Check object exists
     match state.metadata.get_object_version(&bucket, &key, version_id.as_deref()).await {
         Ok(Some(_)) => {}
-        Ok(None) => return error_response(Error::NoSuchKey, &request_id),
+        Ok(None) => return error_response(missing_object(version_id.as_deref()), &request_id),
         Err(e) => return error_response(e, &request_id),
     }
 
-    if let Err(e) = state.metadata.delete_object_tags(&bucket, &key, version_id.as_deref()).await {
+    if let Err(e) = consensus::delete_object_tags(&state, &bucket, &key, version_id.as_deref()).await {
         return error_response(e, &request_id);
     }
+    state.search.object_changed(&bucket, &key);
 
     let mut builder = Response::builder()
         .status(StatusCode::NO_CONTENT)
@@ -1768,6 +2760,119 @@ pub async fn delete_object_tagging(
     builder.body(Body::empty()).unwrap()
 }
 
+// ============= Bucket Tagging Operations =============
+
+/// GET bucket tagging
+pub async fn get_bucket_tagging(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> impl IntoResponse {
+    let request_id = current_request_id();
+    debug!("GetBucketTagging bucket={} request_id={}", bucket, request_id);
+
+    // Check bucket exists
+    match state.metadata.get_bucket(&bucket).await {
+        Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
+        Err(e) => return error_response(e, &request_id),
+        _ => {}
+    }
+
+    match state.metadata.get_bucket_tags(&bucket).await {
+        Ok(tags) if tags.is_empty() => error_response(Error::NoSuchTagSet, &request_id),
+        Ok(tags) => {
+            let mut tag_set = TagSet::new();
+            for (key, value) in tags {
+                tag_set.tags.push(Tag::new(key, value));
+            }
+            tag_set.tags.sort_by(|a, b| a.key.cmp(&b.key));
+            let xml = xml::get_object_tagging_response(&tag_set);
+            success_response(StatusCode::OK, xml, &request_id)
+        }
+        Err(e) => error_response(e, &request_id),
+    }
+}
+
+/// PUT bucket tagging
+pub async fn put_bucket_tagging(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let request_id = current_request_id();
+    info!("PutBucketTagging bucket={} request_id={}", bucket, request_id);
+
+    // Check bucket exists
+    match state.metadata.get_bucket(&bucket).await {
+        Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
+        Err(e) => return error_response(e, &request_id),
+        _ => {}
+    }
+
+    // Tagging documents are the same for buckets and objects
+    let tags = match xml::parse_tagging(&body) {
+        Ok(t) => t,
+        Err(e) => return error_response(Error::MalformedXML(e.to_string()), &request_id),
+    };
+
+    if let Err(e) = validate_bucket_tags(&tags) {
+        return error_response(e, &request_id);
+    }
+
+    if let Err(e) = state.metadata.put_bucket_tags(&bucket, &tags).await {
+        return error_response(e, &request_id);
+    }
+    sync_bucket_tags(&state, &bucket, &tags);
+
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header("x-amz-request-id", &request_id)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// DELETE bucket tagging
+pub async fn delete_bucket_tagging(
+    State(state): State<AppState>,
+    Path(bucket): Path<String>,
+) -> impl IntoResponse {
+    let request_id = current_request_id();
+    info!("DeleteBucketTagging bucket={} request_id={}", bucket, request_id);
+
+    // Check bucket exists
+    match state.metadata.get_bucket(&bucket).await {
+        Ok(None) => return error_response(Error::NoSuchBucket, &request_id),
+        Err(e) => return error_response(e, &request_id),
+        _ => {}
+    }
+
+    if let Err(e) = state.metadata.delete_bucket_tags(&bucket).await {
+        return error_response(e, &request_id);
+    }
+    sync_bucket_tags(&state, &bucket, &TagSet::new());
+
+    Response::builder()
+        .status(StatusCode::NO_CONTENT)
+        .header("x-amz-request-id", &request_id)
+        .body(Body::empty())
+        .unwrap()
+}
+
+/// Hand new bucket tags to the replicator so tag-filtered rules see them
+#[cfg(feature = "cluster")]
+fn sync_bucket_tags(state: &AppState, bucket: &str, tags: &TagSet) {
+    if let Some(cluster) = &state.cluster {
+        let tags = tags
+            .tags
+            .iter()
+            .map(|t| (t.key.clone(), t.value.clone()))
+            .collect();
+        cluster.set_bucket_tags(bucket, tags);
+    }
+}
+
+#[cfg(not(feature = "cluster"))]
+fn sync_bucket_tags(_state: &AppState, _bucket: &str, _tags: &TagSet) {}
+
 // ============= Bucket Lifecycle Operations =============
 
 /// GET bucket lifecycle configuration
@@ -1775,7 +2880,7 @@ pub async fn get_bucket_lifecycle(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetBucketLifecycle bucket={} request_id={}", bucket, request_id);
 
     // Check bucket exists
@@ -1804,7 +2909,7 @@ pub async fn put_bucket_lifecycle(
     Path(bucket): Path<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("PutBucketLifecycle bucket={} request_id={}", bucket, request_id);
 
     // Check bucket exists
@@ -1843,7 +2948,7 @@ pub async fn delete_bucket_lifecycle(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     info!("DeleteBucketLifecycle bucket={} request_id={}", bucket, request_id);
 
     // Check bucket exists
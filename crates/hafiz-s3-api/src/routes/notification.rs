@@ -11,11 +11,11 @@ use axum::{
 use bytes::Bytes;
 use hafiz_core::{
     types::NotificationConfiguration,
-    utils::generate_request_id,
     Error,
 };
 use tracing::{debug, error, info};
 
+use crate::middleware::{current_request_id, request_error};
 use crate::server::AppState;
 
 // ============================================================================
@@ -24,7 +24,7 @@ use crate::server::AppState;
 
 fn error_response(err: Error, request_id: &str) -> Response {
     let status = StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-    let s3_error = hafiz_core::error::S3Error::from(err).with_request_id(request_id);
+    let s3_error = request_error(err).with_request_id(request_id);
 
     Response::builder()
         .status(status)
@@ -60,7 +60,7 @@ pub async fn get_bucket_notification(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetBucketNotificationConfiguration bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -110,7 +110,7 @@ pub async fn put_bucket_notification(
     Path(bucket): Path<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutBucketNotificationConfiguration bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -179,7 +179,7 @@ pub async fn delete_bucket_notification(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("DeleteBucketNotificationConfiguration bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -11,6 +11,14 @@
 //! - PUT /{bucket}/{key}?retention - Put object retention
 //! - GET /{bucket}/{key}?legal-hold - Get object legal hold
 //! - PUT /{bucket}/{key}?legal-hold - Put object legal hold
+//!
+//! Retention and legal holds are enforced by every path that deletes or
+//! overwrites object data (DeleteObject, DeleteObjects, PutObject,
+//! CopyObject, CompleteMultipartUpload) through [`enforce_object_lock`].
+//! There is no lifecycle expiration worker yet; it must use the same check.
+//!
+//! HEAD and GET report the retention and legal hold of the version they
+//! serve through [`object_lock_headers`].
 
 use axum::{
     body::Body,
@@ -19,26 +27,33 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use bytes::Bytes;
+use chrono::{DateTime, Utc};
 use hafiz_core::{
     types::{
-        ObjectLockConfiguration, ObjectRetention, ObjectLegalHold,
-        RetentionMode, LegalHoldStatus, ObjectLockError,
+        ObjectLockConfiguration, ObjectRetention, ObjectLegalHold, ObjectLockState,
+        RetentionMode, LegalHoldStatus, ObjectLockError, PolicyDocument, PolicyEffect,
+        PolicyRequest,
     },
-    utils::generate_request_id,
     Error,
 };
+use hafiz_metadata::repository::GovernanceBypassEntry;
 use serde::Deserialize;
 use tracing::{debug, error, info, warn};
 
+use crate::consensus;
+use crate::middleware::{current_principal, current_request_id, request_error};
 use crate::server::AppState;
 
+/// Principal recorded for governance bypasses made through the admin API
+const ADMIN_API_PRINCIPAL: &str = "admin-api";
+
 // ============================================================================
 // Response Helpers
 // ============================================================================
 
 fn error_response(err: Error, request_id: &str) -> Response {
     let status = StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-    let s3_error = hafiz_core::error::S3Error::from(err).with_request_id(request_id);
+    let s3_error = request_error(err).with_request_id(request_id);
 
     Response::builder()
         .status(status)
@@ -65,25 +80,6 @@ fn no_content_response(request_id: &str) -> Response {
         .unwrap()
 }
 
-fn object_lock_error_response(code: &str, message: &str, request_id: &str) -> Response {
-    let xml = format!(
-        r#"<?xml version="1.0" encoding="UTF-8"?>
-<e>
-<Code>{}</Code>
-<Message>{}</Message>
-<RequestId>{}</RequestId>
-</e>"#,
-        code, message, request_id
-    );
-
-    Response::builder()
-        .status(StatusCode::BAD_REQUEST)
-        .header("Content-Type", "application/xml")
-        .header("x-amz-request-id", request_id)
-        .body(Body::from(xml))
-        .unwrap()
-}
-
 // ============================================================================
 // Query Parameters
 // ============================================================================
@@ -103,7 +99,7 @@ pub async fn get_bucket_object_lock_config(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetObjectLockConfiguration bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -126,11 +122,7 @@ pub async fn get_bucket_object_lock_config(
         }
         Ok(None) => {
             // Object Lock not configured - return error per S3 spec
-            object_lock_error_response(
-                "ObjectLockConfigurationNotFoundError",
-                "Object Lock configuration does not exist for this bucket",
-                &request_id,
-            )
+            error_response(Error::ObjectLockConfigurationNotFound, &request_id)
         }
         Err(e) => {
             error!("Error getting Object Lock config: {}", e);
@@ -145,7 +137,7 @@ pub async fn put_bucket_object_lock_config(
     Path(bucket): Path<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutObjectLockConfiguration bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -194,11 +186,7 @@ pub async fn put_bucket_object_lock_config(
     // Validate configuration
     if let Err(e) = config.validate() {
         error!("Invalid Object Lock configuration: {}", e);
-        return object_lock_error_response(
-            "InvalidObjectLockConfiguration",
-            &e.to_string(),
-            &request_id,
-        );
+        return error_response(Error::MalformedXML(e.to_string()), &request_id);
     }
 
     // Serialize back to clean XML
@@ -234,7 +222,7 @@ pub async fn get_object_retention(
     Path((bucket, key)): Path<(String, String)>,
     Query(query): Query<RetentionQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetObjectRetention bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Check if bucket exists and has Object Lock enabled
@@ -256,11 +244,7 @@ pub async fn get_object_retention(
         }
         Ok(None) => {
             // No retention set
-            object_lock_error_response(
-                "NoSuchObjectLockConfiguration",
-                "The specified object does not have a retention configuration",
-                &request_id,
-            )
+            error_response(Error::NoSuchObjectLockConfiguration, &request_id)
         }
         Err(e) => {
             error!("Error getting object retention: {}", e);
@@ -277,7 +261,7 @@ pub async fn put_object_retention(
     Query(query): Query<RetentionQuery>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutObjectRetention bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Check if bucket exists and has Object Lock enabled
@@ -291,26 +275,7 @@ pub async fn put_object_retention(
         return resp;
     }
 
-    // Check for bypass governance header
-    let bypass_governance = headers
-        .get("x-amz-bypass-governance-retention")
-        .and_then(|v| v.to_str().ok())
-        .map(|s| s.eq_ignore_ascii_case("true"))
-        .unwrap_or(false);
-
-    // Check existing retention
-    if let Ok(Some(existing_xml)) = state.metadata.get_object_retention(&bucket, &key, version_id).await {
-        if let Ok(existing) = ObjectRetention::from_xml(&existing_xml) {
-            if !existing.can_modify(bypass_governance) {
-                warn!("Cannot modify retention: object is locked");
-                return object_lock_error_response(
-                    "AccessDenied",
-                    "Object is locked and cannot be modified",
-                    &request_id,
-                );
-            }
-        }
-    }
+    let bypass_governance = governance_bypass(&state, &bucket, &key, &headers).await;
 
     // Parse XML body
     let xml_str = match std::str::from_utf8(&body) {
@@ -332,6 +297,26 @@ pub async fn put_object_retention(
         }
     };
 
+    if retention.retain_until().is_none() {
+        return error_response(
+            Error::MalformedXML("RetainUntilDate must be an ISO 8601 date".to_string()),
+            &request_id,
+        );
+    }
+
+    // Locked retention may only be extended, or shortened with a governance bypass
+    if let Ok(Some(existing_xml)) = state.metadata.get_object_retention(&bucket, &key, version_id).await {
+        if let Ok(existing) = ObjectRetention::from_xml(&existing_xml) {
+            if !existing.allows_update(&retention, bypass_governance) {
+                warn!("Cannot modify retention: object is locked");
+                return error_response(
+                    Error::ObjectLocked("retention can only be extended".into()),
+                    &request_id,
+                );
+            }
+        }
+    }
+
     // Serialize back to clean XML
     let clean_xml = match retention.to_xml() {
         Ok(xml) => xml,
@@ -342,7 +327,7 @@ pub async fn put_object_retention(
     };
 
     // Store retention
-    match state.metadata.put_object_retention(&bucket, &key, version_id, &clean_xml).await {
+    match consensus::put_object_retention(&state, &bucket, &key, version_id, &clean_xml).await {
         Ok(_) => {
             info!("PutObjectRetention success bucket={} key={} mode={}",
                   bucket, key, retention.mode);
@@ -365,7 +350,7 @@ pub async fn get_object_legal_hold(
     Path((bucket, key)): Path<(String, String)>,
     Query(query): Query<RetentionQuery>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetObjectLegalHold bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Check if bucket exists and has Object Lock enabled
@@ -405,7 +390,7 @@ pub async fn put_object_legal_hold(
     Query(query): Query<RetentionQuery>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutObjectLegalHold bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Check if bucket exists and has Object Lock enabled
@@ -449,7 +434,7 @@ pub async fn put_object_legal_hold(
     };
 
     // Store legal hold
-    match state.metadata.put_object_legal_hold(&bucket, &key, version_id, &clean_xml).await {
+    match consensus::put_object_legal_hold(&state, &bucket, &key, version_id, &clean_xml).await {
         Ok(_) => {
             info!("PutObjectLegalHold success bucket={} key={} status={}",
                   bucket, key, hold.status);
@@ -488,16 +473,14 @@ async fn check_object_lock_enabled(
         Ok(Some(config_xml)) => {
             match ObjectLockConfiguration::from_xml(&config_xml) {
                 Ok(config) if config.is_enabled() => Ok(()),
-                _ => Err(object_lock_error_response(
-                    "InvalidRequest",
-                    "Object Lock is not enabled for this bucket",
+                _ => Err(error_response(
+                    Error::InvalidRequest("Object Lock is not enabled for this bucket".into()),
                     request_id,
                 )),
             }
         }
-        Ok(None) => Err(object_lock_error_response(
-            "InvalidRequest",
-            "Object Lock is not enabled for this bucket",
+        Ok(None) => Err(error_response(
+            Error::InvalidRequest("Object Lock is not enabled for this bucket".into()),
             request_id,
         )),
         Err(e) => Err(error_response(e, request_id)),
@@ -535,54 +518,297 @@ pub async fn can_delete_object(
     version_id: Option<&str>,
     bypass_governance: bool,
 ) -> Result<bool, Error> {
-    // Check legal hold
-    if let Some(hold_xml) = state.metadata.get_object_legal_hold(bucket, key, version_id).await? {
-        if let Ok(hold) = ObjectLegalHold::from_xml(&hold_xml) {
-            if hold.is_active() {
-                return Ok(false);
+    let lock = object_lock_state(state, bucket, key, version_id).await?;
+    Ok(lock.can_delete(bypass_governance))
+}
+
+/// Get Object Lock error message for deletion attempt
+pub async fn get_lock_error_message(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Option<String> {
+    object_lock_state(state, bucket, key, version_id)
+        .await
+        .ok()
+        .and_then(|lock| lock.lock_reason())
+}
+
+// ============================================================================
+// Enforcement
+// ============================================================================
+
+/// Policy action that allows deleting or overwriting under GOVERNANCE retention
+const BYPASS_GOVERNANCE_ACTION: &str = "s3:BypassGovernanceRetention";
+
+/// Retention and legal hold of an object version.
+///
+/// Settings made without a version id apply to the current version, so
+/// they are included when `version_id` is the current version. With no
+/// `version_id` the current version is checked, and nothing is locked if
+/// the key does not exist.
+pub async fn object_lock_state(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+) -> Result<ObjectLockState, Error> {
+    let current = state
+        .metadata
+        .get_object(bucket, key)
+        .await?
+        .map(|object| object.version_id);
+
+    let lookups = match (version_id, current.as_deref()) {
+        (None, None) => return Ok(ObjectLockState::default()),
+        (None, Some(current)) => vec![None, Some(current)],
+        (Some(vid), Some(current)) if vid == current => vec![Some(vid), None],
+        (Some(vid), _) => vec![Some(vid)],
+    };
+
+    let mut lock = ObjectLockState::default();
+    for vid in lookups {
+        if let Some(hold_xml) = state.metadata.get_object_legal_hold(bucket, key, vid).await? {
+            if let Ok(hold) = ObjectLegalHold::from_xml(&hold_xml) {
+                if hold.is_active() {
+                    lock.legal_hold = Some(hold);
+                }
+            }
+        }
+        if let Some(retention_xml) = state.metadata.get_object_retention(bucket, key, vid).await? {
+            if let Ok(retention) = ObjectRetention::from_xml(&retention_xml) {
+                let locked = matches!(&lock.retention, Some(r) if !r.is_expired());
+                if !locked {
+                    lock.retention = Some(retention);
+                }
             }
         }
     }
 
-    // Check retention
-    if let Some(retention_xml) = state.metadata.get_object_retention(bucket, key, version_id).await? {
-        if let Ok(retention) = ObjectRetention::from_xml(&retention_xml) {
-            if !retention.can_delete(bypass_governance) {
-                return Ok(false);
-            }
+    Ok(lock)
+}
+
+/// Add the `x-amz-object-lock-*` headers describing an object version's
+/// retention and legal hold to a HEAD or GET response.
+///
+/// Buckets without Object Lock cannot hold retention, so they cost one
+/// lookup. A failed lookup leaves the headers out rather than failing the
+/// read.
+pub async fn object_lock_headers(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: &str,
+    mut builder: axum::http::response::Builder,
+) -> axum::http::response::Builder {
+    match state.metadata.get_bucket_object_lock_config(bucket).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return builder,
+        Err(e) => {
+            warn!("Failed to read Object Lock configuration of {}: {}", bucket, e);
+            return builder;
         }
     }
 
-    Ok(true)
+    let lock = match object_lock_state(state, bucket, key, Some(version_id)).await {
+        Ok(lock) => lock,
+        Err(e) => {
+            warn!("Failed to read Object Lock state of {}/{}: {}", bucket, key, e);
+            return builder;
+        }
+    };
+    if let Some(retention) = &lock.retention {
+        builder = builder
+            .header("x-amz-object-lock-mode", retention.mode.to_string())
+            .header("x-amz-object-lock-retain-until-date", &retention.retain_until_date);
+    }
+    if lock.legal_hold.is_some() {
+        builder = builder.header("x-amz-object-lock-legal-hold", "ON");
+    }
+    builder
 }
 
-/// Get Object Lock error message for deletion attempt
-pub async fn get_lock_error_message(
+/// Fail with `ObjectLocked` if an object version may not be deleted or
+/// overwritten
+///
+/// A GOVERNANCE retention that is bypassed is recorded in the governance
+/// bypass log, listed by the admin API.
+pub async fn enforce_object_lock(
     state: &AppState,
     bucket: &str,
     key: &str,
     version_id: Option<&str>,
-) -> Option<String> {
-    // Check legal hold
-    if let Ok(Some(hold_xml)) = state.metadata.get_object_legal_hold(bucket, key, version_id).await {
-        if let Ok(hold) = ObjectLegalHold::from_xml(&hold_xml) {
-            if hold.is_active() {
-                return Some("Object is under legal hold".to_string());
-            }
+    bypass_governance: bool,
+) -> Result<(), Error> {
+    let lock = object_lock_state(state, bucket, key, version_id).await?;
+    if lock.can_delete(bypass_governance) {
+        if bypass_governance && lock.is_locked() {
+            record_governance_bypass(state, bucket, key, version_id, &lock).await?;
         }
+        return Ok(());
+    }
+    Err(Error::ObjectLocked(lock.lock_reason().unwrap_or_default()))
+}
+
+/// Add a governance bypass to the audit log
+///
+/// The bypass is refused if it cannot be recorded. Outside an S3 request the
+/// bypass was made through the admin API and is recorded as such.
+async fn record_governance_bypass(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: Option<&str>,
+    lock: &ObjectLockState,
+) -> Result<(), Error> {
+    let principal = current_principal().unwrap_or_else(|| ADMIN_API_PRINCIPAL.to_string());
+    info!(
+        "Governance retention bypassed for {}/{} by {}",
+        bucket, key, principal
+    );
+    let version_id = match version_id {
+        Some(vid) => vid.to_string(),
+        None => state
+            .metadata
+            .get_object(bucket, key)
+            .await?
+            .map(|object| object.version_id)
+            .unwrap_or_default(),
+    };
+    let entry = GovernanceBypassEntry {
+        id: 0,
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        version_id,
+        principal,
+        request_id: current_request_id(),
+        retain_until: lock.retention.as_ref().map(|r| r.retain_until_date.clone()),
+        created_at: Utc::now(),
+    };
+    state.metadata.record_governance_bypass(&entry).await?;
+    metrics::counter!("hafiz_governance_bypass_total").increment(1);
+    Ok(())
+}
+
+/// Whether the request asks to bypass GOVERNANCE retention and may do so.
+///
+/// The root user always may; other principals need
+/// `s3:BypassGovernanceRetention` granted by the bucket policy.
+pub async fn governance_bypass(state: &AppState, bucket: &str, key: &str, headers: &HeaderMap) -> bool {
+    let requested = headers
+        .get("x-amz-bypass-governance-retention")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|s| s.eq_ignore_ascii_case("true"));
+    if !requested {
+        return false;
+    }
+
+    let principal = current_principal().unwrap_or_else(|| "*".to_string());
+    if principal == state.config.auth.root_access_key {
+        return true;
+    }
+
+    let policy = match state.metadata.get_bucket_policy(bucket).await {
+        Ok(Some(json)) => serde_json::from_str::<PolicyDocument>(&json).ok(),
+        _ => None,
+    };
+    let allowed = policy.is_some_and(|policy| {
+        let request = PolicyRequest::new(
+            BYPASS_GOVERNANCE_ACTION,
+            format!("arn:aws:s3:::{}/{}", bucket, key),
+            principal.as_str(),
+        );
+        policy.evaluate(&request) == PolicyEffect::Allow
+    });
+    if !allowed {
+        warn!("Governance bypass denied for {} on {}/{}", principal, bucket, key);
     }
+    allowed
+}
 
-    // Check retention
-    if let Ok(Some(retention_xml)) = state.metadata.get_object_retention(bucket, key, version_id).await {
-        if let Ok(retention) = ObjectRetention::from_xml(&retention_xml) {
-            if !retention.is_expired() {
-                return Some(format!(
-                    "Object is locked in {} mode until {}",
-                    retention.mode, retention.retain_until_date
+/// Object Lock settings for an object about to be written
+#[derive(Debug, Default)]
+pub struct WriteLock {
+    retention: Option<ObjectRetention>,
+    legal_hold: Option<ObjectLegalHold>,
+}
+
+impl WriteLock {
+    /// Resolve the lock of a new object from its request headers.
+    ///
+    /// Explicit `x-amz-object-lock-*` headers take precedence over the
+    /// bucket's default retention. Lock headers are rejected for buckets
+    /// without Object Lock.
+    pub async fn from_request(state: &AppState, bucket: &str, headers: &HeaderMap) -> Result<Self, Error> {
+        let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+        let mode = header("x-amz-object-lock-mode");
+        let retain_until = header("x-amz-object-lock-retain-until-date");
+        let legal_hold = header("x-amz-object-lock-legal-hold");
+
+        let config = match state.metadata.get_bucket_object_lock_config(bucket).await? {
+            Some(xml) => ObjectLockConfiguration::from_xml(&xml).ok(),
+            None => None,
+        };
+        let Some(config) = config.filter(|c| c.is_enabled()) else {
+            if mode.is_some() || retain_until.is_some() || legal_hold.is_some() {
+                return Err(Error::InvalidRequest(
+                    "Bucket is missing Object Lock Configuration".to_string(),
                 ));
             }
-        }
+            return Ok(Self::default());
+        };
+
+        let retention = match (mode, retain_until) {
+            (Some(mode), Some(until)) => {
+                let mode: RetentionMode = mode.parse().map_err(Error::InvalidArgument)?;
+                let until = DateTime::parse_from_rfc3339(until)
+                    .map_err(|_| {
+                        Error::InvalidArgument(format!(
+                            "Invalid x-amz-object-lock-retain-until-date: {}",
+                            until
+                        ))
+                    })?
+                    .with_timezone(&Utc);
+                if until <= Utc::now() {
+                    return Err(Error::InvalidArgument(
+                        "The retain until date must be in the future".to_string(),
+                    ));
+                }
+                Some(ObjectRetention::new(mode, until))
+            }
+            (None, None) => config
+                .rule
+                .as_ref()
+                .and_then(|rule| rule.default_retention.to_retention()),
+            _ => {
+                return Err(Error::InvalidArgument(
+                    "x-amz-object-lock-mode and x-amz-object-lock-retain-until-date must be set together"
+                        .to_string(),
+                ))
+            }
+        };
+
+        let legal_hold = match legal_hold {
+            Some(status) => Some(ObjectLegalHold {
+                status: status.parse().map_err(Error::InvalidArgument)?,
+            }),
+            None => None,
+        };
+
+        Ok(Self { retention, legal_hold })
     }
 
-    None
+    /// Store the lock for the object now written at `bucket/key`
+    pub async fn record(&self, state: &AppState, bucket: &str, key: &str) -> Result<(), Error> {
+        if let Some(ref retention) = self.retention {
+            let xml = retention.to_xml().map_err(Error::InternalError)?;
+            consensus::put_object_retention(state, bucket, key, None, &xml).await?;
+        }
+        if let Some(ref hold) = self.legal_hold {
+            let xml = hold.to_xml().map_err(Error::InternalError)?;
+            consensus::put_object_legal_hold(state, bucket, key, None, &xml).await?;
+        }
+        Ok(())
+    }
 }
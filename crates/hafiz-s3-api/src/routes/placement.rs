@@ -0,0 +1,192 @@
+//! Cluster data placement for object routes
+//!
+//! With placement enabled, object data lives on the nodes the hash ring
+//! assigns it to. Writes are copied to the other owners, and reads of data
+//! that is not stored on this node are proxied to an owner.
+//!
+//! Reads and writes are coordinated at a consistency level taken from the
+//! `x-hafiz-consistency` header, the bucket setting or the cluster default,
+//! and the level reached is reported in the response headers.
+
+use axum::http::{response::Builder, HeaderMap};
+use bytes::Bytes;
+use hafiz_core::types::{
+    ConsistencyLevel, ConsistencyOutcome, ACHIEVED_CONSISTENCY_HEADER, CONSISTENCY_REPLICAS_HEADER,
+};
+use hafiz_core::{Error, Result};
+#[cfg(feature = "cluster")]
+use hafiz_core::types::CONSISTENCY_HEADER;
+use hafiz_storage::StorageEngine;
+
+use crate::server::AppState;
+
+/// Consistency level for a request: the `x-hafiz-consistency` header, then
+/// the bucket setting, then the cluster default
+#[cfg(feature = "cluster")]
+pub async fn requested_consistency(
+    state: &AppState,
+    bucket: &str,
+    headers: &HeaderMap,
+) -> Result<ConsistencyLevel> {
+    let Some(cluster) = state.cluster.as_ref() else {
+        return Ok(ConsistencyLevel::One);
+    };
+
+    if let Some(value) = headers.get(CONSISTENCY_HEADER) {
+        return value
+            .to_str()
+            .map_err(|_| Error::InvalidArgument(format!("Invalid {} header", CONSISTENCY_HEADER)))?
+            .parse()
+            .map_err(Error::InvalidArgument);
+    }
+
+    if let Some(level) = state.metadata.get_bucket_consistency(bucket).await? {
+        return Ok(level);
+    }
+
+    Ok(cluster.config().default_consistency_level)
+}
+
+#[cfg(not(feature = "cluster"))]
+pub async fn requested_consistency(
+    _state: &AppState,
+    _bucket: &str,
+    _headers: &HeaderMap,
+) -> Result<ConsistencyLevel> {
+    Ok(ConsistencyLevel::One)
+}
+
+/// Read object data at `level`.
+///
+/// At level one the local copy is served as is, falling back to the other
+/// replicas when this node has none; stronger levels always consult them.
+pub async fn read_object(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    level: ConsistencyLevel,
+) -> Result<(Bytes, Option<ConsistencyOutcome>)> {
+    let local = match state.storage.get(bucket, key).await {
+        Ok(data) => Some(data),
+        Err(Error::NoSuchKey) => None,
+        Err(e) => return Err(e),
+    };
+
+    read(state, bucket, key, local, level).await
+}
+
+#[cfg(feature = "cluster")]
+async fn read(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    local: Option<Bytes>,
+    level: ConsistencyLevel,
+) -> Result<(Bytes, Option<ConsistencyOutcome>)> {
+    let Some(cluster) = state.cluster.as_ref() else {
+        return local.map(|data| (data, None)).ok_or(Error::NoSuchKey);
+    };
+
+    match cluster.placement().read(bucket, key, None, local, level).await {
+        Ok((data, outcome)) => Ok((data, Some(outcome))),
+        Err(hafiz_cluster::ClusterError::NoHealthyNodes) => Err(Error::NoSuchKey),
+        Err(e) => Err(cluster_error(e)),
+    }
+}
+
+#[cfg(not(feature = "cluster"))]
+async fn read(
+    _state: &AppState,
+    _bucket: &str,
+    _key: &str,
+    local: Option<Bytes>,
+    _level: ConsistencyLevel,
+) -> Result<(Bytes, Option<ConsistencyOutcome>)> {
+    local.map(|data| (data, None)).ok_or(Error::NoSuchKey)
+}
+
+/// Outcome of serving the local copy at level one
+#[cfg(feature = "cluster")]
+pub fn local_outcome(state: &AppState, bucket: &str, key: &str) -> Option<ConsistencyOutcome> {
+    let replicas = state.cluster.as_ref()?.placement().replicas(bucket, key);
+    Some(ConsistencyOutcome::new(ConsistencyLevel::One, 1, replicas.len()))
+}
+
+#[cfg(not(feature = "cluster"))]
+pub fn local_outcome(_state: &AppState, _bucket: &str, _key: &str) -> Option<ConsistencyOutcome> {
+    None
+}
+
+/// Copy a freshly written object to the other replicas, as `level` requires.
+///
+/// If this node is not an owner and another owner accepted the data, the
+/// local copy is dropped; the metadata stays so reads are proxied.
+#[cfg(feature = "cluster")]
+pub async fn distribute(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    data: Bytes,
+    content_type: &str,
+    level: ConsistencyLevel,
+) -> Result<Option<ConsistencyOutcome>> {
+    use hafiz_cluster::placement::CONTENT_TYPE_META_KEY;
+
+    let Some(cluster) = state.cluster.as_ref() else {
+        return Ok(None);
+    };
+    let placement = cluster.placement();
+
+    let metadata = std::collections::HashMap::from([(
+        CONTENT_TYPE_META_KEY.to_string(),
+        content_type.to_string(),
+    )]);
+    let outcome = placement
+        .write(bucket, key, data, &metadata, level)
+        .await
+        .map_err(cluster_error)?;
+
+    if outcome.acks > 0 && !placement.is_local_owner(bucket, key) {
+        if let Err(e) = state.storage.delete(bucket, key).await {
+            tracing::warn!("Failed to drop non-owned copy of {}/{}: {}", bucket, key, e);
+        }
+    }
+
+    Ok(Some(outcome))
+}
+
+#[cfg(not(feature = "cluster"))]
+pub async fn distribute(
+    _state: &AppState,
+    _bucket: &str,
+    _key: &str,
+    _data: Bytes,
+    _content_type: &str,
+    _level: ConsistencyLevel,
+) -> Result<Option<ConsistencyOutcome>> {
+    Ok(None)
+}
+
+/// Record the consistency reached in the response headers
+pub fn consistency_headers(mut builder: Builder, outcome: Option<&ConsistencyOutcome>) -> Builder {
+    if let Some(outcome) = outcome {
+        if let Some(achieved) = outcome.achieved() {
+            builder = builder.header(ACHIEVED_CONSISTENCY_HEADER, achieved.as_str());
+        }
+        builder = builder.header(
+            CONSISTENCY_REPLICAS_HEADER,
+            format!("{}/{}", outcome.acks, outcome.replicas),
+        );
+    }
+    builder
+}
+
+#[cfg(feature = "cluster")]
+fn cluster_error(error: hafiz_cluster::ClusterError) -> Error {
+    match error {
+        hafiz_cluster::ClusterError::QuorumNotReached { needed, got } => Error::ServiceUnavailable(
+            format!("Consistency level not met: {} of {} replicas responded", got, needed),
+        ),
+        e => Error::InternalError(e.to_string()),
+    }
+}
@@ -14,11 +14,12 @@ use hafiz_core::{
         AccessControlPolicy, AclHeaders, CannedAcl, Grant, Grantee, Owner, Permission,
         PolicyDocument,
     },
-    utils::generate_request_id,
     Error,
 };
 use tracing::{debug, error, info};
 
+use crate::consensus;
+use crate::middleware::{current_request_id, request_error};
 use crate::server::AppState;
 
 // ============================================================================
@@ -27,7 +28,7 @@ use crate::server::AppState;
 
 fn error_response(err: Error, request_id: &str) -> Response {
     let status = StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
-    let s3_error = hafiz_core::error::S3Error::from(err).with_request_id(request_id);
+    let s3_error = request_error(err).with_request_id(request_id);
 
     Response::builder()
         .status(status)
@@ -72,7 +73,7 @@ pub async fn get_bucket_policy(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetBucketPolicy bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -108,7 +109,7 @@ pub async fn put_bucket_policy(
     Path(bucket): Path<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutBucketPolicy bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -156,7 +157,7 @@ pub async fn put_bucket_policy(
     }
 
     // Store bucket policy
-    match state.metadata.put_bucket_policy(&bucket, &policy_json).await {
+    match consensus::put_bucket_policy(&state, &bucket, &policy_json).await {
         Ok(_) => {
             info!("Bucket policy set for {}", bucket);
             no_content_response(&request_id)
@@ -173,7 +174,7 @@ pub async fn delete_bucket_policy(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("DeleteBucketPolicy bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -189,7 +190,7 @@ pub async fn delete_bucket_policy(
     }
 
     // Delete bucket policy
-    match state.metadata.delete_bucket_policy(&bucket).await {
+    match consensus::delete_bucket_policy(&state, &bucket).await {
         Ok(_) => {
             info!("Bucket policy deleted for {}", bucket);
             no_content_response(&request_id)
@@ -210,7 +211,7 @@ pub async fn get_bucket_acl(
     State(state): State<AppState>,
     Path(bucket): Path<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetBucketAcl bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -249,7 +250,7 @@ pub async fn put_bucket_acl(
     headers: axum::http::HeaderMap,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutBucketAcl bucket={} request_id={}", bucket, request_id);
 
     // Check if bucket exists
@@ -355,7 +356,7 @@ pub async fn get_object_acl(
     Path((bucket, key)): Path<(String, String)>,
     version_id: Option<String>,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("GetObjectAcl bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Check if bucket exists
@@ -407,7 +408,7 @@ pub async fn put_object_acl(
     version_id: Option<String>,
     body: Bytes,
 ) -> impl IntoResponse {
-    let request_id = generate_request_id();
+    let request_id = current_request_id();
     debug!("PutObjectAcl bucket={} key={} request_id={}", bucket, key, request_id);
 
     // Check if bucket exists
@@ -503,7 +504,7 @@ pub async fn put_object_acl(
     };
 
     // Store object ACL
-    match state.metadata.put_object_acl(&bucket, &key, version_id.as_deref(), &acl_xml).await {
+    match consensus::put_object_acl(&state, &bucket, &key, version_id.as_deref(), &acl_xml).await {
         Ok(_) => {
             info!("Object ACL set for {}/{}", bucket, key);
             no_content_response(&request_id)
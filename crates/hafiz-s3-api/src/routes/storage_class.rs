@@ -0,0 +1,278 @@
+//! Storage classes
+//!
+//! `x-amz-storage-class` on PutObject, CopyObject and CreateMultipartUpload
+//! sets the class of the new object, which HEAD, GET and the listings
+//! report. Most classes only label the object. Data of an object in an
+//! archive class (`GLACIER`, `DEEP_ARCHIVE`) is moved to the archive backend
+//! once written, and the object cannot be read or copied until it is
+//! restored.
+//!
+//! `POST /{bucket}/{key}?restore` starts a restore, which copies the data
+//! back in the background (see [`crate::restore`]). HEAD reports its
+//! progress and expiry in `x-amz-restore`.
+
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{response::Builder, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::Utc;
+use hafiz_core::{
+    types::{ObjectInternal, S3EventType, StorageClass, NULL_VERSION_ID},
+    utils::format_http_datetime,
+    Error,
+};
+use tracing::{debug, info};
+
+use super::object_lock::RetentionQuery;
+use super::error_response;
+use crate::events;
+use crate::middleware::{current_principal, current_request_id};
+use crate::restore::{self, restore_event};
+use crate::server::AppState;
+use crate::xml;
+
+/// Longest restore a request may ask for, in days
+const MAX_RESTORE_DAYS: i32 = 36500;
+
+/// Principal reported in events of anonymous requests
+const ANONYMOUS_PRINCIPAL: &str = "anonymous";
+
+/// Storage class asked for by `x-amz-storage-class`, `STANDARD` if absent
+///
+/// Archive classes are refused when no archive backend is configured.
+pub fn requested_storage_class(state: &AppState, headers: &HeaderMap) -> Result<StorageClass, Error> {
+    let Some(value) = headers.get("x-amz-storage-class") else {
+        return Ok(StorageClass::Standard);
+    };
+    let value = value
+        .to_str()
+        .map_err(|_| Error::InvalidStorageClass("non-ASCII value".into()))?;
+    let class: StorageClass = value.parse()?;
+    if class.is_archive() && !state.storage_router.has_archive() {
+        return Err(Error::InvalidStorageClass(format!(
+            "{} needs an archive backend",
+            value
+        )));
+    }
+    Ok(class)
+}
+
+/// Key the data of an object version is stored under; the null version
+/// lives at the object key itself
+pub(crate) fn storage_key(key: &str, version_id: &str) -> String {
+    if version_id == NULL_VERSION_ID {
+        key.to_string()
+    } else {
+        format!("{}?versionId={}", key, version_id)
+    }
+}
+
+/// Move the data of a just-written object to the backend its class needs
+pub async fn place_object(state: &AppState, object: &ObjectInternal) -> Result<(), Error> {
+    // Rewritten data starts out hot; only the null version is ever rewritten
+    if object.version_id == NULL_VERSION_ID && state.tiering.is_enabled() {
+        state
+            .metadata
+            .delete_object_access(&object.bucket, &object.key, &object.version_id)
+            .await?;
+    }
+    if object.is_archived() {
+        state
+            .storage_router
+            .archive(&object.bucket, &storage_key(&object.key, &object.version_id))
+            .await?;
+        // A restore of data this write replaced no longer applies
+        state
+            .metadata
+            .delete_object_restore(&object.bucket, &object.key, &object.version_id)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Change the class of a readable object in place, moving its data to the
+/// archive backend if the new class needs it
+pub async fn change_storage_class(
+    state: &AppState,
+    object: &ObjectInternal,
+    class: StorageClass,
+) -> Result<(), Error> {
+    check_readable(state, object).await?;
+    if class.is_archive() {
+        state
+            .storage_router
+            .archive(&object.bucket, &storage_key(&object.key, &object.version_id))
+            .await?;
+    }
+    if object.is_archived() {
+        state
+            .metadata
+            .delete_object_restore(&object.bucket, &object.key, &object.version_id)
+            .await?;
+    }
+    state
+        .metadata
+        .set_object_storage_class(&object.bucket, &object.key, &object.version_id, class.as_str())
+        .await
+}
+
+/// Fail with `InvalidObjectState` if the data of `object` cannot be read,
+/// i.e. it is archived and has no restored copy
+pub async fn check_readable(state: &AppState, object: &ObjectInternal) -> Result<(), Error> {
+    if !object.is_archived() {
+        return Ok(());
+    }
+    let restore = state
+        .metadata
+        .get_object_restore(&object.bucket, &object.key, &object.version_id)
+        .await?;
+    if restore.is_some_and(|r| r.is_available(Utc::now())) {
+        return Ok(());
+    }
+    Err(Error::InvalidObjectState(format!(
+        "{} is in the {} storage class and must be restored first",
+        object.key, object.storage_class
+    )))
+}
+
+/// `x-amz-storage-class`, sent for every class but `STANDARD` as S3 does
+pub fn storage_class_header(builder: Builder, object: &ObjectInternal) -> Builder {
+    if object.storage_class == StorageClass::Standard.as_str() {
+        return builder;
+    }
+    builder.header("x-amz-storage-class", &object.storage_class)
+}
+
+/// `x-amz-restore` for an archived object with a restore requested
+pub async fn restore_header(state: &AppState, builder: Builder, object: &ObjectInternal) -> Builder {
+    if !object.is_archived() {
+        return builder;
+    }
+    let restore = match state
+        .metadata
+        .get_object_restore(&object.bucket, &object.key, &object.version_id)
+        .await
+    {
+        Ok(Some(restore)) => restore,
+        Ok(None) => return builder,
+        Err(e) => {
+            debug!("Failed to read restore of {}: {}", object.key, e);
+            return builder;
+        }
+    };
+    match restore.expires_at {
+        Some(expires_at) => builder.header(
+            "x-amz-restore",
+            format!(
+                r#"ongoing-request="false", expiry-date="{}""#,
+                format_http_datetime(&expires_at)
+            ),
+        ),
+        None => builder.header("x-amz-restore", r#"ongoing-request="true""#),
+    }
+}
+
+/// POST /{bucket}/{key}?restore - Restore an archived object
+///
+/// Answers 202 when a restore starts and 200 when the object was already
+/// restored, in which case only the expiry of the restored copy moves.
+pub async fn restore_object(
+    State(state): State<AppState>,
+    Path((bucket, key)): Path<(String, String)>,
+    Query(query): Query<RetentionQuery>,
+    body: Bytes,
+) -> impl IntoResponse {
+    let request_id = current_request_id();
+    debug!("RestoreObject bucket={} key={} request_id={}", bucket, key, request_id);
+
+    let days = match xml::parse_restore_request(&body) {
+        Ok(request) => request.days,
+        Err(e) => return error_response(Error::MalformedXML(e.to_string()), &request_id),
+    };
+    let days = match days {
+        Some(days) if (1..=MAX_RESTORE_DAYS).contains(&days) => days,
+        Some(_) => {
+            return error_response(
+                Error::InvalidArgument(format!("Days must be between 1 and {}", MAX_RESTORE_DAYS)),
+                &request_id,
+            )
+        }
+        None => return error_response(Error::MalformedXML("Days is required".into()), &request_id),
+    };
+
+    let object = match state
+        .metadata
+        .get_object_version(&bucket, &key, query.version_id.as_deref())
+        .await
+    {
+        Ok(Some(object)) if !object.is_delete_marker => object,
+        Ok(_) => return error_response(Error::NoSuchKey, &request_id),
+        Err(e) => return error_response(e, &request_id),
+    };
+    if !object.is_archived() {
+        return error_response(
+            Error::InvalidObjectState(format!(
+                "{} is in the {} storage class, which needs no restore",
+                key, object.storage_class
+            )),
+            &request_id,
+        );
+    }
+
+    let current = match state
+        .metadata
+        .get_object_restore(&bucket, &key, &object.version_id)
+        .await
+    {
+        Ok(current) => current,
+        Err(e) => return error_response(e, &request_id),
+    };
+    match current {
+        Some(restore) if restore.is_ongoing() => {
+            return error_response(Error::RestoreAlreadyInProgress, &request_id);
+        }
+        Some(restore) if restore.is_available(Utc::now()) => {
+            let expires_at = Utc::now() + chrono::Duration::days(days as i64);
+            if let Err(e) = state
+                .metadata
+                .complete_object_restore(&bucket, &key, &object.version_id, days, expires_at)
+                .await
+            {
+                return error_response(e, &request_id);
+            }
+            info!("RestoreObject extended bucket={} key={} days={}", bucket, key, days);
+            return restore_response(StatusCode::OK, &request_id);
+        }
+        _ => {}
+    }
+
+    let restore = match state
+        .metadata
+        .start_object_restore(&bucket, &key, &object.version_id, days)
+        .await
+    {
+        Ok(restore) => restore,
+        Err(e) => return error_response(e, &request_id),
+    };
+    let principal = current_principal().unwrap_or_else(|| ANONYMOUS_PRINCIPAL.to_string());
+    events::notify(
+        &state,
+        restore_event(S3EventType::ObjectRestorePost, &object, &request_id, &principal),
+    )
+    .await;
+    restore::schedule(state.clone(), restore, request_id.clone(), principal);
+
+    info!("RestoreObject started bucket={} key={} days={}", bucket, key, days);
+    restore_response(StatusCode::ACCEPTED, &request_id)
+}
+
+fn restore_response(status: StatusCode, request_id: &str) -> Response {
+    Response::builder()
+        .status(status)
+        .header("x-amz-request-id", request_id)
+        .body(Body::empty())
+        .unwrap()
+}
@@ -0,0 +1,148 @@
+//! STS endpoint for federated identities
+//!
+//! Exchanges an OIDC ID token for temporary S3 credentials whose policies
+//! come from the token's group claims.
+//!
+//! Endpoints:
+//! - POST /?Action=AssumeRoleWithWebIdentity - Issue temporary credentials
+
+use axum::{
+    body::Body,
+    extract::{RawQuery, State},
+    http::StatusCode,
+    response::Response,
+};
+use bytes::Bytes;
+use chrono::{Duration, Utc};
+use hafiz_auth::generate_temporary_credentials;
+use hafiz_core::{types::TemporaryCredentials, Error};
+use std::collections::HashMap;
+use tracing::{debug, info, warn};
+
+use crate::middleware::current_request_id;
+use crate::server::AppState;
+use crate::xml;
+
+/// Shortest session STS will issue, as on AWS
+const MIN_DURATION_SECONDS: u64 = 900;
+
+fn error_response(err: Error, request_id: &str) -> Response {
+    let status = StatusCode::from_u16(err.http_status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let body = xml::sts_error_response(err.code(), &err.to_string(), status.is_client_error(), request_id);
+
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "text/xml")
+        .header("x-amzn-RequestId", request_id)
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// STS action dispatcher; parameters may come in the query or a form body
+pub async fn sts_handler(
+    State(state): State<AppState>,
+    RawQuery(query): RawQuery,
+    body: Bytes,
+) -> Response {
+    let request_id = current_request_id();
+
+    let query = query.unwrap_or_default();
+    let params: HashMap<String, String> = url::form_urlencoded::parse(query.as_bytes())
+        .chain(url::form_urlencoded::parse(&body))
+        .map(|(k, v)| (k.into_owned(), v.into_owned()))
+        .collect();
+
+    let result = match params.get("Action").map(String::as_str) {
+        Some("AssumeRoleWithWebIdentity") => {
+            assume_role_with_web_identity(&state, &params, &request_id).await
+        }
+        Some(action) => Err(Error::NotImplemented(format!("STS action {}", action))),
+        None => Err(Error::InvalidRequest("Missing Action parameter".into())),
+    };
+
+    let outcome = if result.is_ok() { "issued" } else { "rejected" };
+    metrics::counter!("hafiz_sts_requests_total", "outcome" => outcome).increment(1);
+
+    match result {
+        Ok(body) => Response::builder()
+            .status(StatusCode::OK)
+            .header("Content-Type", "text/xml")
+            .header("x-amzn-RequestId", &request_id)
+            .body(Body::from(body))
+            .unwrap(),
+        Err(e) => {
+            debug!("STS request rejected: {}", e);
+            error_response(e, &request_id)
+        }
+    }
+}
+
+/// Validate the web identity token and issue temporary credentials
+async fn assume_role_with_web_identity(
+    state: &AppState,
+    params: &HashMap<String, String>,
+    request_id: &str,
+) -> Result<String, Error> {
+    let verifier = state
+        .oidc
+        .as_ref()
+        .ok_or_else(|| Error::InvalidRequest("OIDC federation is not enabled".into()))?;
+    let config = verifier.config();
+
+    let token = params
+        .get("WebIdentityToken")
+        .ok_or_else(|| Error::InvalidRequest("Missing WebIdentityToken parameter".into()))?;
+    let session_name = params
+        .get("RoleSessionName")
+        .cloned()
+        .unwrap_or_else(|| "hafiz-session".to_string());
+
+    let duration = match params.get("DurationSeconds") {
+        Some(value) => value
+            .parse::<u64>()
+            .map_err(|_| Error::InvalidArgument(format!("Invalid DurationSeconds: {}", value)))?,
+        None => config.sts_default_duration_seconds,
+    };
+    if duration < MIN_DURATION_SECONDS || duration > config.sts_max_duration_seconds {
+        return Err(Error::InvalidArgument(format!(
+            "DurationSeconds must be between {} and {}",
+            MIN_DURATION_SECONDS, config.sts_max_duration_seconds
+        )));
+    }
+
+    let identity = verifier.verify(token).await?;
+
+    let (access_key, secret_key, session_token) = generate_temporary_credentials();
+    let now = Utc::now();
+    let cred = TemporaryCredentials {
+        access_key,
+        secret_key,
+        session_token,
+        subject: identity.subject.clone(),
+        username: identity.username.clone(),
+        policies: identity.policies.clone(),
+        created_at: now,
+        expires_at: now + Duration::seconds(duration as i64),
+    };
+    state.metadata.create_temporary_credentials(&cred).await?;
+
+    // Expired sessions are only ever read to be rejected, so tidy up here
+    match state.metadata.purge_expired_temporary_credentials().await {
+        Ok(0) => {}
+        Ok(n) => debug!("Purged {} expired STS sessions", n),
+        Err(e) => warn!("Failed to purge expired STS sessions: {}", e),
+    }
+
+    info!(
+        "Issued temporary credentials {} to {} ({}), expiring {}",
+        cred.access_key, identity.username, identity.subject, cred.expires_at
+    );
+
+    Ok(xml::assume_role_with_web_identity_response(
+        &cred,
+        &session_name,
+        config.audiences.first().map(String::as_str).unwrap_or_default(),
+        &config.issuer_url,
+        request_id,
+    ))
+}
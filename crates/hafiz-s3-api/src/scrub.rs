@@ -0,0 +1,433 @@
+//! Background object integrity scrubber
+//!
+//! Reads every stored object back and compares it with the ETag recorded
+//! at upload time, so bit rot is found before a client downloads it.
+//! Single-part objects are checked against their MD5; multipart ETags are
+//! not a content hash, so those are checked by size only.
+//!
+//! A damaged object is first restored from a healthy replica when running
+//! in cluster mode. Otherwise its data is moved into [`QUARANTINE_BUCKET`]
+//! (if enabled) so reads fail instead of returning corrupt bytes. Only the
+//! current version of each object is scrubbed.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hafiz_core::config::ScrubConfig;
+use hafiz_core::types::ObjectInfo;
+use hafiz_core::Error;
+use parking_lot::RwLock;
+use serde::Serialize;
+use tracing::{debug, error, info, warn};
+
+use crate::routes::storage_key;
+use crate::server::AppState;
+use crate::sse;
+
+/// Bucket holding the data of quarantined objects, under `<bucket>/<key>`
+pub const QUARANTINE_BUCKET: &str = ".hafiz-quarantine";
+
+/// Objects listed per metadata page
+const LIST_PAGE_SIZE: i32 = 1000;
+
+/// Findings kept in a report; counters keep counting past this
+const MAX_FINDINGS: usize = 1000;
+
+/// What is wrong with an object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubIssue {
+    /// Data does not hash to the recorded ETag
+    Corrupted,
+    /// Data length differs from the recorded size
+    SizeMismatch,
+    /// Metadata exists but the data is gone
+    Missing,
+}
+
+/// What the scrubber did about it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScrubAction {
+    /// Rewritten from an intact replica
+    Repaired,
+    /// Data moved to the quarantine bucket
+    Quarantined,
+    /// Left in place
+    Reported,
+}
+
+/// One damaged object
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrubFinding {
+    pub bucket: String,
+    pub key: String,
+    pub version_id: String,
+    pub issue: ScrubIssue,
+    pub action: ScrubAction,
+    /// Node the object was restored from, or why handling it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+/// Outcome of one scrub run
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrubReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub buckets_scanned: u64,
+    pub objects_checked: u64,
+    pub bytes_checked: u64,
+    pub objects_damaged: u64,
+    pub objects_repaired: u64,
+    pub objects_quarantined: u64,
+    pub findings: Vec<ScrubFinding>,
+    pub errors: Vec<String>,
+}
+
+impl ScrubReport {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            finished_at: None,
+            buckets_scanned: 0,
+            objects_checked: 0,
+            bytes_checked: 0,
+            objects_damaged: 0,
+            objects_repaired: 0,
+            objects_quarantined: 0,
+            findings: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Scrubber state as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct ScrubStatus {
+    pub scheduled: bool,
+    pub interval_secs: u64,
+    pub running: bool,
+    pub last_run: Option<ScrubReport>,
+}
+
+/// Periodic integrity checker
+pub struct Scrubber {
+    config: ScrubConfig,
+    running: AtomicBool,
+    last_run: RwLock<Option<ScrubReport>>,
+}
+
+impl Scrubber {
+    pub fn new(config: ScrubConfig) -> Self {
+        Self {
+            config,
+            running: AtomicBool::new(false),
+            last_run: RwLock::new(None),
+        }
+    }
+
+    pub fn status(&self) -> ScrubStatus {
+        ScrubStatus {
+            scheduled: self.config.enabled,
+            interval_secs: self.config.interval_secs,
+            running: self.is_running(),
+            last_run: self.last_run.read().clone(),
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    /// Start scheduled runs if the scrubber is enabled
+    pub fn start(self: &Arc<Self>, state: AppState) {
+        if !self.config.enabled {
+            return;
+        }
+
+        let scrubber = Arc::clone(self);
+        let interval = Duration::from_secs(self.config.interval_secs.max(60));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // Skip the immediate first tick so startup is not slowed down
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if scrubber.run(&state, None).await.is_none() {
+                    debug!("Skipping scheduled scrub; a run is in progress");
+                }
+            }
+        });
+
+        info!("Integrity scrubber started (every {:?})", interval);
+    }
+
+    /// Scrub every bucket, or only `bucket`
+    ///
+    /// Returns `None` without doing anything if a run is already in progress.
+    pub async fn run(&self, state: &AppState, bucket: Option<&str>) -> Option<ScrubReport> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+
+        let mut report = ScrubReport::new();
+        let buckets = match bucket {
+            Some(bucket) => Ok(vec![bucket.to_string()]),
+            None => state.metadata.list_bucket_names().await,
+        };
+        match buckets {
+            Ok(buckets) => {
+                for bucket in buckets.iter().filter(|b| b.as_str() != QUARANTINE_BUCKET) {
+                    if let Err(e) = self.scrub_bucket(state, bucket, &mut report).await {
+                        report.errors.push(format!("{}: {}", bucket, e));
+                    }
+                }
+            }
+            Err(e) => report.errors.push(e.to_string()),
+        }
+        report.finished_at = Some(Utc::now());
+
+        metrics::gauge!("hafiz_scrub_last_run_timestamp_seconds")
+            .set(report.started_at.timestamp() as f64);
+        info!(
+            "Scrub finished: {} objects ({} bytes) checked, {} damaged, {} repaired, {} quarantined",
+            report.objects_checked,
+            report.bytes_checked,
+            report.objects_damaged,
+            report.objects_repaired,
+            report.objects_quarantined
+        );
+
+        *self.last_run.write() = Some(report.clone());
+        self.running.store(false, Ordering::SeqCst);
+        Some(report)
+    }
+
+    async fn scrub_bucket(
+        &self,
+        state: &AppState,
+        bucket: &str,
+        report: &mut ScrubReport,
+    ) -> hafiz_core::Result<()> {
+        let mut token: Option<String> = None;
+        loop {
+            let (objects, _, truncated, next) = state
+                .metadata
+                .list_objects(bucket, None, None, LIST_PAGE_SIZE, token.as_deref())
+                .await?;
+
+            for object in &objects {
+                self.scrub_object(state, bucket, object, report).await;
+            }
+
+            if !truncated || next.is_none() {
+                break;
+            }
+            token = next;
+        }
+
+        report.buckets_scanned += 1;
+        Ok(())
+    }
+
+    async fn scrub_object(
+        &self,
+        state: &AppState,
+        bucket: &str,
+        object: &ObjectInfo,
+        report: &mut ScrubReport,
+    ) {
+        let version_id = object.version_id.as_deref().unwrap_or("null");
+        let storage_key = storage_key(&object.key, version_id);
+
+        let (issue, data) = match state.storage.get(bucket, &storage_key).await {
+            Ok(data) => {
+                report.objects_checked += 1;
+                report.bytes_checked += data.len() as u64;
+                metrics::counter!("hafiz_scrub_objects_checked_total").increment(1);
+                metrics::counter!("hafiz_scrub_bytes_checked_total").increment(data.len() as u64);
+                self.throttle(data.len()).await;
+
+                let mut issue = verify(object, &data);
+                if issue == Some(ScrubIssue::SizeMismatch) {
+                    if let Some(size) = sealed_size(state, bucket, object).await {
+                        issue = verify(&ObjectInfo { size, ..object.clone() }, &data);
+                    }
+                }
+                match issue {
+                    Some(issue) => (issue, Some(data)),
+                    None => return,
+                }
+            }
+            Err(Error::NoSuchKey) => {
+                report.objects_checked += 1;
+                (ScrubIssue::Missing, None)
+            }
+            Err(e) => {
+                report
+                    .errors
+                    .push(format!("{}/{}: {}", bucket, object.key, e));
+                return;
+            }
+        };
+
+        report.objects_damaged += 1;
+        metrics::counter!("hafiz_scrub_damaged_objects_total", "issue" => issue_label(issue))
+            .increment(1);
+        warn!("Scrub found {}/{} {:?}", bucket, object.key, issue);
+
+        let mut detail = None;
+        let mut action = ScrubAction::Reported;
+
+        #[cfg(feature = "cluster")]
+        if let Some(cluster) = &state.cluster {
+            match cluster
+                .anti_entropy()
+                .restore_object(bucket, &object.key, version_id, &object.etag, object.size)
+                .await
+            {
+                Ok(node) => {
+                    action = ScrubAction::Repaired;
+                    detail = Some(node);
+                }
+                Err(e) => detail = Some(e.to_string()),
+            }
+        }
+
+        if action == ScrubAction::Reported && self.config.quarantine {
+            if let Some(data) = data {
+                match quarantine(state, bucket, &storage_key, data).await {
+                    Ok(()) => action = ScrubAction::Quarantined,
+                    Err(e) => {
+                        error!("Failed to quarantine {}/{}: {}", bucket, object.key, e);
+                        detail = Some(e.to_string());
+                    }
+                }
+            }
+        }
+
+        match action {
+            ScrubAction::Repaired => {
+                report.objects_repaired += 1;
+                metrics::counter!("hafiz_scrub_repaired_objects_total").increment(1);
+            }
+            ScrubAction::Quarantined => {
+                report.objects_quarantined += 1;
+                metrics::counter!("hafiz_scrub_quarantined_objects_total").increment(1);
+            }
+            ScrubAction::Reported => {}
+        }
+
+        if report.findings.len() < MAX_FINDINGS {
+            report.findings.push(ScrubFinding {
+                bucket: bucket.to_string(),
+                key: object.key.clone(),
+                version_id: version_id.to_string(),
+                issue,
+                action,
+                detail,
+            });
+        }
+    }
+
+    /// Sleep long enough to stay under the configured read rate
+    async fn throttle(&self, bytes: usize) {
+        if self.config.max_bytes_per_sec == 0 || bytes == 0 {
+            return;
+        }
+        let secs = bytes as f64 / self.config.max_bytes_per_sec as f64;
+        tokio::time::sleep(Duration::from_secs_f64(secs)).await;
+    }
+}
+
+/// Check object data against its metadata
+fn verify(object: &ObjectInfo, data: &[u8]) -> Option<ScrubIssue> {
+    if data.len() as i64 != object.size {
+        return Some(ScrubIssue::SizeMismatch);
+    }
+    // Multipart ETags are a hash of part hashes, not of the content
+    let etag = object.etag.trim_matches('"');
+    if !hafiz_crypto::is_multipart_etag(etag) && hafiz_crypto::md5_hash(data) != etag {
+        return Some(ScrubIssue::Corrupted);
+    }
+    None
+}
+
+/// Stored size of an object whose data is encrypted, and so longer than
+/// the object
+async fn sealed_size(state: &AppState, bucket: &str, object: &ObjectInfo) -> Option<i64> {
+    let version = state
+        .metadata
+        .get_object_version(bucket, &object.key, object.version_id.as_deref())
+        .await
+        .ok()??;
+    if !version.encryption.is_sealed() {
+        return None;
+    }
+    sse::stored_len(&version.encryption, version.size).ok().map(|len| len as i64)
+}
+
+fn issue_label(issue: ScrubIssue) -> &'static str {
+    match issue {
+        ScrubIssue::Corrupted => "corrupted",
+        ScrubIssue::SizeMismatch => "size_mismatch",
+        ScrubIssue::Missing => "missing",
+    }
+}
+
+/// Move damaged data out of its bucket so it is no longer served
+async fn quarantine(
+    state: &AppState,
+    bucket: &str,
+    storage_key: &str,
+    data: bytes::Bytes,
+) -> hafiz_core::Result<()> {
+    if !state.storage.bucket_exists(QUARANTINE_BUCKET).await? {
+        state.storage.create_bucket(QUARANTINE_BUCKET).await?;
+    }
+    state
+        .storage
+        .put(QUARANTINE_BUCKET, &format!("{}/{}", bucket, storage_key), data)
+        .await?;
+    state.storage.delete(bucket, storage_key).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(data: &[u8], etag: String) -> ObjectInfo {
+        ObjectInfo {
+            key: "k".to_string(),
+            last_modified: Utc::now(),
+            etag,
+            size: data.len() as i64,
+            storage_class: "STANDARD".to_string(),
+            version_id: None,
+            is_latest: Some(true),
+        }
+    }
+
+    #[test]
+    fn test_verify_detects_flipped_bits() {
+        let data = b"hello scrubber".to_vec();
+        let obj = object(&data, hafiz_crypto::md5_hash(&data));
+        assert_eq!(verify(&obj, &data), None);
+
+        let mut rotten = data.clone();
+        rotten[3] ^= 0x01;
+        assert_eq!(verify(&obj, &rotten), Some(ScrubIssue::Corrupted));
+        assert_eq!(verify(&obj, &data[1..]), Some(ScrubIssue::SizeMismatch));
+    }
+
+    #[test]
+    fn test_multipart_objects_are_checked_by_size() {
+        let data = b"two parts".to_vec();
+        let obj = object(&data, "0123456789abcdef0123456789abcdef-2".to_string());
+        assert_eq!(verify(&obj, &data), None);
+        assert_eq!(verify(&obj, b"short"), Some(ScrubIssue::SizeMismatch));
+    }
+}
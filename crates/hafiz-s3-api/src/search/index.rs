@@ -0,0 +1,390 @@
+//! Tantivy index of objects
+//!
+//! One document per object, identified by bucket and key. Free text
+//! matches the key, user metadata values and tag values; fields can be
+//! queried by name, e.g. `tags.project:apollo`, `meta.camera:nikon`,
+//! `size:>1000000` or `modified:[2024-01-01T00:00:00Z TO *]`.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use chrono::{TimeZone, Utc};
+use hafiz_core::{Error, Result};
+use parking_lot::Mutex;
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, RegexQuery, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, JsonObjectOptions, OwnedValue, Schema, Value, INDEXED, STORED,
+    STRING, TEXT,
+};
+use tantivy::{DateTime, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+use super::{IndexedObject, SearchHit, SearchQuery};
+
+/// Memory the writer buffers documents in before flushing a segment
+const WRITER_MEMORY: usize = 50_000_000;
+
+/// A change to apply to the index
+pub(super) enum Change {
+    Upsert(IndexedObject),
+    Remove { bucket: String, key: String },
+}
+
+struct Fields {
+    /// `bucket/key`, to replace and delete documents by
+    id: Field,
+    bucket: Field,
+    /// The key as one term, for prefix filters
+    path: Field,
+    /// The key split into words
+    key: Field,
+    meta: Field,
+    tags: Field,
+    /// Key, metadata values and tag values, for unqualified terms
+    text: Field,
+    size: Field,
+    modified: Field,
+    etag: Field,
+    content_type: Field,
+}
+
+impl Fields {
+    fn schema() -> (Schema, Self) {
+        let mut builder = Schema::builder();
+        let json = JsonObjectOptions::from(TEXT).set_expand_dots_enabled();
+        let fields = Self {
+            id: builder.add_text_field("id", STRING),
+            bucket: builder.add_text_field("bucket", STRING | STORED),
+            path: builder.add_text_field("path", STRING | STORED),
+            key: builder.add_text_field("key", TEXT),
+            meta: builder.add_json_field("meta", json.clone()),
+            tags: builder.add_json_field("tags", json),
+            text: builder.add_text_field("text", TEXT),
+            size: builder.add_u64_field("size", INDEXED | STORED),
+            modified: builder.add_date_field("modified", INDEXED | STORED),
+            etag: builder.add_text_field("etag", STORED),
+            content_type: builder.add_text_field("content_type", STRING | STORED),
+        };
+        (builder.build(), fields)
+    }
+}
+
+pub(super) struct ObjectIndex {
+    index: Index,
+    reader: IndexReader,
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+impl ObjectIndex {
+    /// Open the index in `dir`, creating it if needed
+    ///
+    /// Also returns whether the index was created.
+    pub fn open(dir: &Path) -> Result<(Self, bool)> {
+        std::fs::create_dir_all(dir)?;
+        let (schema, fields) = Fields::schema();
+        let directory = MmapDirectory::open(dir).map_err(index_error)?;
+        let created = !Index::exists(&directory).map_err(index_error)?;
+        let index = Index::open_or_create(directory, schema).map_err(index_error)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(index_error)?;
+        let writer = index.writer(WRITER_MEMORY).map_err(index_error)?;
+        Ok((
+            Self {
+                index,
+                reader,
+                writer: Mutex::new(writer),
+                fields,
+            },
+            created,
+        ))
+    }
+
+    /// Apply `changes` and make them searchable
+    pub fn apply(&self, changes: Vec<Change>) -> Result<()> {
+        let mut writer = self.writer.lock();
+        for change in changes {
+            match change {
+                Change::Upsert(object) => {
+                    writer.delete_term(self.id(&object.bucket, &object.key));
+                    writer
+                        .add_document(self.document(object))
+                        .map_err(index_error)?;
+                }
+                Change::Remove { bucket, key } => {
+                    writer.delete_term(self.id(&bucket, &key));
+                }
+            }
+        }
+        writer.commit().map_err(index_error)?;
+        self.reader.reload().map_err(index_error)
+    }
+
+    /// Remove every document
+    pub fn clear(&self) -> Result<()> {
+        let mut writer = self.writer.lock();
+        writer.delete_all_documents().map_err(index_error)?;
+        writer.commit().map_err(index_error)?;
+        self.reader.reload().map_err(index_error)
+    }
+
+    pub fn num_docs(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+
+    /// Best matches of `query`, best first
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchHit>> {
+        let f = &self.fields;
+        let mut parser = QueryParser::for_index(&self.index, vec![f.text, f.key]);
+        parser.set_conjunction_by_default();
+        let parsed = parser
+            .parse_query(&query.q)
+            .map_err(|e| Error::InvalidArgument(format!("Invalid search query: {}", e)))?;
+
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![(Occur::Must, parsed)];
+        if let Some(bucket) = &query.bucket {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(f.bucket, bucket),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+        if let Some(prefix) = query.prefix.as_deref().filter(|p| !p.is_empty()) {
+            let pattern = format!("{}.*", regex::escape(prefix));
+            let prefix_query = RegexQuery::from_pattern(&pattern, f.path).map_err(index_error)?;
+            clauses.push((Occur::Must, Box::new(prefix_query)));
+        }
+
+        let searcher = self.reader.searcher();
+        let top = searcher
+            .search(
+                &BooleanQuery::new(clauses),
+                &TopDocs::with_limit(query.limit),
+            )
+            .map_err(index_error)?;
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let doc: TantivyDocument = searcher.doc(address).map_err(index_error)?;
+            let text = |field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            let modified = doc
+                .get_first(f.modified)
+                .and_then(|v| v.as_datetime())
+                .map(|d| d.into_timestamp_secs())
+                .unwrap_or_default();
+            hits.push(SearchHit {
+                bucket: text(f.bucket),
+                key: text(f.path),
+                size: doc
+                    .get_first(f.size)
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or_default(),
+                etag: text(f.etag),
+                content_type: Some(text(f.content_type)).filter(|t| !t.is_empty()),
+                last_modified: Utc.timestamp_opt(modified, 0).single().unwrap_or_default(),
+                score,
+            });
+        }
+        Ok(hits)
+    }
+
+    fn id(&self, bucket: &str, key: &str) -> Term {
+        Term::from_field_text(self.fields.id, &format!("{}/{}", bucket, key))
+    }
+
+    fn document(&self, object: IndexedObject) -> TantivyDocument {
+        let f = &self.fields;
+        let mut doc = TantivyDocument::default();
+        doc.add_text(f.id, format!("{}/{}", object.bucket, object.key));
+        doc.add_text(f.bucket, &object.bucket);
+        doc.add_text(f.path, &object.key);
+        doc.add_text(f.key, &object.key);
+        doc.add_text(f.text, &object.key);
+        for value in object.metadata.values().chain(object.tags.values()) {
+            doc.add_text(f.text, value);
+        }
+        doc.add_object(f.meta, json_object(object.metadata));
+        doc.add_object(f.tags, json_object(object.tags));
+        doc.add_u64(f.size, object.size);
+        doc.add_date(
+            f.modified,
+            DateTime::from_timestamp_secs(object.last_modified.timestamp()),
+        );
+        doc.add_text(f.etag, &object.etag);
+        if let Some(content_type) = &object.content_type {
+            doc.add_text(f.content_type, content_type);
+        }
+        doc
+    }
+}
+
+fn json_object(values: BTreeMap<String, String>) -> BTreeMap<String, OwnedValue> {
+    values
+        .into_iter()
+        .map(|(name, value)| (name, OwnedValue::Str(value)))
+        .collect()
+}
+
+fn index_error(e: impl std::fmt::Display) -> Error {
+    Error::InternalError(format!("Search index error: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(bucket: &str, key: &str, size: u64) -> IndexedObject {
+        IndexedObject {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            size,
+            etag: "etag".to_string(),
+            content_type: Some("image/jpeg".to_string()),
+            last_modified: Utc::now(),
+            metadata: BTreeMap::new(),
+            tags: BTreeMap::new(),
+        }
+    }
+
+    fn query(q: &str) -> SearchQuery {
+        SearchQuery {
+            q: q.to_string(),
+            bucket: None,
+            prefix: None,
+            limit: 10,
+        }
+    }
+
+    fn keys(hits: &[SearchHit]) -> Vec<String> {
+        let mut keys: Vec<_> = hits
+            .iter()
+            .map(|h| format!("{}/{}", h.bucket, h.key))
+            .collect();
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn test_search_keys_metadata_and_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let (index, created) = ObjectIndex::open(dir.path()).unwrap();
+        assert!(created);
+
+        let mut cat = object("photos", "2024/holiday/cat.jpg", 2048);
+        cat.metadata
+            .insert("camera".to_string(), "Nikon Z6".to_string());
+        let mut report = object("docs", "reports/q3.pdf", 10);
+        report
+            .tags
+            .insert("project".to_string(), "apollo".to_string());
+        report
+            .tags
+            .insert("app.owner".to_string(), "finance".to_string());
+        index
+            .apply(vec![Change::Upsert(cat), Change::Upsert(report)])
+            .unwrap();
+        assert_eq!(index.num_docs(), 2);
+
+        assert_eq!(
+            keys(&index.search(&query("holiday")).unwrap()),
+            ["photos/2024/holiday/cat.jpg"]
+        );
+        assert_eq!(
+            keys(&index.search(&query("nikon")).unwrap()),
+            ["photos/2024/holiday/cat.jpg"]
+        );
+        assert_eq!(
+            keys(&index.search(&query("meta.camera:nikon")).unwrap()),
+            ["photos/2024/holiday/cat.jpg"]
+        );
+        assert_eq!(
+            keys(&index.search(&query("tags.project:apollo")).unwrap()),
+            ["docs/reports/q3.pdf"]
+        );
+        assert_eq!(
+            keys(&index.search(&query("tags.app.owner:finance")).unwrap()),
+            ["docs/reports/q3.pdf"]
+        );
+        assert!(index
+            .search(&query("tags.project:nikon"))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            keys(&index.search(&query("size:>1000")).unwrap()),
+            ["photos/2024/holiday/cat.jpg"]
+        );
+
+        assert_eq!(
+            index
+                .search(&query("content_type:\"image/jpeg\""))
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let hit = &index.search(&query("cat")).unwrap()[0];
+        assert_eq!(hit.size, 2048);
+        assert_eq!(hit.content_type.as_deref(), Some("image/jpeg"));
+
+        assert!(matches!(
+            index.search(&query("size:[")),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_filters_replace_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let (index, _) = ObjectIndex::open(dir.path()).unwrap();
+        index
+            .apply(vec![
+                Change::Upsert(object("a", "logs/app.log", 1)),
+                Change::Upsert(object("a", "old/app.log", 1)),
+                Change::Upsert(object("b", "logs/app.log", 1)),
+            ])
+            .unwrap();
+
+        let mut q = query("app");
+        q.bucket = Some("a".to_string());
+        assert_eq!(
+            keys(&index.search(&q).unwrap()),
+            ["a/logs/app.log", "a/old/app.log"]
+        );
+        q.prefix = Some("logs/".to_string());
+        assert_eq!(keys(&index.search(&q).unwrap()), ["a/logs/app.log"]);
+
+        // Writing an object again replaces its document
+        let mut tagged = object("a", "logs/app.log", 1);
+        tagged.tags.insert("level".to_string(), "debug".to_string());
+        index
+            .apply(vec![
+                Change::Upsert(tagged),
+                Change::Remove {
+                    bucket: "b".to_string(),
+                    key: "logs/app.log".to_string(),
+                },
+            ])
+            .unwrap();
+        assert_eq!(index.num_docs(), 2);
+        assert_eq!(
+            keys(&index.search(&query("debug")).unwrap()),
+            ["a/logs/app.log"]
+        );
+
+        index.clear().unwrap();
+        assert_eq!(index.num_docs(), 0);
+        drop(index);
+        let (_, created) = ObjectIndex::open(dir.path()).unwrap();
+        assert!(!created);
+    }
+}
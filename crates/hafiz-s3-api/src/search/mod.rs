@@ -0,0 +1,392 @@
+//! Object search
+//!
+//! An optional full-text index, enabled by `[search]` in builds with the
+//! `search` feature, of the keys, user metadata and tags of the latest
+//! version of each object, so objects can be found without listing whole
+//! buckets.
+//!
+//! Handlers that write, delete or retag an object queue its key with
+//! [`Search::object_changed`]. A worker reads each queued object back from
+//! the metadata store and indexes what it finds, or drops the object when
+//! it is gone or its latest version is a delete marker, so the index
+//! follows the metadata store whatever the change was. Changes are applied
+//! in batches and become searchable within about a second.
+//!
+//! A new index is built from the metadata store at startup, and the index
+//! is rebuilt after a metadata import. A rebuild can also be started
+//! through the admin API, e.g. after the metadata database was restored
+//! from a backup.
+
+#[cfg(feature = "search")]
+mod index;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hafiz_core::config::SearchConfig;
+use hafiz_core::{Error, Result};
+#[cfg(feature = "search")]
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+#[cfg(feature = "search")]
+use tracing::info;
+use tracing::warn;
+
+use crate::server::AppState;
+
+/// Most hits one search returns
+pub const MAX_SEARCH_LIMIT: usize = 1000;
+
+/// Queued object keys a rebuild waits to drain before listing more
+#[cfg(feature = "search")]
+const REBUILD_BACKLOG: usize = 10_000;
+
+/// Objects the worker indexes per commit
+#[cfg(feature = "search")]
+const BATCH_SIZE: usize = 1000;
+
+/// How long the worker collects changes before committing them
+#[cfg(feature = "search")]
+const COMMIT_DELAY: Duration = Duration::from_millis(500);
+
+fn default_limit() -> usize {
+    100
+}
+
+/// Query of `GET /api/v1/search`
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchQuery {
+    /// Terms to match, in the query syntax of the index
+    pub q: String,
+    /// Only objects in this bucket
+    #[serde(default)]
+    pub bucket: Option<String>,
+    /// Only keys starting with this
+    #[serde(default)]
+    pub prefix: Option<String>,
+    /// Most hits to return, up to [`MAX_SEARCH_LIMIT`]
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+}
+
+/// An object matching a search
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub bucket: String,
+    pub key: String,
+    pub size: u64,
+    pub etag: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    pub last_modified: DateTime<Utc>,
+    /// Relevance; higher is better
+    pub score: f32,
+}
+
+/// Index state as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchStatus {
+    pub enabled: bool,
+    /// Objects in the index
+    pub documents: u64,
+    /// Changed objects not yet indexed
+    pub pending: usize,
+    pub rebuilding: bool,
+}
+
+/// The indexed fields of an object
+#[cfg_attr(not(feature = "search"), allow(dead_code))]
+pub(crate) struct IndexedObject {
+    pub bucket: String,
+    pub key: String,
+    pub size: u64,
+    pub etag: String,
+    pub content_type: Option<String>,
+    pub last_modified: DateTime<Utc>,
+    pub metadata: BTreeMap<String, String>,
+    pub tags: BTreeMap<String, String>,
+}
+
+type Update = (String, String);
+
+/// The object index and the queue of objects to update in it
+pub struct Search {
+    #[cfg(feature = "search")]
+    index: Option<Arc<index::ObjectIndex>>,
+    updates: Option<mpsc::UnboundedSender<Update>>,
+    /// Taken by the worker at start
+    #[cfg(feature = "search")]
+    receiver: Mutex<Option<mpsc::UnboundedReceiver<Update>>>,
+    /// Objects queued or being indexed
+    pending: Arc<AtomicUsize>,
+    rebuilding: Arc<AtomicBool>,
+    /// Build the index at start; set when it was just created
+    #[cfg(feature = "search")]
+    rebuild_at_start: bool,
+}
+
+impl Search {
+    /// Open the index configured by `config`
+    ///
+    /// The index lives in `search-index` under `data_dir` unless
+    /// `index_dir` is set.
+    pub fn open(config: &SearchConfig, data_dir: &Path) -> Result<Self> {
+        #[cfg_attr(not(feature = "search"), allow(unused_mut))]
+        let mut search = Self {
+            #[cfg(feature = "search")]
+            index: None,
+            updates: None,
+            #[cfg(feature = "search")]
+            receiver: Mutex::new(None),
+            pending: Arc::new(AtomicUsize::new(0)),
+            rebuilding: Arc::new(AtomicBool::new(false)),
+            #[cfg(feature = "search")]
+            rebuild_at_start: false,
+        };
+        if !config.enabled {
+            return Ok(search);
+        }
+
+        #[cfg(feature = "search")]
+        {
+            let dir = config
+                .index_dir
+                .clone()
+                .unwrap_or_else(|| data_dir.join("search-index"));
+            let (index, created) = index::ObjectIndex::open(&dir)?;
+            let (sender, receiver) = mpsc::unbounded_channel();
+            info!(
+                "Object search index at {} ({} objects)",
+                dir.display(),
+                index.num_docs()
+            );
+            search.index = Some(Arc::new(index));
+            search.updates = Some(sender);
+            search.receiver = Mutex::new(Some(receiver));
+            search.rebuild_at_start = created;
+        }
+        #[cfg(not(feature = "search"))]
+        {
+            let _ = data_dir;
+            warn!("Object search is enabled but this build lacks the search feature");
+        }
+        Ok(search)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.updates.is_some()
+    }
+
+    /// Queue `bucket/key` for reindexing after it was written, deleted or
+    /// retagged
+    pub fn object_changed(&self, bucket: &str, key: &str) {
+        if let Some(updates) = &self.updates {
+            self.pending.fetch_add(1, Ordering::SeqCst);
+            if updates.send((bucket.to_string(), key.to_string())).is_err() {
+                self.pending.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Wait until every queued object has been indexed
+    ///
+    /// Returns false if objects were still pending when `timeout` ran out.
+    pub async fn flush(&self, timeout: Duration) -> bool {
+        let deadline = tokio::time::Instant::now() + timeout;
+        while self.pending.load(Ordering::SeqCst) > 0 {
+            if tokio::time::Instant::now() >= deadline {
+                return false;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        true
+    }
+
+    pub fn status(&self) -> SearchStatus {
+        #[cfg(feature = "search")]
+        let documents = self.index.as_ref().map_or(0, |index| index.num_docs());
+        #[cfg(not(feature = "search"))]
+        let documents = 0;
+        SearchStatus {
+            enabled: self.is_enabled(),
+            documents,
+            pending: self.pending.load(Ordering::SeqCst),
+            rebuilding: self.rebuilding.load(Ordering::SeqCst),
+        }
+    }
+
+    fn disabled() -> Error {
+        Error::ServiceUnavailable("Object search is not enabled".into())
+    }
+}
+
+#[cfg(feature = "search")]
+impl Search {
+    /// Start the indexing worker, and build a new index
+    pub fn start(&self, state: AppState) {
+        let (Some(index), Some(receiver)) = (self.index.clone(), self.receiver.lock().take())
+        else {
+            return;
+        };
+        tokio::spawn(index_worker(
+            index,
+            receiver,
+            self.pending.clone(),
+            state.clone(),
+        ));
+        if self.rebuild_at_start {
+            info!("Building the object search index");
+            let _ = self.rebuild(state);
+        }
+    }
+
+    /// Objects matching `query`, best first
+    pub async fn search(&self, mut query: SearchQuery) -> Result<Vec<SearchHit>> {
+        let index = self.index.clone().ok_or_else(Self::disabled)?;
+        if query.q.trim().is_empty() {
+            return Err(Error::InvalidArgument("Empty search query".into()));
+        }
+        query.limit = query.limit.clamp(1, MAX_SEARCH_LIMIT);
+        tokio::task::spawn_blocking(move || index.search(&query))
+            .await
+            .map_err(|e| Error::InternalError(e.to_string()))?
+    }
+
+    /// Empty the index and index every object again, in the background
+    ///
+    /// Searches miss objects not yet indexed until the rebuild finishes.
+    pub fn rebuild(&self, state: AppState) -> Result<SearchStatus> {
+        let index = self.index.clone().ok_or_else(Self::disabled)?;
+        if self.rebuilding.swap(true, Ordering::SeqCst) {
+            return Err(Error::OperationAborted(
+                "The search index is already being rebuilt".into(),
+            ));
+        }
+
+        let rebuilding = self.rebuilding.clone();
+        tokio::spawn(async move {
+            match rebuild_index(&state, index).await {
+                Ok(queued) => info!("Queued {} objects for the search index", queued),
+                Err(e) => warn!("Search index rebuild failed: {}", e),
+            }
+            rebuilding.store(false, Ordering::SeqCst);
+        });
+        Ok(self.status())
+    }
+}
+
+#[cfg(not(feature = "search"))]
+impl Search {
+    pub fn start(&self, _state: AppState) {}
+
+    pub async fn search(&self, _query: SearchQuery) -> Result<Vec<SearchHit>> {
+        Err(Self::disabled())
+    }
+
+    pub fn rebuild(&self, _state: AppState) -> Result<SearchStatus> {
+        Err(Self::disabled())
+    }
+}
+
+/// Index queued objects in batches until the queue closes
+#[cfg(feature = "search")]
+async fn index_worker(
+    index: Arc<index::ObjectIndex>,
+    mut receiver: mpsc::UnboundedReceiver<Update>,
+    pending: Arc<AtomicUsize>,
+    state: AppState,
+) {
+    use std::collections::BTreeSet;
+
+    while let Some(first) = receiver.recv().await {
+        let mut received = 1;
+        let mut batch = BTreeSet::from([first]);
+        let deadline = tokio::time::Instant::now() + COMMIT_DELAY;
+        while batch.len() < BATCH_SIZE {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(update)) => {
+                    received += 1;
+                    batch.insert(update);
+                }
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let mut changes = Vec::with_capacity(batch.len());
+        for (bucket, key) in batch {
+            match load_change(&state, bucket, key).await {
+                Ok(change) => changes.push(change),
+                Err(e) => warn!("Failed to read object for the search index: {}", e),
+            }
+        }
+        let applied = {
+            let index = index.clone();
+            tokio::task::spawn_blocking(move || index.apply(changes)).await
+        };
+        match applied {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => warn!("Failed to update the search index: {}", e),
+            Err(e) => warn!("Failed to update the search index: {}", e),
+        }
+        pending.fetch_sub(received, Ordering::SeqCst);
+    }
+}
+
+/// What the index should hold for `bucket/key`
+#[cfg(feature = "search")]
+async fn load_change(state: &AppState, bucket: String, key: String) -> Result<index::Change> {
+    let object = match state.metadata.get_object(&bucket, &key).await? {
+        Some(object) if !object.is_delete_marker => object,
+        _ => return Ok(index::Change::Remove { bucket, key }),
+    };
+    let tags = state.metadata.get_object_tags(&bucket, &key, None).await?;
+    Ok(index::Change::Upsert(IndexedObject {
+        size: object.size.max(0) as u64,
+        etag: object.etag,
+        content_type: Some(object.content_type).filter(|t| !t.is_empty()),
+        last_modified: object.last_modified,
+        metadata: object.metadata.into_iter().collect(),
+        tags: tags.tags.into_iter().map(|t| (t.key, t.value)).collect(),
+        bucket,
+        key,
+    }))
+}
+
+/// Empty the index and queue every object of every bucket
+#[cfg(feature = "search")]
+async fn rebuild_index(state: &AppState, index: Arc<index::ObjectIndex>) -> Result<u64> {
+    const LIST_PAGE_SIZE: i32 = 1000;
+
+    tokio::task::spawn_blocking(move || index.clear())
+        .await
+        .map_err(|e| Error::InternalError(e.to_string()))??;
+
+    let mut queued = 0;
+    for bucket in state.metadata.list_bucket_names().await? {
+        let mut token = None;
+        loop {
+            // Let the worker keep up rather than queueing every key at once
+            while state.search.status().pending > REBUILD_BACKLOG {
+                tokio::time::sleep(COMMIT_DELAY).await;
+            }
+            let (objects, truncated, next) = state
+                .metadata
+                .list_objects_flat(&bucket, None, LIST_PAGE_SIZE, token.as_deref())
+                .await?;
+            for object in &objects {
+                state.search.object_changed(&bucket, &object.key);
+            }
+            queued += objects.len() as u64;
+            if !truncated || next.is_none() {
+                break;
+            }
+            token = next;
+        }
+    }
+    Ok(queued)
+}
@@ -1,30 +1,63 @@
 //! S3 Server implementation
 
 use axum::{
+    extract::{ConnectInfo, DefaultBodyLimit},
     middleware,
     routing::{delete, get, head, options, post, put},
     Router,
     response::Html,
 };
-use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::rt::{TokioExecutor, TokioIo, TokioTimer};
+use hyper_util::server::conn::auto::Builder;
+use hafiz_auth::OidcVerifier;
 use hafiz_core::{config::HafizConfig, Result};
-use hafiz_metadata::MetadataStore;
-use hafiz_storage::LocalStorage;
+use hafiz_crypto::AccountKeys;
+use hafiz_metadata::{MetadataCache, MetadataStore};
+use hafiz_storage::{
+    CompressedStorage, DedupStorage, LocalStorage, ObjectCache, ReadCache, S3Gateway, SpaceGuard,
+    SpaceMonitor, StorageEngine, StorageRouter, TracedStorage,
+};
+use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
-use tower::Service;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tower::{Layer, Service};
 use tower_http::trace::{DefaultMakeSpan, TraceLayer};
 use tower_service::Service as _;
 use tracing::{error, info, warn};
 
 use crate::routes;
+use crate::ldap_sync::LdapSync;
+use crate::master_key;
+use crate::mirror::Mirror;
+use crate::scrub::Scrubber;
+use crate::search::Search;
+use crate::tiering::Tiering;
+use crate::batch::BatchJobs;
+use crate::prune::PruneJobs;
+use crate::warmup::WarmupJobs;
+use crate::key_rewrap::KeyRewrap;
 use crate::admin;
+use crate::events::{EventDispatcher, EventDispatcherConfig};
+use crate::list_token::ListTokens;
+use crate::middleware::{
+    access_point_routing, account_scope, admission_control, admin_network, anonymous_access, bandwidth_throttle, capture_requests, client_cert_auth, clock_skew, cluster_redirect,
+    expected_bucket_owner, presigned_url_constraints, request_context, verify_signature, AdmissionController,
+    BandwidthLimiter, KeyUsageTracker, RequestCapture,
+};
 use crate::metrics::{MetricsRecorder, metrics_handler, metrics_middleware};
-use crate::tls::TlsAcceptor;
+use crate::tls::{AcmeClient, ClientCertificate, TlsAcceptor};
+use crate::transform::{ObjectTransform, TransformPipelines, TransformRegistry};
 
 #[cfg(feature = "cluster")]
-use hafiz_cluster::ClusterManager;
+use hafiz_cluster::{ClusterError, ClusterManager, MetadataStateMachine};
+#[cfg(feature = "cluster")]
+use hafiz_core::{config::TlsConfig, Error};
+#[cfg(feature = "cluster")]
+use std::path::PathBuf;
 
 // Embed the admin panel HTML at compile time
 const ADMIN_HTML: &str = include_str!("../static/index.html");
@@ -38,10 +71,52 @@ async fn admin_panel() -> Html<&'static str> {
 #[derive(Clone)]
 pub struct AppState {
     pub config: Arc<HafizConfig>,
-    pub storage: Arc<LocalStorage>,
+    pub storage: Arc<dyn StorageEngine>,
+    /// Per-bucket backend table behind `storage`
+    pub storage_router: Arc<StorageRouter>,
+    /// Per-bucket compression applied by `storage`
+    pub compression: Arc<CompressedStorage>,
+    /// Cache of object data in front of `storage`, when enabled
+    pub read_cache: Option<Arc<ReadCache>>,
     pub metadata: Arc<MetadataStore>,
+    /// Background integrity checker
+    pub scrubber: Arc<Scrubber>,
+    /// Read sampling and access-based tiering
+    pub tiering: Arc<Tiering>,
+    /// Provisions users from the LDAP directory
+    pub ldap_sync: Arc<LdapSync>,
+    /// Background batch operations
+    pub batch: Arc<BatchJobs>,
+    /// Background pruning of old versions and delete markers
+    pub prune: Arc<PruneJobs>,
+    /// Loading of objects into the read cache ahead of traffic
+    pub warmup: Arc<WarmupJobs>,
+    /// Keys the DEKs of SSE-S3 objects are encrypted under, when SSE-S3
+    /// is enabled
+    pub object_keys: Option<Arc<AccountKeys>>,
+    /// Moves object data keys to a new master key
+    pub key_rewrap: Arc<KeyRewrap>,
+    /// Scheduled mirroring to external S3 endpoints
+    pub mirror: Arc<Mirror>,
+    pub key_usage: Arc<KeyUsageTracker>,
+    /// Per-bucket and per-access-key byte rate limits
+    pub bandwidth: Arc<BandwidthLimiter>,
+    /// Sheds low-priority S3 requests under overload
+    pub admission: Arc<AdmissionController>,
+    /// Recording of requests for replay, while an administrator wants it
+    pub capture: Arc<RequestCapture>,
+    /// Bucket notification delivery
+    pub events: EventDispatcher,
+    /// Full-text index of objects, when enabled
+    pub search: Arc<Search>,
+    /// Signs listing continuation tokens
+    pub list_tokens: Arc<ListTokens>,
+    /// Content rewriting on GET
+    pub transforms: Arc<TransformPipelines>,
     pub start_time: Instant,
     pub metrics: Arc<MetricsRecorder>,
+    /// ID token validation, when OIDC federation is enabled
+    pub oidc: Option<Arc<OidcVerifier>>,
     #[cfg(feature = "cluster")]
     pub cluster: Option<Arc<ClusterManager>>,
 }
@@ -49,14 +124,52 @@ pub struct AppState {
 /// S3 Server
 pub struct S3Server {
     config: HafizConfig,
+    transforms: TransformRegistry,
 }
 
 impl S3Server {
     pub fn new(config: HafizConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            transforms: TransformRegistry::with_builtins(),
+        }
+    }
+
+    pub fn config(&self) -> &HafizConfig {
+        &self.config
+    }
+
+    /// Make a GET transform available to `[transform]` rules
+    pub fn with_transform(mut self, transform: Arc<dyn ObjectTransform>) -> Self {
+        self.transforms.register(transform);
+        self
     }
 
     pub async fn run(self) -> Result<()> {
+        // Initialize metrics
+        let metrics = Arc::new(
+            MetricsRecorder::with_telemetry(&self.config.telemetry)
+                .with_config(&self.config.metrics),
+        );
+        info!("Prometheus metrics initialized");
+
+        let (state, app) = self.prepare(metrics).await?;
+
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            info!("Shutdown signal received, draining in-flight requests");
+            let _ = shutdown_tx.send(true);
+        });
+
+        let addr = format!("{}:{}", self.config.server.bind_address, self.config.server.port);
+        let listener = TcpListener::bind(&addr).await?;
+        self.serve_until(listener, state, app, shutdown_rx).await
+    }
+
+    /// Open storage and metadata, start the background tasks and build the
+    /// router
+    pub(crate) async fn prepare(&self, metrics: Arc<MetricsRecorder>) -> Result<(AppState, Router)> {
         let start_time = Instant::now();
 
         // Validate TLS config if enabled
@@ -64,16 +177,116 @@ impl S3Server {
             self.config.tls.validate()?;
         }
 
-        // Initialize metrics
-        let metrics = Arc::new(MetricsRecorder::new());
-        info!("Prometheus metrics initialized");
+        let transforms = Arc::new(TransformPipelines::new(&self.config.transform, &self.transforms)?);
+
+        let crypto = hafiz_crypto::backend();
+        info!("Crypto backend: {}{}", crypto.name(), if crypto.is_fips() { " (FIPS)" } else { "" });
 
-        // Initialize storage
-        let storage = LocalStorage::new(&self.config.storage.data_dir);
-        storage.init().await?;
+        // Initialize storage: local disk, or an upstream S3 behind a local cache
+        let storage: Arc<dyn StorageEngine> = if self.config.gateway.enabled {
+            Arc::new(S3Gateway::new(&self.config.gateway, &self.config.storage.data_dir)?)
+        } else {
+            let storage = LocalStorage::new(&self.config.storage.data_dir)
+                .with_read_chunk_size(self.config.storage.read_chunk_size)
+                .with_fsync(
+                    self.config.storage.fsync,
+                    Duration::from_millis(self.config.storage.fsync_interval_ms),
+                )
+                .with_assemble_concurrency(self.config.storage.assemble_concurrency);
+            storage.init().await?;
+
+            // Keep the configured reserve free on the data volume
+            let reserve = &self.config.storage.reserve;
+            let monitor = Arc::new(SpaceMonitor::new(&self.config.storage.data_dir, reserve));
+            if let Err(e) = monitor.refresh() {
+                warn!("Free space accounting disabled: {}", e);
+                Arc::new(storage)
+            } else {
+                monitor
+                    .clone()
+                    .spawn(Duration::from_secs(reserve.check_interval_secs.max(1)));
+                Arc::new(SpaceGuard::new(Arc::new(storage), monitor))
+            }
+        };
+        let storage: Arc<dyn StorageEngine> = if self.config.storage.dedup.enabled {
+            Arc::new(DedupStorage::new(storage, &self.config.storage.dedup).await?)
+        } else {
+            storage
+        };
 
         // Initialize metadata store
-        let metadata = MetadataStore::new(&self.config.database.url).await?;
+        let mut metadata = MetadataStore::connect(&self.config.database).await?;
+        let cache = &self.config.database.cache;
+        if cache.enabled {
+            metadata = metadata.with_cache(MetadataCache::new(
+                cache.max_entries,
+                Duration::from_secs(cache.ttl_secs),
+            ));
+        }
+        if self.config.encryption.encrypt_secret_keys {
+            metadata = master_key::apply_secret_cipher(&self.config.encryption, metadata).await?;
+        }
+        let encryption = &self.config.encryption;
+        let object_keys = if encryption.enabled && encryption.sse_s3_enabled {
+            match master_key::current_master_key(encryption, &metadata).await? {
+                Some(key) => Some(Arc::new(AccountKeys::new(&key).map_err(|e| {
+                    hafiz_core::Error::InvalidArgument(format!("Invalid master key: {}", e))
+                })?)),
+                None => {
+                    warn!("SSE-S3 is disabled: no master key is configured");
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        // Every storage call goes through the per-bucket backend table.
+        // Mappings made through the admin API override the config file.
+        let storage_router = Arc::new(StorageRouter::from_config(storage, &self.config.storage).await?);
+        for (bucket, backend) in metadata.list_bucket_backends().await? {
+            if let Err(e) = storage_router.set_bucket_backend(&bucket, &backend) {
+                warn!("Ignoring storage mapping of bucket {}: {}", bucket, e);
+            }
+        }
+
+        // Buckets with a codec set are compressed on whichever backend holds them
+        let compression = Arc::new(CompressedStorage::new(storage_router.clone()));
+        for (bucket, setting) in metadata.list_bucket_compression().await? {
+            compression.set_bucket_compression(&bucket, setting);
+        }
+        let bandwidth = Arc::new(BandwidthLimiter::new());
+        for (scope, name, limit) in metadata.list_bandwidth_limits().await? {
+            bandwidth.set(scope, &name, limit);
+        }
+        // Outermost, so spans and latencies cover the whole storage stack
+        let traced = TracedStorage::new(compression.clone());
+        let admission = Arc::new(
+            AdmissionController::new(self.config.server.admission.clone())
+                .with_storage_latency(traced.latency()),
+        );
+        let storage: Arc<dyn StorageEngine> = Arc::new(traced);
+        // In front of it, so cache hits skip the stack altogether
+        let read_cache_config = &self.config.storage.read_cache;
+        let read_cache = if read_cache_config.enabled {
+            let mut cache = ReadCache::new(
+                storage.clone(),
+                read_cache_config.memory_bytes,
+                read_cache_config.max_object_size,
+            );
+            if let Some(dir) = &read_cache_config.disk_dir {
+                // Entries carry the ETag they were read at, so they never go stale
+                let disk = ObjectCache::open(dir, read_cache_config.disk_bytes, Duration::MAX)?;
+                cache = cache.with_disk_tier(disk.without_metrics());
+            }
+            Some(Arc::new(cache))
+        } else {
+            None
+        };
+        let storage: Arc<dyn StorageEngine> = match &read_cache {
+            Some(cache) => cache.clone(),
+            None => storage,
+        };
 
         // Create root user if not exists
         let root_user = hafiz_core::types::User::root(
@@ -85,52 +298,349 @@ impl S3Server {
             info!("Created root user with access key: {}", root_user.access_key);
         }
 
+        let oidc = if self.config.oidc.enabled {
+            let verifier = Arc::new(OidcVerifier::new(self.config.oidc.clone())?);
+            // Fail soft: keys are fetched again on first use
+            if let Err(e) = verifier.refresh_keys().await {
+                warn!("Could not load OIDC signing keys yet: {}", e);
+            }
+            info!("OIDC federation enabled for issuer {}", self.config.oidc.issuer_url);
+            Some(verifier)
+        } else {
+            None
+        };
+
+        let tiering = Arc::new(Tiering::new(
+            self.config.storage.tiering.clone(),
+            storage_router.has_archive(),
+        ));
+
+        let metadata = Arc::new(metadata);
+        #[cfg(feature = "cluster")]
+        let cluster = self.start_cluster(&metadata, &storage).await?;
+
         let state = AppState {
             config: Arc::new(self.config.clone()),
-            storage: Arc::new(storage),
-            metadata: Arc::new(metadata),
+            storage,
+            storage_router,
+            compression,
+            read_cache,
+            metadata,
+            scrubber: Arc::new(Scrubber::new(self.config.scrub.clone())),
+            tiering,
+            ldap_sync: Arc::new(LdapSync::new(self.config.ldap.clone())),
+            batch: Arc::new(BatchJobs::new()),
+            prune: Arc::new(PruneJobs::new()),
+            warmup: Arc::new(WarmupJobs::new()),
+            object_keys,
+            key_rewrap: Arc::new(KeyRewrap::new()),
+            mirror: Arc::new(Mirror::new(self.config.mirror.clone())?),
+            key_usage: Arc::new(KeyUsageTracker::new()),
+            bandwidth,
+            admission,
+            capture: Arc::new(RequestCapture::new()),
+            events: EventDispatcher::new(EventDispatcherConfig::default()),
+            search: Arc::new(Search::open(&self.config.search, &self.config.storage.data_dir)?),
+            list_tokens: Arc::new(ListTokens::new(&self.config.auth.root_secret_key)),
+            transforms,
             start_time,
             metrics: metrics.clone(),
+            oidc,
             #[cfg(feature = "cluster")]
-            cluster: None, // Cluster initialized separately if enabled
+            cluster,
         };
 
-        let app = self.create_router(state, metrics);
-        let addr = format!("{}:{}", self.config.server.bind_address, self.config.server.port);
+        #[cfg(feature = "cluster-grpc")]
+        crate::cluster_rpc::spawn_grpc_server(&state)?;
+
+        state.scrubber.start(state.clone());
+        state.tiering.start(state.clone());
+        state.ldap_sync.start(state.clone());
+        state.mirror.start(state.clone());
+        state.search.start(state.clone());
+        crate::multipart_expiry::start(&self.config.lifecycle, state.clone());
+        crate::bucket_usage::start(&self.config.database, state.clone());
+        crate::restore::start(state.clone());
+        state.key_rewrap.resume_interrupted(state.clone());
+
+        let app = self.create_router(state.clone(), metrics);
+        Ok((state, app))
+    }
 
-        if self.config.tls.enabled {
-            self.run_https(app, &addr).await
+    /// Join the cluster, when enabled, and start Raft metadata consensus
+    /// over `metadata` if configured
+    #[cfg(feature = "cluster")]
+    async fn start_cluster(
+        &self,
+        metadata: &Arc<MetadataStore>,
+        storage: &Arc<dyn StorageEngine>,
+    ) -> Result<Option<Arc<ClusterManager>>> {
+        let config = &self.config.cluster;
+        if !config.enabled {
+            return Ok(None);
+        }
+        config.validate()?;
+
+        let cluster_error = |e: ClusterError| Error::InternalError(format!("Cluster: {}", e));
+        let mut cluster_config = config.to_cluster_config(&self.config.server);
+        // Terms, votes and the log must survive restarts
+        if cluster_config.raft_data_dir.is_none() {
+            let raft_dir = self.config.storage.data_dir.join("raft");
+            cluster_config.raft_data_dir = Some(raft_dir.to_string_lossy().into_owned());
+        }
+        let cluster = Arc::new(ClusterManager::new(cluster_config).map_err(cluster_error)?);
+        // Rebalancing and anti-entropy repair move this node's objects
+        cluster.attach_store(metadata.clone(), storage.clone());
+        cluster.start().await.map_err(cluster_error)?;
+
+        if config.metadata_consensus == "raft" {
+            let scratch_dir = self.config.storage.data_dir.join("raft-snapshots");
+            let state_machine = Arc::new(MetadataStateMachine::new(metadata.clone(), scratch_dir));
+            cluster.start_raft(state_machine).await.map_err(cluster_error)?;
+            info!("Metadata writes go through Raft consensus");
+        }
+        Ok(Some(cluster))
+    }
+
+    /// Serve on `listener` until `shutdown` is signalled, then drain the
+    /// connections and flush background work
+    pub(crate) async fn serve_until(
+        &self,
+        listener: TcpListener,
+        state: AppState,
+        app: Router,
+        shutdown: watch::Receiver<bool>,
+    ) -> Result<()> {
+        let webdav = self.serve_webdav(&state, app.clone(), shutdown.clone());
+        let sftp = self.serve_sftp(&state, app.clone(), shutdown.clone());
+        let admin = self.serve_admin(&state, shutdown.clone());
+        let cluster = self.serve_cluster(&state, shutdown.clone());
+        let s3 = async {
+            if self.config.tls.enabled {
+                self.run_https(app, listener, shutdown).await
+            } else {
+                self.run_http(app, listener, shutdown).await
+            }
+        };
+        let served = tokio::try_join!(s3, webdav, sftp, admin, cluster).map(|_| ());
+
+        self.shutdown(&state).await;
+        served
+    }
+
+    /// Serve the WebDAV gateway, when enabled, with the TLS settings of the
+    /// S3 API
+    async fn serve_webdav(&self, state: &AppState, app: Router, shutdown: watch::Receiver<bool>) -> Result<()> {
+        let webdav = &self.config.webdav;
+        if !webdav.enabled {
+            return Ok(());
+        }
+
+        let bind_address = webdav
+            .bind_address
+            .as_deref()
+            .unwrap_or(&self.config.server.bind_address);
+        let listener = TcpListener::bind(format!("{}:{}", bind_address, webdav.port)).await?;
+        let tls_acceptor = if self.config.tls.enabled {
+            Some(Arc::new(TlsAcceptor::from_config(
+                &self.config.tls,
+                self.config.server.http2_enabled,
+            )?))
+        } else {
+            None
+        };
+        info!(
+            "📁 WebDAV gateway at {}://{}{}",
+            if tls_acceptor.is_some() { "https" } else { "http" },
+            listener.local_addr()?,
+            webdav.prefix
+        );
+
+        let router = crate::webdav::router(state.clone(), app, webdav);
+        self.serve(listener, router, tls_acceptor, shutdown).await
+    }
+
+    /// Serve the admin API, admin panel and metrics on their own listener,
+    /// when configured, with the admin TLS settings or else those of the S3 API
+    async fn serve_admin(&self, state: &AppState, shutdown: watch::Receiver<bool>) -> Result<()> {
+        let admin = &self.config.admin;
+        if !admin.separate_listener {
+            return Ok(());
+        }
+
+        let bind_address = admin
+            .bind_address
+            .as_deref()
+            .unwrap_or(&self.config.server.bind_address);
+        let listener = TcpListener::bind(format!("{}:{}", bind_address, self.config.server.admin_port)).await?;
+        let tls = admin.tls.as_ref().unwrap_or(&self.config.tls);
+        let tls_acceptor = if tls.enabled {
+            Some(Arc::new(TlsAcceptor::from_config(tls, self.config.server.http2_enabled)?))
         } else {
-            self.run_http(app, &addr).await
+            None
+        };
+        let scheme = if tls_acceptor.is_some() { "https" } else { "http" };
+        let addr = listener.local_addr()?;
+        info!("🖥️  Admin Panel at {}://{}/admin", scheme, addr);
+        info!("📊 Admin API available at {}://{}/api/v1", scheme, addr);
+        info!("📈 Prometheus metrics at {}://{}/metrics", scheme, addr);
+        if !admin.allowed_cidrs.is_empty() {
+            info!("🔐 Admin listener open to {}", admin.allowed_cidrs.join(", "));
         }
+
+        let router = self.admin_router(state.clone());
+        self.serve(listener, router, tls_acceptor, shutdown).await
     }
 
-    async fn run_http(self, app: Router, addr: &str) -> Result<()> {
-        let listener = TcpListener::bind(addr).await?;
+    /// Serve the SFTP gateway, when enabled
+    async fn serve_sftp(&self, state: &AppState, app: Router, shutdown: watch::Receiver<bool>) -> Result<()> {
+        let sftp = &self.config.sftp;
+        if !sftp.enabled {
+            return Ok(());
+        }
+
+        #[cfg(feature = "sftp")]
+        {
+            let bind_address = sftp
+                .bind_address
+                .as_deref()
+                .unwrap_or(&self.config.server.bind_address);
+            let listener = TcpListener::bind(format!("{}:{}", bind_address, sftp.port)).await?;
+            let host_key_file = sftp
+                .host_key_file
+                .clone()
+                .unwrap_or_else(|| self.config.storage.data_dir.join("sftp_host_key.pem"));
+            info!("📦 SFTP gateway at {}", listener.local_addr()?);
+
+            crate::sftp::serve(state.clone(), app, listener, sftp, &host_key_file, shutdown).await
+        }
+        #[cfg(not(feature = "sftp"))]
+        {
+            let _ = (state, app, shutdown);
+            warn!("The SFTP gateway is enabled but this build lacks the sftp feature");
+            Ok(())
+        }
+    }
+
+    /// Serve the node-to-node endpoints on the cluster port, when clustering
+    /// is enabled; with cluster TLS, peers must present a certificate signed
+    /// by the cluster CA
+    async fn serve_cluster(&self, state: &AppState, shutdown: watch::Receiver<bool>) -> Result<()> {
+        let cluster = &self.config.cluster;
+        if !cluster.enabled {
+            return Ok(());
+        }
+
+        #[cfg(feature = "cluster")]
+        {
+            let listener =
+                TcpListener::bind(format!("{}:{}", self.config.server.bind_address, cluster.cluster_port)).await?;
+            let tls_acceptor = if cluster.cluster_tls_enabled {
+                let tls = TlsConfig {
+                    enabled: true,
+                    cert_file: cluster.cluster_tls_cert.clone().map(PathBuf::from),
+                    key_file: cluster.cluster_tls_key.clone().map(PathBuf::from),
+                    client_ca_file: cluster.cluster_ca_cert.clone().map(PathBuf::from),
+                    require_client_cert: true,
+                    ..TlsConfig::default()
+                };
+                Some(Arc::new(TlsAcceptor::from_config(&tls, false)?))
+            } else {
+                None
+            };
+            info!(
+                "🔗 Cluster endpoints at {}://{}",
+                if tls_acceptor.is_some() { "https" } else { "http" },
+                listener.local_addr()?
+            );
+
+            let router = crate::cluster_rpc::cluster_router(state.clone());
+            self.serve(listener, router, tls_acceptor, shutdown).await
+        }
+        #[cfg(not(feature = "cluster"))]
+        {
+            let _ = (state, shutdown);
+            warn!("Clustering is enabled but this build lacks the cluster feature");
+            Ok(())
+        }
+    }
+
+    /// Time in-flight work gets to finish after a shutdown signal
+    fn drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.config.server.shutdown_timeout_secs)
+    }
+
+    /// Flush queued background work and close the database
+    ///
+    /// Runs once no more requests are being served.
+    async fn shutdown(&self, state: &AppState) {
+        let timeout = self.drain_timeout();
+
+        if !state.events.flush(timeout).await {
+            warn!(
+                "{} event notifications were still queued at shutdown",
+                state.events.pending()
+            );
+        }
+        if !state.search.flush(timeout).await {
+            warn!(
+                "{} objects were still waiting for the search index at shutdown",
+                state.search.status().pending
+            );
+        }
+
+        #[cfg(feature = "cluster")]
+        if let Some(cluster) = &state.cluster {
+            if !cluster.drain_replication(timeout).await {
+                warn!(
+                    "{} replication events were still queued at shutdown",
+                    cluster.replicator_stats().pending
+                );
+            }
+            if let Err(e) = cluster.stop().await {
+                warn!("Failed to leave the cluster cleanly: {}", e);
+            }
+        }
+
+        state.metadata.close().await;
+        info!("Hafiz S3 API server stopped");
+    }
+
+    async fn run_http(&self, app: Router, listener: TcpListener, shutdown: watch::Receiver<bool>) -> Result<()> {
+        let addr = listener.local_addr()?;
 
         info!("🚀 Hafiz S3 API server listening on http://{}", addr);
-        info!("🖥️  Admin Panel at http://{}/admin", addr);
-        info!("📊 Admin API available at http://{}/api/v1", addr);
-        info!("📈 Prometheus metrics at http://{}/metrics", addr);
+        if !self.config.admin.separate_listener {
+            info!("🖥️  Admin Panel at http://{}/admin", addr);
+            info!("📊 Admin API available at http://{}/api/v1", addr);
+            info!("📈 Prometheus metrics at http://{}/metrics", addr);
+        }
         info!("🔑 Access Key: {}", self.config.auth.root_access_key);
 
-        axum::serve(listener, app).await?;
-        Ok(())
+        self.serve(listener, app, None, shutdown).await
     }
 
-    async fn run_https(self, app: Router, addr: &str) -> Result<()> {
-        let tls_acceptor = TlsAcceptor::from_config(&self.config.tls)?;
-        let listener = TcpListener::bind(addr).await?;
+    async fn run_https(&self, app: Router, listener: TcpListener, shutdown: watch::Receiver<bool>) -> Result<()> {
+        let tls_acceptor = TlsAcceptor::from_config(&self.config.tls, self.config.server.http2_enabled)?;
+        let addr = listener.local_addr()?;
 
         info!("🔒 Hafiz S3 API server listening on https://{}", addr);
-        info!("🖥️  Admin Panel at https://{}/admin", addr);
-        info!("📊 Admin API available at https://{}/api/v1", addr);
-        info!("📈 Prometheus metrics at https://{}/metrics", addr);
+        if !self.config.admin.separate_listener {
+            info!("🖥️  Admin Panel at https://{}/admin", addr);
+            info!("📊 Admin API available at https://{}/api/v1", addr);
+            info!("📈 Prometheus metrics at https://{}/metrics", addr);
+        }
         info!("🔑 Access Key: {}", self.config.auth.root_access_key);
 
         if self.config.tls.require_client_cert {
             info!("🔐 mTLS enabled - client certificates required");
         }
+        if !self.config.tls.client_cert_users.is_empty() {
+            info!(
+                "🔐 {} client certificate mapping(s) to users",
+                self.config.tls.client_cert_users.len()
+            );
+        }
 
         if let Some(hsts) = tls_acceptor.hsts_header() {
             info!("🛡️  HSTS enabled: {}", hsts);
@@ -139,63 +649,128 @@ impl S3Server {
         // Log TLS version
         info!("🔒 Minimum TLS version: {:?}", self.config.tls.min_version);
 
-        // Accept connections
-        loop {
-            let (stream, peer_addr) = match listener.accept().await {
-                Ok(conn) => conn,
-                Err(e) => {
-                    error!("Failed to accept connection: {}", e);
-                    continue;
-                }
-            };
+        // Renewed certificates are picked up without a restart
+        let certs = tls_acceptor.certificates().clone();
+        if self.config.tls.reload_interval_secs > 0 {
+            certs
+                .clone()
+                .spawn(Duration::from_secs(self.config.tls.reload_interval_secs));
+        }
+        if self.config.tls.acme.enabled {
+            info!("🔏 ACME certificates for {}", self.config.tls.acme.domains.join(", "));
+            Arc::new(AcmeClient::new(self.config.tls.acme.clone(), certs)?).spawn();
+        }
 
-            let tls_acceptor = tls_acceptor.inner().clone();
-            let app = app.clone();
+        self.serve(listener, app, Some(Arc::new(tls_acceptor)), shutdown)
+            .await
+    }
 
-            tokio::spawn(async move {
-                // Perform TLS handshake
-                let tls_stream = match tls_acceptor.accept(stream).await {
-                    Ok(stream) => stream,
+    /// HTTP/1.1 and HTTP/2 settings from `[server]`
+    fn connection_builder(&self) -> Builder<TokioExecutor> {
+        let server = &self.config.server;
+        let mut builder = Builder::new(TokioExecutor::new());
+        builder
+            .http1()
+            .timer(TokioTimer::new())
+            .keep_alive(server.keep_alive)
+            .header_read_timeout(Duration::from_secs(server.keep_alive_timeout_secs));
+        builder
+            .http2()
+            .timer(TokioTimer::new())
+            .max_concurrent_streams(server.http2_max_concurrent_streams)
+            .keep_alive_interval(
+                (server.http2_keep_alive_interval_secs > 0)
+                    .then(|| Duration::from_secs(server.http2_keep_alive_interval_secs)),
+            )
+            .keep_alive_timeout(Duration::from_secs(server.keep_alive_timeout_secs));
+
+        if server.http2_enabled {
+            info!(
+                "HTTP/2 enabled, up to {} concurrent streams per connection",
+                server.http2_max_concurrent_streams
+            );
+            builder
+        } else {
+            builder.http1_only()
+        }
+    }
+
+    /// Accept connections until the shutdown signal, then drain them
+    async fn serve(
+        &self,
+        listener: TcpListener,
+        app: Router,
+        tls_acceptor: Option<Arc<TlsAcceptor>>,
+        mut shutdown: watch::Receiver<bool>,
+    ) -> Result<()> {
+        let builder = Arc::new(self.connection_builder());
+        let mut connections = JoinSet::new();
+        loop {
+            let (stream, peer_addr) = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok(conn) => conn,
                     Err(e) => {
-                        warn!("TLS handshake failed from {}: {}", peer_addr, e);
-                        return;
+                        error!("Failed to accept connection: {}", e);
+                        continue;
                     }
-                };
+                },
+                // Reap finished connections as we go
+                Some(_) = connections.join_next(), if !connections.is_empty() => continue,
+                _ = stopping(&mut shutdown) => break,
+            };
 
-                // Create hyper service
-                let io = TokioIo::new(tls_stream);
-                let service = hyper::service::service_fn(move |req| {
-                    let mut app = app.clone();
-                    async move {
-                        app.call(req).await
-                    }
-                });
-
-                // Serve the connection
-                if let Err(e) = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new())
-                    .serve_connection(io, service)
-                    .await
-                {
-                    // Ignore connection reset errors
-                    if !e.to_string().contains("connection reset") {
-                        error!("Connection error from {}: {}", peer_addr, e);
+            let tls_acceptor = tls_acceptor.clone();
+            let builder = builder.clone();
+            let app = app.clone();
+            let shutdown = shutdown.clone();
+
+            connections.spawn(async move {
+                match tls_acceptor {
+                    Some(tls_acceptor) => {
+                        // Perform TLS handshake
+                        let tls_stream = match tls_acceptor.inner().accept(stream).await {
+                            Ok(stream) => stream,
+                            Err(e) => {
+                                warn!("TLS handshake failed from {}: {}", peer_addr, e);
+                                return;
+                            }
+                        };
+                        let client_cert = tls_acceptor.client_certificate(tls_stream.get_ref().1);
+                        serve_connection(&builder, tls_stream, app, shutdown, peer_addr, client_cert).await
                     }
+                    None => serve_connection(&builder, stream, app, shutdown, peer_addr, None).await,
                 }
             });
         }
+        drop(listener);
+
+        let timeout = self.drain_timeout();
+        info!("Waiting up to {:?} for {} open connections", timeout, connections.len());
+        let drained = tokio::time::timeout(timeout, async {
+            while connections.join_next().await.is_some() {}
+        })
+        .await;
+        if drained.is_err() {
+            warn!(
+                "Drain timeout of {:?} elapsed, closing {} remaining connections",
+                timeout,
+                connections.len()
+            );
+            connections.shutdown().await;
+        }
+        Ok(())
     }
 
     fn create_router(&self, state: AppState, metrics: Arc<MetricsRecorder>) -> Router {
-        Router::new()
-            // Admin panel (web UI)
-            .route("/admin", get(admin_panel))
-            
-            // Metrics endpoint (no auth required)
-            .route("/metrics", get(metrics_handler))
+        let router: Router<AppState> = Router::new();
 
-            // Admin API routes
-            .nest("/api/v1", admin::admin_routes_no_auth())
+        let body_limit = match self.config.server.max_body_size {
+            0 => DefaultBodyLimit::disable(),
+            max => DefaultBodyLimit::max(usize::try_from(max).unwrap_or(usize::MAX)),
+        };
 
+        // S3 API; unsigned requests only get what policies and ACLs open to everyone
+        let s3_routes = Router::new()
             // Service operations
             .route("/", get(routes::list_buckets))
 
@@ -214,6 +789,48 @@ impl S3Server {
             .route("/:bucket/*key", delete(routes::object_delete_handler)) // DeleteObject, AbortMultipart, or DeleteObjectTagging
             .route("/:bucket/*key", post(routes::object_post_handler)) // CreateMultipart or CompleteMultipart
             .route("/:bucket/*key", options(routes::handle_cors_preflight)) // CORS preflight for object
+            // Innermost, so requests refused earlier use no bandwidth
+            .route_layer(middleware::from_fn_with_state(state.clone(), bandwidth_throttle))
+            // Redirects only requests that would have been served
+            .route_layer(middleware::from_fn_with_state(state.clone(), cluster_redirect))
+            .route_layer(middleware::from_fn_with_state(state.clone(), expected_bucket_owner))
+            .route_layer(middleware::from_fn_with_state(state.clone(), account_scope))
+            .route_layer(middleware::from_fn_with_state(state.clone(), presigned_url_constraints))
+            .route_layer(middleware::from_fn_with_state(state.clone(), anonymous_access))
+            .route_layer(middleware::from_fn_with_state(state.clone(), clock_skew))
+            .layer(middleware::from_fn_with_state(state.clone(), routes::cors_headers))
+            .layer(body_limit)
+            .layer(middleware::map_response(routes::entity_too_large))
+            // Sheds load before any other work, but with a request ID
+            .layer(middleware::from_fn_with_state(state.clone(), admission_control))
+            // Sees the final response, and runs inside the request's ID
+            .layer(middleware::from_fn_with_state(state.clone(), capture_requests))
+            .layer(middleware::from_fn(request_context))
+            // Names the caller of signed requests only once they are verified
+            .layer(middleware::from_fn_with_state(state.clone(), verify_signature))
+            // Outermost, so the request's principal is known from here on
+            .layer(middleware::from_fn_with_state(state.clone(), client_cert_auth));
+
+        // Administration, unless it has a listener of its own
+        let router = if self.config.admin.separate_listener {
+            router
+        } else {
+            router
+                // Admin panel (web UI)
+                .route("/admin", get(admin_panel))
+
+                // Metrics endpoint (no auth required)
+                .route("/metrics", get(metrics_handler))
+
+                // Admin API routes
+                .nest("/api/v1", admin::admin_routes(state.clone()))
+        };
+
+        let router = router
+            // STS; callers authenticate with the web identity token itself
+            .route("/", post(routes::sts_handler).layer(middleware::from_fn(request_context)))
+
+            .merge(s3_routes)
 
             // Metrics middleware for S3 routes
             .layer(middleware::from_fn_with_state(metrics.clone(), metrics_middleware))
@@ -222,6 +839,108 @@ impl S3Server {
                     .make_span_with(DefaultMakeSpan::default().include_headers(true)),
             )
             // Note: S3-specific CORS is handled by bucket configuration, not tower-http CorsLayer
+            .with_state(state.clone());
+
+        // Access point addressing rewrites the path, so it runs before routing
+        Router::new().fallback_service(
+            middleware::from_fn_with_state(state, access_point_routing).layer(router),
+        )
+    }
+
+    /// Router of the admin listener: every admin API request is
+    /// authenticated, and every request must come from an allowed network
+    fn admin_router(&self, state: AppState) -> Router {
+        Router::new()
+            .route("/admin", get(admin_panel))
+            .route("/metrics", get(metrics_handler).with_state(state.metrics.clone()))
+            .nest("/api/v1", admin::admin_routes(state.clone()))
+            .layer(middleware::from_fn_with_state(state.clone(), admin_network))
+            .layer(
+                TraceLayer::new_for_http()
+                    .make_span_with(DefaultMakeSpan::default().include_headers(true)),
+            )
             .with_state(state)
     }
 }
+
+/// Serve requests on one connection until it closes
+///
+/// On shutdown, the requests in progress finish and the connection closes
+/// instead of waiting for the next one. Each request carries the peer
+/// address and the client certificate the connection was made with.
+async fn serve_connection<I>(
+    builder: &Builder<TokioExecutor>,
+    io: I,
+    app: Router,
+    mut shutdown: watch::Receiver<bool>,
+    peer_addr: SocketAddr,
+    client_cert: Option<ClientCertificate>,
+) where
+    I: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    // Create hyper service
+    let service = hyper::service::service_fn(move |mut req: hyper::Request<hyper::body::Incoming>| {
+        let mut app = app.clone();
+        req.extensions_mut().insert(ConnectInfo(peer_addr));
+        if let Some(client_cert) = &client_cert {
+            req.extensions_mut().insert(client_cert.clone());
+        }
+        async move {
+            app.call(req).await
+        }
+    });
+
+    let conn = builder.serve_connection(TokioIo::new(io), service);
+    tokio::pin!(conn);
+    let result = tokio::select! {
+        result = conn.as_mut() => result,
+        _ = stopping(&mut shutdown) => {
+            conn.as_mut().graceful_shutdown();
+            conn.await
+        }
+    };
+    if let Err(e) = result {
+        // Ignore connection reset errors
+        if !e.to_string().contains("connection reset") {
+            error!("Connection error from {}: {}", peer_addr, e);
+        }
+    }
+}
+
+/// Resolves once shutdown has been signalled
+pub(crate) async fn stopping(shutdown: &mut watch::Receiver<bool>) {
+    // Sender gone without a signal: keep serving
+    if shutdown.wait_for(|stop| *stop).await.is_err() {
+        std::future::pending::<()>().await;
+    }
+}
+
+/// Resolves on SIGTERM or Ctrl-C
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl-C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
@@ -0,0 +1,643 @@
+//! SFTP file operations on a bucket
+//!
+//! Directories follow [`hafiz_core::directory`]; listings are reused for
+//! `listing_cache_secs`, since clients stat each entry they have listed.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode as HttpStatus},
+    response::Response,
+};
+use chrono::Utc;
+use hafiz_core::directory::{
+    normalize, split_path, DirEntry, Listing, ListingCache, Tree, TreeCopy,
+};
+use russh_sftp::protocol::{
+    Attrs, Data, File, FileAttributes, Handle, Name, OpenFlags, Status, StatusCode, Version,
+};
+use tokio::io::{AsyncSeekExt, AsyncWriteExt};
+use tracing::{debug, warn};
+
+use crate::loopback::{log_progress, s3_path, Caller, Loopback, LoopbackTree};
+
+/// Mode bits reported for directories
+const DIR_MODE: u32 = 0o40755;
+/// Mode bits reported for files
+const FILE_MODE: u32 = 0o100644;
+
+/// Entries per READDIR reply, keeping replies below client packet limits
+const READDIR_BATCH: usize = 128;
+
+/// The file system of one SFTP session, rooted at its login's home
+pub(super) struct SftpFs {
+    s3: Loopback,
+    caller: Caller,
+    bucket: String,
+    /// The home directory
+    tree: Tree,
+    listings: ListingCache,
+    handles: HashMap<String, Open>,
+    next_handle: u64,
+}
+
+/// An open file or directory
+enum Open {
+    /// Entries are listed by the first READDIR and handed out in batches
+    Dir {
+        path: String,
+        entries: Option<Vec<File>>,
+    },
+    Read {
+        key: String,
+        size: u64,
+    },
+    /// Written data is spooled to a file and uploaded on close
+    Write {
+        path: String,
+        spool: tokio::fs::File,
+        spool_path: PathBuf,
+    },
+}
+
+impl SftpFs {
+    pub fn new(
+        s3: Loopback,
+        caller: Caller,
+        bucket: String,
+        root: &str,
+        listing_ttl: Duration,
+    ) -> Self {
+        Self {
+            s3,
+            caller,
+            bucket,
+            tree: Tree::new(root),
+            listings: ListingCache::new(listing_ttl),
+            handles: HashMap::new(),
+            next_handle: 0,
+        }
+    }
+
+    fn key(&self, path: &str) -> String {
+        self.tree.key(path)
+    }
+
+    /// Key prefix of the objects inside a directory
+    fn dir_prefix(&self, path: &str) -> String {
+        self.tree.dir_prefix(path)
+    }
+
+    fn add_handle(&mut self, open: Open) -> String {
+        self.next_handle += 1;
+        let handle = self.next_handle.to_string();
+        self.handles.insert(handle.clone(), open);
+        handle
+    }
+
+    /// Attributes of a file or directory, by normalized path
+    async fn attrs(&self, path: &str) -> Result<FileAttributes, StatusCode> {
+        if path.is_empty() {
+            return Ok(dir_attrs());
+        }
+        match self.listings.lookup(path) {
+            Some(Some(entry)) => return Ok(entry_attrs(entry)),
+            Some(None) => return Err(StatusCode::NoSuchFile),
+            None => {}
+        }
+        let response = self
+            .s3
+            .head(&self.caller, &self.bucket, &self.key(path))
+            .await;
+        if response.status().is_success() {
+            return Ok(file_attrs_from_headers(&response));
+        }
+        if response.status() != HttpStatus::NOT_FOUND {
+            return Err(status_of(&response));
+        }
+        if self.is_dir(path).await? {
+            Ok(dir_attrs())
+        } else {
+            Err(StatusCode::NoSuchFile)
+        }
+    }
+
+    async fn is_dir(&self, path: &str) -> Result<bool, StatusCode> {
+        self.s3
+            .any_under(&self.caller, &self.bucket, &self.dir_prefix(path))
+            .await
+            .map_err(|response| status_of(&response))
+    }
+
+    /// Entries of a directory, by normalized path
+    async fn listing(&mut self, path: &str) -> Result<Listing, StatusCode> {
+        if let Some(listing) = self.listings.get(path) {
+            return Ok(listing.clone());
+        }
+        let listing = self
+            .s3
+            .listing(&self.caller, &self.bucket, &self.dir_prefix(path))
+            .await
+            .map_err(|response| status_of(&response))?;
+        self.listings.insert(path, listing.clone());
+        Ok(listing)
+    }
+
+    /// Store the marker of the directory of `path` if removing `path` left
+    /// it empty, so the directory does not go away too
+    async fn keep_parent(&mut self, path: &str) -> Result<(), StatusCode> {
+        let (dir, _) = split_path(path);
+        let survives = match self.listings.parent_survives(path) {
+            Some(survives) => survives,
+            None => self.is_dir(dir).await?,
+        };
+        if survives {
+            return Ok(());
+        }
+        let response = self
+            .s3
+            .put_empty(&self.caller, &self.bucket, &self.dir_prefix(dir))
+            .await;
+        check(&response)?;
+        self.listings.insert(dir, Listing::created());
+        Ok(())
+    }
+
+    /// Store a spooled upload as one object
+    async fn upload(
+        &mut self,
+        path: &str,
+        mut spool: tokio::fs::File,
+        spool_path: &Path,
+    ) -> Result<(), StatusCode> {
+        let key = &self.key(path);
+        let length = spool.metadata().await.map_err(io_status)?.len();
+        spool
+            .seek(std::io::SeekFrom::Start(0))
+            .await
+            .map_err(io_status)?;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+        if let Ok(content_type) =
+            HeaderValue::from_str(mime_guess::from_path(key).first_or_octet_stream().as_ref())
+        {
+            headers.insert(header::CONTENT_TYPE, content_type);
+        }
+        let body = Body::from_stream(tokio_util::io::ReaderStream::new(spool));
+        let response = self
+            .s3
+            .call(
+                &self.caller,
+                Method::PUT,
+                &s3_path(&self.bucket, key),
+                headers,
+                body,
+            )
+            .await;
+        let _ = tokio::fs::remove_file(spool_path).await;
+        check(&response)?;
+        self.listings
+            .put_entry(path, DirEntry::file(length, Some(Utc::now())));
+        debug!(
+            "SFTP upload of {}/{} by {} ({} bytes)",
+            self.bucket, key, self.caller.access_key, length
+        );
+        Ok(())
+    }
+}
+
+impl Drop for SftpFs {
+    /// Uploads still open when the session ends are abandoned
+    fn drop(&mut self) {
+        for open in self.handles.values() {
+            if let Open::Write { spool_path, .. } = open {
+                let _ = std::fs::remove_file(spool_path);
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl russh_sftp::server::Handler for SftpFs {
+    type Error = StatusCode;
+
+    fn unimplemented(&self) -> Self::Error {
+        StatusCode::OpUnsupported
+    }
+
+    async fn init(
+        &mut self,
+        _version: u32,
+        _extensions: HashMap<String, String>,
+    ) -> Result<Version, Self::Error> {
+        Ok(Version::new())
+    }
+
+    async fn open(
+        &mut self,
+        id: u32,
+        filename: String,
+        pflags: OpenFlags,
+        _attrs: FileAttributes,
+    ) -> Result<Handle, Self::Error> {
+        let path = normalize(&filename);
+        if path.is_empty() {
+            return Err(StatusCode::Failure);
+        }
+        let key = self.key(&path);
+
+        let open = if pflags.contains(OpenFlags::WRITE) {
+            let exists = self.attrs(&path).await.is_ok();
+            if exists && pflags.contains(OpenFlags::EXCLUDE) {
+                return Err(StatusCode::Failure);
+            }
+            if !exists && !pflags.contains(OpenFlags::CREATE) {
+                return Err(StatusCode::NoSuchFile);
+            }
+            // Files are replaced whole: appending to or resuming a stored
+            // file would need its content
+            if exists
+                && (pflags.contains(OpenFlags::APPEND) || !pflags.contains(OpenFlags::TRUNCATE))
+            {
+                return Err(StatusCode::OpUnsupported);
+            }
+            let spool_path =
+                std::env::temp_dir().join(format!("hafiz-sftp-{}", uuid::Uuid::new_v4()));
+            let spool = tokio::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .open(&spool_path)
+                .await
+                .map_err(io_status)?;
+            Open::Write {
+                path,
+                spool,
+                spool_path,
+            }
+        } else {
+            let response = self.s3.head(&self.caller, &self.bucket, &key).await;
+            check(&response)?;
+            let size = file_attrs_from_headers(&response).size.unwrap_or(0);
+            Open::Read { key, size }
+        };
+
+        Ok(Handle {
+            id,
+            handle: self.add_handle(open),
+        })
+    }
+
+    async fn close(&mut self, id: u32, handle: String) -> Result<Status, Self::Error> {
+        match self.handles.remove(&handle) {
+            Some(Open::Write {
+                path,
+                spool,
+                spool_path,
+            }) => self.upload(&path, spool, &spool_path).await?,
+            Some(_) => {}
+            None => return Err(StatusCode::Failure),
+        }
+        Ok(ok(id))
+    }
+
+    async fn read(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        len: u32,
+    ) -> Result<Data, Self::Error> {
+        let Some(Open::Read { key, size }) = self.handles.get(&handle) else {
+            return Err(StatusCode::Failure);
+        };
+        if offset >= *size || len == 0 {
+            return Err(StatusCode::Eof);
+        }
+        let last = (offset + len as u64).min(*size) - 1;
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header::RANGE,
+            HeaderValue::from_str(&format!("bytes={}-{}", offset, last))
+                .expect("range is a valid header"),
+        );
+        let response = self
+            .s3
+            .call(
+                &self.caller,
+                Method::GET,
+                &s3_path(&self.bucket, key),
+                headers,
+                Body::empty(),
+            )
+            .await;
+        check(&response)?;
+        let data = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .map_err(|_| StatusCode::Failure)?;
+        Ok(Data {
+            id,
+            data: data.to_vec(),
+        })
+    }
+
+    async fn write(
+        &mut self,
+        id: u32,
+        handle: String,
+        offset: u64,
+        data: Vec<u8>,
+    ) -> Result<Status, Self::Error> {
+        let Some(Open::Write { spool, .. }) = self.handles.get_mut(&handle) else {
+            return Err(StatusCode::Failure);
+        };
+        spool
+            .seek(std::io::SeekFrom::Start(offset))
+            .await
+            .map_err(io_status)?;
+        spool.write_all(&data).await.map_err(io_status)?;
+        Ok(ok(id))
+    }
+
+    async fn lstat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+        self.stat(id, path).await
+    }
+
+    async fn fstat(&mut self, id: u32, handle: String) -> Result<Attrs, Self::Error> {
+        let attrs = match self.handles.get(&handle) {
+            Some(Open::Dir { .. }) => dir_attrs(),
+            Some(Open::Read { size, .. }) => file_attrs(*size, None),
+            Some(Open::Write { spool, .. }) => {
+                file_attrs(spool.metadata().await.map_err(io_status)?.len(), None)
+            }
+            None => return Err(StatusCode::Failure),
+        };
+        Ok(Attrs { id, attrs })
+    }
+
+    /// Times and permissions cannot be stored; clients setting them after
+    /// an upload would otherwise report it as failed
+    async fn setstat(
+        &mut self,
+        id: u32,
+        _path: String,
+        _attrs: FileAttributes,
+    ) -> Result<Status, Self::Error> {
+        Ok(ok(id))
+    }
+
+    async fn fsetstat(
+        &mut self,
+        id: u32,
+        _handle: String,
+        _attrs: FileAttributes,
+    ) -> Result<Status, Self::Error> {
+        Ok(ok(id))
+    }
+
+    async fn opendir(&mut self, id: u32, path: String) -> Result<Handle, Self::Error> {
+        let path = normalize(&path);
+        let attrs = self.attrs(&path).await?;
+        if attrs.permissions != Some(DIR_MODE) {
+            return Err(StatusCode::Failure);
+        }
+        Ok(Handle {
+            id,
+            handle: self.add_handle(Open::Dir {
+                path,
+                entries: None,
+            }),
+        })
+    }
+
+    async fn readdir(&mut self, id: u32, handle: String) -> Result<Name, Self::Error> {
+        let path = match self.handles.get(&handle) {
+            Some(Open::Dir {
+                entries: None,
+                path,
+            }) => Some(path.clone()),
+            Some(Open::Dir { .. }) => None,
+            _ => return Err(StatusCode::Failure),
+        };
+        if let Some(path) = path {
+            let listed = self
+                .listing(&path)
+                .await?
+                .entries
+                .iter()
+                .map(|(name, entry)| File::new(name.as_str(), entry_attrs(entry)))
+                .collect();
+            if let Some(Open::Dir { entries, .. }) = self.handles.get_mut(&handle) {
+                *entries = Some(listed);
+            }
+        }
+
+        let Some(Open::Dir {
+            entries: Some(entries),
+            ..
+        }) = self.handles.get_mut(&handle)
+        else {
+            return Err(StatusCode::Failure);
+        };
+        if entries.is_empty() {
+            return Err(StatusCode::Eof);
+        }
+        let batch = entries.len().min(READDIR_BATCH);
+        Ok(Name {
+            id,
+            files: entries.drain(..batch).collect(),
+        })
+    }
+
+    async fn remove(&mut self, id: u32, filename: String) -> Result<Status, Self::Error> {
+        let path = normalize(&filename);
+        let key = self.key(&path);
+        check(&self.s3.head(&self.caller, &self.bucket, &key).await)?;
+        check(&self.s3.delete(&self.caller, &self.bucket, &key).await)?;
+        self.keep_parent(&path).await?;
+        self.listings.remove_entry(&path);
+        Ok(ok(id))
+    }
+
+    async fn mkdir(
+        &mut self,
+        id: u32,
+        path: String,
+        _attrs: FileAttributes,
+    ) -> Result<Status, Self::Error> {
+        let path = normalize(&path);
+        if self.attrs(&path).await.is_ok() {
+            return Err(StatusCode::Failure);
+        }
+        let response = self
+            .s3
+            .put_empty(&self.caller, &self.bucket, &self.dir_prefix(&path))
+            .await;
+        check(&response)?;
+        self.listings.put_entry(&path, DirEntry::dir());
+        self.listings.insert(&path, Listing::created());
+        Ok(ok(id))
+    }
+
+    async fn rmdir(&mut self, id: u32, path: String) -> Result<Status, Self::Error> {
+        let path = normalize(&path);
+        if path.is_empty() {
+            return Err(StatusCode::PermissionDenied);
+        }
+        let prefix = self.dir_prefix(&path);
+        let keys = self
+            .s3
+            .keys_under(&self.caller, &self.bucket, &prefix)
+            .await
+            .map_err(|response| status_of(&response))?;
+        if keys.is_empty() {
+            return Err(StatusCode::NoSuchFile);
+        }
+        if keys.iter().any(|key| *key != prefix) {
+            return Err(StatusCode::Failure);
+        }
+        check(&self.s3.delete(&self.caller, &self.bucket, &prefix).await)?;
+        self.keep_parent(&path).await?;
+        self.listings.remove_entry(&path);
+        Ok(ok(id))
+    }
+
+    async fn realpath(&mut self, id: u32, path: String) -> Result<Name, Self::Error> {
+        Ok(Name {
+            id,
+            files: vec![File::dummy(format!("/{}", normalize(&path)))],
+        })
+    }
+
+    async fn stat(&mut self, id: u32, path: String) -> Result<Attrs, Self::Error> {
+        let attrs = self.attrs(&normalize(&path)).await?;
+        Ok(Attrs { id, attrs })
+    }
+
+    /// Rename a file, or every object of a directory one by one
+    async fn rename(
+        &mut self,
+        id: u32,
+        oldpath: String,
+        newpath: String,
+    ) -> Result<Status, Self::Error> {
+        let (from, to) = (normalize(&oldpath), normalize(&newpath));
+        if from.is_empty() || to.is_empty() || to.starts_with(&format!("{}/", from)) {
+            return Err(StatusCode::Failure);
+        }
+        let attrs = self.attrs(&from).await?;
+        // SFTP renames never replace an existing file
+        if self.attrs(&to).await.is_ok() {
+            return Err(StatusCode::Failure);
+        }
+
+        let plan = if attrs.permissions == Some(DIR_MODE) {
+            let from_prefix = self.dir_prefix(&from);
+            let keys = self
+                .s3
+                .keys_under(&self.caller, &self.bucket, &from_prefix)
+                .await
+                .map_err(|response| status_of(&response))?;
+            TreeCopy::dir(&from_prefix, &self.dir_prefix(&to), keys, true)
+        } else {
+            TreeCopy::file(&self.key(&from), &self.key(&to), true)
+        };
+        let store = LoopbackTree {
+            s3: &self.s3,
+            caller: &self.caller,
+            from_bucket: &self.bucket,
+            to_bucket: &self.bucket,
+        };
+        let (from_key, to_key) = (self.key(&from), self.key(&to));
+        let result = plan
+            .run(&store, log_progress("SFTP", &from_key, &to_key))
+            .await;
+
+        self.listings.invalidate_entry(&to);
+        if let Err(response) = result {
+            self.listings.invalidate_entry(&from);
+            return Err(status_of(&response));
+        }
+        self.keep_parent(&from).await?;
+        self.listings.remove_entry(&from);
+        Ok(ok(id))
+    }
+}
+
+fn ok(id: u32) -> Status {
+    Status {
+        id,
+        status_code: StatusCode::Ok,
+        error_message: "Ok".to_string(),
+        language_tag: "en-US".to_string(),
+    }
+}
+
+fn dir_attrs() -> FileAttributes {
+    FileAttributes {
+        permissions: Some(DIR_MODE),
+        ..Default::default()
+    }
+}
+
+fn entry_attrs(entry: &DirEntry) -> FileAttributes {
+    if entry.is_dir() {
+        dir_attrs()
+    } else {
+        file_attrs(entry.size, entry.modified.map(|t| t.timestamp()))
+    }
+}
+
+fn file_attrs(size: u64, modified: Option<i64>) -> FileAttributes {
+    let mtime = modified.map(|t| t.clamp(0, u32::MAX as i64) as u32);
+    FileAttributes {
+        size: Some(size),
+        permissions: Some(FILE_MODE),
+        atime: mtime,
+        mtime,
+        ..Default::default()
+    }
+}
+
+/// Attributes of a file from its HEAD response
+fn file_attrs_from_headers(response: &Response) -> FileAttributes {
+    let header =
+        |name: header::HeaderName| response.headers().get(name).and_then(|v| v.to_str().ok());
+    let size = header(header::CONTENT_LENGTH)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let modified = header(header::LAST_MODIFIED)
+        .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+        .map(|d| d.timestamp());
+    file_attrs(size, modified)
+}
+
+/// SFTP status of a failed S3 response
+fn status_of(response: &Response) -> StatusCode {
+    match response.status() {
+        HttpStatus::NOT_FOUND => StatusCode::NoSuchFile,
+        HttpStatus::FORBIDDEN | HttpStatus::UNAUTHORIZED => StatusCode::PermissionDenied,
+        status => {
+            debug!("S3 request of SFTP session failed with {}", status);
+            StatusCode::Failure
+        }
+    }
+}
+
+fn check(response: &Response) -> Result<(), StatusCode> {
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(status_of(response))
+    }
+}
+
+fn io_status(e: std::io::Error) -> StatusCode {
+    warn!("SFTP upload spool failed: {}", e);
+    StatusCode::Failure
+}
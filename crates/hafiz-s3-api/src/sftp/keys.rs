@@ -0,0 +1,110 @@
+//! OpenSSH public keys of SFTP logins
+
+use base64::{
+    engine::general_purpose::{STANDARD as BASE64, STANDARD_NO_PAD as BASE64_NO_PAD},
+    Engine as _,
+};
+
+/// Key types SFTP logins may authenticate with
+const ALGORITHMS: [&str; 5] = [
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+];
+
+/// A public key in `authorized_keys` form: `ssh-ed25519 AAAA... comment`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyLine {
+    pub algorithm: String,
+    /// Key in SSH wire format
+    pub blob: Vec<u8>,
+    pub comment: Option<String>,
+}
+
+impl PublicKeyLine {
+    /// Parse one line of an `authorized_keys` or `.pub` file
+    ///
+    /// Options before the key type, as `authorized_keys` allows, are not
+    /// supported.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut parts = line.split_whitespace();
+        let algorithm = parts.next().ok_or("Public key is empty")?;
+        if !ALGORITHMS.contains(&algorithm) {
+            return Err(format!(
+                "Unsupported key type '{}' (expected one of {})",
+                algorithm,
+                ALGORITHMS.join(", ")
+            ));
+        }
+        let blob = parts
+            .next()
+            .and_then(|encoded| BASE64.decode(encoded).ok())
+            .ok_or("Public key is not valid base64")?;
+        // The blob starts with the key type as an SSH string
+        let named = blob
+            .get(..4)
+            .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+            .and_then(|len| blob.get(4..4 + len));
+        if named != Some(algorithm.as_bytes()) {
+            return Err(format!("Public key data is not a {} key", algorithm));
+        }
+        let comment = parts.collect::<Vec<_>>().join(" ");
+
+        Ok(Self {
+            algorithm: algorithm.to_string(),
+            blob,
+            comment: (!comment.is_empty()).then_some(comment),
+        })
+    }
+
+    /// `SHA256:` fingerprint, as printed by `ssh-keygen -l`
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.blob)
+    }
+
+    /// The key without its comment
+    pub fn to_openssh(&self) -> String {
+        format!("{} {}", self.algorithm, BASE64.encode(&self.blob))
+    }
+}
+
+/// `SHA256:` fingerprint of a key in SSH wire format
+pub fn fingerprint(blob: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, blob);
+    format!("SHA256:{}", BASE64_NO_PAD.encode(digest.as_ref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519: &str =
+        "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIJ/Fu3Byq4nzvo/xYaEl4T27zwKi7RvJK7w5DZODTvxW";
+
+    #[test]
+    fn test_parse_public_key() {
+        let key = PublicKeyLine::parse(&format!("{}  scanner at lab\n", ED25519)).unwrap();
+        assert_eq!(key.algorithm, "ssh-ed25519");
+        assert_eq!(key.comment.as_deref(), Some("scanner at lab"));
+        assert_eq!(key.to_openssh(), ED25519);
+        assert_eq!(
+            key.fingerprint(),
+            "SHA256:lFxOBXH39URXMz5LD0uxDjizOZ6w0FjSpMwOZ+6xap8"
+        );
+
+        let key = PublicKeyLine::parse(ED25519).unwrap();
+        assert_eq!(key.comment, None);
+    }
+
+    #[test]
+    fn test_rejects_invalid_keys() {
+        assert!(PublicKeyLine::parse("").is_err());
+        assert!(PublicKeyLine::parse("ssh-dss AAAAB3NzaC1kc3M=").is_err());
+        assert!(PublicKeyLine::parse("ssh-ed25519 not-base64!").is_err());
+        // Data of an ed25519 key labelled as RSA
+        let mislabelled = ED25519.replace("ssh-ed25519", "ssh-rsa");
+        assert!(PublicKeyLine::parse(&mislabelled).is_err());
+    }
+}
@@ -0,0 +1,66 @@
+//! SFTP gateway
+//!
+//! An optional listener, enabled by `[sftp]` in builds with the `sftp`
+//! feature, for appliances and scripts that deliver files over SFTP. Each
+//! SFTP login is stored in the metadata store with the public keys it may
+//! authenticate with, the Hafiz user it acts as and a home directory, a
+//! bucket or a prefix in one, that the session is confined to.
+//!
+//! File operations are carried out as S3 requests made by the login's user
+//! through the [`loopback`](crate::loopback), so bucket policies, quotas
+//! and event notifications apply as they do for S3 clients. Uploads are
+//! spooled to a temporary file and stored as one object when the client
+//! closes the file.
+
+mod keys;
+
+#[cfg(feature = "sftp")]
+mod fs;
+#[cfg(feature = "sftp")]
+mod session;
+
+pub use keys::{fingerprint, PublicKeyLine};
+#[cfg(feature = "sftp")]
+pub(crate) use session::serve;
+
+/// Bucket and key prefix of a home directory given as `bucket` or
+/// `bucket/prefix`
+///
+/// The prefix is empty or ends with `/`.
+pub fn split_home(home: &str) -> Option<(String, String)> {
+    let home = home.trim_matches('/');
+    let (bucket, prefix) = home.split_once('/').unwrap_or((home, ""));
+    if bucket.is_empty() {
+        return None;
+    }
+    if prefix.is_empty() {
+        return Some((bucket.to_string(), String::new()));
+    }
+    if prefix
+        .split('/')
+        .any(|segment| segment.is_empty() || segment == "." || segment == "..")
+    {
+        return None;
+    }
+    Some((bucket.to_string(), format!("{}/", prefix)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_home() {
+        assert_eq!(
+            split_home("drop"),
+            Some(("drop".to_string(), String::new()))
+        );
+        assert_eq!(
+            split_home("/drop/scanner/in/"),
+            Some(("drop".to_string(), "scanner/in/".to_string()))
+        );
+        assert_eq!(split_home(""), None);
+        assert_eq!(split_home("drop/../other"), None);
+        assert_eq!(split_home("drop//in"), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! SSH transport of the SFTP gateway
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use axum::{extract::ConnectInfo, Router};
+use hafiz_core::{config::SftpConfig, types::SftpUser, Error, Result};
+use russh::server::{Auth, Handler, Msg, Session};
+use russh::{Channel, ChannelId, MethodSet};
+use russh_keys::key::{KeyPair, PublicKey};
+use russh_keys::PublicKeyBase64;
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tracing::{debug, info, warn};
+
+use super::fs::SftpFs;
+use super::{fingerprint, split_home};
+use crate::loopback::{Caller, Loopback};
+use crate::server::{stopping, AppState};
+
+/// Accept SFTP sessions on `listener` until shutdown is signalled
+///
+/// Open sessions are closed at shutdown; uploads not yet closed by their
+/// clients are abandoned.
+pub(crate) async fn serve(
+    state: AppState,
+    s3: Router,
+    listener: TcpListener,
+    config: &SftpConfig,
+    host_key_file: &Path,
+    mut shutdown: watch::Receiver<bool>,
+) -> Result<()> {
+    let ssh_config = Arc::new(russh::server::Config {
+        methods: MethodSet::PUBLICKEY,
+        keys: vec![load_host_key(host_key_file)?],
+        inactivity_timeout: Some(Duration::from_secs(config.idle_timeout_secs)),
+        auth_rejection_time: Duration::from_secs(1),
+        auth_rejection_time_initial: Some(Duration::ZERO),
+        ..Default::default()
+    });
+    let s3 = Loopback::new(s3);
+    let listing_ttl = Duration::from_secs(config.listing_cache_secs);
+
+    let mut sessions = JoinSet::new();
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    warn!("Failed to accept SFTP connection: {}", e);
+                    continue;
+                }
+            },
+            // Reap finished sessions as we go
+            Some(_) = sessions.join_next(), if !sessions.is_empty() => continue,
+            _ = stopping(&mut shutdown) => break,
+        };
+        let handler = SshHandler {
+            state: state.clone(),
+            s3: s3.clone(),
+            peer,
+            listing_ttl,
+            login: None,
+            channels: HashMap::new(),
+        };
+        let ssh_config = ssh_config.clone();
+        sessions.spawn(async move {
+            let result = match russh::server::run_stream(ssh_config, stream, handler).await {
+                Ok(session) => session.await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                debug!("SFTP session from {} ended: {}", peer, e);
+            }
+        });
+    }
+
+    sessions.shutdown().await;
+    Ok(())
+}
+
+/// Load the host key, generating it on first start
+fn load_host_key(path: &Path) -> Result<KeyPair> {
+    if path.exists() {
+        return russh_keys::load_secret_key(path, None).map_err(|e| {
+            Error::InternalError(format!(
+                "Failed to load SFTP host key {}: {}",
+                path.display(),
+                e
+            ))
+        });
+    }
+
+    let key = KeyPair::generate_ed25519()
+        .ok_or_else(|| Error::InternalError("Failed to generate SFTP host key".into()))?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let file = options.open(path)?;
+    russh_keys::encode_pkcs8_pem(&key, file)
+        .map_err(|e| Error::InternalError(format!("Failed to write SFTP host key: {}", e)))?;
+
+    info!("Generated SFTP host key {}", path.display());
+    Ok(key)
+}
+
+/// One SSH connection
+struct SshHandler {
+    state: AppState,
+    s3: Loopback,
+    peer: SocketAddr,
+    listing_ttl: Duration,
+    /// Set once authenticated
+    login: Option<SftpUser>,
+    /// Session channels not yet running a subsystem
+    channels: HashMap<ChannelId, Channel<Msg>>,
+}
+
+impl SshHandler {
+    /// The login `user`, if `public_key` is one of its keys and the Hafiz
+    /// user it acts as is enabled
+    async fn find_login(&self, user: &str, public_key: &PublicKey) -> Option<SftpUser> {
+        let login = match self.state.metadata.get_sftp_user(user).await {
+            Ok(login) => login?,
+            Err(e) => {
+                warn!("SFTP login of {} failed: {}", user, e);
+                return None;
+            }
+        };
+        let fingerprint = fingerprint(&public_key.public_key_bytes());
+        if !login.keys.iter().any(|key| key.fingerprint == fingerprint) {
+            debug!("SFTP login {} offered unknown key {}", user, fingerprint);
+            return None;
+        }
+        match self.state.metadata.get_credentials(&login.access_key).await {
+            Ok(Some(credentials)) if credentials.enabled => Some(login),
+            Ok(_) => {
+                warn!(
+                    "SFTP login {} acts as {}, which does not exist or is disabled",
+                    user, login.access_key
+                );
+                None
+            }
+            Err(e) => {
+                warn!("SFTP login of {} failed: {}", user, e);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Handler for SshHandler {
+    type Error = russh::Error;
+
+    async fn auth_publickey(
+        &mut self,
+        user: &str,
+        public_key: &PublicKey,
+    ) -> std::result::Result<Auth, Self::Error> {
+        let Some(login) = self.find_login(user, public_key).await else {
+            return Ok(Auth::Reject {
+                proceed_with_methods: None,
+            });
+        };
+        info!(
+            "SFTP login {} from {} as {}",
+            login.username, self.peer, login.access_key
+        );
+        self.state
+            .key_usage
+            .record(&self.state.metadata, &login.access_key);
+        self.login = Some(login);
+        Ok(Auth::Accept)
+    }
+
+    async fn channel_open_session(
+        &mut self,
+        channel: Channel<Msg>,
+        _session: &mut Session,
+    ) -> std::result::Result<bool, Self::Error> {
+        self.channels.insert(channel.id(), channel);
+        Ok(true)
+    }
+
+    async fn channel_eof(
+        &mut self,
+        channel: ChannelId,
+        session: &mut Session,
+    ) -> std::result::Result<(), Self::Error> {
+        session.close(channel);
+        Ok(())
+    }
+
+    /// Only the `sftp` subsystem is offered; there are no shells or exec
+    async fn subsystem_request(
+        &mut self,
+        channel_id: ChannelId,
+        name: &str,
+        session: &mut Session,
+    ) -> std::result::Result<(), Self::Error> {
+        let channel = self.channels.remove(&channel_id);
+        let (Some(channel), Some(login), "sftp") = (channel, &self.login, name) else {
+            session.channel_failure(channel_id);
+            return Ok(());
+        };
+        let Some((bucket, root)) = split_home(&login.home) else {
+            warn!(
+                "SFTP login {} has an invalid home {}",
+                login.username, login.home
+            );
+            session.channel_failure(channel_id);
+            return Ok(());
+        };
+
+        let caller = Caller {
+            access_key: login.access_key.clone(),
+            connect_info: Some(ConnectInfo(self.peer)),
+        };
+        let fs = SftpFs::new(self.s3.clone(), caller, bucket, &root, self.listing_ttl);
+        session.channel_success(channel_id);
+        russh_sftp::server::run(channel.into_stream(), fs).await;
+        Ok(())
+    }
+}
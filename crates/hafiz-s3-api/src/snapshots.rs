@@ -0,0 +1,258 @@
+//! Bucket snapshots
+//!
+//! A snapshot keeps every version, delete marker and object tag set of a
+//! bucket as it was when the snapshot was taken. The metadata is captured in
+//! one transaction; the data of each version is then copied into
+//! [`SNAPSHOT_BUCKET`] under `<snapshot id>/<storage key>`. Local storage
+//! hard-links those copies, so a snapshot costs no space until the bucket's
+//! objects are overwritten or deleted. Other backends copy the bytes.
+//!
+//! Versioned data is never rewritten in place, so the snapshot of a
+//! versioned bucket is exact. A null version overwritten while the snapshot
+//! is taken is captured in its newer state instead, and a version deleted
+//! before its data was copied is left out and counted in the report.
+//!
+//! A snapshot is restored by cloning it into a bucket that does not exist
+//! yet. The data is copied first and the bucket appears with all of its
+//! versions at once.
+
+use std::collections::BTreeSet;
+
+use hafiz_core::types::{Bucket, BucketSnapshot, ObjectInternal, NULL_VERSION_ID};
+use hafiz_core::{Error, Result};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::routes::storage_key;
+use crate::server::AppState;
+
+/// Bucket holding the data of all snapshots
+pub const SNAPSHOT_BUCKET: &str = ".hafiz-snapshots";
+
+/// Snapshot versions read per metadata page
+const PAGE_SIZE: i64 = 1000;
+
+/// Times a null version rewritten during the snapshot is captured again
+/// before its latest record is kept as is
+const MAX_RECAPTURES: usize = 3;
+
+/// Outcome of taking a snapshot
+#[derive(Debug, Clone, Serialize)]
+pub struct SnapshotReport {
+    pub snapshot: BucketSnapshot,
+    /// Versions deleted before their data was copied, and left out
+    pub dropped: u64,
+    /// Null versions overwritten while the snapshot was taken, and captured
+    /// in their newer state
+    pub recaptured: u64,
+}
+
+/// Key of a version's data in [`SNAPSHOT_BUCKET`]
+fn data_key(snapshot_id: &str, object: &ObjectInternal) -> String {
+    format!("{}/{}", snapshot_id, storage_key(&object.key, &object.version_id))
+}
+
+/// Take a snapshot of `bucket`
+pub async fn create_snapshot(
+    state: &AppState,
+    bucket: &str,
+    description: Option<&str>,
+) -> Result<SnapshotReport> {
+    if !state.storage.bucket_exists(SNAPSHOT_BUCKET).await? {
+        state.storage.create_bucket(SNAPSHOT_BUCKET).await?;
+    }
+
+    let snapshot = state.metadata.create_bucket_snapshot(bucket, description).await?;
+    let mut report = SnapshotReport {
+        snapshot,
+        dropped: 0,
+        recaptured: 0,
+    };
+    if let Err(e) = capture_data(state, &mut report).await {
+        warn!("Snapshot {} of bucket {} failed: {}", report.snapshot.id, bucket, e);
+        if let Err(e) = delete_snapshot(state, &report.snapshot.id).await {
+            warn!("Failed to discard snapshot {}: {}", report.snapshot.id, e);
+        }
+        return Err(e);
+    }
+
+    report.snapshot = state.metadata.complete_bucket_snapshot(&report.snapshot.id).await?;
+    info!(
+        "Took snapshot {} of bucket {}: {} versions, {} dropped, {} recaptured",
+        report.snapshot.id, bucket, report.snapshot.versions, report.dropped, report.recaptured
+    );
+    Ok(report)
+}
+
+/// Copy the data of every version recorded in a new snapshot
+async fn capture_data(state: &AppState, report: &mut SnapshotReport) -> Result<()> {
+    let id = report.snapshot.id.clone();
+    let bucket = report.snapshot.bucket.clone();
+    let mut after: Option<(String, String)> = None;
+
+    loop {
+        let cursor = after.as_ref().map(|(k, v)| (k.as_str(), v.as_str()));
+        let page = state.metadata.list_snapshot_objects(&id, cursor, PAGE_SIZE).await?;
+        let Some(last) = page.last() else {
+            return Ok(());
+        };
+        after = Some((last.key.clone(), last.version_id.clone()));
+        let full_page = page.len() as i64 == PAGE_SIZE;
+
+        for mut object in page {
+            let mut attempts = 0;
+            while !object.is_delete_marker {
+                let copied = state
+                    .storage
+                    .copy(&bucket, &storage_key(&object.key, &object.version_id), SNAPSHOT_BUCKET, &data_key(&id, &object))
+                    .await;
+                match copied {
+                    Ok(()) => {}
+                    Err(Error::NoSuchKey) => {
+                        state
+                            .metadata
+                            .remove_snapshot_object(&id, &object.key, &object.version_id)
+                            .await?;
+                        report.dropped += 1;
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+
+                // Only the null version is rewritten in place; check the
+                // data copied is still the one recorded
+                if object.version_id != NULL_VERSION_ID || attempts == MAX_RECAPTURES {
+                    break;
+                }
+                let live = state
+                    .metadata
+                    .get_object_version(&bucket, &object.key, Some(NULL_VERSION_ID))
+                    .await?;
+                if live.is_some_and(|live| {
+                    live.etag == object.etag && live.last_modified == object.last_modified
+                }) {
+                    break;
+                }
+
+                attempts += 1;
+                report.recaptured += 1;
+                match state
+                    .metadata
+                    .recapture_snapshot_object(&id, &object.key, NULL_VERSION_ID)
+                    .await?
+                {
+                    Some(recaptured) => object = recaptured,
+                    None => {
+                        discard_data(state, &id, &object).await;
+                        report.dropped += 1;
+                        break;
+                    }
+                }
+            }
+        }
+
+        if !full_page {
+            return Ok(());
+        }
+    }
+}
+
+async fn discard_data(state: &AppState, snapshot_id: &str, object: &ObjectInternal) {
+    match state.storage.delete(SNAPSHOT_BUCKET, &data_key(snapshot_id, object)).await {
+        Ok(()) | Err(Error::NoSuchKey) => {}
+        Err(e) => warn!("Failed to delete snapshot data of {}: {}", object.key, e),
+    }
+}
+
+/// Delete a snapshot and its data
+pub async fn delete_snapshot(state: &AppState, id: &str) -> Result<()> {
+    let mut after: Option<(String, String)> = None;
+    loop {
+        let cursor = after.as_ref().map(|(k, v)| (k.as_str(), v.as_str()));
+        let page = state.metadata.list_snapshot_objects(id, cursor, PAGE_SIZE).await?;
+        let Some(last) = page.last() else {
+            break;
+        };
+        after = Some((last.key.clone(), last.version_id.clone()));
+        let full_page = page.len() as i64 == PAGE_SIZE;
+
+        for object in page.iter().filter(|o| !o.is_delete_marker) {
+            discard_data(state, id, object).await;
+        }
+        if !full_page {
+            break;
+        }
+    }
+
+    state.metadata.delete_bucket_snapshot(id).await?;
+    info!("Deleted snapshot {}", id);
+    Ok(())
+}
+
+/// Clone a complete snapshot into the new bucket `target`
+pub async fn restore_snapshot(state: &AppState, id: &str, target: &str) -> Result<Bucket> {
+    let snapshot = state
+        .metadata
+        .get_bucket_snapshot(id)
+        .await?
+        .ok_or_else(|| Error::InvalidArgument(format!("No such snapshot: {}", id)))?;
+    if !snapshot.is_complete() {
+        return Err(Error::InvalidRequest(format!(
+            "Snapshot {} is incomplete and cannot be restored",
+            id
+        )));
+    }
+    Bucket::validate_name_with(target, state.config.server.bucket_name_rules)?;
+    if state.metadata.get_bucket(target).await?.is_some() {
+        return Err(Error::BucketAlreadyExists);
+    }
+
+    state.storage.create_bucket(target).await?;
+    let mut copied = Vec::new();
+    let mut keys = BTreeSet::new();
+    let result = async {
+        let mut after: Option<(String, String)> = None;
+        loop {
+            let cursor = after.as_ref().map(|(k, v)| (k.as_str(), v.as_str()));
+            let page = state.metadata.list_snapshot_objects(id, cursor, PAGE_SIZE).await?;
+            let Some(last) = page.last() else {
+                break;
+            };
+            after = Some((last.key.clone(), last.version_id.clone()));
+            let full_page = page.len() as i64 == PAGE_SIZE;
+
+            for object in page.iter().filter(|o| !o.is_delete_marker) {
+                let key = storage_key(&object.key, &object.version_id);
+                state
+                    .storage
+                    .copy(SNAPSHOT_BUCKET, &data_key(id, object), target, &key)
+                    .await?;
+                copied.push(key);
+                keys.insert(object.key.clone());
+            }
+            if !full_page {
+                break;
+            }
+        }
+        state.metadata.restore_bucket_snapshot(id, target).await
+    }
+    .await;
+
+    match result {
+        Ok(bucket) => {
+            for key in &keys {
+                state.search.object_changed(target, key);
+            }
+            Ok(bucket)
+        }
+        // Whatever is stored under the name now belongs to that bucket
+        Err(Error::BucketAlreadyExists) => Err(Error::BucketAlreadyExists),
+        Err(e) => {
+            for key in &copied {
+                let _ = state.storage.delete(target, key).await;
+            }
+            let _ = state.storage.delete_bucket(target).await;
+            Err(e)
+        }
+    }
+}
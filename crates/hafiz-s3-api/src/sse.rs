@@ -0,0 +1,421 @@
+//! Server-side encryption of object data
+//!
+//! SSE-S3 objects are encrypted under a data encryption key (DEK) of their
+//! own, generated when the object is written and stored in its metadata
+//! encrypted under the current key of the account owning the bucket (see
+//! [`hafiz_crypto::account_keys`]). The object records that key's ID, so
+//! it still decrypts after the account's key is rotated. SSE-C objects are
+//! encrypted under the key the client sends with every request; only its
+//! MD5 is kept.
+//!
+//! The data is sealed in segments (see [`hafiz_crypto::encryption::segments`]),
+//! so a ranged GET reads and decrypts only the segments covering the range.
+//! Object sizes stay those of the plaintext: the stored data is 16 bytes
+//! longer per segment, and its ETag is the MD5 of what is stored.
+
+use std::ops::Range;
+
+use axum::http::HeaderMap;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use bytes::Bytes;
+use hafiz_core::config::DefaultEncryption;
+use hafiz_core::types::{EncryptionInfo, EncryptionType, ObjectInternal};
+use hafiz_core::{Error, Result};
+use hafiz_crypto::{
+    AccountKeys, EncryptedObjectInfo, ObjectEncryptor, SegmentLayout, SseCEncryptor, SseType,
+    DEFAULT_SEGMENT_SIZE, FIRST_KEY_GENERATION,
+};
+
+use crate::server::AppState;
+
+const SSE_HEADER: &str = "x-amz-server-side-encryption";
+const SSE_C_KEY_HEADER: &str = "x-amz-server-side-encryption-customer-key";
+const SSE_C_KEY_MD5_HEADER: &str = "x-amz-server-side-encryption-customer-key-md5";
+const COPY_SOURCE_SSE_C_KEY_HEADER: &str = "x-amz-copy-source-server-side-encryption-customer-key";
+const COPY_SOURCE_SSE_C_KEY_MD5_HEADER: &str =
+    "x-amz-copy-source-server-side-encryption-customer-key-md5";
+
+/// AES-GCM tag appended to data sealed as one message
+const TAG_LEN: u64 = 16;
+
+/// Key a client sends for SSE-C
+#[derive(Clone)]
+pub struct CustomerKey {
+    /// The key, base64
+    key: String,
+    /// MD5 of the key, base64
+    md5: String,
+}
+
+impl CustomerKey {
+    fn from_headers(headers: &HeaderMap, key_header: &str, md5_header: &str) -> Result<Option<Self>> {
+        let Some(key) = header(headers, key_header) else {
+            return Ok(None);
+        };
+        let (_, md5) = ObjectEncryptor::from_customer_key(key)
+            .map_err(|e| Error::InvalidArgument(e.to_string()))?;
+        if header(headers, md5_header).is_some_and(|sent| sent != md5) {
+            return Err(Error::InvalidArgument(
+                "The customer key MD5 does not match the key".into(),
+            ));
+        }
+        Ok(Some(Self {
+            key: key.to_string(),
+            md5,
+        }))
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// SSE-C key sent with a request, checked against its MD5 if one is sent
+pub fn customer_key(headers: &HeaderMap) -> Result<Option<CustomerKey>> {
+    CustomerKey::from_headers(headers, SSE_C_KEY_HEADER, SSE_C_KEY_MD5_HEADER)
+}
+
+/// SSE-C key of the source of a copy
+pub fn copy_source_customer_key(headers: &HeaderMap) -> Result<Option<CustomerKey>> {
+    CustomerKey::from_headers(headers, COPY_SOURCE_SSE_C_KEY_HEADER, COPY_SOURCE_SSE_C_KEY_MD5_HEADER)
+}
+
+/// Encryption an upload asks for, or the configured default
+pub fn requested_encryption(state: &AppState, headers: &HeaderMap) -> Result<EncryptionType> {
+    let config = &state.config.encryption;
+    let requested = if headers.contains_key(SSE_C_KEY_HEADER) {
+        EncryptionType::SseC
+    } else {
+        match EncryptionType::from_header(header(headers, SSE_HEADER)) {
+            EncryptionType::None
+                if config.default_encryption == DefaultEncryption::Aes256 && state.object_keys.is_some() =>
+            {
+                EncryptionType::SseS3
+            }
+            requested => requested,
+        }
+    };
+
+    match requested {
+        EncryptionType::SseS3 if state.object_keys.is_none() => Err(Error::InvalidArgument(
+            "SSE-S3 is not enabled on this server".into(),
+        )),
+        EncryptionType::SseC if !config.enabled || !config.sse_c_enabled => Err(Error::InvalidArgument(
+            "SSE-C is not enabled on this server".into(),
+        )),
+        requested => Ok(requested),
+    }
+}
+
+/// Encrypt the data of a new object of a bucket owned by `owner` as
+/// `encryption_type` asks, returning the bytes to store and the object's
+/// encryption info
+pub async fn seal(
+    state: &AppState,
+    owner: &str,
+    encryption_type: EncryptionType,
+    customer_key: Option<&CustomerKey>,
+    data: Bytes,
+) -> Result<(Bytes, EncryptionInfo)> {
+    let (ciphertext, encryption) = match encryption_type {
+        EncryptionType::None => return Ok((data, EncryptionInfo::none())),
+        EncryptionType::SseS3 => {
+            let keys = state
+                .object_keys
+                .clone()
+                .ok_or_else(|| Error::InvalidArgument("SSE-S3 is not enabled on this server".into()))?;
+            let generation = state
+                .metadata
+                .get_account(owner)
+                .await?
+                .map_or(FIRST_KEY_GENERATION, |account| account.key_generation);
+            let owner = owner.to_string();
+            blocking(move || seal_sse_s3(&keys, &owner, generation, &data)).await?
+        }
+        EncryptionType::SseC => {
+            let key = customer_key
+                .cloned()
+                .ok_or_else(|| Error::InvalidRequest("SSE-C needs the customer key".into()))?;
+            blocking(move || seal_sse_c(&key, &data)).await?
+        }
+    };
+    Ok((Bytes::from(ciphertext), encryption))
+}
+
+/// Plaintext of an object, or of its bytes `start..=end`, decrypting the
+/// data if it is sealed
+///
+/// Only the segments holding the range are read.
+pub async fn read_object(
+    state: &AppState,
+    object: &ObjectInternal,
+    storage_key: &str,
+    range: Option<(i64, i64)>,
+    customer_key: Option<&CustomerKey>,
+) -> Result<Bytes> {
+    if !object.encryption.is_sealed() {
+        return match range {
+            Some((start, end)) => state.storage.get_range(&object.bucket, storage_key, start, end).await,
+            None => state.storage.get(&object.bucket, storage_key).await,
+        };
+    }
+
+    let range = match range {
+        Some((start, end)) => start as u64..end as u64 + 1,
+        None => 0..object.size as u64,
+    };
+    let stored = stored_range(&object.encryption, object.size, range.clone())?;
+    let ciphertext = state
+        .storage
+        .get_range(&object.bucket, storage_key, stored.start as i64, stored.end as i64 - 1)
+        .await?;
+
+    let keys = state.object_keys.clone();
+    let (encryption, size) = (object.encryption.clone(), object.size);
+    let customer_key = customer_key.cloned();
+    let plaintext = blocking(move || {
+        open(keys.as_deref(), &encryption, customer_key.as_ref(), size, &ciphertext, range)
+    })
+    .await?;
+    Ok(Bytes::from(plaintext))
+}
+
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Error::InternalError(e.to_string()))?
+}
+
+/// Encrypt `data` as SSE-S3 under a new DEK, wrapped under generation
+/// `generation` of `account`'s key
+pub fn seal_sse_s3(
+    keys: &AccountKeys,
+    account: &str,
+    generation: u32,
+    data: &[u8],
+) -> Result<(Vec<u8>, EncryptionInfo)> {
+    let (ciphertext, sealed) = keys
+        .encryptor(account, generation, DEFAULT_SEGMENT_SIZE)
+        .and_then(|encryptor| encryptor.encrypt_stream(data))
+        .map_err(crypto_error)?;
+    Ok((ciphertext, encryption_info(EncryptionType::SseS3, sealed)))
+}
+
+/// Encrypt `data` as SSE-C under `key`
+pub fn seal_sse_c(key: &CustomerKey, data: &[u8]) -> Result<(Vec<u8>, EncryptionInfo)> {
+    let (ciphertext, sealed) = SseCEncryptor::encrypt(data, &key.key).map_err(crypto_error)?;
+    Ok((ciphertext, encryption_info(EncryptionType::SseC, sealed)))
+}
+
+/// Bytes stored for a sealed object of `size` plaintext bytes
+pub fn stored_len(encryption: &EncryptionInfo, size: i64) -> Result<u64> {
+    Ok(match layout(encryption, size)? {
+        Some(layout) => layout.ciphertext_len(),
+        None => size as u64 + TAG_LEN,
+    })
+}
+
+/// Stored bytes holding plaintext `range` of a sealed object; the whole
+/// data if it was sealed as one message
+pub fn stored_range(encryption: &EncryptionInfo, size: i64, range: Range<u64>) -> Result<Range<u64>> {
+    Ok(match layout(encryption, size)? {
+        Some(layout) => layout.locate(range).ciphertext,
+        None => 0..size as u64 + TAG_LEN,
+    })
+}
+
+/// Decrypt plaintext `range` of a sealed object from `ciphertext`, the
+/// stored bytes [`stored_range`] gives for it
+pub fn open(
+    keys: Option<&AccountKeys>,
+    encryption: &EncryptionInfo,
+    customer_key: Option<&CustomerKey>,
+    size: i64,
+    ciphertext: &[u8],
+    range: Range<u64>,
+) -> Result<Vec<u8>> {
+    let sealed = sealed_info(encryption)?;
+    let stored_len = stored_len(encryption, size)?;
+    let plaintext = match encryption.encryption_type {
+        EncryptionType::None => return Ok(ciphertext.to_vec()),
+        EncryptionType::SseS3 => {
+            let keys = keys.ok_or_else(|| {
+                Error::InternalError("No master key is configured to decrypt SSE-S3 objects".into())
+            })?;
+            keys.decryptor(encryption.key_id.as_deref(), DEFAULT_SEGMENT_SIZE)
+                .and_then(|decryptor| decryptor.decrypt_range(ciphertext, &sealed, stored_len, range))
+        }
+        EncryptionType::SseC => {
+            let key = customer_key.ok_or_else(|| {
+                Error::InvalidRequest(
+                    "The object was stored with SSE-C; the request must provide its customer key".into(),
+                )
+            })?;
+            if encryption.sse_customer_key_md5.as_ref() != Some(&key.md5) {
+                return Err(Error::AccessDenied);
+            }
+            SseCEncryptor::decrypt_range(ciphertext, &sealed, stored_len, range, &key.key)
+        }
+    };
+    plaintext.map_err(|e| Error::InternalError(format!("Failed to decrypt object data: {}", e)))
+}
+
+fn layout(encryption: &EncryptionInfo, size: i64) -> Result<Option<SegmentLayout>> {
+    match encryption.segment_size {
+        Some(0) => Err(Error::InternalError("Stored segment size is 0".into())),
+        Some(segment_size) => Ok(Some(SegmentLayout::new(segment_size, size as u64))),
+        None => Ok(None),
+    }
+}
+
+fn crypto_error(e: hafiz_crypto::EncryptionError) -> Error {
+    Error::InternalError(format!("Failed to encrypt object data: {}", e))
+}
+
+/// Encryption info to store for data sealed as `sealed` describes
+fn encryption_info(encryption_type: EncryptionType, sealed: EncryptedObjectInfo) -> EncryptionInfo {
+    EncryptionInfo {
+        encryption_type,
+        encrypted_dek: sealed.encrypted_dek.map(|dek| BASE64.encode(dek)),
+        dek_nonce: sealed.dek_nonce.map(|nonce| BASE64.encode(nonce)),
+        data_nonce: Some(BASE64.encode(sealed.data_nonce)),
+        sse_customer_key_md5: sealed.sse_customer_key_md5,
+        segment_size: sealed.segment_size,
+        key_id: sealed.key_id,
+    }
+}
+
+/// How the data of an object was sealed, from its stored encryption info
+fn sealed_info(encryption: &EncryptionInfo) -> Result<EncryptedObjectInfo> {
+    let decode = |value: &str| {
+        BASE64
+            .decode(value)
+            .map_err(|e| Error::InternalError(format!("Invalid stored encryption info: {}", e)))
+    };
+    Ok(EncryptedObjectInfo {
+        sse_type: match encryption.encryption_type {
+            EncryptionType::None => SseType::None,
+            EncryptionType::SseS3 => SseType::SseS3,
+            EncryptionType::SseC => SseType::SseC,
+        },
+        encrypted_dek: encryption.encrypted_dek.as_deref().map(decode).transpose()?,
+        dek_nonce: encryption.dek_nonce.as_deref().map(decode).transpose()?,
+        data_nonce: decode(encryption.data_nonce.as_deref().unwrap_or_default())?,
+        sse_customer_key_md5: encryption.sse_customer_key_md5.clone(),
+        segment_size: encryption.segment_size,
+        key_id: encryption.key_id.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Plaintext spanning three segments
+    fn plaintext() -> Vec<u8> {
+        (0..2 * DEFAULT_SEGMENT_SIZE + 1000).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Read plaintext `range` the way a ranged GET does
+    fn read_range(
+        keys: Option<&AccountKeys>,
+        encryption: &EncryptionInfo,
+        customer_key: Option<&CustomerKey>,
+        stored: &[u8],
+        size: usize,
+        range: Range<u64>,
+    ) -> Result<Vec<u8>> {
+        let located = stored_range(encryption, size as i64, range.clone())?;
+        let ciphertext = &stored[located.start as usize..located.end as usize];
+        open(keys, encryption, customer_key, size as i64, ciphertext, range)
+    }
+
+    #[test]
+    fn test_sse_s3_round_trip() {
+        let keys = AccountKeys::new(&[7; 32]).unwrap();
+        let data = plaintext();
+        let (stored, encryption) = seal_sse_s3(&keys, "acme", 1, &data).unwrap();
+        assert!(encryption.is_sealed());
+        assert!(encryption.encrypted_dek.is_some());
+        assert_eq!(encryption.key_id.as_deref(), Some("acme/v1"));
+        assert_eq!(stored.len() as u64, stored_len(&encryption, data.len() as i64).unwrap());
+        assert_ne!(&stored[..data.len()], &data[..]);
+
+        // A range across the first segment boundary reads two segments only
+        let range = DEFAULT_SEGMENT_SIZE as u64 - 10..DEFAULT_SEGMENT_SIZE as u64 + 10;
+        let located = stored_range(&encryption, data.len() as i64, range.clone()).unwrap();
+        assert!(located.end - located.start < stored.len() as u64);
+        let read = read_range(Some(&keys), &encryption, None, &stored, data.len(), range.clone()).unwrap();
+        assert_eq!(read, &data[range.start as usize..range.end as usize]);
+
+        let whole = read_range(Some(&keys), &encryption, None, &stored, data.len(), 0..data.len() as u64);
+        assert_eq!(whole.unwrap(), data);
+
+        // Another master key does not open it
+        let other = AccountKeys::new(&[8; 32]).unwrap();
+        assert!(read_range(Some(&other), &encryption, None, &stored, data.len(), range).is_err());
+    }
+
+    #[test]
+    fn test_sse_c_round_trip() {
+        let mut headers = HeaderMap::new();
+        headers.insert(SSE_C_KEY_HEADER, BASE64.encode([3u8; 32]).parse().unwrap());
+        let key = customer_key(&headers).unwrap().unwrap();
+
+        let data = plaintext();
+        let (stored, encryption) = seal_sse_c(&key, &data).unwrap();
+        assert!(encryption.encrypted_dek.is_none());
+        assert_ne!(&stored[..data.len()], &data[..]);
+
+        let range = 2 * DEFAULT_SEGMENT_SIZE as u64 + 5..2 * DEFAULT_SEGMENT_SIZE as u64 + 50;
+        let read = read_range(None, &encryption, Some(&key), &stored, data.len(), range.clone()).unwrap();
+        assert_eq!(read, &data[range.start as usize..range.end as usize]);
+
+        // Without the key, or with another, the data stays closed
+        let missing = read_range(None, &encryption, None, &stored, data.len(), range.clone());
+        assert!(matches!(missing, Err(Error::InvalidRequest(_))));
+        headers.insert(SSE_C_KEY_HEADER, BASE64.encode([4u8; 32]).parse().unwrap());
+        let wrong = customer_key(&headers).unwrap().unwrap();
+        let read = read_range(None, &encryption, Some(&wrong), &stored, data.len(), range);
+        assert!(matches!(read, Err(Error::AccessDenied)));
+
+        // A key that does not match the MD5 sent with it is refused
+        headers.insert(SSE_C_KEY_MD5_HEADER, key.md5.parse().unwrap());
+        assert!(customer_key(&headers).is_err());
+    }
+
+    #[test]
+    fn test_dek_is_wrapped_under_the_account_key() {
+        let keys = AccountKeys::new(&[7; 32]).unwrap();
+        let data = plaintext();
+        let (stored, before) = seal_sse_s3(&keys, "acme", 1, &data).unwrap();
+
+        // Only acme's first key opens the DEK, not the master key itself
+        let dek = BASE64.decode(before.encrypted_dek.as_ref().unwrap()).unwrap();
+        let nonce = BASE64.decode(before.dek_nonce.as_ref().unwrap()).unwrap();
+        assert!(keys.key_manager(Some("acme/v1")).unwrap().decrypt_dek(&dek, &nonce).is_ok());
+        assert!(keys.key_manager(None).unwrap().decrypt_dek(&dek, &nonce).is_err());
+        let unlabelled = EncryptionInfo { key_id: None, ..before.clone() };
+        assert!(read_range(Some(&keys), &unlabelled, None, &stored, data.len(), 0..10).is_err());
+
+        // After a rotation new objects use the next generation, and
+        // objects written before it still read
+        let (_, after) = seal_sse_s3(&keys, "acme", 2, &data).unwrap();
+        assert_eq!(after.key_id.as_deref(), Some("acme/v2"));
+        let read = read_range(Some(&keys), &before, None, &stored, data.len(), 0..10).unwrap();
+        assert_eq!(read, &data[..10]);
+    }
+
+    #[test]
+    fn test_empty_object() {
+        let keys = AccountKeys::new(&[7; 32]).unwrap();
+        let (stored, encryption) = seal_sse_s3(&keys, "acme", 1, b"").unwrap();
+        assert_eq!(stored.len() as u64, TAG_LEN);
+        let read = read_range(Some(&keys), &encryption, None, &stored, 0, 0..0).unwrap();
+        assert!(read.is_empty());
+    }
+}
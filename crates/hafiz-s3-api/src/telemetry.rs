@@ -0,0 +1,139 @@
+//! Logging setup and OpenTelemetry export
+//!
+//! [`init`] installs the global tracing subscriber: the log formatter from
+//! `[logging]` and, in builds with the `otel` feature and `[telemetry]`
+//! enabled, an OTLP exporter for spans. The S3 request, storage and
+//! metadata spans then reach the collector as one trace per request.
+//! Metrics are exported by [`MetricsRecorder::with_telemetry`](crate::MetricsRecorder::with_telemetry)
+//! through the meter provider set up here.
+//!
+//! Keep the returned [`Telemetry`] alive for the life of the process;
+//! dropping it flushes whatever is still buffered.
+
+use hafiz_core::config::HafizConfig;
+use hafiz_core::{Error, Result};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
+
+#[cfg(feature = "otel")]
+use hafiz_core::config::TelemetryConfig;
+#[cfg(feature = "otel")]
+use opentelemetry::KeyValue;
+#[cfg(feature = "otel")]
+use opentelemetry_otlp::WithExportConfig;
+#[cfg(feature = "otel")]
+use opentelemetry_sdk::{
+    metrics::SdkMeterProvider,
+    runtime,
+    trace::{self, Sampler},
+    Resource,
+};
+
+/// Exporters to flush on shutdown
+#[derive(Default)]
+pub struct Telemetry {
+    #[cfg(feature = "otel")]
+    tracing: bool,
+    #[cfg(feature = "otel")]
+    meter_provider: Option<SdkMeterProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        {
+            if self.tracing {
+                opentelemetry::global::shutdown_tracer_provider();
+            }
+            if let Some(provider) = self.meter_provider.take() {
+                if let Err(e) = provider.shutdown() {
+                    eprintln!("Failed to flush OpenTelemetry metrics: {}", e);
+                }
+            }
+        }
+    }
+}
+
+/// Install the global subscriber; call once, early, inside the runtime
+pub fn init(config: &HafizConfig) -> Result<Telemetry> {
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(&config.logging.level));
+    let fmt_layer = match config.logging.format.as_str() {
+        "json" => tracing_subscriber::fmt::layer().json().boxed(),
+        _ => tracing_subscriber::fmt::layer().boxed(),
+    };
+    let registry = tracing_subscriber::registry().with(filter).with(fmt_layer);
+
+    #[cfg(feature = "otel")]
+    let (registry, telemetry) = {
+        let mut telemetry = Telemetry::default();
+        let layer = if config.telemetry.enabled {
+            let resource = Resource::new(vec![KeyValue::new(
+                "service.name",
+                config.telemetry.service_name.clone(),
+            )]);
+            let tracer = tracer(&config.telemetry, resource.clone())?;
+            telemetry.tracing = true;
+            if config.telemetry.export_metrics {
+                telemetry.meter_provider = Some(meter_provider(&config.telemetry, resource)?);
+            }
+            Some(tracing_opentelemetry::layer().with_tracer(tracer))
+        } else {
+            None
+        };
+        (registry.with(layer), telemetry)
+    };
+    #[cfg(not(feature = "otel"))]
+    let telemetry = Telemetry::default();
+
+    registry
+        .try_init()
+        .map_err(|e| Error::InternalError(format!("Failed to install tracing subscriber: {}", e)))?;
+
+    if config.telemetry.enabled {
+        if cfg!(feature = "otel") {
+            tracing::info!(
+                "Exporting telemetry to {} as {}",
+                config.telemetry.endpoint,
+                config.telemetry.service_name
+            );
+        } else {
+            tracing::warn!("Telemetry export is enabled but this build lacks the otel feature");
+        }
+    }
+
+    Ok(telemetry)
+}
+
+#[cfg(feature = "otel")]
+fn tracer(config: &TelemetryConfig, resource: Resource) -> Result<opentelemetry_sdk::trace::Tracer> {
+    let ratio = config.sampling_ratio.clamp(0.0, 1.0);
+    opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&config.endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(ratio))))
+                .with_resource(resource),
+        )
+        .install_batch(runtime::Tokio)
+        .map_err(|e| Error::InternalError(format!("Failed to set up OTLP trace export: {}", e)))
+}
+
+#[cfg(feature = "otel")]
+fn meter_provider(config: &TelemetryConfig, resource: Resource) -> Result<SdkMeterProvider> {
+    opentelemetry_otlp::new_pipeline()
+        .metrics(runtime::Tokio)
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&config.endpoint),
+        )
+        .with_resource(resource)
+        .with_period(std::time::Duration::from_secs(config.metrics_interval_seconds.max(1)))
+        .build()
+        .map_err(|e| Error::InternalError(format!("Failed to set up OTLP metrics export: {}", e)))
+}
@@ -0,0 +1,379 @@
+//! Access-based tiering
+//!
+//! Object reads are sampled (`storage.tiering.sample_rate`) into per-object
+//! access statistics, kept in memory and written to the metadata database
+//! in batches. Buckets opt in with an `archive_after_days` setting: a pass
+//! moves the data of their objects that were neither read nor written for
+//! that long to the archive backend. Their storage class does not change
+//! and reads keep working, served from the archive; the next flush that
+//! sees a read of a tiered object moves its data back.
+//!
+//! Objects in archive storage classes are left to RestoreObject.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use hafiz_core::config::TieringConfig;
+use hafiz_core::types::{BucketTiering, ObjectInternal};
+use hafiz_core::Error;
+use hafiz_metadata::repository::ObjectAccess;
+use parking_lot::{Mutex, RwLock};
+use serde::Serialize;
+use tracing::{debug, info, warn};
+
+use crate::routes::storage_key;
+use crate::server::AppState;
+
+/// Candidates listed per metadata page
+const LIST_PAGE_SIZE: i32 = 1000;
+
+/// Reads of one object version since the last flush
+type PendingReads = HashMap<(String, String, String), (DateTime<Utc>, i64)>;
+
+/// Outcome of one tiering pass
+#[derive(Debug, Clone, Serialize)]
+pub struct TieringReport {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub buckets_scanned: u64,
+    pub objects_archived: u64,
+    pub bytes_archived: u64,
+    /// Access statistics of deleted objects dropped
+    pub stale_entries_pruned: u64,
+    pub errors: Vec<String>,
+}
+
+impl TieringReport {
+    fn new() -> Self {
+        Self {
+            started_at: Utc::now(),
+            finished_at: None,
+            buckets_scanned: 0,
+            objects_archived: 0,
+            bytes_archived: 0,
+            stale_entries_pruned: 0,
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// Tiering state as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct TieringStatus {
+    /// Whether reads are tracked; needs an archive backend
+    pub enabled: bool,
+    pub sample_rate: f64,
+    pub scan_interval_secs: u64,
+    pub running: bool,
+    /// Objects moved back from the archive since startup
+    pub objects_promoted: u64,
+    pub last_pass: Option<TieringReport>,
+}
+
+/// Read sampling and the tiering passes
+pub struct Tiering {
+    config: TieringConfig,
+    enabled: bool,
+    /// One read in this many is recorded
+    sample_every: u64,
+    reads: AtomicU64,
+    pending: Mutex<PendingReads>,
+    promoted: AtomicU64,
+    running: AtomicBool,
+    last_pass: RwLock<Option<TieringReport>>,
+}
+
+impl Tiering {
+    /// Tiering is only enabled with an archive backend to move data to
+    pub fn new(config: TieringConfig, enabled: bool) -> Self {
+        let sample_every = (1.0 / config.sample_rate).round().max(1.0) as u64;
+        Self {
+            config,
+            enabled,
+            sample_every,
+            reads: AtomicU64::new(0),
+            pending: Mutex::new(HashMap::new()),
+            promoted: AtomicU64::new(0),
+            running: AtomicBool::new(false),
+            last_pass: RwLock::new(None),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn status(&self) -> TieringStatus {
+        TieringStatus {
+            enabled: self.enabled,
+            sample_rate: self.config.sample_rate,
+            scan_interval_secs: self.config.scan_interval_secs,
+            running: self.running.load(Ordering::SeqCst),
+            objects_promoted: self.promoted.load(Ordering::Relaxed),
+            last_pass: self.last_pass.read().clone(),
+        }
+    }
+
+    /// Note a read of `object`
+    pub fn record(&self, object: &ObjectInternal) {
+        if !self.enabled || self.reads.fetch_add(1, Ordering::Relaxed) % self.sample_every != 0 {
+            return;
+        }
+        let mut pending = self.pending.lock();
+        let entry = pending
+            .entry((
+                object.bucket.clone(),
+                object.key.clone(),
+                object.version_id.clone(),
+            ))
+            .or_insert((Utc::now(), 0));
+        entry.0 = Utc::now();
+        // Each sampled read stands for the reads skipped before it
+        entry.1 += self.sample_every as i64;
+    }
+
+    /// Take the reads recorded since the last call
+    fn drain(&self) -> Vec<ObjectAccess> {
+        std::mem::take(&mut *self.pending.lock())
+            .into_iter()
+            .map(|((bucket, key, version_id), (last_accessed, count))| ObjectAccess {
+                bucket,
+                key,
+                version_id,
+                last_accessed,
+                count,
+            })
+            .collect()
+    }
+
+    /// Write recorded reads to the metadata database and move tiered
+    /// objects that were read back from the archive
+    pub async fn flush(&self, state: &AppState) {
+        let accesses = self.drain();
+        if accesses.is_empty() {
+            return;
+        }
+        let tiered = match state.metadata.record_object_accesses(&accesses).await {
+            Ok(tiered) => tiered,
+            Err(e) => {
+                warn!("Failed to record {} object accesses: {}", accesses.len(), e);
+                return;
+            }
+        };
+
+        for access in tiered {
+            match promote(state, &access).await {
+                Ok(()) => {
+                    self.promoted.fetch_add(1, Ordering::Relaxed);
+                    metrics::counter!("hafiz_tiering_promotions_total").increment(1);
+                }
+                Err(e) => warn!(
+                    "Failed to move {}/{} back from the archive: {}",
+                    access.bucket, access.key, e
+                ),
+            }
+        }
+    }
+
+    /// Archive the cold objects of every bucket that opted in
+    ///
+    /// Returns `None` without doing anything if a pass is already running.
+    pub async fn run(&self, state: &AppState) -> Option<TieringReport> {
+        if self.running.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+
+        let mut report = TieringReport::new();
+        match state.metadata.list_bucket_tiering().await {
+            Ok(buckets) => {
+                for (bucket, tiering) in buckets {
+                    tier_bucket(state, &bucket, tiering, &mut report).await;
+                    report.buckets_scanned += 1;
+                }
+            }
+            Err(e) => report.errors.push(e.to_string()),
+        }
+        match state.metadata.prune_object_access().await {
+            Ok(pruned) => report.stale_entries_pruned = pruned,
+            Err(e) => report.errors.push(e.to_string()),
+        }
+        report.finished_at = Some(Utc::now());
+
+        info!(
+            "Tiering pass: {} buckets, {} objects ({} bytes) archived, {} errors",
+            report.buckets_scanned,
+            report.objects_archived,
+            report.bytes_archived,
+            report.errors.len()
+        );
+        *self.last_pass.write() = Some(report.clone());
+        self.running.store(false, Ordering::SeqCst);
+        Some(report)
+    }
+
+    /// Flush recorded reads and run passes on their intervals
+    pub fn start(self: &Arc<Self>, state: AppState) {
+        if !self.enabled {
+            return;
+        }
+
+        let tiering = Arc::clone(self);
+        let flush_state = state.clone();
+        let flush_interval = Duration::from_secs(self.config.flush_interval_secs);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(flush_interval);
+            loop {
+                ticker.tick().await;
+                tiering.flush(&flush_state).await;
+            }
+        });
+
+        if self.config.scan_interval_secs == 0 {
+            return;
+        }
+        let tiering = Arc::clone(self);
+        let interval = Duration::from_secs(self.config.scan_interval_secs);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // Skip the immediate first tick so startup is not slowed down
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                if tiering.run(&state).await.is_none() {
+                    debug!("Skipping scheduled tiering pass; a pass is in progress");
+                }
+            }
+        });
+
+        info!("Tiering passes scheduled every {:?}", interval);
+    }
+}
+
+/// Archive the objects of `bucket` that went unread for its number of days
+async fn tier_bucket(
+    state: &AppState,
+    bucket: &str,
+    tiering: BucketTiering,
+    report: &mut TieringReport,
+) {
+    let cutoff = Utc::now() - chrono::Duration::days(tiering.archive_after_days as i64);
+    let mut after: Option<(String, String)> = None;
+
+    loop {
+        let page = match state
+            .metadata
+            .list_cold_objects(
+                bucket,
+                cutoff,
+                after.as_ref().map(|(k, v)| (k.as_str(), v.as_str())),
+                LIST_PAGE_SIZE,
+            )
+            .await
+        {
+            Ok(page) => page,
+            Err(e) => {
+                report.errors.push(format!("{}: {}", bucket, e));
+                return;
+            }
+        };
+        let full_page = page.len() == LIST_PAGE_SIZE as usize;
+
+        for (key, version_id) in page {
+            match archive(state, bucket, &key, &version_id).await {
+                Ok(Some(size)) => {
+                    report.objects_archived += 1;
+                    report.bytes_archived += size.max(0) as u64;
+                    metrics::counter!("hafiz_tiering_archived_bytes_total")
+                        .increment(size.max(0) as u64);
+                }
+                Ok(None) => {}
+                Err(e) => report.errors.push(format!("{}/{}: {}", bucket, key, e)),
+            }
+            after = Some((key, version_id));
+        }
+
+        if !full_page {
+            break;
+        }
+    }
+}
+
+/// Move the data of one object version to the archive; returns its size,
+/// or `None` if the version is gone
+async fn archive(
+    state: &AppState,
+    bucket: &str,
+    key: &str,
+    version_id: &str,
+) -> Result<Option<i64>, Error> {
+    let Some(object) = state
+        .metadata
+        .get_object_version(bucket, key, Some(version_id))
+        .await?
+    else {
+        return Ok(None);
+    };
+    state
+        .storage_router
+        .archive(bucket, &storage_key(&object.key, &object.version_id))
+        .await?;
+    state
+        .metadata
+        .set_object_tiered(bucket, key, version_id, true)
+        .await?;
+    debug!("Tiered {}/{} ({})", bucket, key, version_id);
+    Ok(Some(object.size))
+}
+
+/// Move a tiered object version that was read back from the archive
+async fn promote(state: &AppState, access: &ObjectAccess) -> Result<(), Error> {
+    let object = state
+        .metadata
+        .get_object_version(&access.bucket, &access.key, Some(&access.version_id))
+        .await?;
+    // Objects since put in an archive class stay there
+    if let Some(object) = object.filter(|o| !o.is_archived()) {
+        match state
+            .storage_router
+            .promote(&object.bucket, &storage_key(&object.key, &object.version_id))
+            .await
+        {
+            Ok(()) | Err(Error::NoSuchKey) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    state
+        .metadata
+        .set_object_tiered(&access.bucket, &access.key, &access.version_id, false)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampled_reads() {
+        let config = TieringConfig {
+            sample_rate: 0.25,
+            ..Default::default()
+        };
+        let tiering = Tiering::new(config, true);
+        let object = ObjectInternal::new("b".into(), "k".into(), 1, "e".into(), "t".into());
+        for _ in 0..8 {
+            tiering.record(&object);
+        }
+
+        let accesses = tiering.drain();
+        assert_eq!(accesses.len(), 1);
+        assert_eq!(accesses[0].count, 8);
+        assert!(tiering.drain().is_empty());
+
+        let disabled = Tiering::new(TieringConfig::default(), false);
+        disabled.record(&object);
+        assert!(disabled.drain().is_empty());
+    }
+}
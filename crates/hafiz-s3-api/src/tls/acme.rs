@@ -0,0 +1,503 @@
+//! Certificates from an ACME CA such as Let's Encrypt
+//!
+//! [`AcmeClient`] orders a certificate for the configured domains (RFC 8555)
+//! and answers the CA's `tls-alpn-01` challenges (RFC 8737) on the HTTPS
+//! listener itself, through the [`CertStore`]. The certificate is checked
+//! twice a day and ordered again once it is within `renew_before_days` of
+//! expiry. The account key, certificate and private key are kept in
+//! `cache_dir`, so a restart reuses them instead of ordering again.
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use hafiz_core::config::AcmeConfig;
+use hafiz_core::{Error, Result};
+use ring::rand::SystemRandom;
+use ring::signature::{EcdsaKeyPair, KeyPair as _, ECDSA_P256_SHA256_FIXED_SIGNING};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer};
+use tracing::{debug, error, info};
+
+use super::CertStore;
+
+/// How often the certificate's expiry is checked
+const CHECK_INTERVAL: Duration = Duration::from_secs(12 * 3600);
+/// Wait before trying again after a failed order
+const RETRY_INTERVAL: Duration = Duration::from_secs(3600);
+/// Polling of authorizations and orders
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const POLL_ATTEMPTS: u32 = 60;
+
+fn acme_error(message: impl std::fmt::Display) -> Error {
+    Error::InternalError(format!("ACME: {}", message))
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Directory {
+    new_nonce: String,
+    new_account: String,
+    new_order: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Order {
+    status: String,
+    authorizations: Vec<String>,
+    finalize: String,
+    certificate: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Authorization {
+    status: String,
+    identifier: Identifier,
+    challenges: Vec<Challenge>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Identifier {
+    value: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Challenge {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    token: Option<String>,
+    error: Option<Value>,
+}
+
+/// Keeps the certificate in a [`CertStore`] issued and current
+pub struct AcmeClient {
+    config: AcmeConfig,
+    certs: Arc<CertStore>,
+    http: reqwest::Client,
+}
+
+impl AcmeClient {
+    pub fn new(config: AcmeConfig, certs: Arc<CertStore>) -> Result<Self> {
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(acme_error)?;
+        Ok(Self { config, certs, http })
+    }
+
+    /// Obtain a certificate now if needed, then keep renewing it
+    ///
+    /// Must run once the HTTPS listener accepts connections, since the CA
+    /// validates the domains through it.
+    pub fn spawn(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                let wait = match self.renew_if_needed().await {
+                    Ok(_) => CHECK_INTERVAL,
+                    Err(e) => {
+                        error!("Certificate renewal failed: {}", e);
+                        RETRY_INTERVAL
+                    }
+                };
+                tokio::time::sleep(wait).await;
+            }
+        });
+    }
+
+    /// Order a certificate if there is none or it expires soon
+    ///
+    /// Returns whether a new certificate was installed.
+    pub async fn renew_if_needed(&self) -> Result<bool> {
+        if let Some(expires) = certificate_expiry(&self.config.cert_file())? {
+            let remaining = expires - chrono::Utc::now().timestamp();
+            if remaining > i64::from(self.config.renew_before_days) * 86400 {
+                debug!("ACME certificate valid for {} more days", remaining / 86400);
+                return Ok(false);
+            }
+        }
+
+        info!("Ordering certificate for {}", self.config.domains.join(", "));
+        self.order().await?;
+        info!("Installed new certificate for {}", self.config.domains.join(", "));
+        Ok(true)
+    }
+
+    async fn order(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.config.cache_dir)
+            .map_err(|e| acme_error(format!("Failed to create {:?}: {}", self.config.cache_dir, e)))?;
+
+        let directory: Directory = self
+            .http
+            .get(&self.config.directory_url)
+            .send()
+            .await
+            .map_err(acme_error)?
+            .json()
+            .await
+            .map_err(acme_error)?;
+        let account = Account::load_or_create(&self.config.cache_dir.join("account.key"))?;
+        let mut session = Session {
+            http: &self.http,
+            directory,
+            account,
+            kid: None,
+            nonce: None,
+        };
+        session.register(&self.config).await?;
+
+        let identifiers: Vec<Value> = self
+            .config
+            .domains
+            .iter()
+            .map(|d| json!({ "type": "dns", "value": d }))
+            .collect();
+        let new_order = session.directory.new_order.clone();
+        let response = session
+            .post(&new_order, Some(json!({ "identifiers": identifiers })))
+            .await?;
+        let order_url = location(&response)?;
+        let order: Order = response.json().await.map_err(acme_error)?;
+
+        for authorization in &order.authorizations {
+            self.authorize(&mut session, authorization).await?;
+        }
+
+        // The certificate key is new on every order
+        let key_pair = rcgen::KeyPair::generate().map_err(acme_error)?;
+        let csr = rcgen::CertificateParams::new(self.config.domains.clone())
+            .and_then(|params| params.serialize_request(&key_pair))
+            .map_err(acme_error)?;
+        session
+            .post(&order.finalize, Some(json!({ "csr": URL_SAFE_NO_PAD.encode(csr.der()) })))
+            .await?;
+
+        let order = session
+            .poll(&order_url, |order: &Order| match order.status.as_str() {
+                "valid" => Some(Ok(())),
+                "invalid" => Some(Err(acme_error("Order was rejected"))),
+                _ => None,
+            })
+            .await?;
+        let certificate_url = order
+            .certificate
+            .ok_or_else(|| acme_error("Valid order without a certificate"))?;
+        let chain = session
+            .post(&certificate_url, None)
+            .await?
+            .text()
+            .await
+            .map_err(acme_error)?;
+
+        write_private(&self.config.key_file(), key_pair.serialize_pem().as_bytes())?;
+        std::fs::write(self.config.cert_file(), chain)
+            .map_err(|e| acme_error(format!("Failed to write certificate: {}", e)))?;
+        self.certs.reload()
+    }
+
+    /// Complete the `tls-alpn-01` challenge of one authorization
+    async fn authorize(&self, session: &mut Session<'_>, url: &str) -> Result<()> {
+        let authorization: Authorization = session.post(url, None).await?.json().await.map_err(acme_error)?;
+        if authorization.status == "valid" {
+            return Ok(());
+        }
+
+        let domain = authorization.identifier.value;
+        let challenge = authorization
+            .challenges
+            .iter()
+            .find(|c| c.kind == "tls-alpn-01")
+            .ok_or_else(|| acme_error(format!("No tls-alpn-01 challenge offered for {}", domain)))?;
+        let token = challenge
+            .token
+            .as_deref()
+            .ok_or_else(|| acme_error("Challenge without a token"))?;
+
+        let key_authorization = format!("{}.{}", token, session.account.thumbprint());
+        let digest = ring::digest::digest(&ring::digest::SHA256, key_authorization.as_bytes());
+        self.certs
+            .set_challenge(&domain, self.challenge_certificate(&domain, digest.as_ref())?);
+
+        let result = async {
+            session.post(&challenge.url, Some(json!({}))).await?;
+            session
+                .poll(url, |authorization: &Authorization| match authorization.status.as_str() {
+                    "valid" => Some(Ok(())),
+                    "invalid" => {
+                        let detail = authorization
+                            .challenges
+                            .iter()
+                            .find_map(|c| c.error.as_ref())
+                            .and_then(|e| e["detail"].as_str())
+                            .unwrap_or("no detail")
+                            .to_string();
+                        Some(Err(acme_error(format!("Validation of {} failed: {}", domain, detail))))
+                    }
+                    _ => None,
+                })
+                .await
+        }
+        .await;
+
+        self.certs.clear_challenge(&domain);
+        result.map(|_| ())
+    }
+
+    /// Self-signed certificate carrying the `acmeIdentifier` extension
+    fn challenge_certificate(
+        &self,
+        domain: &str,
+        digest: &[u8],
+    ) -> Result<Arc<tokio_rustls::rustls::sign::CertifiedKey>> {
+        let key_pair = rcgen::KeyPair::generate().map_err(acme_error)?;
+        let mut params = rcgen::CertificateParams::new(vec![domain.to_string()]).map_err(acme_error)?;
+        params.custom_extensions = vec![rcgen::CustomExtension::new_acme_identifier(digest)];
+        let cert = params.self_signed(&key_pair).map_err(acme_error)?;
+
+        let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(key_pair.serialize_der()));
+        self.certs
+            .certified_key(vec![CertificateDer::from(cert.der().to_vec())], key)
+    }
+}
+
+/// ACME account key (ECDSA P-256)
+struct Account {
+    key: EcdsaKeyPair,
+    rng: SystemRandom,
+}
+
+impl Account {
+    fn load_or_create(path: &Path) -> Result<Self> {
+        let rng = SystemRandom::new();
+        let pkcs8 = match std::fs::read(path) {
+            Ok(pkcs8) => pkcs8,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let document = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &rng)
+                    .map_err(|_| acme_error("Failed to generate account key"))?;
+                write_private(path, document.as_ref())?;
+                info!("Created ACME account key {:?}", path);
+                document.as_ref().to_vec()
+            }
+            Err(e) => return Err(acme_error(format!("Failed to read {:?}: {}", path, e))),
+        };
+        let key = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_FIXED_SIGNING, &pkcs8, &rng)
+            .map_err(|e| acme_error(format!("Invalid account key {:?}: {}", path, e)))?;
+        Ok(Self { key, rng })
+    }
+
+    /// Public key as a JWK, members in the order RFC 7638 hashes them
+    fn jwk(&self) -> Value {
+        // Uncompressed point: 0x04 || x || y
+        let point = self.key.public_key().as_ref();
+        json!({
+            "crv": "P-256",
+            "kty": "EC",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        })
+    }
+
+    /// RFC 7638 thumbprint, used in key authorizations
+    fn thumbprint(&self) -> String {
+        let digest = ring::digest::digest(&ring::digest::SHA256, self.jwk().to_string().as_bytes());
+        URL_SAFE_NO_PAD.encode(digest.as_ref())
+    }
+
+    /// Flattened JWS of `payload`; `None` is a POST-as-GET
+    fn sign(&self, protected: &Value, payload: Option<&Value>) -> Result<Value> {
+        let protected = URL_SAFE_NO_PAD.encode(protected.to_string());
+        let payload = payload
+            .map(|p| URL_SAFE_NO_PAD.encode(p.to_string()))
+            .unwrap_or_default();
+        let signature = self
+            .key
+            .sign(&self.rng, format!("{}.{}", protected, payload).as_bytes())
+            .map_err(|_| acme_error("Failed to sign request"))?;
+        Ok(json!({
+            "protected": protected,
+            "payload": payload,
+            "signature": URL_SAFE_NO_PAD.encode(signature.as_ref()),
+        }))
+    }
+}
+
+/// Signed requests under one account, with the nonce chain
+struct Session<'a> {
+    http: &'a reqwest::Client,
+    directory: Directory,
+    account: Account,
+    /// Account URL, once registered
+    kid: Option<String>,
+    nonce: Option<String>,
+}
+
+impl Session<'_> {
+    async fn register(&mut self, config: &AcmeConfig) -> Result<()> {
+        let mut payload = json!({ "termsOfServiceAgreed": config.accept_terms });
+        if let Some(email) = &config.contact_email {
+            payload["contact"] = json!([format!("mailto:{}", email)]);
+        }
+        let new_account = self.directory.new_account.clone();
+        let response = self.post(&new_account, Some(payload)).await?;
+        self.kid = Some(location(&response)?);
+        Ok(())
+    }
+
+    async fn new_nonce(&self) -> Result<String> {
+        let response = self
+            .http
+            .head(&self.directory.new_nonce)
+            .send()
+            .await
+            .map_err(acme_error)?;
+        replay_nonce(&response).ok_or_else(|| acme_error("No nonce from the CA"))
+    }
+
+    async fn post(&mut self, url: &str, payload: Option<Value>) -> Result<reqwest::Response> {
+        // A stale nonce is retried once with a fresh one
+        for attempt in 0..2 {
+            let nonce = match self.nonce.take() {
+                Some(nonce) => nonce,
+                None => self.new_nonce().await?,
+            };
+            let mut protected = json!({ "alg": "ES256", "nonce": nonce, "url": url });
+            match &self.kid {
+                Some(kid) => protected["kid"] = json!(kid),
+                None => protected["jwk"] = self.account.jwk(),
+            }
+            let body = self.account.sign(&protected, payload.as_ref())?;
+
+            let response = self
+                .http
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/jose+json")
+                .body(body.to_string())
+                .send()
+                .await
+                .map_err(acme_error)?;
+            self.nonce = replay_nonce(&response);
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response);
+            }
+            let problem: Value = response.json().await.unwrap_or_default();
+            if attempt == 0 && problem["type"] == "urn:ietf:params:acme:error:badNonce" {
+                continue;
+            }
+            return Err(acme_error(format!(
+                "{} returned {}: {}",
+                url,
+                status,
+                problem["detail"].as_str().unwrap_or("no detail")
+            )));
+        }
+        unreachable!("the second attempt always returns")
+    }
+
+    /// POST-as-GET `url` until `done` decides on the resource
+    async fn poll<T, F>(&mut self, url: &str, done: F) -> Result<T>
+    where
+        T: serde::de::DeserializeOwned,
+        F: Fn(&T) -> Option<Result<()>>,
+    {
+        for _ in 0..POLL_ATTEMPTS {
+            let resource: T = self.post(url, None).await?.json().await.map_err(acme_error)?;
+            match done(&resource) {
+                Some(Ok(())) => return Ok(resource),
+                Some(Err(e)) => return Err(e),
+                None => tokio::time::sleep(POLL_INTERVAL).await,
+            }
+        }
+        Err(acme_error(format!("Timed out waiting for {}", url)))
+    }
+}
+
+fn replay_nonce(response: &reqwest::Response) -> Option<String> {
+    response
+        .headers()
+        .get("replay-nonce")
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+}
+
+fn location(response: &reqwest::Response) -> Result<String> {
+    response
+        .headers()
+        .get(reqwest::header::LOCATION)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .ok_or_else(|| acme_error(format!("No Location header from {}", response.url())))
+}
+
+/// Expiry (Unix seconds) of the first certificate in a PEM file
+fn certificate_expiry(path: &Path) -> Result<Option<i64>> {
+    let pem = match std::fs::read(path) {
+        Ok(pem) => pem,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(acme_error(format!("Failed to read {:?}: {}", path, e))),
+    };
+    let (_, pem) = x509_parser::pem::parse_x509_pem(&pem)
+        .map_err(|e| acme_error(format!("Failed to parse {:?}: {}", path, e)))?;
+    let cert = pem
+        .parse_x509()
+        .map_err(|e| acme_error(format!("Failed to parse {:?}: {}", path, e)))?;
+    Ok(Some(cert.validity().not_after.timestamp()))
+}
+
+/// Write a key readable by the owner only
+fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    std::fs::write(path, contents)
+        .map_err(|e| acme_error(format!("Failed to write {:?}: {}", path, e)))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| acme_error(format!("Failed to set permissions of {:?}: {}", path, e)))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::{UnparsedPublicKey, ECDSA_P256_SHA256_FIXED};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_account_key_persists_and_signs() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("account.key");
+        let account = Account::load_or_create(&path).unwrap();
+        let reloaded = Account::load_or_create(&path).unwrap();
+        assert_eq!(account.thumbprint(), reloaded.thumbprint());
+        assert_eq!(account.thumbprint().len(), 43);
+
+        let jws = account
+            .sign(&json!({ "alg": "ES256", "url": "https://ca/new" }), Some(&json!({})))
+            .unwrap();
+        let signing_input = format!(
+            "{}.{}",
+            jws["protected"].as_str().unwrap(),
+            jws["payload"].as_str().unwrap()
+        );
+        let signature = URL_SAFE_NO_PAD.decode(jws["signature"].as_str().unwrap()).unwrap();
+        UnparsedPublicKey::new(&ECDSA_P256_SHA256_FIXED, account.key.public_key().as_ref())
+            .verify(signing_input.as_bytes(), &signature)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_certificate_expiry() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("cert.pem");
+        assert_eq!(certificate_expiry(&path).unwrap(), None);
+
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let mut params = rcgen::CertificateParams::new(vec!["example.com".to_string()]).unwrap();
+        params.not_after = rcgen::date_time_ymd(2030, 1, 1);
+        std::fs::write(&path, params.self_signed(&key_pair).unwrap().pem()).unwrap();
+        assert_eq!(certificate_expiry(&path).unwrap(), Some(1893456000));
+    }
+}
@@ -0,0 +1,142 @@
+//! Client certificate identities
+//!
+//! A client that presents a certificate signed by `tls.client_ca_file`
+//! can authenticate as a Hafiz user without signing its requests, when one
+//! of `tls.client_cert_users` matches the certificate's subject common name
+//! or one of its DNS, URI or email subject alternative names. The identity
+//! is worked out once per connection and attached to each of its requests;
+//! `middleware::client_cert_auth` then checks that the user exists.
+
+use hafiz_core::config::ClientCertMapping;
+use tokio_rustls::rustls::pki_types::CertificateDer;
+use tracing::debug;
+use x509_parser::prelude::*;
+
+/// Verified certificate a connection's client presented
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    /// Subject distinguished name
+    pub subject: String,
+    /// Access key of the user the certificate maps to, if any
+    pub access_key: Option<String>,
+}
+
+/// Names a certificate can be matched by
+#[derive(Debug, Default)]
+struct CertNames {
+    subject: String,
+    common_name: Option<String>,
+    sans: Vec<String>,
+}
+
+impl CertNames {
+    fn parse(der: &CertificateDer<'_>) -> Option<Self> {
+        let (_, cert) = X509Certificate::from_der(der.as_ref()).ok()?;
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(str::to_string);
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(ext)) => ext
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(name)
+                    | GeneralName::URI(name)
+                    | GeneralName::RFC822Name(name) => Some(name.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        Some(Self {
+            subject: cert.subject().to_string(),
+            common_name,
+            sans,
+        })
+    }
+
+    fn matches(&self, mapping: &ClientCertMapping) -> bool {
+        match (&mapping.common_name, &mapping.san) {
+            (Some(cn), _) => self.common_name.as_deref() == Some(cn.as_str()),
+            (None, Some(san)) => self.sans.iter().any(|s| s == san),
+            (None, None) => false,
+        }
+    }
+}
+
+/// Maps client certificates to users by `tls.client_cert_users`
+#[derive(Debug, Clone, Default)]
+pub struct ClientCertMapper {
+    mappings: Vec<ClientCertMapping>,
+}
+
+impl ClientCertMapper {
+    pub fn new(mappings: Vec<ClientCertMapping>) -> Self {
+        Self { mappings }
+    }
+
+    /// Identity of the leaf certificate a client presented; the first
+    /// mapping that matches picks the user
+    pub fn identify(&self, cert: &CertificateDer<'_>) -> Option<ClientCertificate> {
+        let Some(names) = CertNames::parse(cert) else {
+            debug!("Could not parse a verified client certificate");
+            return None;
+        };
+        let access_key = self
+            .mappings
+            .iter()
+            .find(|mapping| names.matches(mapping))
+            .map(|mapping| mapping.access_key.clone());
+        Some(ClientCertificate {
+            subject: names.subject,
+            access_key,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair, SanType};
+
+    fn certificate(common_name: &str, dns: &str) -> CertificateDer<'static> {
+        let mut params = CertificateParams::default();
+        let mut dn = DistinguishedName::new();
+        dn.push(DnType::CommonName, common_name);
+        params.distinguished_name = dn;
+        params.subject_alt_names = vec![SanType::DnsName(dns.try_into().unwrap())];
+        let key = KeyPair::generate().unwrap();
+        params.self_signed(&key).unwrap().der().clone()
+    }
+
+    fn mapping(common_name: Option<&str>, san: Option<&str>, access_key: &str) -> ClientCertMapping {
+        ClientCertMapping {
+            common_name: common_name.map(String::from),
+            san: san.map(String::from),
+            access_key: access_key.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_certificates_map_by_common_name_or_san() {
+        let mapper = ClientCertMapper::new(vec![
+            mapping(Some("backup-agent"), None, "AKIABACKUP"),
+            mapping(None, Some("ingest.internal"), "AKIAINGEST"),
+        ]);
+
+        let by_cn = mapper.identify(&certificate("backup-agent", "backup.internal")).unwrap();
+        assert_eq!(by_cn.access_key.as_deref(), Some("AKIABACKUP"));
+        assert!(by_cn.subject.contains("backup-agent"));
+
+        let by_san = mapper.identify(&certificate("worker-7", "ingest.internal")).unwrap();
+        assert_eq!(by_san.access_key.as_deref(), Some("AKIAINGEST"));
+
+        // Verified but unmapped: the client still has to sign its requests
+        let unmapped = mapper.identify(&certificate("someone", "else.internal")).unwrap();
+        assert_eq!(unmapped.access_key, None);
+    }
+}
@@ -2,10 +2,21 @@
 //!
 //! Provides secure HTTPS connections with support for:
 //! - TLS 1.2 and 1.3
-//! - mTLS (mutual TLS) for client certificate verification
+//! - mTLS (mutual TLS) for client certificate verification, and
+//!   authentication as the user a client certificate maps to
 //! - HSTS headers
+//! - Certificate reload when the PEM files change
+//! - Certificates from an ACME CA such as Let's Encrypt
 //! - Self-signed certificate generation for development
 
+mod acme;
+mod client_cert;
+mod store;
+
+pub use acme::AcmeClient;
+pub use client_cert::{ClientCertMapper, ClientCertificate};
+pub use store::CertStore;
+
 use hafiz_core::config::{TlsConfig, TlsVersion};
 use hafiz_core::{Error, Result};
 use std::fs::File;
@@ -14,46 +25,79 @@ use std::path::Path;
 use std::sync::Arc;
 use tokio_rustls::rustls::{
     self,
+    crypto::CryptoProvider,
     pki_types::{CertificateDer, PrivateKeyDer},
     server::WebPkiClientVerifier,
     RootCertStore,
 };
 use tracing::{info, warn};
 
+/// ALPN protocol of ACME `tls-alpn-01` validation (RFC 8737)
+const ACME_TLS_ALPN: &[u8] = b"acme-tls/1";
+
+/// Crypto provider installed for the process, or the rustls default
+fn crypto_provider() -> Arc<CryptoProvider> {
+    CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()))
+}
+
 /// TLS Acceptor wrapper for async TLS connections
 pub struct TlsAcceptor {
     acceptor: tokio_rustls::TlsAcceptor,
+    certs: Arc<CertStore>,
+    client_certs: ClientCertMapper,
     hsts_enabled: bool,
     hsts_max_age: u64,
 }
 
 impl TlsAcceptor {
     /// Create a new TLS acceptor from configuration
-    pub fn from_config(config: &TlsConfig) -> Result<Self> {
+    ///
+    /// `http2` offers HTTP/2 through ALPN next to HTTP/1.1.
+    pub fn from_config(config: &TlsConfig, http2: bool) -> Result<Self> {
         config.validate()?;
 
-        let cert_file = config.cert_file.as_ref().ok_or_else(|| {
-            Error::InvalidArgument("Certificate file not specified".into())
-        })?;
-        let key_file = config.key_file.as_ref().ok_or_else(|| {
-            Error::InvalidArgument("Key file not specified".into())
-        })?;
-
-        // Load certificates
-        let certs = load_certs(cert_file)?;
-        info!("Loaded {} certificate(s)", certs.len());
+        let provider = crypto_provider();
+
+        // With ACME the certificate lives in the cache and may not exist yet
+        let certs = if config.acme.enabled {
+            let certs = CertStore::new(
+                provider.clone(),
+                config.acme.cert_file(),
+                config.acme.key_file(),
+            );
+            if config.acme.cert_file().exists() {
+                certs.reload()?;
+                info!("Loaded ACME certificate from {:?}", config.acme.cache_dir);
+            }
+            certs
+        } else {
+            let cert_file = config.cert_file.as_ref().ok_or_else(|| {
+                Error::InvalidArgument("Certificate file not specified".into())
+            })?;
+            let key_file = config.key_file.as_ref().ok_or_else(|| {
+                Error::InvalidArgument("Key file not specified".into())
+            })?;
+            let certs = CertStore::new(provider.clone(), cert_file.clone(), key_file.clone());
+            certs.reload()?;
+            info!("Loaded certificate {:?} and private key", cert_file);
+            certs
+        };
+        let certs = Arc::new(certs);
 
-        // Load private key
-        let key = load_private_key(key_file)?;
-        info!("Loaded private key");
+        // Set minimum TLS version
+        let versions: &[&'static rustls::SupportedProtocolVersion] = match config.min_version {
+            TlsVersion::Tls12 => &[&rustls::version::TLS13, &rustls::version::TLS12],
+            TlsVersion::Tls13 => &[&rustls::version::TLS13],
+        };
+        let builder = rustls::ServerConfig::builder_with_provider(provider.clone())
+            .with_protocol_versions(versions)
+            .map_err(|e| Error::InternalError(format!("TLS config error: {}", e)))?;
 
         // Build server config
-        let mut server_config = if config.require_client_cert {
-            // mTLS: require client certificates
-            let client_ca_file = config.client_ca_file.as_ref().ok_or_else(|| {
-                Error::InvalidArgument("Client CA file required for mTLS".into())
-            })?;
-
+        let mut server_config = if let Some(client_ca_file) = &config.client_ca_file {
+            // mTLS: verify client certificates, required or offered
             let client_roots = load_root_certs(client_ca_file)?;
             info!("Loaded {} client CA certificate(s)", client_roots.len());
 
@@ -64,38 +108,38 @@ impl TlsAcceptor {
                 })?;
             }
 
-            let client_verifier = WebPkiClientVerifier::builder(Arc::new(root_store))
-                .build()
-                .map_err(|e| {
-                    Error::InternalError(format!("Failed to build client verifier: {}", e))
-                })?;
+            let mut client_verifier =
+                WebPkiClientVerifier::builder_with_provider(Arc::new(root_store), provider);
+            if !config.require_client_cert {
+                client_verifier = client_verifier.allow_unauthenticated();
+            }
+            let client_verifier = client_verifier.build().map_err(|e| {
+                Error::InternalError(format!("Failed to build client verifier: {}", e))
+            })?;
 
-            rustls::ServerConfig::builder()
+            builder
                 .with_client_cert_verifier(client_verifier)
-                .with_single_cert(certs, key)
-                .map_err(|e| Error::InternalError(format!("TLS config error: {}", e)))?
+                .with_cert_resolver(certs.clone())
         } else {
             // Standard TLS: no client certificates
-            rustls::ServerConfig::builder()
-                .with_no_client_auth()
-                .with_single_cert(certs, key)
-                .map_err(|e| Error::InternalError(format!("TLS config error: {}", e)))?
-        };
-
-        // Set minimum TLS version
-        let min_version = match config.min_version {
-            TlsVersion::Tls12 => &rustls::version::TLS12,
-            TlsVersion::Tls13 => &rustls::version::TLS13,
+            builder.with_no_client_auth().with_cert_resolver(certs.clone())
         };
-        server_config.versions = vec![min_version.clone(), rustls::version::TLS13.clone()];
 
         // Enable ALPN for HTTP/1.1 and HTTP/2
-        server_config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        if http2 {
+            server_config.alpn_protocols.insert(0, b"h2".to_vec());
+        }
+        if config.acme.enabled {
+            server_config.alpn_protocols.push(ACME_TLS_ALPN.to_vec());
+        }
 
         let acceptor = tokio_rustls::TlsAcceptor::from(Arc::new(server_config));
 
         Ok(Self {
             acceptor,
+            certs,
+            client_certs: ClientCertMapper::new(config.client_cert_users.clone()),
             hsts_enabled: config.hsts_enabled,
             hsts_max_age: config.hsts_max_age,
         })
@@ -106,6 +150,18 @@ impl TlsAcceptor {
         &self.acceptor
     }
 
+    /// Certificate served on new connections
+    pub fn certificates(&self) -> &Arc<CertStore> {
+        &self.certs
+    }
+
+    /// Identity of the verified certificate the client of a connection
+    /// presented, if it presented one
+    pub fn client_certificate(&self, conn: &rustls::ServerConnection) -> Option<ClientCertificate> {
+        let leaf = conn.peer_certificates()?.first()?;
+        self.client_certs.identify(leaf)
+    }
+
     /// Check if HSTS is enabled
     pub fn hsts_enabled(&self) -> bool {
         self.hsts_enabled
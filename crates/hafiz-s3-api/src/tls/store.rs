@@ -0,0 +1,186 @@
+//! Server certificate that can be swapped while serving
+//!
+//! [`CertStore`] hands rustls the certificate for each handshake. The
+//! certificate is read from the configured PEM files and read again when
+//! their modification time changes, so renewed certificates take effect on
+//! new connections without a restart. A file that fails to load is logged
+//! and the previous certificate stays in use.
+//!
+//! It also answers ACME `tls-alpn-01` challenges: a handshake offering only
+//! the `acme-tls/1` protocol gets the challenge certificate for its SNI name.
+
+use hafiz_core::{Error, Result};
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio_rustls::rustls::{
+    crypto::CryptoProvider,
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+};
+use tracing::{info, warn};
+
+use super::{load_certs, load_private_key, ACME_TLS_ALPN};
+
+/// Certificate resolver backed by PEM files
+#[derive(Debug)]
+pub struct CertStore {
+    provider: Arc<CryptoProvider>,
+    cert_file: PathBuf,
+    key_file: PathBuf,
+    current: RwLock<Option<Arc<CertifiedKey>>>,
+    /// Modification times of the files behind `current`
+    loaded: Mutex<Option<(SystemTime, SystemTime)>>,
+    /// `tls-alpn-01` certificates by domain
+    challenges: RwLock<HashMap<String, Arc<CertifiedKey>>>,
+}
+
+impl CertStore {
+    /// Empty store; call [`reload`](Self::reload) to load the files
+    pub fn new(provider: Arc<CryptoProvider>, cert_file: PathBuf, key_file: PathBuf) -> Self {
+        Self {
+            provider,
+            cert_file,
+            key_file,
+            current: RwLock::new(None),
+            loaded: Mutex::new(None),
+            challenges: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Whether a certificate has been loaded
+    pub fn has_certificate(&self) -> bool {
+        self.current.read().is_some()
+    }
+
+    /// Load the certificate and key files
+    ///
+    /// On error the certificate in use, if any, is kept.
+    pub fn reload(&self) -> Result<()> {
+        let modified = (modified(&self.cert_file)?, modified(&self.key_file)?);
+        let certs = load_certs(&self.cert_file)?;
+        let key = load_private_key(&self.key_file)?;
+        let certified = self.certified_key(certs, key)?;
+
+        *self.current.write() = Some(certified);
+        *self.loaded.lock() = Some(modified);
+        Ok(())
+    }
+
+    /// Reload if either file changed since the last load
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let modified = (modified(&self.cert_file)?, modified(&self.key_file)?);
+        if *self.loaded.lock() == Some(modified) {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Check the files for changes every `interval`
+    pub fn spawn(self: Arc<Self>, interval: Duration) {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                match self.reload_if_changed() {
+                    Ok(true) => info!("Reloaded TLS certificate from {:?}", self.cert_file),
+                    Ok(false) => {}
+                    Err(e) => warn!("Keeping current TLS certificate: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Pair a certificate chain with its private key
+    pub fn certified_key(
+        &self,
+        certs: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Result<Arc<CertifiedKey>> {
+        let signing_key = self
+            .provider
+            .key_provider
+            .load_private_key(key)
+            .map_err(|e| Error::InternalError(format!("Unsupported private key: {}", e)))?;
+        Ok(Arc::new(CertifiedKey::new(certs, signing_key)))
+    }
+
+    /// Answer `tls-alpn-01` handshakes for `domain` with `cert`
+    pub(crate) fn set_challenge(&self, domain: &str, cert: Arc<CertifiedKey>) {
+        self.challenges.write().insert(domain.to_ascii_lowercase(), cert);
+    }
+
+    pub(crate) fn clear_challenge(&self, domain: &str) {
+        self.challenges.write().remove(&domain.to_ascii_lowercase());
+    }
+}
+
+impl ResolvesServerCert for CertStore {
+    fn resolve(&self, client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        let is_challenge = client_hello
+            .alpn()
+            .map(|mut protocols| protocols.any(|p| p == ACME_TLS_ALPN))
+            .unwrap_or(false);
+        if is_challenge {
+            let domain = client_hello.server_name()?.to_ascii_lowercase();
+            return self.challenges.read().get(&domain).cloned();
+        }
+        self.current.read().clone()
+    }
+}
+
+fn modified(path: &Path) -> Result<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .map_err(|e| Error::InternalError(format!("Failed to read {:?}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn write_cert(dir: &Path, name: &str) {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let cert = rcgen::CertificateParams::new(vec![name.to_string()])
+            .unwrap()
+            .self_signed(&key_pair)
+            .unwrap();
+        std::fs::write(dir.join("cert.pem"), cert.pem()).unwrap();
+        std::fs::write(dir.join("key.pem"), key_pair.serialize_pem()).unwrap();
+    }
+
+    #[test]
+    fn test_reload_if_changed() {
+        let dir = tempdir().unwrap();
+        write_cert(dir.path(), "one.example.com");
+
+        let store = CertStore::new(
+            crate::tls::crypto_provider(),
+            dir.path().join("cert.pem"),
+            dir.path().join("key.pem"),
+        );
+        assert!(!store.has_certificate());
+        assert!(store.reload_if_changed().unwrap());
+        assert!(!store.reload_if_changed().unwrap());
+        let first = store.current.read().clone().unwrap();
+
+        // A newer file replaces the certificate
+        std::thread::sleep(Duration::from_millis(20));
+        write_cert(dir.path(), "two.example.com");
+        assert!(store.reload_if_changed().unwrap());
+        let second = store.current.read().clone().unwrap();
+        assert_ne!(first.cert[0], second.cert[0]);
+
+        // A broken file keeps the old one
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(dir.path().join("cert.pem"), "garbage").unwrap();
+        assert!(store.reload_if_changed().is_err());
+        assert_eq!(store.current.read().clone().unwrap().cert[0], second.cert[0]);
+    }
+}
@@ -0,0 +1,214 @@
+//! `decompress`: serve gzip or zstd compressed objects decompressed
+//!
+//! Parameters: `format` (`gzip`, `zstd` or `auto`, the default, which
+//! tells them apart by their magic bytes) and `content_type`, the type of
+//! the decompressed data. Data that is not compressed in the expected
+//! format fails the request, except under `auto`, where it passes through.
+
+use std::io::Write;
+
+use bytes::Bytes;
+use hafiz_core::{Error, Result};
+
+use super::{
+    chunked, ByteStream, ChunkTransform, ObjectTransform, TransformInput, TransformOutput,
+    TransformParams,
+};
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Gzip,
+    Zstd,
+    Auto,
+}
+
+fn format(params: &TransformParams) -> Result<Format> {
+    match params.get("format").map(String::as_str) {
+        None | Some("auto") => Ok(Format::Auto),
+        Some("gzip") => Ok(Format::Gzip),
+        Some("zstd") => Ok(Format::Zstd),
+        Some(other) => Err(Error::InvalidArgument(format!(
+            "format must be gzip, zstd or auto, not {}",
+            other
+        ))),
+    }
+}
+
+pub struct Decompress;
+
+impl ObjectTransform for Decompress {
+    fn name(&self) -> &'static str {
+        "decompress"
+    }
+
+    fn validate(&self, params: &TransformParams) -> Result<()> {
+        format(params).map(|_| ())
+    }
+
+    fn apply(
+        &self,
+        _input: &TransformInput,
+        params: &TransformParams,
+        body: ByteStream,
+    ) -> Result<TransformOutput> {
+        let decoder = Decoder {
+            format: format(params)?,
+            pending: Vec::new(),
+            state: None,
+        };
+        Ok(TransformOutput {
+            body: chunked(body, decoder),
+            content_type: params.get("content_type").cloned(),
+        })
+    }
+}
+
+enum State {
+    Gzip(Box<flate2::write::MultiGzDecoder<Vec<u8>>>),
+    Zstd(Box<zstd::stream::write::Decoder<'static, Vec<u8>>>),
+    Passthrough,
+}
+
+struct Decoder {
+    format: Format,
+    /// Leading bytes held back until the format is known
+    pending: Vec<u8>,
+    state: Option<State>,
+}
+
+impl Decoder {
+    /// Pick the decoder once enough of the data is known
+    fn start(&mut self, at_end: bool) -> Result<Option<State>> {
+        let head = &self.pending;
+        let detected = if head.starts_with(ZSTD_MAGIC) {
+            Some(Format::Zstd)
+        } else if head.starts_with(GZIP_MAGIC) {
+            Some(Format::Gzip)
+        } else if head.len() < ZSTD_MAGIC.len() && !at_end {
+            // Too short to tell yet
+            return Ok(None);
+        } else {
+            None
+        };
+
+        let format = match (self.format, detected) {
+            (Format::Auto, Some(format)) => format,
+            (Format::Auto, None) => return Ok(Some(State::Passthrough)),
+            (format, _) => format,
+        };
+        Ok(Some(match format {
+            Format::Gzip => State::Gzip(Box::new(flate2::write::MultiGzDecoder::new(Vec::new()))),
+            _ => State::Zstd(Box::new(
+                zstd::stream::write::Decoder::new(Vec::new()).map_err(decode_error)?,
+            )),
+        }))
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<Bytes> {
+        let out = match self.state.as_mut().expect("decoder started") {
+            State::Gzip(d) => {
+                d.write_all(data).map_err(decode_error)?;
+                std::mem::take(d.get_mut())
+            }
+            State::Zstd(d) => {
+                d.write_all(data).map_err(decode_error)?;
+                std::mem::take(d.get_mut())
+            }
+            State::Passthrough => data.to_vec(),
+        };
+        Ok(Bytes::from(out))
+    }
+}
+
+impl ChunkTransform for Decoder {
+    fn update(&mut self, chunk: &[u8]) -> Result<Bytes> {
+        if self.state.is_some() {
+            return self.write(chunk);
+        }
+        self.pending.extend_from_slice(chunk);
+        match self.start(false)? {
+            Some(state) => {
+                self.state = Some(state);
+                let pending = std::mem::take(&mut self.pending);
+                self.write(&pending)
+            }
+            None => Ok(Bytes::new()),
+        }
+    }
+
+    fn finish(&mut self) -> Result<Bytes> {
+        let mut out = Vec::new();
+        if self.state.is_none() {
+            if self.pending.is_empty() {
+                return Ok(Bytes::new());
+            }
+            self.state = self.start(true)?;
+            let pending = std::mem::take(&mut self.pending);
+            out.extend_from_slice(&self.write(&pending)?);
+        }
+        match self.state.take() {
+            Some(State::Gzip(d)) => out.extend(d.finish().map_err(decode_error)?),
+            Some(State::Zstd(mut d)) => {
+                d.flush().map_err(decode_error)?;
+                out.extend(std::mem::take(d.get_mut()));
+            }
+            _ => {}
+        }
+        Ok(Bytes::from(out))
+    }
+}
+
+fn decode_error(e: std::io::Error) -> Error {
+    Error::InvalidRequest(format!("Object could not be decompressed: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::collect;
+    use futures::stream::{self, StreamExt};
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    async fn run(format: Option<&str>, data: Vec<u8>, chunk: usize) -> Result<Bytes> {
+        let mut params = TransformParams::new();
+        if let Some(format) = format {
+            params.insert("format".into(), format.into());
+        }
+        let chunks: Vec<Result<Bytes>> = data
+            .chunks(chunk)
+            .map(|c| Ok(Bytes::copy_from_slice(c)))
+            .collect();
+        let input = TransformInput {
+            bucket: "b".into(),
+            key: "k".into(),
+            content_type: "application/gzip".into(),
+        };
+        let output = Decompress.apply(&input, &params, stream::iter(chunks).boxed())?;
+        collect(output.body, 1 << 20).await
+    }
+
+    #[tokio::test]
+    async fn test_decompress() {
+        let text = b"hello hello hello hello".repeat(100);
+
+        // Split into chunks smaller than the magic bytes
+        assert_eq!(run(None, gzip(&text), 1).await.unwrap(), text);
+        assert_eq!(run(Some("gzip"), gzip(&text), 100).await.unwrap(), text);
+        let zstd = zstd::encode_all(&text[..], 3).unwrap();
+        assert_eq!(run(None, zstd.clone(), 3).await.unwrap(), text);
+        assert_eq!(run(Some("zstd"), zstd, 4096).await.unwrap(), text);
+
+        // Plain data passes through auto, but not an explicit format
+        assert_eq!(run(None, b"plain".to_vec(), 2).await.unwrap(), "plain");
+        assert_eq!(run(None, b"x".to_vec(), 2).await.unwrap(), "x");
+        assert!(run(Some("gzip"), b"plain data".to_vec(), 4).await.is_err());
+    }
+}
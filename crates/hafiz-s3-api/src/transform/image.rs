@@ -0,0 +1,102 @@
+//! `resize_image`: scale images down to fit a bounding box
+//!
+//! Parameters: `width` and `height`, at least one of them, in pixels; the
+//! aspect ratio is kept and images already small enough are re-encoded
+//! unscaled. `format` (`png` or `jpeg`) picks the output encoding, which is
+//! otherwise that of the input. Built with the `image-transforms` feature.
+
+use std::io::Cursor;
+
+use bytes::Bytes;
+use hafiz_core::{Error, Result};
+use image::{imageops::FilterType, ImageFormat};
+
+use super::{buffered, ByteStream, ObjectTransform, TransformInput, TransformOutput, TransformParams};
+
+pub struct ResizeImage;
+
+struct Params {
+    width: u32,
+    height: u32,
+    format: Option<ImageFormat>,
+}
+
+fn params(params: &TransformParams) -> Result<Params> {
+    let dimension = |name: &str| -> Result<Option<u32>> {
+        params
+            .get(name)
+            .map(|v| match v.parse::<u32>() {
+                Ok(n) if n > 0 => Ok(n),
+                _ => Err(Error::InvalidArgument(format!("{} must be a positive integer", name))),
+            })
+            .transpose()
+    };
+    let (width, height) = (dimension("width")?, dimension("height")?);
+    if width.is_none() && height.is_none() {
+        return Err(Error::InvalidArgument("width or height is required".into()));
+    }
+    let format = match params.get("format").map(String::as_str) {
+        None => None,
+        Some("png") => Some(ImageFormat::Png),
+        Some("jpeg") | Some("jpg") => Some(ImageFormat::Jpeg),
+        Some(other) => {
+            return Err(Error::InvalidArgument(format!(
+                "format must be png or jpeg, not {}",
+                other
+            )))
+        }
+    };
+    Ok(Params {
+        width: width.unwrap_or(u32::MAX),
+        height: height.unwrap_or(u32::MAX),
+        format,
+    })
+}
+
+impl ObjectTransform for ResizeImage {
+    fn name(&self) -> &'static str {
+        "resize_image"
+    }
+
+    fn validate(&self, params: &TransformParams) -> Result<()> {
+        self::params(params).map(|_| ())
+    }
+
+    fn apply(
+        &self,
+        input: &TransformInput,
+        params: &TransformParams,
+        body: ByteStream,
+    ) -> Result<TransformOutput> {
+        let params = self::params(params)?;
+        let format = params
+            .format
+            .or_else(|| ImageFormat::from_mime_type(&input.content_type))
+            .filter(|f| matches!(f, ImageFormat::Png | ImageFormat::Jpeg))
+            .unwrap_or(ImageFormat::Png);
+
+        let body = buffered(body, move |data| {
+            let image = image::load_from_memory(&data)
+                .map_err(|e| Error::InvalidRequest(format!("Object is not an image: {}", e)))?;
+            let image = if image.width() > params.width || image.height() > params.height {
+                image.resize(params.width, params.height, FilterType::Lanczos3)
+            } else {
+                image
+            };
+            // JPEG has no alpha channel
+            let image = match format {
+                ImageFormat::Jpeg => image::DynamicImage::ImageRgb8(image.to_rgb8()),
+                _ => image,
+            };
+            let mut out = Cursor::new(Vec::new());
+            image
+                .write_to(&mut out, format)
+                .map_err(|e| Error::InternalError(format!("Failed to encode image: {}", e)))?;
+            Ok(Bytes::from(out.into_inner()))
+        });
+        Ok(TransformOutput {
+            body,
+            content_type: Some(format.to_mime_type().to_string()),
+        })
+    }
+}
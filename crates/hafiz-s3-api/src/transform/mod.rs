@@ -0,0 +1,339 @@
+//! Content transforms applied on GET
+//!
+//! A transform is an [`ObjectTransform`] registered under a name in a
+//! [`TransformRegistry`]. Rules in the `[transform]` configuration section
+//! chain registered transforms into a pipeline for the objects of a bucket,
+//! optionally narrowed by key prefix and content type. GetObject runs the
+//! pipeline of the first matching rule over the stored data.
+//!
+//! Transforms work on a stream of chunks. [`chunked`] adapts a
+//! [`ChunkTransform`] that rewrites data as it passes; [`buffered`] collects
+//! the whole object first, for transforms that need to see all of it.
+//!
+//! Embedders add their own transforms with
+//! [`S3Server::with_transform`](crate::S3Server::with_transform).
+
+mod decompress;
+#[cfg(feature = "image-transforms")]
+mod image;
+mod redact;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use bytes::{Bytes, BytesMut};
+use futures::stream::{self, BoxStream, StreamExt};
+use hafiz_core::config::{TransformConfig, TransformRule};
+use hafiz_core::{Error, Result};
+
+pub use decompress::Decompress;
+#[cfg(feature = "image-transforms")]
+pub use image::ResizeImage;
+pub use redact::RedactJson;
+
+/// Object data as it flows through a pipeline
+pub type ByteStream = BoxStream<'static, Result<Bytes>>;
+
+/// Parameters of a configured step
+pub type TransformParams = HashMap<String, String>;
+
+/// Largest object [`buffered`] transforms accept
+pub const MAX_BUFFERED_BYTES: usize = 64 * 1024 * 1024;
+
+/// The object being transformed
+#[derive(Debug, Clone)]
+pub struct TransformInput {
+    pub bucket: String,
+    pub key: String,
+    /// Content type of the data entering this step
+    pub content_type: String,
+}
+
+/// Output of one step
+pub struct TransformOutput {
+    pub body: ByteStream,
+    /// New content type, if the step changes it
+    pub content_type: Option<String>,
+}
+
+/// A named content rewrite
+pub trait ObjectTransform: Send + Sync {
+    /// Name used in `[[transform.rules.steps]]`
+    fn name(&self) -> &'static str;
+
+    /// Reject bad parameters when the configuration is loaded
+    fn validate(&self, _params: &TransformParams) -> Result<()> {
+        Ok(())
+    }
+
+    /// Rewrite `body`
+    ///
+    /// Errors returned here fail the request. Errors in the returned stream
+    /// fail it as well if they occur in the first chunk, and cut the
+    /// response short after that.
+    fn apply(
+        &self,
+        input: &TransformInput,
+        params: &TransformParams,
+        body: ByteStream,
+    ) -> Result<TransformOutput>;
+}
+
+/// Transforms by name
+#[derive(Clone, Default)]
+pub struct TransformRegistry {
+    transforms: HashMap<&'static str, Arc<dyn ObjectTransform>>,
+}
+
+impl TransformRegistry {
+    /// Registry holding the built-in transforms
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::default();
+        registry.register(Arc::new(Decompress));
+        registry.register(Arc::new(RedactJson));
+        #[cfg(feature = "image-transforms")]
+        registry.register(Arc::new(ResizeImage));
+        registry
+    }
+
+    /// Add a transform, replacing any registered under the same name
+    pub fn register(&mut self, transform: Arc<dyn ObjectTransform>) {
+        self.transforms.insert(transform.name(), transform);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn ObjectTransform>> {
+        self.transforms.get(name).cloned()
+    }
+}
+
+/// The transforms of one rule, ready to run
+pub struct Pipeline {
+    rule: TransformRule,
+    steps: Vec<Arc<dyn ObjectTransform>>,
+}
+
+impl Pipeline {
+    /// Names of the steps, in order
+    pub fn names(&self) -> Vec<&'static str> {
+        self.steps.iter().map(|t| t.name()).collect()
+    }
+
+    /// Run every step over `body`
+    pub fn apply(&self, mut input: TransformInput, mut body: ByteStream) -> Result<TransformOutput> {
+        let mut content_type = None;
+        for (transform, step) in self.steps.iter().zip(&self.rule.steps) {
+            let output = transform.apply(&input, &step.params, body)?;
+            metrics::counter!("hafiz_object_transforms_total", "transform" => transform.name())
+                .increment(1);
+            if let Some(ct) = output.content_type {
+                input.content_type = ct.clone();
+                content_type = Some(ct);
+            }
+            body = output.body;
+        }
+        Ok(TransformOutput { body, content_type })
+    }
+}
+
+/// Configured pipelines
+#[derive(Default)]
+pub struct TransformPipelines {
+    pipelines: Vec<Pipeline>,
+}
+
+impl TransformPipelines {
+    /// Resolve the configured rules against `registry`
+    pub fn new(config: &TransformConfig, registry: &TransformRegistry) -> Result<Self> {
+        let mut pipelines = Vec::new();
+        for rule in &config.rules {
+            if rule.steps.is_empty() {
+                return Err(Error::InvalidArgument(format!(
+                    "Transform rule for bucket {} has no steps",
+                    rule.bucket
+                )));
+            }
+            let mut steps = Vec::new();
+            for step in &rule.steps {
+                let transform = registry.get(&step.name).ok_or_else(|| {
+                    Error::InvalidArgument(format!("Unknown transform {}", step.name))
+                })?;
+                transform.validate(&step.params).map_err(|e| {
+                    Error::InvalidArgument(format!("Transform {}: {}", step.name, e))
+                })?;
+                steps.push(transform);
+            }
+            pipelines.push(Pipeline {
+                rule: rule.clone(),
+                steps,
+            });
+        }
+        Ok(Self { pipelines })
+    }
+
+    /// Pipeline for an object, if a rule covers it
+    pub fn find(&self, bucket: &str, key: &str, content_type: &str) -> Option<&Pipeline> {
+        self.pipelines
+            .iter()
+            .find(|p| p.rule.matches(bucket, key, content_type))
+    }
+}
+
+/// Rewrites data chunk by chunk
+pub trait ChunkTransform: Send + 'static {
+    /// Output for the next chunk of input; may be empty
+    fn update(&mut self, chunk: &[u8]) -> Result<Bytes>;
+
+    /// Output still pending once the input has ended
+    fn finish(&mut self) -> Result<Bytes>;
+}
+
+/// Stream `body` through `transform`
+pub fn chunked<T: ChunkTransform>(body: ByteStream, transform: T) -> ByteStream {
+    stream::unfold(Some((body, transform)), |state| async move {
+        let (mut body, mut transform) = state?;
+        loop {
+            match body.next().await {
+                Some(Ok(chunk)) => match transform.update(&chunk) {
+                    Ok(out) if out.is_empty() => continue,
+                    Ok(out) => return Some((Ok(out), Some((body, transform)))),
+                    Err(e) => return Some((Err(e), None)),
+                },
+                Some(Err(e)) => return Some((Err(e), None)),
+                None => {
+                    return match transform.finish() {
+                        Ok(out) if out.is_empty() => None,
+                        result => Some((result, None)),
+                    };
+                }
+            }
+        }
+    })
+    .boxed()
+}
+
+/// Collect `body` and rewrite it whole with `f`, off the async runtime
+///
+/// Objects larger than [`MAX_BUFFERED_BYTES`] are refused.
+pub fn buffered<F>(body: ByteStream, f: F) -> ByteStream
+where
+    F: FnOnce(Bytes) -> Result<Bytes> + Send + 'static,
+{
+    stream::once(async move {
+        let data = collect(body, MAX_BUFFERED_BYTES).await?;
+        tokio::task::spawn_blocking(move || f(data))
+            .await
+            .map_err(|e| Error::InternalError(format!("Transform panicked: {}", e)))?
+    })
+    .boxed()
+}
+
+/// Read `body` into memory, failing past `limit` bytes
+pub async fn collect(mut body: ByteStream, limit: usize) -> Result<Bytes> {
+    let mut data = BytesMut::new();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        if data.len() + chunk.len() > limit {
+            return Err(Error::InvalidRequest(format!(
+                "Object is too large to transform (limit {} bytes)",
+                limit
+            )));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data.freeze())
+}
+
+/// Stream of a single chunk
+pub fn once(data: Bytes) -> ByteStream {
+    stream::once(async move { Ok(data) }).boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hafiz_core::config::TransformStep;
+
+    /// Upper-cases ASCII as it streams
+    struct Upper;
+
+    impl ChunkTransform for Upper {
+        fn update(&mut self, chunk: &[u8]) -> Result<Bytes> {
+            Ok(Bytes::from(chunk.to_ascii_uppercase()))
+        }
+
+        fn finish(&mut self) -> Result<Bytes> {
+            Ok(Bytes::from_static(b"!"))
+        }
+    }
+
+    impl ObjectTransform for Upper {
+        fn name(&self) -> &'static str {
+            "upper"
+        }
+
+        fn apply(
+            &self,
+            _input: &TransformInput,
+            _params: &TransformParams,
+            body: ByteStream,
+        ) -> Result<TransformOutput> {
+            Ok(TransformOutput {
+                body: chunked(body, Upper),
+                content_type: Some("text/plain".into()),
+            })
+        }
+    }
+
+    fn rule(prefix: &str, steps: &[&str]) -> TransformRule {
+        TransformRule {
+            bucket: "b".into(),
+            prefix: prefix.into(),
+            content_types: vec![],
+            steps: steps
+                .iter()
+                .map(|name| TransformStep {
+                    name: name.to_string(),
+                    params: HashMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pipeline() {
+        let mut registry = TransformRegistry::with_builtins();
+        registry.register(Arc::new(Upper));
+        let config = TransformConfig {
+            rules: vec![rule("loud/", &["upper", "upper"])],
+        };
+        let pipelines = TransformPipelines::new(&config, &registry).unwrap();
+        assert!(pipelines.find("b", "quiet/x", "text/plain").is_none());
+        assert!(pipelines.find("other", "loud/x", "text/plain").is_none());
+
+        let pipeline = pipelines.find("b", "loud/x", "text/plain").unwrap();
+        assert_eq!(pipeline.names(), vec!["upper", "upper"]);
+        let input = TransformInput {
+            bucket: "b".into(),
+            key: "loud/x".into(),
+            content_type: "application/octet-stream".into(),
+        };
+        let body = stream::iter(vec![Ok(Bytes::from("he")), Ok(Bytes::from("llo"))]).boxed();
+        let output = pipeline.apply(input, body).unwrap();
+        assert_eq!(output.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(collect(output.body, 100).await.unwrap(), "HELLO!!");
+    }
+
+    #[test]
+    fn test_unknown_transform() {
+        let config = TransformConfig {
+            rules: vec![rule("", &["nope"])],
+        };
+        assert!(TransformPipelines::new(&config, &TransformRegistry::with_builtins()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_buffered_limit() {
+        let body = once(Bytes::from(vec![0u8; 10]));
+        assert!(collect(body, 9).await.is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! `redact_json`: hide fields of JSON documents
+//!
+//! Parameters: `fields`, a comma-separated list of field names removed at
+//! any depth, and `replacement`, a string that replaces their values
+//! instead of removing them. Documents that are not JSON fail the request.
+
+use std::collections::HashSet;
+
+use bytes::Bytes;
+use hafiz_core::{Error, Result};
+use serde_json::Value;
+
+use super::{buffered, ByteStream, ObjectTransform, TransformInput, TransformOutput, TransformParams};
+
+pub struct RedactJson;
+
+fn fields(params: &TransformParams) -> Result<HashSet<String>> {
+    let fields: HashSet<String> = params
+        .get("fields")
+        .map(|f| f.split(',').map(str::trim).filter(|f| !f.is_empty()).map(String::from).collect())
+        .unwrap_or_default();
+    if fields.is_empty() {
+        return Err(Error::InvalidArgument("fields must name at least one field".into()));
+    }
+    Ok(fields)
+}
+
+impl ObjectTransform for RedactJson {
+    fn name(&self) -> &'static str {
+        "redact_json"
+    }
+
+    fn validate(&self, params: &TransformParams) -> Result<()> {
+        fields(params).map(|_| ())
+    }
+
+    fn apply(
+        &self,
+        _input: &TransformInput,
+        params: &TransformParams,
+        body: ByteStream,
+    ) -> Result<TransformOutput> {
+        let fields = fields(params)?;
+        let replacement = params.get("replacement").cloned();
+        let body = buffered(body, move |data| {
+            let mut doc: Value = serde_json::from_slice(&data)
+                .map_err(|e| Error::InvalidRequest(format!("Object is not JSON: {}", e)))?;
+            redact(&mut doc, &fields, replacement.as_deref());
+            serde_json::to_vec(&doc)
+                .map(Bytes::from)
+                .map_err(|e| Error::InternalError(e.to_string()))
+        });
+        Ok(TransformOutput {
+            body,
+            content_type: None,
+        })
+    }
+}
+
+fn redact(value: &mut Value, fields: &HashSet<String>, replacement: Option<&str>) {
+    match value {
+        Value::Object(map) => {
+            match replacement {
+                Some(r) => {
+                    for (k, v) in map.iter_mut() {
+                        if fields.contains(k) {
+                            *v = Value::String(r.to_string());
+                        }
+                    }
+                }
+                None => map.retain(|k, _| !fields.contains(k)),
+            }
+            for v in map.values_mut() {
+                redact(v, fields, replacement);
+            }
+        }
+        Value::Array(items) => {
+            for v in items {
+                redact(v, fields, replacement);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redact() {
+        let fields: HashSet<String> = ["email", "ssn"].iter().map(|s| s.to_string()).collect();
+        let doc = json!({
+            "name": "a",
+            "email": "a@example.com",
+            "contacts": [{"email": "b@example.com", "phone": "1"}],
+            "ssn": {"nested": true},
+        });
+
+        let mut removed = doc.clone();
+        redact(&mut removed, &fields, None);
+        assert_eq!(removed, json!({"name": "a", "contacts": [{"phone": "1"}]}));
+
+        let mut masked = doc;
+        redact(&mut masked, &fields, Some("***"));
+        assert_eq!(
+            masked,
+            json!({
+                "name": "a",
+                "email": "***",
+                "contacts": [{"email": "***", "phone": "1"}],
+                "ssn": "***",
+            })
+        );
+    }
+
+    #[test]
+    fn test_fields_required() {
+        assert!(RedactJson.validate(&TransformParams::new()).is_err());
+        let params = TransformParams::from([("fields".to_string(), " , ".to_string())]);
+        assert!(RedactJson.validate(&params).is_err());
+    }
+}
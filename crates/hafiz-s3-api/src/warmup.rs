@@ -0,0 +1,373 @@
+//! Warming the read cache ahead of expected traffic
+//!
+//! A warm-up job loads a list of keys, or every object under a prefix,
+//! into the read cache before a known spike such as a release download
+//! window. Only the current version of each key is loaded, and objects
+//! larger than the cache's `max_object_size` are skipped.
+//!
+//! Two limits keep a job from pushing out what current traffic relies on:
+//! it stops once it has loaded `max_bytes`, by default half the memory
+//! tier, and unless `evict` is set it also stops when the memory tier has
+//! no free room left for the next object. Jobs are tracked in memory like
+//! prune jobs, and only one runs at a time.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use futures::stream::{self, StreamExt};
+use hafiz_core::types::ObjectInfo;
+use hafiz_core::{Error, Result};
+use hafiz_storage::ReadCache;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::routes::storage_key;
+use crate::server::AppState;
+
+/// Finished jobs kept for the admin API
+pub const MAX_FINISHED_JOBS: usize = 100;
+
+/// Most objects a job may load at once
+pub const MAX_CONCURRENCY: usize = 32;
+
+/// Objects listed per metadata page
+const LIST_PAGE_SIZE: i32 = 1000;
+
+fn default_concurrency() -> usize {
+    4
+}
+
+/// What a job loads
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WarmupRequest {
+    pub bucket: String,
+    /// Keys to load; when empty, every object under `prefix`
+    #[serde(default)]
+    pub keys: Vec<String>,
+    #[serde(default)]
+    pub prefix: String,
+    /// Stop after loading this many bytes; defaults to half the memory tier
+    pub max_bytes: Option<u64>,
+    /// Keep loading once the memory tier is full, evicting older entries
+    #[serde(default)]
+    pub evict: bool,
+    /// Objects loaded at once
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WarmupJobState {
+    Running,
+    Completed,
+    /// Stopped at `max_bytes` or a full memory tier
+    Stopped,
+    /// The listing failed, e.g. the bucket was deleted
+    Failed,
+    Cancelled,
+}
+
+impl WarmupJobState {
+    pub fn is_finished(self) -> bool {
+        !matches!(self, WarmupJobState::Running)
+    }
+}
+
+/// Job progress as shown by the admin API
+#[derive(Debug, Clone, Serialize)]
+pub struct WarmupJobStatus {
+    pub id: String,
+    pub request: WarmupRequest,
+    pub state: WarmupJobState,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    /// Bytes the job may load
+    pub max_bytes: u64,
+    pub objects_scanned: u64,
+    pub objects_loaded: u64,
+    pub bytes_loaded: u64,
+    /// Objects that were cached already
+    pub already_cached: u64,
+    /// Objects larger than the cache's `max_object_size`
+    pub too_large: u64,
+    /// Listed keys with no current version
+    pub missing: u64,
+    pub failed: u64,
+    /// Last key loaded
+    pub current_key: Option<String>,
+    /// Why the job stopped early or failed
+    pub reason: Option<String>,
+}
+
+/// One submitted job
+struct WarmupJob {
+    status: RwLock<WarmupJobStatus>,
+    cancelled: AtomicBool,
+    /// Set when a limit was reached
+    stopped: AtomicBool,
+}
+
+/// Runs warm-up jobs and keeps track of them
+#[derive(Default)]
+pub struct WarmupJobs {
+    /// In submission order
+    jobs: RwLock<Vec<Arc<WarmupJob>>>,
+}
+
+impl WarmupJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// All known jobs, newest first
+    pub fn list(&self) -> Vec<WarmupJobStatus> {
+        self.jobs
+            .read()
+            .iter()
+            .rev()
+            .map(|job| job.status.read().clone())
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<WarmupJobStatus> {
+        self.find(id).map(|job| job.status.read().clone())
+    }
+
+    /// Stop a job after the objects in flight; `None` if there is no such job
+    pub fn cancel(&self, id: &str) -> Option<WarmupJobStatus> {
+        let job = self.find(id)?;
+        job.cancelled.store(true, Ordering::SeqCst);
+        let status = job.status.read().clone();
+        Some(status)
+    }
+
+    fn find(&self, id: &str) -> Option<Arc<WarmupJob>> {
+        self.jobs.read().iter().find(|job| job.status.read().id == id).cloned()
+    }
+
+    /// Check a job and start it in the background
+    pub async fn submit(&self, state: &AppState, request: WarmupRequest) -> Result<WarmupJobStatus> {
+        let Some(cache) = state.read_cache.clone() else {
+            return Err(Error::InvalidArgument("The read cache is not enabled".into()));
+        };
+        if request.concurrency == 0 || request.concurrency > MAX_CONCURRENCY {
+            return Err(Error::InvalidArgument(format!(
+                "concurrency must be between 1 and {}",
+                MAX_CONCURRENCY
+            )));
+        }
+        let memory = cache.memory_stats().max_bytes;
+        let capacity = memory + cache.disk_stats().map_or(0, |disk| disk.max_bytes);
+        let max_bytes = request.max_bytes.unwrap_or(memory / 2);
+        if max_bytes == 0 || max_bytes > capacity {
+            return Err(Error::InvalidArgument(format!(
+                "max_bytes must be between 1 and the cache size of {} bytes",
+                capacity
+            )));
+        }
+        if state.metadata.get_bucket(&request.bucket).await?.is_none() {
+            return Err(Error::NoSuchBucketNamed(request.bucket.clone()));
+        }
+
+        let job = Arc::new(WarmupJob {
+            status: RwLock::new(WarmupJobStatus {
+                id: uuid::Uuid::new_v4().to_string(),
+                request,
+                state: WarmupJobState::Running,
+                created_at: Utc::now(),
+                finished_at: None,
+                max_bytes,
+                objects_scanned: 0,
+                objects_loaded: 0,
+                bytes_loaded: 0,
+                already_cached: 0,
+                too_large: 0,
+                missing: 0,
+                failed: 0,
+                current_key: None,
+                reason: None,
+            }),
+            cancelled: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        });
+        let status = job.status.read().clone();
+
+        {
+            let mut jobs = self.jobs.write();
+            if jobs.iter().any(|job| !job.status.read().state.is_finished()) {
+                return Err(Error::OperationAborted("A cache warm-up is already running".into()));
+            }
+            jobs.push(job.clone());
+            prune_finished(&mut jobs);
+        }
+        info!(
+            "Cache warm-up {} submitted for bucket {}, up to {} bytes",
+            status.id, status.request.bucket, max_bytes
+        );
+
+        let state = state.clone();
+        tokio::spawn(async move { job.run(&state, &cache).await });
+        Ok(status)
+    }
+}
+
+/// Drop the oldest finished jobs beyond [`MAX_FINISHED_JOBS`]
+fn prune_finished(jobs: &mut Vec<Arc<WarmupJob>>) {
+    let finished = jobs.iter().filter(|job| job.status.read().state.is_finished()).count();
+    let mut excess = finished.saturating_sub(MAX_FINISHED_JOBS);
+    jobs.retain(|job| {
+        if excess > 0 && job.status.read().state.is_finished() {
+            excess -= 1;
+            return false;
+        }
+        true
+    });
+}
+
+impl WarmupJob {
+    async fn run(&self, state: &AppState, cache: &ReadCache) {
+        let request = self.status.read().request.clone();
+        let result = if request.keys.is_empty() {
+            self.load_prefix(state, cache, &request).await
+        } else {
+            self.load_keys(state, cache, &request).await
+        };
+
+        let mut status = self.status.write();
+        status.state = match result {
+            Ok(()) if self.cancelled.load(Ordering::SeqCst) => WarmupJobState::Cancelled,
+            Ok(()) if self.stopped.load(Ordering::SeqCst) => WarmupJobState::Stopped,
+            Ok(()) => WarmupJobState::Completed,
+            Err(e) => {
+                warn!("Cache warm-up {} failed: {}", status.id, e);
+                status.reason = Some(e.to_string());
+                WarmupJobState::Failed
+            }
+        };
+        status.finished_at = Some(Utc::now());
+        info!(
+            "Cache warm-up {} {:?}: {} objects, {} bytes loaded, {} cached already, {} too large, {} missing, {} failed",
+            status.id,
+            status.state,
+            status.objects_loaded,
+            status.bytes_loaded,
+            status.already_cached,
+            status.too_large,
+            status.missing,
+            status.failed
+        );
+    }
+
+    /// Whether to stop taking on objects
+    fn done(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst) || self.stopped.load(Ordering::SeqCst)
+    }
+
+    async fn load_keys(&self, state: &AppState, cache: &ReadCache, request: &WarmupRequest) -> Result<()> {
+        let bucket = &request.bucket;
+        stream::iter(&request.keys)
+            .take_while(|_| std::future::ready(!self.done()))
+            .for_each_concurrent(request.concurrency, |key| async move {
+                match state.metadata.get_object(bucket, key).await {
+                    Ok(Some(object)) if !object.is_delete_marker => {
+                        let object = ObjectInfo {
+                            version_id: Some(object.version_id.clone()),
+                            is_latest: Some(true),
+                            key: object.key,
+                            last_modified: object.last_modified,
+                            etag: object.etag,
+                            size: object.size,
+                            storage_class: object.storage_class,
+                        };
+                        self.load(cache, request, &object).await
+                    }
+                    Ok(_) => {
+                        let mut status = self.status.write();
+                        status.objects_scanned += 1;
+                        status.missing += 1;
+                    }
+                    Err(e) => {
+                        warn!("Cache warm-up of {}/{} failed: {}", bucket, key, e);
+                        let mut status = self.status.write();
+                        status.objects_scanned += 1;
+                        status.failed += 1;
+                    }
+                }
+            })
+            .await;
+        Ok(())
+    }
+
+    /// Page through the current objects under the prefix and load each
+    async fn load_prefix(&self, state: &AppState, cache: &ReadCache, request: &WarmupRequest) -> Result<()> {
+        let prefix = Some(request.prefix.as_str()).filter(|p| !p.is_empty());
+        let mut token: Option<String> = None;
+        loop {
+            let (objects, is_truncated, next) = state
+                .metadata
+                .list_objects_flat(&request.bucket, prefix, LIST_PAGE_SIZE, token.as_deref())
+                .await?;
+            stream::iter(&objects)
+                .take_while(|_| std::future::ready(!self.done()))
+                .for_each_concurrent(request.concurrency, |object| self.load(cache, request, object))
+                .await;
+            if self.done() || !is_truncated {
+                return Ok(());
+            }
+            token = next;
+        }
+    }
+
+    /// Load one object unless it is too large or a limit is reached
+    async fn load(&self, cache: &ReadCache, request: &WarmupRequest, object: &ObjectInfo) {
+        let size = object.size.max(0) as u64;
+        if size > cache.max_object_size() {
+            let mut status = self.status.write();
+            status.objects_scanned += 1;
+            status.too_large += 1;
+            return;
+        }
+        if let Some(reason) = self.limit(cache, request, size) {
+            if !self.stopped.swap(true, Ordering::SeqCst) {
+                self.status.write().reason = Some(reason);
+            }
+            return;
+        }
+
+        let version_id = object.version_id.as_deref().unwrap_or(hafiz_core::types::NULL_VERSION_ID);
+        let key = storage_key(&object.key, version_id);
+        let result = cache.prefetch(&request.bucket, &key, &object.etag).await;
+
+        let mut status = self.status.write();
+        status.objects_scanned += 1;
+        status.current_key = Some(object.key.clone());
+        match result {
+            Ok(0) => status.already_cached += 1,
+            Ok(bytes) => {
+                status.objects_loaded += 1;
+                status.bytes_loaded += bytes;
+            }
+            Err(Error::NoSuchKey) => status.missing += 1,
+            Err(e) => {
+                warn!("Cache warm-up of {}/{} failed: {}", request.bucket, object.key, e);
+                status.failed += 1;
+            }
+        }
+    }
+
+    /// Why an object of `size` bytes may not be loaded, if a limit stops it
+    fn limit(&self, cache: &ReadCache, request: &WarmupRequest, size: u64) -> Option<String> {
+        let status = self.status.read();
+        if status.bytes_loaded + size > status.max_bytes {
+            return Some(format!("Reached max_bytes ({} bytes)", status.max_bytes));
+        }
+        let memory = cache.memory_stats();
+        if !request.evict && memory.used_bytes + size > memory.max_bytes {
+            return Some("The memory tier of the read cache is full".into());
+        }
+        None
+    }
+}
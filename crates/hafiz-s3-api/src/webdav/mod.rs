@@ -0,0 +1,867 @@
+//! WebDAV gateway
+//!
+//! An optional listener, enabled by `[webdav]`, that serves buckets as
+//! WebDAV collections so they can be mounted as network drives in Finder,
+//! Windows Explorer or davfs2. The collection at the configured prefix
+//! lists the user's buckets; inside a bucket, keys are files and
+//! `/`-delimited prefixes are directories, with an optional `dir/` marker
+//! object so empty directories survive.
+//!
+//! Clients authenticate with HTTP Basic auth: the access key as user name,
+//! the secret key as password. Each WebDAV request is carried out as S3
+//! requests made by that user through the [`loopback`](crate::loopback),
+//! so bucket policies, ACLs, quotas and events apply as they do for S3
+//! clients.
+//!
+//! Directories follow [`hafiz_core::directory`]. Listings are reused for
+//! `listing_cache_secs` per user, since clients stat every entry of a
+//! folder they have just listed.
+//!
+//! Locks are granted but not enforced; Finder mounts a share read-only
+//! unless it can lock files.
+
+mod xml;
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, State},
+    http::{header, HeaderMap, HeaderValue, Method, Request, StatusCode},
+    response::{IntoResponse, Response},
+    Router,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hafiz_core::config::WebDavConfig;
+use hafiz_core::directory::{child_path, split_path, DirEntry, Listing, ListingCache, TreeCopy};
+use parking_lot::Mutex;
+use tracing::{debug, warn};
+
+use crate::loopback::{
+    log_progress, s3_path, xml_body, BucketList, Caller, Loopback, LoopbackTree,
+};
+use crate::server::AppState;
+use xml::Resource;
+
+/// Methods answered to OPTIONS
+const ALLOW: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK";
+
+/// Lifetime reported for granted locks
+const LOCK_TIMEOUT_SECS: u64 = 3600;
+
+/// Request headers passed on to GET and HEAD
+const CONDITIONAL_HEADERS: [header::HeaderName; 5] = [
+    header::RANGE,
+    header::IF_MATCH,
+    header::IF_NONE_MATCH,
+    header::IF_MODIFIED_SINCE,
+    header::IF_UNMODIFIED_SINCE,
+];
+
+struct Gateway {
+    state: AppState,
+    /// WebDAV requests are translated into calls of the S3 API
+    s3: Loopback,
+    /// Path prefix without a trailing slash; empty for `/`
+    prefix: String,
+    /// Listings by access key, with paths of the form `bucket/dir`
+    listings: Mutex<HashMap<String, ListingCache>>,
+    listing_ttl: Duration,
+}
+
+/// Router of the WebDAV listener, dispatching to the S3 router `s3`
+pub fn router(state: AppState, s3: Router, config: &WebDavConfig) -> Router {
+    let gateway = Gateway {
+        state,
+        s3: Loopback::new(s3),
+        prefix: config.prefix.trim_end_matches('/').to_string(),
+        listings: Mutex::new(HashMap::new()),
+        listing_ttl: Duration::from_secs(config.listing_cache_secs),
+    };
+    Router::new().fallback(handle).with_state(Arc::new(gateway))
+}
+
+/// What a WebDAV path names
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Target {
+    /// The collection of all buckets
+    Root,
+    Bucket(String),
+    /// A file, or a directory when `key` ends with `/`
+    Object {
+        bucket: String,
+        key: String,
+    },
+}
+
+impl Target {
+    /// Target of a percent-encoded path below the prefix
+    fn parse(path: &str) -> Option<Self> {
+        let path = path.trim_start_matches('/');
+        let (bucket, key) = match path.split_once('/') {
+            None if path.is_empty() => return Some(Target::Root),
+            None => (path, ""),
+            Some((bucket, key)) => (bucket, key),
+        };
+        let bucket = decode_segment(bucket)?;
+        if key.is_empty() {
+            return Some(Target::Bucket(bucket));
+        }
+        let (segments, dir) = match key.strip_suffix('/') {
+            Some(segments) => (segments, true),
+            None => (key, false),
+        };
+        let mut key = segments
+            .split('/')
+            .map(decode_segment)
+            .collect::<Option<Vec<_>>>()?
+            .join("/");
+        if dir {
+            key.push('/');
+        }
+        Some(Target::Object { bucket, key })
+    }
+
+    /// The same object, named as a directory
+    fn as_dir(&self) -> Self {
+        match self {
+            Target::Object { bucket, key } if !key.ends_with('/') => Target::Object {
+                bucket: bucket.clone(),
+                key: format!("{}/", key),
+            },
+            other => other.clone(),
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        match self {
+            Target::Object { key, .. } => key.ends_with('/'),
+            _ => true,
+        }
+    }
+
+    /// Path in the listing cache: `bucket/dir/file`
+    fn cache_path(&self) -> Option<String> {
+        match self {
+            Target::Root => None,
+            Target::Bucket(bucket) => Some(bucket.clone()),
+            Target::Object { bucket, key } => Some(child_path(bucket, key.trim_end_matches('/'))),
+        }
+    }
+}
+
+fn decode_segment(segment: &str) -> Option<String> {
+    let decoded = urlencoding::decode(segment).ok()?;
+    if decoded.is_empty() || decoded == "." || decoded == ".." || decoded.contains('/') {
+        return None;
+    }
+    Some(decoded.into_owned())
+}
+
+async fn handle(State(gateway): State<Arc<Gateway>>, request: Request<Body>) -> Response {
+    let Some(path) = request.uri().path().strip_prefix(gateway.prefix.as_str()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    if !path.is_empty() && !path.starts_with('/') {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    let Some(target) = Target::parse(path) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+
+    if request.method() == Method::OPTIONS {
+        return options();
+    }
+
+    let Some(access_key) = authenticate(&gateway.state, request.headers()).await else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            [(
+                header::WWW_AUTHENTICATE,
+                "Basic realm=\"Hafiz\", charset=\"UTF-8\"",
+            )],
+        )
+            .into_response();
+    };
+    let caller = Caller {
+        access_key,
+        connect_info: request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .cloned(),
+    };
+    debug!(
+        "WebDAV {} {} by {}",
+        request.method(),
+        request.uri().path(),
+        caller.access_key
+    );
+
+    match request.method().as_str() {
+        "PROPFIND" => gateway.propfind(&caller, &target, request.headers()).await,
+        "PROPPATCH" => proppatch(&gateway.href(&target)),
+        "GET" | "HEAD" => gateway.get(&caller, &target, request).await,
+        "PUT" => gateway.put(&caller, &target, request).await,
+        "MKCOL" => gateway.mkcol(&caller, &target, request.headers()).await,
+        "DELETE" => gateway.delete(&caller, &target).await,
+        "COPY" => {
+            gateway
+                .copy(&caller, &target, request.headers(), false)
+                .await
+        }
+        "MOVE" => {
+            gateway
+                .copy(&caller, &target, request.headers(), true)
+                .await
+        }
+        "LOCK" => lock(&gateway.href(&target)),
+        "UNLOCK" => StatusCode::NO_CONTENT.into_response(),
+        _ => (StatusCode::METHOD_NOT_ALLOWED, [(header::ALLOW, ALLOW)]).into_response(),
+    }
+}
+
+/// Access key of valid Basic credentials
+async fn authenticate(state: &AppState, headers: &HeaderMap) -> Option<String> {
+    let encoded = headers
+        .get(header::AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(BASE64.decode(encoded.trim()).ok()?).ok()?;
+    let (access_key, secret_key) = decoded.split_once(':')?;
+
+    let credentials = match state.metadata.get_credentials(access_key).await {
+        Ok(credentials) => credentials?,
+        Err(e) => {
+            warn!("WebDAV login of {} failed: {}", access_key, e);
+            return None;
+        }
+    };
+    if !credentials.enabled || credentials.secret_key != secret_key {
+        return None;
+    }
+    state.key_usage.record(&state.metadata, access_key);
+    Some(access_key.to_string())
+}
+
+fn options() -> Response {
+    (
+        StatusCode::OK,
+        [
+            (header::ALLOW, ALLOW),
+            (header::HeaderName::from_static("dav"), "1, 2"),
+            // Makes Office and older Windows clients speak WebDAV
+            (header::HeaderName::from_static("ms-author-via"), "DAV"),
+        ],
+    )
+        .into_response()
+}
+
+fn multistatus(body: String) -> Response {
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        body,
+    )
+        .into_response()
+}
+
+fn proppatch(href: &str) -> Response {
+    multistatus(xml::proppatch(href))
+}
+
+fn lock(href: &str) -> Response {
+    let token = format!("opaquelocktoken:{}", uuid::Uuid::new_v4());
+    let lock_token =
+        HeaderValue::from_str(&format!("<{}>", token)).expect("lock token is a valid header");
+    (
+        StatusCode::OK,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/xml; charset=utf-8"),
+            ),
+            (header::HeaderName::from_static("lock-token"), lock_token),
+        ],
+        xml::lock_discovery(href, &token, LOCK_TIMEOUT_SECS),
+    )
+        .into_response()
+}
+
+impl Gateway {
+    /// Percent-encoded path of `target` on this listener
+    fn href(&self, target: &Target) -> String {
+        match target {
+            Target::Root => format!("{}/", self.prefix),
+            Target::Bucket(bucket) => format!("{}/{}/", self.prefix, urlencoding::encode(bucket)),
+            Target::Object { bucket, key } => format!("{}{}", self.prefix, s3_path(bucket, key)),
+        }
+    }
+
+    /// Apply `f` to the listing cache of `caller`
+    fn with_cache<T>(&self, caller: &Caller, f: impl FnOnce(&mut ListingCache) -> T) -> T {
+        let mut listings = self.listings.lock();
+        let cache = listings
+            .entry(caller.access_key.clone())
+            .or_insert_with(|| ListingCache::new(self.listing_ttl));
+        f(cache)
+    }
+
+    /// Entries of directory `prefix`, cached or listed
+    async fn listing(
+        &self,
+        caller: &Caller,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Listing, Response> {
+        let dir = child_path(bucket, prefix.trim_end_matches('/'));
+        if let Some(listing) = self.with_cache(caller, |cache| cache.get(&dir).cloned()) {
+            return Ok(listing);
+        }
+        let listing = self.s3.listing(caller, bucket, prefix).await?;
+        self.with_cache(caller, |cache| cache.insert(&dir, listing.clone()));
+        Ok(listing)
+    }
+
+    /// Files and directories directly inside directory `prefix`, or `None`
+    /// when nothing, not even a marker, is stored under it
+    async fn children(
+        &self,
+        caller: &Caller,
+        bucket: &str,
+        prefix: &str,
+    ) -> Result<Option<Vec<Resource>>, Response> {
+        let listing = self.listing(caller, bucket, prefix).await?;
+        if !listing.exists() {
+            return Ok(None);
+        }
+        let children = listing
+            .entries
+            .into_iter()
+            .map(|(name, entry)| {
+                if entry.is_dir() {
+                    let key = format!("{}{}/", prefix, name);
+                    let href = format!("{}{}", self.prefix, s3_path(bucket, &key));
+                    Resource::collection(href, None)
+                } else {
+                    let key = format!("{}{}", prefix, name);
+                    let href = format!("{}{}", self.prefix, s3_path(bucket, &key));
+                    Resource::file(href, entry.size, entry.modified, entry.etag)
+                }
+            })
+            .collect();
+        Ok(Some(children))
+    }
+
+    /// Store the marker of the directory of cache path `path` if removing
+    /// `path` left it empty, so the directory does not go away too
+    async fn keep_parent(&self, caller: &Caller, path: &str) -> Result<(), Response> {
+        let (dir, _) = split_path(path);
+        // A bucket needs no marker
+        let Some((bucket, dir_key)) = dir.split_once('/') else {
+            return Ok(());
+        };
+        let prefix = format!("{}/", dir_key);
+        let survives = match self.with_cache(caller, |cache| cache.parent_survives(path)) {
+            Some(survives) => survives,
+            None => self.s3.any_under(caller, bucket, &prefix).await?,
+        };
+        if survives {
+            return Ok(());
+        }
+        let response = self.s3.put_empty(caller, bucket, &prefix).await;
+        if !response.status().is_success() {
+            return Err(response);
+        }
+        self.with_cache(caller, |cache| cache.insert(dir, Listing::created()));
+        Ok(())
+    }
+
+    /// Whether `target` is a stored file, a directory or missing
+    async fn resolve(&self, caller: &Caller, target: &Target) -> Result<Option<Target>, Response> {
+        let Target::Object { bucket, key } = target else {
+            return Ok(Some(target.clone()));
+        };
+        let path = target.cache_path().expect("objects have cache paths");
+        let cached = self.with_cache(caller, |cache| cache.lookup(&path).map(|e| e.cloned()));
+        if let Some(entry) = cached {
+            return Ok(match entry {
+                Some(entry) if entry.is_dir() => Some(target.as_dir()),
+                Some(_) if !key.ends_with('/') => Some(target.clone()),
+                _ => None,
+            });
+        }
+        if !key.ends_with('/') {
+            let response = self.s3.head(caller, bucket, key).await;
+            if response.status().is_success() {
+                return Ok(Some(target.clone()));
+            }
+            if response.status() != StatusCode::NOT_FOUND {
+                return Err(response);
+            }
+        }
+        let dir = target.as_dir();
+        let Target::Object { key: prefix, .. } = &dir else {
+            unreachable!("a directory of an object is an object");
+        };
+        let exists = self.s3.any_under(caller, bucket, prefix).await?;
+        Ok(exists.then_some(dir))
+    }
+
+    async fn propfind(&self, caller: &Caller, target: &Target, headers: &HeaderMap) -> Response {
+        // Infinite depth is answered like depth 1
+        let depth_zero = headers.get("depth").and_then(|v| v.to_str().ok()) == Some("0");
+
+        let resources = match target {
+            Target::Root => {
+                let mut resources = vec![Resource::collection(self.href(target), None)];
+                if !depth_zero {
+                    let response = self
+                        .s3
+                        .call(caller, Method::GET, "/", HeaderMap::new(), Body::empty())
+                        .await;
+                    let list: BucketList = match xml_body(response).await {
+                        Ok(list) => list,
+                        Err(response) => return response,
+                    };
+                    resources.extend(list.buckets.bucket.iter().map(|bucket| {
+                        Resource::collection(
+                            self.href(&Target::Bucket(bucket.name.clone())),
+                            bucket.created(),
+                        )
+                    }));
+                }
+                resources
+            }
+            Target::Bucket(bucket) => {
+                let response = self
+                    .s3
+                    .call(
+                        caller,
+                        Method::HEAD,
+                        &format!("/{}", urlencoding::encode(bucket)),
+                        HeaderMap::new(),
+                        Body::empty(),
+                    )
+                    .await;
+                if !response.status().is_success() {
+                    return response.status().into_response();
+                }
+                let mut resources = vec![Resource::collection(self.href(target), None)];
+                if !depth_zero {
+                    match self.children(caller, bucket, "").await {
+                        Ok(children) => resources.extend(children.unwrap_or_default()),
+                        Err(response) => return response,
+                    }
+                }
+                resources
+            }
+            Target::Object { bucket, key } if !key.ends_with('/') => {
+                let response = self.s3.head(caller, bucket, key).await;
+                if response.status().is_success() {
+                    return multistatus(xml::multistatus(&[file_resource(
+                        self.href(target),
+                        response.headers(),
+                    )]));
+                }
+                if response.status() != StatusCode::NOT_FOUND {
+                    return response.status().into_response();
+                }
+                return Box::pin(self.propfind(caller, &target.as_dir(), headers)).await;
+            }
+            Target::Object { bucket, key } => {
+                let children = match self.children(caller, bucket, key).await {
+                    Ok(Some(children)) => children,
+                    Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+                    Err(response) => return response,
+                };
+                let mut resources = vec![Resource::collection(self.href(target), None)];
+                if !depth_zero {
+                    resources.extend(children);
+                }
+                resources
+            }
+        };
+        multistatus(xml::multistatus(&resources))
+    }
+
+    async fn get(&self, caller: &Caller, target: &Target, request: Request<Body>) -> Response {
+        let Target::Object { bucket, key } = target else {
+            return StatusCode::METHOD_NOT_ALLOWED.into_response();
+        };
+        if target.is_dir() {
+            return StatusCode::METHOD_NOT_ALLOWED.into_response();
+        }
+        let mut headers = HeaderMap::new();
+        for name in CONDITIONAL_HEADERS {
+            if let Some(value) = request.headers().get(&name) {
+                headers.insert(name, value.clone());
+            }
+        }
+        let method = request.method().clone();
+        self.s3
+            .call(
+                caller,
+                method,
+                &s3_path(bucket, key),
+                headers,
+                Body::empty(),
+            )
+            .await
+    }
+
+    async fn put(&self, caller: &Caller, target: &Target, request: Request<Body>) -> Response {
+        let Target::Object { bucket, key } = target else {
+            return StatusCode::METHOD_NOT_ALLOWED.into_response();
+        };
+        if target.is_dir() {
+            return StatusCode::METHOD_NOT_ALLOWED.into_response();
+        }
+
+        let mut headers = HeaderMap::new();
+        let content_type = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .cloned()
+            .or_else(|| {
+                HeaderValue::from_str(mime_guess::from_path(key).first_or_octet_stream().as_ref())
+                    .ok()
+            });
+        if let Some(content_type) = content_type {
+            headers.insert(header::CONTENT_TYPE, content_type);
+        }
+        // Finder streams uploads chunked, announcing the size separately
+        let length = request
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .or_else(|| request.headers().get("x-expected-entity-length"))
+            .cloned();
+        if let Some(length) = length {
+            headers.insert(header::CONTENT_LENGTH, length);
+        }
+
+        let response = self
+            .s3
+            .call(
+                caller,
+                Method::PUT,
+                &s3_path(bucket, key),
+                headers,
+                request.into_body(),
+            )
+            .await;
+        if let Some(path) = target.cache_path() {
+            self.with_cache(caller, |cache| cache.invalidate_entry(&path));
+        }
+        if response.status().is_success() {
+            StatusCode::CREATED.into_response()
+        } else {
+            response
+        }
+    }
+
+    async fn mkcol(&self, caller: &Caller, target: &Target, headers: &HeaderMap) -> Response {
+        let has_body = headers
+            .get(header::CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v != "0");
+        if has_body {
+            return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+        }
+
+        let (uri, headers) = match target {
+            Target::Root => return StatusCode::METHOD_NOT_ALLOWED.into_response(),
+            Target::Bucket(bucket) => (
+                format!("/{}", urlencoding::encode(bucket)),
+                HeaderMap::new(),
+            ),
+            Target::Object { bucket, .. } => {
+                let dir = target.as_dir();
+                match self.resolve(caller, &dir).await {
+                    Ok(Some(_)) => return StatusCode::METHOD_NOT_ALLOWED.into_response(),
+                    Ok(None) => {}
+                    Err(response) => return response,
+                }
+                let Target::Object { key, .. } = &dir else {
+                    unreachable!("a directory of an object is an object");
+                };
+                let mut headers = HeaderMap::new();
+                headers.insert(header::CONTENT_LENGTH, HeaderValue::from_static("0"));
+                (s3_path(bucket, key), headers)
+            }
+        };
+
+        let response = self
+            .s3
+            .call(caller, Method::PUT, &uri, headers, Body::empty())
+            .await;
+        match response.status() {
+            status if status.is_success() => {
+                if let (Target::Object { .. }, Some(path)) = (target, target.cache_path()) {
+                    self.with_cache(caller, |cache| {
+                        cache.put_entry(&path, DirEntry::dir());
+                        cache.insert(&path, Listing::created());
+                    });
+                }
+                StatusCode::CREATED.into_response()
+            }
+            StatusCode::CONFLICT => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+            _ => response,
+        }
+    }
+
+    /// Delete a file, a directory with everything in it, or an empty bucket
+    async fn delete(&self, caller: &Caller, target: &Target) -> Response {
+        let resolved = match self.resolve(caller, target).await {
+            Ok(Some(resolved)) => resolved,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(response) => return response,
+        };
+        let keys = match &resolved {
+            Target::Root => return StatusCode::FORBIDDEN.into_response(),
+            Target::Bucket(bucket) => {
+                let uri = format!("/{}", urlencoding::encode(bucket));
+                let response = self
+                    .s3
+                    .call(
+                        caller,
+                        Method::DELETE,
+                        &uri,
+                        HeaderMap::new(),
+                        Body::empty(),
+                    )
+                    .await;
+                return if response.status().is_success() {
+                    self.with_cache(caller, |cache| cache.remove_entry(bucket));
+                    StatusCode::NO_CONTENT.into_response()
+                } else {
+                    response
+                };
+            }
+            Target::Object { bucket, key } if key.ends_with('/') => {
+                match self.s3.keys_under(caller, bucket, key).await {
+                    // The marker goes last, so the directory stays until
+                    // everything in it is gone
+                    Ok(mut keys) => {
+                        keys.sort_by_key(|k| k == key);
+                        keys
+                    }
+                    Err(response) => return response,
+                }
+            }
+            Target::Object { key, .. } => vec![key.clone()],
+        };
+
+        let Target::Object { bucket, .. } = &resolved else {
+            unreachable!("only objects are left");
+        };
+        let path = resolved.cache_path().expect("objects have cache paths");
+        for key in keys {
+            let response = self.s3.delete(caller, bucket, &key).await;
+            if !response.status().is_success() {
+                self.with_cache(caller, |cache| cache.invalidate_entry(&path));
+                return response;
+            }
+        }
+        if let Err(response) = self.keep_parent(caller, &path).await {
+            return response;
+        }
+        self.with_cache(caller, |cache| cache.remove_entry(&path));
+        StatusCode::NO_CONTENT.into_response()
+    }
+
+    /// COPY, or MOVE when `remove_source`, of a file or a directory tree
+    ///
+    /// Buckets cannot be copied or renamed.
+    async fn copy(
+        &self,
+        caller: &Caller,
+        target: &Target,
+        headers: &HeaderMap,
+        remove_source: bool,
+    ) -> Response {
+        let Some(destination) = self.destination(headers) else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+        let source = match self.resolve(caller, target).await {
+            Ok(Some(source @ Target::Object { .. })) => source,
+            Ok(Some(_)) => return StatusCode::FORBIDDEN.into_response(),
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(response) => return response,
+        };
+        let destination = if source.is_dir() {
+            destination.as_dir()
+        } else {
+            destination
+        };
+        let (
+            Target::Object {
+                bucket: source_bucket,
+                key: source_key,
+            },
+            Target::Object {
+                bucket: dest_bucket,
+                key: dest_key,
+            },
+        ) = (&source, &destination)
+        else {
+            return StatusCode::FORBIDDEN.into_response();
+        };
+        if source == destination
+            || (source.is_dir()
+                && source_bucket == dest_bucket
+                && dest_key.starts_with(source_key.as_str()))
+        {
+            return StatusCode::FORBIDDEN.into_response();
+        }
+
+        let existed = match self.resolve(caller, &destination).await {
+            Ok(existing) => existing.is_some(),
+            Err(response) => return response,
+        };
+        let overwrite = headers.get("overwrite").and_then(|v| v.to_str().ok()) != Some("F");
+        if existed && !overwrite {
+            return StatusCode::PRECONDITION_FAILED.into_response();
+        }
+
+        let plan = if source.is_dir() {
+            match self.s3.keys_under(caller, source_bucket, source_key).await {
+                Ok(keys) => TreeCopy::dir(source_key, dest_key, keys, remove_source),
+                Err(response) => return response,
+            }
+        } else {
+            TreeCopy::file(source_key, dest_key, remove_source)
+        };
+        let store = LoopbackTree {
+            s3: &self.s3,
+            caller,
+            from_bucket: source_bucket,
+            to_bucket: dest_bucket,
+        };
+        let (from, to) = (
+            format!("{}/{}", source_bucket, source_key),
+            format!("{}/{}", dest_bucket, dest_key),
+        );
+        let result = plan.run(&store, log_progress("WebDAV", &from, &to)).await;
+
+        let source_path = source.cache_path().expect("objects have cache paths");
+        let dest_path = destination.cache_path().expect("objects have cache paths");
+        self.with_cache(caller, |cache| cache.invalidate_entry(&dest_path));
+        if let Err(response) = result {
+            self.with_cache(caller, |cache| cache.invalidate_entry(&source_path));
+            return response;
+        }
+        if remove_source {
+            if let Err(response) = self.keep_parent(caller, &source_path).await {
+                return response;
+            }
+            self.with_cache(caller, |cache| cache.remove_entry(&source_path));
+        }
+
+        if existed {
+            StatusCode::NO_CONTENT.into_response()
+        } else {
+            StatusCode::CREATED.into_response()
+        }
+    }
+
+    /// Target of the `Destination` header, a URL or an absolute path
+    fn destination(&self, headers: &HeaderMap) -> Option<Target> {
+        let value = headers.get("destination")?.to_str().ok()?;
+        let path = match url::Url::parse(value) {
+            Ok(url) => url.path().to_string(),
+            Err(_) => value.to_string(),
+        };
+        let path = path.strip_prefix(self.prefix.as_str())?;
+        if !path.is_empty() && !path.starts_with('/') {
+            return None;
+        }
+        Target::parse(path)
+    }
+}
+
+/// PROPFIND entry of a file from its HEAD response
+fn file_resource(href: String, headers: &HeaderMap) -> Resource {
+    let header = |name: header::HeaderName| headers.get(name).and_then(|v| v.to_str().ok());
+    let modified = header(header::LAST_MODIFIED)
+        .and_then(|v| chrono::DateTime::parse_from_rfc2822(v).ok())
+        .map(|d| d.with_timezone(&chrono::Utc));
+    let size = header(header::CONTENT_LENGTH)
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    Resource {
+        content_type: header(header::CONTENT_TYPE).map(str::to_string),
+        ..Resource::file(
+            href,
+            size,
+            modified,
+            header(header::ETAG).map(str::to_string),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn object(bucket: &str, key: &str) -> Target {
+        Target::Object {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_paths() {
+        assert_eq!(Target::parse(""), Some(Target::Root));
+        assert_eq!(Target::parse("/"), Some(Target::Root));
+        assert_eq!(
+            Target::parse("/photos"),
+            Some(Target::Bucket("photos".into()))
+        );
+        assert_eq!(
+            Target::parse("/photos/"),
+            Some(Target::Bucket("photos".into()))
+        );
+        assert_eq!(
+            Target::parse("/photos/2024/a.jpg"),
+            Some(object("photos", "2024/a.jpg"))
+        );
+        assert_eq!(
+            Target::parse("/photos/2024/"),
+            Some(object("photos", "2024/"))
+        );
+        assert_eq!(
+            Target::parse("/photos/my%20trip/caf%C3%A9.jpg"),
+            Some(object("photos", "my trip/café.jpg"))
+        );
+    }
+
+    #[test]
+    fn test_rejects_unsafe_paths() {
+        assert_eq!(Target::parse("/photos/../secrets"), None);
+        assert_eq!(Target::parse("/photos/a/./b"), None);
+        assert_eq!(Target::parse("/photos/a%2Fb"), None);
+        assert_eq!(Target::parse("/photos/a//b"), None);
+    }
+
+    #[test]
+    fn test_paths_round_trip() {
+        assert_eq!(
+            s3_path("photos", "my trip/a&b.jpg"),
+            "/photos/my%20trip/a%26b.jpg"
+        );
+        let target = object("photos", "my trip/a&b.jpg");
+        assert_eq!(
+            Target::parse(&s3_path("photos", "my trip/a&b.jpg")),
+            Some(target.clone())
+        );
+        assert_eq!(target.as_dir(), object("photos", "my trip/a&b.jpg/"));
+        assert!(target.as_dir().is_dir());
+        assert!(!target.is_dir());
+    }
+}
@@ -0,0 +1,151 @@
+//! WebDAV response bodies
+
+use chrono::{DateTime, Utc};
+use hafiz_core::utils::format_http_datetime;
+
+use crate::xml::xml_escape;
+
+/// A file or collection in a PROPFIND response
+#[derive(Debug, Clone, PartialEq)]
+pub struct Resource {
+    /// Percent-encoded path; collections end with `/`
+    pub href: String,
+    pub collection: bool,
+    pub size: u64,
+    pub modified: Option<DateTime<Utc>>,
+    pub etag: Option<String>,
+    pub content_type: Option<String>,
+}
+
+impl Resource {
+    pub fn collection(href: String, modified: Option<DateTime<Utc>>) -> Self {
+        Self {
+            href,
+            collection: true,
+            size: 0,
+            modified,
+            etag: None,
+            content_type: None,
+        }
+    }
+
+    pub fn file(
+        href: String,
+        size: u64,
+        modified: Option<DateTime<Utc>>,
+        etag: Option<String>,
+    ) -> Self {
+        Self {
+            href,
+            collection: false,
+            size,
+            modified,
+            etag,
+            content_type: None,
+        }
+    }
+}
+
+/// `207 Multi-Status` body listing every property of `resources`
+///
+/// Requested property names are not looked at: the few live properties a
+/// bucket can back are always returned, which clients accept for `prop`
+/// as well as `allprop` requests.
+pub fn multistatus(resources: &[Resource]) -> String {
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n",
+    );
+    for resource in resources {
+        xml.push_str("<D:response><D:href>");
+        xml.push_str(&xml_escape(&resource.href));
+        xml.push_str("</D:href><D:propstat><D:prop>");
+        if resource.collection {
+            xml.push_str("<D:resourcetype><D:collection/></D:resourcetype>");
+        } else {
+            xml.push_str("<D:resourcetype/>");
+            xml.push_str(&format!(
+                "<D:getcontentlength>{}</D:getcontentlength>",
+                resource.size
+            ));
+            let content_type = resource
+                .content_type
+                .as_deref()
+                .unwrap_or("application/octet-stream");
+            xml.push_str(&format!(
+                "<D:getcontenttype>{}</D:getcontenttype>",
+                xml_escape(content_type)
+            ));
+        }
+        if let Some(modified) = &resource.modified {
+            xml.push_str(&format!(
+                "<D:getlastmodified>{}</D:getlastmodified>",
+                format_http_datetime(modified)
+            ));
+        }
+        if let Some(etag) = &resource.etag {
+            xml.push_str(&format!("<D:getetag>{}</D:getetag>", xml_escape(etag)));
+        }
+        xml.push_str("</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n");
+    }
+    xml.push_str("</D:multistatus>");
+    xml
+}
+
+/// `207 Multi-Status` body for PROPPATCH, reporting every change as made
+///
+/// Dead properties are not stored; Windows sets its file times this way
+/// and treats a refusal as a failed copy.
+pub fn proppatch(href: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">\n\
+         <D:response><D:href>{}</D:href><D:propstat><D:prop/>\
+         <D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>\n</D:multistatus>",
+        xml_escape(href)
+    )
+}
+
+/// Body of a granted LOCK
+pub fn lock_discovery(href: &str, token: &str, timeout_secs: u64) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>\
+         <D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>\
+         <D:depth>0</D:depth><D:timeout>Second-{}</D:timeout>\
+         <D:locktoken><D:href>{}</D:href></D:locktoken>\
+         <D:lockroot><D:href>{}</D:href></D:lockroot>\
+         </D:activelock></D:lockdiscovery></D:prop>",
+        timeout_secs,
+        xml_escape(token),
+        xml_escape(href)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_multistatus() {
+        let modified = Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap();
+        let xml = multistatus(&[
+            Resource::collection("/photos/".into(), None),
+            Resource::file(
+                "/photos/a%26b.jpg".into(),
+                42,
+                Some(modified),
+                Some("\"abc\"".into()),
+            ),
+        ]);
+
+        assert!(xml.contains(
+            "<D:href>/photos/</D:href><D:propstat><D:prop><D:resourcetype><D:collection/>"
+        ));
+        assert!(xml.contains("<D:href>/photos/a%26b.jpg</D:href>"));
+        assert!(xml.contains("<D:getcontentlength>42</D:getcontentlength>"));
+        assert!(
+            xml.contains("<D:getlastmodified>Fri, 01 Mar 2024 12:00:00 GMT</D:getlastmodified>")
+        );
+        assert!(xml.contains("<D:getetag>&quot;abc&quot;</D:getetag>"));
+        assert_eq!(xml.matches("<D:response>").count(), 2);
+    }
+}
@@ -3,8 +3,14 @@
 use hafiz_core::types::{BucketInfo, ListObjectsResult};
 use hafiz_core::utils::format_s3_datetime;
 
-/// Generate ListBuckets response XML
-pub fn list_buckets_response(buckets: &[BucketInfo], owner_id: &str) -> String {
+/// Generate ListBuckets response XML; `continuation_token` resumes the
+/// listing when more buckets follow
+pub fn list_buckets_response(
+    buckets: &[BucketInfo],
+    owner_id: &str,
+    prefix: Option<&str>,
+    continuation_token: Option<&str>,
+) -> String {
     let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>
 <ListAllMyBucketsResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
   <Owner>
@@ -22,12 +28,19 @@ pub fn list_buckets_response(buckets: &[BucketInfo], owner_id: &str) -> String {
         xml.push_str(&bucket.name);
         xml.push_str("</Name>\n      <CreationDate>");
         xml.push_str(&format_s3_datetime(&bucket.creation_date));
-        xml.push_str("</CreationDate>\n    </Bucket>");
+        xml.push_str("</CreationDate>\n      <BucketRegion>");
+        xml.push_str(&xml_escape(&bucket.region));
+        xml.push_str("</BucketRegion>\n    </Bucket>");
     }
 
-    xml.push_str(r#"
-  </Buckets>
-</ListAllMyBucketsResult>"#);
+    xml.push_str("\n  </Buckets>");
+    if let Some(token) = continuation_token {
+        xml.push_str(&format!("\n  <ContinuationToken>{}</ContinuationToken>", xml_escape(token)));
+    }
+    if let Some(prefix) = prefix {
+        xml.push_str(&format!("\n  <Prefix>{}</Prefix>", xml_escape(prefix)));
+    }
+    xml.push_str("\n</ListAllMyBucketsResult>");
 
     xml
 }
@@ -40,48 +53,33 @@ pub fn list_objects_response(result: &ListObjectsResult) -> String {
     xml.push_str(&result.name);
     xml.push_str("</Name>\n");
 
-    if let Some(ref prefix) = result.prefix {
-        xml.push_str("  <Prefix>");
-        xml.push_str(prefix);
-        xml.push_str("</Prefix>\n");
-    } else {
-        xml.push_str("  <Prefix></Prefix>\n");
+    xml.push_str("  <Prefix>");
+    xml.push_str(&list_value(result, result.prefix.as_deref().unwrap_or("")));
+    xml.push_str("</Prefix>\n");
+
+    xml.push_str("  <Marker>");
+    xml.push_str(&list_value(result, result.continuation_token.as_deref().unwrap_or("")));
+    xml.push_str("</Marker>\n");
+
+    // Only sent with a delimiter; otherwise clients resume after the last key
+    if result.is_truncated && result.delimiter.is_some() {
+        if let Some(ref marker) = result.next_continuation_token {
+            xml.push_str("  <NextMarker>");
+            xml.push_str(&list_value(result, marker));
+            xml.push_str("</NextMarker>\n");
+        }
     }
 
     if let Some(ref delimiter) = result.delimiter {
         xml.push_str("  <Delimiter>");
-        xml.push_str(delimiter);
+        xml.push_str(&list_value(result, delimiter));
         xml.push_str("</Delimiter>\n");
     }
 
     xml.push_str(&format!("  <MaxKeys>{}</MaxKeys>\n", result.max_keys));
     xml.push_str(&format!("  <IsTruncated>{}</IsTruncated>\n", result.is_truncated));
-
-    for obj in &result.contents {
-        xml.push_str("  <Contents>\n");
-        xml.push_str("    <Key>");
-        xml.push_str(&xml_escape(&obj.key));
-        xml.push_str("</Key>\n");
-        xml.push_str("    <LastModified>");
-        xml.push_str(&format_s3_datetime(&obj.last_modified));
-        xml.push_str("</LastModified>\n");
-        xml.push_str("    <ETag>\"");
-        xml.push_str(&obj.etag);
-        xml.push_str("\"</ETag>\n");
-        xml.push_str(&format!("    <Size>{}</Size>\n", obj.size));
-        xml.push_str("    <StorageClass>");
-        xml.push_str(&obj.storage_class);
-        xml.push_str("</StorageClass>\n");
-        xml.push_str("  </Contents>\n");
-    }
-
-    for prefix in &result.common_prefixes {
-        xml.push_str("  <CommonPrefixes>\n");
-        xml.push_str("    <Prefix>");
-        xml.push_str(&xml_escape(prefix));
-        xml.push_str("</Prefix>\n");
-        xml.push_str("  </CommonPrefixes>\n");
-    }
+    push_encoding_type(&mut xml, result);
+    push_list_entries(&mut xml, result);
 
     xml.push_str("</ListBucketResult>");
     xml
@@ -95,40 +93,61 @@ pub fn list_objects_v2_response(result: &ListObjectsResult) -> String {
     xml.push_str(&result.name);
     xml.push_str("</Name>\n");
 
-    if let Some(ref prefix) = result.prefix {
-        xml.push_str("  <Prefix>");
-        xml.push_str(prefix);
-        xml.push_str("</Prefix>\n");
-    } else {
-        xml.push_str("  <Prefix></Prefix>\n");
-    }
+    xml.push_str("  <Prefix>");
+    xml.push_str(&list_value(result, result.prefix.as_deref().unwrap_or("")));
+    xml.push_str("</Prefix>\n");
 
     if let Some(ref delimiter) = result.delimiter {
         xml.push_str("  <Delimiter>");
-        xml.push_str(delimiter);
+        xml.push_str(&list_value(result, delimiter));
         xml.push_str("</Delimiter>\n");
     }
 
+    // KeyCount covers common prefixes as well as keys
+    let key_count = result.contents.len() + result.common_prefixes.len();
     xml.push_str(&format!("  <MaxKeys>{}</MaxKeys>\n", result.max_keys));
-    xml.push_str(&format!("  <KeyCount>{}</KeyCount>\n", result.contents.len()));
+    xml.push_str(&format!("  <KeyCount>{}</KeyCount>\n", key_count));
     xml.push_str(&format!("  <IsTruncated>{}</IsTruncated>\n", result.is_truncated));
+    push_encoding_type(&mut xml, result);
 
     if let Some(ref token) = result.continuation_token {
         xml.push_str("  <ContinuationToken>");
-        xml.push_str(token);
+        xml.push_str(&xml_escape(token));
         xml.push_str("</ContinuationToken>\n");
     }
 
     if let Some(ref token) = result.next_continuation_token {
         xml.push_str("  <NextContinuationToken>");
-        xml.push_str(token);
+        xml.push_str(&xml_escape(token));
         xml.push_str("</NextContinuationToken>\n");
     }
 
+    if let Some(ref start_after) = result.start_after {
+        xml.push_str("  <StartAfter>");
+        xml.push_str(&list_value(result, start_after));
+        xml.push_str("</StartAfter>\n");
+    }
+
+    push_list_entries(&mut xml, result);
+
+    xml.push_str("</ListBucketResult>");
+    xml
+}
+
+fn push_encoding_type(xml: &mut String, result: &ListObjectsResult) {
+    if let Some(ref encoding_type) = result.encoding_type {
+        xml.push_str("  <EncodingType>");
+        xml.push_str(&xml_escape(encoding_type));
+        xml.push_str("</EncodingType>\n");
+    }
+}
+
+/// Contents and CommonPrefixes of a listing
+fn push_list_entries(xml: &mut String, result: &ListObjectsResult) {
     for obj in &result.contents {
         xml.push_str("  <Contents>\n");
         xml.push_str("    <Key>");
-        xml.push_str(&xml_escape(&obj.key));
+        xml.push_str(&list_value(result, &obj.key));
         xml.push_str("</Key>\n");
         xml.push_str("    <LastModified>");
         xml.push_str(&format_s3_datetime(&obj.last_modified));
@@ -137,6 +156,13 @@ pub fn list_objects_v2_response(result: &ListObjectsResult) -> String {
         xml.push_str(&obj.etag);
         xml.push_str("\"</ETag>\n");
         xml.push_str(&format!("    <Size>{}</Size>\n", obj.size));
+        if let Some(ref owner) = result.owner {
+            xml.push_str("    <Owner>\n      <ID>");
+            xml.push_str(&xml_escape(&owner.id));
+            xml.push_str("</ID>\n      <DisplayName>");
+            xml.push_str(&xml_escape(owner.display_name.as_deref().unwrap_or(&owner.id)));
+            xml.push_str("</DisplayName>\n    </Owner>\n");
+        }
         xml.push_str("    <StorageClass>");
         xml.push_str(&obj.storage_class);
         xml.push_str("</StorageClass>\n");
@@ -146,21 +172,54 @@ pub fn list_objects_v2_response(result: &ListObjectsResult) -> String {
     for prefix in &result.common_prefixes {
         xml.push_str("  <CommonPrefixes>\n");
         xml.push_str("    <Prefix>");
-        xml.push_str(&xml_escape(prefix));
+        xml.push_str(&list_value(result, prefix));
         xml.push_str("</Prefix>\n");
         xml.push_str("  </CommonPrefixes>\n");
     }
+}
 
-    xml.push_str("</ListBucketResult>");
-    xml
+/// Key, prefix or marker as written in a listing
+fn list_value(result: &ListObjectsResult, value: &str) -> String {
+    encoded_value(result.encoding_type.as_deref(), value)
 }
 
-fn xml_escape(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-        .replace('"', "&quot;")
-        .replace('\'', "&apos;")
+/// Key, prefix or marker as written in a listing requested with
+/// `encoding_type`
+///
+/// With `encoding-type=url` the value is URL-encoded so keys with
+/// characters XML cannot carry survive; `/` is left as is.
+fn encoded_value(encoding_type: Option<&str>, value: &str) -> String {
+    if encoding_type == Some("url") {
+        urlencoding::encode(value).replace("%2F", "/")
+    } else {
+        xml_escape(value)
+    }
+}
+
+/// Escape `s` for XML element content
+///
+/// Control characters become character references, which parsers hand
+/// back unchanged where a literal `\r` or tab would be normalized away.
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if c.is_control() => escaped.push_str(&format!("&#x{:X};", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn push_encoding_type_element(xml: &mut String, encoding_type: Option<&str>) {
+    if let Some(encoding_type) = encoding_type {
+        xml.push_str(&format!("\n  <EncodingType>{}</EncodingType>", xml_escape(encoding_type)));
+    }
 }
 
 // ============= Phase 2: Advanced XML Operations =============
@@ -182,6 +241,18 @@ pub fn copy_object_response(etag: &str, last_modified: &DateTime<Utc>) -> String
     )
 }
 
+pub fn copy_part_response(etag: &str, last_modified: &DateTime<Utc>) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<CopyPartResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <LastModified>{}</LastModified>
+  <ETag>"{}"</ETag>
+</CopyPartResult>"#,
+        format_s3_datetime(last_modified),
+        etag
+    )
+}
+
 // ============= Delete Objects =============
 
 #[derive(Debug, Deserialize)]
@@ -236,6 +307,11 @@ pub fn delete_objects_response(deleted: &[DeletedObject], errors: &[DeleteError]
         if d.delete_marker {
             xml.push_str("\n    <DeleteMarker>true</DeleteMarker>");
         }
+        if let Some(ref marker_vid) = d.delete_marker_version_id {
+            xml.push_str("\n    <DeleteMarkerVersionId>");
+            xml.push_str(marker_vid);
+            xml.push_str("</DeleteMarkerVersionId>");
+        }
         xml.push_str("\n  </Deleted>");
     }
 
@@ -403,6 +479,9 @@ pub fn list_multipart_uploads_response(
     max_uploads: i32,
     is_truncated: bool,
     uploads: &[UploadInfo],
+    next_key_marker: Option<&str>,
+    next_upload_id_marker: Option<&str>,
+    encoding_type: Option<&str>,
 ) -> String {
     let mut xml = format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -412,17 +491,17 @@ pub fn list_multipart_uploads_response(
     );
 
     if let Some(p) = prefix {
-        xml.push_str(&format!("\n  <Prefix>{}</Prefix>", xml_escape(p)));
+        xml.push_str(&format!("\n  <Prefix>{}</Prefix>", encoded_value(encoding_type, p)));
     } else {
         xml.push_str("\n  <Prefix></Prefix>");
     }
 
     if let Some(d) = delimiter {
-        xml.push_str(&format!("\n  <Delimiter>{}</Delimiter>", xml_escape(d)));
+        xml.push_str(&format!("\n  <Delimiter>{}</Delimiter>", encoded_value(encoding_type, d)));
     }
 
     if let Some(km) = key_marker {
-        xml.push_str(&format!("\n  <KeyMarker>{}</KeyMarker>", xml_escape(km)));
+        xml.push_str(&format!("\n  <KeyMarker>{}</KeyMarker>", encoded_value(encoding_type, km)));
     } else {
         xml.push_str("\n  <KeyMarker></KeyMarker>");
     }
@@ -439,6 +518,15 @@ pub fn list_multipart_uploads_response(
   <IsTruncated>{}</IsTruncated>"#,
         max_uploads, is_truncated
     ));
+    push_encoding_type_element(&mut xml, encoding_type);
+
+    if let Some(nkm) = next_key_marker {
+        xml.push_str(&format!("\n  <NextKeyMarker>{}</NextKeyMarker>", encoded_value(encoding_type, nkm)));
+    }
+
+    if let Some(num) = next_upload_id_marker {
+        xml.push_str(&format!("\n  <NextUploadIdMarker>{}</NextUploadIdMarker>", num));
+    }
 
     for upload in uploads {
         xml.push_str(&format!(
@@ -457,7 +545,7 @@ pub fn list_multipart_uploads_response(
     <StorageClass>{}</StorageClass>
     <Initiated>{}</Initiated>
   </Upload>"#,
-            xml_escape(&upload.key),
+            encoded_value(encoding_type, &upload.key),
             upload.upload_id,
             upload.initiator_id,
             upload.initiator_id,
@@ -474,7 +562,7 @@ pub fn list_multipart_uploads_response(
 
 // ============= Bucket Versioning =============
 
-use hafiz_core::types::{VersioningStatus, ObjectVersion, DeleteMarker};
+use hafiz_core::types::{AccelerateStatus, VersioningStatus, ObjectVersion, DeleteMarker};
 
 /// Generate GetBucketVersioning response XML
 pub fn get_bucket_versioning_response(status: &VersioningStatus) -> String {
@@ -512,6 +600,51 @@ pub fn parse_versioning_configuration(body: &[u8]) -> Result<VersioningStatus, q
     })
 }
 
+/// Generate GetBucketAccelerateConfiguration response XML
+pub fn get_bucket_accelerate_response(status: Option<AccelerateStatus>) -> String {
+    match status {
+        // Never configured: empty configuration, as S3 returns
+        None => r#"<?xml version="1.0" encoding="UTF-8"?>
+<AccelerateConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/"/>"#.to_string(),
+        Some(status) => format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<AccelerateConfiguration xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Status>{}</Status>
+</AccelerateConfiguration>"#,
+            status.as_str()
+        ),
+    }
+}
+
+/// Parse PutBucketAccelerateConfiguration request XML; the status is None
+/// when missing or not Enabled or Suspended
+pub fn parse_accelerate_configuration(body: &[u8]) -> Result<Option<AccelerateStatus>, quick_xml::DeError> {
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct AccelerateConfiguration {
+        status: Option<String>,
+    }
+
+    let xml_str = String::from_utf8_lossy(body);
+    let config: AccelerateConfiguration = from_str(&xml_str)?;
+
+    Ok(config.status.as_deref().and_then(AccelerateStatus::parse))
+}
+
+/// RestoreObject request body
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct RestoreRequest {
+    /// Days the restored copy is kept
+    pub days: Option<i32>,
+}
+
+/// Parse RestoreObject request XML
+pub fn parse_restore_request(body: &[u8]) -> Result<RestoreRequest, quick_xml::DeError> {
+    let xml_str = String::from_utf8_lossy(body);
+    from_str(&xml_str)
+}
+
 /// Generate ListObjectVersions response XML
 pub fn list_object_versions_response(
     bucket: &str,
@@ -526,6 +659,7 @@ pub fn list_object_versions_response(
     common_prefixes: &[String],
     next_key_marker: Option<&str>,
     next_version_id_marker: Option<&str>,
+    encoding_type: Option<&str>,
 ) -> String {
     let mut xml = format!(
         r#"<?xml version="1.0" encoding="UTF-8"?>
@@ -535,13 +669,13 @@ pub fn list_object_versions_response(
     );
 
     if let Some(p) = prefix {
-        xml.push_str(&format!("\n  <Prefix>{}</Prefix>", xml_escape(p)));
+        xml.push_str(&format!("\n  <Prefix>{}</Prefix>", encoded_value(encoding_type, p)));
     } else {
         xml.push_str("\n  <Prefix></Prefix>");
     }
 
     if let Some(km) = key_marker {
-        xml.push_str(&format!("\n  <KeyMarker>{}</KeyMarker>", xml_escape(km)));
+        xml.push_str(&format!("\n  <KeyMarker>{}</KeyMarker>", encoded_value(encoding_type, km)));
     } else {
         xml.push_str("\n  <KeyMarker></KeyMarker>");
     }
@@ -558,9 +692,10 @@ pub fn list_object_versions_response(
   <IsTruncated>{}</IsTruncated>"#,
         max_keys, is_truncated
     ));
+    push_encoding_type_element(&mut xml, encoding_type);
 
     if let Some(nkm) = next_key_marker {
-        xml.push_str(&format!("\n  <NextKeyMarker>{}</NextKeyMarker>", xml_escape(nkm)));
+        xml.push_str(&format!("\n  <NextKeyMarker>{}</NextKeyMarker>", encoded_value(encoding_type, nkm)));
     }
 
     if let Some(nvim) = next_version_id_marker {
@@ -568,7 +703,7 @@ pub fn list_object_versions_response(
     }
 
     if let Some(d) = delimiter {
-        xml.push_str(&format!("\n  <Delimiter>{}</Delimiter>", xml_escape(d)));
+        xml.push_str(&format!("\n  <Delimiter>{}</Delimiter>", encoded_value(encoding_type, d)));
     }
 
     // Add versions
@@ -588,7 +723,7 @@ pub fn list_object_versions_response(
     </Owner>
     <StorageClass>{}</StorageClass>
   </Version>"#,
-            xml_escape(&v.key),
+            encoded_value(encoding_type, &v.key),
             v.version_id,
             v.is_latest,
             format_s3_datetime(&v.last_modified),
@@ -614,7 +749,7 @@ pub fn list_object_versions_response(
       <DisplayName>{}</DisplayName>
     </Owner>
   </DeleteMarker>"#,
-            xml_escape(&dm.key),
+            encoded_value(encoding_type, &dm.key),
             dm.version_id,
             dm.is_latest,
             format_s3_datetime(&dm.last_modified),
@@ -630,7 +765,7 @@ pub fn list_object_versions_response(
   <CommonPrefixes>
     <Prefix>{}</Prefix>
   </CommonPrefixes>"#,
-            xml_escape(cp)
+            encoded_value(encoding_type, cp)
         ));
     }
 
@@ -927,3 +1062,148 @@ pub fn parse_lifecycle_configuration(body: &[u8]) -> Result<LifecycleConfigurati
 
     Ok(lifecycle)
 }
+
+// ============= Access Control Lists =============
+
+use hafiz_core::types::{AccessControlList, AccessControlPolicy, Grant, Grantee, Owner};
+
+/// Parse a stored or submitted AccessControlPolicy document
+pub fn parse_access_control_policy(body: &[u8]) -> Result<AccessControlPolicy, quick_xml::DeError> {
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct PolicyXml {
+        owner: OwnerXml,
+        #[serde(default)]
+        access_control_list: AclXml,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct OwnerXml {
+        #[serde(rename = "ID")]
+        id: String,
+        #[serde(rename = "DisplayName")]
+        display_name: Option<String>,
+    }
+
+    #[derive(Debug, Default, Deserialize)]
+    struct AclXml {
+        #[serde(rename = "Grant", default)]
+        grants: Vec<GrantXml>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(rename_all = "PascalCase")]
+    struct GrantXml {
+        grantee: GranteeXml,
+        permission: String,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct GranteeXml {
+        #[serde(rename = "ID")]
+        id: Option<String>,
+        #[serde(rename = "DisplayName")]
+        display_name: Option<String>,
+        #[serde(rename = "EmailAddress")]
+        email_address: Option<String>,
+        #[serde(rename = "URI")]
+        uri: Option<String>,
+    }
+
+    let xml_str = String::from_utf8_lossy(body);
+    let parsed: PolicyXml = from_str(&xml_str)?;
+
+    let mut grants = Vec::new();
+    for g in parsed.access_control_list.grants {
+        let grantee = match (g.grantee.uri, g.grantee.id, g.grantee.email_address) {
+            (Some(uri), _, _) => Grantee::Group { uri },
+            (None, Some(id), _) => Grantee::CanonicalUser {
+                id,
+                display_name: g.grantee.display_name,
+            },
+            (None, None, Some(email_address)) => Grantee::AmazonCustomerByEmail { email_address },
+            (None, None, None) => {
+                return Err(quick_xml::DeError::Custom("Grantee has no identifier".into()))
+            }
+        };
+        let permission = g
+            .permission
+            .parse()
+            .map_err(|_| quick_xml::DeError::Custom(format!("Unknown permission {}", g.permission)))?;
+        grants.push(Grant::new(grantee, permission));
+    }
+
+    Ok(AccessControlPolicy {
+        owner: Owner {
+            id: parsed.owner.id,
+            display_name: parsed.owner.display_name,
+        },
+        access_control_list: AccessControlList { grant: grants },
+    })
+}
+
+// ============= STS =============
+
+/// Generate AssumeRoleWithWebIdentity response XML
+pub fn assume_role_with_web_identity_response(
+    cred: &hafiz_core::types::TemporaryCredentials,
+    session_name: &str,
+    audience: &str,
+    provider: &str,
+    request_id: &str,
+) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<AssumeRoleWithWebIdentityResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <AssumeRoleWithWebIdentityResult>
+    <SubjectFromWebIdentityToken>{}</SubjectFromWebIdentityToken>
+    <Audience>{}</Audience>
+    <AssumedRoleUser>
+      <Arn>arn:hafiz:sts:::assumed-role/{}/{}</Arn>
+      <AssumedRoleId>{}:{}</AssumedRoleId>
+    </AssumedRoleUser>
+    <Credentials>
+      <AccessKeyId>{}</AccessKeyId>
+      <SecretAccessKey>{}</SecretAccessKey>
+      <SessionToken>{}</SessionToken>
+      <Expiration>{}</Expiration>
+    </Credentials>
+    <Provider>{}</Provider>
+  </AssumeRoleWithWebIdentityResult>
+  <ResponseMetadata>
+    <RequestId>{}</RequestId>
+  </ResponseMetadata>
+</AssumeRoleWithWebIdentityResponse>"#,
+        xml_escape(&cred.subject),
+        xml_escape(audience),
+        xml_escape(&cred.username),
+        xml_escape(session_name),
+        cred.access_key,
+        xml_escape(session_name),
+        cred.access_key,
+        xml_escape(&cred.secret_key),
+        cred.session_token,
+        format_s3_datetime(&cred.expires_at),
+        xml_escape(provider),
+        request_id
+    )
+}
+
+/// Generate an STS error response; STS uses a different envelope than S3
+pub fn sts_error_response(code: &str, message: &str, sender_fault: bool, request_id: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<ErrorResponse xmlns="https://sts.amazonaws.com/doc/2011-06-15/">
+  <Error>
+    <Type>{}</Type>
+    <Code>{}</Code>
+    <Message>{}</Message>
+  </Error>
+  <RequestId>{}</RequestId>
+</ErrorResponse>"#,
+        if sender_fault { "Sender" } else { "Receiver" },
+        code,
+        xml_escape(message),
+        request_id
+    )
+}
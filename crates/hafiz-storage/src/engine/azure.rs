@@ -0,0 +1,231 @@
+//! Azure Blob Storage backend
+//!
+//! Each bucket maps onto a container of the same name and objects are
+//! stored as block blobs. Multipart upload parts are staged as uncommitted
+//! blocks of the target blob and committed as one block list on completion,
+//! so part data is never copied. Azure discards uncommitted blocks that are
+//! not committed within a week, which takes care of abandoned uploads.
+
+use async_trait::async_trait;
+use azure_core::StatusCode;
+use azure_storage::StorageCredentials;
+use azure_storage_blobs::prelude::*;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use hafiz_core::config::AzureBackendConfig;
+use hafiz_core::{Error, Result};
+use tracing::{debug, info};
+
+use super::{ObjectStat, StorageEngine};
+
+/// Storage engine backed by an Azure storage account
+pub struct AzureBlobStorage {
+    service: BlobServiceClient,
+}
+
+impl AzureBlobStorage {
+    pub fn new(config: &AzureBackendConfig) -> Result<Self> {
+        let credentials =
+            StorageCredentials::access_key(config.account.clone(), config.access_key.clone());
+        let service = ClientBuilder::new(config.account.clone(), credentials).blob_service_client();
+
+        info!("Azure Blob backend for account {}", config.account);
+        Ok(Self { service })
+    }
+
+    fn blob(&self, bucket: &str, key: &str) -> BlobClient {
+        self.service.container_client(bucket).blob_client(key)
+    }
+}
+
+/// Block ID of a multipart upload part.
+///
+/// All block IDs of a blob must have the same length, so the part number is
+/// zero padded; upload IDs are UUIDs and already fixed length.
+fn block_id(upload_id: &str, part_number: i32) -> BlockId {
+    BlockId::new(Bytes::from(format!("{}-{:05}", upload_id, part_number)))
+}
+
+#[async_trait]
+impl StorageEngine for AzureBlobStorage {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        let etag = hafiz_crypto::md5_hash(&data);
+        let size = data.len();
+        self.blob(bucket, key)
+            .put_block_blob(data)
+            .await
+            .map_err(azure_error)?;
+
+        debug!("Stored blob {}/{} ({} bytes)", bucket, key, size);
+        Ok(etag)
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        match self.blob(bucket, key).get_content().await {
+            Ok(data) => Ok(Bytes::from(data)),
+            Err(e) if is_not_found(&e) => Err(Error::NoSuchKey),
+            Err(e) => Err(azure_error(e)),
+        }
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        let mut stream = self
+            .blob(bucket, key)
+            .get()
+            .range(start as u64..end as u64 + 1)
+            .into_stream();
+
+        let mut data = Vec::with_capacity((end - start + 1) as usize);
+        while let Some(chunk) = stream.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(e) if is_not_found(&e) => return Err(Error::NoSuchKey),
+                Err(e) => return Err(azure_error(e)),
+            };
+            data.extend_from_slice(&chunk.data.collect().await.map_err(azure_error)?);
+        }
+        Ok(Bytes::from(data))
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        match self.blob(bucket, key).delete().await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(azure_error(e)),
+        }
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        self.blob(bucket, key).exists().await.map_err(azure_error)
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        self.stat(bucket, key)
+            .await?
+            .map(|stat| stat.size)
+            .ok_or(Error::NoSuchKey)
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        let container = self.service.container_client(bucket);
+        if container.exists().await.map_err(azure_error)? {
+            return Ok(());
+        }
+        match container.create().await {
+            Ok(_) => Ok(()),
+            Err(e) if e.as_http_error().is_some_and(|e| e.status() == StatusCode::Conflict) => {
+                Ok(())
+            }
+            Err(e) => Err(azure_error(e)),
+        }
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        match self.service.container_client(bucket).delete().await {
+            Ok(_) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(azure_error(e)),
+        }
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.service
+            .container_client(bucket)
+            .exists()
+            .await
+            .map_err(azure_error)
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        let properties = match self.blob(bucket, key).get_properties().await {
+            Ok(response) => response.blob.properties,
+            Err(e) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(azure_error(e)),
+        };
+
+        Ok(Some(ObjectStat {
+            size: properties.content_length as i64,
+            etag: properties.etag.to_string().trim_matches('"').to_string(),
+            content_type: Some(properties.content_type).filter(|t| !t.is_empty()),
+            last_modified: DateTime::from_timestamp(properties.last_modified.unix_timestamp(), 0)
+                .unwrap_or_else(Utc::now),
+        }))
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.service
+            .list_containers()
+            .max_results(std::num::NonZeroU32::new(1).unwrap())
+            .into_stream()
+            .next()
+            .await
+            .transpose()
+            .map(|_| ())
+            .map_err(azure_error)
+    }
+
+    async fn put_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        let etag = hafiz_crypto::md5_hash(&data);
+        self.blob(bucket, key)
+            .put_block(block_id(upload_id, part_number), data)
+            .await
+            .map_err(azure_error)?;
+        Ok(etag)
+    }
+
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        let blob = self.blob(bucket, key);
+        let block_list = BlockList {
+            blocks: part_numbers
+                .iter()
+                .map(|&n| BlobBlockType::new_uncommitted(block_id(upload_id, n)))
+                .collect(),
+        };
+        blob.put_block_list(block_list).await.map_err(azure_error)?;
+
+        let properties = blob.get_properties().await.map_err(azure_error)?;
+        debug!(
+            "Committed {} blocks to {}/{} (upload {})",
+            part_numbers.len(),
+            bucket,
+            key,
+            upload_id
+        );
+        Ok(properties.blob.properties.content_length as i64)
+    }
+
+    async fn abort_parts(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _upload_id: &str,
+        _part_numbers: &[i32],
+    ) -> Result<()> {
+        // Uncommitted blocks cannot be deleted individually; Azure drops them
+        // when the next block list is committed or after a week
+        Ok(())
+    }
+}
+
+fn is_not_found(e: &azure_core::Error) -> bool {
+    e.as_http_error()
+        .is_some_and(|e| e.status() == StatusCode::NotFound)
+}
+
+fn azure_error(e: azure_core::Error) -> Error {
+    Error::InternalError(format!("Azure Blob request failed: {}", e))
+}
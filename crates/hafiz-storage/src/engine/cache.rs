@@ -0,0 +1,399 @@
+//! Local disk cache for objects held by a remote backend
+//!
+//! Each cached object is a data file plus a JSON sidecar describing it, so
+//! the cache survives restarts. Entries older than the TTL are refetched and
+//! the least recently used entries are evicted once the cache is full.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use hafiz_core::{Error, Result};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use tracing::{debug, info, warn};
+
+use super::ObjectStat;
+
+type CacheKey = (String, String);
+
+/// Sidecar written next to each cached object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CacheRecord {
+    bucket: String,
+    key: String,
+    stat: ObjectStat,
+    fetched_at: DateTime<Utc>,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    stat: ObjectStat,
+    fetched_at: DateTime<Utc>,
+    /// Position in the recency order
+    tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct CacheIndex {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// Recency order, least recently used first
+    order: BTreeMap<u64, CacheKey>,
+    used_bytes: u64,
+    next_tick: u64,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, key: &CacheKey) {
+        let tick = self.next_tick;
+        if let Some(entry) = self.entries.get_mut(key) {
+            self.order.remove(&entry.tick);
+            entry.tick = tick;
+            self.order.insert(tick, key.clone());
+            self.next_tick += 1;
+        }
+    }
+
+    fn insert(&mut self, key: CacheKey, stat: ObjectStat, fetched_at: DateTime<Utc>) {
+        self.remove(&key);
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.used_bytes += stat.size as u64;
+        self.order.insert(tick, key.clone());
+        self.entries.insert(key, CacheEntry { stat, fetched_at, tick });
+    }
+
+    fn remove(&mut self, key: &CacheKey) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.used_bytes = self.used_bytes.saturating_sub(entry.stat.size as u64);
+        Some(entry)
+    }
+
+    fn pop_oldest(&mut self) -> Option<CacheKey> {
+        let (_, key) = self.order.pop_first()?;
+        if let Some(entry) = self.entries.remove(&key) {
+            self.used_bytes = self.used_bytes.saturating_sub(entry.stat.size as u64);
+        }
+        Some(key)
+    }
+}
+
+/// Cache usage counters
+#[derive(Debug, Clone, Serialize)]
+pub struct CacheStats {
+    pub objects: usize,
+    pub used_bytes: u64,
+    pub max_bytes: u64,
+}
+
+/// LRU object cache on local disk
+pub struct ObjectCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    ttl: Duration,
+    index: Mutex<CacheIndex>,
+    /// Whether to report the `hafiz_gateway_cache_*` metrics
+    metrics: bool,
+}
+
+impl ObjectCache {
+    /// Open the cache, indexing the objects left by a previous run
+    pub fn open(dir: impl AsRef<Path>, max_bytes: u64, ttl: Duration) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir)?;
+
+        let mut records = Vec::new();
+        for shard in std::fs::read_dir(&dir)? {
+            let shard = shard?.path();
+            if !shard.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(&shard)? {
+                let path = entry?.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                match std::fs::read(&path).map(|data| serde_json::from_slice::<CacheRecord>(&data)) {
+                    Ok(Ok(record)) if path.with_extension("data").exists() => records.push(record),
+                    Ok(Ok(_)) => {
+                        let _ = std::fs::remove_file(&path);
+                    }
+                    Ok(Err(e)) => warn!("Skipping unreadable cache entry {}: {}", path.display(), e),
+                    Err(e) => warn!("Skipping unreadable cache entry {}: {}", path.display(), e),
+                }
+            }
+        }
+
+        // Oldest fetches are the first to go
+        records.sort_by_key(|r| r.fetched_at);
+        let mut index = CacheIndex::default();
+        for record in records {
+            index.insert((record.bucket, record.key), record.stat, record.fetched_at);
+        }
+        info!(
+            "Object cache at {:?}: {} objects, {} bytes",
+            dir,
+            index.entries.len(),
+            index.used_bytes
+        );
+
+        let cache = Self {
+            dir,
+            max_bytes,
+            ttl,
+            index: Mutex::new(index),
+            metrics: true,
+        };
+        for (bucket, key) in cache.evict() {
+            cache.unlink_sync(&bucket, &key);
+        }
+        Ok(cache)
+    }
+
+    /// Leave the `hafiz_gateway_cache_*` metrics to the gateway's own
+    /// cache, for a cache with another purpose
+    pub fn without_metrics(mut self) -> Self {
+        self.metrics = false;
+        self
+    }
+
+    /// Cached data of `bucket/key` if it is present and fresh
+    pub async fn get(&self, bucket: &str, key: &str) -> Result<Option<(Bytes, ObjectStat)>> {
+        let Some(stat) = self.stat(bucket, key) else {
+            self.count("hafiz_gateway_cache_misses_total", 1);
+            return Ok(None);
+        };
+
+        match fs::read(self.data_path(bucket, key)).await {
+            Ok(data) => {
+                self.count("hafiz_gateway_cache_hits_total", 1);
+                Ok(Some((Bytes::from(data), stat)))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                self.index.lock().remove(&cache_key(bucket, key));
+                self.count("hafiz_gateway_cache_misses_total", 1);
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Attributes of a fresh cached object, marking it as recently used
+    pub fn stat(&self, bucket: &str, key: &str) -> Option<ObjectStat> {
+        let key = cache_key(bucket, key);
+        let mut index = self.index.lock();
+
+        let fresh = {
+            let entry = index.entries.get(&key)?;
+            let age = Utc::now() - entry.fetched_at;
+            age.to_std().map_or(true, |age| age <= self.ttl)
+        };
+        if !fresh {
+            index.remove(&key);
+            drop(index);
+            self.unlink_sync(&key.0, &key.1);
+            return None;
+        }
+
+        index.touch(&key);
+        index.entries.get(&key).map(|e| e.stat.clone())
+    }
+
+    /// Store `data`, evicting least recently used objects to make room
+    pub async fn insert(&self, bucket: &str, key: &str, data: &Bytes, stat: ObjectStat) -> Result<()> {
+        if data.len() as u64 > self.max_bytes {
+            debug!("Not caching {}/{}: larger than the cache", bucket, key);
+            return Ok(());
+        }
+
+        let record = CacheRecord {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            stat: stat.clone(),
+            fetched_at: Utc::now(),
+        };
+        let data_path = self.data_path(bucket, key);
+        if let Some(parent) = data_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        write_atomic(&data_path, data).await?;
+        let sidecar = serde_json::to_vec(&record).map_err(|e| Error::InternalError(e.to_string()))?;
+        write_atomic(&data_path.with_extension("json"), &sidecar).await?;
+
+        self.index
+            .lock()
+            .insert(cache_key(bucket, key), stat, record.fetched_at);
+        let evicted = self.evict();
+        for (bucket, key) in evicted {
+            self.unlink(&bucket, &key).await;
+        }
+
+        self.update_metrics();
+        Ok(())
+    }
+
+    /// Drop a cached object
+    pub async fn remove(&self, bucket: &str, key: &str) {
+        if self.index.lock().remove(&cache_key(bucket, key)).is_some() {
+            self.unlink(bucket, key).await;
+            self.update_metrics();
+        }
+    }
+
+    /// Drop every cached object of a bucket
+    pub async fn remove_bucket(&self, bucket: &str) {
+        self.remove_matching(bucket, |_| true).await;
+    }
+
+    /// Drop the cached objects of a bucket whose key matches `filter`
+    pub async fn remove_matching(&self, bucket: &str, filter: impl Fn(&str) -> bool) {
+        let keys: Vec<CacheKey> = self
+            .index
+            .lock()
+            .entries
+            .keys()
+            .filter(|(b, k)| b == bucket && filter(k))
+            .cloned()
+            .collect();
+        for (bucket, key) in keys {
+            self.remove(&bucket, &key).await;
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let index = self.index.lock();
+        CacheStats {
+            objects: index.entries.len(),
+            used_bytes: index.used_bytes,
+            max_bytes: self.max_bytes,
+        }
+    }
+
+    /// Verify the cache directory is writable
+    pub async fn health_check(&self) -> Result<()> {
+        let test_file = self.dir.join(".health_check");
+        fs::write(&test_file, "ok").await?;
+        let _ = fs::remove_file(&test_file).await;
+        Ok(())
+    }
+
+    /// Remove entries from the index until it fits, returning them
+    fn evict(&self) -> Vec<CacheKey> {
+        let mut index = self.index.lock();
+        let mut evicted = Vec::new();
+        while index.used_bytes > self.max_bytes {
+            match index.pop_oldest() {
+                Some(key) => evicted.push(key),
+                None => break,
+            }
+        }
+        if !evicted.is_empty() {
+            self.count("hafiz_gateway_cache_evictions_total", evicted.len() as u64);
+            debug!("Evicted {} objects from the cache", evicted.len());
+        }
+        evicted
+    }
+
+    fn data_path(&self, bucket: &str, key: &str) -> PathBuf {
+        let hash = hafiz_crypto::md5_hash(format!("{}/{}", bucket, key).as_bytes());
+        self.dir.join(&hash[..2]).join(format!("{}.data", hash))
+    }
+
+    async fn unlink(&self, bucket: &str, key: &str) {
+        let path = self.data_path(bucket, key);
+        let _ = fs::remove_file(path.with_extension("json")).await;
+        let _ = fs::remove_file(path).await;
+    }
+
+    fn unlink_sync(&self, bucket: &str, key: &str) {
+        let path = self.data_path(bucket, key);
+        let _ = std::fs::remove_file(path.with_extension("json"));
+        let _ = std::fs::remove_file(path);
+    }
+
+    fn count(&self, name: &'static str, n: u64) {
+        if self.metrics {
+            metrics::counter!(name).increment(n);
+        }
+    }
+
+    fn update_metrics(&self) {
+        if !self.metrics {
+            return;
+        }
+        let stats = self.stats();
+        metrics::gauge!("hafiz_gateway_cache_bytes").set(stats.used_bytes as f64);
+        metrics::gauge!("hafiz_gateway_cache_objects").set(stats.objects as f64);
+    }
+}
+
+fn cache_key(bucket: &str, key: &str) -> CacheKey {
+    (bucket.to_string(), key.to_string())
+}
+
+async fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, data).await?;
+    fs::rename(&tmp, path).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stat(size: usize) -> ObjectStat {
+        ObjectStat {
+            size: size as i64,
+            etag: "etag".to_string(),
+            content_type: None,
+            last_modified: Utc::now(),
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("hafiz-cache-{}", uuid::Uuid::new_v4()))
+    }
+
+    #[tokio::test]
+    async fn test_lru_eviction() {
+        let dir = temp_dir();
+        let cache = ObjectCache::open(&dir, 10, Duration::from_secs(60)).unwrap();
+        let data = Bytes::from_static(b"abcd");
+
+        cache.insert("b", "one", &data, stat(4)).await.unwrap();
+        cache.insert("b", "two", &data, stat(4)).await.unwrap();
+        // Touch "one" so "two" is the least recently used
+        assert!(cache.get("b", "one").await.unwrap().is_some());
+        cache.insert("b", "three", &data, stat(4)).await.unwrap();
+
+        assert!(cache.get("b", "two").await.unwrap().is_none());
+        assert!(cache.get("b", "one").await.unwrap().is_some());
+        assert_eq!(cache.stats().used_bytes, 8);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ttl_and_reopen() {
+        let dir = temp_dir();
+        {
+            let cache = ObjectCache::open(&dir, 100, Duration::from_secs(60)).unwrap();
+            cache.insert("b", "k", &Bytes::from_static(b"data"), stat(4)).await.unwrap();
+        }
+
+        let cache = ObjectCache::open(&dir, 100, Duration::from_secs(60)).unwrap();
+        let (data, _) = cache.get("b", "k").await.unwrap().unwrap();
+        assert_eq!(&data[..], b"data");
+
+        let expired = ObjectCache::open(&dir, 100, Duration::ZERO).unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(expired.get("b", "k").await.unwrap().is_none());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,390 @@
+//! Transparent per-bucket compression
+//!
+//! [`CompressedStorage`] wraps another engine and compresses object data of
+//! buckets that have a codec set. Compressed data is stored as a frame: a
+//! magic, the codec and the original size, then the payload. Callers only
+//! ever see the original bytes, so sizes and ETags (MD5 of the original
+//! data) are unaffected. Data that does not shrink is stored as is.
+//!
+//! Only buckets that have (or had) a compression setting are inspected for
+//! frames; every other bucket is passed straight through.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hafiz_core::types::{BucketCompression, CompressionCodec};
+use hafiz_core::{Error, Result};
+use parking_lot::RwLock;
+use tracing::info;
+
+use super::{ObjectStat, StorageEngine};
+
+const FRAME_MAGIC: &[u8; 4] = b"\0HZC";
+
+/// Magic, codec byte and original size
+const HEADER_LEN: usize = FRAME_MAGIC.len() + 1 + 8;
+
+/// Default zstd level when a bucket does not set one
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// Storage engine that compresses data of selected buckets before it
+/// reaches `inner`
+pub struct CompressedStorage {
+    inner: Arc<dyn StorageEngine>,
+    buckets: RwLock<HashMap<String, BucketCompression>>,
+}
+
+impl CompressedStorage {
+    pub fn new(inner: Arc<dyn StorageEngine>) -> Self {
+        Self {
+            inner,
+            buckets: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Compression of new writes to `bucket`
+    pub fn bucket_compression(&self, bucket: &str) -> BucketCompression {
+        self.buckets
+            .read()
+            .get(bucket)
+            .copied()
+            .unwrap_or(BucketCompression {
+                codec: CompressionCodec::None,
+                level: None,
+            })
+    }
+
+    /// Set the compression of new writes to `bucket`. Existing objects keep
+    /// the codec they were written with.
+    pub fn set_bucket_compression(&self, bucket: &str, compression: BucketCompression) {
+        info!("Bucket {} compression set to {}", bucket, compression.codec);
+        self.buckets
+            .write()
+            .insert(bucket.to_string(), compression);
+    }
+
+    /// Forget the setting of a deleted bucket
+    pub fn clear_bucket_compression(&self, bucket: &str) {
+        self.buckets.write().remove(bucket);
+    }
+
+    /// Whether objects of `bucket` may be stored as frames
+    fn has_frames(&self, bucket: &str) -> bool {
+        self.buckets.read().contains_key(bucket)
+    }
+
+    /// Header of the stored object, if it is a frame
+    async fn peek_header(&self, bucket: &str, key: &str) -> Result<Option<(CompressionCodec, u64)>> {
+        if self.inner.size(bucket, key).await? < HEADER_LEN as i64 {
+            return Ok(None);
+        }
+        let head = self
+            .inner
+            .get_range(bucket, key, 0, HEADER_LEN as i64 - 1)
+            .await?;
+        parse_header(&head).transpose()
+    }
+}
+
+/// Codec and original size from a frame header, `None` if `data` is not a frame
+fn parse_header(data: &[u8]) -> Option<Result<(CompressionCodec, u64)>> {
+    if data.len() < HEADER_LEN || &data[..FRAME_MAGIC.len()] != FRAME_MAGIC {
+        return None;
+    }
+    let codec = match data[FRAME_MAGIC.len()] {
+        0 => CompressionCodec::None,
+        1 => CompressionCodec::Zstd,
+        2 => CompressionCodec::Lz4,
+        other => {
+            return Some(Err(Error::InternalError(format!(
+                "Unknown compression codec id {}",
+                other
+            ))))
+        }
+    };
+    let mut size = [0u8; 8];
+    size.copy_from_slice(&data[FRAME_MAGIC.len() + 1..HEADER_LEN]);
+    Some(Ok((codec, u64::from_le_bytes(size))))
+}
+
+fn frame(codec: CompressionCodec, original_size: usize, payload: &[u8]) -> Bytes {
+    let codec_id = match codec {
+        CompressionCodec::None => 0u8,
+        CompressionCodec::Zstd => 1,
+        CompressionCodec::Lz4 => 2,
+    };
+    let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+    data.extend_from_slice(FRAME_MAGIC);
+    data.push(codec_id);
+    data.extend_from_slice(&(original_size as u64).to_le_bytes());
+    data.extend_from_slice(payload);
+    Bytes::from(data)
+}
+
+fn compress(compression: BucketCompression, data: &[u8]) -> Result<Vec<u8>> {
+    match compression.codec {
+        CompressionCodec::None => Ok(data.to_vec()),
+        CompressionCodec::Zstd => {
+            zstd::bulk::compress(data, compression.level.unwrap_or(DEFAULT_ZSTD_LEVEL))
+                .map_err(|e| Error::InternalError(format!("zstd compression failed: {}", e)))
+        }
+        CompressionCodec::Lz4 => Ok(lz4_flex::block::compress(data)),
+    }
+}
+
+fn decompress(codec: CompressionCodec, payload: &[u8], original_size: u64) -> Result<Bytes> {
+    let data = match codec {
+        CompressionCodec::None => payload.to_vec(),
+        CompressionCodec::Zstd => zstd::bulk::decompress(payload, original_size as usize)
+            .map_err(|e| Error::InternalError(format!("zstd decompression failed: {}", e)))?,
+        CompressionCodec::Lz4 => lz4_flex::block::decompress(payload, original_size as usize)
+            .map_err(|e| Error::InternalError(format!("lz4 decompression failed: {}", e)))?,
+    };
+    if data.len() as u64 != original_size {
+        return Err(Error::InternalError(format!(
+            "Decompressed {} bytes, expected {}",
+            data.len(),
+            original_size
+        )));
+    }
+    Ok(Bytes::from(data))
+}
+
+/// Original bytes of stored data
+async fn unframe(data: Bytes) -> Result<Bytes> {
+    let Some(header) = parse_header(&data) else {
+        return Ok(data);
+    };
+    let (codec, size) = header?;
+    if codec == CompressionCodec::None {
+        return Ok(data.slice(HEADER_LEN..));
+    }
+    tokio::task::spawn_blocking(move || decompress(codec, &data[HEADER_LEN..], size))
+        .await
+        .map_err(|e| Error::InternalError(e.to_string()))?
+}
+
+#[async_trait]
+impl StorageEngine for CompressedStorage {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        if !self.has_frames(bucket) {
+            return self.inner.put(bucket, key, data).await;
+        }
+
+        let etag = hafiz_crypto::md5_hash(&data);
+        let compression = self.bucket_compression(bucket);
+        let stored = if compression.codec == CompressionCodec::None {
+            // Data that happens to look like a frame must be framed itself
+            if parse_header(&data).is_some() {
+                frame(CompressionCodec::None, data.len(), &data)
+            } else {
+                data
+            }
+        } else {
+            let original = data.clone();
+            let compressed = tokio::task::spawn_blocking(move || compress(compression, &original))
+                .await
+                .map_err(|e| Error::InternalError(e.to_string()))??;
+
+            let codec = compression.codec.as_str();
+            metrics::counter!("hafiz_compression_input_bytes_total", "codec" => codec)
+                .increment(data.len() as u64);
+            metrics::counter!("hafiz_compression_output_bytes_total", "codec" => codec)
+                .increment(compressed.len().min(data.len()) as u64);
+            if !data.is_empty() {
+                metrics::histogram!("hafiz_compression_ratio", "codec" => codec)
+                    .record(data.len() as f64 / compressed.len().max(1) as f64);
+            }
+
+            if compressed.len() + HEADER_LEN < data.len() {
+                frame(compression.codec, data.len(), &compressed)
+            } else {
+                frame(CompressionCodec::None, data.len(), &data)
+            }
+        };
+
+        self.inner.put(bucket, key, stored).await?;
+        Ok(etag)
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        let data = self.inner.get(bucket, key).await?;
+        if !self.has_frames(bucket) {
+            return Ok(data);
+        }
+        unframe(data).await
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        if !self.has_frames(bucket) {
+            return self.inner.get_range(bucket, key, start, end).await;
+        }
+        match self.peek_header(bucket, key).await? {
+            Some((_, size)) => {
+                if start < 0 || start as u64 >= size {
+                    return Err(Error::InvalidRange(format!("bytes={}-{}", start, end)));
+                }
+                // Frames are not seekable; decode the whole object
+                let data = self.get(bucket, key).await?;
+                let end = (end as usize).min(data.len() - 1);
+                Ok(data.slice(start as usize..=end))
+            }
+            None => self.inner.get_range(bucket, key, start, end).await,
+        }
+    }
+
+    async fn copy(&self, src_bucket: &str, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        // Frames are only read back in buckets with a codec, so data is
+        // shared only between buckets that hold no frames
+        if self.has_frames(src_bucket) || self.has_frames(dst_bucket) {
+            let data = self.get(src_bucket, src_key).await?;
+            self.put(dst_bucket, dst_key, data).await?;
+            return Ok(());
+        }
+        self.inner.copy(src_bucket, src_key, dst_bucket, dst_key).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        self.inner.delete(bucket, key).await
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        self.inner.exists(bucket, key).await
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        if self.has_frames(bucket) {
+            if let Some((_, size)) = self.peek_header(bucket, key).await? {
+                return Ok(size as i64);
+            }
+        }
+        self.inner.size(bucket, key).await
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.create_bucket(bucket).await
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.delete_bucket(bucket).await
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.inner.bucket_exists(bucket).await
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        self.inner.stat(bucket, key).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    async fn put_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        // Parts stay uncompressed; the assembled object is compressed
+        self.inner
+            .put_part(bucket, key, upload_id, part_number, data)
+            .await
+    }
+
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        let size = self
+            .inner
+            .complete_parts(bucket, key, upload_id, part_numbers)
+            .await?;
+
+        if self.bucket_compression(bucket).codec != CompressionCodec::None {
+            let data = self.inner.get(bucket, key).await?;
+            self.put(bucket, key, data).await?;
+        }
+        Ok(size)
+    }
+
+    async fn abort_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<()> {
+        self.inner
+            .abort_parts(bucket, key, upload_id, part_numbers)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStorage;
+
+    fn compressed(codec: CompressionCodec) -> (Arc<MemoryStorage>, CompressedStorage) {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = CompressedStorage::new(inner.clone());
+        storage.set_bucket_compression("b", BucketCompression { codec, level: None });
+        (inner, storage)
+    }
+
+    #[tokio::test]
+    async fn test_roundtrip() {
+        for codec in [CompressionCodec::Zstd, CompressionCodec::Lz4] {
+            let (inner, storage) = compressed(codec);
+            let data = Bytes::from("hafiz ".repeat(10_000));
+
+            let etag = storage.put("b", "k", data.clone()).await.unwrap();
+            assert_eq!(etag, hafiz_crypto::md5_hash(&data));
+            assert!(inner.used_bytes() < data.len() as u64 / 10);
+
+            assert_eq!(storage.get("b", "k").await.unwrap(), data);
+            assert_eq!(storage.size("b", "k").await.unwrap(), data.len() as i64);
+            assert_eq!(
+                storage.get_range("b", "k", 6, 16).await.unwrap(),
+                data.slice(6..=16)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_incompressible_and_lookalike_data() {
+        let (_, storage) = compressed(CompressionCodec::Zstd);
+
+        let tiny = Bytes::from_static(b"x");
+        storage.put("b", "tiny", tiny.clone()).await.unwrap();
+        assert_eq!(storage.get("b", "tiny").await.unwrap(), tiny);
+
+        let lookalike = frame(CompressionCodec::Lz4, 3, b"abc");
+        storage.set_bucket_compression(
+            "b",
+            BucketCompression {
+                codec: CompressionCodec::None,
+                level: None,
+            },
+        );
+        storage.put("b", "frame", lookalike.clone()).await.unwrap();
+        assert_eq!(storage.get("b", "frame").await.unwrap(), lookalike);
+    }
+
+    #[tokio::test]
+    async fn test_other_buckets_pass_through() {
+        let (inner, storage) = compressed(CompressionCodec::Zstd);
+        let data = Bytes::from("plain ".repeat(1000));
+        storage.put("other", "k", data.clone()).await.unwrap();
+        assert_eq!(inner.get("other", "k").await.unwrap(), data);
+    }
+}
@@ -0,0 +1,643 @@
+//! Content-addressable deduplication
+//!
+//! [`DedupStorage`] wraps another engine. Object data is split into chunks,
+//! each chunk is stored once in a hidden chunk bucket under its SHA-256, and
+//! the object itself becomes a small manifest listing its chunks. Every
+//! chunk has a reference count next to it (`<hash>.ref`); a chunk is
+//! removed when the last object using it is overwritten or deleted.
+//!
+//! Objects written before dedup was enabled are not manifests and are
+//! served unchanged, so dedup can be switched on for existing data. Since
+//! such data may happen to start like a manifest, every manifest carries an
+//! HMAC under a random key kept in the chunk bucket; stored data is only
+//! read as a manifest if its HMAC checks out.
+//!
+//! Writes and deletes of one object are serialized, so that the chunks of
+//! the manifest they replace are released exactly once.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::ops::Range;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::{Bytes, BytesMut};
+use hafiz_core::config::{ChunkingMode, DedupConfig};
+use hafiz_core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use super::StorageEngine;
+
+/// Bucket holding chunk data and reference counts
+pub const CHUNK_BUCKET: &str = ".hafiz-chunks";
+
+/// Marks stored data as a chunk manifest
+const MANIFEST_MAGIC: &[u8] = b"HAFIZ-DEDUP-1\n";
+
+/// Key of the manifest HMAC key in the chunk bucket
+const MANIFEST_KEY: &str = ".manifest-key";
+
+/// Length of the hex HMAC line that follows the magic, newline included
+const MANIFEST_MAC_LEN: usize = 64 + 1;
+
+/// Number of lock stripes for reference count updates, and for objects
+const LOCK_STRIPES: usize = 64;
+
+/// Object stored as a list of chunks
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    size: i64,
+    chunks: Vec<ChunkRef>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChunkRef {
+    hash: String,
+    size: u64,
+}
+
+impl Manifest {
+    /// Manifest stored as `data`, `None` if `data` is not one
+    fn parse(data: &[u8], key: &[u8]) -> Option<Result<Self>> {
+        let rest = data.strip_prefix(MANIFEST_MAGIC)?;
+        let (mac, body) = (rest.get(..MANIFEST_MAC_LEN)?, &rest[MANIFEST_MAC_LEN..]);
+        // Data that merely starts like a manifest is served as it is
+        if !mac_matches(&mac[..MANIFEST_MAC_LEN - 1], &manifest_mac(key, body)) {
+            return None;
+        }
+        Some(
+            serde_json::from_slice(body)
+                .map_err(|e| Error::InternalError(format!("Corrupt chunk manifest: {}", e))),
+        )
+    }
+
+    fn encode(&self, key: &[u8]) -> Result<Bytes> {
+        let body = serde_json::to_vec(self).map_err(|e| Error::InternalError(e.to_string()))?;
+        let mut data = MANIFEST_MAGIC.to_vec();
+        data.extend_from_slice(manifest_mac(key, &body).as_bytes());
+        data.push(b'\n');
+        data.extend_from_slice(&body);
+        Ok(Bytes::from(data))
+    }
+}
+
+fn manifest_mac(key: &[u8], body: &[u8]) -> String {
+    hafiz_crypto::hmac_sha256_hex(key, body)
+}
+
+/// Compare a stored HMAC without an early exit
+fn mac_matches(stored: &[u8], expected: &str) -> bool {
+    stored.len() == expected.len()
+        && stored
+            .iter()
+            .zip(expected.as_bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Splits object data into chunks
+#[derive(Debug, Clone, Copy)]
+pub enum Chunker {
+    Fixed(usize),
+    /// Gear-hash content-defined chunking; a boundary falls where the rolling
+    /// hash has its low bits clear, within `min..=max` bytes of the last one
+    ContentDefined { min: usize, max: usize, mask: u64 },
+}
+
+impl Chunker {
+    pub fn new(mode: ChunkingMode, chunk_size: usize) -> Self {
+        match mode {
+            ChunkingMode::Fixed => Self::Fixed(chunk_size),
+            ChunkingMode::ContentDefined => Self::ContentDefined {
+                min: chunk_size / 4,
+                max: chunk_size * 4,
+                mask: chunk_size.next_power_of_two() as u64 - 1,
+            },
+        }
+    }
+
+    /// Chunk boundaries of `data`
+    pub fn split(&self, data: &[u8]) -> Vec<Range<usize>> {
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < data.len() {
+            let end = match *self {
+                Self::Fixed(size) => (start + size).min(data.len()),
+                Self::ContentDefined { min, max, mask } => {
+                    cut_point(&data[start..], min, max, mask) + start
+                }
+            };
+            chunks.push(start..end);
+            start = end;
+        }
+        chunks
+    }
+}
+
+/// Length of the next content-defined chunk at the start of `data`
+fn cut_point(data: &[u8], min: usize, max: usize, mask: u64) -> usize {
+    if data.len() <= min {
+        return data.len();
+    }
+    let limit = data.len().min(max);
+    let mut hash: u64 = 0;
+    for (i, &byte) in data.iter().enumerate().take(limit).skip(min) {
+        hash = (hash << 1).wrapping_add(GEAR[byte as usize]);
+        if hash & mask == 0 {
+            return i + 1;
+        }
+    }
+    limit
+}
+
+/// Random values per byte for the gear hash, generated with splitmix64
+const GEAR: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+};
+
+/// Storage engine that deduplicates object data stored in `inner`
+pub struct DedupStorage {
+    inner: Arc<dyn StorageEngine>,
+    chunker: Chunker,
+    /// Key manifests are authenticated with
+    manifest_key: Vec<u8>,
+    locks: Vec<Mutex<()>>,
+    object_locks: Vec<Mutex<()>>,
+}
+
+impl DedupStorage {
+    pub async fn new(inner: Arc<dyn StorageEngine>, config: &DedupConfig) -> Result<Self> {
+        config.validate()?;
+        inner.create_bucket(CHUNK_BUCKET).await?;
+        let manifest_key = manifest_key(inner.as_ref()).await?;
+
+        info!(
+            "Deduplication enabled ({:?} chunks of {} bytes)",
+            config.chunking, config.chunk_size
+        );
+        Ok(Self {
+            inner,
+            chunker: Chunker::new(config.chunking, config.chunk_size),
+            manifest_key,
+            locks: (0..LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+            object_locks: (0..LOCK_STRIPES).map(|_| Mutex::new(())).collect(),
+        })
+    }
+
+    fn lock_for(&self, hash: &str) -> &Mutex<()> {
+        let stripe = hash
+            .get(..2)
+            .and_then(|prefix| u8::from_str_radix(prefix, 16).ok())
+            .unwrap_or(0) as usize;
+        &self.locks[stripe % self.locks.len()]
+    }
+
+    /// Lock serializing the writes and deletes of one object
+    fn object_lock(&self, bucket: &str, key: &str) -> &Mutex<()> {
+        let mut hasher = DefaultHasher::new();
+        (bucket, key).hash(&mut hasher);
+        &self.object_locks[hasher.finish() as usize % self.object_locks.len()]
+    }
+
+    async fn ref_count(&self, hash: &str) -> Result<u64> {
+        match self.inner.get(CHUNK_BUCKET, &ref_key(hash)).await {
+            Ok(data) => std::str::from_utf8(&data)
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .ok_or_else(|| Error::InternalError(format!("Corrupt refcount for chunk {}", hash))),
+            Err(Error::NoSuchKey) => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Take a reference to a chunk, storing its data if it is new
+    async fn retain(&self, hash: &str, data: Bytes) -> Result<()> {
+        let _guard = self.lock_for(hash).lock().await;
+
+        let count = self.ref_count(hash).await?;
+        if count == 0 {
+            self.inner.put(CHUNK_BUCKET, hash, data).await?;
+            metrics::counter!("hafiz_dedup_chunks_stored_total").increment(1);
+        } else {
+            metrics::counter!("hafiz_dedup_chunks_reused_total").increment(1);
+            metrics::counter!("hafiz_dedup_bytes_saved_total").increment(data.len() as u64);
+        }
+        self.inner
+            .put(CHUNK_BUCKET, &ref_key(hash), Bytes::from((count + 1).to_string()))
+            .await?;
+        Ok(())
+    }
+
+    /// Drop a reference to a chunk, deleting it with the last one
+    async fn release(&self, hash: &str) -> Result<()> {
+        let _guard = self.lock_for(hash).lock().await;
+
+        let count = self.ref_count(hash).await?;
+        if count <= 1 {
+            self.inner.delete(CHUNK_BUCKET, hash).await?;
+            self.inner.delete(CHUNK_BUCKET, &ref_key(hash)).await?;
+            metrics::counter!("hafiz_dedup_chunks_deleted_total").increment(1);
+        } else {
+            self.inner
+                .put(CHUNK_BUCKET, &ref_key(hash), Bytes::from((count - 1).to_string()))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Release every chunk of a manifest, logging failures; a chunk whose
+    /// release fails is leaked rather than lost
+    async fn release_all(&self, chunks: &[ChunkRef]) {
+        for chunk in chunks {
+            if let Err(e) = self.release(&chunk.hash).await {
+                warn!("Failed to release chunk {}: {}", chunk.hash, e);
+            }
+        }
+    }
+
+    /// Manifest stored for an object, `None` if it is missing or not deduplicated
+    async fn manifest(&self, bucket: &str, key: &str) -> Result<Option<Manifest>> {
+        match self.inner.get(bucket, key).await {
+            Ok(data) => Manifest::parse(&data, &self.manifest_key).transpose(),
+            Err(Error::NoSuchKey) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn chunk(&self, chunk: &ChunkRef) -> Result<Bytes> {
+        self.inner
+            .get(CHUNK_BUCKET, &chunk.hash)
+            .await
+            .map_err(|e| match e {
+                Error::NoSuchKey => {
+                    Error::InternalError(format!("Missing dedup chunk {}", chunk.hash))
+                }
+                e => e,
+            })
+    }
+}
+
+fn ref_key(hash: &str) -> String {
+    format!("{}.ref", hash)
+}
+
+/// The manifest HMAC key, made on first use
+async fn manifest_key(inner: &dyn StorageEngine) -> Result<Vec<u8>> {
+    match inner.get(CHUNK_BUCKET, MANIFEST_KEY).await {
+        Ok(key) if !key.is_empty() => return Ok(key.to_vec()),
+        Ok(_) | Err(Error::NoSuchKey) => {}
+        Err(e) => return Err(e),
+    }
+    let mut key = vec![0u8; 32];
+    hafiz_crypto::backend().fill_random(&mut key);
+    inner.put(CHUNK_BUCKET, MANIFEST_KEY, Bytes::from(key.clone())).await?;
+    Ok(key)
+}
+
+#[async_trait]
+impl StorageEngine for DedupStorage {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        let etag = hafiz_crypto::md5_hash(&data);
+
+        let mut chunks = Vec::new();
+        for range in self.chunker.split(&data) {
+            let chunk = data.slice(range);
+            let hash = hafiz_crypto::sha256_hash(&chunk);
+            if let Err(e) = self.retain(&hash, chunk.clone()).await {
+                self.release_all(&chunks).await;
+                return Err(e);
+            }
+            chunks.push(ChunkRef {
+                hash,
+                size: chunk.len() as u64,
+            });
+        }
+
+        let manifest = Manifest {
+            size: data.len() as i64,
+            chunks,
+        };
+        let _guard = self.object_lock(bucket, key).lock().await;
+        let previous = self.manifest(bucket, key).await.ok().flatten();
+        if let Err(e) = self.inner.put(bucket, key, manifest.encode(&self.manifest_key)?).await {
+            self.release_all(&manifest.chunks).await;
+            return Err(e);
+        }
+        if let Some(previous) = previous {
+            self.release_all(&previous.chunks).await;
+        }
+
+        debug!(
+            "Stored {}/{} as {} chunks ({} bytes)",
+            bucket,
+            key,
+            manifest.chunks.len(),
+            data.len()
+        );
+        Ok(etag)
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        let data = self.inner.get(bucket, key).await?;
+        let Some(manifest) = Manifest::parse(&data, &self.manifest_key) else {
+            return Ok(data);
+        };
+        let manifest = manifest?;
+
+        let mut assembled = BytesMut::with_capacity(manifest.size as usize);
+        for chunk in &manifest.chunks {
+            assembled.extend_from_slice(&self.chunk(chunk).await?);
+        }
+        Ok(assembled.freeze())
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        let Some(manifest) = self.manifest(bucket, key).await? else {
+            return self.inner.get_range(bucket, key, start, end).await;
+        };
+        if start < 0 || start >= manifest.size {
+            return Err(Error::InvalidRange(format!("bytes={}-{}", start, end)));
+        }
+        let (start, end) = (start as u64, end.min(manifest.size - 1) as u64);
+
+        // Only fetch the chunks that overlap the range
+        let mut data = BytesMut::with_capacity((end - start + 1) as usize);
+        let mut offset = 0u64;
+        for chunk in &manifest.chunks {
+            let chunk_end = offset + chunk.size;
+            if chunk_end > start && offset <= end {
+                let bytes = self.chunk(chunk).await?;
+                let from = start.saturating_sub(offset) as usize;
+                let to = ((end + 1).min(chunk_end) - offset) as usize;
+                data.extend_from_slice(&bytes[from..to]);
+            }
+            if chunk_end > end {
+                break;
+            }
+            offset = chunk_end;
+        }
+        Ok(data.freeze())
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        let _guard = self.object_lock(bucket, key).lock().await;
+        let manifest = self.manifest(bucket, key).await?;
+        self.inner.delete(bucket, key).await?;
+        if let Some(manifest) = manifest {
+            self.release_all(&manifest.chunks).await;
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        self.inner.exists(bucket, key).await
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        match self.manifest(bucket, key).await? {
+            Some(manifest) => Ok(manifest.size),
+            None => self.inner.size(bucket, key).await,
+        }
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.create_bucket(bucket).await
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.delete_bucket(bucket).await
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.inner.bucket_exists(bucket).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStorage;
+
+    fn pseudo_random(len: usize, seed: u64) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    async fn dedup(chunking: ChunkingMode) -> (Arc<MemoryStorage>, DedupStorage) {
+        let inner = Arc::new(MemoryStorage::new());
+        let config = DedupConfig {
+            enabled: true,
+            chunking,
+            chunk_size: 4096,
+        };
+        let storage = DedupStorage::new(inner.clone(), &config).await.unwrap();
+        (inner, storage)
+    }
+
+    #[tokio::test]
+    async fn test_shared_chunks_are_stored_once() {
+        let (inner, storage) = dedup(ChunkingMode::Fixed).await;
+        let data = Bytes::from(pseudo_random(64 * 1024, 7));
+
+        storage.put("b", "one", data.clone()).await.unwrap();
+        let after_first = inner.used_bytes();
+        storage.put("b", "two", data.clone()).await.unwrap();
+        assert!(inner.used_bytes() - after_first < 4096);
+
+        assert_eq!(storage.get("b", "two").await.unwrap(), data);
+        assert_eq!(storage.size("b", "two").await.unwrap(), data.len() as i64);
+        assert_eq!(
+            storage.get_range("b", "one", 4000, 9000).await.unwrap(),
+            data.slice(4000..=9000)
+        );
+
+        storage.delete("b", "one").await.unwrap();
+        assert_eq!(storage.get("b", "two").await.unwrap(), data);
+
+        let hash = hafiz_crypto::sha256_hash(&data[..4096]);
+        assert!(inner.exists(CHUNK_BUCKET, &hash).await.unwrap());
+        storage.delete("b", "two").await.unwrap();
+        assert!(!inner.exists(CHUNK_BUCKET, &hash).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_overwrite_releases_old_chunks() {
+        let (inner, storage) = dedup(ChunkingMode::Fixed).await;
+        let old = Bytes::from(pseudo_random(8192, 1));
+        storage.put("b", "k", old.clone()).await.unwrap();
+        storage.put("b", "k", Bytes::from_static(b"new")).await.unwrap();
+
+        let hash = hafiz_crypto::sha256_hash(&old[..4096]);
+        assert!(!inner.exists(CHUNK_BUCKET, &hash).await.unwrap());
+        assert_eq!(storage.get("b", "k").await.unwrap(), Bytes::from_static(b"new"));
+    }
+
+    #[tokio::test]
+    async fn test_plain_objects_pass_through() {
+        let (inner, storage) = dedup(ChunkingMode::ContentDefined).await;
+        inner.put("b", "legacy", Bytes::from_static(b"raw data")).await.unwrap();
+
+        assert_eq!(storage.get("b", "legacy").await.unwrap(), Bytes::from_static(b"raw data"));
+        assert_eq!(storage.size("b", "legacy").await.unwrap(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_lookalike_plain_objects_pass_through() {
+        let (inner, storage) = dedup(ChunkingMode::Fixed).await;
+        storage.put("b", "k", Bytes::from_static(b"some data")).await.unwrap();
+        let stored = inner.get("b", "k").await.unwrap();
+
+        // Raw data written before dedup that starts with the magic, or is a
+        // manifest made under another key, is not read as a manifest
+        let forged = Manifest {
+            size: 4,
+            chunks: vec![ChunkRef {
+                hash: hafiz_crypto::sha256_hash(b"some data"),
+                size: 9,
+            }],
+        };
+        for raw in [
+            Bytes::from_static(b"HAFIZ-DEDUP-1\n{\"size\":0,\"chunks\":[]}"),
+            Bytes::from_static(b"HAFIZ-DEDUP-1\n"),
+            forged.encode(b"not the key").unwrap(),
+        ] {
+            inner.put("b", "legacy", raw.clone()).await.unwrap();
+            assert_eq!(storage.get("b", "legacy").await.unwrap(), raw);
+            assert_eq!(storage.size("b", "legacy").await.unwrap(), raw.len() as i64);
+        }
+
+        // The key survives a restart
+        let config = DedupConfig {
+            enabled: true,
+            chunking: ChunkingMode::Fixed,
+            chunk_size: 4096,
+        };
+        let reopened = DedupStorage::new(inner.clone(), &config).await.unwrap();
+        assert_eq!(reopened.get("b", "k").await.unwrap(), Bytes::from_static(b"some data"));
+        assert_eq!(inner.get("b", "k").await.unwrap(), stored);
+    }
+
+    /// Engine yielding before every call, so that concurrent writers
+    /// interleave
+    struct Yielding(MemoryStorage);
+
+    #[async_trait]
+    impl StorageEngine for Yielding {
+        async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+            tokio::task::yield_now().await;
+            self.0.put(bucket, key, data).await
+        }
+        async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+            tokio::task::yield_now().await;
+            self.0.get(bucket, key).await
+        }
+        async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+            self.0.get_range(bucket, key, start, end).await
+        }
+        async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+            tokio::task::yield_now().await;
+            self.0.delete(bucket, key).await
+        }
+        async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+            self.0.exists(bucket, key).await
+        }
+        async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+            self.0.size(bucket, key).await
+        }
+        async fn create_bucket(&self, bucket: &str) -> Result<()> {
+            self.0.create_bucket(bucket).await
+        }
+        async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+            self.0.delete_bucket(bucket).await
+        }
+        async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+            self.0.bucket_exists(bucket).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_overwrites_release_once() {
+        let inner = Arc::new(Yielding(MemoryStorage::new()));
+        let config = DedupConfig {
+            enabled: true,
+            chunking: ChunkingMode::Fixed,
+            chunk_size: 4096,
+        };
+        let storage = Arc::new(DedupStorage::new(inner.clone(), &config).await.unwrap());
+        let shared = Bytes::from(pseudo_random(4096, 3));
+        storage.put("b", "keep", shared.clone()).await.unwrap();
+        storage.put("b", "k", shared.clone()).await.unwrap();
+
+        let writes: Vec<_> = (0..8u64)
+            .map(|i| {
+                let storage = storage.clone();
+                tokio::spawn(async move {
+                    storage.put("b", "k", Bytes::from(pseudo_random(100, i + 10))).await
+                })
+            })
+            .collect();
+        for write in writes {
+            write.await.unwrap().unwrap();
+        }
+
+        // The chunk "keep" still uses survived the overwrites of "k"
+        let hash = hafiz_crypto::sha256_hash(&shared);
+        assert_eq!(storage.ref_count(&hash).await.unwrap(), 1);
+        assert_eq!(storage.get("b", "keep").await.unwrap(), shared);
+    }
+
+    #[tokio::test]
+    async fn test_lock_for_odd_hashes() {
+        let (_, storage) = dedup(ChunkingMode::Fixed).await;
+        for hash in ["", "a", "\u{e9}x", "zz"] {
+            let _ = storage.lock_for(hash);
+        }
+    }
+
+    #[test]
+    fn test_content_defined_chunks_survive_insertion() {
+        let chunker = Chunker::new(ChunkingMode::ContentDefined, 4096);
+        let data = pseudo_random(256 * 1024, 42);
+        let mut shifted = b"inserted prefix".to_vec();
+        shifted.extend_from_slice(&data);
+
+        let hashes = |data: &[u8]| -> Vec<String> {
+            chunker
+                .split(data)
+                .into_iter()
+                .map(|r| hafiz_crypto::sha256_hash(&data[r]))
+                .collect()
+        };
+        let original = hashes(&data);
+        let moved = hashes(&shifted);
+        let shared = moved.iter().filter(|h| original.contains(h)).count();
+        assert!(shared + 2 >= original.len(), "{} of {} shared", shared, original.len());
+
+        let total: usize = chunker.split(&data).iter().map(|r| r.len()).sum();
+        assert_eq!(total, data.len());
+    }
+}
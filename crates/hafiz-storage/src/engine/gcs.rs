@@ -0,0 +1,279 @@
+//! Google Cloud Storage backend
+//!
+//! Each bucket maps onto a GCS bucket of the same name. Multipart upload
+//! parts are stored as temporary objects next to the target; completing the
+//! upload streams them, in order, into a resumable upload session so the
+//! assembled object never has to be held in memory at once.
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use google_cloud_storage::client::google_cloud_auth::credentials::CredentialsFile;
+use google_cloud_storage::client::{Client, ClientConfig};
+use google_cloud_storage::http::buckets::get::GetBucketRequest;
+use google_cloud_storage::http::buckets::insert::{BucketCreationConfig, InsertBucketRequest};
+use google_cloud_storage::http::buckets::delete::DeleteBucketRequest;
+use google_cloud_storage::http::objects::delete::DeleteObjectRequest;
+use google_cloud_storage::http::objects::download::Range;
+use google_cloud_storage::http::objects::get::GetObjectRequest;
+use google_cloud_storage::http::objects::upload::{Media, UploadObjectRequest, UploadType};
+use google_cloud_storage::http::resumable_upload_client::{ChunkSize, UploadStatus};
+use hafiz_core::config::GcsBackendConfig;
+use hafiz_core::{Error, Result};
+use tracing::{debug, info};
+
+use super::{part_key, ObjectStat, StorageEngine};
+
+/// Resumable upload chunk size; GCS requires a multiple of 256 KiB
+const CHUNK_SIZE: usize = 32 * 256 * 1024;
+
+/// Storage engine backed by Google Cloud Storage
+pub struct GcsStorage {
+    client: Client,
+}
+
+impl GcsStorage {
+    /// Connect with the configured service account key, or application
+    /// default credentials when none is set
+    pub async fn new(config: &GcsBackendConfig) -> Result<Self> {
+        let client_config = match &config.credentials_file {
+            Some(path) => {
+                let path = path.to_string_lossy();
+                let credentials = CredentialsFile::new_from_file(path.to_string())
+                    .await
+                    .map_err(|e| {
+                        Error::InvalidArgument(format!("Invalid GCS credentials {}: {}", path, e))
+                    })?;
+                ClientConfig::default().with_credentials(credentials).await
+            }
+            None => ClientConfig::default().with_auth().await,
+        }
+        .map_err(|e| Error::InternalError(format!("GCS authentication failed: {}", e)))?;
+
+        info!("Google Cloud Storage backend ready");
+        Ok(Self {
+            client: Client::new(client_config),
+        })
+    }
+
+    fn object_request(bucket: &str, key: &str) -> GetObjectRequest {
+        GetObjectRequest {
+            bucket: bucket.to_string(),
+            object: key.to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn upload(&self, bucket: &str, key: &str, data: Bytes) -> Result<()> {
+        let request = UploadObjectRequest {
+            bucket: bucket.to_string(),
+            ..Default::default()
+        };
+        let upload_type = UploadType::Simple(Media::new(key.to_string()));
+        self.client
+            .upload_object(&request, data.to_vec(), &upload_type)
+            .await
+            .map(|_| ())
+            .map_err(gcs_error)
+    }
+}
+
+#[async_trait]
+impl StorageEngine for GcsStorage {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        let etag = hafiz_crypto::md5_hash(&data);
+        let size = data.len();
+        self.upload(bucket, key, data).await?;
+
+        debug!("Stored GCS object {}/{} ({} bytes)", bucket, key, size);
+        Ok(etag)
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        match self
+            .client
+            .download_object(&Self::object_request(bucket, key), &Range::default())
+            .await
+        {
+            Ok(data) => Ok(Bytes::from(data)),
+            Err(e) if is_not_found(&e) => Err(Error::NoSuchKey),
+            Err(e) => Err(gcs_error(e)),
+        }
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        let range = Range(Some(start as u64), Some(end as u64));
+        match self
+            .client
+            .download_object(&Self::object_request(bucket, key), &range)
+            .await
+        {
+            Ok(data) => Ok(Bytes::from(data)),
+            Err(e) if is_not_found(&e) => Err(Error::NoSuchKey),
+            Err(e) => Err(gcs_error(e)),
+        }
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        let request = DeleteObjectRequest {
+            bucket: bucket.to_string(),
+            object: key.to_string(),
+            ..Default::default()
+        };
+        match self.client.delete_object(&request).await {
+            Ok(()) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(gcs_error(e)),
+        }
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        Ok(self.stat(bucket, key).await?.is_some())
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        self.stat(bucket, key)
+            .await?
+            .map(|stat| stat.size)
+            .ok_or(Error::NoSuchKey)
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        if self.bucket_exists(bucket).await? {
+            return Ok(());
+        }
+        let request = InsertBucketRequest {
+            name: bucket.to_string(),
+            bucket: BucketCreationConfig::default(),
+            ..Default::default()
+        };
+        self.client
+            .insert_bucket(&request)
+            .await
+            .map(|_| ())
+            .map_err(gcs_error)
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        let request = DeleteBucketRequest {
+            bucket: bucket.to_string(),
+            ..Default::default()
+        };
+        match self.client.delete_bucket(&request).await {
+            Ok(()) => Ok(()),
+            Err(e) if is_not_found(&e) => Ok(()),
+            Err(e) => Err(gcs_error(e)),
+        }
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        let request = GetBucketRequest {
+            bucket: bucket.to_string(),
+            ..Default::default()
+        };
+        match self.client.get_bucket(&request).await {
+            Ok(_) => Ok(true),
+            Err(e) if is_not_found(&e) => Ok(false),
+            Err(e) => Err(gcs_error(e)),
+        }
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        let object = match self.client.get_object(&Self::object_request(bucket, key)).await {
+            Ok(object) => object,
+            Err(e) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(gcs_error(e)),
+        };
+
+        Ok(Some(ObjectStat {
+            size: object.size,
+            etag: object.etag,
+            content_type: object.content_type,
+            last_modified: object
+                .updated
+                .and_then(|t| DateTime::from_timestamp(t.unix_timestamp(), 0))
+                .unwrap_or_else(Utc::now),
+        }))
+    }
+
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        let request = UploadObjectRequest {
+            bucket: bucket.to_string(),
+            ..Default::default()
+        };
+        let upload_type = UploadType::Simple(Media::new(key.to_string()));
+        let session = self
+            .client
+            .prepare_resumable_upload(&request, &upload_type)
+            .await
+            .map_err(gcs_error)?;
+
+        // Chunks other than the last must be exactly CHUNK_SIZE, so part data
+        // is carried over between parts. At least one byte is always held
+        // back for the final chunk, which is the one that declares the size.
+        let mut pending: Vec<u8> = Vec::new();
+        let mut offset: u64 = 0;
+        for &part_number in part_numbers {
+            let part = self
+                .get(bucket, &part_key(key, upload_id, part_number))
+                .await?;
+            pending.extend_from_slice(&part);
+
+            while pending.len() > CHUNK_SIZE {
+                let rest = pending.split_off(CHUNK_SIZE);
+                let chunk = std::mem::replace(&mut pending, rest);
+                let size = ChunkSize::new(offset, offset + CHUNK_SIZE as u64 - 1, None);
+                session
+                    .upload_multiple_chunk(chunk, &size)
+                    .await
+                    .map_err(gcs_error)?;
+                offset += CHUNK_SIZE as u64;
+            }
+        }
+
+        let total = offset + pending.len() as u64;
+        if total == 0 {
+            // A session cannot finish without a chunk; write the empty object directly
+            self.upload(bucket, key, Bytes::new()).await?;
+        } else {
+            let size = ChunkSize::new(offset, total - 1, Some(total));
+            match session
+                .upload_multiple_chunk(pending, &size)
+                .await
+                .map_err(gcs_error)?
+            {
+                UploadStatus::Ok(_) => {}
+                status => {
+                    return Err(Error::InternalError(format!(
+                        "GCS upload of {}/{} did not finish: {:?}",
+                        bucket, key, status
+                    )))
+                }
+            }
+        }
+
+        debug!(
+            "Assembled {}/{} from {} parts ({} bytes)",
+            bucket,
+            key,
+            part_numbers.len(),
+            total
+        );
+        self.abort_parts(bucket, key, upload_id, part_numbers).await?;
+        Ok(total as i64)
+    }
+}
+
+fn is_not_found(e: &google_cloud_storage::http::Error) -> bool {
+    matches!(e, google_cloud_storage::http::Error::Response(r) if r.code == 404)
+}
+
+fn gcs_error(e: google_cloud_storage::http::Error) -> Error {
+    Error::InternalError(format!("GCS request failed: {}", e))
+}
@@ -0,0 +1,103 @@
+//! In-memory storage backend
+//!
+//! Keeps object data in process memory. Useful for scratch buckets and
+//! tests; everything is lost when the server stops.
+
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hafiz_core::{Error, Result};
+use parking_lot::RwLock;
+
+use super::StorageEngine;
+
+/// Storage engine that keeps objects in memory
+#[derive(Default)]
+pub struct MemoryStorage {
+    buckets: RwLock<HashMap<String, HashMap<String, Bytes>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total bytes held
+    pub fn used_bytes(&self) -> u64 {
+        self.buckets
+            .read()
+            .values()
+            .flat_map(HashMap::values)
+            .map(|data| data.len() as u64)
+            .sum()
+    }
+}
+
+#[async_trait]
+impl StorageEngine for MemoryStorage {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        let etag = hafiz_crypto::md5_hash(&data);
+        self.buckets
+            .write()
+            .entry(bucket.to_string())
+            .or_default()
+            .insert(key.to_string(), data);
+        Ok(etag)
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        self.buckets
+            .read()
+            .get(bucket)
+            .and_then(|objects| objects.get(key))
+            .cloned()
+            .ok_or(Error::NoSuchKey)
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        let data = self.get(bucket, key).await?;
+        if start < 0 || start as usize >= data.len() {
+            return Err(Error::InvalidRange(format!("bytes={}-{}", start, end)));
+        }
+        let end = (end as usize).min(data.len() - 1);
+        Ok(data.slice(start as usize..=end))
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        if let Some(objects) = self.buckets.write().get_mut(bucket) {
+            objects.remove(key);
+        }
+        Ok(())
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        Ok(self
+            .buckets
+            .read()
+            .get(bucket)
+            .is_some_and(|objects| objects.contains_key(key)))
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        self.get(bucket, key).await.map(|data| data.len() as i64)
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        self.buckets.write().entry(bucket.to_string()).or_default();
+        Ok(())
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        let mut buckets = self.buckets.write();
+        if buckets.get(bucket).is_some_and(|objects| !objects.is_empty()) {
+            return Err(Error::BucketNotEmpty);
+        }
+        buckets.remove(bucket);
+        Ok(())
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        Ok(self.buckets.read().contains_key(bucket))
+    }
+}
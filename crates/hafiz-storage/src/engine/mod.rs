@@ -1,13 +1,54 @@
 //! Storage engine implementations
 
+#[cfg(feature = "azure")]
+mod azure;
+mod cache;
+mod compression;
+mod dedup;
+#[cfg(feature = "gcs")]
+mod gcs;
+mod memory;
+mod read_cache;
+mod router;
+mod s3;
+mod space;
+mod traced;
+
+#[cfg(feature = "azure")]
+pub use azure::AzureBlobStorage;
+pub use cache::{CacheStats, ObjectCache};
+pub use compression::CompressedStorage;
+pub use dedup::{Chunker, DedupStorage, CHUNK_BUCKET};
+#[cfg(feature = "gcs")]
+pub use gcs::GcsStorage;
+pub use memory::MemoryStorage;
+pub use read_cache::ReadCache;
+pub use router::{open_backend, StorageRouter};
+pub use s3::{S3Client, S3Gateway};
+pub use space::{SpaceGuard, SpaceMonitor};
+pub use traced::{StorageLatency, TracedStorage};
+
 use async_trait::async_trait;
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use hafiz_core::config::FsyncPolicy;
 use hafiz_core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::fs;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 use tracing::{debug, info, warn};
 
+/// Object data read incrementally
+pub type ByteStream = BoxStream<'static, Result<Bytes>>;
+
+/// Default size of the chunks streamed reads are served in
+pub const STREAM_CHUNK_SIZE: usize = 256 * 1024;
+
 /// Storage engine trait
 #[async_trait]
 pub trait StorageEngine: Send + Sync {
@@ -20,6 +61,30 @@ pub trait StorageEngine: Send + Sync {
     /// Retrieve partial object data
     async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes>;
 
+    /// Stream a whole object without buffering it.
+    ///
+    /// By default the object is read with [`get`](Self::get) and sent as one
+    /// chunk; backends that can read incrementally override this.
+    async fn get_stream(&self, bucket: &str, key: &str) -> Result<ByteStream> {
+        let data = self.get(bucket, key).await?;
+        Ok(stream::once(async move { Ok(data) }).boxed())
+    }
+
+    /// Stream bytes `start..=end` of an object without buffering them.
+    ///
+    /// By default the range is read with [`get_range`](Self::get_range) and
+    /// sent as one chunk; backends that can read incrementally override this.
+    async fn get_range_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<ByteStream> {
+        let data = self.get_range(bucket, key, start, end).await?;
+        Ok(stream::once(async move { Ok(data) }).boxed())
+    }
+
     /// Delete object
     async fn delete(&self, bucket: &str, key: &str) -> Result<()>;
 
@@ -37,58 +102,373 @@ pub trait StorageEngine: Send + Sync {
 
     /// Check if bucket exists
     async fn bucket_exists(&self, bucket: &str) -> Result<bool>;
+
+    /// Copy an object to another key, in the same bucket or another one.
+    ///
+    /// By default the data is read and written again. Engines that can share
+    /// data between keys override this to avoid moving the bytes; a later
+    /// write to either key must leave the other one unchanged.
+    async fn copy(&self, src_bucket: &str, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        let data = self.get(src_bucket, src_key).await?;
+        self.put(dst_bucket, dst_key, data).await?;
+        Ok(())
+    }
+
+    /// Describe an object the metadata store may not know about, such as one
+    /// that only exists upstream of a gateway. `None` if there is no such object.
+    async fn stat(&self, _bucket: &str, _key: &str) -> Result<Option<ObjectStat>> {
+        Ok(None)
+    }
+
+    /// Verify the backend is usable
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Store one part of a multipart upload, returning its ETag.
+    ///
+    /// By default parts are stored as ordinary objects under [`part_key`].
+    async fn put_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        self.put(bucket, &part_key(key, upload_id, part_number), data)
+            .await
+    }
+
+    /// Assemble the given parts, in order, into the object and discard them.
+    /// Returns the size of the assembled object.
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        let mut data = BytesMut::new();
+        for &part_number in part_numbers {
+            let part = self
+                .get(bucket, &part_key(key, upload_id, part_number))
+                .await?;
+            data.extend_from_slice(&part);
+        }
+
+        let size = data.len() as i64;
+        self.put(bucket, key, data.freeze()).await?;
+        self.abort_parts(bucket, key, upload_id, part_numbers).await?;
+        Ok(size)
+    }
+
+    /// Discard the stored parts of an upload
+    async fn abort_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<()> {
+        for &part_number in part_numbers {
+            self.delete(bucket, &part_key(key, upload_id, part_number))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// Storage key of a multipart upload part
+pub fn part_key(key: &str, upload_id: &str, part_number: i32) -> String {
+    format!("{}/.parts/{}/{}", key, upload_id, part_number)
+}
+
+/// Stream `len` bytes from `reader` in chunks of up to `chunk_size`.
+/// Data ending early is an error rather than a short body.
+pub fn read_stream<R>(reader: R, len: u64, chunk_size: usize) -> ByteStream
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    stream::try_unfold((reader, len), move |(mut reader, remaining)| async move {
+        if remaining == 0 {
+            return Ok(None);
+        }
+        let mut buf = BytesMut::with_capacity(chunk_size.min(remaining as usize));
+        // A single read may return less than asked for; fill the chunk
+        while buf.len() < buf.capacity() {
+            if reader.read_buf(&mut buf).await? == 0 {
+                break;
+            }
+        }
+        let n = buf.len();
+        if n == 0 {
+            return Err(Error::StorageError(format!(
+                "Object data ended {} bytes short",
+                remaining
+            )));
+        }
+        Ok(Some((buf.freeze(), (reader, remaining - n as u64))))
+    })
+    .boxed()
+}
+
+/// Attributes of an object as reported by a storage backend
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectStat {
+    pub size: i64,
+    pub etag: String,
+    pub content_type: Option<String>,
+    pub last_modified: DateTime<Utc>,
 }
 
 /// Local filesystem storage engine
 pub struct LocalStorage {
     data_dir: PathBuf,
+    read_chunk_size: usize,
+    fsync: FsyncPolicy,
+    fsync_interval: Duration,
+    assemble_concurrency: usize,
+    /// Files and directories written since the last batched flush
+    unsynced: Arc<Mutex<HashSet<PathBuf>>>,
 }
 
 impl LocalStorage {
     pub fn new(data_dir: impl AsRef<Path>) -> Self {
         Self {
             data_dir: data_dir.as_ref().to_path_buf(),
+            read_chunk_size: STREAM_CHUNK_SIZE,
+            fsync: FsyncPolicy::default(),
+            fsync_interval: Duration::from_millis(100),
+            assemble_concurrency: 8,
+            unsynced: Arc::default(),
         }
     }
 
+    /// Size of the chunks streamed GETs read the file in. Larger chunks
+    /// mean fewer reads and body frames per object.
+    pub fn with_read_chunk_size(mut self, size: usize) -> Self {
+        self.read_chunk_size = size.max(1);
+        self
+    }
+
+    /// When written data is flushed to disk; `interval` is how often
+    /// [`FsyncPolicy::Batched`] flushes
+    pub fn with_fsync(mut self, policy: FsyncPolicy, interval: Duration) -> Self {
+        self.fsync = policy;
+        self.fsync_interval = interval.max(Duration::from_millis(1));
+        self
+    }
+
+    /// Parts [`complete_parts`](StorageEngine::complete_parts) copies into
+    /// the object at once
+    pub fn with_assemble_concurrency(mut self, concurrency: usize) -> Self {
+        self.assemble_concurrency = concurrency.max(1);
+        self
+    }
+
     pub async fn init(&self) -> Result<()> {
         fs::create_dir_all(&self.data_dir).await?;
-        info!("Storage initialized at {:?}", self.data_dir);
+        if self.fsync == FsyncPolicy::Batched {
+            // Stops once the storage is dropped
+            let unsynced = Arc::downgrade(&self.unsynced);
+            let mut ticker = tokio::time::interval(self.fsync_interval);
+            tokio::spawn(async move {
+                loop {
+                    ticker.tick().await;
+                    let Some(unsynced) = unsynced.upgrade() else { break };
+                    flush_unsynced(&unsynced).await;
+                }
+            });
+        }
+        info!("Storage initialized at {:?} (fsync {:?})", self.data_dir, self.fsync);
+        Ok(())
+    }
+
+    /// Flush a file's data before it is renamed into place, if every
+    /// object is flushed
+    async fn sync_data(&self, file: &fs::File) -> Result<()> {
+        if self.fsync == FsyncPolicy::PerObject {
+            timed_sync(file).await?;
+        }
         Ok(())
     }
 
+    /// Make a file just put in place at `path` durable, as the fsync
+    /// policy asks: its directory entry now, or the file and the entry
+    /// with the next batch
+    async fn sync_entry(&self, path: &Path) -> Result<()> {
+        let dir = path.parent().unwrap_or(&self.data_dir);
+        match self.fsync {
+            FsyncPolicy::PerObject => sync_dir(dir).await?,
+            FsyncPolicy::Batched => {
+                let mut unsynced = self.unsynced.lock().unwrap();
+                unsynced.insert(path.to_path_buf());
+                unsynced.insert(dir.to_path_buf());
+            }
+            FsyncPolicy::Never => {}
+        }
+        Ok(())
+    }
+
+    /// File holding an object's data
+    ///
+    /// Keys are never used as paths: they may contain `..`, NUL, control
+    /// characters or be longer than a file name allows. The file is named
+    /// by the key's hash instead, under two levels of directories taken
+    /// from the hash (`ab/cd/abcd…`), so no directory holds more than a
+    /// few hundred entries below hundreds of millions of objects.
     fn object_path(&self, bucket: &str, key: &str) -> PathBuf {
-        // Hash-based directory structure to avoid too many files in one dir
         let hash = hafiz_crypto::md5_hash(key.as_bytes());
-        let prefix = &hash[..2];
         self.data_dir
             .join(bucket)
             .join("objects")
-            .join(prefix)
+            .join(&hash[..2])
+            .join(&hash[2..4])
             .join(&hash)
     }
 
+    /// Where [`object_path`](Self::object_path) put the file before the
+    /// second directory level, for data not yet moved by
+    /// [`migrate_layout`](Self::migrate_layout)
+    fn legacy_object_path(&self, bucket: &str, key: &str) -> PathBuf {
+        let hash = hafiz_crypto::md5_hash(key.as_bytes());
+        self.data_dir
+            .join(bucket)
+            .join("objects")
+            .join(&hash[..2])
+            .join(&hash)
+    }
+
+    /// File an object's data is in: its own path, or the legacy one if
+    /// only that exists
+    fn stored_path(&self, bucket: &str, key: &str) -> PathBuf {
+        let path = self.object_path(bucket, key);
+        if path.exists() {
+            return path;
+        }
+        let legacy = self.legacy_object_path(bucket, key);
+        if legacy.exists() {
+            legacy
+        } else {
+            path
+        }
+    }
+
     fn bucket_path(&self, bucket: &str) -> PathBuf {
         self.data_dir.join(bucket)
     }
 
-    /// Health check - verify storage is accessible
-    pub async fn health_check(&self) -> Result<()> {
-        // Check if data directory exists and is writable
-        if !self.data_dir.exists() {
-            return Err(Error::InternalError("Data directory does not exist".to_string()));
+    /// Move data stored in the single-level layout of earlier versions
+    /// into the current one
+    ///
+    /// Safe to run while the server is up and to run again after an
+    /// interruption: each file is linked into place without replacing
+    /// anything written there since, then unlinked from the old path.
+    pub async fn migrate_layout(&self) -> Result<LayoutMigration> {
+        let mut migration = LayoutMigration::default();
+        let mut buckets = match fs::read_dir(&self.data_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(migration),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(bucket) = buckets.next_entry().await? {
+            let objects = bucket.path().join("objects");
+            if !objects.is_dir() {
+                continue;
+            }
+            migration.buckets += 1;
+            let mut fan_out = fs::read_dir(&objects).await?;
+            while let Some(dir) = fan_out.next_entry().await? {
+                if dir.file_type().await?.is_dir() {
+                    migrate_fan_out_dir(&dir.path(), &mut migration).await?;
+                }
+            }
         }
+        info!(
+            "Storage layout of {:?}: moved {} files, dropped {} stale ones in {} buckets",
+            self.data_dir, migration.moved, migration.stale, migration.buckets
+        );
+        Ok(migration)
+    }
+}
 
-        // Try to create a temp file to verify write access
-        let test_file = self.data_dir.join(".health_check");
-        match fs::write(&test_file, "ok").await {
-            Ok(_) => {
-                let _ = fs::remove_file(&test_file).await;
-                Ok(())
+/// Files [`LocalStorage::migrate_layout`] dealt with
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LayoutMigration {
+    /// Bucket directories looked at
+    pub buckets: u64,
+    /// Files moved to their current path
+    pub moved: u64,
+    /// Old files dropped because newer data was already at the current path
+    pub stale: u64,
+}
+
+/// `fsync` a file, recording how long it took
+async fn timed_sync(file: &fs::File) -> std::io::Result<()> {
+    let started = Instant::now();
+    let result = file.sync_all().await;
+    metrics::histogram!("hafiz_storage_fsync_seconds").record(started.elapsed().as_secs_f64());
+    result
+}
+
+/// `fsync` a directory, so that entries created or renamed in it survive
+/// a crash
+async fn sync_dir(dir: &Path) -> std::io::Result<()> {
+    if cfg!(unix) {
+        timed_sync(&fs::File::open(dir).await?).await
+    } else {
+        Ok(())
+    }
+}
+
+/// Flush everything written since the last batch
+async fn flush_unsynced(unsynced: &Mutex<HashSet<PathBuf>>) {
+    let paths = std::mem::take(&mut *unsynced.lock().unwrap());
+    for path in paths {
+        let synced = async {
+            if path.is_dir() {
+                sync_dir(&path).await
+            } else {
+                timed_sync(&fs::File::open(&path).await?).await
             }
-            Err(e) => Err(Error::InternalError(format!("Storage not writable: {}", e))),
         }
+        .await;
+        match synced {
+            Ok(()) => {}
+            // Deleted or replaced since; the replacement is queued itself
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!("Failed to flush {:?}: {}", path, e),
+        }
+    }
+}
+
+/// Move the legacy files of one first-level directory down a level
+async fn migrate_fan_out_dir(dir: &Path, migration: &mut LayoutMigration) -> Result<()> {
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name();
+        let Some(name) = name.to_str() else { continue };
+        // Object files are named by a hex MD5; anything else, such as
+        // the second-level directories, is not legacy data
+        if name.len() != 32 || !name.bytes().all(|b| b.is_ascii_hexdigit()) {
+            continue;
+        }
+        if !entry.file_type().await?.is_file() {
+            continue;
+        }
+
+        let target_dir = dir.join(&name[2..4]);
+        fs::create_dir_all(&target_dir).await?;
+        match fs::hard_link(entry.path(), target_dir.join(name)).await {
+            Ok(()) => migration.moved += 1,
+            // Written since in the current layout; the old data is stale
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => migration.stale += 1,
+            Err(e) => return Err(e.into()),
+        }
+        fs::remove_file(entry.path()).await?;
     }
+    Ok(())
 }
 
 #[async_trait]
@@ -100,9 +480,24 @@ impl StorageEngine for LocalStorage {
             fs::create_dir_all(parent).await?;
         }
 
-        let mut file = fs::File::create(&path).await?;
-        file.write_all(&data).await?;
-        file.sync_all().await?;
+        // Written aside and renamed over the old file, so that copies
+        // hard-linked to it keep their data
+        let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        let mut file = fs::File::create(&tmp).await?;
+        let written = async {
+            file.write_all(&data).await?;
+            self.sync_data(&file).await?;
+            fs::rename(&tmp, &path).await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+        // Data from before the layout migration is superseded
+        let _ = fs::remove_file(self.legacy_object_path(bucket, key)).await;
+        self.sync_entry(&path).await?;
 
         let etag = hafiz_crypto::md5_hash(&data);
         debug!("Stored object {}/{} ({} bytes)", bucket, key, data.len());
@@ -111,7 +506,7 @@ impl StorageEngine for LocalStorage {
     }
 
     async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
-        let path = self.object_path(bucket, key);
+        let path = self.stored_path(bucket, key);
 
         if !path.exists() {
             return Err(Error::NoSuchKey);
@@ -124,7 +519,7 @@ impl StorageEngine for LocalStorage {
     }
 
     async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
-        let path = self.object_path(bucket, key);
+        let path = self.stored_path(bucket, key);
 
         if !path.exists() {
             return Err(Error::NoSuchKey);
@@ -141,24 +536,148 @@ impl StorageEngine for LocalStorage {
         Ok(Bytes::from(buffer))
     }
 
-    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
-        let path = self.object_path(bucket, key);
+    async fn get_stream(&self, bucket: &str, key: &str) -> Result<ByteStream> {
+        let path = self.stored_path(bucket, key);
 
-        if path.exists() {
-            fs::remove_file(&path).await?;
-            debug!("Deleted object {}/{}", bucket, key);
+        let file = match fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(Error::NoSuchKey),
+            Err(e) => return Err(e.into()),
+        };
+        let len = file.metadata().await?.len();
+
+        Ok(read_stream(file, len, self.read_chunk_size))
+    }
+
+    async fn get_range_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<ByteStream> {
+        let path = self.stored_path(bucket, key);
+
+        let mut file = match fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(Error::NoSuchKey),
+            Err(e) => return Err(e.into()),
+        };
+        file.seek(std::io::SeekFrom::Start(start as u64)).await?;
+
+        Ok(read_stream(file, (end - start + 1) as u64, self.read_chunk_size))
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        for path in [self.object_path(bucket, key), self.legacy_object_path(bucket, key)] {
+            if path.exists() {
+                fs::remove_file(&path).await?;
+                debug!("Deleted object {}/{}", bucket, key);
+            }
         }
 
         Ok(())
     }
 
     async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
-        let path = self.object_path(bucket, key);
+        let path = self.stored_path(bucket, key);
         Ok(path.exists())
     }
 
-    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+    /// Hard-links the copy to the original, falling back to copying the
+    /// file when they are on different filesystems
+    async fn copy(&self, src_bucket: &str, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        let src = self.stored_path(src_bucket, src_key);
+        let dst = self.object_path(dst_bucket, dst_key);
+        if !src.exists() {
+            return Err(Error::NoSuchKey);
+        }
+        if let Some(parent) = dst.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        for path in [dst.clone(), self.legacy_object_path(dst_bucket, dst_key)] {
+            match fs::remove_file(&path).await {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        if let Err(e) = fs::hard_link(&src, &dst).await {
+            debug!("Copying {}/{} instead of linking it: {}", src_bucket, src_key, e);
+            fs::copy(&src, &dst).await?;
+            self.sync_data(&fs::File::open(&dst).await?).await?;
+        }
+        self.sync_entry(&dst).await?;
+
+        debug!("Copied object {}/{} to {}/{}", src_bucket, src_key, dst_bucket, dst_key);
+        Ok(())
+    }
+
+    /// Copies the parts into the object file concurrently, each at its
+    /// offset, rather than through memory
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        let mut parts = Vec::with_capacity(part_numbers.len());
+        let mut size = 0;
+        for &part_number in part_numbers {
+            let part = self.stored_path(bucket, &part_key(key, upload_id, part_number));
+            let len = match fs::metadata(&part).await {
+                Ok(metadata) => metadata.len(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(Error::NoSuchKey),
+                Err(e) => return Err(e.into()),
+            };
+            parts.push((part, size));
+            size += len;
+        }
+
         let path = self.object_path(bucket, key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let tmp = path.with_extension(format!("tmp-{}", uuid::Uuid::new_v4().simple()));
+        let file = fs::File::create(&tmp).await?;
+        let assembled = async {
+            file.set_len(size).await?;
+            stream::iter(parts)
+                .map(|(part, offset)| {
+                    let tmp = &tmp;
+                    async move {
+                        let mut src = fs::File::open(&part).await?;
+                        let mut dst = fs::OpenOptions::new().write(true).open(tmp).await?;
+                        dst.seek(std::io::SeekFrom::Start(offset)).await?;
+                        tokio::io::copy(&mut src, &mut dst).await?;
+                        dst.flush().await?;
+                        Ok::<_, Error>(())
+                    }
+                })
+                .buffer_unordered(self.assemble_concurrency)
+                .try_collect::<Vec<()>>()
+                .await?;
+            self.sync_data(&file).await?;
+            fs::rename(&tmp, &path).await?;
+            Ok::<_, Error>(())
+        }
+        .await;
+        if let Err(e) = assembled {
+            let _ = fs::remove_file(&tmp).await;
+            return Err(e);
+        }
+        let _ = fs::remove_file(self.legacy_object_path(bucket, key)).await;
+        self.sync_entry(&path).await?;
+
+        self.abort_parts(bucket, key, upload_id, part_numbers).await?;
+        debug!("Assembled {}/{} from {} parts ({} bytes)", bucket, key, part_numbers.len(), size);
+        Ok(size as i64)
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        let path = self.stored_path(bucket, key);
 
         if !path.exists() {
             return Err(Error::NoSuchKey);
@@ -199,7 +718,268 @@ impl StorageEngine for LocalStorage {
         let path = self.bucket_path(bucket);
         Ok(path.exists())
     }
+
+    /// Health check - verify storage is accessible
+    async fn health_check(&self) -> Result<()> {
+        // Check if data directory exists and is writable
+        if !self.data_dir.exists() {
+            return Err(Error::InternalError("Data directory does not exist".to_string()));
+        }
+
+        // Try to create a temp file to verify write access
+        let test_file = self.data_dir.join(".health_check");
+        match fs::write(&test_file, "ok").await {
+            Ok(_) => {
+                let _ = fs::remove_file(&test_file).await;
+                Ok(())
+            }
+            Err(e) => Err(Error::InternalError(format!("Storage not writable: {}", e))),
+        }
+    }
 }
 
 // Add seek import
 use tokio::io::AsyncSeekExt;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    async fn collect(mut stream: ByteStream) -> Result<Vec<Bytes>> {
+        let mut chunks = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            chunks.push(chunk?);
+        }
+        Ok(chunks)
+    }
+
+    #[tokio::test]
+    async fn test_read_stream() {
+        let data = vec![7u8; STREAM_CHUNK_SIZE * 2 + 10];
+        let len = data.len() as u64 - 5;
+        let chunks = collect(read_stream(Cursor::new(data.clone()), len, STREAM_CHUNK_SIZE))
+            .await
+            .unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].len(), STREAM_CHUNK_SIZE);
+        assert_eq!(chunks.concat(), &data[..data.len() - 5]);
+
+        assert!(collect(read_stream(Cursor::new(vec![1u8; 10]), 20, 4)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_copy_is_isolated_from_overwrites() {
+        let dir = std::env::temp_dir().join(format!("hafiz-copy-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&dir);
+        storage.put("a", "key", Bytes::from_static(b"first")).await.unwrap();
+
+        storage.copy("a", "key", "b", "copy").await.unwrap();
+        storage.put("a", "key", Bytes::from_static(b"second")).await.unwrap();
+        assert_eq!(storage.get("b", "copy").await.unwrap(), Bytes::from_static(b"first"));
+        assert_eq!(storage.get("a", "key").await.unwrap(), Bytes::from_static(b"second"));
+
+        // Copying over an existing key replaces it
+        storage.copy("a", "key", "b", "copy").await.unwrap();
+        assert_eq!(storage.get("b", "copy").await.unwrap(), Bytes::from_static(b"second"));
+        assert!(matches!(storage.copy("a", "missing", "b", "x").await, Err(Error::NoSuchKey)));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_not_paths() {
+        let dir = std::env::temp_dir().join(format!("hafiz-keys-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&dir);
+        let keys = [
+            "../../escape",
+            "a/b/../c",
+            "with space+plus%25",
+            "nul\0and\u{1}control\r\n",
+            "\u{fc}nic\u{f6}de/\u{1F600}",
+        ];
+        let long = "k".repeat(1024);
+        for (i, key) in keys.iter().copied().chain([long.as_str()]).enumerate() {
+            let path = storage.object_path("bucket", key);
+            assert!(path.starts_with(dir.join("bucket").join("objects")));
+            assert_eq!(path.file_name().unwrap().len(), 32);
+
+            let data = Bytes::from(format!("object {}", i));
+            storage.put("bucket", key, data.clone()).await.unwrap();
+            assert_eq!(storage.get("bucket", key).await.unwrap(), data);
+        }
+        assert!(!dir.join("escape").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_layout() {
+        let dir = std::env::temp_dir().join(format!("hafiz-layout-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&dir);
+        storage.create_bucket("b").await.unwrap();
+        for (key, data) in [("old", "old data"), ("replaced", "stale"), ("copied", "shared")] {
+            let legacy = storage.legacy_object_path("b", key);
+            std::fs::create_dir_all(legacy.parent().unwrap()).unwrap();
+            std::fs::write(&legacy, data).unwrap();
+        }
+
+        // Legacy data stays readable until it is moved
+        assert_eq!(storage.get("b", "old").await.unwrap(), Bytes::from_static(b"old data"));
+        storage.put("b", "replaced", Bytes::from_static(b"fresh")).await.unwrap();
+        storage.copy("b", "copied", "b", "copy").await.unwrap();
+        // A stale legacy file left behind, as if written by a racing writer
+        std::fs::write(storage.legacy_object_path("b", "replaced"), "stale").unwrap();
+
+        let migration = storage.migrate_layout().await.unwrap();
+        assert_eq!(migration, LayoutMigration { buckets: 1, moved: 2, stale: 1 });
+        for key in ["old", "replaced", "copied"] {
+            assert!(!storage.legacy_object_path("b", key).exists());
+            assert!(storage.object_path("b", key).exists());
+        }
+        assert_eq!(storage.get("b", "replaced").await.unwrap(), Bytes::from_static(b"fresh"));
+        assert_eq!(storage.get("b", "copy").await.unwrap(), Bytes::from_static(b"shared"));
+
+        // Nothing is left to do the second time
+        let again = storage.migrate_layout().await.unwrap();
+        assert_eq!(again, LayoutMigration { buckets: 1, moved: 0, stale: 0 });
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_complete_parts_in_parallel() {
+        let dir = std::env::temp_dir().join(format!("hafiz-parts-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&dir).with_assemble_concurrency(3);
+        let parts: Vec<Vec<u8>> = (0..7u8).map(|i| vec![i; 1000 + i as usize * 100]).collect();
+        for (i, part) in parts.iter().enumerate() {
+            let number = i as i32 + 1;
+            storage
+                .put_part("b", "big", "up", number, Bytes::from(part.clone()))
+                .await
+                .unwrap();
+        }
+        let numbers: Vec<i32> = (1..=7).collect();
+
+        let size = storage.complete_parts("b", "big", "up", &numbers).await.unwrap();
+        assert_eq!(size as usize, parts.iter().map(Vec::len).sum::<usize>());
+        assert_eq!(storage.get("b", "big").await.unwrap(), parts.concat());
+        assert!(!storage.exists("b", &part_key("big", "up", 1)).await.unwrap());
+
+        // A missing part fails the upload and leaves nothing behind
+        storage.put_part("b", "other", "up", 1, Bytes::from_static(b"x")).await.unwrap();
+        assert!(matches!(
+            storage.complete_parts("b", "other", "up", &[1, 2]).await,
+            Err(Error::NoSuchKey)
+        ));
+        assert!(!storage.exists("b", "other").await.unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_fsync_policies() {
+        let dir = std::env::temp_dir().join(format!("hafiz-fsync-{}", uuid::Uuid::new_v4()));
+        for policy in [FsyncPolicy::PerObject, FsyncPolicy::Batched, FsyncPolicy::Never] {
+            let storage = LocalStorage::new(&dir).with_fsync(policy, Duration::from_millis(10));
+            storage.init().await.unwrap();
+            storage.put("b", "k", Bytes::from_static(b"data")).await.unwrap();
+            storage.copy("b", "k", "b", "copy").await.unwrap();
+            assert_eq!(storage.get("b", "copy").await.unwrap(), Bytes::from_static(b"data"));
+
+            let queued = storage.unsynced.lock().unwrap().len();
+            if policy == FsyncPolicy::Batched {
+                assert!(queued > 0);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                assert!(storage.unsynced.lock().unwrap().is_empty());
+            } else {
+                assert_eq!(queued, 0);
+            }
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Small-object PUT rate under each fsync policy, and multipart
+    /// assembly with a few concurrencies:
+    /// `cargo test -p hafiz-storage --release -- --ignored --nocapture bench_put`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_put() {
+        const OBJECTS: usize = 2000;
+        const PARTS: i32 = 64;
+        const PART_SIZE: usize = 8 * 1024 * 1024;
+
+        let dir = std::env::temp_dir().join(format!("hafiz-bench-{}", uuid::Uuid::new_v4()));
+        let data = Bytes::from(vec![0x5a; 4096]);
+        for policy in [FsyncPolicy::PerObject, FsyncPolicy::Batched, FsyncPolicy::Never] {
+            let storage = LocalStorage::new(&dir).with_fsync(policy, Duration::from_millis(100));
+            storage.init().await.unwrap();
+            let start = std::time::Instant::now();
+            for i in 0..OBJECTS {
+                storage.put("bench", &format!("small/{}", i), data.clone()).await.unwrap();
+            }
+            let elapsed = start.elapsed().as_secs_f64();
+            println!("{:>16}: {:>8.0} PUT/s", format!("{:?}", policy), OBJECTS as f64 / elapsed);
+        }
+
+        let part = Bytes::from(vec![0xa5; PART_SIZE]);
+        let numbers: Vec<i32> = (1..=PARTS).collect();
+        for concurrency in [1, 4, 16] {
+            let storage = LocalStorage::new(&dir).with_assemble_concurrency(concurrency);
+            for &number in &numbers {
+                storage.put_part("bench", "big", "up", number, part.clone()).await.unwrap();
+            }
+            let start = std::time::Instant::now();
+            storage.complete_parts("bench", "big", "up", &numbers).await.unwrap();
+            let mib = (PART_SIZE as f64 * PARTS as f64) / (1024.0 * 1024.0);
+            println!(
+                "{:>16}: {:>8.0} MiB/s",
+                format!("assemble x{}", concurrency),
+                mib / start.elapsed().as_secs_f64()
+            );
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// Whole-object GET throughput, buffered against streamed with a few
+    /// chunk sizes: `cargo test -p hafiz-storage --release -- --ignored --nocapture bench_get`
+    #[tokio::test]
+    #[ignore]
+    async fn bench_get() {
+        const SIZE: usize = 512 * 1024 * 1024;
+        const ROUNDS: u32 = 5;
+
+        let dir = std::env::temp_dir().join(format!("hafiz-bench-{}", uuid::Uuid::new_v4()));
+        let storage = LocalStorage::new(&dir);
+        storage.put("bench", "object", Bytes::from(vec![0x5a; SIZE])).await.unwrap();
+
+        let report = |name: String, elapsed: std::time::Duration| {
+            let mib = (SIZE as f64 * ROUNDS as f64) / (1024.0 * 1024.0);
+            println!("{:>16}: {:>8.0} MiB/s", name, mib / elapsed.as_secs_f64());
+        };
+
+        let start = std::time::Instant::now();
+        for _ in 0..ROUNDS {
+            assert_eq!(storage.get("bench", "object").await.unwrap().len(), SIZE);
+        }
+        report("buffered".into(), start.elapsed());
+
+        for chunk_size in [64 * 1024, 256 * 1024, 1024 * 1024, 4 * 1024 * 1024] {
+            let storage = LocalStorage::new(&dir).with_read_chunk_size(chunk_size);
+            let start = std::time::Instant::now();
+            for _ in 0..ROUNDS {
+                let mut stream = storage.get_stream("bench", "object").await.unwrap();
+                let mut total = 0;
+                while let Some(chunk) = stream.next().await {
+                    total += chunk.unwrap().len();
+                }
+                assert_eq!(total, SIZE);
+            }
+            report(format!("stream {} KiB", chunk_size / 1024), start.elapsed());
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
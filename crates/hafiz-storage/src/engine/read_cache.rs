@@ -0,0 +1,520 @@
+//! Read-through cache of object data
+//!
+//! [`ReadCache`] keeps the data of recent GETs in memory, whole objects and
+//! ranges alike, keyed by bucket, key, ETag and range. A range is also
+//! served from a cached whole object. With a disk tier, whole objects are
+//! also kept in an [`ObjectCache`] directory, typically on a local NVMe
+//! drive, for working sets larger than memory; its hits are promoted back
+//! into memory.
+//!
+//! Writes through the cache drop the entries of the object they change.
+//! As the ETag is part of every key, an entry left by a write that went
+//! around the cache is never served for the new data, and ages out.
+//!
+//! Hits and misses are counted in `hafiz_cache_hits_total` and
+//! `hafiz_cache_misses_total` under `cache="object_data"` for memory and
+//! `cache="object_data_disk"` for the disk tier.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::Utc;
+use hafiz_core::Result;
+use parking_lot::Mutex;
+use tracing::warn;
+
+use super::{ByteStream, CacheStats, ObjectCache, ObjectStat, StorageEngine};
+
+/// Cached data: an object version, or an inclusive byte range of it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct ReadKey {
+    bucket: String,
+    key: String,
+    etag: String,
+    range: Option<(i64, i64)>,
+}
+
+impl ReadKey {
+    fn whole(&self) -> Self {
+        Self {
+            range: None,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug)]
+struct MemoryEntry {
+    data: Bytes,
+    /// Position in the recency order
+    tick: u64,
+}
+
+#[derive(Debug, Default)]
+struct MemoryTier {
+    entries: HashMap<ReadKey, MemoryEntry>,
+    /// Recency order, least recently used first
+    order: BTreeMap<u64, ReadKey>,
+    /// Cached entries of each object, to drop them together
+    by_object: HashMap<(String, String), HashSet<ReadKey>>,
+    used_bytes: u64,
+    next_tick: u64,
+    /// Bumped by every invalidation. A miss only stores what it read when
+    /// no invalidation happened meanwhile, so a read racing a write cannot
+    /// put the old data back.
+    generation: u64,
+}
+
+impl MemoryTier {
+    fn get(&mut self, key: &ReadKey) -> Option<Bytes> {
+        let tick = self.next_tick;
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.tick);
+        entry.tick = tick;
+        self.order.insert(tick, key.clone());
+        self.next_tick += 1;
+        Some(entry.data.clone())
+    }
+
+    fn insert(&mut self, key: ReadKey, data: Bytes, max_bytes: u64) {
+        self.remove(&key);
+        let tick = self.next_tick;
+        self.next_tick += 1;
+        self.used_bytes += data.len() as u64;
+        self.order.insert(tick, key.clone());
+        self.by_object
+            .entry((key.bucket.clone(), key.key.clone()))
+            .or_default()
+            .insert(key.clone());
+        self.entries.insert(key, MemoryEntry { data, tick });
+
+        while self.used_bytes > max_bytes {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+    }
+
+    fn remove(&mut self, key: &ReadKey) {
+        let Some(entry) = self.entries.remove(key) else {
+            return;
+        };
+        self.order.remove(&entry.tick);
+        self.used_bytes = self.used_bytes.saturating_sub(entry.data.len() as u64);
+        let object = (key.bucket.clone(), key.key.clone());
+        if let Some(keys) = self.by_object.get_mut(&object) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.by_object.remove(&object);
+            }
+        }
+    }
+
+    fn remove_object(&mut self, bucket: &str, key: &str) {
+        self.generation += 1;
+        if let Some(keys) = self.by_object.remove(&(bucket.to_string(), key.to_string())) {
+            for key in keys {
+                self.remove(&key);
+            }
+        }
+    }
+
+    fn remove_bucket(&mut self, bucket: &str) {
+        self.generation += 1;
+        let keys: Vec<ReadKey> = self
+            .entries
+            .keys()
+            .filter(|k| k.bucket == bucket)
+            .cloned()
+            .collect();
+        for key in keys {
+            self.remove(&key);
+        }
+    }
+}
+
+/// Storage engine wrapper caching the data of small objects and ranges
+pub struct ReadCache {
+    inner: Arc<dyn StorageEngine>,
+    memory_bytes: u64,
+    max_object_size: u64,
+    memory: Arc<Mutex<MemoryTier>>,
+    disk: Option<Arc<ObjectCache>>,
+}
+
+impl ReadCache {
+    /// Cache up to `memory_bytes` of reads no larger than `max_object_size`
+    pub fn new(inner: Arc<dyn StorageEngine>, memory_bytes: u64, max_object_size: u64) -> Self {
+        Self {
+            inner,
+            memory_bytes,
+            max_object_size,
+            memory: Arc::new(Mutex::new(MemoryTier::default())),
+            disk: None,
+        }
+    }
+
+    /// Also keep whole objects in `disk`
+    pub fn with_disk_tier(mut self, disk: ObjectCache) -> Self {
+        self.disk = Some(Arc::new(disk));
+        self
+    }
+
+    /// Largest read that is cached
+    pub fn max_object_size(&self) -> u64 {
+        self.max_object_size
+    }
+
+    /// Data of the version of `bucket/key` with `etag`, the inclusive
+    /// `range` of it if given, from the cache or else from storage
+    pub async fn get(&self, bucket: &str, key: &str, etag: &str, range: Option<(i64, i64)>) -> Result<Bytes> {
+        let read_key = ReadKey {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            etag: etag.to_string(),
+            range,
+        };
+
+        let generation = {
+            let mut memory = self.memory.lock();
+            if let Some(data) = memory.get(&read_key).or_else(|| cached_range(&mut memory, &read_key)) {
+                count("hafiz_cache_hits_total", "object_data");
+                return Ok(data);
+            }
+            memory.generation
+        };
+        count("hafiz_cache_misses_total", "object_data");
+
+        if let Some(disk) = &self.disk {
+            match disk.get(bucket, key).await {
+                Ok(Some((data, stat))) if stat.etag == etag => {
+                    count("hafiz_cache_hits_total", "object_data_disk");
+                    self.seed_memory(read_key.whole(), data.clone(), generation);
+                    return match range {
+                        Some(range) => Ok(slice(&data, range).unwrap_or_default()),
+                        None => Ok(data),
+                    };
+                }
+                Ok(_) => count("hafiz_cache_misses_total", "object_data_disk"),
+                Err(e) => warn!("Read cache disk tier unavailable: {}", e),
+            }
+        }
+
+        let data = match range {
+            Some((start, end)) => self.inner.get_range(bucket, key, start, end).await?,
+            None => self.inner.get(bucket, key).await?,
+        };
+        if data.len() as u64 <= self.max_object_size {
+            self.seed_memory(read_key.clone(), data.clone(), generation);
+            if range.is_none() {
+                self.seed_disk(read_key, data.clone(), generation);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Load the version of `bucket/key` with `etag` into the cache ahead
+    /// of reads, without counting hits or misses. Returns the bytes
+    /// loaded, 0 if it was cached already.
+    pub async fn prefetch(&self, bucket: &str, key: &str, etag: &str) -> Result<u64> {
+        let read_key = ReadKey {
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            etag: etag.to_string(),
+            range: None,
+        };
+        let generation = {
+            let memory = self.memory.lock();
+            if memory.entries.contains_key(&read_key) {
+                return Ok(0);
+            }
+            memory.generation
+        };
+
+        if let Some(disk) = &self.disk {
+            if let Ok(Some((data, stat))) = disk.get(bucket, key).await {
+                if stat.etag == etag {
+                    self.seed_memory(read_key, data.clone(), generation);
+                    return Ok(data.len() as u64);
+                }
+            }
+        }
+
+        let data = self.inner.get(bucket, key).await?;
+        if data.len() as u64 > self.max_object_size {
+            return Ok(0);
+        }
+        self.seed_memory(read_key.clone(), data.clone(), generation);
+        self.seed_disk(read_key, data.clone(), generation);
+        Ok(data.len() as u64)
+    }
+
+    pub fn memory_stats(&self) -> CacheStats {
+        let memory = self.memory.lock();
+        CacheStats {
+            objects: memory.entries.len(),
+            used_bytes: memory.used_bytes,
+            max_bytes: self.memory_bytes,
+        }
+    }
+
+    pub fn disk_stats(&self) -> Option<CacheStats> {
+        self.disk.as_ref().map(|disk| disk.stats())
+    }
+
+    fn seed_memory(&self, key: ReadKey, data: Bytes, generation: u64) {
+        let mut memory = self.memory.lock();
+        if memory.generation == generation {
+            memory.insert(key, data, self.memory_bytes);
+        }
+    }
+
+    /// Write a whole object to the disk tier in the background
+    fn seed_disk(&self, key: ReadKey, data: Bytes, generation: u64) {
+        let Some(disk) = self.disk.clone() else {
+            return;
+        };
+        let memory = self.memory.clone();
+        tokio::spawn(async move {
+            let stat = ObjectStat {
+                size: data.len() as i64,
+                etag: key.etag.clone(),
+                content_type: None,
+                last_modified: Utc::now(),
+            };
+            if let Err(e) = disk.insert(&key.bucket, &key.key, &data, stat).await {
+                warn!("Could not cache {}/{} on disk: {}", key.bucket, key.key, e);
+                return;
+            }
+            // An invalidation that ran during the write could not see it
+            if memory.lock().generation != generation {
+                disk.remove(&key.bucket, &key.key).await;
+            }
+        });
+    }
+
+    async fn invalidate(&self, bucket: &str, key: &str) {
+        self.memory.lock().remove_object(bucket, key);
+        if let Some(disk) = &self.disk {
+            disk.remove(bucket, key).await;
+        }
+    }
+}
+
+/// A range sliced out of the cached whole object
+fn cached_range(memory: &mut MemoryTier, key: &ReadKey) -> Option<Bytes> {
+    let range = key.range?;
+    let data = memory.get(&key.whole())?;
+    slice(&data, range)
+}
+
+fn slice(data: &Bytes, (start, end): (i64, i64)) -> Option<Bytes> {
+    let (start, end) = (usize::try_from(start).ok()?, usize::try_from(end).ok()?);
+    (start <= end && end < data.len()).then(|| data.slice(start..=end))
+}
+
+fn count(name: &'static str, cache: &'static str) {
+    metrics::counter!(name, "cache" => cache).increment(1);
+}
+
+#[async_trait]
+impl StorageEngine for ReadCache {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        let etag = self.inner.put(bucket, key, data).await?;
+        self.invalidate(bucket, key).await;
+        Ok(etag)
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        self.inner.get(bucket, key).await
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        self.inner.get_range(bucket, key, start, end).await
+    }
+
+    async fn get_stream(&self, bucket: &str, key: &str) -> Result<ByteStream> {
+        self.inner.get_stream(bucket, key).await
+    }
+
+    async fn get_range_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<ByteStream> {
+        self.inner.get_range_stream(bucket, key, start, end).await
+    }
+
+    async fn copy(&self, src_bucket: &str, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        self.inner.copy(src_bucket, src_key, dst_bucket, dst_key).await?;
+        self.invalidate(dst_bucket, dst_key).await;
+        Ok(())
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        let result = self.inner.delete(bucket, key).await;
+        self.invalidate(bucket, key).await;
+        result
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        self.inner.exists(bucket, key).await
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        self.inner.size(bucket, key).await
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.create_bucket(bucket).await
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.delete_bucket(bucket).await?;
+        self.memory.lock().remove_bucket(bucket);
+        if let Some(disk) = &self.disk {
+            disk.remove_bucket(bucket).await;
+        }
+        Ok(())
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.inner.bucket_exists(bucket).await
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        self.inner.stat(bucket, key).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    async fn put_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        self.inner.put_part(bucket, key, upload_id, part_number, data).await
+    }
+
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        let size = self.inner.complete_parts(bucket, key, upload_id, part_numbers).await?;
+        self.invalidate(bucket, key).await;
+        Ok(size)
+    }
+
+    async fn abort_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<()> {
+        self.inner.abort_parts(bucket, key, upload_id, part_numbers).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::MemoryStorage;
+    use std::time::Duration;
+
+    async fn storage() -> Arc<dyn StorageEngine> {
+        let storage = MemoryStorage::new();
+        storage.create_bucket("b").await.unwrap();
+        Arc::new(storage)
+    }
+
+    #[tokio::test]
+    async fn test_read_through_and_invalidation() {
+        let inner = storage().await;
+        let cache = ReadCache::new(inner.clone(), 1024, 64);
+        let etag = cache.put("b", "k", Bytes::from_static(b"hello world")).await.unwrap();
+
+        assert_eq!(&cache.get("b", "k", &etag, None).await.unwrap()[..], b"hello world");
+        // Served from the cached object, even once storage changed underneath
+        inner.put("b", "k", Bytes::from_static(b"HELLO WORLD")).await.unwrap();
+        assert_eq!(&cache.get("b", "k", &etag, None).await.unwrap()[..], b"hello world");
+        assert_eq!(&cache.get("b", "k", &etag, Some((6, 10))).await.unwrap()[..], b"world");
+
+        // A write through the cache drops the object's entries
+        let etag = cache.put("b", "k", Bytes::from_static(b"new data")).await.unwrap();
+        assert!(cache.memory.lock().entries.is_empty());
+        assert_eq!(&cache.get("b", "k", &etag, Some((0, 2))).await.unwrap()[..], b"new");
+        assert_eq!(cache.memory.lock().used_bytes, 3);
+    }
+
+    #[tokio::test]
+    async fn test_lru_budget() {
+        let cache = ReadCache::new(storage().await, 10, 10);
+        for key in ["one", "two"] {
+            cache.put("b", key, Bytes::from_static(b"abcd")).await.unwrap();
+            cache.get("b", key, "e", None).await.unwrap();
+        }
+        // Touch "one" so "two" is the least recently used
+        cache.get("b", "one", "e", None).await.unwrap();
+        cache.put("b", "three", Bytes::from_static(b"abcd")).await.unwrap();
+        cache.get("b", "three", "e", None).await.unwrap();
+
+        let memory = cache.memory.lock();
+        assert_eq!(memory.used_bytes, 8);
+        assert!(!memory.by_object.contains_key(&("b".to_string(), "two".to_string())));
+        assert!(memory.by_object.contains_key(&("b".to_string(), "one".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_prefetch() {
+        let cache = ReadCache::new(storage().await, 1024, 8);
+        let etag = cache.put("b", "k", Bytes::from_static(b"hello")).await.unwrap();
+        cache.put("b", "big", Bytes::from_static(b"hello world")).await.unwrap();
+
+        assert_eq!(cache.prefetch("b", "k", &etag).await.unwrap(), 5);
+        assert_eq!(cache.prefetch("b", "k", &etag).await.unwrap(), 0);
+        assert_eq!(cache.prefetch("b", "big", "e").await.unwrap(), 0);
+        assert!(cache.prefetch("b", "missing", "e").await.is_err());
+        assert_eq!(cache.memory_stats().used_bytes, 5);
+        assert_eq!(cache.memory_stats().objects, 1);
+    }
+
+    #[tokio::test]
+    async fn test_disk_tier() {
+        let dir = std::env::temp_dir().join(format!("hafiz-read-cache-{}", uuid::Uuid::new_v4()));
+        let disk = ObjectCache::open(&dir, 1024, Duration::MAX).unwrap().without_metrics();
+        let inner = storage().await;
+        let cache = ReadCache::new(inner.clone(), 1024, 64).with_disk_tier(disk);
+        let etag = cache.put("b", "k", Bytes::from_static(b"hello world")).await.unwrap();
+
+        cache.get("b", "k", &etag, None).await.unwrap();
+        for _ in 0..100 {
+            if cache.disk.as_ref().unwrap().stats().objects == 1 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Drop memory only; the disk tier still has the object
+        cache.memory.lock().remove_object("b", "k");
+        inner.delete("b", "k").await.unwrap();
+        assert_eq!(&cache.get("b", "k", &etag, Some((0, 4))).await.unwrap()[..], b"hello");
+        // A different version is not served from it
+        assert!(cache.get("b", "k", "other", None).await.is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,486 @@
+//! Per-bucket backend selection
+//!
+//! Buckets listed under `[storage.buckets]` are served by the named backend
+//! from `[storage.backends]`; every other bucket uses the default storage.
+//! The table can be changed at runtime, e.g. to put a new bucket on a cold
+//! disk before any data is written to it.
+//!
+//! With `storage.archive_backend` set, individual objects can also be moved
+//! to that backend with [`StorageRouter::archive`]. Reads of a key the
+//! bucket's backend does not hold fall back to the archive, and deletes
+//! remove both copies. [`StorageRouter::restore`] copies an archived object
+//! back to the bucket's backend for a while, and [`StorageRouter::evict`]
+//! drops that copy again. [`StorageRouter::promote`] moves an archived object
+//! back for good.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hafiz_core::config::{BackendConfig, StorageConfig, DEFAULT_STORAGE_BACKEND};
+use hafiz_core::{Error, Result};
+use parking_lot::RwLock;
+use tracing::{debug, info};
+
+use super::{ByteStream, LocalStorage, MemoryStorage, ObjectStat, S3Gateway, StorageEngine};
+
+/// Storage engine that dispatches each call to the backend of its bucket
+pub struct StorageRouter {
+    default: Arc<dyn StorageEngine>,
+    backends: HashMap<String, Arc<dyn StorageEngine>>,
+    kinds: HashMap<String, &'static str>,
+    buckets: RwLock<HashMap<String, String>>,
+    /// Backend holding archived objects
+    archive: Option<Arc<dyn StorageEngine>>,
+}
+
+impl StorageRouter {
+    /// Build the configured backends around `default`
+    pub async fn from_config(
+        default: Arc<dyn StorageEngine>,
+        config: &StorageConfig,
+    ) -> Result<Self> {
+        config.validate()?;
+
+        let mut backends = HashMap::new();
+        let mut kinds = HashMap::new();
+        for (name, backend) in &config.backends {
+            let data_dir = config.data_dir.join("backends").join(name);
+            backends.insert(name.clone(), open_backend(backend, &data_dir).await?);
+            kinds.insert(name.clone(), backend.kind());
+            info!("Storage backend {} ({}) ready", name, backend.kind());
+        }
+
+        let archive = config
+            .archive_backend
+            .as_ref()
+            .and_then(|name| backends.get(name))
+            .cloned();
+
+        Ok(Self {
+            default,
+            backends,
+            kinds,
+            buckets: RwLock::new(config.buckets.clone()),
+            archive,
+        })
+    }
+
+    /// Backend serving `bucket`
+    pub fn backend_for(&self, bucket: &str) -> Arc<dyn StorageEngine> {
+        self.buckets
+            .read()
+            .get(bucket)
+            .and_then(|name| self.backends.get(name))
+            .unwrap_or(&self.default)
+            .clone()
+    }
+
+    /// Name of the backend serving `bucket`
+    pub fn backend_name(&self, bucket: &str) -> String {
+        self.buckets
+            .read()
+            .get(bucket)
+            .cloned()
+            .unwrap_or_else(|| DEFAULT_STORAGE_BACKEND.to_string())
+    }
+
+    /// Configured backends and their types, without the default storage
+    pub fn backends(&self) -> Vec<(String, &'static str)> {
+        let mut backends: Vec<_> = self
+            .kinds
+            .iter()
+            .map(|(name, kind)| (name.clone(), *kind))
+            .collect();
+        backends.sort();
+        backends
+    }
+
+    /// Buckets mapped to a named backend
+    pub fn bucket_backends(&self) -> HashMap<String, String> {
+        self.buckets.read().clone()
+    }
+
+    /// Whether an archive backend is configured
+    pub fn has_archive(&self) -> bool {
+        self.archive.is_some()
+    }
+
+    /// Move an object from the backend of its bucket to the archive backend
+    pub async fn archive(&self, bucket: &str, key: &str) -> Result<()> {
+        let archive = self.archive_backend()?;
+        let backend = self.backend_for(bucket);
+        let data = match backend.get(bucket, key).await {
+            Ok(data) => data,
+            // Already archived, e.g. by tiering
+            Err(Error::NoSuchKey) if archive.exists(bucket, key).await? => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        archive.put(bucket, key, data).await?;
+        backend.delete(bucket, key).await?;
+        debug!("Archived {}/{}", bucket, key);
+        Ok(())
+    }
+
+    /// Copy an archived object back to the backend of its bucket, keeping
+    /// the archived copy
+    pub async fn restore(&self, bucket: &str, key: &str) -> Result<()> {
+        let data = self.archive_backend()?.get(bucket, key).await?;
+        self.backend_for(bucket).put(bucket, key, data).await?;
+        debug!("Restored {}/{}", bucket, key);
+        Ok(())
+    }
+
+    /// Move an archived object back to the backend of its bucket
+    pub async fn promote(&self, bucket: &str, key: &str) -> Result<()> {
+        let archive = self.archive_backend()?;
+        let backend = self.backend_for(bucket);
+        // Data written since the object was archived is newer
+        if !backend.exists(bucket, key).await? {
+            let data = archive.get(bucket, key).await?;
+            backend.put(bucket, key, data).await?;
+        }
+        archive.delete(bucket, key).await?;
+        debug!("Promoted {}/{}", bucket, key);
+        Ok(())
+    }
+
+    /// Drop the restored copy of an archived object
+    pub async fn evict(&self, bucket: &str, key: &str) -> Result<()> {
+        match self.backend_for(bucket).delete(bucket, key).await {
+            Ok(()) | Err(Error::NoSuchKey) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn archive_backend(&self) -> Result<&Arc<dyn StorageEngine>> {
+        self.archive
+            .as_ref()
+            .ok_or_else(|| Error::InternalError("No archive backend is configured".into()))
+    }
+
+    /// Route `bucket` to `backend`; the default backend name clears the mapping
+    pub fn set_bucket_backend(&self, bucket: &str, backend: &str) -> Result<()> {
+        if backend == DEFAULT_STORAGE_BACKEND {
+            self.buckets.write().remove(bucket);
+            return Ok(());
+        }
+        if !self.backends.contains_key(backend) {
+            return Err(Error::InvalidArgument(format!(
+                "Unknown storage backend: {}",
+                backend
+            )));
+        }
+        self.buckets
+            .write()
+            .insert(bucket.to_string(), backend.to_string());
+        info!("Bucket {} now stored on backend {}", bucket, backend);
+        Ok(())
+    }
+}
+
+/// Open a backend; `data_dir` holds any local state such as a cache
+pub async fn open_backend(
+    config: &BackendConfig,
+    data_dir: &Path,
+) -> Result<Arc<dyn StorageEngine>> {
+    match config {
+        BackendConfig::Local(config) => {
+            let storage = LocalStorage::new(&config.data_dir);
+            storage.init().await?;
+            Ok(Arc::new(storage))
+        }
+        BackendConfig::Memory => Ok(Arc::new(MemoryStorage::new())),
+        BackendConfig::S3(config) => {
+            // Mapping a bucket to the backend is what enables it
+            let config = hafiz_core::config::GatewayConfig {
+                enabled: true,
+                ..config.clone()
+            };
+            Ok(Arc::new(S3Gateway::new(&config, data_dir)?))
+        }
+        #[cfg(feature = "azure")]
+        BackendConfig::Azure(config) => Ok(Arc::new(super::AzureBlobStorage::new(config)?)),
+        #[cfg(not(feature = "azure"))]
+        BackendConfig::Azure(_) => Err(Error::InvalidArgument(
+            "Azure backends need Hafiz built with the azure feature".into(),
+        )),
+        #[cfg(feature = "gcs")]
+        BackendConfig::Gcs(config) => Ok(Arc::new(super::GcsStorage::new(config).await?)),
+        #[cfg(not(feature = "gcs"))]
+        BackendConfig::Gcs(_) => Err(Error::InvalidArgument(
+            "GCS backends need Hafiz built with the gcs feature".into(),
+        )),
+    }
+}
+
+#[async_trait]
+impl StorageEngine for StorageRouter {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        self.backend_for(bucket).put(bucket, key, data).await
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        match (self.backend_for(bucket).get(bucket, key).await, &self.archive) {
+            (Err(Error::NoSuchKey), Some(archive)) => archive.get(bucket, key).await,
+            (result, _) => result,
+        }
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        let result = self
+            .backend_for(bucket)
+            .get_range(bucket, key, start, end)
+            .await;
+        match (result, &self.archive) {
+            (Err(Error::NoSuchKey), Some(archive)) => {
+                archive.get_range(bucket, key, start, end).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    async fn get_stream(&self, bucket: &str, key: &str) -> Result<ByteStream> {
+        match (self.backend_for(bucket).get_stream(bucket, key).await, &self.archive) {
+            (Err(Error::NoSuchKey), Some(archive)) => archive.get_stream(bucket, key).await,
+            (result, _) => result,
+        }
+    }
+
+    async fn get_range_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<ByteStream> {
+        let result = self
+            .backend_for(bucket)
+            .get_range_stream(bucket, key, start, end)
+            .await;
+        match (result, &self.archive) {
+            (Err(Error::NoSuchKey), Some(archive)) => {
+                archive.get_range_stream(bucket, key, start, end).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    async fn copy(&self, src_bucket: &str, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        let src = self.backend_for(src_bucket);
+        let dst = self.backend_for(dst_bucket);
+        if Arc::ptr_eq(&src, &dst) {
+            match src.copy(src_bucket, src_key, dst_bucket, dst_key).await {
+                // Archived objects are read back from the archive below
+                Err(Error::NoSuchKey) if self.archive.is_some() => {}
+                result => return result,
+            }
+        }
+        let data = self.get(src_bucket, src_key).await?;
+        dst.put(dst_bucket, dst_key, data).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        self.backend_for(bucket).delete(bucket, key).await?;
+        match &self.archive {
+            Some(archive) => match archive.delete(bucket, key).await {
+                Ok(()) | Err(Error::NoSuchKey) => Ok(()),
+                Err(e) => Err(e),
+            },
+            None => Ok(()),
+        }
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        if self.backend_for(bucket).exists(bucket, key).await? {
+            return Ok(true);
+        }
+        match &self.archive {
+            Some(archive) => archive.exists(bucket, key).await,
+            None => Ok(false),
+        }
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        match (self.backend_for(bucket).size(bucket, key).await, &self.archive) {
+            (Err(Error::NoSuchKey), Some(archive)) => archive.size(bucket, key).await,
+            (result, _) => result,
+        }
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        self.backend_for(bucket).create_bucket(bucket).await?;
+        if let Some(archive) = &self.archive {
+            archive.create_bucket(bucket).await?;
+        }
+        Ok(())
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        self.backend_for(bucket).delete_bucket(bucket).await?;
+        if let Some(archive) = &self.archive {
+            if archive.bucket_exists(bucket).await? {
+                archive.delete_bucket(bucket).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.backend_for(bucket).bucket_exists(bucket).await
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        match (self.backend_for(bucket).stat(bucket, key).await?, &self.archive) {
+            (None, Some(archive)) => archive.stat(bucket, key).await,
+            (stat, _) => Ok(stat),
+        }
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.default.health_check().await?;
+        for (name, backend) in &self.backends {
+            backend
+                .health_check()
+                .await
+                .map_err(|e| Error::InternalError(format!("Storage backend {}: {}", name, e)))?;
+        }
+        Ok(())
+    }
+
+    async fn put_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        self.backend_for(bucket)
+            .put_part(bucket, key, upload_id, part_number, data)
+            .await
+    }
+
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        self.backend_for(bucket)
+            .complete_parts(bucket, key, upload_id, part_numbers)
+            .await
+    }
+
+    async fn abort_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<()> {
+        self.backend_for(bucket)
+            .abort_parts(bucket, key, upload_id, part_numbers)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LocalStorage;
+
+    #[tokio::test]
+    async fn test_buckets_route_to_their_backend() {
+        let root = std::env::temp_dir().join(format!("hafiz-router-{}", uuid::Uuid::new_v4()));
+        let default = Arc::new(LocalStorage::new(root.join("default")));
+        let cold = Arc::new(LocalStorage::new(root.join("cold")));
+
+        let router = StorageRouter {
+            default,
+            backends: HashMap::from([("cold".to_string(), cold.clone() as Arc<dyn StorageEngine>)]),
+            kinds: HashMap::from([("cold".to_string(), "local")]),
+            buckets: RwLock::new(HashMap::from([("archive".to_string(), "cold".to_string())])),
+            archive: None,
+        };
+
+        router.put("archive", "a", Bytes::from_static(b"old")).await.unwrap();
+        router.put("live", "b", Bytes::from_static(b"new")).await.unwrap();
+
+        assert!(cold.exists("archive", "a").await.unwrap());
+        assert!(!cold.exists("live", "b").await.unwrap());
+        assert_eq!(router.get("live", "b").await.unwrap(), Bytes::from_static(b"new"));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_runtime_mapping() {
+        let config = StorageConfig {
+            backends: HashMap::from([("scratch".to_string(), BackendConfig::Memory)]),
+            ..Default::default()
+        };
+        let default = Arc::new(MemoryStorage::new());
+        let router = StorageRouter::from_config(default.clone(), &config).await.unwrap();
+
+        assert!(router.set_bucket_backend("tmp", "missing").is_err());
+        router.set_bucket_backend("tmp", "scratch").unwrap();
+        assert_eq!(router.backend_name("tmp"), "scratch");
+
+        router.put("tmp", "k", Bytes::from_static(b"v")).await.unwrap();
+        assert!(!default.exists("tmp", "k").await.unwrap());
+
+        router.set_bucket_backend("tmp", DEFAULT_STORAGE_BACKEND).unwrap();
+        assert_eq!(router.backend_name("tmp"), DEFAULT_STORAGE_BACKEND);
+        assert!(!router.exists("tmp", "k").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_backend_is_rejected() {
+        let config = StorageConfig {
+            buckets: HashMap::from([("archive".to_string(), "missing".to_string())]),
+            ..Default::default()
+        };
+        let default = Arc::new(LocalStorage::new(std::env::temp_dir()));
+        assert!(StorageRouter::from_config(default, &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_archived_objects() {
+        let config = StorageConfig {
+            backends: HashMap::from([("cold".to_string(), BackendConfig::Memory)]),
+            archive_backend: Some("cold".to_string()),
+            ..Default::default()
+        };
+        let default = Arc::new(MemoryStorage::new());
+        let router = StorageRouter::from_config(default.clone(), &config).await.unwrap();
+        assert!(router.has_archive());
+
+        router.put("docs", "a", Bytes::from_static(b"old")).await.unwrap();
+        router.archive("docs", "a").await.unwrap();
+        assert!(!default.exists("docs", "a").await.unwrap());
+
+        // Reads fall back to the archive; deletes clear it
+        assert_eq!(router.get("docs", "a").await.unwrap(), Bytes::from_static(b"old"));
+        assert_eq!(router.size("docs", "a").await.unwrap(), 3);
+        // A restored copy is served by the bucket's backend until evicted
+        router.restore("docs", "a").await.unwrap();
+        assert!(default.exists("docs", "a").await.unwrap());
+        router.evict("docs", "a").await.unwrap();
+        assert!(!default.exists("docs", "a").await.unwrap());
+        assert_eq!(router.get("docs", "a").await.unwrap(), Bytes::from_static(b"old"));
+
+        router.promote("docs", "a").await.unwrap();
+        assert!(default.exists("docs", "a").await.unwrap());
+        router.archive("docs", "a").await.unwrap();
+        // Archiving twice is harmless
+        router.archive("docs", "a").await.unwrap();
+
+        router.delete("docs", "a").await.unwrap();
+        assert!(!router.exists("docs", "a").await.unwrap());
+    }
+}
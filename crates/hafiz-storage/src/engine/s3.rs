@@ -0,0 +1,404 @@
+//! S3 gateway backend
+//!
+//! Objects live in an upstream S3 service. Reads are served from the local
+//! [`ObjectCache`] and fall through to the upstream on a miss; writes and
+//! deletes go to the upstream first and then update the cache.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use hafiz_core::config::GatewayConfig;
+use hafiz_core::{Error, Result};
+use reqwest::{header::HeaderMap, Method, StatusCode};
+use tracing::{debug, info};
+use url::Url;
+
+use super::{ObjectCache, ObjectStat, StorageEngine};
+
+/// Signed client for an S3-compatible endpoint
+pub struct S3Client {
+    http: reqwest::Client,
+    endpoint: Url,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    path_style: bool,
+}
+
+impl S3Client {
+    pub fn new(config: &GatewayConfig) -> Result<Self> {
+        let endpoint = Url::parse(&config.endpoint)
+            .map_err(|e| Error::InvalidArgument(format!("Invalid gateway endpoint: {}", e)))?;
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(300))
+            .build()
+            .map_err(|e| Error::InternalError(e.to_string()))?;
+
+        Ok(Self {
+            http,
+            endpoint,
+            region: config.region.clone(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+            path_style: config.path_style,
+        })
+    }
+
+    /// Fetch an object, `None` if it does not exist
+    pub async fn get_object(&self, bucket: &str, key: &str) -> Result<Option<(Bytes, ObjectStat)>> {
+        let resp = self
+            .send(Method::GET, bucket, Some(key), HeaderMap::new(), Bytes::new())
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let resp = check(resp).await?;
+
+        let mut stat = object_stat(resp.headers());
+        let data = resp.bytes().await.map_err(upstream_error)?;
+        stat.size = data.len() as i64;
+        Ok(Some((data, stat)))
+    }
+
+    /// Fetch bytes `start..=end` of an object
+    pub async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            reqwest::header::RANGE,
+            format!("bytes={}-{}", start, end)
+                .parse()
+                .map_err(|_| Error::InvalidRange(format!("bytes={}-{}", start, end)))?,
+        );
+        let resp = self
+            .send(Method::GET, bucket, Some(key), headers, Bytes::new())
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NoSuchKey);
+        }
+        check(resp).await?.bytes().await.map_err(upstream_error)
+    }
+
+    /// Attributes of an object, `None` if it does not exist
+    pub async fn head_object(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        let resp = self
+            .send(Method::HEAD, bucket, Some(key), HeaderMap::new(), Bytes::new())
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(object_stat(check(resp).await?.headers())))
+    }
+
+    /// Upload an object, returning its ETag
+    pub async fn put_object(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        let resp = self
+            .send(Method::PUT, bucket, Some(key), HeaderMap::new(), data)
+            .await?;
+        let resp = check(resp).await?;
+        Ok(etag(resp.headers()))
+    }
+
+    /// Upload an object with its content type and user metadata, returning
+    /// its ETag. Metadata values that are not valid header values are left out.
+    pub async fn put_object_with_metadata(
+        &self,
+        bucket: &str,
+        key: &str,
+        data: Bytes,
+        content_type: &str,
+        metadata: &HashMap<String, String>,
+    ) -> Result<String> {
+        let mut headers = HeaderMap::new();
+        if let Ok(value) = content_type.parse() {
+            headers.insert(reqwest::header::CONTENT_TYPE, value);
+        }
+        for (name, value) in metadata {
+            let name = format!("x-amz-meta-{}", name.to_lowercase());
+            let name = reqwest::header::HeaderName::from_bytes(name.as_bytes());
+            if let (Ok(name), Ok(value)) = (name, value.parse()) {
+                headers.insert(name, value);
+            }
+        }
+
+        let resp = self.send(Method::PUT, bucket, Some(key), headers, data).await?;
+        let resp = check(resp).await?;
+        Ok(etag(resp.headers()))
+    }
+
+    pub async fn delete_object(&self, bucket: &str, key: &str) -> Result<()> {
+        let resp = self
+            .send(Method::DELETE, bucket, Some(key), HeaderMap::new(), Bytes::new())
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        check(resp).await.map(|_| ())
+    }
+
+    pub async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        let resp = self
+            .send(Method::HEAD, bucket, None, HeaderMap::new(), Bytes::new())
+            .await?;
+        if resp.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        check(resp).await.map(|_| true)
+    }
+
+    async fn send(
+        &self,
+        method: Method,
+        bucket: &str,
+        key: Option<&str>,
+        mut headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<reqwest::Response> {
+        let (url, host, path) = self.locate(bucket, key)?;
+
+        let payload_hash = hafiz_crypto::sha256_hash(&body[..]);
+        let amz_date = Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        // Every header sent is signed; S3 requires it of x-amz-meta-*
+        let mut signed: BTreeMap<String, String> = headers
+            .iter()
+            .filter_map(|(name, value)| {
+                Some((name.as_str().to_string(), value.to_str().ok()?.trim().to_string()))
+            })
+            .collect();
+        signed.insert("host".to_string(), host);
+        signed.insert("x-amz-content-sha256".to_string(), payload_hash.clone());
+        signed.insert("x-amz-date".to_string(), amz_date);
+
+        let authorization = hafiz_auth::sign_request_v4(
+            method.as_str(),
+            &path,
+            "",
+            &signed,
+            &payload_hash,
+            &self.access_key,
+            &self.secret_key,
+            &self.region,
+            "s3",
+        )?;
+
+        for (name, value) in &signed {
+            if name != "host" {
+                headers.insert(
+                    reqwest::header::HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|e| Error::InternalError(e.to_string()))?,
+                    value.parse().map_err(|_| Error::InternalError(format!("Invalid {} header", name)))?,
+                );
+            }
+        }
+        headers.insert(
+            reqwest::header::AUTHORIZATION,
+            authorization
+                .parse()
+                .map_err(|_| Error::InternalError("Invalid authorization header".into()))?,
+        );
+
+        debug!("Gateway {} {}", method, url);
+        self.http
+            .request(method, url)
+            .headers(headers)
+            .body(body)
+            .send()
+            .await
+            .map_err(upstream_error)
+    }
+
+    /// URL, host header and canonical path of a bucket or object
+    fn locate(&self, bucket: &str, key: Option<&str>) -> Result<(Url, String, String)> {
+        let mut url = self.endpoint.clone();
+        let host = url
+            .host_str()
+            .ok_or_else(|| Error::InvalidArgument("Gateway endpoint has no host".into()))?
+            .to_string();
+
+        let object_path = key.map(|k| format!("/{}", k)).unwrap_or_default();
+        let (host, path) = if self.path_style {
+            (host, format!("/{}{}", bucket, object_path))
+        } else {
+            let host = format!("{}.{}", bucket, host);
+            url.set_host(Some(&host))
+                .map_err(|e| Error::InvalidArgument(format!("Invalid bucket host: {}", e)))?;
+            (host, if object_path.is_empty() { "/".to_string() } else { object_path })
+        };
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host,
+        };
+
+        let encoded = hafiz_auth::uri_encode(&path);
+        url.set_path(&encoded);
+        Ok((url, host, path))
+    }
+}
+
+/// Storage engine backed by an upstream S3 service with a local read cache
+pub struct S3Gateway {
+    client: S3Client,
+    cache: ObjectCache,
+}
+
+impl S3Gateway {
+    /// Create a gateway; the cache defaults to `<data_dir>/gateway-cache`
+    pub fn new(config: &GatewayConfig, data_dir: &Path) -> Result<Self> {
+        config.validate()?;
+        let cache_dir = config
+            .cache_dir
+            .clone()
+            .unwrap_or_else(|| data_dir.join("gateway-cache"));
+        let cache = ObjectCache::open(
+            cache_dir,
+            config.cache_max_bytes,
+            Duration::from_secs(config.cache_ttl_secs),
+        )?;
+
+        info!("Gateway mode: objects are served from {}", config.endpoint);
+        Ok(Self {
+            client: S3Client::new(config)?,
+            cache,
+        })
+    }
+
+    pub fn cache(&self) -> &ObjectCache {
+        &self.cache
+    }
+
+    /// Whole object, from the cache or fetched and cached
+    async fn fetch(&self, bucket: &str, key: &str) -> Result<(Bytes, ObjectStat)> {
+        if let Some(hit) = self.cache.get(bucket, key).await? {
+            return Ok(hit);
+        }
+
+        let (data, stat) = self
+            .client
+            .get_object(bucket, key)
+            .await?
+            .ok_or(Error::NoSuchKey)?;
+        self.cache.insert(bucket, key, &data, stat.clone()).await?;
+        Ok((data, stat))
+    }
+}
+
+#[async_trait]
+impl StorageEngine for S3Gateway {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        let etag = self.client.put_object(bucket, key, data.clone()).await?;
+
+        let stat = ObjectStat {
+            size: data.len() as i64,
+            etag: etag.clone(),
+            content_type: None,
+            last_modified: Utc::now(),
+        };
+        self.cache.insert(bucket, key, &data, stat).await?;
+        Ok(etag)
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        self.fetch(bucket, key).await.map(|(data, _)| data)
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        if let Some((data, _)) = self.cache.get(bucket, key).await? {
+            let end = (end as usize).min(data.len().saturating_sub(1));
+            return Ok(data.slice(start as usize..=end));
+        }
+        // Ranges of uncached objects are not worth caching the whole object for
+        self.client.get_range(bucket, key, start, end).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        self.client.delete_object(bucket, key).await?;
+        self.cache.remove(bucket, key).await;
+        Ok(())
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        Ok(self.stat(bucket, key).await?.is_some())
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        self.stat(bucket, key)
+            .await?
+            .map(|stat| stat.size)
+            .ok_or(Error::NoSuchKey)
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        // Buckets map onto existing upstream buckets of the same name
+        if !self.client.bucket_exists(bucket).await? {
+            return Err(Error::NoSuchBucketNamed(bucket.to_string()));
+        }
+        Ok(())
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        // The upstream bucket is left alone
+        self.cache.remove_bucket(bucket).await;
+        Ok(())
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.client.bucket_exists(bucket).await
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        if let Some(stat) = self.cache.stat(bucket, key) {
+            return Ok(Some(stat));
+        }
+        self.client.head_object(bucket, key).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.cache.health_check().await
+    }
+}
+
+async fn check(resp: reqwest::Response) -> Result<reqwest::Response> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(resp);
+    }
+    let body = resp.text().await.unwrap_or_default();
+    Err(Error::InternalError(format!(
+        "Upstream S3 returned {}: {}",
+        status,
+        body.chars().take(512).collect::<String>()
+    )))
+}
+
+fn upstream_error(e: reqwest::Error) -> Error {
+    Error::InternalError(format!("Upstream S3 request failed: {}", e))
+}
+
+fn etag(headers: &HeaderMap) -> String {
+    headers
+        .get(reqwest::header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim_matches('"').to_string())
+        .unwrap_or_default()
+}
+
+fn object_stat(headers: &HeaderMap) -> ObjectStat {
+    let header = |name: reqwest::header::HeaderName| {
+        headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string)
+    };
+
+    ObjectStat {
+        size: header(reqwest::header::CONTENT_LENGTH)
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0),
+        etag: etag(headers),
+        content_type: header(reqwest::header::CONTENT_TYPE),
+        last_modified: header(reqwest::header::LAST_MODIFIED)
+            .and_then(|v| DateTime::parse_from_rfc2822(&v).ok())
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now),
+    }
+}
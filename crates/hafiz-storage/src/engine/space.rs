@@ -0,0 +1,337 @@
+//! Free space accounting for the data volume
+//!
+//! A full disk used to surface as opaque 500s from half-written files.
+//! [`SpaceMonitor`] measures the volume periodically and [`SpaceGuard`]
+//! refuses writes with `InsufficientStorage` once they would eat into the
+//! configured reserve. Reads and deletes are never blocked, so space can
+//! always be reclaimed.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hafiz_core::config::SpaceReserveConfig;
+use hafiz_core::{Error, Result};
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+use super::{ByteStream, ObjectStat, StorageEngine};
+
+/// Tracks free space on the volume holding a directory
+pub struct SpaceMonitor {
+    path: PathBuf,
+    min_free_bytes: u64,
+    min_free_percent: f64,
+    free: AtomicU64,
+    total: AtomicU64,
+    /// False until the volume has been measured once
+    measured: AtomicBool,
+}
+
+impl SpaceMonitor {
+    pub fn new(path: impl AsRef<Path>, config: &SpaceReserveConfig) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            min_free_bytes: config.min_free_bytes,
+            min_free_percent: config.min_free_percent.clamp(0.0, 100.0),
+            free: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            measured: AtomicBool::new(false),
+        }
+    }
+
+    /// Measure the volume and update the gauges
+    pub fn refresh(&self) -> Result<()> {
+        let (free, total) = volume_space(&self.path)?;
+        self.record(free, total);
+        debug!("Data volume: {} of {} bytes free", free, total);
+        Ok(())
+    }
+
+    fn record(&self, free: u64, total: u64) {
+        self.free.store(free, Ordering::Relaxed);
+        self.total.store(total, Ordering::Relaxed);
+        self.measured.store(true, Ordering::Relaxed);
+        metrics::gauge!("hafiz_storage_free_bytes").set(free as f64);
+        metrics::gauge!("hafiz_storage_total_bytes").set(total as f64);
+    }
+
+    /// Re-measure the volume every `interval` in the background
+    pub fn spawn(self: Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                let monitor = self.clone();
+                match tokio::task::spawn_blocking(move || monitor.refresh()).await {
+                    Ok(Err(e)) => warn!("Failed to measure free space: {}", e),
+                    Err(e) => warn!("Free space check panicked: {}", e),
+                    Ok(Ok(())) => {}
+                }
+            }
+        })
+    }
+
+    /// Free bytes at the last measurement, less writes accepted since
+    pub fn free_bytes(&self) -> u64 {
+        self.free.load(Ordering::Relaxed)
+    }
+
+    /// Size of the volume in bytes
+    pub fn total_bytes(&self) -> u64 {
+        self.total.load(Ordering::Relaxed)
+    }
+
+    /// Bytes that must stay free
+    pub fn reserve_bytes(&self) -> u64 {
+        let percent = (self.total_bytes() as f64 * self.min_free_percent / 100.0) as u64;
+        self.min_free_bytes.max(percent)
+    }
+
+    /// Whether free space is already below the reserve
+    pub fn below_reserve(&self) -> bool {
+        self.measured.load(Ordering::Relaxed) && self.free_bytes() < self.reserve_bytes()
+    }
+
+    /// Admit a write of `len` bytes, or fail if it would cross the reserve.
+    ///
+    /// Accepted writes are deducted from the estimate so a burst between
+    /// two measurements cannot overshoot it.
+    pub fn reserve(&self, len: u64) -> Result<()> {
+        if !self.measured.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        let reserve = self.reserve_bytes();
+        let admitted = self
+            .free
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |free| {
+                free.checked_sub(len).filter(|left| *left >= reserve)
+            });
+        match admitted {
+            Ok(_) => Ok(()),
+            Err(free) => {
+                metrics::counter!("hafiz_storage_writes_rejected_total").increment(1);
+                Err(Error::InsufficientStorage(format!(
+                    "{} bytes free, {} bytes reserved",
+                    free, reserve
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn volume_space(path: &Path) -> Result<(u64, u64)> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| Error::InvalidArgument(e.to_string()))?;
+    // SAFETY: statvfs only writes into the zeroed struct we own
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let block = stat.f_frsize as u64;
+    Ok((stat.f_bavail as u64 * block, stat.f_blocks as u64 * block))
+}
+
+#[cfg(not(unix))]
+fn volume_space(_path: &Path) -> Result<(u64, u64)> {
+    Err(Error::InternalError(
+        "Free space accounting is only supported on Unix".into(),
+    ))
+}
+
+/// Storage engine that refuses writes once the volume is nearly full
+pub struct SpaceGuard {
+    inner: Arc<dyn StorageEngine>,
+    monitor: Arc<SpaceMonitor>,
+}
+
+impl SpaceGuard {
+    pub fn new(inner: Arc<dyn StorageEngine>, monitor: Arc<SpaceMonitor>) -> Self {
+        Self { inner, monitor }
+    }
+
+    pub fn monitor(&self) -> &Arc<SpaceMonitor> {
+        &self.monitor
+    }
+}
+
+#[async_trait]
+impl StorageEngine for SpaceGuard {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        self.monitor.reserve(data.len() as u64)?;
+        self.inner.put(bucket, key, data).await
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        self.inner.get(bucket, key).await
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        self.inner.get_range(bucket, key, start, end).await
+    }
+
+    async fn get_stream(&self, bucket: &str, key: &str) -> Result<ByteStream> {
+        self.inner.get_stream(bucket, key).await
+    }
+
+    async fn get_range_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<ByteStream> {
+        self.inner.get_range_stream(bucket, key, start, end).await
+    }
+
+    async fn copy(&self, src_bucket: &str, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        // Hard links take no space; a copy that needs it fails on its own
+        self.inner.copy(src_bucket, src_key, dst_bucket, dst_key).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        self.inner.delete(bucket, key).await
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        self.inner.exists(bucket, key).await
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        self.inner.size(bucket, key).await
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.create_bucket(bucket).await
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        self.inner.delete_bucket(bucket).await
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.inner.bucket_exists(bucket).await
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        self.inner.stat(bucket, key).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await?;
+        if self.monitor.below_reserve() {
+            return Err(Error::InsufficientStorage(format!(
+                "{} bytes free, {} bytes reserved",
+                self.monitor.free_bytes(),
+                self.monitor.reserve_bytes()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn put_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        self.monitor.reserve(data.len() as u64)?;
+        self.inner
+            .put_part(bucket, key, upload_id, part_number, data)
+            .await
+    }
+
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        // Assembly briefly holds the parts and the object side by side
+        let mut assembled = 0;
+        for &part_number in part_numbers {
+            let part = super::part_key(key, upload_id, part_number);
+            assembled += self.inner.size(bucket, &part).await.unwrap_or(0).max(0) as u64;
+        }
+        self.monitor.reserve(assembled)?;
+        self.inner
+            .complete_parts(bucket, key, upload_id, part_numbers)
+            .await
+    }
+
+    async fn abort_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<()> {
+        self.inner
+            .abort_parts(bucket, key, upload_id, part_numbers)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MemoryStorage;
+
+    fn monitor(min_free_bytes: u64, min_free_percent: f64) -> SpaceMonitor {
+        SpaceMonitor::new(
+            std::env::temp_dir(),
+            &SpaceReserveConfig {
+                min_free_bytes,
+                min_free_percent,
+                check_interval_secs: 10,
+            },
+        )
+    }
+
+    #[test]
+    fn test_reserve_is_the_larger_minimum() {
+        let monitor = monitor(100, 10.0);
+        monitor.record(5_000, 10_000);
+        assert_eq!(monitor.reserve_bytes(), 1_000);
+
+        let monitor = self::monitor(2_000, 10.0);
+        monitor.record(5_000, 10_000);
+        assert_eq!(monitor.reserve_bytes(), 2_000);
+    }
+
+    #[tokio::test]
+    async fn test_writes_stop_at_the_reserve() {
+        let monitor = Arc::new(monitor(1_000, 0.0));
+        monitor.record(1_500, 10_000);
+        let guard = SpaceGuard::new(Arc::new(MemoryStorage::new()), monitor.clone());
+
+        guard.put("b", "a", Bytes::from(vec![0; 400])).await.unwrap();
+        assert_eq!(monitor.free_bytes(), 1_100);
+
+        let err = guard.put("b", "c", Bytes::from(vec![0; 200])).await.unwrap_err();
+        assert!(matches!(err, Error::InsufficientStorage(_)));
+        assert_eq!(err.http_status(), 507);
+
+        // Nothing is refused before the first measurement
+        let unmeasured = self::monitor(u64::MAX, 0.0);
+        assert!(unmeasured.reserve(1).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_refresh_measures_the_volume() {
+        let monitor = monitor(0, 0.0);
+        monitor.refresh().unwrap();
+        assert!(monitor.total_bytes() > 0);
+        assert!(!monitor.below_reserve());
+    }
+}
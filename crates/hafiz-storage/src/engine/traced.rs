@@ -0,0 +1,202 @@
+//! Spans and latency metrics for storage calls
+//!
+//! [`TracedStorage`] runs every call of the wrapped engine in a
+//! `storage.<op>` span carrying the bucket and key, and records its latency
+//! in `hafiz_storage_operation_duration_seconds`. With OpenTelemetry export
+//! enabled the spans show up under the S3 request that made them. A moving
+//! average of the latencies is kept in [`StorageLatency`] for admission
+//! control.
+
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use hafiz_core::Result;
+use tracing::{info_span, Instrument};
+
+use super::{ByteStream, ObjectStat, StorageEngine};
+
+/// Exponentially weighted moving average of storage call latency
+#[derive(Debug, Default)]
+pub struct StorageLatency {
+    /// Average in microseconds
+    average_us: AtomicU64,
+}
+
+impl StorageLatency {
+    /// Weight of the previous average against a new sample
+    const SMOOTHING: u64 = 8;
+
+    pub fn record(&self, latency: Duration) {
+        let sample = latency.as_micros().min(u64::MAX as u128) as u64;
+        let _ = self
+            .average_us
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |average| {
+                Some(average - average / Self::SMOOTHING + sample / Self::SMOOTHING)
+            });
+    }
+
+    pub fn average(&self) -> Duration {
+        Duration::from_micros(self.average_us.load(Ordering::Relaxed))
+    }
+}
+
+/// Storage engine wrapper that traces and times every call
+pub struct TracedStorage {
+    inner: Arc<dyn StorageEngine>,
+    latency: Arc<StorageLatency>,
+}
+
+impl TracedStorage {
+    pub fn new(inner: Arc<dyn StorageEngine>) -> Self {
+        Self {
+            inner,
+            latency: Arc::new(StorageLatency::default()),
+        }
+    }
+
+    /// Average latency of the calls made so far
+    pub fn latency(&self) -> Arc<StorageLatency> {
+        self.latency.clone()
+    }
+
+    async fn traced<T>(&self, op: &'static str, bucket: &str, key: &str, call: impl Future<Output = Result<T>>) -> Result<T> {
+        let span = info_span!("storage", op = op, bucket = bucket, key = key, otel.name = %format!("storage.{}", op));
+        let start = Instant::now();
+        let result = call.instrument(span).await;
+
+        let elapsed = start.elapsed();
+        metrics::histogram!(
+            "hafiz_storage_operation_duration_seconds",
+            "operation" => op,
+            "status" => if result.is_ok() { "success" } else { "error" }
+        )
+        .record(elapsed.as_secs_f64());
+        self.latency.record(elapsed);
+        result
+    }
+}
+
+#[async_trait]
+impl StorageEngine for TracedStorage {
+    async fn put(&self, bucket: &str, key: &str, data: Bytes) -> Result<String> {
+        self.traced("put", bucket, key, self.inner.put(bucket, key, data)).await
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Bytes> {
+        self.traced("get", bucket, key, self.inner.get(bucket, key)).await
+    }
+
+    async fn get_range(&self, bucket: &str, key: &str, start: i64, end: i64) -> Result<Bytes> {
+        self.traced("get_range", bucket, key, self.inner.get_range(bucket, key, start, end)).await
+    }
+
+    async fn get_stream(&self, bucket: &str, key: &str) -> Result<ByteStream> {
+        self.traced("get_stream", bucket, key, self.inner.get_stream(bucket, key)).await
+    }
+
+    async fn get_range_stream(
+        &self,
+        bucket: &str,
+        key: &str,
+        start: i64,
+        end: i64,
+    ) -> Result<ByteStream> {
+        // Times opening the stream; the body is read after the call returns
+        self.traced(
+            "get_range_stream",
+            bucket,
+            key,
+            self.inner.get_range_stream(bucket, key, start, end),
+        )
+        .await
+    }
+
+    async fn copy(&self, src_bucket: &str, src_key: &str, dst_bucket: &str, dst_key: &str) -> Result<()> {
+        self.traced("copy", src_bucket, src_key, self.inner.copy(src_bucket, src_key, dst_bucket, dst_key)).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<()> {
+        self.traced("delete", bucket, key, self.inner.delete(bucket, key)).await
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool> {
+        self.traced("exists", bucket, key, self.inner.exists(bucket, key)).await
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<i64> {
+        self.traced("size", bucket, key, self.inner.size(bucket, key)).await
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<()> {
+        self.traced("create_bucket", bucket, "", self.inner.create_bucket(bucket)).await
+    }
+
+    async fn delete_bucket(&self, bucket: &str) -> Result<()> {
+        self.traced("delete_bucket", bucket, "", self.inner.delete_bucket(bucket)).await
+    }
+
+    async fn bucket_exists(&self, bucket: &str) -> Result<bool> {
+        self.traced("bucket_exists", bucket, "", self.inner.bucket_exists(bucket)).await
+    }
+
+    async fn stat(&self, bucket: &str, key: &str) -> Result<Option<ObjectStat>> {
+        self.traced("stat", bucket, key, self.inner.stat(bucket, key)).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    async fn put_part(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_number: i32,
+        data: Bytes,
+    ) -> Result<String> {
+        self.traced(
+            "put_part",
+            bucket,
+            key,
+            self.inner.put_part(bucket, key, upload_id, part_number, data),
+        )
+        .await
+    }
+
+    async fn complete_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<i64> {
+        self.traced(
+            "complete_parts",
+            bucket,
+            key,
+            self.inner.complete_parts(bucket, key, upload_id, part_numbers),
+        )
+        .await
+    }
+
+    async fn abort_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        upload_id: &str,
+        part_numbers: &[i32],
+    ) -> Result<()> {
+        self.traced(
+            "abort_parts",
+            bucket,
+            key,
+            self.inner.abort_parts(bucket, key, upload_id, part_numbers),
+        )
+        .await
+    }
+}
@@ -2,4 +2,12 @@
 
 pub mod engine;
 
-pub use engine::{StorageEngine, LocalStorage};
+pub use engine::{
+    open_backend, part_key, read_stream, ByteStream, CacheStats, CompressedStorage, DedupStorage,
+    LayoutMigration, LocalStorage, MemoryStorage, ObjectCache, ObjectStat, ReadCache, S3Client, S3Gateway, SpaceGuard,
+    SpaceMonitor, StorageEngine, StorageLatency, StorageRouter, TracedStorage,
+};
+#[cfg(feature = "azure")]
+pub use engine::AzureBlobStorage;
+#[cfg(feature = "gcs")]
+pub use engine::GcsStorage;
@@ -0,0 +1,175 @@
+//! Test fixtures for Hafiz
+//!
+//! [`TestServer`] starts an ephemeral Hafiz server inside the test process,
+//! with in-memory metadata and a temporary data directory, and hands out a
+//! [`Client`] that signs with the server's root credentials.
+//!
+//! Every `#[tokio::test]` runs on a runtime of its own and the server's
+//! tasks live on it, so start one server per test:
+//!
+//! ```no_run
+//! use hafiz_testing::TestServer;
+//!
+//! #[tokio::test]
+//! async fn stores_objects() {
+//!     let server = TestServer::start().await;
+//!     let bucket = server.create_bucket().await;
+//!     server.put(&bucket, "hello.txt", "hello").await;
+//!
+//!     let object = server.client().get_object(&bucket, "hello.txt").await.unwrap();
+//!     assert_eq!(object.body.bytes().await.unwrap(), "hello");
+//!     server.shutdown().await;
+//! }
+//! ```
+//!
+//! Set `RUST_LOG` to see the server's logs in the test output.
+
+use std::fmt::Debug;
+use std::sync::Once;
+
+use bytes::Bytes;
+use hafiz_client::{Client, Credentials, PutObjectOptions};
+use hafiz_s3_api::{HafizServer, HafizServerBuilder};
+
+/// Access key of the test servers' root user
+pub const ACCESS_KEY: &str = "hafiz-test-access-key";
+
+/// Secret key of the test servers' root user
+pub const SECRET_KEY: &str = "hafiz-test-secret-key";
+
+/// An ephemeral Hafiz server and a client for it
+pub struct TestServer {
+    server: HafizServer,
+    client: Client,
+}
+
+impl TestServer {
+    /// Start a server with the embedded defaults
+    pub async fn start() -> Self {
+        Self::start_with(HafizServer::builder()).await
+    }
+
+    /// Start a server from a customized builder
+    ///
+    /// The root credentials are always [`ACCESS_KEY`] and [`SECRET_KEY`].
+    pub async fn start_with(builder: HafizServerBuilder) -> Self {
+        init_tracing();
+        let server = builder
+            .credentials(ACCESS_KEY, SECRET_KEY)
+            .start()
+            .await
+            .expect("Failed to start the Hafiz test server");
+        let client = client_for(&server.endpoint(), ACCESS_KEY, SECRET_KEY);
+        Self { server, client }
+    }
+
+    /// Client signing with the root credentials
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Client signing with other credentials, e.g. to test auth failures
+    pub fn client_as(&self, access_key: &str, secret_key: &str) -> Client {
+        client_for(&self.server.endpoint(), access_key, secret_key)
+    }
+
+    pub fn endpoint(&self) -> String {
+        self.server.endpoint()
+    }
+
+    /// Create a bucket with a name no other test uses
+    pub async fn create_bucket(&self) -> String {
+        let bucket = unique_name("test");
+        self.client
+            .create_bucket(&bucket, None)
+            .await
+            .unwrap_or_else(|e| panic!("Failed to create bucket {}: {}", bucket, e));
+        bucket
+    }
+
+    /// Upload an object, panicking on failure; returns its version ID
+    pub async fn put(&self, bucket: &str, key: &str, body: impl Into<Bytes>) -> Option<String> {
+        self.client
+            .put_object(bucket, key, body.into(), &PutObjectOptions::default())
+            .await
+            .unwrap_or_else(|e| panic!("Failed to put {}/{}: {}", bucket, key, e))
+            .version_id
+    }
+
+    /// Stop the server and remove its data
+    pub async fn shutdown(self) {
+        self.server
+            .shutdown()
+            .await
+            .expect("Hafiz test server failed while shutting down");
+    }
+}
+
+fn client_for(endpoint: &str, access_key: &str, secret_key: &str) -> Client {
+    Client::builder(endpoint)
+        .credentials(Credentials::new(access_key, secret_key))
+        .build()
+        .expect("Invalid test server endpoint")
+}
+
+/// A valid bucket name, unique to this call
+pub fn unique_name(prefix: &str) -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    format!("{}-{}", prefix, &id[..16])
+}
+
+/// `len` bytes of a pattern that differs per `seed`, so bodies of the same
+/// length can be told apart
+pub fn test_data(len: usize, seed: u8) -> Bytes {
+    (0..len)
+        .map(|i| (i % 251) as u8 ^ seed)
+        .collect::<Vec<_>>()
+        .into()
+}
+
+/// Assert that a request failed with the given S3 error code and status
+#[track_caller]
+pub fn assert_s3_error<T: Debug>(result: hafiz_client::Result<T>, code: &str, status: u16) {
+    match result {
+        Ok(value) => panic!("Expected {} ({}), got success: {:?}", code, status, value),
+        Err(e) => {
+            assert_eq!(e.code(), Some(code), "unexpected error: {}", e);
+            assert_eq!(e.status(), Some(status), "unexpected status for {}", code);
+        }
+    }
+}
+
+fn init_tracing() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        if std::env::var_os("RUST_LOG").is_some() {
+            let _ = tracing_subscriber::fmt()
+                .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
+                .with_test_writer()
+                .try_init();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unique_name_is_a_valid_bucket_name() {
+        let a = unique_name("test");
+        let b = unique_name("test");
+        assert_ne!(a, b);
+        assert!(a.len() <= 63);
+        assert!(a
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-'));
+    }
+
+    #[test]
+    fn test_data_differs_by_seed() {
+        assert_eq!(test_data(1000, 1).len(), 1000);
+        assert_eq!(test_data(1000, 1), test_data(1000, 1));
+        assert_ne!(test_data(1000, 1), test_data(1000, 2));
+    }
+}